@@ -464,6 +464,9 @@ function encodeSet(view: DataView, offset: number, msg: SetMessage): number {
     offset += 4;
   }
 
+  // Options byte, only written for traced SETs
+  if (msg.trace) view.setUint8(offset++, 0x01);
+
   return offset;
 }
 
@@ -614,6 +617,7 @@ function encodeAnnounce(view: DataView, offset: number, msg: AnnounceMessage): n
       if (sig.meta.range) metaFlags |= 0x02;
       if (sig.meta.default !== undefined) metaFlags |= 0x04;
       if (sig.meta.description) metaFlags |= 0x08;
+      if (sig.meta.enum) metaFlags |= 0x10;
       view.setUint8(offset++, metaFlags);
 
       if (sig.meta.unit) offset = encodeString(view, offset, sig.meta.unit);
@@ -628,6 +632,7 @@ function encodeAnnounce(view: DataView, offset: number, msg: AnnounceMessage): n
         offset = encodeValueData(view, offset, sig.meta.default);
       }
       if (sig.meta.description) offset = encodeString(view, offset, sig.meta.description);
+      if (sig.meta.enum) offset = encodeValueData(view, offset, sig.meta.enum);
     }
   }
 
@@ -1110,6 +1115,11 @@ function decodeAnnounce(view: DataView, offset: number): AnnounceMessage {
         meta.description = desc;
         offset = o;
       }
+      if (metaFlags & 0x10) {
+        const [values, o] = decodeValueData(view, offset, VAL.ARRAY);
+        meta.enum = values as Value[];
+        offset = o;
+      }
     }
 
     signals.push({
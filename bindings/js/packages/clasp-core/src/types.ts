@@ -134,6 +134,8 @@ export interface SetMessage {
   unlock?: boolean;
   ttl?: number;
   absolute?: boolean;
+  /** Ask the router for a trace report on /clasp/trace/{session} (admin only) */
+  trace?: boolean;
 }
 
 /** GET message */
@@ -246,6 +248,8 @@ export interface SignalDefinition {
     range?: [number, number];
     default?: Value;
     description?: string;
+    /** Allowed values, for params that take one of a fixed set */
+    enum?: Value[];
   };
 }
 
@@ -13,6 +13,8 @@ fn bench_encode_set(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let mut group = c.benchmark_group("encode");
@@ -31,6 +33,8 @@ fn bench_decode_set(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let encoded = encode(&msg).unwrap();
 
@@ -52,6 +56,8 @@ fn bench_roundtrip(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let mut group = c.benchmark_group("roundtrip");
@@ -105,6 +111,8 @@ fn bench_bundle(c: &mut Criterion) {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
             Message::Set(SetMessage {
                 address: "/bundle/2".to_string(),
@@ -113,6 +121,8 @@ fn bench_bundle(c: &mut Criterion) {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
             Message::Set(SetMessage {
                 address: "/bundle/3".to_string(),
@@ -121,6 +131,8 @@ fn bench_bundle(c: &mut Criterion) {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
         ],
     });
@@ -148,6 +160,8 @@ fn bench_large_array(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let mut group = c.benchmark_group("large_payload");
@@ -88,6 +88,7 @@ fn test_mqtt_config_custom() -> TestResult {
         qos: 1,
         keep_alive_secs: 30,
         namespace: "/sensors".to_string(),
+        homeassistant: None,
     };
 
     if config.broker_host == "mqtt.example.com"
@@ -18,7 +18,10 @@ fn main() {
             })
             .collect();
 
-        let snapshot = Message::Snapshot(SnapshotMessage { params });
+        let snapshot = Message::Snapshot(SnapshotMessage {
+            params,
+            ..Default::default()
+        });
 
         // Measure encoding
         let start = Instant::now();
@@ -106,6 +106,8 @@ fn test_decoding_compatibility() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&set_msg).unwrap();
@@ -118,6 +118,7 @@ impl TestClient {
             ],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
 
         self.sender
@@ -147,7 +148,7 @@ impl TestClient {
                         _ => {}
                     }
                 }
-                Ok(Some(TransportEvent::Connected)) => continue,
+                Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
                 Ok(Some(TransportEvent::Disconnected { reason })) => {
                     return Err(format!("Disconnected during handshake: {:?}", reason));
                 }
@@ -184,6 +185,8 @@ impl TestClient {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         self.sender
@@ -226,7 +229,7 @@ impl TestClient {
                     let (msg, _) = codec::decode(&data).map_err(|e| e.to_string())?;
                     return Ok(msg);
                 }
-                Ok(Some(TransportEvent::Connected)) => continue,
+                Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
                 Ok(Some(TransportEvent::Disconnected { reason })) => {
                     return Err(format!("Disconnected: {:?}", reason));
                 }
@@ -136,6 +136,7 @@ impl TestClient {
             features: vec!["param".to_string(), "event".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
 
         self.sender
@@ -163,7 +164,7 @@ impl TestClient {
                         _ => {}
                     }
                 }
-                Ok(Some(TransportEvent::Connected)) => continue,
+                Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
                 Ok(Some(TransportEvent::Disconnected { reason })) => {
                     return Err(format!("Disconnected during handshake: {:?}", reason));
                 }
@@ -200,6 +201,8 @@ impl TestClient {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         self.sender
@@ -243,8 +246,8 @@ impl TestClient {
                     let (msg, _) = codec::decode(&data).map_err(|e| e.to_string())?;
                     return Ok(msg);
                 }
-                Ok(Some(TransportEvent::Connected)) => {
-                    // Skip connected events, keep waiting for data
+                Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => {
+                    // Skip connection events, keep waiting for data
                     continue;
                 }
                 Ok(Some(TransportEvent::Disconnected { reason })) => {
@@ -651,6 +654,7 @@ async fn test_state_persistence() -> TestResult {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         client2
             .sender
@@ -158,6 +158,7 @@ impl RawClient {
             ],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         self.send(&hello).await?;
 
@@ -178,7 +179,7 @@ impl RawClient {
                         _ => {}
                     }
                 }
-                Ok(Some(TransportEvent::Connected)) => continue,
+                Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
                 Ok(Some(TransportEvent::Disconnected { reason })) => {
                     return Err(format!("Disconnected: {:?}", reason));
                 }
@@ -214,7 +215,7 @@ impl RawClient {
                     let (msg, _) = codec::decode(&data).map_err(|e| e.to_string())?;
                     return Ok(msg);
                 }
-                Ok(Some(TransportEvent::Connected)) => continue,
+                Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
                 Ok(Some(TransportEvent::Disconnected { reason })) => {
                     return Err(format!("Disconnected: {:?}", reason));
                 }
@@ -271,6 +272,8 @@ async fn bench_set_ack_latency() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
             client.recv(5000).await?;
@@ -288,6 +291,8 @@ async fn bench_set_ack_latency() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
 
@@ -353,6 +358,8 @@ async fn bench_pubsub_latency() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
 
@@ -411,6 +418,8 @@ async fn bench_throughput() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await
                 .is_ok()
@@ -508,6 +517,8 @@ async fn bench_fanout_throughput() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
         }
@@ -605,6 +616,8 @@ async fn stress_concurrent_clients() -> TestResult {
                                         lock: false,
                                         unlock: false,
                                         ttl: None,
+                                        trace: false,
+                                        branch: None,
                                     }))
                                     .await
                                     .is_ok()
@@ -706,6 +719,8 @@ async fn stress_concurrent_writes() -> TestResult {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         }))
                         .await
                         .is_ok()
@@ -854,6 +869,8 @@ async fn test_subscription_patterns() -> TestResult {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     }))
                     .await?;
                 let _ = pub_client.recv(500).await;
@@ -916,6 +933,8 @@ async fn test_message_ordering() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
         }
@@ -991,6 +1010,8 @@ async fn test_state_consistency() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
             let _ = setter.recv(1000).await;
@@ -1004,6 +1025,8 @@ async fn test_state_consistency() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
             .await?;
         let _ = setter.recv(1000).await;
@@ -1091,6 +1114,8 @@ async fn test_bundle_atomicity() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
             Message::Set(SetMessage {
                 address: format!("{}/b", base),
@@ -1099,6 +1124,8 @@ async fn test_bundle_atomicity() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
             Message::Set(SetMessage {
                 address: format!("{}/c", base),
@@ -1107,6 +1134,8 @@ async fn test_bundle_atomicity() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
         ];
 
@@ -1173,6 +1202,8 @@ async fn test_large_payloads() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await
                 .is_err()
@@ -1231,6 +1262,8 @@ async fn test_special_characters() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
 
@@ -1283,6 +1316,8 @@ async fn test_empty_null_values() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await?;
 
@@ -1365,6 +1400,8 @@ async fn test_sustained_load() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .await
                 .is_ok()
@@ -159,6 +159,7 @@ async fn connect_with_token(
         features: vec!["param".to_string()],
         capabilities: None,
         token: token.map(|s| s.to_string()),
+        session_resume: None,
     });
 
     sender
@@ -208,6 +209,8 @@ async fn try_set<S: TransportSender, R: TransportReceiver>(
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     sender
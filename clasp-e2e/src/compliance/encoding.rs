@@ -33,6 +33,7 @@ async fn test_hello_encoding(_config: &ConformanceConfig, report: &mut Conforman
             features: vec!["param".to_string(), "event".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
 
         // Encode
@@ -89,6 +90,8 @@ async fn test_set_encoding(_config: &ConformanceConfig, report: &mut Conformance
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&set)?;
@@ -158,6 +161,8 @@ async fn test_value_int_encoding(_config: &ConformanceConfig, report: &mut Confo
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded = codec::encode(&msg)?;
@@ -214,6 +219,8 @@ async fn test_value_float_encoding(_config: &ConformanceConfig, report: &mut Con
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded = codec::encode(&msg)?;
@@ -269,6 +276,8 @@ async fn test_value_string_encoding(_config: &ConformanceConfig, report: &mut Co
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded = codec::encode(&msg)?;
@@ -319,6 +328,8 @@ async fn test_value_bool_encoding(_config: &ConformanceConfig, report: &mut Conf
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded = codec::encode(&msg)?;
@@ -373,6 +384,8 @@ async fn test_value_bytes_encoding(_config: &ConformanceConfig, report: &mut Con
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded = codec::encode(&msg)?;
@@ -423,6 +436,7 @@ async fn test_roundtrip_encoding(_config: &ConformanceConfig, report: &mut Confo
                 features: vec![],
                 capabilities: None,
                 token: Some("token".to_string()),
+                session_resume: None,
             }),
             Message::Set(SetMessage {
                 address: "/a/b/c".to_string(),
@@ -431,6 +445,8 @@ async fn test_roundtrip_encoding(_config: &ConformanceConfig, report: &mut Confo
                 lock: true,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }),
         ];
 
@@ -40,6 +40,8 @@ async fn test_hello_must_be_first(config: &ConformanceConfig, report: &mut Confo
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         sender.send(codec::encode(&set)?).await?;
 
@@ -136,6 +138,7 @@ async fn test_version_negotiation(config: &ConformanceConfig, report: &mut Confo
             features: vec![],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender.send(codec::encode(&hello)?).await?;
 
@@ -235,6 +238,7 @@ async fn test_duplicate_hello_rejected(config: &ConformanceConfig, report: &mut
             features: vec![],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender.send(codec::encode(&hello)?).await?;
 
@@ -268,6 +272,7 @@ async fn test_duplicate_hello_rejected(config: &ConformanceConfig, report: &mut
             features: vec![],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender.send(codec::encode(&hello2)?).await?;
 
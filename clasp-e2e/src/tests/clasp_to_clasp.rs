@@ -48,6 +48,7 @@ async fn test_message_encoding() -> TestResult {
                 ],
                 capabilities: None,
                 token: None,
+                session_resume: None,
             });
 
             let encoded = encode(&msg).map_err(|e| format!("Failed to encode Hello: {:?}", e))?;
@@ -94,6 +95,9 @@ async fn test_message_decoding() -> TestResult {
                 features: vec!["param".to_string(), "event".to_string()],
                 time: 1704067200000000,
                 token: None,
+                challenge: None,
+                resume_token: None,
+                resumed: false,
             });
 
             let encoded = encode(&msg).map_err(|e| format!("Failed to encode: {:?}", e))?;
@@ -134,6 +138,7 @@ async fn test_all_message_types() -> TestResult {
                     features: vec!["param".to_string()],
                     capabilities: None,
                     token: None,
+                    session_resume: None,
                 }),
                 Message::Welcome(WelcomeMessage {
                     session: "sess-1".to_string(),
@@ -142,6 +147,9 @@ async fn test_all_message_types() -> TestResult {
                     features: vec!["param".to_string()],
                     time: 1000000,
                     token: None,
+                    challenge: None,
+                    resume_token: None,
+                    resumed: false,
                 }),
                 Message::Subscribe(SubscribeMessage {
                     id: 1,
@@ -157,6 +165,8 @@ async fn test_all_message_types() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }),
                 Message::Publish(PublishMessage {
                     address: "/test/event".to_string(),
@@ -179,6 +189,8 @@ async fn test_all_message_types() -> TestResult {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     })],
                 }),
                 Message::Sync(SyncMessage {
@@ -194,6 +206,8 @@ async fn test_all_message_types() -> TestResult {
                     locked: None,
                     holder: None,
                     correlation_id: None,
+                    results: Vec::new(),
+                    cursor: None,
                 }),
                 Message::Error(ErrorMessage {
                     code: 400,
@@ -203,6 +217,7 @@ async fn test_all_message_types() -> TestResult {
                 }),
                 Message::Query(QueryMessage {
                     pattern: "/test/**".to_string(),
+                    ..Default::default()
                 }),
             ];
 
@@ -265,6 +280,8 @@ async fn test_value_types() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded =
@@ -304,6 +321,8 @@ async fn test_qos_levels() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             // Test each QoS level
@@ -341,6 +360,8 @@ async fn test_timestamp_handling() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let timestamp = 1704067200000000u64; // Microseconds
@@ -435,6 +456,8 @@ async fn test_bundle_messages() -> TestResult {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     }),
                     Message::Set(SetMessage {
                         address: "/bundle/light/2".to_string(),
@@ -443,6 +466,8 @@ async fn test_bundle_messages() -> TestResult {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     }),
                     Message::Publish(PublishMessage {
                         address: "/bundle/cue".to_string(),
@@ -498,6 +523,9 @@ async fn test_subscription_patterns() -> TestResult {
                     epsilon: Some(0.001),
                     history: None,
                     window: None,
+                    tick_ms: None,
+                    convert_to: None,
+                    stream: None,
                 }),
             });
 
@@ -544,6 +572,8 @@ async fn test_state_revision() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded1 = encode(&set1).map_err(|e| format!("Set1 encode failed: {:?}", e))?;
@@ -568,6 +598,8 @@ async fn test_state_revision() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded2 = encode(&set2).map_err(|e| format!("Set2 encode failed: {:?}", e))?;
@@ -42,6 +42,8 @@ async fn test_encoding_throughput() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let count = 10_000;
@@ -79,6 +81,8 @@ async fn test_decoding_throughput() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             // Pre-encode messages
@@ -122,6 +126,8 @@ async fn test_roundtrip_throughput() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 });
 
                 let encoded = encode(&msg).map_err(|e| format!("Encode {} failed: {:?}", i, e))?;
@@ -160,6 +166,8 @@ async fn test_large_payload() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let start = Instant::now();
@@ -281,6 +289,8 @@ async fn test_concurrent_encoding() -> TestResult {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         });
 
                         if encode(&msg).is_ok() {
@@ -344,6 +354,8 @@ async fn test_memory_stability() -> TestResult {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     });
 
                     let encoded =
@@ -385,6 +397,8 @@ async fn test_latency_distribution() -> TestResult {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 });
 
                 let start = Instant::now();
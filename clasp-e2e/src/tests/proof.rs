@@ -44,6 +44,8 @@ pub fn benchmark_three_way_encoding(iterations: usize) -> TestResult {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         let _encoded = codec::encode(&msg).unwrap();
     }
@@ -138,6 +140,8 @@ pub fn benchmark_three_way_decoding(iterations: usize) -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_encoded = codec::encode(&clasp_msg).unwrap();
 
@@ -239,6 +243,8 @@ pub fn benchmark_three_way_sizes() -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_size = codec::encode(&clasp_msg).unwrap().len();
 
@@ -327,6 +333,8 @@ pub fn benchmark_clasp_vs_osc_encoding(iterations: usize) -> TestResult {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         let _encoded = codec::encode(&msg).unwrap();
     }
@@ -402,6 +410,8 @@ pub fn benchmark_clasp_vs_osc_decoding(iterations: usize) -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_encoded = codec::encode(&clasp_msg).unwrap();
 
@@ -527,6 +537,8 @@ pub fn benchmark_message_sizes() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             })
         } else {
             Message::Set(SetMessage {
@@ -536,6 +548,8 @@ pub fn benchmark_message_sizes() -> TestResult {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             })
         };
         let clasp_encoded = codec::encode(&clasp_msg).unwrap();
@@ -594,6 +608,8 @@ pub fn benchmark_latency_distribution(samples: usize) -> TestResult {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         let encoded = codec::encode(&msg).unwrap();
         let _ = codec::decode(&encoded).unwrap();
@@ -610,6 +626,8 @@ pub fn benchmark_latency_distribution(samples: usize) -> TestResult {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         let encoded = codec::encode(&msg).unwrap();
         let _ = codec::decode(&encoded).unwrap();
@@ -752,6 +770,8 @@ pub fn visualize_osc_to_clasp_bridge() -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_bytes = codec::encode(&clasp_msg).unwrap();
 
@@ -803,6 +823,8 @@ pub fn visualize_osc_to_clasp_bridge() -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_rgb_bytes = codec::encode(&clasp_rgb).unwrap();
 
@@ -830,6 +852,8 @@ pub fn visualize_osc_to_clasp_bridge() -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_str_bytes = codec::encode(&clasp_str).unwrap();
 
@@ -876,6 +900,8 @@ pub fn visualize_midi_to_clasp_bridge() -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let clasp_cc_bytes = codec::encode(&clasp_cc).unwrap();
 
@@ -1271,6 +1297,8 @@ pub fn stress_test_throughput_limit() -> TestResult {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     // Test increasing batch sizes
@@ -1345,6 +1373,8 @@ pub fn stress_test_concurrent_access() -> TestResult {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     });
 
                     for _ in 0..messages_per_thread {
@@ -1409,6 +1439,8 @@ pub fn stress_test_memory_stability() -> TestResult {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&msg).unwrap();
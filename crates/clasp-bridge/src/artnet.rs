@@ -1,11 +1,18 @@
 //! Art-Net bridge
+//!
+//! Maps DMX channels to `{namespace}/{universe}/{channel}` (channels 1-512)
+//! in both directions. Incoming ArtDmx frames become SETs for the channels
+//! that changed; SETs to those addresses update the universe, which is sent
+//! to `remote_addr` at `refresh_rate` frames per second while it has changes.
 
 use artnet_protocol::{ArtCommand, Output, Poll};
 use async_trait::async_trait;
 use clasp_core::{Message, SetMessage, Value};
 use parking_lot::Mutex;
+use std::collections::HashSet;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info};
@@ -22,10 +29,12 @@ pub struct ArtNetBridgeConfig {
     pub bind_addr: String,
     /// Remote Art-Net node (for output)
     pub remote_addr: Option<String>,
-    /// Universes to listen to (empty = all)
+    /// Universes to bridge in either direction (empty = all)
     pub universes: Vec<u16>,
     /// Address namespace
     pub namespace: String,
+    /// Output frames per second per changed universe (0 = send on every SET)
+    pub refresh_rate: f64,
 }
 
 impl Default for ArtNetBridgeConfig {
@@ -35,10 +44,17 @@ impl Default for ArtNetBridgeConfig {
             remote_addr: None,
             universes: vec![],
             namespace: "/artnet".to_string(),
+            refresh_rate: 44.0,
         }
     }
 }
 
+impl ArtNetBridgeConfig {
+    fn bridges_universe(&self, universe: u16) -> bool {
+        self.universes.is_empty() || self.universes.contains(&universe)
+    }
+}
+
 /// Art-Net to Clasp bridge
 pub struct ArtNetBridge {
     config: BridgeConfig,
@@ -47,6 +63,8 @@ pub struct ArtNetBridge {
     running: Arc<Mutex<bool>>,
     /// Current DMX values per universe (for delta detection)
     dmx_state: Arc<Mutex<std::collections::HashMap<u16, [u8; 512]>>>,
+    /// Universes changed by SETs since the last output frame
+    dirty: Arc<Mutex<HashSet<u16>>>,
 }
 
 impl ArtNetBridge {
@@ -64,6 +82,7 @@ impl ArtNetBridge {
             socket: None,
             running: Arc::new(Mutex::new(false)),
             dmx_state: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            dirty: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
@@ -107,31 +126,41 @@ impl ArtNetBridge {
             .parse()
             .map_err(|e| BridgeError::Send(format!("Invalid remote address: {}", e)))?;
 
-        // Create DMX output command
-        // In artnet_protocol 0.2, Output has: version, sequence, physical, subnet, length, data
-        // The subnet field is used for the universe/subnet addressing
-        let output = Output {
-            subnet: universe,
-            data: data.to_vec(),
-            length: data.len() as u16,
-            ..Default::default()
-        };
-
-        let command = ArtCommand::Output(output);
-        let bytes = command
-            .into_buffer()
-            .map_err(|e| BridgeError::Protocol(format!("Failed to encode DMX: {:?}", e)))?;
-
-        socket
-            .send_to(&bytes, remote_addr)
-            .await
-            .map_err(|e| BridgeError::Send(e.to_string()))?;
-
-        debug!("Sent DMX to universe {} ({} bytes)", universe, data.len());
-        Ok(())
+        send_output(socket, remote_addr, universe, data).await
     }
 }
 
+/// Send one ArtDmx frame
+async fn send_output(
+    socket: &UdpSocket,
+    remote_addr: SocketAddr,
+    universe: u16,
+    data: &[u8],
+) -> Result<()> {
+    // Create DMX output command
+    // In artnet_protocol 0.2, Output has: version, sequence, physical, subnet, length, data
+    // The subnet field is used for the universe/subnet addressing
+    let output = Output {
+        subnet: universe,
+        data: data.to_vec(),
+        length: data.len() as u16,
+        ..Default::default()
+    };
+
+    let command = ArtCommand::Output(output);
+    let bytes = command
+        .into_buffer()
+        .map_err(|e| BridgeError::Protocol(format!("Failed to encode DMX: {:?}", e)))?;
+
+    socket
+        .send_to(&bytes, remote_addr)
+        .await
+        .map_err(|e| BridgeError::Send(e.to_string()))?;
+
+    debug!("Sent DMX to universe {} ({} bytes)", universe, data.len());
+    Ok(())
+}
+
 #[async_trait]
 impl Bridge for ArtNetBridge {
     fn config(&self) -> &BridgeConfig {
@@ -143,6 +172,16 @@ impl Bridge for ArtNetBridge {
             return Err(BridgeError::Other("Bridge already running".to_string()));
         }
 
+        // Output is paced by a refresh loop when there is somewhere to send it
+        let refresh_target: Option<SocketAddr> = match self.artnet_config.remote_addr {
+            Some(ref remote) if self.artnet_config.refresh_rate > 0.0 => {
+                Some(remote.parse().map_err(|e| {
+                    BridgeError::ConnectionFailed(format!("Invalid remote address: {}", e))
+                })?)
+            }
+            _ => None,
+        };
+
         let socket = UdpSocket::bind(&self.artnet_config.bind_addr)
             .await
             .map_err(|e| BridgeError::ConnectionFailed(e.to_string()))?;
@@ -167,6 +206,18 @@ impl Bridge for ArtNetBridge {
         let universes = self.artnet_config.universes.clone();
         let dmx_state = self.dmx_state.clone();
 
+        if let Some(remote_addr) = refresh_target {
+            let period = Duration::from_secs_f64(1.0 / self.artnet_config.refresh_rate);
+            tokio::spawn(refresh_loop(
+                socket.clone(),
+                remote_addr,
+                period,
+                self.dirty.clone(),
+                dmx_state.clone(),
+                self.running.clone(),
+            ));
+        }
+
         // Spawn receiver task
         tokio::spawn(async move {
             let mut buf = vec![0u8; 65536];
@@ -230,7 +281,7 @@ impl Bridge for ArtNetBridge {
                     .parse()
                     .map_err(|_| BridgeError::Mapping("Invalid channel".to_string()))?;
 
-                if channel > 0 && channel <= 512 {
+                if channel > 0 && channel <= 512 && self.artnet_config.bridges_universe(universe) {
                     let value = set.value.as_i64().unwrap_or(0).clamp(0, 255) as u8;
 
                     // Get the DMX data, update it, then release the lock before await
@@ -241,8 +292,16 @@ impl Bridge for ArtNetBridge {
                         *dmx // Copy the array
                     };
 
-                    // Now send without holding the lock
-                    self.send_dmx(universe, &dmx_copy).await?;
+                    if self.artnet_config.refresh_rate > 0.0
+                        && self.artnet_config.remote_addr.is_some()
+                        && self.is_running()
+                    {
+                        // The refresh loop sends it on its next frame
+                        self.dirty.lock().insert(universe);
+                    } else {
+                        // Now send without holding the lock
+                        self.send_dmx(universe, &dmx_copy).await?;
+                    }
                 }
             }
         }
@@ -259,6 +318,38 @@ impl Bridge for ArtNetBridge {
     }
 }
 
+/// Send universes changed by SETs, at most one frame each per period
+async fn refresh_loop(
+    socket: Arc<UdpSocket>,
+    remote_addr: SocketAddr,
+    period: Duration,
+    dirty: Arc<Mutex<HashSet<u16>>>,
+    dmx_state: Arc<Mutex<std::collections::HashMap<u16, [u8; 512]>>>,
+    running: Arc<Mutex<bool>>,
+) {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    while *running.lock() {
+        interval.tick().await;
+
+        let frames: Vec<(u16, [u8; 512])> = {
+            let universes: Vec<u16> = dirty.lock().drain().collect();
+            let state = dmx_state.lock();
+            universes
+                .into_iter()
+                .filter_map(|u| state.get(&u).map(|dmx| (u, *dmx)))
+                .collect()
+        };
+
+        for (universe, data) in frames {
+            if let Err(e) = send_output(&socket, remote_addr, universe, &data).await {
+                debug!("Art-Net refresh error for universe {}: {}", universe, e);
+            }
+        }
+    }
+}
+
 /// Convert Art-Net command to Clasp messages
 fn artnet_to_clasp(
     command: &ArtCommand,
@@ -295,6 +386,8 @@ fn artnet_to_clasp(
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     }));
                 }
             }
@@ -326,5 +419,45 @@ mod tests {
         let config = ArtNetBridgeConfig::default();
         assert_eq!(config.namespace, "/artnet");
         assert!(config.universes.is_empty());
+        assert_eq!(config.refresh_rate, 44.0);
+    }
+
+    #[test]
+    fn test_universe_filter() {
+        let config = ArtNetBridgeConfig {
+            universes: vec![1, 3],
+            ..Default::default()
+        };
+        assert!(config.bridges_universe(3));
+        assert!(!config.bridges_universe(2));
+        assert!(ArtNetBridgeConfig::default().bridges_universe(2));
+    }
+
+    #[test]
+    fn test_input_reports_changed_channels() {
+        let dmx_state = Arc::new(Mutex::new(std::collections::HashMap::new()));
+        let mut data = vec![0u8; 512];
+        data[0] = 255;
+        data[9] = 10;
+        let command = ArtCommand::Output(Output {
+            subnet: 2,
+            length: 512,
+            data,
+            ..Default::default()
+        });
+
+        let messages = artnet_to_clasp(&command, "/artnet", &[], &dmx_state).unwrap();
+        let addresses: Vec<_> = messages
+            .iter()
+            .map(|m| match m {
+                Message::Set(set) => set.address.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(addresses, ["/artnet/2/1", "/artnet/2/10"]);
+
+        // Unchanged frames and filtered universes produce nothing
+        assert!(artnet_to_clasp(&command, "/artnet", &[], &dmx_state).is_none());
+        assert!(artnet_to_clasp(&command, "/artnet", &[1], &dmx_state).is_none());
     }
 }
@@ -0,0 +1,833 @@
+//! Audio analysis bridge
+//!
+//! Computes audio features from a CLASP stream signal (or, with the
+//! `audio-capture` feature, a local input device via cpal) and publishes
+//! them as signals at a fixed rate:
+//!
+//! | Address | Signal | Value |
+//! |---------|--------|-------|
+//! | `{namespace}/rms` | param | RMS level, 0.0 to 1.0 |
+//! | `{namespace}/peak` | param | Peak level, 0.0 to 1.0 |
+//! | `{namespace}/centroid` | param | Spectral centroid in Hz |
+//! | `{namespace}/bands/{n}` | param | Band magnitude (log-spaced, low to high) |
+//! | `{namespace}/bpm` | param | Tempo estimate from recent beats |
+//! | `{namespace}/beat` | event | Beat strength (energy over recent average) |
+
+use async_trait::async_trait;
+use clasp_core::{Message, PublishMessage, SetMessage, SignalType, Value};
+use parking_lot::Mutex;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info};
+
+use crate::{Bridge, BridgeConfig, BridgeError, BridgeEvent, Result};
+
+/// Lowest frequency covered by the analysis bands
+const MIN_BAND_HZ: f32 = 20.0;
+
+/// Upper edge of the low-frequency range used for beat detection
+const BEAT_MAX_HZ: f32 = 150.0;
+
+/// Number of recent beats used for the tempo estimate
+const BPM_HISTORY: usize = 8;
+
+/// Where audio comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AudioSource {
+    /// Samples from PUBLISH messages on a CLASP stream signal
+    Signal { address: String },
+    /// A local capture device (requires the `audio-capture` feature).
+    /// Uses the default input device when no name is given.
+    Device {
+        #[serde(default)]
+        name: Option<String>,
+    },
+}
+
+/// Audio analysis configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioAnalysisConfig {
+    /// Audio input
+    pub source: AudioSource,
+    /// Address prefix for published features
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// Analysis frames published per second
+    #[serde(default = "default_rate_hz")]
+    pub rate_hz: f64,
+    /// FFT window size in samples (power of two)
+    #[serde(default = "default_fft_size")]
+    pub fft_size: usize,
+    /// Number of frequency bands
+    #[serde(default = "default_bands")]
+    pub bands: usize,
+    /// Sample rate assumed for stream samples without a `rate`
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// Beat threshold as a multiple of the recent low-frequency energy
+    #[serde(default = "default_beat_sensitivity")]
+    pub beat_sensitivity: f32,
+    /// Minimum time between beats in milliseconds
+    #[serde(default = "default_min_beat_interval_ms")]
+    pub min_beat_interval_ms: u64,
+}
+
+fn default_namespace() -> String {
+    "/audio/analysis".to_string()
+}
+
+fn default_rate_hz() -> f64 {
+    30.0
+}
+
+fn default_fft_size() -> usize {
+    1024
+}
+
+fn default_bands() -> usize {
+    8
+}
+
+fn default_sample_rate() -> u32 {
+    48000
+}
+
+fn default_beat_sensitivity() -> f32 {
+    1.4
+}
+
+fn default_min_beat_interval_ms() -> u64 {
+    250
+}
+
+impl Default for AudioAnalysisConfig {
+    fn default() -> Self {
+        Self {
+            source: AudioSource::Device { name: None },
+            namespace: default_namespace(),
+            rate_hz: default_rate_hz(),
+            fft_size: default_fft_size(),
+            bands: default_bands(),
+            sample_rate: default_sample_rate(),
+            beat_sensitivity: default_beat_sensitivity(),
+            min_beat_interval_ms: default_min_beat_interval_ms(),
+        }
+    }
+}
+
+impl AudioAnalysisConfig {
+    fn validate(&self) -> Result<()> {
+        if !self.fft_size.is_power_of_two() || self.fft_size < 64 {
+            return Err(BridgeError::Other(format!(
+                "fft_size must be a power of two >= 64, got {}",
+                self.fft_size
+            )));
+        }
+        if self.bands == 0 {
+            return Err(BridgeError::Other("bands must be at least 1".to_string()));
+        }
+        if !(self.rate_hz > 0.0 && self.rate_hz <= 1000.0) {
+            return Err(BridgeError::Other(format!(
+                "rate_hz must be in (0, 1000], got {}",
+                self.rate_hz
+            )));
+        }
+        if self.sample_rate == 0 {
+            return Err(BridgeError::Other(
+                "sample_rate must be non-zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// One analysis result
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisFrame {
+    pub rms: f32,
+    pub peak: f32,
+    /// Spectral centroid in Hz
+    pub centroid: f32,
+    /// Band magnitudes, low to high
+    pub bands: Vec<f32>,
+    /// Beat strength if a beat was detected in this frame
+    pub beat: Option<f32>,
+    /// Tempo estimate once enough beats have been seen
+    pub bpm: Option<f32>,
+}
+
+/// Windowed FFT analysis with energy-based beat detection.
+///
+/// Feed samples with [`push_samples`](Self::push_samples) and call
+/// [`analyze`](Self::analyze) once per output frame.
+pub struct AudioAnalyzer {
+    fft: Arc<dyn Fft<f32>>,
+    fft_size: usize,
+    bands: usize,
+    sample_rate: u32,
+    window: Vec<f32>,
+    /// Most recent `fft_size` samples
+    samples: VecDeque<f32>,
+    /// Samples received since the last analysis
+    fresh: usize,
+    beat_sensitivity: f32,
+    /// Low-frequency energy of recent frames (about one second)
+    energy_history: VecDeque<f32>,
+    energy_history_len: usize,
+    min_beat_frames: u64,
+    frame: u64,
+    last_beat: Option<u64>,
+    beat_intervals: VecDeque<u64>,
+    rate_hz: f64,
+}
+
+impl AudioAnalyzer {
+    pub fn new(config: &AudioAnalysisConfig) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(config.fft_size);
+        // Hann window to reduce spectral leakage
+        let window = (0..config.fft_size)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / config.fft_size as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        let frame_ms = 1000.0 / config.rate_hz;
+
+        Self {
+            fft,
+            fft_size: config.fft_size,
+            bands: config.bands,
+            sample_rate: config.sample_rate,
+            window,
+            samples: VecDeque::from(vec![0.0; config.fft_size]),
+            fresh: 0,
+            beat_sensitivity: config.beat_sensitivity,
+            energy_history: VecDeque::new(),
+            energy_history_len: config.rate_hz.ceil().max(8.0) as usize,
+            min_beat_frames: (config.min_beat_interval_ms as f64 / frame_ms).ceil() as u64,
+            frame: 0,
+            last_beat: None,
+            beat_intervals: VecDeque::new(),
+            rate_hz: config.rate_hz,
+        }
+    }
+
+    /// Update the input sample rate
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate > 0 {
+            self.sample_rate = sample_rate;
+        }
+    }
+
+    /// Append mono samples
+    pub fn push_samples(&mut self, samples: impl IntoIterator<Item = f32>) {
+        for sample in samples {
+            if self.samples.len() == self.fft_size {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(sample);
+            self.fresh += 1;
+        }
+    }
+
+    /// Whether samples arrived since the last analysis
+    pub fn has_fresh_samples(&self) -> bool {
+        self.fresh > 0
+    }
+
+    /// Analyze the current window
+    pub fn analyze(&mut self) -> AnalysisFrame {
+        self.fresh = 0;
+        self.frame += 1;
+
+        let mut rms = 0.0f32;
+        let mut peak = 0.0f32;
+        for &sample in &self.samples {
+            rms += sample * sample;
+            peak = peak.max(sample.abs());
+        }
+        let rms = (rms / self.fft_size as f32).sqrt().min(1.0);
+        let peak = peak.min(1.0);
+
+        let mut buffer: Vec<Complex<f32>> = self
+            .samples
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| Complex::new(s * w, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        // Normalize so a full-scale sine peaks near 1.0 (Hann gain is 0.5)
+        let half = self.fft_size / 2;
+        let scale = 4.0 / self.fft_size as f32;
+        let magnitudes: Vec<f32> = buffer[..half].iter().map(|c| c.norm() * scale).collect();
+        let bin_hz = self.sample_rate as f32 / self.fft_size as f32;
+
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+        for (bin, mag) in magnitudes.iter().enumerate().skip(1) {
+            weighted += bin as f32 * bin_hz * mag;
+            total += mag;
+        }
+        let centroid = if total > 0.0 { weighted / total } else { 0.0 };
+
+        let bands = self.band_magnitudes(&magnitudes, bin_hz);
+
+        let beat_bins = ((BEAT_MAX_HZ / bin_hz).ceil() as usize).clamp(2, half);
+        let energy =
+            magnitudes[1..beat_bins].iter().map(|m| m * m).sum::<f32>() / (beat_bins - 1) as f32;
+        let beat = self.detect_beat(energy);
+
+        AnalysisFrame {
+            rms,
+            peak,
+            centroid,
+            bands,
+            beat,
+            bpm: self.bpm(),
+        }
+    }
+
+    /// Average magnitude per log-spaced band between 20 Hz and Nyquist
+    fn band_magnitudes(&self, magnitudes: &[f32], bin_hz: f32) -> Vec<f32> {
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let ratio = (nyquist / MIN_BAND_HZ).max(1.0);
+        let last_bin = magnitudes.len() - 1;
+
+        (0..self.bands)
+            .map(|band| {
+                let lo = MIN_BAND_HZ * ratio.powf(band as f32 / self.bands as f32);
+                let hi = MIN_BAND_HZ * ratio.powf((band + 1) as f32 / self.bands as f32);
+                let start = ((lo / bin_hz).floor() as usize).clamp(1, last_bin);
+                // Low bands can be narrower than a bin; always cover at least one
+                let end = ((hi / bin_hz).ceil() as usize).clamp(start + 1, last_bin + 1);
+                let slice = &magnitudes[start..end];
+                slice.iter().sum::<f32>() / slice.len() as f32
+            })
+            .collect()
+    }
+
+    fn detect_beat(&mut self, energy: f32) -> Option<f32> {
+        let average = if self.energy_history.is_empty() {
+            0.0
+        } else {
+            self.energy_history.iter().sum::<f32>() / self.energy_history.len() as f32
+        };
+        let warmed_up = self.energy_history.len() >= self.energy_history_len / 2;
+
+        if self.energy_history.len() == self.energy_history_len {
+            self.energy_history.pop_front();
+        }
+        self.energy_history.push_back(energy);
+
+        let spaced = self
+            .last_beat
+            .is_none_or(|last| self.frame - last >= self.min_beat_frames);
+        if !warmed_up || !spaced || energy <= 1e-6 || energy <= average * self.beat_sensitivity {
+            return None;
+        }
+
+        if let Some(last) = self.last_beat {
+            if self.beat_intervals.len() == BPM_HISTORY {
+                self.beat_intervals.pop_front();
+            }
+            self.beat_intervals.push_back(self.frame - last);
+        }
+        self.last_beat = Some(self.frame);
+
+        Some(if average > 0.0 {
+            energy / average
+        } else {
+            self.beat_sensitivity
+        })
+    }
+
+    fn bpm(&self) -> Option<f32> {
+        if self.beat_intervals.len() < 2 {
+            return None;
+        }
+        let mean =
+            self.beat_intervals.iter().sum::<u64>() as f64 / self.beat_intervals.len() as f64;
+        Some((60.0 * self.rate_hz / mean) as f32)
+    }
+}
+
+/// Convert an analysis frame into CLASP messages
+fn frame_to_messages(frame: &AnalysisFrame, namespace: &str) -> Vec<Message> {
+    let set = |suffix: &str, value: f32| {
+        Message::Set(SetMessage {
+            address: format!("{}/{}", namespace, suffix),
+            value: Value::Float(value as f64),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    };
+
+    let mut messages = vec![
+        set("rms", frame.rms),
+        set("peak", frame.peak),
+        set("centroid", frame.centroid),
+    ];
+    messages.extend(
+        frame
+            .bands
+            .iter()
+            .enumerate()
+            .map(|(i, magnitude)| set(&format!("bands/{}", i), *magnitude)),
+    );
+    if let Some(bpm) = frame.bpm {
+        messages.push(set("bpm", bpm));
+    }
+    if let Some(strength) = frame.beat {
+        messages.push(Message::Publish(PublishMessage {
+            address: format!("{}/beat", namespace),
+            signal: Some(SignalType::Event),
+            value: None,
+            payload: Some(Value::Float(strength as f64)),
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        }));
+    }
+    messages
+}
+
+/// Audio analysis bridge
+pub struct AudioAnalysisBridge {
+    config: BridgeConfig,
+    audio_config: AudioAnalysisConfig,
+    analyzer: Arc<Mutex<AudioAnalyzer>>,
+    running: Arc<Mutex<bool>>,
+    /// Handle to keep the capture stream alive
+    _capture_thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl AudioAnalysisBridge {
+    pub fn new(audio_config: AudioAnalysisConfig) -> Self {
+        let config = BridgeConfig {
+            name: "Audio Analysis".to_string(),
+            protocol: "audio".to_string(),
+            bidirectional: false,
+            ..Default::default()
+        };
+        let analyzer = Arc::new(Mutex::new(AudioAnalyzer::new(&audio_config)));
+
+        Self {
+            config,
+            audio_config,
+            analyzer,
+            running: Arc::new(Mutex::new(false)),
+            _capture_thread: None,
+        }
+    }
+
+    /// List available audio input devices
+    #[cfg(feature = "audio-capture")]
+    pub fn list_input_devices() -> Result<Vec<String>> {
+        use cpal::traits::{DeviceTrait, HostTrait};
+
+        let devices = cpal::default_host()
+            .input_devices()
+            .map_err(|e| BridgeError::Protocol(e.to_string()))?;
+        Ok(devices.filter_map(|d| d.name().ok()).collect())
+    }
+
+    #[cfg(feature = "audio-capture")]
+    fn start_capture(
+        &self,
+        name: Option<String>,
+        tx: mpsc::Sender<BridgeEvent>,
+    ) -> Result<std::thread::JoinHandle<()>> {
+        let analyzer = self.analyzer.clone();
+        let running = self.running.clone();
+
+        // cpal streams are not Send, so the stream lives on its own thread
+        Ok(std::thread::spawn(move || {
+            let fail = |e: String| {
+                tracing::warn!("Audio capture failed: {}", e);
+                let _ = tx.blocking_send(BridgeEvent::Error(e));
+            };
+            match capture::open(name.as_deref(), analyzer) {
+                Ok(_stream) => {
+                    while *running.lock() {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                }
+                Err(e) => fail(e),
+            }
+        }))
+    }
+
+    #[cfg(not(feature = "audio-capture"))]
+    fn start_capture(
+        &self,
+        _name: Option<String>,
+        _tx: mpsc::Sender<BridgeEvent>,
+    ) -> Result<std::thread::JoinHandle<()>> {
+        Err(BridgeError::Other(
+            "audio device capture requires the audio-capture feature".to_string(),
+        ))
+    }
+}
+
+#[async_trait]
+impl Bridge for AudioAnalysisBridge {
+    fn config(&self) -> &BridgeConfig {
+        &self.config
+    }
+
+    async fn start(&mut self) -> Result<mpsc::Receiver<BridgeEvent>> {
+        if *self.running.lock() {
+            return Err(BridgeError::Other("Bridge already running".to_string()));
+        }
+        self.audio_config.validate()?;
+        *self.analyzer.lock() = AudioAnalyzer::new(&self.audio_config);
+
+        let (tx, rx) = mpsc::channel(100);
+        *self.running.lock() = true;
+
+        if let AudioSource::Device { name } = &self.audio_config.source {
+            match self.start_capture(name.clone(), tx.clone()) {
+                Ok(handle) => self._capture_thread = Some(handle),
+                Err(e) => {
+                    *self.running.lock() = false;
+                    return Err(e);
+                }
+            }
+        }
+
+        let analyzer = self.analyzer.clone();
+        let running = self.running.clone();
+        let namespace = self.audio_config.namespace.clone();
+        let period = Duration::from_secs_f64(1.0 / self.audio_config.rate_hz);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            while *running.lock() {
+                interval.tick().await;
+                let frame = {
+                    let mut analyzer = analyzer.lock();
+                    // Hold the last values rather than publishing stale frames
+                    if !analyzer.has_fresh_samples() {
+                        continue;
+                    }
+                    analyzer.analyze()
+                };
+                for msg in frame_to_messages(&frame, &namespace) {
+                    if tx.send(BridgeEvent::ToClasp(Box::new(msg))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            let _ = tx.send(BridgeEvent::Disconnected { reason: None }).await;
+        });
+
+        info!(
+            "Audio analysis publishing to {} at {} Hz",
+            self.audio_config.namespace, self.audio_config.rate_hz
+        );
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        *self.running.lock() = false;
+        // The capture thread exits on its own when running becomes false
+        self._capture_thread = None;
+        info!("Audio analysis bridge stopped");
+        Ok(())
+    }
+
+    async fn send(&self, message: Message) -> Result<()> {
+        let AudioSource::Signal { address } = &self.audio_config.source else {
+            return Ok(());
+        };
+        if let Message::Publish(publish) = &message {
+            if &publish.address == address {
+                if let Some(samples) = &publish.samples {
+                    let mut analyzer = self.analyzer.lock();
+                    if let Some(rate) = publish.rate {
+                        analyzer.set_sample_rate(rate);
+                    }
+                    analyzer.push_samples(samples.iter().map(|s| *s as f32));
+                    debug!("Audio analysis received {} samples", samples.len());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        *self.running.lock()
+    }
+
+    fn namespace(&self) -> &str {
+        &self.audio_config.namespace
+    }
+}
+
+#[cfg(feature = "audio-capture")]
+mod capture {
+    use super::AudioAnalyzer;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{SampleFormat, Stream};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    /// Open an input device and feed its samples, downmixed to mono,
+    /// into the analyzer. The stream stops when dropped.
+    pub(super) fn open(
+        name: Option<&str>,
+        analyzer: Arc<Mutex<AudioAnalyzer>>,
+    ) -> Result<Stream, String> {
+        let host = cpal::default_host();
+        let device = match name {
+            Some(name) => host
+                .input_devices()
+                .map_err(|e| e.to_string())?
+                .find(|d| d.name().map(|n| n.contains(name)).unwrap_or(false))
+                .ok_or_else(|| format!("audio input device not found: {}", name))?,
+            None => host
+                .default_input_device()
+                .ok_or_else(|| "no default audio input device".to_string())?,
+        };
+        let supported = device.default_input_config().map_err(|e| e.to_string())?;
+        let channels = supported.channels().max(1) as usize;
+        analyzer.lock().set_sample_rate(supported.sample_rate().0);
+        tracing::info!(
+            "Opening audio input: {}",
+            device.name().unwrap_or_else(|_| "Unknown".to_string())
+        );
+
+        let config = supported.config();
+        let on_error = |e| tracing::warn!("Audio stream error: {}", e);
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => device.build_input_stream(
+                &config,
+                move |data: &[f32], _| push_frames(&analyzer, data, channels, |s| s),
+                on_error,
+                None,
+            ),
+            SampleFormat::I16 => device.build_input_stream(
+                &config,
+                move |data: &[i16], _| {
+                    push_frames(&analyzer, data, channels, |s| s as f32 / i16::MAX as f32)
+                },
+                on_error,
+                None,
+            ),
+            SampleFormat::U16 => device.build_input_stream(
+                &config,
+                move |data: &[u16], _| {
+                    push_frames(&analyzer, data, channels, |s| {
+                        (s as f32 - 32768.0) / 32768.0
+                    })
+                },
+                on_error,
+                None,
+            ),
+            other => return Err(format!("unsupported sample format: {:?}", other)),
+        }
+        .map_err(|e| e.to_string())?;
+
+        stream.play().map_err(|e| e.to_string())?;
+        Ok(stream)
+    }
+
+    fn push_frames<T: Copy>(
+        analyzer: &Mutex<AudioAnalyzer>,
+        data: &[T],
+        channels: usize,
+        to_f32: impl Fn(T) -> f32,
+    ) {
+        let mono = data
+            .chunks(channels)
+            .map(|frame| frame.iter().map(|s| to_f32(*s)).sum::<f32>() / channels as f32);
+        analyzer.lock().push_samples(mono);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AudioAnalysisConfig {
+        AudioAnalysisConfig {
+            source: AudioSource::Signal {
+                address: "/audio/in".to_string(),
+            },
+            sample_rate: 48000,
+            fft_size: 1024,
+            bands: 8,
+            rate_hz: 30.0,
+            ..Default::default()
+        }
+    }
+
+    fn sine(freq: f32, amplitude: f32, count: usize, sample_rate: f32) -> Vec<f32> {
+        (0..count)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_levels_and_spectrum() {
+        let mut analyzer = AudioAnalyzer::new(&config());
+        analyzer.push_samples(sine(1000.0, 0.5, 1024, 48000.0));
+        let frame = analyzer.analyze();
+
+        // RMS of a sine is amplitude / sqrt(2)
+        assert!((frame.rms - 0.354).abs() < 0.01, "rms {}", frame.rms);
+        assert!((frame.peak - 0.5).abs() < 0.01);
+        assert!(
+            (frame.centroid - 1000.0).abs() < 150.0,
+            "centroid {}",
+            frame.centroid
+        );
+
+        // The band containing 1 kHz dominates
+        let loudest = frame
+            .bands
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert_eq!(frame.bands.len(), 8);
+        assert_eq!(loudest, 4);
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut analyzer = AudioAnalyzer::new(&config());
+        assert!(!analyzer.has_fresh_samples());
+        analyzer.push_samples(vec![0.0; 512]);
+        assert!(analyzer.has_fresh_samples());
+        let frame = analyzer.analyze();
+        assert!(!analyzer.has_fresh_samples());
+        assert_eq!(frame.rms, 0.0);
+        assert_eq!(frame.centroid, 0.0);
+        assert!(frame.beat.is_none());
+    }
+
+    #[test]
+    fn test_beat_detection_and_bpm() {
+        let config = config();
+        let mut analyzer = AudioAnalyzer::new(&config);
+        let per_frame = (48000.0 / config.rate_hz) as usize;
+        let quiet = sine(60.0, 0.01, per_frame, 48000.0);
+        let kick = sine(60.0, 0.9, per_frame, 48000.0);
+
+        // A kick every 15 frames at 30 Hz is 120 BPM
+        let mut beats = 0;
+        let mut bpm = None;
+        for frame in 0..150 {
+            let block = if frame % 15 == 14 { &kick } else { &quiet };
+            analyzer.push_samples(block.iter().copied());
+            let result = analyzer.analyze();
+            if result.beat.is_some() {
+                beats += 1;
+            }
+            bpm = result.bpm.or(bpm);
+        }
+
+        assert!(beats >= 8, "detected {} beats", beats);
+        let bpm = bpm.unwrap();
+        assert!((bpm - 120.0).abs() < 5.0, "bpm {}", bpm);
+    }
+
+    #[test]
+    fn test_frame_messages() {
+        let frame = AnalysisFrame {
+            rms: 0.5,
+            peak: 0.9,
+            centroid: 440.0,
+            bands: vec![0.1, 0.2],
+            beat: Some(2.0),
+            bpm: None,
+        };
+        let messages = frame_to_messages(&frame, "/audio/analysis");
+        assert_eq!(messages.len(), 6);
+        assert!(matches!(&messages[3], Message::Set(s) if s.address == "/audio/analysis/bands/0"));
+        assert!(matches!(
+            &messages[5],
+            Message::Publish(p) if p.address == "/audio/analysis/beat"
+                && p.signal == Some(SignalType::Event)
+        ));
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(config().validate().is_ok());
+        let bad = AudioAnalysisConfig {
+            fft_size: 1000,
+            ..config()
+        };
+        assert!(bad.validate().is_err());
+        let bad = AudioAnalysisConfig {
+            bands: 0,
+            ..config()
+        };
+        assert!(bad.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bridge_analyzes_stream_signal() {
+        let mut bridge = AudioAnalysisBridge::new(AudioAnalysisConfig {
+            rate_hz: 100.0,
+            ..config()
+        });
+        let mut rx = bridge.start().await.unwrap();
+
+        bridge
+            .send(Message::Publish(PublishMessage {
+                address: "/audio/in".to_string(),
+                signal: Some(SignalType::Stream),
+                value: None,
+                payload: None,
+                samples: Some(
+                    sine(440.0, 0.5, 1024, 44100.0)
+                        .into_iter()
+                        .map(f64::from)
+                        .collect(),
+                ),
+                rate: Some(44100),
+                id: None,
+                phase: None,
+                timestamp: None,
+                timeline: None,
+            }))
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match event {
+            BridgeEvent::ToClasp(msg) => match *msg {
+                Message::Set(set) => {
+                    assert_eq!(set.address, "/audio/analysis/rms");
+                    assert!(set.value.as_f64().unwrap() > 0.3);
+                }
+                other => panic!("unexpected message {:?}", other),
+            },
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        bridge.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,550 @@
+//! Home Assistant MQTT discovery for the MQTT bridge
+//!
+//! Publishes retained discovery configs under
+//! `<discovery_prefix>/<component>/<node_id>/<object_id>/config` for CLASP
+//! signals matching the configured patterns, so they show up in Home
+//! Assistant as entities without any YAML. Entity types and ranges are
+//! derived from the signal's ANNOUNCE definition:
+//!
+//! | Signal | Entity |
+//! |--------|--------|
+//! | writable bool param | `switch` |
+//! | read-only bool | `binary_sensor` |
+//! | writable numeric param | `number` (min/max from `meta.range`) |
+//! | writable string param | `text` |
+//! | anything else | `sensor` |
+//!
+//! State is published (retained) to `<topic_prefix>/state<address>` and
+//! Home Assistant commands arrive on `<topic_prefix>/command<address>`,
+//! which the bridge turns into CLASP SETs.
+
+use clasp_core::address::glob_match;
+use clasp_core::{SignalDefinition, SignalType, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn default_topic_prefix() -> String {
+    "clasp".to_string()
+}
+
+fn default_node_id() -> String {
+    "clasp".to_string()
+}
+
+fn default_device_name() -> String {
+    "CLASP".to_string()
+}
+
+/// Home Assistant discovery configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HaDiscoveryConfig {
+    /// Discovery prefix Home Assistant listens on
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+    /// Prefix for state, command and availability topics
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    /// CLASP address patterns to expose (e.g. `/lights/**`)
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Node ID used in discovery topics and as the device identifier
+    #[serde(default = "default_node_id")]
+    pub node_id: String,
+    /// Device name shown in Home Assistant
+    #[serde(default = "default_device_name")]
+    pub device_name: String,
+}
+
+impl Default for HaDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            discovery_prefix: default_discovery_prefix(),
+            topic_prefix: default_topic_prefix(),
+            patterns: Vec::new(),
+            node_id: default_node_id(),
+            device_name: default_device_name(),
+        }
+    }
+}
+
+/// Value kind derived from the signal datatype
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaValueKind {
+    Bool,
+    Int,
+    Float,
+    String,
+    Unknown,
+}
+
+impl HaValueKind {
+    fn from_datatype(datatype: Option<&str>) -> Self {
+        let Some(datatype) = datatype else {
+            return HaValueKind::Unknown;
+        };
+        match datatype.to_ascii_lowercase().as_str() {
+            "bool" | "boolean" => HaValueKind::Bool,
+            "int" | "integer" | "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => {
+                HaValueKind::Int
+            }
+            "float" | "double" | "number" | "f32" | "f64" => HaValueKind::Float,
+            "string" | "str" | "text" => HaValueKind::String,
+            _ => HaValueKind::Unknown,
+        }
+    }
+
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => HaValueKind::Bool,
+            Value::Int(_) => HaValueKind::Int,
+            Value::Float(_) => HaValueKind::Float,
+            Value::String(_) => HaValueKind::String,
+            _ => HaValueKind::Unknown,
+        }
+    }
+}
+
+/// Home Assistant entity component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaComponent {
+    Switch,
+    BinarySensor,
+    Number,
+    Text,
+    Sensor,
+}
+
+impl HaComponent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HaComponent::Switch => "switch",
+            HaComponent::BinarySensor => "binary_sensor",
+            HaComponent::Number => "number",
+            HaComponent::Text => "text",
+            HaComponent::Sensor => "sensor",
+        }
+    }
+
+    /// Whether Home Assistant can send commands to this entity
+    pub fn is_writable(&self) -> bool {
+        matches!(
+            self,
+            HaComponent::Switch | HaComponent::Number | HaComponent::Text
+        )
+    }
+}
+
+/// A discovered entity ready to be published
+#[derive(Debug, Clone)]
+pub struct HaEntity {
+    pub address: String,
+    pub component: HaComponent,
+    pub kind: HaValueKind,
+    /// Discovery config topic
+    pub config_topic: String,
+    /// Retained discovery config payload
+    pub config: serde_json::Value,
+}
+
+/// Tracks exposed signals and builds discovery configs
+#[derive(Debug, Clone)]
+pub struct HaDiscovery {
+    config: HaDiscoveryConfig,
+    /// Registered signal definitions matching the configured patterns
+    signals: Vec<SignalDefinition>,
+    /// Entities already announced, by concrete address
+    entities: HashMap<String, HaEntity>,
+}
+
+impl HaDiscovery {
+    pub fn new(config: HaDiscoveryConfig) -> Self {
+        Self {
+            config,
+            signals: Vec::new(),
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &HaDiscoveryConfig {
+        &self.config
+    }
+
+    /// Topic Home Assistant uses to announce its own online/offline status
+    pub fn ha_status_topic(&self) -> String {
+        format!("{}/status", self.config.discovery_prefix)
+    }
+
+    /// Availability topic for the bridge (retained online/offline)
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.config.topic_prefix)
+    }
+
+    /// State topic for a CLASP address
+    pub fn state_topic(&self, address: &str) -> String {
+        format!("{}/state{}", self.config.topic_prefix, address)
+    }
+
+    /// Command topic for a CLASP address
+    pub fn command_topic(&self, address: &str) -> String {
+        format!("{}/command{}", self.config.topic_prefix, address)
+    }
+
+    /// Subscription filter covering all command topics
+    pub fn command_filter(&self) -> String {
+        format!("{}/command/#", self.config.topic_prefix)
+    }
+
+    /// Whether an MQTT topic belongs to the discovery namespace and should
+    /// not be bridged as a plain topic
+    pub fn owns_topic(&self, topic: &str) -> bool {
+        topic.starts_with(&format!("{}/", self.config.topic_prefix))
+            || topic.starts_with(&format!("{}/", self.config.discovery_prefix))
+    }
+
+    fn exposed(&self, address: &str) -> bool {
+        self.config
+            .patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, address))
+    }
+
+    /// Register announced signals.
+    ///
+    /// Concrete addresses are announced immediately; wildcard definitions
+    /// are kept and announced per address as values are observed.
+    pub fn register(&mut self, signals: &[SignalDefinition]) -> Vec<HaEntity> {
+        let mut announced = Vec::new();
+        for signal in signals {
+            let concrete = !signal.address.contains('*');
+            if concrete && !self.exposed(&signal.address) {
+                continue;
+            }
+            self.signals
+                .retain(|existing| existing.address != signal.address);
+            self.signals.push(signal.clone());
+
+            if concrete {
+                self.entities.remove(&signal.address);
+                if let Some(entity) = self.announce(&signal.address, None) {
+                    announced.push(entity);
+                }
+            }
+        }
+        announced
+    }
+
+    /// Observe a value on an address, returning a new entity to announce
+    /// if this is the first value seen for a matching signal.
+    pub fn observe(&mut self, address: &str, value: &Value) -> Option<HaEntity> {
+        if self.entities.contains_key(address) || !self.exposed(address) {
+            return None;
+        }
+        self.announce(address, Some(value))
+    }
+
+    /// Whether an address has been announced to Home Assistant
+    pub fn is_announced(&self, address: &str) -> bool {
+        self.entities.contains_key(address)
+    }
+
+    /// All announced entities (republished when Home Assistant restarts)
+    pub fn entities(&self) -> impl Iterator<Item = &HaEntity> {
+        self.entities.values()
+    }
+
+    fn announce(&mut self, address: &str, value: Option<&Value>) -> Option<HaEntity> {
+        let signal = self
+            .signals
+            .iter()
+            .find(|s| s.address == address || glob_match(&s.address, address))?
+            .clone();
+        let entity = self.build_entity(address, &signal, value);
+        self.entities.insert(address.to_string(), entity.clone());
+        Some(entity)
+    }
+
+    fn build_entity(
+        &self,
+        address: &str,
+        signal: &SignalDefinition,
+        value: Option<&Value>,
+    ) -> HaEntity {
+        let mut kind = HaValueKind::from_datatype(signal.datatype.as_deref());
+        if kind == HaValueKind::Unknown {
+            if let Some(value) = value {
+                kind = HaValueKind::from_value(value);
+            }
+        }
+        // Access defaults to read-write, as in the protocol examples
+        let writable = signal.signal_type == SignalType::Param
+            && signal.access.as_deref().is_none_or(|a| a.contains('w'));
+
+        let component = match (kind, writable) {
+            (HaValueKind::Bool, true) => HaComponent::Switch,
+            (HaValueKind::Bool, false) => HaComponent::BinarySensor,
+            (HaValueKind::Int | HaValueKind::Float, true) => HaComponent::Number,
+            (HaValueKind::String, true) => HaComponent::Text,
+            _ => HaComponent::Sensor,
+        };
+
+        let object_id = object_id(address);
+        let meta = signal.meta.as_ref();
+        let name = meta
+            .and_then(|m| m.description.clone())
+            .unwrap_or_else(|| address.to_string());
+
+        let mut config = json!({
+            "name": name,
+            "object_id": object_id,
+            "unique_id": format!("{}_{}", self.config.node_id, object_id),
+            "state_topic": self.state_topic(address),
+            "availability_topic": self.availability_topic(),
+            "device": {
+                "identifiers": [self.config.node_id],
+                "name": self.config.device_name,
+                "manufacturer": "CLASP",
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        if component.is_writable() {
+            config["command_topic"] = json!(self.command_topic(address));
+        }
+
+        match component {
+            HaComponent::Switch | HaComponent::BinarySensor => {
+                config["payload_on"] = json!("true");
+                config["payload_off"] = json!("false");
+                if component == HaComponent::Switch {
+                    config["state_on"] = json!("true");
+                    config["state_off"] = json!("false");
+                }
+            }
+            HaComponent::Number => {
+                match meta.and_then(|m| m.range) {
+                    Some((min, max)) => {
+                        config["min"] = json!(min);
+                        config["max"] = json!(max);
+                        config["mode"] = json!("slider");
+                    }
+                    None => {
+                        config["min"] = json!(-1_000_000);
+                        config["max"] = json!(1_000_000);
+                        config["mode"] = json!("box");
+                    }
+                }
+                config["step"] = if kind == HaValueKind::Int {
+                    json!(1)
+                } else {
+                    json!(0.01)
+                };
+            }
+            _ => {}
+        }
+
+        if matches!(component, HaComponent::Number | HaComponent::Sensor) {
+            // "normalized" is a CLASP convention, not a unit Home Assistant knows
+            if let Some(unit) = meta
+                .and_then(|m| m.unit.as_deref())
+                .filter(|u| *u != "normalized")
+            {
+                config["unit_of_measurement"] = json!(unit);
+            }
+        }
+
+        HaEntity {
+            address: address.to_string(),
+            component,
+            kind,
+            config_topic: format!(
+                "{}/{}/{}/{}/config",
+                self.config.discovery_prefix,
+                component.as_str(),
+                self.config.node_id,
+                object_id
+            ),
+            config,
+        }
+    }
+
+    /// Convert a Home Assistant command into a CLASP address and value.
+    ///
+    /// Returns `None` for topics that are not command topics of a writable
+    /// announced entity.
+    pub fn parse_command(&self, topic: &str, payload: &[u8]) -> Option<(String, Value)> {
+        let prefix = format!("{}/command", self.config.topic_prefix);
+        let address = topic.strip_prefix(&prefix)?;
+        let entity = self.entities.get(address)?;
+        if !entity.component.is_writable() {
+            return None;
+        }
+
+        let text = std::str::from_utf8(payload).ok()?.trim();
+        let value = match entity.kind {
+            HaValueKind::Bool => match text {
+                "true" | "ON" | "on" | "1" => Value::Bool(true),
+                "false" | "OFF" | "off" | "0" => Value::Bool(false),
+                _ => return None,
+            },
+            HaValueKind::Int => {
+                // Number entities may send "42.0" even with step 1
+                Value::Int(text.parse::<f64>().ok()?.round() as i64)
+            }
+            HaValueKind::Float => Value::Float(text.parse().ok()?),
+            HaValueKind::String | HaValueKind::Unknown => Value::String(text.to_string()),
+        };
+        Some((address.to_string(), value))
+    }
+}
+
+/// Derive an MQTT-safe object ID from a CLASP address
+fn object_id(address: &str) -> String {
+    address
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::SignalMeta;
+
+    fn discovery() -> HaDiscovery {
+        HaDiscovery::new(HaDiscoveryConfig {
+            patterns: vec!["/lights/**".to_string(), "/sensors/**".to_string()],
+            ..Default::default()
+        })
+    }
+
+    fn signal(address: &str, datatype: &str, access: Option<&str>) -> SignalDefinition {
+        SignalDefinition {
+            address: address.to_string(),
+            signal_type: SignalType::Param,
+            datatype: Some(datatype.to_string()),
+            access: access.map(String::from),
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn test_numeric_param_becomes_number_with_range() {
+        let mut ha = discovery();
+        let mut sig = signal("/lights/wash/level", "f32", Some("rw"));
+        sig.meta = Some(SignalMeta {
+            unit: Some("normalized".to_string()),
+            range: Some((0.0, 1.0)),
+            default: None,
+            description: Some("Wash level".to_string()),
+            enum_values: None,
+        });
+
+        let entities = ha.register(&[sig]);
+        assert_eq!(entities.len(), 1);
+        let entity = &entities[0];
+        assert_eq!(entity.component, HaComponent::Number);
+        assert_eq!(
+            entity.config_topic,
+            "homeassistant/number/clasp/lights_wash_level/config"
+        );
+        assert_eq!(entity.config["name"], "Wash level");
+        assert_eq!(entity.config["min"], 0.0);
+        assert_eq!(entity.config["max"], 1.0);
+        assert_eq!(
+            entity.config["state_topic"],
+            "clasp/state/lights/wash/level"
+        );
+        assert_eq!(
+            entity.config["command_topic"],
+            "clasp/command/lights/wash/level"
+        );
+        assert!(entity.config.get("unit_of_measurement").is_none());
+    }
+
+    #[test]
+    fn test_bool_access_picks_switch_or_binary_sensor() {
+        let mut ha = discovery();
+        let entities = ha.register(&[
+            signal("/lights/wash/on", "bool", None),
+            signal("/sensors/door/open", "bool", Some("r")),
+        ]);
+        let by_address: HashMap<_, _> = entities
+            .iter()
+            .map(|e| (e.address.as_str(), e.component))
+            .collect();
+        assert_eq!(by_address["/lights/wash/on"], HaComponent::Switch);
+        assert_eq!(by_address["/sensors/door/open"], HaComponent::BinarySensor);
+    }
+
+    #[test]
+    fn test_unexposed_signals_are_ignored() {
+        let mut ha = discovery();
+        assert!(ha
+            .register(&[signal("/audio/gain", "float", None)])
+            .is_empty());
+        assert!(ha.observe("/audio/gain", &Value::Float(1.0)).is_none());
+    }
+
+    #[test]
+    fn test_wildcard_signal_announced_on_first_value() {
+        let mut ha = discovery();
+        let mut sig = signal("/sensors/*/temp", "", Some("r"));
+        sig.datatype = None;
+        assert!(ha.register(&[sig]).is_empty());
+
+        let entity = ha
+            .observe("/sensors/booth/temp", &Value::Float(21.5))
+            .unwrap();
+        assert_eq!(entity.component, HaComponent::Sensor);
+        assert!(ha
+            .observe("/sensors/booth/temp", &Value::Float(22.0))
+            .is_none());
+        assert!(ha.is_announced("/sensors/booth/temp"));
+    }
+
+    #[test]
+    fn test_commands_convert_to_typed_values() {
+        let mut ha = discovery();
+        ha.register(&[
+            signal("/lights/wash/on", "bool", None),
+            signal("/lights/wash/level", "int", None),
+            signal("/sensors/door/open", "bool", Some("r")),
+        ]);
+
+        assert_eq!(
+            ha.parse_command("clasp/command/lights/wash/on", b"false"),
+            Some(("/lights/wash/on".to_string(), Value::Bool(false)))
+        );
+        assert_eq!(
+            ha.parse_command("clasp/command/lights/wash/level", b"42.0"),
+            Some(("/lights/wash/level".to_string(), Value::Int(42)))
+        );
+        // Read-only and unknown entities reject commands
+        assert!(ha
+            .parse_command("clasp/command/sensors/door/open", b"true")
+            .is_none());
+        assert!(ha.parse_command("clasp/command/other", b"1").is_none());
+        assert!(ha
+            .parse_command("clasp/state/lights/wash/on", b"true")
+            .is_none());
+    }
+
+    #[test]
+    fn test_owns_topic() {
+        let ha = discovery();
+        assert!(ha.owns_topic("clasp/state/lights/wash/on"));
+        assert!(ha.owns_topic("homeassistant/status"));
+        assert!(!ha.owns_topic("home/sensors/temp"));
+    }
+}
@@ -366,6 +366,8 @@ async fn set_signal(
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     if let Err(e) = state
@@ -455,6 +457,8 @@ async fn delete_signal(
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let _ = state
@@ -653,6 +657,8 @@ impl Bridge for HttpBridge {
                                                                         lock: false,
                                                                         unlock: false,
                                                                         ttl: None,
+                                                                        trace: false,
+                                                                        branch: None,
                                                                     });
 
                                                                 if let Err(e) = tx_clone
@@ -695,6 +701,8 @@ impl Bridge for HttpBridge {
                                                                 lock: false,
                                                                 unlock: false,
                                                                 ttl: None,
+                                                                trace: false,
+                                                                branch: None,
                                                             });
 
                                                             let _ = tx_clone
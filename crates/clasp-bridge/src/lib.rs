@@ -14,9 +14,17 @@
 //! - WebSocket (real-time bidirectional)
 //! - Socket.IO (event-based WebSocket)
 //! - HTTP/REST (request-response API)
+//!
+//! ## Analysis
+//! - Audio features (RMS, FFT bands, beats) from a stream signal or capture device
+//!
+//! ## Supervision
+//! - [`BridgeSupervisor`] restarts failed bridges with backoff and publishes
+//!   their health to `/clasp/bridges/{name}/status`
 
 pub mod error;
 pub mod mapping;
+pub mod supervisor;
 pub mod traits;
 pub mod transform;
 
@@ -35,6 +43,12 @@ pub mod dmx;
 #[cfg(feature = "sacn")]
 pub mod sacn;
 
+#[cfg(feature = "audio-analysis")]
+pub mod audio;
+
+#[cfg(feature = "mqtt")]
+pub mod homeassistant;
+
 #[cfg(feature = "mqtt")]
 pub mod mqtt;
 
@@ -49,6 +63,7 @@ pub mod http;
 
 pub use error::{BridgeError, Result};
 pub use mapping::{AddressMapping, ValueTransform};
+pub use supervisor::{BridgeHealth, BridgeState, BridgeSupervisor, SupervisorConfig};
 pub use traits::{Bridge, BridgeConfig, BridgeEvent};
 pub use transform::{Aggregator, AggregatorState, Condition, CurveType, Transform, TransformState};
 
@@ -67,6 +82,14 @@ pub use dmx::{DmxBridge, DmxBridgeConfig, DmxInterfaceType};
 #[cfg(feature = "sacn")]
 pub use sacn::{SacnBridge, SacnBridgeConfig, SacnMode};
 
+#[cfg(feature = "audio-analysis")]
+pub use audio::{
+    AnalysisFrame, AudioAnalysisBridge, AudioAnalysisConfig, AudioAnalyzer, AudioSource,
+};
+
+#[cfg(feature = "mqtt")]
+pub use homeassistant::{HaComponent, HaDiscovery, HaDiscoveryConfig, HaEntity};
+
 #[cfg(feature = "mqtt")]
 pub use mqtt::{MqttBridge, MqttBridgeConfig};
 
@@ -1,8 +1,27 @@
 //! MIDI bridge
+//!
+//! Incoming MIDI is mapped under `{namespace}/{device_name}/ch/{channel}`
+//! (channels 0-15):
+//!
+//! - Control Change: SET `.../cc/{controller}` with the raw value (0-127)
+//! - Note On/Off: PUBLISH `.../note` with `{note, velocity, on}`
+//! - Program Change: PUBLISH `.../program` with the program number
+//! - Pitch Bend: SET `.../bend` (-8192 to 8191)
+//!
+//! Clock and transport messages are published to `{namespace}/{device_name}/clock`
+//! and `.../transport`. The same addresses work in the other direction: a SET of
+//! `.../cc/{controller}` or `.../bend`, a SET or PUBLISH of `.../program` and a
+//! PUBLISH of `.../note` are sent to the MIDI output.
+//!
+//! With `virtual_port` the bridge creates input and output ports named
+//! `device_name` that other applications can connect to, instead of opening
+//! existing ports (macOS and Linux only).
 
 use async_trait::async_trait;
 use clasp_core::{Message, PublishMessage, SetMessage, SignalType, Value};
-use midir::{MidiInput, MidiInputPort, MidiOutput, MidiOutputPort};
+use midir::{
+    MidiInput, MidiInputConnection, MidiInputPort, MidiOutput, MidiOutputConnection, MidiOutputPort,
+};
 use parking_lot::Mutex;
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -21,6 +40,9 @@ pub struct MidiBridgeConfig {
     pub namespace: String,
     /// Device name in addresses
     pub device_name: String,
+    /// Create virtual ports named `device_name` instead of opening
+    /// `input_port`/`output_port` (macOS and Linux only)
+    pub virtual_port: bool,
 }
 
 impl Default for MidiBridgeConfig {
@@ -30,6 +52,7 @@ impl Default for MidiBridgeConfig {
             output_port: None,
             namespace: "/midi".to_string(),
             device_name: "default".to_string(),
+            virtual_port: false,
         }
     }
 }
@@ -152,14 +175,23 @@ impl Bridge for MidiBridge {
         if *self.running.lock() {
             return Err(BridgeError::Other("Bridge already running".to_string()));
         }
+        if self.midi_config.virtual_port && cfg!(not(unix)) {
+            return Err(BridgeError::Protocol(
+                "virtual MIDI ports are only supported on macOS and Linux".to_string(),
+            ));
+        }
 
         let (tx, rx) = mpsc::channel(100);
         self.tx = Some(tx.clone());
 
+        // The port threads run until this is cleared
+        *self.running.lock() = true;
+
         // Set up MIDI input in a separate thread (midir types are not Send)
         let namespace = self.midi_config.namespace.clone();
         let device_name = self.midi_config.device_name.clone();
         let input_port_name = self.midi_config.input_port.clone();
+        let virtual_port = self.midi_config.virtual_port;
         let running = self.running.clone();
 
         let input_thread = std::thread::spawn(move || {
@@ -171,43 +203,38 @@ impl Bridge for MidiBridge {
                 }
             };
 
-            let port = match Self::find_input_port(&midi_in, input_port_name.as_deref()) {
-                Some(p) => p,
-                None => {
-                    warn!("No MIDI input port found");
-                    return;
+            let base_addr = format!("{}/{}", namespace, device_name);
+            let callback = move |_stamp: u64, message: &[u8], _: &mut ()| {
+                if let Some(msg) = midi_message_to_clasp(message, &base_addr) {
+                    // midir calls this on its own thread, outside the runtime
+                    let _ = tx.blocking_send(BridgeEvent::ToClasp(Box::new(msg)));
                 }
             };
 
-            let port_name = midi_in
-                .port_name(&port)
-                .unwrap_or_else(|_| "Unknown".to_string());
-            info!("Opening MIDI input: {}", port_name);
+            let conn = if virtual_port {
+                info!("Creating virtual MIDI input: {}", device_name);
+                create_virtual_input(midi_in, &device_name, callback)
+            } else {
+                let port = match Self::find_input_port(&midi_in, input_port_name.as_deref()) {
+                    Some(p) => p,
+                    None => {
+                        warn!("No MIDI input port found");
+                        return;
+                    }
+                };
 
-            let tx_clone = tx.clone();
-            let base_addr = format!("{}/{}", namespace, device_name);
+                let port_name = midi_in
+                    .port_name(&port)
+                    .unwrap_or_else(|_| "Unknown".to_string());
+                info!("Opening MIDI input: {}", port_name);
 
-            // Connect to input - this blocks until disconnected
-            let _conn = match midi_in.connect(
-                &port,
-                "clasp-midi",
-                move |_stamp, message, _| {
-                    if let Some(msg) = midi_message_to_clasp(message, &base_addr) {
-                        // Use blocking send since we're in a callback
-                        let tx = tx_clone.clone();
-                        // Spawn a task to send asynchronously
-                        std::thread::spawn(move || {
-                            let rt = tokio::runtime::Handle::try_current();
-                            if let Ok(handle) = rt {
-                                handle.spawn(async move {
-                                    let _ = tx.send(BridgeEvent::ToClasp(Box::new(msg))).await;
-                                });
-                            }
-                        });
-                    }
-                },
-                (),
-            ) {
+                midi_in
+                    .connect(&port, "clasp-midi", callback, ())
+                    .map_err(|e| e.to_string())
+            };
+
+            // The connection stays open while it is held
+            let _conn = match conn {
                 Ok(conn) => conn,
                 Err(e) => {
                     warn!("Failed to connect to MIDI input: {}", e);
@@ -225,6 +252,7 @@ impl Bridge for MidiBridge {
 
         // Set up MIDI output in a separate thread
         let output_port_name = self.midi_config.output_port.clone();
+        let device_name = self.midi_config.device_name.clone();
         let running_out = self.running.clone();
         let (midi_tx, midi_rx) = std::sync::mpsc::channel::<Vec<u8>>();
 
@@ -237,20 +265,29 @@ impl Bridge for MidiBridge {
                 }
             };
 
-            let port = match Self::find_output_port(&midi_out, output_port_name.as_deref()) {
-                Some(p) => p,
-                None => {
-                    warn!("No MIDI output port found");
-                    return;
-                }
-            };
+            let conn = if virtual_port {
+                info!("Creating virtual MIDI output: {}", device_name);
+                create_virtual_output(midi_out, &device_name)
+            } else {
+                let port = match Self::find_output_port(&midi_out, output_port_name.as_deref()) {
+                    Some(p) => p,
+                    None => {
+                        warn!("No MIDI output port found");
+                        return;
+                    }
+                };
 
-            let port_name = midi_out
-                .port_name(&port)
-                .unwrap_or_else(|_| "Unknown".to_string());
-            info!("Opening MIDI output: {}", port_name);
+                let port_name = midi_out
+                    .port_name(&port)
+                    .unwrap_or_else(|_| "Unknown".to_string());
+                info!("Opening MIDI output: {}", port_name);
 
-            let mut conn = match midi_out.connect(&port, "clasp-midi") {
+                midi_out
+                    .connect(&port, "clasp-midi")
+                    .map_err(|e| e.to_string())
+            };
+
+            let mut conn = match conn {
                 Ok(c) => c,
                 Err(e) => {
                     warn!("Failed to connect to MIDI output: {}", e);
@@ -277,7 +314,6 @@ impl Bridge for MidiBridge {
         self._output_thread = Some(output_thread);
         self.midi_sender = Some(MidiSender { tx: midi_tx });
 
-        *self.running.lock() = true;
         let _ = self.tx.as_ref().unwrap().send(BridgeEvent::Connected).await;
 
         Ok(rx)
@@ -319,6 +355,52 @@ impl Bridge for MidiBridge {
     }
 }
 
+/// Create a virtual input port that other applications can send to
+#[cfg(unix)]
+fn create_virtual_input<F>(
+    midi_in: MidiInput,
+    name: &str,
+    callback: F,
+) -> std::result::Result<MidiInputConnection<()>, String>
+where
+    F: FnMut(u64, &[u8], &mut ()) + Send + 'static,
+{
+    use midir::os::unix::VirtualInput;
+    midi_in
+        .create_virtual(name, callback, ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn create_virtual_input<F>(
+    _midi_in: MidiInput,
+    _name: &str,
+    _callback: F,
+) -> std::result::Result<MidiInputConnection<()>, String>
+where
+    F: FnMut(u64, &[u8], &mut ()) + Send + 'static,
+{
+    Err("virtual MIDI ports are not supported on this platform".to_string())
+}
+
+/// Create a virtual output port that other applications can receive from
+#[cfg(unix)]
+fn create_virtual_output(
+    midi_out: MidiOutput,
+    name: &str,
+) -> std::result::Result<MidiOutputConnection, String> {
+    use midir::os::unix::VirtualOutput;
+    midi_out.create_virtual(name).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn create_virtual_output(
+    _midi_out: MidiOutput,
+    _name: &str,
+) -> std::result::Result<MidiOutputConnection, String> {
+    Err("virtual MIDI ports are not supported on this platform".to_string())
+}
+
 /// Convert MIDI message bytes to Clasp (standalone function for callback)
 fn midi_message_to_clasp(message: &[u8], base_addr: &str) -> Option<Message> {
     if message.is_empty() {
@@ -367,6 +449,8 @@ fn midi_message_to_clasp(message: &[u8], base_addr: &str) -> Option<Message> {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
         }
         // Program Change
@@ -397,6 +481,8 @@ fn midi_message_to_clasp(message: &[u8], base_addr: &str) -> Option<Message> {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
         }
         // System messages (clock, transport)
@@ -456,55 +542,60 @@ fn midi_message_to_clasp(message: &[u8], base_addr: &str) -> Option<Message> {
 }
 
 /// Convert Clasp message to MIDI bytes
-fn clasp_to_midi(message: &Message, _config: &MidiBridgeConfig) -> Option<Vec<u8>> {
-    match message {
-        Message::Set(set) => {
-            // Parse address to extract MIDI parameters
-            let parts: Vec<&str> = set.address.split('/').collect();
-
-            // Looking for pattern: /midi/{device}/ch/{channel}/cc/{num}
-            if parts.len() >= 6 && parts[4] == "cc" {
-                let channel: u8 = parts[3].parse().ok()?;
-                let cc: u8 = parts[5].parse().ok()?;
-                let value = set.value.as_i64()?.clamp(0, 127) as u8;
-
-                return Some(vec![0xB0 | (channel & 0x0F), cc, value]);
-            }
-
-            // Looking for pattern: /midi/{device}/ch/{channel}/bend
-            if parts.len() >= 5 && parts[4] == "bend" {
-                let channel: u8 = parts[3].parse().ok()?;
-                let value = (set.value.as_i64()? + 8192).clamp(0, 16383) as u16;
-                let lsb = (value & 0x7F) as u8;
-                let msb = ((value >> 7) & 0x7F) as u8;
-
-                return Some(vec![0xE0 | (channel & 0x0F), lsb, msb]);
-            }
-
-            None
+///
+/// Only addresses under `{namespace}/{device_name}/ch/{channel}` are sent.
+fn clasp_to_midi(message: &Message, config: &MidiBridgeConfig) -> Option<Vec<u8>> {
+    let (address, value) = match message {
+        Message::Set(set) => (&set.address, Some(&set.value)),
+        Message::Publish(pub_msg) => (
+            &pub_msg.address,
+            pub_msg.payload.as_ref().or(pub_msg.value.as_ref()),
+        ),
+        _ => return None,
+    };
+    let value = value?;
+
+    let base = format!(
+        "{}/{}/ch/",
+        config.namespace.trim_end_matches('/'),
+        config.device_name
+    );
+    let parts: Vec<&str> = address.strip_prefix(&base)?.split('/').collect();
+    let channel = parts[0].parse::<u8>().ok().filter(|c| *c < 16)?;
+
+    match (message, &parts[1..]) {
+        // {channel}/cc/{num}
+        (Message::Set(_), ["cc", cc]) => {
+            let cc = cc.parse::<u8>().ok().filter(|cc| *cc < 128)?;
+            let value = value.as_i64()?.clamp(0, 127) as u8;
+            Some(vec![0xB0 | channel, cc, value])
         }
-        Message::Publish(pub_msg) => {
-            // Handle note events
-            let parts: Vec<&str> = pub_msg.address.split('/').collect();
-
-            // Looking for pattern: /midi/{device}/ch/{channel}/note
-            if parts.len() >= 5 && parts[4] == "note" {
-                let channel: u8 = parts[3].parse().ok()?;
-
-                if let Some(Value::Map(map)) = &pub_msg.payload {
-                    let note = map.get("note")?.as_i64()?.clamp(0, 127) as u8;
-                    let velocity = map.get("velocity")?.as_i64()?.clamp(0, 127) as u8;
-                    let on = map
-                        .get("on")
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(velocity > 0);
-
-                    let status = if on { 0x90 } else { 0x80 };
-                    return Some(vec![status | (channel & 0x0F), note, velocity]);
-                }
-            }
-
-            None
+        // {channel}/bend
+        (Message::Set(_), ["bend"]) => {
+            let value = (value.as_i64()? + 8192).clamp(0, 16383) as u16;
+            let lsb = (value & 0x7F) as u8;
+            let msb = ((value >> 7) & 0x7F) as u8;
+            Some(vec![0xE0 | channel, lsb, msb])
+        }
+        // {channel}/program
+        (_, ["program"]) => {
+            let program = value.as_i64()?.clamp(0, 127) as u8;
+            Some(vec![0xC0 | channel, program])
+        }
+        // {channel}/note
+        (Message::Publish(_), ["note"]) => {
+            let Value::Map(map) = value else {
+                return None;
+            };
+            let note = map.get("note")?.as_i64()?.clamp(0, 127) as u8;
+            let velocity = map.get("velocity")?.as_i64()?.clamp(0, 127) as u8;
+            let on = map
+                .get("on")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(velocity > 0);
+
+            let status = if on { 0x90 } else { 0x80 };
+            Some(vec![status | channel, note, velocity])
         }
         _ => None,
     }
@@ -533,10 +624,80 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_clasp_to_midi() {
+        let config = MidiBridgeConfig {
+            namespace: "/studio/midi".to_string(),
+            device_name: "keys".to_string(),
+            ..Default::default()
+        };
+        let set = |address: &str, value: Value| {
+            Message::Set(SetMessage {
+                address: address.to_string(),
+                value,
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            })
+        };
+
+        assert_eq!(
+            clasp_to_midi(
+                &set("/studio/midi/keys/ch/2/cc/74", Value::Int(200)),
+                &config
+            ),
+            Some(vec![0xB2, 74, 127])
+        );
+        assert_eq!(
+            clasp_to_midi(&set("/studio/midi/keys/ch/0/bend", Value::Int(0)), &config),
+            Some(vec![0xE0, 0x00, 0x40])
+        );
+        assert_eq!(
+            clasp_to_midi(
+                &set("/studio/midi/keys/ch/9/program", Value::Int(5)),
+                &config
+            ),
+            Some(vec![0xC9, 5])
+        );
+        // Other devices, bad channels and unknown paths aren't sent
+        assert_eq!(
+            clasp_to_midi(&set("/studio/midi/pads/ch/0/cc/1", Value::Int(1)), &config),
+            None
+        );
+        assert_eq!(
+            clasp_to_midi(&set("/studio/midi/keys/ch/16/cc/1", Value::Int(1)), &config),
+            None
+        );
+        assert_eq!(
+            clasp_to_midi(
+                &set("/studio/midi/keys/ch/0/cc/1/x", Value::Int(1)),
+                &config
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_program_change_roundtrip() {
+        let config = MidiBridgeConfig {
+            device_name: "test".to_string(),
+            ..Default::default()
+        };
+        let msg = midi_message_to_clasp(&[0xC3, 42], "/midi/test").unwrap();
+        assert_eq!(clasp_to_midi(&msg, &config), Some(vec![0xC3, 42]));
+
+        let note = midi_message_to_clasp(&[0x91, 60, 100], "/midi/test").unwrap();
+        assert_eq!(clasp_to_midi(&note, &config), Some(vec![0x91, 60, 100]));
+    }
+
     #[test]
     fn test_config_default() {
         let config = MidiBridgeConfig::default();
         assert_eq!(config.namespace, "/midi");
         assert_eq!(config.device_name, "default");
+        assert!(!config.virtual_port);
     }
 }
@@ -3,11 +3,12 @@
 //! Provides bidirectional bridging between MQTT and CLASP protocols.
 //! Supports MQTT 3.1.1 and 5.0 via rumqttc.
 
+use crate::homeassistant::{HaDiscovery, HaDiscoveryConfig, HaEntity};
 use crate::{Bridge, BridgeConfig, BridgeError, BridgeEvent, Result};
 use async_trait::async_trait;
 use clasp_core::{Message, SetMessage, Value};
 use parking_lot::Mutex;
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS as MqttQoS};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS as MqttQoS};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -42,6 +43,9 @@ pub struct MqttBridgeConfig {
     /// CLASP namespace prefix
     #[serde(default = "default_namespace")]
     pub namespace: String,
+    /// Home Assistant MQTT discovery (disabled when unset)
+    #[serde(default)]
+    pub homeassistant: Option<HaDiscoveryConfig>,
 }
 
 fn default_keep_alive() -> u16 {
@@ -67,6 +71,7 @@ impl Default for MqttBridgeConfig {
             qos: 0,
             keep_alive_secs: 60,
             namespace: "/mqtt".to_string(),
+            homeassistant: None,
         }
     }
 }
@@ -77,6 +82,7 @@ pub struct MqttBridge {
     mqtt_config: MqttBridgeConfig,
     client: Option<AsyncClient>,
     running: Arc<Mutex<bool>>,
+    discovery: Option<Arc<Mutex<HaDiscovery>>>,
 }
 
 impl MqttBridge {
@@ -89,11 +95,17 @@ impl MqttBridge {
             ..Default::default()
         };
 
+        let discovery = mqtt_config
+            .homeassistant
+            .clone()
+            .map(|ha| Arc::new(Mutex::new(HaDiscovery::new(ha))));
+
         Self {
             config,
             mqtt_config,
             client: None,
             running: Arc::new(Mutex::new(false)),
+            discovery,
         }
     }
 
@@ -173,6 +185,20 @@ impl MqttBridge {
         }
     }
 
+    /// Publish retained discovery configs without blocking the event loop
+    fn publish_discovery(client: &AsyncClient, entities: &[HaEntity]) {
+        for entity in entities {
+            if let Err(e) = client.try_publish(
+                &entity.config_topic,
+                MqttQoS::AtLeastOnce,
+                true,
+                entity.config.to_string(),
+            ) {
+                warn!("Home Assistant discovery publish failed: {}", e);
+            }
+        }
+    }
+
     /// Convert CLASP Value to MQTT payload
     fn value_to_payload(value: &Value) -> Vec<u8> {
         match value {
@@ -212,6 +238,15 @@ impl Bridge for MqttBridge {
             mqttoptions.set_credentials(user, pass);
         }
 
+        if let Some(discovery) = &self.discovery {
+            mqttoptions.set_last_will(LastWill::new(
+                discovery.lock().availability_topic(),
+                "offline",
+                MqttQoS::AtLeastOnce,
+                true,
+            ));
+        }
+
         // Create client
         let (client, mut eventloop) = AsyncClient::new(mqttoptions, 100);
         self.client = Some(client.clone());
@@ -227,9 +262,26 @@ impl Bridge for MqttBridge {
             debug!("MQTT subscribed to: {}", topic);
         }
 
+        if let Some(discovery) = &self.discovery {
+            let topics = {
+                let discovery = discovery.lock();
+                [discovery.command_filter(), discovery.ha_status_topic()]
+            };
+            for topic in topics {
+                client
+                    .subscribe(&topic, MqttQoS::AtLeastOnce)
+                    .await
+                    .map_err(|e| {
+                        BridgeError::ConnectionFailed(format!("Subscribe failed: {}", e))
+                    })?;
+                debug!("MQTT subscribed to: {}", topic);
+            }
+        }
+
         let (tx, rx) = mpsc::channel(100);
         let running = self.running.clone();
         let namespace = self.mqtt_config.namespace.clone();
+        let discovery = self.discovery.clone();
 
         info!(
             "MQTT bridge connecting to {}:{}",
@@ -250,8 +302,28 @@ impl Bridge for MqttBridge {
 
                         debug!("MQTT received: {} ({} bytes)", topic, payload.len());
 
-                        let address = format!("{}/{}", namespace, topic);
-                        let value = MqttBridge::parse_payload(&payload);
+                        let (address, value) = match &discovery {
+                            Some(discovery) if discovery.lock().owns_topic(&topic) => {
+                                let discovery = discovery.lock();
+                                if topic == discovery.ha_status_topic() {
+                                    // Home Assistant restarted, re-announce entities
+                                    if payload == b"online" {
+                                        let entities: Vec<_> =
+                                            discovery.entities().cloned().collect();
+                                        MqttBridge::publish_discovery(&client, &entities);
+                                    }
+                                    continue;
+                                }
+                                match discovery.parse_command(&topic, &payload) {
+                                    Some(command) => command,
+                                    None => continue,
+                                }
+                            }
+                            _ => (
+                                format!("{}/{}", namespace, topic),
+                                MqttBridge::parse_payload(&payload),
+                            ),
+                        };
 
                         let msg = Message::Set(SetMessage {
                             address,
@@ -260,6 +332,8 @@ impl Bridge for MqttBridge {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         });
 
                         if tx.send(BridgeEvent::ToClasp(Box::new(msg))).await.is_err() {
@@ -268,6 +342,17 @@ impl Bridge for MqttBridge {
                     }
                     Ok(Event::Incoming(Packet::ConnAck(_))) => {
                         info!("MQTT connected to broker");
+                        if let Some(discovery) = &discovery {
+                            let discovery = discovery.lock();
+                            let _ = client.try_publish(
+                                discovery.availability_topic(),
+                                MqttQoS::AtLeastOnce,
+                                true,
+                                "online",
+                            );
+                            let entities: Vec<_> = discovery.entities().cloned().collect();
+                            MqttBridge::publish_discovery(&client, &entities);
+                        }
                         let _ = tx.send(BridgeEvent::Connected).await;
                     }
                     Ok(Event::Incoming(Packet::Disconnect)) => {
@@ -309,6 +394,13 @@ impl Bridge for MqttBridge {
             .ok_or_else(|| BridgeError::Other("Not connected".to_string()))?;
 
         let (address, value) = match &msg {
+            Message::Announce(announce) => {
+                if let Some(discovery) = &self.discovery {
+                    let entities = discovery.lock().register(&announce.signals);
+                    Self::publish_discovery(client, &entities);
+                }
+                return Ok(());
+            }
             Message::Set(set) => (&set.address, &set.value),
             Message::Publish(pub_msg) => {
                 if let Some(val) = &pub_msg.value {
@@ -330,6 +422,30 @@ impl Bridge for MqttBridge {
             .map_err(|e| BridgeError::Other(format!("MQTT publish failed: {}", e)))?;
 
         debug!("MQTT sent to topic: {}", topic);
+
+        if let Some(discovery) = &self.discovery {
+            let state_topic = {
+                let mut discovery = discovery.lock();
+                if let Some(entity) = discovery.observe(address, value) {
+                    Self::publish_discovery(client, &[entity]);
+                }
+                discovery
+                    .is_announced(address)
+                    .then(|| discovery.state_topic(address))
+            };
+            if let Some(state_topic) = state_topic {
+                client
+                    .publish(
+                        &state_topic,
+                        MqttQoS::AtLeastOnce,
+                        true,
+                        Self::value_to_payload(value),
+                    )
+                    .await
+                    .map_err(|e| BridgeError::Other(format!("MQTT publish failed: {}", e)))?;
+            }
+        }
+
         Ok(())
     }
 
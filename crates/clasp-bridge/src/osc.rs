@@ -79,6 +79,8 @@ impl OscBridge {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
     }
 
@@ -306,6 +308,8 @@ fn packet_to_messages(packet: &OscPacket, namespace: &str) -> Option<Vec<Message
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             })])
         }
         OscPacket::Bundle(bundle) => {
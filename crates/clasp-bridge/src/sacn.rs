@@ -89,6 +89,9 @@ pub struct SacnBridgeConfig {
     /// Synchronization address (0 = no sync)
     #[serde(default)]
     pub sync_address: u16,
+    /// Sender frames per second for changed universes
+    #[serde(default = "default_refresh_rate")]
+    pub refresh_rate: f64,
 }
 
 fn default_universes() -> Vec<u16> {
@@ -111,6 +114,10 @@ fn default_namespace() -> String {
     "/sacn".to_string()
 }
 
+fn default_refresh_rate() -> f64 {
+    44.0 // Typical DMX refresh rate
+}
+
 impl Default for SacnBridgeConfig {
     fn default() -> Self {
         Self {
@@ -124,6 +131,7 @@ impl Default for SacnBridgeConfig {
             namespace: default_namespace(),
             preview: false,
             sync_address: 0,
+            refresh_rate: default_refresh_rate(),
         }
     }
 }
@@ -175,6 +183,8 @@ impl SacnBridge {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         })
     }
 
@@ -334,8 +344,14 @@ impl SacnBridge {
             config.source_name, config.universes
         );
 
-        // Transmission interval (44Hz is typical for DMX)
-        let mut interval = tokio::time::interval(std::time::Duration::from_millis(23));
+        // Transmission interval
+        let refresh_rate = if config.refresh_rate > 0.0 {
+            config.refresh_rate
+        } else {
+            default_refresh_rate()
+        };
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs_f64(1.0 / refresh_rate));
         let mut dirty_universes: std::collections::HashSet<u16> = std::collections::HashSet::new();
 
         loop {
@@ -552,6 +568,7 @@ mod tests {
         assert_eq!(config.universes, vec![1]);
         assert_eq!(config.priority, 100);
         assert!(config.multicast);
+        assert_eq!(config.refresh_rate, 44.0);
     }
 
     #[test]
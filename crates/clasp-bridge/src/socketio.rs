@@ -245,6 +245,8 @@ impl Bridge for SocketIOBridge {
                         lock: false,
                         unlock: false,
                         ttl: None,
+                        trace: false,
+                        branch: None,
                     });
 
                     debug!("Socket.IO received event: {}", event_name);
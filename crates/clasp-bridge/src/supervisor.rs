@@ -0,0 +1,621 @@
+//! Bridge supervisor
+//!
+//! A [`BridgeSupervisor`] runs several bridges at once and restarts any that
+//! fail, waiting longer after each consecutive failure (see
+//! [`SupervisorConfig`]). A bridge is restarted when:
+//!
+//! - `start` fails
+//! - its event channel closes
+//! - it reports `Disconnected`, or stops reporting `is_running`, and doesn't
+//!   report `Connected` again within `reconnect_grace` (bridges that
+//!   reconnect by themselves get the chance to)
+//!
+//! `Error` events are recorded but don't restart a bridge on their own.
+//!
+//! Every bridge's messages come out of one channel, together with a SET of
+//! its health to `/clasp/bridges/{name}/status` whenever that changes:
+//!
+//! ```text
+//! { "state": "running", "restarts": 2, "last_error": "Broker disconnect" }
+//! ```
+//!
+//! where `state` is `starting`, `running`, `disconnected`, `restarting`,
+//! `failed` (out of restarts) or `stopped`. Forward the channel to a router
+//! or client as with a single bridge.
+
+use clasp_core::{Message, SetMessage, Value};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{Bridge, BridgeError, BridgeEvent, Result};
+
+/// Restart and health reporting settings
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// Wait before the first restart; doubles with each consecutive failure
+    pub initial_backoff: Duration,
+    /// Longest wait between restarts
+    pub max_backoff: Duration,
+    /// Consecutive failures after which a bridge is left `failed` (`None`:
+    /// restart forever)
+    pub max_restarts: Option<u32>,
+    /// A bridge that ran this long before failing starts over from
+    /// `initial_backoff` and a clean failure count
+    pub stable_after: Duration,
+    /// How long a disconnected bridge has to reconnect by itself
+    pub reconnect_grace: Duration,
+    /// How often `is_running` is checked
+    pub health_interval: Duration,
+    /// Status addresses are `{status_prefix}/{name}/status`
+    pub status_prefix: String,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_restarts: None,
+            stable_after: Duration::from_secs(60),
+            reconnect_grace: Duration::from_secs(10),
+            health_interval: Duration::from_secs(1),
+            status_prefix: "/clasp/bridges".to_string(),
+        }
+    }
+}
+
+impl SupervisorConfig {
+    /// Wait before restarting after `failures` consecutive failures
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Where a supervised bridge is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeState {
+    /// `start` is being called
+    Starting,
+    /// Started, and connected if the bridge reports connections
+    Running,
+    /// Reported `Disconnected`; waiting for it to reconnect
+    Disconnected,
+    /// Failed; waiting out the backoff before the next start
+    Restarting,
+    /// Out of restarts; no longer supervised
+    Failed,
+    /// Not started yet, or stopped by the supervisor
+    Stopped,
+}
+
+impl BridgeState {
+    /// Name used in status values
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BridgeState::Starting => "starting",
+            BridgeState::Running => "running",
+            BridgeState::Disconnected => "disconnected",
+            BridgeState::Restarting => "restarting",
+            BridgeState::Failed => "failed",
+            BridgeState::Stopped => "stopped",
+        }
+    }
+}
+
+/// Health of one supervised bridge
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeHealth {
+    pub state: BridgeState,
+    /// Restarts since the bridge was added
+    pub restarts: u32,
+    /// Most recent error or disconnect reason
+    pub last_error: Option<String>,
+}
+
+impl BridgeHealth {
+    /// The value published on the bridge's status address
+    pub fn to_value(&self) -> Value {
+        let mut map = HashMap::new();
+        map.insert(
+            "state".to_string(),
+            Value::String(self.state.as_str().to_string()),
+        );
+        map.insert("restarts".to_string(), Value::Int(self.restarts as i64));
+        map.insert(
+            "last_error".to_string(),
+            self.last_error
+                .clone()
+                .map(Value::String)
+                .unwrap_or(Value::Null),
+        );
+        Value::Map(map)
+    }
+}
+
+type SharedBridge = Arc<Mutex<Box<dyn Bridge>>>;
+
+struct Supervised {
+    bridge: SharedBridge,
+    health: Arc<RwLock<BridgeHealth>>,
+}
+
+/// Runs bridges, restarts them on failure and reports their health
+pub struct BridgeSupervisor {
+    config: Arc<SupervisorConfig>,
+    bridges: HashMap<String, Supervised>,
+    /// Set once started
+    events: Option<mpsc::Sender<BridgeEvent>>,
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl BridgeSupervisor {
+    pub fn new(config: SupervisorConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            bridges: HashMap::new(),
+            events: None,
+            shutdown: watch::channel(false).0,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Supervise a bridge under `name`, which becomes a segment of its
+    /// status address. Starts it right away if the supervisor is running.
+    pub fn add(&mut self, name: &str, bridge: Box<dyn Bridge>) -> Result<()> {
+        if name.is_empty() || name.contains(['/', '*', '#', '?']) {
+            return Err(BridgeError::Other(format!(
+                "invalid bridge name {:?}",
+                name
+            )));
+        }
+        if self.bridges.contains_key(name) {
+            return Err(BridgeError::Other(format!(
+                "bridge {} is already supervised",
+                name
+            )));
+        }
+
+        let supervised = Supervised {
+            bridge: Arc::new(Mutex::new(bridge)),
+            health: Arc::new(RwLock::new(BridgeHealth {
+                state: BridgeState::Stopped,
+                restarts: 0,
+                last_error: None,
+            })),
+        };
+        if let Some(events) = &self.events {
+            self.tasks
+                .push(self.spawn(name, &supervised, events.clone()));
+        }
+        self.bridges.insert(name.to_string(), supervised);
+        Ok(())
+    }
+
+    /// Start every bridge. The receiver yields their `ToClasp` messages and
+    /// the status SETs.
+    pub fn start(&mut self) -> Result<mpsc::Receiver<BridgeEvent>> {
+        if self.events.is_some() {
+            return Err(BridgeError::Other(
+                "bridge supervisor already started".to_string(),
+            ));
+        }
+        let (tx, rx) = mpsc::channel(1000);
+        self.shutdown.send_replace(false);
+        for (name, supervised) in &self.bridges {
+            self.tasks.push(self.spawn(name, supervised, tx.clone()));
+        }
+        self.events = Some(tx);
+        Ok(rx)
+    }
+
+    /// Stop supervising and stop every bridge
+    pub async fn stop(&mut self) -> Result<()> {
+        self.shutdown.send_replace(true);
+        for task in self.tasks.drain(..) {
+            let _ = task.await;
+        }
+        if let Some(events) = self.events.take() {
+            for (name, supervised) in &self.bridges {
+                let health = {
+                    let mut health = supervised.health.write();
+                    health.state = BridgeState::Stopped;
+                    health.clone()
+                };
+                let status = status_address(&self.config, name);
+                let _ = events.send(status_event(&status, &health)).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the supervised bridges, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.bridges.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Current health of a bridge
+    pub fn health(&self, name: &str) -> Option<BridgeHealth> {
+        self.bridges.get(name).map(|s| s.health.read().clone())
+    }
+
+    /// Send a message from CLASP to one bridge
+    pub async fn send(&self, name: &str, message: Message) -> Result<()> {
+        let supervised = self
+            .bridges
+            .get(name)
+            .ok_or_else(|| BridgeError::Other(format!("no bridge named {}", name)))?;
+        let bridge = supervised.bridge.lock().await;
+        bridge.send(message).await
+    }
+
+    fn spawn(
+        &self,
+        name: &str,
+        supervised: &Supervised,
+        events: mpsc::Sender<BridgeEvent>,
+    ) -> JoinHandle<()> {
+        let task = Task {
+            status: status_address(&self.config, name),
+            name: name.to_string(),
+            bridge: supervised.bridge.clone(),
+            health: supervised.health.clone(),
+            config: self.config.clone(),
+            events,
+            shutdown: self.shutdown.subscribe(),
+        };
+        tokio::spawn(task.run())
+    }
+}
+
+fn status_address(config: &SupervisorConfig, name: &str) -> String {
+    format!("{}/{}/status", config.status_prefix, name)
+}
+
+fn status_event(address: &str, health: &BridgeHealth) -> BridgeEvent {
+    BridgeEvent::ToClasp(Box::new(Message::Set(SetMessage {
+        address: address.to_string(),
+        value: health.to_value(),
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    })))
+}
+
+/// Supervises one bridge
+struct Task {
+    name: String,
+    status: String,
+    bridge: SharedBridge,
+    health: Arc<RwLock<BridgeHealth>>,
+    config: Arc<SupervisorConfig>,
+    events: mpsc::Sender<BridgeEvent>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Task {
+    async fn run(mut self) {
+        let mut failures = 0u32;
+        loop {
+            self.update(|h| h.state = BridgeState::Starting).await;
+            let started = self.bridge.lock().await.start().await;
+            let reason = match started {
+                Ok(events) => {
+                    let up = Instant::now();
+                    let reason = self.watch(events).await;
+                    if let Err(e) = self.bridge.lock().await.stop().await {
+                        warn!("Bridge {} failed to stop: {}", self.name, e);
+                    }
+                    match reason {
+                        Some(reason) => {
+                            if up.elapsed() >= self.config.stable_after {
+                                failures = 0;
+                            }
+                            reason
+                        }
+                        None => return,
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+            if *self.shutdown.borrow() {
+                return;
+            }
+
+            failures += 1;
+            if self.config.max_restarts.is_some_and(|max| failures > max) {
+                warn!(
+                    "Bridge {} failed {} times, giving up: {}",
+                    self.name, failures, reason
+                );
+                self.update(|h| {
+                    h.state = BridgeState::Failed;
+                    h.last_error = Some(reason);
+                })
+                .await;
+                return;
+            }
+
+            let delay = self.config.backoff(failures);
+            warn!(
+                "Bridge {} failed ({}), restarting in {:?}",
+                self.name, reason, delay
+            );
+            self.update(|h| {
+                h.state = BridgeState::Restarting;
+                h.restarts += 1;
+                h.last_error = Some(reason);
+            })
+            .await;
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = self.shutdown.changed() => return,
+            }
+        }
+    }
+
+    /// Forward a started bridge's messages until it fails (returning why)
+    /// or the supervisor stops (returning `None`)
+    async fn watch(&mut self, mut events: mpsc::Receiver<BridgeEvent>) -> Option<String> {
+        self.update(|h| h.state = BridgeState::Running).await;
+        info!("Bridge {} started", self.name);
+
+        let mut check = tokio::time::interval(self.config.health_interval);
+        check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        check.tick().await;
+        // Since when the bridge has been disconnected or not running, and why
+        let mut down_since: Option<Instant> = None;
+        let mut down_reason: Option<String> = None;
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown.changed() => return None,
+                event = events.recv() => match event {
+                    Some(BridgeEvent::ToClasp(msg)) => {
+                        let _ = self.events.send(BridgeEvent::ToClasp(msg)).await;
+                    }
+                    Some(BridgeEvent::Connected) => {
+                        down_since = None;
+                        self.update(|h| h.state = BridgeState::Running).await;
+                    }
+                    Some(BridgeEvent::Disconnected { reason }) => {
+                        down_since.get_or_insert_with(Instant::now);
+                        if reason.is_some() {
+                            down_reason.clone_from(&reason);
+                        }
+                        self.update(|h| {
+                            h.state = BridgeState::Disconnected;
+                            if reason.is_some() {
+                                h.last_error = reason;
+                            }
+                        })
+                        .await;
+                    }
+                    Some(BridgeEvent::Error(e)) => {
+                        self.update(|h| h.last_error = Some(e)).await;
+                    }
+                    None => return Some("bridge closed its event channel".to_string()),
+                },
+                _ = check.tick() => {
+                    if !self.bridge.lock().await.is_running() {
+                        down_since.get_or_insert_with(Instant::now);
+                    }
+                    if down_since.is_some_and(|t| t.elapsed() >= self.config.reconnect_grace) {
+                        return Some(
+                            down_reason.unwrap_or_else(|| "bridge stopped running".to_string()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Change the bridge's health and publish it if it changed
+    async fn update(&self, change: impl FnOnce(&mut BridgeHealth)) {
+        let health = {
+            let mut health = self.health.write();
+            let before = health.clone();
+            change(&mut health);
+            if *health == before {
+                return;
+            }
+            health.clone()
+        };
+        let _ = self.events.send(status_event(&self.status, &health)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BridgeConfig;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    /// Fails `start` a set number of times, then runs until told to die
+    struct FlakyBridge {
+        config: BridgeConfig,
+        failures_left: u32,
+        starts: Arc<AtomicU32>,
+        running: Arc<AtomicBool>,
+        tx: Option<mpsc::Sender<BridgeEvent>>,
+    }
+
+    impl FlakyBridge {
+        fn new(failures: u32) -> (Self, Arc<AtomicU32>, Arc<AtomicBool>) {
+            let starts = Arc::new(AtomicU32::new(0));
+            let running = Arc::new(AtomicBool::new(false));
+            let bridge = Self {
+                config: BridgeConfig::default(),
+                failures_left: failures,
+                starts: starts.clone(),
+                running: running.clone(),
+                tx: None,
+            };
+            (bridge, starts, running)
+        }
+    }
+
+    #[async_trait]
+    impl Bridge for FlakyBridge {
+        fn config(&self) -> &BridgeConfig {
+            &self.config
+        }
+
+        async fn start(&mut self) -> Result<mpsc::Receiver<BridgeEvent>> {
+            self.starts.fetch_add(1, Ordering::SeqCst);
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(BridgeError::ConnectionFailed("port busy".to_string()));
+            }
+            let (tx, rx) = mpsc::channel(10);
+            let _ = tx.send(BridgeEvent::Connected).await;
+            self.running.store(true, Ordering::SeqCst);
+            self.tx = Some(tx);
+            Ok(rx)
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            self.running.store(false, Ordering::SeqCst);
+            self.tx = None;
+            Ok(())
+        }
+
+        async fn send(&self, _message: Message) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            self.running.load(Ordering::SeqCst)
+        }
+
+        fn namespace(&self) -> &str {
+            "/flaky"
+        }
+    }
+
+    fn fast_config() -> SupervisorConfig {
+        SupervisorConfig {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+            reconnect_grace: Duration::from_millis(50),
+            health_interval: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    /// Status states published so far
+    fn states(rx: &mut mpsc::Receiver<BridgeEvent>) -> Vec<String> {
+        let mut states = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let BridgeEvent::ToClasp(msg) = event {
+                if let Message::Set(set) = *msg {
+                    if let Value::Map(map) = set.value {
+                        states.push(map["state"].as_str().unwrap().to_string());
+                    }
+                }
+            }
+        }
+        states
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let config = fast_config();
+        assert_eq!(config.backoff(1), Duration::from_millis(10));
+        assert_eq!(config.backoff(2), Duration::from_millis(20));
+        assert_eq!(config.backoff(3), Duration::from_millis(40));
+        assert_eq!(config.backoff(40), Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_rejects_bad_and_duplicate_names() {
+        let mut supervisor = BridgeSupervisor::new(fast_config());
+        assert!(supervisor
+            .add("osc/1", Box::new(FlakyBridge::new(0).0))
+            .is_err());
+        assert!(supervisor
+            .add("osc", Box::new(FlakyBridge::new(0).0))
+            .is_ok());
+        assert!(supervisor
+            .add("osc", Box::new(FlakyBridge::new(0).0))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restarts_after_failed_starts() {
+        let (bridge, starts, _) = FlakyBridge::new(2);
+        let mut supervisor = BridgeSupervisor::new(fast_config());
+        supervisor.add("osc", Box::new(bridge)).unwrap();
+        let mut rx = supervisor.start().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        let health = supervisor.health("osc").unwrap();
+        assert_eq!(health.state, BridgeState::Running);
+        assert_eq!(health.restarts, 2);
+        assert_eq!(
+            health.last_error.as_deref(),
+            Some("connection failed: port busy")
+        );
+
+        let states = states(&mut rx);
+        assert_eq!(states.first().map(String::as_str), Some("starting"));
+        assert!(states.iter().any(|s| s == "restarting"));
+        assert_eq!(states.last().map(String::as_str), Some("running"));
+
+        supervisor.stop().await.unwrap();
+        assert_eq!(
+            supervisor.health("osc").unwrap().state,
+            BridgeState::Stopped
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restarts_bridge_that_stops_running() {
+        let (bridge, starts, running) = FlakyBridge::new(0);
+        let mut supervisor = BridgeSupervisor::new(fast_config());
+        supervisor.add("mqtt", Box::new(bridge)).unwrap();
+        let _rx = supervisor.start().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        running.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        assert_eq!(supervisor.health("mqtt").unwrap().restarts, 1);
+        supervisor.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_restarts() {
+        let (bridge, starts, _) = FlakyBridge::new(u32::MAX);
+        let mut supervisor = BridgeSupervisor::new(SupervisorConfig {
+            max_restarts: Some(2),
+            ..fast_config()
+        });
+        supervisor.add("http", Box::new(bridge)).unwrap();
+        let _rx = supervisor.start().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            supervisor.health("http").unwrap().state,
+            BridgeState::Failed
+        );
+        supervisor.stop().await.unwrap();
+    }
+}
@@ -173,6 +173,8 @@ impl WebSocketBridge {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         }))
                     } else {
                         // Plain text message
@@ -183,6 +185,8 @@ impl WebSocketBridge {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         }))
                     }
                 }
@@ -202,6 +206,8 @@ impl WebSocketBridge {
                                 lock: false,
                                 unlock: false,
                                 ttl: None,
+                                trace: false,
+                                branch: None,
                             }))
                         } else {
                             // Wrap text as a message for the namespace
@@ -212,6 +218,8 @@ impl WebSocketBridge {
                                 lock: false,
                                 unlock: false,
                                 ttl: None,
+                                trace: false,
+                                branch: None,
                             }))
                         }
                     } else {
@@ -223,6 +231,8 @@ impl WebSocketBridge {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         }))
                     }
                 }
@@ -241,6 +251,8 @@ impl WebSocketBridge {
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         }))
                     }
                 }
@@ -251,6 +263,8 @@ impl WebSocketBridge {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 })),
             },
             _ => None,
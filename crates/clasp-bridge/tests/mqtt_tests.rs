@@ -61,6 +61,7 @@ impl TestEnv {
             qos: 0,
             keep_alive_secs: 60,
             namespace: "/mqtt".to_string(),
+            homeassistant: None,
         };
 
         let mut bridge = MqttBridge::new(config);
@@ -123,6 +123,8 @@ async fn test_clasp_set_to_websocket_json() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     bridge
@@ -0,0 +1,497 @@
+//! Audit log of capability token validations
+//!
+//! Capability tokens are validated locally, so without a record nobody can
+//! say afterwards which delegate's tokens were used, or when. With an
+//! [`AuditLog`] attached
+//! ([`CapabilityValidator::with_audit`](crate::CapabilityValidator::with_audit)),
+//! every `cap_` token presented to the validator is recorded with its
+//! issuer, the root of its delegation chain, audience, scopes, chain depth
+//! and the outcome, accepted or not.
+//!
+//! A token the validator could not decode is recorded too, with only the
+//! rejection reason. Writes are best effort: a failing audit log never
+//! changes a validation result.
+
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Result;
+use crate::revocation::issuer_fingerprint;
+use crate::token::CapabilityToken;
+
+/// Records kept by a [`MemoryAuditLog`] created with `new()`
+pub const DEFAULT_MEMORY_CAPACITY: usize = 10_000;
+
+/// Records returned by a query without a limit
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+
+/// Result of a validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditOutcome {
+    Valid,
+    Expired,
+    Invalid,
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Valid => "valid",
+            AuditOutcome::Expired => "expired",
+            AuditOutcome::Invalid => "invalid",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "valid" => Some(AuditOutcome::Valid),
+            "expired" => Some(AuditOutcome::Expired),
+            "invalid" => Some(AuditOutcome::Invalid),
+            _ => None,
+        }
+    }
+}
+
+/// One validated (or rejected) capability token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// When the token was presented (Unix timestamp, seconds)
+    pub timestamp: u64,
+    /// Token nonce; `None` if the token could not be decoded
+    pub nonce: Option<String>,
+    /// Fingerprint of the key that signed the token
+    pub issuer: Option<String>,
+    /// Fingerprint of the root of the delegation chain
+    pub root_issuer: Option<String>,
+    /// Hex public key the token is bound to, if any
+    pub audience: Option<String>,
+    pub scopes: Vec<String>,
+    pub chain_depth: usize,
+    pub outcome: AuditOutcome,
+    /// Why the token was rejected
+    pub reason: Option<String>,
+}
+
+impl AuditRecord {
+    /// Record for a decoded token
+    pub fn for_token(token: &CapabilityToken, outcome: AuditOutcome) -> Self {
+        let root = token.proofs.first().map_or(&token.issuer, |p| &p.issuer);
+        Self {
+            timestamp: now(),
+            nonce: Some(token.nonce.clone()),
+            issuer: Some(issuer_fingerprint(&token.issuer)),
+            root_issuer: Some(issuer_fingerprint(root)),
+            audience: token.audience.as_deref().map(issuer_fingerprint),
+            scopes: token.scopes.clone(),
+            chain_depth: token.chain_depth(),
+            outcome,
+            reason: None,
+        }
+    }
+
+    /// Record for a token that could not be decoded
+    pub fn undecodable(reason: impl Into<String>) -> Self {
+        Self {
+            timestamp: now(),
+            nonce: None,
+            issuer: None,
+            root_issuer: None,
+            audience: None,
+            scopes: Vec::new(),
+            chain_depth: 0,
+            outcome: AuditOutcome::Invalid,
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// Attach a rejection reason
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn matches(&self, query: &AuditQuery) -> bool {
+        query.issuer.as_ref().is_none_or(|issuer| {
+            self.issuer.as_ref() == Some(issuer) || self.root_issuer.as_ref() == Some(issuer)
+        }) && query
+            .nonce
+            .as_ref()
+            .is_none_or(|nonce| self.nonce.as_ref() == Some(nonce))
+            && query.outcome.is_none_or(|outcome| self.outcome == outcome)
+            && query.since.is_none_or(|since| self.timestamp >= since)
+            && query.until.is_none_or(|until| self.timestamp < until)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Filter for [`AuditLog::query`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    /// Issuer or root issuer fingerprint
+    pub issuer: Option<String>,
+    pub nonce: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    /// Records at or after this Unix timestamp
+    pub since: Option<u64>,
+    /// Records before this Unix timestamp
+    pub until: Option<u64>,
+    /// At most this many records (default [`DEFAULT_QUERY_LIMIT`])
+    pub limit: Option<usize>,
+}
+
+/// Storage for audit records
+///
+/// `record` is called synchronously on every capability token validation.
+pub trait AuditLog: Send + Sync {
+    /// Append a record
+    fn record(&self, record: AuditRecord) -> Result<()>;
+
+    /// Records matching `query`, newest first
+    fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>>;
+
+    /// Delete records older than a Unix timestamp; returns how many
+    fn prune(&self, before: u64) -> Result<usize>;
+}
+
+/// In-memory audit log keeping the most recent records
+#[derive(Debug)]
+pub struct MemoryAuditLog {
+    capacity: usize,
+    records: RwLock<VecDeque<AuditRecord>>,
+}
+
+impl MemoryAuditLog {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_MEMORY_CAPACITY)
+    }
+
+    /// Keep at most `capacity` records, dropping the oldest
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            records: RwLock::new(VecDeque::new()),
+        }
+    }
+}
+
+impl Default for MemoryAuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditLog for MemoryAuditLog {
+    fn record(&self, record: AuditRecord) -> Result<()> {
+        let mut records = self.records.write().unwrap();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+        Ok(())
+    }
+
+    fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        Ok(self
+            .records
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|r| r.matches(query))
+            .take(query.limit.unwrap_or(DEFAULT_QUERY_LIMIT))
+            .cloned()
+            .collect())
+    }
+
+    fn prune(&self, before: u64) -> Result<usize> {
+        let mut records = self.records.write().unwrap();
+        let len = records.len();
+        records.retain(|r| r.timestamp >= before);
+        Ok(len - records.len())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteAuditLog;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use rusqlite::{params, params_from_iter, types::Value, Connection};
+    use std::sync::Mutex;
+
+    use super::{AuditLog, AuditOutcome, AuditQuery, AuditRecord, DEFAULT_QUERY_LIMIT};
+    use crate::error::{CapError, Result};
+
+    /// SQLite-backed audit log
+    ///
+    /// Uses WAL mode, so several relays can append to one file and a
+    /// compliance review can read it while they run.
+    pub struct SqliteAuditLog {
+        conn: Mutex<Connection>,
+    }
+
+    fn storage_error(e: rusqlite::Error) -> CapError {
+        CapError::Storage(e.to_string())
+    }
+
+    impl SqliteAuditLog {
+        /// Open or create an audit log at the given path
+        pub fn open(path: &str) -> Result<Self> {
+            let conn = Connection::open(path).map_err(storage_error)?;
+            conn.execute_batch(
+                "PRAGMA journal_mode=WAL;
+                 PRAGMA synchronous=NORMAL;
+                 CREATE TABLE IF NOT EXISTS cap_audit (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     timestamp INTEGER NOT NULL,
+                     nonce TEXT,
+                     issuer TEXT,
+                     root_issuer TEXT,
+                     audience TEXT,
+                     scopes TEXT NOT NULL,
+                     chain_depth INTEGER NOT NULL,
+                     outcome TEXT NOT NULL,
+                     reason TEXT
+                 );
+                 CREATE INDEX IF NOT EXISTS idx_cap_audit_timestamp ON cap_audit(timestamp);
+                 CREATE INDEX IF NOT EXISTS idx_cap_audit_issuer ON cap_audit(issuer);
+                 CREATE INDEX IF NOT EXISTS idx_cap_audit_root ON cap_audit(root_issuer);",
+            )
+            .map_err(storage_error)?;
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        /// Create an in-memory log (for testing)
+        pub fn in_memory() -> Result<Self> {
+            Self::open(":memory:")
+        }
+    }
+
+    impl AuditLog for SqliteAuditLog {
+        fn record(&self, record: AuditRecord) -> Result<()> {
+            self.conn
+                .lock()
+                .unwrap()
+                .prepare_cached(
+                    "INSERT INTO cap_audit (timestamp, nonce, issuer, root_issuer, audience,
+                         scopes, chain_depth, outcome, reason)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .and_then(|mut stmt| {
+                    stmt.execute(params![
+                        record.timestamp as i64,
+                        record.nonce,
+                        record.issuer,
+                        record.root_issuer,
+                        record.audience,
+                        record.scopes.join("\n"),
+                        record.chain_depth as i64,
+                        record.outcome.as_str(),
+                        record.reason,
+                    ])
+                })
+                .map_err(storage_error)?;
+            Ok(())
+        }
+
+        fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+            let mut sql = String::from(
+                "SELECT timestamp, nonce, issuer, root_issuer, audience, scopes, chain_depth,
+                     outcome, reason
+                 FROM cap_audit WHERE 1=1",
+            );
+            let mut args: Vec<Value> = Vec::new();
+            if let Some(ref issuer) = query.issuer {
+                args.push(Value::Text(issuer.clone()));
+                sql.push_str(&format!(
+                    " AND (issuer = ?{0} OR root_issuer = ?{0})",
+                    args.len()
+                ));
+            }
+            if let Some(ref nonce) = query.nonce {
+                args.push(Value::Text(nonce.clone()));
+                sql.push_str(&format!(" AND nonce = ?{}", args.len()));
+            }
+            if let Some(outcome) = query.outcome {
+                args.push(Value::Text(outcome.as_str().to_string()));
+                sql.push_str(&format!(" AND outcome = ?{}", args.len()));
+            }
+            if let Some(since) = query.since {
+                args.push(Value::Integer(since as i64));
+                sql.push_str(&format!(" AND timestamp >= ?{}", args.len()));
+            }
+            if let Some(until) = query.until {
+                args.push(Value::Integer(until as i64));
+                sql.push_str(&format!(" AND timestamp < ?{}", args.len()));
+            }
+            args.push(Value::Integer(
+                query.limit.unwrap_or(DEFAULT_QUERY_LIMIT) as i64
+            ));
+            sql.push_str(&format!(" ORDER BY id DESC LIMIT ?{}", args.len()));
+
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(&sql).map_err(storage_error)?;
+            let rows = stmt
+                .query_map(params_from_iter(args), |row| {
+                    Ok((
+                        AuditRecord {
+                            timestamp: row.get::<_, i64>(0)? as u64,
+                            nonce: row.get(1)?,
+                            issuer: row.get(2)?,
+                            root_issuer: row.get(3)?,
+                            audience: row.get(4)?,
+                            scopes: row
+                                .get::<_, String>(5)?
+                                .split('\n')
+                                .filter(|s| !s.is_empty())
+                                .map(String::from)
+                                .collect(),
+                            chain_depth: row.get::<_, i64>(6)? as usize,
+                            outcome: AuditOutcome::Invalid,
+                            reason: row.get(8)?,
+                        },
+                        row.get::<_, String>(7)?,
+                    ))
+                })
+                .map_err(storage_error)?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                let (mut record, outcome) = row.map_err(storage_error)?;
+                record.outcome = AuditOutcome::parse(&outcome).ok_or_else(|| {
+                    CapError::Storage(format!("unknown audit outcome {}", outcome))
+                })?;
+                records.push(record);
+            }
+            Ok(records)
+        }
+
+        fn prune(&self, before: u64) -> Result<usize> {
+            self.conn
+                .lock()
+                .unwrap()
+                .execute(
+                    "DELETE FROM cap_audit WHERE timestamp < ?1",
+                    params![before as i64],
+                )
+                .map_err(storage_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(nonce: &str, issuer: &str, timestamp: u64, outcome: AuditOutcome) -> AuditRecord {
+        AuditRecord {
+            timestamp,
+            nonce: Some(nonce.to_string()),
+            issuer: Some(issuer.to_string()),
+            root_issuer: Some("root".to_string()),
+            audience: None,
+            scopes: vec!["read:/a/**".to_string(), "write:/a/b".to_string()],
+            chain_depth: 1,
+            outcome,
+            reason: None,
+        }
+    }
+
+    fn exercise(log: &dyn AuditLog) {
+        log.record(record("n1", "alice", 100, AuditOutcome::Valid))
+            .unwrap();
+        log.record(record("n2", "bob", 200, AuditOutcome::Expired))
+            .unwrap();
+        log.record(AuditRecord::undecodable("encoding error"))
+            .unwrap();
+
+        let all = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].reason.as_deref(), Some("encoding error"));
+        assert_eq!(all[2], record("n1", "alice", 100, AuditOutcome::Valid));
+
+        let query = |q: AuditQuery| {
+            log.query(&q)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.nonce)
+                .collect::<Vec<_>>()
+        };
+        let n = |s: &str| Some(s.to_string());
+        assert_eq!(
+            query(AuditQuery {
+                issuer: n("bob"),
+                ..Default::default()
+            }),
+            vec![n("n2")]
+        );
+        assert_eq!(
+            query(AuditQuery {
+                issuer: n("root"),
+                ..Default::default()
+            }),
+            vec![n("n2"), n("n1")]
+        );
+        assert_eq!(
+            query(AuditQuery {
+                outcome: Some(AuditOutcome::Valid),
+                ..Default::default()
+            }),
+            vec![n("n1")]
+        );
+        assert_eq!(
+            query(AuditQuery {
+                since: Some(150),
+                until: Some(300),
+                ..Default::default()
+            }),
+            vec![n("n2")]
+        );
+        assert_eq!(
+            query(AuditQuery {
+                limit: Some(1),
+                ..Default::default()
+            }),
+            vec![None]
+        );
+
+        assert_eq!(log.prune(150).unwrap(), 1);
+        assert_eq!(log.query(&AuditQuery::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_memory_log() {
+        exercise(&MemoryAuditLog::new());
+    }
+
+    #[test]
+    fn test_memory_log_capacity() {
+        let log = MemoryAuditLog::with_capacity(2);
+        for nonce in ["n1", "n2", "n3"] {
+            log.record(record(nonce, "alice", 100, AuditOutcome::Valid))
+                .unwrap();
+        }
+        let nonces: Vec<_> = log
+            .query(&AuditQuery::default())
+            .unwrap()
+            .into_iter()
+            .filter_map(|r| r.nonce)
+            .collect();
+        assert_eq!(nonces, vec!["n3", "n2"]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_log() {
+        exercise(&SqliteAuditLog::in_memory().unwrap());
+    }
+}
@@ -36,6 +36,18 @@ pub enum CapError {
     /// Key error
     #[error("key error: {0}")]
     KeyError(String),
+
+    /// Token nonce or an issuer in its chain has been revoked
+    #[error("token revoked: {0}")]
+    Revoked(String),
+
+    /// Use-limited token presented more often than it allows
+    #[error("token use limit reached: {0}")]
+    UsesExhausted(String),
+
+    /// Revocation store failure
+    #[error("storage error: {0}")]
+    Storage(String),
 }
 
 /// Result type for capability operations
@@ -32,11 +32,42 @@
 //! // Use with ValidatorChain
 //! // chain.add(validator);
 //! ```
+//!
+//! # Revocation
+//!
+//! Attach a [`RevocationStore`] to kill individual tokens (by nonce) or
+//! everything a compromised key issued or delegated (by issuer fingerprint)
+//! without rotating the root key. See [`revocation`].
+//!
+//! # Auditing
+//!
+//! Attach an [`AuditLog`] to record every token presented: issuer, chain
+//! root, audience, scopes, chain depth and outcome. See [`audit`].
+//!
+//! # Use Limits
+//!
+//! A root token can carry a signed `max_uses`, e.g. for guest links handed
+//! to an audience. Attach a [`UseCounter`] to count presentations
+//! server-side. See [`uses`].
 
+pub mod audit;
 pub mod error;
+pub mod revocation;
 pub mod token;
+pub mod uses;
 pub mod validator;
 
+#[cfg(feature = "sqlite")]
+pub use audit::SqliteAuditLog;
+pub use audit::{AuditLog, AuditOutcome, AuditQuery, AuditRecord, MemoryAuditLog};
 pub use error::{CapError, Result};
+#[cfg(feature = "sqlite")]
+pub use revocation::SqliteRevocationStore;
+pub use revocation::{
+    issuer_fingerprint, MemoryRevocationStore, Revocation, RevocationKind, RevocationStore,
+};
 pub use token::{CapabilityToken, ProofLink};
+#[cfg(feature = "sqlite")]
+pub use uses::SqliteUseCounter;
+pub use uses::{MemoryUseCounter, UseCounter};
 pub use validator::CapabilityValidator;
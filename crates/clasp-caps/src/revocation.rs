@@ -0,0 +1,327 @@
+//! Revocation lists for capability tokens
+//!
+//! Capability tokens are self-contained, so a leaked or compromised token
+//! stays valid until it expires. A [`RevocationStore`] lets an operator kill
+//! one early without rotating the root key:
+//!
+//! - by **nonce**: revokes one token
+//! - by **issuer fingerprint**: revokes every token signed by that key or
+//!   delegated through it, e.g. a compromised intermediate delegate
+//!
+//! [`CapabilityValidator::with_revocations`](crate::CapabilityValidator::with_revocations)
+//! consults the store on every validation. Share one store between
+//! validators with an `Arc`, or point several relays at the same
+//! [`SqliteRevocationStore`] file (feature `sqlite`).
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Result;
+
+/// What a revocation entry matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RevocationKind {
+    /// A token's nonce
+    Nonce,
+    /// An issuer key fingerprint (see [`issuer_fingerprint`])
+    Issuer,
+}
+
+impl RevocationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevocationKind::Nonce => "nonce",
+            RevocationKind::Issuer => "issuer",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "nonce" => Some(RevocationKind::Nonce),
+            "issuer" => Some(RevocationKind::Issuer),
+            _ => None,
+        }
+    }
+}
+
+/// A revoked nonce or issuer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revocation {
+    pub kind: RevocationKind,
+    /// Nonce, or hex issuer fingerprint
+    pub value: String,
+    /// When it was revoked (Unix timestamp, seconds)
+    pub revoked_at: u64,
+    /// Free-form reason for audit logs
+    pub reason: Option<String>,
+}
+
+impl Revocation {
+    /// Revoke a single token by nonce
+    pub fn nonce(nonce: impl Into<String>) -> Self {
+        Self::new(RevocationKind::Nonce, nonce.into())
+    }
+
+    /// Revoke every token issued or delegated by a public key
+    pub fn issuer(public_key: &[u8]) -> Self {
+        Self::new(RevocationKind::Issuer, issuer_fingerprint(public_key))
+    }
+
+    /// Attach a reason
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn new(kind: RevocationKind, value: String) -> Self {
+        Self {
+            kind,
+            value,
+            revoked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            reason: None,
+        }
+    }
+}
+
+/// Fingerprint of an issuer public key: its lowercase hex encoding
+pub fn issuer_fingerprint(public_key: &[u8]) -> String {
+    public_key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Storage for revoked nonces and issuers
+///
+/// Called synchronously on every capability token validation, so lookups
+/// should be cheap.
+pub trait RevocationStore: Send + Sync {
+    /// Add (or replace) a revocation
+    fn revoke(&self, revocation: Revocation) -> Result<()>;
+
+    /// Lift a revocation; returns whether it existed
+    fn unrevoke(&self, kind: RevocationKind, value: &str) -> Result<bool>;
+
+    /// Whether a nonce or issuer fingerprint is revoked
+    fn is_revoked(&self, kind: RevocationKind, value: &str) -> Result<bool>;
+
+    /// Every revocation, oldest first
+    fn list(&self) -> Result<Vec<Revocation>>;
+}
+
+/// In-memory revocation list
+#[derive(Debug, Default)]
+pub struct MemoryRevocationStore {
+    entries: RwLock<HashMap<(RevocationKind, String), Revocation>>,
+}
+
+impl MemoryRevocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RevocationStore for MemoryRevocationStore {
+    fn revoke(&self, revocation: Revocation) -> Result<()> {
+        self.entries
+            .write()
+            .unwrap()
+            .insert((revocation.kind, revocation.value.clone()), revocation);
+        Ok(())
+    }
+
+    fn unrevoke(&self, kind: RevocationKind, value: &str) -> Result<bool> {
+        Ok(self
+            .entries
+            .write()
+            .unwrap()
+            .remove(&(kind, value.to_string()))
+            .is_some())
+    }
+
+    fn is_revoked(&self, kind: RevocationKind, value: &str) -> Result<bool> {
+        Ok(self
+            .entries
+            .read()
+            .unwrap()
+            .contains_key(&(kind, value.to_string())))
+    }
+
+    fn list(&self) -> Result<Vec<Revocation>> {
+        let mut list: Vec<_> = self.entries.read().unwrap().values().cloned().collect();
+        list.sort_by_key(|r| r.revoked_at);
+        Ok(list)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteRevocationStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::sync::Mutex;
+
+    use super::{Revocation, RevocationKind, RevocationStore};
+    use crate::error::{CapError, Result};
+
+    /// SQLite-backed revocation list
+    ///
+    /// Uses WAL mode, so several processes can share one file: each lookup
+    /// reads the table, and a revocation written by one relay is seen by the
+    /// others on their next validation.
+    pub struct SqliteRevocationStore {
+        conn: Mutex<Connection>,
+    }
+
+    fn storage_error(e: rusqlite::Error) -> CapError {
+        CapError::Storage(e.to_string())
+    }
+
+    impl SqliteRevocationStore {
+        /// Open or create a revocation list at the given path
+        pub fn open(path: &str) -> Result<Self> {
+            let conn = Connection::open(path).map_err(storage_error)?;
+            conn.execute_batch(
+                "PRAGMA journal_mode=WAL;
+                 PRAGMA synchronous=NORMAL;
+                 CREATE TABLE IF NOT EXISTS revocations (
+                     kind TEXT NOT NULL,
+                     value TEXT NOT NULL,
+                     revoked_at INTEGER NOT NULL,
+                     reason TEXT,
+                     PRIMARY KEY (kind, value)
+                 );",
+            )
+            .map_err(storage_error)?;
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        /// Create an in-memory store (for testing)
+        pub fn in_memory() -> Result<Self> {
+            Self::open(":memory:")
+        }
+    }
+
+    impl RevocationStore for SqliteRevocationStore {
+        fn revoke(&self, revocation: Revocation) -> Result<()> {
+            self.conn
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR REPLACE INTO revocations (kind, value, revoked_at, reason)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        revocation.kind.as_str(),
+                        revocation.value,
+                        revocation.revoked_at as i64,
+                        revocation.reason
+                    ],
+                )
+                .map_err(storage_error)?;
+            Ok(())
+        }
+
+        fn unrevoke(&self, kind: RevocationKind, value: &str) -> Result<bool> {
+            let removed = self
+                .conn
+                .lock()
+                .unwrap()
+                .execute(
+                    "DELETE FROM revocations WHERE kind = ?1 AND value = ?2",
+                    params![kind.as_str(), value],
+                )
+                .map_err(storage_error)?;
+            Ok(removed > 0)
+        }
+
+        fn is_revoked(&self, kind: RevocationKind, value: &str) -> Result<bool> {
+            let conn = self.conn.lock().unwrap();
+            let found: Option<i64> = conn
+                .prepare_cached("SELECT 1 FROM revocations WHERE kind = ?1 AND value = ?2")
+                .and_then(|mut stmt| {
+                    stmt.query_row(params![kind.as_str(), value], |row| row.get(0))
+                        .optional()
+                })
+                .map_err(storage_error)?;
+            Ok(found.is_some())
+        }
+
+        fn list(&self) -> Result<Vec<Revocation>> {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT kind, value, revoked_at, reason FROM revocations
+                     ORDER BY revoked_at",
+                )
+                .map_err(storage_error)?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })
+                .map_err(storage_error)?;
+
+            let mut list = Vec::new();
+            for row in rows {
+                let (kind, value, revoked_at, reason) = row.map_err(storage_error)?;
+                let kind = RevocationKind::parse(&kind).ok_or_else(|| {
+                    CapError::Storage(format!("unknown revocation kind {}", kind))
+                })?;
+                list.push(Revocation {
+                    kind,
+                    value,
+                    revoked_at: revoked_at as u64,
+                    reason,
+                });
+            }
+            Ok(list)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(store: &dyn RevocationStore) {
+        store
+            .revoke(Revocation::nonce("n1").with_reason("leaked"))
+            .unwrap();
+        store.revoke(Revocation::issuer(&[0xab; 32])).unwrap();
+
+        assert!(store.is_revoked(RevocationKind::Nonce, "n1").unwrap());
+        assert!(!store.is_revoked(RevocationKind::Issuer, "n1").unwrap());
+        assert!(store
+            .is_revoked(RevocationKind::Issuer, &"ab".repeat(32))
+            .unwrap());
+
+        let list = store.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list
+            .iter()
+            .any(|r| r.value == "n1" && r.reason.as_deref() == Some("leaked")));
+
+        assert!(store.unrevoke(RevocationKind::Nonce, "n1").unwrap());
+        assert!(!store.unrevoke(RevocationKind::Nonce, "n1").unwrap());
+        assert!(!store.is_revoked(RevocationKind::Nonce, "n1").unwrap());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        exercise(&MemoryRevocationStore::new());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        exercise(&SqliteRevocationStore::in_memory().unwrap());
+    }
+}
@@ -30,6 +30,10 @@ pub struct CapabilityToken {
     pub expires_at: u64,
     /// Unique nonce to prevent replay
     pub nonce: String,
+    /// Times the token may be presented, counted server-side by a
+    /// [`UseCounter`](crate::UseCounter) (None = unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u64>,
     /// Proof chain: signatures of parent tokens in the delegation chain
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub proofs: Vec<ProofLink>,
@@ -60,6 +64,31 @@ impl CapabilityToken {
         scopes: Vec<String>,
         expires_at: u64,
         audience: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        Self::issue(signing_key, scopes, expires_at, audience, None)
+    }
+
+    /// Create and sign a root token that may be presented at most
+    /// `max_uses` times.
+    ///
+    /// Use-limited tokens cannot be delegated, since children would be
+    /// counted separately.
+    pub fn create_root_with_uses(
+        signing_key: &SigningKey,
+        scopes: Vec<String>,
+        expires_at: u64,
+        audience: Option<Vec<u8>>,
+        max_uses: u64,
+    ) -> Result<Self> {
+        Self::issue(signing_key, scopes, expires_at, audience, Some(max_uses))
+    }
+
+    fn issue(
+        signing_key: &SigningKey,
+        scopes: Vec<String>,
+        expires_at: u64,
+        audience: Option<Vec<u8>>,
+        max_uses: Option<u64>,
     ) -> Result<Self> {
         let issuer = signing_key.verifying_key().to_bytes().to_vec();
         let nonce = uuid::Uuid::new_v4().to_string();
@@ -71,6 +100,7 @@ impl CapabilityToken {
             scopes,
             expires_at,
             nonce,
+            max_uses,
             proofs: vec![],
             signature: vec![],
         };
@@ -93,6 +123,12 @@ impl CapabilityToken {
         expires_at: u64,
         audience: Option<Vec<u8>>,
     ) -> Result<Self> {
+        if self.max_uses.is_some() {
+            return Err(CapError::AttenuationViolation(
+                "use-limited tokens cannot be delegated".to_string(),
+            ));
+        }
+
         // Verify attenuation: child scopes must be subset of parent scopes.
         // See pentest CAP-02: Scope Attenuation Bypass, CAP-04: Proof Chain Manipulation
         for child_scope in &child_scopes {
@@ -125,6 +161,7 @@ impl CapabilityToken {
             scopes: child_scopes,
             expires_at: child_expires,
             nonce,
+            max_uses: None,
             proofs,
             signature: vec![],
         };
@@ -244,6 +281,7 @@ impl CapabilityToken {
             scopes: &self.scopes,
             expires_at: self.expires_at,
             nonce: &self.nonce,
+            max_uses: self.max_uses,
             proofs: &self.proofs,
         };
 
@@ -260,6 +298,8 @@ struct SignableToken<'a> {
     scopes: &'a [String],
     expires_at: u64,
     nonce: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_uses: Option<u64>,
     proofs: &'a [ProofLink],
 }
 
@@ -404,6 +444,38 @@ mod tests {
         assert_eq!(decoded.issuer, token.issuer);
     }
 
+    #[test]
+    fn test_use_limited_token() {
+        let key = test_key();
+        let token = CapabilityToken::create_root_with_uses(
+            &key,
+            vec!["write:/votes/**".to_string()],
+            future_timestamp(),
+            None,
+            500,
+        )
+        .unwrap();
+
+        let decoded = CapabilityToken::decode(&token.encode().unwrap()).unwrap();
+        assert_eq!(decoded.max_uses, Some(500));
+        assert!(decoded.verify_signature().is_ok());
+
+        // The limit is signed
+        let mut raised = decoded.clone();
+        raised.max_uses = None;
+        assert!(raised.verify_signature().is_err());
+
+        let err = token
+            .delegate(
+                &SigningKey::from_bytes(&[2u8; 32]),
+                vec!["write:/votes/a".to_string()],
+                future_timestamp(),
+                None,
+            )
+            .unwrap_err();
+        assert!(matches!(err, CapError::AttenuationViolation(_)));
+    }
+
     #[test]
     fn test_delegation() {
         let root_key = test_key();
@@ -0,0 +1,184 @@
+//! Server-side use counts for use-limited capability tokens
+//!
+//! A token created with
+//! [`CapabilityToken::create_root_with_uses`](crate::CapabilityToken::create_root_with_uses)
+//! carries a signed `max_uses`. The token itself can't count, so
+//! [`CapabilityValidator::with_use_counter`](crate::CapabilityValidator::with_use_counter)
+//! records every accepted presentation in a [`UseCounter`] keyed by nonce
+//! and rejects the token once the count passes the limit. Without a counter,
+//! use-limited tokens are rejected outright.
+//!
+//! Counts only need to live as long as the token, so stores keep each
+//! token's expiry and [`prune`](UseCounter::prune) drops expired ones. Point
+//! several relays at the same [`SqliteUseCounter`] file (feature `sqlite`)
+//! to share one budget.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::error::Result;
+
+/// Storage for per-token use counts
+///
+/// Called synchronously on every validation of a use-limited token.
+pub trait UseCounter: Send + Sync {
+    /// Count one use of the token with `nonce` and return its total so far.
+    /// `expires_at` is the token's expiry, for pruning.
+    fn record_use(&self, nonce: &str, expires_at: u64) -> Result<u64>;
+
+    /// Uses counted so far (0 for an unknown nonce)
+    fn uses(&self, nonce: &str) -> Result<u64>;
+
+    /// Forget tokens that expired before `now` (Unix timestamp, seconds).
+    /// Returns how many were dropped.
+    fn prune(&self, now: u64) -> Result<usize>;
+}
+
+/// In-memory use counter
+#[derive(Debug, Default)]
+pub struct MemoryUseCounter {
+    /// nonce -> (uses, expires_at)
+    counts: RwLock<HashMap<String, (u64, u64)>>,
+}
+
+impl MemoryUseCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UseCounter for MemoryUseCounter {
+    fn record_use(&self, nonce: &str, expires_at: u64) -> Result<u64> {
+        let mut counts = self.counts.write().unwrap();
+        let entry = counts.entry(nonce.to_string()).or_insert((0, expires_at));
+        entry.0 += 1;
+        Ok(entry.0)
+    }
+
+    fn uses(&self, nonce: &str) -> Result<u64> {
+        Ok(self
+            .counts
+            .read()
+            .unwrap()
+            .get(nonce)
+            .map_or(0, |(uses, _)| *uses))
+    }
+
+    fn prune(&self, now: u64) -> Result<usize> {
+        let mut counts = self.counts.write().unwrap();
+        let before = counts.len();
+        counts.retain(|_, (_, expires_at)| *expires_at >= now);
+        Ok(before - counts.len())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteUseCounter;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::sync::Mutex;
+
+    use super::UseCounter;
+    use crate::error::{CapError, Result};
+
+    /// SQLite-backed use counter
+    ///
+    /// Each use is a single upsert, so several processes sharing one file
+    /// (WAL mode) draw from the same budget.
+    pub struct SqliteUseCounter {
+        conn: Mutex<Connection>,
+    }
+
+    fn storage_error(e: rusqlite::Error) -> CapError {
+        CapError::Storage(e.to_string())
+    }
+
+    impl SqliteUseCounter {
+        /// Open or create a use counter at the given path
+        pub fn open(path: &str) -> Result<Self> {
+            let conn = Connection::open(path).map_err(storage_error)?;
+            conn.execute_batch(
+                "PRAGMA journal_mode=WAL;
+                 PRAGMA synchronous=NORMAL;
+                 CREATE TABLE IF NOT EXISTS cap_uses (
+                     nonce TEXT PRIMARY KEY,
+                     uses INTEGER NOT NULL,
+                     expires_at INTEGER NOT NULL
+                 );",
+            )
+            .map_err(storage_error)?;
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        /// Create an in-memory counter (for testing)
+        pub fn in_memory() -> Result<Self> {
+            Self::open(":memory:")
+        }
+    }
+
+    impl UseCounter for SqliteUseCounter {
+        fn record_use(&self, nonce: &str, expires_at: u64) -> Result<u64> {
+            let conn = self.conn.lock().unwrap();
+            let uses: i64 = conn
+                .prepare_cached(
+                    "INSERT INTO cap_uses (nonce, uses, expires_at) VALUES (?1, 1, ?2)
+                     ON CONFLICT(nonce) DO UPDATE SET uses = uses + 1
+                     RETURNING uses",
+                )
+                .and_then(|mut stmt| {
+                    stmt.query_row(params![nonce, expires_at as i64], |row| row.get(0))
+                })
+                .map_err(storage_error)?;
+            Ok(uses as u64)
+        }
+
+        fn uses(&self, nonce: &str) -> Result<u64> {
+            let conn = self.conn.lock().unwrap();
+            let uses: Option<i64> = conn
+                .prepare_cached("SELECT uses FROM cap_uses WHERE nonce = ?1")
+                .and_then(|mut stmt| stmt.query_row([nonce], |row| row.get(0)).optional())
+                .map_err(storage_error)?;
+            Ok(uses.unwrap_or(0) as u64)
+        }
+
+        fn prune(&self, now: u64) -> Result<usize> {
+            self.conn
+                .lock()
+                .unwrap()
+                .execute("DELETE FROM cap_uses WHERE expires_at < ?1", [now as i64])
+                .map_err(storage_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(counter: &dyn UseCounter) {
+        assert_eq!(counter.uses("n1").unwrap(), 0);
+        assert_eq!(counter.record_use("n1", 100).unwrap(), 1);
+        assert_eq!(counter.record_use("n1", 100).unwrap(), 2);
+        assert_eq!(counter.record_use("n2", 300).unwrap(), 1);
+        assert_eq!(counter.uses("n1").unwrap(), 2);
+
+        assert_eq!(counter.prune(200).unwrap(), 1);
+        assert_eq!(counter.uses("n1").unwrap(), 0);
+        assert_eq!(counter.uses("n2").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_memory_counter() {
+        exercise(&MemoryUseCounter::new());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_counter() {
+        exercise(&SqliteUseCounter::in_memory().unwrap());
+    }
+}
@@ -3,12 +3,19 @@
 //! Implements `TokenValidator` so capability tokens can be validated
 //! alongside existing CPSK tokens via `ValidatorChain`.
 
-use clasp_core::security::{Action, Scope, TokenInfo, TokenValidator, ValidationResult};
+use clasp_core::security::{
+    Action, Scope, TokenInfo, TokenValidator, ValidationResult, AUDIENCE_METADATA,
+};
+use ed25519_dalek::VerifyingKey;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, UNIX_EPOCH};
 
+use crate::audit::{AuditLog, AuditOutcome, AuditRecord};
 use crate::error::CapError;
+use crate::revocation::{issuer_fingerprint, RevocationKind, RevocationStore};
 use crate::token::{CapabilityToken, TOKEN_PREFIX};
+use crate::uses::UseCounter;
 
 /// Validates CLASP capability tokens.
 ///
@@ -19,6 +26,12 @@ pub struct CapabilityValidator {
     trust_anchors: Vec<Vec<u8>>,
     /// Maximum delegation chain depth
     max_depth: usize,
+    /// Revoked nonces and issuers
+    revocations: Option<Arc<dyn RevocationStore>>,
+    /// Where validations are recorded
+    audit: Option<Arc<dyn AuditLog>>,
+    /// Use counts for use-limited tokens
+    uses: Option<Arc<dyn UseCounter>>,
 }
 
 impl CapabilityValidator {
@@ -31,19 +44,112 @@ impl CapabilityValidator {
         Self {
             trust_anchors,
             max_depth,
+            revocations: None,
+            audit: None,
+            uses: None,
         }
     }
 
+    /// Reject tokens whose nonce, issuer or any delegating issuer is revoked
+    /// in `store`.
+    pub fn with_revocations(mut self, store: Arc<dyn RevocationStore>) -> Self {
+        self.revocations = Some(store);
+        self
+    }
+
+    /// Record every `cap_` token validation, accepted or not, in `log`.
+    pub fn with_audit(mut self, log: Arc<dyn AuditLog>) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Count presentations of use-limited tokens in `counter`. Without a
+    /// counter, tokens with `max_uses` are rejected.
+    pub fn with_use_counter(mut self, counter: Arc<dyn UseCounter>) -> Self {
+        self.uses = Some(counter);
+        self
+    }
+
+    /// Count one use of a use-limited token. Called last, so rejected
+    /// presentations don't spend the budget.
+    fn check_uses(&self, token: &CapabilityToken) -> std::result::Result<(), CapError> {
+        let Some(max_uses) = token.max_uses else {
+            return Ok(());
+        };
+        let Some(counter) = &self.uses else {
+            return Err(CapError::UsesExhausted(
+                "no use counter configured".to_string(),
+            ));
+        };
+        let used = counter.record_use(&token.nonce, token.expires_at)?;
+        if used > max_uses {
+            return Err(CapError::UsesExhausted(format!(
+                "{} of {} uses",
+                used, max_uses
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the token nonce and every issuer in the chain against the
+    /// revocation list. Store failures reject the token (fail closed).
+    fn check_revoked(&self, token: &CapabilityToken) -> std::result::Result<(), CapError> {
+        let Some(store) = &self.revocations else {
+            return Ok(());
+        };
+        if store.is_revoked(RevocationKind::Nonce, &token.nonce)? {
+            return Err(CapError::Revoked(format!("nonce {}", token.nonce)));
+        }
+        let issuers = token
+            .proofs
+            .iter()
+            .map(|proof| &proof.issuer)
+            .chain(std::iter::once(&token.issuer));
+        for issuer in issuers {
+            let fingerprint = issuer_fingerprint(issuer);
+            if store.is_revoked(RevocationKind::Issuer, &fingerprint)? {
+                return Err(CapError::Revoked(format!("issuer {}", fingerprint)));
+            }
+        }
+        Ok(())
+    }
+
     /// Add a trust anchor (root issuer public key)
     pub fn add_trust_anchor(&mut self, public_key: Vec<u8>) {
         self.trust_anchors.push(public_key);
     }
 
-    /// Validate a capability token and return the result
+    /// Decode and validate a capability token, recording the result in the
+    /// audit log
     fn validate_token(&self, token_str: &str) -> std::result::Result<CapabilityToken, CapError> {
-        // Decode the token
-        let token = CapabilityToken::decode(token_str)?;
+        let token = match CapabilityToken::decode(token_str) {
+            Ok(token) => token,
+            Err(e) => {
+                self.audit(|| AuditRecord::undecodable(e.to_string()));
+                return Err(e);
+            }
+        };
+        let result = self.check(&token);
+        self.audit(|| match &result {
+            Ok(()) => AuditRecord::for_token(&token, AuditOutcome::Valid),
+            Err(CapError::Expired) => AuditRecord::for_token(&token, AuditOutcome::Expired),
+            Err(e) => {
+                AuditRecord::for_token(&token, AuditOutcome::Invalid).with_reason(e.to_string())
+            }
+        });
+        result.map(|()| token)
+    }
 
+    /// Append to the audit log, if any. A failing log doesn't affect the
+    /// validation.
+    fn audit(&self, record: impl FnOnce() -> AuditRecord) {
+        if let Some(log) = &self.audit {
+            let _ = log.record(record());
+        }
+    }
+
+    /// Validate a decoded capability token
+    fn check(&self, token: &CapabilityToken) -> std::result::Result<(), CapError> {
         // Check expiration
         if token.is_expired() {
             return Err(CapError::Expired);
@@ -60,6 +166,18 @@ impl CapabilityValidator {
         // Verify signature
         token.verify_signature()?;
 
+        // An audience must be a usable Ed25519 key, or no client could ever
+        // answer the router's proof of possession challenge
+        if let Some(ref audience) = token.audience {
+            let key = <[u8; 32]>::try_from(audience.as_slice())
+                .map_err(|_| CapError::KeyError("invalid audience key length".to_string()))?;
+            VerifyingKey::from_bytes(&key)
+                .map_err(|e| CapError::KeyError(format!("invalid audience key: {}", e)))?;
+        }
+
+        // Check the revocation list
+        self.check_revoked(token)?;
+
         // Verify the delegation chain root leads to a trust anchor
         let root_issuer = if token.proofs.is_empty() {
             &token.issuer
@@ -72,7 +190,7 @@ impl CapabilityValidator {
             .iter()
             .any(|anchor| anchor == root_issuer)
         {
-            return Err(CapError::UntrustedIssuer(hex::encode(root_issuer)));
+            return Err(CapError::UntrustedIssuer(issuer_fingerprint(root_issuer)));
         }
 
         // Verify scope attenuation through the chain
@@ -103,7 +221,10 @@ impl CapabilityValidator {
             }
         }
 
-        Ok(token)
+        // Count the use last, once everything else has passed
+        self.check_uses(token)?;
+
+        Ok(())
     }
 }
 
@@ -133,13 +254,6 @@ fn scope_within_parent(scope: &str, parent_scopes: &[String]) -> bool {
     false
 }
 
-/// Hex encoding helper (minimal, avoids a dependency)
-mod hex {
-    pub fn encode(bytes: &[u8]) -> String {
-        bytes.iter().map(|b| format!("{:02x}", b)).collect()
-    }
-}
-
 impl TokenValidator for CapabilityValidator {
     fn validate(&self, token: &str) -> ValidationResult {
         // Only handle cap_ tokens
@@ -176,6 +290,13 @@ impl TokenValidator for CapabilityValidator {
                     "chain_depth".to_string(),
                     cap_token.chain_depth().to_string(),
                 );
+                // Audience-bound tokens need proof of possession at HELLO
+                if let Some(ref audience) = cap_token.audience {
+                    metadata.insert(
+                        AUDIENCE_METADATA.to_string(),
+                        audience.iter().map(|b| format!("{:02x}", b)).collect(),
+                    );
+                }
 
                 let info = TokenInfo {
                     token_id: cap_token.nonce.clone(),
@@ -183,6 +304,7 @@ impl TokenValidator for CapabilityValidator {
                     scopes,
                     expires_at,
                     metadata,
+                    entity: None,
                 };
 
                 ValidationResult::Valid(info)
@@ -249,6 +371,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_audience_in_metadata() {
+        let validator = make_validator();
+        let key = root_key();
+        let device = SigningKey::from_bytes(&[9u8; 32]);
+        let audience = device.verifying_key().to_bytes().to_vec();
+
+        for (audience, expected) in [(None, None), (Some(audience.clone()), Some(hex(&audience)))] {
+            let token = CapabilityToken::create_root(
+                &key,
+                vec!["read:/**".to_string()],
+                future_timestamp(),
+                audience,
+            )
+            .unwrap();
+            match validator.validate(&token.encode().unwrap()) {
+                ValidationResult::Valid(info) => {
+                    assert_eq!(info.metadata.get(AUDIENCE_METADATA), expected.as_ref());
+                }
+                other => panic!("expected Valid, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reject_malformed_audience() {
+        let validator = make_validator();
+        let key = root_key();
+
+        for audience in [vec![9u8; 16], vec![9u8; 33]] {
+            let token = CapabilityToken::create_root(
+                &key,
+                vec!["read:/**".to_string()],
+                future_timestamp(),
+                Some(audience),
+            )
+            .unwrap();
+            match validator.validate(&token.encode().unwrap()) {
+                ValidationResult::Invalid(msg) => assert!(msg.contains("audience"), "{}", msg),
+                other => panic!("expected Invalid, got {:?}", other),
+            }
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
     #[test]
     fn test_validate_delegated_token() {
         let validator = make_validator();
@@ -494,4 +664,171 @@ mod tests {
             ValidationResult::Invalid(_)
         ));
     }
+
+    #[test]
+    fn test_revoked_nonce_and_delegate() {
+        use crate::revocation::{MemoryRevocationStore, Revocation};
+
+        let store = Arc::new(MemoryRevocationStore::new());
+        let validator = make_validator().with_revocations(store.clone());
+        let root_key = root_key();
+        let delegate_key = SigningKey::from_bytes(&[2u8; 32]);
+        let leaf_key = SigningKey::from_bytes(&[3u8; 32]);
+
+        let root = CapabilityToken::create_root(
+            &root_key,
+            vec!["admin:/**".to_string()],
+            future_timestamp(),
+            None,
+        )
+        .unwrap();
+        let delegated = root
+            .delegate(
+                &delegate_key,
+                vec!["write:/lights/**".to_string()],
+                future_timestamp(),
+                None,
+            )
+            .unwrap();
+        let leaf = delegated
+            .delegate(
+                &leaf_key,
+                vec!["read:/lights/**".to_string()],
+                future_timestamp(),
+                None,
+            )
+            .unwrap();
+        let root_encoded = root.encode().unwrap();
+        let leaf_encoded = leaf.encode().unwrap();
+        assert!(matches!(
+            validator.validate(&leaf_encoded),
+            ValidationResult::Valid(_)
+        ));
+
+        // Revoking one nonce kills only that token
+        store.revoke(Revocation::nonce(root.nonce.clone())).unwrap();
+        match validator.validate(&root_encoded) {
+            ValidationResult::Invalid(msg) => assert!(msg.contains("revoked"), "{}", msg),
+            other => panic!("expected Invalid, got {:?}", other),
+        }
+        assert!(matches!(
+            validator.validate(&leaf_encoded),
+            ValidationResult::Valid(_)
+        ));
+
+        // Revoking the compromised delegate kills everything below it
+        let delegate_pub = delegate_key.verifying_key().to_bytes();
+        store.revoke(Revocation::issuer(&delegate_pub)).unwrap();
+        match validator.validate(&leaf_encoded) {
+            ValidationResult::Invalid(msg) => assert!(msg.contains("revoked"), "{}", msg),
+            other => panic!("expected Invalid, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_audit_records_every_validation() {
+        use crate::audit::{AuditQuery, MemoryAuditLog};
+
+        let log = Arc::new(MemoryAuditLog::new());
+        let validator = make_validator().with_audit(log.clone());
+        let root_key = root_key();
+        let delegate_key = SigningKey::from_bytes(&[2u8; 32]);
+
+        let root = CapabilityToken::create_root(
+            &root_key,
+            vec!["admin:/**".to_string()],
+            future_timestamp(),
+            None,
+        )
+        .unwrap();
+        let delegated = root
+            .delegate(
+                &delegate_key,
+                vec!["write:/lights/**".to_string()],
+                future_timestamp(),
+                None,
+            )
+            .unwrap();
+        assert!(matches!(
+            validator.validate(&delegated.encode().unwrap()),
+            ValidationResult::Valid(_)
+        ));
+        let untrusted = CapabilityToken::create_root(
+            &delegate_key,
+            vec!["admin:/**".to_string()],
+            future_timestamp(),
+            None,
+        )
+        .unwrap();
+        validator.validate(&untrusted.encode().unwrap());
+        validator.validate("cap_!!!");
+        validator.validate("cpsk_not_mine");
+
+        let records = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].outcome, AuditOutcome::Invalid);
+        assert!(records[0].nonce.is_none());
+        assert_eq!(records[1].outcome, AuditOutcome::Invalid);
+        assert!(records[1]
+            .reason
+            .as_deref()
+            .unwrap()
+            .contains("untrusted issuer"));
+
+        let delegate_fp = issuer_fingerprint(&delegate_key.verifying_key().to_bytes());
+        let root_fp = issuer_fingerprint(&root_key.verifying_key().to_bytes());
+        let valid = &records[2];
+        assert_eq!(valid.outcome, AuditOutcome::Valid);
+        assert_eq!(valid.nonce.as_deref(), Some(delegated.nonce.as_str()));
+        assert_eq!(valid.issuer.as_deref(), Some(delegate_fp.as_str()));
+        assert_eq!(valid.root_issuer.as_deref(), Some(root_fp.as_str()));
+        assert_eq!(valid.scopes, vec!["write:/lights/**".to_string()]);
+        assert_eq!(valid.chain_depth, 1);
+    }
+
+    #[test]
+    fn test_use_limited_token() {
+        use crate::uses::MemoryUseCounter;
+
+        let token = CapabilityToken::create_root_with_uses(
+            &root_key(),
+            vec!["write:/votes/**".to_string()],
+            future_timestamp(),
+            None,
+            2,
+        )
+        .unwrap()
+        .encode()
+        .unwrap();
+
+        // Fails closed without a counter
+        match make_validator().validate(&token) {
+            ValidationResult::Invalid(msg) => assert!(msg.contains("use limit"), "{}", msg),
+            other => panic!("expected Invalid, got {:?}", other),
+        }
+
+        let counter = Arc::new(MemoryUseCounter::new());
+        let validator = make_validator().with_use_counter(counter.clone());
+        for _ in 0..2 {
+            assert!(matches!(
+                validator.validate(&token),
+                ValidationResult::Valid(_)
+            ));
+        }
+        match validator.validate(&token) {
+            ValidationResult::Invalid(msg) => assert!(msg.contains("3 of 2"), "{}", msg),
+            other => panic!("expected Invalid, got {:?}", other),
+        }
+
+        // Ordinary tokens are not counted
+        let unlimited = CapabilityToken::create_root(
+            &root_key(),
+            vec!["read:/**".to_string()],
+            future_timestamp(),
+            None,
+        )
+        .unwrap();
+        validator.validate(&unlimited.encode().unwrap());
+        assert_eq!(counter.uses(&unlimited.nonce).unwrap(), 0);
+    }
 }
@@ -0,0 +1,1006 @@
+//! Dissect subcommand: decode CLASP frames from pcap captures and generate a
+//! Wireshark Lua dissector.
+//!
+//! Reads classic pcap files (`tcpdump -w`, Wireshark "pcap" format) or a pcap
+//! stream on stdin, so live traffic can be piped in:
+//!
+//! ```text
+//! tcpdump -i eth0 -U -w - port 7330 | clasp dissect -
+//! ```
+//!
+//! Understood encapsulations:
+//! - WebSocket over TCP (HTTP upgrade is skipped, client frames are unmasked)
+//! - raw TCP transport (4-byte length prefix)
+//! - UDP datagrams carrying CLASP frames
+//! - router wire tap captures (link type USER0)
+//!
+//! QUIC payloads are encrypted and cannot be decoded from a capture; they are
+//! counted and reported in the summary.
+
+use anyhow::{bail, Context, Result};
+use clasp_core::codec::{self, msg, sig, val};
+use clasp_core::{Frame, Message, MAGIC_BYTE};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_USER0: u32 = 147;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// Decode a pcap file (or stdin for `-`) and print every CLASP message found
+pub fn handle_dissect(input: &Path, ports: &[u16], json: bool) -> Result<()> {
+    let reader: Box<dyn Read> = if input == Path::new("-") {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(
+            std::fs::File::open(input)
+                .with_context(|| format!("Failed to open capture: {}", input.display()))?,
+        )
+    };
+    let mut pcap = PcapReader::new(BufReader::new(reader))?;
+    let mut dissector = Dissector::new(ports);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    while let Some(packet) = pcap.next_packet()? {
+        for record in dissector.packet(pcap.linktype, &packet) {
+            print_record(&mut out, &record, json)?;
+        }
+    }
+
+    let stats = &dissector.stats;
+    eprintln!(
+        "{} {} packets, {} CLASP messages, {} undecodable frames",
+        "Done:".green().bold(),
+        stats.packets,
+        stats.messages,
+        stats.errors,
+    );
+    if stats.quic_packets > 0 {
+        eprintln!(
+            "  {} QUIC packets skipped: payloads are encrypted. Use the router wire tap \
+             (/clasp/admin/tap/...) to capture decoded frames.",
+            stats.quic_packets
+        );
+    }
+    if stats.gaps > 0 {
+        eprintln!(
+            "  {} TCP gaps: some segments were missing from the capture",
+            stats.gaps
+        );
+    }
+    Ok(())
+}
+
+/// Write the generated Lua dissector to `out` (or stdout for `-`)
+pub fn handle_lua(out: &Path) -> Result<()> {
+    let script = lua_dissector();
+    if out == Path::new("-") {
+        print!("{}", script);
+    } else {
+        std::fs::write(out, script)
+            .with_context(|| format!("Failed to write dissector: {}", out.display()))?;
+        eprintln!(
+            "{} Lua dissector written to: {}",
+            "OK".green().bold(),
+            out.display()
+        );
+        eprintln!("  Copy it to your Wireshark plugins directory or load it with: wireshark -X lua_script:{}", out.display());
+    }
+    Ok(())
+}
+
+fn print_record(out: &mut impl Write, record: &Record, json: bool) -> Result<()> {
+    if json {
+        let line = serde_json::json!({
+            "timestamp": record.timestamp,
+            "src": record.src,
+            "dst": record.dst,
+            "transport": record.transport,
+            "bytes": record.bytes,
+            "message": record.message,
+        });
+        writeln!(out, "{}", line)?;
+        return Ok(());
+    }
+
+    let secs = record.timestamp / 1_000_000;
+    let time = format!(
+        "{:02}:{:02}:{:02}.{:06}",
+        (secs / 3600) % 24,
+        (secs / 60) % 60,
+        secs % 60,
+        record.timestamp % 1_000_000
+    );
+    let mut body = serde_json::to_value(&record.message).unwrap_or_default();
+    let kind = body
+        .as_object_mut()
+        .and_then(|fields| fields.remove("type"))
+        .and_then(|v| v.as_str().map(str::to_uppercase))
+        .unwrap_or_else(|| "?".to_string());
+    writeln!(
+        out,
+        "{} {} -> {} [{}] {} {}",
+        time.dimmed(),
+        record.src,
+        record.dst,
+        record.transport,
+        kind.cyan().bold(),
+        body
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// pcap reading
+// ============================================================================
+
+struct PcapReader<R> {
+    reader: R,
+    swapped: bool,
+    nanos: bool,
+    linktype: u32,
+}
+
+struct Packet {
+    /// Capture time in microseconds since the Unix epoch
+    timestamp: u64,
+    data: Vec<u8>,
+}
+
+impl<R: Read> PcapReader<R> {
+    fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 24];
+        reader
+            .read_exact(&mut header)
+            .context("Capture is too short for a pcap header")?;
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let (swapped, nanos) = match magic {
+            0xa1b2_c3d4 => (false, false),
+            0xd4c3_b2a1 => (true, false),
+            0xa1b2_3c4d => (false, true),
+            0x4d3c_b2a1 => (true, true),
+            0x0a0d_0d0a => {
+                bail!("pcapng is not supported; convert with: editcap -F pcap in.pcapng out.pcap")
+            }
+            other => bail!("Not a pcap file (magic {:#010x})", other),
+        };
+        let mut pcap = Self {
+            reader,
+            swapped,
+            nanos,
+            linktype: 0,
+        };
+        pcap.linktype = pcap.u32_at(&header, 20) & 0x0fff_ffff;
+        Ok(pcap)
+    }
+
+    fn u32_at(&self, buf: &[u8], at: usize) -> u32 {
+        let bytes = [buf[at], buf[at + 1], buf[at + 2], buf[at + 3]];
+        if self.swapped {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn next_packet(&mut self) -> Result<Option<Packet>> {
+        let mut header = [0u8; 16];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let secs = self.u32_at(&header, 0) as u64;
+        let frac = self.u32_at(&header, 4) as u64;
+        let caplen = self.u32_at(&header, 8) as usize;
+        if caplen > 256 * 1024 {
+            bail!("Corrupt pcap record ({} bytes)", caplen);
+        }
+        let mut data = vec![0u8; caplen];
+        self.reader
+            .read_exact(&mut data)
+            .context("Capture ends mid-packet")?;
+        let micros = if self.nanos { frac / 1000 } else { frac };
+        Ok(Some(Packet {
+            timestamp: secs * 1_000_000 + micros,
+            data,
+        }))
+    }
+}
+
+// ============================================================================
+// Packet dissection
+// ============================================================================
+
+/// A decoded CLASP message and where it was seen
+struct Record {
+    timestamp: u64,
+    src: String,
+    dst: String,
+    transport: &'static str,
+    bytes: usize,
+    message: Message,
+}
+
+#[derive(Default)]
+struct Stats {
+    packets: u64,
+    messages: u64,
+    errors: u64,
+    quic_packets: u64,
+    gaps: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    src: (IpAddr, u16),
+    dst: (IpAddr, u16),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StreamMode {
+    Unknown,
+    HttpUpgrade,
+    WebSocket,
+    RawTcp,
+}
+
+/// One direction of a TCP connection
+struct TcpStream {
+    next_seq: Option<u32>,
+    buf: Vec<u8>,
+    mode: StreamMode,
+    /// Payload of a fragmented WebSocket message
+    ws_message: Vec<u8>,
+}
+
+impl TcpStream {
+    fn new() -> Self {
+        Self {
+            next_seq: None,
+            buf: Vec::new(),
+            mode: StreamMode::Unknown,
+            ws_message: Vec::new(),
+        }
+    }
+}
+
+struct Dissector {
+    ports: Vec<u16>,
+    streams: HashMap<FlowKey, TcpStream>,
+    stats: Stats,
+}
+
+impl Dissector {
+    fn new(ports: &[u16]) -> Self {
+        Self {
+            ports: ports.to_vec(),
+            streams: HashMap::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    fn packet(&mut self, linktype: u32, packet: &Packet) -> Vec<Record> {
+        self.stats.packets += 1;
+        let data = &packet.data[..];
+        let ip = match linktype {
+            LINKTYPE_USER0 => return self.tap_record(packet),
+            LINKTYPE_NULL if data.len() >= 4 => &data[4..],
+            LINKTYPE_ETHERNET => match ethernet_payload(data) {
+                Some(ip) => ip,
+                None => return Vec::new(),
+            },
+            LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => data,
+            LINKTYPE_LINUX_SLL if data.len() >= 16 => &data[16..],
+            LINKTYPE_LINUX_SLL2 if data.len() >= 20 => &data[20..],
+            _ => return Vec::new(),
+        };
+        let Some((src, dst, proto, transport)) = ip_payload(ip) else {
+            return Vec::new();
+        };
+        match proto {
+            6 => self.tcp(packet.timestamp, src, dst, transport),
+            17 => self.udp(packet.timestamp, src, dst, transport),
+            _ => Vec::new(),
+        }
+    }
+
+    fn wanted(&self, src_port: u16, dst_port: u16) -> bool {
+        self.ports.is_empty() || self.ports.contains(&src_port) || self.ports.contains(&dst_port)
+    }
+
+    /// Wire tap record: direction, session id, then the raw frame
+    fn tap_record(&mut self, packet: &Packet) -> Vec<Record> {
+        let data = &packet.data;
+        if data.len() < 2 || data.len() < 2 + data[1] as usize {
+            self.stats.errors += 1;
+            return Vec::new();
+        }
+        let session_len = data[1] as usize;
+        let session = String::from_utf8_lossy(&data[2..2 + session_len]);
+        let (src, dst) = if data[0] == 0 {
+            (format!("session:{}", session), "router".to_string())
+        } else {
+            ("router".to_string(), format!("session:{}", session))
+        };
+        self.frames(
+            packet.timestamp,
+            &src,
+            &dst,
+            "tap",
+            &data[2 + session_len..],
+        )
+    }
+
+    fn udp(&mut self, ts: u64, src: IpAddr, dst: IpAddr, udp: &[u8]) -> Vec<Record> {
+        if udp.len() < 8 {
+            return Vec::new();
+        }
+        let sport = u16::from_be_bytes([udp[0], udp[1]]);
+        let dport = u16::from_be_bytes([udp[2], udp[3]]);
+        if !self.wanted(sport, dport) {
+            return Vec::new();
+        }
+        let payload = &udp[8..];
+        match payload.first() {
+            Some(&MAGIC_BYTE) => self.frames(
+                ts,
+                &endpoint(src, sport),
+                &endpoint(dst, dport),
+                "udp",
+                payload,
+            ),
+            // QUIC packets always have the fixed bit (0x40) set
+            Some(first) if first & 0x40 != 0 => {
+                self.stats.quic_packets += 1;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn tcp(&mut self, ts: u64, src: IpAddr, dst: IpAddr, tcp: &[u8]) -> Vec<Record> {
+        if tcp.len() < 20 {
+            return Vec::new();
+        }
+        let sport = u16::from_be_bytes([tcp[0], tcp[1]]);
+        let dport = u16::from_be_bytes([tcp[2], tcp[3]]);
+        if !self.wanted(sport, dport) {
+            return Vec::new();
+        }
+        let seq = u32::from_be_bytes([tcp[4], tcp[5], tcp[6], tcp[7]]);
+        let offset = ((tcp[12] >> 4) as usize) * 4;
+        let flags = tcp[13];
+        let key = FlowKey {
+            src: (src, sport),
+            dst: (dst, dport),
+        };
+
+        // SYN or RST starts a fresh stream
+        if flags & 0x06 != 0 {
+            let mut stream = TcpStream::new();
+            if flags & 0x02 != 0 {
+                stream.next_seq = Some(seq.wrapping_add(1));
+            }
+            self.streams.insert(key, stream);
+            return Vec::new();
+        }
+        if offset < 20 || tcp.len() <= offset {
+            return Vec::new();
+        }
+        let payload = &tcp[offset..];
+
+        let stream = self.streams.entry(key).or_insert_with(TcpStream::new);
+        let expected = *stream.next_seq.get_or_insert(seq);
+        let ahead = seq.wrapping_sub(expected) as i32;
+        if ahead > 0 {
+            // Missing segment: drop what we had and resynchronise
+            self.stats.gaps += 1;
+            stream.buf.clear();
+            stream.ws_message.clear();
+            stream.mode = StreamMode::Unknown;
+            stream.buf.extend_from_slice(payload);
+        } else {
+            // Retransmissions overlap data we already have
+            let overlap = (-ahead) as usize;
+            if overlap < payload.len() {
+                stream.buf.extend_from_slice(&payload[overlap..]);
+            }
+        }
+        let end = seq.wrapping_add(payload.len() as u32);
+        if (end.wrapping_sub(expected) as i32) > 0 {
+            stream.next_seq = Some(end);
+        }
+
+        let (src, dst) = (endpoint(src, sport), endpoint(dst, dport));
+        let mut frames = Vec::new();
+        drain_stream(stream, |transport, frame| frames.push((transport, frame)));
+        let mut records = Vec::new();
+        for (transport, frame) in frames {
+            records.extend(self.frames(ts, &src, &dst, transport, &frame));
+        }
+        records
+    }
+
+    /// Decode one or more back-to-back CLASP frames
+    fn frames(
+        &mut self,
+        ts: u64,
+        src: &str,
+        dst: &str,
+        transport: &'static str,
+        mut data: &[u8],
+    ) -> Vec<Record> {
+        let mut records = Vec::new();
+        while !data.is_empty() {
+            let Some(len) = Frame::check_complete(data) else {
+                self.stats.errors += 1;
+                break;
+            };
+            match codec::decode(&data[..len]) {
+                Ok((message, frame)) => {
+                    self.stats.messages += 1;
+                    records.push(Record {
+                        timestamp: frame.timestamp.unwrap_or(ts),
+                        src: src.to_string(),
+                        dst: dst.to_string(),
+                        transport,
+                        bytes: len,
+                        message,
+                    });
+                }
+                Err(_) => self.stats.errors += 1,
+            }
+            data = &data[len..];
+        }
+        records
+    }
+}
+
+/// Pull complete CLASP frames out of a reassembled TCP stream
+fn drain_stream(stream: &mut TcpStream, mut emit: impl FnMut(&'static str, Vec<u8>)) {
+    loop {
+        match stream.mode {
+            StreamMode::Unknown => {
+                if stream.buf.len() < 5 {
+                    return;
+                }
+                stream.mode = if stream.buf.starts_with(b"GET ") || stream.buf.starts_with(b"HTTP/")
+                {
+                    StreamMode::HttpUpgrade
+                } else if stream.buf[4] == MAGIC_BYTE && stream.buf[0] == 0 {
+                    StreamMode::RawTcp
+                } else if stream.buf[0] & 0x70 == 0 {
+                    // Joined mid-connection: RSV bits clear looks like WebSocket
+                    StreamMode::WebSocket
+                } else {
+                    stream.buf.clear();
+                    return;
+                };
+            }
+            StreamMode::HttpUpgrade => {
+                let Some(end) = stream.buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    return;
+                };
+                stream.buf.drain(..end + 4);
+                stream.mode = StreamMode::WebSocket;
+            }
+            StreamMode::RawTcp => {
+                if stream.buf.len() < 4 {
+                    return;
+                }
+                let len = u32::from_be_bytes([
+                    stream.buf[0],
+                    stream.buf[1],
+                    stream.buf[2],
+                    stream.buf[3],
+                ]) as usize;
+                if stream.buf.len() < 4 + len {
+                    return;
+                }
+                let frame = stream.buf[4..4 + len].to_vec();
+                stream.buf.drain(..4 + len);
+                emit("tcp", frame);
+            }
+            StreamMode::WebSocket => {
+                let Some((fin, opcode, payload, used)) = websocket_frame(&stream.buf) else {
+                    return;
+                };
+                stream.buf.drain(..used);
+                match opcode {
+                    0 | 2 => {
+                        stream.ws_message.extend_from_slice(&payload);
+                        if fin {
+                            emit("ws", std::mem::take(&mut stream.ws_message));
+                        }
+                    }
+                    // Text, close, ping and pong carry no CLASP frames
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Parse one WebSocket frame: (fin, opcode, unmasked payload, bytes used)
+fn websocket_frame(buf: &[u8]) -> Option<(bool, u8, Vec<u8>, usize)> {
+    if buf.len() < 2 {
+        return None;
+    }
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0f;
+    let masked = buf[1] & 0x80 != 0;
+    let (len, mut at) = match buf[1] & 0x7f {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as usize, 4),
+        127 if buf.len() >= 10 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(bytes) as usize, 10)
+        }
+        126 | 127 => return None,
+        n => (n as usize, 2),
+    };
+    let mask = if masked {
+        let key = buf.get(at..at + 4)?;
+        at += 4;
+        Some([key[0], key[1], key[2], key[3]])
+    } else {
+        None
+    };
+    let mut payload = buf.get(at..at.checked_add(len)?)?.to_vec();
+    if let Some(mask) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Some((fin, opcode, payload, at + len))
+}
+
+fn ethernet_payload(data: &[u8]) -> Option<&[u8]> {
+    let mut at = 12;
+    let mut ethertype = u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]);
+    // Skip 802.1Q / 802.1ad VLAN tags
+    while ethertype == 0x8100 || ethertype == 0x88a8 {
+        at += 4;
+        ethertype = u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]);
+    }
+    match ethertype {
+        0x0800 | 0x86dd => data.get(at + 2..),
+        _ => None,
+    }
+}
+
+/// Split an IP packet into (src, dst, protocol, transport header + payload)
+fn ip_payload(ip: &[u8]) -> Option<(IpAddr, IpAddr, u8, &[u8])> {
+    match ip.first()? >> 4 {
+        4 => {
+            let ihl = ((ip[0] & 0x0f) as usize) * 4;
+            let total = u16::from_be_bytes([*ip.get(2)?, *ip.get(3)?]) as usize;
+            // Only the first fragment carries the transport header
+            let frag_offset = u16::from_be_bytes([*ip.get(6)?, *ip.get(7)?]) & 0x1fff;
+            if ip.len() < 20 || ihl < 20 || frag_offset != 0 {
+                return None;
+            }
+            let src: [u8; 4] = ip[12..16].try_into().ok()?;
+            let dst: [u8; 4] = ip[16..20].try_into().ok()?;
+            let end = total.clamp(ihl, ip.len());
+            Some((src.into(), dst.into(), ip[9], ip.get(ihl..end)?))
+        }
+        6 => {
+            if ip.len() < 40 {
+                return None;
+            }
+            let src: [u8; 16] = ip[8..24].try_into().ok()?;
+            let dst: [u8; 16] = ip[24..40].try_into().ok()?;
+            let len = u16::from_be_bytes([ip[4], ip[5]]) as usize;
+            let end = (40 + len).min(ip.len());
+            Some((src.into(), dst.into(), ip[6], ip.get(40..end)?))
+        }
+        _ => None,
+    }
+}
+
+fn endpoint(ip: IpAddr, port: u16) -> String {
+    match ip {
+        IpAddr::V4(v4) => format!("{}:{}", v4, port),
+        IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
+    }
+}
+
+// ============================================================================
+// Lua dissector generation
+// ============================================================================
+
+const MESSAGE_TYPES: &[(&str, u8)] = &[
+    ("HELLO", msg::HELLO),
+    ("WELCOME", msg::WELCOME),
+    ("ANNOUNCE", msg::ANNOUNCE),
+    ("FEDERATION_SYNC", msg::FEDERATION_SYNC),
+    ("SUBSCRIBE", msg::SUBSCRIBE),
+    ("UNSUBSCRIBE", msg::UNSUBSCRIBE),
+    ("SUBSCRIBE_ACK", msg::SUBSCRIBE_ACK),
+    ("UNSUBSCRIBE_ACK", msg::UNSUBSCRIBE_ACK),
+    ("PUBLISH", msg::PUBLISH),
+    ("SET", msg::SET),
+    ("GET", msg::GET),
+    ("SNAPSHOT", msg::SNAPSHOT),
+    ("REPLAY", msg::REPLAY),
+    ("BUNDLE", msg::BUNDLE),
+    ("SYNC", msg::SYNC),
+    ("PING", msg::PING),
+    ("PONG", msg::PONG),
+    ("ACK", msg::ACK),
+    ("ERROR", msg::ERROR),
+    ("QUERY", msg::QUERY),
+    ("RESULT", msg::RESULT),
+];
+
+const SIGNAL_TYPES: &[(&str, u8)] = &[
+    ("param", sig::PARAM),
+    ("event", sig::EVENT),
+    ("stream", sig::STREAM),
+    ("gesture", sig::GESTURE),
+    ("timeline", sig::TIMELINE),
+];
+
+const VALUE_TYPES: &[(&str, u8)] = &[
+    ("null", val::NULL),
+    ("bool", val::BOOL),
+    ("i8", val::I8),
+    ("i16", val::I16),
+    ("i32", val::I32),
+    ("i64", val::I64),
+    ("f32", val::F32),
+    ("f64", val::F64),
+    ("string", val::STRING),
+    ("bytes", val::BYTES),
+    ("array", val::ARRAY),
+    ("map", val::MAP),
+];
+
+/// Offset of the u16-length-prefixed address from the message type byte
+const ADDRESS_OFFSETS: &[(u8, usize)] = &[
+    (msg::SET, 2),
+    (msg::PUBLISH, 2),
+    (msg::GET, 1),
+    (msg::SUBSCRIBE, 5),
+    (msg::QUERY, 1),
+];
+
+fn lua_table(entries: &[(&str, u8)]) -> String {
+    entries
+        .iter()
+        .map(|(name, code)| format!("    [0x{:02x}] = \"{}\",\n", code, name))
+        .collect()
+}
+
+/// Generate a Wireshark Lua dissector from the codec definitions
+fn lua_dissector() -> String {
+    let offsets: String = ADDRESS_OFFSETS
+        .iter()
+        .map(|(code, offset)| format!("    [0x{:02x}] = {},\n", code, offset))
+        .collect();
+
+    format!(
+        r#"-- CLASP protocol dissector for Wireshark
+-- Generated by `clasp dissect --lua` (clasp {version}) from the codec tables.
+--
+-- Decodes CLASP frames carried in WebSocket binary messages (port {ws_port}),
+-- UDP datagrams and router wire tap captures (link type USER0).
+-- QUIC traffic is encrypted and cannot be dissected here.
+
+local clasp = Proto("clasp", "CLASP")
+
+local MAGIC = 0x{magic:02x}
+local SET = 0x{set:02x}
+local PUBLISH = 0x{publish:02x}
+local SUBSCRIBE = 0x{subscribe:02x}
+
+local msg_types = {{
+{msg_types}}}
+
+local signal_types = {{
+{signal_types}}}
+
+local value_types = {{
+{value_types}}}
+
+local address_offsets = {{
+{offsets}}}
+
+local qos_names = {{ [0] = "fire", [1] = "confirm", [2] = "commit" }}
+
+local f_magic = ProtoField.uint8("clasp.magic", "Magic", base.HEX)
+local f_flags = ProtoField.uint8("clasp.flags", "Flags", base.HEX)
+local f_qos = ProtoField.uint8("clasp.flags.qos", "QoS", base.DEC, qos_names, 0xc0)
+local f_has_ts = ProtoField.bool("clasp.flags.timestamp", "Timestamp present", 8, nil, 0x20)
+local f_encrypted = ProtoField.bool("clasp.flags.encrypted", "Encrypted", 8, nil, 0x10)
+local f_compressed = ProtoField.bool("clasp.flags.compressed", "Compressed", 8, nil, 0x08)
+local f_has_corr = ProtoField.bool("clasp.flags.correlation", "Correlation ID present", 8, nil, 0x04)
+local f_version = ProtoField.uint8("clasp.flags.version", "Encoding version", base.DEC, nil, 0x03)
+local f_length = ProtoField.uint16("clasp.length", "Payload length", base.DEC)
+local f_timestamp = ProtoField.uint64("clasp.timestamp", "Timestamp (us)", base.DEC)
+local f_correlation = ProtoField.uint32("clasp.correlation_id", "Correlation ID", base.DEC)
+local f_type = ProtoField.uint8("clasp.type", "Message type", base.HEX, msg_types)
+local f_address = ProtoField.string("clasp.address", "Address")
+local f_signal = ProtoField.uint8("clasp.signal", "Signal type", base.DEC, signal_types, 0xe0)
+local f_value_type = ProtoField.uint8("clasp.value_type", "Value type", base.HEX, value_types, 0x0f)
+local f_sub_id = ProtoField.uint32("clasp.subscription_id", "Subscription id", base.DEC)
+local f_payload = ProtoField.bytes("clasp.payload", "Payload")
+local f_tap_dir = ProtoField.uint8("clasp.tap.direction", "Direction", base.DEC, {{ [0] = "in", [1] = "out" }})
+local f_tap_session = ProtoField.string("clasp.tap.session", "Session")
+
+clasp.fields = {{
+    f_magic, f_flags, f_qos, f_has_ts, f_encrypted, f_compressed, f_has_corr, f_version,
+    f_length, f_timestamp, f_correlation, f_type, f_address, f_signal, f_value_type, f_sub_id,
+    f_payload, f_tap_dir, f_tap_session,
+}}
+
+-- Returns bytes consumed, 0 if this is not a CLASP frame, or a negative
+-- number of missing bytes when the frame continues in the next segment.
+local function dissect_frame(tvb, pinfo, tree, offset)
+    local remaining = tvb:len() - offset
+    if remaining < 4 or tvb(offset, 1):uint() ~= MAGIC then
+        return 0
+    end
+    local flags = tvb(offset + 1, 1):uint()
+    local len = tvb(offset + 2, 2):uint()
+    local has_ts = bit.band(flags, 0x20) ~= 0
+    local has_corr = bit.band(flags, 0x04) ~= 0
+    local header = 4
+    if has_ts then
+        header = header + 8
+    end
+    if has_corr then
+        header = header + 4
+    end
+    if remaining < header + len then
+        return remaining - (header + len)
+    end
+
+    local subtree = tree:add(clasp, tvb(offset, header + len))
+    subtree:add(f_magic, tvb(offset, 1))
+    local flag_tree = subtree:add(f_flags, tvb(offset + 1, 1))
+    flag_tree:add(f_qos, tvb(offset + 1, 1))
+    flag_tree:add(f_has_ts, tvb(offset + 1, 1))
+    flag_tree:add(f_encrypted, tvb(offset + 1, 1))
+    flag_tree:add(f_compressed, tvb(offset + 1, 1))
+    flag_tree:add(f_has_corr, tvb(offset + 1, 1))
+    flag_tree:add(f_version, tvb(offset + 1, 1))
+    subtree:add(f_length, tvb(offset + 2, 2))
+    if has_ts then
+        subtree:add(f_timestamp, tvb(offset + 4, 8))
+    end
+    if has_corr then
+        subtree:add(f_correlation, tvb(offset + header - 4, 4))
+    end
+
+    local p = offset + header
+    local encrypted = bit.band(flags, 0x10) ~= 0
+    if len > 0 and not encrypted and bit.band(flags, 0x03) >= 1 then
+        local mtype = tvb(p, 1):uint()
+        subtree:add(f_type, tvb(p, 1))
+        local info = msg_types[mtype] or string.format("0x%02x", mtype)
+        if mtype == SET and len > 1 then
+            subtree:add(f_value_type, tvb(p + 1, 1))
+        elseif mtype == PUBLISH and len > 1 then
+            subtree:add(f_signal, tvb(p + 1, 1))
+        elseif mtype == SUBSCRIBE and len > 4 then
+            subtree:add(f_sub_id, tvb(p + 1, 4))
+        end
+        local addr_offset = address_offsets[mtype]
+        if addr_offset and len >= addr_offset + 2 then
+            local a = p + addr_offset
+            local alen = tvb(a, 2):uint()
+            if alen > 0 and a + 2 + alen <= p + len then
+                subtree:add(f_address, tvb(a + 2, alen))
+                info = info .. " " .. tvb(a + 2, alen):string()
+            end
+        end
+        subtree:append_text(": " .. info)
+        pinfo.cols.info:append(info .. " ")
+    end
+    if len > 0 then
+        subtree:add(f_payload, tvb(p, len))
+    end
+    return header + len
+end
+
+function clasp.dissector(tvb, pinfo, tree)
+    local offset = 0
+    local first = true
+    while offset < tvb:len() do
+        local used = dissect_frame(tvb, pinfo, tree, offset)
+        if used == 0 then
+            break
+        end
+        if first then
+            pinfo.cols.protocol = "CLASP"
+            pinfo.cols.info:clear()
+            first = false
+        end
+        if used < 0 then
+            pinfo.desegment_offset = offset
+            pinfo.desegment_len = -used
+            return tvb:len()
+        end
+        offset = offset + used
+    end
+    return offset
+end
+
+-- Router wire tap captures: direction, session id length, session id, frame
+local clasp_tap = Proto("clasp_tap", "CLASP wire tap")
+
+function clasp_tap.dissector(tvb, pinfo, tree)
+    local slen = tvb(1, 1):uint()
+    local subtree = tree:add(clasp_tap, tvb(0, 2 + slen))
+    subtree:add(f_tap_dir, tvb(0, 1))
+    subtree:add(f_tap_session, tvb(2, slen))
+    return clasp.dissector(tvb(2 + slen):tvb(), pinfo, tree)
+end
+
+local function heuristic(tvb, pinfo, tree)
+    if tvb:len() < 4 or tvb(0, 1):uint() ~= MAGIC then
+        return false
+    end
+    return clasp.dissector(tvb, pinfo, tree) > 0
+end
+
+local ws_port = DissectorTable.get("ws.port")
+if ws_port then
+    ws_port:add({ws_port}, clasp)
+end
+local ws_protocol = DissectorTable.get("ws.protocol")
+if ws_protocol then
+    ws_protocol:add("{subprotocol}", clasp)
+end
+clasp:register_heuristic("ws", heuristic)
+clasp:register_heuristic("udp", heuristic)
+
+local encaps = wtap_encaps or wtap
+if encaps and encaps.USER0 then
+    DissectorTable.get("wtap_encap"):add(encaps.USER0, clasp_tap)
+end
+"#,
+        version = env!("CARGO_PKG_VERSION"),
+        ws_port = clasp_core::DEFAULT_WS_PORT,
+        subprotocol = clasp_core::WS_SUBPROTOCOL,
+        magic = MAGIC_BYTE,
+        set = msg::SET,
+        publish = msg::PUBLISH,
+        subscribe = msg::SUBSCRIBE,
+        msg_types = lua_table(MESSAGE_TYPES),
+        signal_types = lua_table(SIGNAL_TYPES),
+        value_types = lua_table(VALUE_TYPES),
+        offsets = offsets,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{SetMessage, Value};
+
+    fn set_frame(address: &str) -> Vec<u8> {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(0.5),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+        .to_vec()
+    }
+
+    fn ipv4_tcp(seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut tcp = vec![0u8; 20];
+        tcp[0..2].copy_from_slice(&50000u16.to_be_bytes());
+        tcp[2..4].copy_from_slice(&7330u16.to_be_bytes());
+        tcp[4..8].copy_from_slice(&seq.to_be_bytes());
+        tcp[12] = 5 << 4;
+        tcp[13] = flags;
+        tcp.extend_from_slice(payload);
+
+        let mut ip = vec![0u8; 20];
+        ip[0] = 0x45;
+        ip[2..4].copy_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+        ip[9] = 6;
+        ip[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        ip.extend_from_slice(&tcp);
+        ip
+    }
+
+    fn masked_ws_frame(payload: &[u8]) -> Vec<u8> {
+        let mask = [1u8, 2, 3, 4];
+        let mut frame = vec![0x82, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_websocket_stream_split_across_segments() {
+        let mut dissector = Dissector::new(&[7330]);
+        let mut stream = b"GET / HTTP/1.1\r\nUpgrade: websocket\r\n\r\n".to_vec();
+        stream.extend(masked_ws_frame(&set_frame("/lights/1")));
+        let (a, b) = stream.split_at(50);
+
+        let packet = |seq, flags, data: &[u8]| Packet {
+            timestamp: 0,
+            data: ipv4_tcp(seq, flags, data),
+        };
+        assert!(dissector
+            .packet(LINKTYPE_RAW, &packet(99, 0x02, &[]))
+            .is_empty());
+        assert!(dissector
+            .packet(LINKTYPE_RAW, &packet(100, 0x18, a))
+            .is_empty());
+        // A retransmission of the first segment is ignored
+        assert!(dissector
+            .packet(LINKTYPE_RAW, &packet(100, 0x18, a))
+            .is_empty());
+        let records = dissector.packet(LINKTYPE_RAW, &packet(150, 0x18, b));
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].src, "10.0.0.1:50000");
+        assert_eq!(records[0].transport, "ws");
+        match &records[0].message {
+            Message::Set(set) => assert_eq!(set.address, "/lights/1"),
+            other => panic!("expected SET, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pcap_reader_and_tap_records() {
+        let frame = set_frame("/a");
+        let mut record = vec![1u8, 3];
+        record.extend_from_slice(b"sid");
+        record.extend_from_slice(&frame);
+
+        let mut file = Vec::new();
+        file.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        file.extend_from_slice(&[2, 0, 4, 0]);
+        file.extend_from_slice(&[0u8; 8]);
+        file.extend_from_slice(&65535u32.to_le_bytes());
+        file.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+        file.extend_from_slice(&7u32.to_le_bytes());
+        file.extend_from_slice(&42u32.to_le_bytes());
+        file.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        file.extend_from_slice(&record);
+
+        let mut pcap = PcapReader::new(&file[..]).unwrap();
+        assert_eq!(pcap.linktype, LINKTYPE_USER0);
+        let packet = pcap.next_packet().unwrap().unwrap();
+        assert_eq!(packet.timestamp, 7_000_042);
+        assert!(pcap.next_packet().unwrap().is_none());
+
+        let records = Dissector::new(&[]).packet(pcap.linktype, &packet);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].src, "router");
+        assert_eq!(records[0].dst, "session:sid");
+    }
+
+    #[test]
+    fn test_lua_dissector_uses_codec_codes() {
+        let lua = lua_dissector();
+        assert!(lua.contains("[0x21] = \"SET\""));
+        assert!(lua.contains("ws_port:add(7330, clasp)"));
+        assert!(lua.contains("local MAGIC = 0x53"));
+    }
+}
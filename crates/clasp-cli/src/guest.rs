@@ -0,0 +1,67 @@
+//! Guest link subcommand: mint a short-lived guest link via the relay REST API.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+/// Mint a guest link and print its URL.
+pub async fn handle_create(
+    relay_url: &str,
+    scopes: &[String],
+    expires: &str,
+    max_uses: Option<u64>,
+    token: Option<&str>,
+) -> Result<()> {
+    let expires_in = clasp_core::security::parse_duration(expires)
+        .context("Failed to parse expiration")?
+        .as_secs();
+    let relay_url = relay_url.trim_end_matches('/');
+    let url = format!("{}/api/guest-links", relay_url);
+    let mut body = serde_json::json!({
+        "scopes": scopes,
+        "expires_in": expires_in,
+    });
+    if let Some(max) = max_uses {
+        body["max_uses"] = max.into();
+    }
+
+    let client = reqwest::Client::new();
+    let mut req = client.post(&url).json(&body);
+    if let Some(t) = token {
+        req = req.header("Authorization", format!("Bearer {}", t));
+    }
+
+    let resp = req
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach relay at {}", url))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        anyhow::bail!("POST {} returned {}: {}", url, status, body);
+    }
+
+    let link: serde_json::Value = resp
+        .json()
+        .await
+        .context("Failed to parse response as JSON")?;
+    let path = link
+        .get("path")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    println!("{}{}", relay_url, path);
+    eprintln!("{}: {}", "Scopes".cyan(), scopes.join(", "));
+    if let Some(expires_at) = link.get("expires_at").and_then(|v| v.as_u64()) {
+        eprintln!("{}: {} (in {})", "Expires".cyan(), expires_at, expires);
+    }
+    match link.get("max_uses").and_then(|v| v.as_u64()) {
+        Some(max) => eprintln!("{}: {}", "Max uses".cyan(), max),
+        None => eprintln!("{}: unlimited", "Max uses".cyan()),
+    }
+    if let Some(t) = link.get("token").and_then(|v| v.as_str()) {
+        eprintln!("{}: {}", "Token".cyan(), t);
+    }
+
+    Ok(())
+}
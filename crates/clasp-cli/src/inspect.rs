@@ -0,0 +1,190 @@
+//! Live state inspection subcommands: get and query against a running router.
+
+use anyhow::Result;
+use clasp_core::{ParamValue, QueryMessage, ResultMessage, SignalDefinition, SignalField};
+use colored::Colorize;
+
+use crate::{connect_client, format_value, ConnectArgs};
+
+/// How `clasp get` and `clasp query` print what the router returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for reading
+    Table,
+    /// Pretty-printed JSON for scripts
+    Json,
+}
+
+/// Fetch the current value of an address, or every param matching a pattern.
+pub async fn handle_get(
+    server: &str,
+    address: &str,
+    depth: Option<u8>,
+    output: OutputFormat,
+    args: &ConnectArgs,
+) -> Result<()> {
+    let client = connect_client(server, args).await?;
+    let params = client.get_matching(address, depth).await;
+    client.close().await;
+    let params = params?;
+
+    // An exact address with no value is an error so scripts can branch on it
+    if params.is_empty() && !address.contains('*') {
+        anyhow::bail!("No value at {}", address);
+    }
+
+    match output {
+        OutputFormat::Json => {
+            // An exact address prints its param, a pattern the list
+            let json = if address.contains('*') {
+                serde_json::to_string_pretty(&params)?
+            } else {
+                serde_json::to_string_pretty(&params[0])?
+            };
+            println!("{}", json);
+        }
+        OutputFormat::Table => print_params(&params),
+    }
+    Ok(())
+}
+
+/// List the announced signals matching a pattern.
+///
+/// Without `limit` every page is fetched and printed as one list. With it,
+/// one page is printed along with the cursor for the next.
+pub async fn handle_query(
+    server: &str,
+    pattern: &str,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    output: OutputFormat,
+    args: &ConnectArgs,
+) -> Result<()> {
+    let client = connect_client(server, args).await?;
+    let result = fetch_signals(&client, pattern, limit, cursor).await;
+    client.close().await;
+    let result = result?;
+
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+        OutputFormat::Table => {
+            print_signals(&result.signals);
+            if let Some(cursor) = &result.next_cursor {
+                eprintln!(
+                    "Showing {} of {} signals, next page: --cursor {}",
+                    result.signals.len(),
+                    result
+                        .total
+                        .map(|total| total.to_string())
+                        .unwrap_or_else(|| "?".into()),
+                    cursor
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn fetch_signals(
+    client: &clasp_client::Clasp,
+    pattern: &str,
+    limit: Option<u32>,
+    mut cursor: Option<String>,
+) -> Result<ResultMessage> {
+    let mut all = ResultMessage::default();
+    loop {
+        let page = client
+            .query(QueryMessage {
+                pattern: pattern.to_string(),
+                limit,
+                cursor: cursor.take(),
+                fields: Some(vec![
+                    SignalField::Datatype,
+                    SignalField::Access,
+                    SignalField::Meta,
+                ]),
+            })
+            .await?;
+        if limit.is_some() {
+            return Ok(page);
+        }
+        all.signals.extend(page.signals);
+        all.total = page.total;
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok(all),
+        }
+    }
+}
+
+fn print_params(params: &[ParamValue]) {
+    if params.is_empty() {
+        println!("No params.");
+        return;
+    }
+    let rows: Vec<[String; 4]> = params
+        .iter()
+        .map(|p| {
+            [
+                p.address.clone(),
+                format_value(&p.value),
+                p.revision.to_string(),
+                p.writer.clone().unwrap_or_else(|| "-".into()),
+            ]
+        })
+        .collect();
+    print_table(["ADDRESS", "VALUE", "REVISION", "WRITER"], &rows);
+}
+
+fn print_signals(signals: &[SignalDefinition]) {
+    if signals.is_empty() {
+        println!("No signals.");
+        return;
+    }
+    let rows: Vec<[String; 5]> = signals
+        .iter()
+        .map(|s| {
+            [
+                s.address.clone(),
+                format!("{:?}", s.signal_type).to_lowercase(),
+                s.datatype.clone().unwrap_or_else(|| "-".into()),
+                s.access.clone().unwrap_or_else(|| "-".into()),
+                s.meta
+                    .as_ref()
+                    .and_then(|m| m.unit.clone())
+                    .unwrap_or_else(|| "-".into()),
+            ]
+        })
+        .collect();
+    print_table(["ADDRESS", "TYPE", "DATATYPE", "ACCESS", "UNIT"], &rows);
+}
+
+/// Print rows under a bold header, each column padded to its widest cell
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header
+        .iter()
+        .zip(widths)
+        .map(|(h, w)| format!("{:<w$}", h, w = w))
+        .collect();
+    println!("{}", header.join("  ").trim_end().bold());
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{:<w$}", cell, w = w))
+            .collect();
+        // Address first, colored after padding so the escapes don't count
+        println!(
+            "{}  {}",
+            cells[0].yellow(),
+            cells[1..].join("  ").trim_end()
+        );
+    }
+}
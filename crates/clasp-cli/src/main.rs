@@ -3,10 +3,15 @@
 //! Start protocol servers, bridges, and manage CLASP signals from the command line.
 
 mod crypto;
+mod dissect;
 mod entity;
+#[cfg(feature = "caps")]
+mod guest;
 mod identity;
+mod inspect;
 mod journal;
 mod server;
+mod simulate;
 mod tokens;
 
 #[cfg(feature = "lens")]
@@ -17,7 +22,7 @@ use clap::{Parser, Subcommand};
 use colored::Colorize;
 use ed25519_dalek::SigningKey;
 use std::path::PathBuf;
-use tokens::{create_token, default_token_file, format_timestamp, TokenStore};
+use tokens::{create_token, default_token_file, format_timestamp, resolve_token, TokenStore};
 use tokio::sync::mpsc;
 use tracing::{info, warn};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
@@ -138,6 +143,9 @@ enum Commands {
 
         /// Value to publish (JSON format)
         value: String,
+
+        #[command(flatten)]
+        connect: ConnectArgs,
     },
 
     /// Subscribe to signals
@@ -149,6 +157,70 @@ enum Commands {
         /// Address pattern to subscribe to
         #[arg(default_value = "/**")]
         pattern: String,
+
+        #[command(flatten)]
+        connect: ConnectArgs,
+    },
+
+    /// Print the current value of an address, or of every param matching a pattern
+    Get {
+        /// CLASP server URL
+        #[arg(short, long, default_value = "quic://localhost:7331")]
+        server: String,
+
+        /// Signal address or pattern (e.g. "/lights/**")
+        address: String,
+
+        /// Only match params this many segments below the pattern's fixed prefix
+        #[arg(long)]
+        depth: Option<u8>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        output: inspect::OutputFormat,
+
+        #[command(flatten)]
+        connect: ConnectArgs,
+    },
+
+    /// List announced signals matching a pattern
+    Query {
+        /// CLASP server URL
+        #[arg(short, long, default_value = "quic://localhost:7331")]
+        server: String,
+
+        /// Address pattern to query
+        #[arg(default_value = "/**")]
+        pattern: String,
+
+        /// Return one page of at most this many signals (default: all)
+        #[arg(long)]
+        limit: Option<u32>,
+
+        /// Continue after the page that printed this cursor
+        #[arg(long)]
+        cursor: Option<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        output: inspect::OutputFormat,
+
+        #[command(flatten)]
+        connect: ConnectArgs,
+    },
+
+    /// Generate synthetic signals from a config file (--config sim.yaml)
+    Simulate {
+        /// CLASP server URL
+        #[arg(short, long, default_value = "quic://localhost:7331")]
+        server: String,
+
+        /// Stop after this many seconds (default: run until Ctrl+C)
+        #[arg(short, long)]
+        duration: Option<f64>,
+
+        #[command(flatten)]
+        connect: ConnectArgs,
     },
 
     /// Show version and system info
@@ -200,6 +272,25 @@ enum Commands {
         #[command(subcommand)]
         action: CryptoAction,
     },
+
+    /// Decode CLASP messages from a pcap capture
+    Dissect {
+        /// pcap file to read ("-" for stdin, e.g. `tcpdump -U -w - | clasp dissect -`)
+        #[arg(required_unless_present = "lua")]
+        file: Option<PathBuf>,
+
+        /// TCP/UDP ports carrying CLASP traffic (default: all ports)
+        #[arg(short, long)]
+        port: Vec<u16>,
+
+        /// Print one JSON object per message
+        #[arg(long)]
+        json: bool,
+
+        /// Write a Wireshark Lua dissector to this path ("-" for stdout)
+        #[arg(long, value_name = "PATH")]
+        lua: Option<PathBuf>,
+    },
 }
 
 /// Key management actions
@@ -223,6 +314,22 @@ enum KeyAction {
     },
 }
 
+/// Options for connecting to a CLASP router
+#[derive(clap::Args)]
+struct ConnectArgs {
+    /// Auth token, or a prefix of one saved with `clasp token create`
+    #[arg(long, env = "CLASP_TOKEN")]
+    token: Option<String>,
+
+    /// Token file to resolve --token against (default: ~/.config/clasp/tokens.json)
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+
+    /// Skip QUIC certificate verification (self-signed dev servers only)
+    #[arg(long)]
+    insecure: bool,
+}
+
 /// Token management actions
 #[derive(Subcommand)]
 enum TokenAction {
@@ -263,6 +370,30 @@ enum TokenAction {
     /// Remove all expired tokens
     Prune,
 
+    /// Mint a guest link on a relay (short URL with a narrow, expiring capability token)
+    #[cfg(feature = "caps")]
+    Guest {
+        /// Scope to grant (repeatable, e.g., "write:/votes/**")
+        #[arg(long = "scope", required = true)]
+        scope: Vec<String>,
+
+        /// Expiration (e.g., "2h", "30m", at most "7d")
+        #[arg(short, long, default_value = "1h")]
+        expires: String,
+
+        /// Maximum number of connections the token may be used for
+        #[arg(long)]
+        max_uses: Option<u64>,
+
+        /// Admin token for authentication
+        #[arg(long, env = "CLASP_ADMIN_TOKEN")]
+        token: Option<String>,
+
+        /// Relay URL (auth HTTP port)
+        #[arg(long, env = "CLASP_RELAY_URL")]
+        relay: String,
+    },
+
     /// Capability token operations (delegatable Ed25519 tokens)
     #[cfg(feature = "caps")]
     Cap {
@@ -645,6 +776,7 @@ async fn main() -> Result<()> {
             server,
             address,
             value,
+            connect,
         } => {
             println!(
                 "{} Publishing to {} -> {}",
@@ -652,17 +784,58 @@ async fn main() -> Result<()> {
                 address.yellow(),
                 value
             );
-            publish_value(&server, &address, &value).await?;
+            publish_value(&server, &address, &value, &connect).await?;
         }
 
-        Commands::Sub { server, pattern } => {
+        Commands::Sub {
+            server,
+            pattern,
+            connect,
+        } => {
             println!(
                 "{} Subscribing to {} on {}",
                 "CLASP".cyan().bold(),
                 pattern.yellow(),
                 server
             );
-            subscribe_pattern(&server, &pattern, &mut shutdown_rx).await?;
+            subscribe_pattern(&server, &pattern, &connect, &mut shutdown_rx).await?;
+        }
+
+        Commands::Get {
+            server,
+            address,
+            depth,
+            output,
+            connect,
+        } => {
+            inspect::handle_get(&server, &address, depth, output, &connect).await?;
+        }
+
+        Commands::Query {
+            server,
+            pattern,
+            limit,
+            cursor,
+            output,
+            connect,
+        } => {
+            inspect::handle_query(&server, &pattern, limit, cursor, output, &connect).await?;
+        }
+
+        Commands::Simulate {
+            server,
+            duration,
+            connect,
+        } => {
+            let config = cli
+                .config
+                .context("clasp simulate needs --config <sim.yaml>")?;
+            let duration = duration
+                .map(std::time::Duration::try_from_secs_f64)
+                .transpose()
+                .context("Invalid --duration")?;
+            simulate::handle_simulate(&server, &config, duration, &connect, &mut shutdown_rx)
+                .await?;
         }
 
         Commands::Info => {
@@ -815,6 +988,18 @@ async fn main() -> Result<()> {
                     println!("{} Removed {} expired token(s)", "OK".green().bold(), count);
                 }
 
+                #[cfg(feature = "caps")]
+                TokenAction::Guest {
+                    scope,
+                    expires,
+                    max_uses,
+                    token,
+                    relay,
+                } => {
+                    guest::handle_create(&relay, &scope, &expires, max_uses, token.as_deref())
+                        .await?;
+                }
+
                 #[cfg(feature = "caps")]
                 TokenAction::Cap { action } => {
                     handle_cap_command(action)?;
@@ -847,6 +1032,20 @@ async fn main() -> Result<()> {
         Commands::Crypto { action } => {
             handle_crypto_command(action)?;
         }
+
+        Commands::Dissect {
+            file,
+            port,
+            json,
+            lua,
+        } => {
+            if let Some(path) = lua {
+                dissect::handle_lua(&path)?;
+            }
+            if let Some(path) = file {
+                dissect::handle_dissect(&path, &port, json)?;
+            }
+        }
     }
 
     Ok(())
@@ -887,7 +1086,11 @@ async fn handle_entity_api_command(action: EntityApiAction) -> Result<()> {
         EntityApiAction::Get { id, relay } => {
             entity::handle_get(&relay, &id).await?;
         }
-        EntityApiAction::Create { name, r#type, relay } => {
+        EntityApiAction::Create {
+            name,
+            r#type,
+            relay,
+        } => {
             entity::handle_create(&relay, &name, &r#type).await?;
         }
     }
@@ -978,7 +1181,7 @@ async fn run_bridge(
     );
 
     // Parse options into a map
-    let _options: std::collections::HashMap<String, String> = opts
+    let options: std::collections::HashMap<String, String> = opts
         .iter()
         .filter_map(|opt| {
             let parts: Vec<&str> = opt.splitn(2, '=').collect();
@@ -994,6 +1197,12 @@ async fn run_bridge(
         "osc" => {
             println!("  Use 'clasp osc' for OSC-specific options");
         }
+        "midi" => {
+            return run_midi_bridge(&options, shutdown_rx).await;
+        }
+        "artnet" => {
+            return run_artnet_bridge(&options, shutdown_rx).await;
+        }
         "mqtt" => {
             println!("  Use 'clasp mqtt' for MQTT-specific options");
         }
@@ -1046,6 +1255,114 @@ async fn run_osc_server(bind: &str, port: u16, shutdown_rx: &mut mpsc::Receiver<
     Ok(())
 }
 
+/// Art-Net bridge options: bind, remote, universes (comma-separated),
+/// namespace, refresh_rate
+async fn run_midi_bridge(
+    options: &std::collections::HashMap<String, String>,
+    shutdown_rx: &mut mpsc::Receiver<()>,
+) -> Result<()> {
+    use clasp_bridge::{Bridge, MidiBridge, MidiBridgeConfig};
+
+    if options.contains_key("list") {
+        println!("MIDI inputs:");
+        for port in MidiBridge::list_input_ports()? {
+            println!("  {}", port);
+        }
+        println!("MIDI outputs:");
+        for port in MidiBridge::list_output_ports()? {
+            println!("  {}", port);
+        }
+        return Ok(());
+    }
+
+    let mut config = MidiBridgeConfig {
+        input_port: options.get("input").cloned(),
+        output_port: options.get("output").cloned(),
+        ..Default::default()
+    };
+    if let Some(namespace) = options.get("namespace") {
+        config.namespace = namespace.clone();
+    }
+    if let Some(device) = options.get("device") {
+        config.device_name = device.clone();
+    }
+    if let Some(virtual_port) = options.get("virtual") {
+        config.virtual_port = virtual_port
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid virtual '{}': {}", virtual_port, e))?;
+    }
+
+    let mut bridge = MidiBridge::new(config);
+    let mut event_rx = bridge.start().await?;
+
+    println!("{} MIDI bridge running", "OK".green().bold());
+
+    loop {
+        tokio::select! {
+            event = event_rx.recv() => {
+                if let Some(event) = event {
+                    println!("{} {:?}", "MIDI".cyan(), event);
+                }
+            }
+            _ = shutdown_rx.recv() => {
+                bridge.stop().await?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_artnet_bridge(
+    options: &std::collections::HashMap<String, String>,
+    shutdown_rx: &mut mpsc::Receiver<()>,
+) -> Result<()> {
+    use clasp_bridge::{ArtNetBridge, ArtNetBridgeConfig, Bridge};
+
+    let mut config = ArtNetBridgeConfig::default();
+    if let Some(bind) = options.get("bind") {
+        config.bind_addr = bind.clone();
+    }
+    config.remote_addr = options.get("remote").cloned();
+    if let Some(universes) = options.get("universes") {
+        config.universes = universes
+            .split(',')
+            .map(|u| u.trim().parse::<u16>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid universes '{}': {}", universes, e))?;
+    }
+    if let Some(namespace) = options.get("namespace") {
+        config.namespace = namespace.clone();
+    }
+    if let Some(rate) = options.get("refresh_rate") {
+        config.refresh_rate = rate
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid refresh_rate '{}': {}", rate, e))?;
+    }
+
+    let mut bridge = ArtNetBridge::new(config);
+    let mut event_rx = bridge.start().await?;
+
+    println!("{} Art-Net bridge listening", "OK".green().bold());
+
+    loop {
+        tokio::select! {
+            event = event_rx.recv() => {
+                if let Some(event) = event {
+                    println!("{} {:?}", "ARTNET".cyan(), event);
+                }
+            }
+            _ = shutdown_rx.recv() => {
+                bridge.stop().await?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 async fn run_mqtt_bridge(
     host: &str,
     port: u16,
@@ -1169,40 +1486,101 @@ async fn run_http_server(
     Ok(())
 }
 
-async fn publish_value(_server: &str, address: &str, value: &str) -> Result<()> {
-    // Parse value as JSON
-    let parsed: serde_json::Value = serde_json::from_str(value)
-        .or_else(|_| Ok::<_, serde_json::Error>(serde_json::Value::String(value.to_string())))?;
+/// Connect to a router at `server` (ws://, wss://, or quic://host:port)
+async fn connect_client(server: &str, args: &ConnectArgs) -> Result<clasp_client::Clasp> {
+    let mut builder = clasp_client::Clasp::builder(server)
+        .name("clasp-cli")
+        .reconnect(false);
+
+    if let Some(token) = &args.token {
+        let token_path = args.token_file.clone().unwrap_or_else(default_token_file);
+        builder = builder.token(&resolve_token(token, &token_path)?);
+    }
+    if args.insecure {
+        builder = builder.quic_config(clasp_transport::quic::QuicConfig::insecure());
+    }
+
+    tokio::time::timeout(std::time::Duration::from_secs(10), builder.connect())
+        .await
+        .with_context(|| format!("Timed out connecting to {}", server))?
+        .with_context(|| format!("Failed to connect to {}", server))
+}
+
+/// Parse a CLI value as JSON, falling back to a plain string
+fn parse_value(value: &str) -> clasp_core::Value {
+    serde_json::from_str(value).unwrap_or_else(|_| clasp_core::Value::String(value.to_string()))
+}
+
+fn format_value(value: &clasp_core::Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+async fn publish_value(server: &str, address: &str, value: &str, args: &ConnectArgs) -> Result<()> {
+    let value = parse_value(value);
+    let client = connect_client(server, args).await?;
+
+    client.set(address, value.clone()).await?;
+
+    // A GET after the SET is answered in order, so it confirms the router
+    // has processed the SET (and reported any error) before we disconnect
+    let confirmed = client.get(address).await;
+    let error = client.last_error();
+    client.close().await;
+
+    if let Some(error) = error {
+        anyhow::bail!("Router rejected SET {}: {}", address, error.message);
+    }
+    if let Err(e) = confirmed {
+        warn!("Could not confirm {}: {}", address, e);
+    }
 
     println!(
         "{} Published {} = {}",
         "OK".green().bold(),
         address.yellow(),
-        serde_json::to_string_pretty(&parsed)?
+        format_value(&value)
     );
 
-    // TODO: Connect to CLASP server and publish
-    warn!("Server connection not yet implemented");
-
     Ok(())
 }
 
 async fn subscribe_pattern(
-    _server: &str,
+    server: &str,
     pattern: &str,
+    args: &ConnectArgs,
     shutdown_rx: &mut mpsc::Receiver<()>,
 ) -> Result<()> {
+    let client = connect_client(server, args).await?;
+
+    client
+        .subscribe(pattern, |value, address| {
+            println!("{} = {}", address.yellow(), format_value(&value));
+        })
+        .await?;
+
     println!(
-        "{} Subscribed to pattern: {}",
+        "{} Subscribed to pattern: {} (press Ctrl+C to exit)",
         "OK".green().bold(),
         pattern.yellow()
     );
 
-    // TODO: Connect to CLASP server and subscribe
-    warn!("Server connection not yet implemented - press Ctrl+C to exit");
-
-    shutdown_rx.recv().await;
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(500));
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => break,
+            _ = interval.tick() => {
+                if let Some(error) = client.last_error() {
+                    client.close().await;
+                    anyhow::bail!("Router error: {}", error.message);
+                }
+                if !client.is_connected() {
+                    anyhow::bail!("Connection to {} lost", server);
+                }
+            }
+        }
+    }
 
+    client.close().await;
     Ok(())
 }
 
@@ -0,0 +1,51 @@
+//! Synthetic data subcommand: runs a simulation config against a router.
+
+use anyhow::{Context, Result};
+use clasp_client::simulate::{SimConfig, Simulation};
+use colored::Colorize;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::{connect_client, ConnectArgs};
+
+/// Load a simulation config from YAML and generate its signals until
+/// Ctrl+C, or for `duration` when given.
+pub async fn handle_simulate(
+    server: &str,
+    config_path: &Path,
+    duration: Option<Duration>,
+    args: &ConnectArgs,
+    shutdown_rx: &mut mpsc::Receiver<()>,
+) -> Result<()> {
+    let text = std::fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to read {}", config_path.display()))?;
+    let config: SimConfig = serde_yaml::from_str(&text)
+        .with_context(|| format!("Invalid simulation config {}", config_path.display()))?;
+    let simulation = Simulation::new(config)?;
+
+    let client = connect_client(server, args).await?;
+    println!(
+        "{} Simulating {} signals on {} (press Ctrl+C to stop)",
+        "CLASP".cyan().bold(),
+        simulation.signals().len(),
+        server
+    );
+    for signal in simulation.signals() {
+        println!("  {}", signal.address.yellow());
+    }
+
+    let stop = async {
+        match duration {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+    let result = tokio::select! {
+        result = simulation.run(&client) => result.context("Simulation stopped"),
+        _ = shutdown_rx.recv() => Ok(()),
+        _ = stop => Ok(()),
+    };
+    client.close().await;
+    result
+}
@@ -183,6 +183,12 @@ impl TokenStore {
         self.tokens.get(token)
     }
 
+    /// Find a token by exact match or prefix
+    pub fn find(&self, token: &str) -> Option<&TokenRecord> {
+        self.get(token)
+            .or_else(|| self.list().find(|r| r.token.starts_with(token)))
+    }
+
     /// List all tokens
     pub fn list(&self) -> impl Iterator<Item = &TokenRecord> {
         self.tokens.values()
@@ -285,6 +291,21 @@ pub fn format_timestamp(ts: u64) -> String {
     }
 }
 
+/// Resolve a `--token` argument for connecting to a router.
+///
+/// Tokens saved with `clasp token create` can be given by prefix; anything
+/// not in the store is sent as-is (e.g. capability or entity tokens).
+pub fn resolve_token(token: &str, path: impl AsRef<Path>) -> Result<String> {
+    let store = TokenStore::load(path)?;
+    match store.find(token) {
+        Some(record) if record.is_expired() => {
+            anyhow::bail!("Token {} has expired", record.token)
+        }
+        Some(record) => Ok(record.token.clone()),
+        None => Ok(token.to_string()),
+    }
+}
+
 /// Get the default token file path
 pub fn default_token_file() -> std::path::PathBuf {
     dirs::config_dir()
@@ -333,6 +354,26 @@ mod tests {
         assert!(store.is_empty());
     }
 
+    #[test]
+    fn test_resolve_token() {
+        let path = std::env::temp_dir().join(format!("clasp-tokens-{}.json", std::process::id()));
+        let mut store = TokenStore::new();
+        store.add(TokenRecord::new(
+            "cpsk_abcdef".to_string(),
+            vec!["read:/**".to_string()],
+        ));
+        let mut expired = TokenRecord::new("cpsk_old".to_string(), vec![]);
+        expired.expires_at = Some(0);
+        store.add(expired);
+        store.save(&path).unwrap();
+
+        assert_eq!(resolve_token("cpsk_abc", &path).unwrap(), "cpsk_abcdef");
+        assert_eq!(resolve_token("cap_xyz", &path).unwrap(), "cap_xyz");
+        assert!(resolve_token("cpsk_old", &path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_create_token() {
         let record = create_token(
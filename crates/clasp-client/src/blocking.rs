@@ -0,0 +1,194 @@
+//! Blocking client API
+//!
+//! For hosts that don't run tokio (plugins, scripts, game engine hooks), the
+//! blocking [`Clasp`] wraps the async client in a runtime of its own and
+//! waits for each call to finish:
+//!
+//! ```no_run
+//! use clasp_client::blocking::Clasp;
+//! use std::time::Duration;
+//!
+//! # fn main() -> clasp_client::Result<()> {
+//! let client = Clasp::connect("ws://localhost:7330")?;
+//! client.set("/mixer/gain", 0.5)?;
+//! println!("gain = {:?}", client.get("/mixer/gain")?);
+//!
+//! let updates = client.subscribe("/mixer/**")?;
+//! while let Some((address, value)) = updates.recv_timeout(Duration::from_secs(1)) {
+//!     println!("{} = {:?}", address, value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Use [`Clasp::with_builder`] for tokens, features or a reconnect policy;
+//! the client reconnects in the background if the builder allows it.
+//!
+//! Don't call these methods from inside an async runtime: they block the
+//! calling thread, and tokio panics when a runtime is started or dropped
+//! from async code.
+
+use clasp_core::{Message, SignalDefinition, Value};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use crate::error::{ClientError, Result};
+use crate::{ClaspBuilder, ClientEvent};
+
+/// A client whose calls block until they complete
+pub struct Clasp {
+    inner: Arc<crate::Clasp>,
+    runtime: Arc<Runtime>,
+}
+
+impl Clasp {
+    /// Connect with default settings
+    pub fn connect(url: &str) -> Result<Self> {
+        Self::with_builder(ClaspBuilder::new(url))
+    }
+
+    /// Connect with the settings of an async client builder
+    pub fn with_builder(builder: ClaspBuilder) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("clasp-client")
+            .enable_all()
+            .build()
+            .map_err(|e| ClientError::Other(format!("failed to start runtime: {}", e)))?;
+        let inner = runtime.block_on(builder.connect_shared())?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Check if connected
+    pub fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    /// Get session ID
+    pub fn session_id(&self) -> Option<String> {
+        self.inner.session_id()
+    }
+
+    /// Connection lifecycle events, read with `blocking_recv`
+    pub fn events(&self) -> tokio::sync::broadcast::Receiver<ClientEvent> {
+        self.inner.events()
+    }
+
+    /// Set a parameter value
+    pub fn set(&self, address: &str, value: impl Into<Value>) -> Result<()> {
+        self.runtime.block_on(self.inner.set(address, value))
+    }
+
+    /// Send a request and wait for the router's answer or ERROR for it
+    pub fn request(&self, message: Message) -> Result<Message> {
+        self.runtime.block_on(self.inner.request(message))
+    }
+
+    /// Get current value (cached or request)
+    pub fn get(&self, address: &str) -> Result<Value> {
+        self.runtime.block_on(self.inner.get(address))
+    }
+
+    /// Get cached param value
+    pub fn cached(&self, address: &str) -> Option<Value> {
+        self.inner.cached(address)
+    }
+
+    /// Emit an event
+    pub fn emit(&self, address: &str, payload: impl Into<Value>) -> Result<()> {
+        self.runtime.block_on(self.inner.emit(address, payload))
+    }
+
+    /// Send stream sample
+    pub fn stream(&self, address: &str, value: impl Into<Value>) -> Result<()> {
+        self.runtime.block_on(self.inner.stream(address, value))
+    }
+
+    /// Subscribe to an address pattern; updates arrive on the returned
+    /// [`Subscription`] until it is dropped
+    pub fn subscribe(&self, pattern: &str) -> Result<Subscription> {
+        let (tx, rx) = mpsc::channel();
+        let id = self
+            .runtime
+            .block_on(self.inner.subscribe(pattern, move |value, address| {
+                let _ = tx.send((address.to_string(), value));
+            }))?;
+        Ok(Subscription {
+            id,
+            updates: rx,
+            inner: Arc::clone(&self.inner),
+            runtime: Arc::clone(&self.runtime),
+        })
+    }
+
+    /// Get all announced signals
+    pub fn signals(&self) -> Vec<SignalDefinition> {
+        self.inner.signals()
+    }
+
+    /// Close connection.
+    /// Disables auto-reconnect and closes the connection.
+    pub fn close(&self) {
+        self.runtime.block_on(self.inner.close())
+    }
+
+    /// The async client, for calls this wrapper doesn't cover (run them
+    /// with [`Clasp::block_on`])
+    pub fn inner(&self) -> &crate::Clasp {
+        &self.inner
+    }
+
+    /// Run a future on the client's runtime and wait for it
+    pub fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+/// Updates for a subscribed pattern as `(address, value)` pairs.
+///
+/// Iterating blocks until the next update. Dropping the subscription
+/// unsubscribes.
+pub struct Subscription {
+    id: u32,
+    updates: mpsc::Receiver<(String, Value)>,
+    inner: Arc<crate::Clasp>,
+    runtime: Arc<Runtime>,
+}
+
+impl Subscription {
+    /// Subscription ID
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Wait up to `timeout` for the next update
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<(String, Value)> {
+        self.updates.recv_timeout(timeout).ok()
+    }
+
+    /// Next update if one is waiting
+    pub fn try_recv(&self) -> Option<(String, Value)> {
+        self.updates.try_recv().ok()
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = (String, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.updates.recv().ok()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if self.inner.is_connected() {
+            let _ = self.runtime.block_on(self.inner.unsubscribe(self.id));
+        }
+    }
+}
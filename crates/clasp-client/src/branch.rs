@@ -0,0 +1,122 @@
+//! State branch helper
+//!
+//! Programs the next show on a named branch while the current one runs.
+//! Writes through a [`Branch`] land on the router's copy-on-write overlay,
+//! not live state; a branch subscription sees the branch's values over live
+//! state. At showtime an admin [`merge`](Branch::merge)s the branch into live
+//! state in one transaction.
+//!
+//! ```ignore
+//! let next = client.branch("next-show");
+//! next.set("/lights/1/level", 0.8).await?;
+//!
+//! // Preview: the branch over live state
+//! preview.branch("next-show").subscribe("/lights/**", |value, address| {
+//!     println!("{} = {:?}", address, value);
+//! }).await?;
+//!
+//! // Showtime
+//! admin.branch("next-show").merge().await?;
+//! ```
+//!
+//! Watch a branch from its own client: the param cache and subscription
+//! callbacks don't tell branch values from live ones.
+
+use clasp_core::{AckMessage, Message, SetMessage, SubscribeOptions, Value};
+
+use crate::client::Clasp;
+use crate::error::Result;
+
+/// Address prefix of branch admin operations on the router
+const ADMIN_PREFIX: &str = "/clasp/admin/branch/";
+
+/// One state branch, addressed by name
+pub struct Branch<'a> {
+    client: &'a Clasp,
+    name: String,
+}
+
+impl<'a> Branch<'a> {
+    pub(crate) fn new(client: &'a Clasp, name: &str) -> Self {
+        Self {
+            client,
+            name: name.to_string(),
+        }
+    }
+
+    /// Name of the branch
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set a param on the branch
+    pub async fn set(&self, address: &str, value: impl Into<Value>) -> Result<()> {
+        self.client
+            .send_message(&self.set_message(address, value))
+            .await
+    }
+
+    /// Set a param on the branch and wait for the router to apply it
+    pub async fn set_confirmed(
+        &self,
+        address: &str,
+        value: impl Into<Value>,
+    ) -> Result<AckMessage> {
+        match self
+            .client
+            .request(self.set_message(address, value))
+            .await?
+        {
+            Message::Ack(ack) => Ok(ack),
+            other => Err(crate::client::unexpected_answer(other)),
+        }
+    }
+
+    /// Watch the branch: a snapshot of its params over live state, then
+    /// every change seen on it. Returns the subscription ID.
+    pub async fn subscribe<F>(&self, pattern: &str, callback: F) -> Result<u32>
+    where
+        F: Fn(Value, &str) + Send + Sync + 'static,
+    {
+        let options = SubscribeOptions {
+            branch: Some(self.name.clone()),
+            ..Default::default()
+        };
+        self.client
+            .subscribe_with_options(pattern, options, callback)
+            .await
+    }
+
+    /// Apply every param on the branch to live state in one transaction
+    /// (admin scope required). The ACK lists each merged address with its
+    /// new revision.
+    pub async fn merge(&self) -> Result<AckMessage> {
+        self.client
+            .set_confirmed(&self.admin_address(), "merge")
+            .await
+    }
+
+    /// Drop the branch's changes (admin scope required)
+    pub async fn discard(&self) -> Result<AckMessage> {
+        self.client
+            .set_confirmed(&self.admin_address(), "discard")
+            .await
+    }
+
+    fn admin_address(&self) -> String {
+        format!("{}{}", ADMIN_PREFIX, self.name)
+    }
+
+    fn set_message(&self, address: &str, value: impl Into<Value>) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: value.into(),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: Some(self.name.clone()),
+        })
+    }
+}
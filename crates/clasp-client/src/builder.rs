@@ -1,6 +1,10 @@
 //! Client builder pattern
 
+use crate::client::PossessionSigner;
+use crate::interceptor::{Interceptor, Interceptors};
+use crate::reconnect::ReconnectPolicy;
 use crate::{Clasp, Result};
+use std::sync::Arc;
 
 /// Builder for Clasp client
 pub struct ClaspBuilder {
@@ -8,8 +12,11 @@ pub struct ClaspBuilder {
     name: String,
     features: Vec<String>,
     token: Option<String>,
+    possession_signer: Option<PossessionSigner>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
     reconnect: bool,
-    reconnect_interval_ms: u64,
+    reconnect_policy: ReconnectPolicy,
+    quic_config: Option<clasp_transport::QuicConfig>,
     #[cfg(feature = "p2p")]
     p2p_config: Option<clasp_core::P2PConfig>,
 }
@@ -24,10 +31,14 @@ impl ClaspBuilder {
                 "param".to_string(),
                 "event".to_string(),
                 "stream".to_string(),
+                "alias".to_string(),
             ],
             token: None,
+            possession_signer: None,
+            interceptors: Vec::new(),
             reconnect: true,
-            reconnect_interval_ms: 5000,
+            reconnect_policy: ReconnectPolicy::default(),
+            quic_config: None,
             #[cfg(feature = "p2p")]
             p2p_config: None,
         }
@@ -51,15 +62,59 @@ impl ClaspBuilder {
         self
     }
 
+    /// Sign proof of possession challenges for an audience-bound token with
+    /// the audience's private key:
+    ///
+    /// ```no_run
+    /// # use clasp_client::ClaspBuilder;
+    /// use ed25519_dalek::{Signer, SigningKey};
+    ///
+    /// # async fn example(token: &str, key: SigningKey) -> clasp_client::Result<()> {
+    /// let client = ClaspBuilder::new("ws://localhost:7330")
+    ///     .token(token)
+    ///     .possession_signer(move |payload| key.sign(payload).to_bytes().to_vec())
+    ///     .connect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn possession_signer(
+        mut self,
+        signer: impl Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Self {
+        self.possession_signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Add an interceptor for outgoing and incoming messages (see
+    /// [`crate::interceptor`])
+    pub fn interceptor(mut self, interceptor: impl Interceptor) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
     /// Enable/disable auto-reconnect
     pub fn reconnect(mut self, enabled: bool) -> Self {
         self.reconnect = enabled;
         self
     }
 
-    /// Set reconnect interval in milliseconds
+    /// Set the delay before the first reconnect attempt in milliseconds
     pub fn reconnect_interval(mut self, ms: u64) -> Self {
-        self.reconnect_interval_ms = ms;
+        self.reconnect_policy.initial_delay = std::time::Duration::from_millis(ms);
+        self
+    }
+
+    /// Set the reconnect policy (attempts, backoff and jitter)
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Set QUIC configuration used for quic:// URLs (e.g. to trust a
+    /// self-signed development certificate)
+    pub fn quic_config(mut self, config: clasp_transport::QuicConfig) -> Self {
+        self.quic_config = Some(config);
         self
     }
 
@@ -72,14 +127,40 @@ impl ClaspBuilder {
 
     /// Build and connect
     pub async fn connect(self) -> Result<Clasp> {
+        let mut client = self.build();
+        client.do_connect().await?;
+        Ok(client)
+    }
+
+    /// Build, connect and start reconnecting in the background when the
+    /// connection drops (unless `reconnect(false)`). On each reconnect the
+    /// client re-sends HELLO with its token, re-subscribes every pattern and
+    /// reports progress through [`Clasp::events`].
+    pub async fn connect_shared(self) -> Result<Arc<Clasp>> {
+        let client = Arc::new(self.connect().await?);
+        client.start_reconnect_loop();
+        Ok(client)
+    }
+
+    /// Build the client without connecting it
+    pub(crate) fn build(self) -> Clasp {
         let mut client = Clasp::new(
             &self.url,
             self.name,
             self.features,
             self.token,
             self.reconnect,
-            self.reconnect_interval_ms,
+            self.reconnect_policy.initial_delay.as_millis() as u64,
         );
+        client.set_reconnect_policy(self.reconnect_policy);
+        if let Some(signer) = self.possession_signer {
+            client.set_possession_signer(signer);
+        }
+        client.set_interceptors(Interceptors::new(self.interceptors));
+
+        if let Some(quic_config) = self.quic_config {
+            client.set_quic_config(quic_config);
+        }
 
         // Set P2P config if provided
         #[cfg(feature = "p2p")]
@@ -89,7 +170,6 @@ impl ClaspBuilder {
             }
         }
 
-        client.do_connect().await?;
-        Ok(client)
+        client
     }
 }
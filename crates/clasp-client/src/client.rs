@@ -1,32 +1,71 @@
 //! Main Clasp client implementation
 
 use bytes::Bytes;
+use clasp_core::alias::{self, InboundAliases, OutboundAliases};
+use clasp_core::frame::CORRELATION_FEATURE;
+use clasp_core::link_stats::{LinkStats, STATS_ADDRESS};
+use clasp_core::security::possession_payload;
 use clasp_core::{
-    codec, time::ClockSync, BundleMessage, ErrorMessage, GesturePhase, GetMessage, HelloMessage,
-    Message, PublishMessage, SetMessage, SignalDefinition, SignalType, SubscribeMessage,
-    SubscribeOptions, TimelineData, UnsubscribeMessage, Value, PROTOCOL_VERSION,
+    codec, time::ClockSync, AckMessage, BundleMessage, ErrorMessage, GesturePhase, GetMessage,
+    HelloMessage, Message, ParamValue, ProofMessage, PublishMessage, QueryMessage, ReplayMessage,
+    ResultMessage, SetManyMessage, SetMessage, SignalDefinition, SignalType, SnapshotMessage,
+    SubscribeAckMessage, SubscribeMessage, SubscribeOptions, TimelineData, UnsubscribeAckMessage,
+    UnsubscribeMessage, Value, WelcomeMessage, PROTOCOL_VERSION,
 };
 use clasp_transport::{
-    Transport, TransportEvent, TransportReceiver, TransportSender, WebSocketTransport,
+    QuicConfig, QuicTransport, Transport, TransportEvent, TransportReceiver, TransportSender,
+    WebSocketTransport,
 };
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
 use tracing::{debug, error, info, warn};
 
+use crate::branch::Branch;
 use crate::builder::ClaspBuilder;
 use crate::error::{ClientError, Result};
+use crate::interceptor::Interceptors;
 #[cfg(feature = "p2p")]
 use crate::p2p;
+use crate::reconnect::{ClientEvent, ReconnectPolicy};
+use crate::shadow::Shadow;
 #[cfg(feature = "p2p")]
 use clasp_core::{P2PConfig, P2P_SIGNAL_PREFIX};
 
+/// Sends of an address before the client aliases it
+const ALIAS_HOT_AFTER: u32 = 8;
+
+/// Connection events buffered for slow event receivers
+const EVENT_CAPACITY: usize = 64;
+
+/// How long GETs and correlated requests wait for the router's answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Subscription callback type
 pub type SubscriptionCallback = Box<dyn Fn(Value, &str) + Send + Sync>;
 
+/// Requests waiting for their answer or ERROR, by correlation ID
+type PendingRequests = Arc<DashMap<u32, PendingRequest>>;
+
+/// QUERYs awaiting their RESULT, oldest first. RESULT carries no
+/// correlation ID, but the router answers QUERYs in the order they arrive.
+type PendingQueries = Arc<Mutex<VecDeque<oneshot::Sender<ResultMessage>>>>;
+
+/// A request awaiting its answer
+struct PendingRequest {
+    tx: oneshot::Sender<Result<Message>>,
+    /// Params from earlier chunks of a SNAPSHOT answer
+    partial: Vec<ParamValue>,
+}
+
+/// Signs a WELCOME challenge with the private key an audience-bound token
+/// is issued to, returning the Ed25519 signature
+pub type PossessionSigner = Arc<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
 /// A Clasp client
 pub struct Clasp {
     url: String,
@@ -34,7 +73,13 @@ pub struct Clasp {
     features: Vec<String>,
     token: Option<String>,
     reconnect: bool,
-    reconnect_interval_ms: u64,
+    reconnect_policy: ReconnectPolicy,
+
+    /// Answers proof of possession challenges for the token
+    possession_signer: Option<PossessionSigner>,
+
+    /// Interceptors for outgoing and incoming messages
+    interceptors: Interceptors,
 
     /// Session ID (set after connect)
     session_id: RwLock<Option<String>>,
@@ -48,9 +93,15 @@ pub struct Clasp {
     /// Local param cache
     params: Arc<DashMap<String, Value>>,
 
+    /// Topic aliases for addresses we send (set when the router supports them)
+    aliases: tokio::sync::Mutex<Option<OutboundAliases>>,
+
     /// Subscriptions
     subscriptions: Arc<DashMap<u32, (String, SubscriptionCallback)>>,
 
+    /// Options of subscriptions made with any, resent when resubscribing
+    subscription_options: DashMap<u32, SubscribeOptions>,
+
     /// Subscription ID counter
     next_sub_id: AtomicU32,
 
@@ -58,7 +109,19 @@ pub struct Clasp {
     clock: RwLock<ClockSync>,
 
     /// Pending get requests
-    pending_gets: Arc<DashMap<String, oneshot::Sender<Value>>>,
+    pending_gets: Arc<DashMap<String, oneshot::Sender<Result<Value>>>>,
+
+    /// Whether the router echoes correlation IDs (from WELCOME)
+    correlation: AtomicBool,
+
+    /// Correlation ID counter
+    next_correlation_id: AtomicU32,
+
+    /// Requests sent with [`Clasp::request`] awaiting an answer
+    pending_requests: PendingRequests,
+
+    /// QUERYs sent with [`Clasp::query`] awaiting their RESULT
+    pending_queries: PendingQueries,
 
     /// Announced signals (from server)
     signals: Arc<DashMap<String, SignalDefinition>>,
@@ -69,8 +132,8 @@ pub struct Clasp {
     /// Reconnect attempt counter
     reconnect_attempts: Arc<AtomicU32>,
 
-    /// Max reconnect attempts (0 = unlimited)
-    max_reconnect_attempts: u32,
+    /// Connection lifecycle events
+    events: broadcast::Sender<ClientEvent>,
 
     /// Flag to indicate intentional close (don't reconnect)
     intentionally_closed: Arc<AtomicBool>,
@@ -78,6 +141,9 @@ pub struct Clasp {
     /// Notify for triggering reconnect
     reconnect_notify: Arc<Notify>,
 
+    /// QUIC settings for quic:// URLs
+    quic_config: QuicConfig,
+
     /// P2P config (optional, feature-gated)
     #[cfg(feature = "p2p")]
     p2p_config: Option<P2PConfig>,
@@ -103,21 +169,33 @@ impl Clasp {
             features,
             token,
             reconnect,
-            reconnect_interval_ms,
+            reconnect_policy: ReconnectPolicy {
+                initial_delay: Duration::from_millis(reconnect_interval_ms),
+                ..Default::default()
+            },
+            possession_signer: None,
+            interceptors: Interceptors::default(),
             session_id: RwLock::new(None),
             connected: Arc::new(RwLock::new(false)),
             sender: RwLock::new(None),
             params: Arc::new(DashMap::new()),
+            aliases: tokio::sync::Mutex::new(None),
             subscriptions: Arc::new(DashMap::new()),
+            subscription_options: DashMap::new(),
             next_sub_id: AtomicU32::new(1),
             clock: RwLock::new(ClockSync::new()),
             pending_gets: Arc::new(DashMap::new()),
+            correlation: AtomicBool::new(false),
+            next_correlation_id: AtomicU32::new(1),
+            pending_requests: Arc::new(DashMap::new()),
+            pending_queries: Arc::new(Mutex::new(VecDeque::new())),
             signals: Arc::new(DashMap::new()),
             last_error: Arc::new(RwLock::new(None)),
             reconnect_attempts: Arc::new(AtomicU32::new(0)),
-            max_reconnect_attempts: 10,
+            events: broadcast::channel(EVENT_CAPACITY).0,
             intentionally_closed: Arc::new(AtomicBool::new(false)),
             reconnect_notify: Arc::new(Notify::new()),
+            quic_config: QuicConfig::default(),
             #[cfg(feature = "p2p")]
             p2p_config: None,
             #[cfg(feature = "p2p")]
@@ -125,6 +203,26 @@ impl Clasp {
         }
     }
 
+    /// Set reconnect policy (internal, called by builder)
+    pub(crate) fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
+    /// Set the proof of possession signer (internal, called by builder)
+    pub(crate) fn set_possession_signer(&mut self, signer: PossessionSigner) {
+        self.possession_signer = Some(signer);
+    }
+
+    /// Set interceptors (internal, called by builder)
+    pub(crate) fn set_interceptors(&mut self, interceptors: Interceptors) {
+        self.interceptors = interceptors;
+    }
+
+    /// Set QUIC configuration (internal, called by builder)
+    pub(crate) fn set_quic_config(&mut self, config: QuicConfig) {
+        self.quic_config = config;
+    }
+
     /// Set P2P configuration (internal, called by builder)
     #[cfg(feature = "p2p")]
     pub(crate) fn set_p2p_config(&mut self, config: P2PConfig) {
@@ -149,8 +247,7 @@ impl Clasp {
 
         info!("Connecting to {}", self.url);
 
-        // Connect WebSocket
-        let (sender, mut receiver) = <WebSocketTransport as Transport>::connect(&self.url).await?;
+        let (sender, mut receiver) = connect_transport(&self.url, &self.quic_config).await?;
 
         // Create send channel
         let (tx, mut rx) = mpsc::channel::<Bytes>(100);
@@ -159,7 +256,6 @@ impl Clasp {
         let connected = self.connected.clone();
 
         // Spawn sender task
-        let sender = Arc::new(sender);
         let sender_clone = sender.clone();
         tokio::spawn(async move {
             while let Some(data) = rx.recv().await {
@@ -177,6 +273,7 @@ impl Clasp {
             features: self.features.clone(),
             capabilities: None,
             token: self.token.clone(),
+            session_resume: None,
         });
 
         self.send_message(&hello).await?;
@@ -187,8 +284,10 @@ impl Clasp {
                 Some(TransportEvent::Data(data)) => {
                     match codec::decode(&data) {
                         Ok((Message::Welcome(welcome), _)) => {
+                            self.answer_challenge(&welcome).await?;
                             *self.session_id.write() = Some(welcome.session.clone());
                             *connected.write() = true;
+                            self.negotiate(&welcome.features).await;
 
                             // Sync clock
                             self.clock.write().process_sync(
@@ -242,8 +341,16 @@ impl Clasp {
                             }
 
                             info!("Connected, session: {}", welcome.session);
+                            let _ = self.events.send(ClientEvent::Connected {
+                                session: welcome.session,
+                            });
                             break;
                         }
+                        Ok((Message::Error(error), _)) => {
+                            let error = ClientError::from(error);
+                            warn!("Handshake refused: {}", error);
+                            return Err(error);
+                        }
                         Ok((msg, _)) => {
                             debug!("Received during handshake: {:?}", msg);
                         }
@@ -277,20 +384,30 @@ impl Clasp {
         let params = Arc::clone(&self.params);
         let subscriptions = Arc::clone(&self.subscriptions);
         let pending_gets = Arc::clone(&self.pending_gets);
+        let pending_requests = Arc::clone(&self.pending_requests);
+        let pending_queries = Arc::clone(&self.pending_queries);
         let signals = Arc::clone(&self.signals);
         let last_error = Arc::clone(&self.last_error);
         let connected_clone = Arc::clone(&self.connected);
         let reconnect_notify = Arc::clone(&self.reconnect_notify);
         let intentionally_closed = Arc::clone(&self.intentionally_closed);
         let reconnect_enabled = self.reconnect;
+        let events = self.events.clone();
+        let interceptors = self.interceptors.clone();
         #[cfg(feature = "p2p")]
         let p2p_manager = self.p2p_manager.clone();
 
         tokio::spawn(async move {
+            let mut aliases = InboundAliases::new(alias::MAX_ALIAS);
             while let Some(event) = receiver.recv().await {
                 match event {
                     TransportEvent::Data(data) => {
-                        if let Ok((msg, _)) = codec::decode(&data) {
+                        if let Ok((mut msg, _)) = codec::decode(&data) {
+                            if !resolve_aliases(&mut aliases, &mut msg)
+                                || !interceptors.incoming(&mut msg)
+                            {
+                                continue;
+                            }
                             #[cfg(feature = "p2p")]
                             {
                                 // Forward P2P signals to P2P manager (handled in subscription callback)
@@ -301,6 +418,8 @@ impl Clasp {
                                 &params,
                                 &subscriptions,
                                 &pending_gets,
+                                &pending_requests,
+                                &pending_queries,
                                 &signals,
                                 &last_error,
                             );
@@ -309,6 +428,10 @@ impl Clasp {
                     TransportEvent::Disconnected { reason } => {
                         info!("Disconnected: {:?}", reason);
                         *connected_clone.write() = false;
+                        // Requests in flight won't be answered on a new session
+                        pending_requests.clear();
+                        pending_queries.lock().clear();
+                        let _ = events.send(ClientEvent::Dropped { reason });
 
                         // Trigger reconnect if enabled and not intentionally closed
                         if reconnect_enabled && !intentionally_closed.load(Ordering::SeqCst) {
@@ -327,7 +450,8 @@ impl Clasp {
         Ok(())
     }
 
-    /// Start the reconnect loop (call after initial connect)
+    /// Start the reconnect loop (call after initial connect, or connect with
+    /// [`ClaspBuilder::connect_shared`](crate::ClaspBuilder::connect_shared))
     pub fn start_reconnect_loop(self: &Arc<Self>) {
         if !self.reconnect {
             return;
@@ -343,27 +467,31 @@ impl Clasp {
                     break;
                 }
 
-                // Attempt reconnection with exponential backoff
+                // Attempt reconnection with exponential backoff, or after the
+                // server's hint when it deferred us with ERROR 504
+                let mut retry_hint: Option<Duration> = None;
                 loop {
-                    let attempts = client.reconnect_attempts.fetch_add(1, Ordering::SeqCst);
-
-                    if client.max_reconnect_attempts > 0
-                        && attempts >= client.max_reconnect_attempts
-                    {
-                        error!(
-                            "Max reconnect attempts ({}) reached",
-                            client.max_reconnect_attempts
-                        );
+                    let policy = &client.reconnect_policy;
+                    let attempts = match retry_hint {
+                        // Deferrals don't count towards max_attempts
+                        Some(_) => client.reconnect_attempts.load(Ordering::SeqCst),
+                        None => client.reconnect_attempts.fetch_add(1, Ordering::SeqCst),
+                    };
+
+                    if policy.gives_up(attempts) {
+                        error!("Max reconnect attempts ({}) reached", policy.max_attempts);
+                        let _ = client.events.send(ClientEvent::GaveUp { attempts });
                         break;
                     }
 
-                    // Exponential backoff: base * 1.5^attempts, max 30 seconds
-                    let base_ms = client.reconnect_interval_ms;
-                    let delay_ms =
-                        (base_ms as f64 * 1.5_f64.powi(attempts as i32)).min(30000.0) as u64;
+                    let delay = retry_hint.take().unwrap_or_else(|| policy.delay(attempts));
 
-                    info!("Reconnect attempt {} in {}ms", attempts + 1, delay_ms);
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    info!("Reconnect attempt {} in {:?}", attempts + 1, delay);
+                    let _ = client.events.send(ClientEvent::Reconnecting {
+                        attempt: attempts + 1,
+                        delay,
+                    });
+                    tokio::time::sleep(delay).await;
 
                     if client.intentionally_closed.load(Ordering::SeqCst) {
                         break;
@@ -382,6 +510,10 @@ impl Clasp {
                             }
                             break;
                         }
+                        Err(ClientError::RetryAfter(delay)) => {
+                            info!("Server busy, retrying after {:?}", delay);
+                            retry_hint = Some(delay);
+                        }
                         Err(e) => {
                             warn!("Reconnect failed: {}", e);
                         }
@@ -391,19 +523,22 @@ impl Clasp {
         });
     }
 
+    /// Wake the reconnect loop as if the connection had dropped
+    pub(crate) fn schedule_reconnect(&self) {
+        self.reconnect_notify.notify_one();
+    }
+
     /// Internal reconnect attempt
-    async fn try_reconnect(&self) -> Result<()> {
+    pub(crate) async fn try_reconnect(&self) -> Result<()> {
         info!("Attempting to reconnect to {}", self.url);
 
-        // Connect WebSocket
-        let (sender, mut receiver) = <WebSocketTransport as Transport>::connect(&self.url).await?;
+        let (sender, mut receiver) = connect_transport(&self.url, &self.quic_config).await?;
 
         // Create send channel
         let (tx, mut rx) = mpsc::channel::<Bytes>(100);
         *self.sender.write() = Some(tx);
 
         // Spawn sender task
-        let sender = Arc::new(sender);
         let sender_clone = sender.clone();
         tokio::spawn(async move {
             while let Some(data) = rx.recv().await {
@@ -421,6 +556,7 @@ impl Clasp {
             features: self.features.clone(),
             capabilities: None,
             token: self.token.clone(),
+            session_resume: None,
         });
 
         self.send_message(&hello).await?;
@@ -433,8 +569,10 @@ impl Clasp {
             match tokio::time::timeout_at(deadline, receiver.recv()).await {
                 Ok(Some(TransportEvent::Data(data))) => match codec::decode(&data) {
                     Ok((Message::Welcome(welcome), _)) => {
+                        self.answer_challenge(&welcome).await?;
                         *self.session_id.write() = Some(welcome.session.clone());
                         *self.connected.write() = true;
+                        self.negotiate(&welcome.features).await;
 
                         self.clock.write().process_sync(
                             clasp_core::time::now(),
@@ -444,8 +582,14 @@ impl Clasp {
                         );
 
                         info!("Reconnected, session: {}", welcome.session);
+                        let _ = self.events.send(ClientEvent::Connected {
+                            session: welcome.session,
+                        });
                         break;
                     }
+                    Ok((Message::Error(error), _)) => {
+                        return Err(ClientError::from(error));
+                    }
                     Ok((msg, _)) => {
                         debug!("Received during reconnect handshake: {:?}", msg);
                     }
@@ -477,31 +621,53 @@ impl Clasp {
         let params = Arc::clone(&self.params);
         let subscriptions = Arc::clone(&self.subscriptions);
         let pending_gets = Arc::clone(&self.pending_gets);
+        let pending_requests = Arc::clone(&self.pending_requests);
+        let pending_queries = Arc::clone(&self.pending_queries);
         let signals = Arc::clone(&self.signals);
         let last_error = Arc::clone(&self.last_error);
         let connected_clone = Arc::clone(&self.connected);
         let reconnect_notify = Arc::clone(&self.reconnect_notify);
         let intentionally_closed = Arc::clone(&self.intentionally_closed);
         let reconnect_enabled = self.reconnect;
+        let events = self.events.clone();
+        let interceptors = self.interceptors.clone();
 
         tokio::spawn(async move {
+            let mut aliases = InboundAliases::new(alias::MAX_ALIAS);
+            let mut resynced = false;
             while let Some(event) = receiver.recv().await {
                 match event {
                     TransportEvent::Data(data) => {
-                        if let Ok((msg, _)) = codec::decode(&data) {
+                        if let Ok((mut msg, _)) = codec::decode(&data) {
+                            if !resolve_aliases(&mut aliases, &mut msg)
+                                || !interceptors.incoming(&mut msg)
+                            {
+                                continue;
+                            }
                             handle_message(
                                 &msg,
                                 &params,
                                 &subscriptions,
                                 &pending_gets,
+                                &pending_requests,
+                                &pending_queries,
                                 &signals,
                                 &last_error,
                             );
+                            // The router follows WELCOME with a full snapshot
+                            if !resynced && matches!(msg, Message::Snapshot(_)) {
+                                resynced = true;
+                                let _ = events.send(ClientEvent::SnapshotResynced);
+                            }
                         }
                     }
                     TransportEvent::Disconnected { reason } => {
                         info!("Disconnected: {:?}", reason);
                         *connected_clone.write() = false;
+                        // Requests in flight won't be answered on a new session
+                        pending_requests.clear();
+                        pending_queries.lock().clear();
+                        let _ = events.send(ClientEvent::Dropped { reason });
 
                         if reconnect_enabled && !intentionally_closed.load(Ordering::SeqCst) {
                             reconnect_notify.notify_one();
@@ -529,11 +695,16 @@ impl Clasp {
             .collect();
 
         for (id, pattern) in subs {
+            let options = self
+                .subscription_options
+                .get(&id)
+                .map(|options| options.clone())
+                .unwrap_or_default();
             let msg = Message::Subscribe(SubscribeMessage {
                 id,
                 pattern: pattern.clone(),
                 types: vec![],
-                options: Some(SubscribeOptions::default()),
+                options: Some(options),
             });
 
             self.send_message(&msg).await?;
@@ -543,6 +714,15 @@ impl Clasp {
         Ok(())
     }
 
+    /// Subscribe to connection lifecycle events.
+    ///
+    /// Only events after this call are delivered; the initial connection
+    /// completes before [`ClaspBuilder::connect`] returns, so check
+    /// [`is_connected`](Self::is_connected) for the current state.
+    pub fn events(&self) -> broadcast::Receiver<ClientEvent> {
+        self.events.subscribe()
+    }
+
     /// Check if connected
     pub fn is_connected(&self) -> bool {
         *self.connected.read()
@@ -558,12 +738,79 @@ impl Clasp {
         self.clock.read().server_time()
     }
 
+    /// Send PROOF for a WELCOME challenge, signed with the key the token is
+    /// bound to
+    async fn answer_challenge(&self, welcome: &WelcomeMessage) -> Result<()> {
+        let Some(ref challenge) = welcome.challenge else {
+            return Ok(());
+        };
+        let Some(ref signer) = self.possession_signer else {
+            return Err(ClientError::ConnectionFailed(
+                "router requires proof of possession but no signer is configured".to_string(),
+            ));
+        };
+        let payload = possession_payload(&welcome.session, challenge);
+        let proof = Message::Proof(ProofMessage {
+            signature: signer(&payload),
+        });
+        self.send_message(&proof).await
+    }
+
     /// Send a raw message
-    async fn send_message(&self, message: &Message) -> Result<()> {
-        let data = codec::encode(message)?;
+    pub(crate) async fn send_message(&self, message: &Message) -> Result<()> {
+        self.send_correlated(message, None).await
+    }
+
+    /// Send a message, asking the router to echo `correlation_id` in its answer
+    async fn send_correlated(&self, message: &Message, correlation_id: Option<u32>) -> Result<()> {
+        let intercepted;
+        let message = if self.interceptors.intercepts_outgoing(message) {
+            let mut message = message.clone();
+            self.interceptors.outgoing(&mut message)?;
+            intercepted = message;
+            &intercepted
+        } else {
+            message
+        };
+        if matches!(message, Message::Set(_) | Message::Publish(_)) {
+            // Hold the table until both frames are queued so an aliased frame
+            // can't overtake its binding
+            let mut aliases = self.aliases.lock().await;
+            if let Some(aliases) = aliases.as_mut() {
+                let mut message = message.clone();
+                if let Some(bind) = aliases.apply(&mut message) {
+                    let address = bind.address.clone();
+                    let data = codec::encode(&Message::Alias(bind))?;
+                    if let Err(e) = self.send_raw(data).await {
+                        aliases.forget(&address);
+                        return Err(e);
+                    }
+                }
+                let data = encode_request(&message, correlation_id)?;
+                return self.send_raw(data).await;
+            }
+        }
+        let data = encode_request(message, correlation_id)?;
         self.send_raw(data).await
     }
 
+    /// Apply the features the router advertised in WELCOME
+    async fn negotiate(&self, router_features: &[String]) {
+        self.correlation.store(
+            router_features.iter().any(|f| f == CORRELATION_FEATURE),
+            Ordering::SeqCst,
+        );
+        self.reset_aliases(router_features).await;
+    }
+
+    /// Start a fresh alias table for a new session, if the router accepts aliases
+    async fn reset_aliases(&self, router_features: &[String]) {
+        *self.aliases.lock().await = router_features
+            .iter()
+            .any(|f| f == alias::FEATURE)
+            .then(|| OutboundAliases::new(alias::MIN_ALIASES, ALIAS_HOT_AFTER));
+    }
+
     /// Send raw bytes
     async fn send_raw(&self, data: Bytes) -> Result<()> {
         // Clone the sender to avoid holding the lock across await
@@ -584,6 +831,20 @@ impl Clasp {
 
     /// Subscribe to an address pattern
     pub async fn subscribe<F>(&self, pattern: &str, callback: F) -> Result<u32>
+    where
+        F: Fn(Value, &str) + Send + Sync + 'static,
+    {
+        self.subscribe_with_options(pattern, SubscribeOptions::default(), callback)
+            .await
+    }
+
+    /// Subscribe with options, kept for resubscribing after a reconnect
+    pub(crate) async fn subscribe_with_options<F>(
+        &self,
+        pattern: &str,
+        options: SubscribeOptions,
+        callback: F,
+    ) -> Result<u32>
     where
         F: Fn(Value, &str) + Send + Sync + 'static,
     {
@@ -592,13 +853,16 @@ impl Clasp {
         // Store callback
         self.subscriptions
             .insert(id, (pattern.to_string(), Box::new(callback)));
+        if options != SubscribeOptions::default() {
+            self.subscription_options.insert(id, options.clone());
+        }
 
         // Send subscribe message
         let msg = Message::Subscribe(SubscribeMessage {
             id,
             pattern: pattern.to_string(),
             types: vec![],
-            options: Some(SubscribeOptions::default()),
+            options: Some(options),
         });
 
         self.send_message(&msg).await?;
@@ -607,6 +871,46 @@ impl Clasp {
         Ok(id)
     }
 
+    /// Subscribe and wait until the subscription is live
+    ///
+    /// Returns the router's SUBSCRIBE_ACK with the subscription ID, the number
+    /// of params in the snapshot that follows and the options in effect.
+    /// Unlike [`subscribe`](Self::subscribe), a refused pattern (bad syntax,
+    /// missing scope) fails here instead of only showing up in
+    /// [`last_error`](Self::last_error).
+    pub async fn subscribe_confirmed<F>(
+        &self,
+        pattern: &str,
+        callback: F,
+    ) -> Result<SubscribeAckMessage>
+    where
+        F: Fn(Value, &str) + Send + Sync + 'static,
+    {
+        let id = self.next_sub_id.fetch_add(1, Ordering::SeqCst);
+        self.subscriptions
+            .insert(id, (pattern.to_string(), Box::new(callback)));
+
+        let msg = Message::Subscribe(SubscribeMessage {
+            id,
+            pattern: pattern.to_string(),
+            types: vec![],
+            options: Some(SubscribeOptions::default()),
+        });
+        match self.request(msg).await {
+            Ok(Message::SubscribeAck(ack)) => {
+                debug!(
+                    "Subscribed to {} (id: {}, {} params)",
+                    pattern, id, ack.matched
+                );
+                Ok(ack)
+            }
+            other => {
+                self.subscriptions.remove(&id);
+                Err(unexpected_answer(other?))
+            }
+        }
+    }
+
     /// Shorthand for subscribe
     pub async fn on<F>(&self, pattern: &str, callback: F) -> Result<u32>
     where
@@ -618,6 +922,7 @@ impl Clasp {
     /// Unsubscribe
     pub async fn unsubscribe(&self, id: u32) -> Result<()> {
         self.subscriptions.remove(&id);
+        self.subscription_options.remove(&id);
 
         let msg = Message::Unsubscribe(UnsubscribeMessage { id });
         self.send_message(&msg).await?;
@@ -625,6 +930,18 @@ impl Clasp {
         Ok(())
     }
 
+    /// Unsubscribe and wait for the router to drop the subscription
+    pub async fn unsubscribe_confirmed(&self, id: u32) -> Result<UnsubscribeAckMessage> {
+        self.subscriptions.remove(&id);
+        self.subscription_options.remove(&id);
+
+        let msg = Message::Unsubscribe(UnsubscribeMessage { id });
+        match self.request(msg).await? {
+            Message::UnsubscribeAck(ack) => Ok(ack),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
     /// Set a parameter value
     pub async fn set(&self, address: &str, value: impl Into<Value>) -> Result<()> {
         let msg = Message::Set(SetMessage {
@@ -634,11 +951,120 @@ impl Clasp {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         self.send_message(&msg).await
     }
 
+    /// Write many params under one base address in a single frame, applied
+    /// as one transaction (see [`SetManyMessage`])
+    ///
+    /// ```no_run
+    /// # async fn example(client: &clasp_client::Clasp) -> clasp_client::Result<()> {
+    /// use clasp_core::{SetManyMessage, Value};
+    ///
+    /// let levels: Vec<Value> = (0..512).map(|_| Value::Int(255)).collect();
+    /// client.set_many(SetManyMessage::run("/dmx/1", 1, levels)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_many(&self, batch: SetManyMessage) -> Result<()> {
+        self.send_message(&Message::SetMany(batch)).await
+    }
+
+    /// Write a batch and wait for the router to apply it
+    ///
+    /// If any entry is refused, none take effect and the refusal comes back
+    /// as [`ClientError::Server`]. The ACK carries the last revision.
+    pub async fn set_many_confirmed(&self, batch: SetManyMessage) -> Result<AckMessage> {
+        match self.request(Message::SetMany(batch)).await? {
+            Message::Ack(ack) => Ok(ack),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
+    /// Device shadow under `base` (see [`crate::shadow`])
+    pub fn shadow(&self, base: &str) -> Shadow<'_> {
+        Shadow::new(self, base)
+    }
+
+    /// State branch `name` (see [`crate::branch`])
+    pub fn branch(&self, name: &str) -> Branch<'_> {
+        Branch::new(self, name)
+    }
+
+    /// Set a parameter value and wait for the router to apply it
+    ///
+    /// Returns the ACK with the new revision, or the router's refusal as
+    /// [`ClientError::Server`].
+    pub async fn set_confirmed(
+        &self,
+        address: &str,
+        value: impl Into<Value>,
+    ) -> Result<AckMessage> {
+        let msg = Message::Set(SetMessage {
+            address: address.to_string(),
+            value: value.into(),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+
+        match self.request(msg).await? {
+            Message::Ack(ack) => Ok(ack),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
+    /// Send a request and wait for the router's answer to it
+    ///
+    /// The message goes out with a fresh correlation ID, and the returned
+    /// future resolves with the answer or fails with the ERROR that carries
+    /// the same ID. The router answers SET, PUBLISH, ANNOUNCE and BUNDLE
+    /// with ACK, SUBSCRIBE with SUBSCRIBE_ACK, UNSUBSCRIBE with
+    /// UNSUBSCRIBE_ACK and GET with SNAPSHOT (chunks are joined); other
+    /// messages time out. Needs a router that
+    /// advertises correlation IDs in WELCOME.
+    pub async fn request(&self, message: Message) -> Result<Message> {
+        if !self.correlation.load(Ordering::SeqCst) {
+            return Err(ClientError::Other(
+                "router does not support request correlation".to_string(),
+            ));
+        }
+
+        let id = self.next_correlation_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests.insert(
+            id,
+            PendingRequest {
+                tx,
+                partial: Vec::new(),
+            },
+        );
+
+        if let Err(e) = self.send_correlated(&message, Some(id)).await {
+            self.pending_requests.remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            // Dropped when the connection went away
+            Ok(Err(_)) => Err(ClientError::ConnectionFailed(
+                "connection lost before the router answered".to_string(),
+            )),
+            Err(_) => {
+                self.pending_requests.remove(&id);
+                Err(ClientError::Timeout)
+            }
+        }
+    }
+
     /// Set with lock
     pub async fn set_locked(&self, address: &str, value: impl Into<Value>) -> Result<()> {
         let msg = Message::Set(SetMessage {
@@ -648,6 +1074,8 @@ impl Clasp {
             lock: true,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         self.send_message(&msg).await
@@ -662,6 +1090,8 @@ impl Clasp {
             lock: false,
             unlock: true,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         self.send_message(&msg).await
@@ -681,11 +1111,19 @@ impl Clasp {
             lock: false,
             unlock: false,
             ttl: Some(ttl),
+            trace: false,
+            branch: None,
         });
 
         self.send_message(&msg).await
     }
 
+    /// Set a session-scoped value that the router deletes when this client disconnects
+    pub async fn set_ephemeral(&self, address: &str, value: impl Into<Value>) -> Result<()> {
+        self.set_with_ttl(address, value, clasp_core::Ttl::Session)
+            .await
+    }
+
     /// Get current value (cached or request)
     pub async fn get(&self, address: &str) -> Result<Value> {
         // Check cache first
@@ -700,12 +1138,13 @@ impl Clasp {
 
         let msg = Message::Get(GetMessage {
             address: address.to_string(),
+            depth: None,
         });
         self.send_message(&msg).await?;
 
         // Wait for response (with timeout)
-        match tokio::time::timeout(std::time::Duration::from_secs(5), rx).await {
-            Ok(Ok(value)) => Ok(value),
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
             Ok(Err(_)) => {
                 // Cancelled - remove from pending
                 self.pending_gets.remove(&address_key);
@@ -719,6 +1158,70 @@ impl Clasp {
         }
     }
 
+    /// Fetch every param matching `pattern` without subscribing
+    ///
+    /// `depth` limits matches to that many segments below the part of the
+    /// pattern before its first wildcard, so `("/lights/**", Some(1))`
+    /// returns `/lights/a` but not `/lights/a/level`. Needs a router that
+    /// advertises correlation IDs in WELCOME.
+    pub async fn get_matching(&self, pattern: &str, depth: Option<u8>) -> Result<Vec<ParamValue>> {
+        let msg = Message::Get(GetMessage {
+            address: pattern.to_string(),
+            depth,
+        });
+        match self.request(msg).await? {
+            Message::Snapshot(snapshot) => Ok(snapshot.params),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
+    /// This session's link health as the router sees it
+    ///
+    /// Reports, for each subscription, how many updates the router handed
+    /// to the transport and how many it dropped because this client wasn't
+    /// reading fast enough, plus the router's send queue depth for this
+    /// connection. Poll it to show link health or to back off a send rate.
+    /// Needs a router that advertises correlation IDs in WELCOME.
+    pub async fn link_stats(&self) -> Result<LinkStats> {
+        let msg = Message::Get(GetMessage {
+            address: STATS_ADDRESS.to_string(),
+            depth: None,
+        });
+        match self.request(msg).await? {
+            Message::Snapshot(snapshot) => snapshot
+                .params
+                .iter()
+                .find(|p| p.address == STATS_ADDRESS)
+                .and_then(|p| LinkStats::from_value(&p.value))
+                .ok_or_else(|| ClientError::Other("router doesn't report link stats".to_string())),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
+    /// Ask the router for the signals matching a QUERY
+    ///
+    /// Resolves with the router's RESULT. Set `limit` to page through a
+    /// large registry, passing each page's `next_cursor` back as `cursor`.
+    pub async fn query(&self, query: QueryMessage) -> Result<ResultMessage> {
+        let (tx, rx) = oneshot::channel();
+        // Queue before sending so the answer can't arrive first. A QUERY
+        // that times out stays queued until its late RESULT pops it.
+        self.pending_queries.lock().push_back(tx);
+        if let Err(e) = self.send_message(&Message::Query(query)).await {
+            self.pending_queries.lock().pop_back();
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => Ok(result),
+            // Dropped when the connection went away
+            Ok(Err(_)) => Err(ClientError::ConnectionFailed(
+                "connection lost before the router answered".to_string(),
+            )),
+            Err(_) => Err(ClientError::Timeout),
+        }
+    }
+
     /// Emit an event
     pub async fn emit(&self, address: &str, payload: impl Into<Value>) -> Result<()> {
         let msg = Message::Publish(PublishMessage {
@@ -798,6 +1301,34 @@ impl Clasp {
         self.send_message(&msg).await
     }
 
+    /// Ask the router to replay journaled SETs and PUBLISHes
+    ///
+    /// Entries arrive like live updates, through the cache and the
+    /// subscriptions matching their addresses. With `timed` set they are
+    /// spaced as they were recorded, so a recorded gesture plays back with
+    /// every move at its original timing. Needs a router with a journal.
+    pub async fn replay(&self, replay: ReplayMessage) -> Result<()> {
+        self.send_message(&Message::Replay(replay)).await
+    }
+
+    /// Replay one page and wait until it has arrived
+    ///
+    /// Routers answer a REPLAY with at most one page of entries. Returns
+    /// the cursor for the next page, or None once everything matching has
+    /// been replayed, so a whole range can be fetched page by page:
+    ///
+    /// ```ignore
+    /// while let Some(cursor) = client.replay_page(replay.clone()).await? {
+    ///     replay.cursor = Some(cursor);
+    /// }
+    /// ```
+    pub async fn replay_page(&self, replay: ReplayMessage) -> Result<Option<String>> {
+        match self.request(Message::Replay(replay)).await? {
+            Message::Ack(ack) => Ok(ack.cursor),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
     /// Publish timeline automation
     ///
     /// Timelines are pre-computed automation curves with keyframes.
@@ -845,6 +1376,24 @@ impl Clasp {
         self.send_message(&msg).await
     }
 
+    /// Send an atomic bundle and wait for the router to apply it
+    ///
+    /// The SETs are applied as one transaction: if any is refused, none
+    /// take effect and the refusal comes back as [`ClientError::Server`].
+    /// On success the ACK's `results` carry each SET's new revision in
+    /// bundle order.
+    pub async fn bundle_confirmed(&self, messages: Vec<Message>) -> Result<AckMessage> {
+        let msg = Message::Bundle(BundleMessage {
+            timestamp: None,
+            messages,
+        });
+
+        match self.request(msg).await? {
+            Message::Ack(ack) => Ok(ack),
+            other => Err(unexpected_answer(other)),
+        }
+    }
+
     /// Send scheduled bundle
     pub async fn bundle_at(&self, messages: Vec<Message>, time: u64) -> Result<()> {
         let msg = Message::Bundle(BundleMessage {
@@ -999,12 +1548,79 @@ impl Clasp {
     }
 }
 
+/// Open the transport for `url`: ws:// and wss:// use WebSocket, quic://
+/// opens a bidirectional stream on a new QUIC connection.
+async fn connect_transport(
+    url: &str,
+    quic_config: &QuicConfig,
+) -> Result<(Arc<dyn TransportSender>, Box<dyn TransportReceiver>)> {
+    let Some(authority) = url.strip_prefix("quic://") else {
+        let (sender, receiver) = <WebSocketTransport as Transport>::connect(url).await?;
+        return Ok((Arc::new(sender), Box::new(receiver)));
+    };
+
+    let authority = authority.split('/').next().unwrap_or_default();
+    let (host, port) = authority
+        .rsplit_once(':')
+        .filter(|(host, port)| !host.is_empty() && !port.contains(']'))
+        .ok_or_else(|| {
+            ClientError::ConnectionFailed(format!("expected quic://host:port, got {}", url))
+        })?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| ClientError::ConnectionFailed(format!("invalid port in {}", url)))?;
+    let server_name = host.trim_start_matches('[').trim_end_matches(']');
+    let addr = tokio::net::lookup_host((server_name, port))
+        .await
+        .map_err(|e| ClientError::ConnectionFailed(e.to_string()))?
+        .next()
+        .ok_or_else(|| ClientError::ConnectionFailed(format!("could not resolve {}", host)))?;
+
+    let transport = QuicTransport::new_client_with_config(quic_config.clone())?;
+    let connection = transport.connect(addr, server_name).await?;
+    let (sender, receiver) = connection.open_bi().await?;
+    Ok((Arc::new(sender), Box::new(receiver)))
+}
+
+/// Apply ALIAS bindings from the router and resolve aliased addresses.
+/// Returns `false` if the message was consumed or can't be delivered.
+fn resolve_aliases(aliases: &mut InboundAliases, msg: &mut Message) -> bool {
+    let result = match msg {
+        Message::Alias(bind) => aliases.bind(bind).map(|_| false),
+        _ => aliases.resolve(msg).map(|_| true),
+    };
+    result.unwrap_or_else(|e| {
+        warn!("Dropping message from server: {}", e);
+        false
+    })
+}
+
+/// Encode a message, with a correlation ID if the caller awaits the answer
+fn encode_request(message: &Message, correlation_id: Option<u32>) -> Result<Bytes> {
+    let data = match correlation_id {
+        Some(id) => codec::encode_with_correlation(message, id)?,
+        None => codec::encode(message)?,
+    };
+    Ok(data)
+}
+
+/// An answer of the wrong kind for a request
+pub(crate) fn unexpected_answer(answer: Message) -> ClientError {
+    ClientError::Other(format!(
+        "unexpected answer from router: {:?}",
+        answer.type_code()
+    ))
+}
+
 /// Handle incoming message
+#[allow(clippy::too_many_arguments)]
 fn handle_message(
     msg: &Message,
     params: &Arc<DashMap<String, Value>>,
     subscriptions: &Arc<DashMap<u32, (String, SubscriptionCallback)>>,
-    pending_gets: &Arc<DashMap<String, oneshot::Sender<Value>>>,
+    pending_gets: &Arc<DashMap<String, oneshot::Sender<Result<Value>>>>,
+    pending_requests: &PendingRequests,
+    pending_queries: &PendingQueries,
     signals: &Arc<DashMap<String, SignalDefinition>>,
     last_error: &Arc<RwLock<Option<ErrorMessage>>>,
 ) {
@@ -1022,13 +1638,27 @@ fn handle_message(
             }
         }
 
+        Message::SetMany(batch) => {
+            for (index, value) in batch.entries() {
+                let address = batch.address(index);
+                params.insert(address.clone(), value.clone());
+
+                for entry in subscriptions.iter() {
+                    let (pattern, callback) = entry.value();
+                    if clasp_core::address::glob_match(pattern, &address) {
+                        callback(value.clone(), &address);
+                    }
+                }
+            }
+        }
+
         Message::Snapshot(snapshot) => {
             for param in &snapshot.params {
                 params.insert(param.address.clone(), param.value.clone());
 
                 // Complete pending gets
                 if let Some((_, tx)) = pending_gets.remove(&param.address) {
-                    let _ = tx.send(param.value.clone());
+                    let _ = tx.send(Ok(param.value.clone()));
                 }
 
                 // Notify subscribers
@@ -1039,6 +1669,23 @@ fn handle_message(
                     }
                 }
             }
+
+            // Answer to a correlated GET, possibly in chunks
+            if let Some(id) = snapshot.correlation_id {
+                if snapshot.more {
+                    if let Some(mut pending) = pending_requests.get_mut(&id) {
+                        pending.partial.extend(snapshot.params.iter().cloned());
+                    }
+                } else if let Some((_, pending)) = pending_requests.remove(&id) {
+                    let mut params = pending.partial;
+                    params.extend(snapshot.params.iter().cloned());
+                    let _ = pending.tx.send(Ok(Message::Snapshot(SnapshotMessage {
+                        params,
+                        correlation_id: Some(id),
+                        more: false,
+                    })));
+                }
+            }
         }
 
         Message::Publish(pub_msg) => {
@@ -1075,14 +1722,41 @@ fn handle_message(
                 error.code, error.message, error.address
             );
             *last_error.write() = Some(error.clone());
+
+            if let Some(id) = error.correlation_id {
+                if let Some((_, pending)) = pending_requests.remove(&id) {
+                    let _ = pending.tx.send(Err(ClientError::from(error.clone())));
+                    return;
+                }
+            }
+
+            // A refused GET fails now instead of timing out
+            if let Some(ref address) = error.address {
+                if let Some((_, tx)) = pending_gets.remove(address) {
+                    let _ = tx.send(Err(ClientError::from(error.clone())));
+                }
+            }
         }
 
         Message::Ack(ack) => {
-            // Log acknowledgment (could be extended to track pending requests)
             debug!(
                 "Received ACK for {:?} (revision: {:?})",
                 ack.address, ack.revision
             );
+            if let Some(id) = ack.correlation_id {
+                if let Some((_, pending)) = pending_requests.remove(&id) {
+                    let _ = pending.tx.send(Ok(msg.clone()));
+                }
+            }
+        }
+
+        Message::SubscribeAck(SubscribeAckMessage { correlation_id, .. })
+        | Message::UnsubscribeAck(UnsubscribeAckMessage { correlation_id, .. }) => {
+            if let Some(id) = correlation_id {
+                if let Some((_, pending)) = pending_requests.remove(id) {
+                    let _ = pending.tx.send(Ok(msg.clone()));
+                }
+            }
         }
 
         Message::Announce(announce) => {
@@ -1112,11 +1786,16 @@ fn handle_message(
             for signal in &result.signals {
                 signals.insert(signal.address.clone(), signal.clone());
             }
+            if let Some(tx) = pending_queries.lock().pop_front() {
+                let _ = tx.send(result.clone());
+            }
         }
 
         // Messages that are typically client-initiated, not expected from server
         Message::Hello(_)
         | Message::Welcome(_)
+        | Message::Proof(_)
+        | Message::Alias(_)
         | Message::Subscribe(_)
         | Message::Unsubscribe(_)
         | Message::Get(_)
@@ -1134,6 +1813,8 @@ fn handle_message(
                     params,
                     subscriptions,
                     pending_gets,
+                    pending_requests,
+                    pending_queries,
                     signals,
                     last_error,
                 );
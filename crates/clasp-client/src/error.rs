@@ -1,5 +1,6 @@
 //! Client error types
 
+use clasp_core::{ErrorCode, ErrorMessage};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, ClientError>;
@@ -21,15 +22,71 @@ pub enum ClientError {
     #[error("timeout")]
     Timeout,
 
+    /// The server is overloaded and asked the client to reconnect later
+    #[error("server busy, retry after {0:?}")]
+    RetryAfter(std::time::Duration),
+
+    /// The router refused a request with an ERROR
+    #[error("server error {code}: {message}")]
+    Server {
+        /// Code as sent, which may be one this version doesn't know
+        code: u16,
+        /// `code` as a known [`ErrorCode`]
+        kind: Option<ErrorCode>,
+        message: String,
+        address: Option<String>,
+    },
+
     #[error("protocol error: {0}")]
     Protocol(#[from] clasp_core::Error),
 
     #[error("transport error: {0}")]
     Transport(#[from] clasp_transport::TransportError),
 
+    /// An interceptor blocked an outgoing message
+    #[error("blocked by interceptor: {0}")]
+    Blocked(String),
+
     #[error("P2P not connected to peer: {0}")]
     P2PNotConnected(String),
 
     #[error("client error: {0}")]
     Other(String),
 }
+
+impl ClientError {
+    /// The protocol error code behind this error, if it came from the router
+    ///
+    /// ```
+    /// use clasp_client::ClientError;
+    /// use clasp_core::ErrorCode;
+    ///
+    /// fn should_refresh_token(error: &ClientError) -> bool {
+    ///     matches!(
+    ///         error.error_code(),
+    ///         Some(ErrorCode::Unauthorized | ErrorCode::TokenExpired)
+    ///     )
+    /// }
+    /// ```
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            ClientError::Server { kind, .. } => *kind,
+            ClientError::RetryAfter(_) => Some(ErrorCode::RetryAfter),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorMessage> for ClientError {
+    fn from(error: ErrorMessage) -> Self {
+        if let Some(delay) = error.retry_after_hint() {
+            return ClientError::RetryAfter(delay);
+        }
+        ClientError::Server {
+            code: error.code,
+            kind: error.error_code(),
+            message: error.message,
+            address: error.address,
+        }
+    }
+}
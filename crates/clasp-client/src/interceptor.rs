@@ -0,0 +1,193 @@
+//! Interceptors for outgoing and incoming messages
+//!
+//! An [`Interceptor`] sees messages as they pass through the client and can
+//! modify or block them, e.g. to validate writes, count traffic or encrypt
+//! values, without wrapping the client:
+//!
+//! ```
+//! use clasp_client::{Flow, Interceptor};
+//! use clasp_core::Message;
+//!
+//! /// Refuses writes outside /lights
+//! struct LightsOnly;
+//!
+//! impl Interceptor for LightsOnly {
+//!     fn outgoing(&self, msg: &mut Message) -> Flow {
+//!         match msg {
+//!             Message::Set(set) if !set.address.starts_with("/lights/") => {
+//!                 Flow::Block(format!("{} is not a light", set.address))
+//!             }
+//!             _ => Flow::Continue,
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Register interceptors with
+//! [`ClaspBuilder::interceptor`](crate::ClaspBuilder::interceptor). Like
+//! layers, outgoing messages pass through them in the order they were added
+//! and incoming messages in reverse order, so the first interceptor added is
+//! the outermost.
+//!
+//! Outgoing interception covers SET, SET_MANY and PUBLISH, including those
+//! inside a BUNDLE; blocking any of them fails the send with
+//! [`ClientError::Blocked`](crate::ClientError::Blocked) (a bundle is sent
+//! whole or not at all). Incoming interception covers every message from
+//! the router after the handshake, before the client updates its cache and
+//! calls subscription callbacks; blocked messages are dropped.
+
+use clasp_core::Message;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::error::{ClientError, Result};
+
+/// What happens to an intercepted message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Flow {
+    /// Pass the (possibly modified) message on
+    Continue,
+    /// Drop the message, with a reason
+    Block(String),
+}
+
+/// Observes, modifies or blocks messages passing through the client
+pub trait Interceptor: Send + Sync + 'static {
+    /// An outgoing SET, SET_MANY or PUBLISH, before it is sent
+    fn outgoing(&self, _msg: &mut Message) -> Flow {
+        Flow::Continue
+    }
+
+    /// A message from the router, before the client handles it
+    fn incoming(&self, _msg: &mut Message) -> Flow {
+        Flow::Continue
+    }
+}
+
+/// The client's interceptors, in the order they were added
+#[derive(Clone, Default)]
+pub(crate) struct Interceptors(Arc<Vec<Arc<dyn Interceptor>>>);
+
+impl Interceptors {
+    pub(crate) fn new(interceptors: Vec<Arc<dyn Interceptor>>) -> Self {
+        Self(Arc::new(interceptors))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `msg` is subject to outgoing interception
+    pub(crate) fn intercepts_outgoing(&self, msg: &Message) -> bool {
+        !self.is_empty()
+            && matches!(
+                msg,
+                Message::Set(_) | Message::SetMany(_) | Message::Publish(_) | Message::Bundle(_)
+            )
+    }
+
+    /// Run an outgoing message through the interceptors
+    pub(crate) fn outgoing(&self, msg: &mut Message) -> Result<()> {
+        if let Message::Bundle(bundle) = msg {
+            for inner in &mut bundle.messages {
+                if matches!(inner, Message::Set(_) | Message::Publish(_)) {
+                    self.outgoing(inner)?;
+                }
+            }
+            return Ok(());
+        }
+        for interceptor in self.0.iter() {
+            if let Flow::Block(reason) = interceptor.outgoing(msg) {
+                return Err(ClientError::Blocked(reason));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run an incoming message through the interceptors; false if blocked
+    pub(crate) fn incoming(&self, msg: &mut Message) -> bool {
+        for interceptor in self.0.iter().rev() {
+            if let Flow::Block(reason) = interceptor.incoming(msg) {
+                debug!("Incoming message blocked: {}", reason);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{BundleMessage, SetMessage, Value};
+    use parking_lot::Mutex;
+
+    fn set(address: &str) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(1),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    /// Records its name on every call and prefixes SET addresses with it
+    struct Tag(&'static str, Arc<Mutex<Vec<String>>>);
+
+    impl Interceptor for Tag {
+        fn outgoing(&self, msg: &mut Message) -> Flow {
+            self.1.lock().push(format!("out {}", self.0));
+            if let Message::Set(set) = msg {
+                if set.address.starts_with("/blocked") {
+                    return Flow::Block("blocked".to_string());
+                }
+                set.address = format!("/{}{}", self.0, set.address);
+            }
+            Flow::Continue
+        }
+
+        fn incoming(&self, _msg: &mut Message) -> Flow {
+            self.1.lock().push(format!("in {}", self.0));
+            Flow::Continue
+        }
+    }
+
+    #[test]
+    fn test_onion_order_and_modification() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let interceptors = Interceptors::new(vec![
+            Arc::new(Tag("a", log.clone())),
+            Arc::new(Tag("b", log.clone())),
+        ]);
+
+        let mut msg = set("/x");
+        interceptors.outgoing(&mut msg).unwrap();
+        let Message::Set(ref out) = msg else {
+            unreachable!()
+        };
+        assert_eq!(out.address, "/b/a/x");
+        assert!(interceptors.incoming(&mut msg));
+        assert_eq!(*log.lock(), ["out a", "out b", "in b", "in a"]);
+    }
+
+    #[test]
+    fn test_block_fails_whole_bundle() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let interceptors = Interceptors::new(vec![Arc::new(Tag("a", log))]);
+
+        let mut bundle = Message::Bundle(BundleMessage {
+            timestamp: None,
+            messages: vec![set("/x"), set("/blocked")],
+        });
+        assert!(interceptors.intercepts_outgoing(&bundle));
+        assert!(matches!(
+            interceptors.outgoing(&mut bundle),
+            Err(ClientError::Blocked(reason)) if reason == "blocked"
+        ));
+        assert!(!interceptors.intercepts_outgoing(&Message::Ping));
+    }
+}
@@ -16,6 +16,13 @@
 //! - **Streams**: High-rate data streaming (QoS fire)
 //! - **Bundles**: Atomic multi-message operations
 //! - **Time sync**: Automatic clock synchronization with server
+//! - **Reconnect**: Configurable backoff with connection lifecycle events
+//! - **Interceptors**: Observe, modify or block outgoing and incoming messages
+//! - **Offline-first**: Local param cache and SET queue replayed on reconnect ([`offline`])
+//! - **Device shadows**: Desired vs reported state with router-computed deltas ([`shadow`])
+//! - **State branches**: Program the next show on a branch and merge it at showtime ([`branch`])
+//! - **Blocking API**: Synchronous facade for hosts without an async runtime ([`blocking`])
+//! - **Simulation**: Config-driven synthetic signals for development and tests ([`simulate`])
 //!
 //! ## Quick Start
 //!
@@ -89,17 +96,30 @@
 //!
 //! - `p2p` - Enable peer-to-peer mesh networking support
 
+pub mod blocking;
+pub mod branch;
 pub mod builder;
 pub mod client;
 pub mod error;
+pub mod interceptor;
+pub mod offline;
 #[cfg(feature = "p2p")]
 pub mod p2p;
+pub mod reconnect;
+pub mod shadow;
+pub mod simulate;
 
+pub use branch::Branch;
 pub use builder::ClaspBuilder;
-pub use client::Clasp;
+pub use client::{Clasp, PossessionSigner};
 pub use error::{ClientError, Result};
+pub use interceptor::{Flow, Interceptor};
+pub use offline::{ClaspOffline, Conflict, Resolution};
 #[cfg(feature = "p2p")]
 pub use p2p::{P2PEvent, P2PManager, SendResult};
+pub use reconnect::{Backoff, ClientEvent, ReconnectPolicy};
+pub use shadow::Shadow;
+pub use simulate::{SimConfig, Simulation};
 
 // Re-export P2P routing mode for convenience
 #[cfg(feature = "p2p")]
@@ -110,14 +130,20 @@ pub mod prelude {
     pub use crate::builder::ClaspBuilder;
     pub use crate::client::Clasp;
     pub use crate::error::{ClientError, Result};
+    pub use crate::interceptor::{Flow, Interceptor};
+    pub use crate::offline::{ClaspOffline, Conflict, Resolution};
     #[cfg(feature = "p2p")]
     pub use crate::p2p::{P2PEvent, P2PManager, SendResult};
+    pub use crate::reconnect::{ClientEvent, ReconnectPolicy};
+    pub use crate::shadow::Shadow;
     #[cfg(feature = "p2p")]
     pub use clasp_core::RoutingMode;
     pub use clasp_core::{
-        EasingType, GesturePhase, Message, SignalType, TimelineData, TimelineKeyframe, Value,
+        EasingType, GesturePhase, Message, SetManyMessage, SignalType, TimelineData,
+        TimelineKeyframe, Value,
     };
 }
 
 // Re-export types for convenience
+pub use clasp_core::link_stats::{LinkStats, SubscriptionStats};
 pub use clasp_core::{EasingType, GesturePhase, TimelineData, TimelineKeyframe};
@@ -0,0 +1,469 @@
+//! Offline-first client
+//!
+//! [`ClaspOffline`] wraps a [`Clasp`] client for apps that must keep working
+//! while the router is unreachable. It keeps a local copy of every param it
+//! has seen, queues SETs made while disconnected and replays them once the
+//! router's snapshot is back:
+//!
+//! ```no_run
+//! use clasp_client::{ClaspBuilder, ClaspOffline, Resolution};
+//!
+//! # async fn example() -> clasp_client::Result<()> {
+//! let client = ClaspOffline::open("stage.state")
+//!     .on_conflict(|conflict| {
+//!         if conflict.address.starts_with("/safety/") {
+//!             Resolution::Remote
+//!         } else {
+//!             conflict.last_writer_wins()
+//!         }
+//!     })
+//!     .connect(ClaspBuilder::new("ws://localhost:7330"))
+//!     .await?;
+//!
+//! // Sent now, or queued until the router is reachable again
+//! client.set("/lights/front/level", 0.8).await?;
+//! println!("{:?}", client.cached("/lights/front/level"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The cache and the queue are stored in one file, written when a SET is
+//! queued, when the connection drops, after reconciling and on
+//! [`ClaspOffline::close`], so a restarted app starts from its last-known
+//! state and still replays what it couldn't send.
+//!
+//! On reconnect each queued SET is compared with the param's revision in
+//! the router's snapshot. If nobody else wrote the param in the meantime
+//! the SET is sent; otherwise the conflict callback decides, and without
+//! one the later write wins ([`Conflict::last_writer_wins`]). Write times
+//! are taken from the client's clock synced with the router's, so they
+//! compare with the snapshot's timestamps.
+//!
+//! Unlike [`ClaspBuilder::connect`], connecting doesn't fail when the router
+//! can't be reached: the client starts offline and keeps trying with the
+//! builder's reconnect policy (reconnect is always enabled).
+
+use clasp_core::{Message, Value};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::error::{ClientError, Result};
+use crate::interceptor::{Flow, Interceptor};
+use crate::{Clasp, ClaspBuilder, ClientEvent};
+
+/// Decides a [`Conflict`] between a queued SET and the router's value
+pub type ConflictResolver = Arc<dyn Fn(&Conflict) -> Resolution + Send + Sync>;
+
+/// A param written offline that someone else changed in the meantime
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub address: String,
+    /// The value written while offline
+    pub local: Value,
+    /// When it was written (server time, microseconds)
+    pub local_time: u64,
+    /// The router's current value
+    pub remote: Value,
+    /// When the router's value was written, if it knows (microseconds)
+    pub remote_time: Option<u64>,
+    /// The revision the offline write was based on
+    pub base_revision: Option<u64>,
+    /// The router's current revision
+    pub remote_revision: u64,
+}
+
+impl Conflict {
+    /// Keep whichever value was written last (the local one if the router
+    /// doesn't know when its value was written)
+    pub fn last_writer_wins(&self) -> Resolution {
+        if self
+            .remote_time
+            .is_none_or(|remote| self.local_time >= remote)
+        {
+            Resolution::Local
+        } else {
+            Resolution::Remote
+        }
+    }
+}
+
+/// How a [`Conflict`] is resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Send the value written offline
+    Local,
+    /// Drop the offline write and keep the router's value
+    Remote,
+    /// Send a different value, e.g. a merge of both
+    Value(Value),
+}
+
+/// A param as last seen from the router
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedParam {
+    value: Value,
+    revision: Option<u64>,
+    timestamp: Option<u64>,
+}
+
+/// A SET waiting to be sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingSet {
+    value: Value,
+    base_revision: Option<u64>,
+    written_at: u64,
+}
+
+/// What is stored on disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredState {
+    params: BTreeMap<String, CachedParam>,
+    pending: BTreeMap<String, PendingSet>,
+}
+
+struct Store {
+    path: PathBuf,
+    state: Mutex<StoredState>,
+    /// Serializes writes of the state file
+    save_lock: tokio::sync::Mutex<()>,
+    resolver: Option<ConflictResolver>,
+}
+
+impl Store {
+    fn load(path: PathBuf, resolver: Option<ConflictResolver>) -> Result<Self> {
+        let state = match std::fs::read(&path) {
+            Ok(data) => rmp_serde::from_slice(&data).map_err(|e| {
+                ClientError::Other(format!("invalid state file {}: {}", path.display(), e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoredState::default(),
+            Err(e) => {
+                return Err(ClientError::Other(format!(
+                    "failed to read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+            save_lock: tokio::sync::Mutex::new(()),
+            resolver,
+        })
+    }
+
+    async fn save(&self) -> Result<()> {
+        let _guard = self.save_lock.lock().await;
+        let data = rmp_serde::to_vec_named(&*self.state.lock())
+            .map_err(|e| ClientError::Other(e.to_string()))?;
+        // Write a temporary file first so a crash can't leave half a state
+        let tmp = self.path.with_extension("tmp");
+        let write = async {
+            tokio::fs::write(&tmp, data).await?;
+            tokio::fs::rename(&tmp, &self.path).await
+        };
+        write.await.map_err(|e| {
+            ClientError::Other(format!("failed to write {}: {}", self.path.display(), e))
+        })
+    }
+
+    fn record(&self, address: &str, value: Value, revision: Option<u64>, timestamp: Option<u64>) {
+        let mut state = self.state.lock();
+        let revision = revision.or_else(|| state.params.get(address).and_then(|p| p.revision));
+        state.params.insert(
+            address.to_string(),
+            CachedParam {
+                value,
+                revision,
+                timestamp,
+            },
+        );
+    }
+}
+
+/// Keeps the store's copy of the router's params up to date
+struct Tracker(Arc<Store>);
+
+impl Tracker {
+    fn track(&self, msg: &Message) {
+        match msg {
+            Message::Set(set) => {
+                self.0
+                    .record(&set.address, set.value.clone(), set.revision, None);
+            }
+            Message::SetMany(batch) => {
+                for (index, value) in batch.entries() {
+                    self.0
+                        .record(&batch.address(index), value.clone(), None, None);
+                }
+            }
+            Message::Snapshot(snapshot) => {
+                for param in &snapshot.params {
+                    self.0.record(
+                        &param.address,
+                        param.value.clone(),
+                        Some(param.revision),
+                        param.timestamp,
+                    );
+                }
+            }
+            Message::Ack(ack) => {
+                if let (Some(address), Some(revision)) = (&ack.address, ack.revision) {
+                    if let Some(param) = self.0.state.lock().params.get_mut(address) {
+                        param.revision = Some(revision);
+                    }
+                }
+            }
+            Message::Bundle(bundle) => {
+                for inner in &bundle.messages {
+                    self.track(inner);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Interceptor for Tracker {
+    fn incoming(&self, msg: &mut Message) -> Flow {
+        self.track(msg);
+        Flow::Continue
+    }
+}
+
+/// Builder for [`ClaspOffline`]
+pub struct OfflineBuilder {
+    path: PathBuf,
+    resolver: Option<ConflictResolver>,
+}
+
+impl OfflineBuilder {
+    /// Decide conflicts between queued SETs and the router's values
+    /// (default: [`Conflict::last_writer_wins`])
+    pub fn on_conflict<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&Conflict) -> Resolution + Send + Sync + 'static,
+    {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Load the stored state and connect, starting offline if the router
+    /// can't be reached
+    ///
+    /// Fails if the state file can't be read or the router refuses the
+    /// client (e.g. an invalid token).
+    pub async fn connect(self, builder: ClaspBuilder) -> Result<ClaspOffline> {
+        let store = Arc::new(Store::load(self.path, self.resolver)?);
+        let client = Arc::new(
+            builder
+                .reconnect(true)
+                .interceptor(Tracker(Arc::clone(&store)))
+                .build(),
+        );
+
+        let mut events = client.events();
+        let weak = Arc::downgrade(&client);
+        let task_store = Arc::clone(&store);
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                match event {
+                    ClientEvent::SnapshotResynced => reconcile(&weak, &task_store).await,
+                    ClientEvent::Dropped { .. } => {
+                        if let Err(e) = task_store.save().await {
+                            warn!("Failed to save offline state: {}", e);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        client.start_reconnect_loop();
+        match client.try_reconnect().await {
+            Ok(()) => {}
+            Err(e @ ClientError::Server { .. }) => return Err(e),
+            Err(e) => {
+                info!("Router unreachable, starting offline: {}", e);
+                client.schedule_reconnect();
+            }
+        }
+
+        Ok(ClaspOffline { client, store })
+    }
+}
+
+/// Send queued SETs after a resync, resolving conflicts with the snapshot
+async fn reconcile(client: &Weak<Clasp>, store: &Store) {
+    let Some(client) = client.upgrade() else {
+        return;
+    };
+
+    let mut sends = Vec::new();
+    let mut conflicts = Vec::new();
+    {
+        let mut state = store.state.lock();
+        let pending = std::mem::take(&mut state.pending);
+        for (address, set) in pending {
+            match state.params.get(&address) {
+                Some(CachedParam {
+                    value,
+                    revision: Some(revision),
+                    timestamp,
+                }) if set.base_revision != Some(*revision) => {
+                    conflicts.push(Conflict {
+                        address,
+                        local: set.value,
+                        local_time: set.written_at,
+                        remote: value.clone(),
+                        remote_time: *timestamp,
+                        base_revision: set.base_revision,
+                        remote_revision: *revision,
+                    });
+                }
+                _ => sends.push((address, set)),
+            }
+        }
+    }
+
+    for conflict in conflicts {
+        let resolution = match &store.resolver {
+            Some(resolver) => resolver(&conflict),
+            None => conflict.last_writer_wins(),
+        };
+        debug!("Conflict on {}: {:?}", conflict.address, resolution);
+        let value = match resolution {
+            Resolution::Local => conflict.local,
+            Resolution::Remote => continue,
+            Resolution::Value(value) => value,
+        };
+        sends.push((
+            conflict.address,
+            PendingSet {
+                value,
+                base_revision: Some(conflict.remote_revision),
+                written_at: conflict.local_time,
+            },
+        ));
+    }
+
+    for (address, set) in sends {
+        match client.set(&address, set.value.clone()).await {
+            Ok(()) => store.record(&address, set.value, None, Some(set.written_at)),
+            Err(e) => {
+                // Dropped again; keep it for the next resync unless it was
+                // written over in the meantime
+                debug!("Replay of {} failed: {}", address, e);
+                store.state.lock().pending.entry(address).or_insert(set);
+            }
+        }
+    }
+
+    if let Err(e) = store.save().await {
+        warn!("Failed to save offline state: {}", e);
+    }
+}
+
+/// A client that caches params locally and queues SETs while offline (see
+/// the [module docs](self))
+pub struct ClaspOffline {
+    client: Arc<Clasp>,
+    store: Arc<Store>,
+}
+
+impl ClaspOffline {
+    /// Start building an offline-first client whose state is stored at `path`
+    pub fn open(path: impl AsRef<Path>) -> OfflineBuilder {
+        OfflineBuilder {
+            path: path.as_ref().to_path_buf(),
+            resolver: None,
+        }
+    }
+
+    /// Set a parameter value, queuing it if the router can't be reached
+    pub async fn set(&self, address: &str, value: impl Into<Value>) -> Result<()> {
+        let value = value.into();
+        if self.client.is_connected() {
+            match self.client.set(address, value.clone()).await {
+                Ok(()) => {
+                    self.store
+                        .record(address, value, None, Some(self.client.time()));
+                    return Ok(());
+                }
+                Err(ClientError::NotConnected | ClientError::SendFailed(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        {
+            let mut state = self.store.state.lock();
+            let base_revision = state.params.get(address).and_then(|p| p.revision);
+            let written_at = self.client.time();
+            state
+                .pending
+                .entry(address.to_string())
+                .and_modify(|set| {
+                    set.value = value.clone();
+                    set.written_at = written_at;
+                })
+                .or_insert(PendingSet {
+                    value,
+                    base_revision,
+                    written_at,
+                });
+        }
+        self.store.save().await
+    }
+
+    /// The local value of a param: a queued SET, or the value last seen
+    /// from the router
+    pub fn cached(&self, address: &str) -> Option<Value> {
+        let state = self.store.state.lock();
+        state
+            .pending
+            .get(address)
+            .map(|set| set.value.clone())
+            .or_else(|| state.params.get(address).map(|p| p.value.clone()))
+    }
+
+    /// Addresses with SETs waiting to be sent
+    pub fn pending(&self) -> Vec<String> {
+        self.store.state.lock().pending.keys().cloned().collect()
+    }
+
+    /// Check if connected
+    pub fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    /// Connection lifecycle events
+    pub fn events(&self) -> tokio::sync::broadcast::Receiver<ClientEvent> {
+        self.client.events()
+    }
+
+    /// Write the cache and queue to disk now
+    pub async fn save(&self) -> Result<()> {
+        self.store.save().await
+    }
+
+    /// The wrapped client, for subscriptions and other calls
+    pub fn client(&self) -> &Clasp {
+        &self.client
+    }
+
+    /// Save the state and close the connection
+    pub async fn close(&self) -> Result<()> {
+        self.client.close().await;
+        self.store.save().await
+    }
+}
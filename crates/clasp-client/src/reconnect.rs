@@ -0,0 +1,84 @@
+//! Reconnect policy and connection lifecycle events
+
+use rand::Rng;
+use std::time::Duration;
+
+/// How the delay between reconnect attempts grows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// Always wait the initial delay
+    Constant,
+    /// Initial delay times the attempt number
+    Linear,
+    /// Initial delay times `factor` to the power of previous attempts
+    Exponential(f64),
+}
+
+/// When and how often the client reconnects after losing its connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Attempts before giving up (0 = unlimited)
+    pub max_attempts: u32,
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound on any delay
+    pub max_delay: Duration,
+    /// Growth of the delay with each failed attempt
+    pub backoff: Backoff,
+    /// Random spread applied to each delay as a fraction (0.2 = ±20%), so
+    /// clients dropped together don't all come back at once
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            initial_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(30),
+            backoff: Backoff::Exponential(1.5),
+            jitter: 0.0,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before the reconnect attempt that follows `failed` failed
+    /// attempts, including jitter
+    pub fn delay(&self, failed: u32) -> Duration {
+        let initial = self.initial_delay.as_secs_f64();
+        let base = match self.backoff {
+            Backoff::Constant => initial,
+            Backoff::Linear => initial * (failed as f64 + 1.0),
+            Backoff::Exponential(factor) => initial * factor.powi(failed as i32),
+        };
+        let max = self.max_delay.as_secs_f64();
+        let mut secs = base.min(max);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter > 0.0 {
+            secs *= 1.0 + rand::thread_rng().gen_range(-jitter..=jitter);
+        }
+        Duration::from_secs_f64(secs.clamp(0.0, max))
+    }
+
+    /// Whether the client stops after `failed` failed attempts
+    pub fn gives_up(&self, failed: u32) -> bool {
+        self.max_attempts > 0 && failed >= self.max_attempts
+    }
+}
+
+/// Connection lifecycle events, from [`Clasp::events`](crate::Clasp::events)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientEvent {
+    /// A handshake completed and the client has a session
+    Connected { session: String },
+    /// A reconnect attempt starts after `delay`
+    Reconnecting { attempt: u32, delay: Duration },
+    /// After a reconnect (or an offline-first client's first connect), the
+    /// router's state snapshot has been applied to the param cache
+    SnapshotResynced,
+    /// The connection was lost
+    Dropped { reason: Option<String> },
+    /// The policy ran out of attempts; the client stays disconnected
+    GaveUp { attempts: u32 },
+}
@@ -0,0 +1,87 @@
+//! Device shadow helper
+//!
+//! Works against a router with shadows enabled (`RouterConfig::shadow`,
+//! relay `--shadow`). Apps [`desire`](Shadow::desire) a state, the router
+//! publishes what the device still has to apply on `<base>/delta`, and the
+//! device applies it and [`report`](Shadow::report)s back.
+//!
+//! ```ignore
+//! // Device side
+//! let lamp = client.shadow("/devices/lamp");
+//! lamp.on_delta(|delta| apply_to_hardware(&delta)).await?;
+//!
+//! // App side
+//! client.shadow("/devices/lamp").desire(level_map).await?;
+//! ```
+
+use clasp_core::shadow::{self, ShadowPart};
+use clasp_core::Value;
+
+use crate::client::Clasp;
+use crate::error::Result;
+
+/// One device shadow, addressed by its base (e.g. `/devices/lamp`)
+pub struct Shadow<'a> {
+    client: &'a Clasp,
+    base: String,
+}
+
+impl<'a> Shadow<'a> {
+    pub(crate) fn new(client: &'a Clasp, base: &str) -> Self {
+        Self {
+            client,
+            base: base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Base address of the shadow
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Address apps write
+    pub fn desired_address(&self) -> String {
+        shadow::address(&self.base, ShadowPart::Desired)
+    }
+
+    /// Address the device writes
+    pub fn reported_address(&self) -> String {
+        shadow::address(&self.base, ShadowPart::Reported)
+    }
+
+    /// Address the router publishes deltas on
+    pub fn delta_address(&self) -> String {
+        shadow::address(&self.base, ShadowPart::Delta)
+    }
+
+    /// Ask the device to reach `value` (`Value::Null` withdraws the desire)
+    pub async fn desire(&self, value: impl Into<Value>) -> Result<()> {
+        self.client.set(&self.desired_address(), value).await
+    }
+
+    /// Report the device's current state
+    pub async fn report(&self, value: impl Into<Value>) -> Result<()> {
+        self.client.set(&self.reported_address(), value).await
+    }
+
+    /// Apply `delta` to `reported` and report the result
+    pub async fn report_delta(&self, reported: &mut Value, delta: &Value) -> Result<()> {
+        *reported = shadow::merge(reported, delta);
+        self.report(reported.clone()).await
+    }
+
+    /// Call `callback` with every pending delta. `null` deltas (device in
+    /// sync) are skipped. Returns the subscription ID.
+    pub async fn on_delta<F>(&self, callback: F) -> Result<u32>
+    where
+        F: Fn(Value) + Send + Sync + 'static,
+    {
+        self.client
+            .subscribe(&self.delta_address(), move |value, _| {
+                if value != Value::Null {
+                    callback(value);
+                }
+            })
+            .await
+    }
+}
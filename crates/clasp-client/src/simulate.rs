@@ -0,0 +1,318 @@
+//! Synthetic data generator
+//!
+//! A [`Simulation`] drives a connected [`Clasp`] client with made-up but
+//! plausible signals, so frontends can be built and tests run without the
+//! hardware behind them. Each [`SimSignal`] names an address, how to send it
+//! (`param`, `event` or `stream`), how often, and a [`Waveform`]:
+//!
+//! - `sine`: a smooth swing between `min` and `max` every `period` seconds
+//! - `noise`: an independent random value between `min` and `max` per tick
+//! - `random_walk`: wanders from the middle by up to `step` of the range per tick
+//! - `cues`: a fixed sequence of values, each held for its `hold` seconds
+//!
+//! The config deserializes from any serde format. `clasp simulate --config
+//! sim.yaml` reads it from YAML:
+//!
+//! ```yaml
+//! seed: 7
+//! signals:
+//!   - address: /sensors/temp
+//!     pattern: sine
+//!     period: 10
+//!     min: 18
+//!     max: 24
+//!     rate: 5
+//!   - address: /mixer/ch/1/level
+//!     pattern: random_walk
+//!     step: 0.02
+//!     rate: 30
+//!   - address: /show/cue
+//!     signal: event
+//!     pattern: cues
+//!     repeat: true
+//!     cues:
+//!       - { value: "intro", hold: 5 }
+//!       - { value: "verse", hold: 20 }
+//! ```
+//!
+//! In tests, build the config in code and bound the run with a timeout:
+//!
+//! ```no_run
+//! use clasp_client::simulate::{SimConfig, SimSignal, Simulation, Waveform};
+//! use std::time::Duration;
+//!
+//! # async fn example(client: clasp_client::Clasp) -> clasp_client::Result<()> {
+//! let sim = Simulation::new(SimConfig {
+//!     seed: Some(1),
+//!     signals: vec![SimSignal::new("/sensors/temp", Waveform::Noise)],
+//! })?;
+//! let _ = tokio::time::timeout(Duration::from_secs(1), sim.run(&client)).await;
+//! # Ok(())
+//! # }
+//! ```
+
+use clasp_core::{SignalType, Value};
+use futures::future::try_join_all;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::client::Clasp;
+use crate::error::{ClientError, Result};
+
+/// A set of signals to generate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimConfig {
+    /// Seed for noise and random walks, for repeatable runs (default: random)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub signals: Vec<SimSignal>,
+}
+
+/// One generated signal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimSignal {
+    pub address: String,
+    /// How values are sent: `param` (SET), `event` or `stream`
+    #[serde(default = "default_signal")]
+    pub signal: SignalType,
+    /// Values per second (ignored by `cues`)
+    #[serde(default = "default_rate")]
+    pub rate: f64,
+    /// Lowest value generated
+    #[serde(default)]
+    pub min: f64,
+    /// Highest value generated
+    #[serde(default = "default_max")]
+    pub max: f64,
+    #[serde(flatten)]
+    pub waveform: Waveform,
+}
+
+impl SimSignal {
+    /// A param at 10 values per second between 0 and 1
+    pub fn new(address: &str, waveform: Waveform) -> Self {
+        Self {
+            address: address.to_string(),
+            signal: default_signal(),
+            rate: default_rate(),
+            min: 0.0,
+            max: default_max(),
+            waveform,
+        }
+    }
+}
+
+/// The shape of a generated signal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "pattern", rename_all = "snake_case")]
+pub enum Waveform {
+    /// Swings from `min` to `max` and back once every `period` seconds
+    Sine {
+        #[serde(default = "default_period")]
+        period: f64,
+    },
+    /// A random value between `min` and `max` on every tick
+    Noise,
+    /// Starts in the middle and moves up to `step` (a fraction of the
+    /// range) per tick, staying within `min` and `max`
+    RandomWalk {
+        #[serde(default = "default_step")]
+        step: f64,
+    },
+    /// Sends each value in turn, waiting its `hold` before the next
+    Cues {
+        cues: Vec<Cue>,
+        /// Start over after the last cue instead of stopping
+        #[serde(default)]
+        repeat: bool,
+    },
+}
+
+/// One step of a cue sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cue {
+    pub value: Value,
+    /// Seconds before the next cue
+    #[serde(default)]
+    pub hold: f64,
+}
+
+fn default_signal() -> SignalType {
+    SignalType::Param
+}
+
+fn default_rate() -> f64 {
+    10.0
+}
+
+fn default_max() -> f64 {
+    1.0
+}
+
+fn default_period() -> f64 {
+    4.0
+}
+
+fn default_step() -> f64 {
+    0.05
+}
+
+/// Runs a [`SimConfig`] against a client
+pub struct Simulation {
+    config: SimConfig,
+}
+
+impl Simulation {
+    /// Check the config and prepare to run it
+    pub fn new(config: SimConfig) -> Result<Self> {
+        for signal in &config.signals {
+            validate(signal).map_err(|e| {
+                ClientError::Other(format!("simulated signal {}: {}", signal.address, e))
+            })?;
+        }
+        Ok(Self { config })
+    }
+
+    /// The signals this simulation generates
+    pub fn signals(&self) -> &[SimSignal] {
+        &self.config.signals
+    }
+
+    /// Generate every signal until a send fails
+    ///
+    /// Runs forever unless every signal is a non-repeating cue sequence, in
+    /// which case it returns once the last cue is sent. Stop it by dropping
+    /// the future (`tokio::select!`, `tokio::time::timeout`).
+    pub async fn run(&self, client: &Clasp) -> Result<()> {
+        let generators = self.config.signals.iter().enumerate().map(|(i, signal)| {
+            let rng = match self.config.seed {
+                Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(i as u64)),
+                None => StdRng::from_entropy(),
+            };
+            Generator::new(signal, rng).run(client)
+        });
+        try_join_all(generators).await?;
+        Ok(())
+    }
+}
+
+fn validate(signal: &SimSignal) -> std::result::Result<(), String> {
+    if !matches!(
+        signal.signal,
+        SignalType::Param | SignalType::Event | SignalType::Stream
+    ) {
+        return Err("signal must be param, event or stream".to_string());
+    }
+    if !(signal.min.is_finite() && signal.max.is_finite() && signal.min <= signal.max) {
+        return Err(format!(
+            "min {} and max {} are not a range",
+            signal.min, signal.max
+        ));
+    }
+    match &signal.waveform {
+        Waveform::Cues { cues, repeat } => {
+            if cues.is_empty() {
+                return Err("cues is empty".to_string());
+            }
+            if cues
+                .iter()
+                .any(|cue| Duration::try_from_secs_f64(cue.hold).is_err())
+            {
+                return Err("cue hold must be zero or more seconds".to_string());
+            }
+            if *repeat && cues.iter().all(|cue| cue.hold == 0.0) {
+                return Err("repeating cues need a hold".to_string());
+            }
+        }
+        waveform => {
+            if !(signal.rate > 0.0 && Duration::try_from_secs_f64(1.0 / signal.rate).is_ok()) {
+                return Err(format!("rate {} must be above 0", signal.rate));
+            }
+            match waveform {
+                Waveform::Sine { period } if !(period.is_finite() && *period > 0.0) => {
+                    return Err(format!("period {} must be above 0", period));
+                }
+                Waveform::RandomWalk { step } if !(0.0..=1.0).contains(step) => {
+                    return Err(format!("step {} must be between 0 and 1", step));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Produces one signal's values
+struct Generator<'a> {
+    signal: &'a SimSignal,
+    rng: StdRng,
+    /// Current position of a random walk
+    walk: f64,
+    /// Next cue to send
+    cue: usize,
+}
+
+impl<'a> Generator<'a> {
+    fn new(signal: &'a SimSignal, rng: StdRng) -> Self {
+        Self {
+            signal,
+            rng,
+            walk: (signal.min + signal.max) / 2.0,
+            cue: 0,
+        }
+    }
+
+    /// The value at `elapsed` seconds into the run and how long to wait
+    /// before the next, or `None` once a cue sequence is done
+    fn next(&mut self, elapsed: f64) -> Option<(Value, Duration)> {
+        let (min, max) = (self.signal.min, self.signal.max);
+        let value = match &self.signal.waveform {
+            Waveform::Sine { period } => {
+                let phase = std::f64::consts::TAU * elapsed / period;
+                min + (max - min) * (0.5 + 0.5 * phase.sin())
+            }
+            Waveform::Noise => self.rng.gen_range(min..=max),
+            Waveform::RandomWalk { step } => {
+                let step = step * (max - min);
+                self.walk = (self.walk + self.rng.gen_range(-step..=step)).clamp(min, max);
+                self.walk
+            }
+            Waveform::Cues { cues, repeat } => {
+                if self.cue == cues.len() {
+                    if !repeat {
+                        return None;
+                    }
+                    self.cue = 0;
+                }
+                let cue = &cues[self.cue];
+                self.cue += 1;
+                return Some((cue.value.clone(), Duration::from_secs_f64(cue.hold)));
+            }
+        };
+        Some((
+            Value::Float(value),
+            Duration::from_secs_f64(1.0 / self.signal.rate),
+        ))
+    }
+
+    async fn run(mut self, client: &Clasp) -> Result<()> {
+        let start = Instant::now();
+        let mut due = start;
+        // Waits are measured from when each value was due, not sent, so a
+        // slow send doesn't lower the rate
+        while let Some((value, wait)) = self.next(due.duration_since(start).as_secs_f64()) {
+            let address = &self.signal.address;
+            match self.signal.signal {
+                SignalType::Event => client.emit(address, value).await?,
+                SignalType::Stream => client.stream(address, value).await?,
+                _ => client.set(address, value).await?,
+            }
+            due += wait;
+            tokio::time::sleep_until(due).await;
+        }
+        Ok(())
+    }
+}
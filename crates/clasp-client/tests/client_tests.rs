@@ -6,12 +6,19 @@
 //! - Parameter operations (set, get, subscribe)
 //! - Event operations (emit, subscribe)
 //! - Advanced features (bundles, caching, clock sync)
+//! - Reconnect policy and connection events
+//! - Offline cache and replay on reconnect
+//! - Simulated signals
 //! - Negative tests and edge cases
 //! - Value type coverage
 
-use clasp_client::{Clasp, ClaspBuilder};
-use clasp_core::{Message, SetMessage, Value};
-use clasp_test_utils::{TestRouter, ValueCollector};
+use clasp_client::simulate::{Cue, SimConfig, SimSignal, Simulation, Waveform};
+use clasp_client::{
+    Backoff, Clasp, ClaspBuilder, ClaspOffline, ClientError, ClientEvent, Conflict, Flow,
+    Interceptor, ReconnectPolicy, Resolution,
+};
+use clasp_core::{Message, SetMessage, SignalType, Value};
+use clasp_test_utils::{wait_for, TestRouter, ValueCollector};
 use std::time::Duration;
 use tokio::time::timeout;
 
@@ -476,6 +483,8 @@ async fn test_bundle() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }),
         Message::Set(SetMessage {
             address: "/bundle/b".to_string(),
@@ -484,6 +493,8 @@ async fn test_bundle() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }),
         Message::Set(SetMessage {
             address: "/bundle/c".to_string(),
@@ -492,6 +503,8 @@ async fn test_bundle() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }),
     ];
 
@@ -528,6 +541,8 @@ async fn test_bundle_atomicity() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             })
         })
         .collect();
@@ -556,6 +571,8 @@ async fn test_bundle_at() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     })];
 
     let future_time = client.time() + 100_000; // 100ms in microseconds
@@ -806,6 +823,100 @@ async fn test_value_type_null() {
 // Two-Client Interaction Tests
 // ============================================================================
 
+/// Doubles outgoing float SETs and refuses writes under /private
+struct DoubleWrites;
+
+impl Interceptor for DoubleWrites {
+    fn outgoing(&self, msg: &mut Message) -> Flow {
+        if let Message::Set(set) = msg {
+            if set.address.starts_with("/private/") {
+                return Flow::Block("private".to_string());
+            }
+            if let Value::Float(f) = set.value {
+                set.value = Value::Float(f * 2.0);
+            }
+        }
+        Flow::Continue
+    }
+}
+
+/// Drops incoming SETs for /mix/hidden
+struct HideIncoming;
+
+impl Interceptor for HideIncoming {
+    fn incoming(&self, msg: &mut Message) -> Flow {
+        match msg {
+            Message::Set(set) if set.address == "/mix/hidden" => Flow::Block("hidden".to_string()),
+            _ => Flow::Continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_interceptors() {
+    let router = TestRouter::start().await;
+
+    let writer = ClaspBuilder::new(&router.url())
+        .interceptor(DoubleWrites)
+        .connect()
+        .await
+        .expect("Connect failed");
+    let reader = ClaspBuilder::new(&router.url())
+        .interceptor(HideIncoming)
+        .connect()
+        .await
+        .expect("Connect failed");
+
+    let collector = ValueCollector::new();
+    reader
+        .subscribe("/mix/**", collector.callback_ref())
+        .await
+        .expect("Subscribe failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(matches!(
+        writer.set("/private/key", 1.0).await,
+        Err(ClientError::Blocked(_))
+    ));
+    writer.set("/mix/hidden", 1.0).await.expect("Set failed");
+    writer.set("/mix/gain", 0.25).await.expect("Set failed");
+
+    assert!(collector.wait_for_count(1, Duration::from_secs(2)).await);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        collector.values(),
+        vec![("/mix/gain".to_string(), Value::Float(0.5))]
+    );
+    assert_eq!(reader.cached("/mix/hidden"), None);
+
+    writer.close().await;
+    reader.close().await;
+}
+
+#[test]
+fn test_blocking_client() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let router = rt.block_on(TestRouter::start());
+
+    let client = clasp_client::blocking::Clasp::connect(&router.url()).expect("Connect failed");
+    assert!(client.is_connected());
+
+    let updates = client.subscribe("/blocking/**").expect("Subscribe failed");
+    std::thread::sleep(Duration::from_millis(50));
+    client.set("/blocking/value", 7).expect("Set failed");
+
+    let (address, value) = updates
+        .recv_timeout(Duration::from_secs(2))
+        .expect("no update received");
+    assert_eq!(address, "/blocking/value");
+    assert_eq!(value, Value::Int(7));
+    assert_eq!(client.get("/blocking/value").unwrap(), Value::Int(7));
+
+    drop(updates);
+    client.close();
+    assert!(!client.is_connected());
+}
+
 #[tokio::test]
 async fn test_two_client_set_receive() {
     let router = TestRouter::start().await;
@@ -966,6 +1077,113 @@ async fn test_operations_before_connect() {
     client.close().await;
 }
 
+#[tokio::test]
+async fn test_connect_returns_retry_after_when_server_busy() {
+    let router = TestRouter::start_with_config(clasp_router::RouterConfig {
+        overload: clasp_router::OverloadConfig {
+            max_accept_rate: 1,
+            retry_after: Duration::from_millis(300),
+            retry_jitter: Duration::ZERO,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    // Let the readiness probe's accept window pass, then use up a fresh one
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let first = Clasp::connect_to(&router.url())
+        .await
+        .expect("Connect failed");
+
+    match Clasp::connect_to(&router.url()).await {
+        Err(ClientError::RetryAfter(delay)) => assert_eq!(delay, Duration::from_millis(300)),
+        Err(e) => panic!("expected RetryAfter, got {}", e),
+        Ok(_) => panic!("expected RetryAfter, got a connection"),
+    }
+
+    first.close().await;
+}
+
+#[tokio::test]
+async fn test_connect_returns_typed_server_error() {
+    let router = TestRouter::start_with_config(clasp_router::RouterConfig {
+        security_mode: clasp_core::SecurityMode::Authenticated,
+        ..Default::default()
+    })
+    .await;
+
+    match Clasp::connect_to(&router.url()).await {
+        Err(e) => {
+            assert_eq!(e.error_code(), Some(clasp_core::ErrorCode::Unauthorized));
+            assert!(matches!(e, ClientError::Server { code: 300, .. }));
+        }
+        Ok(_) => panic!("expected Unauthorized, got a connection"),
+    }
+}
+
+#[tokio::test]
+async fn test_correlated_requests_resolve_on_their_answer() {
+    let router = TestRouter::start().await;
+    let client = Clasp::connect_to(&router.url())
+        .await
+        .expect("Connect failed");
+
+    let first = client.set_confirmed("/corr/level", 0.5).await.unwrap();
+    let second = client.set_confirmed("/corr/level", 0.75).await.unwrap();
+    assert_eq!(first.address.as_deref(), Some("/corr/level"));
+    assert!(second.revision > first.revision);
+    assert_ne!(first.correlation_id, second.correlation_id);
+
+    // Subscriptions are acknowledged once live, with the snapshot size
+    let sub = client
+        .subscribe_confirmed("/corr/**", |_, _| {})
+        .await
+        .expect("subscribe should be acknowledged");
+    assert_eq!(sub.matched, 1);
+    let unsub = client.unsubscribe_confirmed(sub.id).await.unwrap();
+    assert!(unsub.removed);
+    assert!(!client.unsubscribe_confirmed(sub.id).await.unwrap().removed);
+
+    // Requests without a reply of their own still get an ACK
+    client
+        .request(clasp_core::Message::Publish(clasp_core::PublishMessage {
+            address: "/corr/hit".to_string(),
+            signal: Some(clasp_core::SignalType::Event),
+            value: None,
+            payload: Some(Value::Int(1)),
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        }))
+        .await
+        .expect("publish should be acknowledged");
+
+    // A refused SET fails its own future with the router's error
+    let stale = clasp_core::Message::Set(clasp_core::SetMessage {
+        address: "/corr/level".to_string(),
+        value: Value::Float(1.0),
+        revision: Some(first.revision.unwrap()),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    match client.request(stale).await {
+        Err(e) => {
+            assert_eq!(
+                e.error_code(),
+                Some(clasp_core::ErrorCode::RevisionConflict)
+            );
+        }
+        Ok(ack) => panic!("expected a revision conflict, got {:?}", ack),
+    }
+}
+
 #[tokio::test]
 async fn test_operations_after_close() {
     let router = TestRouter::start().await;
@@ -1017,3 +1235,545 @@ async fn test_special_characters_in_address() {
 
     client.close().await;
 }
+
+// ============================================================================
+// Reconnect Tests
+// ============================================================================
+
+/// TCP proxy in front of a router whose open connections can be cut
+struct CutProxy {
+    port: u16,
+    conns: std::sync::Arc<parking_lot::Mutex<Vec<tokio::task::JoinHandle<()>>>>,
+}
+
+impl CutProxy {
+    async fn start(upstream: u16) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let conns = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let tracked = conns.clone();
+        tokio::spawn(async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                tracked.lock().push(tokio::spawn(async move {
+                    if let Ok(mut outbound) =
+                        tokio::net::TcpStream::connect(("127.0.0.1", upstream)).await
+                    {
+                        let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                    }
+                }));
+            }
+        });
+        Self { port, conns }
+    }
+
+    fn url(&self) -> String {
+        format!("ws://127.0.0.1:{}", self.port)
+    }
+
+    fn cut(&self) {
+        for conn in self.conns.lock().drain(..) {
+            conn.abort();
+        }
+    }
+}
+
+async fn next_event(
+    events: &mut tokio::sync::broadcast::Receiver<ClientEvent>,
+) -> Option<ClientEvent> {
+    timeout(Duration::from_secs(5), events.recv())
+        .await
+        .ok()?
+        .ok()
+}
+
+#[tokio::test]
+async fn test_reconnect_policy_delays() {
+    let policy = ReconnectPolicy {
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+        backoff: Backoff::Exponential(2.0),
+        ..Default::default()
+    };
+    assert_eq!(policy.delay(0), Duration::from_millis(100));
+    assert_eq!(policy.delay(2), Duration::from_millis(400));
+    assert_eq!(policy.delay(10), Duration::from_millis(1000));
+
+    let linear = ReconnectPolicy {
+        backoff: Backoff::Linear,
+        ..policy.clone()
+    };
+    assert_eq!(linear.delay(2), Duration::from_millis(300));
+
+    let jittered = ReconnectPolicy {
+        backoff: Backoff::Constant,
+        jitter: 0.5,
+        ..policy
+    };
+    for _ in 0..50 {
+        let delay = jittered.delay(3);
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+    }
+
+    let unlimited = ReconnectPolicy {
+        max_attempts: 0,
+        ..Default::default()
+    };
+    assert!(!unlimited.gives_up(1000));
+    assert!(ReconnectPolicy::default().gives_up(10));
+}
+
+#[tokio::test]
+async fn test_reconnect_events() {
+    let router = TestRouter::start().await;
+    let proxy = CutProxy::start(router.port()).await;
+
+    let client = std::sync::Arc::new(
+        ClaspBuilder::new(&proxy.url())
+            .reconnect_policy(ReconnectPolicy {
+                initial_delay: Duration::from_millis(50),
+                ..Default::default()
+            })
+            .connect()
+            .await
+            .expect("Connect failed"),
+    );
+    client.start_reconnect_loop();
+
+    // Written by another client; this one isn't subscribed so it only
+    // learns the value from the snapshot after reconnecting
+    let writer = router.connect_client().await.expect("Connect failed");
+    writer.set("/reconnect/value", 1.0).await.unwrap();
+    writer.get("/reconnect/value").await.unwrap();
+    assert_eq!(client.cached("/reconnect/value"), None);
+    let first_session = client.session_id().unwrap();
+    let mut events = client.events();
+
+    proxy.cut();
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Dropped { .. })
+    ));
+    assert_eq!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Reconnecting {
+            attempt: 1,
+            delay: Duration::from_millis(50)
+        })
+    );
+    match next_event(&mut events).await {
+        Some(ClientEvent::Connected { session }) => assert_ne!(session, first_session),
+        other => panic!("expected Connected, got {:?}", other),
+    }
+    assert_eq!(
+        next_event(&mut events).await,
+        Some(ClientEvent::SnapshotResynced)
+    );
+    assert!(client.is_connected());
+    assert_eq!(client.cached("/reconnect/value"), Some(Value::Float(1.0)));
+
+    writer.close().await;
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_connect_shared_resubscribes() {
+    let router = TestRouter::start().await;
+    let proxy = CutProxy::start(router.port()).await;
+
+    let client = ClaspBuilder::new(&proxy.url())
+        .reconnect_policy(ReconnectPolicy {
+            initial_delay: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .connect_shared()
+        .await
+        .expect("Connect failed");
+    let collector = ValueCollector::new();
+    client
+        .subscribe("/resub/**", collector.callback_ref())
+        .await
+        .expect("Subscribe failed");
+    let mut events = client.events();
+
+    proxy.cut();
+    loop {
+        match next_event(&mut events).await {
+            Some(ClientEvent::SnapshotResynced) => break,
+            Some(_) => {}
+            None => panic!("client did not resync"),
+        }
+    }
+
+    // Only delivered if the subscription was restored on the new session
+    let writer = router.connect_client().await.expect("Connect failed");
+    writer.set("/resub/value", 2.0).await.unwrap();
+    assert!(
+        wait_for(
+            || async { collector.has_address("/resub/value") },
+            Duration::from_millis(20),
+            Duration::from_secs(2),
+        )
+        .await
+    );
+
+    writer.close().await;
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_reconnect_gives_up() {
+    let mut router = TestRouter::start().await;
+    let proxy = CutProxy::start(router.port()).await;
+
+    let client = std::sync::Arc::new(
+        ClaspBuilder::new(&proxy.url())
+            .reconnect_policy(ReconnectPolicy {
+                max_attempts: 2,
+                initial_delay: Duration::from_millis(20),
+                backoff: Backoff::Constant,
+                ..Default::default()
+            })
+            .connect()
+            .await
+            .expect("Connect failed"),
+    );
+    client.start_reconnect_loop();
+    let mut events = client.events();
+
+    router.stop();
+    proxy.cut();
+
+    let mut reconnecting = 0;
+    loop {
+        match next_event(&mut events).await {
+            Some(ClientEvent::Reconnecting { .. }) => reconnecting += 1,
+            Some(ClientEvent::GaveUp { attempts }) => {
+                assert_eq!(attempts, 2);
+                break;
+            }
+            Some(ClientEvent::Dropped { .. }) => {}
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+    assert_eq!(reconnecting, 2);
+    assert!(!client.is_connected());
+}
+
+// ============================================================================
+// Offline Tests
+// ============================================================================
+
+/// Reconnect policy that leaves a window to write while disconnected
+fn slow_reconnect() -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_delay: Duration::from_millis(300),
+        backoff: Backoff::Constant,
+        ..Default::default()
+    }
+}
+
+async fn wait_replayed(client: &ClaspOffline) -> bool {
+    wait_for(
+        || async { client.pending().is_empty() },
+        Duration::from_millis(20),
+        Duration::from_secs(5),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_offline_set_replays_on_reconnect() {
+    let router = TestRouter::start().await;
+    let proxy = CutProxy::start(router.port()).await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let client = ClaspOffline::open(dir.path().join("state"))
+        .connect(ClaspBuilder::new(&proxy.url()).reconnect_policy(slow_reconnect()))
+        .await
+        .expect("Connect failed");
+    assert!(client.is_connected());
+    let mut events = client.events();
+
+    proxy.cut();
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Dropped { .. })
+    ));
+
+    client.set("/offline/a", 1.0).await.unwrap();
+    assert_eq!(client.pending(), vec!["/offline/a".to_string()]);
+    assert_eq!(client.cached("/offline/a"), Some(Value::Float(1.0)));
+
+    assert!(wait_replayed(&client).await, "SET was not replayed");
+    let reader = router.connect_client().await.expect("Connect failed");
+    assert_eq!(reader.get("/offline/a").await.unwrap(), Value::Float(1.0));
+
+    reader.close().await;
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_offline_conflict_resolution() {
+    let router = TestRouter::start().await;
+    let proxy = CutProxy::start(router.port()).await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let writer = router.connect_client().await.expect("Connect failed");
+    writer.set("/offline/b", 1.0).await.unwrap();
+    writer.get("/offline/b").await.unwrap();
+
+    let conflicts = std::sync::Arc::new(parking_lot::Mutex::new(Vec::<Conflict>::new()));
+    let seen = conflicts.clone();
+    let client = ClaspOffline::open(dir.path().join("state"))
+        .on_conflict(move |conflict| {
+            seen.lock().push(conflict.clone());
+            Resolution::Remote
+        })
+        .connect(ClaspBuilder::new(&proxy.url()).reconnect_policy(slow_reconnect()))
+        .await
+        .expect("Connect failed");
+    assert!(
+        wait_for(
+            || async { client.cached("/offline/b") == Some(Value::Float(1.0)) },
+            Duration::from_millis(20),
+            Duration::from_secs(2),
+        )
+        .await
+    );
+    let mut events = client.events();
+
+    proxy.cut();
+    assert!(matches!(
+        next_event(&mut events).await,
+        Some(ClientEvent::Dropped { .. })
+    ));
+
+    // Both sides write while disconnected; the offline write is the later one
+    writer.set("/offline/b", 2.0).await.unwrap();
+    writer.get("/offline/b").await.unwrap();
+    // Well past the error of the synced clock
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    client.set("/offline/b", 3.0).await.unwrap();
+
+    assert!(wait_replayed(&client).await, "conflict was not resolved");
+    let conflicts = conflicts.lock().clone();
+    assert_eq!(conflicts.len(), 1);
+    let conflict = &conflicts[0];
+    assert_eq!(conflict.address, "/offline/b");
+    assert_eq!(conflict.local, Value::Float(3.0));
+    assert_eq!(conflict.remote, Value::Float(2.0));
+    assert_ne!(conflict.base_revision, Some(conflict.remote_revision));
+    assert_eq!(conflict.last_writer_wins(), Resolution::Local);
+
+    // The resolver kept the router's value
+    assert_eq!(client.cached("/offline/b"), Some(Value::Float(2.0)));
+    let reader = router.connect_client().await.expect("Connect failed");
+    assert_eq!(reader.get("/offline/b").await.unwrap(), Value::Float(2.0));
+
+    reader.close().await;
+    writer.close().await;
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_offline_state_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state");
+
+    // Nothing listens here, so the client starts offline
+    let port = clasp_test_utils::find_available_port().await;
+    let client = ClaspOffline::open(&path)
+        .connect(ClaspBuilder::new(&format!("ws://127.0.0.1:{}", port)))
+        .await
+        .expect("Offline start failed");
+    assert!(!client.is_connected());
+    client.set("/offline/c", 4.0).await.unwrap();
+    client.close().await.unwrap();
+    assert!(path.exists());
+
+    let router = TestRouter::start().await;
+    let client = ClaspOffline::open(&path)
+        .connect(ClaspBuilder::new(&router.url()))
+        .await
+        .expect("Connect failed");
+    assert_eq!(client.cached("/offline/c"), Some(Value::Float(4.0)));
+
+    assert!(wait_replayed(&client).await, "stored SET was not replayed");
+    let reader = router.connect_client().await.expect("Connect failed");
+    assert_eq!(reader.get("/offline/c").await.unwrap(), Value::Float(4.0));
+
+    reader.close().await;
+    client.close().await.unwrap();
+}
+
+// ============================================================================
+// Simulation Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_simulation_generates_within_range() {
+    let router = TestRouter::start().await;
+    let watcher = Clasp::connect_to(&router.url())
+        .await
+        .expect("Connect failed");
+    let simulator = Clasp::connect_to(&router.url())
+        .await
+        .expect("Connect failed");
+
+    let collector = ValueCollector::new();
+    watcher
+        .subscribe("/sim/**", collector.callback_ref())
+        .await
+        .expect("Subscribe failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut walk = SimSignal::new("/sim/walk", Waveform::RandomWalk { step: 0.5 });
+    walk.rate = 50.0;
+    walk.min = 10.0;
+    walk.max = 20.0;
+    let mut sine = SimSignal::new("/sim/sine", Waveform::Sine { period: 0.2 });
+    sine.rate = 50.0;
+    let sim = Simulation::new(SimConfig {
+        seed: Some(3),
+        signals: vec![walk, sine],
+    })
+    .expect("Config should be valid");
+
+    let stopped = timeout(Duration::from_millis(400), sim.run(&simulator)).await;
+    assert!(stopped.is_err(), "Periodic signals run until stopped");
+    assert!(collector.wait_for_count(20, Duration::from_secs(2)).await);
+
+    for (address, value) in collector.values() {
+        let value = value.as_f64().expect("Generated values are floats");
+        match address.as_str() {
+            "/sim/walk" => assert!((10.0..=20.0).contains(&value), "walk {}", value),
+            "/sim/sine" => assert!((0.0..=1.0).contains(&value), "sine {}", value),
+            other => panic!("Unexpected address {}", other),
+        }
+    }
+
+    watcher.close().await;
+    simulator.close().await;
+}
+
+#[tokio::test]
+async fn test_simulation_plays_cues_in_order() {
+    let router = TestRouter::start().await;
+    let watcher = Clasp::connect_to(&router.url())
+        .await
+        .expect("Connect failed");
+    let simulator = Clasp::connect_to(&router.url())
+        .await
+        .expect("Connect failed");
+
+    let collector = ValueCollector::new();
+    watcher
+        .subscribe("/show/cue", collector.callback_ref())
+        .await
+        .expect("Subscribe failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let cue = |name: &str| Cue {
+        value: Value::String(name.to_string()),
+        hold: 0.05,
+    };
+    let mut cues = SimSignal::new(
+        "/show/cue",
+        Waveform::Cues {
+            cues: vec![cue("intro"), cue("verse"), cue("outro")],
+            repeat: false,
+        },
+    );
+    cues.signal = SignalType::Event;
+    let sim = Simulation::new(SimConfig {
+        seed: None,
+        signals: vec![cues],
+    })
+    .expect("Config should be valid");
+
+    timeout(Duration::from_secs(2), sim.run(&simulator))
+        .await
+        .expect("A non-repeating cue list ends")
+        .expect("Cues should be sent");
+
+    assert!(collector.wait_for_count(3, Duration::from_secs(2)).await);
+    let played: Vec<Value> = collector.values().into_iter().map(|(_, v)| v).collect();
+    assert_eq!(
+        played,
+        vec![
+            Value::String("intro".to_string()),
+            Value::String("verse".to_string()),
+            Value::String("outro".to_string()),
+        ]
+    );
+
+    watcher.close().await;
+    simulator.close().await;
+}
+
+#[test]
+fn test_simulation_rejects_bad_config() {
+    let mut inverted = SimSignal::new("/sim/bad", Waveform::Noise);
+    inverted.min = 1.0;
+    inverted.max = 0.0;
+    let mut stopped = SimSignal::new("/sim/bad", Waveform::Noise);
+    stopped.rate = 0.0;
+    let silent = SimSignal::new(
+        "/sim/bad",
+        Waveform::Cues {
+            cues: Vec::new(),
+            repeat: true,
+        },
+    );
+
+    for signal in [inverted, stopped, silent] {
+        assert!(Simulation::new(SimConfig {
+            seed: None,
+            signals: vec![signal],
+        })
+        .is_err());
+    }
+}
+
+// ============================================================================
+// QUIC Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_quic_connect_set_get() {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let port = {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap().port()
+    };
+    let addr = format!("127.0.0.1:{}", port).parse().unwrap();
+    let router = std::sync::Arc::new(clasp_router::Router::default());
+    let server = router.clone();
+    tokio::spawn(async move {
+        let _ = server
+            .serve_quic(addr, cert.der().to_vec(), key_pair.serialize_der())
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = ClaspBuilder::new(&format!("quic://localhost:{}", port))
+        .quic_config(clasp_transport::QuicConfig::insecure())
+        .connect()
+        .await
+        .expect("QUIC connect failed");
+    assert!(client.is_connected());
+
+    client.set("/quic/value", 42.0).await.expect("Set failed");
+    let value = client.get("/quic/value").await.expect("Get failed");
+    assert_eq!(value.as_f64(), Some(42.0));
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_quic_url_requires_port() {
+    let result = Clasp::connect_to("quic://localhost").await;
+    assert!(matches!(result, Err(ClientError::ConnectionFailed(_))));
+}
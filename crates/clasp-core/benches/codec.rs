@@ -11,6 +11,8 @@ fn encode_benchmark(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     c.bench_function("encode_set_message", |b| {
@@ -26,6 +28,8 @@ fn decode_benchmark(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     let encoded = codec::encode(&msg).unwrap();
 
@@ -53,6 +57,8 @@ fn roundtrip_benchmark(c: &mut Criterion) {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     c.bench_function("roundtrip_complex_message", |b| {
@@ -0,0 +1,363 @@
+//! Topic aliases for hot addresses
+//!
+//! High-rate streams repeat the same long address in every frame. Either side
+//! of a connection can bind a small integer to an address with an
+//! [`AliasMessage`](crate::AliasMessage); later SET and PUBLISH frames then
+//! carry the alias instead of the address string. Aliases are per direction:
+//! a sender's bindings only apply to frames it sends.
+//!
+//! In memory an aliased address is written `#<n>`, which the codec encodes as
+//! a two-byte reference instead of a string.
+//!
+//! ```
+//! use clasp_core::alias::{alias_address, InboundAliases, OutboundAliases};
+//! use clasp_core::{Message, SetMessage, Value};
+//!
+//! let mut out = OutboundAliases::new(16, 1);
+//! let mut inbound = InboundAliases::new(16);
+//!
+//! let mut msg = Message::Set(SetMessage {
+//!     address: "/lights/front/brightness".to_string(),
+//!     value: Value::Float(0.5),
+//!     revision: None,
+//!     lock: false,
+//!     unlock: false,
+//!     ttl: None,
+//!     trace: false,
+//!     branch: None,
+//! });
+//! let bind = out.apply(&mut msg).unwrap();
+//! assert_eq!(bind.alias, 1);
+//!
+//! inbound.bind(&bind).unwrap();
+//! inbound.resolve(&mut msg).unwrap();
+//! assert!(matches!(msg, Message::Set(ref s) if s.address == "/lights/front/brightness"));
+//! assert_eq!(alias_address(1), "#1");
+//! ```
+
+use crate::{AliasMessage, Message};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Feature name advertised in HELLO/WELCOME by peers that understand aliases
+pub const FEATURE: &str = "alias";
+
+/// Largest alias number the wire format can carry
+pub const MAX_ALIAS: u16 = 0x7FFF;
+
+/// Aliases a peer that advertises [`FEATURE`] accepts at the least, so the
+/// other side can alias this many without further negotiation
+pub const MIN_ALIASES: u16 = 64;
+
+/// Upper bound on addresses tracked while waiting to become hot
+const MAX_TRACKED: usize = 4096;
+
+/// Alias errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AliasError {
+    /// A frame referenced an alias that was never bound
+    #[error("unknown alias: {0}")]
+    Unknown(u16),
+
+    /// The alias number is zero or above the negotiated maximum
+    #[error("alias {alias} out of range (max {max})")]
+    OutOfRange { alias: u16, max: u16 },
+
+    /// Aliases can only stand for concrete addresses
+    #[error("cannot alias address: {0}")]
+    InvalidAddress(String),
+}
+
+/// The in-memory form of an aliased address
+pub fn alias_address(alias: u16) -> String {
+    format!("#{}", alias)
+}
+
+/// Parse an address written as `#<n>`, returning the alias number
+pub fn parse_alias_address(address: &str) -> Option<u16> {
+    let alias: u16 = address.strip_prefix('#')?.parse().ok()?;
+    (1..=MAX_ALIAS).contains(&alias).then_some(alias)
+}
+
+/// Aliases bound by the remote side, used to resolve frames it sends
+#[derive(Debug, Default)]
+pub struct InboundAliases {
+    map: HashMap<u16, String>,
+    max: u16,
+}
+
+impl InboundAliases {
+    /// Accept up to `max` aliases from the peer
+    pub fn new(max: u16) -> Self {
+        Self {
+            map: HashMap::new(),
+            max: max.min(MAX_ALIAS),
+        }
+    }
+
+    /// Apply an ALIAS message. An empty address releases the alias.
+    pub fn bind(&mut self, msg: &AliasMessage) -> Result<(), AliasError> {
+        if msg.alias == 0 || msg.alias > self.max {
+            return Err(AliasError::OutOfRange {
+                alias: msg.alias,
+                max: self.max,
+            });
+        }
+        if msg.address.is_empty() {
+            self.map.remove(&msg.alias);
+            return Ok(());
+        }
+        if !msg.address.starts_with('/') || msg.address.contains('*') {
+            return Err(AliasError::InvalidAddress(msg.address.clone()));
+        }
+        self.map.insert(msg.alias, msg.address.clone());
+        Ok(())
+    }
+
+    /// Replace alias references in SET and PUBLISH addresses (including
+    /// inside bundles) with the addresses they stand for
+    pub fn resolve(&self, message: &mut Message) -> Result<(), AliasError> {
+        match message {
+            Message::Set(set) => self.resolve_address(&mut set.address),
+            Message::Publish(publish) => self.resolve_address(&mut publish.address),
+            Message::Bundle(bundle) => bundle.messages.iter_mut().try_for_each(|m| self.resolve(m)),
+            _ => Ok(()),
+        }
+    }
+
+    fn resolve_address(&self, address: &mut String) -> Result<(), AliasError> {
+        if let Some(alias) = parse_alias_address(address) {
+            let resolved = self.map.get(&alias).ok_or(AliasError::Unknown(alias))?;
+            address.clone_from(resolved);
+        }
+        Ok(())
+    }
+
+    /// Number of bound aliases
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Aliases this side assigns to its own hot addresses
+#[derive(Debug)]
+pub struct OutboundAliases {
+    assigned: HashMap<String, u16>,
+    hits: HashMap<String, u32>,
+    next: u16,
+    max: u16,
+    hot_after: u32,
+}
+
+impl OutboundAliases {
+    /// Assign up to `max` aliases, each once an address has been sent
+    /// `hot_after` times
+    pub fn new(max: u16, hot_after: u32) -> Self {
+        Self {
+            assigned: HashMap::new(),
+            hits: HashMap::new(),
+            next: 1,
+            max: max.min(MAX_ALIAS),
+            hot_after: hot_after.max(1),
+        }
+    }
+
+    /// Rewrite the address of a SET or PUBLISH to its alias.
+    ///
+    /// When the address just became hot, returns the ALIAS binding that must
+    /// be sent before `message`.
+    pub fn apply(&mut self, message: &mut Message) -> Option<AliasMessage> {
+        let address = match message {
+            Message::Set(set) => &mut set.address,
+            Message::Publish(publish) => &mut publish.address,
+            _ => return None,
+        };
+        if let Some(&alias) = self.assigned.get(address.as_str()) {
+            *address = alias_address(alias);
+            return None;
+        }
+        if self.next > self.max || !address.starts_with('/') || address.contains('*') {
+            return None;
+        }
+
+        let hits = match self.hits.get_mut(address.as_str()) {
+            Some(hits) => hits,
+            None => {
+                if self.hits.len() >= MAX_TRACKED {
+                    self.hits.clear();
+                }
+                self.hits.entry(address.clone()).or_insert(0)
+            }
+        };
+        *hits += 1;
+        if *hits < self.hot_after {
+            return None;
+        }
+
+        self.hits.remove(address.as_str());
+        let alias = self.next;
+        self.next += 1;
+        self.assigned.insert(address.clone(), alias);
+        let bind = AliasMessage {
+            alias,
+            address: std::mem::replace(address, alias_address(alias)),
+        };
+        Some(bind)
+    }
+
+    /// Stop using the alias for `address`, e.g. when its binding could not
+    /// be delivered. The alias number is not reused.
+    pub fn forget(&mut self, address: &str) {
+        self.assigned.remove(address);
+    }
+
+    /// Number of assigned aliases
+    pub fn len(&self) -> usize {
+        self.assigned.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assigned.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PublishMessage, SetMessage, SignalType, Value};
+
+    fn set(address: &str) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(1.0),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    fn publish(address: &str) -> Message {
+        Message::Publish(PublishMessage {
+            address: address.to_string(),
+            signal: Some(SignalType::Stream),
+            value: Some(Value::Float(1.0)),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        })
+    }
+
+    fn address(msg: &Message) -> &str {
+        match msg {
+            Message::Set(s) => &s.address,
+            Message::Publish(p) => &p.address,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_alias_address() {
+        assert_eq!(parse_alias_address("#7"), Some(7));
+        assert_eq!(parse_alias_address("#0"), None);
+        assert_eq!(parse_alias_address("#40000"), None);
+        assert_eq!(parse_alias_address("/a/b"), None);
+        assert_eq!(parse_alias_address("#x"), None);
+    }
+
+    #[test]
+    fn test_outbound_assigns_after_hot() {
+        let mut out = OutboundAliases::new(4, 3);
+        let mut msg = publish("/gesture/touch");
+        assert!(out.apply(&mut msg).is_none());
+        let mut msg = publish("/gesture/touch");
+        assert!(out.apply(&mut msg).is_none());
+        assert_eq!(address(&msg), "/gesture/touch");
+
+        let mut msg = publish("/gesture/touch");
+        let bind = out.apply(&mut msg).unwrap();
+        assert_eq!(bind.alias, 1);
+        assert_eq!(bind.address, "/gesture/touch");
+        assert_eq!(address(&msg), "#1");
+
+        // Once bound, later frames use the alias without another binding
+        let mut msg = set("/gesture/touch");
+        assert!(out.apply(&mut msg).is_none());
+        assert_eq!(address(&msg), "#1");
+    }
+
+    #[test]
+    fn test_outbound_respects_max() {
+        let mut out = OutboundAliases::new(1, 1);
+        assert!(out.apply(&mut set("/a")).is_some());
+        let mut msg = set("/b");
+        assert!(out.apply(&mut msg).is_none());
+        assert_eq!(address(&msg), "/b");
+        assert_eq!(out.len(), 1);
+
+        let mut disabled = OutboundAliases::new(0, 1);
+        assert!(disabled.apply(&mut set("/a")).is_none());
+    }
+
+    #[test]
+    fn test_inbound_bind_and_resolve() {
+        let mut inbound = InboundAliases::new(8);
+        inbound
+            .bind(&AliasMessage {
+                alias: 3,
+                address: "/mixer/gain".to_string(),
+            })
+            .unwrap();
+
+        let mut msg = set("#3");
+        inbound.resolve(&mut msg).unwrap();
+        assert_eq!(address(&msg), "/mixer/gain");
+
+        let mut msg = set("#4");
+        assert_eq!(inbound.resolve(&mut msg), Err(AliasError::Unknown(4)));
+
+        // Unbinding releases the alias
+        inbound
+            .bind(&AliasMessage {
+                alias: 3,
+                address: String::new(),
+            })
+            .unwrap();
+        assert!(inbound.is_empty());
+    }
+
+    #[test]
+    fn test_inbound_rejects_bad_bindings() {
+        let mut inbound = InboundAliases::new(8);
+        let bind = |alias: u16, address: &str| AliasMessage {
+            alias,
+            address: address.to_string(),
+        };
+        assert!(matches!(
+            inbound.bind(&bind(9, "/a")),
+            Err(AliasError::OutOfRange { alias: 9, max: 8 })
+        ));
+        assert!(matches!(
+            inbound.bind(&bind(0, "/a")),
+            Err(AliasError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            inbound.bind(&bind(1, "/a/*")),
+            Err(AliasError::InvalidAddress(_))
+        ));
+        assert!(matches!(
+            inbound.bind(&bind(1, "#2")),
+            Err(AliasError::InvalidAddress(_))
+        ));
+    }
+}
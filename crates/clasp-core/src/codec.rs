@@ -23,14 +23,19 @@ pub mod msg {
     pub const HELLO: u8 = 0x01;
     pub const WELCOME: u8 = 0x02;
     pub const ANNOUNCE: u8 = 0x03;
+    pub const PROOF: u8 = 0x05;
     pub const SUBSCRIBE: u8 = 0x10;
     pub const UNSUBSCRIBE: u8 = 0x11;
+    pub const ALIAS: u8 = 0x12;
+    pub const SUBSCRIBE_ACK: u8 = 0x13;
+    pub const UNSUBSCRIBE_ACK: u8 = 0x14;
     pub const PUBLISH: u8 = 0x20;
     pub const SET: u8 = 0x21;
     pub const GET: u8 = 0x22;
     pub const SNAPSHOT: u8 = 0x23;
     pub const FEDERATION_SYNC: u8 = 0x04;
     pub const REPLAY: u8 = 0x24;
+    pub const SET_MANY: u8 = 0x25;
     pub const BUNDLE: u8 = 0x30;
     pub const SYNC: u8 = 0x40;
     pub const PING: u8 = 0x41;
@@ -41,6 +46,14 @@ pub mod msg {
     pub const RESULT: u8 = 0x61;
 }
 
+/// SET_MANY element type for integers in 0..=255, one byte each
+const ELEM_U8: u8 = 0x0C;
+/// SET_MANY element type for values that each carry their own type code
+const ELEM_MIXED: u8 = 0x0F;
+
+/// High bit of a SET/PUBLISH address length marks an alias reference
+const ALIAS_REF: u16 = 0x8000;
+
 /// Value type codes for efficient binary encoding
 pub mod val {
     pub const NULL: u8 = 0x00;
@@ -100,6 +113,7 @@ fn estimate_message_size(msg: &Message) -> usize {
                 + if m.ttl.is_some() { 4 } else { 0 }
         }
         Message::Publish(m) => 2 + 2 + m.address.len() + 16,
+        Message::SetMany(m) => 10 + m.base.len() + m.len() * 9,
         Message::Hello(m) => 4 + m.name.len() + 2,
         Message::Welcome(m) => 12 + m.name.len() + m.session.len() + 4,
         Message::Subscribe(m) => 6 + m.pattern.len() + 16,
@@ -165,6 +179,17 @@ pub fn encode_with_options(
     frame.encode()
 }
 
+/// Encode a request whose ACK or ERROR should carry `correlation_id`
+/// (binary encoding)
+pub fn encode_with_correlation(message: &Message, correlation_id: u32) -> Result<Bytes> {
+    let payload = encode_message(message)?;
+    let mut frame = Frame::new(payload)
+        .with_qos(message.default_qos())
+        .with_correlation_id(correlation_id);
+    frame.flags.version = 1; // binary encoding (1 = binary, 0 = MessagePack legacy)
+    frame.encode()
+}
+
 /// Decode a frame and extract the message
 #[inline]
 pub fn decode(bytes: &[u8]) -> Result<(Message, Frame)> {
@@ -184,6 +209,20 @@ pub fn decode_payload(bytes: &[u8]) -> Result<Message> {
     decode_message(bytes)
 }
 
+/// Address and signal type of a binary SET or PUBLISH payload, read
+/// without decoding its value. `None` for any other message.
+pub fn peek_address(payload: &[u8]) -> Option<(String, SignalType)> {
+    let (&msg_type, rest) = payload.split_first()?;
+    let (&flags, mut buf) = rest.split_first()?;
+    let signal = match msg_type {
+        msg::SET => SignalType::Param,
+        msg::PUBLISH => signal_type_from_code((flags >> 5) & 0x07),
+        _ => return None,
+    };
+    let address = decode_address(&mut buf).ok()?;
+    Some((address, signal))
+}
+
 // ============================================================================
 // BINARY ENCODING
 // ============================================================================
@@ -192,11 +231,16 @@ fn encode_message_to_buf(buf: &mut BytesMut, msg: &Message) -> Result<()> {
     match msg {
         Message::Hello(m) => encode_hello(buf, m),
         Message::Welcome(m) => encode_welcome(buf, m),
+        Message::Proof(m) => encode_proof(buf, m),
         Message::Announce(m) => encode_announce(buf, m),
         Message::Subscribe(m) => encode_subscribe(buf, m),
         Message::Unsubscribe(m) => encode_unsubscribe(buf, m),
+        Message::Alias(m) => encode_alias(buf, m),
+        Message::SubscribeAck(m) => encode_subscribe_ack(buf, m),
+        Message::UnsubscribeAck(m) => encode_unsubscribe_ack(buf, m),
         Message::Publish(m) => encode_publish(buf, m),
         Message::Set(m) => encode_set(buf, m),
+        Message::SetMany(m) => encode_set_many(buf, m),
         Message::Get(m) => encode_get(buf, m),
         Message::Snapshot(m) => encode_snapshot(buf, m),
         Message::Replay(m) => encode_replay(buf, m),
@@ -220,6 +264,7 @@ fn encode_message_to_buf(buf: &mut BytesMut, msg: &Message) -> Result<()> {
 
 /// SET (0x21) - Parameter Update
 /// Flags: [has_rev:1][lock:1][unlock:1][rsv:1][vtype:4]
+/// A trailing options byte (bit 0: trace) is only written for traced SETs
 #[inline]
 fn encode_set(buf: &mut BytesMut, msg: &SetMessage) -> Result<()> {
     buf.put_u8(msg::SET);
@@ -241,7 +286,7 @@ fn encode_set(buf: &mut BytesMut, msg: &SetMessage) -> Result<()> {
     buf.put_u8(flags);
 
     // Address
-    encode_string(buf, &msg.address)?;
+    encode_address(buf, &msg.address)?;
 
     // Value (type already in flags for simple types)
     encode_value_data(buf, &msg.value)?;
@@ -256,11 +301,138 @@ fn encode_set(buf: &mut BytesMut, msg: &SetMessage) -> Result<()> {
         let raw = match ttl {
             Ttl::Never => 0u32,
             Ttl::Sliding(secs) => secs & 0x7FFF_FFFF,
-            Ttl::Absolute(secs) => (secs & 0x7FFF_FFFF) | 0x8000_0000,
+            // Absolute(0) would expire on arrival, so its raw form marks Session
+            Ttl::Absolute(secs) => (secs & 0x7FFF_FFFF).max(1) | 0x8000_0000,
+            Ttl::Session => 0x8000_0000,
         };
         buf.put_u32(raw);
     }
 
+    // Trailing options: bit 0 trace, bit 1 branch name follows
+    let mut options: u8 = 0;
+    if msg.trace {
+        options |= 0x01;
+    }
+    if msg.branch.is_some() {
+        options |= 0x02;
+    }
+    if options != 0 {
+        buf.put_u8(options);
+    }
+    if let Some(ref branch) = msg.branch {
+        encode_string(buf, branch)?;
+    }
+
+    Ok(())
+}
+
+/// SET_MANY (0x25) - Writes under one base address
+/// Flags: [indexed:1][rsv:3][etype:4]
+/// Then the base, a u16 count, a u32 start index (runs only), and per entry
+/// a u32 index (indexed only) and the value. When every value has the same
+/// fixed-size type, `etype` names it and values are written bare; otherwise
+/// `etype` is MIXED and each value is prefixed with its type code.
+fn encode_set_many(buf: &mut BytesMut, msg: &SetManyMessage) -> Result<()> {
+    buf.put_u8(msg::SET_MANY);
+
+    let count = msg.len();
+    if count > u16::MAX as usize {
+        return Err(Error::EncodeError(format!(
+            "SET_MANY has {} values, at most {} fit",
+            count,
+            u16::MAX
+        )));
+    }
+    let etype = set_many_element_type(msg.entries().map(|(_, value)| value));
+    let indexed = matches!(msg.values, SetManyValues::Indexed(_));
+    let mut flags = etype & 0x0F;
+    if indexed {
+        flags |= 0x80;
+    }
+    buf.put_u8(flags);
+
+    encode_string(buf, &msg.base)?;
+    buf.put_u16(count as u16);
+    match &msg.values {
+        SetManyValues::Run { start, values } => {
+            buf.put_u32(*start);
+            for value in values {
+                encode_set_many_element(buf, etype, value)?;
+            }
+        }
+        SetManyValues::Indexed(entries) => {
+            for (index, value) in entries {
+                buf.put_u32(*index);
+                encode_set_many_element(buf, etype, value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The narrowest element type every value fits, or MIXED
+fn set_many_element_type<'a>(mut values: impl Iterator<Item = &'a Value>) -> u8 {
+    let Some(first) = values.next() else {
+        return ELEM_MIXED;
+    };
+    match first {
+        Value::Bool(_) => {
+            if values.all(|v| matches!(v, Value::Bool(_))) {
+                val::BOOL
+            } else {
+                ELEM_MIXED
+            }
+        }
+        Value::Int(i) => {
+            let (mut min, mut max) = (*i, *i);
+            for value in values {
+                let Value::Int(i) = value else {
+                    return ELEM_MIXED;
+                };
+                min = min.min(*i);
+                max = max.max(*i);
+            }
+            if min >= 0 && max <= u8::MAX as i64 {
+                ELEM_U8
+            } else if min >= i16::MIN as i64 && max <= i16::MAX as i64 {
+                val::I16
+            } else if min >= i32::MIN as i64 && max <= i32::MAX as i64 {
+                val::I32
+            } else {
+                val::I64
+            }
+        }
+        Value::Float(f) => {
+            let mut exact = (*f as f32) as f64 == *f;
+            for value in values {
+                let Value::Float(f) = value else {
+                    return ELEM_MIXED;
+                };
+                exact &= (*f as f32) as f64 == *f;
+            }
+            if exact {
+                val::F32
+            } else {
+                val::F64
+            }
+        }
+        _ => ELEM_MIXED,
+    }
+}
+
+fn encode_set_many_element(buf: &mut BytesMut, etype: u8, value: &Value) -> Result<()> {
+    match (etype, value) {
+        (ELEM_U8, Value::Int(i)) => buf.put_u8(*i as u8),
+        (val::I16, Value::Int(i)) => buf.put_i16(*i as i16),
+        (val::I32, Value::Int(i)) => buf.put_i32(*i as i32),
+        (val::F32, Value::Float(f)) => buf.put_f32(*f as f32),
+        (ELEM_MIXED, value) => {
+            buf.put_u8(value_type_code(value));
+            encode_value_data(buf, value)?;
+        }
+        (_, value) => encode_value_data(buf, value)?,
+    }
     Ok(())
 }
 
@@ -283,7 +455,7 @@ fn encode_publish(buf: &mut BytesMut, msg: &PublishMessage) -> Result<()> {
     buf.put_u8(flags);
 
     // Address
-    encode_string(buf, &msg.address)?;
+    encode_address(buf, &msg.address)?;
 
     // Value/payload
     if let Some(ref value) = msg.value {
@@ -337,6 +509,8 @@ fn encode_hello(buf: &mut BytesMut, msg: &HelloMessage) -> Result<()> {
             "gesture" => features |= 0x10,
             "timeline" => features |= 0x08,
             "federation" => features |= 0x04,
+            "alias" => features |= 0x02,
+            "correlation" => features |= 0x01,
             _ => {}
         }
     }
@@ -352,6 +526,12 @@ fn encode_hello(buf: &mut BytesMut, msg: &HelloMessage) -> Result<()> {
         buf.put_u16(0);
     }
 
+    // Durable session request (optional, trailing so older decoders
+    // ignore it)
+    if let Some(ref resume) = msg.session_resume {
+        encode_string(buf, resume)?;
+    }
+
     Ok(())
 }
 
@@ -360,7 +540,8 @@ fn encode_welcome(buf: &mut BytesMut, msg: &WelcomeMessage) -> Result<()> {
     buf.put_u8(msg::WELCOME);
     buf.put_u8(msg.version);
 
-    // Feature flags (same as HELLO)
+    // Feature flags (same as HELLO, except 0x04: federation is only
+    // requested by peers, e2e only offered by routers)
     let mut features: u8 = 0;
     for f in &msg.features {
         match f.as_str() {
@@ -369,6 +550,9 @@ fn encode_welcome(buf: &mut BytesMut, msg: &WelcomeMessage) -> Result<()> {
             "stream" => features |= 0x20,
             "gesture" => features |= 0x10,
             "timeline" => features |= 0x08,
+            E2E_FEATURE => features |= 0x04,
+            "alias" => features |= 0x02,
+            "correlation" => features |= 0x01,
             _ => {}
         }
     }
@@ -390,6 +574,29 @@ fn encode_welcome(buf: &mut BytesMut, msg: &WelcomeMessage) -> Result<()> {
         buf.put_u16(0);
     }
 
+    // Proof of possession challenge (optional, trailing so older decoders
+    // ignore it)
+    // Durable session token follows the challenge, which is then written
+    // empty if there is none
+    if msg.challenge.is_some() || msg.resume_token.is_some() {
+        encode_string(buf, msg.challenge.as_deref().unwrap_or(""))?;
+    }
+    if let Some(ref resume_token) = msg.resume_token {
+        encode_string(buf, resume_token)?;
+        buf.put_u8(msg.resumed as u8);
+    }
+
+    Ok(())
+}
+
+/// PROOF (0x05)
+fn encode_proof(buf: &mut BytesMut, msg: &ProofMessage) -> Result<()> {
+    buf.put_u8(msg::PROOF);
+    if msg.signature.len() > u16::MAX as usize {
+        return Err(Error::PayloadTooLarge(msg.signature.len()));
+    }
+    buf.put_u16(msg.signature.len() as u16);
+    buf.extend_from_slice(&msg.signature);
     Ok(())
 }
 
@@ -401,59 +608,72 @@ fn encode_announce(buf: &mut BytesMut, msg: &AnnounceMessage) -> Result<()> {
     buf.put_u16(msg.signals.len() as u16);
 
     for sig in &msg.signals {
-        encode_string(buf, &sig.address)?;
-        buf.put_u8(signal_type_code(sig.signal_type));
+        encode_signal_definition(buf, sig)?;
+    }
 
-        // Optional fields flags
-        let mut opt_flags: u8 = 0;
-        if sig.datatype.is_some() {
-            opt_flags |= 0x01;
+    Ok(())
+}
+
+/// Signal definition as carried by ANNOUNCE and RESULT
+fn encode_signal_definition(buf: &mut BytesMut, sig: &SignalDefinition) -> Result<()> {
+    encode_string(buf, &sig.address)?;
+    buf.put_u8(signal_type_code(sig.signal_type));
+
+    // Optional fields flags
+    let mut opt_flags: u8 = 0;
+    if sig.datatype.is_some() {
+        opt_flags |= 0x01;
+    }
+    if sig.access.is_some() {
+        opt_flags |= 0x02;
+    }
+    if sig.meta.is_some() {
+        opt_flags |= 0x04;
+    }
+    buf.put_u8(opt_flags);
+
+    if let Some(ref dt) = sig.datatype {
+        encode_string(buf, dt)?;
+    }
+    if let Some(ref access) = sig.access {
+        encode_string(buf, access)?;
+    }
+    if let Some(ref meta) = sig.meta {
+        // Encode meta as simple fields
+        let mut meta_flags: u8 = 0;
+        if meta.unit.is_some() {
+            meta_flags |= 0x01;
         }
-        if sig.access.is_some() {
-            opt_flags |= 0x02;
+        if meta.range.is_some() {
+            meta_flags |= 0x02;
         }
-        if sig.meta.is_some() {
-            opt_flags |= 0x04;
+        if meta.default.is_some() {
+            meta_flags |= 0x04;
         }
-        buf.put_u8(opt_flags);
-
-        if let Some(ref dt) = sig.datatype {
-            encode_string(buf, dt)?;
+        if meta.description.is_some() {
+            meta_flags |= 0x08;
         }
-        if let Some(ref access) = sig.access {
-            encode_string(buf, access)?;
+        if meta.enum_values.is_some() {
+            meta_flags |= 0x10;
         }
-        if let Some(ref meta) = sig.meta {
-            // Encode meta as simple fields
-            let mut meta_flags: u8 = 0;
-            if meta.unit.is_some() {
-                meta_flags |= 0x01;
-            }
-            if meta.range.is_some() {
-                meta_flags |= 0x02;
-            }
-            if meta.default.is_some() {
-                meta_flags |= 0x04;
-            }
-            if meta.description.is_some() {
-                meta_flags |= 0x08;
-            }
-            buf.put_u8(meta_flags);
+        buf.put_u8(meta_flags);
 
-            if let Some(ref unit) = meta.unit {
-                encode_string(buf, unit)?;
-            }
-            if let Some((min, max)) = meta.range {
-                buf.put_f64(min);
-                buf.put_f64(max);
-            }
-            if let Some(ref default) = meta.default {
-                buf.put_u8(value_type_code(default));
-                encode_value_data(buf, default)?;
-            }
-            if let Some(ref desc) = meta.description {
-                encode_string(buf, desc)?;
-            }
+        if let Some(ref unit) = meta.unit {
+            encode_string(buf, unit)?;
+        }
+        if let Some((min, max)) = meta.range {
+            buf.put_f64(min);
+            buf.put_f64(max);
+        }
+        if let Some(ref default) = meta.default {
+            buf.put_u8(value_type_code(default));
+            encode_value_data(buf, default)?;
+        }
+        if let Some(ref desc) = meta.description {
+            encode_string(buf, desc)?;
+        }
+        if let Some(ref values) = meta.enum_values {
+            encode_value_data(buf, &Value::Array(values.clone()))?;
         }
     }
 
@@ -484,8 +704,14 @@ fn encode_subscribe(buf: &mut BytesMut, msg: &SubscribeMessage) -> Result<()> {
     }
     buf.put_u8(type_mask);
 
-    // Options
-    if let Some(ref opts) = msg.options {
+    encode_subscribe_options(buf, msg.options.as_ref())?;
+    encode_transform(buf, msg.options.as_ref().and_then(|o| o.transform.as_ref()));
+    Ok(())
+}
+
+/// Subscription options as in SUBSCRIBE: a flags byte, then the set fields
+fn encode_subscribe_options(buf: &mut BytesMut, options: Option<&SubscribeOptions>) -> Result<()> {
+    if let Some(opts) = options {
         let mut opt_flags: u8 = 0;
         if opts.max_rate.is_some() {
             opt_flags |= 0x01;
@@ -499,6 +725,18 @@ fn encode_subscribe(buf: &mut BytesMut, msg: &SubscribeMessage) -> Result<()> {
         if opts.window.is_some() {
             opt_flags |= 0x08;
         }
+        if opts.tick_ms.is_some() {
+            opt_flags |= 0x10;
+        }
+        if opts.convert_to.is_some() {
+            opt_flags |= 0x20;
+        }
+        if opts.stream.is_some() {
+            opt_flags |= 0x40;
+        }
+        if opts.branch.is_some() {
+            opt_flags |= 0x80;
+        }
         buf.put_u8(opt_flags);
 
         if let Some(rate) = opts.max_rate {
@@ -513,6 +751,24 @@ fn encode_subscribe(buf: &mut BytesMut, msg: &SubscribeMessage) -> Result<()> {
         if let Some(win) = opts.window {
             buf.put_u32(win);
         }
+        if let Some(tick) = opts.tick_ms {
+            buf.put_u32(tick);
+        }
+        if let Some(ref unit) = opts.convert_to {
+            encode_string(buf, unit)?;
+        }
+        match opts.stream {
+            Some(StreamPolicy::LatestOnly) => buf.put_u8(0),
+            Some(StreamPolicy::Ring(capacity)) => {
+                buf.put_u8(1);
+                buf.put_u32(capacity);
+            }
+            Some(StreamPolicy::Reliable) => buf.put_u8(2),
+            None => {}
+        }
+        if let Some(ref branch) = opts.branch {
+            encode_string(buf, branch)?;
+        }
     } else {
         buf.put_u8(0); // No options
     }
@@ -520,6 +776,48 @@ fn encode_subscribe(buf: &mut BytesMut, msg: &SubscribeMessage) -> Result<()> {
     Ok(())
 }
 
+/// Trailing value transform of SUBSCRIBE and SUBSCRIBE_ACK, written only when
+/// set: a flags byte, then the set steps
+fn encode_transform(buf: &mut BytesMut, transform: Option<&ValueTransform>) {
+    let Some(transform) = transform else {
+        return;
+    };
+    let mut flags: u8 = 0;
+    if transform.map.is_some() {
+        flags |= 0x01;
+    }
+    if transform.scale.is_some() {
+        flags |= 0x02;
+    }
+    if transform.offset.is_some() {
+        flags |= 0x04;
+    }
+    if transform.clamp.is_some() {
+        flags |= 0x08;
+    }
+    if transform.round {
+        flags |= 0x10;
+    }
+    buf.put_u8(flags);
+
+    if let Some(map) = transform.map {
+        buf.put_f64(map.from.0);
+        buf.put_f64(map.from.1);
+        buf.put_f64(map.to.0);
+        buf.put_f64(map.to.1);
+    }
+    if let Some(scale) = transform.scale {
+        buf.put_f64(scale);
+    }
+    if let Some(offset) = transform.offset {
+        buf.put_f64(offset);
+    }
+    if let Some((min, max)) = transform.clamp {
+        buf.put_f64(min);
+        buf.put_f64(max);
+    }
+}
+
 /// UNSUBSCRIBE (0x11)
 fn encode_unsubscribe(buf: &mut BytesMut, msg: &UnsubscribeMessage) -> Result<()> {
     buf.put_u8(msg::UNSUBSCRIBE);
@@ -527,10 +825,58 @@ fn encode_unsubscribe(buf: &mut BytesMut, msg: &UnsubscribeMessage) -> Result<()
     Ok(())
 }
 
+/// SUBSCRIBE_ACK (0x13)
+fn encode_subscribe_ack(buf: &mut BytesMut, msg: &SubscribeAckMessage) -> Result<()> {
+    buf.put_u8(msg::SUBSCRIBE_ACK);
+    buf.put_u32(msg.id);
+    buf.put_u32(msg.matched);
+    encode_subscribe_options(buf, Some(&msg.options))?;
+    match msg.correlation_id {
+        Some(corr) => {
+            buf.put_u8(0x01);
+            buf.put_u32(corr);
+        }
+        None => buf.put_u8(0),
+    }
+    encode_transform(buf, msg.options.transform.as_ref());
+    Ok(())
+}
+
+/// UNSUBSCRIBE_ACK (0x14)
+fn encode_unsubscribe_ack(buf: &mut BytesMut, msg: &UnsubscribeAckMessage) -> Result<()> {
+    buf.put_u8(msg::UNSUBSCRIBE_ACK);
+    buf.put_u32(msg.id);
+    let mut flags: u8 = 0;
+    if msg.removed {
+        flags |= 0x01;
+    }
+    if msg.correlation_id.is_some() {
+        flags |= 0x02;
+    }
+    buf.put_u8(flags);
+    if let Some(corr) = msg.correlation_id {
+        buf.put_u32(corr);
+    }
+    Ok(())
+}
+
+/// ALIAS (0x12)
+fn encode_alias(buf: &mut BytesMut, msg: &AliasMessage) -> Result<()> {
+    buf.put_u8(msg::ALIAS);
+    buf.put_u16(msg.alias);
+    encode_string(buf, &msg.address)?;
+    Ok(())
+}
+
 /// GET (0x22)
 fn encode_get(buf: &mut BytesMut, msg: &GetMessage) -> Result<()> {
     buf.put_u8(msg::GET);
     encode_string(buf, &msg.address)?;
+    // Trailing flags only when needed, so plain GETs stay byte-identical
+    if let Some(depth) = msg.depth {
+        buf.put_u8(0x01);
+        buf.put_u8(depth);
+    }
     Ok(())
 }
 
@@ -562,11 +908,17 @@ fn encode_snapshot(buf: &mut BytesMut, msg: &SnapshotMessage) -> Result<()> {
         }
     }
 
+    // Answers to correlated GETs: [correlated:1][more:1][rsv:6] + u32
+    if let Some(corr) = msg.correlation_id {
+        buf.put_u8(if msg.more { 0x03 } else { 0x01 });
+        buf.put_u32(corr);
+    }
+
     Ok(())
 }
 
 /// REPLAY (0x24) - Journal replay request
-/// Flags: [has_from:1][has_to:1][has_limit:1][rsv:5]
+/// Flags: [has_from:1][has_to:1][has_limit:1][timed:1][has_cursor:1][rsv:3]
 fn encode_replay(buf: &mut BytesMut, msg: &ReplayMessage) -> Result<()> {
     buf.put_u8(msg::REPLAY);
 
@@ -580,6 +932,12 @@ fn encode_replay(buf: &mut BytesMut, msg: &ReplayMessage) -> Result<()> {
     if msg.limit.is_some() {
         flags |= 0x20;
     }
+    if msg.timed {
+        flags |= 0x10;
+    }
+    if msg.cursor.is_some() {
+        flags |= 0x08;
+    }
     buf.put_u8(flags);
 
     encode_string(buf, &msg.pattern)?;
@@ -593,6 +951,9 @@ fn encode_replay(buf: &mut BytesMut, msg: &ReplayMessage) -> Result<()> {
     if let Some(limit) = msg.limit {
         buf.put_u32(limit);
     }
+    if let Some(ref cursor) = msg.cursor {
+        encode_string(buf, cursor)?;
+    }
 
     // Signal type filter as bitmask (same format as SUBSCRIBE)
     let mut type_mask: u8 = 0;
@@ -616,6 +977,7 @@ fn encode_replay(buf: &mut BytesMut, msg: &ReplayMessage) -> Result<()> {
 
 /// FEDERATION_SYNC (0x04) - Router-to-router federation
 /// Layout: [op:u8][pattern_count:u16][patterns...][revision_count:u16][revisions...][flags:u8][optional fields]
+/// Optional fields, in flag order: since (u64), origin (string), payload (u32 length + bytes)
 fn encode_federation_sync(buf: &mut BytesMut, msg: &FederationSyncMessage) -> Result<()> {
     buf.put_u8(msg::FEDERATION_SYNC);
     buf.put_u8(msg.op as u8);
@@ -633,7 +995,7 @@ fn encode_federation_sync(buf: &mut BytesMut, msg: &FederationSyncMessage) -> Re
         buf.put_u64(*val);
     }
 
-    // Flags: [has_since:1][has_origin:1][rsv:6]
+    // Flags: [has_since:1][has_origin:1][has_payload:1][rsv:5]
     let mut flags: u8 = 0;
     if msg.since_revision.is_some() {
         flags |= 0x80;
@@ -641,6 +1003,9 @@ fn encode_federation_sync(buf: &mut BytesMut, msg: &FederationSyncMessage) -> Re
     if msg.origin.is_some() {
         flags |= 0x40;
     }
+    if msg.payload.is_some() {
+        flags |= 0x20;
+    }
     buf.put_u8(flags);
 
     if let Some(since) = msg.since_revision {
@@ -649,6 +1014,10 @@ fn encode_federation_sync(buf: &mut BytesMut, msg: &FederationSyncMessage) -> Re
     if let Some(ref origin) = msg.origin {
         encode_string(buf, origin)?;
     }
+    if let Some(ref payload) = msg.payload {
+        buf.put_u32(payload.len() as u32);
+        buf.extend_from_slice(payload);
+    }
 
     Ok(())
 }
@@ -724,6 +1093,12 @@ fn encode_ack(buf: &mut BytesMut, msg: &AckMessage) -> Result<()> {
     if msg.correlation_id.is_some() {
         flags |= 0x10;
     }
+    if !msg.results.is_empty() {
+        flags |= 0x20;
+    }
+    if msg.cursor.is_some() {
+        flags |= 0x40;
+    }
     buf.put_u8(flags);
 
     if let Some(ref addr) = msg.address {
@@ -741,6 +1116,22 @@ fn encode_ack(buf: &mut BytesMut, msg: &AckMessage) -> Result<()> {
     if let Some(corr) = msg.correlation_id {
         buf.put_u32(corr);
     }
+    if !msg.results.is_empty() {
+        buf.put_u16(msg.results.len() as u16);
+        for result in &msg.results {
+            encode_string(buf, &result.address)?;
+            match result.revision {
+                Some(rev) => {
+                    buf.put_u8(1);
+                    buf.put_u64(rev);
+                }
+                None => buf.put_u8(0),
+            }
+        }
+    }
+    if let Some(ref cursor) = msg.cursor {
+        encode_string(buf, cursor)?;
+    }
 
     Ok(())
 }
@@ -774,33 +1165,74 @@ fn encode_error(buf: &mut BytesMut, msg: &ErrorMessage) -> Result<()> {
 fn encode_query(buf: &mut BytesMut, msg: &QueryMessage) -> Result<()> {
     buf.put_u8(msg::QUERY);
     encode_string(buf, &msg.pattern)?;
+
+    // Paging fields trail the pattern, so older decoders ignore them
+    if msg.limit.is_none() && msg.cursor.is_none() && msg.fields.is_none() {
+        return Ok(());
+    }
+    let mut flags: u8 = 0;
+    if msg.limit.is_some() {
+        flags |= 0x01;
+    }
+    if msg.cursor.is_some() {
+        flags |= 0x02;
+    }
+    if msg.fields.is_some() {
+        flags |= 0x04;
+    }
+    buf.put_u8(flags);
+    if let Some(limit) = msg.limit {
+        buf.put_u32(limit);
+    }
+    if let Some(ref cursor) = msg.cursor {
+        encode_string(buf, cursor)?;
+    }
+    if let Some(ref fields) = msg.fields {
+        let mut mask: u8 = 0;
+        for field in fields {
+            mask |= signal_field_bit(*field);
+        }
+        buf.put_u8(mask);
+    }
     Ok(())
 }
 
+/// Bit of a [`SignalField`] in QUERY's field mask (the same bits as a
+/// signal definition's optional field flags)
+fn signal_field_bit(field: SignalField) -> u8 {
+    match field {
+        SignalField::Datatype => 0x01,
+        SignalField::Access => 0x02,
+        SignalField::Meta => 0x04,
+    }
+}
+
 /// RESULT (0x61)
 fn encode_result(buf: &mut BytesMut, msg: &ResultMessage) -> Result<()> {
     buf.put_u8(msg::RESULT);
     buf.put_u16(msg.signals.len() as u16);
 
     for sig in &msg.signals {
-        encode_string(buf, &sig.address)?;
-        buf.put_u8(signal_type_code(sig.signal_type));
-
-        let mut opt_flags: u8 = 0;
-        if sig.datatype.is_some() {
-            opt_flags |= 0x01;
-        }
-        if sig.access.is_some() {
-            opt_flags |= 0x02;
-        }
-        buf.put_u8(opt_flags);
+        encode_signal_definition(buf, sig)?;
+    }
 
-        if let Some(ref dt) = sig.datatype {
-            encode_string(buf, dt)?;
-        }
-        if let Some(ref access) = sig.access {
-            encode_string(buf, access)?;
-        }
+    // Paging fields trail the signals, so older decoders ignore them
+    if msg.next_cursor.is_none() && msg.total.is_none() {
+        return Ok(());
+    }
+    let mut flags: u8 = 0;
+    if msg.next_cursor.is_some() {
+        flags |= 0x01;
+    }
+    if msg.total.is_some() {
+        flags |= 0x02;
+    }
+    buf.put_u8(flags);
+    if let Some(ref cursor) = msg.next_cursor {
+        encode_string(buf, cursor)?;
+    }
+    if let Some(total) = msg.total {
+        buf.put_u32(total);
     }
 
     Ok(())
@@ -821,6 +1253,20 @@ fn encode_string(buf: &mut BytesMut, s: &str) -> Result<()> {
     Ok(())
 }
 
+/// SET/PUBLISH address: a string whose length has the high bit clear, or an
+/// alias reference (`#<n>`) written as `0x8000 | n` with no bytes following
+#[inline(always)]
+fn encode_address(buf: &mut BytesMut, address: &str) -> Result<()> {
+    if let Some(alias) = crate::alias::parse_alias_address(address) {
+        buf.put_u16(ALIAS_REF | alias);
+        return Ok(());
+    }
+    if address.len() >= ALIAS_REF as usize {
+        return Err(Error::PayloadTooLarge(address.len()));
+    }
+    encode_string(buf, address)
+}
+
 #[inline]
 fn encode_value_data(buf: &mut BytesMut, value: &Value) -> Result<()> {
     match value {
@@ -900,11 +1346,16 @@ fn decode_v3_binary(bytes: &[u8]) -> Result<Message> {
     match msg_type {
         msg::HELLO => decode_hello(&mut buf),
         msg::WELCOME => decode_welcome(&mut buf),
+        msg::PROOF => decode_proof(&mut buf),
         msg::ANNOUNCE => decode_announce(&mut buf),
         msg::SUBSCRIBE => decode_subscribe(&mut buf),
         msg::UNSUBSCRIBE => decode_unsubscribe(&mut buf),
+        msg::ALIAS => decode_alias(&mut buf),
+        msg::SUBSCRIBE_ACK => decode_subscribe_ack(&mut buf),
+        msg::UNSUBSCRIBE_ACK => decode_unsubscribe_ack(&mut buf),
         msg::PUBLISH => decode_publish(&mut buf),
         msg::SET => decode_set(&mut buf),
+        msg::SET_MANY => decode_set_many(&mut buf),
         msg::GET => decode_get(&mut buf),
         msg::SNAPSHOT => decode_snapshot(&mut buf),
         msg::REPLAY => decode_replay(&mut buf),
@@ -930,7 +1381,7 @@ fn decode_set(buf: &mut &[u8]) -> Result<Message> {
     let unlock = (flags & 0x20) != 0;
     let has_ttl = (flags & 0x10) != 0;
 
-    let address = decode_string(buf)?;
+    let address = decode_address(buf)?;
     let value = decode_value_data(buf, vtype)?;
 
     let revision = if has_rev { Some(buf.get_u64()) } else { None };
@@ -939,6 +1390,8 @@ fn decode_set(buf: &mut &[u8]) -> Result<Message> {
         let raw = buf.get_u32();
         if raw == 0 {
             Some(Ttl::Never)
+        } else if raw == 0x8000_0000 {
+            Some(Ttl::Session)
         } else if raw & 0x8000_0000 != 0 {
             Some(Ttl::Absolute(raw & 0x7FFF_FFFF))
         } else {
@@ -947,6 +1400,13 @@ fn decode_set(buf: &mut &[u8]) -> Result<Message> {
     } else {
         None
     };
+    let options = if buf.has_remaining() { buf.get_u8() } else { 0 };
+    let trace = options & 0x01 != 0;
+    let branch = if options & 0x02 != 0 {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
 
     Ok(Message::Set(SetMessage {
         address,
@@ -955,9 +1415,73 @@ fn decode_set(buf: &mut &[u8]) -> Result<Message> {
         lock,
         unlock,
         ttl,
+        trace,
+        branch,
     }))
 }
 
+fn decode_set_many(buf: &mut &[u8]) -> Result<Message> {
+    let flags = buf.get_u8();
+    let indexed = (flags & 0x80) != 0;
+    let etype = flags & 0x0F;
+
+    let base = decode_string(buf)?;
+    if buf.remaining() < 2 {
+        return Err(Error::BufferTooSmall {
+            needed: 2,
+            have: buf.remaining(),
+        });
+    }
+    let count = buf.get_u16() as usize;
+    // Fixed-size parts only; MIXED values check their own data
+    let element_size = match etype {
+        val::NULL => 0,
+        ELEM_U8 | val::BOOL | val::I8 | ELEM_MIXED => 1,
+        val::I16 => 2,
+        val::I32 | val::F32 => 4,
+        _ => 8,
+    };
+    let needed = if indexed {
+        count * (4 + element_size)
+    } else {
+        4 + count * element_size
+    };
+    if buf.remaining() < needed {
+        return Err(Error::BufferTooSmall {
+            needed,
+            have: buf.remaining(),
+        });
+    }
+    let values = if indexed {
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let index = buf.get_u32();
+            entries.push((index, decode_set_many_element(buf, etype)?));
+        }
+        SetManyValues::Indexed(entries)
+    } else {
+        let start = buf.get_u32();
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(decode_set_many_element(buf, etype)?);
+        }
+        SetManyValues::Run { start, values }
+    };
+
+    Ok(Message::SetMany(SetManyMessage { base, values }))
+}
+
+fn decode_set_many_element(buf: &mut &[u8], etype: u8) -> Result<Value> {
+    match etype {
+        ELEM_U8 => Ok(Value::Int(buf.get_u8() as i64)),
+        ELEM_MIXED => {
+            let vtype = buf.get_u8();
+            decode_value_data(buf, vtype)
+        }
+        _ => decode_value_data(buf, etype),
+    }
+}
+
 fn decode_publish(buf: &mut &[u8]) -> Result<Message> {
     let flags = buf.get_u8();
     let sig_code = (flags >> 5) & 0x07;
@@ -965,7 +1489,7 @@ fn decode_publish(buf: &mut &[u8]) -> Result<Message> {
     let has_id = (flags & 0x08) != 0;
     let phase_code = flags & 0x07;
 
-    let address = decode_string(buf)?;
+    let address = decode_address(buf)?;
 
     // Value indicator
     let value_indicator = buf.get_u8();
@@ -1037,6 +1561,12 @@ fn decode_hello(buf: &mut &[u8]) -> Result<Message> {
     if feature_flags & 0x04 != 0 {
         features.push("federation".to_string());
     }
+    if feature_flags & 0x02 != 0 {
+        features.push("alias".to_string());
+    }
+    if feature_flags & 0x01 != 0 {
+        features.push("correlation".to_string());
+    }
 
     let name = decode_string(buf)?;
     let token_str = decode_string(buf)?;
@@ -1046,12 +1576,19 @@ fn decode_hello(buf: &mut &[u8]) -> Result<Message> {
         Some(token_str)
     };
 
+    let session_resume = if buf.has_remaining() {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
+
     Ok(Message::Hello(HelloMessage {
         version,
         name,
         features,
         capabilities: None,
         token,
+        session_resume,
     }))
 }
 
@@ -1075,6 +1612,15 @@ fn decode_welcome(buf: &mut &[u8]) -> Result<Message> {
     if feature_flags & 0x08 != 0 {
         features.push("timeline".to_string());
     }
+    if feature_flags & 0x04 != 0 {
+        features.push(E2E_FEATURE.to_string());
+    }
+    if feature_flags & 0x02 != 0 {
+        features.push("alias".to_string());
+    }
+    if feature_flags & 0x01 != 0 {
+        features.push("correlation".to_string());
+    }
 
     let time = buf.get_u64();
     let session = decode_string(buf)?;
@@ -1087,88 +1633,136 @@ fn decode_welcome(buf: &mut &[u8]) -> Result<Message> {
         Some(token_str)
     };
 
-    Ok(Message::Welcome(WelcomeMessage {
-        version,
+    let challenge = if buf.has_remaining() {
+        let challenge = decode_string(buf)?;
+        (!challenge.is_empty()).then_some(challenge)
+    } else {
+        None
+    };
+    let resume_token = if buf.has_remaining() {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
+    let resumed = buf.has_remaining() && buf.get_u8() != 0;
+
+    Ok(Message::Welcome(WelcomeMessage {
+        version,
         session,
         name,
         features,
         time,
         token,
+        challenge,
+        resume_token,
+        resumed,
     }))
 }
 
+fn decode_proof(buf: &mut &[u8]) -> Result<Message> {
+    if buf.remaining() < 2 {
+        return Err(Error::BufferTooSmall {
+            needed: 2,
+            have: buf.remaining(),
+        });
+    }
+    let len = buf.get_u16() as usize;
+    if buf.remaining() < len {
+        return Err(Error::BufferTooSmall {
+            needed: len,
+            have: buf.remaining(),
+        });
+    }
+    let signature = buf[..len].to_vec();
+    buf.advance(len);
+    Ok(Message::Proof(ProofMessage { signature }))
+}
+
 fn decode_announce(buf: &mut &[u8]) -> Result<Message> {
     let namespace = decode_string(buf)?;
     let count = buf.get_u16() as usize;
 
     let mut signals = Vec::with_capacity(count);
     for _ in 0..count {
-        let address = decode_string(buf)?;
-        let sig_code = buf.get_u8();
-        let opt_flags = buf.get_u8();
+        signals.push(decode_signal_definition(buf)?);
+    }
 
-        let datatype = if opt_flags & 0x01 != 0 {
+    Ok(Message::Announce(AnnounceMessage {
+        namespace,
+        signals,
+        meta: None,
+    }))
+}
+
+fn decode_signal_definition(buf: &mut &[u8]) -> Result<SignalDefinition> {
+    let address = decode_string(buf)?;
+    let sig_code = buf.get_u8();
+    let opt_flags = buf.get_u8();
+
+    let datatype = if opt_flags & 0x01 != 0 {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
+    let access = if opt_flags & 0x02 != 0 {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
+
+    let meta = if opt_flags & 0x04 != 0 {
+        let meta_flags = buf.get_u8();
+
+        let unit = if meta_flags & 0x01 != 0 {
             Some(decode_string(buf)?)
         } else {
             None
         };
-        let access = if opt_flags & 0x02 != 0 {
+        let range = if meta_flags & 0x02 != 0 {
+            let min = buf.get_f64();
+            let max = buf.get_f64();
+            Some((min, max))
+        } else {
+            None
+        };
+        let default = if meta_flags & 0x04 != 0 {
+            let vtype = buf.get_u8();
+            Some(decode_value_data(buf, vtype)?)
+        } else {
+            None
+        };
+        let description = if meta_flags & 0x08 != 0 {
             Some(decode_string(buf)?)
         } else {
             None
         };
-
-        let meta = if opt_flags & 0x04 != 0 {
-            let meta_flags = buf.get_u8();
-
-            let unit = if meta_flags & 0x01 != 0 {
-                Some(decode_string(buf)?)
-            } else {
-                None
-            };
-            let range = if meta_flags & 0x02 != 0 {
-                let min = buf.get_f64();
-                let max = buf.get_f64();
-                Some((min, max))
-            } else {
-                None
-            };
-            let default = if meta_flags & 0x04 != 0 {
-                let vtype = buf.get_u8();
-                Some(decode_value_data(buf, vtype)?)
-            } else {
-                None
-            };
-            let description = if meta_flags & 0x08 != 0 {
-                Some(decode_string(buf)?)
-            } else {
-                None
-            };
-
-            Some(SignalMeta {
-                unit,
-                range,
-                default,
-                description,
-            })
+        let enum_values = if meta_flags & 0x10 != 0 {
+            match decode_value_data(buf, val::ARRAY)? {
+                Value::Array(values) => Some(values),
+                _ => None,
+            }
         } else {
             None
         };
 
-        signals.push(SignalDefinition {
-            address,
-            signal_type: signal_type_from_code(sig_code),
-            datatype,
-            access,
-            meta,
-        });
-    }
+        Some(SignalMeta {
+            unit,
+            range,
+            default,
+            description,
+            enum_values,
+        })
+    } else {
+        None
+    };
 
-    Ok(Message::Announce(AnnounceMessage {
-        namespace,
-        signals,
-        meta: None,
-    }))
+    Ok(SignalDefinition {
+        address,
+        signal_type: signal_type_from_code(sig_code),
+        datatype,
+        access,
+        meta,
+    })
 }
 
 fn decode_subscribe(buf: &mut &[u8]) -> Result<Message> {
@@ -1197,8 +1791,23 @@ fn decode_subscribe(buf: &mut &[u8]) -> Result<Message> {
         }
     }
 
+    let mut options = decode_subscribe_options(buf)?;
+    if let Some(transform) = decode_transform(buf)? {
+        options.get_or_insert_with(Default::default).transform = Some(transform);
+    }
+
+    Ok(Message::Subscribe(SubscribeMessage {
+        id,
+        pattern,
+        types,
+        options,
+    }))
+}
+
+/// Subscription options as written by [`encode_subscribe_options`]
+fn decode_subscribe_options(buf: &mut &[u8]) -> Result<Option<SubscribeOptions>> {
     let opt_flags = buf.get_u8();
-    let options = if opt_flags != 0 {
+    Ok(if opt_flags != 0 {
         let max_rate = if opt_flags & 0x01 != 0 {
             Some(buf.get_u32())
         } else {
@@ -1219,22 +1828,102 @@ fn decode_subscribe(buf: &mut &[u8]) -> Result<Message> {
         } else {
             None
         };
+        let tick_ms = if opt_flags & 0x10 != 0 {
+            Some(buf.get_u32())
+        } else {
+            None
+        };
+        let convert_to = if opt_flags & 0x20 != 0 {
+            Some(decode_string(buf)?)
+        } else {
+            None
+        };
+        let stream = if opt_flags & 0x40 != 0 {
+            match buf.get_u8() {
+                0 => Some(StreamPolicy::LatestOnly),
+                1 => Some(StreamPolicy::Ring(buf.get_u32())),
+                2 => Some(StreamPolicy::Reliable),
+                other => {
+                    return Err(Error::DecodeError(format!(
+                        "unknown stream policy {}",
+                        other
+                    )))
+                }
+            }
+        } else {
+            None
+        };
+        let branch = if opt_flags & 0x80 != 0 {
+            Some(decode_string(buf)?)
+        } else {
+            None
+        };
 
         Some(SubscribeOptions {
             max_rate,
             epsilon,
             history,
             window,
+            tick_ms,
+            convert_to,
+            stream,
+            branch,
+            transform: None,
+        })
+    } else {
+        None
+    })
+}
+
+/// Value transform as written by [`encode_transform`], if the frame has one
+fn decode_transform(buf: &mut &[u8]) -> Result<Option<ValueTransform>> {
+    if !buf.has_remaining() {
+        return Ok(None);
+    }
+    let flags = buf.get_u8();
+    // Bytes per step: map has four f64s, clamp two
+    let needed: usize = [(0x01, 32), (0x02, 8), (0x04, 8), (0x08, 16)]
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, bytes)| bytes)
+        .sum();
+    if buf.remaining() < needed {
+        return Err(Error::BufferTooSmall {
+            needed,
+            have: buf.remaining(),
+        });
+    }
+
+    let map = if flags & 0x01 != 0 {
+        Some(RangeMap {
+            from: (buf.get_f64(), buf.get_f64()),
+            to: (buf.get_f64(), buf.get_f64()),
         })
     } else {
         None
     };
+    let scale = if flags & 0x02 != 0 {
+        Some(buf.get_f64())
+    } else {
+        None
+    };
+    let offset = if flags & 0x04 != 0 {
+        Some(buf.get_f64())
+    } else {
+        None
+    };
+    let clamp = if flags & 0x08 != 0 {
+        Some((buf.get_f64(), buf.get_f64()))
+    } else {
+        None
+    };
 
-    Ok(Message::Subscribe(SubscribeMessage {
-        id,
-        pattern,
-        types,
-        options,
+    Ok(Some(ValueTransform {
+        map,
+        scale,
+        offset,
+        clamp,
+        round: flags & 0x10 != 0,
     }))
 }
 
@@ -1243,9 +1932,71 @@ fn decode_unsubscribe(buf: &mut &[u8]) -> Result<Message> {
     Ok(Message::Unsubscribe(UnsubscribeMessage { id }))
 }
 
+fn decode_subscribe_ack(buf: &mut &[u8]) -> Result<Message> {
+    if buf.remaining() < 9 {
+        return Err(Error::BufferTooSmall {
+            needed: 9,
+            have: buf.remaining(),
+        });
+    }
+    let id = buf.get_u32();
+    let matched = buf.get_u32();
+    let mut options = decode_subscribe_options(buf)?.unwrap_or_default();
+    let correlation_id = if buf.has_remaining() && buf.get_u8() & 0x01 != 0 {
+        Some(buf.get_u32())
+    } else {
+        None
+    };
+    options.transform = decode_transform(buf)?;
+    Ok(Message::SubscribeAck(SubscribeAckMessage {
+        id,
+        matched,
+        options,
+        correlation_id,
+    }))
+}
+
+fn decode_unsubscribe_ack(buf: &mut &[u8]) -> Result<Message> {
+    if buf.remaining() < 5 {
+        return Err(Error::BufferTooSmall {
+            needed: 5,
+            have: buf.remaining(),
+        });
+    }
+    let id = buf.get_u32();
+    let flags = buf.get_u8();
+    let correlation_id = if flags & 0x02 != 0 {
+        Some(buf.get_u32())
+    } else {
+        None
+    };
+    Ok(Message::UnsubscribeAck(UnsubscribeAckMessage {
+        id,
+        removed: flags & 0x01 != 0,
+        correlation_id,
+    }))
+}
+
+fn decode_alias(buf: &mut &[u8]) -> Result<Message> {
+    if buf.remaining() < 2 {
+        return Err(Error::BufferTooSmall {
+            needed: 2,
+            have: buf.remaining(),
+        });
+    }
+    let alias = buf.get_u16();
+    let address = decode_string(buf)?;
+    Ok(Message::Alias(AliasMessage { alias, address }))
+}
+
 fn decode_get(buf: &mut &[u8]) -> Result<Message> {
     let address = decode_string(buf)?;
-    Ok(Message::Get(GetMessage { address }))
+    let depth = if buf.remaining() >= 2 && buf.get_u8() & 0x01 != 0 {
+        Some(buf.get_u8())
+    } else {
+        None
+    };
+    Ok(Message::Get(GetMessage { address, depth }))
 }
 
 fn decode_snapshot(buf: &mut &[u8]) -> Result<Message> {
@@ -1279,7 +2030,23 @@ fn decode_snapshot(buf: &mut &[u8]) -> Result<Message> {
         });
     }
 
-    Ok(Message::Snapshot(SnapshotMessage { params }))
+    let (correlation_id, more) = if buf.remaining() >= 5 {
+        let flags = buf.get_u8();
+        let corr = buf.get_u32();
+        if flags & 0x01 != 0 {
+            (Some(corr), flags & 0x02 != 0)
+        } else {
+            (None, false)
+        }
+    } else {
+        (None, false)
+    };
+
+    Ok(Message::Snapshot(SnapshotMessage {
+        params,
+        correlation_id,
+        more,
+    }))
 }
 
 fn decode_replay(buf: &mut &[u8]) -> Result<Message> {
@@ -1287,12 +2054,19 @@ fn decode_replay(buf: &mut &[u8]) -> Result<Message> {
     let has_from = (flags & 0x80) != 0;
     let has_to = (flags & 0x40) != 0;
     let has_limit = (flags & 0x20) != 0;
+    let timed = (flags & 0x10) != 0;
+    let has_cursor = (flags & 0x08) != 0;
 
     let pattern = decode_string(buf)?;
 
     let from = if has_from { Some(buf.get_u64()) } else { None };
     let to = if has_to { Some(buf.get_u64()) } else { None };
     let limit = if has_limit { Some(buf.get_u32()) } else { None };
+    let cursor = if has_cursor {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
 
     let type_mask = buf.get_u8();
     let mut types = Vec::new();
@@ -1320,6 +2094,8 @@ fn decode_replay(buf: &mut &[u8]) -> Result<Message> {
         to,
         limit,
         types,
+        timed,
+        cursor,
     }))
 }
 
@@ -1330,6 +2106,10 @@ fn decode_federation_sync(buf: &mut &[u8]) -> Result<Message> {
         0x02 => FederationOp::RequestSync,
         0x03 => FederationOp::RevisionVector,
         0x04 => FederationOp::SyncComplete,
+        0x05 => FederationOp::RequestJournalSync,
+        0x06 => FederationOp::JournalCheckpoint,
+        0x07 => FederationOp::RequestRegistrySync,
+        0x08 => FederationOp::RegistryEvent,
         _ => return Err(Error::DecodeError("unknown federation op".into())),
     };
 
@@ -1349,7 +2129,7 @@ fn decode_federation_sync(buf: &mut &[u8]) -> Result<Message> {
         revisions.insert(key, val);
     }
 
-    // Flags: [has_since:1][has_origin:1][rsv:6]
+    // Flags: [has_since:1][has_origin:1][has_payload:1][rsv:5]
     let flags = buf.get_u8();
     let since_revision = if flags & 0x80 != 0 {
         Some(buf.get_u64())
@@ -1361,6 +2141,26 @@ fn decode_federation_sync(buf: &mut &[u8]) -> Result<Message> {
     } else {
         None
     };
+    let payload = if flags & 0x20 != 0 {
+        if buf.remaining() < 4 {
+            return Err(Error::BufferTooSmall {
+                needed: 4,
+                have: buf.remaining(),
+            });
+        }
+        let len = buf.get_u32() as usize;
+        if buf.remaining() < len {
+            return Err(Error::BufferTooSmall {
+                needed: len,
+                have: buf.remaining(),
+            });
+        }
+        let payload = buf[..len].to_vec();
+        buf.advance(len);
+        Some(payload)
+    } else {
+        None
+    };
 
     Ok(Message::FederationSync(FederationSyncMessage {
         op,
@@ -1368,6 +2168,7 @@ fn decode_federation_sync(buf: &mut &[u8]) -> Result<Message> {
         revisions,
         since_revision,
         origin,
+        payload,
     }))
 }
 
@@ -1437,6 +2238,35 @@ fn decode_ack(buf: &mut &[u8]) -> Result<Message> {
     } else {
         None
     };
+    let mut results = Vec::new();
+    if flags & 0x20 != 0 {
+        if buf.remaining() < 2 {
+            return Err(Error::BufferTooSmall {
+                needed: 2,
+                have: buf.remaining(),
+            });
+        }
+        let count = buf.get_u16();
+        for _ in 0..count {
+            let address = decode_string(buf)?;
+            let has_revision = buf.first().is_some_and(|flag| *flag != 0);
+            let needed = if has_revision { 9 } else { 1 };
+            if buf.remaining() < needed {
+                return Err(Error::BufferTooSmall {
+                    needed,
+                    have: buf.remaining(),
+                });
+            }
+            buf.advance(1);
+            let revision = has_revision.then(|| buf.get_u64());
+            results.push(BundleResult { address, revision });
+        }
+    }
+    let cursor = if flags & 0x40 != 0 {
+        Some(decode_string(buf)?)
+    } else {
+        None
+    };
 
     Ok(Message::Ack(AckMessage {
         address,
@@ -1444,6 +2274,8 @@ fn decode_ack(buf: &mut &[u8]) -> Result<Message> {
         locked,
         holder,
         correlation_id,
+        results,
+        cursor,
     }))
 }
 
@@ -1473,39 +2305,75 @@ fn decode_error(buf: &mut &[u8]) -> Result<Message> {
 
 fn decode_query(buf: &mut &[u8]) -> Result<Message> {
     let pattern = decode_string(buf)?;
-    Ok(Message::Query(QueryMessage { pattern }))
+    let mut query = QueryMessage {
+        pattern,
+        ..Default::default()
+    };
+    if !buf.has_remaining() {
+        return Ok(Message::Query(query));
+    }
+
+    let flags = buf.get_u8();
+    if flags & 0x01 != 0 {
+        if buf.remaining() < 4 {
+            return Err(Error::BufferTooSmall {
+                needed: 4,
+                have: buf.remaining(),
+            });
+        }
+        query.limit = Some(buf.get_u32());
+    }
+    if flags & 0x02 != 0 {
+        query.cursor = Some(decode_string(buf)?);
+    }
+    if flags & 0x04 != 0 {
+        if !buf.has_remaining() {
+            return Err(Error::BufferTooSmall { needed: 1, have: 0 });
+        }
+        let mask = buf.get_u8();
+        query.fields = Some(
+            [
+                SignalField::Datatype,
+                SignalField::Access,
+                SignalField::Meta,
+            ]
+            .into_iter()
+            .filter(|field| mask & signal_field_bit(*field) != 0)
+            .collect(),
+        );
+    }
+    Ok(Message::Query(query))
 }
 
 fn decode_result(buf: &mut &[u8]) -> Result<Message> {
     let count = buf.get_u16() as usize;
     let mut signals = Vec::with_capacity(count);
-
     for _ in 0..count {
-        let address = decode_string(buf)?;
-        let sig_code = buf.get_u8();
-        let opt_flags = buf.get_u8();
-
-        let datatype = if opt_flags & 0x01 != 0 {
-            Some(decode_string(buf)?)
-        } else {
-            None
-        };
-        let access = if opt_flags & 0x02 != 0 {
-            Some(decode_string(buf)?)
-        } else {
-            None
-        };
+        signals.push(decode_signal_definition(buf)?);
+    }
 
-        signals.push(SignalDefinition {
-            address,
-            signal_type: signal_type_from_code(sig_code),
-            datatype,
-            access,
-            meta: None,
-        });
+    let mut result = ResultMessage {
+        signals,
+        ..Default::default()
+    };
+    if !buf.has_remaining() {
+        return Ok(Message::Result(result));
     }
 
-    Ok(Message::Result(ResultMessage { signals }))
+    let flags = buf.get_u8();
+    if flags & 0x01 != 0 {
+        result.next_cursor = Some(decode_string(buf)?);
+    }
+    if flags & 0x02 != 0 {
+        if buf.remaining() < 4 {
+            return Err(Error::BufferTooSmall {
+                needed: 4,
+                have: buf.remaining(),
+            });
+        }
+        result.total = Some(buf.get_u32());
+    }
+    Ok(Message::Result(result))
 }
 
 // ============================================================================
@@ -1532,6 +2400,15 @@ fn decode_string(buf: &mut &[u8]) -> Result<String> {
     String::from_utf8(bytes.to_vec()).map_err(|e| Error::DecodeError(e.to_string()))
 }
 
+#[inline(always)]
+fn decode_address(buf: &mut &[u8]) -> Result<String> {
+    if buf.remaining() >= 2 && buf[0] & 0x80 != 0 {
+        let alias = buf.get_u16() & !ALIAS_REF;
+        return Ok(crate::alias::alias_address(alias));
+    }
+    decode_string(buf)
+}
+
 #[inline]
 fn decode_value_data(buf: &mut &[u8], vtype: u8) -> Result<Value> {
     match vtype {
@@ -1663,6 +2540,7 @@ mod tests {
             features: vec!["param".to_string(), "event".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
 
         let encoded = encode(&msg).unwrap();
@@ -1691,6 +2569,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = encode(&msg).unwrap();
@@ -1717,6 +2597,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         // Binary encoding
@@ -1758,6 +2640,8 @@ mod tests {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }),
                 Message::Set(SetMessage {
                     address: "/light/2".to_string(),
@@ -1766,6 +2650,8 @@ mod tests {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }),
             ],
         });
@@ -1782,6 +2668,100 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_many_roundtrip() {
+        let cases = vec![
+            // A DMX universe packs one byte per channel
+            SetManyMessage::run("/dmx/1", 1, (0..512).map(|i| Value::Int(i % 256)).collect()),
+            SetManyMessage::run("/strip", 0, vec![Value::Int(-300), Value::Int(70000)]),
+            SetManyMessage::run("/strip", 0, vec![Value::Float(0.5), Value::Float(0.1)]),
+            SetManyMessage::indexed(
+                "/lights",
+                vec![(3, Value::Bool(true)), (40, Value::Bool(false))],
+            ),
+            SetManyMessage::indexed(
+                "/mixed",
+                vec![(1, Value::Int(1)), (2, Value::String("two".into()))],
+            ),
+            SetManyMessage::run("/empty", 0, Vec::new()),
+        ];
+        for msg in cases {
+            let encoded = encode(&Message::SetMany(msg.clone())).unwrap();
+            let (decoded, _) = decode(&encoded).unwrap();
+            match decoded {
+                Message::SetMany(decoded) => assert_eq!(decoded, msg),
+                other => panic!("Expected SetMany message, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_many_size() {
+        let levels: Vec<Value> = (0..512).map(|i| Value::Int(i % 256)).collect();
+        let many =
+            encode_message(&Message::SetMany(SetManyMessage::run("/dmx/1", 1, levels))).unwrap();
+        // type, flags, base, count, start, then one byte per channel
+        assert_eq!(many.len(), 1 + 1 + 2 + 6 + 2 + 4 + 512);
+
+        let truncated = &many[..many.len() - 1];
+        assert!(decode_message(truncated).is_err());
+    }
+
+    #[test]
+    fn test_set_many_entries() {
+        let run =
+            SetManyMessage::from_entries("/dmx/1/", vec![(4, Value::Int(1)), (5, Value::Int(2))]);
+        assert!(matches!(run.values, SetManyValues::Run { start: 4, .. }));
+        let sets = run.to_sets();
+        assert_eq!(sets[0].address, "/dmx/1/4");
+        assert_eq!(sets[1].address, "/dmx/1/5");
+
+        let sparse =
+            SetManyMessage::from_entries("/dmx/1", vec![(4, Value::Int(1)), (9, Value::Int(2))]);
+        assert!(matches!(sparse.values, SetManyValues::Indexed(_)));
+        assert!(!SetManyMessage::run("/a", u32::MAX, vec![Value::Null; 2]).in_range());
+    }
+
+    #[test]
+    fn test_peek_address() {
+        let set = encode_message(&Message::Set(SetMessage {
+            address: "/mixer/1/level".to_string(),
+            value: Value::Float(0.5),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap();
+        assert_eq!(
+            peek_address(&set),
+            Some(("/mixer/1/level".to_string(), SignalType::Param))
+        );
+
+        let publish = encode_message(&Message::Publish(PublishMessage {
+            address: "/sensor/x".to_string(),
+            signal: Some(SignalType::Stream),
+            value: Some(Value::Float(1.0)),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        }))
+        .unwrap();
+        assert_eq!(
+            peek_address(&publish),
+            Some(("/sensor/x".to_string(), SignalType::Stream))
+        );
+
+        assert_eq!(peek_address(&encode_message(&Message::Ping).unwrap()), None);
+        assert_eq!(peek_address(&set[..4]), None);
+    }
+
     #[test]
     fn test_value_types() {
         let values = vec![
@@ -1801,6 +2781,8 @@ mod tests {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
 
             let encoded = encode(&msg).unwrap();
@@ -1825,6 +2807,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         };
 
         // Encode as v2 (MessagePack with named keys)
@@ -1852,6 +2836,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: Some(Ttl::Never),
+            trace: false,
+            branch: None,
         });
 
         let encoded = encode(&msg).unwrap();
@@ -1874,6 +2860,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: Some(Ttl::Absolute(1800)),
+            trace: false,
+            branch: None,
         });
 
         let encoded = encode(&msg).unwrap();
@@ -1896,6 +2884,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: Some(Ttl::Sliding(35)),
+            trace: false,
+            branch: None,
         });
 
         let encoded = encode(&msg).unwrap();
@@ -1911,9 +2901,8 @@ mod tests {
 
     #[test]
     fn test_set_ttl_absolute_zero_is_never() {
-        // Raw binary 0x80000000 should NOT be produced by encode for Absolute(0).
-        // If someone sends raw 0x80000000, it decodes as Absolute(0).
-        // But Ttl::Never must encode as raw 0 and decode back as Never.
+        // Raw binary 0x80000000 is reserved for Ttl::Session.
+        // Ttl::Never must encode as raw 0 and decode back as Never.
         let never = Message::Set(SetMessage {
             address: "/t".to_string(),
             value: Value::Null,
@@ -1921,6 +2910,8 @@ mod tests {
             lock: false,
             unlock: false,
             ttl: Some(Ttl::Never),
+            trace: false,
+            branch: None,
         });
 
         let encoded = encode(&never).unwrap();
@@ -1935,6 +2926,167 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_ttl_session_roundtrip() {
+        let msg = Message::Set(SetMessage {
+            address: "/tmp/cursor".to_string(),
+            value: Value::Float(0.5),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: Some(Ttl::Session),
+            trace: false,
+            branch: None,
+        });
+        let (decoded, _) = decode(&encode(&msg).unwrap()).unwrap();
+        match decoded {
+            Message::Set(set) => assert_eq!(set.ttl, Some(Ttl::Session)),
+            _ => panic!("Expected Set message"),
+        }
+
+        // Absolute(0) must not be mistaken for Session on the wire
+        let msg = Message::Set(SetMessage {
+            address: "/t".to_string(),
+            value: Value::Null,
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: Some(Ttl::Absolute(0)),
+            trace: false,
+            branch: None,
+        });
+        let (decoded, _) = decode(&encode(&msg).unwrap()).unwrap();
+        match decoded {
+            Message::Set(set) => assert_eq!(set.ttl, Some(Ttl::Absolute(1))),
+            _ => panic!("Expected Set message"),
+        }
+    }
+
+    #[test]
+    fn test_replay_timed_roundtrip() {
+        let replay = ReplayMessage {
+            pattern: "/input/touch/**".to_string(),
+            from: Some(1_000),
+            to: None,
+            limit: Some(500),
+            types: vec![SignalType::Gesture],
+            timed: true,
+            cursor: None,
+        };
+        match decode(&encode(&Message::Replay(replay)).unwrap())
+            .unwrap()
+            .0
+        {
+            Message::Replay(decoded) => {
+                assert!(decoded.timed);
+                assert_eq!(decoded.from, Some(1_000));
+                assert_eq!(decoded.limit, Some(500));
+                assert_eq!(decoded.types, vec![SignalType::Gesture]);
+            }
+            _ => panic!("Expected Replay message"),
+        }
+    }
+
+    #[test]
+    fn test_replay_cursor_roundtrip() {
+        let replay = ReplayMessage {
+            pattern: "/sensors/**".to_string(),
+            from: None,
+            to: Some(9_000),
+            limit: None,
+            types: vec![],
+            timed: false,
+            cursor: Some("1700000000000000.3".to_string()),
+        };
+        match decode(&encode(&Message::Replay(replay)).unwrap())
+            .unwrap()
+            .0
+        {
+            Message::Replay(decoded) => {
+                assert_eq!(decoded.cursor.as_deref(), Some("1700000000000000.3"));
+                assert_eq!(decoded.to, Some(9_000));
+                assert!(decoded.types.is_empty());
+            }
+            _ => panic!("Expected Replay message"),
+        }
+
+        let ack = Message::Ack(AckMessage {
+            address: Some("/sensors/**".to_string()),
+            revision: None,
+            locked: None,
+            holder: None,
+            correlation_id: Some(2),
+            results: Vec::new(),
+            cursor: Some("1700000000000999.1".to_string()),
+        });
+        match decode(&encode(&ack).unwrap()).unwrap().0 {
+            Message::Ack(a) => {
+                assert_eq!(a.cursor.as_deref(), Some("1700000000000999.1"));
+                assert_eq!(a.correlation_id, Some(2));
+            }
+            _ => panic!("Expected Ack message"),
+        }
+    }
+
+    #[test]
+    fn test_set_trace_roundtrip() {
+        let set = SetMessage {
+            address: "/lights/1/level".to_string(),
+            value: Value::Float(0.8),
+            revision: Some(4),
+            lock: false,
+            unlock: false,
+            ttl: Some(Ttl::Sliding(60)),
+            trace: true,
+            branch: None,
+        };
+        let traced = encode(&Message::Set(set.clone())).unwrap();
+        match decode(&traced).unwrap().0 {
+            Message::Set(decoded) => {
+                assert!(decoded.trace);
+                assert_eq!(decoded.ttl, Some(Ttl::Sliding(60)));
+            }
+            _ => panic!("Expected Set message"),
+        }
+
+        // Untraced SETs carry no options byte
+        let plain = encode(&Message::Set(SetMessage {
+            trace: false,
+            ..set
+        }))
+        .unwrap();
+        assert_eq!(plain.len() + 1, traced.len());
+        match decode(&plain).unwrap().0 {
+            Message::Set(decoded) => assert!(!decoded.trace),
+            _ => panic!("Expected Set message"),
+        }
+    }
+
+    #[test]
+    fn test_federation_payload_roundtrip() {
+        let event = Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::RegistryEvent,
+            patterns: vec![],
+            revisions: HashMap::new(),
+            since_revision: None,
+            origin: Some("site-a".to_string()),
+            payload: Some(vec![0x92, 0x01, 0x02]),
+        });
+        let encoded = encode(&event).unwrap();
+        match decode(&encoded).unwrap().0 {
+            Message::FederationSync(decoded) => {
+                assert_eq!(decoded.op, FederationOp::RegistryEvent);
+                assert_eq!(decoded.origin.as_deref(), Some("site-a"));
+                assert_eq!(decoded.payload, Some(vec![0x92, 0x01, 0x02]));
+            }
+            _ => panic!("Expected FederationSync message"),
+        }
+
+        // A payload longer than the frame is refused
+        let truncated = &encoded[..encoded.len() - 1];
+        assert!(decode(truncated).is_err());
+    }
+
     #[test]
     fn test_ping_pong() {
         let ping = encode(&Message::Ping).unwrap();
@@ -1985,6 +3137,9 @@ mod tests {
                 epsilon: Some(0.01),
                 history: None,
                 window: None,
+                tick_ms: Some(16),
+                convert_to: Some("°F".to_string()),
+                stream: None,
             }),
         });
 
@@ -1998,8 +3153,452 @@ mod tests {
                 assert!(sub.types.contains(&SignalType::Param));
                 assert!(sub.types.contains(&SignalType::Stream));
                 assert_eq!(sub.options.as_ref().unwrap().max_rate, Some(60));
+                assert_eq!(sub.options.as_ref().unwrap().tick_ms, Some(16));
+                assert_eq!(
+                    sub.options.as_ref().unwrap().convert_to.as_deref(),
+                    Some("°F")
+                );
+            }
+            _ => panic!("Expected Subscribe message"),
+        }
+    }
+
+    #[test]
+    fn test_branch_roundtrip() {
+        let set = Message::Set(SetMessage {
+            address: "/lights/1/level".to_string(),
+            value: Value::Float(0.5),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: true,
+            branch: Some("next-show".to_string()),
+        });
+        match decode(&encode(&set).unwrap()).unwrap().0 {
+            Message::Set(decoded) => {
+                assert!(decoded.trace);
+                assert_eq!(decoded.branch.as_deref(), Some("next-show"));
+            }
+            _ => panic!("Expected Set message"),
+        }
+
+        let sub = Message::Subscribe(SubscribeMessage {
+            id: 2,
+            pattern: "/lights/**".to_string(),
+            types: vec![],
+            options: Some(SubscribeOptions {
+                stream: Some(StreamPolicy::Reliable),
+                branch: Some("next-show".to_string()),
+                ..Default::default()
+            }),
+        });
+        match decode(&encode(&sub).unwrap()).unwrap().0 {
+            Message::Subscribe(decoded) => {
+                let options = decoded.options.unwrap();
+                assert_eq!(options.stream, Some(StreamPolicy::Reliable));
+                assert_eq!(options.branch.as_deref(), Some("next-show"));
             }
             _ => panic!("Expected Subscribe message"),
         }
     }
+
+    #[test]
+    fn test_subscribe_stream_policy_roundtrip() {
+        for policy in [
+            StreamPolicy::LatestOnly,
+            StreamPolicy::Ring(32),
+            StreamPolicy::Reliable,
+        ] {
+            let msg = Message::Subscribe(SubscribeMessage {
+                id: 1,
+                pattern: "/mocap/**".to_string(),
+                types: vec![SignalType::Stream],
+                options: Some(SubscribeOptions {
+                    tick_ms: Some(16),
+                    stream: Some(policy),
+                    ..Default::default()
+                }),
+            });
+            match decode(&encode(&msg).unwrap()).unwrap().0 {
+                Message::Subscribe(sub) => {
+                    let options = sub.options.unwrap();
+                    assert_eq!(options.stream, Some(policy));
+                    assert_eq!(options.tick_ms, Some(16));
+                }
+                _ => panic!("Expected Subscribe message"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_subscribe_transform_roundtrip() {
+        let transform = ValueTransform {
+            map: Some(RangeMap {
+                from: (0.0, 1.0),
+                to: (0.0, 255.0),
+            }),
+            offset: Some(-1.5),
+            clamp: Some((0.0, 255.0)),
+            round: true,
+            ..Default::default()
+        };
+        let msg = Message::Subscribe(SubscribeMessage {
+            id: 3,
+            pattern: "/lights/**".to_string(),
+            types: vec![],
+            options: Some(SubscribeOptions {
+                transform: Some(transform.clone()),
+                ..Default::default()
+            }),
+        });
+        match decode(&encode(&msg).unwrap()).unwrap().0 {
+            Message::Subscribe(sub) => {
+                assert_eq!(sub.options.unwrap().transform, Some(transform.clone()));
+            }
+            _ => panic!("Expected Subscribe message"),
+        }
+
+        let ack = Message::SubscribeAck(SubscribeAckMessage {
+            id: 3,
+            matched: 0,
+            options: SubscribeOptions {
+                max_rate: Some(30),
+                transform: Some(transform.clone()),
+                ..Default::default()
+            },
+            correlation_id: Some(5),
+        });
+        let encoded = encode_message(&ack).unwrap();
+        match decode_message(&encoded).unwrap() {
+            Message::SubscribeAck(a) => {
+                assert_eq!(a.correlation_id, Some(5));
+                assert_eq!(a.options.max_rate, Some(30));
+                assert_eq!(a.options.transform, Some(transform));
+            }
+            _ => panic!("Expected SubscribeAck message"),
+        }
+        assert!(decode_message(&encoded[..encoded.len() - 8]).is_err());
+    }
+
+    #[test]
+    fn test_alias_roundtrip() {
+        let msg = Message::Alias(AliasMessage {
+            alias: 12,
+            address: "/controller/gesture/finger/1/position".to_string(),
+        });
+        let encoded = encode(&msg).unwrap();
+        match decode(&encoded).unwrap().0 {
+            Message::Alias(alias) => {
+                assert_eq!(alias.alias, 12);
+                assert_eq!(alias.address, "/controller/gesture/finger/1/position");
+            }
+            _ => panic!("Expected Alias message"),
+        }
+
+        let hello = Message::Hello(HelloMessage {
+            version: 1,
+            name: "Test Client".to_string(),
+            features: vec!["stream".to_string(), "alias".to_string()],
+            capabilities: None,
+            token: None,
+            session_resume: None,
+        });
+        match decode(&encode(&hello).unwrap()).unwrap().0 {
+            Message::Hello(hello) => assert!(hello.features.contains(&"alias".to_string())),
+            _ => panic!("Expected Hello message"),
+        }
+    }
+
+    #[test]
+    fn test_subscription_ack_roundtrip() {
+        let ack = Message::SubscribeAck(SubscribeAckMessage {
+            id: 4,
+            matched: 12,
+            options: SubscribeOptions {
+                tick_ms: Some(60_000),
+                convert_to: Some("F".to_string()),
+                ..Default::default()
+            },
+            correlation_id: Some(9),
+        });
+        let encoded = encode_message(&ack).unwrap();
+        assert_eq!(encoded[0], msg::SUBSCRIBE_ACK);
+        match decode_message(&encoded).unwrap() {
+            Message::SubscribeAck(a) => {
+                assert_eq!((a.id, a.matched, a.correlation_id), (4, 12, Some(9)));
+                assert_eq!(a.options.tick_ms, Some(60_000));
+                assert_eq!(a.options.convert_to.as_deref(), Some("F"));
+            }
+            _ => panic!("Expected SubscribeAck message"),
+        }
+        assert!(decode_message(&encoded[..5]).is_err());
+
+        let ack = Message::UnsubscribeAck(UnsubscribeAckMessage {
+            id: 4,
+            removed: true,
+            correlation_id: None,
+        });
+        match decode(&encode(&ack).unwrap()).unwrap().0 {
+            Message::UnsubscribeAck(a) => {
+                assert_eq!((a.id, a.removed, a.correlation_id), (4, true, None))
+            }
+            _ => panic!("Expected UnsubscribeAck message"),
+        }
+    }
+
+    #[test]
+    fn test_ack_bundle_results_roundtrip() {
+        let ack = Message::Ack(AckMessage {
+            address: None,
+            revision: Some(7),
+            locked: None,
+            holder: None,
+            correlation_id: Some(3),
+            results: vec![
+                BundleResult {
+                    address: "/a".to_string(),
+                    revision: Some(7),
+                },
+                BundleResult {
+                    address: "/event".to_string(),
+                    revision: None,
+                },
+            ],
+            cursor: None,
+        });
+        let encoded = encode_message(&ack).unwrap();
+        match decode_message(&encoded).unwrap() {
+            Message::Ack(a) => {
+                assert_eq!((a.revision, a.correlation_id), (Some(7), Some(3)));
+                assert_eq!(a.results.len(), 2);
+                assert_eq!(a.results[0].revision, Some(7));
+                assert_eq!(a.results[1].address, "/event");
+                assert_eq!(a.results[1].revision, None);
+            }
+            _ => panic!("Expected Ack message"),
+        }
+        assert!(decode_message(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_get_depth_and_snapshot_correlation_roundtrip() {
+        // A plain GET is encoded as before
+        let plain = Message::Get(GetMessage {
+            address: "/a".to_string(),
+            depth: None,
+        });
+        assert_eq!(encode_message(&plain).unwrap().len(), 1 + 2 + 2);
+
+        let get = Message::Get(GetMessage {
+            address: "/lights/**".to_string(),
+            depth: Some(2),
+        });
+        match decode_message(&encode_message(&get).unwrap()).unwrap() {
+            Message::Get(g) => assert_eq!((g.address.as_str(), g.depth), ("/lights/**", Some(2))),
+            _ => panic!("Expected Get message"),
+        }
+
+        let snapshot = Message::Snapshot(SnapshotMessage {
+            params: vec![],
+            correlation_id: Some(11),
+            more: true,
+        });
+        match decode_message(&encode_message(&snapshot).unwrap()).unwrap() {
+            Message::Snapshot(s) => {
+                assert!(s.params.is_empty());
+                assert_eq!((s.correlation_id, s.more), (Some(11), true));
+            }
+            _ => panic!("Expected Snapshot message"),
+        }
+    }
+
+    #[test]
+    fn test_query_paging_roundtrip() {
+        // A plain QUERY is encoded as before
+        let plain = Message::Query(QueryMessage {
+            pattern: "/a/**".to_string(),
+            ..Default::default()
+        });
+        let encoded = encode_message(&plain).unwrap();
+        assert_eq!(encoded.len(), 1 + 2 + 5);
+
+        let query = Message::Query(QueryMessage {
+            pattern: "/a/**".to_string(),
+            limit: Some(100),
+            cursor: Some("/a/m".to_string()),
+            fields: Some(vec![SignalField::Meta]),
+        });
+        match decode(&encode(&query).unwrap()).unwrap().0 {
+            Message::Query(q) => {
+                assert_eq!(q.limit, Some(100));
+                assert_eq!(q.cursor.as_deref(), Some("/a/m"));
+                assert_eq!(q.fields, Some(vec![SignalField::Meta]));
+                assert!(q.wants(SignalField::Meta) && !q.wants(SignalField::Access));
+            }
+            _ => panic!("Expected Query message"),
+        }
+
+        let result = Message::Result(ResultMessage {
+            signals: vec![SignalDefinition {
+                address: "/a/gain".to_string(),
+                signal_type: SignalType::Param,
+                datatype: None,
+                access: None,
+                meta: Some(SignalMeta {
+                    unit: Some("dB".to_string()),
+                    range: Some((-60.0, 6.0)),
+                    default: None,
+                    description: None,
+                    enum_values: Some(vec![Value::Float(-60.0), Value::Float(0.0)]),
+                }),
+            }],
+            next_cursor: Some("/a/gain".to_string()),
+            total: Some(250),
+        });
+        match decode(&encode(&result).unwrap()).unwrap().0 {
+            Message::Result(r) => {
+                let meta = r.signals[0].meta.as_ref().unwrap();
+                assert_eq!(meta.unit.as_deref(), Some("dB"));
+                assert_eq!(meta.range, Some((-60.0, 6.0)));
+                assert_eq!(
+                    meta.enum_values,
+                    Some(vec![Value::Float(-60.0), Value::Float(0.0)])
+                );
+                assert_eq!(r.next_cursor.as_deref(), Some("/a/gain"));
+                assert_eq!(r.total, Some(250));
+            }
+            _ => panic!("Expected Result message"),
+        }
+    }
+
+    #[test]
+    fn test_welcome_feature_flags() {
+        let features: Vec<String> = ["param", E2E_FEATURE, "alias", "correlation"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let welcome = Message::Welcome(WelcomeMessage {
+            version: 1,
+            session: "session-1".to_string(),
+            name: "Router".to_string(),
+            features: features.clone(),
+            time: 1,
+            token: None,
+            challenge: None,
+            resume_token: None,
+            resumed: false,
+        });
+        match decode(&encode(&welcome).unwrap()).unwrap().0 {
+            Message::Welcome(w) => assert_eq!(w.features, features),
+            _ => panic!("Expected Welcome message"),
+        }
+    }
+
+    #[test]
+    fn test_proof_roundtrip() {
+        let welcome = |challenge: Option<&str>| {
+            Message::Welcome(WelcomeMessage {
+                version: 1,
+                session: "session-1".to_string(),
+                name: "Router".to_string(),
+                features: vec![],
+                time: 1,
+                token: None,
+                challenge: challenge.map(str::to_string),
+                resume_token: None,
+                resumed: false,
+            })
+        };
+        for challenge in [None, Some("c0ffee")] {
+            match decode(&encode(&welcome(challenge)).unwrap()).unwrap().0 {
+                Message::Welcome(w) => assert_eq!(w.challenge.as_deref(), challenge),
+                _ => panic!("Expected Welcome message"),
+            }
+        }
+
+        let proof = Message::Proof(ProofMessage {
+            signature: vec![7; 64],
+        });
+        let encoded = encode_message(&proof).unwrap();
+        assert_eq!(encoded[0], msg::PROOF);
+        match decode_message(&encoded).unwrap() {
+            Message::Proof(p) => assert_eq!(p.signature, vec![7; 64]),
+            _ => panic!("Expected Proof message"),
+        }
+        assert!(decode_message(&encoded[..10]).is_err());
+    }
+
+    #[test]
+    fn test_durable_session_roundtrip() {
+        for resume in [None, Some(""), Some("abc123")] {
+            let hello = Message::Hello(HelloMessage {
+                version: 1,
+                name: "Client".to_string(),
+                features: vec![],
+                capabilities: None,
+                token: None,
+                session_resume: resume.map(str::to_string),
+            });
+            match decode(&encode(&hello).unwrap()).unwrap().0 {
+                Message::Hello(h) => assert_eq!(h.session_resume.as_deref(), resume),
+                _ => panic!("Expected Hello message"),
+            }
+        }
+
+        for challenge in [None, Some("c0ffee")] {
+            let welcome = Message::Welcome(WelcomeMessage {
+                version: 1,
+                session: "session-1".to_string(),
+                name: "Router".to_string(),
+                features: vec![],
+                time: 1,
+                token: None,
+                challenge: challenge.map(str::to_string),
+                resume_token: Some("abc123".to_string()),
+                resumed: true,
+            });
+            match decode(&encode(&welcome).unwrap()).unwrap().0 {
+                Message::Welcome(w) => {
+                    assert_eq!(w.challenge.as_deref(), challenge);
+                    assert_eq!(w.resume_token.as_deref(), Some("abc123"));
+                    assert!(w.resumed);
+                }
+                _ => panic!("Expected Welcome message"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_aliased_address_size() {
+        let publish = |address: &str| {
+            Message::Publish(PublishMessage {
+                address: address.to_string(),
+                signal: Some(SignalType::Stream),
+                value: Some(Value::Float(0.5)),
+                payload: None,
+                samples: None,
+                rate: None,
+                id: None,
+                phase: None,
+                timestamp: None,
+                timeline: None,
+            })
+        };
+        let full = encode_message(&publish("/controller/gesture/finger/1/position")).unwrap();
+        let aliased = encode_message(&publish("#300")).unwrap();
+        assert_eq!(full.len() - aliased.len(), 37);
+
+        match decode_message(&aliased).unwrap() {
+            Message::Publish(p) => {
+                assert_eq!(p.address, "#300");
+                assert_eq!(p.value, Some(Value::Float(0.5)));
+            }
+            _ => panic!("Expected Publish message"),
+        }
+
+        // Plain addresses must leave the high length bit clear
+        let long = format!("/{}", "a".repeat(0x8000));
+        assert!(encode_message(&publish(&long)).is_err());
+    }
 }
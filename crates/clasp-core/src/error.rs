@@ -1,5 +1,6 @@
 //! Error types for Clasp
 
+use std::fmt;
 use thiserror::Error;
 
 /// Result type alias for Clasp operations
@@ -82,13 +83,19 @@ impl From<rmp_serde::decode::Error> for Error {
 }
 
 /// Protocol error codes (for ERROR messages)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// [`ErrorMessage`](crate::ErrorMessage) carries the code as a `u16` so that
+/// codes added by newer routers still decode; use
+/// [`ErrorMessage::error_code`](crate::ErrorMessage::error_code) to match on
+/// the known ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u16)]
 pub enum ErrorCode {
     // 100-199: Protocol errors
     InvalidFrame = 100,
     InvalidMessage = 101,
     UnsupportedVersion = 102,
+    InvalidRequest = 103,
 
     // 200-299: Address errors
     InvalidAddress = 200,
@@ -99,16 +106,54 @@ pub enum ErrorCode {
     Unauthorized = 300,
     Forbidden = 301,
     TokenExpired = 302,
+    SessionHandedOff = 303,
+    SessionLimit = 304,
+    SessionKicked = 305,
+    QuotaExceeded = 306,
 
     // 400-499: State errors
     RevisionConflict = 400,
     LockHeld = 401,
     InvalidValue = 402,
+    WriteRejected = 403,
+    TargetNotFound = 404,
+    RateLimited = 429,
 
     // 500-599: Server errors
     InternalError = 500,
     ServiceUnavailable = 501,
     Timeout = 502,
+    Maintenance = 503,
+    RetryAfter = 504,
+}
+
+/// Range an error code belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// 100-199: malformed or invalid messages (a client bug)
+    Protocol,
+    /// 200-299: bad addresses or patterns
+    Address,
+    /// 300-399: authentication, scopes and session limits
+    Auth,
+    /// 400-499: writes refused because of state, locks or limits
+    State,
+    /// 500-599: server trouble; retry with backoff
+    Server,
+}
+
+impl ErrorCategory {
+    /// Category of any code in the defined ranges, known or not
+    pub fn of(code: u16) -> Option<Self> {
+        match code {
+            100..=199 => Some(ErrorCategory::Protocol),
+            200..=299 => Some(ErrorCategory::Address),
+            300..=399 => Some(ErrorCategory::Auth),
+            400..=499 => Some(ErrorCategory::State),
+            500..=599 => Some(ErrorCategory::Server),
+            _ => None,
+        }
+    }
 }
 
 impl ErrorCode {
@@ -117,19 +162,79 @@ impl ErrorCode {
             100 => Some(ErrorCode::InvalidFrame),
             101 => Some(ErrorCode::InvalidMessage),
             102 => Some(ErrorCode::UnsupportedVersion),
+            103 => Some(ErrorCode::InvalidRequest),
             200 => Some(ErrorCode::InvalidAddress),
             201 => Some(ErrorCode::AddressNotFound),
             202 => Some(ErrorCode::PatternError),
             300 => Some(ErrorCode::Unauthorized),
             301 => Some(ErrorCode::Forbidden),
             302 => Some(ErrorCode::TokenExpired),
+            303 => Some(ErrorCode::SessionHandedOff),
+            304 => Some(ErrorCode::SessionLimit),
+            305 => Some(ErrorCode::SessionKicked),
+            306 => Some(ErrorCode::QuotaExceeded),
             400 => Some(ErrorCode::RevisionConflict),
             401 => Some(ErrorCode::LockHeld),
             402 => Some(ErrorCode::InvalidValue),
+            403 => Some(ErrorCode::WriteRejected),
+            404 => Some(ErrorCode::TargetNotFound),
+            429 => Some(ErrorCode::RateLimited),
             500 => Some(ErrorCode::InternalError),
             501 => Some(ErrorCode::ServiceUnavailable),
             502 => Some(ErrorCode::Timeout),
+            503 => Some(ErrorCode::Maintenance),
+            504 => Some(ErrorCode::RetryAfter),
             _ => None,
         }
     }
+
+    /// Numeric code as sent on the wire
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    pub fn category(self) -> ErrorCategory {
+        ErrorCategory::of(self as u16).unwrap_or(ErrorCategory::Server)
+    }
+
+    /// Whether the same request may succeed if sent again later
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::LockHeld | ErrorCode::RateLimited | ErrorCode::QuotaExceeded
+        ) || (self.category() == ErrorCategory::Server && self != ErrorCode::InternalError)
+    }
+}
+
+impl From<ErrorCode> for u16 {
+    fn from(code: ErrorCode) -> Self {
+        code as u16
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?})", *self as u16, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_roundtrip_and_category() {
+        for code in 0..=600u16 {
+            if let Some(known) = ErrorCode::from_u16(code) {
+                assert_eq!(known.as_u16(), code);
+                assert_eq!(Some(known.category()), ErrorCategory::of(code));
+            }
+        }
+        assert_eq!(ErrorCode::WriteRejected.category(), ErrorCategory::State);
+        assert_eq!(ErrorCategory::of(418), Some(ErrorCategory::State));
+        assert_eq!(ErrorCategory::of(42), None);
+        assert!(ErrorCode::RetryAfter.is_transient());
+        assert!(!ErrorCode::Forbidden.is_transient());
+        assert_eq!(ErrorCode::Forbidden.to_string(), "301 (Forbidden)");
+    }
 }
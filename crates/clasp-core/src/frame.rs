@@ -9,14 +9,21 @@
 //! │             [5]   Timestamp present                             │
 //! │             [4]   Encrypted                                     │
 //! │             [3]   Compressed                                    │
-//! │             [2:0] Reserved                                      │
+//! │             [2]   Correlation ID present                        │
+//! │             [1:0] Encoding version (0=MessagePack, 1=binary)    │
 //! │ Byte 2-3:   Payload Length (uint16 big-endian, max 65535)       │
 //! ├─────────────────────────────────────────────────────────────────┤
-//! │ [If timestamp flag] Bytes 4-11: Timestamp (uint64 µs)           │
+//! │ [If timestamp flag] Timestamp (uint64 µs)                       │
+//! │ [If correlation flag] Correlation ID (uint32)                   │
 //! ├─────────────────────────────────────────────────────────────────┤
 //! │ Payload (MessagePack encoded)                                   │
 //! └─────────────────────────────────────────────────────────────────┘
 //! ```
+//!
+//! A request frame with a correlation ID asks the router to echo the ID in
+//! the ACK or ERROR it answers with. Peers only send it to routers that
+//! advertise [`CORRELATION_FEATURE`], since older decoders don't know the
+//! extra header field.
 
 use crate::{Error, QoS, Result, MAGIC_BYTE};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
@@ -30,6 +37,12 @@ pub const HEADER_SIZE_WITH_TS: usize = 12;
 /// Maximum payload size
 pub const MAX_PAYLOAD_SIZE: usize = 65535;
 
+/// Size of the optional correlation ID header field
+pub const CORRELATION_SIZE: usize = 4;
+
+/// Feature name advertised in WELCOME by routers that accept correlation IDs
+pub const CORRELATION_FEATURE: &str = "correlation";
+
 /// Frame flags
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FrameFlags {
@@ -37,6 +50,7 @@ pub struct FrameFlags {
     pub has_timestamp: bool,
     pub encrypted: bool,
     pub compressed: bool,
+    pub has_correlation: bool,
     /// Encoding version: 0 = legacy (MessagePack named), 1+ = compact binary
     pub version: u8,
 }
@@ -54,8 +68,11 @@ impl FrameFlags {
         if self.compressed {
             flags |= 0x08;
         }
-        // Version in bits 0-2 (0 = legacy MessagePack, 1+ = compact binary)
-        flags |= self.version & 0x07;
+        if self.has_correlation {
+            flags |= 0x04;
+        }
+        // Version in bits 0-1 (0 = legacy MessagePack, 1+ = compact binary)
+        flags |= self.version & 0x03;
         flags
     }
 
@@ -65,7 +82,8 @@ impl FrameFlags {
             has_timestamp: (byte & 0x20) != 0,
             encrypted: (byte & 0x10) != 0,
             compressed: (byte & 0x08) != 0,
-            version: byte & 0x07,
+            has_correlation: (byte & 0x04) != 0,
+            version: byte & 0x03,
         }
     }
 
@@ -73,6 +91,18 @@ impl FrameFlags {
     pub fn is_binary_encoding(&self) -> bool {
         self.version >= 1
     }
+
+    /// Header size for frames with these flags
+    pub fn header_size(&self) -> usize {
+        let mut size = HEADER_SIZE;
+        if self.has_timestamp {
+            size += 8;
+        }
+        if self.has_correlation {
+            size += CORRELATION_SIZE;
+        }
+        size
+    }
 }
 
 /// A Clasp frame
@@ -80,6 +110,7 @@ impl FrameFlags {
 pub struct Frame {
     pub flags: FrameFlags,
     pub timestamp: Option<u64>,
+    pub correlation_id: Option<u32>,
     pub payload: Bytes,
 }
 
@@ -89,6 +120,7 @@ impl Frame {
         Self {
             flags: FrameFlags::default(),
             timestamp: None,
+            correlation_id: None,
             payload: payload.into(),
         }
     }
@@ -106,6 +138,13 @@ impl Frame {
         self
     }
 
+    /// Create a frame with a correlation ID for the response to echo
+    pub fn with_correlation_id(mut self, correlation_id: u32) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self.flags.has_correlation = true;
+        self
+    }
+
     /// Create a frame with encryption flag
     pub fn with_encrypted(mut self, encrypted: bool) -> Self {
         self.flags.encrypted = encrypted;
@@ -120,12 +159,7 @@ impl Frame {
 
     /// Calculate the total frame size
     pub fn size(&self) -> usize {
-        let header = if self.flags.has_timestamp {
-            HEADER_SIZE_WITH_TS
-        } else {
-            HEADER_SIZE
-        };
-        header + self.payload.len()
+        self.flags.header_size() + self.payload.len()
     }
 
     /// Encode frame to bytes
@@ -150,6 +184,11 @@ impl Frame {
             buf.put_u64(ts);
         }
 
+        // Correlation ID (if present)
+        if let Some(correlation_id) = self.correlation_id {
+            buf.put_u32(correlation_id);
+        }
+
         // Payload
         buf.extend_from_slice(&self.payload);
 
@@ -178,12 +217,8 @@ impl Frame {
         let payload_len = buf.get_u16() as usize;
 
         // Calculate required size
-        let header_size = if flags.has_timestamp {
-            HEADER_SIZE_WITH_TS
-        } else {
-            HEADER_SIZE
-        };
-        let total_remaining = if flags.has_timestamp { 8 } else { 0 } + payload_len;
+        let header_size = flags.header_size();
+        let total_remaining = header_size - HEADER_SIZE + payload_len;
 
         if buf.remaining() < total_remaining {
             return Err(Error::BufferTooSmall {
@@ -199,12 +234,20 @@ impl Frame {
             None
         };
 
+        // Correlation ID
+        let correlation_id = if flags.has_correlation {
+            Some(buf.get_u32())
+        } else {
+            None
+        };
+
         // Payload
         let payload = buf.copy_to_bytes(payload_len);
 
         Ok(Self {
             flags,
             timestamp,
+            correlation_id,
             payload,
         })
     }
@@ -222,13 +265,7 @@ impl Frame {
         let flags = FrameFlags::from_byte(buf[1]);
         let payload_len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
 
-        let header_size = if flags.has_timestamp {
-            HEADER_SIZE_WITH_TS
-        } else {
-            HEADER_SIZE
-        };
-
-        let total_size = header_size + payload_len;
+        let total_size = flags.header_size() + payload_len;
 
         if buf.len() >= total_size {
             Some(total_size)
@@ -264,6 +301,7 @@ mod tests {
             has_timestamp: true,
             encrypted: true,
             compressed: false,
+            has_correlation: true,
             version: 1, // v3 binary encoding
         };
 
@@ -274,6 +312,7 @@ mod tests {
         assert!(decoded.has_timestamp);
         assert!(decoded.encrypted);
         assert!(!decoded.compressed);
+        assert!(decoded.has_correlation);
         assert_eq!(decoded.version, 1);
         assert!(decoded.is_binary_encoding());
     }
@@ -295,6 +334,22 @@ mod tests {
         assert!(v3_flags.is_binary_encoding());
     }
 
+    #[test]
+    fn test_correlation_id_roundtrip() {
+        let frame = Frame::new(b"set".as_slice())
+            .with_timestamp(42)
+            .with_correlation_id(0xDEAD_BEEF);
+
+        let encoded = frame.encode().unwrap();
+        assert_eq!(encoded.len(), HEADER_SIZE_WITH_TS + CORRELATION_SIZE + 3);
+        assert_eq!(Frame::check_complete(&encoded), Some(encoded.len()));
+
+        let decoded = Frame::decode(&encoded[..]).unwrap();
+        assert_eq!(decoded.timestamp, Some(42));
+        assert_eq!(decoded.correlation_id, Some(0xDEAD_BEEF));
+        assert_eq!(decoded.payload.as_ref(), b"set");
+    }
+
     #[test]
     fn test_check_complete() {
         let frame = Frame::new(b"test".as_slice());
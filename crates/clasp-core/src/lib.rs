@@ -13,8 +13,12 @@
 //! - Protocol message types ([`Message`], [`SignalType`])
 //! - Binary frame encoding/decoding ([`Frame`], [`codec`])
 //! - Address parsing and wildcard matching ([`Address`])
+//! - Topic aliases for hot addresses ([`alias`])
 //! - State management primitives ([`ParamState`])
 //! - Timing utilities ([`Timestamp`])
+//! - Unit conversion for numeric values ([`units`])
+//! - Desired vs reported device state ([`shadow`])
+//! - Per-session delivery statistics ([`link_stats`])
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -22,22 +26,29 @@
 extern crate alloc;
 
 pub mod address;
+pub mod alias;
 pub mod codec;
 pub mod error;
 pub mod frame;
 #[cfg(feature = "std")]
+pub mod link_stats;
+#[cfg(feature = "std")]
 pub mod p2p;
 #[cfg(feature = "std")]
 pub mod security;
+#[cfg(feature = "std")]
+pub mod shadow;
 pub mod state;
 pub mod time;
 #[cfg(feature = "std")]
 pub mod timeline;
 pub mod types;
+#[cfg(feature = "std")]
+pub mod units;
 
 pub use address::Address;
 pub use codec::{decode, encode};
-pub use error::{Error, Result};
+pub use error::{Error, ErrorCategory, ErrorCode, Result};
 pub use frame::Frame;
 #[cfg(feature = "std")]
 pub use p2p::{
@@ -47,8 +58,8 @@ pub use p2p::{
 };
 #[cfg(feature = "std")]
 pub use security::{
-    Action, CpskValidator, Scope, SecurityMode, TokenInfo, TokenValidator, ValidationResult,
-    ValidatorChain,
+    Action, AsyncTokenValidator, CachingValidator, CpskValidator, EntityInfo, Scope, SecurityMode,
+    SyncAdapter, TokenInfo, TokenValidator, ValidationResult, ValidatorChain,
 };
 pub use state::ParamState;
 pub use time::Timestamp;
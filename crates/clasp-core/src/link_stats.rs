@@ -0,0 +1,141 @@
+//! Link health a router reports to each session
+//!
+//! A GET of [`STATS_ADDRESS`] is answered with the asking session's own
+//! delivery statistics, never shared state, so it needs no read scope:
+//!
+//! ```text
+//! {
+//!   "queued": 3,              // frames waiting in the router's send buffer (null if unknown)
+//!   "dropped": 12,            // frames dropped since the session started
+//!   "subscriptions": [
+//!     {"id": 1, "pattern": "/mixer/**", "delivered": 5012, "dropped": 12}
+//!   ]
+//! }
+//! ```
+//!
+//! ```
+//! use clasp_core::link_stats::{LinkStats, SubscriptionStats};
+//!
+//! let stats = LinkStats {
+//!     queued: Some(3),
+//!     dropped: 12,
+//!     subscriptions: vec![SubscriptionStats {
+//!         id: 1,
+//!         pattern: "/mixer/**".to_string(),
+//!         delivered: 5012,
+//!         dropped: 12,
+//!     }],
+//! };
+//! assert_eq!(LinkStats::from_value(&stats.to_value()), Some(stats));
+//! ```
+
+use crate::Value;
+use std::collections::HashMap;
+
+/// Address a session GETs for its own [`LinkStats`]
+pub const STATS_ADDRESS: &str = "/clasp/stats";
+
+/// Delivery counts for one subscription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionStats {
+    /// Subscription ID the client chose
+    pub id: u32,
+    /// Pattern the subscription matches
+    pub pattern: String,
+    /// Updates handed to the transport
+    pub delivered: u64,
+    /// Updates dropped because the send buffer was full
+    pub dropped: u64,
+}
+
+/// A session's link health as seen by the router
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Frames waiting in the router's send buffer for this session, if the
+    /// transport reports it
+    pub queued: Option<u64>,
+    /// Frames dropped since the session started, for any reason
+    pub dropped: u64,
+    /// Counts per subscription, by ID
+    pub subscriptions: Vec<SubscriptionStats>,
+}
+
+impl SubscriptionStats {
+    fn to_value(&self) -> Value {
+        Value::Map(HashMap::from([
+            ("id".to_string(), Value::Int(self.id as i64)),
+            ("pattern".to_string(), Value::String(self.pattern.clone())),
+            ("delivered".to_string(), count(self.delivered)),
+            ("dropped".to_string(), count(self.dropped)),
+        ]))
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        let Value::Map(map) = value else {
+            return None;
+        };
+        Some(Self {
+            id: u32::try_from(map.get("id")?.as_i64()?).ok()?,
+            pattern: map.get("pattern")?.as_str()?.to_string(),
+            delivered: uncount(map.get("delivered")?)?,
+            dropped: uncount(map.get("dropped")?)?,
+        })
+    }
+}
+
+impl LinkStats {
+    /// The map served at [`STATS_ADDRESS`]
+    pub fn to_value(&self) -> Value {
+        Value::Map(HashMap::from([
+            ("queued".to_string(), self.queued.map_or(Value::Null, count)),
+            ("dropped".to_string(), count(self.dropped)),
+            (
+                "subscriptions".to_string(),
+                Value::Array(
+                    self.subscriptions
+                        .iter()
+                        .map(SubscriptionStats::to_value)
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+
+    /// Parse the map served at [`STATS_ADDRESS`]
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let Value::Map(map) = value else {
+            return None;
+        };
+        let queued = match map.get("queued") {
+            None | Some(Value::Null) => None,
+            Some(queued) => Some(uncount(queued)?),
+        };
+        let Some(Value::Array(subscriptions)) = map.get("subscriptions") else {
+            return None;
+        };
+        Some(Self {
+            queued,
+            dropped: uncount(map.get("dropped")?)?,
+            subscriptions: subscriptions
+                .iter()
+                .map(SubscriptionStats::from_value)
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Counts for the subscription with `id`
+    pub fn subscription(&self, id: u32) -> Option<&SubscriptionStats> {
+        self.subscriptions.iter().find(|s| s.id == id)
+    }
+}
+
+fn count(n: u64) -> Value {
+    Value::Int(i64::try_from(n).unwrap_or(i64::MAX))
+}
+
+fn uncount(value: &Value) -> Option<u64> {
+    match value {
+        Value::Int(n) => u64::try_from(*n).ok(),
+        _ => None,
+    }
+}
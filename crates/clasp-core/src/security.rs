@@ -43,7 +43,7 @@ use crate::{Error, Result};
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Actions that can be performed on addresses
@@ -178,6 +178,26 @@ pub struct TokenInfo {
     pub expires_at: Option<SystemTime>,
     /// Additional metadata
     pub metadata: HashMap<String, String>,
+    /// Registry entity the token was issued to, for entity (`ent_`) tokens
+    pub entity: Option<Arc<EntityInfo>>,
+}
+
+/// Registry entity behind a validated token
+///
+/// Carried on [`TokenInfo`] so the router can key policy off the entity's
+/// type and registry metadata rather than only the subject string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityInfo {
+    /// Entity ID (also the token subject)
+    pub id: String,
+    /// Entity type (`device`, `user`, `service` or `router`)
+    pub entity_type: String,
+    /// Human-readable entity name
+    pub name: String,
+    /// Registry tags
+    pub tags: Vec<String>,
+    /// Registry metadata
+    pub metadata: HashMap<String, String>,
 }
 
 impl TokenInfo {
@@ -189,6 +209,7 @@ impl TokenInfo {
             scopes,
             expires_at: None,
             metadata: HashMap::new(),
+            entity: None,
         }
     }
 
@@ -234,10 +255,32 @@ impl TokenInfo {
         self.metadata.insert(key.into(), value.into());
         self
     }
+
+    /// Set the registry entity the token was issued to
+    pub fn with_entity(mut self, entity: EntityInfo) -> Self {
+        self.entity = Some(Arc::new(entity));
+        self
+    }
+}
+
+/// [`TokenInfo::metadata`] key for the hex-encoded Ed25519 public key a
+/// token is bound to. A client presenting such a token must sign the WELCOME
+/// challenge with the matching private key before its session starts.
+pub const AUDIENCE_METADATA: &str = "audience";
+
+/// Bytes a client signs to answer a WELCOME `challenge`, tied to the
+/// session so a proof can't be reused on another connection
+pub fn possession_payload(session: &str, challenge: &str) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(12 + session.len() + challenge.len());
+    payload.extend_from_slice(b"clasp-proof\0");
+    payload.extend_from_slice(session.as_bytes());
+    payload.push(0);
+    payload.extend_from_slice(challenge.as_bytes());
+    payload
 }
 
 /// Result of token validation
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ValidationResult {
     /// Token is valid
     Valid(TokenInfo),
@@ -261,6 +304,39 @@ pub trait TokenValidator: Send + Sync + std::any::Any {
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
+/// Async token validation, for validators that do I/O (registry lookups,
+/// JWKS fetches) and should not block the task handling HELLO.
+///
+/// Existing [`TokenValidator`]s run on the async path through [`SyncAdapter`].
+#[async_trait::async_trait]
+pub trait AsyncTokenValidator: Send + Sync {
+    /// Validate a token and return token information if valid
+    async fn validate(&self, token: &str) -> ValidationResult;
+
+    /// Get the validator name (for logging)
+    fn name(&self) -> &str;
+}
+
+/// Runs a synchronous [`TokenValidator`] on the async validation path
+pub struct SyncAdapter<V: ?Sized>(pub std::sync::Arc<V>);
+
+impl<V: TokenValidator> SyncAdapter<V> {
+    pub fn new(validator: V) -> Self {
+        Self(std::sync::Arc::new(validator))
+    }
+}
+
+#[async_trait::async_trait]
+impl<V: TokenValidator + ?Sized> AsyncTokenValidator for SyncAdapter<V> {
+    async fn validate(&self, token: &str) -> ValidationResult {
+        self.0.validate(token)
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
 /// Capability Pre-Shared Key (CPSK) validator
 ///
 /// Stores tokens in memory with their associated scopes.
@@ -372,8 +448,27 @@ impl TokenValidator for CpskValidator {
 }
 
 /// A chain of validators that tries each one in order
+///
+/// Each validator call is timed. With the `metrics` feature, outcomes are
+/// counted in `clasp_validator_results_total` (labels `validator` and
+/// `result`: `accepted`, `rejected`, `not_my_token` or `timeout`) and
+/// latencies recorded in `clasp_validator_duration_seconds`.
+///
+/// With a timeout set, each validator runs on its own thread and is abandoned
+/// if it has not answered in time; the chain moves on to the next validator.
+/// If no later validator accepts the token, it is rejected as timed out, so
+/// one slow validator bounds handshake latency without failing open.
 pub struct ValidatorChain {
-    validators: Vec<Box<dyn TokenValidator>>,
+    validators: Vec<ChainEntry>,
+    timeout: Option<Duration>,
+}
+
+struct ChainEntry {
+    validator: std::sync::Arc<dyn TokenValidator>,
+    /// Native async implementation, used by the chain's async path
+    async_validator: Option<std::sync::Arc<dyn AsyncTokenValidator>>,
+    /// Overrides the chain-wide timeout
+    timeout: Option<Duration>,
 }
 
 impl ValidatorChain {
@@ -381,12 +476,41 @@ impl ValidatorChain {
     pub fn new() -> Self {
         Self {
             validators: Vec::new(),
+            timeout: None,
         }
     }
 
     /// Add a validator to the chain
     pub fn add<V: TokenValidator + 'static>(&mut self, validator: V) {
-        self.validators.push(Box::new(validator));
+        self.validators.push(ChainEntry {
+            validator: std::sync::Arc::new(validator),
+            async_validator: None,
+            timeout: None,
+        });
+    }
+
+    /// Add a validator that also has a native async implementation. The
+    /// chain's [`AsyncTokenValidator`] path awaits it instead of blocking.
+    pub fn add_async<V: TokenValidator + AsyncTokenValidator + 'static>(&mut self, validator: V) {
+        let validator = std::sync::Arc::new(validator);
+        self.validators.push(ChainEntry {
+            validator: validator.clone(),
+            async_validator: Some(validator),
+            timeout: None,
+        });
+    }
+
+    /// Add a validator with its own timeout, overriding the chain-wide one
+    pub fn add_with_timeout<V: TokenValidator + 'static>(
+        &mut self,
+        validator: V,
+        timeout: Duration,
+    ) {
+        self.validators.push(ChainEntry {
+            validator: std::sync::Arc::new(validator),
+            async_validator: None,
+            timeout: Some(timeout),
+        });
     }
 
     /// Add a validator and return self for chaining
@@ -395,15 +519,74 @@ impl ValidatorChain {
         self
     }
 
+    /// Time limit for each validator call (default: none)
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Set the per-validator time limit and return self for chaining
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Validate a token using all validators in order
     pub fn validate(&self, token: &str) -> ValidationResult {
-        for validator in &self.validators {
-            match validator.validate(token) {
-                ValidationResult::NotMyToken => continue,
-                result => return result,
+        let mut timed_out = None;
+        for entry in &self.validators {
+            let started = std::time::Instant::now();
+            let result = self.run(entry, token);
+            if let Some(result) = Self::settle(entry, result, started, &mut timed_out) {
+                return result;
+            }
+        }
+        Self::rejection(timed_out)
+    }
+
+    /// Record a validator's outcome. Returns the chain's result if this
+    /// validator decided it.
+    fn settle(
+        entry: &ChainEntry,
+        result: Option<ValidationResult>,
+        started: std::time::Instant,
+        timed_out: &mut Option<String>,
+    ) -> Option<ValidationResult> {
+        let outcome = match &result {
+            Some(ValidationResult::Valid(_)) => "accepted",
+            Some(ValidationResult::NotMyToken) => "not_my_token",
+            Some(_) => "rejected",
+            None => "timeout",
+        };
+        record_validation(entry.validator.name(), outcome, started.elapsed());
+        match result {
+            Some(ValidationResult::NotMyToken) => None,
+            Some(result) => Some(result),
+            None => {
+                timed_out.get_or_insert_with(|| entry.validator.name().to_string());
+                None
             }
         }
-        ValidationResult::Invalid("no validator accepted the token".to_string())
+    }
+
+    fn rejection(timed_out: Option<String>) -> ValidationResult {
+        match timed_out {
+            Some(name) => ValidationResult::Invalid(format!("validator {} timed out", name)),
+            None => ValidationResult::Invalid("no validator accepted the token".to_string()),
+        }
+    }
+
+    /// Run one validator, returning `None` if it exceeded its timeout
+    fn run(&self, entry: &ChainEntry, token: &str) -> Option<ValidationResult> {
+        let Some(timeout) = entry.timeout.or(self.timeout) else {
+            return Some(entry.validator.validate(token));
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let validator = std::sync::Arc::clone(&entry.validator);
+        let token = token.to_string();
+        std::thread::spawn(move || {
+            let _ = tx.send(validator.validate(&token));
+        });
+        rx.recv_timeout(timeout).ok()
     }
 
     /// Get the number of validators
@@ -417,15 +600,27 @@ impl ValidatorChain {
     }
 }
 
+#[cfg(feature = "metrics")]
+fn record_validation(validator: &str, outcome: &'static str, elapsed: Duration) {
+    metrics::counter!(
+        "clasp_validator_results_total",
+        "validator" => validator.to_string(),
+        "result" => outcome
+    )
+    .increment(1);
+    metrics::histogram!(
+        "clasp_validator_duration_seconds",
+        "validator" => validator.to_string()
+    )
+    .record(elapsed.as_secs_f64());
+}
+
+#[cfg(not(feature = "metrics"))]
+fn record_validation(_validator: &str, _outcome: &'static str, _elapsed: Duration) {}
+
 impl TokenValidator for ValidatorChain {
     fn validate(&self, token: &str) -> ValidationResult {
-        for validator in &self.validators {
-            match validator.validate(token) {
-                ValidationResult::NotMyToken => continue,
-                result => return result,
-            }
-        }
-        ValidationResult::Invalid("no validator accepted the token".to_string())
+        ValidatorChain::validate(self, token)
     }
 
     fn name(&self) -> &str {
@@ -437,12 +632,231 @@ impl TokenValidator for ValidatorChain {
     }
 }
 
+/// Awaits validators added with [`ValidatorChain::add_async`]; others run as
+/// on the sync path, including their timeouts. Async validators are not
+/// subject to the chain's timeouts, so bound the whole call with the
+/// runtime's timer instead.
+#[async_trait::async_trait]
+impl AsyncTokenValidator for ValidatorChain {
+    async fn validate(&self, token: &str) -> ValidationResult {
+        let mut timed_out = None;
+        for entry in &self.validators {
+            let started = std::time::Instant::now();
+            let result = match entry.async_validator {
+                Some(ref validator) => Some(validator.validate(token).await),
+                None => self.run(entry, token),
+            };
+            if let Some(result) = Self::settle(entry, result, started, &mut timed_out) {
+                return result;
+            }
+        }
+        Self::rejection(timed_out)
+    }
+
+    fn name(&self) -> &str {
+        "ValidatorChain"
+    }
+}
+
 impl Default for ValidatorChain {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// LRU cache of validation results in front of an expensive validator
+///
+/// Entity and capability tokens are signature-checked on every HELLO, so a
+/// reconnect storm repeats the same Ed25519 verification thousands of times.
+/// `CachingValidator` remembers results per token, keyed by a SHA-256 of the
+/// token so raw tokens are never used as map keys:
+///
+/// - accepted tokens for `ttl`, never past the token's own expiry
+/// - rejected and expired tokens for `negative_ttl` (zero disables negative
+///   caching); `NotMyToken` is never cached
+///
+/// Call [`invalidate_subject`](Self::invalidate_subject) when an entity is
+/// revoked or suspended, or [`clear`](Self::clear) when trust changes
+/// wholesale. With the `metrics` feature, lookups are counted in
+/// `clasp_validation_cache_total` (labels `validator` and `result`: `hit` or
+/// `miss`).
+pub struct CachingValidator<V> {
+    inner: V,
+    entries: std::sync::Mutex<hashlink::LruCache<[u8; 32], CacheEntry>>,
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+struct CacheEntry {
+    result: ValidationResult,
+    until: std::time::Instant,
+}
+
+impl<V> CachingValidator<V> {
+    /// Cache up to `capacity` results from `inner`, accepted ones for `ttl`
+    pub fn new(inner: V, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            entries: std::sync::Mutex::new(hashlink::LruCache::new(capacity.max(1))),
+            ttl,
+            negative_ttl: Duration::from_secs(5),
+        }
+    }
+
+    /// How long rejections are cached (default 5 seconds, zero = never)
+    pub fn with_negative_ttl(mut self, negative_ttl: Duration) -> Self {
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    /// The wrapped validator
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Number of cached results
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget the cached result for one token
+    pub fn invalidate(&self, token: &str) -> bool {
+        self.lock().remove(&Self::key(token)).is_some()
+    }
+
+    /// Forget accepted tokens for `subject`, e.g. after revoking an entity.
+    /// Cached rejections carry no subject, so they are all dropped too (a
+    /// newly registered entity must not stay "not found").
+    pub fn invalidate_subject(&self, subject: &str) -> usize {
+        let mut entries = self.lock();
+        let stale: Vec<[u8; 32]> = entries
+            .iter()
+            .filter(|(_, entry)| match &entry.result {
+                ValidationResult::Valid(info) => info.subject.as_deref() == Some(subject),
+                _ => true,
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &stale {
+            entries.remove(key);
+        }
+        stale.len()
+    }
+
+    /// Forget everything
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, hashlink::LruCache<[u8; 32], CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn key(token: &str) -> [u8; 32] {
+        use sha2::Digest;
+        sha2::Sha256::digest(token.as_bytes()).into()
+    }
+
+    fn lookup(&self, name: &str, key: &[u8; 32]) -> Option<ValidationResult> {
+        let mut entries = self.lock();
+        let hit = match entries.get(key) {
+            Some(entry) if entry.until > std::time::Instant::now() => match &entry.result {
+                ValidationResult::Valid(info) if info.is_expired() => None,
+                result => Some(result.clone()),
+            },
+            _ => None,
+        };
+        if hit.is_none() {
+            entries.remove(key);
+        }
+        drop(entries);
+        record_cache_lookup(name, hit.is_some());
+        hit
+    }
+
+    fn store(&self, key: [u8; 32], result: &ValidationResult) {
+        let now = std::time::Instant::now();
+        let until = match result {
+            ValidationResult::Valid(info) => {
+                let left = info
+                    .expires_at
+                    .map(|at| at.duration_since(SystemTime::now()).unwrap_or_default())
+                    .unwrap_or(self.ttl);
+                now + self.ttl.min(left)
+            }
+            ValidationResult::NotMyToken => return,
+            ValidationResult::Invalid(_) | ValidationResult::Expired => {
+                if self.negative_ttl.is_zero() {
+                    return;
+                }
+                now + self.negative_ttl
+            }
+        };
+        self.lock().insert(
+            key,
+            CacheEntry {
+                result: result.clone(),
+                until,
+            },
+        );
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn record_cache_lookup(validator: &str, hit: bool) {
+    metrics::counter!(
+        "clasp_validation_cache_total",
+        "validator" => validator.to_string(),
+        "result" => if hit { "hit" } else { "miss" }
+    )
+    .increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+fn record_cache_lookup(_validator: &str, _hit: bool) {}
+
+impl<V: TokenValidator> TokenValidator for CachingValidator<V> {
+    fn validate(&self, token: &str) -> ValidationResult {
+        let key = Self::key(token);
+        if let Some(result) = self.lookup(self.inner.name(), &key) {
+            return result;
+        }
+        let result = self.inner.validate(token);
+        self.store(key, &result);
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<V: AsyncTokenValidator> AsyncTokenValidator for CachingValidator<V> {
+    async fn validate(&self, token: &str) -> ValidationResult {
+        let key = Self::key(token);
+        if let Some(result) = self.lookup(self.inner.name(), &key) {
+            return result;
+        }
+        let result = self.inner.validate(token).await;
+        self.store(key, &result);
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
 /// Security mode for the router
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SecurityMode {
@@ -675,6 +1089,159 @@ mod tests {
         }
     }
 
+    struct SlowValidator(Duration);
+
+    impl TokenValidator for SlowValidator {
+        fn validate(&self, _token: &str) -> ValidationResult {
+            std::thread::sleep(self.0);
+            ValidationResult::NotMyToken
+        }
+
+        fn name(&self) -> &str {
+            "Slow"
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_validator_chain_timeout() {
+        let cpsk = CpskValidator::new();
+        let token = CpskValidator::generate_token();
+        cpsk.register(token.clone(), TokenInfo::new(token.clone(), vec![]));
+
+        let chain = ValidatorChain::new()
+            .with_timeout(Duration::from_millis(50))
+            .with(SlowValidator(Duration::from_secs(5)))
+            .with(cpsk);
+
+        // The slow validator is abandoned and the next one accepts
+        let started = std::time::Instant::now();
+        assert!(matches!(chain.validate(&token), ValidationResult::Valid(_)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Nobody accepts: the timeout is reported
+        match chain.validate("other_token") {
+            ValidationResult::Invalid(reason) => assert!(reason.contains("Slow timed out")),
+            _ => panic!("expected invalid token"),
+        }
+
+        // A per-validator timeout overrides the chain-wide one
+        let mut chain = ValidatorChain::new().with_timeout(Duration::from_millis(10));
+        chain.add_with_timeout(
+            SlowValidator(Duration::from_millis(20)),
+            Duration::from_secs(2),
+        );
+        match chain.validate("other_token") {
+            ValidationResult::Invalid(reason) => assert!(!reason.contains("timed out")),
+            _ => panic!("expected invalid token"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validator_chain_async_path() {
+        let cpsk = CpskValidator::new();
+        let token = CpskValidator::generate_token();
+        cpsk.register(token.clone(), TokenInfo::new(token.clone(), vec![]));
+
+        let adapter = SyncAdapter::new(cpsk);
+        assert!(matches!(
+            AsyncTokenValidator::validate(&adapter, &token).await,
+            ValidationResult::Valid(_)
+        ));
+
+        // Sync entries still honour their timeouts on the async path
+        let chain = ValidatorChain::new()
+            .with_timeout(Duration::from_millis(50))
+            .with(SlowValidator(Duration::from_secs(5)));
+        match AsyncTokenValidator::validate(&chain, "other_token").await {
+            ValidationResult::Invalid(reason) => assert!(reason.contains("timed out")),
+            _ => panic!("expected invalid token"),
+        }
+    }
+
+    /// Accepts `ok_*` tokens as subject `dev`, rejects everything else
+    struct CountingValidator(std::sync::atomic::AtomicUsize);
+
+    impl TokenValidator for CountingValidator {
+        fn validate(&self, token: &str) -> ValidationResult {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if token.starts_with("ok_") {
+                ValidationResult::Valid(
+                    TokenInfo::new(token.to_string(), vec![]).with_subject("dev"),
+                )
+            } else {
+                ValidationResult::Invalid("bad signature".to_string())
+            }
+        }
+
+        fn name(&self) -> &str {
+            "Counting"
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_caching_validator() {
+        let cache = CachingValidator::new(
+            CountingValidator(Default::default()),
+            2,
+            Duration::from_secs(60),
+        );
+        let calls = || cache.inner().0.load(std::sync::atomic::Ordering::SeqCst);
+
+        for _ in 0..3 {
+            assert!(matches!(cache.validate("ok_a"), ValidationResult::Valid(_)));
+            assert!(matches!(
+                cache.validate("forged"),
+                ValidationResult::Invalid(_)
+            ));
+        }
+        assert_eq!(calls(), 2);
+
+        // Revoking the subject drops its entries and all rejections
+        assert_eq!(cache.invalidate_subject("dev"), 2);
+        assert!(cache.is_empty());
+        cache.validate("ok_a");
+        assert_eq!(calls(), 3);
+
+        // Least recently used entries are evicted at capacity
+        cache.validate("ok_b");
+        cache.validate("ok_a");
+        cache.validate("ok_c");
+        assert_eq!(cache.len(), 2);
+        cache.validate("ok_a");
+        assert_eq!(calls(), 5);
+        cache.validate("ok_b");
+        assert_eq!(calls(), 6);
+
+        // Tokens are never served past their own expiry
+        let mut info = TokenInfo::new("ok_old".to_string(), vec![]);
+        info.expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+        cache.store(
+            CachingValidator::<CountingValidator>::key("ok_old"),
+            &ValidationResult::Valid(info),
+        );
+        cache.validate("ok_old");
+        assert_eq!(calls(), 7);
+
+        // Negative caching can be turned off
+        let cache = CachingValidator::new(
+            CountingValidator(Default::default()),
+            16,
+            Duration::from_secs(60),
+        )
+        .with_negative_ttl(Duration::ZERO);
+        cache.validate("forged");
+        cache.validate("forged");
+        assert_eq!(cache.inner().0.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn test_validator_chain_as_trait_object() {
         let mut chain = ValidatorChain::new();
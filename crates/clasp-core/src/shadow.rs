@@ -0,0 +1,183 @@
+//! Device shadows: desired vs reported state
+//!
+//! A shadow is three params under one base address:
+//!
+//! - `<base>/desired`: what apps want the device to be (written by apps)
+//! - `<base>/reported`: what the device last said it is (written by the device)
+//! - `<base>/delta`: the part of `desired` that `reported` doesn't match yet
+//!   (computed by the router, `null` once they agree)
+//!
+//! Map values are compared key by key, recursively, so a delta only carries
+//! the keys the device still has to apply; other values are compared whole.
+//!
+//! ```
+//! use clasp_core::shadow::{delta, merge};
+//! use clasp_core::Value;
+//! use std::collections::HashMap;
+//!
+//! let map = |pairs: &[(&str, Value)]| {
+//!     Value::Map(pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect::<HashMap<_, _>>())
+//! };
+//! let desired = map(&[("power", Value::Bool(true)), ("level", Value::Float(0.8))]);
+//! let reported = map(&[("power", Value::Bool(true)), ("level", Value::Float(0.2))]);
+//!
+//! let pending = delta(&desired, Some(&reported)).unwrap();
+//! assert_eq!(pending, map(&[("level", Value::Float(0.8))]));
+//!
+//! // The device applies the delta and reports back
+//! let reported = merge(&reported, &pending);
+//! assert_eq!(delta(&desired, Some(&reported)), None);
+//! ```
+
+use crate::Value;
+use std::collections::HashMap;
+
+/// Last segment of the address apps write
+pub const DESIRED: &str = "desired";
+
+/// Last segment of the address the device writes
+pub const REPORTED: &str = "reported";
+
+/// Last segment of the address the router computes
+pub const DELTA: &str = "delta";
+
+/// Which side of a shadow an address is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowPart {
+    Desired,
+    Reported,
+    Delta,
+}
+
+impl ShadowPart {
+    /// The address segment for this part
+    pub fn segment(self) -> &'static str {
+        match self {
+            ShadowPart::Desired => DESIRED,
+            ShadowPart::Reported => REPORTED,
+            ShadowPart::Delta => DELTA,
+        }
+    }
+}
+
+/// Split a shadow address into its base and part, e.g.
+/// `/devices/lamp/desired` into `/devices/lamp` and [`ShadowPart::Desired`]
+pub fn split(address: &str) -> Option<(&str, ShadowPart)> {
+    let (base, last) = address.rsplit_once('/')?;
+    if base.is_empty() {
+        return None;
+    }
+    let part = match last {
+        DESIRED => ShadowPart::Desired,
+        REPORTED => ShadowPart::Reported,
+        DELTA => ShadowPart::Delta,
+        _ => return None,
+    };
+    Some((base, part))
+}
+
+/// The address of one part of the shadow at `base`
+pub fn address(base: &str, part: ShadowPart) -> String {
+    format!("{}/{}", base.trim_end_matches('/'), part.segment())
+}
+
+/// What `reported` still lacks of `desired`, or `None` if it matches.
+/// A `null` desired state asks for nothing.
+pub fn delta(desired: &Value, reported: Option<&Value>) -> Option<Value> {
+    match (desired, reported) {
+        (Value::Null, _) => None,
+        (Value::Map(desired), Some(Value::Map(reported))) => {
+            let pending: HashMap<String, Value> = desired
+                .iter()
+                .filter_map(|(key, want)| {
+                    delta(want, reported.get(key)).map(|value| (key.clone(), value))
+                })
+                .collect();
+            (!pending.is_empty()).then_some(Value::Map(pending))
+        }
+        (desired, Some(reported)) if desired == reported => None,
+        (desired, _) => Some(desired.clone()),
+    }
+}
+
+/// `reported` with `delta` applied: maps are merged key by key, anything
+/// else is replaced
+pub fn merge(reported: &Value, delta: &Value) -> Value {
+    match (reported, delta) {
+        (Value::Map(reported), Value::Map(delta)) => {
+            let mut merged = reported.clone();
+            for (key, value) in delta {
+                let value = match merged.get(key) {
+                    Some(current) => merge(current, value),
+                    None => value.clone(),
+                };
+                merged.insert(key.clone(), value);
+            }
+            Value::Map(merged)
+        }
+        (_, delta) => delta.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, Value)]) -> Value {
+        Value::Map(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_split_and_address() {
+        assert_eq!(
+            split("/devices/lamp/desired"),
+            Some(("/devices/lamp", ShadowPart::Desired))
+        );
+        assert_eq!(
+            split("/devices/lamp/reported"),
+            Some(("/devices/lamp", ShadowPart::Reported))
+        );
+        assert_eq!(split("/desired"), None);
+        assert_eq!(split("/devices/lamp/level"), None);
+        assert_eq!(
+            address("/devices/lamp/", ShadowPart::Delta),
+            "/devices/lamp/delta"
+        );
+    }
+
+    #[test]
+    fn test_nested_delta() {
+        let desired = map(&[
+            ("power", Value::Bool(true)),
+            (
+                "color",
+                map(&[("r", Value::Int(255)), ("g", Value::Int(0))]),
+            ),
+        ]);
+        let reported = map(&[
+            ("power", Value::Bool(true)),
+            (
+                "color",
+                map(&[("r", Value::Int(255)), ("g", Value::Int(10))]),
+            ),
+            ("uptime", Value::Int(42)),
+        ]);
+
+        let pending = delta(&desired, Some(&reported)).unwrap();
+        assert_eq!(pending, map(&[("color", map(&[("g", Value::Int(0))]))]));
+        assert_eq!(delta(&desired, Some(&merge(&reported, &pending))), None);
+
+        // Scalars and missing reports
+        assert_eq!(
+            delta(&Value::Int(1), Some(&Value::Int(2))),
+            Some(Value::Int(1))
+        );
+        assert_eq!(delta(&Value::Int(1), None), Some(Value::Int(1)));
+        assert_eq!(delta(&Value::Null, Some(&Value::Int(2))), None);
+    }
+}
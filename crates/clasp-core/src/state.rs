@@ -2,7 +2,7 @@
 //!
 //! Provides conflict resolution and revision tracking for stateful parameters.
 
-use crate::{ConflictStrategy, Ttl, Value};
+use crate::{ConflictStrategy, SetMessage, Ttl, Value};
 use std::collections::HashMap;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -267,6 +267,19 @@ impl std::fmt::Display for UpdateError {
 
 impl std::error::Error for UpdateError {}
 
+impl UpdateError {
+    /// The ERROR code a router reports this with
+    pub fn error_code(&self) -> crate::error::ErrorCode {
+        use crate::error::ErrorCode;
+        match self {
+            Self::RevisionConflict { .. } | Self::ConflictRejected => ErrorCode::RevisionConflict,
+            Self::LockHeld { .. } => ErrorCode::LockHeld,
+            Self::OutOfRange => ErrorCode::InvalidValue,
+            Self::AtCapacity => ErrorCode::InternalError,
+        }
+    }
+}
+
 /// Error returned when state store is at capacity
 #[derive(Debug, Clone)]
 pub struct CapacityError;
@@ -381,6 +394,101 @@ impl StateStore {
         }
     }
 
+    /// Set several params as one unit. Either every update is applied or,
+    /// if any is rejected, every param is restored to its previous state.
+    ///
+    /// New params never evict existing ones here: if the batch does not fit
+    /// under `max_params` it is rejected with `AtCapacity`. With `dry_run`
+    /// the updates are checked and rolled back even on success.
+    ///
+    /// Returns the new revisions in order, or the index of the first
+    /// rejected update with its error.
+    pub fn set_all(
+        &mut self,
+        updates: &[(String, Value, Option<Ttl>)],
+        writer: &str,
+        dry_run: bool,
+    ) -> Result<Vec<u64>, (usize, UpdateError)> {
+        let staged = updates
+            .iter()
+            .map(|(address, value, ttl)| (address.as_str(), value, None, false, false, *ttl));
+        self.set_staged(staged, writer, dry_run)
+    }
+
+    /// Apply SET messages as one unit, like [`set_all`](Self::set_all) but
+    /// with each message's revision check, lock, unlock and TTL
+    pub fn apply_all(
+        &mut self,
+        sets: &[SetMessage],
+        writer: &str,
+    ) -> Result<Vec<u64>, (usize, UpdateError)> {
+        let staged = sets.iter().map(|set| {
+            (
+                set.address.as_str(),
+                &set.value,
+                set.revision,
+                set.lock,
+                set.unlock,
+                set.ttl,
+            )
+        });
+        self.set_staged(staged, writer, false)
+    }
+
+    /// Shared body of `set_all` and `apply_all`: each update is
+    /// `(address, value, revision, lock, unlock, ttl)`
+    fn set_staged<'a>(
+        &mut self,
+        updates: impl Iterator<Item = (&'a str, &'a Value, Option<u64>, bool, bool, Option<Ttl>)>
+            + Clone,
+        writer: &str,
+        dry_run: bool,
+    ) -> Result<Vec<u64>, (usize, UpdateError)> {
+        if let Some(max) = self.config.max_params {
+            let mut new_params = std::collections::HashSet::new();
+            for (i, (address, ..)) in updates.clone().enumerate() {
+                if !self.params.contains_key(address)
+                    && new_params.insert(address)
+                    && self.params.len() + new_params.len() > max
+                {
+                    return Err((i, UpdateError::AtCapacity));
+                }
+            }
+        }
+
+        let mut previous: Vec<(&str, Option<ParamState>)> = Vec::new();
+        let mut revisions = Vec::new();
+        for (i, (address, value, revision, lock, unlock, ttl)) in updates.enumerate() {
+            previous.push((address, self.params.get(address).cloned()));
+            match self.set(address, value.clone(), writer, revision, lock, unlock, ttl) {
+                Ok(revision) => revisions.push(revision),
+                Err(e) => {
+                    self.restore(previous);
+                    return Err((i, e));
+                }
+            }
+        }
+        if dry_run {
+            self.restore(previous);
+        }
+        Ok(revisions)
+    }
+
+    /// Undo a partial `set_all`, newest change first so repeated addresses
+    /// end up at their original state
+    fn restore(&mut self, previous: Vec<(&str, Option<ParamState>)>) {
+        for (address, state) in previous.into_iter().rev() {
+            match state {
+                Some(state) => {
+                    self.params.insert(address.to_string(), state);
+                }
+                None => {
+                    self.params.remove(address);
+                }
+            }
+        }
+    }
+
     /// Evict the least recently accessed param
     fn evict_lru(&mut self) {
         if let Some(oldest_key) = self
@@ -408,26 +516,38 @@ impl StateStore {
     /// Remove params that haven't been accessed within the TTL
     /// Returns the number of params removed
     pub fn cleanup_stale(&mut self, ttl: Duration) -> usize {
+        self.remove_stale(ttl).len()
+    }
+
+    /// Remove params that haven't been accessed within the TTL
+    /// Returns the removed addresses
+    pub fn remove_stale(&mut self, ttl: Duration) -> Vec<String> {
         let now = current_timestamp();
         let global_ttl_micros = ttl.as_micros() as u64;
 
-        let before = self.params.len();
-        self.params.retain(|_, v| match v.ttl {
-            Some(Ttl::Never) => true,
-            Some(Ttl::Sliding(secs)) => {
-                let cutoff = now.saturating_sub(secs as u64 * 1_000_000);
-                v.last_accessed >= cutoff
-            }
-            Some(Ttl::Absolute(secs)) => {
-                let expires_at = v.timestamp.saturating_add(secs as u64 * 1_000_000);
-                now < expires_at
-            }
-            None => {
-                let cutoff = now.saturating_sub(global_ttl_micros);
-                v.last_accessed >= cutoff
+        let mut removed = Vec::new();
+        self.params.retain(|address, v| {
+            let keep = match v.ttl {
+                Some(Ttl::Never) | Some(Ttl::Session) => true,
+                Some(Ttl::Sliding(secs)) => {
+                    let cutoff = now.saturating_sub(secs as u64 * 1_000_000);
+                    v.last_accessed >= cutoff
+                }
+                Some(Ttl::Absolute(secs)) => {
+                    let expires_at = v.timestamp.saturating_add(secs as u64 * 1_000_000);
+                    now < expires_at
+                }
+                None => {
+                    let cutoff = now.saturating_sub(global_ttl_micros);
+                    v.last_accessed >= cutoff
+                }
+            };
+            if !keep {
+                removed.push(address.clone());
             }
+            keep
         });
-        before - self.params.len()
+        removed
     }
 
     /// Run cleanup using the configured TTL (if any)
@@ -471,6 +591,40 @@ impl StateStore {
         self.params.remove(address)
     }
 
+    /// Put a param at `address` as it is, without conflict resolution or
+    /// limits. Returns the param it replaced.
+    pub fn insert(&mut self, address: String, param: ParamState) -> Option<ParamState> {
+        self.params.insert(address, param)
+    }
+
+    /// Remove the session-scoped params last written by `writer`
+    /// Returns the removed addresses with their final state
+    pub fn remove_session_scoped(&mut self, writer: &str) -> Vec<(String, ParamState)> {
+        let owned: Vec<String> = self
+            .params
+            .iter()
+            .filter(|(_, v)| v.ttl == Some(Ttl::Session) && v.writer == writer)
+            .map(|(k, _)| k.clone())
+            .collect();
+        owned
+            .into_iter()
+            .filter_map(|k| self.params.remove(&k).map(|v| (k, v)))
+            .collect()
+    }
+
+    /// Move ownership of `from`'s session-scoped params to `to`, so they
+    /// live until `to` ends instead. Returns the number of params moved.
+    pub fn transfer_session_scoped(&mut self, from: &str, to: &str) -> usize {
+        let mut moved = 0;
+        for param in self.params.values_mut() {
+            if param.ttl == Some(Ttl::Session) && param.writer == from {
+                param.writer = to.to_string();
+                moved += 1;
+            }
+        }
+        moved
+    }
+
     /// Clear all params
     pub fn clear(&mut self) {
         self.params.clear();
@@ -720,6 +874,144 @@ mod tests {
         assert!(store.is_empty());
     }
 
+    #[test]
+    fn test_remove_session_scoped() {
+        let mut store = StateStore::new();
+        let session = Some(Ttl::Session);
+        store
+            .set("/tmp/s1", Value::Int(1), "s1", None, false, false, session)
+            .unwrap();
+        store
+            .set("/tmp/s2", Value::Int(2), "s2", None, false, false, session)
+            .unwrap();
+        store
+            .set("/kept", Value::Int(3), "s1", None, false, false, None)
+            .unwrap();
+
+        // Session-scoped params survive TTL cleanup
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(store.cleanup_stale(Duration::from_millis(5)), 1);
+
+        let removed = store.remove_session_scoped("s1");
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].0, "/tmp/s1");
+        assert!(store.get("/tmp/s2").is_some());
+    }
+
+    #[test]
+    fn test_transfer_session_scoped() {
+        let mut store = StateStore::new();
+        let session = Some(Ttl::Session);
+        store
+            .set(
+                "/cursor",
+                Value::Int(1),
+                "phone",
+                None,
+                false,
+                false,
+                session,
+            )
+            .unwrap();
+        store
+            .set("/kept", Value::Int(2), "phone", None, false, false, None)
+            .unwrap();
+
+        assert_eq!(store.transfer_session_scoped("phone", "tablet"), 1);
+        assert!(store.remove_session_scoped("phone").is_empty());
+        assert_eq!(store.get("/kept").unwrap().writer, "phone");
+        assert_eq!(store.remove_session_scoped("tablet").len(), 1);
+    }
+
+    #[test]
+    fn test_set_all_rolls_back_on_rejection() {
+        let mut store = StateStore::new();
+        store
+            .set("/a", Value::Int(1), "s1", None, false, false, None)
+            .unwrap();
+        store
+            .set("/locked", Value::Int(1), "s2", None, true, false, None)
+            .unwrap();
+
+        let updates = vec![
+            ("/a".to_string(), Value::Int(2), None),
+            ("/b".to_string(), Value::Int(2), None),
+            ("/locked".to_string(), Value::Int(2), None),
+        ];
+        let (index, error) = store.set_all(&updates, "s1", false).unwrap_err();
+        assert_eq!(index, 2);
+        assert!(matches!(error, UpdateError::LockHeld { .. }));
+        assert_eq!(store.get_value("/a"), Some(&Value::Int(1)));
+        assert_eq!(store.get("/a").unwrap().revision, 1);
+        assert!(store.get("/b").is_none());
+
+        // Dry runs report revisions without keeping them
+        assert_eq!(
+            store.set_all(&updates[..2], "s1", true).unwrap(),
+            vec![2, 1]
+        );
+        assert!(store.get("/b").is_none());
+        assert_eq!(
+            store.set_all(&updates[..2], "s1", false).unwrap(),
+            vec![2, 1]
+        );
+        assert_eq!(store.get_value("/b"), Some(&Value::Int(2)));
+    }
+
+    #[test]
+    fn test_apply_all_checks_each_revision() {
+        let mut store = StateStore::new();
+        store
+            .set("/a", Value::Int(1), "s1", None, false, false, None)
+            .unwrap();
+        let set = |address: &str, revision: Option<u64>, lock: bool| SetMessage {
+            address: address.to_string(),
+            value: Value::Int(2),
+            revision,
+            lock,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        };
+
+        // A stale revision on the last SET rolls back the lock taken before it
+        let sets = vec![
+            set("/a", Some(1), true),
+            set("/b", None, false),
+            set("/a", Some(1), false),
+        ];
+        let (index, error) = store.apply_all(&sets, "s1").unwrap_err();
+        assert_eq!(index, 2);
+        assert!(matches!(error, UpdateError::RevisionConflict { .. }));
+        assert_eq!(store.get("/a").unwrap().lock_holder, None);
+        assert!(store.get("/b").is_none());
+
+        assert_eq!(store.apply_all(&sets[..2], "s1").unwrap(), vec![2, 1]);
+        assert_eq!(store.get("/a").unwrap().lock_holder, Some("s1".to_string()));
+    }
+
+    #[test]
+    fn test_set_all_does_not_evict() {
+        let mut store = StateStore::with_config(StateStoreConfig {
+            max_params: Some(2),
+            param_ttl: None,
+            eviction: EvictionStrategy::Lru,
+        });
+        store
+            .set("/a", Value::Int(1), "s1", None, false, false, None)
+            .unwrap();
+        let updates = vec![
+            ("/b".to_string(), Value::Int(1), None),
+            ("/c".to_string(), Value::Int(1), None),
+        ];
+        assert!(matches!(
+            store.set_all(&updates, "s1", false),
+            Err((1, UpdateError::AtCapacity))
+        ));
+        assert_eq!(store.len(), 1);
+    }
+
     #[test]
     fn test_last_accessed_tracking() {
         let mut state = ParamState::new(Value::Float(0.5), "session1".to_string());
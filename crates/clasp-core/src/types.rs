@@ -11,13 +11,18 @@ pub enum MessageType {
     Welcome = 0x02,
     Announce = 0x03,
     FederationSync = 0x04,
+    Proof = 0x05,
     Subscribe = 0x10,
     Unsubscribe = 0x11,
+    Alias = 0x12,
+    SubscribeAck = 0x13,
+    UnsubscribeAck = 0x14,
     Publish = 0x20,
     Set = 0x21,
     Get = 0x22,
     Snapshot = 0x23,
     Replay = 0x24,
+    SetMany = 0x25,
     Bundle = 0x30,
     Sync = 0x40,
     Ping = 0x41,
@@ -35,13 +40,18 @@ impl MessageType {
             0x02 => Some(MessageType::Welcome),
             0x03 => Some(MessageType::Announce),
             0x04 => Some(MessageType::FederationSync),
+            0x05 => Some(MessageType::Proof),
             0x10 => Some(MessageType::Subscribe),
             0x11 => Some(MessageType::Unsubscribe),
+            0x12 => Some(MessageType::Alias),
+            0x13 => Some(MessageType::SubscribeAck),
+            0x14 => Some(MessageType::UnsubscribeAck),
             0x20 => Some(MessageType::Publish),
             0x21 => Some(MessageType::Set),
             0x22 => Some(MessageType::Get),
             0x23 => Some(MessageType::Snapshot),
             0x24 => Some(MessageType::Replay),
+            0x25 => Some(MessageType::SetMany),
             0x30 => Some(MessageType::Bundle),
             0x40 => Some(MessageType::Sync),
             0x41 => Some(MessageType::Ping),
@@ -261,8 +271,27 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Whether this is an end-to-end encrypted envelope (`clasp-crypto`):
+    /// a map with `_e2e: 1` and string `ct` and `iv` fields, or the same
+    /// object serialized as a JSON string
+    pub fn is_e2e_envelope(&self) -> bool {
+        match self {
+            Value::Map(map) => {
+                map.get("_e2e").and_then(Value::as_i64) == Some(1)
+                    && matches!(map.get("ct"), Some(Value::String(_)))
+                    && matches!(map.get("iv"), Some(Value::String(_)))
+            }
+            Value::String(s) => s.starts_with("{\"_e2e\":1,"),
+            _ => false,
+        }
+    }
 }
 
+/// WELCOME feature of routers that store and forward E2E envelopes
+/// untouched (see [`Value::is_e2e_envelope`])
+pub const E2E_FEATURE: &str = "e2e";
+
 impl From<f64> for Value {
     fn from(v: f64) -> Self {
         Value::Float(v)
@@ -315,6 +344,9 @@ pub enum Message {
     #[serde(rename = "WELCOME")]
     Welcome(WelcomeMessage),
 
+    #[serde(rename = "PROOF")]
+    Proof(ProofMessage),
+
     #[serde(rename = "ANNOUNCE")]
     Announce(AnnounceMessage),
 
@@ -324,12 +356,24 @@ pub enum Message {
     #[serde(rename = "UNSUBSCRIBE")]
     Unsubscribe(UnsubscribeMessage),
 
+    #[serde(rename = "ALIAS")]
+    Alias(AliasMessage),
+
+    #[serde(rename = "SUBSCRIBE_ACK")]
+    SubscribeAck(SubscribeAckMessage),
+
+    #[serde(rename = "UNSUBSCRIBE_ACK")]
+    UnsubscribeAck(UnsubscribeAckMessage),
+
     #[serde(rename = "PUBLISH")]
     Publish(PublishMessage),
 
     #[serde(rename = "SET")]
     Set(SetMessage),
 
+    #[serde(rename = "SET_MANY")]
+    SetMany(SetManyMessage),
+
     #[serde(rename = "GET")]
     Get(GetMessage),
 
@@ -378,6 +422,10 @@ pub struct HelloMessage {
     pub capabilities: Option<Capabilities>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// Resume token from an earlier WELCOME to restore that session's
+    /// subscriptions, or empty to start a durable session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_resume: Option<String>,
 }
 
 /// WELCOME message - connection accepted
@@ -391,6 +439,24 @@ pub struct WelcomeMessage {
     pub time: u64,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// Nonce the client must sign with the key its token is bound to
+    /// (answered with PROOF before the session starts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    /// Token to send as `session_resume` when reconnecting, issued when the
+    /// HELLO asked for a durable session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
+    /// Whether the previous session's subscriptions were restored
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+}
+
+/// PROOF message - answer to a WELCOME challenge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofMessage {
+    /// Signature over [`possession_payload`](crate::security::possession_payload)
+    pub signature: Vec<u8>,
 }
 
 /// Client/server capabilities
@@ -437,6 +503,9 @@ pub struct SignalMeta {
     pub default: Option<Value>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Allowed values, for params that take one of a fixed set
+    #[serde(rename = "enum", default, skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<Value>>,
 }
 
 /// SUBSCRIBE message
@@ -451,7 +520,7 @@ pub struct SubscribeMessage {
 }
 
 /// Subscription options
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct SubscribeOptions {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_rate: Option<u32>,
@@ -461,6 +530,65 @@ pub struct SubscribeOptions {
     pub history: Option<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub window: Option<u32>,
+    /// Aggregate matching changes into one BUNDLE per tick (milliseconds),
+    /// keeping only the last write per address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick_ms: Option<u32>,
+    /// Deliver numeric values converted to this unit (see [`crate::units`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub convert_to: Option<String>,
+    /// What the router does with stream frames the client can't keep up
+    /// with (unset: dropped like any other broadcast)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<StreamPolicy>,
+    /// Watch a state branch (its params over live state) instead of live
+    /// state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Arithmetic applied to numeric values before delivery, after any
+    /// `convert_to` (see [`crate::units::transform_value`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<ValueTransform>,
+}
+
+/// Range mapping, scaling, clamping and rounding for delivered values.
+/// The steps run in field order; unset steps are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValueTransform {
+    /// Map linearly from one range onto another
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<RangeMap>,
+    /// Multiply by this factor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    /// Add this offset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
+    /// Limit to `(min, max)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clamp: Option<(f64, f64)>,
+    /// Round to the nearest integer and deliver an Int
+    #[serde(default)]
+    pub round: bool,
+}
+
+/// Linear map between two ranges, e.g. `0..1` onto `0..255` for DMX
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RangeMap {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+}
+
+/// Backpressure policy for the stream frames of one subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamPolicy {
+    /// Keep only the newest pending frame per address
+    LatestOnly,
+    /// Keep the newest N pending frames, dropping the oldest
+    Ring(u32),
+    /// Queue every frame and deliver in order
+    Reliable,
 }
 
 /// UNSUBSCRIBE message
@@ -469,6 +597,37 @@ pub struct UnsubscribeMessage {
     pub id: u32,
 }
 
+/// SUBSCRIBE_ACK message - the subscription is live; its snapshot follows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribeAckMessage {
+    pub id: u32,
+    /// Params in the snapshot that follows
+    pub matched: u32,
+    /// Options in effect, after the router clamped them
+    #[serde(default)]
+    pub options: SubscribeOptions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<u32>,
+}
+
+/// UNSUBSCRIBE_ACK message - the subscription is gone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsubscribeAckMessage {
+    pub id: u32,
+    /// False if the session had no subscription with this ID
+    pub removed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<u32>,
+}
+
+/// ALIAS message - bind a small integer to an address for later frames
+/// from the same sender. An empty address releases the alias.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AliasMessage {
+    pub alias: u16,
+    pub address: String,
+}
+
 /// PUBLISH message - for events, streams, gestures, timelines
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublishMessage {
@@ -505,6 +664,8 @@ pub enum Ttl {
     Absolute(u32),
     /// Never expires (overrides server TTL)
     Never,
+    /// Deleted when the writing session ends (scratch state)
+    Session,
 }
 
 /// SET message - set param value
@@ -520,18 +681,152 @@ pub struct SetMessage {
     pub unlock: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ttl: Option<Ttl>,
+    /// Ask the router to report what it did with this SET (admin scope
+    /// required); the report is published on `/clasp/trace/{session_id}`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trace: bool,
+    /// Write to this state branch instead of live state; the value stays
+    /// on the branch until the branch is merged or discarded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+/// SET_MANY message - write many params under one base address as one
+/// transaction, e.g. a frame of DMX channels or LED pixels
+///
+/// The value at index `i` is written to `{base}/{i}`. Subscribers receive
+/// one SET_MANY with the entries they subscribe to instead of a SET per
+/// address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetManyMessage {
+    pub base: String,
+    pub values: SetManyValues,
+}
+
+/// Values of a [`SetManyMessage`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SetManyValues {
+    /// `values[k]` is written to index `start + k`
+    Run { start: u32, values: Vec<Value> },
+    /// Sparse `(index, value)` pairs
+    Indexed(Vec<(u32, Value)>),
+}
+
+impl SetManyMessage {
+    /// Write `values` to consecutive indices from `start`
+    pub fn run(base: impl Into<String>, start: u32, values: Vec<Value>) -> Self {
+        Self {
+            base: base.into(),
+            values: SetManyValues::Run { start, values },
+        }
+    }
+
+    /// Write sparse `(index, value)` pairs
+    pub fn indexed(base: impl Into<String>, entries: Vec<(u32, Value)>) -> Self {
+        Self {
+            base: base.into(),
+            values: SetManyValues::Indexed(entries),
+        }
+    }
+
+    /// A run if the indices are consecutive, otherwise indexed pairs
+    pub fn from_entries(base: impl Into<String>, entries: Vec<(u32, Value)>) -> Self {
+        let consecutive = entries
+            .windows(2)
+            .all(|pair| pair[0].0.checked_add(1) == Some(pair[1].0));
+        match entries.first() {
+            Some(&(start, _)) if consecutive => Self::run(
+                base,
+                start,
+                entries.into_iter().map(|(_, value)| value).collect(),
+            ),
+            _ => Self::indexed(base, entries),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.values {
+            SetManyValues::Run { values, .. } => values.len(),
+            SetManyValues::Indexed(entries) => entries.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether every index fits in a u32 (a run can't extend past `u32::MAX`)
+    pub fn in_range(&self) -> bool {
+        match &self.values {
+            SetManyValues::Run { start, values } => {
+                values.is_empty() || start.checked_add(values.len() as u32 - 1).is_some()
+            }
+            SetManyValues::Indexed(_) => true,
+        }
+    }
+
+    /// The address written by `index`
+    pub fn address(&self, index: u32) -> String {
+        format!("{}/{}", self.base.trim_end_matches('/'), index)
+    }
+
+    /// `(index, value)` for every entry, in message order
+    pub fn entries(&self) -> Box<dyn Iterator<Item = (u32, &Value)> + '_> {
+        match &self.values {
+            SetManyValues::Run { start, values } => Box::new(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(move |(k, value)| (start.wrapping_add(k as u32), value)),
+            ),
+            SetManyValues::Indexed(entries) => {
+                Box::new(entries.iter().map(|(index, value)| (*index, value)))
+            }
+        }
+    }
+
+    /// The equivalent individual SETs, in message order
+    pub fn to_sets(&self) -> Vec<SetMessage> {
+        self.entries()
+            .map(|(index, value)| SetMessage {
+                address: self.address(index),
+                value: value.clone(),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            })
+            .collect()
+    }
 }
 
 /// GET message - request current value
+///
+/// `address` may be a pattern (`/lights/**`), in which case the answer is a
+/// SNAPSHOT of every matching param, possibly empty.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetMessage {
     pub address: String,
+    /// For patterns: how many segments below the part before the first
+    /// wildcard a match may be (`/lights/**` with depth 1 returns
+    /// `/lights/a` but not `/lights/a/level`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u8>,
 }
 
 /// SNAPSHOT message - current state
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SnapshotMessage {
     pub params: Vec<ParamValue>,
+    /// Set when the snapshot answers a correlated GET
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<u32>,
+    /// More chunks of the same correlated answer follow
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub more: bool,
 }
 
 /// Parameter value in snapshot
@@ -557,12 +852,18 @@ pub struct ReplayMessage {
     /// End time (microseconds since epoch)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub to: Option<u64>,
-    /// Maximum number of entries to return
+    /// Maximum number of entries to return in this page
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub limit: Option<u32>,
     /// Filter by signal types
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub types: Vec<SignalType>,
+    /// Play entries back spaced as they were recorded instead of all at once
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed: bool,
+    /// Continue after the page that ended with an ACK carrying this cursor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
 }
 
 /// Federation sync operation types
@@ -577,6 +878,14 @@ pub enum FederationOp {
     RevisionVector = 0x03,
     /// Confirm sync completion
     SyncComplete = 0x04,
+    /// Request journal entries for a pattern after a sequence number
+    RequestJournalSync = 0x05,
+    /// Journal entries up to a sequence number have been sent
+    JournalCheckpoint = 0x06,
+    /// Request the peer's entity registry as signed events
+    RequestRegistrySync = 0x07,
+    /// One signed entity registry event (in the payload)
+    RegistryEvent = 0x08,
 }
 
 /// FEDERATION_SYNC message - router-to-router federation
@@ -590,12 +899,75 @@ pub struct FederationSyncMessage {
     /// Revision map (for RevisionVector)
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub revisions: HashMap<String, u64>,
-    /// Since revision (for RequestSync)
+    /// Since revision (for RequestSync), or journal sequence number (for
+    /// RequestJournalSync, JournalCheckpoint and a journal SyncComplete)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub since_revision: Option<u64>,
     /// Origin router ID (for loop prevention)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub origin: Option<String>,
+    /// Opaque operation data (the signed event, for RegistryEvent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Vec<u8>>,
+}
+
+/// Origin of a gossiped namespace declaration in a federation mesh.
+///
+/// Encoded in [`FederationSyncMessage::origin`] as `router;hops=N;ttl=M`,
+/// where `router` owns the namespaces, `hops` is the distance from the owner
+/// to the sender and `ttl` is how many more times the declaration may be
+/// relayed. A plain router ID (no hop metadata) is a direct declaration and
+/// is never relayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GossipOrigin {
+    /// Router that owns the declared namespaces
+    pub router_id: String,
+    /// Links between the owner and the sender (0 when sent by the owner)
+    pub hops: u8,
+    /// Remaining relays before the declaration is dropped
+    pub ttl: u8,
+}
+
+impl GossipOrigin {
+    /// Parse an origin field, returning `None` for a plain router ID.
+    pub fn parse(origin: &str) -> Option<Self> {
+        let mut parts = origin.split(';');
+        let router_id = parts.next()?;
+        let (mut hops, mut ttl) = (None, None);
+        for part in parts {
+            match part.split_once('=') {
+                Some(("hops", v)) => hops = v.parse().ok(),
+                Some(("ttl", v)) => ttl = v.parse().ok(),
+                _ => {}
+            }
+        }
+        if router_id.is_empty() {
+            return None;
+        }
+        Some(Self {
+            router_id: router_id.to_string(),
+            hops: hops?,
+            ttl: ttl?,
+        })
+    }
+
+    /// Encode for use as a [`FederationSyncMessage::origin`].
+    pub fn encode(&self) -> String {
+        format!("{};hops={};ttl={}", self.router_id, self.hops, self.ttl)
+    }
+
+    /// The origin to use when relaying this declaration one more link,
+    /// or `None` once the TTL is spent.
+    pub fn relayed(&self) -> Option<Self> {
+        if self.ttl == 0 {
+            return None;
+        }
+        Some(Self {
+            router_id: self.router_id.clone(),
+            hops: self.hops.saturating_add(1),
+            ttl: self.ttl - 1,
+        })
+    }
 }
 
 /// BUNDLE message - atomic group
@@ -629,6 +1001,21 @@ pub struct AckMessage {
     pub holder: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub correlation_id: Option<u32>,
+    /// Per-message outcome of an applied BUNDLE, in bundle order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<BundleResult>,
+    /// Continuation token ending a REPLAY page when more entries match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// Outcome of one SET or PUBLISH in an applied BUNDLE
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleResult {
+    pub address: String,
+    /// New revision of a SET (None for PUBLISH)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
 }
 
 /// ERROR message
@@ -642,16 +1029,102 @@ pub struct ErrorMessage {
     pub correlation_id: Option<u32>,
 }
 
+impl ErrorMessage {
+    pub fn new(code: crate::error::ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code: code.as_u16(),
+            message: message.into(),
+            address: None,
+            correlation_id: None,
+        }
+    }
+
+    /// Set the address the error refers to
+    pub fn with_address(mut self, address: impl Into<String>) -> Self {
+        self.address = Some(address.into());
+        self
+    }
+
+    /// The code as an [`ErrorCode`](crate::ErrorCode), if it is a known one
+    pub fn error_code(&self) -> Option<crate::error::ErrorCode> {
+        crate::error::ErrorCode::from_u16(self.code)
+    }
+
+    /// The code's range, for codes this version doesn't know
+    pub fn category(&self) -> Option<crate::error::ErrorCategory> {
+        crate::error::ErrorCategory::of(self.code)
+    }
+
+    /// ERROR 504 telling a client to back off and reconnect after `delay`
+    ///
+    /// The delay travels in the message text ("retry after 1500ms") so older
+    /// clients still see a readable reason.
+    pub fn retry_after(delay: std::time::Duration) -> Self {
+        Self::new(
+            crate::error::ErrorCode::RetryAfter,
+            format!("Server busy, retry after {}ms", delay.as_millis()),
+        )
+    }
+
+    /// The backoff hint carried by an ERROR 504, if this is one
+    pub fn retry_after_hint(&self) -> Option<std::time::Duration> {
+        if self.error_code() != Some(crate::error::ErrorCode::RetryAfter) {
+            return None;
+        }
+        let (_, rest) = self.message.rsplit_once("retry after ")?;
+        let ms = rest.strip_suffix("ms")?.parse().ok()?;
+        Some(std::time::Duration::from_millis(ms))
+    }
+}
+
 /// QUERY message - introspection
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Without a `limit` the router answers with every matching signal it can
+/// fit in one RESULT. With one, signals come back in address order, a page
+/// at a time; pass the previous RESULT's `next_cursor` to get the next page.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QueryMessage {
     pub pattern: String,
+    /// Most signals to return
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Continue after the page that returned this cursor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Optional fields to include (none = datatype and access)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<SignalField>>,
+}
+
+impl QueryMessage {
+    /// Whether the RESULT should include `field`
+    pub fn wants(&self, field: SignalField) -> bool {
+        match &self.fields {
+            Some(fields) => fields.contains(&field),
+            None => field != SignalField::Meta,
+        }
+    }
+}
+
+/// Optional [`SignalDefinition`] fields a QUERY can select
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalField {
+    Datatype,
+    Access,
+    Meta,
 }
 
 /// RESULT message - query response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResultMessage {
     pub signals: Vec<SignalDefinition>,
+    /// Cursor for the next page (none = this was the last page)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Number of signals matching the pattern across all pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u32>,
 }
 
 impl Message {
@@ -660,11 +1133,16 @@ impl Message {
         match self {
             Message::Hello(_) => MessageType::Hello,
             Message::Welcome(_) => MessageType::Welcome,
+            Message::Proof(_) => MessageType::Proof,
             Message::Announce(_) => MessageType::Announce,
             Message::Subscribe(_) => MessageType::Subscribe,
             Message::Unsubscribe(_) => MessageType::Unsubscribe,
+            Message::Alias(_) => MessageType::Alias,
+            Message::SubscribeAck(_) => MessageType::SubscribeAck,
+            Message::UnsubscribeAck(_) => MessageType::UnsubscribeAck,
             Message::Publish(_) => MessageType::Publish,
             Message::Set(_) => MessageType::Set,
+            Message::SetMany(_) => MessageType::SetMany,
             Message::Get(_) => MessageType::Get,
             Message::Snapshot(_) => MessageType::Snapshot,
             Message::Replay(_) => MessageType::Replay,
@@ -683,7 +1161,7 @@ impl Message {
     /// Get the default QoS for this message type
     pub fn default_qos(&self) -> QoS {
         match self {
-            Message::Set(_) => QoS::Confirm,
+            Message::Set(_) | Message::SetMany(_) => QoS::Confirm,
             Message::Publish(p) => p.signal.map(|s| s.default_qos()).unwrap_or(QoS::Fire),
             Message::Bundle(_) => QoS::Commit,
             Message::Replay(_) => QoS::Confirm,
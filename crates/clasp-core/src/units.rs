@@ -0,0 +1,365 @@
+//! Built-in unit table for converting numeric values between units
+//!
+//! Signals announce their unit in [`SignalMeta::unit`](crate::SignalMeta),
+//! and subscribers can ask for values in a different unit with
+//! [`SubscribeOptions::convert_to`](crate::SubscribeOptions). Conversions are
+//! only allowed within one dimension (temperature, length, ratio).
+//!
+//! [`transform_value`] applies a subscription's
+//! [`ValueTransform`](crate::ValueTransform) on top, for consumers that
+//! need values in a device range rather than a physical unit.
+//!
+//! ```
+//! use clasp_core::units;
+//!
+//! assert_eq!(units::convert(100.0, "C", "F").unwrap(), 212.0);
+//! assert!(units::convert(1.0, "m", "F").is_err());
+//! ```
+
+use crate::{Value, ValueTransform};
+use thiserror::Error;
+
+/// What a unit measures; only units of the same dimension convert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Temperature,
+    Length,
+    Ratio,
+}
+
+/// A unit as an affine map to its dimension's base unit:
+/// `base = value * scale + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    /// Canonical symbol
+    pub symbol: &'static str,
+    pub dimension: Dimension,
+    scale: f64,
+    offset: f64,
+}
+
+impl Unit {
+    const fn new(symbol: &'static str, dimension: Dimension, scale: f64, offset: f64) -> Self {
+        Self {
+            symbol,
+            dimension,
+            scale,
+            offset,
+        }
+    }
+}
+
+/// Unit conversion errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum UnitError {
+    /// The unit is not in the built-in table
+    #[error("unknown unit: {0}")]
+    Unknown(String),
+
+    /// The units measure different things
+    #[error("cannot convert {from} to {to}")]
+    Incompatible { from: String, to: String },
+
+    /// The value is not numeric
+    #[error("value is not numeric")]
+    NotNumeric,
+
+    /// The transform can't be applied
+    #[error("invalid transform: {0}")]
+    InvalidTransform(&'static str),
+}
+
+// Base units: kelvin, metre, fraction (0..1)
+const UNITS: &[(&[&str], Unit)] = &[
+    (
+        &["C", "°C", "degC", "celsius"],
+        Unit::new("°C", Dimension::Temperature, 1.0, 273.15),
+    ),
+    (
+        &["F", "°F", "degF", "fahrenheit"],
+        Unit::new("°F", Dimension::Temperature, 5.0 / 9.0, 459.67 * 5.0 / 9.0),
+    ),
+    (
+        &["K", "kelvin"],
+        Unit::new("K", Dimension::Temperature, 1.0, 0.0),
+    ),
+    (
+        &["m", "meter", "metre"],
+        Unit::new("m", Dimension::Length, 1.0, 0.0),
+    ),
+    (&["km"], Unit::new("km", Dimension::Length, 1000.0, 0.0)),
+    (&["cm"], Unit::new("cm", Dimension::Length, 0.01, 0.0)),
+    (&["mm"], Unit::new("mm", Dimension::Length, 0.001, 0.0)),
+    (
+        &["in", "inch"],
+        Unit::new("in", Dimension::Length, 0.0254, 0.0),
+    ),
+    (
+        &["ft", "foot", "feet"],
+        Unit::new("ft", Dimension::Length, 0.3048, 0.0),
+    ),
+    (
+        &["yd", "yard"],
+        Unit::new("yd", Dimension::Length, 0.9144, 0.0),
+    ),
+    (
+        &["mi", "mile"],
+        Unit::new("mi", Dimension::Length, 1609.344, 0.0),
+    ),
+    (
+        &["%", "percent"],
+        Unit::new("%", Dimension::Ratio, 0.01, 0.0),
+    ),
+    (
+        &["ratio", "fraction"],
+        Unit::new("ratio", Dimension::Ratio, 1.0, 0.0),
+    ),
+];
+
+/// Look up a unit by symbol or name (case-insensitive for names)
+pub fn lookup(name: &str) -> Option<&'static Unit> {
+    let name = name.trim();
+    UNITS
+        .iter()
+        .find(|(aliases, _)| {
+            aliases
+                .iter()
+                .any(|a| *a == name || (a.len() > 2 && a.eq_ignore_ascii_case(name)))
+        })
+        .map(|(_, unit)| unit)
+}
+
+/// Check that `from` can be converted to `to`
+pub fn check(from: &str, to: &str) -> Result<(&'static Unit, &'static Unit), UnitError> {
+    let src = lookup(from).ok_or_else(|| UnitError::Unknown(from.to_string()))?;
+    let dst = lookup(to).ok_or_else(|| UnitError::Unknown(to.to_string()))?;
+    if src.dimension != dst.dimension {
+        return Err(UnitError::Incompatible {
+            from: src.symbol.to_string(),
+            to: dst.symbol.to_string(),
+        });
+    }
+    Ok((src, dst))
+}
+
+/// Convert a number between units
+pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, UnitError> {
+    let (src, dst) = check(from, to)?;
+    if src == dst {
+        return Ok(value);
+    }
+    let base = value * src.scale + src.offset;
+    Ok(round_significant((base - dst.offset) / dst.scale))
+}
+
+/// Drop float noise from the affine maps so 100 °C is 212 °F, not
+/// 211.99999999999997
+fn round_significant(value: f64) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let magnitude = 10f64.powi(12 - value.abs().log10().ceil() as i32);
+    (value * magnitude).round() / magnitude
+}
+
+/// Convert a numeric value (or array of numbers) between units.
+///
+/// Ints become floats since most conversions are not integral.
+pub fn convert_value(value: &Value, from: &str, to: &str) -> Result<Value, UnitError> {
+    match value {
+        Value::Float(f) => Ok(Value::Float(convert(*f, from, to)?)),
+        Value::Int(i) => Ok(Value::Float(convert(*i as f64, from, to)?)),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| convert_value(item, from, to))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        _ => Err(UnitError::NotNumeric),
+    }
+}
+
+/// Check that a transform's numbers are finite, its `map` source range is
+/// not empty and its `clamp` minimum is not above the maximum
+pub fn check_transform(transform: &ValueTransform) -> Result<(), UnitError> {
+    let mut numbers = Vec::new();
+    if let Some(map) = transform.map {
+        if map.from.0 == map.from.1 {
+            return Err(UnitError::InvalidTransform("map source range is empty"));
+        }
+        numbers.extend([map.from.0, map.from.1, map.to.0, map.to.1]);
+    }
+    numbers.extend(transform.scale);
+    numbers.extend(transform.offset);
+    if let Some((min, max)) = transform.clamp {
+        if min > max {
+            return Err(UnitError::InvalidTransform("clamp min is above max"));
+        }
+        numbers.extend([min, max]);
+    }
+    if numbers.iter().any(|n| !n.is_finite()) {
+        return Err(UnitError::InvalidTransform("numbers must be finite"));
+    }
+    Ok(())
+}
+
+/// Apply a transform to a number: map, scale, offset, then clamp
+pub fn transform(value: f64, transform: &ValueTransform) -> f64 {
+    let mut value = value;
+    if let Some(map) = transform.map {
+        let (from, to) = (map.from, map.to);
+        value = to.0 + (value - from.0) * (to.1 - to.0) / (from.1 - from.0);
+    }
+    if let Some(scale) = transform.scale {
+        value *= scale;
+    }
+    if let Some(offset) = transform.offset {
+        value += offset;
+    }
+    if let Some((min, max)) = transform.clamp {
+        value = value.clamp(min, max);
+    }
+    value
+}
+
+/// Apply a transform to a numeric value (or array of numbers).
+///
+/// Results are floats, or ints when the transform rounds.
+pub fn transform_value(value: &Value, spec: &ValueTransform) -> Result<Value, UnitError> {
+    let number = |n: f64| {
+        let n = transform(n, spec);
+        if spec.round {
+            Value::Int(n.round() as i64)
+        } else {
+            Value::Float(round_significant(n))
+        }
+    };
+    match value {
+        Value::Float(f) => Ok(number(*f)),
+        Value::Int(i) => Ok(number(*i as f64)),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| transform_value(item, spec))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        _ => Err(UnitError::NotNumeric),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RangeMap;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_temperature() {
+        assert!(close(convert(0.0, "C", "F").unwrap(), 32.0));
+        assert!(close(convert(98.6, "°F", "celsius").unwrap(), 37.0));
+        assert!(close(convert(0.0, "K", "C").unwrap(), -273.15));
+        assert!(close(convert(-40.0, "F", "C").unwrap(), -40.0));
+    }
+
+    #[test]
+    fn test_length_and_ratio() {
+        assert!(close(convert(1.0, "ft", "cm").unwrap(), 30.48));
+        assert!(close(convert(1.0, "mi", "km").unwrap(), 1.609344));
+        assert!(close(convert(0.42, "ratio", "%").unwrap(), 42.0));
+    }
+
+    #[test]
+    fn test_rejects_incompatible_and_unknown() {
+        assert_eq!(
+            convert(1.0, "m", "C"),
+            Err(UnitError::Incompatible {
+                from: "m".into(),
+                to: "°C".into()
+            })
+        );
+        assert_eq!(
+            convert(1.0, "furlong", "m"),
+            Err(UnitError::Unknown("furlong".into()))
+        );
+        assert_eq!(
+            convert_value(&Value::String("x".into()), "C", "F"),
+            Err(UnitError::NotNumeric)
+        );
+    }
+
+    #[test]
+    fn test_convert_value_types() {
+        assert_eq!(
+            convert_value(&Value::Int(100), "C", "F").unwrap(),
+            Value::Float(212.0)
+        );
+        let Value::Array(items) = convert_value(
+            &Value::Array(vec![Value::Int(1), Value::Float(2.0)]),
+            "m",
+            "cm",
+        )
+        .unwrap() else {
+            panic!("expected array");
+        };
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn test_transform_value() {
+        let dmx = ValueTransform {
+            map: Some(RangeMap {
+                from: (0.0, 1.0),
+                to: (0.0, 255.0),
+            }),
+            clamp: Some((0.0, 255.0)),
+            round: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            transform_value(&Value::Float(0.5), &dmx),
+            Ok(Value::Int(128))
+        );
+        assert_eq!(
+            transform_value(&Value::Float(1.2), &dmx),
+            Ok(Value::Int(255))
+        );
+
+        let percent = ValueTransform {
+            scale: Some(100.0),
+            offset: Some(-50.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            transform_value(&Value::Int(1), &percent),
+            Ok(Value::Float(50.0))
+        );
+        assert_eq!(
+            transform_value(&Value::Bool(true), &percent),
+            Err(UnitError::NotNumeric)
+        );
+    }
+
+    #[test]
+    fn test_check_transform() {
+        assert!(check_transform(&ValueTransform::default()).is_ok());
+        let empty_range = ValueTransform {
+            map: Some(RangeMap {
+                from: (1.0, 1.0),
+                to: (0.0, 255.0),
+            }),
+            ..Default::default()
+        };
+        assert!(check_transform(&empty_range).is_err());
+        let inverted = ValueTransform {
+            clamp: Some((10.0, 0.0)),
+            ..Default::default()
+        };
+        assert!(check_transform(&inverted).is_err());
+        let infinite = ValueTransform {
+            scale: Some(f64::INFINITY),
+            ..Default::default()
+        };
+        assert!(check_transform(&infinite).is_err());
+    }
+}
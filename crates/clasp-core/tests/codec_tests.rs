@@ -2,8 +2,8 @@
 //! Tests both binary encoding (default) and backward compatibility with MessagePack
 
 use clasp_core::{
-    codec, HelloMessage, Message, PublishMessage, SetMessage, SignalType, SubscribeMessage, Ttl,
-    Value, WelcomeMessage,
+    codec, ErrorMessage, HelloMessage, Message, PublishMessage, SetMessage, SignalType,
+    SubscribeMessage, Ttl, Value, WelcomeMessage,
 };
 
 #[test]
@@ -14,6 +14,7 @@ fn test_encode_decode_hello() {
         features: vec!["param".to_string(), "event".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -38,6 +39,9 @@ fn test_encode_decode_welcome() {
         features: vec!["param".to_string()],
         time: 1234567890,
         token: None,
+        challenge: None,
+        resume_token: None,
+        resumed: false,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -61,6 +65,8 @@ fn test_encode_decode_set() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -126,6 +132,35 @@ fn test_encode_decode_subscribe() {
     }
 }
 
+#[test]
+fn test_encode_decode_retry_after() {
+    let msg = Message::Error(ErrorMessage::retry_after(std::time::Duration::from_millis(
+        2750,
+    )));
+
+    let encoded = codec::encode(&msg).expect("encode failed");
+    let (decoded, _frame) = codec::decode(&encoded).expect("decode failed");
+
+    match decoded {
+        Message::Error(err) => {
+            assert_eq!(err.code, 504);
+            assert_eq!(
+                err.retry_after_hint(),
+                Some(std::time::Duration::from_millis(2750))
+            );
+        }
+        _ => panic!("Expected Error message"),
+    }
+
+    let other = ErrorMessage {
+        code: 503,
+        message: "retry after 5ms".to_string(),
+        address: None,
+        correlation_id: None,
+    };
+    assert_eq!(other.retry_after_hint(), None);
+}
+
 #[test]
 fn test_value_types() {
     // Test all value types roundtrip
@@ -158,6 +193,8 @@ fn test_value_types() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&msg).expect("encode failed");
@@ -190,6 +227,8 @@ fn test_v3_set_message_size() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -213,6 +252,8 @@ fn test_v3_set_message_with_revision() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -235,6 +276,8 @@ fn test_v3_set_message_with_lock() {
         lock: true,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -258,6 +301,8 @@ fn test_v3_set_message_string_value() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -282,6 +327,8 @@ fn test_v3_encoding_starts_with_message_type() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&set_msg).expect("encode failed");
@@ -296,6 +343,7 @@ fn test_v3_encoding_starts_with_message_type() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
 
     let encoded = codec::encode(&hello_msg).expect("encode failed");
@@ -315,6 +363,8 @@ fn test_v3_benchmark_set_encoding() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let iterations = 100_000;
@@ -752,6 +802,8 @@ fn test_set_with_sliding_ttl() {
         lock: false,
         unlock: false,
         ttl: Some(Ttl::Sliding(60)),
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -775,6 +827,8 @@ fn test_set_with_absolute_ttl() {
         lock: false,
         unlock: false,
         ttl: Some(Ttl::Absolute(300)),
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -798,6 +852,8 @@ fn test_set_with_never_ttl() {
         lock: false,
         unlock: false,
         ttl: Some(Ttl::Never),
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -820,6 +876,8 @@ fn test_set_without_ttl_backward_compat() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
@@ -843,6 +901,8 @@ fn test_set_with_ttl_and_lock() {
         lock: true,
         unlock: false,
         ttl: Some(Ttl::Sliding(3600)),
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).expect("encode failed");
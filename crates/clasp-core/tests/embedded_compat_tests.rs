@@ -47,12 +47,15 @@ fn test_core_set_to_embedded_decode() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     };
 
     // Encode using core
     let frame = Frame {
         flags: FrameFlags::default(),
         timestamp: None,
+        correlation_id: None,
         payload: codec::encode_message(&Message::Set(set_msg)).unwrap(),
     };
     let encoded = frame.encode().unwrap();
@@ -45,7 +45,7 @@ async fn recv_message(
                     Err(_) => continue, // Skip malformed responses
                 }
             }
-            Ok(Some(TransportEvent::Connected)) => continue,
+            Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
             Ok(Some(TransportEvent::Disconnected { .. })) => return None,
             Ok(Some(TransportEvent::Error(_))) => return None,
             Ok(None) => return None,
@@ -66,6 +66,7 @@ async fn complete_handshake(
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     if sender.send(codec::encode(&hello).unwrap()).await.is_err() {
         return false;
@@ -155,6 +156,7 @@ async fn test_truncated_message_returns_error_400() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     let bytes = codec::encode(&hello).expect("Failed to encode");
     // Truncate to just 3 bytes (incomplete frame)
@@ -201,6 +203,7 @@ async fn test_wrong_protocol_version_returns_error_505() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender
         .send(codec::encode(&hello).expect("Failed to encode"))
@@ -253,6 +256,8 @@ async fn test_message_before_hello_returns_error_401() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     sender
         .send(codec::encode(&set).expect("Failed to encode"))
@@ -301,6 +306,7 @@ async fn test_duplicate_hello() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender
         .send(codec::encode(&hello).expect("Failed to encode"))
@@ -329,6 +335,7 @@ async fn test_duplicate_hello() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender
         .send(codec::encode(&hello2).expect("Failed to encode"))
@@ -356,6 +363,7 @@ async fn test_very_long_address() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender
         .send(codec::encode(&hello).expect("Failed to encode"))
@@ -385,6 +393,8 @@ async fn test_very_long_address() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     sender
         .send(codec::encode(&set).expect("Failed to encode"))
@@ -420,6 +430,8 @@ async fn test_empty_address_returns_error_400() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     sender
         .send(codec::encode(&set).expect("Failed to encode"))
@@ -480,6 +492,8 @@ async fn test_invalid_address_returns_error_400() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         sender
             .send(codec::encode(&set).expect("Failed to encode"))
@@ -530,6 +544,7 @@ async fn test_rapid_disconnect_reconnect() {
             features: vec![],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender
             .send(codec::encode(&hello).expect("Failed to encode"))
@@ -577,6 +592,7 @@ async fn test_special_characters_in_address() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender
         .send(codec::encode(&hello).expect("Failed to encode"))
@@ -614,6 +630,8 @@ async fn test_special_characters_in_address() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         sender
             .send(codec::encode(&set).expect("Failed to encode"))
@@ -654,6 +672,8 @@ async fn test_unauthorized_write_to_locked_address_returns_error_403() {
         lock: true, // Acquire lock
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     owner_sender
         .send(codec::encode(&set_locked).expect("Failed to encode"))
@@ -685,6 +705,8 @@ async fn test_unauthorized_write_to_locked_address_returns_error_403() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     intruder_sender
         .send(codec::encode(&set_intruder).expect("Failed to encode"))
@@ -8,7 +8,10 @@
 //!   ECDH P-256, HKDF-SHA256, ECDSA P-256. No CLASP dependency.
 //! - **Protocol** (`protocol`): E2ESession state machine for key exchange
 //!   over CLASP paths.
-//! - **Storage** (`storage`): KeyStore trait with MemoryKeyStore.
+//! - **Storage** (`storage`): KeyStore trait with MemoryKeyStore, plus
+//!   FileSystemKeyStore (`fs-store`), the passphrase-encrypted
+//!   EncryptedFileKeyStore (`encrypted-store`) and the OS-keychain
+//!   KeychainKeyStore (`keychain`).
 //! - **Client** (`client`, behind `client` feature): CryptoClient wrapper
 //!   for transparent encrypt/decrypt over a `clasp_client::Clasp` instance.
 
@@ -29,8 +32,12 @@ pub use primitives::{
     jwk_to_group_key, jwk_to_public_key, public_key_to_jwk, sign, verify,
 };
 pub use protocol::{E2ESession, E2ESessionConfig};
+#[cfg(feature = "encrypted-store")]
+pub use storage::EncryptedFileKeyStore;
 #[cfg(feature = "fs-store")]
 pub use storage::FileSystemKeyStore;
+#[cfg(feature = "keychain")]
+pub use storage::KeychainKeyStore;
 pub use storage::{KeyStore, MemoryKeyStore};
 pub use types::{
     E2EEnvelope, ECDHKeyPair, KeyData, KeyExchangeMessage, SigningKeyPair, TofuRecord,
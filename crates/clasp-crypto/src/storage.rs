@@ -7,6 +7,16 @@ use std::sync::Mutex;
 use crate::error::{CryptoError, Result};
 use crate::types::{KeyData, TofuRecord};
 
+#[cfg(feature = "encrypted-store")]
+mod encrypted;
+#[cfg(feature = "keychain")]
+mod keychain;
+
+#[cfg(feature = "encrypted-store")]
+pub use encrypted::EncryptedFileKeyStore;
+#[cfg(feature = "keychain")]
+pub use keychain::KeychainKeyStore;
+
 /// Pluggable persistence interface for crypto keys and TOFU records.
 #[async_trait]
 pub trait KeyStore: Send + Sync {
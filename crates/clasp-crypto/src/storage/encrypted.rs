@@ -0,0 +1,322 @@
+//! Passphrase-encrypted filesystem key store.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use super::{FileSystemKeyStore, KeyStore};
+use crate::error::{CryptoError, Result};
+use crate::types::{KeyData, TofuRecord};
+
+const HEADER_FILE: &str = "keystore.json";
+const HEADER_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const IV_LEN: usize = 12;
+/// Encrypted into the header so a wrong passphrase fails at open
+const CHECK_PLAINTEXT: &[u8] = b"clasp-keystore";
+
+/// Salt and Argon2id cost of a store's key, written once at creation.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    version: u8,
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    check: String,
+}
+
+/// Filesystem key store that encrypts every record with AES-256-GCM under a
+/// key derived from a passphrase with Argon2id.
+///
+/// Layout:
+///   `<base_dir>/keystore.json` (salt, KDF cost, passphrase check)
+///   `<base_dir>/group-keys/<sha256(session_id)>.enc`
+///   `<base_dir>/tofu/<sha256(id)>.enc`
+///
+/// Each `.enc` file is the IV followed by the ciphertext, authenticated
+/// together with its path relative to `base_dir`, so records can't be
+/// swapped between slots.
+pub struct EncryptedFileKeyStore {
+    base_dir: PathBuf,
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl EncryptedFileKeyStore {
+    /// Open the store at `base_dir`, creating it if it doesn't exist.
+    ///
+    /// Fails with [`CryptoError::DecryptionFailed`] if the store was created
+    /// with a different passphrase.
+    pub async fn open(base_dir: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        let base_dir = base_dir.into();
+        let header_path = base_dir.join(HEADER_FILE);
+        let passphrase = Zeroizing::new(passphrase.as_bytes().to_vec());
+
+        let existing = match tokio::fs::read(&header_path).await {
+            Ok(bytes) => Some(
+                serde_json::from_slice::<Header>(&bytes)
+                    .map_err(|e| CryptoError::Storage(format!("parse keystore header: {e}")))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(CryptoError::Storage(format!("read keystore header: {e}"))),
+        };
+
+        match existing {
+            Some(header) => {
+                if header.version != HEADER_VERSION {
+                    return Err(CryptoError::Storage(format!(
+                        "unsupported keystore version {}",
+                        header.version
+                    )));
+                }
+                let salt = B64
+                    .decode(&header.salt)
+                    .map_err(|e| CryptoError::Storage(format!("keystore salt: {e}")))?;
+                let params = Params::new(
+                    header.memory_kib,
+                    header.iterations,
+                    header.parallelism,
+                    Some(32),
+                )
+                .map_err(|e| CryptoError::Storage(format!("keystore KDF params: {e}")))?;
+                let key = derive_key(passphrase, salt, params).await?;
+                let check = B64
+                    .decode(&header.check)
+                    .map_err(|e| CryptoError::Storage(format!("keystore check: {e}")))?;
+                let store = Self { base_dir, key };
+                if store.open_sealed(&check, HEADER_FILE).is_err() {
+                    return Err(CryptoError::DecryptionFailed(
+                        "wrong passphrase for key store".into(),
+                    ));
+                }
+                Ok(store)
+            }
+            None => {
+                let mut salt = vec![0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let params = Params::default();
+                let (memory_kib, iterations, parallelism) =
+                    (params.m_cost(), params.t_cost(), params.p_cost());
+                let key = derive_key(passphrase, salt.clone(), params).await?;
+                let store = Self { base_dir, key };
+                let header = Header {
+                    version: HEADER_VERSION,
+                    salt: B64.encode(&salt),
+                    memory_kib,
+                    iterations,
+                    parallelism,
+                    check: B64.encode(store.seal(CHECK_PLAINTEXT, HEADER_FILE)?),
+                };
+                let json = serde_json::to_vec_pretty(&header)
+                    .map_err(|e| CryptoError::Serialization(e.to_string()))?;
+                FileSystemKeyStore::atomic_write(&header_path, &json).await?;
+                Ok(store)
+            }
+        }
+    }
+
+    fn slot(kind: &str, id: &str) -> String {
+        format!("{kind}/{}.enc", FileSystemKeyStore::hash_id(id))
+    }
+
+    fn seal(&self, plaintext: &[u8], slot: &str) -> Result<Vec<u8>> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.key.as_slice()));
+        let mut iv = [0u8; IV_LEN];
+        OsRng.fill_bytes(&mut iv);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&iv),
+                Payload {
+                    msg: plaintext,
+                    aad: slot.as_bytes(),
+                },
+            )
+            .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+        let mut sealed = iv.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    fn open_sealed(&self, sealed: &[u8], slot: &str) -> Result<Zeroizing<Vec<u8>>> {
+        if sealed.len() < IV_LEN {
+            return Err(CryptoError::DecryptionFailed("record too short".into()));
+        }
+        let (iv, ciphertext) = sealed.split_at(IV_LEN);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.key.as_slice()));
+        cipher
+            .decrypt(
+                Nonce::from_slice(iv),
+                Payload {
+                    msg: ciphertext,
+                    aad: slot.as_bytes(),
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|e| CryptoError::DecryptionFailed(e.to_string()))
+    }
+
+    async fn save<T: Serialize>(&self, slot: &str, value: &T) -> Result<()> {
+        let json = Zeroizing::new(
+            serde_json::to_vec(value).map_err(|e| CryptoError::Serialization(e.to_string()))?,
+        );
+        let sealed = self.seal(&json, slot)?;
+        FileSystemKeyStore::atomic_write(&self.base_dir.join(slot), &sealed).await
+    }
+
+    async fn load<T: DeserializeOwned>(&self, slot: &str) -> Result<Option<T>> {
+        let sealed = match tokio::fs::read(self.base_dir.join(slot)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CryptoError::Storage(format!("read {slot}: {e}"))),
+        };
+        let json = self.open_sealed(&sealed, slot)?;
+        serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|e| CryptoError::Storage(format!("parse {slot}: {e}")))
+    }
+
+    async fn delete(&self, slot: &str) -> Result<()> {
+        remove_if_exists(&self.base_dir.join(slot)).await
+    }
+}
+
+/// Run Argon2id off the async runtime; it is deliberately slow.
+async fn derive_key(
+    passphrase: Zeroizing<Vec<u8>>,
+    salt: Vec<u8>,
+    params: Params,
+) -> Result<Zeroizing<[u8; 32]>> {
+    tokio::task::spawn_blocking(move || {
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(&passphrase, &salt, key.as_mut_slice())
+            .map_err(|e| CryptoError::KeyDerivationFailed(e.to_string()))?;
+        Ok(key)
+    })
+    .await
+    .map_err(|e| CryptoError::KeyDerivationFailed(e.to_string()))?
+}
+
+async fn remove_if_exists(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(CryptoError::Storage(format!("delete: {e}"))),
+    }
+}
+
+#[async_trait]
+impl KeyStore for EncryptedFileKeyStore {
+    async fn save_group_key(&self, session_id: &str, data: KeyData) -> Result<()> {
+        self.save(&Self::slot("group-keys", session_id), &data)
+            .await
+    }
+
+    async fn load_group_key(&self, session_id: &str) -> Result<Option<KeyData>> {
+        self.load(&Self::slot("group-keys", session_id)).await
+    }
+
+    async fn delete_group_key(&self, session_id: &str) -> Result<()> {
+        self.delete(&Self::slot("group-keys", session_id)).await
+    }
+
+    async fn save_tofu_record(&self, id: &str, record: TofuRecord) -> Result<()> {
+        self.save(&Self::slot("tofu", id), &record).await
+    }
+
+    async fn load_tofu_record(&self, id: &str) -> Result<Option<TofuRecord>> {
+        self.load(&Self::slot("tofu", id)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_data() -> KeyData {
+        KeyData {
+            key: serde_json::json!({"kty": "oct", "k": "c2VjcmV0LWdyb3VwLWtleQ=="}),
+            stored_at: 42000,
+        }
+    }
+
+    #[tokio::test]
+    async fn encrypted_store_persist_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = EncryptedFileKeyStore::open(dir.path(), "hunter2")
+                .await
+                .unwrap();
+            store.save_group_key("room-1", key_data()).await.unwrap();
+            store
+                .save_tofu_record(
+                    "peer-1",
+                    TofuRecord {
+                        fingerprint: "abcd 1234".to_string(),
+                        first_seen: 5000,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let store = EncryptedFileKeyStore::open(dir.path(), "hunter2")
+            .await
+            .unwrap();
+        let loaded = store.load_group_key("room-1").await.unwrap().unwrap();
+        assert_eq!(loaded.key["k"], "c2VjcmV0LWdyb3VwLWtleQ==");
+        assert_eq!(loaded.stored_at, 42000);
+        let record = store.load_tofu_record("peer-1").await.unwrap().unwrap();
+        assert_eq!(record.fingerprint, "abcd 1234");
+        assert!(store.load_group_key("room-2").await.unwrap().is_none());
+
+        store.delete_group_key("room-1").await.unwrap();
+        assert!(store.load_group_key("room-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn encrypted_store_rejects_wrong_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        EncryptedFileKeyStore::open(dir.path(), "hunter2")
+            .await
+            .unwrap();
+        let result = EncryptedFileKeyStore::open(dir.path(), "hunter3").await;
+        assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn encrypted_store_hides_and_binds_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EncryptedFileKeyStore::open(dir.path(), "hunter2")
+            .await
+            .unwrap();
+        store.save_group_key("room-1", key_data()).await.unwrap();
+        store.save_group_key("room-2", key_data()).await.unwrap();
+
+        let path_1 = dir
+            .path()
+            .join(EncryptedFileKeyStore::slot("group-keys", "room-1"));
+        let path_2 = dir
+            .path()
+            .join(EncryptedFileKeyStore::slot("group-keys", "room-2"));
+        let sealed = std::fs::read(&path_1).unwrap();
+        let needle = b"c2VjcmV0LWdyb3VwLWtleQ";
+        assert!(!sealed.windows(needle.len()).any(|w| w == needle));
+
+        // A record copied into another slot fails authentication
+        std::fs::copy(&path_1, &path_2).unwrap();
+        assert!(matches!(
+            store.load_group_key("room-2").await,
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+//! OS keychain key store.
+
+use async_trait::async_trait;
+use keyring::Entry;
+use serde::{de::DeserializeOwned, Serialize};
+use zeroize::Zeroizing;
+
+use super::KeyStore;
+use crate::error::{CryptoError, Result};
+use crate::types::{KeyData, TofuRecord};
+
+/// Key store backed by the platform credential store: the macOS Keychain,
+/// Windows Credential Manager, or the Secret Service (GNOME Keyring,
+/// KWallet) on Linux.
+///
+/// Each record is one credential under `service`, with account
+/// `group-key:<session_id>` or `tofu:<id>` and the record's JSON as the
+/// secret. Keychain calls may block on a user prompt, so they run on
+/// tokio's blocking pool.
+pub struct KeychainKeyStore {
+    service: String,
+}
+
+impl KeychainKeyStore {
+    /// Store records under `service` (e.g. your app's bundle identifier).
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+        }
+    }
+
+    async fn with_entry<T, F>(&self, account: String, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(Entry) -> Result<T> + Send + 'static,
+    {
+        let service = self.service.clone();
+        tokio::task::spawn_blocking(move || {
+            let entry = Entry::new(&service, &account)
+                .map_err(|e| CryptoError::Storage(format!("keychain entry {account}: {e}")))?;
+            f(entry)
+        })
+        .await
+        .map_err(|e| CryptoError::Storage(format!("keychain task: {e}")))?
+    }
+
+    async fn save<T: Serialize>(&self, account: String, value: &T) -> Result<()> {
+        let json = Zeroizing::new(
+            serde_json::to_string(value).map_err(|e| CryptoError::Serialization(e.to_string()))?,
+        );
+        self.with_entry(account, move |entry| {
+            entry
+                .set_password(&json)
+                .map_err(|e| CryptoError::Storage(format!("keychain write: {e}")))
+        })
+        .await
+    }
+
+    async fn load<T: DeserializeOwned + Send + 'static>(
+        &self,
+        account: String,
+    ) -> Result<Option<T>> {
+        self.with_entry(account, |entry| match entry.get_password() {
+            Ok(json) => {
+                let json = Zeroizing::new(json);
+                serde_json::from_str(&json)
+                    .map(Some)
+                    .map_err(|e| CryptoError::Storage(format!("parse keychain record: {e}")))
+            }
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(CryptoError::Storage(format!("keychain read: {e}"))),
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl KeyStore for KeychainKeyStore {
+    async fn save_group_key(&self, session_id: &str, data: KeyData) -> Result<()> {
+        self.save(format!("group-key:{session_id}"), &data).await
+    }
+
+    async fn load_group_key(&self, session_id: &str) -> Result<Option<KeyData>> {
+        self.load(format!("group-key:{session_id}")).await
+    }
+
+    async fn delete_group_key(&self, session_id: &str) -> Result<()> {
+        self.with_entry(format!("group-key:{session_id}"), |entry| {
+            match entry.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(CryptoError::Storage(format!("keychain delete: {e}"))),
+            }
+        })
+        .await
+    }
+
+    async fn save_tofu_record(&self, id: &str, record: TofuRecord) -> Result<()> {
+        self.save(format!("tofu:{id}"), &record).await
+    }
+
+    async fn load_tofu_record(&self, id: &str) -> Result<Option<TofuRecord>> {
+        self.load(format!("tofu:{id}")).await
+    }
+}
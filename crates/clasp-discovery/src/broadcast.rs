@@ -31,6 +31,7 @@ pub async fn discover(port: u16, tx: mpsc::Sender<DiscoveryEvent>) -> Result<()>
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
 
     let hello_bytes = codec::encode(&hello).map_err(|e| DiscoveryError::Network(e.to_string()))?;
@@ -149,6 +150,9 @@ impl BroadcastResponder {
                         features: self.features.clone(),
                         time: clasp_core::time::now(),
                         token: None,
+                        challenge: None,
+                        resume_token: None,
+                        resumed: false,
                     });
 
                     if let Ok(response) = codec::encode(&welcome) {
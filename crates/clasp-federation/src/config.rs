@@ -44,6 +44,15 @@ pub struct FederationConfig {
     pub client_name: String,
     /// Features to advertise in HELLO
     pub features: Vec<String>,
+    /// How many times a namespace declaration may be relayed in mesh mode
+    /// before it is dropped (0 = only direct peers learn our namespaces)
+    pub gossip_ttl: u8,
+    /// Sync by streaming the peer's journal from the last checkpoint instead
+    /// of requesting a snapshot (see [`crate::FederationLink::with_journal_checkpoints`])
+    pub journal_sync: bool,
+    /// Ask the peer for its entity registry after the handshake and accept
+    /// registry events from it (see [`crate::LinkEvent::RegistryEvent`])
+    pub registry_sync: bool,
 }
 
 impl Default for FederationConfig {
@@ -64,6 +73,9 @@ impl Default for FederationConfig {
                 "stream".to_string(),
                 "federation".to_string(),
             ],
+            gossip_ttl: 4,
+            journal_sync: false,
+            registry_sync: false,
         }
     }
 }
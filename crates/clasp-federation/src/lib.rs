@@ -17,7 +17,10 @@
 //!
 //! - **Hub**: Central router that accepts leaf connections (star topology)
 //! - **Leaf**: Edge router that connects to a single hub
-//! - **Mesh**: Peer-to-peer connections between multiple routers
+//! - **Mesh**: Peer-to-peer connections between multiple routers. Namespace
+//!   declarations are gossiped with a hop count and TTL in their `origin`
+//!   (see [`clasp_core::GossipOrigin`]), so each router learns the namespaces
+//!   of peers-of-peers and forwards to the next hop on the shortest path
 //!
 //! # Example
 //!
@@ -47,6 +50,6 @@ pub mod namespace;
 
 pub use config::{FederationConfig, FederationMode, PeerInfo, PeerState};
 pub use error::{FederationError, Result};
-pub use link::{FederationLink, LinkEvent};
+pub use link::{FederationLink, GossipAnnouncement, LinkEvent};
 pub use manager::FederationManager;
-pub use namespace::NamespaceManager;
+pub use namespace::{NamespaceManager, Route};
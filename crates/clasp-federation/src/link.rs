@@ -5,16 +5,16 @@
 //! normal client session on the peer router.
 
 use clasp_core::{
-    codec, FederationOp, FederationSyncMessage, HelloMessage, Message, QoS, SetMessage,
-    SubscribeMessage, Value, PROTOCOL_VERSION,
+    codec, FederationOp, FederationSyncMessage, GossipOrigin, HelloMessage, Message, QoS,
+    SetMessage, SubscribeMessage, UnsubscribeMessage, Value, PROTOCOL_VERSION,
 };
 use clasp_transport::{TransportEvent, TransportReceiver, TransportSender};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
-use crate::config::{FederationConfig, PeerInfo, PeerState};
+use crate::config::{FederationConfig, FederationMode, PeerInfo, PeerState};
 use crate::error::{FederationError, Result};
 
 /// Events emitted by a federation link to the local router
@@ -40,6 +40,15 @@ pub enum LinkEvent {
         pattern: String,
         revision: u64,
     },
+    /// Peer's journal has been applied up to `seq` for `pattern`. Keep it
+    /// and pass it to the next link's
+    /// [`with_journal_checkpoints`](FederationLink::with_journal_checkpoints)
+    /// so resync after a partition only streams what was missed.
+    JournalCheckpoint {
+        router_id: String,
+        pattern: String,
+        seq: u64,
+    },
     /// Peer disconnected
     Disconnected {
         router_id: String,
@@ -47,6 +56,32 @@ pub enum LinkEvent {
     },
     /// Peer connected and handshake complete
     Connected { router_id: String },
+    /// Peer relayed a gossiped namespace declaration (mesh mode).
+    /// Empty `patterns` withdraws the route.
+    RouteAnnounced {
+        via: String,
+        origin: GossipOrigin,
+        patterns: Vec<String>,
+    },
+    /// Peer sent a signed entity registry event (with `registry_sync` on).
+    /// Verify and apply it, e.g. with `clasp_registry::replication`.
+    RegistryEvent { router_id: String, payload: Vec<u8> },
+    /// Peer asked for our entity registry (with `registry_sync` on). Send
+    /// each event through [`FederationLink::with_registry_events`].
+    RegistrySyncRequested { router_id: String },
+}
+
+/// A gossiped namespace declaration for links to send to their peers
+#[derive(Debug, Clone)]
+pub struct GossipAnnouncement {
+    /// Owner and hop metadata to send
+    pub origin: GossipOrigin,
+    /// Owned patterns (empty to withdraw)
+    pub patterns: Vec<String>,
+    /// Don't send to this peer (the route's next hop)
+    pub skip: Option<String>,
+    /// Only send to this peer (initial table for a new link)
+    pub only: Option<String>,
 }
 
 /// A federation link to a single peer router.
@@ -67,6 +102,16 @@ pub struct FederationLink {
     event_tx: mpsc::Sender<LinkEvent>,
     /// Revision vector: address -> last known revision from this peer
     revision_vector: HashMap<String, u64>,
+    /// Announcements from the manager to relay to the peer (mesh mode)
+    gossip_rx: Option<broadcast::Receiver<GossipAnnouncement>>,
+    /// Next subscription ID for gossiped routes
+    next_subscription_id: u32,
+    /// Subscriptions made for gossiped owners (owner -> subscription IDs)
+    gossip_subscriptions: HashMap<String, Vec<u32>>,
+    /// Journal sequence numbers of the peer applied so far (pattern -> seq)
+    journal_checkpoints: HashMap<String, u64>,
+    /// Encoded registry events from the local router to send to the peer
+    registry_rx: Option<broadcast::Receiver<Vec<u8>>>,
 }
 
 impl FederationLink {
@@ -85,9 +130,33 @@ impl FederationLink {
             state: PeerState::Connecting,
             event_tx,
             revision_vector: HashMap::new(),
+            gossip_rx: None,
+            next_subscription_id: 10_000, // Above the direct namespace range
+            gossip_subscriptions: HashMap::new(),
+            journal_checkpoints: HashMap::new(),
+            registry_rx: None,
         }
     }
 
+    /// Relay namespace gossip from the manager to the peer.
+    pub fn with_gossip(mut self, gossip_rx: broadcast::Receiver<GossipAnnouncement>) -> Self {
+        self.gossip_rx = Some(gossip_rx);
+        self
+    }
+
+    /// Send encoded registry events from the local router to the peer
+    pub fn with_registry_events(mut self, registry_rx: broadcast::Receiver<Vec<u8>>) -> Self {
+        self.registry_rx = Some(registry_rx);
+        self
+    }
+
+    /// Resume journal sync from earlier checkpoints (pattern -> journal
+    /// sequence number), as reported by [`LinkEvent::JournalCheckpoint`].
+    pub fn with_journal_checkpoints(mut self, checkpoints: HashMap<String, u64>) -> Self {
+        self.journal_checkpoints = checkpoints;
+        self
+    }
+
     /// Run the federation link protocol.
     ///
     /// This performs the handshake, initial sync, and then relays messages
@@ -98,8 +167,43 @@ impl FederationLink {
         self.state = PeerState::Handshaking;
 
         // Step 2: Wait for WELCOME and process messages
+        let mut gossip_rx = self.gossip_rx.take();
+        let mut registry_rx = self.registry_rx.take();
         loop {
-            match receiver.recv().await {
+            let event = tokio::select! {
+                event = receiver.recv() => event,
+                announcement = recv_optional(&mut gossip_rx) => {
+                    match announcement {
+                        Ok(announcement) => {
+                            if let Err(e) = self.send_gossip(&announcement).await {
+                                error!("Federation link error: {}", e);
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            warn!("Federation link dropped {} gossip announcements", n);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => gossip_rx = None,
+                    }
+                    continue;
+                }
+                payload = recv_optional(&mut registry_rx) => {
+                    match payload {
+                        Ok(payload) => {
+                            if let Err(e) = self.send_registry_event(payload).await {
+                                error!("Federation link error: {}", e);
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            warn!("Federation link dropped {} registry events", n);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => registry_rx = None,
+                    }
+                    continue;
+                }
+            };
+            match event {
                 Some(TransportEvent::Data(data)) => {
                     if let Err(e) = self.handle_data(&data).await {
                         error!("Federation link error: {}", e);
@@ -129,6 +233,9 @@ impl FederationLink {
                 Some(TransportEvent::Connected) => {
                     debug!("Federation transport connected event");
                 }
+                Some(TransportEvent::Migrated { from, to }) => {
+                    info!("Federation peer moved from {} to {}", from, to);
+                }
                 None => {
                     debug!("Federation transport stream ended");
                     break;
@@ -148,6 +255,7 @@ impl FederationLink {
             features: self.config.features.clone(),
             capabilities: None,
             token: self.config.auth_token.clone(),
+            session_resume: None,
         });
 
         self.send_message(&hello, QoS::Confirm).await
@@ -161,6 +269,54 @@ impl FederationLink {
             revisions: HashMap::new(),
             since_revision: None,
             origin: Some(self.config.router_id.clone()),
+            payload: None,
+        });
+
+        self.send_message(&msg, QoS::Confirm).await
+    }
+
+    /// Declare our namespaces in gossip form so the peer relays them (mesh mode)
+    async fn gossip_namespaces(&self) -> Result<()> {
+        let origin = GossipOrigin {
+            router_id: self.config.router_id.clone(),
+            hops: 0,
+            ttl: self.config.gossip_ttl,
+        };
+        let msg = Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::DeclareNamespaces,
+            patterns: self.config.owned_namespaces.clone(),
+            revisions: HashMap::new(),
+            since_revision: None,
+            origin: Some(origin.encode()),
+            payload: None,
+        });
+
+        self.send_message(&msg, QoS::Confirm).await
+    }
+
+    /// Send a gossip announcement from the manager, unless it is not meant for this peer
+    async fn send_gossip(&self, announcement: &GossipAnnouncement) -> Result<()> {
+        // Before the handshake there is nobody to send to; the manager sends
+        // its full table once we report Connected.
+        let Some(ref peer) = self.peer else {
+            return Ok(());
+        };
+        if announcement.skip.as_deref() == Some(peer.router_id.as_str()) {
+            return Ok(());
+        }
+        if let Some(ref only) = announcement.only {
+            if *only != peer.router_id {
+                return Ok(());
+            }
+        }
+
+        let msg = Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::DeclareNamespaces,
+            patterns: announcement.patterns.clone(),
+            revisions: HashMap::new(),
+            since_revision: None,
+            origin: Some(announcement.origin.encode()),
+            payload: None,
         });
 
         self.send_message(&msg, QoS::Confirm).await
@@ -180,6 +336,26 @@ impl FederationLink {
         Ok(())
     }
 
+    /// Subscribe to a gossiped owner's namespaces through the peer.
+    ///
+    /// Returns the subscription IDs used.
+    async fn subscribe_to_route(&mut self, patterns: &[String]) -> Result<Vec<u32>> {
+        let mut ids = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            let id = self.next_subscription_id;
+            self.next_subscription_id = self.next_subscription_id.wrapping_add(1).max(10_000);
+            let sub = Message::Subscribe(SubscribeMessage {
+                id,
+                pattern: pattern.clone(),
+                types: vec![],
+                options: None,
+            });
+            self.send_message(&sub, QoS::Confirm).await?;
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
     /// Request state sync from peer for a pattern
     async fn request_sync(&self, pattern: &str, since: Option<u64>) -> Result<()> {
         let msg = Message::FederationSync(FederationSyncMessage {
@@ -188,11 +364,87 @@ impl FederationLink {
             revisions: HashMap::new(),
             since_revision: since,
             origin: Some(self.config.router_id.clone()),
+            payload: None,
+        });
+
+        self.send_message(&msg, QoS::Confirm).await
+    }
+
+    /// Ask the peer to stream its journal for a pattern from our checkpoint
+    async fn request_journal_sync(&self, pattern: &str) -> Result<()> {
+        let msg = Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::RequestJournalSync,
+            patterns: vec![pattern.to_string()],
+            revisions: HashMap::new(),
+            since_revision: self.journal_checkpoints.get(pattern).copied(),
+            origin: Some(self.config.router_id.clone()),
+            payload: None,
         });
 
         self.send_message(&msg, QoS::Confirm).await
     }
 
+    /// Ask the peer for its entity registry
+    async fn request_registry_sync(&self) -> Result<()> {
+        let msg = Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::RequestRegistrySync,
+            patterns: vec![],
+            revisions: HashMap::new(),
+            since_revision: None,
+            origin: Some(self.config.router_id.clone()),
+            payload: None,
+        });
+
+        self.send_message(&msg, QoS::Confirm).await
+    }
+
+    /// Send an encoded registry event to the peer, once the handshake is done
+    async fn send_registry_event(&self, payload: Vec<u8>) -> Result<()> {
+        if self.peer.is_none() {
+            return Ok(());
+        }
+        let msg = Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::RegistryEvent,
+            patterns: vec![],
+            revisions: HashMap::new(),
+            since_revision: None,
+            origin: Some(self.config.router_id.clone()),
+            payload: Some(payload),
+        });
+
+        self.send_message(&msg, QoS::Confirm).await
+    }
+
+    /// Record how far the peer's journal has been applied for `patterns`
+    async fn journal_checkpoint(&mut self, patterns: &[String], seq: u64) {
+        let router_id = self
+            .peer
+            .as_ref()
+            .map(|p| p.router_id.clone())
+            .unwrap_or_default();
+        for pattern in patterns {
+            self.journal_checkpoints.insert(pattern.clone(), seq);
+            let _ = self
+                .event_tx
+                .send(LinkEvent::JournalCheckpoint {
+                    router_id: router_id.clone(),
+                    pattern: pattern.clone(),
+                    seq,
+                })
+                .await;
+        }
+    }
+
+    /// Whether a SET or snapshot param from the peer is older than one
+    /// already applied. Journal sync pages and live forwards can interleave,
+    /// so the highest revision seen per address wins.
+    fn is_stale(&self, address: &str, revision: Option<u64>) -> bool {
+        match (revision, self.revision_vector.get(address)) {
+            (Some(revision), Some(known)) => revision <= *known,
+            _ => false,
+        }
+    }
+
     /// Send our revision vector to the peer for sync negotiation
     async fn send_revision_vector(&self) -> Result<()> {
         let msg = Message::FederationSync(FederationSyncMessage {
@@ -201,6 +453,7 @@ impl FederationLink {
             revisions: self.revision_vector.clone(),
             since_revision: None,
             origin: Some(self.config.router_id.clone()),
+            payload: None,
         });
 
         self.send_message(&msg, QoS::Confirm).await
@@ -222,6 +475,8 @@ impl FederationLink {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         self.send_message(&set, QoS::Confirm).await
@@ -282,6 +537,19 @@ impl FederationLink {
 
                 // Declare our namespaces to the peer
                 self.declare_namespaces().await?;
+                if matches!(self.config.mode, FederationMode::Mesh { .. }) {
+                    self.gossip_namespaces().await?;
+                }
+                // Catch up on writes the peer recorded under our namespaces
+                // while we were away
+                if self.config.journal_sync {
+                    for pattern in &self.config.owned_namespaces {
+                        self.request_journal_sync(pattern).await?;
+                    }
+                }
+                if self.config.registry_sync {
+                    self.request_registry_sync().await?;
+                }
                 self.state = PeerState::Syncing;
             }
 
@@ -290,6 +558,10 @@ impl FederationLink {
             }
 
             Message::Set(set_msg) => {
+                if self.is_stale(&set_msg.address, set_msg.revision) {
+                    return Ok(());
+                }
+
                 // Peer sent us a SET -- apply it locally
                 let origin = self
                     .peer
@@ -338,6 +610,9 @@ impl FederationLink {
                     .unwrap_or_default();
 
                 for param in snapshot.params {
+                    if self.is_stale(&param.address, Some(param.revision)) {
+                        continue;
+                    }
                     self.revision_vector
                         .insert(param.address.clone(), param.revision);
                     let _ = self
@@ -383,6 +658,10 @@ impl FederationLink {
     async fn handle_federation_sync(&mut self, msg: FederationSyncMessage) -> Result<()> {
         match msg.op {
             FederationOp::DeclareNamespaces => {
+                if let Some(origin) = msg.origin.as_deref().and_then(GossipOrigin::parse) {
+                    return self.handle_gossip(origin, msg.patterns).await;
+                }
+
                 let router_id = msg
                     .origin
                     .clone()
@@ -410,7 +689,11 @@ impl FederationLink {
 
                 // Request initial sync
                 for pattern in &msg.patterns {
-                    self.request_sync(pattern, None).await?;
+                    if self.config.journal_sync {
+                        self.request_journal_sync(pattern).await?;
+                    } else {
+                        self.request_sync(pattern, None).await?;
+                    }
                 }
             }
 
@@ -430,7 +713,48 @@ impl FederationLink {
                 // For now, just store the peer's revision vector for reference
             }
 
+            FederationOp::RequestJournalSync => {
+                // Routers answer journal sync requests; a link has no journal
+                debug!("Ignoring journal sync request for {:?}", msg.patterns);
+            }
+
+            FederationOp::RequestRegistrySync => {
+                if self.config.registry_sync {
+                    let router_id = self.peer_router_id();
+                    debug!("Peer {} requests our registry", router_id);
+                    let _ = self
+                        .event_tx
+                        .send(LinkEvent::RegistrySyncRequested { router_id })
+                        .await;
+                }
+            }
+
+            FederationOp::RegistryEvent => match msg.payload {
+                Some(payload) if self.config.registry_sync => {
+                    let _ = self
+                        .event_tx
+                        .send(LinkEvent::RegistryEvent {
+                            router_id: self.peer_router_id(),
+                            payload,
+                        })
+                        .await;
+                }
+                _ => debug!("Ignoring registry event from peer"),
+            },
+
+            FederationOp::JournalCheckpoint => {
+                if let Some(seq) = msg.since_revision {
+                    debug!("Journal checkpoint for {:?} at seq {}", msg.patterns, seq);
+                    self.journal_checkpoint(&msg.patterns, seq).await;
+                }
+            }
+
             FederationOp::SyncComplete => {
+                // A journal sync ends with the sequence number it reached
+                if let Some(seq) = msg.since_revision {
+                    self.journal_checkpoint(&msg.patterns, seq).await;
+                }
+
                 let router_id = self
                     .peer
                     .as_ref()
@@ -475,6 +799,57 @@ impl FederationLink {
         Ok(())
     }
 
+    /// Handle a gossiped namespace declaration relayed by the peer
+    async fn handle_gossip(&mut self, origin: GossipOrigin, patterns: Vec<String>) -> Result<()> {
+        // Our own namespaces came back around the mesh
+        if origin.router_id == self.config.router_id {
+            return Ok(());
+        }
+        let via = self
+            .peer
+            .as_ref()
+            .map(|p| p.router_id.clone())
+            .unwrap_or_default();
+
+        debug!(
+            "Peer {} relays namespaces of {} ({} hops): {:?}",
+            via, origin.router_id, origin.hops, patterns
+        );
+
+        // Replace any subscriptions made for an earlier announcement
+        for id in self
+            .gossip_subscriptions
+            .remove(&origin.router_id)
+            .unwrap_or_default()
+        {
+            let unsub = Message::Unsubscribe(UnsubscribeMessage { id });
+            self.send_message(&unsub, QoS::Confirm).await?;
+        }
+        if !patterns.is_empty() {
+            let ids = self.subscribe_to_route(&patterns).await?;
+            self.gossip_subscriptions
+                .insert(origin.router_id.clone(), ids);
+        }
+
+        let _ = self
+            .event_tx
+            .send(LinkEvent::RouteAnnounced {
+                via,
+                origin,
+                patterns,
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Router ID of the peer (empty before the handshake)
+    fn peer_router_id(&self) -> String {
+        self.peer
+            .as_ref()
+            .map(|p| p.router_id.clone())
+            .unwrap_or_default()
+    }
+
     /// Encode and send a message to the peer
     async fn send_message(&self, msg: &Message, _qos: QoS) -> Result<()> {
         let data = codec::encode(msg).map_err(|e| FederationError::Codec(e.to_string()))?;
@@ -484,3 +859,13 @@ impl FederationLink {
             .map_err(|e| FederationError::Transport(e.to_string()))
     }
 }
+
+/// Receive from an optional broadcast channel, waiting forever without one
+async fn recv_optional<T: Clone>(
+    rx: &mut Option<broadcast::Receiver<T>>,
+) -> std::result::Result<T, broadcast::error::RecvError> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
@@ -3,15 +3,21 @@
 //! The FederationManager owns all outbound federation links and
 //! coordinates namespace management, message forwarding, and
 //! reconnection logic.
+//!
+//! In mesh mode the manager also gossips: when the best route to a
+//! router's namespaces changes it re-announces the route (one hop further,
+//! one TTL lower) to every other link, so routers learn namespaces owned
+//! by peers-of-peers without configuring every pair.
 
+use clasp_core::GossipOrigin;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
-use tracing::{info, warn};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, info, warn};
 
 use crate::config::{FederationConfig, FederationMode, PeerInfo, PeerState};
-use crate::link::{FederationLink, LinkEvent};
-use crate::namespace::NamespaceManager;
+use crate::link::{FederationLink, GossipAnnouncement, LinkEvent};
+use crate::namespace::{NamespaceManager, Route};
 
 /// Federation manager that coordinates all peer connections.
 ///
@@ -29,12 +35,15 @@ pub struct FederationManager {
     event_rx: Option<mpsc::Receiver<LinkEvent>>,
     /// Event sender (cloned to each link)
     event_tx: mpsc::Sender<LinkEvent>,
+    /// Gossip announcements for links to relay (mesh mode)
+    gossip_tx: broadcast::Sender<GossipAnnouncement>,
 }
 
 impl FederationManager {
     /// Create a new federation manager
     pub fn new(config: FederationConfig) -> Self {
         let (event_tx, event_rx) = mpsc::channel(1024);
+        let (gossip_tx, _) = broadcast::channel(1024);
         let namespaces = NamespaceManager::new(config.owned_namespaces.clone());
 
         Self {
@@ -43,6 +52,7 @@ impl FederationManager {
             peers: Arc::new(RwLock::new(HashMap::new())),
             event_rx: Some(event_rx),
             event_tx,
+            gossip_tx,
         }
     }
 
@@ -65,6 +75,12 @@ impl FederationManager {
     /// and begin relaying messages. Call `link.run(receiver)` to start it.
     pub fn create_link(&self, sender: Arc<dyn clasp_transport::TransportSender>) -> FederationLink {
         FederationLink::new(self.config.clone(), sender, self.event_tx.clone())
+            .with_gossip(self.gossip_tx.subscribe())
+    }
+
+    /// Subscribe to gossip announcements, for links not made with `create_link()`
+    pub fn gossip_receiver(&self) -> broadcast::Receiver<GossipAnnouncement> {
+        self.gossip_tx.subscribe()
     }
 
     /// Process a link event, updating internal state.
@@ -115,6 +131,23 @@ impl FederationManager {
                 if let Some(peer) = self.peers.write().await.get_mut(router_id) {
                     peer.state = PeerState::Active;
                 }
+
+                // Bring the new peer up to date with everything we can reach
+                if self.is_mesh() {
+                    let routes: Vec<Route> = self
+                        .namespaces
+                        .read()
+                        .await
+                        .routes()
+                        .into_iter()
+                        .cloned()
+                        .collect();
+                    for route in routes {
+                        if route.via != *router_id {
+                            self.announce_route(&route, Some(router_id.clone()));
+                        }
+                    }
+                }
             }
 
             LinkEvent::Disconnected { router_id, reason } => {
@@ -122,10 +155,43 @@ impl FederationManager {
                     "Federation peer disconnected: {} (reason: {:?})",
                     router_id, reason
                 );
-                self.namespaces.write().await.remove_peer(router_id);
+                let changed = {
+                    let mut ns = self.namespaces.write().await;
+                    ns.remove_peer(router_id);
+                    ns.remove_routes_via(router_id)
+                };
                 if let Some(peer) = self.peers.write().await.get_mut(router_id) {
                     peer.state = PeerState::Disconnected;
                 }
+                for owner in changed {
+                    self.announce_best(&owner).await;
+                }
+            }
+
+            LinkEvent::RouteAnnounced {
+                via,
+                origin,
+                patterns,
+            } => {
+                if origin.router_id == self.config.router_id {
+                    return;
+                }
+                debug!(
+                    "Route to {} via {} ({} hops): {:?}",
+                    origin.router_id,
+                    via,
+                    origin.hops as u16 + 1,
+                    patterns
+                );
+                let owner = origin.router_id.clone();
+                let changed = self.namespaces.write().await.learn_route(
+                    via,
+                    origin.clone(),
+                    patterns.clone(),
+                );
+                if changed {
+                    self.announce_best(&owner).await;
+                }
             }
 
             LinkEvent::SyncComplete {
@@ -144,6 +210,62 @@ impl FederationManager {
         }
     }
 
+    /// Announce the current best route to `owner` to all links (or withdraw
+    /// it if there is none left). No-op outside mesh mode.
+    async fn announce_best(&self, owner: &str) {
+        if !self.is_mesh() {
+            return;
+        }
+        let best = self.namespaces.read().await.best_route(owner).cloned();
+        match best {
+            Some(route) => self.announce_route(&route, None),
+            None => {
+                let _ = self.gossip_tx.send(GossipAnnouncement {
+                    origin: GossipOrigin {
+                        router_id: owner.to_string(),
+                        hops: 0,
+                        ttl: self.config.gossip_ttl,
+                    },
+                    patterns: vec![],
+                    skip: None,
+                    only: None,
+                });
+            }
+        }
+    }
+
+    /// Relay a route one hop further, unless its TTL is spent.
+    ///
+    /// The route's next hop is skipped (split horizon), so a route is never
+    /// advertised back to the peer it was learned from.
+    fn announce_route(&self, route: &Route, only: Option<String>) {
+        let Some(origin) = route.origin.relayed() else {
+            return;
+        };
+        // Err only means no link is listening yet
+        let _ = self.gossip_tx.send(GossipAnnouncement {
+            origin,
+            patterns: route.patterns.clone(),
+            skip: Some(route.via.clone()),
+            only,
+        });
+    }
+
+    fn is_mesh(&self) -> bool {
+        matches!(self.config.mode, FederationMode::Mesh { .. })
+    }
+
+    /// Best gossiped route to every router that is not a direct peer
+    pub async fn routes(&self) -> Vec<Route> {
+        self.namespaces
+            .read()
+            .await
+            .routes()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
     /// Check if an address should be forwarded to federation peers
     pub async fn should_forward(&self, address: &str, origin: Option<&str>) -> bool {
         let ns = self.namespaces.read().await;
@@ -211,6 +333,25 @@ mod tests {
         }
     }
 
+    fn mesh_manager() -> FederationManager {
+        FederationManager::new(FederationConfig {
+            mode: FederationMode::Mesh { peers: vec![] },
+            ..test_config()
+        })
+    }
+
+    fn announced(via: &str, owner: &str, hops: u8, ttl: u8, patterns: &[&str]) -> LinkEvent {
+        LinkEvent::RouteAnnounced {
+            via: via.to_string(),
+            origin: GossipOrigin {
+                router_id: owner.to_string(),
+                hops,
+                ttl,
+            },
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
     #[tokio::test]
     async fn test_manager_creation() {
         let manager = FederationManager::new(test_config());
@@ -275,4 +416,111 @@ mod tests {
             .await;
         assert!(peers.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_mesh_learns_and_relays_routes() {
+        let manager = mesh_manager();
+        let mut gossip = manager.gossip_receiver();
+
+        manager
+            .process_event(&announced("peer-b", "site-c", 0, 3, &["/site-c/**"]))
+            .await;
+
+        // Traffic for the peer-of-peer goes to the next hop
+        assert_eq!(
+            manager.peers_for_address("/site-c/x", None).await,
+            vec!["peer-b".to_string()]
+        );
+        assert!(manager
+            .peers_for_address("/site-c/x", Some("peer-b"))
+            .await
+            .is_empty());
+
+        // ...and is relayed one hop further, but not back to peer-b
+        let relayed = gossip.try_recv().unwrap();
+        assert_eq!(relayed.origin.encode(), "site-c;hops=1;ttl=2");
+        assert_eq!(relayed.patterns, vec!["/site-c/**".to_string()]);
+        assert_eq!(relayed.skip.as_deref(), Some("peer-b"));
+
+        // Re-announcing the same route is not news
+        manager
+            .process_event(&announced("peer-b", "site-c", 0, 3, &["/site-c/**"]))
+            .await;
+        assert!(gossip.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mesh_ttl_and_own_namespaces() {
+        let manager = mesh_manager();
+        let mut gossip = manager.gossip_receiver();
+
+        // TTL spent: learned, not relayed
+        manager
+            .process_event(&announced("peer-b", "site-d", 3, 0, &["/site-d/**"]))
+            .await;
+        assert!(manager.should_forward("/site-d/x", None).await);
+        assert!(gossip.try_recv().is_err());
+
+        // Our own namespaces coming back around the mesh are ignored
+        manager
+            .process_event(&announced("peer-b", "test-router", 2, 2, &["/local/**"]))
+            .await;
+        assert!(!manager.should_forward("/local/x", None).await);
+        assert!(gossip.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mesh_disconnect_fails_over_and_withdraws() {
+        let manager = mesh_manager();
+        manager
+            .process_event(&announced("peer-b", "site-c", 0, 3, &["/site-c/**"]))
+            .await;
+        manager
+            .process_event(&announced("peer-d", "site-c", 2, 1, &["/site-c/**"]))
+            .await;
+        assert_eq!(
+            manager.peers_for_address("/site-c/x", None).await,
+            vec!["peer-b".to_string()]
+        );
+
+        let mut gossip = manager.gossip_receiver();
+        manager
+            .process_event(&LinkEvent::Disconnected {
+                router_id: "peer-b".to_string(),
+                reason: None,
+            })
+            .await;
+
+        // Fails over to the longer path and announces it
+        assert_eq!(
+            manager.peers_for_address("/site-c/x", None).await,
+            vec!["peer-d".to_string()]
+        );
+        let relayed = gossip.try_recv().unwrap();
+        assert_eq!(relayed.origin.hops, 3);
+        assert_eq!(relayed.skip.as_deref(), Some("peer-d"));
+
+        // Losing the last path withdraws the route
+        manager
+            .process_event(&LinkEvent::Disconnected {
+                router_id: "peer-d".to_string(),
+                reason: None,
+            })
+            .await;
+        assert!(!manager.should_forward("/site-c/x", None).await);
+        let withdrawn = gossip.try_recv().unwrap();
+        assert_eq!(withdrawn.origin.router_id, "site-c");
+        assert!(withdrawn.patterns.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_non_mesh_does_not_relay() {
+        let manager = FederationManager::new(test_config());
+        let mut gossip = manager.gossip_receiver();
+        manager
+            .process_event(&announced("peer-b", "site-c", 0, 3, &["/site-c/**"]))
+            .await;
+        assert!(manager.should_forward("/site-c/x", None).await);
+        assert!(gossip.try_recv().is_err());
+    }
 }
@@ -2,9 +2,33 @@
 //!
 //! Tracks which peer routers own which address patterns,
 //! enabling intelligent message forwarding and loop prevention.
+//!
+//! In mesh mode, namespaces owned by routers that are not direct peers
+//! are learned through gossip and kept as [`Route`]s: for each owner the
+//! shortest announced path wins, and messages are forwarded to the direct
+//! peer at the start of that path.
 
+use clasp_core::GossipOrigin;
 use std::collections::HashMap;
 
+/// A namespace route to a non-adjacent router, learned through gossip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    /// Direct peer that announced the route (the next hop)
+    pub via: String,
+    /// Patterns owned by the route's router
+    pub patterns: Vec<String>,
+    /// Origin as received from `via`
+    pub origin: GossipOrigin,
+}
+
+impl Route {
+    /// Number of links between the local router and the owner
+    pub fn distance(&self) -> u16 {
+        self.origin.hops as u16 + 1
+    }
+}
+
 /// Manages namespace ownership across federated peers.
 ///
 /// Each peer declares the address patterns it owns. When a message arrives,
@@ -15,6 +39,8 @@ pub struct NamespaceManager {
     peer_namespaces: HashMap<String, Vec<String>>,
     /// Local router's owned patterns
     local_namespaces: Vec<String>,
+    /// Gossiped routes: owner router ID -> next hop -> route
+    routes: HashMap<String, HashMap<String, Route>>,
 }
 
 impl NamespaceManager {
@@ -23,6 +49,7 @@ impl NamespaceManager {
         Self {
             peer_namespaces: HashMap::new(),
             local_namespaces,
+            routes: HashMap::new(),
         }
     }
 
@@ -36,12 +63,79 @@ impl NamespaceManager {
         self.peer_namespaces.remove(router_id);
     }
 
+    /// Record a gossiped route to `origin.router_id` through the direct
+    /// peer `via`. An empty pattern list withdraws the route.
+    ///
+    /// Returns true if the best route to the owner changed.
+    pub fn learn_route(&mut self, via: &str, origin: GossipOrigin, patterns: Vec<String>) -> bool {
+        if patterns.is_empty() {
+            return self.withdraw_route(&origin.router_id, via);
+        }
+        let owner = origin.router_id.clone();
+        let before = self.best_route(&owner).cloned();
+        self.routes.entry(owner.clone()).or_default().insert(
+            via.to_string(),
+            Route {
+                via: via.to_string(),
+                patterns,
+                origin,
+            },
+        );
+        self.best_route(&owner) != before.as_ref()
+    }
+
+    /// Forget the route to `owner` through `via`.
+    ///
+    /// Returns true if the best route to the owner changed.
+    pub fn withdraw_route(&mut self, owner: &str, via: &str) -> bool {
+        let Some(candidates) = self.routes.get_mut(owner) else {
+            return false;
+        };
+        let before = best_of(candidates).cloned();
+        if candidates.remove(via).is_none() {
+            return false;
+        }
+        let changed = best_of(candidates) != before.as_ref();
+        if candidates.is_empty() {
+            self.routes.remove(owner);
+        }
+        changed
+    }
+
+    /// Forget every route learned through `via` (e.g. when it disconnects).
+    ///
+    /// Returns the owners whose best route changed.
+    pub fn remove_routes_via(&mut self, via: &str) -> Vec<String> {
+        let owners: Vec<String> = self
+            .routes
+            .iter()
+            .filter(|(_, candidates)| candidates.contains_key(via))
+            .map(|(owner, _)| owner.clone())
+            .collect();
+        owners
+            .into_iter()
+            .filter(|owner| self.withdraw_route(owner, via))
+            .collect()
+    }
+
+    /// Shortest known route to `owner` (ties broken by next hop ID)
+    pub fn best_route(&self, owner: &str) -> Option<&Route> {
+        self.routes.get(owner).and_then(best_of)
+    }
+
+    /// Best route to every owner reachable through gossip
+    pub fn routes(&self) -> Vec<&Route> {
+        self.routes.values().filter_map(best_of).collect()
+    }
+
     /// Get all peers that should receive a message for the given address.
     ///
-    /// Returns peer router IDs whose namespace patterns match the address.
+    /// Returns peer router IDs whose namespace patterns match the address,
+    /// plus the next hop towards any gossiped owner whose patterns match.
     /// Excludes the origin peer to prevent loops.
     pub fn peers_for_address(&self, address: &str, exclude_origin: Option<&str>) -> Vec<String> {
-        self.peer_namespaces
+        let mut peers: Vec<String> = self
+            .peer_namespaces
             .iter()
             .filter(|(router_id, patterns)| {
                 // Exclude origin to prevent loops
@@ -56,7 +150,29 @@ impl NamespaceManager {
                     .any(|p| clasp_core::address::glob_match(p, address))
             })
             .map(|(id, _)| id.clone())
-            .collect()
+            .collect();
+
+        for (owner, candidates) in &self.routes {
+            // A direct declaration always beats a gossiped route
+            if self.peer_namespaces.contains_key(owner) {
+                continue;
+            }
+            let Some(route) = best_of(candidates) else {
+                continue;
+            };
+            if exclude_origin == Some(route.via.as_str()) || peers.contains(&route.via) {
+                continue;
+            }
+            if route
+                .patterns
+                .iter()
+                .any(|p| clasp_core::address::glob_match(p, address))
+            {
+                peers.push(route.via.clone());
+            }
+        }
+
+        peers
     }
 
     /// Check if an address belongs to the local router's namespace
@@ -66,13 +182,16 @@ impl NamespaceManager {
             .any(|p| clasp_core::address::glob_match(p, address))
     }
 
-    /// Check if an address belongs to any peer's namespace
+    /// Check if an address belongs to any peer's (or gossiped router's) namespace
     pub fn is_remote(&self, address: &str) -> bool {
-        self.peer_namespaces.values().any(|patterns| {
-            patterns
-                .iter()
-                .any(|p| clasp_core::address::glob_match(p, address))
-        })
+        self.peer_namespaces
+            .values()
+            .chain(self.routes().into_iter().map(|r| &r.patterns))
+            .any(|patterns| {
+                patterns
+                    .iter()
+                    .any(|p| clasp_core::address::glob_match(p, address))
+            })
     }
 
     /// Check for namespace conflicts between peers.
@@ -125,6 +244,12 @@ impl NamespaceManager {
     }
 }
 
+fn best_of(candidates: &HashMap<String, Route>) -> Option<&Route> {
+    candidates
+        .values()
+        .min_by(|a, b| (a.distance(), &a.via).cmp(&(b.distance(), &b.via)))
+}
+
 /// Check if two glob patterns can potentially match the same address.
 ///
 /// This is a conservative check -- it may return true for patterns that
@@ -234,4 +359,74 @@ mod tests {
         assert!(patterns_overlap("/shared/**", "/shared/**"));
         assert!(!patterns_overlap("/site-a/data", "/site-b/data"));
     }
+
+    fn origin(owner: &str, hops: u8) -> GossipOrigin {
+        GossipOrigin {
+            router_id: owner.to_string(),
+            hops,
+            ttl: 2,
+        }
+    }
+
+    #[test]
+    fn test_routes_prefer_shortest_path() {
+        let mut ns = NamespaceManager::new(vec![]);
+        let patterns = vec!["/site-c/**".to_string()];
+
+        assert!(ns.learn_route("peer-d", origin("site-c", 2), patterns.clone()));
+        assert!(ns.learn_route("peer-b", origin("site-c", 0), patterns.clone()));
+        assert!(!ns.learn_route("peer-e", origin("site-c", 1), patterns.clone()));
+        assert_eq!(ns.best_route("site-c").unwrap().via, "peer-b");
+        assert_eq!(ns.best_route("site-c").unwrap().distance(), 1);
+        assert_eq!(ns.peers_for_address("/site-c/x", None), vec!["peer-b"]);
+        assert!(ns.is_remote("/site-c/x"));
+
+        // Withdrawal falls back to the next shortest path
+        assert!(ns.learn_route("peer-b", origin("site-c", 0), vec![]));
+        assert_eq!(ns.best_route("site-c").unwrap().via, "peer-e");
+
+        assert_eq!(ns.remove_routes_via("peer-e"), vec!["site-c".to_string()]);
+        assert_eq!(ns.best_route("site-c").unwrap().via, "peer-d");
+        assert_eq!(ns.remove_routes_via("peer-d"), vec!["site-c".to_string()]);
+        assert!(ns.best_route("site-c").is_none());
+        assert!(ns.routes().is_empty());
+    }
+
+    #[test]
+    fn test_direct_declaration_beats_route() {
+        let mut ns = NamespaceManager::new(vec![]);
+        ns.register_peer("site-c", vec!["/site-c/**".to_string()]);
+        ns.learn_route(
+            "peer-b",
+            origin("site-c", 0),
+            vec!["/site-c/**".to_string()],
+        );
+        ns.learn_route(
+            "peer-b",
+            origin("site-d", 0),
+            vec!["/site-d/**".to_string()],
+        );
+
+        assert_eq!(ns.peers_for_address("/site-c/x", None), vec!["site-c"]);
+        assert_eq!(ns.peers_for_address("/site-d/x", None), vec!["peer-b"]);
+        assert!(ns.peers_for_address("/site-d/x", Some("peer-b")).is_empty());
+    }
+
+    #[test]
+    fn test_gossip_origin_encoding() {
+        let origin = GossipOrigin::parse("site-c;hops=1;ttl=3").unwrap();
+        assert_eq!(origin.router_id, "site-c");
+        assert_eq!((origin.hops, origin.ttl), (1, 3));
+        assert_eq!(origin.encode(), "site-c;hops=1;ttl=3");
+        assert_eq!(origin.relayed().unwrap().encode(), "site-c;hops=2;ttl=2");
+        assert!(GossipOrigin::parse("site-c;hops=1;ttl=0")
+            .unwrap()
+            .relayed()
+            .is_none());
+
+        // Plain router IDs and malformed metadata are direct declarations
+        assert!(GossipOrigin::parse("site-c").is_none());
+        assert!(GossipOrigin::parse("site-c;hops=x;ttl=1").is_none());
+        assert!(GossipOrigin::parse(";hops=1;ttl=1").is_none());
+    }
 }
@@ -0,0 +1,616 @@
+//! HAP accessory database built from the bridge configuration.
+//!
+//! The bridge itself is accessory 1. Each configured accessory becomes a
+//! bridged accessory (aid 2, 3, ...) with an Accessory Information service
+//! and one primary service whose characteristics are bound to CLASP
+//! addresses. Value conversion in both directions lives here so the
+//! server only deals in HAP JSON.
+
+use std::collections::HashMap;
+
+use clasp_core::Value;
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha512};
+
+use crate::config::{AccessoryConfig, AccessoryKind, HomeKitConfig, TemperatureUnit};
+
+/// HAP status codes returned per characteristic in 207 responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HapStatus {
+    InsufficientPrivileges = -70401,
+    CommunicationFailure = -70402,
+    ReadOnly = -70404,
+    WriteOnly = -70405,
+    NotificationUnsupported = -70406,
+    NotFound = -70409,
+    InvalidValue = -70410,
+}
+
+impl HapStatus {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// HAP characteristic types used by the bridge (short UUID form).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharType {
+    Identify,
+    Manufacturer,
+    Model,
+    Name,
+    SerialNumber,
+    FirmwareRevision,
+    Version,
+    On,
+    Brightness,
+    CurrentTemperature,
+    OutletInUse,
+}
+
+impl CharType {
+    fn uuid(self) -> &'static str {
+        match self {
+            CharType::Identify => "14",
+            CharType::Manufacturer => "20",
+            CharType::Model => "21",
+            CharType::Name => "23",
+            CharType::SerialNumber => "30",
+            CharType::FirmwareRevision => "52",
+            CharType::Version => "37",
+            CharType::On => "25",
+            CharType::Brightness => "8",
+            CharType::CurrentTemperature => "11",
+            CharType::OutletInUse => "26",
+        }
+    }
+
+    fn perms(self) -> &'static [&'static str] {
+        match self {
+            CharType::Identify => &["pw"],
+            CharType::On | CharType::Brightness => &["pr", "pw", "ev"],
+            CharType::CurrentTemperature | CharType::OutletInUse => &["pr", "ev"],
+            _ => &["pr"],
+        }
+    }
+
+    fn readable(self) -> bool {
+        self.perms().contains(&"pr")
+    }
+
+    fn writable(self) -> bool {
+        self.perms().contains(&"pw")
+    }
+
+    fn notifies(self) -> bool {
+        self.perms().contains(&"ev")
+    }
+
+    /// Format and range metadata.
+    fn meta(self) -> JsonValue {
+        match self {
+            CharType::Identify | CharType::On | CharType::OutletInUse => {
+                json!({ "format": "bool" })
+            }
+            CharType::Brightness => json!({
+                "format": "int", "unit": "percentage",
+                "minValue": 0, "maxValue": 100, "minStep": 1
+            }),
+            CharType::CurrentTemperature => json!({
+                "format": "float", "unit": "celsius",
+                "minValue": -270, "maxValue": 100, "minStep": 0.1
+            }),
+            _ => json!({ "format": "string" }),
+        }
+    }
+}
+
+/// How a characteristic value maps to and from a CLASP value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conversion {
+    Bool,
+    Level { min: f64, max: f64 },
+    Temperature(TemperatureUnit),
+}
+
+impl Conversion {
+    fn to_hap(self, value: &Value) -> Option<JsonValue> {
+        match self {
+            Conversion::Bool => match value {
+                Value::Bool(b) => Some(json!(*b)),
+                Value::Int(_) | Value::Float(_) => Some(json!(value.as_f64()? != 0.0)),
+                _ => None,
+            },
+            Conversion::Level { min, max } => {
+                let v = value.as_f64()?;
+                let percent = ((v - min) / (max - min) * 100.0).round().clamp(0.0, 100.0);
+                Some(json!(percent as i64))
+            }
+            Conversion::Temperature(unit) => {
+                let v = value.as_f64()?;
+                let celsius = match unit {
+                    TemperatureUnit::Celsius => v,
+                    TemperatureUnit::Fahrenheit => (v - 32.0) * 5.0 / 9.0,
+                };
+                Some(json!((celsius.clamp(-270.0, 100.0) * 10.0).round() / 10.0))
+            }
+        }
+    }
+
+    fn to_clasp(self, value: &JsonValue) -> Option<Value> {
+        match self {
+            // Controllers may send booleans as 0/1
+            Conversion::Bool => match value {
+                JsonValue::Bool(b) => Some(Value::Bool(*b)),
+                JsonValue::Number(n) => match n.as_u64()? {
+                    0 => Some(Value::Bool(false)),
+                    1 => Some(Value::Bool(true)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Conversion::Level { min, max } => {
+                let percent = value.as_f64()?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return None;
+                }
+                Some(Value::Float(min + percent / 100.0 * (max - min)))
+            }
+            Conversion::Temperature(_) => None,
+        }
+    }
+}
+
+struct Characteristic {
+    iid: u64,
+    ty: CharType,
+    value: JsonValue,
+    binding: Option<(String, Conversion)>,
+}
+
+struct Service {
+    iid: u64,
+    uuid: &'static str,
+    primary: bool,
+    characteristics: Vec<Characteristic>,
+}
+
+struct Accessory {
+    aid: u64,
+    services: Vec<Service>,
+}
+
+/// A characteristic change to push to subscribed controllers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacteristicEvent {
+    pub aid: u64,
+    pub iid: u64,
+    pub value: JsonValue,
+}
+
+/// The accessories, services and characteristics exposed over HAP.
+pub struct AccessoryDatabase {
+    accessories: Vec<Accessory>,
+    /// CLASP address to (aid, iid) of every characteristic bound to it.
+    by_address: HashMap<String, Vec<(u64, u64)>>,
+}
+
+/// Sequential instance ID allocator, unique within one accessory.
+struct Iids(u64);
+
+impl Iids {
+    fn next(&mut self) -> u64 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+fn info_service(iids: &mut Iids, name: &str, model: &str, serial: &str) -> Service {
+    let mut ch = |ty, value: JsonValue| Characteristic {
+        iid: iids.next(),
+        ty,
+        value,
+        binding: None,
+    };
+    let iid = 1;
+    let characteristics = vec![
+        ch(CharType::Identify, JsonValue::Null),
+        ch(CharType::Manufacturer, json!("CLASP")),
+        ch(CharType::Model, json!(model)),
+        ch(CharType::Name, json!(name)),
+        ch(CharType::SerialNumber, json!(serial)),
+        ch(CharType::FirmwareRevision, json!(env!("CARGO_PKG_VERSION"))),
+    ];
+    Service {
+        iid,
+        uuid: "3E",
+        primary: false,
+        characteristics,
+    }
+}
+
+impl AccessoryDatabase {
+    /// Build the database for a configuration.
+    pub fn from_config(config: &HomeKitConfig) -> Self {
+        let mut accessories = Vec::with_capacity(config.accessories.len() + 1);
+
+        // Bridge accessory: info service (iid 1) plus protocol information
+        let mut iids = Iids(1);
+        let info = info_service(
+            &mut iids,
+            &config.name,
+            "CLASP HomeKit Bridge",
+            &config.setup_id,
+        );
+        let protocol = Service {
+            iid: iids.next(),
+            uuid: "A2",
+            primary: false,
+            characteristics: vec![Characteristic {
+                iid: iids.next(),
+                ty: CharType::Version,
+                value: json!("1.1.0"),
+                binding: None,
+            }],
+        };
+        accessories.push(Accessory {
+            aid: 1,
+            services: vec![info, protocol],
+        });
+
+        let mut by_address: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+        for (index, cfg) in config.accessories.iter().enumerate() {
+            let aid = index as u64 + 2;
+            let accessory = Self::bridged(aid, cfg);
+            for service in &accessory.services {
+                for ch in &service.characteristics {
+                    if let Some((address, _)) = &ch.binding {
+                        by_address
+                            .entry(address.clone())
+                            .or_default()
+                            .push((aid, ch.iid));
+                    }
+                }
+            }
+            accessories.push(accessory);
+        }
+
+        Self {
+            accessories,
+            by_address,
+        }
+    }
+
+    fn bridged(aid: u64, cfg: &AccessoryConfig) -> Accessory {
+        let mut iids = Iids(1);
+        let (model, uuid) = match cfg.kind {
+            AccessoryKind::Switch => ("Switch", "49"),
+            AccessoryKind::Outlet => ("Outlet", "47"),
+            AccessoryKind::Lightbulb => ("Lightbulb", "43"),
+            AccessoryKind::TemperatureSensor => ("Temperature Sensor", "8A"),
+        };
+        let info = info_service(&mut iids, &cfg.name, model, &format!("CLASP-{}", aid));
+
+        let service_iid = iids.next();
+        let mut characteristics = Vec::new();
+        let mut bind = |ty, address: &Option<String>, conversion, default: JsonValue| {
+            if let Some(address) = address {
+                characteristics.push(Characteristic {
+                    iid: iids.next(),
+                    ty,
+                    value: default,
+                    binding: Some((address.clone(), conversion)),
+                });
+            }
+        };
+        bind(CharType::On, &cfg.on, Conversion::Bool, json!(false));
+        bind(
+            CharType::Brightness,
+            &cfg.level,
+            Conversion::Level {
+                min: cfg.level_range[0],
+                max: cfg.level_range[1],
+            },
+            json!(0),
+        );
+        bind(
+            CharType::CurrentTemperature,
+            &cfg.temperature,
+            Conversion::Temperature(cfg.temperature_unit),
+            json!(0.0),
+        );
+        if cfg.kind == AccessoryKind::Outlet {
+            characteristics.push(Characteristic {
+                iid: iids.next(),
+                ty: CharType::OutletInUse,
+                value: json!(true),
+                binding: None,
+            });
+        }
+
+        let main = Service {
+            iid: service_iid,
+            uuid,
+            primary: true,
+            characteristics,
+        };
+        Accessory {
+            aid,
+            services: vec![info, main],
+        }
+    }
+
+    fn find(&self, aid: u64, iid: u64) -> Option<&Characteristic> {
+        self.accessories
+            .iter()
+            .find(|a| a.aid == aid)?
+            .services
+            .iter()
+            .flat_map(|s| s.characteristics.iter())
+            .find(|c| c.iid == iid)
+    }
+
+    fn find_mut(&mut self, aid: u64, iid: u64) -> Option<&mut Characteristic> {
+        self.accessories
+            .iter_mut()
+            .find(|a| a.aid == aid)?
+            .services
+            .iter_mut()
+            .flat_map(|s| s.characteristics.iter_mut())
+            .find(|c| c.iid == iid)
+    }
+
+    /// CLASP addresses the bridge must subscribe to.
+    pub fn addresses(&self) -> Vec<String> {
+        let mut addresses: Vec<String> = self.by_address.keys().cloned().collect();
+        addresses.sort();
+        addresses
+    }
+
+    /// Full database as returned by `GET /accessories`.
+    pub fn to_json(&self) -> JsonValue {
+        let accessories: Vec<JsonValue> = self
+            .accessories
+            .iter()
+            .map(|accessory| {
+                let services: Vec<JsonValue> = accessory
+                    .services
+                    .iter()
+                    .map(|service| {
+                        let characteristics: Vec<JsonValue> = service
+                            .characteristics
+                            .iter()
+                            .map(|ch| {
+                                let mut obj = json!({
+                                    "iid": ch.iid,
+                                    "type": ch.ty.uuid(),
+                                    "perms": ch.ty.perms(),
+                                });
+                                merge(&mut obj, ch.ty.meta());
+                                if ch.ty.readable() {
+                                    obj["value"] = ch.value.clone();
+                                }
+                                obj
+                            })
+                            .collect();
+                        let mut obj = json!({
+                            "iid": service.iid,
+                            "type": service.uuid,
+                            "characteristics": characteristics,
+                        });
+                        if service.primary {
+                            obj["primary"] = json!(true);
+                        }
+                        obj
+                    })
+                    .collect();
+                json!({ "aid": accessory.aid, "services": services })
+            })
+            .collect();
+        json!({ "accessories": accessories })
+    }
+
+    /// Hash of the database layout, excluding values.
+    ///
+    /// A change means accessories or characteristics were added or removed,
+    /// which must be announced by bumping the `c#` configuration number.
+    pub fn config_hash(&self) -> String {
+        let mut hasher = Sha512::new();
+        for accessory in &self.accessories {
+            hasher.update(accessory.aid.to_be_bytes());
+            for service in &accessory.services {
+                hasher.update(service.uuid.as_bytes());
+                hasher.update(service.iid.to_be_bytes());
+                for ch in &service.characteristics {
+                    hasher.update(ch.ty.uuid().as_bytes());
+                    hasher.update(ch.iid.to_be_bytes());
+                }
+            }
+        }
+        hasher
+            .finalize()
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Read a characteristic, optionally with its metadata.
+    pub fn read(&self, aid: u64, iid: u64, meta: bool) -> Result<JsonValue, HapStatus> {
+        let ch = self.find(aid, iid).ok_or(HapStatus::NotFound)?;
+        if !ch.ty.readable() {
+            return Err(HapStatus::WriteOnly);
+        }
+        let mut obj = json!({ "aid": aid, "iid": iid, "value": ch.value });
+        if meta {
+            merge(&mut obj, ch.ty.meta());
+            obj["type"] = json!(ch.ty.uuid());
+            obj["perms"] = json!(ch.ty.perms());
+        }
+        Ok(obj)
+    }
+
+    /// Check that events can be enabled on a characteristic.
+    pub fn check_events(&self, aid: u64, iid: u64) -> Result<(), HapStatus> {
+        let ch = self.find(aid, iid).ok_or(HapStatus::NotFound)?;
+        if ch.ty.notifies() {
+            Ok(())
+        } else {
+            Err(HapStatus::NotificationUnsupported)
+        }
+    }
+
+    /// Apply a controller write.
+    ///
+    /// Returns the CLASP SET to forward, or `None` for characteristics that
+    /// are not bound to an address (such as Identify).
+    pub fn write(
+        &mut self,
+        aid: u64,
+        iid: u64,
+        value: &JsonValue,
+    ) -> Result<Option<(String, Value)>, HapStatus> {
+        let ch = self.find_mut(aid, iid).ok_or(HapStatus::NotFound)?;
+        if !ch.ty.writable() {
+            return Err(HapStatus::ReadOnly);
+        }
+        let Some((address, conversion)) = ch.binding.clone() else {
+            return Ok(None);
+        };
+        let clasp_value = conversion.to_clasp(value).ok_or(HapStatus::InvalidValue)?;
+        // Store the normalized HAP value so reads reflect the write immediately
+        if let Some(normalized) = conversion.to_hap(&clasp_value) {
+            ch.value = normalized;
+        }
+        Ok(Some((address, clasp_value)))
+    }
+
+    /// Apply a CLASP update and return the characteristics whose value changed.
+    pub fn update_from_clasp(&mut self, address: &str, value: &Value) -> Vec<CharacteristicEvent> {
+        let Some(targets) = self.by_address.get(address).cloned() else {
+            return Vec::new();
+        };
+        let mut events = Vec::new();
+        for (aid, iid) in targets {
+            let Some(ch) = self.find_mut(aid, iid) else {
+                continue;
+            };
+            let Some((_, conversion)) = ch.binding else {
+                continue;
+            };
+            if let Some(hap) = conversion.to_hap(value) {
+                if ch.value != hap {
+                    ch.value = hap.clone();
+                    events.push(CharacteristicEvent {
+                        aid,
+                        iid,
+                        value: hap,
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+fn merge(target: &mut JsonValue, extra: JsonValue) {
+    if let (Some(target), JsonValue::Object(extra)) = (target.as_object_mut(), extra) {
+        target.extend(extra);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db() -> AccessoryDatabase {
+        let config = HomeKitConfig::from_json(
+            r#"{
+                "setup_code": "031-45-154",
+                "accessories": [
+                    { "name": "Wash", "kind": "lightbulb", "on": "/wash/on",
+                      "level": "/wash/level", "level_range": [0, 255] },
+                    { "name": "Booth", "kind": "temperature_sensor",
+                      "temperature": "/booth/temp", "temperature_unit": "fahrenheit" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        AccessoryDatabase::from_config(&config)
+    }
+
+    #[test]
+    fn layout_assigns_stable_ids() {
+        let json = db().to_json();
+        let accessories = json["accessories"].as_array().unwrap();
+        assert_eq!(accessories.len(), 3);
+        assert_eq!(accessories[0]["aid"], 1);
+        assert_eq!(accessories[0]["services"][0]["type"], "3E");
+
+        let light = &accessories[1];
+        assert_eq!(light["aid"], 2);
+        let service = &light["services"][1];
+        assert_eq!(service["type"], "43");
+        assert_eq!(service["primary"], true);
+        assert_eq!(service["characteristics"][0]["type"], "25");
+        assert_eq!(service["characteristics"][1]["unit"], "percentage");
+
+        // Identify is write-only and carries no value
+        assert!(light["services"][0]["characteristics"][0]
+            .get("value")
+            .is_none());
+    }
+
+    #[test]
+    fn config_hash_ignores_values() {
+        let mut db = db();
+        let before = db.config_hash();
+        db.update_from_clasp("/wash/on", &Value::Bool(true));
+        assert_eq!(db.config_hash(), before);
+    }
+
+    #[test]
+    fn clasp_updates_convert_and_report_changes() {
+        let mut db = db();
+        let events = db.update_from_clasp("/wash/level", &Value::Int(255));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].value, json!(100));
+        assert!(db
+            .update_from_clasp("/wash/level", &Value::Float(255.0))
+            .is_empty());
+
+        let events = db.update_from_clasp("/booth/temp", &Value::Float(212.0));
+        assert_eq!(events[0].value, json!(100.0));
+        assert!(db.update_from_clasp("/unmapped", &Value::Int(1)).is_empty());
+    }
+
+    #[test]
+    fn controller_writes_map_to_clasp_values() {
+        let mut db = db();
+        let on_iid = 9;
+        let level_iid = 10;
+        assert_eq!(
+            db.write(2, on_iid, &json!(1)).unwrap(),
+            Some(("/wash/on".into(), Value::Bool(true)))
+        );
+        assert_eq!(
+            db.write(2, level_iid, &json!(50)).unwrap(),
+            Some(("/wash/level".into(), Value::Float(127.5)))
+        );
+        assert_eq!(db.read(2, level_iid, false).unwrap()["value"], json!(50));
+    }
+
+    #[test]
+    fn invalid_writes_return_hap_status() {
+        let mut db = db();
+        assert_eq!(db.write(2, 10, &json!(150)), Err(HapStatus::InvalidValue));
+        assert_eq!(db.write(3, 9, &json!(20)), Err(HapStatus::ReadOnly));
+        assert_eq!(db.write(9, 1, &json!(true)), Err(HapStatus::NotFound));
+        assert_eq!(db.write(2, 2, &json!(true)), Ok(None));
+        assert_eq!(db.read(2, 2, false), Err(HapStatus::WriteOnly));
+        assert_eq!(
+            db.check_events(2, 3),
+            Err(HapStatus::NotificationUnsupported)
+        );
+    }
+}
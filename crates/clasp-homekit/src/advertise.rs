@@ -0,0 +1,103 @@
+//! Bonjour advertisement of the `_hap._tcp` service.
+//!
+//! The Home app discovers accessories through these TXT records. `sf`
+//! (status flags) tells iOS whether the bridge is still waiting to be
+//! paired, so it is re-announced whenever the pairing state changes.
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use tracing::info;
+
+use crate::error::{HomeKitError, Result};
+use crate::setup::{setup_hash, CATEGORY_BRIDGE};
+
+const SERVICE_TYPE: &str = "_hap._tcp.local.";
+
+/// Advertises the HAP server over mDNS.
+pub struct HapAdvertiser {
+    mdns: ServiceDaemon,
+    name: String,
+    port: u16,
+    device_id: String,
+    setup_id: String,
+    config_number: u32,
+    fullname: Option<String>,
+}
+
+impl HapAdvertiser {
+    /// Create an advertiser; nothing is announced until [`announce`](Self::announce).
+    pub fn new(
+        name: &str,
+        port: u16,
+        device_id: &str,
+        setup_id: &str,
+        config_number: u32,
+    ) -> Result<Self> {
+        let mdns = ServiceDaemon::new().map_err(|e| HomeKitError::Mdns(e.to_string()))?;
+        Ok(Self {
+            mdns,
+            name: name.to_string(),
+            port,
+            device_id: device_id.to_string(),
+            setup_id: setup_id.to_string(),
+            config_number,
+            fullname: None,
+        })
+    }
+
+    /// Announce (or re-announce) the service with the given pairing state.
+    pub fn announce(&mut self, paired: bool) -> Result<()> {
+        let config_number = self.config_number.to_string();
+        let category = CATEGORY_BRIDGE.to_string();
+        let status = if paired { "0" } else { "1" };
+        let hash = setup_hash(&self.setup_id, &self.device_id);
+        let properties: &[(&str, &str)] = &[
+            ("c#", &config_number),
+            ("ff", "0"),
+            ("id", &self.device_id),
+            ("md", &self.name),
+            ("pv", "1.1"),
+            ("s#", "1"),
+            ("sf", status),
+            ("ci", &category),
+            ("sh", &hash),
+        ];
+
+        let host = hostname::get()
+            .map(|h| h.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "clasp-homekit".to_string());
+        let service_info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &self.name,
+            &format!("{}.local.", host),
+            "",
+            self.port,
+            properties,
+        )
+        .map_err(|e| HomeKitError::Mdns(e.to_string()))?
+        .enable_addr_auto();
+
+        self.fullname = Some(service_info.get_fullname().to_string());
+        self.mdns
+            .register(service_info)
+            .map_err(|e| HomeKitError::Mdns(e.to_string()))?;
+
+        info!(name = %self.name, port = self.port, paired, "Advertising HomeKit bridge");
+        Ok(())
+    }
+
+    /// Stop advertising.
+    pub fn stop(&mut self) -> Result<()> {
+        if let Some(fullname) = self.fullname.take() {
+            self.mdns
+                .unregister(&fullname)
+                .map_err(|e| HomeKitError::Mdns(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HapAdvertiser {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
@@ -0,0 +1,254 @@
+//! Top-level bridge that ties the HAP server to CLASP.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::accessory::AccessoryDatabase;
+use crate::config::HomeKitConfig;
+use crate::error::{HomeKitError, Result};
+use crate::pairing::PairingContext;
+use crate::server::HapServer;
+use crate::setup::CATEGORY_BRIDGE;
+use crate::store::PairingStore;
+use crate::traits::{SignalReceiver, SignalSender};
+
+/// Bidirectional bridge between HomeKit controllers and CLASP.
+///
+/// Values published on mapped CLASP addresses update the matching
+/// characteristics and are pushed to subscribed controllers. Writes from
+/// the Home app are converted and sent back to CLASP as SETs.
+pub struct HomeKitBridge {
+    config: HomeKitConfig,
+    advertise: bool,
+    bind: Option<SocketAddr>,
+}
+
+impl HomeKitBridge {
+    /// Create a bridge, validating the configuration.
+    pub fn new(config: HomeKitConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            advertise: cfg!(feature = "mdns"),
+            bind: None,
+        })
+    }
+
+    /// Disable the Bonjour advertisement (e.g. when another responder announces it).
+    pub fn without_advertisement(mut self) -> Self {
+        self.advertise = false;
+        self
+    }
+
+    /// Override the listen address (default: `0.0.0.0:<config.port>`).
+    pub fn with_bind_addr(mut self, addr: SocketAddr) -> Self {
+        self.bind = Some(addr);
+        self
+    }
+
+    /// Start the bridge.
+    ///
+    /// Serves HAP and relays values until `shutdown` is set.
+    pub async fn run(
+        self,
+        sender: Arc<dyn SignalSender>,
+        receiver: Arc<dyn SignalReceiver>,
+        shutdown: Arc<AtomicBool>,
+    ) -> Result<()> {
+        let setup_code = self.config.setup_code()?;
+        let mut store = match &self.config.store {
+            Some(path) => PairingStore::open(path)?,
+            None => {
+                warn!("No HomeKit store configured; pairings will be lost on restart");
+                PairingStore::in_memory()
+            }
+        };
+
+        let db = AccessoryDatabase::from_config(&self.config);
+        if store.update_config_hash(&db.config_hash())? {
+            info!(
+                config_number = store.config_number(),
+                "HomeKit accessory layout changed"
+            );
+        }
+        let device_id = store.device_id().to_string();
+        let config_number = store.config_number();
+        let addresses = db.addresses();
+
+        if !store.is_paired() {
+            info!(
+                setup_code = %setup_code,
+                setup_uri = %setup_code.setup_uri(CATEGORY_BRIDGE, &self.config.setup_id),
+                "HomeKit bridge ready to pair"
+            );
+        }
+
+        let server = HapServer::new(db, PairingContext::new(store, setup_code), sender);
+        let bind = self
+            .bind
+            .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], self.config.port)));
+        let listener = TcpListener::bind(bind).await?;
+        let port = listener.local_addr()?.port();
+        info!(%bind, accessories = self.config.accessories.len(), "HomeKit bridge listening");
+
+        let mut tasks = vec![tokio::spawn({
+            let server = server.clone();
+            async move {
+                if let Err(e) = server.serve(listener).await {
+                    warn!(error = %e, "HAP server stopped");
+                }
+            }
+        })];
+
+        // CLASP -> HomeKit
+        for address in addresses {
+            let mut rx = receiver
+                .subscribe(&address)
+                .await
+                .map_err(|e| HomeKitError::Subscription(e.to_string()))?;
+            let server = server.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Some((address, value)) = rx.recv().await {
+                    server.apply_clasp_update(&address, &value);
+                }
+            }));
+        }
+
+        if self.advertise {
+            tasks.push(self.spawn_advertiser(&server, port, &device_id, config_number)?);
+        }
+
+        while !shutdown.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        info!("HomeKit bridge stopping");
+        for task in tasks {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "mdns")]
+    fn spawn_advertiser(
+        &self,
+        server: &HapServer,
+        port: u16,
+        device_id: &str,
+        config_number: u32,
+    ) -> Result<tokio::task::JoinHandle<()>> {
+        let mut advertiser = crate::advertise::HapAdvertiser::new(
+            &self.config.name,
+            port,
+            device_id,
+            &self.config.setup_id,
+            config_number,
+        )?;
+        let mut paired = server.paired();
+        advertiser.announce(*paired.borrow())?;
+
+        Ok(tokio::spawn(async move {
+            while paired.changed().await.is_ok() {
+                let is_paired = *paired.borrow();
+                if let Err(e) = advertiser.announce(is_paired) {
+                    warn!(error = %e, "failed to update HomeKit advertisement");
+                }
+            }
+        }))
+    }
+
+    #[cfg(not(feature = "mdns"))]
+    fn spawn_advertiser(
+        &self,
+        _server: &HapServer,
+        _port: u16,
+        _device_id: &str,
+        _config_number: u32,
+    ) -> Result<tokio::task::JoinHandle<()>> {
+        Err(HomeKitError::Mdns(
+            "built without the `mdns` feature".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use clasp_core::Value;
+    use tokio::sync::mpsc;
+
+    struct NullSender;
+
+    #[async_trait]
+    impl SignalSender for NullSender {
+        async fn set(
+            &self,
+            _address: &str,
+            _value: Value,
+        ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Ok(())
+        }
+    }
+
+    /// Records subscribed patterns; channels stay open for the test's lifetime.
+    #[derive(Default)]
+    struct RecordingReceiver {
+        patterns: parking_lot::Mutex<Vec<String>>,
+        senders: parking_lot::Mutex<Vec<mpsc::Sender<(String, Value)>>>,
+    }
+
+    #[async_trait]
+    impl SignalReceiver for RecordingReceiver {
+        async fn subscribe(
+            &self,
+            pattern: &str,
+        ) -> std::result::Result<
+            mpsc::Receiver<(String, Value)>,
+            Box<dyn std::error::Error + Send + Sync>,
+        > {
+            let (tx, rx) = mpsc::channel(8);
+            self.patterns.lock().push(pattern.to_string());
+            self.senders.lock().push(tx);
+            Ok(rx)
+        }
+    }
+
+    #[tokio::test]
+    async fn run_subscribes_to_mapped_addresses_and_stops() {
+        let config = HomeKitConfig::from_json(
+            r#"{ "setup_code": "031-45-154", "accessories": [
+                { "name": "Wash", "kind": "lightbulb", "on": "/wash/on", "level": "/wash/level" },
+                { "name": "Fog", "kind": "switch", "on": "/fog" }
+            ] }"#,
+        )
+        .unwrap();
+        let bridge = HomeKitBridge::new(config)
+            .unwrap()
+            .without_advertisement()
+            .with_bind_addr("127.0.0.1:0".parse().unwrap());
+
+        let receiver = Arc::new(RecordingReceiver::default());
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle =
+            tokio::spawn(bridge.run(Arc::new(NullSender), receiver.clone(), shutdown.clone()));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            receiver.patterns.lock().clone(),
+            vec!["/fog", "/wash/level", "/wash/on"]
+        );
+
+        shutdown.store(true, Ordering::Relaxed);
+        tokio::time::timeout(Duration::from_secs(2), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
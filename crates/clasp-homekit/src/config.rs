@@ -0,0 +1,270 @@
+//! Bridge configuration: identity, setup code and address mappings.
+//!
+//! ```json
+//! {
+//!   "name": "Studio Bridge",
+//!   "setup_code": "031-45-154",
+//!   "store": "homekit.json",
+//!   "accessories": [
+//!     { "name": "Stage Wash", "kind": "lightbulb",
+//!       "on": "/lights/wash/on", "level": "/lights/wash/level" },
+//!     { "name": "Fog Machine", "kind": "switch", "on": "/fx/fog/enabled" },
+//!     { "name": "Booth", "kind": "temperature_sensor",
+//!       "temperature": "/sensors/booth/temp" }
+//!   ]
+//! }
+//! ```
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{HomeKitError, Result};
+use crate::setup::{validate_setup_id, SetupCode};
+
+/// Maximum bridged accessories (HAP allows 150 including the bridge itself).
+pub const MAX_ACCESSORIES: usize = 149;
+
+fn default_name() -> String {
+    "CLASP Bridge".to_string()
+}
+
+fn default_setup_id() -> String {
+    "CLSP".to_string()
+}
+
+fn default_port() -> u16 {
+    51826
+}
+
+fn default_level_range() -> [f64; 2] {
+    [0.0, 1.0]
+}
+
+/// Top-level HomeKit bridge configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HomeKitConfig {
+    /// Bridge name shown in the Home app.
+    #[serde(default = "default_name")]
+    pub name: String,
+    /// Eight digit setup code (`XXX-XX-XXX`).
+    pub setup_code: String,
+    /// Four character setup ID embedded in the QR payload.
+    #[serde(default = "default_setup_id")]
+    pub setup_id: String,
+    /// TCP port for the HAP server.
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Path of the pairing store. Without one, pairings are lost on restart.
+    #[serde(default)]
+    pub store: Option<PathBuf>,
+    /// Accessories exposed through the bridge.
+    ///
+    /// Accessory IDs follow list order, so append new entries rather than
+    /// reordering, or the Home app will treat moved accessories as new ones.
+    #[serde(default)]
+    pub accessories: Vec<AccessoryConfig>,
+}
+
+/// Kind of accessory, which decides its HAP service and characteristics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessoryKind {
+    /// On/off switch (`on`).
+    Switch,
+    /// Power outlet (`on`).
+    Outlet,
+    /// Light with on/off (`on`) and optional dimming (`level`).
+    Lightbulb,
+    /// Read-only temperature sensor (`temperature`).
+    TemperatureSensor,
+}
+
+/// Unit of the CLASP temperature value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+/// Maps one accessory to CLASP addresses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessoryConfig {
+    /// Accessory name shown in the Home app.
+    pub name: String,
+    /// Accessory kind.
+    pub kind: AccessoryKind,
+    /// Address carrying the on/off state.
+    #[serde(default)]
+    pub on: Option<String>,
+    /// Address carrying the dimmer level.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Address carrying the temperature reading.
+    #[serde(default)]
+    pub temperature: Option<String>,
+    /// CLASP value range that maps to 0-100% brightness.
+    #[serde(default = "default_level_range")]
+    pub level_range: [f64; 2],
+    /// Unit of the CLASP temperature value (HomeKit always uses Celsius).
+    #[serde(default)]
+    pub temperature_unit: TemperatureUnit,
+}
+
+impl HomeKitConfig {
+    /// Parse a JSON configuration.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: Self =
+            serde_json::from_str(json).map_err(|e| HomeKitError::Config(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parsed setup code.
+    pub fn setup_code(&self) -> Result<SetupCode> {
+        SetupCode::parse(&self.setup_code)
+    }
+
+    /// Check the configuration for errors that would only surface at pairing time.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(HomeKitError::Config("name must not be empty".into()));
+        }
+        self.setup_code()?;
+        validate_setup_id(&self.setup_id)?;
+        if self.accessories.len() > MAX_ACCESSORIES {
+            return Err(HomeKitError::Config(format!(
+                "at most {} accessories can be bridged",
+                MAX_ACCESSORIES
+            )));
+        }
+        let mut names = std::collections::HashSet::new();
+        for accessory in &self.accessories {
+            accessory.validate()?;
+            if !names.insert(accessory.name.as_str()) {
+                return Err(HomeKitError::Config(format!(
+                    "duplicate accessory name {:?}",
+                    accessory.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AccessoryConfig {
+    fn validate(&self) -> Result<()> {
+        let err = |msg: &str| HomeKitError::Config(format!("accessory {:?}: {}", self.name, msg));
+
+        if self.name.trim().is_empty() {
+            return Err(HomeKitError::Config(
+                "accessory name must not be empty".into(),
+            ));
+        }
+
+        let (required, allowed): (&[&str], &[&str]) = match self.kind {
+            AccessoryKind::Switch | AccessoryKind::Outlet => (&["on"], &["on"]),
+            AccessoryKind::Lightbulb => (&["on"], &["on", "level"]),
+            AccessoryKind::TemperatureSensor => (&["temperature"], &["temperature"]),
+        };
+
+        for (field, address) in [
+            ("on", &self.on),
+            ("level", &self.level),
+            ("temperature", &self.temperature),
+        ] {
+            match address {
+                Some(address) => {
+                    if !allowed.contains(&field) {
+                        return Err(err(&format!(
+                            "{:?} does not support `{}`",
+                            self.kind, field
+                        )));
+                    }
+                    if !address.starts_with('/') || address.contains('*') {
+                        return Err(err(&format!(
+                            "`{}` must be a concrete address, got {:?}",
+                            field, address
+                        )));
+                    }
+                }
+                None if required.contains(&field) => {
+                    return Err(err(&format!("`{}` address is required", field)));
+                }
+                None => {}
+            }
+        }
+
+        let [min, max] = self.level_range;
+        if !(min.is_finite() && max.is_finite() && min < max) {
+            return Err(err("level_range must be [min, max] with min < max"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(accessories: &str) -> Result<HomeKitConfig> {
+        HomeKitConfig::from_json(&format!(
+            r#"{{ "setup_code": "031-45-154", "accessories": {} }}"#,
+            accessories
+        ))
+    }
+
+    #[test]
+    fn defaults_are_applied() {
+        let config = parse("[]").unwrap();
+        assert_eq!(config.name, "CLASP Bridge");
+        assert_eq!(config.setup_id, "CLSP");
+        assert_eq!(config.port, 51826);
+        assert!(config.store.is_none());
+    }
+
+    #[test]
+    fn lightbulb_with_level_is_valid() {
+        let config = parse(
+            r#"[{ "name": "Wash", "kind": "lightbulb", "on": "/l/on", "level": "/l/level",
+                 "level_range": [0, 255] }]"#,
+        )
+        .unwrap();
+        assert_eq!(config.accessories[0].level_range, [0.0, 255.0]);
+    }
+
+    #[test]
+    fn missing_required_address_is_rejected() {
+        assert!(parse(r#"[{ "name": "Fog", "kind": "switch" }]"#).is_err());
+        assert!(parse(r#"[{ "name": "T", "kind": "temperature_sensor" }]"#).is_err());
+    }
+
+    #[test]
+    fn unsupported_or_wildcard_address_is_rejected() {
+        assert!(
+            parse(r#"[{ "name": "Fog", "kind": "switch", "on": "/a", "level": "/b" }]"#).is_err()
+        );
+        assert!(parse(r#"[{ "name": "Fog", "kind": "switch", "on": "/fx/*" }]"#).is_err());
+        assert!(parse(r#"[{ "name": "Fog", "kind": "switch", "on": "fx" }]"#).is_err());
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        assert!(parse(
+            r#"[{ "name": "A", "kind": "switch", "on": "/a" },
+                { "name": "A", "kind": "outlet", "on": "/b" }]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_setup_code_or_range_is_rejected() {
+        assert!(HomeKitConfig::from_json(r#"{ "setup_code": "123-45-678" }"#).is_err());
+        assert!(parse(
+            r#"[{ "name": "W", "kind": "lightbulb", "on": "/on", "level_range": [1, 1] }]"#
+        )
+        .is_err());
+    }
+}
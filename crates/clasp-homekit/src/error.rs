@@ -0,0 +1,38 @@
+//! Error types for the HomeKit bridge.
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HomeKitError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    #[error("pairing store error: {0}")]
+    Store(String),
+
+    #[error("pairing failed: {0}")]
+    Pairing(String),
+
+    #[error("malformed TLV8: {0}")]
+    Tlv(String),
+
+    #[error("session crypto error: {0}")]
+    Crypto(String),
+
+    #[error("malformed HTTP request: {0}")]
+    Http(String),
+
+    #[error("mDNS advertisement failed: {0}")]
+    Mdns(String),
+
+    #[error("signal send failed: {0}")]
+    Signal(String),
+
+    #[error("subscription failed: {0}")]
+    Subscription(String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, HomeKitError>;
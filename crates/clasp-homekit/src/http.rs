@@ -0,0 +1,208 @@
+//! Minimal HTTP/1.1 framing for HAP.
+//!
+//! HAP runs HTTP over a connection that switches to the encrypted transport
+//! mid-stream and pushes unsolicited `EVENT/1.0` messages, which general
+//! purpose HTTP servers do not accommodate. Requests are small and always
+//! carry a `Content-Length`, so a simple parser is sufficient.
+
+use std::collections::HashMap;
+
+use crate::error::{HomeKitError, Result};
+
+/// Largest accepted request head.
+const MAX_HEAD: usize = 8 * 1024;
+
+/// Largest accepted request body.
+const MAX_BODY: usize = 64 * 1024;
+
+pub const CONTENT_TYPE_TLV: &str = "application/pairing+tlv8";
+pub const CONTENT_TYPE_JSON: &str = "application/hap+json";
+
+/// A parsed HTTP request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Parse one request from the front of `buf`.
+///
+/// Returns `Ok(None)` until a complete request is buffered; consumed bytes
+/// are drained so pipelined requests parse on the next call.
+pub fn parse_request(buf: &mut Vec<u8>) -> Result<Option<Request>> {
+    let Some(head_end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+        if buf.len() > MAX_HEAD {
+            return Err(HomeKitError::Http("request head too large".into()));
+        }
+        return Ok(None);
+    };
+
+    let head = std::str::from_utf8(&buf[..head_end])
+        .map_err(|_| HomeKitError::Http("request head is not UTF-8".into()))?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(HomeKitError::Http(format!(
+            "bad request line {:?}",
+            request_line
+        )));
+    };
+
+    let mut content_length = 0usize;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| HomeKitError::Http("invalid Content-Length".into()))?;
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(HomeKitError::Http("request body too large".into()));
+    }
+
+    let body_start = head_end + 4;
+    if buf.len() < body_start + content_length {
+        return Ok(None);
+    }
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, parse_query(query)),
+        None => (target, HashMap::new()),
+    };
+    let request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        body: buf[body_start..body_start + content_length].to_vec(),
+    };
+    buf.drain(..body_start + content_length);
+    Ok(Some(request))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (!k.is_empty()).then(|| (k.to_string(), v.to_string()))
+        })
+        .collect()
+}
+
+/// An HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: Option<&'static str>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            content_type: None,
+            body: Vec::new(),
+        }
+    }
+
+    pub fn tlv(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: Some(CONTENT_TYPE_TLV),
+            body,
+        }
+    }
+
+    pub fn json(status: u16, body: &serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: Some(CONTENT_TYPE_JSON),
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            204 => "No Content",
+            207 => "Multi-Status",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            470 => "Connection Authorization Required",
+            500 => "Internal Server Error",
+            _ => "Unknown",
+        }
+    }
+
+    /// Serialize as an HTTP/1.1 response.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serialize(&format!("HTTP/1.1 {} {}", self.status, self.reason()), self)
+    }
+
+    /// Serialize as an unsolicited `EVENT/1.0` notification.
+    pub fn to_event_bytes(&self) -> Vec<u8> {
+        serialize("EVENT/1.0 200 OK", self)
+    }
+}
+
+fn serialize(status_line: &str, response: &Response) -> Vec<u8> {
+    let mut head = format!("{}\r\n", status_line);
+    if let Some(content_type) = response.content_type {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    head.push_str(&format!("Content-Length: {}\r\n\r\n", response.body.len()));
+    let mut out = head.into_bytes();
+    out.extend_from_slice(&response.body);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_with_body_and_query() {
+        let mut buf = b"PUT /characteristics?id=1.9,2.10&meta=1 HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\n{}GET".to_vec();
+        let request = parse_request(&mut buf).unwrap().unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/characteristics");
+        assert_eq!(request.query["id"], "1.9,2.10");
+        assert_eq!(request.query["meta"], "1");
+        assert_eq!(request.body, b"{}");
+        assert_eq!(buf, b"GET");
+    }
+
+    #[test]
+    fn waits_for_complete_request() {
+        let mut buf = b"POST /pair-setup HTTP/1.1\r\nContent-Length: 6\r\n\r\n\x06\x01".to_vec();
+        assert!(parse_request(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(b"\x01\x00\x01\x00");
+        assert_eq!(parse_request(&mut buf).unwrap().unwrap().body.len(), 6);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn rejects_oversized_body() {
+        let mut buf = b"POST / HTTP/1.1\r\nContent-Length: 999999\r\n\r\n".to_vec();
+        assert!(parse_request(&mut buf).is_err());
+    }
+
+    #[test]
+    fn serializes_response_and_event() {
+        let response = Response::json(200, &serde_json::json!({ "a": 1 }));
+        let bytes = String::from_utf8(response.to_bytes()).unwrap();
+        assert!(bytes.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/hap+json\r\n"));
+        assert!(bytes.ends_with("Content-Length: 7\r\n\r\n{\"a\":1}"));
+
+        let event = String::from_utf8(response.to_event_bytes()).unwrap();
+        assert!(event.starts_with("EVENT/1.0 200 OK\r\n"));
+    }
+}
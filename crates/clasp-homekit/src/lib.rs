@@ -0,0 +1,56 @@
+//! Apple HomeKit bridge for CLASP.
+//!
+//! Exposes selected CLASP addresses as HomeKit accessories so they show up
+//! in the Home app, Siri and home automations. The crate implements the
+//! HomeKit Accessory Protocol (HAP) over IP as a bridge accessory:
+//!
+//! - **Commissioning**: setup code and `X-HM://` QR payload, Bonjour
+//!   advertisement, SRP-based Pair Setup and per-connection Pair Verify
+//! - **Mapping**: switches, outlets and lightbulbs (on/off, brightness) and
+//!   temperature sensors, each bound to CLASP addresses with value conversion
+//! - **Bidirectional updates**: CLASP values become characteristic events;
+//!   Home app writes become CLASP SETs
+//!
+//! # Architecture
+//!
+//! The CLASP side is abstracted behind [`SignalSender`] and [`SignalReceiver`]
+//! traits so the crate can be tested without a running router. Enable the
+//! `client` feature to use [`clasp_client::Clasp`] directly.
+//!
+//! ```ignore
+//! use clasp_homekit::{HomeKitBridge, HomeKitConfig};
+//!
+//! let config = HomeKitConfig::from_json(&std::fs::read_to_string("homekit.json")?)?;
+//! let client = std::sync::Arc::new(clasp_client::Clasp::connect_to("ws://localhost:7330").await?);
+//! HomeKitBridge::new(config)?
+//!     .run(client.clone(), client, shutdown)
+//!     .await?;
+//! ```
+
+mod accessory;
+#[cfg(feature = "mdns")]
+mod advertise;
+mod bridge;
+mod config;
+mod error;
+mod http;
+mod pairing;
+mod server;
+mod session;
+mod setup;
+mod srp;
+mod store;
+mod tlv;
+mod traits;
+
+pub use accessory::{AccessoryDatabase, CharacteristicEvent, HapStatus};
+#[cfg(feature = "mdns")]
+pub use advertise::HapAdvertiser;
+pub use bridge::HomeKitBridge;
+pub use config::{AccessoryConfig, AccessoryKind, HomeKitConfig, TemperatureUnit, MAX_ACCESSORIES};
+pub use error::{HomeKitError, Result};
+pub use pairing::PairingContext;
+pub use server::HapServer;
+pub use setup::{setup_hash, SetupCode, CATEGORY_BRIDGE};
+pub use store::{Pairing, PairingStore};
+pub use traits::{SignalReceiver, SignalSender};
@@ -0,0 +1,644 @@
+//! HAP pairing state machines: Pair Setup, Pair Verify and pairing management.
+//!
+//! Pair Setup runs once per controller (the commissioning flow started by
+//! entering the setup code) and exchanges long-term Ed25519 keys over an
+//! SRP-authenticated channel. Pair Verify runs on every connection and
+//! proves both sides still hold those keys, yielding the session keys for
+//! the encrypted transport.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use parking_lot::Mutex;
+use tracing::{info, warn};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+use crate::session::{hkdf_sha512, open_message, seal_message, SecureSession};
+use crate::setup::SetupCode;
+use crate::srp::{SrpServer, PAIR_SETUP_USERNAME};
+use crate::store::{Pairing, PairingStore};
+use crate::tlv::{errors, types, Tlv};
+
+/// Failed Pair Setup attempts before the accessory refuses further tries.
+const MAX_SETUP_ATTEMPTS: u32 = 100;
+
+/// Maximum paired controllers.
+const MAX_PAIRINGS: usize = 16;
+
+const METHOD_ADD_PAIRING: u8 = 3;
+const METHOD_REMOVE_PAIRING: u8 = 4;
+const METHOD_LIST_PAIRINGS: u8 = 5;
+
+/// Accessory-wide pairing state shared by all connections.
+pub struct PairingContext {
+    store: Mutex<PairingStore>,
+    setup_code: SetupCode,
+    failed_attempts: AtomicU32,
+}
+
+impl PairingContext {
+    pub fn new(store: PairingStore, setup_code: SetupCode) -> Self {
+        Self {
+            store: Mutex::new(store),
+            setup_code,
+            failed_attempts: AtomicU32::new(0),
+        }
+    }
+
+    /// Access the underlying store.
+    pub fn store(&self) -> parking_lot::MutexGuard<'_, PairingStore> {
+        self.store.lock()
+    }
+
+    /// Whether any controller is paired.
+    pub fn is_paired(&self) -> bool {
+        self.store.lock().is_paired()
+    }
+}
+
+/// Per-connection Pair Setup progress.
+#[derive(Default)]
+pub struct PairSetupState {
+    srp: Option<SrpServer>,
+    session_key: Option<Vec<u8>>,
+}
+
+/// Per-connection Pair Verify progress.
+#[derive(Default)]
+pub struct PairVerifyState {
+    pending: Option<PendingVerify>,
+}
+
+struct PendingVerify {
+    shared: [u8; 32],
+    controller_public: [u8; 32],
+    accessory_public: [u8; 32],
+    session_key: [u8; 32],
+}
+
+/// A connection that completed Pair Verify.
+pub struct VerifiedSession {
+    pub controller_id: String,
+    pub session: SecureSession,
+}
+
+/// Outcome of a `/pairings` request.
+pub struct PairingsOutcome {
+    pub response: Tlv,
+    /// Controller whose pairing was removed; its connections must close.
+    pub removed: Option<String>,
+}
+
+fn parse_key(bytes: &[u8]) -> Option<VerifyingKey> {
+    VerifyingKey::from_bytes(bytes.try_into().ok()?).ok()
+}
+
+fn parse_signature(bytes: &[u8]) -> Option<Signature> {
+    Signature::from_slice(bytes).ok()
+}
+
+/// Handle a `POST /pair-setup` body.
+pub fn pair_setup(ctx: &PairingContext, state: &mut PairSetupState, body: &[u8]) -> Tlv {
+    let request = match Tlv::decode(body) {
+        Ok(tlv) => tlv,
+        Err(_) => return Tlv::error(2, errors::UNKNOWN),
+    };
+    match request.get_u8(types::STATE) {
+        Some(1) => setup_m2(ctx, state),
+        Some(3) => setup_m4(ctx, state, &request),
+        Some(5) => setup_m6(ctx, state, &request),
+        other => {
+            warn!(state = ?other, "unexpected pair-setup state");
+            Tlv::error(other.unwrap_or(0).saturating_add(1), errors::UNKNOWN)
+        }
+    }
+}
+
+fn setup_m2(ctx: &PairingContext, state: &mut PairSetupState) -> Tlv {
+    if ctx.is_paired() {
+        return Tlv::error(2, errors::UNAVAILABLE);
+    }
+    if ctx.failed_attempts.load(Ordering::Relaxed) >= MAX_SETUP_ATTEMPTS {
+        return Tlv::error(2, errors::MAX_TRIES);
+    }
+
+    let srp = SrpServer::new(PAIR_SETUP_USERNAME, ctx.setup_code.as_str().as_bytes());
+    let response = Tlv::new()
+        .with(types::STATE, [2])
+        .with(types::PUBLIC_KEY, srp.public_key())
+        .with(types::SALT, srp.salt().to_vec());
+    *state = PairSetupState {
+        srp: Some(srp),
+        session_key: None,
+    };
+    response
+}
+
+fn setup_m4(ctx: &PairingContext, state: &mut PairSetupState, request: &Tlv) -> Tlv {
+    let (Some(srp), Some(a_pub), Some(proof)) = (
+        state.srp.as_ref(),
+        request.get(types::PUBLIC_KEY),
+        request.get(types::PROOF),
+    ) else {
+        return Tlv::error(4, errors::UNKNOWN);
+    };
+
+    match srp.verify(a_pub, proof) {
+        Some(session) => {
+            state.session_key = Some(session.key);
+            Tlv::new()
+                .with(types::STATE, [4])
+                .with(types::PROOF, session.server_proof)
+        }
+        None => {
+            let attempts = ctx.failed_attempts.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(attempts, "pair-setup rejected: incorrect setup code");
+            *state = PairSetupState::default();
+            Tlv::error(4, errors::AUTHENTICATION)
+        }
+    }
+}
+
+fn setup_m6(ctx: &PairingContext, state: &mut PairSetupState, request: &Tlv) -> Tlv {
+    let (Some(key), Some(encrypted)) =
+        (state.session_key.take(), request.get(types::ENCRYPTED_DATA))
+    else {
+        return Tlv::error(6, errors::UNKNOWN);
+    };
+    *state = PairSetupState::default();
+
+    let encrypt_key = hkdf_sha512(&key, b"Pair-Setup-Encrypt-Salt", b"Pair-Setup-Encrypt-Info");
+    let Ok(plain) = open_message(&encrypt_key, b"PS-Msg05", encrypted) else {
+        return Tlv::error(6, errors::AUTHENTICATION);
+    };
+    let Ok(sub) = Tlv::decode(&plain) else {
+        return Tlv::error(6, errors::UNKNOWN);
+    };
+    let (Some(controller_id), Some(controller_key), Some(signature)) = (
+        sub.get(types::IDENTIFIER),
+        sub.get(types::PUBLIC_KEY).and_then(parse_key),
+        sub.get(types::SIGNATURE).and_then(parse_signature),
+    ) else {
+        return Tlv::error(6, errors::UNKNOWN);
+    };
+
+    // Controller proves possession of its long-term key
+    let controller_x = hkdf_sha512(
+        &key,
+        b"Pair-Setup-Controller-Sign-Salt",
+        b"Pair-Setup-Controller-Sign-Info",
+    );
+    let mut controller_info = controller_x.to_vec();
+    controller_info.extend_from_slice(controller_id);
+    controller_info.extend_from_slice(controller_key.as_bytes());
+    if controller_key.verify(&controller_info, &signature).is_err() {
+        return Tlv::error(6, errors::AUTHENTICATION);
+    }
+
+    let controller_id = String::from_utf8_lossy(controller_id).into_owned();
+    let mut store = ctx.store();
+    if store.is_paired() {
+        // Another controller completed setup while this one was in progress
+        return Tlv::error(6, errors::UNAVAILABLE);
+    }
+    if let Err(e) = store.add_pairing(Pairing::new(
+        &controller_id,
+        controller_key.as_bytes(),
+        true,
+    )) {
+        warn!(error = %e, "failed to persist pairing");
+        return Tlv::error(6, errors::UNKNOWN);
+    }
+    ctx.failed_attempts.store(0, Ordering::Relaxed);
+
+    // Accessory proves its own long-term key in return
+    let signing_key = store.signing_key();
+    let device_id = store.device_id().to_string();
+    drop(store);
+
+    let accessory_x = hkdf_sha512(
+        &key,
+        b"Pair-Setup-Accessory-Sign-Salt",
+        b"Pair-Setup-Accessory-Sign-Info",
+    );
+    let accessory_public = signing_key.verifying_key();
+    let mut accessory_info = accessory_x.to_vec();
+    accessory_info.extend_from_slice(device_id.as_bytes());
+    accessory_info.extend_from_slice(accessory_public.as_bytes());
+    let signature = signing_key.sign(&accessory_info);
+
+    let sub = Tlv::new()
+        .with(types::IDENTIFIER, device_id.into_bytes())
+        .with(types::PUBLIC_KEY, accessory_public.as_bytes().to_vec())
+        .with(types::SIGNATURE, signature.to_bytes().to_vec());
+
+    info!(controller = %controller_id, "HomeKit controller paired");
+    Tlv::new().with(types::STATE, [6]).with(
+        types::ENCRYPTED_DATA,
+        seal_message(&encrypt_key, b"PS-Msg06", &sub.encode()),
+    )
+}
+
+/// Handle a `POST /pair-verify` body.
+///
+/// Returns the response and, after a successful M3, the verified session.
+pub fn pair_verify(
+    ctx: &PairingContext,
+    state: &mut PairVerifyState,
+    body: &[u8],
+) -> (Tlv, Option<VerifiedSession>) {
+    let request = match Tlv::decode(body) {
+        Ok(tlv) => tlv,
+        Err(_) => return (Tlv::error(2, errors::UNKNOWN), None),
+    };
+    match request.get_u8(types::STATE) {
+        Some(1) => (verify_m2(ctx, state, &request), None),
+        Some(3) => verify_m4(ctx, state, &request),
+        other => (
+            Tlv::error(other.unwrap_or(0).saturating_add(1), errors::UNKNOWN),
+            None,
+        ),
+    }
+}
+
+fn verify_m2(ctx: &PairingContext, state: &mut PairVerifyState, request: &Tlv) -> Tlv {
+    let Some(controller_public) = request
+        .get(types::PUBLIC_KEY)
+        .and_then(|k| <[u8; 32]>::try_from(k).ok())
+    else {
+        return Tlv::error(2, errors::UNKNOWN);
+    };
+
+    let secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+    let accessory_public = PublicKey::from(&secret).to_bytes();
+    let shared = secret
+        .diffie_hellman(&PublicKey::from(controller_public))
+        .to_bytes();
+
+    let (signing_key, device_id) = {
+        let store = ctx.store();
+        (store.signing_key(), store.device_id().to_string())
+    };
+    let mut accessory_info = accessory_public.to_vec();
+    accessory_info.extend_from_slice(device_id.as_bytes());
+    accessory_info.extend_from_slice(&controller_public);
+    let signature = signing_key.sign(&accessory_info);
+
+    let sub = Tlv::new()
+        .with(types::IDENTIFIER, device_id.into_bytes())
+        .with(types::SIGNATURE, signature.to_bytes().to_vec());
+    let session_key = hkdf_sha512(
+        &shared,
+        b"Pair-Verify-Encrypt-Salt",
+        b"Pair-Verify-Encrypt-Info",
+    );
+
+    state.pending = Some(PendingVerify {
+        shared,
+        controller_public,
+        accessory_public,
+        session_key,
+    });
+
+    Tlv::new()
+        .with(types::STATE, [2])
+        .with(types::PUBLIC_KEY, accessory_public.to_vec())
+        .with(
+            types::ENCRYPTED_DATA,
+            seal_message(&session_key, b"PV-Msg02", &sub.encode()),
+        )
+}
+
+fn verify_m4(
+    ctx: &PairingContext,
+    state: &mut PairVerifyState,
+    request: &Tlv,
+) -> (Tlv, Option<VerifiedSession>) {
+    let fail = |code| (Tlv::error(4, code), None);
+
+    let (Some(pending), Some(encrypted)) =
+        (state.pending.take(), request.get(types::ENCRYPTED_DATA))
+    else {
+        return fail(errors::UNKNOWN);
+    };
+    let Ok(plain) = open_message(&pending.session_key, b"PV-Msg03", encrypted) else {
+        return fail(errors::AUTHENTICATION);
+    };
+    let Ok(sub) = Tlv::decode(&plain) else {
+        return fail(errors::UNKNOWN);
+    };
+    let (Some(controller_id), Some(signature)) = (
+        sub.get(types::IDENTIFIER),
+        sub.get(types::SIGNATURE).and_then(parse_signature),
+    ) else {
+        return fail(errors::UNKNOWN);
+    };
+
+    let controller_id = String::from_utf8_lossy(controller_id).into_owned();
+    let Some(controller_key) = ctx
+        .store()
+        .pairing(&controller_id)
+        .and_then(|p| p.public_key_bytes())
+        .and_then(|k| parse_key(&k))
+    else {
+        warn!(controller = %controller_id, "pair-verify from unknown controller");
+        return fail(errors::AUTHENTICATION);
+    };
+
+    let mut controller_info = pending.controller_public.to_vec();
+    controller_info.extend_from_slice(controller_id.as_bytes());
+    controller_info.extend_from_slice(&pending.accessory_public);
+    if controller_key.verify(&controller_info, &signature).is_err() {
+        return fail(errors::AUTHENTICATION);
+    }
+
+    (
+        Tlv::new().with(types::STATE, [4]),
+        Some(VerifiedSession {
+            controller_id,
+            session: SecureSession::from_shared_secret(&pending.shared),
+        }),
+    )
+}
+
+/// Handle a `POST /pairings` body from a verified controller.
+pub fn pairings(ctx: &PairingContext, controller_id: &str, body: &[u8]) -> PairingsOutcome {
+    let respond = |response| PairingsOutcome {
+        response,
+        removed: None,
+    };
+    let Ok(request) = Tlv::decode(body) else {
+        return respond(Tlv::error(2, errors::UNKNOWN));
+    };
+
+    let mut store = ctx.store();
+    if !store.pairing(controller_id).is_some_and(|p| p.admin) {
+        return respond(Tlv::error(2, errors::AUTHENTICATION));
+    }
+
+    match request.get_u8(types::METHOD) {
+        Some(METHOD_ADD_PAIRING) => {
+            let (Some(id), Some(key)) = (
+                request.get(types::IDENTIFIER),
+                request.get(types::PUBLIC_KEY).filter(|k| k.len() == 32),
+            ) else {
+                return respond(Tlv::error(2, errors::UNKNOWN));
+            };
+            let id = String::from_utf8_lossy(id).into_owned();
+            let admin = request.get_u8(types::PERMISSIONS) == Some(1);
+            match store.pairing(&id) {
+                Some(existing)
+                    if existing.public_key_bytes().as_ref().map(|k| &k[..]) != Some(key) =>
+                {
+                    return respond(Tlv::error(2, errors::UNKNOWN));
+                }
+                None if store.pairings().len() >= MAX_PAIRINGS => {
+                    return respond(Tlv::error(2, errors::MAX_PEERS));
+                }
+                _ => {}
+            }
+            if store.add_pairing(Pairing::new(&id, key, admin)).is_err() {
+                return respond(Tlv::error(2, errors::UNKNOWN));
+            }
+            info!(controller = %id, admin, "HomeKit pairing added");
+            respond(Tlv::new().with(types::STATE, [2]))
+        }
+        Some(METHOD_REMOVE_PAIRING) => {
+            let Some(id) = request.get(types::IDENTIFIER) else {
+                return respond(Tlv::error(2, errors::UNKNOWN));
+            };
+            let id = String::from_utf8_lossy(id).into_owned();
+            match store.remove_pairing(&id) {
+                Ok(removed) => {
+                    if removed {
+                        info!(controller = %id, "HomeKit pairing removed");
+                    }
+                    PairingsOutcome {
+                        response: Tlv::new().with(types::STATE, [2]),
+                        removed: removed.then_some(id),
+                    }
+                }
+                Err(_) => respond(Tlv::error(2, errors::UNKNOWN)),
+            }
+        }
+        Some(METHOD_LIST_PAIRINGS) => {
+            let mut response = Tlv::new().with(types::STATE, [2]);
+            for (i, pairing) in store.pairings().iter().enumerate() {
+                if i > 0 {
+                    response.push(types::SEPARATOR, Vec::new());
+                }
+                response.push(types::IDENTIFIER, pairing.id.as_bytes().to_vec());
+                response.push(
+                    types::PUBLIC_KEY,
+                    pairing.public_key_bytes().unwrap_or_default().to_vec(),
+                );
+                response.push(types::PERMISSIONS, [pairing.admin as u8]);
+            }
+            respond(response)
+        }
+        _ => respond(Tlv::error(2, errors::UNKNOWN)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn context() -> PairingContext {
+        PairingContext::new(
+            PairingStore::in_memory(),
+            SetupCode::parse("031-45-154").unwrap(),
+        )
+    }
+
+    fn paired_context(controller: &SigningKey, admin: bool) -> PairingContext {
+        let ctx = context();
+        ctx.store()
+            .add_pairing(Pairing::new(
+                "controller",
+                controller.verifying_key().as_bytes(),
+                admin,
+            ))
+            .unwrap();
+        ctx
+    }
+
+    #[test]
+    fn setup_m1_returns_salt_and_public_key() {
+        let ctx = context();
+        let mut state = PairSetupState::default();
+        let body = Tlv::new()
+            .with(types::STATE, [1])
+            .with(types::METHOD, [0])
+            .encode();
+        let response = pair_setup(&ctx, &mut state, &body);
+        assert_eq!(response.get_u8(types::STATE), Some(2));
+        assert_eq!(response.get(types::SALT).unwrap().len(), 16);
+        assert_eq!(response.get(types::PUBLIC_KEY).unwrap().len(), 384);
+    }
+
+    #[test]
+    fn setup_is_unavailable_once_paired() {
+        let ctx = paired_context(&SigningKey::from_bytes(&[1u8; 32]), true);
+        let mut state = PairSetupState::default();
+        let body = Tlv::new().with(types::STATE, [1]).encode();
+        let response = pair_setup(&ctx, &mut state, &body);
+        assert_eq!(response.get_u8(types::ERROR), Some(errors::UNAVAILABLE));
+    }
+
+    #[test]
+    fn setup_m3_with_bad_proof_counts_attempt() {
+        let ctx = context();
+        let mut state = PairSetupState::default();
+        pair_setup(
+            &ctx,
+            &mut state,
+            &Tlv::new().with(types::STATE, [1]).encode(),
+        );
+        let body = Tlv::new()
+            .with(types::STATE, [3])
+            .with(types::PUBLIC_KEY, vec![2u8; 384])
+            .with(types::PROOF, vec![0u8; 64])
+            .encode();
+        let response = pair_setup(&ctx, &mut state, &body);
+        assert_eq!(response.get_u8(types::ERROR), Some(errors::AUTHENTICATION));
+        assert_eq!(ctx.failed_attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn verify_roundtrip_with_paired_controller() {
+        let controller = SigningKey::from_bytes(&[7u8; 32]);
+        let ctx = paired_context(&controller, true);
+        let mut state = PairVerifyState::default();
+
+        let controller_secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+        let controller_public = PublicKey::from(&controller_secret);
+        let body = Tlv::new()
+            .with(types::STATE, [1])
+            .with(types::PUBLIC_KEY, controller_public.as_bytes().to_vec())
+            .encode();
+        let (m2, session) = pair_verify(&ctx, &mut state, &body);
+        assert!(session.is_none());
+
+        // Controller side: check the accessory signature, then sign M3
+        let accessory_public: [u8; 32] = m2.get(types::PUBLIC_KEY).unwrap().try_into().unwrap();
+        let shared = controller_secret.diffie_hellman(&PublicKey::from(accessory_public));
+        let key = hkdf_sha512(
+            shared.as_bytes(),
+            b"Pair-Verify-Encrypt-Salt",
+            b"Pair-Verify-Encrypt-Info",
+        );
+        let sub = Tlv::decode(
+            &open_message(&key, b"PV-Msg02", m2.get(types::ENCRYPTED_DATA).unwrap()).unwrap(),
+        )
+        .unwrap();
+        let device_id = ctx.store().device_id().to_string();
+        assert_eq!(sub.get(types::IDENTIFIER), Some(device_id.as_bytes()));
+        let mut accessory_info = accessory_public.to_vec();
+        accessory_info.extend_from_slice(device_id.as_bytes());
+        accessory_info.extend_from_slice(controller_public.as_bytes());
+        let accessory_key = ctx.store().signing_key().verifying_key();
+        accessory_key
+            .verify(
+                &accessory_info,
+                &parse_signature(sub.get(types::SIGNATURE).unwrap()).unwrap(),
+            )
+            .unwrap();
+
+        let mut controller_info = controller_public.as_bytes().to_vec();
+        controller_info.extend_from_slice(b"controller");
+        controller_info.extend_from_slice(&accessory_public);
+        let sub = Tlv::new()
+            .with(types::IDENTIFIER, b"controller".to_vec())
+            .with(
+                types::SIGNATURE,
+                controller.sign(&controller_info).to_bytes().to_vec(),
+            );
+        let body = Tlv::new()
+            .with(types::STATE, [3])
+            .with(
+                types::ENCRYPTED_DATA,
+                seal_message(&key, b"PV-Msg03", &sub.encode()),
+            )
+            .encode();
+        let (m4, session) = pair_verify(&ctx, &mut state, &body);
+        assert_eq!(m4.get_u8(types::STATE), Some(4));
+        assert!(m4.get(types::ERROR).is_none());
+        assert_eq!(session.unwrap().controller_id, "controller");
+    }
+
+    #[test]
+    fn verify_rejects_unknown_controller() {
+        let ctx = context();
+        let mut state = PairVerifyState::default();
+        let secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+        let body = Tlv::new()
+            .with(types::STATE, [1])
+            .with(
+                types::PUBLIC_KEY,
+                PublicKey::from(&secret).as_bytes().to_vec(),
+            )
+            .encode();
+        let (m2, _) = pair_verify(&ctx, &mut state, &body);
+        let key = hkdf_sha512(
+            secret
+                .diffie_hellman(&PublicKey::from(
+                    <[u8; 32]>::try_from(m2.get(types::PUBLIC_KEY).unwrap()).unwrap(),
+                ))
+                .as_bytes(),
+            b"Pair-Verify-Encrypt-Salt",
+            b"Pair-Verify-Encrypt-Info",
+        );
+        let sub = Tlv::new()
+            .with(types::IDENTIFIER, b"stranger".to_vec())
+            .with(types::SIGNATURE, vec![0u8; 64]);
+        let body = Tlv::new()
+            .with(types::STATE, [3])
+            .with(
+                types::ENCRYPTED_DATA,
+                seal_message(&key, b"PV-Msg03", &sub.encode()),
+            )
+            .encode();
+        let (m4, session) = pair_verify(&ctx, &mut state, &body);
+        assert_eq!(m4.get_u8(types::ERROR), Some(errors::AUTHENTICATION));
+        assert!(session.is_none());
+    }
+
+    #[test]
+    fn admin_can_add_list_and_remove_pairings() {
+        let ctx = paired_context(&SigningKey::from_bytes(&[1u8; 32]), true);
+        let add = Tlv::new()
+            .with(types::STATE, [1])
+            .with(types::METHOD, [METHOD_ADD_PAIRING])
+            .with(types::IDENTIFIER, b"guest".to_vec())
+            .with(types::PUBLIC_KEY, vec![5u8; 32])
+            .with(types::PERMISSIONS, [0])
+            .encode();
+        let outcome = pairings(&ctx, "controller", &add);
+        assert!(outcome.response.get(types::ERROR).is_none());
+
+        let list = Tlv::new()
+            .with(types::STATE, [1])
+            .with(types::METHOD, [METHOD_LIST_PAIRINGS])
+            .encode();
+        let response = pairings(&ctx, "controller", &list).response.encode();
+        let decoded = Tlv::decode(&response).unwrap();
+        assert_eq!(decoded.get(types::IDENTIFIER), Some(&b"controller"[..]));
+
+        // Non-admin controllers may not manage pairings
+        assert_eq!(
+            pairings(&ctx, "guest", &list).response.get_u8(types::ERROR),
+            Some(errors::AUTHENTICATION)
+        );
+
+        let remove = Tlv::new()
+            .with(types::STATE, [1])
+            .with(types::METHOD, [METHOD_REMOVE_PAIRING])
+            .with(types::IDENTIFIER, b"guest".to_vec())
+            .encode();
+        let outcome = pairings(&ctx, "controller", &remove);
+        assert_eq!(outcome.removed.as_deref(), Some("guest"));
+        assert!(ctx.store().pairing("guest").is_none());
+    }
+}
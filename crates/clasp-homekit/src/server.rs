@@ -0,0 +1,635 @@
+//! HAP accessory server.
+//!
+//! Each controller connection starts in plaintext, where only the pairing
+//! endpoints are reachable. A successful Pair Verify switches the
+//! connection to the encrypted transport, after which the controller can
+//! read the accessory database, read and write characteristics, subscribe
+//! to events and manage pairings.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use clasp_core::Value;
+use parking_lot::RwLock;
+use serde_json::{json, Value as JsonValue};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tracing::{debug, info, warn};
+
+use crate::accessory::{AccessoryDatabase, CharacteristicEvent, HapStatus};
+use crate::error::Result;
+use crate::http::{parse_request, Request, Response};
+use crate::pairing::{
+    pair_setup, pair_verify, pairings, PairSetupState, PairVerifyState, PairingContext,
+    VerifiedSession,
+};
+use crate::session::SecureSession;
+use crate::traits::SignalSender;
+
+/// A characteristic change tagged with the connection that caused it.
+#[derive(Debug, Clone)]
+struct Notice {
+    origin: u64,
+    event: CharacteristicEvent,
+}
+
+/// Origin used for changes that arrive from CLASP.
+const ORIGIN_CLASP: u64 = 0;
+
+struct Shared {
+    pairing: PairingContext,
+    db: RwLock<AccessoryDatabase>,
+    sender: Arc<dyn SignalSender>,
+    notices: broadcast::Sender<Notice>,
+    removed: broadcast::Sender<String>,
+    paired: watch::Sender<bool>,
+    next_connection: AtomicU64,
+}
+
+/// HAP server shared by all controller connections.
+#[derive(Clone)]
+pub struct HapServer {
+    shared: Arc<Shared>,
+}
+
+impl HapServer {
+    /// Create a server for the given database and pairing state.
+    ///
+    /// Controller writes to bound characteristics are forwarded to `sender`
+    /// as CLASP SETs.
+    pub fn new(
+        db: AccessoryDatabase,
+        pairing: PairingContext,
+        sender: Arc<dyn SignalSender>,
+    ) -> Self {
+        let (notices, _) = broadcast::channel(256);
+        let (removed, _) = broadcast::channel(16);
+        let (paired, _) = watch::channel(pairing.is_paired());
+        Self {
+            shared: Arc::new(Shared {
+                pairing,
+                db: RwLock::new(db),
+                sender,
+                notices,
+                removed,
+                paired,
+                next_connection: AtomicU64::new(ORIGIN_CLASP + 1),
+            }),
+        }
+    }
+
+    /// Watch the paired flag (drives the `sf` Bonjour TXT record).
+    pub fn paired(&self) -> watch::Receiver<bool> {
+        self.shared.paired.subscribe()
+    }
+
+    /// Apply a value received from CLASP and notify subscribed controllers.
+    pub fn apply_clasp_update(&self, address: &str, value: &Value) {
+        let events = self.shared.db.write().update_from_clasp(address, value);
+        for event in events {
+            let _ = self.shared.notices.send(Notice {
+                origin: ORIGIN_CLASP,
+                event,
+            });
+        }
+    }
+
+    /// Accept controller connections until the listener fails.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let _ = stream.set_nodelay(true);
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream, peer).await {
+                    debug!(%peer, error = %e, "HAP connection closed with error");
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, mut stream: TcpStream, peer: SocketAddr) -> Result<()> {
+        let id = self.shared.next_connection.fetch_add(1, Ordering::Relaxed);
+        let mut conn = Connection {
+            id,
+            shared: Arc::clone(&self.shared),
+            secure: None,
+            controller: None,
+            setup: PairSetupState::default(),
+            verify: PairVerifyState::default(),
+            subscriptions: HashSet::new(),
+        };
+        let mut notices = self.shared.notices.subscribe();
+        let mut removed = self.shared.removed.subscribe();
+        let mut raw = Vec::new();
+        let mut plain = Vec::new();
+        let mut read_buf = [0u8; 4096];
+        debug!(%peer, id, "HAP connection opened");
+
+        loop {
+            tokio::select! {
+                n = stream.read(&mut read_buf) => {
+                    let n = n?;
+                    if n == 0 {
+                        break;
+                    }
+                    raw.extend_from_slice(&read_buf[..n]);
+                    match conn.secure.as_mut() {
+                        Some(session) => plain.extend(session.decrypt(&mut raw)?),
+                        None => plain.append(&mut raw),
+                    }
+                    while let Some(request) = parse_request(&mut plain)? {
+                        let (response, upgrade) = conn.handle(request).await;
+                        let bytes = conn.seal(response.to_bytes());
+                        stream.write_all(&bytes).await?;
+                        // The Pair Verify M4 response is the last plaintext message
+                        if let Some(verified) = upgrade {
+                            debug!(%peer, controller = %verified.controller_id, "HAP session verified");
+                            conn.controller = Some(verified.controller_id);
+                            conn.secure = Some(verified.session);
+                        }
+                        if conn.closing() {
+                            return Ok(());
+                        }
+                    }
+                }
+                notice = notices.recv() => {
+                    match notice {
+                        Ok(notice) => {
+                            if let Some(bytes) = conn.event(&notice) {
+                                stream.write_all(&bytes).await?;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            warn!(id, skipped = n, "HAP connection lagged behind events");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                controller = removed.recv() => {
+                    if let Ok(controller) = controller {
+                        if conn.controller.as_deref() == Some(controller.as_str()) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        debug!(%peer, id, "HAP connection closed");
+        Ok(())
+    }
+}
+
+struct Connection {
+    id: u64,
+    shared: Arc<Shared>,
+    secure: Option<SecureSession>,
+    controller: Option<String>,
+    setup: PairSetupState,
+    verify: PairVerifyState,
+    subscriptions: HashSet<(u64, u64)>,
+}
+
+impl Connection {
+    /// Encrypt outgoing bytes once the session is verified.
+    fn seal(&mut self, bytes: Vec<u8>) -> Vec<u8> {
+        match self.secure.as_mut() {
+            Some(session) => session.encrypt(&bytes),
+            None => bytes,
+        }
+    }
+
+    /// Whether the connection's own pairing was removed.
+    fn closing(&self) -> bool {
+        match &self.controller {
+            Some(id) => self.shared.pairing.store().pairing(id).is_none(),
+            None => false,
+        }
+    }
+
+    /// Serialize an event for this connection if it subscribed to it.
+    fn event(&mut self, notice: &Notice) -> Option<Vec<u8>> {
+        let key = (notice.event.aid, notice.event.iid);
+        if self.secure.is_none() || notice.origin == self.id || !self.subscriptions.contains(&key) {
+            return None;
+        }
+        let body = json!({ "characteristics": [{
+            "aid": notice.event.aid,
+            "iid": notice.event.iid,
+            "value": notice.event.value,
+        }]});
+        let bytes = Response::json(200, &body).to_event_bytes();
+        Some(self.seal(bytes))
+    }
+
+    async fn handle(&mut self, request: Request) -> (Response, Option<VerifiedSession>) {
+        let shared = Arc::clone(&self.shared);
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/pair-setup") => {
+                let response = pair_setup(&shared.pairing, &mut self.setup, &request.body);
+                shared.paired.send_replace(shared.pairing.is_paired());
+                (Response::tlv(response.encode()), None)
+            }
+            ("POST", "/pair-verify") => {
+                let (response, verified) =
+                    pair_verify(&shared.pairing, &mut self.verify, &request.body);
+                (Response::tlv(response.encode()), verified)
+            }
+            ("POST", "/identify") => {
+                // Only unpaired accessories may be identified without a session
+                if shared.pairing.is_paired() {
+                    let body = json!({ "status": HapStatus::InsufficientPrivileges.code() });
+                    (Response::json(400, &body), None)
+                } else {
+                    info!("HomeKit identify requested");
+                    (Response::new(204), None)
+                }
+            }
+            _ if self.secure.is_none() => {
+                let body = json!({ "status": HapStatus::InsufficientPrivileges.code() });
+                (Response::json(470, &body), None)
+            }
+            ("GET", "/accessories") => (Response::json(200, &shared.db.read().to_json()), None),
+            ("GET", "/characteristics") => (self.read_characteristics(&request), None),
+            ("PUT", "/characteristics") => (self.write_characteristics(&request).await, None),
+            ("POST", "/pairings") => {
+                let controller = self.controller.clone().unwrap_or_default();
+                let outcome = pairings(&shared.pairing, &controller, &request.body);
+                if let Some(removed) = outcome.removed {
+                    let _ = shared.removed.send(removed);
+                    shared.paired.send_replace(shared.pairing.is_paired());
+                }
+                (Response::tlv(outcome.response.encode()), None)
+            }
+            _ => (Response::new(404), None),
+        }
+    }
+
+    fn read_characteristics(&self, request: &Request) -> Response {
+        let Some(ids) = request.query.get("id").and_then(|ids| parse_ids(ids)) else {
+            return Response::new(400);
+        };
+        let meta = request.query.get("meta").is_some_and(|v| v == "1");
+        let with_ev = request.query.get("ev").is_some_and(|v| v == "1");
+
+        let db = self.shared.db.read();
+        let mut failed = false;
+        let mut results: Vec<JsonValue> = ids
+            .iter()
+            .map(|&(aid, iid)| match db.read(aid, iid, meta) {
+                Ok(mut value) => {
+                    if with_ev {
+                        value["ev"] = json!(self.subscriptions.contains(&(aid, iid)));
+                    }
+                    value
+                }
+                Err(status) => {
+                    failed = true;
+                    json!({ "aid": aid, "iid": iid, "status": status.code() })
+                }
+            })
+            .collect();
+
+        if failed {
+            // Multi-status responses carry a status on every entry
+            for result in &mut results {
+                if result.get("status").is_none() {
+                    result["status"] = json!(0);
+                }
+            }
+            Response::json(207, &json!({ "characteristics": results }))
+        } else {
+            Response::json(200, &json!({ "characteristics": results }))
+        }
+    }
+
+    async fn write_characteristics(&mut self, request: &Request) -> Response {
+        let Ok(body) = serde_json::from_slice::<JsonValue>(&request.body) else {
+            return Response::new(400);
+        };
+        let Some(writes) = body["characteristics"].as_array() else {
+            return Response::new(400);
+        };
+
+        let mut statuses = Vec::with_capacity(writes.len());
+        for write in writes {
+            let (Some(aid), Some(iid)) = (write["aid"].as_u64(), write["iid"].as_u64()) else {
+                return Response::new(400);
+            };
+            let status = self.write_one(aid, iid, write).await;
+            statuses.push((aid, iid, status));
+        }
+
+        if statuses.iter().all(|(_, _, s)| s.is_ok()) {
+            return Response::new(204);
+        }
+        let results: Vec<JsonValue> = statuses
+            .into_iter()
+            .map(|(aid, iid, status)| {
+                let code = status.err().map(HapStatus::code).unwrap_or(0);
+                json!({ "aid": aid, "iid": iid, "status": code })
+            })
+            .collect();
+        Response::json(207, &json!({ "characteristics": results }))
+    }
+
+    async fn write_one(
+        &mut self,
+        aid: u64,
+        iid: u64,
+        write: &JsonValue,
+    ) -> std::result::Result<(), HapStatus> {
+        if let Some(ev) = write.get("ev").and_then(JsonValue::as_bool) {
+            self.shared.db.read().check_events(aid, iid)?;
+            if ev {
+                self.subscriptions.insert((aid, iid));
+            } else {
+                self.subscriptions.remove(&(aid, iid));
+            }
+        }
+
+        let Some(value) = write.get("value") else {
+            return Ok(());
+        };
+        let forward = self.shared.db.write().write(aid, iid, value)?;
+        let Some((address, clasp_value)) = forward else {
+            return Ok(());
+        };
+
+        if let Err(e) = self.shared.sender.set(&address, clasp_value).await {
+            warn!(address = %address, error = %e, "failed to forward HomeKit write");
+            return Err(HapStatus::CommunicationFailure);
+        }
+
+        // Tell other controllers; the writer already knows the new value
+        if let Ok(value) = self.shared.db.read().read(aid, iid, false) {
+            let _ = self.shared.notices.send(Notice {
+                origin: self.id,
+                event: CharacteristicEvent {
+                    aid,
+                    iid,
+                    value: value["value"].clone(),
+                },
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Parse an `id=1.9,2.10` query value.
+fn parse_ids(ids: &str) -> Option<Vec<(u64, u64)>> {
+    ids.split(',')
+        .map(|pair| {
+            let (aid, iid) = pair.split_once('.')?;
+            Some((aid.parse().ok()?, iid.parse().ok()?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HomeKitConfig;
+    use crate::setup::SetupCode;
+    use crate::store::{Pairing, PairingStore};
+    use crate::tlv::{types, Tlv};
+    use async_trait::async_trait;
+    use ed25519_dalek::{Signer, SigningKey};
+    use x25519_dalek::{EphemeralSecret, PublicKey};
+
+    #[derive(Default)]
+    struct RecordingSender {
+        sets: parking_lot::Mutex<Vec<(String, Value)>>,
+    }
+
+    #[async_trait]
+    impl SignalSender for RecordingSender {
+        async fn set(
+            &self,
+            address: &str,
+            value: Value,
+        ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.sets.lock().push((address.to_string(), value));
+            Ok(())
+        }
+    }
+
+    struct Controller {
+        stream: TcpStream,
+        session: SecureSession,
+        buf: Vec<u8>,
+    }
+
+    impl Controller {
+        /// Connect and run Pair Verify with an already paired key.
+        async fn connect(addr: SocketAddr, key: &SigningKey) -> Self {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+            let public = PublicKey::from(&secret);
+
+            let m1 = Tlv::new()
+                .with(types::STATE, [1])
+                .with(types::PUBLIC_KEY, public.as_bytes().to_vec())
+                .encode();
+            let m2 = Tlv::decode(&plain_post(&mut stream, "/pair-verify", &m1).await).unwrap();
+            let accessory_public: [u8; 32] = m2.get(types::PUBLIC_KEY).unwrap().try_into().unwrap();
+            let shared = secret.diffie_hellman(&PublicKey::from(accessory_public));
+            let verify_key = crate::session::hkdf_sha512(
+                shared.as_bytes(),
+                b"Pair-Verify-Encrypt-Salt",
+                b"Pair-Verify-Encrypt-Info",
+            );
+
+            let mut info = public.as_bytes().to_vec();
+            info.extend_from_slice(b"controller");
+            info.extend_from_slice(&accessory_public);
+            let sub = Tlv::new()
+                .with(types::IDENTIFIER, b"controller".to_vec())
+                .with(types::SIGNATURE, key.sign(&info).to_bytes().to_vec());
+            let m3 = Tlv::new()
+                .with(types::STATE, [3])
+                .with(
+                    types::ENCRYPTED_DATA,
+                    crate::session::seal_message(&verify_key, b"PV-Msg03", &sub.encode()),
+                )
+                .encode();
+            let m4 = Tlv::decode(&plain_post(&mut stream, "/pair-verify", &m3).await).unwrap();
+            assert_eq!(m4.get_u8(types::STATE), Some(4));
+            assert!(m4.get(types::ERROR).is_none());
+
+            Self {
+                stream,
+                session: SecureSession::from_shared_secret(shared.as_bytes()).peer(),
+                buf: Vec::new(),
+            }
+        }
+
+        async fn request(&mut self, method: &str, path: &str, body: &str) -> String {
+            let request = format!(
+                "{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                method,
+                path,
+                body.len(),
+                body
+            );
+            let sealed = self.session.encrypt(request.as_bytes());
+            self.stream.write_all(&sealed).await.unwrap();
+            self.read_message().await
+        }
+
+        async fn read_message(&mut self) -> String {
+            let mut plain = Vec::new();
+            let mut chunk = [0u8; 4096];
+            loop {
+                plain.extend(self.session.decrypt(&mut self.buf).unwrap());
+                if let Some(message) = split_message(&mut plain) {
+                    return message;
+                }
+                let n = self.stream.read(&mut chunk).await.unwrap();
+                assert!(n > 0, "connection closed");
+                self.buf.extend_from_slice(&chunk[..n]);
+            }
+        }
+    }
+
+    /// Split one complete HTTP or EVENT message off the front of `buf`.
+    fn split_message(buf: &mut Vec<u8>) -> Option<String> {
+        let head_end = buf.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+        let len: usize = head
+            .lines()
+            .find_map(|l| l.strip_prefix("Content-Length: "))
+            .map(|v| v.parse().unwrap())
+            .unwrap_or(0);
+        if buf.len() < head_end + 4 + len {
+            return None;
+        }
+        let message: Vec<u8> = buf.drain(..head_end + 4 + len).collect();
+        Some(String::from_utf8(message).unwrap())
+    }
+
+    async fn plain_post(stream: &mut TcpStream, path: &str, body: &[u8]) -> Vec<u8> {
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nContent-Type: application/pairing+tlv8\r\nContent-Length: {}\r\n\r\n",
+            path,
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+        stream.write_all(&request).await.unwrap();
+
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(head_end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                let message = String::from_utf8_lossy(&buf[..head_end]).to_string();
+                let len: usize = message
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                if buf.len() >= head_end + 4 + len {
+                    return buf[head_end + 4..head_end + 4 + len].to_vec();
+                }
+            }
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0);
+            buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    async fn start() -> (HapServer, SocketAddr, SigningKey, Arc<RecordingSender>) {
+        let config = HomeKitConfig::from_json(
+            r#"{ "setup_code": "031-45-154", "accessories": [
+                { "name": "Wash", "kind": "lightbulb", "on": "/wash/on", "level": "/wash/level" }
+            ] }"#,
+        )
+        .unwrap();
+        let key = SigningKey::from_bytes(&[4u8; 32]);
+        let mut store = PairingStore::in_memory();
+        store
+            .add_pairing(Pairing::new(
+                "controller",
+                key.verifying_key().as_bytes(),
+                true,
+            ))
+            .unwrap();
+        let sender = Arc::new(RecordingSender::default());
+        let server = HapServer::new(
+            AccessoryDatabase::from_config(&config),
+            PairingContext::new(store, SetupCode::parse("031-45-154").unwrap()),
+            sender.clone(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.clone().serve(listener));
+        (server, addr, key, sender)
+    }
+
+    #[tokio::test]
+    async fn unverified_connections_are_refused() {
+        let (_, addr, _, _) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /accessories HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = [0u8; 256];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 470"));
+    }
+
+    #[tokio::test]
+    async fn verified_controller_reads_and_writes() {
+        let (_, addr, key, sender) = start().await;
+        let mut controller = Controller::connect(addr, &key).await;
+
+        let response = controller.request("GET", "/accessories", "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"type\":\"43\""));
+
+        let response = controller
+            .request(
+                "PUT",
+                "/characteristics",
+                r#"{"characteristics":[{"aid":2,"iid":10,"value":40}]}"#,
+            )
+            .await;
+        assert!(response.starts_with("HTTP/1.1 204"));
+        assert_eq!(
+            sender.sets.lock().clone(),
+            vec![("/wash/level".to_string(), Value::Float(0.4))]
+        );
+
+        let response = controller
+            .request("GET", "/characteristics?id=2.10,9.9", "")
+            .await;
+        assert!(response.starts_with("HTTP/1.1 207"));
+        assert!(response.contains("\"value\":40"));
+        assert!(response.contains("-70409"));
+    }
+
+    #[tokio::test]
+    async fn clasp_updates_reach_subscribed_controllers() {
+        let (server, addr, key, _) = start().await;
+        let mut controller = Controller::connect(addr, &key).await;
+
+        let response = controller
+            .request(
+                "PUT",
+                "/characteristics",
+                r#"{"characteristics":[{"aid":2,"iid":9,"ev":true}]}"#,
+            )
+            .await;
+        assert!(response.starts_with("HTTP/1.1 204"));
+
+        server.apply_clasp_update("/wash/on", &Value::Bool(true));
+        let event = controller.read_message().await;
+        assert!(event.starts_with("EVENT/1.0 200 OK"));
+        assert!(event.contains(r#"{"aid":2,"iid":9,"value":true}"#));
+    }
+}
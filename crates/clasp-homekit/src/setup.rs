@@ -0,0 +1,182 @@
+//! Setup codes and the commissioning payloads derived from them.
+//!
+//! A user pairs the bridge by entering its eight digit setup code in the
+//! Home app or scanning a QR code that encodes the `X-HM://` setup URI.
+
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::Rng;
+use sha2::{Digest, Sha512};
+
+use crate::error::{HomeKitError, Result};
+
+/// HAP accessory category advertised by the bridge.
+pub const CATEGORY_BRIDGE: u8 = 2;
+
+/// Codes the HAP specification forbids because they are trivially guessable.
+const FORBIDDEN: &[&str] = &[
+    "00000000", "11111111", "22222222", "33333333", "44444444", "55555555", "66666666", "77777777",
+    "88888888", "99999999", "12345678", "87654321",
+];
+
+/// An eight digit setup code, formatted `XXX-XX-XXX`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupCode(String);
+
+impl SetupCode {
+    /// Parse a code given as `XXX-XX-XXX` or eight bare digits.
+    pub fn parse(code: &str) -> Result<Self> {
+        let digits: String = code.chars().filter(|c| *c != '-').collect();
+        if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(HomeKitError::Config(format!(
+                "setup code must be eight digits (XXX-XX-XXX), got {:?}",
+                code
+            )));
+        }
+        if FORBIDDEN.contains(&digits.as_str()) {
+            return Err(HomeKitError::Config(format!(
+                "setup code {} is not allowed by HomeKit",
+                code
+            )));
+        }
+        Ok(Self(format!(
+            "{}-{}-{}",
+            &digits[..3],
+            &digits[3..5],
+            &digits[5..]
+        )))
+    }
+
+    /// Generate a random valid setup code.
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        loop {
+            let digits = format!("{:08}", rng.gen_range(0..100_000_000u32));
+            if let Ok(code) = Self::parse(&digits) {
+                return code;
+            }
+        }
+    }
+
+    /// The formatted `XXX-XX-XXX` code, which is also the SRP password.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn numeric(&self) -> u64 {
+        self.0
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse()
+            .expect("setup code holds eight digits")
+    }
+
+    /// `X-HM://` setup URI for QR codes.
+    ///
+    /// The payload packs the setup code, an "IP transport" flag and the
+    /// accessory category into a base36 string followed by the setup ID.
+    pub fn setup_uri(&self, category: u8, setup_id: &str) -> String {
+        const IP_FLAG: u64 = 1 << 28;
+        let payload = self.numeric() | IP_FLAG | ((category as u64) << 31);
+        format!("X-HM://{:0>9}{}", to_base36(payload), setup_id)
+    }
+}
+
+impl fmt::Display for SetupCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn to_base36(mut value: u64) -> String {
+    const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    if value == 0 {
+        return "0".into();
+    }
+    let mut out = Vec::new();
+    while value > 0 {
+        out.push(DIGITS[(value % 36) as usize]);
+        value /= 36;
+    }
+    out.reverse();
+    String::from_utf8(out).expect("base36 digits are ASCII")
+}
+
+/// Validate a four character setup ID (`[0-9A-Z]{4}`).
+pub fn validate_setup_id(setup_id: &str) -> Result<()> {
+    if setup_id.len() == 4
+        && setup_id
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+    {
+        Ok(())
+    } else {
+        Err(HomeKitError::Config(format!(
+            "setup_id must be four characters 0-9/A-Z, got {:?}",
+            setup_id
+        )))
+    }
+}
+
+/// Setup hash advertised as the `sh` TXT record.
+///
+/// Lets an iOS device match a scanned QR code to the Bonjour service.
+pub fn setup_hash(setup_id: &str, device_id: &str) -> String {
+    let digest = Sha512::digest(format!("{}{}", setup_id, device_id).as_bytes());
+    BASE64.encode(&digest[..4])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_both_formats() {
+        assert_eq!(SetupCode::parse("03145154").unwrap().as_str(), "031-45-154");
+        assert_eq!(
+            SetupCode::parse("031-45-154").unwrap().as_str(),
+            "031-45-154"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_and_forbidden_codes() {
+        assert!(SetupCode::parse("1234").is_err());
+        assert!(SetupCode::parse("abc-de-fgh").is_err());
+        assert!(SetupCode::parse("123-45-678").is_err());
+        assert!(SetupCode::parse("111-11-111").is_err());
+    }
+
+    #[test]
+    fn generated_codes_are_valid() {
+        for _ in 0..20 {
+            let code = SetupCode::generate();
+            assert_eq!(SetupCode::parse(code.as_str()).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn setup_uri_encodes_code_flags_and_category() {
+        let code = SetupCode::parse("518-08-582").unwrap();
+        let uri = code.setup_uri(CATEGORY_BRIDGE, "1QJ8");
+        // 51808582 | 1 << 28 | 2 << 31 = 4615211334 = "24BRZUU" in base36
+        assert_eq!(uri, "X-HM://0024BRZUU1QJ8");
+        assert_eq!(uri.len(), "X-HM://".len() + 9 + 4);
+    }
+
+    #[test]
+    fn setup_id_validation() {
+        assert!(validate_setup_id("CLSP").is_ok());
+        assert!(validate_setup_id("clsp").is_err());
+        assert!(validate_setup_id("CLASP").is_err());
+    }
+
+    #[test]
+    fn setup_hash_is_four_bytes() {
+        let hash = setup_hash("CLSP", "AA:BB:CC:DD:EE:FF");
+        assert_eq!(BASE64.decode(hash).unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,309 @@
+//! SRP-6a server side for HAP Pair Setup.
+//!
+//! HAP uses the 3072-bit group from RFC 5054 with SHA-512, the username
+//! `Pair-Setup` and the accessory setup code (`XXX-XX-XXX`) as password.
+
+use num_bigint::BigUint;
+use rand::RngCore;
+use sha2::{Digest, Sha512};
+use subtle::ConstantTimeEq;
+
+/// RFC 5054 3072-bit group prime.
+const N_HEX: &str = concat!(
+    "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+    "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+    "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+    "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+    "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+    "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+    "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+    "3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33",
+    "A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7",
+    "ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864",
+    "D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2",
+    "08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A93AD2CAFFFFFFFFFFFFFFFF",
+);
+
+/// Group generator.
+const G: u32 = 5;
+
+/// Byte length of the group prime; public values are padded to this.
+const N_LEN: usize = 384;
+
+/// Username used by HAP Pair Setup.
+pub const PAIR_SETUP_USERNAME: &[u8] = b"Pair-Setup";
+
+fn n() -> BigUint {
+    BigUint::parse_bytes(N_HEX.as_bytes(), 16).expect("valid group prime")
+}
+
+fn g() -> BigUint {
+    BigUint::from(G)
+}
+
+fn pad(value: &BigUint) -> Vec<u8> {
+    let bytes = value.to_bytes_be();
+    let mut out = vec![0u8; N_LEN.saturating_sub(bytes.len())];
+    out.extend_from_slice(&bytes);
+    out
+}
+
+fn hash(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Multiplier `k = H(N | PAD(g))`.
+fn k() -> BigUint {
+    BigUint::from_bytes_be(&hash(&[&pad(&n()), &pad(&g())]))
+}
+
+/// Private key `x = H(s | H(I | ":" | P))`.
+fn x(salt: &[u8], username: &[u8], password: &[u8]) -> BigUint {
+    let inner = hash(&[username, b":", password]);
+    BigUint::from_bytes_be(&hash(&[salt, &inner]))
+}
+
+/// Client proof `M1 = H(H(N) xor H(g) | H(I) | s | A | B | K)`.
+fn client_proof(username: &[u8], salt: &[u8], a_pub: &[u8], b_pub: &[u8], key: &[u8]) -> Vec<u8> {
+    let hn = hash(&[&n().to_bytes_be()]);
+    let hg = hash(&[&g().to_bytes_be()]);
+    let hng: Vec<u8> = hn.iter().zip(hg.iter()).map(|(a, b)| a ^ b).collect();
+    hash(&[&hng, &hash(&[username]), salt, a_pub, b_pub, key])
+}
+
+/// Result of a successful SRP exchange.
+#[derive(Debug, Clone)]
+pub struct SrpSession {
+    /// Shared session key `K = H(S)`.
+    pub key: Vec<u8>,
+    /// Server proof `M2 = H(A | M1 | K)` to return to the controller.
+    pub server_proof: Vec<u8>,
+}
+
+/// Server state for a single Pair Setup attempt.
+pub struct SrpServer {
+    username: Vec<u8>,
+    salt: [u8; 16],
+    verifier: BigUint,
+    secret: BigUint,
+    public: BigUint,
+}
+
+impl SrpServer {
+    /// Start an exchange with a fresh random salt and ephemeral secret.
+    pub fn new(username: &[u8], password: &[u8]) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; 16];
+        rng.fill_bytes(&mut salt);
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+        Self::with_secret(username, password, salt, &secret)
+    }
+
+    fn with_secret(username: &[u8], password: &[u8], salt: [u8; 16], secret: &[u8]) -> Self {
+        let n = n();
+        let verifier = g().modpow(&x(&salt, username, password), &n);
+        let secret = BigUint::from_bytes_be(secret);
+        let public = (k() * &verifier + g().modpow(&secret, &n)) % &n;
+        Self {
+            username: username.to_vec(),
+            salt,
+            verifier,
+            secret,
+            public,
+        }
+    }
+
+    /// Salt to send in Pair Setup M2.
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    /// Server public value `B`, padded to the group length.
+    pub fn public_key(&self) -> Vec<u8> {
+        pad(&self.public)
+    }
+
+    /// Verify the controller's public value `A` and proof `M1`.
+    ///
+    /// Returns `None` when the proof does not match, which means the
+    /// controller used the wrong setup code.
+    pub fn verify(&self, a_pub: &[u8], proof: &[u8]) -> Option<SrpSession> {
+        let n = n();
+        let a = BigUint::from_bytes_be(a_pub);
+        if (&a % &n) == BigUint::default() {
+            return None;
+        }
+
+        let a_padded = pad(&a);
+        let b_padded = pad(&self.public);
+        let u = BigUint::from_bytes_be(&hash(&[&a_padded, &b_padded]));
+        let s = (a * self.verifier.modpow(&u, &n)).modpow(&self.secret, &n);
+        // HAP hashes S as is, without padding it to the group length
+        let key = hash(&[&s.to_bytes_be()]);
+
+        let expected = client_proof(&self.username, &self.salt, a_pub, &b_padded, &key);
+        if !bool::from(expected.ct_eq(proof)) {
+            return None;
+        }
+
+        let server_proof = hash(&[a_pub, &expected, &key]);
+        Some(SrpSession { key, server_proof })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // HAP specification SRP test vector: RFC 5054 inputs with the 3072-bit
+    // group and SHA-512
+    const VECTOR_SALT: &str = "BEB25379D1A8581EB5A727673A2441EE";
+    const VECTOR_A: &str = "60975527035CF2AD1989806F0407210BC81EDC04E2762A56AFD529DDDA2D4393";
+    const VECTOR_B: &str = "E487CB59D31AC550471E81F00F6928E01DDA08E974A004F49E61F5D105284D20";
+    const VECTOR_V: &str = concat!(
+        "9B5E061701EA7AEB39CF6E3519655A853CF94C75CAF2555EF1FAF759BB79CB47",
+        "7014E04A88D68FFC05323891D4C205B8DE81C2F203D8FAD1B24D2C109737F1BE",
+        "BBD71F912447C4A03C26B9FAD8EDB3E780778E302529ED1EE138CCFC36D4BA31",
+        "3CC48B14EA8C22A0186B222E655F2DF5603FD75DF76B3B08FF8950069ADD03A7",
+        "54EE4AE88587CCE1BFDE36794DBAE4592B7B904F442B041CB17AEBAD1E3AEBE3",
+        "CBE99DE65F4BB1FA00B0E7AF06863DB53B02254EC66E781E3B62A8212C86BEB0",
+        "D50B5BA6D0B478D8C4E9BBCEC21765326FBD14058D2BBDE2C33045F03873E539",
+        "48D78B794F0790E48C36AED6E880F557427B2FC06DB5E1E2E1D7E661AC482D18",
+        "E528D7295EF7437295FF1A72D402771713F16876DD050AE5B7AD53CCB90855C9",
+        "3956648358ADFD966422F52498732D68D1D7FBEF10D78034AB8DCB6F0FCF885C",
+        "C2B2EA2C3E6AC86609EA058A9DA8CC63531DC915414DF568B09482DDAC1954DE",
+        "C7EB714F6FF7D44CD5B86F6BD115810930637C01D0F6013BC9740FA2C633BA89",
+    );
+    const VECTOR_A_PUB: &str = concat!(
+        "FAB6F5D2615D1E323512E7991CC37443F487DA604CA8C9230FCB04E541DCE628",
+        "0B27CA4680B0374F179DC3BDC7553FE62459798C701AD864A91390A28C93B644",
+        "ADBF9C00745B942B79F9012A21B9B78782319D83A1F8362866FBD6F46BFC0DDB",
+        "2E1AB6E4B45A9906B82E37F05D6F97F6A3EB6E182079759C4F6847837B62321A",
+        "C1B4FA68641FCB4BB98DD697A0C73641385F4BAB25B793584CC39FC8D48D4BD8",
+        "67A9A3C10F8EA12170268E34FE3BBE6FF89998D60DA2F3E4283CBEC1393D52AF",
+        "724A57230C604E9FBCE583D7613E6BFFD67596AD121A8707EEC4694495703368",
+        "6A155F644D5C5863B48F61BDBF19A53EAB6DAD0A186B8C152E5F5D8CAD4B0EF8",
+        "AA4EA5008834C3CD342E5E0F167AD04592CD8BD279639398EF9E114DFAAAB919",
+        "E14E850989224DDD98576D79385D2210902E9F9B1F2D86CFA47EE244635465F7",
+        "1058421A0184BE51DD10CC9D079E6F1604E7AA9B7CF7883C7D4CE12B06EBE160",
+        "81E23F27A231D18432D7D1BB55C28AE21FFCF005F57528D15A88881BB3BBB7FE",
+    );
+    const VECTOR_B_PUB: &str = concat!(
+        "40F57088A482D4C7733384FE0D301FDDCA9080AD7D4F6FDF09A01006C3CB6D56",
+        "2E41639AE8FA21DE3B5DBA7585B275589BDB279863C562807B2B99083CD1429C",
+        "DBE89E25BFBD7E3CAD3173B2E3C5A0B174DA6D5391E6A06E465F037A40062548",
+        "39A56BF76DA84B1C94E0AE208576156FE5C140A4BA4FFC9E38C3B07B88845FC6",
+        "F7DDDA93381FE0CA6084C4CD2D336E5451C464CCB6EC65E7D16E548A273E8262",
+        "84AF2559B6264274215960FFF47BDD63D3AFF064D6137AF769661C9D4FEE4738",
+        "2603C88EAA0980581D07758461B777E4356DDA5835198B51FEEA308D70F75450",
+        "B71675C08C7D8302FD7539DD1FF2A11CB4258AA70D234436AA42B6A0615F3F91",
+        "5D55CC3B966B2716B36E4D1A06CE5E5D2EA3BEE5A1270E8751DA45B60B997B0F",
+        "FDB0F9962FEE4F03BEE780BA0A845B1D9271421783AE6601A61EA2E342E4F2E8",
+        "BC935A409EAD19F221BD1B74E2964DD19FC845F60EFC09338B60B6B256D8CAC8",
+        "89CCA306CC370A0B18C8B886E95DA0AF5235FEF4393020D2B7F3056904759042",
+    );
+    const VECTOR_K: &str = concat!(
+        "5CBC219DB052138EE1148C71CD4498963D682549CE91CA24F098468F06015BEB",
+        "6AF245C2093F98C3651BCA83AB8CAB2B580BBF02184FEFDF26142F73DF95AC50",
+    );
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Minimal SRP client used to drive the server in tests.
+    fn client_exchange(
+        server: &SrpServer,
+        password: &[u8],
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
+        let n = n();
+        let a = BigUint::from_bytes_be(&[0x42; 32]);
+        let a_pub = pad(&g().modpow(&a, &n));
+        let b_pub = server.public_key();
+        let b = BigUint::from_bytes_be(&b_pub);
+
+        let u = BigUint::from_bytes_be(&hash(&[&a_pub, &b_pub]));
+        let x = x(server.salt(), PAIR_SETUP_USERNAME, password);
+        let kgx = (k() * g().modpow(&x, &n)) % &n;
+        let base = (b + &n - kgx) % &n;
+        let s = base.modpow(&(a + u * x), &n);
+        let key = hash(&[&s.to_bytes_be()]);
+
+        let m1 = client_proof(PAIR_SETUP_USERNAME, server.salt(), &a_pub, &b_pub, &key);
+        let m2 = hash(&[&a_pub, &m1, &key]);
+        (a_pub, m1, key, m2)
+    }
+
+    #[test]
+    fn group_prime_is_well_formed() {
+        let n = n();
+        assert_eq!(n.bits(), 3072);
+        // Fermat check catches transcription errors in the constant
+        let one = BigUint::from(1u32);
+        assert_eq!(BigUint::from(2u32).modpow(&(&n - &one), &n), one);
+    }
+
+    #[test]
+    fn matching_password_yields_shared_key() {
+        let server = SrpServer::new(PAIR_SETUP_USERNAME, b"031-45-154");
+        assert_eq!(server.public_key().len(), N_LEN);
+
+        let (a_pub, m1, key, m2) = client_exchange(&server, b"031-45-154");
+        let session = server.verify(&a_pub, &m1).expect("proof accepted");
+        assert_eq!(session.key, key);
+        assert_eq!(session.server_proof, m2);
+    }
+
+    #[test]
+    fn hap_test_vector() {
+        let salt: [u8; 16] = unhex(VECTOR_SALT).try_into().unwrap();
+        let server = SrpServer::with_secret(b"alice", b"password123", salt, &unhex(VECTOR_B));
+        assert_eq!(pad(&server.verifier), unhex(VECTOR_V));
+        assert_eq!(server.public_key(), unhex(VECTOR_B_PUB));
+
+        let a_pub = pad(&g().modpow(&BigUint::from_bytes_be(&unhex(VECTOR_A)), &n()));
+        assert_eq!(a_pub, unhex(VECTOR_A_PUB));
+
+        let key = unhex(VECTOR_K);
+        let m1 = client_proof(b"alice", &salt, &a_pub, &server.public_key(), &key);
+        let session = server.verify(&a_pub, &m1).expect("proof accepted");
+        assert_eq!(session.key, key);
+        assert_eq!(session.server_proof, hash(&[&a_pub, &m1, &key]));
+    }
+
+    #[test]
+    fn short_premaster_secret_is_hashed_unpadded() {
+        // With this secret S has a leading zero byte
+        let server = SrpServer::with_secret(PAIR_SETUP_USERNAME, b"031-45-154", [7; 16], &[34]);
+        let (a_pub, m1, key, _) = client_exchange(&server, b"031-45-154");
+        let n = n();
+        let u = BigUint::from_bytes_be(&hash(&[&a_pub, &server.public_key()]));
+        let s = (BigUint::from_bytes_be(&a_pub) * server.verifier.modpow(&u, &n))
+            .modpow(&server.secret, &n);
+        assert!(s.to_bytes_be().len() < N_LEN);
+
+        let session = server.verify(&a_pub, &m1).expect("proof accepted");
+        assert_eq!(session.key, key);
+    }
+
+    #[test]
+    fn wrong_password_is_rejected() {
+        let server = SrpServer::new(PAIR_SETUP_USERNAME, b"031-45-154");
+        let (a_pub, m1, _, _) = client_exchange(&server, b"111-22-333");
+        assert!(server.verify(&a_pub, &m1).is_none());
+    }
+
+    #[test]
+    fn zero_public_value_is_rejected() {
+        let server = SrpServer::new(PAIR_SETUP_USERNAME, b"031-45-154");
+        assert!(server.verify(&pad(&n()), &[0u8; 64]).is_none());
+    }
+}
@@ -0,0 +1,263 @@
+//! Persistent accessory identity and controller pairings.
+//!
+//! HomeKit remembers an accessory by its device ID and long-term Ed25519
+//! key, so both must survive restarts or every paired home loses the
+//! bridge. The store is a small JSON file rewritten on every change.
+
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::SigningKey;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{HomeKitError, Result};
+
+/// A paired controller (an iOS device or home hub).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pairing {
+    /// Controller pairing identifier.
+    pub id: String,
+    /// Controller long-term Ed25519 public key, base64 encoded.
+    pub public_key: String,
+    /// Admin controllers may add and remove other pairings.
+    pub admin: bool,
+}
+
+impl Pairing {
+    /// Create a pairing from raw key bytes.
+    pub fn new(id: impl Into<String>, public_key: &[u8], admin: bool) -> Self {
+        Self {
+            id: id.into(),
+            public_key: BASE64.encode(public_key),
+            admin,
+        }
+    }
+
+    /// Decoded public key bytes.
+    pub fn public_key_bytes(&self) -> Option<[u8; 32]> {
+        BASE64.decode(&self.public_key).ok()?.try_into().ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoreData {
+    device_id: String,
+    signing_key: String,
+    config_number: u32,
+    #[serde(default)]
+    config_hash: String,
+    #[serde(default)]
+    pairings: Vec<Pairing>,
+}
+
+/// Accessory identity and pairing list, optionally backed by a file.
+pub struct PairingStore {
+    path: Option<PathBuf>,
+    data: StoreData,
+}
+
+impl PairingStore {
+    /// Open the store at `path`, creating a fresh identity if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            let raw = std::fs::read_to_string(&path)?;
+            let data: StoreData = serde_json::from_str(&raw)
+                .map_err(|e| HomeKitError::Store(format!("{}: {}", path.display(), e)))?;
+            let store = Self {
+                path: Some(path),
+                data,
+            };
+            // Validate the key up front rather than at first Pair Verify
+            store.try_signing_key()?;
+            Ok(store)
+        } else {
+            let store = Self {
+                path: Some(path),
+                data: Self::fresh(),
+            };
+            store.save()?;
+            Ok(store)
+        }
+    }
+
+    /// A store that lives only in memory (identity changes on every start).
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            data: Self::fresh(),
+        }
+    }
+
+    fn fresh() -> StoreData {
+        let mut rng = rand::thread_rng();
+        let mut id = [0u8; 6];
+        rng.fill_bytes(&mut id);
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+
+        StoreData {
+            device_id: id
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(":"),
+            signing_key: BASE64.encode(seed),
+            config_number: 1,
+            config_hash: String::new(),
+            pairings: Vec::new(),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&self.data)
+            .map_err(|e| HomeKitError::Store(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn try_signing_key(&self) -> Result<SigningKey> {
+        let seed: [u8; 32] = BASE64
+            .decode(&self.data.signing_key)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| HomeKitError::Store("invalid signing key".into()))?;
+        Ok(SigningKey::from_bytes(&seed))
+    }
+
+    /// Accessory pairing identifier (`XX:XX:XX:XX:XX:XX`).
+    pub fn device_id(&self) -> &str {
+        &self.data.device_id
+    }
+
+    /// Accessory long-term signing key.
+    pub fn signing_key(&self) -> SigningKey {
+        self.try_signing_key()
+            .expect("signing key validated when the store was opened")
+    }
+
+    /// Current configuration number advertised as `c#`.
+    pub fn config_number(&self) -> u32 {
+        self.data.config_number
+    }
+
+    /// Record the accessory database hash, bumping `c#` when it changed.
+    ///
+    /// Returns `true` if the configuration number was incremented.
+    pub fn update_config_hash(&mut self, hash: &str) -> Result<bool> {
+        if self.data.config_hash == hash {
+            return Ok(false);
+        }
+        let first = self.data.config_hash.is_empty();
+        self.data.config_hash = hash.to_string();
+        if !first {
+            // c# wraps from 65535 back to 1 per the HAP specification
+            self.data.config_number = if self.data.config_number >= 65535 {
+                1
+            } else {
+                self.data.config_number + 1
+            };
+        }
+        self.save()?;
+        Ok(!first)
+    }
+
+    /// Whether at least one controller is paired.
+    pub fn is_paired(&self) -> bool {
+        !self.data.pairings.is_empty()
+    }
+
+    /// Look up a pairing by controller identifier.
+    pub fn pairing(&self, id: &str) -> Option<&Pairing> {
+        self.data.pairings.iter().find(|p| p.id == id)
+    }
+
+    /// All pairings.
+    pub fn pairings(&self) -> &[Pairing] {
+        &self.data.pairings
+    }
+
+    /// Add a pairing, or update the permissions of an existing one.
+    pub fn add_pairing(&mut self, pairing: Pairing) -> Result<()> {
+        match self.data.pairings.iter_mut().find(|p| p.id == pairing.id) {
+            Some(existing) => existing.admin = pairing.admin,
+            None => self.data.pairings.push(pairing),
+        }
+        self.save()
+    }
+
+    /// Remove a pairing. Returns `true` if it existed.
+    pub fn remove_pairing(&mut self, id: &str) -> Result<bool> {
+        let before = self.data.pairings.len();
+        self.data.pairings.retain(|p| p.id != id);
+        let removed = self.data.pairings.len() != before;
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_and_pairings_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("homekit.json");
+
+        let (device_id, public) = {
+            let mut store = PairingStore::open(&path).unwrap();
+            store
+                .add_pairing(Pairing::new("controller-1", &[3u8; 32], true))
+                .unwrap();
+            (
+                store.device_id().to_string(),
+                store.signing_key().verifying_key(),
+            )
+        };
+
+        let store = PairingStore::open(&path).unwrap();
+        assert_eq!(store.device_id(), device_id);
+        assert_eq!(store.signing_key().verifying_key(), public);
+        assert!(store.is_paired());
+        assert_eq!(
+            store.pairing("controller-1").unwrap().public_key_bytes(),
+            Some([3u8; 32])
+        );
+    }
+
+    #[test]
+    fn config_number_bumps_only_on_change() {
+        let mut store = PairingStore::in_memory();
+        assert!(!store.update_config_hash("a").unwrap());
+        assert_eq!(store.config_number(), 1);
+        assert!(!store.update_config_hash("a").unwrap());
+        assert!(store.update_config_hash("b").unwrap());
+        assert_eq!(store.config_number(), 2);
+    }
+
+    #[test]
+    fn add_existing_pairing_updates_permissions() {
+        let mut store = PairingStore::in_memory();
+        store
+            .add_pairing(Pairing::new("c", &[1u8; 32], true))
+            .unwrap();
+        store
+            .add_pairing(Pairing::new("c", &[1u8; 32], false))
+            .unwrap();
+        assert_eq!(store.pairings().len(), 1);
+        assert!(!store.pairing("c").unwrap().admin);
+        assert!(store.remove_pairing("c").unwrap());
+        assert!(!store.remove_pairing("c").unwrap());
+        assert!(!store.is_paired());
+    }
+}
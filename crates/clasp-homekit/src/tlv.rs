@@ -0,0 +1,169 @@
+//! TLV8 encoding used by the HAP pairing endpoints.
+//!
+//! Each item is a one-byte type, a one-byte length and up to 255 bytes of
+//! value. Longer values are split into consecutive fragments of the same
+//! type, which the decoder merges back together.
+
+use crate::error::{HomeKitError, Result};
+
+/// TLV8 item types from the HAP specification.
+pub mod types {
+    pub const METHOD: u8 = 0x00;
+    pub const IDENTIFIER: u8 = 0x01;
+    pub const SALT: u8 = 0x02;
+    pub const PUBLIC_KEY: u8 = 0x03;
+    pub const PROOF: u8 = 0x04;
+    pub const ENCRYPTED_DATA: u8 = 0x05;
+    pub const STATE: u8 = 0x06;
+    pub const ERROR: u8 = 0x07;
+    pub const SIGNATURE: u8 = 0x0A;
+    pub const PERMISSIONS: u8 = 0x0B;
+    pub const SEPARATOR: u8 = 0xFF;
+}
+
+/// TLV8 error codes returned to controllers.
+pub mod errors {
+    pub const UNKNOWN: u8 = 0x01;
+    pub const AUTHENTICATION: u8 = 0x02;
+    pub const MAX_PEERS: u8 = 0x04;
+    pub const MAX_TRIES: u8 = 0x05;
+    pub const UNAVAILABLE: u8 = 0x06;
+}
+
+/// An ordered list of decoded TLV8 items.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tlv {
+    items: Vec<(u8, Vec<u8>)>,
+}
+
+impl Tlv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an item (builder style).
+    pub fn with(mut self, ty: u8, value: impl Into<Vec<u8>>) -> Self {
+        self.push(ty, value);
+        self
+    }
+
+    /// Append an item.
+    pub fn push(&mut self, ty: u8, value: impl Into<Vec<u8>>) {
+        self.items.push((ty, value.into()));
+    }
+
+    /// First value with the given type.
+    pub fn get(&self, ty: u8) -> Option<&[u8]> {
+        self.items
+            .iter()
+            .find(|(t, _)| *t == ty)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// First value with the given type, as a single byte.
+    pub fn get_u8(&self, ty: u8) -> Option<u8> {
+        self.get(ty).and_then(|v| v.first().copied())
+    }
+
+    /// Decode a TLV8 buffer, merging fragmented items.
+    pub fn decode(mut buf: &[u8]) -> Result<Self> {
+        let mut items: Vec<(u8, Vec<u8>)> = Vec::new();
+        let mut last_was_full = false;
+
+        while !buf.is_empty() {
+            if buf.len() < 2 {
+                return Err(HomeKitError::Tlv("truncated header".into()));
+            }
+            let ty = buf[0];
+            let len = buf[1] as usize;
+            if buf.len() < 2 + len {
+                return Err(HomeKitError::Tlv("truncated value".into()));
+            }
+            let value = &buf[2..2 + len];
+
+            match items.last_mut() {
+                // A full 255-byte item followed by the same type is a fragment
+                Some((last_ty, last_value)) if last_was_full && *last_ty == ty => {
+                    last_value.extend_from_slice(value);
+                }
+                _ => items.push((ty, value.to_vec())),
+            }
+
+            last_was_full = len == 255;
+            buf = &buf[2 + len..];
+        }
+
+        Ok(Self { items })
+    }
+
+    /// Encode to bytes, fragmenting values longer than 255 bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (ty, value) in &self.items {
+            if value.is_empty() {
+                out.push(*ty);
+                out.push(0);
+                continue;
+            }
+            for chunk in value.chunks(255) {
+                out.push(*ty);
+                out.push(chunk.len() as u8);
+                out.extend_from_slice(chunk);
+            }
+        }
+        out
+    }
+
+    /// Build an error response for the given pairing state.
+    pub fn error(state: u8, code: u8) -> Self {
+        Self::new()
+            .with(types::STATE, [state])
+            .with(types::ERROR, [code])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_short_items() {
+        let tlv = Tlv::new()
+            .with(types::STATE, [1])
+            .with(types::IDENTIFIER, b"abc".to_vec())
+            .with(types::SEPARATOR, Vec::new());
+        let bytes = tlv.encode();
+        assert_eq!(bytes, vec![6, 1, 1, 1, 3, b'a', b'b', b'c', 0xFF, 0]);
+        assert_eq!(Tlv::decode(&bytes).unwrap(), tlv);
+    }
+
+    #[test]
+    fn long_values_are_fragmented_and_merged() {
+        let key = vec![7u8; 384];
+        let bytes = Tlv::new().with(types::PUBLIC_KEY, key.clone()).encode();
+        assert_eq!(bytes.len(), 384 + 4);
+        assert_eq!(bytes[1], 255);
+        assert_eq!(bytes[257], types::PUBLIC_KEY);
+        assert_eq!(bytes[258], 129);
+
+        let decoded = Tlv::decode(&bytes).unwrap();
+        assert_eq!(decoded.get(types::PUBLIC_KEY), Some(key.as_slice()));
+    }
+
+    #[test]
+    fn separator_keeps_repeated_items_apart() {
+        let bytes = Tlv::new()
+            .with(types::IDENTIFIER, b"a".to_vec())
+            .with(types::SEPARATOR, Vec::new())
+            .with(types::IDENTIFIER, b"b".to_vec())
+            .encode();
+        let decoded = Tlv::decode(&bytes).unwrap();
+        assert_eq!(decoded.items.len(), 3);
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        assert!(Tlv::decode(&[6]).is_err());
+        assert!(Tlv::decode(&[6, 2, 1]).is_err());
+    }
+}
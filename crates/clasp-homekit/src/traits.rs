@@ -0,0 +1,68 @@
+//! Abstraction traits for the CLASP side of the bridge.
+//!
+//! These traits decouple the bridge from a concrete CLASP client so the
+//! crate can be tested without a running router. With the `client` feature
+//! they are implemented for [`clasp_client::Clasp`].
+
+use async_trait::async_trait;
+use clasp_core::Value;
+
+/// Trait for sending signals to a CLASP router.
+#[async_trait]
+pub trait SignalSender: Send + Sync {
+    /// Send a SET signal (persistent parameter update).
+    async fn set(
+        &self,
+        address: &str,
+        value: Value,
+    ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Trait for receiving signals from a CLASP router.
+#[async_trait]
+pub trait SignalReceiver: Send + Sync {
+    /// Subscribe to a CLASP address pattern.
+    ///
+    /// Returns a channel receiver that yields `(address, value)` pairs for
+    /// every signal matching the pattern.
+    async fn subscribe(
+        &self,
+        pattern: &str,
+    ) -> std::result::Result<
+        tokio::sync::mpsc::Receiver<(String, Value)>,
+        Box<dyn std::error::Error + Send + Sync>,
+    >;
+}
+
+#[cfg(feature = "client")]
+#[async_trait]
+impl SignalSender for clasp_client::Clasp {
+    async fn set(
+        &self,
+        address: &str,
+        value: Value,
+    ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        clasp_client::Clasp::set(self, address, value)
+            .await
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(feature = "client")]
+#[async_trait]
+impl SignalReceiver for clasp_client::Clasp {
+    async fn subscribe(
+        &self,
+        pattern: &str,
+    ) -> std::result::Result<
+        tokio::sync::mpsc::Receiver<(String, Value)>,
+        Box<dyn std::error::Error + Send + Sync>,
+    > {
+        let (tx, rx) = tokio::sync::mpsc::channel(256);
+        clasp_client::Clasp::subscribe(self, pattern, move |value, address| {
+            let _ = tx.try_send((address.to_string(), value));
+        })
+        .await?;
+        Ok(rx)
+    }
+}
@@ -0,0 +1,669 @@
+//! Archival of journal segments to object storage.
+//!
+//! A [`JournalArchive`] cuts the oldest journal entries into sealed segments
+//! of `segment_entries` entries, uploads each one to a [`SegmentStore`] (see
+//! [`S3SegmentStore`](crate::s3::S3SegmentStore)) as gzip-compressed JSON
+//! lines, and then compacts them out of the local journal.
+//!
+//! Wrapping the local journal in an [`ArchivedJournal`] makes the archive
+//! transparent to readers: `query` (used for REPLAY and History) reads
+//! archived segments for the part of the range that is no longer local.
+//! Before compacting, SETs in the segment are folded into the journal
+//! snapshot, so crash recovery (snapshot + `since`) stays complete without
+//! downloading the archive.
+//!
+//! Segment keys encode their sequence and timestamp ranges, so the archive
+//! can be indexed from a single listing and no local bookkeeping is needed:
+//!
+//! ```text
+//! {prefix}{first_seq:020}-{last_seq:020}-{min_ts:020}-{max_ts:020}.jsonl.gz
+//! ```
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use clasp_core::SignalType;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use parking_lot::Mutex;
+use tokio::sync::RwLock;
+
+use crate::entry::{JournalEntry, ParamSnapshot};
+use crate::error::{JournalError, Result};
+use crate::journal::Journal;
+
+/// Object storage for sealed journal segments.
+#[async_trait]
+pub trait SegmentStore: Send + Sync {
+    /// Store an object, replacing any existing one.
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    /// Fetch an object.
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// List the keys that start with `prefix`.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Delete an object (deleting a missing key is not an error).
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// In-memory [`SegmentStore`] for testing.
+#[derive(Default)]
+pub struct MemorySegmentStore {
+    objects: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemorySegmentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SegmentStore for MemorySegmentStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.objects.lock().insert(key.to_string(), data);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        self.objects
+            .lock()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| JournalError::StorageError(format!("no such object: {}", key)))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .objects
+            .lock()
+            .keys()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.objects.lock().remove(key);
+        Ok(())
+    }
+}
+
+/// Retention rules applied to archived segments.
+#[derive(Debug, Clone, Default)]
+pub struct LifecycleConfig {
+    /// Delete segments whose newest entry is older than this
+    pub expire_after: Option<Duration>,
+}
+
+/// Journal archival settings.
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    /// Key prefix for segments in the store
+    pub prefix: String,
+    /// Entries per sealed segment
+    pub segment_entries: u32,
+    /// How often the archival task checks for sealed segments
+    pub interval: Duration,
+    /// Remove archived entries from the local journal
+    pub compact_local: bool,
+    /// Retention for archived segments
+    pub lifecycle: LifecycleConfig,
+    /// Decoded segments kept in memory for read-through queries
+    pub cache_segments: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "journal/".to_string(),
+            segment_entries: 10_000,
+            interval: Duration::from_secs(60),
+            compact_local: true,
+            lifecycle: LifecycleConfig::default(),
+            cache_segments: 4,
+        }
+    }
+}
+
+/// Location of an archived segment, parsed from its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentKey {
+    pub key: String,
+    pub first_seq: u64,
+    pub last_seq: u64,
+    /// Oldest entry timestamp (microseconds since epoch)
+    pub min_ts: u64,
+    /// Newest entry timestamp (microseconds since epoch)
+    pub max_ts: u64,
+}
+
+impl SegmentKey {
+    fn new(prefix: &str, first_seq: u64, last_seq: u64, min_ts: u64, max_ts: u64) -> Self {
+        Self {
+            key: format!(
+                "{}{:020}-{:020}-{:020}-{:020}.jsonl.gz",
+                prefix, first_seq, last_seq, min_ts, max_ts
+            ),
+            first_seq,
+            last_seq,
+            min_ts,
+            max_ts,
+        }
+    }
+
+    /// Parse a key written by [`JournalArchive`]; other objects yield `None`.
+    pub fn parse(prefix: &str, key: &str) -> Option<Self> {
+        let name = key.strip_prefix(prefix)?.strip_suffix(".jsonl.gz")?;
+        let mut parts = name.split('-').map(|p| p.parse::<u64>().ok());
+        let first_seq = parts.next()??;
+        let last_seq = parts.next()??;
+        let min_ts = parts.next()??;
+        let max_ts = parts.next()??;
+        if parts.next().is_some() || first_seq > last_seq {
+            return None;
+        }
+        Some(Self {
+            key: key.to_string(),
+            first_seq,
+            last_seq,
+            min_ts,
+            max_ts,
+        })
+    }
+
+    fn overlaps(&self, from: Option<u64>, to: Option<u64>) -> bool {
+        from.is_none_or(|f| self.max_ts >= f) && to.is_none_or(|t| self.min_ts <= t)
+    }
+}
+
+/// Archived segments in a [`SegmentStore`], with an index and a small cache.
+pub struct JournalArchive {
+    store: Arc<dyn SegmentStore>,
+    config: ArchiveConfig,
+    /// Segments sorted by first_seq (loaded from the store on first use)
+    index: RwLock<Option<Vec<SegmentKey>>>,
+    /// Recently decoded segments, most recent last
+    cache: Mutex<VecDeque<(String, Arc<Vec<JournalEntry>>)>>,
+}
+
+impl JournalArchive {
+    pub fn new(store: Arc<dyn SegmentStore>, config: ArchiveConfig) -> Self {
+        Self {
+            store,
+            config,
+            index: RwLock::new(None),
+            cache: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ArchiveConfig {
+        &self.config
+    }
+
+    /// All archived segments, oldest first.
+    pub async fn segments(&self) -> Result<Vec<SegmentKey>> {
+        if let Some(ref index) = *self.index.read().await {
+            return Ok(index.clone());
+        }
+        let mut index = self.index.write().await;
+        if index.is_none() {
+            let mut segments: Vec<SegmentKey> = self
+                .store
+                .list(&self.config.prefix)
+                .await?
+                .iter()
+                .filter_map(|key| SegmentKey::parse(&self.config.prefix, key))
+                .collect();
+            segments.sort_by_key(|s| s.first_seq);
+            *index = Some(segments);
+        }
+        Ok(index.clone().unwrap_or_default())
+    }
+
+    /// Last sequence number that has been archived (0 if none).
+    pub async fn archived_through(&self) -> Result<u64> {
+        Ok(self
+            .segments()
+            .await?
+            .iter()
+            .map(|s| s.last_seq)
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Download and decode a segment.
+    pub async fn load(&self, segment: &SegmentKey) -> Result<Arc<Vec<JournalEntry>>> {
+        if let Some((_, entries)) = self
+            .cache
+            .lock()
+            .iter()
+            .find(|(key, _)| *key == segment.key)
+        {
+            return Ok(Arc::clone(entries));
+        }
+
+        let data = self.store.get(&segment.key).await?;
+        let entries = Arc::new(decode_segment(&data)?);
+
+        if self.config.cache_segments > 0 {
+            let mut cache = self.cache.lock();
+            if cache.len() >= self.config.cache_segments {
+                cache.pop_front();
+            }
+            cache.push_back((segment.key.clone(), Arc::clone(&entries)));
+        }
+        Ok(entries)
+    }
+
+    /// Archive every sealed segment in `journal`, then apply the lifecycle.
+    ///
+    /// Returns the number of segments uploaded.
+    pub async fn archive_once(&self, journal: &dyn Journal) -> Result<usize> {
+        let mut uploaded = 0;
+        let mut after = self.archived_through().await?;
+
+        loop {
+            let entries = journal
+                .since(after, Some(self.config.segment_entries))
+                .await?;
+            // Only full segments are sealed; the tail keeps growing
+            if entries.is_empty() || entries.len() < self.config.segment_entries as usize {
+                break;
+            }
+            let segment = self.upload(&entries).await?;
+            after = segment.last_seq;
+            uploaded += 1;
+
+            if self.config.compact_local {
+                fold_into_snapshot(journal, &entries).await?;
+                journal.compact(segment.last_seq + 1).await?;
+            }
+        }
+
+        self.expire().await?;
+        Ok(uploaded)
+    }
+
+    async fn upload(&self, entries: &[JournalEntry]) -> Result<SegmentKey> {
+        let first = entries.first().map(|e| e.seq).unwrap_or_default();
+        let last = entries.last().map(|e| e.seq).unwrap_or_default();
+        let min_ts = entries
+            .iter()
+            .map(|e| e.timestamp)
+            .min()
+            .unwrap_or_default();
+        let max_ts = entries
+            .iter()
+            .map(|e| e.timestamp)
+            .max()
+            .unwrap_or_default();
+        let segment = SegmentKey::new(&self.config.prefix, first, last, min_ts, max_ts);
+
+        self.store
+            .put(&segment.key, encode_segment(entries)?)
+            .await?;
+        tracing::debug!(
+            "Archived journal seq {}..={} to {}",
+            first,
+            last,
+            segment.key
+        );
+
+        let mut index = self.index.write().await;
+        if let Some(ref mut index) = *index {
+            index.push(segment.clone());
+        }
+        Ok(segment)
+    }
+
+    async fn expire(&self) -> Result<()> {
+        let Some(expire_after) = self.config.lifecycle.expire_after else {
+            return Ok(());
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let cutoff = now.saturating_sub(expire_after).as_micros() as u64;
+
+        for segment in self.segments().await? {
+            if segment.max_ts >= cutoff {
+                continue;
+            }
+            self.store.delete(&segment.key).await?;
+            tracing::info!("Expired archived journal segment {}", segment.key);
+            if let Some(ref mut index) = *self.index.write().await {
+                index.retain(|s| s.key != segment.key);
+            }
+            self.cache.lock().retain(|(key, _)| *key != segment.key);
+        }
+        Ok(())
+    }
+
+    /// Archive sealed segments every `interval` until the task is dropped.
+    pub async fn run(self: Arc<Self>, journal: Arc<dyn Journal>) {
+        let mut interval = tokio::time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match self.archive_once(journal.as_ref()).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Archived {} journal segment(s)", n),
+                Err(e) => tracing::warn!("Journal archival failed: {}", e),
+            }
+        }
+    }
+}
+
+/// Merge the newest SET per address in `entries` into the journal snapshot,
+/// keeping snapshot values with a higher revision.
+async fn fold_into_snapshot(journal: &dyn Journal, entries: &[JournalEntry]) -> Result<()> {
+    let mut params: HashMap<String, ParamSnapshot> = journal
+        .load_snapshot()
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| (p.address.clone(), p))
+        .collect();
+
+    for entry in entries.iter().filter(|e| e.msg_type == 0x21) {
+        let Some(revision) = entry.revision else {
+            continue;
+        };
+        if params
+            .get(&entry.address)
+            .is_some_and(|p| p.revision >= revision)
+        {
+            continue;
+        }
+        params.insert(
+            entry.address.clone(),
+            ParamSnapshot {
+                address: entry.address.clone(),
+                value: entry.value.clone(),
+                revision,
+                writer: entry.author.clone(),
+                timestamp: entry.timestamp,
+            },
+        );
+    }
+
+    let params: Vec<ParamSnapshot> = params.into_values().collect();
+    journal.snapshot(&params).await?;
+    Ok(())
+}
+
+fn encode_segment(entries: &[JournalEntry]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for entry in entries {
+        serde_json::to_writer(&mut encoder, entry)
+            .map_err(|e| JournalError::SerializationError(e.to_string()))?;
+        encoder
+            .write_all(b"\n")
+            .map_err(|e| JournalError::StorageError(e.to_string()))?;
+    }
+    encoder
+        .finish()
+        .map_err(|e| JournalError::StorageError(e.to_string()))
+}
+
+fn decode_segment(data: &[u8]) -> Result<Vec<JournalEntry>> {
+    let mut json = String::new();
+    GzDecoder::new(data)
+        .read_to_string(&mut json)
+        .map_err(|e| JournalError::StorageError(format!("corrupt segment: {}", e)))?;
+    json.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| JournalError::SerializationError(e.to_string()))
+        })
+        .collect()
+}
+
+/// A journal with transparent read-through to its archive.
+///
+/// Writes, `since`, snapshots and compaction go to the local journal;
+/// `query` also reads archived segments older than the oldest local entry.
+pub struct ArchivedJournal {
+    inner: Arc<dyn Journal>,
+    archive: Arc<JournalArchive>,
+}
+
+impl ArchivedJournal {
+    pub fn new(inner: Arc<dyn Journal>, archive: Arc<JournalArchive>) -> Self {
+        Self { inner, archive }
+    }
+
+    /// The local journal (e.g. for the archival task).
+    pub fn inner(&self) -> &Arc<dyn Journal> {
+        &self.inner
+    }
+
+    pub fn archive(&self) -> &Arc<JournalArchive> {
+        &self.archive
+    }
+}
+
+#[async_trait]
+impl Journal for ArchivedJournal {
+    async fn append(&self, entry: JournalEntry) -> Result<u64> {
+        self.inner.append(entry).await
+    }
+
+    async fn append_batch(&self, entries: Vec<JournalEntry>) -> Result<Vec<u64>> {
+        self.inner.append_batch(entries).await
+    }
+
+    async fn query(
+        &self,
+        pattern: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        limit: Option<u32>,
+        types: &[SignalType],
+    ) -> Result<Vec<JournalEntry>> {
+        let limit = limit.unwrap_or(u32::MAX) as usize;
+        // Anything at or after the oldest local entry is served locally
+        let local_from_seq = match self.inner.since(0, Some(1)).await?.first() {
+            Some(entry) => entry.seq,
+            None => self.inner.latest_seq().await? + 1,
+        };
+
+        let mut results = Vec::new();
+        for segment in self.archive.segments().await? {
+            if results.len() >= limit || segment.first_seq >= local_from_seq {
+                break;
+            }
+            if !segment.overlaps(from, to) {
+                continue;
+            }
+            let entries = self.archive.load(&segment).await?;
+            results.extend(
+                entries
+                    .iter()
+                    .filter(|e| {
+                        e.seq < local_from_seq
+                            && from.is_none_or(|f| e.timestamp >= f)
+                            && to.is_none_or(|t| e.timestamp <= t)
+                            && (types.is_empty() || types.contains(&e.signal_type))
+                            && clasp_core::address::glob_match(pattern, &e.address)
+                    })
+                    .take(limit - results.len())
+                    .cloned(),
+            );
+        }
+
+        if results.len() < limit {
+            let remaining = (limit - results.len()).min(u32::MAX as usize) as u32;
+            results.extend(
+                self.inner
+                    .query(pattern, from, to, Some(remaining), types)
+                    .await?,
+            );
+        }
+        Ok(results)
+    }
+
+    async fn since(&self, seq: u64, limit: Option<u32>) -> Result<Vec<JournalEntry>> {
+        self.inner.since(seq, limit).await
+    }
+
+    async fn latest_seq(&self) -> Result<u64> {
+        self.inner.latest_seq().await
+    }
+
+    async fn snapshot(&self, state: &[ParamSnapshot]) -> Result<u64> {
+        self.inner.snapshot(state).await
+    }
+
+    async fn load_snapshot(&self) -> Result<Option<Vec<ParamSnapshot>>> {
+        self.inner.load_snapshot().await
+    }
+
+    async fn compact(&self, before_seq: u64) -> Result<u64> {
+        self.inner.compact(before_seq).await
+    }
+
+    async fn len(&self) -> Result<usize> {
+        self.inner.len().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryJournal;
+    use clasp_core::Value;
+
+    fn config(segment_entries: u32) -> ArchiveConfig {
+        ArchiveConfig {
+            segment_entries,
+            ..Default::default()
+        }
+    }
+
+    async fn fill(journal: &MemoryJournal, count: u64) {
+        for i in 1..=count {
+            journal
+                .append(JournalEntry::from_set(
+                    format!("/lights/{}", i % 3),
+                    Value::Int(i as i64),
+                    i,
+                    "s1".to_string(),
+                    i * 1000,
+                ))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_segment_key_roundtrip() {
+        let key = SegmentKey::new("j/", 1, 100, 5, 9);
+        assert_eq!(SegmentKey::parse("j/", &key.key), Some(key.clone()));
+        assert!(SegmentKey::parse("other/", &key.key).is_none());
+        assert!(SegmentKey::parse("j/", "j/notes.txt").is_none());
+        assert!(SegmentKey::parse("j/", "j/5-1-0-0.jsonl.gz").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_archives_sealed_segments_only() {
+        let journal = MemoryJournal::new(1000);
+        fill(&journal, 25).await;
+        let store = Arc::new(MemorySegmentStore::new());
+        let archive = JournalArchive::new(store.clone(), config(10));
+
+        assert_eq!(archive.archive_once(&journal).await.unwrap(), 2);
+        assert_eq!(archive.archived_through().await.unwrap(), 20);
+        assert_eq!(store.list("journal/").await.unwrap().len(), 2);
+        // Archived entries are compacted out of the local journal
+        assert_eq!(journal.len().await.unwrap(), 5);
+        // ...but their latest values survive in the snapshot
+        let snapshot = journal.load_snapshot().await.unwrap().unwrap();
+        assert_eq!(snapshot.len(), 3);
+        assert!(snapshot
+            .iter()
+            .any(|p| p.address == "/lights/2" && p.value == Value::Int(20)));
+
+        // Nothing new is sealed until the tail fills up
+        assert_eq!(archive.archive_once(&journal).await.unwrap(), 0);
+        fill(&journal, 5).await;
+        assert_eq!(archive.archive_once(&journal).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_index_rebuilt_from_store() {
+        let journal = MemoryJournal::new(1000);
+        fill(&journal, 20).await;
+        let store = Arc::new(MemorySegmentStore::new());
+        JournalArchive::new(store.clone(), config(10))
+            .archive_once(&journal)
+            .await
+            .unwrap();
+
+        // A restarted relay picks up where the archive left off
+        let archive = JournalArchive::new(store, config(10));
+        assert_eq!(archive.archived_through().await.unwrap(), 20);
+        assert_eq!(archive.archive_once(&journal).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_reads_through_archive() {
+        let local = Arc::new(MemoryJournal::new(1000));
+        fill(&local, 25).await;
+        let archive = Arc::new(JournalArchive::new(
+            Arc::new(MemorySegmentStore::new()),
+            config(10),
+        ));
+        archive.archive_once(local.as_ref()).await.unwrap();
+        let journal = ArchivedJournal::new(local.clone(), archive);
+
+        let all = journal.query("/**", None, None, None, &[]).await.unwrap();
+        let seqs: Vec<u64> = all.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (1..=25).collect::<Vec<_>>());
+
+        // Time range spanning archived and local entries, with a limit
+        let some = journal
+            .query("/lights/1", Some(5_000), Some(24_000), Some(4), &[])
+            .await
+            .unwrap();
+        let seqs: Vec<u64> = some.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![7, 10, 13, 16]);
+
+        let tail = journal
+            .query("/**", Some(21_000), None, None, &[])
+            .await
+            .unwrap();
+        assert_eq!(tail.len(), 5);
+        assert!(journal
+            .query("/**", None, None, None, &[SignalType::Event])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_expires_old_segments() {
+        let journal = MemoryJournal::new(1000);
+        fill(&journal, 10).await; // timestamps near the epoch
+        let store = Arc::new(MemorySegmentStore::new());
+        let archive = JournalArchive::new(
+            store.clone(),
+            ArchiveConfig {
+                lifecycle: LifecycleConfig {
+                    expire_after: Some(Duration::from_secs(3600)),
+                },
+                ..config(10)
+            },
+        );
+        assert_eq!(archive.archive_once(&journal).await.unwrap(), 1);
+        assert!(store.list("journal/").await.unwrap().is_empty());
+        assert!(archive.segments().await.unwrap().is_empty());
+    }
+}
@@ -15,6 +15,20 @@ pub trait Journal: Send + Sync {
     /// Append an entry to the journal. Returns the assigned sequence number.
     async fn append(&self, entry: JournalEntry) -> Result<u64>;
 
+    /// Append entries as one transaction: either all are written, with
+    /// consecutive sequence numbers, or none are. Returns the assigned
+    /// sequence numbers in order.
+    ///
+    /// The default appends one at a time, so journals that can fail partway
+    /// should override it.
+    async fn append_batch(&self, entries: Vec<JournalEntry>) -> Result<Vec<u64>> {
+        let mut seqs = Vec::with_capacity(entries.len());
+        for entry in entries {
+            seqs.push(self.append(entry).await?);
+        }
+        Ok(seqs)
+    }
+
     /// Query entries matching a pattern within a time range.
     async fn query(
         &self,
@@ -8,6 +8,12 @@
 //! - [`MemoryJournal`] -- in-memory ring buffer for dev/testing
 //! - [`SqliteJournal`] -- persistent SQLite storage (requires `sqlite` feature)
 //! - `DefraJournal` -- DefraDB P2P backend via Merkle CRDTs (see `clasp-journal-defra` crate)
+//!
+//! # Archival
+//!
+//! With the `s3` feature, [`JournalArchive`] uploads sealed segments to
+//! S3-compatible storage and [`ArchivedJournal`] reads through to them for
+//! queries spanning archived ranges.
 
 pub mod entry;
 pub mod error;
@@ -17,6 +23,11 @@ pub mod memory;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
+#[cfg(feature = "s3")]
+pub mod archive;
+#[cfg(feature = "s3")]
+pub mod s3;
+
 // Re-exports
 pub use entry::{JournalEntry, ParamSnapshot};
 pub use error::{JournalError, Result};
@@ -25,3 +36,11 @@ pub use memory::MemoryJournal;
 
 #[cfg(feature = "sqlite")]
 pub use sqlite::{BatchingSqliteJournal, SqliteJournal};
+
+#[cfg(feature = "s3")]
+pub use archive::{
+    ArchiveConfig, ArchivedJournal, JournalArchive, LifecycleConfig, MemorySegmentStore,
+    SegmentKey, SegmentStore,
+};
+#[cfg(feature = "s3")]
+pub use s3::{S3Config, S3SegmentStore};
@@ -58,6 +58,23 @@ impl Journal for MemoryJournal {
         Ok(seq)
     }
 
+    async fn append_batch(&self, batch: Vec<JournalEntry>) -> Result<Vec<u64>> {
+        let mut entries = self.entries.write();
+        let mut next_seq = self.next_seq.write();
+
+        let mut seqs = Vec::with_capacity(batch.len());
+        for mut entry in batch {
+            entry.seq = *next_seq;
+            *next_seq += 1;
+            if entries.len() >= self.capacity {
+                entries.pop_front();
+            }
+            seqs.push(entry.seq);
+            entries.push_back(entry);
+        }
+        Ok(seqs)
+    }
+
     async fn query(
         &self,
         pattern: &str,
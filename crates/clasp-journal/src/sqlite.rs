@@ -240,12 +240,9 @@ impl SqliteJournal {
             computed == expected
         }
     }
-}
 
-#[async_trait]
-impl Journal for SqliteJournal {
-    async fn append(&self, entry: JournalEntry) -> Result<u64> {
-        let conn = self.conn.lock();
+    /// Insert one entry, returning its sequence number
+    fn insert(&self, conn: &Connection, entry: &JournalEntry) -> Result<u64> {
         let value_json = serde_json::to_string(&entry.value)
             .map_err(|e| JournalError::SerializationError(e.to_string()))?;
 
@@ -275,6 +272,28 @@ impl Journal for SqliteJournal {
         let seq = conn.last_insert_rowid() as u64;
         Ok(seq)
     }
+}
+
+#[async_trait]
+impl Journal for SqliteJournal {
+    async fn append(&self, entry: JournalEntry) -> Result<u64> {
+        let conn = self.conn.lock();
+        self.insert(&conn, &entry)
+    }
+
+    async fn append_batch(&self, entries: Vec<JournalEntry>) -> Result<Vec<u64>> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| JournalError::StorageError(e.to_string()))?;
+        let seqs = entries
+            .iter()
+            .map(|entry| self.insert(&tx, entry))
+            .collect::<Result<Vec<u64>>>()?;
+        tx.commit()
+            .map_err(|e| JournalError::StorageError(e.to_string()))?;
+        Ok(seqs)
+    }
 
     async fn query(
         &self,
@@ -736,6 +755,11 @@ impl Journal for BatchingSqliteJournal {
             .map_err(|_| JournalError::StorageError("writer dropped response".to_string()))?
     }
 
+    async fn append_batch(&self, entries: Vec<JournalEntry>) -> Result<Vec<u64>> {
+        // Written directly in one transaction, alongside the writer's batches
+        self.inner.append_batch(entries).await
+    }
+
     async fn query(
         &self,
         pattern: &str,
@@ -804,6 +828,39 @@ mod tests {
         assert_eq!(results[0].seq, 1);
     }
 
+    #[tokio::test]
+    async fn test_sqlite_append_batch() {
+        let journal = SqliteJournal::in_memory().unwrap();
+        journal
+            .append(JournalEntry::from_set(
+                "/test/first".to_string(),
+                Value::Int(0),
+                1,
+                "s1".to_string(),
+                1000000,
+            ))
+            .await
+            .unwrap();
+
+        let batch = (0..3)
+            .map(|i| {
+                JournalEntry::from_set(
+                    format!("/test/{}", i),
+                    Value::Int(i),
+                    1,
+                    "s1".to_string(),
+                    2000000,
+                )
+            })
+            .collect();
+        let seqs = journal.append_batch(batch).await.unwrap();
+        assert_eq!(seqs, vec![2, 3, 4]);
+
+        let entries = journal.since(1, None).await.unwrap();
+        let addresses: Vec<&str> = entries.iter().map(|e| e.address.as_str()).collect();
+        assert_eq!(addresses, vec!["/test/0", "/test/1", "/test/2"]);
+    }
+
     #[tokio::test]
     async fn test_sqlite_since() {
         let journal = SqliteJournal::in_memory().unwrap();
@@ -5,7 +5,7 @@ use tracing::debug;
 
 use clasp_journal_defra::{json_to_graphql_input, DefraClient};
 use clasp_registry::error::{RegistryError, Result};
-use clasp_registry::store::EntityStore;
+use clasp_registry::store::{EntityFilter, EntityStore};
 use clasp_registry::{Entity, EntityId, EntityStatus};
 
 use crate::convert::{defra_to_entity, entity_to_defra, hex_encode};
@@ -257,6 +257,46 @@ impl EntityStore for DefraEntityStore {
         Ok(results)
     }
 
+    async fn query(&self, filter: &EntityFilter) -> Result<Vec<Entity>> {
+        // Metadata is stored as a JSON string, so filter client-side
+        let query = r#"query {
+            ClaspEntity {
+                entityId
+                entityType
+                name
+                publicKey
+                createdAt
+                metadata
+                tags
+                namespaces
+                scopes
+                status
+            }
+        }"#;
+
+        let data = self
+            .client
+            .graphql(query, None)
+            .await
+            .map_err(|e| RegistryError::StorageError(format!("query failed: {e}")))?;
+
+        let docs = data
+            .get("ClaspEntity")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut results = Vec::new();
+        for doc in &docs {
+            let entity = defra_to_entity(doc).map_err(RegistryError::from)?;
+            if filter.matches(&entity) {
+                results.push(entity);
+            }
+        }
+
+        Ok(results)
+    }
+
     async fn list(&self, offset: usize, limit: usize) -> Result<Vec<Entity>> {
         let query = format!(
             r#"query {{
@@ -488,6 +528,31 @@ mod tests {
         store.delete(&entity.id).await.unwrap();
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn test_query_by_attribute() {
+        let store = DefraEntityStore::connect("http://localhost:9181")
+            .await
+            .unwrap();
+        let mut entity = make_test_entity("attr-device");
+        entity
+            .metadata
+            .insert("location".to_string(), "stage-left".to_string());
+        let _ = store.delete(&entity.id).await;
+
+        store.create(&entity).await.unwrap();
+
+        let filter = EntityFilter::new().attribute("location", "stage-left");
+        let found = store.query(&filter).await.unwrap();
+        assert!(found.iter().any(|e| e.id == entity.id));
+
+        let filter = EntityFilter::new().attribute("location", "stage-right");
+        let found = store.query(&filter).await.unwrap();
+        assert!(!found.iter().any(|e| e.id == entity.id));
+
+        store.delete(&entity.id).await.unwrap();
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_update_status() {
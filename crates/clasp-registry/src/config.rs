@@ -0,0 +1,135 @@
+//! Per-entity configuration documents
+//!
+//! Fleet operators store a configuration document per entity; the router
+//! delivers it to the entity's sessions when they connect. Every `put`
+//! bumps the document's version, and the entity acknowledges the version it
+//! applied, so operators can see which devices picked up a rollout.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::entity::EntityId;
+use crate::error::Result;
+
+/// A configuration document for one entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigDocument {
+    pub entity_id: EntityId,
+    /// Starts at 1 and increases with every update
+    pub version: u64,
+    /// Configuration payload (any JSON value)
+    pub config: serde_json::Value,
+    pub updated_at: SystemTime,
+    /// Latest version the entity confirmed receiving
+    pub acked_version: Option<u64>,
+    pub acked_at: Option<SystemTime>,
+}
+
+impl ConfigDocument {
+    /// Whether the entity has confirmed the current version
+    pub fn is_acked(&self) -> bool {
+        self.acked_version == Some(self.version)
+    }
+}
+
+/// Storage for per-entity configuration documents
+#[async_trait]
+pub trait ConfigStore: Send + Sync {
+    /// Get an entity's configuration document
+    async fn get_config(&self, id: &EntityId) -> Result<Option<ConfigDocument>>;
+
+    /// Store a new configuration for an entity, returning the document with
+    /// its new version
+    async fn put_config(&self, id: &EntityId, config: serde_json::Value) -> Result<ConfigDocument>;
+
+    /// Record that the entity applied `version`. Returns false if the
+    /// entity has no document or `version` is not its current version.
+    async fn ack_config(&self, id: &EntityId, version: u64) -> Result<bool>;
+
+    /// Delete an entity's configuration document
+    async fn delete_config(&self, id: &EntityId) -> Result<bool>;
+}
+
+/// In-memory configuration store for development and testing
+#[derive(Default)]
+pub struct MemoryConfigStore {
+    configs: RwLock<HashMap<String, ConfigDocument>>,
+}
+
+impl MemoryConfigStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ConfigStore for MemoryConfigStore {
+    async fn get_config(&self, id: &EntityId) -> Result<Option<ConfigDocument>> {
+        Ok(self.configs.read().unwrap().get(id.as_str()).cloned())
+    }
+
+    async fn put_config(&self, id: &EntityId, config: serde_json::Value) -> Result<ConfigDocument> {
+        let mut configs = self.configs.write().unwrap();
+        let previous = configs.get(id.as_str());
+        let doc = ConfigDocument {
+            entity_id: id.clone(),
+            version: previous.map_or(0, |d| d.version) + 1,
+            config,
+            updated_at: SystemTime::now(),
+            acked_version: previous.and_then(|d| d.acked_version),
+            acked_at: previous.and_then(|d| d.acked_at),
+        };
+        configs.insert(id.as_str().to_string(), doc.clone());
+        Ok(doc)
+    }
+
+    async fn ack_config(&self, id: &EntityId, version: u64) -> Result<bool> {
+        let mut configs = self.configs.write().unwrap();
+        match configs.get_mut(id.as_str()) {
+            Some(doc) if doc.version == version => {
+                doc.acked_version = Some(version);
+                doc.acked_at = Some(SystemTime::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_config(&self, id: &EntityId) -> Result<bool> {
+        Ok(self.configs.write().unwrap().remove(id.as_str()).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityKeypair;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_memory_config_versions_and_acks() {
+        let store = MemoryConfigStore::new();
+        let id = EntityKeypair::generate().unwrap().entity_id;
+        assert!(store.get_config(&id).await.unwrap().is_none());
+        assert!(!store.ack_config(&id, 1).await.unwrap());
+
+        let doc = store.put_config(&id, json!({"rate": 30})).await.unwrap();
+        assert_eq!(doc.version, 1);
+        assert!(!doc.is_acked());
+        assert!(store.ack_config(&id, 1).await.unwrap());
+        assert!(store.get_config(&id).await.unwrap().unwrap().is_acked());
+
+        let doc = store.put_config(&id, json!({"rate": 60})).await.unwrap();
+        assert_eq!(doc.version, 2);
+        assert_eq!(doc.acked_version, Some(1));
+        assert!(!doc.is_acked());
+        // A stale acknowledgement doesn't confirm the new version
+        assert!(!store.ack_config(&id, 1).await.unwrap());
+
+        assert!(store.delete_config(&id).await.unwrap());
+        assert!(store.get_config(&id).await.unwrap().is_none());
+    }
+}
@@ -32,4 +32,13 @@ pub enum RegistryError {
 
     #[error("scope error: {0}")]
     ScopeError(String),
+
+    #[error("invalid fleet name: {0}")]
+    InvalidFleet(String),
+
+    #[error("registry is read-only: {0}")]
+    ReadOnly(String),
+
+    #[error("untrusted origin router: {0}")]
+    Untrusted(String),
 }
@@ -0,0 +1,173 @@
+//! Fleets: named groups of entities
+//!
+//! An entity can belong to any number of fleets, e.g. `kiosks-building-b`
+//! and `firmware-beta`. The router fans a PUBLISH to
+//! `/clasp/fleet/{name}/...` out to every connected session of the fleet's
+//! members, so one command reaches the whole group.
+
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::entity::EntityId;
+use crate::error::{RegistryError, Result};
+
+/// Longest accepted fleet name
+pub const MAX_FLEET_NAME: usize = 64;
+
+/// Check that a fleet name is usable as an address segment: 1 to
+/// [`MAX_FLEET_NAME`] ASCII letters, digits, `-`, `_` or `.`
+pub fn validate_fleet_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_FLEET_NAME
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(RegistryError::InvalidFleet(name.to_string()))
+    }
+}
+
+/// Storage for fleet membership
+#[async_trait]
+pub trait FleetStore: Send + Sync {
+    /// Add an entity to a fleet. Returns false if it was already a member.
+    async fn add_to_fleet(&self, fleet: &str, id: &EntityId) -> Result<bool>;
+
+    /// Remove an entity from a fleet. Returns false if it wasn't a member.
+    async fn remove_from_fleet(&self, fleet: &str, id: &EntityId) -> Result<bool>;
+
+    /// Members of a fleet (empty for an unknown fleet)
+    async fn fleet_members(&self, fleet: &str) -> Result<Vec<EntityId>>;
+
+    /// Fleets an entity belongs to
+    async fn entity_fleets(&self, id: &EntityId) -> Result<Vec<String>>;
+
+    /// Every fleet with at least one member, with its member count
+    async fn list_fleets(&self) -> Result<Vec<(String, usize)>>;
+}
+
+/// In-memory fleet store for development and testing
+#[derive(Default)]
+pub struct MemoryFleetStore {
+    fleets: RwLock<BTreeMap<String, Vec<EntityId>>>,
+}
+
+impl MemoryFleetStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl FleetStore for MemoryFleetStore {
+    async fn add_to_fleet(&self, fleet: &str, id: &EntityId) -> Result<bool> {
+        validate_fleet_name(fleet)?;
+        let mut fleets = self.fleets.write().unwrap();
+        let members = fleets.entry(fleet.to_string()).or_default();
+        if members.contains(id) {
+            return Ok(false);
+        }
+        members.push(id.clone());
+        Ok(true)
+    }
+
+    async fn remove_from_fleet(&self, fleet: &str, id: &EntityId) -> Result<bool> {
+        let mut fleets = self.fleets.write().unwrap();
+        let Some(members) = fleets.get_mut(fleet) else {
+            return Ok(false);
+        };
+        let before = members.len();
+        members.retain(|m| m != id);
+        let removed = members.len() != before;
+        if members.is_empty() {
+            fleets.remove(fleet);
+        }
+        Ok(removed)
+    }
+
+    async fn fleet_members(&self, fleet: &str) -> Result<Vec<EntityId>> {
+        Ok(self
+            .fleets
+            .read()
+            .unwrap()
+            .get(fleet)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn entity_fleets(&self, id: &EntityId) -> Result<Vec<String>> {
+        Ok(self
+            .fleets
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, members)| members.contains(id))
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    async fn list_fleets(&self) -> Result<Vec<(String, usize)>> {
+        Ok(self
+            .fleets
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, members)| (name.clone(), members.len()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityKeypair;
+
+    #[test]
+    fn test_fleet_names() {
+        assert!(validate_fleet_name("kiosks-building-b").is_ok());
+        assert!(validate_fleet_name("beta_2.1").is_ok());
+        for bad in [
+            "",
+            "a/b",
+            "with space",
+            "*",
+            &"x".repeat(MAX_FLEET_NAME + 1),
+        ] {
+            assert!(validate_fleet_name(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_fleet_membership() {
+        let store = MemoryFleetStore::new();
+        let a = EntityKeypair::generate().unwrap().entity_id;
+        let b = EntityKeypair::generate().unwrap().entity_id;
+
+        assert!(store.add_to_fleet("kiosks", &a).await.unwrap());
+        assert!(!store.add_to_fleet("kiosks", &a).await.unwrap());
+        assert!(store.add_to_fleet("kiosks", &b).await.unwrap());
+        assert!(store.add_to_fleet("beta", &a).await.unwrap());
+        assert!(store.add_to_fleet("bad/name", &a).await.is_err());
+
+        assert_eq!(
+            store.fleet_members("kiosks").await.unwrap(),
+            vec![a.clone(), b.clone()]
+        );
+        assert_eq!(
+            store.entity_fleets(&a).await.unwrap(),
+            vec!["beta", "kiosks"]
+        );
+        assert_eq!(
+            store.list_fleets().await.unwrap(),
+            vec![("beta".to_string(), 1), ("kiosks".to_string(), 2)]
+        );
+
+        assert!(store.remove_from_fleet("beta", &a).await.unwrap());
+        assert!(!store.remove_from_fleet("beta", &a).await.unwrap());
+        assert!(store.fleet_members("beta").await.unwrap().is_empty());
+        assert_eq!(store.list_fleets().await.unwrap().len(), 1);
+    }
+}
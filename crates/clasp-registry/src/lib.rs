@@ -15,7 +15,22 @@
 //!
 //! # Storage Backends
 //! - `MemoryEntityStore` -- default, no deps, for dev/testing
-//! - `SqliteEntityStore` -- feature-gated behind `sqlite`, single file, WAL mode
+//! - `SqliteEntityStore` -- feature-gated behind `sqlite`, single file, WAL mode,
+//!   schema upgraded on open by versioned [`migrate`] migrations
+//!
+//! # Configuration Documents
+//! Each entity can have a versioned configuration document (`ConfigStore`),
+//! which the relay delivers at `/clasp/config/{entity_id}` when the entity
+//! connects. `SqliteEntityStore` keeps them in the registry database.
+//!
+//! # Fleets
+//! Entities can be grouped into named fleets (`FleetStore`). The relay fans
+//! a PUBLISH to `/clasp/fleet/{name}/...` out to every connected member.
+//!
+//! # Replication
+//! Routers in a federation keep their registries in step with signed
+//! [`replication`] events, resolved by timestamp; leaves can run a read-only
+//! mirror of their hub's registry.
 //!
 //! # Integration
 //!
@@ -33,18 +48,25 @@
 //!     .with(EntityValidator::new(store));
 //! ```
 
+pub mod config;
 pub mod entity;
 pub mod error;
+pub mod fleet;
+#[cfg(feature = "sqlite")]
+pub mod migrate;
+pub mod replication;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 pub mod store;
 pub mod token;
 pub mod validator;
 
+pub use config::{ConfigDocument, ConfigStore, MemoryConfigStore};
 pub use entity::{Entity, EntityId, EntityKeypair, EntityStatus, EntityType};
 pub use error::{RegistryError, Result};
+pub use fleet::{validate_fleet_name, FleetStore, MemoryFleetStore};
 #[cfg(feature = "sqlite")]
 pub use sqlite::SqliteEntityStore;
-pub use store::{EntityStore, MemoryEntityStore};
+pub use store::{EntityFilter, EntityStore, MemoryEntityStore};
 pub use token::{generate_token, parse_token, ENTITY_TOKEN_PREFIX};
 pub use validator::EntityValidator;
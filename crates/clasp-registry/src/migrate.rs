@@ -0,0 +1,292 @@
+//! Versioned schema migrations for SQLite databases
+//!
+//! A database's schema is a list of [`Migration`]s numbered from 1, each
+//! with the SQL to apply it and to revert it. Applied versions are recorded
+//! in a `schema_migrations` table. [`migrate`] applies the pending ones in
+//! one transaction, after copying a database that already has tables to
+//! `{path}.v{version}.bak`. A database at a newer version than the list is
+//! refused, so an older build never writes to a schema it doesn't know.
+//!
+//! The first migration of a store that predates migrations creates its
+//! tables with `CREATE TABLE IF NOT EXISTS`, so existing databases adopt
+//! version 1 unchanged.
+//!
+//! `SqliteEntityStore` migrates its database on open; the relay does the
+//! same for its auth database and reports pending migrations with
+//! `clasp-relay migrate --dry-run`.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{RegistryError, Result};
+
+/// One schema change
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Position in the list, starting at 1
+    pub version: u32,
+    /// Short description, recorded when applied
+    pub name: &'static str,
+    /// SQL that applies the change
+    pub up: &'static str,
+    /// SQL that reverts it
+    pub down: &'static str,
+}
+
+const HISTORY: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied_at INTEGER NOT NULL
+)";
+
+fn storage(context: &str, e: rusqlite::Error) -> RegistryError {
+    RegistryError::StorageError(format!("{}: {}", context, e))
+}
+
+/// Highest applied version (0 for a database without migrations)
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    let has_history: bool = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'",
+            [],
+            |_| Ok(true),
+        )
+        .optional()
+        .map_err(|e| storage("failed to read schema version", e))?
+        .unwrap_or(false);
+    if !has_history {
+        return Ok(0);
+    }
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| storage("failed to read schema version", e))
+}
+
+/// Migrations not yet applied, in order
+///
+/// Fails if the database is at a version `migrations` doesn't reach.
+pub fn pending<'a>(conn: &Connection, migrations: &'a [Migration]) -> Result<Vec<&'a Migration>> {
+    check_order(migrations)?;
+    let current = current_version(conn)?;
+    let latest = migrations.len() as u32;
+    if current > latest {
+        return Err(RegistryError::StorageError(format!(
+            "database schema version {} is newer than this build supports ({})",
+            current, latest
+        )));
+    }
+    Ok(migrations[current as usize..].iter().collect())
+}
+
+/// Apply every pending migration in one transaction, backing the database
+/// up first if it already has tables. Returns the versions applied.
+pub fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<Vec<u32>> {
+    let pending = pending(conn, migrations)?;
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+    backup(conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| storage("failed to start migration", e))?;
+    tx.execute_batch(HISTORY)
+        .map_err(|e| storage("failed to create migration history", e))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for migration in &pending {
+        tx.execute_batch(migration.up).map_err(|e| {
+            storage(
+                &format!(
+                    "migration {} ({}) failed",
+                    migration.version, migration.name
+                ),
+                e,
+            )
+        })?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, now],
+        )
+        .map_err(|e| storage("failed to record migration", e))?;
+    }
+    tx.commit()
+        .map_err(|e| storage("failed to commit migration", e))?;
+    Ok(pending.iter().map(|m| m.version).collect())
+}
+
+/// Revert migrations down to `target` (0 reverts all) in one transaction.
+/// Returns the versions reverted, newest first.
+pub fn rollback(conn: &mut Connection, migrations: &[Migration], target: u32) -> Result<Vec<u32>> {
+    check_order(migrations)?;
+    let current = current_version(conn)?;
+    if current as usize > migrations.len() {
+        return Err(RegistryError::StorageError(format!(
+            "database schema version {} is newer than this build supports ({})",
+            current,
+            migrations.len()
+        )));
+    }
+    if target >= current {
+        return Ok(Vec::new());
+    }
+    backup(conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| storage("failed to start rollback", e))?;
+    let mut reverted = Vec::new();
+    for migration in migrations[target as usize..current as usize].iter().rev() {
+        tx.execute_batch(migration.down).map_err(|e| {
+            storage(
+                &format!(
+                    "reverting migration {} ({}) failed",
+                    migration.version, migration.name
+                ),
+                e,
+            )
+        })?;
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = ?1",
+            params![migration.version],
+        )
+        .map_err(|e| storage("failed to record rollback", e))?;
+        reverted.push(migration.version);
+    }
+    tx.commit()
+        .map_err(|e| storage("failed to commit rollback", e))?;
+    Ok(reverted)
+}
+
+/// Copy a file database that has tables to `{path}.v{version}.bak`,
+/// replacing an older copy at the same version. Returns the backup's path.
+pub fn backup(conn: &Connection) -> Result<Option<PathBuf>> {
+    let path = match conn.path() {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => return Ok(None),
+    };
+    let tables: u32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| storage("failed to inspect database", e))?;
+    if tables == 0 {
+        return Ok(None);
+    }
+
+    let mut name = path.clone().into_os_string();
+    name.push(format!(".v{}.bak", current_version(conn)?));
+    let target = PathBuf::from(name);
+    if target.exists() {
+        std::fs::remove_file(&target).map_err(|e| {
+            RegistryError::StorageError(format!(
+                "failed to replace backup {}: {}",
+                target.display(),
+                e
+            ))
+        })?;
+    }
+    conn.execute("VACUUM INTO ?1", params![target.to_string_lossy()])
+        .map_err(|e| storage("failed to back up database", e))?;
+    Ok(Some(target))
+}
+
+fn check_order(migrations: &[Migration]) -> Result<()> {
+    for (i, migration) in migrations.iter().enumerate() {
+        if migration.version as usize != i + 1 {
+            return Err(RegistryError::StorageError(format!(
+                "migration {} ({}) is out of order",
+                migration.version, migration.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "create widgets",
+            up: "CREATE TABLE IF NOT EXISTS widgets (id TEXT PRIMARY KEY);",
+            down: "DROP TABLE widgets;",
+        },
+        Migration {
+            version: 2,
+            name: "add widget color",
+            up: "ALTER TABLE widgets ADD COLUMN color TEXT;",
+            down: "ALTER TABLE widgets DROP COLUMN color;",
+        },
+    ];
+
+    #[test]
+    fn test_migrate_and_rollback() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(pending(&conn, MIGRATIONS).unwrap().len(), 2);
+        assert_eq!(migrate(&mut conn, MIGRATIONS).unwrap(), vec![1, 2]);
+        assert_eq!(current_version(&conn).unwrap(), 2);
+        assert!(migrate(&mut conn, MIGRATIONS).unwrap().is_empty());
+        conn.execute("INSERT INTO widgets (id, color) VALUES ('a', 'red')", [])
+            .unwrap();
+
+        assert_eq!(rollback(&mut conn, MIGRATIONS, 1).unwrap(), vec![2]);
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert!(conn
+            .execute("INSERT INTO widgets (id, color) VALUES ('b', 'blue')", [])
+            .is_err());
+    }
+
+    #[test]
+    fn test_failed_migration_changes_nothing() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let broken = [
+            MIGRATIONS[0],
+            Migration {
+                version: 2,
+                name: "broken",
+                up: "ALTER TABLE missing ADD COLUMN x TEXT;",
+                down: "",
+            },
+        ];
+        assert!(migrate(&mut conn, &broken).is_err());
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert!(conn.prepare("SELECT * FROM widgets").is_err());
+    }
+
+    #[test]
+    fn test_refuses_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+        let err = migrate(&mut conn, &MIGRATIONS[..1]).unwrap_err();
+        assert!(err.to_string().contains("newer"), "{}", err);
+    }
+
+    #[test]
+    fn test_backs_up_existing_database() {
+        let dir = std::env::temp_dir().join(format!("clasp-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("store.db");
+        let _ = std::fs::remove_file(&path);
+
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE widgets (id TEXT PRIMARY KEY);")
+            .unwrap();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+
+        let backup = Connection::open(dir.join("store.db.v0.bak")).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 0);
+        drop(backup);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,566 @@
+//! Entity registry replication between routers
+//!
+//! Multi-site deployments keep one registry per router and replicate it over
+//! federation links, so an entity registered at one site is recognized at
+//! every site. Each change made on a router (create, status change,
+//! revocation, deletion) becomes a [`RegistryEvent`] carrying the whole
+//! entity, signed with the router's Ed25519 key by [`Replicator::record`].
+//! Peers verify the signature, check that the origin router is trusted and
+//! apply the event with [`Replicator::apply`].
+//!
+//! # Conflict resolution
+//!
+//! Concurrent changes to the same entity are resolved by timestamp: the
+//! event with the later `timestamp` wins, ties broken by origin router ID, so
+//! every replica settles on the same entity whatever order events arrive in.
+//! A deletion is kept as a tombstone so an older create can't bring the
+//! entity back. Revocation is final: an update never reactivates a revoked
+//! entity, however late it is; only a fresh create after a deletion does.
+//!
+//! # Modes
+//!
+//! - [`Replicator::new`]: a read-write replica that signs its own changes
+//!   and applies its peers'
+//! - [`Replicator::mirror`]: a read-only mirror for leaves, which applies
+//!   events from its hub and refuses local changes
+//!   ([`Replicator::check_writable`])
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use clasp_registry::replication::{RegistryEventKind, Replicator};
+//! use clasp_registry::{EntityKeypair, EntityStore, EntityType, MemoryEntityStore};
+//!
+//! # async fn example() {
+//! let hub_key = EntityKeypair::generate().unwrap();
+//! let hub_public = hub_key.public_key_bytes().to_vec();
+//! let hub_store = Arc::new(MemoryEntityStore::new());
+//! let hub = Replicator::new(hub_store.clone(), hub_key);
+//!
+//! let leaf_store = Arc::new(MemoryEntityStore::new());
+//! let leaf = Replicator::mirror(leaf_store.clone()).trust(&hub_public).unwrap();
+//!
+//! let device = EntityKeypair::generate()
+//!     .unwrap()
+//!     .to_entity(EntityType::Device, "kiosk-1".to_string());
+//! hub_store.create(&device).await.unwrap();
+//! let event = hub.record(RegistryEventKind::Created, &device).await.unwrap();
+//!
+//! assert!(leaf.apply(&event).await.unwrap().is_some());
+//! assert!(leaf_store.get(&device.id).await.unwrap().is_some());
+//! # }
+//! ```
+
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::entity::{Entity, EntityId, EntityKeypair, EntityStatus};
+use crate::error::{RegistryError, Result};
+use crate::store::EntityStore;
+
+/// What happened to an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistryEventKind {
+    Created,
+    Updated,
+    Revoked,
+    Deleted,
+}
+
+impl fmt::Display for RegistryEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryEventKind::Created => write!(f, "created"),
+            RegistryEventKind::Updated => write!(f, "updated"),
+            RegistryEventKind::Revoked => write!(f, "revoked"),
+            RegistryEventKind::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+/// One change to the registry, as made on its origin router
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEvent {
+    pub kind: RegistryEventKind,
+    /// The entity after the change (before it, for a deletion)
+    pub entity: Entity,
+    /// Entity ID of the router the change was made on
+    pub origin: String,
+    /// When the change was made (milliseconds since the Unix epoch)
+    pub timestamp: u64,
+}
+
+impl RegistryEvent {
+    /// Whether this event supersedes one with `other`'s version
+    fn is_newer_than(&self, other: &RegistryEvent) -> bool {
+        (self.timestamp, self.origin.as_str()) > (other.timestamp, other.origin.as_str())
+    }
+}
+
+/// Wire form: the encoded event and the origin router's signature over it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SignedEvent {
+    #[serde(with = "crate::token::serde_bytes")]
+    body: Vec<u8>,
+    #[serde(with = "crate::token::serde_bytes")]
+    public_key: Vec<u8>,
+    #[serde(with = "crate::token::serde_bytes")]
+    signature: Vec<u8>,
+}
+
+/// Whether a [`Replicator`] makes changes of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicationMode {
+    /// Signs local changes and applies peers'
+    ReadWrite,
+    /// Applies events from trusted routers and refuses local changes
+    Mirror,
+}
+
+/// Latest known event per entity, with its encoded form for catch-up
+struct Known {
+    event: RegistryEvent,
+    encoded: Vec<u8>,
+}
+
+/// Signs, verifies and applies registry events for one router's store
+pub struct Replicator {
+    store: Arc<dyn EntityStore>,
+    mode: ReplicationMode,
+    keypair: Option<EntityKeypair>,
+    /// Public keys of the routers whose events are accepted
+    trusted: HashSet<Vec<u8>>,
+    /// Entity ID -> latest event applied or recorded. Held across store
+    /// writes so events for an entity are applied one at a time.
+    known: Mutex<HashMap<String, Known>>,
+}
+
+impl Replicator {
+    /// A read-write replica signing its changes with `keypair`, the router's
+    /// identity. Its own events are trusted.
+    pub fn new(store: Arc<dyn EntityStore>, keypair: EntityKeypair) -> Self {
+        let mut trusted = HashSet::new();
+        trusted.insert(keypair.public_key_bytes().to_vec());
+        Self {
+            store,
+            mode: ReplicationMode::ReadWrite,
+            keypair: Some(keypair),
+            trusted,
+            known: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A read-only mirror, following the routers passed to [`Self::trust`]
+    pub fn mirror(store: Arc<dyn EntityStore>) -> Self {
+        Self {
+            store,
+            mode: ReplicationMode::Mirror,
+            keypair: None,
+            trusted: HashSet::new(),
+            known: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accept events signed by the router with this Ed25519 public key
+    pub fn trust(mut self, public_key: &[u8]) -> Result<Self> {
+        verifying_key(public_key)?;
+        self.trusted.insert(public_key.to_vec());
+        Ok(self)
+    }
+
+    pub fn mode(&self) -> ReplicationMode {
+        self.mode
+    }
+
+    /// Entity ID of this router, for a read-write replica
+    pub fn router_id(&self) -> Option<&EntityId> {
+        self.keypair.as_ref().map(|k| &k.entity_id)
+    }
+
+    /// Refuse local changes on a mirror
+    pub fn check_writable(&self) -> Result<()> {
+        match self.mode {
+            ReplicationMode::ReadWrite => Ok(()),
+            ReplicationMode::Mirror => Err(RegistryError::ReadOnly(
+                "changes must be made on the hub".to_string(),
+            )),
+        }
+    }
+
+    /// Sign a change already made to the local store and remember it.
+    /// Returns the encoded event to send to peers.
+    ///
+    /// The event is stamped with the current time, or just after the
+    /// entity's latest known event if that is later, so a local change
+    /// always supersedes what this router has seen.
+    pub async fn record(&self, kind: RegistryEventKind, entity: &Entity) -> Result<Vec<u8>> {
+        self.check_writable()?;
+        let mut known = self.known.lock().await;
+        let mut timestamp = now_millis();
+        if let Some(latest) = known.get(entity.id.as_str()) {
+            timestamp = timestamp.max(latest.event.timestamp + 1);
+        }
+        let event = RegistryEvent {
+            kind,
+            entity: entity.clone(),
+            origin: String::new(),
+            timestamp,
+        };
+        let (event, encoded) = self.sign(event)?;
+        known.insert(
+            entity.id.as_str().to_string(),
+            Known {
+                event,
+                encoded: encoded.clone(),
+            },
+        );
+        Ok(encoded)
+    }
+
+    /// Verify an encoded event from a peer and apply it to the store if it
+    /// supersedes what is known about the entity. Returns the event if it
+    /// changed the registry, for the caller to pass on to other peers; an
+    /// event that is stale, a duplicate or would reactivate a revoked entity
+    /// is ignored.
+    pub async fn apply(&self, encoded: &[u8]) -> Result<Option<RegistryEvent>> {
+        let event = self.verify(encoded)?;
+        let id = event.entity.id.clone();
+        let expected = EntityId::from_public_key(&event.entity.public_key)?;
+        if expected != id {
+            return Err(RegistryError::InvalidId(format!(
+                "entity {} does not match its public key",
+                id
+            )));
+        }
+
+        let mut known = self.known.lock().await;
+        if let Some(latest) = known.get(id.as_str()) {
+            if !event.is_newer_than(&latest.event) {
+                return Ok(None);
+            }
+        }
+
+        let current = self.store.get(&id).await?;
+        if event.kind == RegistryEventKind::Updated
+            && event.entity.status != EntityStatus::Revoked
+            && current
+                .as_ref()
+                .is_some_and(|e| e.status == EntityStatus::Revoked)
+        {
+            return Ok(None);
+        }
+
+        match (event.kind, current) {
+            (RegistryEventKind::Deleted, _) => {
+                self.store.delete(&id).await?;
+            }
+            (_, Some(_)) => self.store.update(&event.entity).await?,
+            (_, None) => self.store.create(&event.entity).await?,
+        }
+        known.insert(
+            id.as_str().to_string(),
+            Known {
+                event: event.clone(),
+                encoded: encoded.to_vec(),
+            },
+        );
+        Ok(Some(event))
+    }
+
+    /// Every event a new peer needs to catch up: the latest event for each
+    /// entity, including deletions. A read-write replica also signs a
+    /// `Created` event, stamped with its creation time, for each entity it
+    /// had before replication started.
+    pub async fn snapshot(&self) -> Result<Vec<Vec<u8>>> {
+        let mut known = self.known.lock().await;
+        if self.mode == ReplicationMode::ReadWrite {
+            let count = self.store.count().await?;
+            for entity in self.store.list(0, count).await? {
+                if known.contains_key(entity.id.as_str()) {
+                    continue;
+                }
+                let timestamp = entity
+                    .created_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                let (event, encoded) = self.sign(RegistryEvent {
+                    kind: RegistryEventKind::Created,
+                    entity,
+                    origin: String::new(),
+                    timestamp,
+                })?;
+                known.insert(
+                    event.entity.id.as_str().to_string(),
+                    Known { event, encoded },
+                );
+            }
+        }
+        Ok(known.values().map(|k| k.encoded.clone()).collect())
+    }
+
+    /// Decode an event without verifying it (for logging)
+    pub fn decode(encoded: &[u8]) -> Result<RegistryEvent> {
+        let signed: SignedEvent = rmp_serde::from_slice(encoded)
+            .map_err(|e| RegistryError::TokenError(format!("invalid registry event: {}", e)))?;
+        decode_body(&signed.body)
+    }
+
+    fn sign(&self, mut event: RegistryEvent) -> Result<(RegistryEvent, Vec<u8>)> {
+        let keypair = self
+            .keypair
+            .as_ref()
+            .ok_or_else(|| RegistryError::ReadOnly("no signing key".to_string()))?;
+        event.origin = keypair.entity_id.as_str().to_string();
+        let body = rmp_serde::to_vec(&event)
+            .map_err(|e| RegistryError::TokenError(format!("failed to encode event: {}", e)))?;
+        let signature = keypair
+            .signing_key
+            .try_sign(&body)
+            .map_err(|e| RegistryError::SignatureError(e.to_string()))?;
+        let encoded = rmp_serde::to_vec(&SignedEvent {
+            body,
+            public_key: keypair.public_key_bytes().to_vec(),
+            signature: signature.to_bytes().to_vec(),
+        })
+        .map_err(|e| RegistryError::TokenError(format!("failed to encode event: {}", e)))?;
+        Ok((event, encoded))
+    }
+
+    /// Check the signature and that the origin is a trusted router
+    fn verify(&self, encoded: &[u8]) -> Result<RegistryEvent> {
+        let signed: SignedEvent = rmp_serde::from_slice(encoded)
+            .map_err(|e| RegistryError::TokenError(format!("invalid registry event: {}", e)))?;
+        let key = verifying_key(&signed.public_key)?;
+        let signature: [u8; 64] =
+            signed.signature.as_slice().try_into().map_err(|_| {
+                RegistryError::SignatureError("signature must be 64 bytes".to_string())
+            })?;
+        key.verify(&signed.body, &Signature::from_bytes(&signature))
+            .map_err(|e| RegistryError::SignatureError(e.to_string()))?;
+
+        let event = decode_body(&signed.body)?;
+        let signer = EntityId::from_public_key(&signed.public_key)?;
+        if event.origin != signer.as_str() {
+            return Err(RegistryError::SignatureError(format!(
+                "event from {} is signed by {}",
+                event.origin, signer
+            )));
+        }
+        if !self.trusted.contains(&signed.public_key) {
+            return Err(RegistryError::Untrusted(event.origin));
+        }
+        Ok(event)
+    }
+}
+
+fn decode_body(body: &[u8]) -> Result<RegistryEvent> {
+    rmp_serde::from_slice(body)
+        .map_err(|e| RegistryError::TokenError(format!("invalid registry event: {}", e)))
+}
+
+fn verifying_key(public_key: &[u8]) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = public_key
+        .try_into()
+        .map_err(|_| RegistryError::InvalidKey("public key must be 32 bytes".to_string()))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|e| RegistryError::InvalidKey(format!("invalid public key: {}", e)))
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityType;
+    use crate::store::MemoryEntityStore;
+
+    fn device(name: &str) -> Entity {
+        EntityKeypair::generate()
+            .unwrap()
+            .to_entity(EntityType::Device, name.to_string())
+    }
+
+    /// A read-write router and a store that trusts it
+    fn site() -> (Replicator, Arc<MemoryEntityStore>, Vec<u8>) {
+        let key = EntityKeypair::generate().unwrap();
+        let public = key.public_key_bytes().to_vec();
+        let store = Arc::new(MemoryEntityStore::new());
+        (Replicator::new(store.clone(), key), store, public)
+    }
+
+    #[tokio::test]
+    async fn test_events_replicate_create_and_revoke() {
+        let (hub, hub_store, hub_key) = site();
+        let leaf_store = Arc::new(MemoryEntityStore::new());
+        let leaf = Replicator::mirror(leaf_store.clone())
+            .trust(&hub_key)
+            .unwrap();
+
+        let mut entity = device("kiosk");
+        hub_store.create(&entity).await.unwrap();
+        let created = hub
+            .record(RegistryEventKind::Created, &entity)
+            .await
+            .unwrap();
+        entity.status = EntityStatus::Revoked;
+        hub_store.update(&entity).await.unwrap();
+        let revoked = hub
+            .record(RegistryEventKind::Revoked, &entity)
+            .await
+            .unwrap();
+
+        assert!(leaf.apply(&created).await.unwrap().is_some());
+        assert!(leaf.apply(&revoked).await.unwrap().is_some());
+        let mirrored = leaf_store.get(&entity.id).await.unwrap().unwrap();
+        assert_eq!(mirrored.status, EntityStatus::Revoked);
+
+        // Redelivery changes nothing
+        assert!(leaf.apply(&revoked).await.unwrap().is_none());
+        assert!(leaf.apply(&created).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_later_timestamp_wins() {
+        let (a, a_store, a_key) = site();
+        let (b, b_store, b_key) = site();
+        let c_store = Arc::new(MemoryEntityStore::new());
+        let c = Replicator::mirror(c_store.clone())
+            .trust(&a_key)
+            .unwrap()
+            .trust(&b_key)
+            .unwrap();
+
+        let mut entity = device("panel");
+        entity.tags = vec!["from-a".to_string()];
+        a_store.create(&entity).await.unwrap();
+        let first = a.record(RegistryEventKind::Created, &entity).await.unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        entity.tags = vec!["from-b".to_string()];
+        b_store.create(&entity).await.unwrap();
+        let second = b.record(RegistryEventKind::Updated, &entity).await.unwrap();
+
+        // Newer first, then the older one: the newer stays
+        assert!(c.apply(&second).await.unwrap().is_some());
+        assert!(c.apply(&first).await.unwrap().is_none());
+        let entity = c_store.get(&entity.id).await.unwrap().unwrap();
+        assert_eq!(entity.tags, vec!["from-b".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_deletion_is_a_tombstone() {
+        let (hub, hub_store, hub_key) = site();
+        let leaf_store = Arc::new(MemoryEntityStore::new());
+        let leaf = Replicator::mirror(leaf_store.clone())
+            .trust(&hub_key)
+            .unwrap();
+
+        let entity = device("sensor");
+        hub_store.create(&entity).await.unwrap();
+        let created = hub
+            .record(RegistryEventKind::Created, &entity)
+            .await
+            .unwrap();
+        hub_store.delete(&entity.id).await.unwrap();
+        let deleted = hub
+            .record(RegistryEventKind::Deleted, &entity)
+            .await
+            .unwrap();
+
+        assert!(leaf.apply(&deleted).await.unwrap().is_some());
+        assert!(leaf.apply(&created).await.unwrap().is_none());
+        assert!(leaf_store.get(&entity.id).await.unwrap().is_none());
+        assert_eq!(leaf.snapshot().await.unwrap(), vec![deleted]);
+    }
+
+    #[tokio::test]
+    async fn test_update_does_not_reactivate_revoked_entity() {
+        let (a, a_store, a_key) = site();
+        let (b, b_store, _) = site();
+        let b = b.trust(&a_key).unwrap();
+
+        let mut entity = device("badge");
+        a_store.create(&entity).await.unwrap();
+        b_store.create(&entity).await.unwrap();
+        entity.status = EntityStatus::Revoked;
+        b_store.update(&entity).await.unwrap();
+        b.record(RegistryEventKind::Revoked, &entity).await.unwrap();
+
+        // A concurrent suspension stamped after the revocation
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        entity.status = EntityStatus::Suspended;
+        let suspended = a.record(RegistryEventKind::Updated, &entity).await.unwrap();
+        assert!(b.apply(&suspended).await.unwrap().is_none());
+        let entity = b_store.get(&entity.id).await.unwrap().unwrap();
+        assert_eq!(entity.status, EntityStatus::Revoked);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_untrusted_and_tampered_events() {
+        let (hub, hub_store, hub_key) = site();
+        let (rogue, _, _) = site();
+        let leaf = Replicator::mirror(Arc::new(MemoryEntityStore::new()))
+            .trust(&hub_key)
+            .unwrap();
+
+        let entity = device("door");
+        let forged = rogue
+            .record(RegistryEventKind::Created, &entity)
+            .await
+            .unwrap();
+        assert!(matches!(
+            leaf.apply(&forged).await,
+            Err(RegistryError::Untrusted(_))
+        ));
+
+        hub_store.create(&entity).await.unwrap();
+        let event = hub
+            .record(RegistryEventKind::Created, &entity)
+            .await
+            .unwrap();
+        let mut signed: SignedEvent = rmp_serde::from_slice(&event).unwrap();
+        let last = signed.body.len() - 1;
+        signed.body[last] ^= 0x01;
+        let tampered = rmp_serde::to_vec(&signed).unwrap();
+        assert!(leaf.apply(&tampered).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_refuses_local_changes() {
+        let mirror = Replicator::mirror(Arc::new(MemoryEntityStore::new()));
+        assert!(mirror.check_writable().is_err());
+        assert!(mirror
+            .record(RegistryEventKind::Created, &device("x"))
+            .await
+            .is_err());
+        assert!(mirror.snapshot().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_covers_entities_from_before_replication() {
+        let (hub, hub_store, hub_key) = site();
+        for name in ["a", "b"] {
+            hub_store.create(&device(name)).await.unwrap();
+        }
+
+        let leaf_store = Arc::new(MemoryEntityStore::new());
+        let leaf = Replicator::mirror(leaf_store.clone())
+            .trust(&hub_key)
+            .unwrap();
+        for event in hub.snapshot().await.unwrap() {
+            leaf.apply(&event).await.unwrap();
+        }
+        assert_eq!(leaf_store.count().await.unwrap(), 2);
+    }
+}
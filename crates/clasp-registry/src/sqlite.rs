@@ -7,9 +7,49 @@ use rusqlite::{params, Connection};
 use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::config::{ConfigDocument, ConfigStore};
 use crate::entity::{Entity, EntityId, EntityStatus, EntityType};
 use crate::error::{RegistryError, Result};
-use crate::store::EntityStore;
+use crate::fleet::{validate_fleet_name, FleetStore};
+use crate::migrate::{self, Migration};
+use crate::store::{EntityFilter, EntityStore};
+
+/// Schema history of the entity store database
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "entities, configs and fleets",
+    up: "CREATE TABLE IF NOT EXISTS entities (
+                id TEXT PRIMARY KEY,
+                entity_type TEXT NOT NULL,
+                name TEXT NOT NULL,
+                public_key BLOB NOT NULL,
+                created_at INTEGER NOT NULL,
+                metadata TEXT NOT NULL DEFAULT '{}',
+                tags TEXT NOT NULL DEFAULT '[]',
+                namespaces TEXT NOT NULL DEFAULT '[]',
+                scopes TEXT NOT NULL DEFAULT '[]',
+                status TEXT NOT NULL DEFAULT 'active'
+            );
+            CREATE INDEX IF NOT EXISTS idx_entities_public_key ON entities(public_key);
+            CREATE INDEX IF NOT EXISTS idx_entities_status ON entities(status);
+            CREATE TABLE IF NOT EXISTS entity_configs (
+                entity_id TEXT PRIMARY KEY,
+                version INTEGER NOT NULL,
+                config TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                acked_version INTEGER,
+                acked_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS fleet_members (
+                fleet TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                PRIMARY KEY (fleet, entity_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_fleet_members_entity ON fleet_members(entity_id);",
+    down: "DROP TABLE fleet_members;
+           DROP TABLE entity_configs;
+           DROP TABLE entities;",
+}];
 
 /// SQLite-backed entity store
 ///
@@ -21,7 +61,7 @@ pub struct SqliteEntityStore {
 impl SqliteEntityStore {
     /// Open or create a SQLite entity store at the given path
     pub fn open(path: &str) -> Result<Self> {
-        let conn = Connection::open(path)
+        let mut conn = Connection::open(path)
             .map_err(|e| RegistryError::StorageError(format!("failed to open database: {}", e)))?;
 
         conn.execute_batch(
@@ -31,11 +71,10 @@ impl SqliteEntityStore {
         )
         .map_err(|e| RegistryError::StorageError(format!("failed to set pragmas: {}", e)))?;
 
-        let store = Self {
+        migrate::migrate(&mut conn, MIGRATIONS)?;
+        Ok(Self {
             conn: Mutex::new(conn),
-        };
-        store.create_tables()?;
-        Ok(store)
+        })
     }
 
     /// Create an in-memory SQLite store (for testing)
@@ -43,28 +82,6 @@ impl SqliteEntityStore {
         Self::open(":memory:")
     }
 
-    fn create_tables(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS entities (
-                id TEXT PRIMARY KEY,
-                entity_type TEXT NOT NULL,
-                name TEXT NOT NULL,
-                public_key BLOB NOT NULL,
-                created_at INTEGER NOT NULL,
-                metadata TEXT NOT NULL DEFAULT '{}',
-                tags TEXT NOT NULL DEFAULT '[]',
-                namespaces TEXT NOT NULL DEFAULT '[]',
-                scopes TEXT NOT NULL DEFAULT '[]',
-                status TEXT NOT NULL DEFAULT 'active'
-            );
-            CREATE INDEX IF NOT EXISTS idx_entities_public_key ON entities(public_key);
-            CREATE INDEX IF NOT EXISTS idx_entities_status ON entities(status);",
-        )
-        .map_err(|e| RegistryError::StorageError(format!("failed to create tables: {}", e)))?;
-        Ok(())
-    }
-
     fn row_to_entity(row: &rusqlite::Row) -> rusqlite::Result<Entity> {
         let id_str: String = row.get(0)?;
         let entity_type_str: String = row.get(1)?;
@@ -225,6 +242,34 @@ impl EntityStore for SqliteEntityStore {
         Ok(result)
     }
 
+    async fn query(&self, filter: &EntityFilter) -> Result<Vec<Entity>> {
+        // Type and status narrow the scan in SQL; tags and metadata are
+        // JSON columns, so those are checked on the decoded rows
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id, entity_type, name, public_key, created_at, metadata, tags, namespaces, scopes, status FROM entities WHERE (?1 IS NULL OR entity_type = ?1) AND (?2 IS NULL OR status = ?2)")
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(
+                params![
+                    filter.entity_type.map(|t| t.to_string()),
+                    filter.status.map(|s| s.to_string()),
+                ],
+                Self::row_to_entity,
+            )
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let entity = row.map_err(|e| RegistryError::StorageError(e.to_string()))?;
+            if filter.matches(&entity) {
+                result.push(entity);
+            }
+        }
+        Ok(result)
+    }
+
     async fn list(&self, offset: usize, limit: usize) -> Result<Vec<Entity>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn
@@ -294,6 +339,11 @@ impl EntityStore for SqliteEntityStore {
         let rows = conn
             .execute("DELETE FROM entities WHERE id = ?1", params![id.as_str()])
             .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        conn.execute(
+            "DELETE FROM fleet_members WHERE entity_id = ?1",
+            params![id.as_str()],
+        )
+        .map_err(|e| RegistryError::StorageError(e.to_string()))?;
         Ok(rows > 0)
     }
 
@@ -306,6 +356,154 @@ impl EntityStore for SqliteEntityStore {
     }
 }
 
+/// Configuration documents live in the same database as the entities
+#[async_trait]
+impl ConfigStore for SqliteEntityStore {
+    async fn get_config(&self, id: &EntityId) -> Result<Option<ConfigDocument>> {
+        let conn = self.conn.lock().unwrap();
+        let row = conn
+            .query_row(
+                "SELECT version, config, updated_at, acked_version, acked_at FROM entity_configs WHERE entity_id = ?1",
+                params![id.as_str()],
+                |row| {
+                    Ok((
+                        row.get::<_, u64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, u64>(2)?,
+                        row.get::<_, Option<u64>>(3)?,
+                        row.get::<_, Option<u64>>(4)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+
+        let Some((version, config, updated_at, acked_version, acked_at)) = row else {
+            return Ok(None);
+        };
+        let secs = |s: u64| UNIX_EPOCH + std::time::Duration::from_secs(s);
+        Ok(Some(ConfigDocument {
+            entity_id: id.clone(),
+            version,
+            config: serde_json::from_str(&config)
+                .map_err(|e| RegistryError::StorageError(e.to_string()))?,
+            updated_at: secs(updated_at),
+            acked_version,
+            acked_at: acked_at.map(secs),
+        }))
+    }
+
+    async fn put_config(&self, id: &EntityId, config: serde_json::Value) -> Result<ConfigDocument> {
+        let json = serde_json::to_string(&config)
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        let updated_at = SystemTime::now();
+        {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO entity_configs (entity_id, version, config, updated_at)
+                 VALUES (?1, 1, ?2, ?3)
+                 ON CONFLICT(entity_id) DO UPDATE SET
+                     version = version + 1, config = excluded.config, updated_at = excluded.updated_at",
+                params![id.as_str(), json, system_time_to_secs(updated_at)],
+            )
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        }
+        self.get_config(id)
+            .await?
+            .ok_or_else(|| RegistryError::NotFound(id.as_str().to_string()))
+    }
+
+    async fn ack_config(&self, id: &EntityId, version: u64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn
+            .execute(
+                "UPDATE entity_configs SET acked_version = ?1, acked_at = ?2 WHERE entity_id = ?3 AND version = ?1",
+                params![version, system_time_to_secs(SystemTime::now()), id.as_str()],
+            )
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        Ok(rows > 0)
+    }
+
+    async fn delete_config(&self, id: &EntityId) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn
+            .execute(
+                "DELETE FROM entity_configs WHERE entity_id = ?1",
+                params![id.as_str()],
+            )
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        Ok(rows > 0)
+    }
+}
+
+/// Fleet membership lives in the same database as the entities
+#[async_trait]
+impl FleetStore for SqliteEntityStore {
+    async fn add_to_fleet(&self, fleet: &str, id: &EntityId) -> Result<bool> {
+        validate_fleet_name(fleet)?;
+        let conn = self.conn.lock().unwrap();
+        let rows = conn
+            .execute(
+                "INSERT OR IGNORE INTO fleet_members (fleet, entity_id) VALUES (?1, ?2)",
+                params![fleet, id.as_str()],
+            )
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        Ok(rows > 0)
+    }
+
+    async fn remove_from_fleet(&self, fleet: &str, id: &EntityId) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn
+            .execute(
+                "DELETE FROM fleet_members WHERE fleet = ?1 AND entity_id = ?2",
+                params![fleet, id.as_str()],
+            )
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        Ok(rows > 0)
+    }
+
+    async fn fleet_members(&self, fleet: &str) -> Result<Vec<EntityId>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT entity_id FROM fleet_members WHERE fleet = ?1 ORDER BY rowid")
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        let ids = stmt
+            .query_map(params![fleet], |row| row.get::<_, String>(0))
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        ids.iter().map(|id| EntityId::parse(id)).collect()
+    }
+
+    async fn entity_fleets(&self, id: &EntityId) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT fleet FROM fleet_members WHERE entity_id = ?1 ORDER BY fleet")
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        let fleets = stmt
+            .query_map(params![id.as_str()], |row| row.get(0))
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        Ok(fleets)
+    }
+
+    async fn list_fleets(&self) -> Result<Vec<(String, usize)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT fleet, COUNT(*) FROM fleet_members GROUP BY fleet ORDER BY fleet")
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        let fleets = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+            })
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        Ok(fleets)
+    }
+}
+
 // Need this trait for optional() method
 trait OptionalExt<T> {
     fn optional(self) -> std::result::Result<Option<T>, rusqlite::Error>;
@@ -384,6 +582,41 @@ mod tests {
         assert!(found.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_sqlite_metadata_query() {
+        let store = SqliteEntityStore::in_memory().unwrap();
+        let mut left = create_test_entity("left");
+        left.metadata
+            .insert("location".to_string(), "stage-left".to_string());
+        left.metadata
+            .insert("model".to_string(), "ESP32".to_string());
+        store.create(&left).await.unwrap();
+        let mut right = create_test_entity("right");
+        right
+            .metadata
+            .insert("location".to_string(), "stage-right".to_string());
+        store.create(&right).await.unwrap();
+
+        let found = store.get(&left.id).await.unwrap().unwrap();
+        assert_eq!(found.metadata, left.metadata);
+
+        let filter = EntityFilter::new()
+            .entity_type(EntityType::Device)
+            .status(EntityStatus::Active)
+            .attribute("model", "ESP32");
+        let found = store.query(&filter).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, left.id);
+
+        store
+            .update_status(&left.id, EntityStatus::Suspended)
+            .await
+            .unwrap();
+        assert!(store.query(&filter).await.unwrap().is_empty());
+        let filter = EntityFilter::new().tag("test");
+        assert_eq!(store.query(&filter).await.unwrap().len(), 2);
+    }
+
     #[tokio::test]
     async fn test_sqlite_update_status() {
         let store = SqliteEntityStore::in_memory().unwrap();
@@ -425,4 +658,101 @@ mod tests {
         let page = store.list(0, 3).await.unwrap();
         assert_eq!(page.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_sqlite_config_versions_and_acks() {
+        let store = SqliteEntityStore::in_memory().unwrap();
+        let entity = create_test_entity("test-device");
+        let config = serde_json::json!({"rate": 30, "mode": "eco"});
+
+        let doc = store.put_config(&entity.id, config.clone()).await.unwrap();
+        assert_eq!(doc.version, 1);
+        assert_eq!(doc.config, config);
+        assert!(store.ack_config(&entity.id, 1).await.unwrap());
+
+        let doc = store
+            .put_config(&entity.id, serde_json::json!({"rate": 60}))
+            .await
+            .unwrap();
+        assert_eq!(doc.version, 2);
+        assert_eq!(doc.acked_version, Some(1));
+        assert!(!store.ack_config(&entity.id, 1).await.unwrap());
+        assert!(store.ack_config(&entity.id, 2).await.unwrap());
+        assert!(store
+            .get_config(&entity.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_acked());
+
+        assert!(store.delete_config(&entity.id).await.unwrap());
+        assert!(store.get_config(&entity.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_fleet_membership() {
+        let store = SqliteEntityStore::in_memory().unwrap();
+        let a = create_test_entity("kiosk-a");
+        let b = create_test_entity("kiosk-b");
+        store.create(&a).await.unwrap();
+        store.create(&b).await.unwrap();
+
+        assert!(store.add_to_fleet("kiosks", &a.id).await.unwrap());
+        assert!(!store.add_to_fleet("kiosks", &a.id).await.unwrap());
+        assert!(store.add_to_fleet("kiosks", &b.id).await.unwrap());
+        assert!(store.add_to_fleet("beta", &a.id).await.unwrap());
+        assert!(store.add_to_fleet("bad name", &a.id).await.is_err());
+
+        assert_eq!(
+            store.fleet_members("kiosks").await.unwrap(),
+            vec![a.id.clone(), b.id.clone()]
+        );
+        assert_eq!(
+            store.entity_fleets(&a.id).await.unwrap(),
+            vec!["beta", "kiosks"]
+        );
+        assert_eq!(
+            store.list_fleets().await.unwrap(),
+            vec![("beta".to_string(), 1), ("kiosks".to_string(), 2)]
+        );
+
+        // Deleting an entity drops its memberships
+        store.delete(&a.id).await.unwrap();
+        assert_eq!(
+            store.fleet_members("kiosks").await.unwrap(),
+            vec![b.id.clone()]
+        );
+        assert_eq!(
+            store.list_fleets().await.unwrap(),
+            vec![("kiosks".to_string(), 1)]
+        );
+        assert!(store.remove_from_fleet("kiosks", &b.id).await.unwrap());
+        assert!(store.list_fleets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_adopts_database_without_migrations() {
+        let dir = std::env::temp_dir().join(format!("clasp-registry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("registry.db");
+        let path_str = path.to_str().unwrap();
+
+        // A database from before migrations, with one entity
+        let store = SqliteEntityStore::in_memory().unwrap();
+        let entity = create_test_entity("legacy");
+        store.create(&entity).await.unwrap();
+        {
+            let conn = store.conn.lock().unwrap();
+            conn.execute_batch("DROP TABLE schema_migrations").unwrap();
+            conn.execute("VACUUM INTO ?1", params![path_str]).unwrap();
+        }
+
+        let store = SqliteEntityStore::open(path_str).unwrap();
+        assert!(store.get(&entity.id).await.unwrap().is_some());
+        let conn = store.conn.lock().unwrap();
+        assert_eq!(migrate::current_version(&conn).unwrap(), 1);
+        assert!(dir.join("registry.db.v0.bak").exists());
+        drop(conn);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -4,9 +4,72 @@ use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::RwLock;
 
-use crate::entity::{Entity, EntityId, EntityStatus};
+use crate::entity::{Entity, EntityId, EntityStatus, EntityType};
 use crate::error::{RegistryError, Result};
 
+/// Criteria for [`EntityStore::query`]; every criterion set must match
+///
+/// ```
+/// use clasp_registry::{EntityFilter, EntityStatus, EntityType};
+///
+/// let filter = EntityFilter::new()
+///     .entity_type(EntityType::Device)
+///     .status(EntityStatus::Active)
+///     .attribute("location", "stage-left");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityFilter {
+    pub entity_type: Option<EntityType>,
+    pub status: Option<EntityStatus>,
+    pub tag: Option<String>,
+    /// Metadata entries the entity must have, as (key, value)
+    pub attributes: Vec<(String, String)>,
+}
+
+impl EntityFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only entities of this type
+    pub fn entity_type(mut self, entity_type: EntityType) -> Self {
+        self.entity_type = Some(entity_type);
+        self
+    }
+
+    /// Only entities with this status
+    pub fn status(mut self, status: EntityStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only entities with this tag
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Only entities whose metadata has `key` set to `value`
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        self.attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Check whether an entity matches
+    pub fn matches(&self, entity: &Entity) -> bool {
+        self.entity_type.is_none_or(|t| t == entity.entity_type)
+            && self.status.is_none_or(|s| s == entity.status)
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| entity.tags.iter().any(|t| t == tag))
+            && self
+                .attributes
+                .iter()
+                .all(|(key, value)| entity.metadata.get(key) == Some(value))
+    }
+}
+
 /// Storage backend for entities
 #[async_trait]
 pub trait EntityStore: Send + Sync {
@@ -25,6 +88,9 @@ pub trait EntityStore: Send + Sync {
     /// Find entities by namespace pattern
     async fn find_by_namespace(&self, namespace: &str) -> Result<Vec<Entity>>;
 
+    /// Find entities matching a filter
+    async fn query(&self, filter: &EntityFilter) -> Result<Vec<Entity>>;
+
     /// List entities with pagination
     async fn list(&self, offset: usize, limit: usize) -> Result<Vec<Entity>>;
 
@@ -100,6 +166,15 @@ impl EntityStore for MemoryEntityStore {
             .collect())
     }
 
+    async fn query(&self, filter: &EntityFilter) -> Result<Vec<Entity>> {
+        let entities = self.entities.read().unwrap();
+        Ok(entities
+            .values()
+            .filter(|e| filter.matches(e))
+            .cloned()
+            .collect())
+    }
+
     async fn list(&self, offset: usize, limit: usize) -> Result<Vec<Entity>> {
         let entities = self.entities.read().unwrap();
         Ok(entities
@@ -201,6 +276,37 @@ mod tests {
         assert!(found.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_memory_store_query() {
+        let store = MemoryEntityStore::new();
+        for (name, location, model) in [
+            ("a", "stage-left", "ESP32"),
+            ("b", "stage-left", "RP2040"),
+            ("c", "stage-right", "ESP32"),
+        ] {
+            let mut entity = create_test_entity(name);
+            entity
+                .metadata
+                .insert("location".to_string(), location.to_string());
+            entity
+                .metadata
+                .insert("model".to_string(), model.to_string());
+            store.create(&entity).await.unwrap();
+        }
+
+        let filter = EntityFilter::new().attribute("location", "stage-left");
+        assert_eq!(store.query(&filter).await.unwrap().len(), 2);
+
+        let filter = filter.attribute("model", "ESP32");
+        let found = store.query(&filter).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "a");
+
+        let filter = EntityFilter::new().entity_type(EntityType::User);
+        assert!(store.query(&filter).await.unwrap().is_empty());
+        assert_eq!(store.query(&EntityFilter::new()).await.unwrap().len(), 3);
+    }
+
     #[tokio::test]
     async fn test_memory_store_update_status() {
         let store = MemoryEntityStore::new();
@@ -116,7 +116,7 @@ pub fn verify_token_signature(payload: &EntityTokenPayload, public_key: &[u8]) -
         .map_err(|e| RegistryError::SignatureError(format!("signature verification failed: {}", e)))
 }
 
-mod serde_bytes {
+pub(crate) mod serde_bytes {
     use serde::{self, Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
@@ -2,11 +2,12 @@
 
 use std::sync::Arc;
 
-use clasp_core::security::{Scope, TokenInfo, TokenValidator, ValidationResult};
+use clasp_core::security::{EntityInfo, Scope, TokenInfo, TokenValidator, ValidationResult};
 
-use crate::entity::Entity;
+use crate::entity::{Entity, EntityId};
+use crate::error::RegistryError;
 use crate::store::EntityStore;
-use crate::token::{parse_token, verify_token_signature, ENTITY_TOKEN_PREFIX};
+use crate::token::{parse_token, verify_token_signature, EntityTokenPayload, ENTITY_TOKEN_PREFIX};
 
 /// Token validator for entity-signed tokens.
 ///
@@ -34,18 +35,20 @@ impl EntityValidator {
     }
 }
 
-impl TokenValidator for EntityValidator {
-    fn validate(&self, token: &str) -> ValidationResult {
+impl EntityValidator {
+    /// Checks that need no store lookup. `Err` carries the final result.
+    fn precheck(
+        &self,
+        token: &str,
+    ) -> Result<(EntityTokenPayload, EntityId), Box<ValidationResult>> {
         // Check prefix -- if not ours, pass to next validator
         if !token.starts_with(ENTITY_TOKEN_PREFIX) {
-            return ValidationResult::NotMyToken;
+            return Err(Box::new(ValidationResult::NotMyToken));
         }
 
         // Parse the token payload
-        let payload = match parse_token(token) {
-            Ok(p) => p,
-            Err(e) => return ValidationResult::Invalid(format!("malformed entity token: {}", e)),
-        };
+        let payload = parse_token(token)
+            .map_err(|e| ValidationResult::Invalid(format!("malformed entity token: {}", e)))?;
 
         // Check token age if configured
         if self.max_token_age > 0 {
@@ -55,25 +58,23 @@ impl TokenValidator for EntityValidator {
                 .unwrap_or(0);
 
             if now.saturating_sub(payload.timestamp) > self.max_token_age {
-                return ValidationResult::Expired;
+                return Err(Box::new(ValidationResult::Expired));
             }
         }
 
-        // Look up entity -- we need to block on the async store
-        // Since TokenValidator::validate is sync, we use a thread-local runtime
-        // or assume we're called from within a tokio runtime
-        let store = self.store.clone();
-        let entity_id_str = payload.entity_id.clone();
-
-        let entity_id = match crate::entity::EntityId::parse(&entity_id_str) {
-            Ok(id) => id,
-            Err(e) => return ValidationResult::Invalid(format!("invalid entity ID: {}", e)),
-        };
+        let entity_id = EntityId::parse(&payload.entity_id)
+            .map_err(|e| ValidationResult::Invalid(format!("invalid entity ID: {}", e)))?;
+        Ok((payload, entity_id))
+    }
 
-        // Use tokio::task::block_in_place to call async from sync context
-        let entity: Entity = match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(store.get(&entity_id))
-        }) {
+    /// Validate against the looked-up entity
+    fn finish(
+        token: &str,
+        payload: &EntityTokenPayload,
+        lookup: Result<Option<Entity>, RegistryError>,
+    ) -> ValidationResult {
+        let entity_id_str = payload.entity_id.clone();
+        let entity = match lookup {
             Ok(Some(e)) => e,
             Ok(None) => {
                 return ValidationResult::Invalid(format!("entity not found: {}", entity_id_str))
@@ -90,7 +91,7 @@ impl TokenValidator for EntityValidator {
         }
 
         // Verify signature
-        if let Err(e) = verify_token_signature(&payload, &entity.public_key) {
+        if let Err(e) = verify_token_signature(payload, &entity.public_key) {
             return ValidationResult::Invalid(format!("signature error: {}", e));
         }
 
@@ -122,12 +123,36 @@ impl TokenValidator for EntityValidator {
         };
 
         let info = TokenInfo::new(token.to_string(), scopes)
-            .with_subject(entity_id_str)
+            .with_subject(entity_id_str.clone())
             .with_metadata("entity_type", entity.entity_type.to_string())
-            .with_metadata("entity_name", entity.name);
+            .with_metadata("entity_name", entity.name.clone())
+            .with_entity(EntityInfo {
+                id: entity_id_str,
+                entity_type: entity.entity_type.to_string(),
+                name: entity.name,
+                tags: entity.tags,
+                metadata: entity.metadata,
+            });
 
         ValidationResult::Valid(info)
     }
+}
+
+impl TokenValidator for EntityValidator {
+    fn validate(&self, token: &str) -> ValidationResult {
+        let (payload, entity_id) = match self.precheck(token) {
+            Ok(parsed) => parsed,
+            Err(result) => return *result,
+        };
+
+        // Since TokenValidator::validate is sync, block on the async store
+        // from within the tokio runtime. The AsyncTokenValidator impl avoids this.
+        let store = self.store.clone();
+        let lookup = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(store.get(&entity_id))
+        });
+        Self::finish(token, &payload, lookup)
+    }
 
     fn name(&self) -> &str {
         "Entity"
@@ -138,6 +163,22 @@ impl TokenValidator for EntityValidator {
     }
 }
 
+#[async_trait::async_trait]
+impl clasp_core::security::AsyncTokenValidator for EntityValidator {
+    async fn validate(&self, token: &str) -> ValidationResult {
+        let (payload, entity_id) = match self.precheck(token) {
+            Ok(parsed) => parsed,
+            Err(result) => return *result,
+        };
+        let lookup = self.store.get(&entity_id).await;
+        Self::finish(token, &payload, lookup)
+    }
+
+    fn name(&self) -> &str {
+        "Entity"
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,9 +201,39 @@ mod tests {
             ValidationResult::Valid(info) => {
                 assert!(info.has_scope(clasp_core::Action::Read, "/any/path"));
                 assert_eq!(info.subject.as_deref(), Some(keypair.entity_id.as_str()));
+                let entity = info.entity.expect("entity info");
+                assert_eq!(entity.id, keypair.entity_id.as_str());
+                assert_eq!(entity.entity_type, "device");
+                assert_eq!(entity.name, "test-device");
+            }
+            other => panic!("expected Valid, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entity_validator_async_current_thread() {
+        use clasp_core::security::AsyncTokenValidator;
+
+        // The async path never blocks, so it works on a current-thread runtime
+        let store = Arc::new(MemoryEntityStore::new());
+        let keypair = EntityKeypair::generate().unwrap();
+        let mut entity = keypair.to_entity(EntityType::Device, "test-device".to_string());
+        entity.scopes = vec!["write:/sensors/**".to_string()];
+        store.create(&entity).await.unwrap();
+
+        let validator = EntityValidator::new(store);
+        let token = generate_token(&keypair).unwrap();
+
+        match AsyncTokenValidator::validate(&validator, &token).await {
+            ValidationResult::Valid(info) => {
+                assert!(info.has_scope(clasp_core::Action::Write, "/sensors/temp"));
             }
             other => panic!("expected Valid, got {:?}", other),
         }
+        assert!(matches!(
+            AsyncTokenValidator::validate(&validator, "cpsk_other").await,
+            ValidationResult::NotMyToken
+        ));
     }
 
     #[tokio::test(flavor = "multi_thread")]
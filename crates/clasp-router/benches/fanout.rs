@@ -0,0 +1,64 @@
+//! Fan-out benchmarks
+//!
+//! Broadcasts one SET frame to 100 sessions with 10 subscriptions each,
+//! with and without per-subscription delivery stats.
+
+use clasp_core::{codec, Message, SetMessage, SubscribeOptions, Value};
+use clasp_router::delivery_stats::DeliveryStats;
+use clasp_router::subscription::Subscription;
+use clasp_router::Session;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const SESSIONS: usize = 100;
+const SUBSCRIPTIONS: u32 = 10;
+
+fn sessions(delivery_stats: bool) -> Vec<Session> {
+    (0..SESSIONS)
+        .map(|_| {
+            let mut session = Session::stub(None);
+            if delivery_stats {
+                session.set_delivery_stats(DeliveryStats::enabled());
+            }
+            for id in 0..SUBSCRIPTIONS {
+                let subscription = Subscription::new(
+                    id,
+                    session.id.clone(),
+                    &format!("/mixer/{}/**", id),
+                    vec![],
+                    SubscribeOptions::default(),
+                )
+                .unwrap();
+                session.delivery_stats().add(subscription);
+            }
+            session
+        })
+        .collect()
+}
+
+fn fanout_benchmark(c: &mut Criterion) {
+    let frame = codec::encode(&Message::Set(SetMessage {
+        address: "/mixer/3/level".to_string(),
+        value: Value::Float(0.5),
+        revision: Some(1),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    }))
+    .unwrap();
+
+    for (name, delivery_stats) in [("fanout_set", false), ("fanout_set_delivery_stats", true)] {
+        let sessions = sessions(delivery_stats);
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                for session in &sessions {
+                    black_box(session.try_send(frame.clone())).unwrap();
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(benches, fanout_benchmark);
+criterion_main!(benches);
@@ -8,6 +8,7 @@
 //!
 //! - [`MqttServerAdapter`] - Accept MQTT clients on port 1883/8883
 //! - [`OscServerAdapter`] - Accept OSC clients via UDP with session tracking
+//! - [`RespServerAdapter`] - Accept Redis clients (GET/SET/SUBSCRIBE) on port 6379
 //!
 //! ## Architecture
 //!
@@ -29,10 +30,21 @@
 //! ```
 //!
 //! Adapters share the router's core state (sessions, subscriptions, state storage)
-//! and translate between their native protocol and CLASP semantics.
+//! and translate between their native protocol and CLASP semantics. Writes
+//! they store pass the router's SET checks (see [`WriteChecks`]).
 
+#[cfg(feature = "mqtt-server")]
 pub mod mqtt_server;
+#[cfg(feature = "osc-server")]
 pub mod osc_server;
+#[cfg(feature = "resp-server")]
+pub mod resp_server;
+pub mod write_checks;
 
+#[cfg(feature = "mqtt-server")]
 pub use mqtt_server::{MqttServerAdapter, MqttServerConfig};
+#[cfg(feature = "osc-server")]
 pub use osc_server::{OscServerAdapter, OscServerConfig};
+#[cfg(feature = "resp-server")]
+pub use resp_server::{RespServerAdapter, RespServerConfig};
+pub use write_checks::WriteChecks;
@@ -11,9 +11,13 @@
 //! | QoS 0 | Fire-and-forget |
 //! | QoS 1 | With ACK |
 //! | Username/Password | Token auth |
+//!
+//! An [`AddressMapper`] can rename namespaced addresses further
+//! (`/mqtt/desk/3/fader` → `/mixer/channel/3/level`), see
+//! [`crate::address_map`].
 
 use bytes::{Bytes, BytesMut};
-use clasp_core::{codec, Message, SetMessage, SignalType, Value};
+use clasp_core::{codec, Message, PublishMessage, SetMessage, SignalType, Value};
 use dashmap::DashMap;
 use mqttbytes::v4::{
     ConnAck, ConnectReturnCode, Packet, PingResp, PubAck, Publish, SubAck, SubscribeReasonCode,
@@ -31,10 +35,16 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+use crate::address_map::AddressMapper;
+use crate::canonical::AddressPolicy;
 use crate::error::{Result, RouterError};
+use crate::reserved::ReservedNamespace;
 use crate::session::{Session, SessionId};
 use crate::state::RouterState;
 use crate::subscription::{Subscription, SubscriptionManager};
+use crate::transport_policy::TransportPolicy;
+
+use super::WriteChecks;
 
 use clasp_core::security::{TokenValidator, ValidationResult};
 
@@ -159,6 +169,16 @@ pub struct MqttServerAdapter {
     running: Arc<RwLock<bool>>,
     /// Token validator for authentication (if require_auth is true)
     validator: Option<Arc<dyn TokenValidator>>,
+    /// What MQTT clients may do
+    policy: TransportPolicy,
+    /// How mapped addresses are canonicalized
+    address_policy: AddressPolicy,
+    /// Translation between namespaced topics and router addresses
+    mapper: Option<Arc<dyn AddressMapper>>,
+    /// Who may write under `/clasp/`, if enforced
+    reserved: Option<ReservedNamespace>,
+    /// The router's SET checks, if enforced
+    checks: Option<WriteChecks>,
     /// TLS acceptor (if configured)
     #[cfg(feature = "mqtts")]
     tls_acceptor: Option<TlsAcceptor>,
@@ -180,6 +200,11 @@ impl MqttServerAdapter {
             mqtt_sessions: Arc::new(DashMap::new()),
             running: Arc::new(RwLock::new(false)),
             validator: None,
+            policy: TransportPolicy::default(),
+            address_policy: AddressPolicy::off(),
+            mapper: None,
+            reserved: None,
+            checks: None,
             #[cfg(feature = "mqtts")]
             tls_acceptor: None,
         }
@@ -195,6 +220,52 @@ impl MqttServerAdapter {
         self
     }
 
+    /// Restrict what MQTT clients may do (see [`crate::transport_policy`])
+    ///
+    /// Where SET is refused but PUBLISH is allowed, MQTT PUBLISH packets
+    /// are forwarded as events instead of being stored.
+    pub fn with_policy(mut self, policy: TransportPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Canonicalize mapped addresses (see [`crate::canonical`])
+    ///
+    /// Publishes to topics whose address is refused are dropped, and
+    /// subscriptions to them fail.
+    pub fn with_address_policy(mut self, policy: AddressPolicy) -> Self {
+        self.address_policy = policy;
+        self
+    }
+
+    /// Rename addresses between the MQTT namespace and the router's naming
+    /// scheme (see [`crate::address_map`])
+    ///
+    /// Topic filters are mapped like topics: with a rule from
+    /// `/mqtt/desk/{n}/fader`, `desk/+/fader` subscribes to every mapped
+    /// fader, the placeholder standing for the wildcard.
+    pub fn with_address_mapper(mut self, mapper: Arc<dyn AddressMapper>) -> Self {
+        self.mapper = Some(mapper);
+        self
+    }
+
+    /// Protect the reserved namespace (see [`crate::reserved`])
+    ///
+    /// PUBLISH packets to reserved addresses are dropped and audited unless
+    /// the client authenticated with a token that has admin scope.
+    pub fn with_reserved(mut self, reserved: ReservedNamespace) -> Self {
+        self.reserved = Some(reserved);
+        self
+    }
+
+    /// Run the router's SET checks on stored publishes (see [`WriteChecks`])
+    ///
+    /// PUBLISH packets the checks refuse are dropped.
+    pub fn with_write_checks(mut self, checks: WriteChecks) -> Self {
+        self.checks = Some(checks);
+        self
+    }
+
     /// Start the MQTT server
     pub async fn serve(&self) -> Result<()> {
         let listener = TcpListener::bind(&self.config.bind_addr)
@@ -238,6 +309,7 @@ impl MqttServerAdapter {
         let mqtt_sessions = Arc::clone(&self.mqtt_sessions);
         let clasp_sessions = Arc::clone(&self.sessions);
         let subscriptions = Arc::clone(&self.subscriptions);
+        let state = Arc::clone(&self.state);
         let running = Arc::clone(&self.running);
         let timeout = Duration::from_secs(self.config.session_timeout_secs);
 
@@ -268,6 +340,12 @@ impl MqttServerAdapter {
                         // Clean up CLASP session
                         clasp_sessions.remove(&mqtt_session.clasp_session_id);
                         subscriptions.remove_session(&mqtt_session.clasp_session_id);
+                        crate::handlers::release_session_state(
+                            &mqtt_session.clasp_session_id,
+                            &state,
+                            &clasp_sessions,
+                            &subscriptions,
+                        );
                     }
                 }
             }
@@ -283,6 +361,11 @@ impl MqttServerAdapter {
         let mqtt_sessions = Arc::clone(&self.mqtt_sessions);
         let running = Arc::clone(&self.running);
         let validator = self.validator.clone();
+        let policy = self.policy.clone();
+        let address_policy = self.address_policy.clone();
+        let mapper = self.mapper.clone();
+        let reserved = self.reserved.clone();
+        let checks = self.checks.clone();
 
         tokio::spawn(async move {
             if let Err(e) = handle_mqtt_connection(
@@ -295,6 +378,11 @@ impl MqttServerAdapter {
                 mqtt_sessions,
                 running,
                 validator,
+                policy,
+                address_policy,
+                mapper,
+                reserved,
+                checks,
             )
             .await
             {
@@ -325,6 +413,11 @@ async fn handle_mqtt_connection(
     mqtt_sessions: Arc<DashMap<String, Arc<MqttSession>>>,
     running: Arc<RwLock<bool>>,
     validator: Option<Arc<dyn TokenValidator>>,
+    policy: TransportPolicy,
+    address_policy: AddressPolicy,
+    mapper: Option<Arc<dyn AddressMapper>>,
+    reserved: Option<ReservedNamespace>,
+    checks: Option<WriteChecks>,
 ) -> Result<()> {
     let mut read_buf = BytesMut::with_capacity(4096);
 
@@ -364,6 +457,7 @@ async fn handle_mqtt_connection(
     info!("MQTT CONNECT from {} (client_id: {})", peer_addr, client_id);
 
     // Validate credentials if required
+    let mut auth = None;
     if config.require_auth {
         let login = match &connect.login {
             Some(login) => login,
@@ -383,8 +477,9 @@ async fn handle_mqtt_connection(
         let token = &login.password;
         if let Some(ref validator) = validator {
             match validator.validate(token) {
-                ValidationResult::Valid(_token_info) => {
+                ValidationResult::Valid(token_info) => {
                     debug!("MQTT client {} authenticated successfully", client_id);
+                    auth = Some((token.clone(), token_info));
                 }
                 ValidationResult::Invalid(reason) => {
                     warn!("MQTT auth failed for {}: {}", client_id, reason);
@@ -437,12 +532,18 @@ async fn handle_mqtt_connection(
     }
 
     // Create CLASP session (using a transport sender that writes to our channel)
-    let mqtt_sender = MqttTransportSender::new(tx.clone());
-    let clasp_session = Arc::new(Session::new(
+    let mqtt_sender =
+        MqttTransportSender::new(tx.clone(), config.namespace.clone(), mapper.clone());
+    let mut clasp_session = Session::new(
         Arc::new(mqtt_sender),
         format!("mqtt:{}", client_id),
         vec!["mqtt".to_string()],
-    ));
+    );
+    clasp_session.set_transport("mqtt");
+    if let Some((token, info)) = auth {
+        clasp_session.set_authenticated(token, info.subject, info.scopes);
+    }
+    let clasp_session = Arc::new(clasp_session);
     let clasp_session_id = clasp_session.id.clone();
     clasp_sessions.insert(clasp_session_id.clone(), clasp_session);
 
@@ -494,6 +595,11 @@ async fn handle_mqtt_connection(
                                         &packet,
                                         &mqtt_session,
                                         &config,
+                                        &policy,
+                                        &address_policy,
+                                        mapper.as_deref(),
+                                        reserved.as_ref(),
+                                        checks.as_ref(),
                                         &subscriptions,
                                         &state,
                                         &clasp_sessions,
@@ -540,16 +646,80 @@ async fn handle_mqtt_connection(
     mqtt_sessions.remove(&client_id);
     clasp_sessions.remove(&clasp_session_id);
     subscriptions.remove_session(&clasp_session_id);
+    crate::handlers::release_session_state(
+        &clasp_session_id,
+        &state,
+        &clasp_sessions,
+        &subscriptions,
+    );
 
     info!("MQTT session {} cleaned up", client_id);
     Ok(())
 }
 
+/// Whether the reserved namespace lets the MQTT client behind
+/// `clasp_session_id` write `address`
+fn reserved_permits(
+    reserved: Option<&ReservedNamespace>,
+    clasp_session_id: &SessionId,
+    address: &str,
+    clasp_sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) -> bool {
+    if reserved.is_none() {
+        return true;
+    }
+    let Some(session) = clasp_sessions
+        .get(clasp_session_id)
+        .map(|s| Arc::clone(s.value()))
+    else {
+        return false;
+    };
+    crate::reserved::adapter_permits(
+        reserved,
+        &session,
+        address,
+        "PUBLISH",
+        clasp_sessions,
+        subscriptions,
+    )
+}
+
+/// The value to store for a SET to `address` by the MQTT client behind
+/// `clasp_session_id`, or `None` if the router's SET checks refuse it
+fn checked_value(
+    checks: Option<&WriteChecks>,
+    clasp_session_id: &SessionId,
+    address: &str,
+    value: Value,
+    clasp_sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    state: &RouterState,
+) -> Option<Value> {
+    let Some(checks) = checks else {
+        return Some(value);
+    };
+    let session = clasp_sessions
+        .get(clasp_session_id)
+        .map(|s| Arc::clone(s.value()))?;
+    match checks.check(&session, address, value, state) {
+        Ok(value) => Some(value),
+        Err(reason) => {
+            debug!("MQTT PUBLISH to {} dropped: {}", address, reason);
+            None
+        }
+    }
+}
+
 /// Handle a single MQTT packet
 async fn handle_mqtt_packet(
     packet: &Packet,
     mqtt_session: &Arc<MqttSession>,
     config: &MqttServerConfig,
+    policy: &TransportPolicy,
+    address_policy: &AddressPolicy,
+    mapper: Option<&dyn AddressMapper>,
+    reserved: Option<&ReservedNamespace>,
+    checks: Option<&WriteChecks>,
     subscriptions: &Arc<SubscriptionManager>,
     state: &Arc<RouterState>,
     clasp_sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
@@ -570,6 +740,23 @@ async fn handle_mqtt_packet(
 
                 // Convert MQTT topic filter to CLASP pattern
                 let clasp_pattern = mqtt_topic_to_clasp_pattern(&config.namespace, topic_filter);
+                let clasp_pattern = map_inbound(mapper, clasp_pattern);
+                let clasp_pattern = match address_policy.canonicalize(&clasp_pattern) {
+                    Ok(pattern) => pattern.into_owned(),
+                    Err(reason) => {
+                        debug!("MQTT subscription {} refused: {}", topic_filter, reason);
+                        return_codes.push(SubscribeReasonCode::Failure);
+                        continue;
+                    }
+                };
+                if !policy.permits("SUBSCRIBE", &clasp_pattern) {
+                    debug!(
+                        "MQTT subscription {} refused: transport policy",
+                        topic_filter
+                    );
+                    return_codes.push(SubscribeReasonCode::Failure);
+                    continue;
+                }
 
                 // Create CLASP subscription
                 let sub_id = mqtt_session.next_subscription_id();
@@ -592,7 +779,7 @@ async fn handle_mqtt_packet(
                         let snapshot = state.snapshot(&clasp_pattern);
                         for param in snapshot.params {
                             let mqtt_topic =
-                                clasp_address_to_mqtt_topic(&config.namespace, &param.address);
+                                mapped_mqtt_topic(&config.namespace, mapper, &param.address);
                             let payload = value_to_mqtt_payload(&param.value);
 
                             let publish = Publish::new(&mqtt_topic, QoS::AtMostOnce, payload);
@@ -628,28 +815,93 @@ async fn handle_mqtt_packet(
 
             // Convert MQTT topic to CLASP address
             let clasp_address = mqtt_topic_to_clasp_address(&config.namespace, &publish.topic);
+            let clasp_address = map_inbound(mapper, clasp_address);
+            let clasp_address = match address_policy.canonicalize(&clasp_address) {
+                Ok(address) => address.into_owned(),
+                Err(reason) => {
+                    debug!("MQTT PUBLISH to {} dropped: {}", publish.topic, reason);
+                    return Ok(());
+                }
+            };
 
             // Parse payload to CLASP value
             let value = mqtt_payload_to_value(&publish.payload);
 
-            // Apply to state
-            let set_msg = SetMessage {
-                address: clasp_address.clone(),
-                value: value.clone(),
-                revision: None,
-                lock: false,
-                unlock: false,
-                ttl: None,
+            // Store as a param, or forward as an event where the policy
+            // only allows PUBLISH
+            let outgoing = if state.maintenance().blocks(&clasp_address) {
+                debug!(
+                    "MQTT PUBLISH to {} dropped: maintenance mode",
+                    clasp_address
+                );
+                None
+            } else if !reserved_permits(
+                reserved,
+                &mqtt_session.clasp_session_id,
+                &clasp_address,
+                clasp_sessions,
+                subscriptions,
+            ) {
+                debug!(
+                    "MQTT PUBLISH to {} dropped: reserved address",
+                    clasp_address
+                );
+                None
+            } else if policy.permits("SET", &clasp_address) {
+                checked_value(
+                    checks,
+                    &mqtt_session.clasp_session_id,
+                    &clasp_address,
+                    value,
+                    clasp_sessions,
+                    state,
+                )
+                .and_then(|value| {
+                    let set_msg = SetMessage {
+                        address: clasp_address.clone(),
+                        value,
+                        revision: None,
+                        lock: false,
+                        unlock: false,
+                        ttl: None,
+                        trace: false,
+                        branch: None,
+                    };
+                    state
+                        .apply_set(&set_msg, &mqtt_session.clasp_session_id)
+                        .ok()
+                        .map(|revision| {
+                            let updated_set = SetMessage {
+                                revision: Some(revision),
+                                ..set_msg
+                            };
+                            (Message::Set(updated_set), SignalType::Param)
+                        })
+                })
+            } else if policy.permits("PUBLISH", &clasp_address) {
+                let event = Message::Publish(PublishMessage {
+                    address: clasp_address.clone(),
+                    signal: Some(SignalType::Event),
+                    value: Some(value),
+                    payload: None,
+                    samples: None,
+                    rate: None,
+                    id: None,
+                    phase: None,
+                    timestamp: Some(clasp_core::time::now()),
+                    timeline: None,
+                });
+                Some((event, SignalType::Event))
+            } else {
+                debug!(
+                    "MQTT PUBLISH to {} dropped: transport policy",
+                    clasp_address
+                );
+                None
             };
-
-            if let Ok(revision) = state.apply_set(&set_msg, &mqtt_session.clasp_session_id) {
+            if let Some((broadcast_msg, signal)) = outgoing {
                 // Broadcast to CLASP subscribers
-                let subscribers =
-                    subscriptions.find_subscribers(&clasp_address, Some(SignalType::Param));
-
-                let mut updated_set = set_msg.clone();
-                updated_set.revision = Some(revision);
-                let broadcast_msg = Message::Set(updated_set);
+                let subscribers = subscriptions.find_subscribers(&clasp_address, Some(signal));
 
                 if let Ok(bytes) = codec::encode(&broadcast_msg) {
                     for sub_session_id in subscribers {
@@ -731,11 +983,25 @@ fn mqtt_topic_to_clasp_address(namespace: &str, topic: &str) -> String {
 fn clasp_address_to_mqtt_topic(namespace: &str, address: &str) -> String {
     address
         .strip_prefix(namespace)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
         .unwrap_or(address)
         .trim_start_matches('/')
         .to_string()
 }
 
+/// Apply the adapter's address mapper to a namespaced address or pattern
+fn map_inbound(mapper: Option<&dyn AddressMapper>, address: String) -> String {
+    mapper.and_then(|m| m.inbound(&address)).unwrap_or(address)
+}
+
+/// Convert CLASP address to MQTT topic, undoing the address mapping
+fn mapped_mqtt_topic(namespace: &str, mapper: Option<&dyn AddressMapper>, address: &str) -> String {
+    match mapper.and_then(|m| m.outbound(address)) {
+        Some(mapped) => clasp_address_to_mqtt_topic(namespace, &mapped),
+        None => clasp_address_to_mqtt_topic(namespace, address),
+    }
+}
+
 /// Convert MQTT payload to CLASP Value
 fn mqtt_payload_to_value(payload: &[u8]) -> Value {
     if let Ok(text) = std::str::from_utf8(payload) {
@@ -805,11 +1071,25 @@ fn value_to_mqtt_payload(value: &Value) -> Vec<u8> {
 /// Transport sender implementation for MQTT clients
 struct MqttTransportSender {
     tx: mpsc::Sender<Bytes>,
+    namespace: String,
+    mapper: Option<Arc<dyn AddressMapper>>,
 }
 
 impl MqttTransportSender {
-    fn new(tx: mpsc::Sender<Bytes>) -> Self {
-        Self { tx }
+    fn new(
+        tx: mpsc::Sender<Bytes>,
+        namespace: String,
+        mapper: Option<Arc<dyn AddressMapper>>,
+    ) -> Self {
+        Self {
+            tx,
+            namespace,
+            mapper,
+        }
+    }
+
+    fn encode(&self, msg: &Message) -> Option<Bytes> {
+        clasp_to_mqtt_publish(msg, &self.namespace, self.mapper.as_deref())
     }
 }
 
@@ -823,7 +1103,7 @@ impl clasp_transport::TransportSender for MqttTransportSender {
 
         // Decode CLASP message
         if let Ok((msg, _)) = codec::decode(&data) {
-            if let Some(mqtt_data) = clasp_to_mqtt_publish(&msg) {
+            if let Some(mqtt_data) = self.encode(&msg) {
                 self.tx
                     .send(mqtt_data)
                     .await
@@ -835,7 +1115,7 @@ impl clasp_transport::TransportSender for MqttTransportSender {
 
     fn try_send(&self, data: Bytes) -> std::result::Result<(), clasp_transport::TransportError> {
         if let Ok((msg, _)) = codec::decode(&data) {
-            if let Some(mqtt_data) = clasp_to_mqtt_publish(&msg) {
+            if let Some(mqtt_data) = self.encode(&msg) {
                 self.tx
                     .try_send(mqtt_data)
                     .map_err(|e| clasp_transport::TransportError::SendFailed(e.to_string()))?;
@@ -855,7 +1135,11 @@ impl clasp_transport::TransportSender for MqttTransportSender {
 }
 
 /// Convert CLASP message to MQTT PUBLISH packet bytes
-fn clasp_to_mqtt_publish(msg: &Message) -> Option<Bytes> {
+fn clasp_to_mqtt_publish(
+    msg: &Message,
+    namespace: &str,
+    mapper: Option<&dyn AddressMapper>,
+) -> Option<Bytes> {
     let (address, value) = match msg {
         Message::Set(set) => (&set.address, &set.value),
         Message::Publish(pub_msg) => {
@@ -873,13 +1157,10 @@ fn clasp_to_mqtt_publish(msg: &Message) -> Option<Bytes> {
         _ => return None,
     };
 
-    // Strip /mqtt prefix to get MQTT topic
-    let topic = address
-        .strip_prefix("/mqtt/")
-        .unwrap_or(address.trim_start_matches('/'));
+    let topic = mapped_mqtt_topic(namespace, mapper, address);
     let payload = value_to_mqtt_payload(value);
 
-    let publish = Publish::new(topic, QoS::AtMostOnce, payload);
+    let publish = Publish::new(&topic, QoS::AtMostOnce, payload);
     let mut buf = BytesMut::new();
     if publish.write(&mut buf).is_ok() {
         Some(buf.freeze())
@@ -914,6 +1195,38 @@ mod tests {
             clasp_address_to_mqtt_topic("/mqtt", "/mqtt/sensors/temp"),
             "sensors/temp"
         );
+        assert_eq!(
+            clasp_address_to_mqtt_topic("/mqtt", "/mqttx/temp"),
+            "mqttx/temp"
+        );
+    }
+
+    #[test]
+    fn test_mapped_topics() {
+        use crate::address_map::{AddressMap, AddressRule};
+
+        let mapper = AddressMap::new(vec![AddressRule::Template {
+            from: "/mqtt/desk/{n}/fader".into(),
+            to: "/mixer/channel/{n}/level".into(),
+        }])
+        .unwrap();
+        let mapper = Some(&mapper as &dyn AddressMapper);
+        assert_eq!(
+            map_inbound(mapper, mqtt_topic_to_clasp_address("/mqtt", "desk/3/fader")),
+            "/mixer/channel/3/level"
+        );
+        assert_eq!(
+            map_inbound(mapper, mqtt_topic_to_clasp_pattern("/mqtt", "desk/+/fader")),
+            "/mixer/channel/*/level"
+        );
+        assert_eq!(
+            mapped_mqtt_topic("/mqtt", mapper, "/mixer/channel/3/level"),
+            "desk/3/fader"
+        );
+        assert_eq!(
+            mapped_mqtt_topic("/mqtt", mapper, "/mqtt/sensors/temp"),
+            "sensors/temp"
+        );
     }
 
     #[test]
@@ -14,13 +14,16 @@
 //! OSC addresses are prefixed with the configured namespace (default: `/osc`):
 //! - OSC `/synth/volume` → CLASP `/osc/synth/volume`
 //!
+//! An [`AddressMapper`] can rename them further (`/osc/1/fader3` → CLASP
+//! `/mixer/channel/3/level`), see [`crate::address_map`].
+//!
 //! ## Bidirectional Communication
 //!
 //! When CLASP messages are published to addresses matching OSC subscriptions,
 //! they are converted back to OSC and sent to the subscribed UDP clients.
 
 use bytes::Bytes;
-use clasp_core::{codec, Message, SetMessage, SignalType, Value};
+use clasp_core::{codec, Message, PublishMessage, SetMessage, SignalType, Value};
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use rosc::{OscBundle, OscMessage, OscPacket, OscType};
@@ -33,10 +36,16 @@ use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 use tracing::{debug, error, info, warn};
 
+use crate::address_map::AddressMapper;
+use crate::canonical::AddressPolicy;
 use crate::error::{Result, RouterError};
+use crate::reserved::ReservedNamespace;
 use crate::session::{Session, SessionId};
 use crate::state::RouterState;
 use crate::subscription::{Subscription, SubscriptionManager};
+use crate::transport_policy::TransportPolicy;
+
+use super::WriteChecks;
 
 /// OSC Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,6 +138,16 @@ pub struct OscServerAdapter {
     running: Arc<RwLock<bool>>,
     /// UDP socket for sending replies
     socket: Arc<RwLock<Option<Arc<UdpSocket>>>>,
+    /// What OSC clients may do
+    policy: TransportPolicy,
+    /// How mapped addresses are canonicalized
+    address_policy: AddressPolicy,
+    /// Translation between namespaced OSC addresses and router addresses
+    mapper: Option<Arc<dyn AddressMapper>>,
+    /// Who may write under `/clasp/`, if enforced
+    reserved: Option<ReservedNamespace>,
+    /// The router's SET checks, if enforced
+    checks: Option<WriteChecks>,
 }
 
 impl OscServerAdapter {
@@ -147,9 +166,58 @@ impl OscServerAdapter {
             osc_sessions: Arc::new(DashMap::new()),
             running: Arc::new(RwLock::new(false)),
             socket: Arc::new(RwLock::new(None)),
+            policy: TransportPolicy::default(),
+            address_policy: AddressPolicy::off(),
+            mapper: None,
+            reserved: None,
+            checks: None,
         }
     }
 
+    /// Restrict what OSC clients may do (see [`crate::transport_policy`])
+    ///
+    /// Where SET is refused but PUBLISH is allowed, OSC messages are
+    /// forwarded as events instead of being stored.
+    pub fn with_policy(mut self, policy: TransportPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Canonicalize mapped addresses (see [`crate::canonical`])
+    ///
+    /// Messages to addresses that are refused are dropped.
+    pub fn with_address_policy(mut self, policy: AddressPolicy) -> Self {
+        self.address_policy = policy;
+        self
+    }
+
+    /// Rename addresses between the OSC namespace and the router's naming
+    /// scheme (see [`crate::address_map`])
+    ///
+    /// With `auto_subscribe`, sessions are also subscribed to the mapper's
+    /// [patterns](AddressMapper::patterns).
+    pub fn with_address_mapper(mut self, mapper: Arc<dyn AddressMapper>) -> Self {
+        self.mapper = Some(mapper);
+        self
+    }
+
+    /// Protect the reserved namespace (see [`crate::reserved`])
+    ///
+    /// OSC clients never authenticate, so messages to reserved addresses
+    /// are dropped and audited.
+    pub fn with_reserved(mut self, reserved: ReservedNamespace) -> Self {
+        self.reserved = Some(reserved);
+        self
+    }
+
+    /// Run the router's SET checks on stored messages (see [`WriteChecks`])
+    ///
+    /// Messages the checks refuse are dropped.
+    pub fn with_write_checks(mut self, checks: WriteChecks) -> Self {
+        self.checks = Some(checks);
+        self
+    }
+
     /// Start the OSC server
     pub async fn serve(&self) -> Result<()> {
         let socket = UdpSocket::bind(&self.config.bind_addr)
@@ -205,12 +273,19 @@ impl OscServerAdapter {
         }
 
         // Create new OSC session
-        let osc_sender = OscTransportSender::new(peer_addr, Arc::clone(&self.socket));
-        let clasp_session = Arc::new(Session::new(
+        let osc_sender = OscTransportSender::new(
+            peer_addr,
+            Arc::clone(&self.socket),
+            self.config.namespace.clone(),
+            self.mapper.clone(),
+        );
+        let mut clasp_session = Session::new(
             Arc::new(osc_sender),
             format!("osc:{}", peer_addr),
             vec!["osc".to_string()],
-        ));
+        );
+        clasp_session.set_transport("osc");
+        let clasp_session = Arc::new(clasp_session);
         let clasp_session_id = clasp_session.id.clone();
         self.sessions
             .insert(clasp_session_id.clone(), Arc::clone(&clasp_session));
@@ -219,17 +294,25 @@ impl OscServerAdapter {
 
         // Auto-subscribe if configured
         if self.config.auto_subscribe {
-            let pattern = format!("{}/**", self.config.namespace);
-            let sub_id = osc_session.next_subscription_id();
-            if let Ok(subscription) = Subscription::new(
-                sub_id,
-                clasp_session_id.clone(),
-                &pattern,
-                vec![],
-                Default::default(),
-            ) {
-                self.subscriptions.add(subscription);
-                osc_session.subscriptions.write().insert(pattern);
+            let mut patterns = vec![format!("{}/**", self.config.namespace)];
+            if let Some(mapper) = &self.mapper {
+                patterns.extend(mapper.patterns());
+            }
+            for pattern in patterns {
+                if !self.policy.permits("SUBSCRIBE", &pattern) {
+                    continue;
+                }
+                let sub_id = osc_session.next_subscription_id();
+                if let Ok(subscription) = Subscription::new(
+                    sub_id,
+                    clasp_session_id.clone(),
+                    &pattern,
+                    vec![],
+                    Default::default(),
+                ) {
+                    self.subscriptions.add(subscription);
+                    osc_session.subscriptions.write().insert(pattern);
+                }
             }
         }
 
@@ -260,11 +343,42 @@ impl OscServerAdapter {
         );
 
         // Convert OSC address to CLASP address
-        let clasp_address = format!("{}{}", self.config.namespace, msg.addr);
+        let mut clasp_address = format!("{}{}", self.config.namespace, msg.addr);
+        if let Some(mapped) = self.mapper.as_ref().and_then(|m| m.inbound(&clasp_address)) {
+            clasp_address = mapped;
+        }
+        let clasp_address = match self.address_policy.canonicalize(&clasp_address) {
+            Ok(address) => address.into_owned(),
+            Err(reason) => {
+                debug!("OSC message to {} dropped: {}", msg.addr, reason);
+                return;
+            }
+        };
 
         // Convert OSC args to CLASP value
         let value = osc_args_to_value(&msg.args);
 
+        if self.state.maintenance().blocks(&clasp_address) {
+            debug!("OSC message to {} dropped: maintenance mode", clasp_address);
+            return;
+        }
+        if !self.reserved_permits(osc_session, &clasp_address) {
+            debug!("OSC message to {} dropped: reserved address", clasp_address);
+            return;
+        }
+        if !self.policy.permits("SET", &clasp_address) {
+            if self.policy.permits("PUBLISH", &clasp_address) {
+                self.forward_event(osc_session, clasp_address, value);
+            } else {
+                debug!("OSC message to {} dropped: transport policy", clasp_address);
+            }
+            return;
+        }
+
+        let Some(value) = self.checked_value(osc_session, &clasp_address, value) else {
+            return;
+        };
+
         // Apply to state
         let set_msg = SetMessage {
             address: clasp_address.clone(),
@@ -273,6 +387,8 @@ impl OscServerAdapter {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         };
 
         if let Ok(revision) = self
@@ -301,6 +417,81 @@ impl OscServerAdapter {
         }
     }
 
+    /// Forward an OSC message to CLASP subscribers as an event, without storing it
+    fn forward_event(&self, osc_session: &Arc<OscSession>, address: String, value: Value) {
+        let subscribers = self
+            .subscriptions
+            .find_subscribers(&address, Some(SignalType::Event));
+        let msg = Message::Publish(PublishMessage {
+            address,
+            signal: Some(SignalType::Event),
+            value: Some(value),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: Some(clasp_core::time::now()),
+            timeline: None,
+        });
+
+        if let Ok(bytes) = codec::encode(&msg) {
+            for sub_session_id in subscribers {
+                if sub_session_id != osc_session.clasp_session_id {
+                    if let Some(sub_session) = self.sessions.get(&sub_session_id) {
+                        let _ = sub_session.try_send(bytes.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the reserved namespace lets this OSC source write `address`
+    fn reserved_permits(&self, osc_session: &OscSession, address: &str) -> bool {
+        if self.reserved.is_none() {
+            return true;
+        }
+        let Some(session) = self
+            .sessions
+            .get(&osc_session.clasp_session_id)
+            .map(|s| Arc::clone(s.value()))
+        else {
+            return false;
+        };
+        crate::reserved::adapter_permits(
+            self.reserved.as_ref(),
+            &session,
+            address,
+            "SET",
+            &self.sessions,
+            &self.subscriptions,
+        )
+    }
+
+    /// The value to store for a SET to `address`, or `None` if the router's
+    /// SET checks refuse it
+    fn checked_value(
+        &self,
+        osc_session: &OscSession,
+        address: &str,
+        value: Value,
+    ) -> Option<Value> {
+        let Some(ref checks) = self.checks else {
+            return Some(value);
+        };
+        let session = self
+            .sessions
+            .get(&osc_session.clasp_session_id)
+            .map(|s| Arc::clone(s.value()))?;
+        match checks.check(&session, address, value, &self.state) {
+            Ok(value) => Some(value),
+            Err(reason) => {
+                debug!("OSC message to {} dropped: {}", address, reason);
+                None
+            }
+        }
+    }
+
     /// Handle an OSC bundle
     async fn handle_osc_bundle(&self, osc_session: &Arc<OscSession>, bundle: OscBundle) {
         for packet in bundle.content {
@@ -313,6 +504,7 @@ impl OscServerAdapter {
         let osc_sessions = Arc::clone(&self.osc_sessions);
         let clasp_sessions = Arc::clone(&self.sessions);
         let subscriptions = Arc::clone(&self.subscriptions);
+        let state = Arc::clone(&self.state);
         let running = Arc::clone(&self.running);
         let timeout = Duration::from_secs(self.config.session_timeout_secs);
 
@@ -343,6 +535,12 @@ impl OscServerAdapter {
                         // Clean up CLASP session
                         clasp_sessions.remove(&osc_session.clasp_session_id);
                         subscriptions.remove_session(&osc_session.clasp_session_id);
+                        crate::handlers::release_session_state(
+                            &osc_session.clasp_session_id,
+                            &state,
+                            &clasp_sessions,
+                            &subscriptions,
+                        );
                     }
                 }
             }
@@ -447,11 +645,27 @@ fn value_to_osc_args(value: &Value) -> Vec<OscType> {
 struct OscTransportSender {
     peer_addr: SocketAddr,
     socket: Arc<RwLock<Option<Arc<UdpSocket>>>>,
+    namespace: String,
+    mapper: Option<Arc<dyn AddressMapper>>,
 }
 
 impl OscTransportSender {
-    fn new(peer_addr: SocketAddr, socket: Arc<RwLock<Option<Arc<UdpSocket>>>>) -> Self {
-        Self { peer_addr, socket }
+    fn new(
+        peer_addr: SocketAddr,
+        socket: Arc<RwLock<Option<Arc<UdpSocket>>>>,
+        namespace: String,
+        mapper: Option<Arc<dyn AddressMapper>>,
+    ) -> Self {
+        Self {
+            peer_addr,
+            socket,
+            namespace,
+            mapper,
+        }
+    }
+
+    fn encode(&self, msg: &Message) -> Option<Vec<u8>> {
+        clasp_to_osc(msg, &self.namespace, self.mapper.as_deref())
     }
 }
 
@@ -463,7 +677,7 @@ impl clasp_transport::TransportSender for OscTransportSender {
 
         // Decode CLASP message and convert to OSC
         if let Ok((msg, _)) = codec::decode(&data) {
-            if let Some(osc_data) = clasp_to_osc(&msg) {
+            if let Some(osc_data) = self.encode(&msg) {
                 socket
                     .send_to(&osc_data, self.peer_addr)
                     .await
@@ -479,7 +693,7 @@ impl clasp_transport::TransportSender for OscTransportSender {
         let socket = self.socket.read().clone();
         if let Some(socket) = socket {
             if let Ok((msg, _)) = codec::decode(&data) {
-                if let Some(osc_data) = clasp_to_osc(&msg) {
+                if let Some(osc_data) = self.encode(&msg) {
                     // Use try_send equivalent - for UDP this is effectively instant
                     let _ = socket.try_send_to(&osc_data, self.peer_addr);
                 }
@@ -499,7 +713,11 @@ impl clasp_transport::TransportSender for OscTransportSender {
 }
 
 /// Convert CLASP message to OSC packet bytes
-fn clasp_to_osc(msg: &Message) -> Option<Vec<u8>> {
+fn clasp_to_osc(
+    msg: &Message,
+    namespace: &str,
+    mapper: Option<&dyn AddressMapper>,
+) -> Option<Vec<u8>> {
     let (address, value) = match msg {
         Message::Set(set) => (&set.address, &set.value),
         Message::Publish(pub_msg) => {
@@ -512,8 +730,14 @@ fn clasp_to_osc(msg: &Message) -> Option<Vec<u8>> {
         _ => return None,
     };
 
-    // Strip /osc prefix to get OSC address
-    let osc_addr = address.strip_prefix("/osc").unwrap_or(address).to_string();
+    // Undo the mapping, then strip the namespace to get the OSC address
+    let mapped = mapper.and_then(|m| m.outbound(address));
+    let address = mapped.as_deref().unwrap_or(address);
+    let osc_addr = address
+        .strip_prefix(namespace)
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(address)
+        .to_string();
 
     let args = value_to_osc_args(value);
     let msg = OscMessage {
@@ -552,4 +776,35 @@ mod tests {
         assert_eq!(args.len(), 1);
         assert!(matches!(args[0], OscType::Float(f) if (f - 42.5).abs() < 0.001));
     }
+
+    #[test]
+    fn test_clasp_to_osc_unmaps_address() {
+        use crate::address_map::{AddressMap, AddressRule};
+
+        let mapper = AddressMap::new(vec![AddressRule::Template {
+            from: "/desk/1/fader{n}".into(),
+            to: "/mixer/channel/{n}/level".into(),
+        }])
+        .unwrap();
+        let osc_addr = |address: &str| {
+            let msg = Message::Set(SetMessage {
+                address: address.to_string(),
+                value: Value::Float(0.5),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            });
+            let bytes = clasp_to_osc(&msg, "/desk", Some(&mapper)).unwrap();
+            match rosc::decoder::decode_udp(&bytes).unwrap().1 {
+                OscPacket::Message(msg) => msg.addr,
+                OscPacket::Bundle(_) => unreachable!(),
+            }
+        };
+        assert_eq!(osc_addr("/mixer/channel/3/level"), "/1/fader3");
+        assert_eq!(osc_addr("/desk/synth/volume"), "/synth/volume");
+        assert_eq!(osc_addr("/desktop/x"), "/desktop/x");
+    }
 }
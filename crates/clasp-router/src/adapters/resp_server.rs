@@ -0,0 +1,1323 @@
+//! Redis Protocol (RESP) Server Adapter
+//!
+//! Lets `redis-cli` and existing Redis client libraries talk to the CLASP
+//! router. Keys are CLASP addresses (optionally under a namespace prefix):
+//!
+//! | Redis | CLASP |
+//! |-------|-------|
+//! | `SET /lights/1 0.5` | Set `/lights/1` |
+//! | `GET /lights/1` | Current param value |
+//! | `KEYS /lights/*` | Params matching the glob |
+//! | `PUBLISH /cue/go 1` | Publish event `/cue/go` |
+//! | `SUBSCRIBE /lights/1` | Subscribe `/lights/1` |
+//! | `PSUBSCRIBE /lights/*` | Subscribe `/lights/**`, filtered by the glob |
+//! | `AUTH <token>` | Token auth |
+//!
+//! Values are sent as text. Incoming values are parsed as integers, floats,
+//! booleans or JSON where possible and stored as strings otherwise.
+
+use bytes::{Buf, Bytes, BytesMut};
+use clasp_core::security::{Action, TokenValidator, ValidationResult};
+use clasp_core::{codec, Message, PublishMessage, SetMessage, SignalType, Ttl, Value};
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::canonical::AddressPolicy;
+use crate::error::{Result, RouterError};
+use crate::reserved::ReservedNamespace;
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::{Subscription, SubscriptionManager};
+use crate::transport_policy::TransportPolicy;
+
+use super::WriteChecks;
+
+/// Largest accepted request (bulk strings and inline commands)
+const MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+
+/// Largest accepted argument count in a single command
+const MAX_ARGS: usize = 1024 * 1024;
+
+/// RESP Server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespServerConfig {
+    /// Bind address (e.g., "0.0.0.0:6379")
+    pub bind_addr: String,
+    /// CLASP namespace prefix for keys (default: "", keys are addresses)
+    #[serde(default)]
+    pub namespace: String,
+    /// Require `AUTH <token>` before any other command
+    #[serde(default)]
+    pub require_auth: bool,
+    /// Maximum clients (0 = unlimited)
+    #[serde(default)]
+    pub max_clients: usize,
+}
+
+impl Default for RespServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0:6379".to_string(),
+            namespace: String::new(),
+            require_auth: false,
+            max_clients: 0,
+        }
+    }
+}
+
+/// A RESP2 reply
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+    /// Several replies written back to back (e.g. SUBSCRIBE to many channels)
+    Multi(Vec<Reply>),
+}
+
+impl Reply {
+    fn ok() -> Self {
+        Reply::Simple("OK".into())
+    }
+
+    fn err(msg: impl Into<String>) -> Self {
+        Reply::Error(format!("ERR {}", msg.into()))
+    }
+
+    fn bulk(data: impl Into<Vec<u8>>) -> Self {
+        Reply::Bulk(Some(data.into()))
+    }
+
+    /// Append the wire encoding of this reply to `buf`
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Reply::Simple(s) => {
+                buf.push(b'+');
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Reply::Error(s) => {
+                buf.push(b'-');
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Reply::Integer(n) => {
+                buf.push(b':');
+                buf.extend_from_slice(n.to_string().as_bytes());
+            }
+            Reply::Bulk(None) => buf.extend_from_slice(b"$-1"),
+            Reply::Bulk(Some(data)) => {
+                buf.push(b'$');
+                buf.extend_from_slice(data.len().to_string().as_bytes());
+                buf.extend_from_slice(b"\r\n");
+                buf.extend_from_slice(data);
+            }
+            Reply::Array(items) => {
+                buf.push(b'*');
+                buf.extend_from_slice(items.len().to_string().as_bytes());
+                buf.extend_from_slice(b"\r\n");
+                for item in items {
+                    item.encode(buf);
+                }
+                return;
+            }
+            Reply::Multi(replies) => {
+                for reply in replies {
+                    reply.encode(buf);
+                }
+                return;
+            }
+        }
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    fn to_bytes(&self) -> Bytes {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        Bytes::from(buf)
+    }
+}
+
+/// Parse one command from the buffer.
+///
+/// Accepts both RESP arrays of bulk strings (what client libraries send) and
+/// inline commands (what `telnet` sends). Returns `Ok(None)` when more data is
+/// needed; consumed bytes are removed from `buf` only on success.
+pub fn parse_command(buf: &mut BytesMut) -> std::result::Result<Option<Vec<Vec<u8>>>, String> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+    if buf[0] != b'*' {
+        return parse_inline(buf);
+    }
+
+    let mut pos = 0;
+    let Some(count) = read_number(buf, &mut pos, b'*')? else {
+        return Ok(None);
+    };
+    if count < 0 {
+        buf.advance(pos);
+        return Ok(Some(Vec::new()));
+    }
+    if count as usize > MAX_ARGS {
+        return Err("too many arguments".into());
+    }
+
+    let mut args = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if pos >= buf.len() {
+            return Ok(None);
+        }
+        if buf[pos] != b'$' {
+            return Err(format!("expected '$', got '{}'", buf[pos] as char));
+        }
+        let Some(len) = read_number(buf, &mut pos, b'$')? else {
+            return Ok(None);
+        };
+        if len < 0 || len as usize > MAX_REQUEST_BYTES {
+            return Err("invalid bulk length".into());
+        }
+        let len = len as usize;
+        if buf.len() < pos + len + 2 {
+            return Ok(None);
+        }
+        args.push(buf[pos..pos + len].to_vec());
+        pos += len + 2;
+    }
+    buf.advance(pos);
+    Ok(Some(args))
+}
+
+/// Read `<prefix><number>\r\n` starting at `pos`
+fn read_number(
+    buf: &BytesMut,
+    pos: &mut usize,
+    prefix: u8,
+) -> std::result::Result<Option<i64>, String> {
+    debug_assert_eq!(buf[*pos], prefix);
+    let Some(end) = find_crlf(&buf[*pos..]) else {
+        if buf.len() - *pos > 32 {
+            return Err("invalid length line".into());
+        }
+        return Ok(None);
+    };
+    let line = std::str::from_utf8(&buf[*pos + 1..*pos + end])
+        .map_err(|_| "invalid length line".to_string())?;
+    let n = line
+        .parse::<i64>()
+        .map_err(|_| "invalid length line".to_string())?;
+    *pos += end + 2;
+    Ok(Some(n))
+}
+
+fn parse_inline(buf: &mut BytesMut) -> std::result::Result<Option<Vec<Vec<u8>>>, String> {
+    let Some(end) = buf.iter().position(|&b| b == b'\n') else {
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err("inline command too long".into());
+        }
+        return Ok(None);
+    };
+    let line = buf.split_to(end + 1);
+    Ok(Some(
+        line[..]
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_vec())
+            .collect(),
+    ))
+}
+
+fn find_crlf(data: &[u8]) -> Option<usize> {
+    data.windows(2).position(|w| w == b"\r\n")
+}
+
+/// Redis-style glob match (`*`, `?`, `[abc]`, `[^a-z]`, `\` escapes)
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                b'[' => {
+                    if let Some((matched, next)) = match_class(pattern, p, text[t]) {
+                        if matched {
+                            p = next;
+                            t += 1;
+                            continue;
+                        }
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == text[t] {
+                        p += 2;
+                        t += 1;
+                        continue;
+                    }
+                }
+                c if c == text[t] => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p + 1;
+                t = star_t + 1;
+                backtrack = Some((star_p, star_t + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Match `c` against the character class starting at `pattern[start] == '['`.
+/// Returns whether it matched and the index after the closing `]`.
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negate = pattern.get(i) == Some(&b'^');
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    while i < pattern.len() && pattern[i] != b']' {
+        if pattern[i] == b'\\' && i + 1 < pattern.len() {
+            matched |= pattern[i + 1] == c;
+            i += 2;
+        } else if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            let (lo, hi) = (
+                pattern[i].min(pattern[i + 2]),
+                pattern[i].max(pattern[i + 2]),
+            );
+            matched |= (lo..=hi).contains(&c);
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+    if i >= pattern.len() {
+        return None;
+    }
+    Some((matched != negate, i + 1))
+}
+
+/// Normalize a key so it always starts with `/`
+fn normalize_key(key: &str) -> String {
+    if key.starts_with('/') || key.starts_with('*') {
+        key.to_string()
+    } else {
+        format!("/{}", key)
+    }
+}
+
+/// Convert a Redis key to a CLASP address
+fn key_to_address(namespace: &str, key: &str) -> String {
+    format!("{}{}", namespace, normalize_key(key))
+}
+
+/// Convert a CLASP address to a Redis key
+fn address_to_key<'a>(namespace: &str, address: &'a str) -> Option<&'a str> {
+    address
+        .strip_prefix(namespace)
+        .filter(|rest| rest.starts_with('/'))
+}
+
+/// Narrowest CLASP subscription pattern that covers a Redis glob.
+///
+/// Redis `*` crosses `/` boundaries, so the pattern covers everything below
+/// the last literal segment; exact matching happens on delivery.
+fn glob_to_clasp_pattern(namespace: &str, glob: &str) -> String {
+    let glob = normalize_key(glob);
+    match glob.find(['*', '?', '[', '\\']) {
+        None => format!("{}{}", namespace, glob),
+        Some(first) => {
+            let prefix = &glob[..glob[..first].rfind('/').unwrap_or(0)];
+            format!("{}{}/**", namespace, prefix)
+        }
+    }
+}
+
+/// Parse an incoming value: integer, float, boolean, JSON, else string
+pub fn parse_value(data: &[u8]) -> Value {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return Value::Bytes(data.to_vec());
+    };
+    if let Ok(i) = text.parse::<i64>() {
+        return Value::Int(i);
+    }
+    if let Ok(f) = text.parse::<f64>() {
+        if f.is_finite() {
+            return Value::Float(f);
+        }
+    }
+    match text {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        "null" => return Value::Null,
+        _ => {}
+    }
+    if text.starts_with('{') || text.starts_with('[') {
+        if let Ok(value) = serde_json::from_str::<Value>(text) {
+            return value;
+        }
+    }
+    Value::String(text.to_string())
+}
+
+/// Render a value as the text returned by GET
+pub fn format_value(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Null => b"null".to_vec(),
+        Value::Bool(b) => b.to_string().into_bytes(),
+        Value::Int(i) => i.to_string().into_bytes(),
+        Value::Float(f) => f.to_string().into_bytes(),
+        Value::String(s) => s.as_bytes().to_vec(),
+        Value::Bytes(b) => b.clone(),
+        Value::Array(_) | Value::Map(_) => {
+            serde_json::to_vec(value).unwrap_or_else(|_| b"null".to_vec())
+        }
+    }
+}
+
+/// How a Redis pub/sub subscription was made
+#[derive(Debug, Clone)]
+enum PubSub {
+    Channel(String),
+    Pattern(String),
+}
+
+/// Pub/sub subscriptions of one connection, keyed by CLASP subscription id
+#[derive(Default)]
+struct PubSubState {
+    subs: HashMap<u32, PubSub>,
+}
+
+impl PubSubState {
+    fn find(&self, pubsub: &PubSub) -> Option<u32> {
+        self.subs.iter().find_map(|(id, s)| match (s, pubsub) {
+            (PubSub::Channel(a), PubSub::Channel(b)) | (PubSub::Pattern(a), PubSub::Pattern(b))
+                if a == b =>
+            {
+                Some(*id)
+            }
+            _ => None,
+        })
+    }
+
+    fn count(&self) -> i64 {
+        self.subs.len() as i64
+    }
+}
+
+/// Transport sender that turns CLASP broadcasts into RESP push messages
+struct RespTransportSender {
+    tx: mpsc::Sender<Bytes>,
+    namespace: String,
+    pubsub: Arc<Mutex<PubSubState>>,
+}
+
+impl RespTransportSender {
+    fn to_pushes(&self, data: &[u8]) -> Vec<Bytes> {
+        let Ok((msg, _)) = codec::decode(data) else {
+            return Vec::new();
+        };
+        let (address, value) = match &msg {
+            Message::Set(set) => (&set.address, Some(&set.value)),
+            Message::Publish(publish) => (
+                &publish.address,
+                publish.value.as_ref().or(publish.payload.as_ref()),
+            ),
+            _ => return Vec::new(),
+        };
+        let Some(key) = address_to_key(&self.namespace, address) else {
+            return Vec::new();
+        };
+        let payload = value.map(format_value).unwrap_or_default();
+
+        let pubsub = self.pubsub.lock();
+        pubsub
+            .subs
+            .values()
+            .filter_map(|sub| match sub {
+                PubSub::Channel(channel) if normalize_key(channel) == key => Some(
+                    Reply::Array(vec![
+                        Reply::bulk("message"),
+                        Reply::bulk(channel.as_str()),
+                        Reply::bulk(payload.clone()),
+                    ])
+                    .to_bytes(),
+                ),
+                PubSub::Pattern(pattern)
+                    if glob_match(normalize_key(pattern).as_bytes(), key.as_bytes()) =>
+                {
+                    Some(
+                        Reply::Array(vec![
+                            Reply::bulk("pmessage"),
+                            Reply::bulk(pattern.as_str()),
+                            Reply::bulk(key),
+                            Reply::bulk(payload.clone()),
+                        ])
+                        .to_bytes(),
+                    )
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl clasp_transport::TransportSender for RespTransportSender {
+    async fn send(&self, data: Bytes) -> std::result::Result<(), clasp_transport::TransportError> {
+        for push in self.to_pushes(&data) {
+            self.tx
+                .send(push)
+                .await
+                .map_err(|e| clasp_transport::TransportError::SendFailed(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn try_send(&self, data: Bytes) -> std::result::Result<(), clasp_transport::TransportError> {
+        for push in self.to_pushes(&data) {
+            self.tx
+                .try_send(push)
+                .map_err(|e| clasp_transport::TransportError::SendFailed(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    async fn close(&self) -> std::result::Result<(), clasp_transport::TransportError> {
+        Ok(())
+    }
+}
+
+/// RESP Server Adapter
+///
+/// Accepts Redis client connections and translates commands to CLASP operations.
+pub struct RespServerAdapter {
+    config: RespServerConfig,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+    state: Arc<RouterState>,
+    clients: Arc<AtomicU32>,
+    running: Arc<RwLock<bool>>,
+    validator: Option<Arc<dyn TokenValidator>>,
+    policy: TransportPolicy,
+    address_policy: AddressPolicy,
+    reserved: Option<ReservedNamespace>,
+    checks: Option<WriteChecks>,
+}
+
+impl RespServerAdapter {
+    /// Create a new RESP server adapter
+    pub fn new(
+        config: RespServerConfig,
+        sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+        subscriptions: Arc<SubscriptionManager>,
+        state: Arc<RouterState>,
+    ) -> Self {
+        Self {
+            config,
+            sessions,
+            subscriptions,
+            state,
+            clients: Arc::new(AtomicU32::new(0)),
+            running: Arc::new(RwLock::new(false)),
+            validator: None,
+            policy: TransportPolicy::default(),
+            address_policy: AddressPolicy::off(),
+            reserved: None,
+            checks: None,
+        }
+    }
+
+    /// Set a token validator for `AUTH`
+    ///
+    /// When `require_auth` is true, clients must send `AUTH <token>` (or
+    /// `AUTH <user> <token>`) first. The token's scopes then apply to every
+    /// key the client reads or writes.
+    pub fn with_validator(mut self, validator: Arc<dyn TokenValidator>) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Restrict what clients may do (see [`crate::transport_policy`])
+    pub fn with_policy(mut self, policy: TransportPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Canonicalize the addresses keys map to (see [`crate::canonical`])
+    ///
+    /// Writes and subscriptions to keys whose address is refused get an
+    /// error reply; reads find nothing.
+    pub fn with_address_policy(mut self, policy: AddressPolicy) -> Self {
+        self.address_policy = policy;
+        self
+    }
+
+    /// Protect the reserved namespace (see [`crate::reserved`])
+    ///
+    /// SET and PUBLISH to reserved addresses need a token with admin scope
+    /// and are otherwise refused with `NOPERM`.
+    pub fn with_reserved(mut self, reserved: ReservedNamespace) -> Self {
+        self.reserved = Some(reserved);
+        self
+    }
+
+    /// Run the router's SET checks on writes (see [`WriteChecks`])
+    ///
+    /// A refused SET gets an error reply carrying the reason.
+    pub fn with_write_checks(mut self, checks: WriteChecks) -> Self {
+        self.checks = Some(checks);
+        self
+    }
+
+    /// Start the RESP server
+    pub async fn serve(&self) -> Result<()> {
+        let listener = TcpListener::bind(&self.config.bind_addr)
+            .await
+            .map_err(|e| RouterError::Transport(e.into()))?;
+        self.serve_on(listener).await
+    }
+
+    /// Serve on an already bound listener
+    pub async fn serve_on(&self, listener: TcpListener) -> Result<()> {
+        info!(
+            "RESP server listening on {}",
+            listener
+                .local_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_else(|_| self.config.bind_addr.clone())
+        );
+        *self.running.write() = true;
+
+        while *self.running.read() {
+            match listener.accept().await {
+                Ok((stream, peer_addr)) => {
+                    if self.config.max_clients > 0
+                        && self.clients.load(Ordering::Relaxed) as usize >= self.config.max_clients
+                    {
+                        warn!(
+                            "Rejecting RESP connection from {}: max clients reached",
+                            peer_addr
+                        );
+                        continue;
+                    }
+                    debug!("RESP connection from {}", peer_addr);
+                    self.spawn_connection_handler(stream, peer_addr);
+                }
+                Err(e) => {
+                    error!("RESP accept error: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn spawn_connection_handler(&self, stream: TcpStream, peer_addr: SocketAddr) {
+        let conn = RespConnection {
+            config: self.config.clone(),
+            sessions: Arc::clone(&self.sessions),
+            subscriptions: Arc::clone(&self.subscriptions),
+            state: Arc::clone(&self.state),
+            validator: self.validator.clone(),
+            policy: self.policy.clone(),
+            address_policy: self.address_policy.clone(),
+            reserved: self.reserved.clone(),
+            checks: self.checks.clone(),
+            session: None,
+            pubsub: Arc::new(Mutex::new(PubSubState::default())),
+            next_sub_id: 1,
+        };
+        let clients = Arc::clone(&self.clients);
+        let running = Arc::clone(&self.running);
+
+        clients.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            if let Err(e) = conn.run(stream, running).await {
+                debug!("RESP connection {} ended: {}", peer_addr, e);
+            }
+            clients.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Stop the RESP server
+    pub fn stop(&self) {
+        *self.running.write() = false;
+    }
+
+    /// Get connected client count
+    pub fn client_count(&self) -> usize {
+        self.clients.load(Ordering::Relaxed) as usize
+    }
+}
+
+/// Per-connection state
+struct RespConnection {
+    config: RespServerConfig,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+    state: Arc<RouterState>,
+    validator: Option<Arc<dyn TokenValidator>>,
+    policy: TransportPolicy,
+    address_policy: AddressPolicy,
+    reserved: Option<ReservedNamespace>,
+    checks: Option<WriteChecks>,
+    /// CLASP session, created once the client is authenticated
+    session: Option<Arc<Session>>,
+    pubsub: Arc<Mutex<PubSubState>>,
+    next_sub_id: u32,
+}
+
+impl RespConnection {
+    async fn run(mut self, mut stream: TcpStream, running: Arc<RwLock<bool>>) -> Result<()> {
+        let mut read_buf = BytesMut::with_capacity(4096);
+        let (tx, mut rx) = mpsc::channel::<Bytes>(1024);
+
+        if !self.config.require_auth {
+            self.open_session(tx.clone(), None);
+        }
+
+        let result = loop {
+            if !*running.read() {
+                break Ok(());
+            }
+
+            tokio::select! {
+                result = stream.read_buf(&mut read_buf) => {
+                    match result {
+                        Ok(0) => break Ok(()),
+                        Ok(_) => {}
+                        Err(e) => break Err(RouterError::Transport(e.into())),
+                    }
+
+                    let mut out = Vec::new();
+                    let mut quit = false;
+                    loop {
+                        match parse_command(&mut read_buf) {
+                            Ok(Some(args)) if args.is_empty() => {}
+                            Ok(Some(args)) => {
+                                let (reply, close) = self.execute(&args, &tx);
+                                reply.encode(&mut out);
+                                if close {
+                                    quit = true;
+                                    break;
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(e) => {
+                                Reply::Error(format!("ERR Protocol error: {}", e)).encode(&mut out);
+                                quit = true;
+                                break;
+                            }
+                        }
+                    }
+                    if !out.is_empty() {
+                        if let Err(e) = stream.write_all(&out).await {
+                            break Err(RouterError::Transport(e.into()));
+                        }
+                    }
+                    if quit {
+                        break Ok(());
+                    }
+                }
+
+                Some(data) = rx.recv() => {
+                    if let Err(e) = stream.write_all(&data).await {
+                        break Err(RouterError::Transport(e.into()));
+                    }
+                }
+            }
+        };
+
+        if let Some(session) = self.session.take() {
+            self.sessions.remove(&session.id);
+            self.subscriptions.remove_session(&session.id);
+            crate::handlers::release_session_state(
+                &session.id,
+                &self.state,
+                &self.sessions,
+                &self.subscriptions,
+            );
+        }
+        result
+    }
+
+    fn open_session(&mut self, tx: mpsc::Sender<Bytes>, auth: Option<(String, ValidationToken)>) {
+        let sender = RespTransportSender {
+            tx,
+            namespace: self.config.namespace.clone(),
+            pubsub: Arc::clone(&self.pubsub),
+        };
+        let mut session = Session::new(
+            Arc::new(sender),
+            "resp-client".to_string(),
+            vec!["resp".to_string()],
+        );
+        session.set_transport("resp");
+        if let Some((token, info)) = auth {
+            session.set_authenticated(token, info.subject, info.scopes);
+            session.entity = info.entity;
+        }
+        let session = Arc::new(session);
+        self.sessions
+            .insert(session.id.clone(), Arc::clone(&session));
+        self.session = Some(session);
+    }
+
+    /// Execute one command, returning the reply and whether to close
+    fn execute(&mut self, args: &[Vec<u8>], tx: &mpsc::Sender<Bytes>) -> (Reply, bool) {
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+        let args: Vec<String> = args[1..]
+            .iter()
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect();
+
+        match name.as_str() {
+            "QUIT" => return (Reply::ok(), true),
+            "PING" => {
+                return match args.first() {
+                    Some(msg) => (Reply::bulk(msg.as_str()), false),
+                    None => (Reply::Simple("PONG".into()), false),
+                }
+            }
+            "AUTH" => return (self.auth(&args, tx), false),
+            _ => {}
+        }
+
+        let Some(session) = self.session.clone() else {
+            return (
+                Reply::Error("NOAUTH Authentication required.".into()),
+                false,
+            );
+        };
+
+        let reply = match name.as_str() {
+            "ECHO" if args.len() == 1 => Reply::bulk(args[0].as_str()),
+            "SELECT" if args.len() == 1 => match args[0].as_str() {
+                "0" => Reply::ok(),
+                _ => Reply::err("DB index is out of range"),
+            },
+            "CLIENT" | "READONLY" | "READWRITE" => Reply::ok(),
+            "COMMAND" => Reply::Array(Vec::new()),
+            "INFO" => Reply::bulk(format!(
+                "# Server\r\nredis_version:7.0.0\r\nclasp_version:{}\r\nredis_mode:standalone\r\n",
+                env!("CARGO_PKG_VERSION")
+            )),
+            "GET" if args.len() == 1 => self.get(&session, &args[0]),
+            "MGET" if !args.is_empty() => {
+                Reply::Array(args.iter().map(|key| self.get(&session, key)).collect())
+            }
+            "EXISTS" if !args.is_empty() => Reply::Integer(
+                args.iter()
+                    .filter(|key| {
+                        self.key_address(key).is_ok_and(|address| {
+                            self.readable(&session, &address) && self.state.get(&address).is_some()
+                        })
+                    })
+                    .count() as i64,
+            ),
+            "SET" if args.len() >= 2 => self.set(&session, &args),
+            "KEYS" if args.len() == 1 => Reply::Array(
+                self.readable_keys(&session)
+                    .into_iter()
+                    .filter(|key| glob_match(normalize_key(&args[0]).as_bytes(), key.as_bytes()))
+                    .map(Reply::bulk)
+                    .collect(),
+            ),
+            "DBSIZE" => Reply::Integer(self.readable_keys(&session).len() as i64),
+            "PUBLISH" if args.len() == 2 => self.publish(&session, &args[0], &args[1]),
+            "SUBSCRIBE" if !args.is_empty() => self.subscribe(&session, &args, false),
+            "PSUBSCRIBE" if !args.is_empty() => self.subscribe(&session, &args, true),
+            "UNSUBSCRIBE" => self.unsubscribe(&session, &args, false),
+            "PUNSUBSCRIBE" => self.unsubscribe(&session, &args, true),
+            "ECHO" | "SELECT" | "GET" | "MGET" | "EXISTS" | "SET" | "KEYS" | "PUBLISH"
+            | "SUBSCRIBE" | "PSUBSCRIBE" => Reply::err(format!(
+                "wrong number of arguments for '{}' command",
+                name.to_ascii_lowercase()
+            )),
+            _ => Reply::err(format!("unknown command '{}'", name.to_ascii_lowercase())),
+        };
+        (reply, false)
+    }
+
+    fn auth(&mut self, args: &[String], tx: &mpsc::Sender<Bytes>) -> Reply {
+        // AUTH <token> or AUTH <user> <token>
+        let Some(token) = args.last().filter(|_| args.len() <= 2) else {
+            return Reply::err("wrong number of arguments for 'auth' command");
+        };
+        let Some(validator) = &self.validator else {
+            return Reply::err("AUTH called without any token validator configured");
+        };
+        match validator.validate(token) {
+            ValidationResult::Valid(info) => {
+                if let Some(old) = self.session.take() {
+                    self.sessions.remove(&old.id);
+                    self.subscriptions.remove_session(&old.id);
+                    crate::handlers::release_session_state(
+                        &old.id,
+                        &self.state,
+                        &self.sessions,
+                        &self.subscriptions,
+                    );
+                    self.pubsub.lock().subs.clear();
+                }
+                let auth = ValidationToken {
+                    subject: info.subject,
+                    scopes: info.scopes,
+                    entity: info.entity,
+                };
+                self.open_session(tx.clone(), Some((token.clone(), auth)));
+                Reply::ok()
+            }
+            ValidationResult::Expired => Reply::Error("WRONGPASS token expired".into()),
+            _ => Reply::Error("WRONGPASS invalid token".into()),
+        }
+    }
+
+    /// The canonical address a key or channel maps to
+    fn key_address(&self, key: &str) -> std::result::Result<String, String> {
+        let address = key_to_address(&self.config.namespace, key);
+        self.address_policy
+            .canonicalize(&address)
+            .map(|canonical| canonical.into_owned())
+            .map_err(|reason| format!("invalid address '{}': {}", address, reason))
+    }
+
+    fn get(&self, session: &Session, key: &str) -> Reply {
+        let Ok(address) = self.key_address(key) else {
+            return Reply::Bulk(None);
+        };
+        if !self.readable(session, &address) {
+            return Reply::Bulk(None);
+        }
+        Reply::Bulk(self.state.get(&address).map(|v| format_value(&v)))
+    }
+
+    /// Whether the session's scopes and the transport policy allow reading
+    fn readable(&self, session: &Session, address: &str) -> bool {
+        session.has_scope(Action::Read, address) && self.policy.permits("GET", address)
+    }
+
+    fn readable_keys(&self, session: &Session) -> Vec<String> {
+        let pattern = if self.config.namespace.is_empty() {
+            "/**".to_string()
+        } else {
+            format!("{}/**", self.config.namespace)
+        };
+        let mut keys: Vec<String> = self
+            .state
+            .get_matching(&pattern)
+            .into_iter()
+            .filter(|(address, _)| self.readable(session, address))
+            .filter_map(|(address, _)| {
+                address_to_key(&self.config.namespace, &address).map(str::to_string)
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    fn set(&self, session: &Session, args: &[String]) -> Reply {
+        let address = match self.key_address(&args[0]) {
+            Ok(address) => address,
+            Err(e) => return Reply::err(e),
+        };
+        if clasp_core::Address::parse(&address).is_err() {
+            return Reply::err(format!("invalid address '{}'", address));
+        }
+        if !session.has_scope(Action::Write, &address) {
+            return Reply::Error(format!("NOPERM no write access to '{}'", args[0]));
+        }
+        if !self.policy.permits("SET", &address) {
+            return Reply::Error(format!("NOPERM SET to '{}' is not allowed", args[0]));
+        }
+        if !self.reserved_permits(session, &address, "SET") {
+            return Reply::Error(format!("NOPERM '{}' is reserved", args[0]));
+        }
+        if self.state.maintenance().blocks(&address) {
+            return Reply::Error("READONLY router is in maintenance mode".into());
+        }
+
+        let mut ttl = None;
+        let (mut nx, mut xx) = (false, false);
+        let mut opts = args[2..].iter();
+        while let Some(opt) = opts.next() {
+            match opt.to_ascii_uppercase().as_str() {
+                "NX" => nx = true,
+                "XX" => xx = true,
+                "EX" | "PX" => {
+                    let millis = opt.eq_ignore_ascii_case("PX");
+                    let Some(n) = opts.next().and_then(|n| n.parse::<u64>().ok()) else {
+                        return Reply::err("value is not an integer or out of range");
+                    };
+                    let secs = if millis { n.div_ceil(1000) } else { n };
+                    if secs == 0 {
+                        return Reply::err("invalid expire time in 'set' command");
+                    }
+                    ttl = Some(Ttl::Absolute(secs.min(u32::MAX as u64) as u32));
+                }
+                _ => return Reply::err("syntax error"),
+            }
+        }
+        if nx && xx {
+            return Reply::err("syntax error");
+        }
+        let exists = self.state.get(&address).is_some();
+        if (nx && exists) || (xx && !exists) {
+            return Reply::Bulk(None);
+        }
+
+        if self.state.is_session_scoped(&address) {
+            ttl = Some(Ttl::Session);
+        }
+        let mut value = parse_value(args[1].as_bytes());
+        if let Some(ref checks) = self.checks {
+            value = match checks.check(session, &address, value, &self.state) {
+                Ok(value) => value,
+                Err(reason) => return Reply::err(reason),
+            };
+        }
+        match self.state.set(
+            &address,
+            value.clone(),
+            &session.id,
+            None,
+            false,
+            false,
+            ttl,
+        ) {
+            Ok(revision) => {
+                let msg = Message::Set(SetMessage {
+                    address: address.clone(),
+                    value,
+                    revision: Some(revision),
+                    lock: false,
+                    unlock: false,
+                    ttl,
+                    trace: false,
+                    branch: None,
+                });
+                self.broadcast(&session.id, &address, SignalType::Param, &msg);
+                Reply::ok()
+            }
+            Err(e) => Reply::err(format!("{:?}", e)),
+        }
+    }
+
+    /// Whether the reserved namespace lets `session` write `address`
+    fn reserved_permits(&self, session: &Session, address: &str, operation: &str) -> bool {
+        crate::reserved::adapter_permits(
+            self.reserved.as_ref(),
+            session,
+            address,
+            operation,
+            &self.sessions,
+            &self.subscriptions,
+        )
+    }
+
+    fn publish(&self, session: &Session, channel: &str, payload: &str) -> Reply {
+        let address = match self.key_address(channel) {
+            Ok(address) => address,
+            Err(e) => return Reply::err(e),
+        };
+        if clasp_core::Address::parse(&address).is_err() {
+            return Reply::err(format!("invalid address '{}'", address));
+        }
+        if !session.has_scope(Action::Write, &address) {
+            return Reply::Error(format!("NOPERM no write access to '{}'", channel));
+        }
+        if !self.policy.permits("PUBLISH", &address) {
+            return Reply::Error(format!("NOPERM PUBLISH to '{}' is not allowed", channel));
+        }
+        if !self.reserved_permits(session, &address, "PUBLISH") {
+            return Reply::Error(format!("NOPERM '{}' is reserved", channel));
+        }
+        if self.state.maintenance().blocks(&address) {
+            return Reply::Error("READONLY router is in maintenance mode".into());
+        }
+        let msg = Message::Publish(PublishMessage {
+            address: address.clone(),
+            signal: Some(SignalType::Event),
+            value: Some(parse_value(payload.as_bytes())),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: Some(clasp_core::time::now()),
+            timeline: None,
+        });
+        Reply::Integer(self.broadcast(&session.id, &address, SignalType::Event, &msg) as i64)
+    }
+
+    /// Send a message to subscribers other than the sender; returns the count
+    fn broadcast(
+        &self,
+        sender: &SessionId,
+        address: &str,
+        signal: SignalType,
+        msg: &Message,
+    ) -> usize {
+        let Ok(bytes) = codec::encode(msg) else {
+            return 0;
+        };
+        let mut delivered = 0;
+        for session_id in self.subscriptions.find_subscribers(address, Some(signal)) {
+            if &session_id == sender {
+                continue;
+            }
+            if let Some(session) = self.sessions.get(&session_id) {
+                if session.try_send(bytes.clone()).is_ok() {
+                    delivered += 1;
+                }
+            }
+        }
+        delivered
+    }
+
+    fn subscribe(&mut self, session: &Session, names: &[String], pattern: bool) -> Reply {
+        let kind = if pattern { "psubscribe" } else { "subscribe" };
+        let mut replies = Vec::with_capacity(names.len());
+        for name in names {
+            let pubsub = if pattern {
+                PubSub::Pattern(name.clone())
+            } else {
+                PubSub::Channel(name.clone())
+            };
+            let clasp_pattern = if pattern {
+                glob_to_clasp_pattern(&self.config.namespace, name)
+            } else {
+                key_to_address(&self.config.namespace, name)
+            };
+            let clasp_pattern = match self.address_policy.canonicalize(&clasp_pattern) {
+                Ok(canonical) => canonical.into_owned(),
+                Err(reason) => {
+                    return Reply::err(format!("invalid channel '{}': {}", name, reason))
+                }
+            };
+
+            let existing = self.pubsub.lock().find(&pubsub);
+            if existing.is_none() {
+                if !session.has_scope(Action::Read, &clasp_pattern)
+                    || !self.policy.permits("SUBSCRIBE", &clasp_pattern)
+                {
+                    return Reply::Error(format!("NOPERM no read access to '{}'", name));
+                }
+                let id = self.next_sub_id;
+                match Subscription::new(
+                    id,
+                    session.id.clone(),
+                    &clasp_pattern,
+                    vec![],
+                    Default::default(),
+                ) {
+                    Ok(sub) => {
+                        self.next_sub_id += 1;
+                        self.subscriptions.add(sub);
+                        session.add_subscription(id);
+                        self.pubsub.lock().subs.insert(id, pubsub);
+                    }
+                    Err(e) => return Reply::err(format!("invalid channel '{}': {}", name, e)),
+                }
+            }
+            replies.push(Reply::Array(vec![
+                Reply::bulk(kind),
+                Reply::bulk(name.as_str()),
+                Reply::Integer(self.pubsub.lock().count()),
+            ]));
+        }
+        flatten(replies)
+    }
+
+    fn unsubscribe(&mut self, session: &Session, names: &[String], pattern: bool) -> Reply {
+        let kind = if pattern {
+            "punsubscribe"
+        } else {
+            "unsubscribe"
+        };
+        let targets: Vec<String> = if names.is_empty() {
+            self.pubsub
+                .lock()
+                .subs
+                .values()
+                .filter_map(|s| match (s, pattern) {
+                    (PubSub::Pattern(p), true) | (PubSub::Channel(p), false) => Some(p.clone()),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            names.to_vec()
+        };
+
+        if targets.is_empty() {
+            return Reply::Array(vec![
+                Reply::bulk(kind),
+                Reply::Bulk(None),
+                Reply::Integer(self.pubsub.lock().count()),
+            ]);
+        }
+
+        let mut replies = Vec::with_capacity(targets.len());
+        for name in targets {
+            let pubsub = if pattern {
+                PubSub::Pattern(name.clone())
+            } else {
+                PubSub::Channel(name.clone())
+            };
+            let mut state = self.pubsub.lock();
+            if let Some(id) = state.find(&pubsub) {
+                state.subs.remove(&id);
+                self.subscriptions.remove(&session.id, id);
+                session.remove_subscription(id);
+            }
+            replies.push(Reply::Array(vec![
+                Reply::bulk(kind),
+                Reply::bulk(name),
+                Reply::Integer(state.count()),
+            ]));
+        }
+        flatten(replies)
+    }
+}
+
+/// Subject and scopes from a validated `AUTH` token
+struct ValidationToken {
+    subject: Option<String>,
+    scopes: Vec<clasp_core::security::Scope>,
+    entity: Option<Arc<clasp_core::EntityInfo>>,
+}
+
+/// Commands that answer once per argument (SUBSCRIBE with several channels)
+/// send consecutive frames rather than a nested array, as Redis does.
+fn flatten(mut replies: Vec<Reply>) -> Reply {
+    if replies.len() == 1 {
+        return replies.remove(0);
+    }
+    Reply::Multi(replies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &[u8]) -> Option<Vec<String>> {
+        let mut buf = BytesMut::from(input);
+        parse_command(&mut buf).unwrap().map(|args| {
+            args.into_iter()
+                .map(|a| String::from_utf8(a).unwrap())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_parse_array_and_inline_commands() {
+        assert_eq!(
+            parse(b"*3\r\n$3\r\nSET\r\n$9\r\n/lights/1\r\n$3\r\n0.5\r\n"),
+            Some(vec!["SET".into(), "/lights/1".into(), "0.5".into()])
+        );
+        assert_eq!(parse(b"*2\r\n$3\r\nGET\r\n$9\r\n/lig"), None);
+        assert_eq!(
+            parse(b"PING hello\r\n"),
+            Some(vec!["PING".into(), "hello".into()])
+        );
+        assert_eq!(parse(b"PING"), None);
+    }
+
+    #[test]
+    fn test_parse_keeps_partial_data() {
+        let mut buf = BytesMut::from(&b"*1\r\n$4\r\nPI"[..]);
+        assert_eq!(parse_command(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"NG\r\n");
+        assert_eq!(
+            parse_command(&mut buf).unwrap(),
+            Some(vec![b"PING".to_vec()])
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_reply_encoding() {
+        let mut buf = Vec::new();
+        Reply::Array(vec![
+            Reply::bulk("message"),
+            Reply::Bulk(None),
+            Reply::Integer(2),
+        ])
+        .encode(&mut buf);
+        assert_eq!(buf, b"*3\r\n$7\r\nmessage\r\n$-1\r\n:2\r\n");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"/lights/*", b"/lights/1/dimmer"));
+        assert!(glob_match(b"/lights/?", b"/lights/1"));
+        assert!(!glob_match(b"/lights/?", b"/lights/10"));
+        assert!(glob_match(b"/l[aeiou]ghts/*", b"/lights/1"));
+        assert!(!glob_match(b"/l[^i]ghts/*", b"/lights/1"));
+        assert!(glob_match(b"/ch[0-9]", b"/ch7"));
+        assert!(glob_match(b"*", b"/anything/at/all"));
+        assert!(glob_match(b"/a\\*", b"/a*"));
+        assert!(!glob_match(b"/a\\*", b"/ab"));
+    }
+
+    #[test]
+    fn test_key_mapping() {
+        assert_eq!(key_to_address("", "/lights/1"), "/lights/1");
+        assert_eq!(key_to_address("", "lights/1"), "/lights/1");
+        assert_eq!(key_to_address("/redis", "lights/1"), "/redis/lights/1");
+        assert_eq!(
+            address_to_key("/redis", "/redis/lights/1"),
+            Some("/lights/1")
+        );
+        assert_eq!(address_to_key("/redis", "/other/x"), None);
+        assert_eq!(glob_to_clasp_pattern("", "/lights/*"), "/lights/**");
+        assert_eq!(glob_to_clasp_pattern("", "/lights/1?"), "/lights/**");
+        assert_eq!(glob_to_clasp_pattern("", "*"), "/**");
+        assert_eq!(glob_to_clasp_pattern("/r", "/a/b"), "/r/a/b");
+    }
+
+    #[test]
+    fn test_value_conversion() {
+        assert_eq!(parse_value(b"42"), Value::Int(42));
+        assert_eq!(parse_value(b"0.5"), Value::Float(0.5));
+        assert_eq!(parse_value(b"true"), Value::Bool(true));
+        assert_eq!(parse_value(b"hello"), Value::String("hello".into()));
+        assert!(matches!(parse_value(b"{\"a\":1}"), Value::Map(_)));
+        assert_eq!(parse_value(b"NaN"), Value::String("NaN".into()));
+        assert_eq!(format_value(&Value::Float(0.5)), b"0.5");
+        assert_eq!(format_value(&Value::String("hi".into())), b"hi");
+    }
+}
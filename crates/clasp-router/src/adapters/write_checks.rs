@@ -0,0 +1,77 @@
+//! Router checks for SETs from protocol adapter clients
+//!
+//! An MQTT, OSC or RESP write that would be stored passes the checks a
+//! CLASP SET does before it reaches state: addresses the router maintains
+//! itself are refused, the application's [`WriteValidator`] gets a say, and
+//! the value is checked against the schema announced for it (see
+//! [`crate::schema`]). Get one from [`Router::write_checks`].
+//!
+//! [`Router::write_checks`]: crate::Router::write_checks
+
+use clasp_core::Value;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::router::{RouterConfig, WriteValidator};
+use crate::session::Session;
+use crate::state::RouterState;
+
+/// The SET checks of a router, for its protocol adapters
+#[derive(Clone)]
+pub struct WriteChecks {
+    config: RouterConfig,
+    config_source: bool,
+    validator: Option<Arc<dyn WriteValidator>>,
+}
+
+impl WriteChecks {
+    pub(crate) fn new(
+        config: RouterConfig,
+        config_source: bool,
+        validator: Option<Arc<dyn WriteValidator>>,
+    ) -> Self {
+        Self {
+            config,
+            config_source,
+            validator,
+        }
+    }
+
+    /// Check a SET of `value` to `address` by `session`
+    ///
+    /// Returns the value to store, clamped into range where the schema
+    /// mode allows it, or why the write is refused.
+    pub(crate) fn check(
+        &self,
+        session: &Session,
+        address: &str,
+        value: Value,
+        state: &RouterState,
+    ) -> Result<Value, String> {
+        if let Some(reason) =
+            crate::handlers::owned_by_router(&self.config, self.config_source, address)
+        {
+            return Err(reason.to_string());
+        }
+        if let Some(ref validator) = self.validator {
+            if let Err(reason) = validator.validate_write(address, &value, session, state) {
+                warn!(
+                    "Session {} denied SET to {} by write validator: {}",
+                    session.id, address, reason
+                );
+                return Err(reason);
+            }
+        }
+        match crate::schema::enforce(state, address, &value, self.config.schema) {
+            Ok(None) => Ok(value),
+            Ok(Some(clamped)) => Ok(clamped),
+            Err(violation) => {
+                warn!(
+                    "Session {} SET to {} violates its schema: {}",
+                    session.id, address, violation
+                );
+                Err(violation.to_string())
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Address translation for protocol adapters
+//!
+//! The MQTT and OSC adapters put everything their clients send under a
+//! namespace (`/osc/1/fader3`). An [`AddressMapper`] installed for an
+//! adapter (see [`Router::set_address_mapper`]) rewrites those addresses
+//! to the router's naming scheme on the way in and back on the way out, so
+//! a fader can live at `/mixer/channel/3/level` without CLASP clients
+//! knowing which console is bridged in:
+//!
+//! ```
+//! use clasp_router::{AddressMap, AddressMapper, AddressRule};
+//!
+//! let map = AddressMap::new(vec![AddressRule::Template {
+//!     from: "/osc/1/fader{n}".into(),
+//!     to: "/mixer/channel/{n}/level".into(),
+//! }])
+//! .unwrap();
+//! assert_eq!(map.inbound("/osc/1/fader3").unwrap(), "/mixer/channel/3/level");
+//! assert_eq!(map.outbound("/mixer/channel/3/level").unwrap(), "/osc/1/fader3");
+//! assert_eq!(map.inbound("/osc/2/mute"), None);
+//! ```
+//!
+//! Mapping runs after the adapter applies its namespace and before the
+//! address is canonicalized. Addresses no rule matches keep their
+//! namespaced form.
+//!
+//! [`Router::set_address_mapper`]: crate::Router::set_address_mapper
+
+use regex_lite::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// Rewrites adapter addresses to router addresses and back
+pub trait AddressMapper: Send + Sync {
+    /// The router address for a namespaced adapter address (or subscription
+    /// pattern), or None to keep it
+    fn inbound(&self, address: &str) -> Option<String>;
+
+    /// The namespaced adapter address for a router address, or None if no
+    /// rule produces it
+    fn outbound(&self, address: &str) -> Option<String>;
+
+    /// Router-side patterns covering every address [`inbound`] produces,
+    /// which adapters subscribe to alongside their namespace
+    ///
+    /// [`inbound`]: AddressMapper::inbound
+    fn patterns(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// One rule of an [`AddressMap`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AddressRule {
+    /// Reversible rewrite between two templates whose `{name}` placeholders
+    /// each stand for text within one segment
+    Template { from: String, to: String },
+    /// Regex rewrites for either direction
+    Regex {
+        #[serde(default)]
+        inbound: Option<Rewrite>,
+        #[serde(default)]
+        outbound: Option<Rewrite>,
+    },
+}
+
+/// A regex and its replacement (`$1`, `${name}`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rewrite {
+    pub regex: String,
+    pub replace: String,
+}
+
+/// An [`AddressMapper`] applying the first matching rule
+#[derive(Debug, Clone)]
+pub struct AddressMap {
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug, Clone)]
+enum CompiledRule {
+    Template {
+        from: Template,
+        to: Template,
+    },
+    Regex {
+        inbound: Option<(Regex, String)>,
+        outbound: Option<(Regex, String)>,
+    },
+}
+
+impl AddressMap {
+    /// Compile `rules`, or say which one is malformed
+    pub fn new(rules: Vec<AddressRule>) -> Result<Self, String> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| match rule {
+                AddressRule::Template { from, to } => {
+                    let from = Template::parse(&from)?;
+                    let to = Template::parse(&to)?;
+                    if from.names() != to.names() {
+                        return Err(format!(
+                            "templates {} and {} use different placeholders",
+                            from.source, to.source
+                        ));
+                    }
+                    Ok(CompiledRule::Template { from, to })
+                }
+                AddressRule::Regex { inbound, outbound } => {
+                    if inbound.is_none() && outbound.is_none() {
+                        return Err("regex rule without inbound or outbound".to_string());
+                    }
+                    let compile = |rewrite: Option<Rewrite>| {
+                        rewrite
+                            .map(|r| {
+                                Regex::new(&r.regex)
+                                    .map(|regex| (regex, r.replace))
+                                    .map_err(|e| format!("invalid regex {}: {}", r.regex, e))
+                            })
+                            .transpose()
+                    };
+                    Ok(CompiledRule::Regex {
+                        inbound: compile(inbound)?,
+                        outbound: compile(outbound)?,
+                    })
+                }
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules })
+    }
+}
+
+impl AddressMapper for AddressMap {
+    fn inbound(&self, address: &str) -> Option<String> {
+        self.rules.iter().find_map(|rule| match rule {
+            CompiledRule::Template { from, to } => from.rewrite(address, to),
+            CompiledRule::Regex { inbound, .. } => rewrite(inbound.as_ref()?, address),
+        })
+    }
+
+    fn outbound(&self, address: &str) -> Option<String> {
+        self.rules.iter().find_map(|rule| match rule {
+            CompiledRule::Template { from, to } => to.rewrite(address, from),
+            CompiledRule::Regex { outbound, .. } => rewrite(outbound.as_ref()?, address),
+        })
+    }
+
+    /// The `to` side of every template, with placeholders as `*`.
+    /// Regex rules can't be turned into patterns, so their addresses only
+    /// reach clients that subscribe to them explicitly.
+    fn patterns(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter_map(|rule| match rule {
+                CompiledRule::Template { to, .. } => Some(to.pattern()),
+                CompiledRule::Regex { .. } => None,
+            })
+            .collect()
+    }
+}
+
+fn rewrite((regex, replace): &(Regex, String), address: &str) -> Option<String> {
+    regex
+        .is_match(address)
+        .then(|| regex.replace(address, replace.as_str()).into_owned())
+}
+
+/// An address with `{name}` placeholders
+#[derive(Debug, Clone)]
+struct Template {
+    source: String,
+    parts: Vec<Part>,
+    regex: Regex,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Name(String),
+}
+
+impl Template {
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in {}", source))?;
+            let name = &rest[open + 1..open + close];
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid placeholder {{{}}} in {}", name, source));
+            }
+            if parts.contains(&Part::Name(name.to_string())) {
+                return Err(format!("placeholder {{{}}} repeated in {}", name, source));
+            }
+            if matches!(parts.last(), Some(Part::Name(_))) {
+                return Err(format!("adjacent placeholders in {}", source));
+            }
+            parts.push(Part::Name(name.to_string()));
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        let mut regex = String::from("^");
+        for part in &parts {
+            match part {
+                Part::Text(text) => regex.push_str(&regex_lite::escape(text)),
+                Part::Name(name) => regex.push_str(&format!("(?P<{}>[^/]+)", name)),
+            }
+        }
+        regex.push('$');
+        Ok(Self {
+            source: source.to_string(),
+            parts,
+            regex: Regex::new(&regex).map_err(|e| format!("invalid template {}: {}", source, e))?,
+        })
+    }
+
+    fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Name(name) => Some(name.as_str()),
+                Part::Text(_) => None,
+            })
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// `address` rewritten into `target` if it matches this template
+    fn rewrite(&self, address: &str, target: &Template) -> Option<String> {
+        let captures = self.regex.captures(address)?;
+        Some(target.fill(&captures))
+    }
+
+    fn fill(&self, captures: &Captures<'_>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Name(name) => captures.name(name).map_or("", |m| m.as_str()),
+            })
+            .collect()
+    }
+
+    /// A subscription pattern matching every address the template fills
+    fn pattern(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Name(_) => "*",
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fader_map() -> AddressMap {
+        AddressMap::new(vec![
+            AddressRule::Template {
+                from: "/osc/{bank}/fader{n}".into(),
+                to: "/mixer/{bank}/channel/{n}/level".into(),
+            },
+            AddressRule::Regex {
+                inbound: Some(Rewrite {
+                    regex: r"^/osc/mute/(\d+)$".into(),
+                    replace: "/mixer/mute/$1".into(),
+                }),
+                outbound: None,
+            },
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_template_roundtrip() {
+        let map = fader_map();
+        assert_eq!(
+            map.inbound("/osc/a/fader12").unwrap(),
+            "/mixer/a/channel/12/level"
+        );
+        assert_eq!(
+            map.outbound("/mixer/a/channel/12/level").unwrap(),
+            "/osc/a/fader12"
+        );
+        assert_eq!(map.inbound("/osc/a/fader"), None);
+        assert_eq!(map.inbound("/osc/a/b/fader1"), None);
+        assert_eq!(map.outbound("/mixer/a/channel/12"), None);
+    }
+
+    #[test]
+    fn test_wildcards_map_through_placeholders() {
+        let map = fader_map();
+        assert_eq!(
+            map.inbound("/osc/*/fader*").unwrap(),
+            "/mixer/*/channel/*/level"
+        );
+        assert_eq!(map.patterns(), vec!["/mixer/*/channel/*/level".to_string()]);
+
+        let partial = AddressMap::new(vec![AddressRule::Template {
+            from: "/osc/{n}".into(),
+            to: "/mixer/ch{n}".into(),
+        }])
+        .unwrap();
+        assert_eq!(partial.patterns(), vec!["/mixer/ch*".to_string()]);
+    }
+
+    #[test]
+    fn test_regex_rules() {
+        let map = fader_map();
+        assert_eq!(map.inbound("/osc/mute/4").unwrap(), "/mixer/mute/4");
+        assert_eq!(map.outbound("/mixer/mute/4"), None);
+    }
+
+    #[test]
+    fn test_malformed_rules() {
+        let template = |from: &str, to: &str| {
+            AddressMap::new(vec![AddressRule::Template {
+                from: from.into(),
+                to: to.into(),
+            }])
+        };
+        assert!(template("/a/{n}", "/b/{m}").is_err());
+        assert!(template("/a/{n", "/b/{n}").is_err());
+        assert!(template("/a/{n}{m}", "/b/{n}/{m}").is_err());
+        assert!(template("/a/{n}/{n}", "/b/{n}").is_err());
+        assert!(template("/a/{}", "/b").is_err());
+        assert!(AddressMap::new(vec![AddressRule::Regex {
+            inbound: Some(Rewrite {
+                regex: "(".into(),
+                replace: String::new(),
+            }),
+            outbound: None,
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn test_rules_from_json() {
+        let rules: Vec<AddressRule> = serde_json::from_str(
+            r#"[
+                {"from": "/osc/{n}", "to": "/x/{n}"},
+                {"inbound": {"regex": "^/osc/y$", "replace": "/y"}}
+            ]"#,
+        )
+        .unwrap();
+        assert!(matches!(rules[0], AddressRule::Template { .. }));
+        assert!(matches!(
+            rules[1],
+            AddressRule::Regex {
+                inbound: Some(_),
+                outbound: None
+            }
+        ));
+    }
+}
@@ -0,0 +1,478 @@
+//! Aggregated high-fan-in writes
+//!
+//! Thousands of audience phones voting or clapping into one address would
+//! each be broadcast to every subscriber. An [`AggregateRule`] in
+//! [`RouterConfig::aggregates`] turns that around: SET and PUBLISH messages
+//! to a matching address are absorbed, and every `interval_ms` the router
+//! writes one aggregate of what arrived to the same address, as a param
+//! with writer [`AGGREGATE_WRITER`]. Subscribers, GET and late joiners see
+//! only the aggregate, so 10k writes a second become 10 messages a second:
+//!
+//! ```
+//! use clasp_router::{AggregateKind, AggregateRule};
+//!
+//! // Live vote tally, updated ten times a second
+//! let votes = AggregateRule::new("/votes/**", AggregateKind::Histogram).cumulative(true);
+//! // Claps in the last 100ms, for a clap meter
+//! let claps = AggregateRule::new("/claps", AggregateKind::Count);
+//! assert_eq!(claps.interval_ms, 100);
+//! ```
+//!
+//! Kinds and the value written:
+//!
+//! - `count`: number of writes (Int)
+//! - `sum`: sum of the numeric values (Float); other values only count
+//! - `histogram`: writes per distinct value (Map of value to Int), for
+//!   string, integer, float and boolean values
+//! - `last(N)`: the newest N values (Array), oldest first
+//!
+//! By default each interval starts from zero, and once writes stop the
+//! empty aggregate is written once. A `cumulative` rule keeps counting
+//! for the life of the router and is only written when it changed; use a
+//! fresh address per poll. `last(N)` always keeps the newest N values.
+//!
+//! Aggregation tracks up to [`MAX_ADDRESSES`] addresses per rule and
+//! [`MAX_BUCKETS`] histogram buckets per address; writes beyond those are
+//! absorbed and discarded. Writes inside a BUNDLE are not aggregated.
+//!
+//! [`RouterConfig::aggregates`]: crate::RouterConfig::aggregates
+
+use clasp_core::address::glob_match;
+use clasp_core::{codec, Message, SetMessage, SignalType, Value};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Writer recorded on aggregate params
+pub const AGGREGATE_WRITER: &str = "aggregate";
+
+/// Addresses tracked per rule
+pub const MAX_ADDRESSES: usize = 1024;
+
+/// Distinct values counted per histogram
+pub const MAX_BUCKETS: usize = 256;
+
+/// Largest accepted `last(N)`; larger values are clamped
+pub const MAX_LAST: usize = 1024;
+
+/// Shortest accepted interval; shorter values are clamped
+pub const MIN_INTERVAL_MS: u64 = 10;
+
+/// How absorbed values are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateKind {
+    Count,
+    Sum,
+    Histogram,
+    Last(usize),
+}
+
+/// Aggregate writes to addresses matching a pattern (see the
+/// [module docs](self))
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateRule {
+    /// Address pattern whose writes are aggregated
+    pub pattern: String,
+    pub kind: AggregateKind,
+    /// How often the aggregate is written, in milliseconds
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    /// Keep counting across intervals instead of starting from zero
+    #[serde(default)]
+    pub cumulative: bool,
+}
+
+fn default_interval_ms() -> u64 {
+    100
+}
+
+impl AggregateRule {
+    pub fn new(pattern: &str, kind: AggregateKind) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            kind,
+            interval_ms: default_interval_ms(),
+            cumulative: false,
+        }
+    }
+
+    /// Write the aggregate every `ms` milliseconds
+    pub fn interval_ms(mut self, ms: u64) -> Self {
+        self.interval_ms = ms;
+        self
+    }
+
+    /// Keep counting across intervals
+    pub fn cumulative(mut self, cumulative: bool) -> Self {
+        self.cumulative = cumulative;
+        self
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.max(MIN_INTERVAL_MS))
+    }
+}
+
+/// What has been absorbed for one address
+#[derive(Debug, Default)]
+struct Window {
+    count: u64,
+    sum: f64,
+    buckets: BTreeMap<String, u64>,
+    last: VecDeque<Value>,
+    /// Absorbed anything since the last write
+    dirty: bool,
+}
+
+impl Window {
+    fn absorb(&mut self, kind: AggregateKind, value: &Value) -> bool {
+        match kind {
+            AggregateKind::Count => {}
+            AggregateKind::Sum => self.sum += value.as_f64().unwrap_or(0.0),
+            AggregateKind::Histogram => {
+                let Some(key) = bucket(value) else {
+                    return false;
+                };
+                if !self.buckets.contains_key(&key) && self.buckets.len() >= MAX_BUCKETS {
+                    return false;
+                }
+                *self.buckets.entry(key).or_default() += 1;
+            }
+            AggregateKind::Last(n) => {
+                if self.last.len() >= n.clamp(1, MAX_LAST) {
+                    self.last.pop_front();
+                }
+                self.last.push_back(value.clone());
+            }
+        }
+        self.count += 1;
+        self.dirty = true;
+        true
+    }
+
+    fn value(&self, kind: AggregateKind) -> Value {
+        match kind {
+            AggregateKind::Count => Value::Int(self.count as i64),
+            AggregateKind::Sum => Value::Float(self.sum),
+            AggregateKind::Histogram => Value::Map(
+                self.buckets
+                    .iter()
+                    .map(|(key, n)| (key.clone(), Value::Int(*n as i64)))
+                    .collect(),
+            ),
+            AggregateKind::Last(_) => Value::Array(self.last.iter().cloned().collect()),
+        }
+    }
+
+    fn reset(&mut self, kind: AggregateKind) {
+        let last = std::mem::take(&mut self.last);
+        *self = Window::default();
+        // The newest values stay the newest values across intervals
+        if let AggregateKind::Last(_) = kind {
+            self.last = last;
+        }
+    }
+}
+
+/// Histogram key of a value
+fn bucket(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Int(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+struct RuleState {
+    rule: AggregateRule,
+    next_write: Instant,
+    windows: HashMap<String, Window>,
+}
+
+/// Absorbed writes and their aggregates, for the rules of a router
+pub struct Aggregator {
+    rules: Mutex<Vec<RuleState>>,
+    absorbed: AtomicU64,
+    discarded: AtomicU64,
+    /// Whether the write task is running (one per router)
+    started: AtomicBool,
+}
+
+impl Aggregator {
+    pub fn new(rules: Vec<AggregateRule>) -> Self {
+        let now = Instant::now();
+        Self {
+            rules: Mutex::new(
+                rules
+                    .into_iter()
+                    .map(|rule| RuleState {
+                        next_write: now + rule.interval(),
+                        rule,
+                        windows: HashMap::new(),
+                    })
+                    .collect(),
+            ),
+            absorbed: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Absorb a write if a rule matches `address`. Returns `true` when the
+    /// write must not be applied or broadcast.
+    pub fn absorb(&self, address: &str, value: &Value) -> bool {
+        let mut rules = self.rules.lock();
+        let Some(state) = rules
+            .iter_mut()
+            .find(|state| glob_match(&state.rule.pattern, address))
+        else {
+            return false;
+        };
+
+        let kind = state.rule.kind;
+        let tracked = state.windows.len();
+        let kept = match state.windows.get_mut(address) {
+            Some(window) => window.absorb(kind, value),
+            None if tracked < MAX_ADDRESSES => {
+                let mut window = Window::default();
+                let kept = window.absorb(kind, value);
+                if kept {
+                    state.windows.insert(address.to_string(), window);
+                }
+                kept
+            }
+            None => false,
+        };
+        if kept {
+            self.absorbed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Aggregates due at `now`, as (address, value)
+    pub fn due(&self, now: Instant) -> Vec<(String, Value)> {
+        let mut out = Vec::new();
+        for state in self.rules.lock().iter_mut() {
+            if state.next_write > now {
+                continue;
+            }
+            state.next_write = now + state.rule.interval();
+
+            let kind = state.rule.kind;
+            let cumulative = state.rule.cumulative || matches!(kind, AggregateKind::Last(_));
+            state.windows.retain(|address, window| {
+                if window.dirty {
+                    out.push((address.clone(), window.value(kind)));
+                    if cumulative {
+                        window.dirty = false;
+                    } else {
+                        window.reset(kind);
+                    }
+                    true
+                } else if cumulative {
+                    true
+                } else {
+                    // Quiet for a whole interval: write the empty aggregate once
+                    out.push((address.clone(), window.value(kind)));
+                    false
+                }
+            });
+        }
+        out
+    }
+
+    /// How often the write task has to check for due aggregates
+    pub fn tick(&self) -> Duration {
+        self.rules
+            .lock()
+            .iter()
+            .map(|state| state.rule.interval())
+            .min()
+            .unwrap_or(Duration::from_millis(default_interval_ms()))
+    }
+
+    /// Writes absorbed into an aggregate so far
+    pub fn absorbed(&self) -> u64 {
+        self.absorbed.load(Ordering::Relaxed)
+    }
+
+    /// Writes absorbed but dropped (over [`MAX_ADDRESSES`] or
+    /// [`MAX_BUCKETS`], or not countable in a histogram)
+    pub fn discarded(&self) -> u64 {
+        self.discarded.load(Ordering::Relaxed)
+    }
+
+    /// Claim the write task; false if one is already running
+    pub(crate) fn start(&self) -> bool {
+        !self.started.swap(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn stopped(&self) {
+        self.started.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Store an aggregate as a param and send it to its subscribers
+pub(crate) fn write(
+    address: &str,
+    value: Value,
+    state: &Arc<RouterState>,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &Arc<SubscriptionManager>,
+) {
+    let revision = match state.set(
+        address,
+        value.clone(),
+        &AGGREGATE_WRITER.to_string(),
+        None,
+        false,
+        false,
+        None,
+    ) {
+        Ok(revision) => revision,
+        Err(e) => {
+            warn!("Aggregate for {} not stored: {:?}", address, e);
+            return;
+        }
+    };
+    let msg = Message::Set(SetMessage {
+        address: address.to_string(),
+        value,
+        revision: Some(revision),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    let Ok(bytes) = codec::encode(&msg) else {
+        return;
+    };
+    for session_id in subscriptions.find_subscribers(address, Some(SignalType::Param)) {
+        if let Some(session) = sessions.get(&session_id) {
+            crate::handlers::try_send_with_drop_tracking_sync(
+                session.value(),
+                bytes.clone(),
+                &session_id,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run due writes, each call one second later than the last
+    fn flush(aggregator: &Aggregator, clock: &mut Instant) -> HashMap<String, Value> {
+        *clock += Duration::from_secs(1);
+        aggregator.due(*clock).into_iter().collect()
+    }
+
+    #[test]
+    fn test_count_and_sum() {
+        let aggregator = Aggregator::new(vec![
+            AggregateRule::new("/claps", AggregateKind::Count),
+            AggregateRule::new("/level/*", AggregateKind::Sum),
+        ]);
+        let mut clock = Instant::now();
+        for _ in 0..1000 {
+            assert!(aggregator.absorb("/claps", &Value::Null));
+        }
+        assert!(aggregator.absorb("/level/a", &Value::Float(0.5)));
+        assert!(aggregator.absorb("/level/a", &Value::Int(2)));
+        assert!(!aggregator.absorb("/other", &Value::Int(1)));
+        assert!(aggregator.due(Instant::now()).is_empty());
+
+        let out = flush(&aggregator, &mut clock);
+        assert_eq!(out["/claps"], Value::Int(1000));
+        assert_eq!(out["/level/a"], Value::Float(2.5));
+        assert_eq!(aggregator.absorbed(), 1002);
+
+        // Quiet interval: zero once, then nothing
+        let out = flush(&aggregator, &mut clock);
+        assert_eq!(out["/claps"], Value::Int(0));
+        assert!(flush(&aggregator, &mut clock).is_empty());
+    }
+
+    #[test]
+    fn test_cumulative_histogram() {
+        let aggregator = Aggregator::new(vec![AggregateRule::new(
+            "/votes/**",
+            AggregateKind::Histogram,
+        )
+        .cumulative(true)]);
+        let mut clock = Instant::now();
+        for vote in ["a", "b", "a"] {
+            aggregator.absorb("/votes/poll", &Value::String(vote.to_string()));
+        }
+        aggregator.absorb("/votes/poll", &Value::Array(vec![]));
+        assert_eq!(aggregator.discarded(), 1);
+
+        let out = flush(&aggregator, &mut clock);
+        let Value::Map(tally) = &out["/votes/poll"] else {
+            panic!("expected a map, got {:?}", out);
+        };
+        assert_eq!(tally["a"], Value::Int(2));
+        assert_eq!(tally["b"], Value::Int(1));
+
+        // Unchanged tallies aren't rewritten; new votes add up
+        assert!(flush(&aggregator, &mut clock).is_empty());
+        aggregator.absorb("/votes/poll", &Value::String("b".to_string()));
+        let out = flush(&aggregator, &mut clock);
+        let Value::Map(tally) = &out["/votes/poll"] else {
+            panic!("expected a map, got {:?}", out);
+        };
+        assert_eq!(tally["b"], Value::Int(2));
+    }
+
+    #[test]
+    fn test_last_n() {
+        let aggregator = Aggregator::new(vec![AggregateRule::new("/wall", AggregateKind::Last(2))]);
+        let mut clock = Instant::now();
+        for i in 0..3 {
+            aggregator.absorb("/wall", &Value::Int(i));
+        }
+        let out = flush(&aggregator, &mut clock);
+        assert_eq!(
+            out["/wall"],
+            Value::Array(vec![Value::Int(1), Value::Int(2)])
+        );
+        aggregator.absorb("/wall", &Value::Int(3));
+        let out = flush(&aggregator, &mut clock);
+        assert_eq!(
+            out["/wall"],
+            Value::Array(vec![Value::Int(2), Value::Int(3)])
+        );
+    }
+
+    #[test]
+    fn test_rule_json() {
+        let rules: Vec<AggregateRule> = serde_json::from_str(
+            r#"[{"pattern": "/votes/**", "kind": "histogram", "cumulative": true},
+                {"pattern": "/wall", "kind": {"last": 20}, "interval_ms": 250}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                AggregateRule::new("/votes/**", AggregateKind::Histogram).cumulative(true),
+                AggregateRule::new("/wall", AggregateKind::Last(20)).interval_ms(250),
+            ]
+        );
+    }
+}
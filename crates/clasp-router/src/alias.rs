@@ -0,0 +1,125 @@
+//! Per-session topic alias tables
+//!
+//! Streams at 100-200 Hz spend most of each frame on the address. With topic
+//! aliases (see [`clasp_core::alias`]) either side binds a small integer to an
+//! address with an ALIAS message and later SET/PUBLISH frames carry the
+//! two-byte alias instead.
+//!
+//! - Inbound: any session may bind up to [`TopicAliasConfig::max_aliases`]
+//!   aliases (never fewer than [`MIN_ALIASES`]); frames are resolved before
+//!   they reach the handlers, and an unknown alias is answered with ERROR 200.
+//! - Outbound: for sessions that advertise the `alias` feature in HELLO, the
+//!   router aliases an address once it has delivered it
+//!   [`TopicAliasConfig::hot_after`] times, sending the binding just before
+//!   the first aliased frame.
+//!
+//! Like unit conversion, outbound aliasing happens in
+//! [`Session::try_send`](crate::Session::try_send), so it applies to
+//! broadcasts only; direct replies keep full addresses.
+
+use bytes::Bytes;
+use clasp_core::alias::{AliasError, InboundAliases, OutboundAliases, FEATURE, MIN_ALIASES};
+use clasp_core::{codec, AliasMessage, Message};
+use clasp_transport::{TransportError, TransportSender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Topic alias limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TopicAliasConfig {
+    /// Aliases per session and direction (0 = disabled)
+    pub max_aliases: u16,
+    /// Deliveries of an address before the router aliases it
+    pub hot_after: u32,
+}
+
+impl Default for TopicAliasConfig {
+    fn default() -> Self {
+        Self {
+            max_aliases: 256,
+            hot_after: 8,
+        }
+    }
+}
+
+impl TopicAliasConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_aliases > 0
+    }
+}
+
+/// A session's alias tables in both directions
+#[derive(Debug, Default)]
+pub struct SessionAliases {
+    inbound: Mutex<InboundAliases>,
+    outbound: Option<Mutex<OutboundAliases>>,
+}
+
+impl SessionAliases {
+    /// Tables for a session whose HELLO advertised `features`
+    pub fn new(config: &TopicAliasConfig, features: &[String]) -> Self {
+        if !config.is_enabled() {
+            return Self::default();
+        }
+        let outbound = features
+            .iter()
+            .any(|f| f == FEATURE)
+            .then(|| Mutex::new(OutboundAliases::new(config.max_aliases, config.hot_after)));
+        Self {
+            inbound: Mutex::new(InboundAliases::new(config.max_aliases.max(MIN_ALIASES))),
+            outbound,
+        }
+    }
+
+    /// Apply an ALIAS message from the client
+    pub fn bind(&self, msg: &AliasMessage) -> Result<(), AliasError> {
+        self.inbound.lock().bind(msg)
+    }
+
+    /// Replace alias references in a message from the client
+    pub fn resolve(&self, message: &mut Message) -> Result<(), AliasError> {
+        self.inbound.lock().resolve(message)
+    }
+
+    /// Whether the router aliases addresses it sends to this session
+    pub fn is_outbound(&self) -> bool {
+        self.outbound.is_some()
+    }
+
+    /// Send a broadcast, aliasing its address if it is hot. The lock is held
+    /// across both sends so no aliased frame can overtake its binding.
+    pub(crate) fn try_send(
+        &self,
+        data: Bytes,
+        sender: &dyn TransportSender,
+    ) -> Result<(), TransportError> {
+        let Some(outbound) = &self.outbound else {
+            return sender.try_send(data);
+        };
+        let Ok((mut message, frame)) = codec::decode(&data) else {
+            return sender.try_send(data);
+        };
+        if !matches!(message, Message::Set(_) | Message::Publish(_)) {
+            return sender.try_send(data);
+        }
+
+        let mut outbound = outbound.lock();
+        let bind = outbound.apply(&mut message);
+        let Ok(aliased) =
+            codec::encode_with_options(&message, Some(frame.flags.qos), frame.timestamp)
+        else {
+            return sender.try_send(data);
+        };
+        if let Some(bind) = bind {
+            let bytes = codec::encode(&Message::Alias(bind.clone()))
+                .map_err(|e| TransportError::SendFailed(e.to_string()))?;
+            if let Err(e) = sender.try_send(bytes) {
+                // The client never saw the binding, so don't use it
+                outbound.forget(&bind.address);
+                return Err(e);
+            }
+        }
+        sender.try_send(aliased)
+    }
+}
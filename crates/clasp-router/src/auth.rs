@@ -0,0 +1,244 @@
+//! Non-blocking token validation for HELLO
+//!
+//! The HELLO handler awaits an [`AsyncTokenValidator`] instead of calling
+//! [`TokenValidator::validate`](clasp_core::TokenValidator::validate) inline,
+//! so validators that do I/O (registry lookups, JWKS fetches) don't stall the
+//! connection task. Sync validators set with
+//! [`Router::set_validator`](crate::Router::set_validator) run through
+//! [`SyncAdapter`](clasp_core::SyncAdapter).
+//!
+//! Each validation is bounded by [`ValidationConfig::timeout`]. Successful
+//! results can be cached for [`ValidationConfig::cache_ttl`] so reconnect
+//! storms don't hit the backing store once per client; entries never outlive
+//! the token's own expiry. The cache is keyed by a SHA-256 of the token, and
+//! only accepted tokens are cached. A revoked token stays accepted until its
+//! entry expires or [`Router::clear_validation_cache`](crate::Router::clear_validation_cache)
+//! is called, which is why caching is off by default.
+
+use clasp_core::security::{AsyncTokenValidator, TokenInfo, ValidationResult};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+/// Token validation settings for HELLO
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    /// Upper bound on a single validation (including async I/O)
+    pub timeout: Duration,
+    /// How long an accepted token is cached (zero = no caching)
+    pub cache_ttl: Duration,
+    /// Maximum cached tokens
+    pub cache_capacity: usize,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            cache_ttl: Duration::ZERO,
+            cache_capacity: 10_000,
+        }
+    }
+}
+
+/// Cache of accepted tokens, keyed by token hash
+#[derive(Default)]
+pub(crate) struct ValidationCache {
+    entries: Mutex<HashMap<[u8; 32], (Instant, TokenInfo)>>,
+}
+
+impl ValidationCache {
+    fn key(token: &str) -> [u8; 32] {
+        Sha256::digest(token.as_bytes()).into()
+    }
+
+    fn get(&self, token: &str) -> Option<TokenInfo> {
+        let key = Self::key(token);
+        let mut entries = self.entries.lock();
+        match entries.get(&key) {
+            Some((until, info)) if *until > Instant::now() && !info.is_expired() => {
+                Some(info.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, token: &str, info: &TokenInfo, config: &ValidationConfig) {
+        let now = Instant::now();
+        let mut until = now + config.cache_ttl;
+        if let Some(expires_at) = info.expires_at {
+            let left = expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO);
+            until = until.min(now + left);
+        }
+
+        let mut entries = self.entries.lock();
+        if entries.len() >= config.cache_capacity {
+            entries.retain(|_, (until, _)| *until > now);
+        }
+        if entries.len() >= config.cache_capacity {
+            // Still full of live entries: evict the one expiring soonest
+            let soonest = entries
+                .iter()
+                .min_by_key(|(_, (until, _))| *until)
+                .map(|(key, _)| *key);
+            match soonest {
+                Some(key) => {
+                    entries.remove(&key);
+                }
+                None => return,
+            }
+        }
+        entries.insert(Self::key(token), (until, info.clone()));
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+}
+
+/// Validate a HELLO token, consulting and filling the cache
+pub(crate) async fn validate(
+    validator: &dyn AsyncTokenValidator,
+    cache: &ValidationCache,
+    config: &ValidationConfig,
+    token: &str,
+) -> ValidationResult {
+    let caching = !config.cache_ttl.is_zero() && config.cache_capacity > 0;
+    if caching {
+        if let Some(info) = cache.get(token) {
+            return ValidationResult::Valid(info);
+        }
+    }
+
+    let result = match tokio::time::timeout(config.timeout, validator.validate(token)).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!(
+                "Token validation by {} timed out after {:?}",
+                validator.name(),
+                config.timeout
+            );
+            return ValidationResult::Invalid("validation timed out".to_string());
+        }
+    };
+
+    if caching {
+        if let ValidationResult::Valid(ref info) = result {
+            cache.insert(token, info, config);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::security::Scope;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting {
+        calls: AtomicUsize,
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl AsyncTokenValidator for Counting {
+        async fn validate(&self, token: &str) -> ValidationResult {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            match token {
+                "good" => ValidationResult::Valid(TokenInfo::new(
+                    token.to_string(),
+                    vec![Scope::parse("read:/**").unwrap()],
+                )),
+                _ => ValidationResult::Invalid("bad".to_string()),
+            }
+        }
+
+        fn name(&self) -> &str {
+            "Counting"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_timeout() {
+        let validator = Counting {
+            calls: AtomicUsize::new(0),
+            delay: Duration::ZERO,
+        };
+        let cache = ValidationCache::default();
+        let config = ValidationConfig {
+            cache_ttl: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        for _ in 0..3 {
+            let result = validate(&validator, &cache, &config, "good").await;
+            assert!(matches!(result, ValidationResult::Valid(_)));
+        }
+        assert_eq!(validator.calls.load(Ordering::SeqCst), 1);
+
+        // Rejections are not cached
+        for _ in 0..2 {
+            let result = validate(&validator, &cache, &config, "bad").await;
+            assert!(matches!(result, ValidationResult::Invalid(_)));
+        }
+        assert_eq!(validator.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 1);
+
+        cache.clear();
+        validate(&validator, &cache, &config, "good").await;
+        assert_eq!(validator.calls.load(Ordering::SeqCst), 4);
+
+        let slow = Counting {
+            calls: AtomicUsize::new(0),
+            delay: Duration::from_secs(5),
+        };
+        let config = ValidationConfig {
+            timeout: Duration::from_millis(20),
+            ..Default::default()
+        };
+        match validate(&slow, &cache, &config, "good").await {
+            ValidationResult::Invalid(reason) => assert!(reason.contains("timed out")),
+            other => panic!("expected timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cache_respects_token_expiry_and_capacity() {
+        let cache = ValidationCache::default();
+        let config = ValidationConfig {
+            cache_ttl: Duration::from_secs(60),
+            cache_capacity: 2,
+            ..Default::default()
+        };
+
+        let mut expired = TokenInfo::new("old".to_string(), Vec::new());
+        expired.expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+        cache.insert("old", &expired, &config);
+        assert!(cache.get("old").is_none());
+
+        for token in ["a", "b", "c"] {
+            cache.insert(
+                token,
+                &TokenInfo::new(token.to_string(), Vec::new()),
+                &config,
+            );
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("c").is_some());
+    }
+}
@@ -0,0 +1,596 @@
+//! Persistent storage backends for router state
+//!
+//! [`RouterState`](crate::RouterState) keeps params in memory for fast reads
+//! and matching. A [`StateBackend`] adds durability: every param write is
+//! written through to the backend, and
+//! [`RouterState::restore_from_backend`](crate::RouterState::restore_from_backend)
+//! loads the stored params at startup, so state survives restarts without
+//! replaying a journal from scratch.
+//!
+//! What is stored: value, revision, writer, timestamp, TTL, lock holder and
+//! origin of each param. Session-scoped params are never stored. Params that
+//! expire or are removed by TTL cleanup are deleted from the backend; params
+//! evicted to stay under `max_params` are not, so set a backend-friendly
+//! `max_params` (or none) on relays that use one.
+//!
+//! The signal registry is stored too: every ANNOUNCEd definition (with its
+//! datatype, access, unit, range and description) is written through and
+//! restored with the params, so discovery data survives restarts without
+//! every device announcing again. Signals dropped by TTL cleanup are deleted.
+//!
+//! Backend errors are logged and do not fail the write: the in-memory state
+//! stays authoritative while the router runs.
+//!
+//! Implementations: [`MemoryStateBackend`] (tests and embedding), with the
+//! `state-sqlite` feature [`SqliteStateBackend`], and with the `state-sled`
+//! feature [`SledStateBackend`]. Other key-value stores (RocksDB, ...) plug
+//! in by implementing the trait.
+
+use clasp_core::state::ParamState;
+use clasp_core::{SignalDefinition, Ttl, Value};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::Result;
+#[cfg(any(feature = "state-sqlite", feature = "state-sled"))]
+use crate::error::RouterError;
+
+/// The durable part of a param
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredParam {
+    pub value: Value,
+    pub revision: u64,
+    pub writer: String,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub ttl: Option<Ttl>,
+    #[serde(default)]
+    pub lock_holder: Option<String>,
+    #[serde(default)]
+    pub origin: Option<String>,
+}
+
+impl From<&ParamState> for StoredParam {
+    fn from(param: &ParamState) -> Self {
+        Self {
+            value: param.value.clone(),
+            revision: param.revision,
+            writer: param.writer.clone(),
+            timestamp: param.timestamp,
+            ttl: param.ttl,
+            lock_holder: param.lock_holder.clone(),
+            origin: param.origin.clone(),
+        }
+    }
+}
+
+/// Durable key-value storage for params, keyed by address
+///
+/// Methods are called synchronously on the write path, after the in-memory
+/// state has been updated, so implementations should be fast and do their
+/// own internal synchronization.
+pub trait StateBackend: Send + Sync {
+    /// Every stored param
+    fn load(&self) -> Result<Vec<(String, StoredParam)>>;
+
+    /// Insert or replace a param
+    fn put(&self, address: &str, param: &StoredParam) -> Result<()>;
+
+    /// Write several params, atomically if the store supports it
+    fn put_all(&self, params: &[(String, StoredParam)]) -> Result<()> {
+        for (address, param) in params {
+            self.put(address, param)?;
+        }
+        Ok(())
+    }
+
+    /// Delete params (missing addresses are ignored)
+    fn remove(&self, addresses: &[String]) -> Result<()>;
+
+    /// Delete every param
+    fn clear(&self) -> Result<()>;
+
+    /// Every stored signal definition (backends that don't store signals
+    /// return none)
+    fn load_signals(&self) -> Result<Vec<SignalDefinition>> {
+        Ok(Vec::new())
+    }
+
+    /// Insert or replace signal definitions, keyed by address
+    fn put_signals(&self, _signals: &[SignalDefinition]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Delete signal definitions (missing addresses are ignored)
+    fn remove_signals(&self, _addresses: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Backend name (for logging)
+    fn name(&self) -> &str;
+}
+
+/// In-memory backend, for tests and for embedders that persist elsewhere
+#[derive(Debug, Default)]
+pub struct MemoryStateBackend {
+    params: RwLock<HashMap<String, StoredParam>>,
+    signals: RwLock<HashMap<String, SignalDefinition>>,
+}
+
+impl MemoryStateBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A stored param
+    pub fn get(&self, address: &str) -> Option<StoredParam> {
+        self.params.read().get(address).cloned()
+    }
+
+    /// Number of stored params
+    pub fn len(&self) -> usize {
+        self.params.read().len()
+    }
+
+    /// Whether nothing is stored
+    pub fn is_empty(&self) -> bool {
+        self.params.read().is_empty()
+    }
+
+    /// Number of stored signal definitions
+    pub fn signal_count(&self) -> usize {
+        self.signals.read().len()
+    }
+}
+
+impl StateBackend for MemoryStateBackend {
+    fn load(&self) -> Result<Vec<(String, StoredParam)>> {
+        Ok(self
+            .params
+            .read()
+            .iter()
+            .map(|(address, param)| (address.clone(), param.clone()))
+            .collect())
+    }
+
+    fn put(&self, address: &str, param: &StoredParam) -> Result<()> {
+        self.params
+            .write()
+            .insert(address.to_string(), param.clone());
+        Ok(())
+    }
+
+    fn remove(&self, addresses: &[String]) -> Result<()> {
+        let mut params = self.params.write();
+        for address in addresses {
+            params.remove(address);
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.params.write().clear();
+        Ok(())
+    }
+
+    fn load_signals(&self) -> Result<Vec<SignalDefinition>> {
+        Ok(self.signals.read().values().cloned().collect())
+    }
+
+    fn put_signals(&self, signals: &[SignalDefinition]) -> Result<()> {
+        let mut stored = self.signals.write();
+        for signal in signals {
+            stored.insert(signal.address.clone(), signal.clone());
+        }
+        Ok(())
+    }
+
+    fn remove_signals(&self, addresses: &[String]) -> Result<()> {
+        let mut stored = self.signals.write();
+        for address in addresses {
+            stored.remove(address);
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+}
+
+/// SQLite backend: one row per param or signal, MessagePack-encoded
+///
+/// Uses WAL mode with `synchronous = NORMAL`, so a write costs a page update
+/// rather than an fsync; the last few writes before a power loss may be lost,
+/// but the database stays consistent.
+#[cfg(feature = "state-sqlite")]
+pub struct SqliteStateBackend {
+    conn: parking_lot::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "state-sqlite")]
+impl SqliteStateBackend {
+    /// Open (or create) a state database at `path`
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let conn = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        Self::init(conn)
+    }
+
+    /// A database that lives only as long as this backend
+    pub fn in_memory() -> Result<Self> {
+        let conn = rusqlite::Connection::open_in_memory().map_err(sqlite_error)?;
+        Self::init(conn)
+    }
+
+    fn init(conn: rusqlite::Connection) -> Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             CREATE TABLE IF NOT EXISTS params (
+                 address TEXT PRIMARY KEY NOT NULL,
+                 data BLOB NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS signals (
+                 address TEXT PRIMARY KEY NOT NULL,
+                 data BLOB NOT NULL
+             );",
+        )
+        .map_err(sqlite_error)?;
+        Ok(Self {
+            conn: parking_lot::Mutex::new(conn),
+        })
+    }
+}
+
+#[cfg(feature = "state-sqlite")]
+fn sqlite_error(e: rusqlite::Error) -> RouterError {
+    RouterError::State(format!("sqlite state backend: {}", e))
+}
+
+#[cfg(any(feature = "state-sqlite", feature = "state-sled"))]
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    rmp_serde::to_vec_named(value).map_err(|e| RouterError::State(e.to_string()))
+}
+
+#[cfg(any(feature = "state-sqlite", feature = "state-sled"))]
+fn decode<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T> {
+    rmp_serde::from_slice(data).map_err(|e| RouterError::State(e.to_string()))
+}
+
+#[cfg(feature = "state-sqlite")]
+impl StateBackend for SqliteStateBackend {
+    fn load(&self) -> Result<Vec<(String, StoredParam)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare("SELECT address, data FROM params")
+            .map_err(sqlite_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(sqlite_error)?;
+        let mut params = Vec::new();
+        for row in rows {
+            let (address, data) = row.map_err(sqlite_error)?;
+            match decode(&data) {
+                Ok(param) => params.push((address, param)),
+                Err(e) => tracing::warn!("Skipping unreadable stored param {}: {}", address, e),
+            }
+        }
+        Ok(params)
+    }
+
+    fn put(&self, address: &str, param: &StoredParam) -> Result<()> {
+        let data = encode(param)?;
+        self.conn
+            .lock()
+            .prepare_cached("INSERT OR REPLACE INTO params (address, data) VALUES (?1, ?2)")
+            .and_then(|mut stmt| stmt.execute(rusqlite::params![address, data]))
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    fn put_all(&self, params: &[(String, StoredParam)]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        {
+            let mut stmt = tx
+                .prepare_cached("INSERT OR REPLACE INTO params (address, data) VALUES (?1, ?2)")
+                .map_err(sqlite_error)?;
+            for (address, param) in params {
+                stmt.execute(rusqlite::params![address, encode(param)?])
+                    .map_err(sqlite_error)?;
+            }
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    fn remove(&self, addresses: &[String]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        {
+            let mut stmt = tx
+                .prepare_cached("DELETE FROM params WHERE address = ?1")
+                .map_err(sqlite_error)?;
+            for address in addresses {
+                stmt.execute([address]).map_err(sqlite_error)?;
+            }
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.conn
+            .lock()
+            .execute("DELETE FROM params", [])
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    fn load_signals(&self) -> Result<Vec<SignalDefinition>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare("SELECT address, data FROM signals")
+            .map_err(sqlite_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(sqlite_error)?;
+        let mut signals = Vec::new();
+        for row in rows {
+            let (address, data) = row.map_err(sqlite_error)?;
+            match decode(&data) {
+                Ok(signal) => signals.push(signal),
+                Err(e) => tracing::warn!("Skipping unreadable stored signal {}: {}", address, e),
+            }
+        }
+        Ok(signals)
+    }
+
+    fn put_signals(&self, signals: &[SignalDefinition]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        {
+            let mut stmt = tx
+                .prepare_cached("INSERT OR REPLACE INTO signals (address, data) VALUES (?1, ?2)")
+                .map_err(sqlite_error)?;
+            for signal in signals {
+                stmt.execute(rusqlite::params![signal.address, encode(signal)?])
+                    .map_err(sqlite_error)?;
+            }
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    fn remove_signals(&self, addresses: &[String]) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        {
+            let mut stmt = tx
+                .prepare_cached("DELETE FROM signals WHERE address = ?1")
+                .map_err(sqlite_error)?;
+            for address in addresses {
+                stmt.execute([address]).map_err(sqlite_error)?;
+            }
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+}
+
+/// sled backend: a tree each for params and signals, MessagePack-encoded
+///
+/// sled flushes to disk in the background (every 500ms by default), so the
+/// last writes before a crash may be lost; the database stays consistent.
+/// [`put_all`](StateBackend::put_all) and the removals apply as one batch.
+#[cfg(feature = "state-sled")]
+pub struct SledStateBackend {
+    db: sled::Db,
+    params: sled::Tree,
+    signals: sled::Tree,
+}
+
+#[cfg(feature = "state-sled")]
+impl SledStateBackend {
+    /// Open (or create) a state database in the directory `path`
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::init(sled::open(path).map_err(sled_error)?)
+    }
+
+    /// A database that is deleted when this backend is dropped
+    pub fn temporary() -> Result<Self> {
+        Self::init(
+            sled::Config::new()
+                .temporary(true)
+                .open()
+                .map_err(sled_error)?,
+        )
+    }
+
+    fn init(db: sled::Db) -> Result<Self> {
+        Ok(Self {
+            params: db.open_tree("params").map_err(sled_error)?,
+            signals: db.open_tree("signals").map_err(sled_error)?,
+            db,
+        })
+    }
+
+    /// Write everything buffered to disk
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush().map(|_| ()).map_err(sled_error)
+    }
+}
+
+#[cfg(feature = "state-sled")]
+fn sled_error(e: sled::Error) -> RouterError {
+    RouterError::State(format!("sled state backend: {}", e))
+}
+
+#[cfg(feature = "state-sled")]
+fn load_tree<T: serde::de::DeserializeOwned>(
+    tree: &sled::Tree,
+    kind: &str,
+) -> Result<Vec<(String, T)>> {
+    let mut entries = Vec::new();
+    for entry in tree.iter() {
+        let (key, data) = entry.map_err(sled_error)?;
+        let address = String::from_utf8_lossy(&key).into_owned();
+        match decode(&data) {
+            Ok(value) => entries.push((address, value)),
+            Err(e) => tracing::warn!("Skipping unreadable stored {} {}: {}", kind, address, e),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(feature = "state-sled")]
+fn remove_from_tree(tree: &sled::Tree, addresses: &[String]) -> Result<()> {
+    let mut batch = sled::Batch::default();
+    for address in addresses {
+        batch.remove(address.as_bytes());
+    }
+    tree.apply_batch(batch).map_err(sled_error)
+}
+
+#[cfg(feature = "state-sled")]
+impl StateBackend for SledStateBackend {
+    fn load(&self) -> Result<Vec<(String, StoredParam)>> {
+        load_tree(&self.params, "param")
+    }
+
+    fn put(&self, address: &str, param: &StoredParam) -> Result<()> {
+        self.params
+            .insert(address.as_bytes(), encode(param)?)
+            .map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn put_all(&self, params: &[(String, StoredParam)]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for (address, param) in params {
+            batch.insert(address.as_bytes(), encode(param)?);
+        }
+        self.params.apply_batch(batch).map_err(sled_error)
+    }
+
+    fn remove(&self, addresses: &[String]) -> Result<()> {
+        remove_from_tree(&self.params, addresses)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.params.clear().map_err(sled_error)
+    }
+
+    fn load_signals(&self) -> Result<Vec<SignalDefinition>> {
+        Ok(load_tree(&self.signals, "signal")?
+            .into_iter()
+            .map(|(_, signal)| signal)
+            .collect())
+    }
+
+    fn put_signals(&self, signals: &[SignalDefinition]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for signal in signals {
+            batch.insert(signal.address.as_bytes(), encode(signal)?);
+        }
+        self.signals.apply_batch(batch).map_err(sled_error)
+    }
+
+    fn remove_signals(&self, addresses: &[String]) -> Result<()> {
+        remove_from_tree(&self.signals, addresses)
+    }
+
+    fn name(&self) -> &str {
+        "sled"
+    }
+}
+
+#[cfg(all(test, any(feature = "state-sqlite", feature = "state-sled")))]
+mod tests {
+    use super::*;
+
+    /// Write through `open()`, reopen, and check what was stored
+    fn round_trip<B: StateBackend>(open: impl Fn() -> B) {
+        let param = StoredParam {
+            value: Value::String("warm".to_string()),
+            revision: 7,
+            writer: "s1".to_string(),
+            timestamp: 42,
+            ttl: Some(Ttl::Never),
+            lock_holder: Some("seed".to_string()),
+            origin: None,
+        };
+
+        {
+            let backend = open();
+            backend.put("/a", &param).unwrap();
+            backend
+                .put_all(&[
+                    ("/b".to_string(), param.clone()),
+                    ("/c".to_string(), param.clone()),
+                ])
+                .unwrap();
+            backend.remove(&["/b".to_string()]).unwrap();
+        }
+
+        let backend = open();
+        let mut loaded = backend.load().unwrap();
+        loaded.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            loaded,
+            vec![("/a".to_string(), param.clone()), ("/c".to_string(), param)]
+        );
+
+        let signal = SignalDefinition {
+            address: "/mixer/gain".to_string(),
+            signal_type: clasp_core::SignalType::Param,
+            datatype: Some("f".to_string()),
+            access: Some("rw".to_string()),
+            meta: Some(clasp_core::SignalMeta {
+                unit: Some("dB".to_string()),
+                range: Some((-60.0, 12.0)),
+                default: None,
+                description: None,
+                enum_values: None,
+            }),
+        };
+        backend.put_signals(&[signal]).unwrap();
+        let signals = backend.load_signals().unwrap();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(
+            signals[0].meta.as_ref().and_then(|m| m.range),
+            Some((-60.0, 12.0))
+        );
+        backend
+            .remove_signals(&["/mixer/gain".to_string()])
+            .unwrap();
+        assert!(backend.load_signals().unwrap().is_empty());
+
+        backend.clear().unwrap();
+        assert!(backend.load().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "state-sqlite")]
+    #[test]
+    fn test_sqlite_backend_round_trip() {
+        let dir = std::env::temp_dir().join(format!("clasp-state-{}", uuid::Uuid::new_v4()));
+        let path = dir.with_extension("db");
+        round_trip(|| SqliteStateBackend::open(&path).unwrap());
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[cfg(feature = "state-sled")]
+    #[test]
+    fn test_sled_backend_round_trip() {
+        let path = std::env::temp_dir().join(format!("clasp-state-{}", uuid::Uuid::new_v4()));
+        round_trip(|| SledStateBackend::open(&path).unwrap());
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,278 @@
+//! State branches for pre-show programming
+//!
+//! A branch is a named copy-on-write overlay over live state, so the next
+//! show can be programmed while the current one runs. A SET with
+//! `branch: "next"` passes the same checks as a live SET (scope,
+//! maintenance, validator, transforms, schema) but lands on the branch:
+//! live state, live subscribers and the journal never see it. The first
+//! write to an address copies its live revision, so revision checks on a
+//! branch carry on from live. TTLs don't apply on branches, and branch
+//! SETs can't be bundled.
+//!
+//! A SUBSCRIBE with the `branch` option watches the branch instead of live
+//! state. Its snapshot is the branch's params over live state, and it then
+//! receives the branch's SETs plus client SETs to live addresses the branch
+//! hasn't changed. `branch` can't be combined with `tick_ms`, `stream` or
+//! `convert_to`. GET always reads live state.
+//!
+//! At showtime an admin (admin scope in authenticated mode) SETs
+//! `/clasp/admin/branch/{name}` (see [`BRANCH_ADMIN_PREFIX`]) to:
+//!
+//! - `"merge"`: apply every param on the branch to live state as one
+//!   transaction. If any is refused (locked by another session, say), none
+//!   are applied. Live subscribers receive the new values, and the merged
+//!   SETs are appended to the journal in one transaction, with consecutive
+//!   sequence numbers.
+//! - `"discard"`: drop the branch's changes. Its subscribers get the live
+//!   values back.
+//!
+//! Either way the branch is left empty and its subscribers keep watching it.
+//! Branches are created on first use and removed once they have neither
+//! changes nor subscribers. At most [`MAX_BRANCHES`] exist at once.
+
+use clasp_core::state::{ParamState, StateStore, UpdateError};
+use clasp_core::{SetMessage, SignalType};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+use crate::subscription::{Subscription, SubscriptionManager};
+use crate::SessionId;
+
+/// SET `/clasp/admin/branch/{name}` to `"merge"` or `"discard"` a branch
+pub const BRANCH_ADMIN_PREFIX: &str = "/clasp/admin/branch/";
+
+/// Most branches that can exist at once
+pub const MAX_BRANCHES: usize = 64;
+
+/// Longest branch name accepted
+const MAX_NAME_LEN: usize = 64;
+
+/// What an admin SET on a branch asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchOp {
+    /// Promote the branch's params to live state
+    Merge,
+    /// Drop the branch's params
+    Discard,
+}
+
+impl BranchOp {
+    /// Parse the value of an admin SET
+    pub fn parse(value: &clasp_core::Value) -> Result<Self, String> {
+        match value.as_str() {
+            Some("merge") => Ok(Self::Merge),
+            Some("discard") => Ok(Self::Discard),
+            _ => Err("Branch operation must be \"merge\" or \"discard\"".to_string()),
+        }
+    }
+}
+
+/// Check a branch name: 1 to 64 ASCII letters, digits, `-`, `_` or `.`
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "Branch name must be 1 to {} characters",
+            MAX_NAME_LEN
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!("Invalid branch name {:?}", name));
+    }
+    Ok(())
+}
+
+/// Branch named by an admin address, if it is one
+pub fn admin_branch(address: &str) -> Option<&str> {
+    address.strip_prefix(BRANCH_ADMIN_PREFIX)
+}
+
+#[derive(Default)]
+struct Branch {
+    /// Params written on the branch
+    params: StateStore,
+    /// Sessions watching the branch
+    subscriptions: SubscriptionManager,
+}
+
+impl Branch {
+    fn is_unused(&self) -> bool {
+        self.params.is_empty() && self.subscriptions.is_empty()
+    }
+}
+
+/// Every state branch on a router
+#[derive(Default)]
+pub struct Branches {
+    branches: RwLock<HashMap<String, Branch>>,
+}
+
+impl Branches {
+    /// Names of the branches that currently exist, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.branches.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Params changed on a branch, in address order
+    pub fn changes(&self, name: &str) -> Vec<(String, ParamState)> {
+        self.branches
+            .read()
+            .get(name)
+            .map(|branch| sorted(&branch.params))
+            .unwrap_or_default()
+    }
+
+    /// Apply a SET to a branch. `live` is the address's live state, copied
+    /// onto the branch on its first write there.
+    pub(crate) fn set(
+        &self,
+        name: &str,
+        msg: &SetMessage,
+        writer: &str,
+        live: Option<ParamState>,
+    ) -> Result<u64, UpdateError> {
+        let mut branches = self.branches.write();
+        if !branches.contains_key(name) && branches.len() >= MAX_BRANCHES {
+            return Err(UpdateError::AtCapacity);
+        }
+        let branch = branches.entry(name.to_string()).or_default();
+
+        let copied = match live {
+            Some(live) if branch.params.get(&msg.address).is_none() => {
+                branch.params.set(
+                    &msg.address,
+                    live.value,
+                    &live.writer,
+                    None,
+                    false,
+                    false,
+                    None,
+                )?;
+                if let Some(param) = branch.params.get_mut(&msg.address) {
+                    param.revision = live.revision;
+                }
+                true
+            }
+            _ => false,
+        };
+
+        let result = branch.params.set(
+            &msg.address,
+            msg.value.clone(),
+            writer,
+            msg.revision,
+            msg.lock,
+            msg.unlock,
+            None,
+        );
+        if result.is_err() && copied {
+            branch.params.remove(&msg.address);
+        }
+        if branch.is_unused() {
+            branches.remove(name);
+        }
+        result
+    }
+
+    /// Start a subscription on a branch
+    pub(crate) fn subscribe(&self, name: &str, sub: Subscription) -> Result<(), UpdateError> {
+        let mut branches = self.branches.write();
+        if !branches.contains_key(name) && branches.len() >= MAX_BRANCHES {
+            return Err(UpdateError::AtCapacity);
+        }
+        branches
+            .entry(name.to_string())
+            .or_default()
+            .subscriptions
+            .add(sub);
+        Ok(())
+    }
+
+    /// End a session's branch subscription, whichever branch it is on
+    pub(crate) fn unsubscribe(&self, session_id: &SessionId, id: u32) {
+        let mut branches = self.branches.write();
+        if branches.is_empty() {
+            return;
+        }
+        for branch in branches.values() {
+            branch.subscriptions.remove(session_id, id);
+        }
+        branches.retain(|_, branch| !branch.is_unused());
+    }
+
+    /// End every branch subscription of a session
+    pub(crate) fn remove_session(&self, session_id: &SessionId) {
+        let mut branches = self.branches.write();
+        if branches.is_empty() {
+            return;
+        }
+        for branch in branches.values() {
+            branch.subscriptions.remove_session(session_id);
+        }
+        branches.retain(|_, branch| !branch.is_unused());
+    }
+
+    /// Sessions watching `address` on a branch
+    pub(crate) fn subscribers(&self, name: &str, address: &str) -> Vec<SessionId> {
+        self.branches
+            .read()
+            .get(name)
+            .map(|branch| {
+                branch
+                    .subscriptions
+                    .find_subscribers(address, Some(SignalType::Param))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sessions that see a live write to `address` through a branch that
+    /// hasn't changed it
+    pub(crate) fn live_subscribers(&self, address: &str) -> Vec<SessionId> {
+        let branches = self.branches.read();
+        let mut subscribers = Vec::new();
+        for branch in branches.values() {
+            if branch.params.get(address).is_none() {
+                subscribers.extend(
+                    branch
+                        .subscriptions
+                        .find_subscribers(address, Some(SignalType::Param)),
+                );
+            }
+        }
+        subscribers
+    }
+
+    /// Hand a branch's changes to `apply` and empty the branch if it
+    /// succeeds. The branch is locked meanwhile, so no write is lost between
+    /// reading and emptying it. Returns `None` if the branch has no changes.
+    pub(crate) fn take_with<T, E>(
+        &self,
+        name: &str,
+        apply: impl FnOnce(Vec<(String, ParamState)>) -> Result<T, E>,
+    ) -> Option<Result<T, E>> {
+        let mut branches = self.branches.write();
+        let branch = branches.get_mut(name).filter(|b| !b.params.is_empty())?;
+        let result = apply(sorted(&branch.params));
+        if result.is_ok() {
+            branch.params.clear();
+            if branch.is_unused() {
+                branches.remove(name);
+            }
+        }
+        Some(result)
+    }
+}
+
+fn sorted(params: &StateStore) -> Vec<(String, ParamState)> {
+    let mut params: Vec<(String, ParamState)> = params
+        .snapshot()
+        .into_iter()
+        .map(|(address, param)| (address.to_string(), param.clone()))
+        .collect();
+    params.sort_by(|a, b| a.0.cmp(&b.0));
+    params
+}
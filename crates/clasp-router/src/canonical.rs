@@ -0,0 +1,368 @@
+//! Address canonicalization
+//!
+//! `/lights/1`, `/lights/1/` and `/lights//1` would otherwise be three
+//! params, and `/caf\u{e9}` and `/cafe\u{301}` two, which splits state and
+//! lets a write slip past validators that compare prefixes. With an
+//! [`AddressPolicy`] (on by default, see [`RouterConfig::address_policy`])
+//! every inbound address is rewritten to one canonical form before anything
+//! else sees it:
+//!
+//! - empty segments are dropped, so repeated and trailing slashes go
+//! - text is Unicode NFC
+//! - `%XX` escapes are kept as they are, decoded, or refused
+//!   ([`PercentPolicy`])
+//!
+//! and refused when it doesn't start with `/`, contains control characters
+//! or `.`/`..` segments, or is over `max_length` bytes or `max_depth`
+//! segments:
+//!
+//! ```
+//! use clasp_router::AddressPolicy;
+//!
+//! let policy = AddressPolicy::default();
+//! assert_eq!(policy.canonicalize("/lights//1/").unwrap(), "/lights/1");
+//! assert_eq!(policy.canonicalize("/cafe\u{301}").unwrap(), "/caf\u{e9}");
+//! assert!(policy.canonicalize("/lights/../admin").is_err());
+//! ```
+//!
+//! Native clients whose message has an invalid address get ERROR 200. The
+//! MQTT, OSC and RESP adapters canonicalize topics, addresses and keys
+//! after mapping them, dropping invalid ones (RESP answers with an error).
+//!
+//! State written before canonicalization was turned on can be migrated with
+//! [`RouterState::canonicalize`](crate::RouterState::canonicalize), which
+//! re-keys params and keeps the latest write where several collapse onto
+//! one address.
+//!
+//! [`RouterConfig::address_policy`]: crate::RouterConfig::address_policy
+
+use clasp_core::Message;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// What to do with `%XX` escapes in addresses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PercentPolicy {
+    /// Leave escapes as they are (`%20` and a space are different addresses)
+    #[default]
+    Keep,
+    /// Decode escapes; an escaped `/` or invalid UTF-8 is refused
+    Decode,
+    /// Refuse addresses containing escapes
+    Reject,
+}
+
+impl std::str::FromStr for PercentPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "decode" => Ok(Self::Decode),
+            "reject" => Ok(Self::Reject),
+            other => Err(format!(
+                "unknown percent policy '{}' (expected keep, decode or reject)",
+                other
+            )),
+        }
+    }
+}
+
+/// How inbound addresses are canonicalized (see the [module docs](self))
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressPolicy {
+    /// Rewrite and check addresses at all
+    pub enabled: bool,
+    /// Longest canonical address in bytes (0 = unlimited)
+    pub max_length: usize,
+    /// Most segments in an address (0 = unlimited)
+    pub max_depth: usize,
+    /// What to do with `%XX` escapes
+    pub percent: PercentPolicy,
+}
+
+impl Default for AddressPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_length: 1024,
+            max_depth: 32,
+            percent: PercentPolicy::Keep,
+        }
+    }
+}
+
+impl AddressPolicy {
+    /// Accept every address as it is
+    pub fn off() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// The canonical form of `address` (or subscription pattern), or why it
+    /// is refused
+    pub fn canonicalize<'a>(&self, address: &'a str) -> Result<Cow<'a, str>, String> {
+        if !self.enabled {
+            return Ok(Cow::Borrowed(address));
+        }
+        if !address.starts_with('/') {
+            return Err("address must start with '/'".to_string());
+        }
+
+        let mut text = Cow::Borrowed(address);
+        match self.percent {
+            PercentPolicy::Keep => {}
+            PercentPolicy::Decode => {
+                if let Some(decoded) = percent_decode(&text)? {
+                    text = Cow::Owned(decoded);
+                }
+            }
+            PercentPolicy::Reject => {
+                if has_escape(&text) {
+                    return Err("percent-encoded address".to_string());
+                }
+            }
+        }
+        if text.chars().any(char::is_control) {
+            return Err("control character in address".to_string());
+        }
+        if !is_nfc(&text) {
+            text = Cow::Owned(text.nfc().collect());
+        }
+
+        let segments: Vec<&str> = text.split('/').filter(|s| !s.is_empty()).collect();
+        if segments.iter().any(|s| *s == "." || *s == "..") {
+            return Err("'.' and '..' segments are not allowed".to_string());
+        }
+        if self.max_depth > 0 && segments.len() > self.max_depth {
+            return Err(format!(
+                "{} segments, at most {} allowed",
+                segments.len(),
+                self.max_depth
+            ));
+        }
+        let canonical = format!("/{}", segments.join("/"));
+        if self.max_length > 0 && canonical.len() > self.max_length {
+            return Err(format!(
+                "{} bytes long, at most {} allowed",
+                canonical.len(),
+                self.max_length
+            ));
+        }
+
+        Ok(if canonical == address {
+            Cow::Borrowed(address)
+        } else {
+            Cow::Owned(canonical)
+        })
+    }
+
+    /// Rewrite every address and pattern in `msg` to its canonical form,
+    /// or say which one is refused and why
+    pub fn apply(&self, msg: &mut Message) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        match msg {
+            Message::Set(set) => self.rewrite(&mut set.address),
+            Message::SetMany(batch) => self.rewrite(&mut batch.base),
+            Message::Publish(publish) => self.rewrite(&mut publish.address),
+            Message::Get(get) => self.rewrite(&mut get.address),
+            Message::Subscribe(sub) => self.rewrite(&mut sub.pattern),
+            Message::Query(query) => self.rewrite(&mut query.pattern),
+            Message::Replay(replay) => self.rewrite(&mut replay.pattern),
+            Message::Announce(announce) => {
+                self.rewrite(&mut announce.namespace)?;
+                announce
+                    .signals
+                    .iter_mut()
+                    .try_for_each(|signal| self.rewrite(&mut signal.address))
+            }
+            Message::Bundle(bundle) => bundle.messages.iter_mut().try_for_each(|m| self.apply(m)),
+            _ => Ok(()),
+        }
+    }
+
+    fn rewrite(&self, address: &mut String) -> Result<(), String> {
+        match self.canonicalize(address) {
+            Ok(Cow::Borrowed(_)) => Ok(()),
+            Ok(Cow::Owned(canonical)) => {
+                *address = canonical;
+                Ok(())
+            }
+            Err(reason) => Err(format!("Invalid address {}: {}", address, reason)),
+        }
+    }
+}
+
+/// What [`RouterState::canonicalize`](crate::RouterState::canonicalize) changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CanonicalizeReport {
+    /// Params moved to their canonical address, as (old, new)
+    pub renamed: Vec<(String, String)>,
+    /// Params dropped because a later write to the same canonical address won
+    pub merged: Vec<String>,
+    /// Params dropped because their address is refused outright
+    pub invalid: Vec<String>,
+}
+
+impl CanonicalizeReport {
+    /// Whether the state was already canonical
+    pub fn is_empty(&self) -> bool {
+        self.renamed.is_empty() && self.merged.is_empty() && self.invalid.is_empty()
+    }
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
+
+fn has_escape(s: &str) -> bool {
+    s.as_bytes()
+        .windows(3)
+        .any(|w| w[0] == b'%' && hex_value(w[1]).is_some() && hex_value(w[2]).is_some())
+}
+
+/// Decode `%XX` escapes, or None if there are none
+fn percent_decode(s: &str) -> Result<Option<String>, String> {
+    if !has_escape(s) {
+        return Ok(None);
+    }
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| Some(hex_value(*bytes.get(i + 1)?)? << 4 | hex_value(*bytes.get(i + 2)?)?))
+            .flatten();
+        match escaped {
+            Some(b'/') => return Err("escaped '/' in address".to_string()),
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded)
+        .map(Some)
+        .map_err(|_| "percent-encoded address is not UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{BundleMessage, PublishMessage, SetMessage, Value};
+
+    #[test]
+    fn test_separators_and_nfc() {
+        let policy = AddressPolicy::default();
+        for (input, expected) in [
+            ("/a/b", "/a/b"),
+            ("/a/b/", "/a/b"),
+            ("//a///b", "/a/b"),
+            ("/", "/"),
+            ("/lights/**", "/lights/**"),
+            ("/cafe\u{301}/x", "/caf\u{e9}/x"),
+        ] {
+            assert_eq!(policy.canonicalize(input).unwrap(), expected, "{}", input);
+        }
+        assert!(matches!(policy.canonicalize("/a/b"), Ok(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn test_invalid_forms() {
+        let policy = AddressPolicy {
+            max_length: 16,
+            max_depth: 3,
+            ..Default::default()
+        };
+        for input in [
+            "a/b",
+            "",
+            "/a/\u{0}/b",
+            "/a/../b",
+            "/./a",
+            "/a/b/c/d",
+            "/abcdefghijklmnopqrstuvwxyz",
+        ] {
+            assert!(policy.canonicalize(input).is_err(), "{:?}", input);
+        }
+        assert_eq!(AddressPolicy::off().canonicalize("a//b/").unwrap(), "a//b/");
+    }
+
+    #[test]
+    fn test_percent_policies() {
+        let keep = AddressPolicy::default();
+        assert_eq!(keep.canonicalize("/a%20b").unwrap(), "/a%20b");
+
+        let decode = AddressPolicy {
+            percent: PercentPolicy::Decode,
+            ..Default::default()
+        };
+        assert_eq!(decode.canonicalize("/a%20b/%41").unwrap(), "/a b/A");
+        assert_eq!(decode.canonicalize("/100%").unwrap(), "/100%");
+        assert!(decode.canonicalize("/a%2Fb").is_err());
+        assert!(decode.canonicalize("/a%00b").is_err());
+        assert!(decode.canonicalize("/a%FFb").is_err());
+
+        let reject = AddressPolicy {
+            percent: PercentPolicy::Reject,
+            ..Default::default()
+        };
+        assert!(reject.canonicalize("/a%20b").is_err());
+        assert!(reject.canonicalize("/100%").is_ok());
+    }
+
+    #[test]
+    fn test_apply_rewrites_bundles() {
+        let set = |address: &str| {
+            Message::Set(SetMessage {
+                address: address.to_string(),
+                value: Value::Int(1),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            })
+        };
+        let mut msg = Message::Bundle(BundleMessage {
+            timestamp: None,
+            messages: vec![
+                set("/a//b/"),
+                Message::Publish(PublishMessage {
+                    address: "/e/".to_string(),
+                    signal: None,
+                    value: None,
+                    payload: None,
+                    samples: None,
+                    rate: None,
+                    id: None,
+                    phase: None,
+                    timestamp: None,
+                    timeline: None,
+                }),
+            ],
+        });
+        let policy = AddressPolicy::default();
+        policy.apply(&mut msg).unwrap();
+        let Message::Bundle(bundle) = msg else {
+            unreachable!()
+        };
+        assert!(matches!(&bundle.messages[0], Message::Set(s) if s.address == "/a/b"));
+        assert!(matches!(&bundle.messages[1], Message::Publish(p) if p.address == "/e"));
+
+        let err = policy.apply(&mut set("/a/../b")).unwrap_err();
+        assert!(err.contains("/a/../b"), "{}", err);
+    }
+}
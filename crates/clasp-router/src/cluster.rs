@@ -0,0 +1,656 @@
+//! Router clustering over a shared backplane
+//!
+//! Several routers behind one load balancer can serve the same address
+//! space. Each node passes the SETs and PUBLISHes its own clients make to a
+//! [`Backplane`], a pub/sub channel every node reads. The other nodes apply
+//! the SETs to their state with the writer's revision, writer and timestamp
+//! (see [`RouterState::apply_replicated`]) and deliver both to their local
+//! subscribers, so a client sees the same params and events whichever node
+//! it lands on:
+//!
+//! ```no_run
+//! use clasp_router::cluster::MemoryBackplane;
+//! use clasp_router::{Router, RouterConfig};
+//! use std::sync::Arc;
+//!
+//! // Two routers in one process; across machines use a RedisBackplane
+//! let backplane = Arc::new(MemoryBackplane::new());
+//! let a = Router::new(RouterConfig::default()).with_cluster(backplane.clone());
+//! let b = Router::new(RouterConfig::default()).with_cluster(backplane);
+//! ```
+//!
+//! A node that starts asks the others for their state and applies what they
+//! answer, so it joins with the params written before it came up.
+//!
+//! Concurrent writes to one param resolve by revision, then by writer, so
+//! every node settles on the same value. What stays on its node:
+//!
+//! - sessions, subscriptions and locks (a lock only guards writes made
+//!   through the node holding it)
+//! - params with a TTL, session-scoped params, and branch writes
+//! - the reserved `/clasp/` namespace (presence, schemas, admin state)
+//! - P2P signals, and fleet fan-out to the sessions of member entities
+//! - the journal: each node journals its own clients' writes
+//!
+//! Implementations: [`MemoryBackplane`] (tests and embedding) and, with the
+//! `cluster-redis` feature, [`RedisBackplane`] on a Redis stream.
+
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use clasp_core::{
+    codec, GetMessage, Message, ParamValue, PublishMessage, SetMessage, SignalType, SnapshotMessage,
+};
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::error::{Result, RouterError};
+use crate::handlers::broadcast_to_subscriber_list;
+use crate::reserved::RESERVED_PREFIX;
+use crate::session::{Session, SessionId};
+use crate::shadow::ShadowConfig;
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+#[cfg(feature = "cluster-redis")]
+pub use redis_backplane::RedisBackplane;
+
+/// Envelopes queued for the backplane before new ones are dropped
+pub const OUTBOUND_BUFFER: usize = 4096;
+
+/// Params per SNAPSHOT envelope answering a joining node
+const SYNC_CHUNK: usize = 256;
+
+const ENVELOPE_VERSION: u8 = 1;
+
+/// Pub/sub channel shared by the nodes of a cluster
+///
+/// Every payload published by any node must reach every subscriber,
+/// including the publishing node (which skips its own).
+#[async_trait]
+pub trait Backplane: Send + Sync {
+    /// Send a payload to every node
+    async fn publish(&self, payload: Bytes) -> Result<()>;
+
+    /// Receive the payloads published from now on
+    async fn subscribe(&self) -> Result<mpsc::Receiver<Bytes>>;
+}
+
+/// In-process backplane for routers that share a process
+#[derive(Clone)]
+pub struct MemoryBackplane {
+    tx: broadcast::Sender<Bytes>,
+}
+
+impl MemoryBackplane {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(OUTBOUND_BUFFER).0,
+        }
+    }
+}
+
+impl Default for MemoryBackplane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Backplane for MemoryBackplane {
+    async fn publish(&self, payload: Bytes) -> Result<()> {
+        // No receivers just means no node is listening yet
+        let _ = self.tx.send(payload);
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<mpsc::Receiver<Bytes>> {
+        let mut rx = self.tx.subscribe();
+        let (tx, out) = mpsc::channel(OUTBOUND_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(payload) => {
+                        if tx.send(payload).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Cluster: memory backplane subscriber lagged by {}", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(out)
+    }
+}
+
+/// A message relayed between nodes, with the node and session it came from
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    /// Node that published it
+    pub node: String,
+    /// Session that wrote it, on that node
+    pub writer: String,
+    /// When the node applied it (microseconds)
+    pub timestamp: u64,
+    pub message: Message,
+}
+
+impl Envelope {
+    /// Wire layout: `[version:u8][node:u8 len][writer:u16 len][timestamp:u64][message payload]`
+    pub fn encode(&self) -> Result<Bytes> {
+        if self.node.len() > u8::MAX as usize || self.writer.len() > u16::MAX as usize {
+            return Err(RouterError::Backplane("envelope id too long".to_string()));
+        }
+        let message = codec::encode_message(&self.message)?;
+        let mut buf =
+            BytesMut::with_capacity(12 + self.node.len() + self.writer.len() + message.len());
+        buf.put_u8(ENVELOPE_VERSION);
+        buf.put_u8(self.node.len() as u8);
+        buf.put_slice(self.node.as_bytes());
+        buf.put_u16(self.writer.len() as u16);
+        buf.put_slice(self.writer.as_bytes());
+        buf.put_u64(self.timestamp);
+        buf.put_slice(&message);
+        Ok(buf.freeze())
+    }
+
+    pub fn decode(mut buf: &[u8]) -> Result<Self> {
+        let invalid = || RouterError::Backplane("truncated envelope".to_string());
+        if buf.remaining() < 2 {
+            return Err(invalid());
+        }
+        let version = buf.get_u8();
+        if version != ENVELOPE_VERSION {
+            return Err(RouterError::Backplane(format!(
+                "unsupported envelope version {}",
+                version
+            )));
+        }
+        let node_len = buf.get_u8() as usize;
+        if buf.remaining() < node_len + 2 {
+            return Err(invalid());
+        }
+        let node = String::from_utf8_lossy(&buf[..node_len]).into_owned();
+        buf.advance(node_len);
+        let writer_len = buf.get_u16() as usize;
+        if buf.remaining() < writer_len + 8 {
+            return Err(invalid());
+        }
+        let writer = String::from_utf8_lossy(&buf[..writer_len]).into_owned();
+        buf.advance(writer_len);
+        let timestamp = buf.get_u64();
+        let message = codec::decode_message(buf)?;
+        Ok(Self {
+            node,
+            writer,
+            timestamp,
+            message,
+        })
+    }
+}
+
+/// This node's link to the rest of the cluster
+pub struct Cluster {
+    node: String,
+    backplane: Arc<dyn Backplane>,
+    outbound: mpsc::Sender<Bytes>,
+    /// Taken by the first [`Cluster::start`]
+    pending: Mutex<Option<mpsc::Receiver<Bytes>>>,
+    forwarded: AtomicU64,
+    received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Cluster {
+    pub fn new(backplane: Arc<dyn Backplane>) -> Self {
+        let (outbound, pending) = mpsc::channel(OUTBOUND_BUFFER);
+        Self {
+            node: Uuid::new_v4().to_string(),
+            backplane,
+            outbound,
+            pending: Mutex::new(Some(pending)),
+            forwarded: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Random ID of this node, fixed for the life of the router
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Envelopes queued for the backplane
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    /// Envelopes from other nodes applied here
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Envelopes dropped because the backplane fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Whether writes to `address` are shared with the other nodes
+    pub fn shares(address: &str) -> bool {
+        !address.starts_with(RESERVED_PREFIX)
+    }
+
+    /// Queue a SET applied to live state at `revision` for the other nodes
+    pub(crate) fn forward_set(&self, set: &SetMessage, revision: u64, writer: &str) {
+        self.forward(
+            writer,
+            Message::Set(SetMessage {
+                revision: Some(revision),
+                lock: false,
+                unlock: false,
+                trace: false,
+                ..set.clone()
+            }),
+        );
+    }
+
+    /// Queue a routed PUBLISH for the other nodes
+    pub(crate) fn forward_publish(&self, publish: &PublishMessage, writer: &str) {
+        if Self::shares(&publish.address) {
+            self.forward(writer, Message::Publish(publish.clone()));
+        }
+    }
+
+    fn forward(&self, writer: &str, message: Message) {
+        let envelope = Envelope {
+            node: self.node.clone(),
+            writer: writer.to_string(),
+            timestamp: clasp_core::time::now(),
+            message,
+        };
+        let payload = match envelope.encode() {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Cluster: can't encode envelope: {}", e);
+                return;
+            }
+        };
+        if self.outbound.try_send(payload).is_ok() {
+            self.forwarded.fetch_add(1, Ordering::Relaxed);
+        } else {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                warn!("Cluster: backplane behind, {} envelopes dropped", dropped);
+            }
+        }
+    }
+
+    /// Start publishing queued envelopes and applying other nodes' ones,
+    /// then ask the other nodes for their state. Does nothing after the
+    /// first call.
+    pub(crate) fn start(
+        self: &Arc<Self>,
+        state: Arc<RouterState>,
+        sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+        subscriptions: Arc<SubscriptionManager>,
+        shadow: ShadowConfig,
+        running: Arc<RwLock<bool>>,
+    ) {
+        let Some(mut pending) = self.pending.lock().take() else {
+            return;
+        };
+
+        let backplane = Arc::clone(&self.backplane);
+        tokio::spawn(async move {
+            while let Some(payload) = pending.recv().await {
+                if let Err(e) = backplane.publish(payload).await {
+                    warn!("Cluster: publish failed: {}", e);
+                }
+            }
+        });
+
+        let cluster = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut inbound = match cluster.backplane.subscribe().await {
+                Ok(inbound) => inbound,
+                Err(e) => {
+                    warn!("Cluster: can't subscribe to the backplane: {}", e);
+                    return;
+                }
+            };
+            info!("Cluster: node {} joined", cluster.node);
+            cluster.forward(
+                "",
+                Message::Get(GetMessage {
+                    address: "/**".to_string(),
+                    depth: None,
+                }),
+            );
+
+            while let Some(payload) = inbound.recv().await {
+                if !*running.read() {
+                    break;
+                }
+                let envelope = match Envelope::decode(&payload) {
+                    Ok(envelope) => envelope,
+                    Err(e) => {
+                        debug!("Cluster: ignoring envelope: {}", e);
+                        continue;
+                    }
+                };
+                if envelope.node == cluster.node {
+                    continue;
+                }
+                cluster.received.fetch_add(1, Ordering::Relaxed);
+                cluster.apply(envelope, &state, &sessions, &subscriptions, &shadow);
+            }
+            debug!("Cluster: node {} stopped", cluster.node);
+        });
+    }
+
+    /// Apply an envelope from another node
+    fn apply(
+        &self,
+        envelope: Envelope,
+        state: &RouterState,
+        sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+        subscriptions: &SubscriptionManager,
+        shadow: &ShadowConfig,
+    ) {
+        match envelope.message {
+            Message::Set(set) => {
+                let Some(revision) = set.revision else {
+                    return;
+                };
+                apply_param(
+                    &set.address,
+                    set.value,
+                    &envelope.writer,
+                    revision,
+                    envelope.timestamp,
+                    state,
+                    sessions,
+                    subscriptions,
+                    shadow,
+                );
+            }
+            Message::Publish(publish) => {
+                let subscribers = subscriptions.find_subscribers(&publish.address, publish.signal);
+                if let Ok(bytes) = codec::encode(&Message::Publish(publish)) {
+                    broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+                }
+            }
+            // A node joined and wants the state it missed
+            Message::Get(_) => self.answer_sync(state),
+            Message::Snapshot(snapshot) => {
+                for param in snapshot.params {
+                    apply_param(
+                        &param.address,
+                        param.value,
+                        param.writer.as_deref().unwrap_or_default(),
+                        param.revision,
+                        param.timestamp.unwrap_or(envelope.timestamp),
+                        state,
+                        sessions,
+                        subscriptions,
+                        shadow,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Publish every shared param for a joining node
+    fn answer_sync(&self, state: &RouterState) {
+        let params: Vec<ParamValue> = state
+            .get_matching("/**")
+            .into_iter()
+            .filter(|(address, param)| {
+                Self::shares(address) && param.ttl.is_none() && !state.is_session_scoped(address)
+            })
+            .map(|(address, param)| ParamValue {
+                address,
+                value: param.value,
+                revision: param.revision,
+                writer: Some(param.writer),
+                timestamp: Some(param.timestamp),
+            })
+            .collect();
+        debug!(
+            "Cluster: sharing {} params with a joining node",
+            params.len()
+        );
+        for chunk in params.chunks(SYNC_CHUNK) {
+            self.forward(
+                "",
+                Message::Snapshot(SnapshotMessage {
+                    params: chunk.to_vec(),
+                    ..Default::default()
+                }),
+            );
+        }
+    }
+}
+
+/// Apply a param from another node and tell local subscribers if it changed
+#[allow(clippy::too_many_arguments)]
+fn apply_param(
+    address: &str,
+    value: clasp_core::Value,
+    writer: &str,
+    revision: u64,
+    timestamp: u64,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+    shadow: &ShadowConfig,
+) {
+    if !Cluster::shares(address)
+        || !state.apply_replicated(address, value.clone(), writer, revision, timestamp)
+    {
+        return;
+    }
+    let update = Message::Set(SetMessage {
+        address: address.to_string(),
+        value,
+        revision: Some(revision),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    if let Ok(bytes) = codec::encode(&update) {
+        let subscribers = subscriptions.find_subscribers(address, Some(SignalType::Param));
+        broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+        let watchers = state.branches().live_subscribers(address);
+        broadcast_to_subscriber_list(&bytes, &watchers, sessions, None);
+    }
+    state
+        .shadows()
+        .after_set(address, shadow, state, sessions, subscriptions);
+}
+
+#[cfg(feature = "cluster-redis")]
+mod redis_backplane {
+    use super::*;
+    use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+    use redis::AsyncCommands;
+    use std::time::Duration;
+
+    /// Field holding the envelope in each stream entry
+    const FIELD: &str = "e";
+
+    /// How long one XREAD waits for new entries
+    const BLOCK_MS: usize = 5000;
+
+    const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    /// Backplane on a Redis stream
+    ///
+    /// Envelopes are appended with `XADD` (trimmed to about `max_len`
+    /// entries) and read with a blocking `XREAD`. A node that loses its
+    /// connection resumes after the last entry it read, so a short outage
+    /// loses nothing as long as the stream still holds what it missed.
+    pub struct RedisBackplane {
+        client: redis::Client,
+        stream: String,
+        max_len: usize,
+        connection: tokio::sync::Mutex<Option<redis::aio::MultiplexedConnection>>,
+    }
+
+    impl RedisBackplane {
+        /// Use the stream `stream` on the Redis server at `url`
+        /// (e.g. `redis://cache:6379/0`)
+        pub fn new(url: &str, stream: impl Into<String>) -> Result<Self> {
+            let client = redis::Client::open(url)
+                .map_err(|e| RouterError::Config(format!("invalid Redis URL: {}", e)))?;
+            Ok(Self {
+                client,
+                stream: stream.into(),
+                max_len: 100_000,
+                connection: tokio::sync::Mutex::new(None),
+            })
+        }
+
+        /// Keep about this many entries in the stream (default 100,000)
+        pub fn max_len(mut self, max_len: usize) -> Self {
+            self.max_len = max_len;
+            self
+        }
+    }
+
+    fn backplane_error(e: redis::RedisError) -> RouterError {
+        RouterError::Backplane(e.to_string())
+    }
+
+    #[async_trait]
+    impl Backplane for RedisBackplane {
+        async fn publish(&self, payload: Bytes) -> Result<()> {
+            let mut connection = self.connection.lock().await;
+            if connection.is_none() {
+                *connection = Some(
+                    self.client
+                        .get_multiplexed_tokio_connection()
+                        .await
+                        .map_err(backplane_error)?,
+                );
+            }
+            let Some(conn) = connection.as_mut() else {
+                return Ok(());
+            };
+            let result: redis::RedisResult<String> = conn
+                .xadd_maxlen(
+                    &self.stream,
+                    StreamMaxlen::Approx(self.max_len),
+                    "*",
+                    &[(FIELD, payload.as_ref())],
+                )
+                .await;
+            if let Err(e) = result {
+                // Reconnect on the next publish
+                *connection = None;
+                return Err(backplane_error(e));
+            }
+            Ok(())
+        }
+
+        async fn subscribe(&self) -> Result<mpsc::Receiver<Bytes>> {
+            let client = self.client.clone();
+            let stream = self.stream.clone();
+            let (tx, rx) = mpsc::channel(OUTBOUND_BUFFER);
+            // Fail fast on a bad server; later outages are retried
+            let mut conn = client
+                .get_multiplexed_tokio_connection()
+                .await
+                .map_err(backplane_error)?;
+            tokio::spawn(async move {
+                let options = StreamReadOptions::default().block(BLOCK_MS).count(256);
+                let mut last_id = "$".to_string();
+                let mut backoff = INITIAL_BACKOFF;
+                loop {
+                    let reply: redis::RedisResult<Option<StreamReadReply>> =
+                        conn.xread_options(&[&stream], &[&last_id], &options).await;
+                    match reply {
+                        Ok(reply) => {
+                            backoff = INITIAL_BACKOFF;
+                            for key in reply.map(|r| r.keys).unwrap_or_default() {
+                                for entry in key.ids {
+                                    last_id = entry.id.clone();
+                                    let Some(redis::Value::Data(data)) = entry.map.get(FIELD)
+                                    else {
+                                        continue;
+                                    };
+                                    if tx.send(Bytes::copy_from_slice(data)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Cluster: Redis read failed: {}", e);
+                            tokio::time::sleep(backoff).await;
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                            match client.get_multiplexed_tokio_connection().await {
+                                Ok(fresh) => conn = fresh,
+                                Err(e) => warn!("Cluster: Redis reconnect failed: {}", e),
+                            }
+                        }
+                    }
+                    if tx.is_closed() {
+                        return;
+                    }
+                }
+            });
+            Ok(rx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::Value;
+
+    #[test]
+    fn envelope_roundtrip() {
+        let envelope = Envelope {
+            node: "node-a".to_string(),
+            writer: "session-1".to_string(),
+            timestamp: 42,
+            message: Message::Set(SetMessage {
+                address: "/mixer/1/level".to_string(),
+                value: Value::Float(0.5),
+                revision: Some(7),
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            }),
+        };
+        let bytes = envelope.encode().unwrap();
+        let decoded = Envelope::decode(&bytes).unwrap();
+        assert_eq!(decoded.node, "node-a");
+        assert_eq!(decoded.writer, "session-1");
+        assert_eq!(decoded.timestamp, 42);
+        assert!(matches!(decoded.message, Message::Set(set) if set.revision == Some(7)));
+
+        assert!(Envelope::decode(&bytes[..5]).is_err());
+    }
+
+    #[test]
+    fn reserved_namespace_stays_local() {
+        assert!(Cluster::shares("/mixer/1/level"));
+        assert!(!Cluster::shares("/clasp/presence/abc"));
+    }
+}
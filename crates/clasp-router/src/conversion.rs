@@ -0,0 +1,302 @@
+//! Unit conversion and value transforms for subscriptions with `convert_to`
+//! or `transform`
+//!
+//! Mixed fleets report the same quantity in different units. A subscription
+//! with `convert_to` set receives numeric SET and PUBLISH values converted
+//! from the unit announced in the signal registry (`SignalMeta::unit`) to
+//! the requested one, using the table in [`clasp_core::units`].
+//!
+//! A subscription with `transform` set then has its numeric values mapped,
+//! scaled, offset, clamped and rounded, e.g. `0..1` levels onto DMX's
+//! `0..255`, so DMX and OSC consumers don't each remap ranges themselves.
+//!
+//! Like tick aggregation, conversion happens in
+//! [`Session::try_send`](crate::Session::try_send) so it applies to every
+//! broadcast. Values with no announced unit, or whose unit cannot be
+//! converted, skip unit conversion, and non-numeric values are delivered
+//! unchanged; incompatible units already announced for the pattern and
+//! invalid transforms are rejected when subscribing.
+
+use bytes::Bytes;
+use clasp_core::{codec, units, Message, SignalType, SubscribeOptions, Value};
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use tracing::debug;
+
+use crate::state::RouterState;
+use crate::subscription::Subscription;
+
+struct UnitSubscription {
+    subscription: Subscription,
+    state: Weak<RouterState>,
+}
+
+/// Per-session set of converting subscriptions
+#[derive(Default)]
+pub struct UnitConversions {
+    /// Fast-path check so sessions without conversions skip decoding
+    active: AtomicBool,
+    subs: RwLock<Vec<UnitSubscription>>,
+}
+
+impl UnitConversions {
+    /// Register (or replace) a converting subscription. Its `convert_to`
+    /// and `transform` options say what to do.
+    pub fn add(&self, subscription: Subscription, state: &Arc<RouterState>) {
+        let mut subs = self.subs.write();
+        subs.retain(|s| s.subscription.id != subscription.id);
+        subs.push(UnitSubscription {
+            subscription,
+            state: Arc::downgrade(state),
+        });
+        self.active.store(true, Ordering::Release);
+    }
+
+    /// Remove a converting subscription. Returns `true` if it existed.
+    pub fn remove(&self, id: u32) -> bool {
+        let mut subs = self.subs.write();
+        let before = subs.len();
+        subs.retain(|s| s.subscription.id != id);
+        self.active.store(!subs.is_empty(), Ordering::Release);
+        subs.len() != before
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Rewrite a broadcast with converted values if it matches a converting
+    /// subscription. Anything else is returned untouched.
+    pub fn apply(&self, data: Bytes) -> Bytes {
+        if !self.is_active() {
+            return data;
+        }
+        let Ok((mut message, frame)) = codec::decode(&data) else {
+            return data;
+        };
+        let (address, signal_type) = match &message {
+            Message::Set(set) => (set.address.clone(), Some(SignalType::Param)),
+            Message::Publish(publish) => (publish.address.clone(), publish.signal),
+            _ => return data,
+        };
+
+        let (options, state) = {
+            let subs = self.subs.read();
+            let Some(sub) = subs
+                .iter()
+                .find(|s| s.subscription.matches(&address, signal_type))
+            else {
+                return data;
+            };
+            (sub.subscription.options.clone(), sub.state.clone())
+        };
+        let Some(state) = state.upgrade() else {
+            return data;
+        };
+
+        let converted = match &mut message {
+            Message::Set(set) => convert_for(&mut set.value, &address, &options, &state),
+            Message::Publish(publish) => match publish.value.as_mut() {
+                Some(value) => convert_for(value, &address, &options, &state),
+                None => false,
+            },
+            _ => false,
+        };
+        if !converted {
+            return data;
+        }
+        match codec::encode_with_options(&message, Some(frame.flags.qos), frame.timestamp) {
+            Ok(bytes) => bytes,
+            Err(_) => data,
+        }
+    }
+}
+
+/// Whether a subscription's values need converting before delivery
+pub(crate) fn converts(options: &SubscribeOptions) -> bool {
+    options.convert_to.is_some() || options.transform.is_some()
+}
+
+/// Convert a value delivered at `address` for a subscription with these
+/// options: to its `convert_to` unit, then through its `transform`.
+/// Returns `true` if the value changed.
+pub(crate) fn convert_for(
+    value: &mut Value,
+    address: &str,
+    options: &SubscribeOptions,
+    state: &RouterState,
+) -> bool {
+    let mut changed = false;
+    if let Some(ref target) = options.convert_to {
+        if let Some(source) = state.signal_unit(address) {
+            changed = convert_in_place(value, &source, target);
+        }
+    }
+    if let Some(ref transform) = options.transform {
+        match units::transform_value(value, transform) {
+            Ok(transformed) => {
+                *value = transformed;
+                changed = true;
+            }
+            Err(e) => debug!("Not transforming {}: {}", address, e),
+        }
+    }
+    changed
+}
+
+/// Convert a value in place, returning `true` if it changed
+pub(crate) fn convert_in_place(value: &mut Value, from: &str, to: &str) -> bool {
+    match units::convert_value(value, from, to) {
+        Ok(converted) => {
+            *value = converted;
+            true
+        }
+        Err(e) => {
+            debug!("Not converting {} to {}: {}", from, to, e);
+            false
+        }
+    }
+}
+
+/// Check a subscription's `convert_to` against the units already announced
+/// for its pattern, and its `transform` on its own. Returns the first
+/// error, e.g. for an unknown target unit, a signal whose unit measures
+/// something else, or an empty range to map from.
+pub(crate) fn validate(
+    state: &RouterState,
+    pattern: &str,
+    options: &SubscribeOptions,
+) -> Result<(), units::UnitError> {
+    if let Some(ref transform) = options.transform {
+        units::check_transform(transform)?;
+    }
+    let Some(ref target) = options.convert_to else {
+        return Ok(());
+    };
+    if units::lookup(target).is_none() {
+        return Err(units::UnitError::Unknown(target.to_string()));
+    }
+    for signal in state.query_signals(pattern) {
+        if let Some(unit) = signal.meta.and_then(|m| m.unit) {
+            units::check(&unit, target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{RangeMap, SetMessage, SignalDefinition, SignalMeta, ValueTransform};
+
+    fn state_with_unit(address: &str, unit: &str) -> Arc<RouterState> {
+        let state = Arc::new(RouterState::new());
+        state.register_signals(vec![SignalDefinition {
+            address: address.to_string(),
+            signal_type: SignalType::Param,
+            datatype: Some("float".to_string()),
+            access: None,
+            meta: Some(SignalMeta {
+                unit: Some(unit.to_string()),
+                range: None,
+                default: None,
+                description: None,
+                enum_values: None,
+            }),
+        }]);
+        state
+    }
+
+    fn set_bytes(address: &str, value: Value) -> Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value,
+            revision: Some(1),
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    fn subscription(pattern: &str, options: SubscribeOptions) -> Subscription {
+        Subscription::new(1, "s".to_string(), pattern, vec![], options).unwrap()
+    }
+
+    fn convert_to(unit: &str) -> SubscribeOptions {
+        SubscribeOptions {
+            convert_to: Some(unit.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_converts_matching_values() {
+        let state = state_with_unit("/sensors/a/temp", "C");
+        let conversions = UnitConversions::default();
+        conversions.add(subscription("/sensors/**", convert_to("F")), &state);
+
+        let out = conversions.apply(set_bytes("/sensors/a/temp", Value::Float(100.0)));
+        let (Message::Set(set), _) = codec::decode(&out).unwrap() else {
+            panic!("expected SET");
+        };
+        assert_eq!(set.value, Value::Float(212.0));
+        assert_eq!(set.revision, Some(1));
+
+        // No announced unit: delivered unchanged
+        let data = set_bytes("/sensors/b/temp", Value::Float(100.0));
+        assert_eq!(conversions.apply(data.clone()), data);
+    }
+
+    #[test]
+    fn test_transforms_after_converting() {
+        let state = state_with_unit("/sensors/a/temp", "C");
+        let conversions = UnitConversions::default();
+        let options = SubscribeOptions {
+            transform: Some(ValueTransform {
+                map: Some(RangeMap {
+                    from: (32.0, 212.0),
+                    to: (0.0, 255.0),
+                }),
+                round: true,
+                ..Default::default()
+            }),
+            ..convert_to("F")
+        };
+        conversions.add(subscription("/sensors/**", options), &state);
+
+        let out = conversions.apply(set_bytes("/sensors/a/temp", Value::Float(100.0)));
+        let (Message::Set(set), _) = codec::decode(&out).unwrap() else {
+            panic!("expected SET");
+        };
+        assert_eq!(set.value, Value::Int(255));
+
+        // No announced unit: transformed without converting
+        let out = conversions.apply(set_bytes("/sensors/b/temp", Value::Float(122.0)));
+        let (Message::Set(set), _) = codec::decode(&out).unwrap() else {
+            panic!("expected SET");
+        };
+        assert_eq!(set.value, Value::Int(128));
+    }
+
+    #[test]
+    fn test_validate_rejects_incompatible() {
+        let state = state_with_unit("/sensors/a/height", "m");
+        assert!(validate(&state, "/sensors/**", &convert_to("ft")).is_ok());
+        assert!(validate(&state, "/sensors/**", &convert_to("F")).is_err());
+        assert!(validate(&state, "/sensors/**", &convert_to("parsec")).is_err());
+
+        let clamp = |min, max| SubscribeOptions {
+            transform: Some(ValueTransform {
+                clamp: Some((min, max)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(validate(&state, "/sensors/**", &clamp(0.0, 1.0)).is_ok());
+        assert!(validate(&state, "/sensors/**", &clamp(1.0, 0.0)).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Per-subscription delivery counts for link health reporting
+//!
+//! Counting is off unless [`RouterConfig::delivery_stats`] is set. When it
+//! is on, every broadcast to a session passes through
+//! [`Session::try_send`](crate::Session::try_send), which credits each
+//! update in the frame to the session's subscriptions that match it: as
+//! delivered when the transport accepts the frame, as dropped when the send
+//! buffer is full. BUNDLE and SET_MANY frames count once per update they
+//! carry. Updates a tick subscription or stream policy holds back are
+//! counted when their bundle or queue is sent; updates a policy coalesces
+//! away are counted neither way.
+//!
+//! A client reads its own counts, with the router-side queue depth for its
+//! connection, by GETting [`STATS_ADDRESS`]; see [`clasp_core::link_stats`]
+//! for the value. With counting off the subscription list is empty.
+//!
+//! Counting costs an address peek and a glob match per subscription for
+//! every frame sent; off, [`Session::try_send`](crate::Session::try_send)
+//! only checks a flag. `benches/fanout.rs` compares the two.
+//!
+//! [`RouterConfig::delivery_stats`]: crate::RouterConfig::delivery_stats
+
+use bytes::Bytes;
+use clasp_core::link_stats::SubscriptionStats;
+use clasp_core::{codec, Frame, Message, SignalType};
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub use clasp_core::link_stats::STATS_ADDRESS;
+
+use crate::subscription::Subscription;
+
+#[derive(Debug)]
+struct Counted {
+    subscription: Subscription,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Delivery counts for one session's subscriptions
+///
+/// The default counts nothing; see [`DeliveryStats::enabled`].
+#[derive(Debug, Default)]
+pub struct DeliveryStats {
+    enabled: bool,
+    subs: RwLock<Vec<Counted>>,
+}
+
+impl DeliveryStats {
+    /// Stats that count deliveries
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            subs: RwLock::default(),
+        }
+    }
+
+    /// Start counting for a subscription, replacing (and resetting) any
+    /// earlier one with the same ID
+    pub fn add(&self, subscription: Subscription) {
+        if !self.enabled {
+            return;
+        }
+        let mut subs = self.subs.write();
+        subs.retain(|s| s.subscription.id != subscription.id);
+        subs.push(Counted {
+            subscription,
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+    }
+
+    /// Stop counting for a subscription. Returns `true` if it existed.
+    pub fn remove(&self, id: u32) -> bool {
+        let mut subs = self.subs.write();
+        let before = subs.len();
+        subs.retain(|s| s.subscription.id != id);
+        subs.len() != before
+    }
+
+    /// Credit the updates in a broadcast frame to matching subscriptions
+    pub fn record(&self, data: &Bytes, delivered: bool) {
+        if !self.enabled {
+            return;
+        }
+        let subs = self.subs.read();
+        if subs.is_empty() {
+            return;
+        }
+        let Ok(frame) = Frame::decode(data.clone()) else {
+            return;
+        };
+        let credit = |address: &str, signal_type: Option<SignalType>| {
+            for sub in subs
+                .iter()
+                .filter(|s| s.subscription.matches(address, signal_type))
+            {
+                let counter = if delivered {
+                    &sub.delivered
+                } else {
+                    &sub.dropped
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+
+        // SET and PUBLISH, nearly every broadcast, don't need a full decode
+        if let Some((address, signal_type)) = codec::peek_address(&frame.payload) {
+            credit(&address, Some(signal_type));
+            return;
+        }
+        match codec::decode_message(&frame.payload) {
+            Ok(Message::Bundle(bundle)) => {
+                for message in &bundle.messages {
+                    match message {
+                        Message::Set(set) => credit(&set.address, Some(SignalType::Param)),
+                        Message::Publish(publish) => credit(&publish.address, publish.signal),
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Message::SetMany(batch)) => {
+                for (index, _) in batch.entries() {
+                    credit(&batch.address(index), Some(SignalType::Param));
+                }
+            }
+            Ok(Message::Set(set)) => credit(&set.address, Some(SignalType::Param)),
+            Ok(Message::Publish(publish)) => credit(&publish.address, publish.signal),
+            _ => {}
+        }
+    }
+
+    /// Current counts, in subscription order
+    pub fn snapshot(&self) -> Vec<SubscriptionStats> {
+        self.subs
+            .read()
+            .iter()
+            .map(|s| SubscriptionStats {
+                id: s.subscription.id,
+                pattern: s.subscription.pattern.address().as_str().to_string(),
+                delivered: s.delivered.load(Ordering::Relaxed),
+                dropped: s.dropped.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{BundleMessage, SetMessage, SubscribeOptions, Value};
+
+    fn subscription(id: u32, pattern: &str) -> Subscription {
+        Subscription::new(
+            id,
+            "session".to_string(),
+            pattern,
+            vec![],
+            SubscribeOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn set(address: &str) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(1),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    #[test]
+    fn counts_per_matching_subscription() {
+        let stats = DeliveryStats::enabled();
+        stats.add(subscription(1, "/mixer/**"));
+        stats.add(subscription(2, "/lights/**"));
+
+        stats.record(&codec::encode(&set("/mixer/1/level")).unwrap(), true);
+        stats.record(&codec::encode(&set("/mixer/2/level")).unwrap(), false);
+        let bundle = Message::Bundle(BundleMessage {
+            timestamp: None,
+            messages: vec![set("/mixer/3/level"), set("/lights/1")],
+        });
+        stats.record(&codec::encode(&bundle).unwrap(), true);
+
+        let counts = stats.snapshot();
+        assert_eq!((counts[0].delivered, counts[0].dropped), (2, 1));
+        assert_eq!(counts[0].pattern, "/mixer/**");
+        assert_eq!((counts[1].delivered, counts[1].dropped), (1, 0));
+
+        // Resubscribing an ID starts its counts over
+        stats.add(subscription(1, "/mixer/**"));
+        assert_eq!(stats.snapshot()[1].delivered, 0);
+        assert!(stats.remove(2));
+        assert!(!stats.remove(2));
+    }
+
+    #[test]
+    fn default_counts_nothing() {
+        let stats = DeliveryStats::default();
+        stats.add(subscription(1, "/mixer/**"));
+        stats.record(&codec::encode(&set("/mixer/1/level")).unwrap(), true);
+        assert!(stats.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+//! Durable sessions that survive a reconnect
+//!
+//! A client that sends `session_resume` in HELLO (empty to start) is given a
+//! resume token in WELCOME. When its connection ends, the router keeps the
+//! session's subscriptions under that token for `durable_session_ttl`
+//! seconds instead of dropping them, much like an MQTT persistent session.
+//! Reconnecting with the token restores the subscriptions under their old
+//! IDs and replays the SETs they missed from the journal, oldest first,
+//! before the usual snapshot. Without a journal, or once the journal no
+//! longer reaches back to the disconnect, the snapshot alone brings the
+//! client up to date.
+//!
+//! Tokens are single use: every WELCOME carries a fresh one. A token only
+//! resumes a session for the subject it was issued to, and each restored
+//! subscription is checked against the new session's scopes.
+
+use clasp_core::SecurityMode;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::handlers::HandlerContext;
+use crate::session::Session;
+use crate::state::RouterState;
+use crate::subscription::{Subscription, SubscriptionManager};
+
+/// Suspended sessions kept at once; later disconnects are dropped until
+/// some expire
+pub const MAX_SUSPENDED: usize = 10_000;
+
+/// Missed SETs replayed on resume; past this the snapshot catches up
+#[cfg(feature = "journal")]
+pub const MAX_REPLAY: usize = 10_000;
+
+/// Journal entries read per page during a replay
+#[cfg(feature = "journal")]
+const REPLAY_PAGE: u32 = 1000;
+
+/// A disconnected session waiting to be resumed
+#[derive(Debug, Clone)]
+pub struct SuspendedSession {
+    /// Token subject the session authenticated as
+    pub subject: Option<String>,
+    /// Subscriptions to restore, still carrying the old session ID
+    pub subscriptions: Vec<Subscription>,
+    /// Journal sequence number at disconnect (replay starts after it)
+    pub journal_seq: Option<u64>,
+    /// When the session is forgotten
+    pub expires_at: Instant,
+}
+
+/// Suspended sessions by resume token
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, SuspendedSession>>,
+}
+
+impl SessionStore {
+    /// Keep `session` until it expires. Returns false if the store is full.
+    pub fn suspend(&self, token: String, session: SuspendedSession) -> bool {
+        let mut sessions = self.sessions.lock();
+        let now = Instant::now();
+        sessions.retain(|_, s| s.expires_at > now);
+        if sessions.len() >= MAX_SUSPENDED {
+            return false;
+        }
+        sessions.insert(token, session);
+        true
+    }
+
+    /// Remove and return the session suspended under `token`, if it hasn't
+    /// expired and was issued to `subject`
+    pub fn take(&self, token: &str, subject: Option<&str>) -> Option<SuspendedSession> {
+        let mut sessions = self.sessions.lock();
+        let session = sessions.get(token)?;
+        if session.subject.as_deref() != subject {
+            return None;
+        }
+        let session = sessions.remove(token)?;
+        (session.expires_at > Instant::now()).then_some(session)
+    }
+
+    /// Number of suspended sessions, including expired ones not yet pruned
+    pub fn len(&self) -> usize {
+        self.sessions.lock().len()
+    }
+
+    /// Whether no sessions are suspended
+    pub fn is_empty(&self) -> bool {
+        self.sessions.lock().is_empty()
+    }
+}
+
+/// Move a departing session's subscriptions into the store under its resume
+/// token. Does nothing for sessions that didn't ask to be durable. Call
+/// before the session's subscriptions are removed.
+pub(crate) async fn suspend(
+    session: &Session,
+    state: &RouterState,
+    subscriptions: &SubscriptionManager,
+    ttl: u64,
+) {
+    let Some(token) = session.resume_token().filter(|_| ttl > 0) else {
+        return;
+    };
+
+    let kept: Vec<Subscription> = session
+        .subscriptions()
+        .into_iter()
+        .filter_map(|id| subscriptions.remove(&session.id, id))
+        .collect();
+
+    #[cfg(feature = "journal")]
+    let journal_seq = match state.federation_journal() {
+        Some(journal) => journal.latest_seq().await.ok(),
+        None => None,
+    };
+    #[cfg(not(feature = "journal"))]
+    let journal_seq = None;
+
+    let count = kept.len();
+    let suspended = SuspendedSession {
+        subject: session.subject.clone(),
+        subscriptions: kept,
+        journal_seq,
+        expires_at: Instant::now() + Duration::from_secs(ttl),
+    };
+    if state
+        .durable_sessions()
+        .suspend(token.to_string(), suspended)
+    {
+        debug!(
+            "Session {} suspended with {} subscription(s) for {}s",
+            session.id, count, ttl
+        );
+    } else {
+        warn!(
+            "Session {} not suspended: {} sessions already waiting to resume",
+            session.id, MAX_SUSPENDED
+        );
+    }
+}
+
+/// Restore a suspended session's subscriptions on `session` and replay the
+/// SETs they missed. Call once the session is registered, before its
+/// snapshot is sent, so the snapshot lands after anything replayed.
+pub(crate) async fn resume(
+    session: &Arc<Session>,
+    suspended: SuspendedSession,
+    ctx: &HandlerContext<'_>,
+) {
+    let mut restored = Vec::new();
+    for subscription in suspended.subscriptions {
+        if ctx.security_mode == SecurityMode::Authenticated
+            && !session.has_strict_read_scope(subscription.pattern.address().as_str())
+        {
+            warn!(
+                "Session {} not resuming {}: insufficient scope",
+                session.id,
+                subscription.pattern.address().as_str()
+            );
+            continue;
+        }
+        restored.push(subscription.clone());
+        crate::handoff::adopt(session, subscription, ctx.subscriptions, ctx.state);
+    }
+
+    #[cfg(feature = "journal")]
+    let replayed = match suspended.journal_seq {
+        Some(since) => replay(session, &restored, since, ctx).await,
+        None => 0,
+    };
+    #[cfg(not(feature = "journal"))]
+    let replayed = 0;
+
+    info!(
+        "Session {} resumed {} subscription(s), replayed {} missed SET(s)",
+        session.id,
+        restored.len(),
+        replayed
+    );
+}
+
+/// Send the journaled SETs after `since` that match `subscriptions`, oldest
+/// first. Returns how many were sent.
+#[cfg(feature = "journal")]
+async fn replay(
+    session: &Arc<Session>,
+    subscriptions: &[Subscription],
+    since: u64,
+    ctx: &HandlerContext<'_>,
+) -> usize {
+    use clasp_core::{codec, Message, SetMessage, SignalType};
+
+    if subscriptions.is_empty() {
+        return 0;
+    }
+    let Some(journal) = ctx.state.federation_journal() else {
+        return 0;
+    };
+
+    // Entries past `since` already pruned mean missed revisions are gone;
+    // the snapshot still brings every value up to date
+    let oldest = journal
+        .since(0, Some(1))
+        .await
+        .ok()
+        .and_then(|entries| entries.first().map(|e| e.seq));
+    if oldest.is_some_and(|oldest| oldest > since + 1) {
+        debug!(
+            "Session {}: journal no longer reaches back to {}, skipping replay",
+            session.id, since
+        );
+        return 0;
+    }
+
+    let mut from = since;
+    let mut sent = 0;
+    while sent < MAX_REPLAY {
+        let page = match journal.since(from, Some(REPLAY_PAGE)).await {
+            Ok(page) if !page.is_empty() => page,
+            Ok(_) => break,
+            Err(e) => {
+                warn!(
+                    "Session {}: journal read failed during replay: {}",
+                    session.id, e
+                );
+                break;
+            }
+        };
+
+        let mut params = Vec::new();
+        for entry in page {
+            from = entry.seq;
+            let Some(revision) = entry.revision else {
+                continue;
+            };
+            if entry.msg_type != codec::msg::SET
+                || !subscriptions
+                    .iter()
+                    .any(|s| s.matches(&entry.address, Some(SignalType::Param)))
+            {
+                continue;
+            }
+            params.push(clasp_core::ParamValue {
+                address: entry.address,
+                value: entry.value,
+                revision,
+                writer: Some(entry.author),
+                timestamp: Some(entry.timestamp),
+            });
+        }
+        if let Some(ref filter) = ctx.snapshot_filter {
+            params = filter.filter_snapshot(params, session, ctx.state);
+        }
+
+        for param in params.into_iter().take(MAX_REPLAY - sent) {
+            let msg = Message::Set(SetMessage {
+                address: param.address,
+                value: param.value,
+                revision: Some(param.revision),
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            });
+            if let Ok(bytes) = codec::encode(&msg) {
+                let _ = ctx.sender.send(bytes).await;
+                sent += 1;
+            }
+        }
+    }
+    sent
+}
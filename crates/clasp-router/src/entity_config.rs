@@ -0,0 +1,214 @@
+//! Per-entity configuration distribution
+//!
+//! With a [`ConfigSource`] installed
+//! ([`Router::set_config_source`](crate::Router::set_config_source)), a
+//! session authenticated as a registry entity (see
+//! [`Session::entity`](crate::Session::entity)) receives its configuration
+//! document at `/clasp/config/{entity_id}` right after its snapshot:
+//!
+//! - `version`: increases with every update
+//! - `config`: the document itself
+//!
+//! The entity confirms it applied a version by SETting that version number
+//! to `/clasp/config/{entity_id}/ack`. The router records the
+//! acknowledgement with the source and keeps the latest acknowledged version
+//! at that address, so operators subscribed to `/clasp/config/**` see a
+//! rollout land device by device. Documents updated while the entity is
+//! connected are pushed with [`publish`].
+//!
+//! Only the router writes under [`CONFIG_PREFIX`]. An entity may acknowledge
+//! its own configuration without any write scope, and nobody else's.
+
+use clasp_core::{codec, Message, SetMessage, SignalType, Value};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::handlers::broadcast_to_subscriber_list;
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Configuration documents live under this prefix, one per entity
+pub const CONFIG_PREFIX: &str = "/clasp/config/";
+
+/// Writer recorded on configuration documents and acknowledgements
+const WRITER: &str = "config";
+
+/// A versioned configuration document
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityConfig {
+    pub version: u64,
+    pub config: Value,
+}
+
+impl EntityConfig {
+    /// Value published at the entity's configuration address
+    pub fn entry(&self) -> Value {
+        let mut map = HashMap::new();
+        map.insert("version".to_string(), Value::Int(self.version as i64));
+        map.insert("config".to_string(), self.config.clone());
+        Value::Map(map)
+    }
+}
+
+/// Where the router looks up configuration documents, e.g. the entity
+/// registry
+#[async_trait::async_trait]
+pub trait ConfigSource: Send + Sync {
+    /// Current configuration of an entity, if it has one
+    async fn config(&self, entity_id: &str) -> Option<EntityConfig>;
+
+    /// Record that the entity applied `version`. Returns false if `version`
+    /// is not the entity's current version.
+    async fn acknowledge(&self, entity_id: &str, version: u64) -> bool;
+}
+
+/// Configuration address of an entity
+pub fn address(entity_id: &str) -> String {
+    format!("{}{}", CONFIG_PREFIX, entity_id)
+}
+
+/// Acknowledgement address of an entity
+pub fn ack_address(entity_id: &str) -> String {
+    format!("{}{}/ack", CONFIG_PREFIX, entity_id)
+}
+
+/// Whether `address` is under [`CONFIG_PREFIX`]
+pub fn is_config(address: &str) -> bool {
+    address.starts_with(CONFIG_PREFIX)
+}
+
+/// Entity whose acknowledgement address `address` is
+pub fn acked_entity(address: &str) -> Option<&str> {
+    address
+        .strip_prefix(CONFIG_PREFIX)?
+        .strip_suffix("/ack")
+        .filter(|id| !id.is_empty())
+}
+
+/// Publish an entity's configuration to its connected sessions and to
+/// subscribers of its configuration address
+pub fn publish(
+    entity_id: &str,
+    config: &EntityConfig,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let mut targets = subscriptions.find_subscribers(&address(entity_id), Some(SignalType::Param));
+    for session in sessions.iter() {
+        let own = session
+            .entity
+            .as_ref()
+            .is_some_and(|entity| entity.id == entity_id);
+        if own && !targets.contains(session.key()) {
+            targets.push(session.key().clone());
+        }
+    }
+    set(
+        &address(entity_id),
+        config.entry(),
+        &targets,
+        state,
+        sessions,
+    );
+    debug!(
+        "Published config v{} for {} to {} session(s)",
+        config.version,
+        entity_id,
+        targets.len()
+    );
+}
+
+/// Keep an acknowledged version at the entity's acknowledgement address.
+/// Returns the new revision.
+pub(crate) fn record_ack(
+    entity_id: &str,
+    version: u64,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) -> Option<u64> {
+    let address = ack_address(entity_id);
+    let targets = subscriptions.find_subscribers(&address, Some(SignalType::Param));
+    set(
+        &address,
+        Value::Int(version as i64),
+        &targets,
+        state,
+        sessions,
+    )
+}
+
+/// Send a connecting entity its configuration, if it has one
+pub(crate) async fn deliver(
+    session: &Session,
+    source: &dyn ConfigSource,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let Some(ref entity) = session.entity else {
+        return;
+    };
+    if let Some(config) = source.config(&entity.id).await {
+        publish(&entity.id, &config, state, sessions, subscriptions);
+    }
+}
+
+fn set(
+    address: &str,
+    value: Value,
+    targets: &[SessionId],
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+) -> Option<u64> {
+    let revision = match state.set(
+        address,
+        value.clone(),
+        &WRITER.to_string(),
+        None,
+        false,
+        false,
+        None,
+    ) {
+        Ok(revision) => revision,
+        Err(e) => {
+            warn!("Config SET to {} failed: {:?}", address, e);
+            return None;
+        }
+    };
+
+    let msg = Message::Set(SetMessage {
+        address: address.to_string(),
+        value,
+        revision: Some(revision),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    if let Ok(bytes) = codec::encode(&msg) {
+        broadcast_to_subscriber_list(&bytes, targets, sessions, None);
+    }
+    Some(revision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acked_entity() {
+        assert_eq!(
+            acked_entity("/clasp/config/clasp:abc/ack"),
+            Some("clasp:abc")
+        );
+        assert_eq!(acked_entity("/clasp/config/clasp:abc"), None);
+        assert_eq!(acked_entity("/clasp/config//ack"), None);
+        assert_eq!(acked_entity("/other/clasp:abc/ack"), None);
+    }
+}
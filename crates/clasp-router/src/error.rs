@@ -43,6 +43,9 @@ pub enum RouterError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("cluster backplane error: {0}")]
+    Backplane(String),
+
     #[error("router error: {0}")]
     Other(String),
 
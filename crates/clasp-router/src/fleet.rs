@@ -0,0 +1,91 @@
+//! Fleet fan-out
+//!
+//! A fleet is a named group of registry entities, such as every kiosk in
+//! building B. With a [`FleetSource`] installed
+//! ([`Router::set_fleet_source`](crate::Router::set_fleet_source)), a
+//! PUBLISH to `/clasp/fleet/{name}/...` reaches every connected session
+//! authenticated as a member entity (see
+//! [`Session::entity`](crate::Session::entity)), whether or not it
+//! subscribed, as well as the address's ordinary subscribers.
+//!
+//! Members are looked up on every fleet PUBLISH, so membership changes apply
+//! to the next message. The publisher still needs write scope for the
+//! address; members receive it without read scope, the same way they receive
+//! their configuration.
+
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::session::{Session, SessionId};
+
+/// Fleet commands are published under this prefix
+pub const FLEET_PREFIX: &str = "/clasp/fleet/";
+
+/// Where the router looks up fleet membership, e.g. the entity registry
+#[async_trait::async_trait]
+pub trait FleetSource: Send + Sync {
+    /// IDs of the entities in a fleet (empty for an unknown fleet)
+    async fn members(&self, fleet: &str) -> Vec<String>;
+}
+
+/// Fleet an address under [`FLEET_PREFIX`] targets
+pub fn fleet_name(address: &str) -> Option<&str> {
+    address
+        .strip_prefix(FLEET_PREFIX)?
+        .split('/')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// Add the sessions of `address`'s fleet members to `targets`. Returns how
+/// many were added.
+pub(crate) async fn add_members(
+    address: &str,
+    source: &dyn FleetSource,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    targets: &mut Vec<SessionId>,
+) -> usize {
+    let Some(fleet) = fleet_name(address) else {
+        return 0;
+    };
+    let members: HashSet<String> = source.members(fleet).await.into_iter().collect();
+    if members.is_empty() {
+        return 0;
+    }
+
+    let mut seen: HashSet<SessionId> = targets.iter().cloned().collect();
+    let before = targets.len();
+    for session in sessions.iter() {
+        let member = session
+            .entity
+            .as_ref()
+            .is_some_and(|entity| members.contains(&entity.id));
+        if member && seen.insert(session.key().clone()) {
+            targets.push(session.key().clone());
+        }
+    }
+    let added = targets.len() - before;
+    debug!(
+        "Fleet {}: {} member(s), {} extra session(s) for {}",
+        fleet,
+        members.len(),
+        added,
+        address
+    );
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fleet_name() {
+        assert_eq!(fleet_name("/clasp/fleet/kiosks-b/cue"), Some("kiosks-b"));
+        assert_eq!(fleet_name("/clasp/fleet/kiosks-b"), Some("kiosks-b"));
+        assert_eq!(fleet_name("/clasp/fleet//cue"), None);
+        assert_eq!(fleet_name("/clasp/fleets/kiosks-b/cue"), None);
+    }
+}
@@ -6,6 +6,10 @@
 //! - Immediately forwards Start, End, and Cancel phases
 //! - Flushes buffered Moves when a non-Move phase arrives or after a timeout
 //!
+//! With a journal, every gesture frame is recorded before coalescing, so a
+//! timed REPLAY plays back the full move sequence ([`journal_value`],
+//! [`from_journal`]).
+//!
 //! # Example
 //!
 //! ```ignore
@@ -19,6 +23,8 @@
 //! registry.process(gesture_end);  // -> Some([buffered_move, end])
 //! ```
 
+#[cfg(feature = "journal")]
+use clasp_core::Value;
 use clasp_core::{GesturePhase, PublishMessage, SignalType};
 use dashmap::DashMap;
 use std::sync::Arc;
@@ -208,6 +214,61 @@ pub fn spawn_flush_task(
     })
 }
 
+/// Journal value recording a gesture frame: `{"id", "phase", "payload"}`
+#[cfg(feature = "journal")]
+pub fn journal_value(msg: &PublishMessage) -> Value {
+    let mut map = std::collections::HashMap::new();
+    if let Some(id) = msg.id {
+        map.insert("id".to_string(), Value::Int(id as i64));
+    }
+    if let Some(phase) = msg.phase {
+        let phase = match phase {
+            GesturePhase::Start => "start",
+            GesturePhase::Move => "move",
+            GesturePhase::End => "end",
+            GesturePhase::Cancel => "cancel",
+        };
+        map.insert("phase".to_string(), Value::String(phase.to_string()));
+    }
+    if let Some(payload) = msg.payload.as_ref().or(msg.value.as_ref()) {
+        map.insert("payload".to_string(), payload.clone());
+    }
+    Value::Map(map)
+}
+
+/// Rebuild a gesture frame from its [`journal_value`]. Values journaled
+/// before gestures were recorded whole come back as a bare payload.
+#[cfg(feature = "journal")]
+pub fn from_journal(address: String, value: Value, timestamp: u64) -> PublishMessage {
+    let mut msg = PublishMessage {
+        address,
+        signal: Some(SignalType::Gesture),
+        value: None,
+        payload: None,
+        samples: None,
+        rate: None,
+        id: None,
+        phase: None,
+        timestamp: Some(timestamp),
+        timeline: None,
+    };
+    match value {
+        Value::Map(mut map) if map.contains_key("phase") => {
+            msg.id = map.get("id").and_then(Value::as_i64).map(|id| id as u32);
+            msg.phase = match map.get("phase").and_then(Value::as_str) {
+                Some("start") => Some(GesturePhase::Start),
+                Some("move") => Some(GesturePhase::Move),
+                Some("end") => Some(GesturePhase::End),
+                Some("cancel") => Some(GesturePhase::Cancel),
+                _ => None,
+            };
+            msg.payload = map.remove("payload");
+        }
+        other => msg.payload = Some(other),
+    }
+    msg
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -685,4 +746,23 @@ mod tests {
             _ => panic!("Expected Forward"),
         }
     }
+
+    #[cfg(feature = "journal")]
+    #[test]
+    fn test_journal_value_roundtrip() {
+        let mut point = HashMap::new();
+        point.insert("x".to_string(), Value::Float(0.25));
+        let msg = make_gesture_with_payload("/touch", 7, GesturePhase::Move, Value::Map(point));
+
+        let replayed = from_journal("/touch".to_string(), journal_value(&msg), 42);
+        assert_eq!(replayed.id, Some(7));
+        assert_eq!(replayed.phase, Some(GesturePhase::Move));
+        assert_eq!(replayed.payload, msg.payload);
+        assert_eq!(replayed.timestamp, Some(42));
+
+        // Bare values from older journals keep their payload
+        let bare = from_journal("/touch".to_string(), Value::Float(1.0), 42);
+        assert_eq!(bare.phase, None);
+        assert_eq!(bare.payload, Some(Value::Float(1.0)));
+    }
 }
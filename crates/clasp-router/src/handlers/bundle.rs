@@ -2,12 +2,17 @@
 //!
 //! Validates all inner SET/PUBLISH messages before applying any (two-phase
 //! commit). If any message fails scope or write validation, the entire
-//! bundle is rejected.
+//! bundle is rejected. The SETs are then applied as one state transaction:
+//! if any is refused (stale revision, lock, conflict strategy, capacity),
+//! the ones before it are rolled back and the bundle fails with that SET's
+//! error. The ACK lists each SET's new revision in bundle order.
 
+use clasp_core::error::ErrorCode;
 use clasp_core::{
-    codec, AckMessage, Action, ErrorMessage, Message, SecurityMode, SetMessage, SignalType,
+    codec, AckMessage, Action, BundleResult, ErrorMessage, Message, SecurityMode, SetMessage,
+    SignalType,
 };
-use tracing::{debug, error, warn};
+use tracing::{debug, warn};
 
 use super::{broadcast_to_subscriber_list, HandlerContext, MessageResult};
 
@@ -17,13 +22,76 @@ pub(crate) async fn handle(
 ) -> Option<MessageResult> {
     let session = ctx.session.as_ref()?;
 
+    // Maintenance mode rejects the whole bundle if any write is blocked
+    let blocked = bundle.messages.iter().find_map(|inner| {
+        let address = match inner {
+            Message::Set(set) => &set.address,
+            Message::Publish(publish) => &publish.address,
+            _ => return None,
+        };
+        ctx.state.maintenance().blocks(address).then_some(address)
+    });
+    if let Some(address) = blocked {
+        let err_bytes =
+            codec::encode(&ctx.correlate(ctx.state.maintenance().error(address))).ok()?;
+        return Some(MessageResult::Send(err_bytes));
+    }
+
+    // Shadow deltas, presence and entity configuration are router-maintained;
+    // a bundle can't write them (acknowledgements go through a plain SET)
+    let owned = bundle.messages.iter().find_map(|inner| match inner {
+        Message::Set(set) => {
+            super::router_owned(ctx, &set.address).map(|reason| (&set.address, reason))
+        }
+        _ => None,
+    });
+    if let Some((address, reason)) = owned {
+        let err = Message::Error(ErrorMessage {
+            code: ErrorCode::WriteRejected as u16,
+            message: format!("Bundle rejected: {}", reason),
+            address: Some(address.clone()),
+            correlation_id: ctx.correlation_id,
+        });
+        let err_bytes = codec::encode(&err).ok()?;
+        return Some(MessageResult::Send(err_bytes));
+    }
+
+    // Writes to the reserved /clasp/ namespace need admin scope
+    let reserved = bundle.messages.iter().find_map(|inner| match inner {
+        Message::Set(set) => super::reserved_denied(ctx, session, &set.address, "bundled SET"),
+        Message::Publish(publish) => {
+            super::reserved_denied(ctx, session, &publish.address, "bundled PUBLISH")
+        }
+        _ => None,
+    });
+    if let Some(error) = reserved {
+        let err_bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(err_bytes));
+    }
+
     // PHASE 1: Validate ALL messages first (atomic validation)
-    let mut validated_sets: Vec<&SetMessage> = Vec::new();
+    let mut validated_sets: Vec<SetMessage> = Vec::new();
     let mut validated_pubs: Vec<&clasp_core::PublishMessage> = Vec::new();
 
     for inner_msg in &bundle.messages {
         match inner_msg {
             Message::Set(set) => {
+                // Branch writes aren't part of live state, so they can't join
+                // a live transaction
+                if set.branch.is_some() {
+                    let err = Message::Error(ErrorMessage {
+                        code: ErrorCode::InvalidRequest as u16,
+                        message: format!(
+                            "Bundle rejected: SET to {} targets a branch",
+                            set.address
+                        ),
+                        address: Some(set.address.clone()),
+                        correlation_id: ctx.correlation_id,
+                    });
+                    let err_bytes = codec::encode(&err).ok()?;
+                    return Some(MessageResult::Send(err_bytes));
+                }
+
                 if ctx.security_mode == SecurityMode::Authenticated
                     && !session.has_scope(Action::Write, &set.address)
                 {
@@ -32,13 +100,13 @@ pub(crate) async fn handle(
                         session.id, set.address
                     );
                     let err = Message::Error(ErrorMessage {
-                        code: 403,
+                        code: ErrorCode::WriteRejected as u16,
                         message: format!(
                             "Bundle rejected: insufficient scope for SET to {}",
                             set.address
                         ),
                         address: Some(set.address.clone()),
-                        correlation_id: None,
+                        correlation_id: ctx.correlation_id,
                     });
                     let err_bytes = codec::encode(&err).ok()?;
                     return Some(MessageResult::Send(err_bytes));
@@ -53,17 +121,44 @@ pub(crate) async fn handle(
                             session.id, set.address, reason
                         );
                         let err = Message::Error(ErrorMessage {
-                            code: 403,
+                            code: ErrorCode::WriteRejected as u16,
                             message: format!("Bundle rejected: {}", reason),
                             address: Some(set.address.clone()),
-                            correlation_id: None,
+                            correlation_id: ctx.correlation_id,
                         });
                         let err_bytes = codec::encode(&err).ok()?;
                         return Some(MessageResult::Send(err_bytes));
                     }
                 }
 
-                validated_sets.push(set);
+                let value = match crate::schema::enforce(
+                    ctx.state,
+                    &set.address,
+                    &set.value,
+                    ctx.config.schema,
+                ) {
+                    Ok(clamped) => clamped.unwrap_or_else(|| set.value.clone()),
+                    Err(violation) => {
+                        warn!(
+                            "Session {} bundled SET to {} violates its schema - rejecting entire bundle: {}",
+                            session.id, set.address, violation
+                        );
+                        let err = Message::Error(ErrorMessage {
+                            code: ErrorCode::InvalidValue as u16,
+                            message: format!("Bundle rejected: {}", violation),
+                            address: Some(set.address.clone()),
+                            correlation_id: ctx.correlation_id,
+                        });
+                        let err_bytes = codec::encode(&err).ok()?;
+                        return Some(MessageResult::Send(err_bytes));
+                    }
+                };
+
+                validated_sets.push(SetMessage {
+                    value,
+                    trace: false,
+                    ..set.clone()
+                });
             }
             Message::Publish(pub_msg) => {
                 if ctx.security_mode == SecurityMode::Authenticated
@@ -74,13 +169,13 @@ pub(crate) async fn handle(
                         session.id, pub_msg.address
                     );
                     let err = Message::Error(ErrorMessage {
-                        code: 403,
+                        code: ErrorCode::WriteRejected as u16,
                         message: format!(
                             "Bundle rejected: insufficient scope for PUBLISH to {}",
                             pub_msg.address
                         ),
                         address: Some(pub_msg.address.clone()),
-                        correlation_id: None,
+                        correlation_id: ctx.correlation_id,
                     });
                     let err_bytes = codec::encode(&err).ok()?;
                     return Some(MessageResult::Send(err_bytes));
@@ -100,10 +195,10 @@ pub(crate) async fn handle(
                             session.id, pub_msg.address, reason
                         );
                         let err = Message::Error(ErrorMessage {
-                            code: 403,
+                            code: ErrorCode::WriteRejected as u16,
                             message: format!("Bundle rejected: {}", reason),
                             address: Some(pub_msg.address.clone()),
-                            correlation_id: None,
+                            correlation_id: ctx.correlation_id,
                         });
                         let err_bytes = codec::encode(&err).ok()?;
                         return Some(MessageResult::Send(err_bytes));
@@ -118,30 +213,52 @@ pub(crate) async fn handle(
         }
     }
 
-    // PHASE 2: Apply all validated changes atomically
-    let mut applied_revisions: Vec<(String, u64)> = Vec::new();
-
-    for set in &validated_sets {
-        match ctx.state.apply_set(set, &session.id) {
-            Ok(revision) => {
-                applied_revisions.push((set.address.clone(), revision));
+    // PHASE 2: Apply all SETs as one transaction; any rejection rolls back all
+    let sets = validated_sets;
+    let revisions = match ctx.state.apply_all(&sets, &session.id) {
+        Ok(revisions) => revisions,
+        Err((index, e)) => {
+            let address = &sets[index].address;
+            warn!(
+                "Session {} bundle rolled back - SET to {} failed: {}",
+                session.id, address, e
+            );
+            let code = e.error_code();
+            #[cfg(feature = "metrics")]
+            metrics::counter!("clasp_errors_total", "code" => code.as_u16().to_string())
+                .increment(1);
+            let err = Message::Error(ErrorMessage {
+                code: code.as_u16(),
+                message: format!("Bundle rolled back: SET to {} failed: {}", address, e),
+                address: Some(address.clone()),
+                correlation_id: ctx.correlation_id,
+            });
+            let err_bytes = codec::encode(&err).ok()?;
+            return Some(MessageResult::Send(err_bytes));
+        }
+    };
 
-                let subscribers = ctx
-                    .subscriptions
-                    .find_subscribers(&set.address, Some(SignalType::Param));
+    for (set, revision) in sets.iter().zip(&revisions) {
+        let subscribers = ctx
+            .subscriptions
+            .find_subscribers(&set.address, Some(SignalType::Param));
 
-                let mut updated_set: SetMessage = (*set).clone();
-                updated_set.revision = Some(revision);
-                let broadcast_msg = Message::Set(updated_set);
+        let mut updated_set = set.clone();
+        updated_set.revision = Some(*revision);
+        let broadcast_msg = Message::Set(updated_set);
 
-                if let Ok(bytes) = codec::encode(&broadcast_msg) {
-                    broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None);
-                }
-            }
-            Err(e) => {
-                error!("Bundle SET apply failed after validation: {}", e);
-            }
+        if let Ok(bytes) = codec::encode(&broadcast_msg) {
+            broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None);
         }
+        ctx.state.cluster_forward_set(set, *revision, &session.id);
+
+        ctx.state.shadows().after_set(
+            &set.address,
+            &ctx.config.shadow,
+            ctx.state,
+            ctx.sessions,
+            ctx.subscriptions,
+        );
     }
 
     for pub_msg in &validated_pubs {
@@ -153,14 +270,35 @@ pub(crate) async fn handle(
         if let Ok(bytes) = codec::encode(&inner_msg) {
             broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, Some(&session.id));
         }
+        ctx.state.cluster_forward_publish(pub_msg, &session.id);
     }
 
+    // One result per SET and PUBLISH, in bundle order
+    let mut set_revisions = revisions.iter();
+    let results = bundle
+        .messages
+        .iter()
+        .filter_map(|inner| match inner {
+            Message::Set(set) => Some(BundleResult {
+                address: set.address.clone(),
+                revision: set_revisions.next().copied(),
+            }),
+            Message::Publish(publish) => Some(BundleResult {
+                address: publish.address.clone(),
+                revision: None,
+            }),
+            _ => None,
+        })
+        .collect();
+
     let ack = Message::Ack(AckMessage {
         address: None,
-        revision: applied_revisions.last().map(|(_, r)| *r),
+        revision: revisions.last().copied(),
         locked: None,
         holder: None,
-        correlation_id: None,
+        correlation_id: ctx.correlation_id,
+        results,
+        cursor: None,
     });
     let ack_bytes = codec::encode(&ack).ok()?;
     Some(MessageResult::Send(ack_bytes))
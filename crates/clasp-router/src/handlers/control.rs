@@ -1,16 +1,45 @@
-//! Control message handlers -- PING, QUERY, REPLAY, ANNOUNCE, SYNC.
+//! Control message handlers -- ALIAS, PING, QUERY, REPLAY, ANNOUNCE, SYNC.
 //!
-//! Lightweight handlers for protocol housekeeping: heartbeat, signal discovery,
-//! journal replay, signal announcement, and clock synchronization.
+//! Lightweight handlers for protocol housekeeping: topic alias binding,
+//! heartbeat, signal discovery, journal replay, signal announcement, and clock
+//! synchronization.
 
+#[cfg(feature = "journal")]
+use bytes::Bytes;
+use clasp_core::error::ErrorCode;
 use clasp_core::{codec, AckMessage, Message};
+use clasp_core::{AliasMessage, ErrorMessage};
+#[cfg(feature = "journal")]
+use clasp_core::{PublishMessage, SecurityMode, SetMessage, SignalType};
+use clasp_core::{QueryMessage, ResultMessage, SignalDefinition, SignalField};
 #[cfg(feature = "journal")]
-use clasp_core::{ErrorMessage, PublishMessage, SecurityMode, SetMessage};
-use tracing::debug;
+use clasp_transport::TransportSender;
 #[cfg(feature = "journal")]
-use tracing::warn;
+use std::sync::Arc;
+use tracing::{debug, warn};
 
 use super::{HandlerContext, MessageResult};
+#[cfg(feature = "journal")]
+use crate::replay::{paginate, ReplayCursor};
+
+pub(crate) async fn handle_alias(
+    alias: &AliasMessage,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let session = ctx.session.as_ref()?;
+    if let Err(e) = session.aliases().bind(alias) {
+        debug!("Rejected alias {} from {}: {}", alias.alias, session.id, e);
+        let error = Message::Error(ErrorMessage {
+            code: ErrorCode::InvalidAddress as u16,
+            message: e.to_string(),
+            address: Some(alias.address.clone()).filter(|a| !a.is_empty()),
+            correlation_id: ctx.correlation_id,
+        });
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+    Some(MessageResult::None)
+}
 
 pub(crate) async fn handle_ping(_ctx: &HandlerContext<'_>) -> Option<MessageResult> {
     let pong = Message::Pong;
@@ -18,14 +47,76 @@ pub(crate) async fn handle_ping(_ctx: &HandlerContext<'_>) -> Option<MessageResu
     Some(MessageResult::Send(bytes))
 }
 
+/// Most signals in one RESULT when a QUERY asks for a page
+const MAX_QUERY_PAGE: usize = 1000;
+
+/// Answer a QUERY with a page of matching signals.
+///
+/// Without a limit the page holds as many signals as the RESULT count
+/// field allows. Either way, a page that doesn't fit in a frame is halved
+/// until it does, and `next_cursor` points past what was sent.
 pub(crate) async fn handle_query(
-    query: &clasp_core::QueryMessage,
+    query: &QueryMessage,
     ctx: &HandlerContext<'_>,
 ) -> Option<MessageResult> {
-    let signals = ctx.state.query_signals(&query.pattern);
-    let result = Message::Result(clasp_core::ResultMessage { signals });
-    let bytes = codec::encode(&result).ok()?;
-    Some(MessageResult::Send(bytes))
+    let limit = match query.limit {
+        Some(limit) if limit > 0 => (limit as usize).min(MAX_QUERY_PAGE),
+        _ => u16::MAX as usize,
+    };
+    let page = ctx
+        .state
+        .query_signals_page(&query.pattern, query.cursor.as_deref(), limit);
+    let paged = query.limit.is_some() || query.cursor.is_some();
+
+    let mut more = page.more;
+    let mut signals: Vec<SignalDefinition> = page
+        .signals
+        .into_iter()
+        .map(|mut signal| {
+            if !query.wants(SignalField::Datatype) {
+                signal.datatype = None;
+            }
+            if !query.wants(SignalField::Access) {
+                signal.access = None;
+            }
+            if !query.wants(SignalField::Meta) {
+                signal.meta = None;
+            }
+            signal
+        })
+        .collect();
+
+    loop {
+        let result = Message::Result(ResultMessage {
+            next_cursor: more
+                .then(|| signals.last().map(|s| s.address.clone()))
+                .flatten(),
+            total: (paged || more).then_some(page.total as u32),
+            signals,
+        });
+        match codec::encode(&result) {
+            Ok(bytes) => return Some(MessageResult::Send(bytes)),
+            Err(clasp_core::Error::PayloadTooLarge(_)) => {
+                let Message::Result(result) = result else {
+                    unreachable!()
+                };
+                signals = result.signals;
+                if signals.len() <= 1 {
+                    warn!(
+                        "QUERY {}: a single signal exceeds the frame size",
+                        query.pattern
+                    );
+                    return None;
+                }
+                signals.truncate(signals.len() / 2);
+                more = true;
+            }
+            Err(e) => {
+                warn!("Failed to encode RESULT for {}: {}", query.pattern, e);
+                return None;
+            }
+        }
+    }
 }
 
 #[cfg(feature = "journal")]
@@ -43,28 +134,53 @@ pub(crate) async fn handle_replay(
             session.id, replay.pattern
         );
         let error = Message::Error(ErrorMessage {
-            code: 301,
+            code: ErrorCode::Forbidden as u16,
             message: "Insufficient scope for replay".to_string(),
             address: Some(replay.pattern.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
     }
 
-    if let Some(journal) = ctx.state.journal() {
-        match journal
-            .query(
-                &replay.pattern,
-                replay.from,
-                replay.to,
-                replay.limit,
-                &replay.types,
-            )
-            .await
-        {
+    let after = match replay.cursor.as_deref() {
+        Some(cursor) => match ReplayCursor::parse(cursor) {
+            Some(after) => Some(after),
+            None => {
+                let error = Message::Error(ErrorMessage {
+                    code: ErrorCode::InvalidRequest as u16,
+                    message: format!("Invalid replay cursor {}", cursor),
+                    address: Some(replay.pattern.clone()),
+                    correlation_id: ctx.correlation_id,
+                });
+                let bytes = codec::encode(&error).ok()?;
+                return Some(MessageResult::Send(bytes));
+            }
+        },
+        None => None,
+    };
+    let page_size = ctx.config.replay.page_limit(replay.limit);
+    let from = match after {
+        Some(after) => Some(after.from(replay.from)),
+        None => replay.from,
+    };
+    // One entry past the page shows whether another page follows
+    let query_limit = page_size.map(|size| {
+        size.saturating_add(after.map_or(0, |after| after.skip))
+            .saturating_add(1)
+    });
+
+    if let Some(result) = ctx
+        .state
+        .query_journal(&replay.pattern, from, replay.to, query_limit, &replay.types)
+        .await
+    {
+        match result {
             Ok(entries) => {
+                let (entries, next) = paginate(entries, after, page_size);
+                let mut frames = Vec::with_capacity(entries.len());
                 for entry in entries {
+                    let timestamp = entry.timestamp;
                     let msg = if entry.msg_type == 0x21 {
                         Message::Set(SetMessage {
                             address: entry.address,
@@ -73,7 +189,15 @@ pub(crate) async fn handle_replay(
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         })
+                    } else if entry.signal_type == SignalType::Gesture {
+                        Message::Publish(crate::gesture::from_journal(
+                            entry.address,
+                            entry.value,
+                            timestamp,
+                        ))
                     } else {
                         Message::Publish(PublishMessage {
                             address: entry.address,
@@ -89,16 +213,47 @@ pub(crate) async fn handle_replay(
                         })
                     };
                     if let Ok(bytes) = codec::encode(&msg) {
+                        frames.push((timestamp, bytes));
+                    }
+                }
+
+                // The ACK ends the page, after its last entry
+                let ack = Message::Ack(AckMessage {
+                    address: Some(replay.pattern.clone()),
+                    revision: None,
+                    locked: None,
+                    holder: None,
+                    correlation_id: ctx.correlation_id,
+                    results: Vec::new(),
+                    cursor: next.map(|next| next.to_string()),
+                });
+                let ack = codec::encode(&ack).ok();
+
+                let rate = ctx.config.replay.max_rate;
+                if replay.timed || rate > 0 {
+                    tokio::spawn(play(
+                        Arc::clone(ctx.sender),
+                        Arc::clone(session),
+                        frames,
+                        ack,
+                        replay.timed,
+                        rate,
+                    ));
+                } else {
+                    for (_, bytes) in frames {
                         let _ = ctx.sender.send(bytes).await;
                     }
+                    if let Some(ack) = ack {
+                        let _ = ctx.sender.send(ack).await;
+                    }
                 }
             }
             Err(e) => {
                 let error = Message::Error(ErrorMessage {
-                    code: 500,
+                    code: ErrorCode::InternalError as u16,
                     message: format!("Journal query failed: {}", e),
                     address: Some(replay.pattern.clone()),
-                    correlation_id: None,
+                    correlation_id: ctx.correlation_id,
                 });
                 let bytes = codec::encode(&error).ok()?;
                 return Some(MessageResult::Send(bytes));
@@ -106,10 +261,10 @@ pub(crate) async fn handle_replay(
         }
     } else {
         let error = Message::Error(ErrorMessage {
-            code: 501,
+            code: ErrorCode::ServiceUnavailable as u16,
             message: "Journal not configured on this router".to_string(),
             address: Some(replay.pattern.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
@@ -118,11 +273,48 @@ pub(crate) async fn handle_replay(
     Some(MessageResult::None)
 }
 
+/// Send replayed frames and the closing ACK, spaced as they were recorded
+/// when `timed` and no faster than the session's replay rate, stopping if
+/// the session goes away
+#[cfg(feature = "journal")]
+async fn play(
+    sender: Arc<dyn TransportSender>,
+    session: Arc<crate::session::Session>,
+    frames: Vec<(u64, Bytes)>,
+    ack: Option<Bytes>,
+    timed: bool,
+    rate: u32,
+) {
+    let first = frames.first().map_or(0, |&(timestamp, _)| timestamp);
+    let start = tokio::time::Instant::now();
+    for (timestamp, bytes) in frames {
+        if timed {
+            let offset = std::time::Duration::from_micros(timestamp.saturating_sub(first));
+            tokio::time::sleep_until(start + offset).await;
+        }
+        session.replay_pacer().pace(rate).await;
+        if sender.send(bytes).await.is_err() {
+            return;
+        }
+    }
+    if let Some(ack) = ack {
+        let _ = sender.send(ack).await;
+    }
+}
+
 pub(crate) async fn handle_announce(
     announce: &clasp_core::AnnounceMessage,
     ctx: &HandlerContext<'_>,
 ) -> Option<MessageResult> {
     ctx.state.register_signals(announce.signals.clone());
+    if ctx.config.schema != crate::schema::SchemaMode::Off {
+        crate::schema::publish(
+            &announce.signals,
+            ctx.state,
+            ctx.sessions,
+            ctx.subscriptions,
+        );
+    }
     debug!(
         "Registered {} signals in namespace {}",
         announce.signals.len(),
@@ -133,7 +325,9 @@ pub(crate) async fn handle_announce(
         revision: None,
         locked: None,
         holder: None,
-        correlation_id: None,
+        correlation_id: ctx.correlation_id,
+        results: Vec::new(),
+        cursor: None,
     });
     let bytes = codec::encode(&ack).ok()?;
     Some(MessageResult::Send(bytes))
@@ -1,14 +1,26 @@
 //! FederationSync message handler -- inter-router state synchronization.
 //!
-//! Handles namespace declaration, full/delta sync requests, and revision vector
-//! exchange between federated CLASP routers. Only sessions with the `federation`
-//! feature flag may use these operations.
+//! Handles namespace declaration, full/delta sync requests, journal streaming
+//! sync, and revision vector exchange between federated CLASP routers. Only
+//! sessions with the `federation` feature flag may use these operations.
+//!
+//! Declarations whose origin carries hop metadata ([`GossipOrigin`]) are mesh
+//! gossip: the router forwards matching traffic to the peer with the shortest
+//! path to the owner and relays the declaration to its other federation peers
+//! until the TTL runs out.
 
-use clasp_core::{codec, AckMessage, Action, ErrorMessage, Message, SecurityMode, SnapshotMessage};
+use clasp_core::error::ErrorCode;
+use clasp_core::{
+    codec, AckMessage, Action, ErrorMessage, GossipOrigin, Message, SecurityMode, SnapshotMessage,
+};
+use dashmap::DashMap;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 
 use super::{send_chunked_snapshot, HandlerContext, MessageResult};
+use crate::session::{Session, SessionId};
+use crate::subscription::SubscriptionManager;
 
 /// Resource limits for federation operations.
 /// See pentest FED-02: Pattern Count Exhaustion
@@ -16,6 +28,18 @@ const MAX_FEDERATION_PATTERNS: usize = 1000;
 /// See pentest FED-03: Revision Vector Exhaustion
 const MAX_REVISION_ENTRIES: usize = 10_000;
 
+/// Subscriptions for gossiped routes use IDs from here up
+/// (direct namespace declarations use 50000..).
+const ROUTE_SUBSCRIPTION_BASE: u32 = 60000;
+
+/// Journal entries read per page of a journal sync; each page ends with a
+/// checkpoint
+#[cfg(feature = "journal")]
+const JOURNAL_SYNC_PAGE: u32 = 1000;
+/// How long a journal sync waits for in-flight appends before taking its cut
+#[cfg(feature = "journal")]
+const JOURNAL_SETTLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
 pub(crate) async fn handle(
     fed_msg: &clasp_core::FederationSyncMessage,
     ctx: &HandlerContext<'_>,
@@ -29,10 +53,10 @@ pub(crate) async fn handle(
             session.id
         );
         let error = Message::Error(ErrorMessage {
-            code: 403,
+            code: ErrorCode::WriteRejected as u16,
             message: "FederationSync requires federation feature".to_string(),
             address: None,
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
@@ -43,6 +67,9 @@ pub(crate) async fn handle(
             handle_declare_namespaces(fed_msg, session, ctx).await
         }
         clasp_core::FederationOp::RequestSync => handle_request_sync(fed_msg, session, ctx).await,
+        clasp_core::FederationOp::RequestJournalSync => {
+            handle_request_journal_sync(fed_msg, session, ctx).await
+        }
         clasp_core::FederationOp::RevisionVector => {
             handle_revision_vector(fed_msg, session, ctx).await
         }
@@ -53,6 +80,103 @@ pub(crate) async fn handle(
             );
             Some(MessageResult::None)
         }
+        clasp_core::FederationOp::JournalCheckpoint => Some(MessageResult::None),
+        clasp_core::FederationOp::RequestRegistrySync => {
+            handle_request_registry_sync(session, ctx).await
+        }
+        clasp_core::FederationOp::RegistryEvent => {
+            handle_registry_event(fed_msg, session, ctx).await
+        }
+    }
+}
+
+/// ERROR for a registry operation the peer may not make, if any
+fn reject_registry_peer(session: &Session, ctx: &HandlerContext<'_>) -> Option<Message> {
+    let reason = if ctx.registry_replica.is_none() {
+        "this router does not replicate an entity registry"
+    } else if ctx.security_mode == SecurityMode::Authenticated
+        && !session.has_scope(Action::Admin, crate::registry_sync::REGISTRY_SCOPE_ADDRESS)
+    {
+        "registry replication requires admin scope"
+    } else {
+        return None;
+    };
+    Some(Message::Error(ErrorMessage {
+        code: ErrorCode::WriteRejected as u16,
+        message: reason.to_string(),
+        address: None,
+        correlation_id: ctx.correlation_id,
+    }))
+}
+
+/// Send the peer every registry event we hold, then ask for its own
+async fn handle_request_registry_sync(
+    session: &Arc<Session>,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    if let Some(error) = reject_registry_peer(session, ctx) {
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+    let replica = ctx.registry_replica.as_ref()?;
+
+    let events = replica.snapshot().await;
+    debug!(
+        "Federation: sending {} registry event(s) to peer {}",
+        events.len(),
+        session.federation_router_id().unwrap_or_default()
+    );
+    for payload in events {
+        if let Ok(bytes) = codec::encode(&crate::registry_sync::registry_event(payload)) {
+            let _ = ctx.sender.send(bytes).await;
+        }
+    }
+
+    let request = crate::registry_sync::request_registry_sync(&ctx.config.name);
+    let bytes = codec::encode(&request).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// Apply a signed registry event from the peer
+async fn handle_registry_event(
+    fed_msg: &clasp_core::FederationSyncMessage,
+    session: &Arc<Session>,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    if let Some(error) = reject_registry_peer(session, ctx) {
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+    let replica = ctx.registry_replica.as_ref()?;
+
+    let Some(ref payload) = fed_msg.payload else {
+        let error = Message::Error(ErrorMessage {
+            code: ErrorCode::InvalidRequest as u16,
+            message: "RegistryEvent without a payload".to_string(),
+            address: None,
+            correlation_id: ctx.correlation_id,
+        });
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    };
+
+    match replica.apply(&session.id, payload).await {
+        Ok(()) => Some(MessageResult::None),
+        Err(reason) => {
+            warn!(
+                "Federation: refused registry event from peer {}: {}",
+                session.federation_router_id().unwrap_or_default(),
+                reason
+            );
+            let error = Message::Error(ErrorMessage {
+                code: ErrorCode::WriteRejected as u16,
+                message: format!("registry event refused: {}", reason),
+                address: None,
+                correlation_id: ctx.correlation_id,
+            });
+            let bytes = codec::encode(&error).ok()?;
+            Some(MessageResult::Send(bytes))
+        }
     }
 }
 
@@ -69,23 +193,27 @@ async fn handle_declare_namespaces(
             MAX_FEDERATION_PATTERNS
         );
         let error = Message::Error(ErrorMessage {
-            code: 400,
+            code: ErrorCode::InvalidRequest as u16,
             message: format!(
                 "too many namespace patterns: {} (max {})",
                 fed_msg.patterns.len(),
                 MAX_FEDERATION_PATTERNS
             ),
             address: None,
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
     }
 
-    let router_id = fed_msg
-        .origin
-        .clone()
-        .unwrap_or_else(|| session.name.clone());
+    let gossip = fed_msg.origin.as_deref().and_then(GossipOrigin::parse);
+    let router_id = match gossip {
+        Some(ref origin) => origin.router_id.clone(),
+        None => fed_msg
+            .origin
+            .clone()
+            .unwrap_or_else(|| session.name.clone()),
+    };
 
     if ctx.security_mode == SecurityMode::Authenticated {
         for pattern in &fed_msg.patterns {
@@ -95,10 +223,10 @@ async fn handle_declare_namespaces(
                     router_id, pattern
                 );
                 let error = Message::Error(ErrorMessage {
-                    code: 403,
+                    code: ErrorCode::WriteRejected as u16,
                     message: format!("insufficient scope for namespace: {}", pattern),
                     address: None,
-                    correlation_id: None,
+                    correlation_id: ctx.correlation_id,
                 });
                 let bytes = codec::encode(&error).ok()?;
                 return Some(MessageResult::Send(bytes));
@@ -106,6 +234,21 @@ async fn handle_declare_namespaces(
         }
     }
 
+    if let Some(origin) = gossip {
+        handle_gossip(origin, fed_msg.patterns.clone(), session, ctx);
+        let ack = Message::Ack(AckMessage {
+            address: None,
+            revision: None,
+            locked: None,
+            holder: None,
+            correlation_id: ctx.correlation_id,
+            results: Vec::new(),
+            cursor: None,
+        });
+        let bytes = codec::encode(&ack).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+
     info!(
         "Federation peer {} declares namespaces: {:?}",
         router_id, fed_msg.patterns
@@ -161,16 +304,394 @@ async fn handle_declare_namespaces(
         revision: None,
         locked: None,
         holder: None,
-        correlation_id: None,
+        correlation_id: ctx.correlation_id,
+        results: Vec::new(),
+        cursor: None,
     });
     let bytes = codec::encode(&ack).ok()?;
     Some(MessageResult::Send(bytes))
 }
 
+/// Record a gossiped declaration from `session`, then relay it onwards.
+fn handle_gossip(
+    origin: GossipOrigin,
+    patterns: Vec<String>,
+    session: &Arc<Session>,
+    ctx: &HandlerContext<'_>,
+) {
+    // Our own namespaces came back around the mesh
+    if origin.router_id == ctx.config.name {
+        return;
+    }
+    if !session.set_federation_route(origin.clone(), patterns.clone()) {
+        return;
+    }
+
+    if patterns.is_empty() {
+        info!(
+            "Federation: {} withdrew route to {}",
+            session.federation_router_id().unwrap_or_default(),
+            origin.router_id
+        );
+    } else {
+        info!(
+            "Federation: {} has a route to {} ({} hops): {:?}",
+            session.federation_router_id().unwrap_or_default(),
+            origin.router_id,
+            origin.hops as u16 + 1,
+            patterns
+        );
+    }
+
+    refresh_route_subscriptions(ctx.sessions, ctx.subscriptions);
+    if let Some(next) = origin.relayed() {
+        relay_gossip(&next, patterns, &session.id, ctx.sessions);
+    }
+}
+
+/// Withdraw routes gossiped through a federation peer that has gone away.
+///
+/// Call after the session has been removed from `sessions`.
+pub(crate) fn release_federation_routes(
+    session: &Session,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let routes = session.federation_routes();
+    if routes.is_empty() {
+        return;
+    }
+    debug!(
+        "Federation: withdrawing {} route(s) through {}",
+        routes.len(),
+        session.id
+    );
+    refresh_route_subscriptions(sessions, subscriptions);
+    for (origin, _) in routes {
+        if let Some(next) = origin.relayed() {
+            relay_gossip(&next, vec![], &session.id, sessions);
+        }
+    }
+}
+
+/// Send a gossiped declaration to every federation peer except the one it
+/// came from and the owner itself.
+fn relay_gossip(
+    origin: &GossipOrigin,
+    patterns: Vec<String>,
+    from: &SessionId,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+) {
+    let msg = Message::FederationSync(clasp_core::FederationSyncMessage {
+        op: clasp_core::FederationOp::DeclareNamespaces,
+        patterns,
+        revisions: HashMap::new(),
+        since_revision: None,
+        origin: Some(origin.encode()),
+        payload: None,
+    });
+    let Ok(bytes) = codec::encode(&msg) else {
+        return;
+    };
+    for entry in sessions.iter() {
+        let peer = entry.value();
+        if peer.id == *from
+            || !peer.is_federation_peer()
+            || peer.federation_router_id().as_deref() == Some(origin.router_id.as_str())
+        {
+            continue;
+        }
+        let _ = peer.try_send(bytes.clone());
+    }
+}
+
+/// Point each gossiped owner's namespaces at the federation peer with the
+/// shortest path to it (ties broken by session ID), so traffic for a
+/// peer-of-peer goes down exactly one link.
+fn refresh_route_subscriptions(
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let peers: Vec<Arc<Session>> = sessions
+        .iter()
+        .filter(|e| e.value().is_federation_peer())
+        .map(|e| Arc::clone(e.value()))
+        .collect();
+
+    // owner -> (hops, session ID) of the best path
+    let mut best: HashMap<String, (u8, SessionId)> = HashMap::new();
+    for peer in &peers {
+        let peer_router = peer.federation_router_id();
+        for (origin, _) in peer.federation_routes() {
+            // The peer's own namespaces are covered by its direct declaration
+            if peer_router.as_deref() == Some(origin.router_id.as_str()) {
+                continue;
+            }
+            let candidate = (origin.hops, peer.id.clone());
+            best.entry(origin.router_id)
+                .and_modify(|current| {
+                    if candidate < *current {
+                        *current = candidate.clone();
+                    }
+                })
+                .or_insert(candidate);
+        }
+    }
+
+    for peer in &peers {
+        for id in peer.subscriptions() {
+            if id >= ROUTE_SUBSCRIPTION_BASE {
+                subscriptions.remove(&peer.id, id);
+                peer.remove_subscription(id);
+            }
+        }
+
+        let mut patterns: Vec<String> = peer
+            .federation_routes()
+            .into_iter()
+            .filter(|(origin, _)| best.get(&origin.router_id).map(|(_, id)| id) == Some(&peer.id))
+            .flat_map(|(_, patterns)| patterns)
+            .collect();
+        patterns.sort();
+        patterns.dedup();
+
+        for (i, pattern) in patterns.iter().enumerate() {
+            let sub_id = ROUTE_SUBSCRIPTION_BASE + i as u32;
+            match crate::subscription::Subscription::new(
+                sub_id,
+                peer.id.clone(),
+                pattern,
+                vec![],
+                Default::default(),
+            ) {
+                Ok(subscription) => {
+                    subscriptions.add(subscription);
+                    peer.add_subscription(sub_id);
+                }
+                Err(e) => {
+                    warn!(
+                        "Federation: failed to create route subscription for {}: {:?}",
+                        pattern, e
+                    );
+                }
+            }
+        }
+    }
+}
+
 async fn handle_request_sync(
     fed_msg: &clasp_core::FederationSyncMessage,
     session: &Arc<crate::session::Session>,
     ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    if let Some(rejected) = reject_sync_patterns(fed_msg, session, ctx) {
+        return Some(rejected);
+    }
+
+    send_pattern_snapshots(fed_msg, fed_msg.since_revision, session, ctx).await;
+    sync_complete(&fed_msg.patterns, None, ctx)
+}
+
+/// Stream journal entries for the requested patterns after
+/// `since_revision` (a journal sequence number), up to the journal's head
+/// when the request arrived.
+///
+/// Each page of entries goes out as a SNAPSHOT followed by a
+/// `JournalCheckpoint` carrying the last sequence number covered, and the
+/// closing `SyncComplete` carries the head. The peer keeps the last
+/// checkpoint and asks from there after a partition, so resync is
+/// incremental. When the journal no longer holds the entries after
+/// `since_revision` (first sync, or compacted away), or there is no single
+/// journal to stream from, the current state is sent as a snapshot instead.
+async fn handle_request_journal_sync(
+    fed_msg: &clasp_core::FederationSyncMessage,
+    session: &Arc<crate::session::Session>,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    if let Some(rejected) = reject_sync_patterns(fed_msg, session, ctx) {
+        return Some(rejected);
+    }
+
+    #[cfg(feature = "journal")]
+    if let Some(journal) = ctx.state.federation_journal() {
+        let cut = stream_journal(fed_msg, journal.as_ref(), session, ctx).await;
+        return sync_complete(&fed_msg.patterns, cut, ctx);
+    }
+
+    debug!("Federation: no journal to stream from, sending a snapshot");
+    send_pattern_snapshots(fed_msg, None, session, ctx).await;
+    sync_complete(&fed_msg.patterns, None, ctx)
+}
+
+/// Send the journal entries a `RequestJournalSync` asks for and return the
+/// sequence number the peer is now caught up to
+#[cfg(feature = "journal")]
+async fn stream_journal(
+    fed_msg: &clasp_core::FederationSyncMessage,
+    journal: &dyn clasp_journal::Journal,
+    session: &Arc<crate::session::Session>,
+    ctx: &HandlerContext<'_>,
+) -> Option<u64> {
+    // Writes already applied to state are appended in the background; let
+    // them land so the cut covers everything the peer could have seen live
+    let settle = tokio::time::Instant::now() + JOURNAL_SETTLE_TIMEOUT;
+    while ctx.state.journal_lag() > 0 && tokio::time::Instant::now() < settle {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    let cut = match journal.latest_seq().await {
+        Ok(seq) => seq,
+        Err(e) => {
+            warn!(
+                "Federation: journal unavailable for sync, sending a snapshot: {}",
+                e
+            );
+            send_pattern_snapshots(fed_msg, None, session, ctx).await;
+            return None;
+        }
+    };
+
+    let oldest = journal
+        .since(0, Some(1))
+        .await
+        .ok()
+        .and_then(|entries| entries.first().map(|e| e.seq));
+    let since = match fed_msg.since_revision {
+        Some(since) if since <= cut && oldest.is_none_or(|oldest| oldest <= since + 1) => since,
+        _ => {
+            // Nothing to stream from: the snapshot is taken after the cut,
+            // so it already holds every entry up to it
+            debug!(
+                "Federation: journal has no history after {:?} (oldest {:?}), sending a snapshot",
+                fed_msg.since_revision, oldest
+            );
+            send_pattern_snapshots(fed_msg, None, session, ctx).await;
+            return Some(cut);
+        }
+    };
+
+    let mut from = since;
+    let mut streamed = 0;
+    while from < cut {
+        let page = match journal.since(from, Some(JOURNAL_SYNC_PAGE)).await {
+            Ok(page) if !page.is_empty() => page,
+            Ok(_) => break,
+            Err(e) => {
+                warn!("Federation: journal read failed during sync: {}", e);
+                return Some(from);
+            }
+        };
+
+        let mut params = Vec::new();
+        for entry in page {
+            if entry.seq > cut {
+                break;
+            }
+            from = entry.seq;
+            let Some(revision) = entry.revision else {
+                continue;
+            };
+            if entry.msg_type != clasp_core::codec::msg::SET
+                || !fed_msg
+                    .patterns
+                    .iter()
+                    .any(|p| clasp_core::address::glob_match(p, &entry.address))
+            {
+                continue;
+            }
+            params.push(clasp_core::ParamValue {
+                address: entry.address,
+                value: entry.value,
+                revision,
+                writer: Some(entry.author),
+                timestamp: Some(entry.timestamp),
+            });
+        }
+        if let Some(ref filter) = ctx.snapshot_filter {
+            params = filter.filter_snapshot(params, session, ctx.state);
+        }
+        if !params.is_empty() {
+            streamed += params.len();
+            send_chunked_snapshot(
+                ctx.sender,
+                SnapshotMessage {
+                    params,
+                    ..Default::default()
+                },
+            )
+            .await;
+        }
+
+        let checkpoint = Message::FederationSync(clasp_core::FederationSyncMessage {
+            op: clasp_core::FederationOp::JournalCheckpoint,
+            patterns: fed_msg.patterns.clone(),
+            revisions: HashMap::new(),
+            since_revision: Some(from),
+            origin: Some(ctx.config.name.clone()),
+            payload: None,
+        });
+        if let Ok(bytes) = codec::encode(&checkpoint) {
+            let _ = ctx.sender.send(bytes).await;
+        }
+    }
+
+    debug!(
+        "Federation: streamed {} journal entries ({}..={}) to peer {}",
+        streamed,
+        since + 1,
+        cut,
+        session.federation_router_id().unwrap_or_default()
+    );
+    Some(cut)
+}
+
+/// Send the current state of each requested pattern, keeping only params
+/// newer than `since` if given
+async fn send_pattern_snapshots(
+    fed_msg: &clasp_core::FederationSyncMessage,
+    since: Option<u64>,
+    session: &Arc<crate::session::Session>,
+    ctx: &HandlerContext<'_>,
+) {
+    for pattern in &fed_msg.patterns {
+        let mut snapshot = ctx.state.snapshot(pattern);
+
+        if let Some(since) = since {
+            snapshot.params.retain(|p| p.revision > since);
+        }
+
+        if let Some(ref filter) = ctx.snapshot_filter {
+            snapshot.params = filter.filter_snapshot(snapshot.params, session, ctx.state);
+        }
+
+        send_chunked_snapshot(ctx.sender, snapshot).await;
+    }
+}
+
+/// The `SyncComplete` closing a sync, with the journal sequence number it
+/// reached for journal syncs
+fn sync_complete(
+    patterns: &[String],
+    seq: Option<u64>,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let complete = Message::FederationSync(clasp_core::FederationSyncMessage {
+        op: clasp_core::FederationOp::SyncComplete,
+        patterns: patterns.to_vec(),
+        revisions: std::collections::HashMap::new(),
+        since_revision: seq,
+        origin: Some(ctx.config.name.clone()),
+        payload: None,
+    });
+    let bytes = codec::encode(&complete).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// ERROR for a sync request the peer may not make, if any.
+fn reject_sync_patterns(
+    fed_msg: &clasp_core::FederationSyncMessage,
+    session: &Arc<crate::session::Session>,
+    ctx: &HandlerContext<'_>,
 ) -> Option<MessageResult> {
     if fed_msg.patterns.len() > MAX_FEDERATION_PATTERNS {
         warn!(
@@ -179,14 +700,14 @@ async fn handle_request_sync(
             MAX_FEDERATION_PATTERNS
         );
         let error = Message::Error(ErrorMessage {
-            code: 400,
+            code: ErrorCode::InvalidRequest as u16,
             message: format!(
                 "too many sync patterns: {} (max {})",
                 fed_msg.patterns.len(),
                 MAX_FEDERATION_PATTERNS
             ),
             address: None,
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
@@ -204,10 +725,10 @@ async fn handle_request_sync(
                 pattern, declared
             );
             let error = Message::Error(ErrorMessage {
-                code: 403,
+                code: ErrorCode::WriteRejected as u16,
                 message: format!("pattern '{}' not covered by declared namespaces", pattern),
                 address: None,
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
             });
             let bytes = codec::encode(&error).ok()?;
             return Some(MessageResult::Send(bytes));
@@ -221,39 +742,17 @@ async fn handle_request_sync(
                 pattern
             );
             let error = Message::Error(ErrorMessage {
-                code: 403,
+                code: ErrorCode::WriteRejected as u16,
                 message: format!("insufficient scope for pattern: {}", pattern),
                 address: None,
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
             });
             let bytes = codec::encode(&error).ok()?;
             return Some(MessageResult::Send(bytes));
         }
     }
 
-    for pattern in &fed_msg.patterns {
-        let mut snapshot = ctx.state.snapshot(pattern);
-
-        if let Some(since) = fed_msg.since_revision {
-            snapshot.params.retain(|p| p.revision > since);
-        }
-
-        if let Some(ref filter) = ctx.snapshot_filter {
-            snapshot.params = filter.filter_snapshot(snapshot.params, session, ctx.state);
-        }
-
-        send_chunked_snapshot(ctx.sender, snapshot).await;
-    }
-
-    let complete = Message::FederationSync(clasp_core::FederationSyncMessage {
-        op: clasp_core::FederationOp::SyncComplete,
-        patterns: fed_msg.patterns.clone(),
-        revisions: std::collections::HashMap::new(),
-        since_revision: None,
-        origin: Some(ctx.config.name.clone()),
-    });
-    let bytes = codec::encode(&complete).ok()?;
-    Some(MessageResult::Send(bytes))
+    None
 }
 
 async fn handle_revision_vector(
@@ -268,14 +767,14 @@ async fn handle_revision_vector(
             MAX_REVISION_ENTRIES
         );
         let error = Message::Error(ErrorMessage {
-            code: 400,
+            code: ErrorCode::InvalidRequest as u16,
             message: format!(
                 "too many revision entries: {} (max {})",
                 fed_msg.revisions.len(),
                 MAX_REVISION_ENTRIES
             ),
             address: None,
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
@@ -328,6 +827,7 @@ async fn handle_revision_vector(
     if !delta_params.is_empty() {
         let snapshot = SnapshotMessage {
             params: delta_params,
+            ..Default::default()
         };
         send_chunked_snapshot(ctx.sender, snapshot).await;
     }
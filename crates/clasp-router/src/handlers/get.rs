@@ -1,13 +1,26 @@
 //! GET message handler -- returns current state values.
 //!
 //! Looks up a single address in the router state and returns a SNAPSHOT
-//! containing the current value, revision, and writer. Respects scope checks
-//! and snapshot filtering.
+//! containing the current value, revision, and writer. A correlated GET of
+//! an unknown address gets an empty SNAPSHOT. A pattern address
+//! (`/lights/**`) returns every matching param instead, limited to `depth`
+//! segments below the pattern's fixed prefix when set, and is always
+//! answered, with an empty SNAPSHOT if nothing matches. Respects scope
+//! checks and snapshot filtering.
+//!
+//! A GET of [`STATS_ADDRESS`] is answered with the session's own link
+//! statistics (see [`crate::delivery_stats`]) instead of router state, so
+//! it needs no read scope.
 
-use clasp_core::{codec, Action, ErrorMessage, Message, SecurityMode};
+use clasp_core::address::Pattern;
+use clasp_core::error::ErrorCode;
+use clasp_core::link_stats::LinkStats;
+use clasp_core::{codec, Action, ErrorMessage, Message, ParamValue, SecurityMode, SnapshotMessage};
 use tracing::warn;
 
-use super::{HandlerContext, MessageResult};
+use super::{send_chunked_snapshot, HandlerContext, MessageResult};
+use crate::delivery_stats::STATS_ADDRESS;
+use crate::session::Session;
 
 pub(crate) async fn handle(
     get: &clasp_core::GetMessage,
@@ -15,6 +28,16 @@ pub(crate) async fn handle(
 ) -> Option<MessageResult> {
     let session = ctx.session.as_ref()?;
 
+    if get.address == STATS_ADDRESS {
+        let snapshot = Message::Snapshot(SnapshotMessage {
+            params: vec![link_stats(session)],
+            correlation_id: ctx.correlation_id,
+            more: false,
+        });
+        let bytes = codec::encode(&snapshot).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+
     if ctx.security_mode == SecurityMode::Authenticated
         && !session.has_scope(Action::Read, &get.address)
     {
@@ -23,36 +46,100 @@ pub(crate) async fn handle(
             session.id, get.address
         );
         let error = Message::Error(ErrorMessage {
-            code: 301,
+            code: ErrorCode::Forbidden as u16,
             message: "Insufficient scope for read operation".to_string(),
             address: Some(get.address.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
     }
 
-    if let Some(param_state) = ctx.state.get_state(&get.address) {
-        let mut params = vec![clasp_core::ParamValue {
+    if get.address.contains('*') {
+        if let Err(e) = Pattern::compile(&get.address) {
+            let error = Message::Error(ErrorMessage {
+                code: ErrorCode::PatternError as u16,
+                message: e.to_string(),
+                address: Some(get.address.clone()),
+                correlation_id: ctx.correlation_id,
+            });
+            let bytes = codec::encode(&error).ok()?;
+            return Some(MessageResult::Send(bytes));
+        }
+
+        let mut snapshot = ctx.state.snapshot(&get.address);
+        if let Some(depth) = get.depth {
+            let base = fixed_depth(&get.address);
+            snapshot
+                .params
+                .retain(|p| segment_count(&p.address) <= base + depth as usize);
+        }
+        if let Some(ref filter) = ctx.snapshot_filter {
+            snapshot.params = filter.filter_snapshot(snapshot.params, session, ctx.state);
+        }
+        snapshot.correlation_id = ctx.correlation_id;
+        send_chunked_snapshot(ctx.sender, snapshot).await;
+        return Some(MessageResult::None);
+    }
+
+    let mut params: Vec<_> = ctx
+        .state
+        .get_state(&get.address)
+        .map(|param_state| ParamValue {
             address: get.address.clone(),
             value: param_state.value,
             revision: param_state.revision,
             writer: Some(param_state.writer),
             timestamp: Some(param_state.timestamp),
-        }];
+        })
+        .into_iter()
+        .collect();
 
-        if let Some(ref filter) = ctx.snapshot_filter {
+    if let Some(ref filter) = ctx.snapshot_filter {
+        if !params.is_empty() {
             params = filter.filter_snapshot(params, session, ctx.state);
         }
+    }
 
-        if params.is_empty() {
-            return Some(MessageResult::None);
-        }
+    // Correlated GETs are always answered so the request can complete
+    if params.is_empty() && ctx.correlation_id.is_none() {
+        return Some(MessageResult::None);
+    }
 
-        let snapshot = Message::Snapshot(clasp_core::SnapshotMessage { params });
-        let bytes = codec::encode(&snapshot).ok()?;
-        return Some(MessageResult::Send(bytes));
+    let snapshot = Message::Snapshot(SnapshotMessage {
+        params,
+        correlation_id: ctx.correlation_id,
+        more: false,
+    });
+    let bytes = codec::encode(&snapshot).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// The session's delivery counts and send queue depth, as a param
+fn link_stats(session: &Session) -> ParamValue {
+    let stats = LinkStats {
+        queued: session.queued().map(|n| n as u64),
+        dropped: session.total_drops(),
+        subscriptions: session.delivery_stats().snapshot(),
+    };
+    ParamValue {
+        address: STATS_ADDRESS.to_string(),
+        value: stats.to_value(),
+        revision: 0,
+        writer: None,
+        timestamp: Some(clasp_core::time::now()),
     }
+}
+
+/// Segments before the first wildcard (`/lights/*/level` -> 1)
+fn fixed_depth(pattern: &str) -> usize {
+    pattern
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .take_while(|s| !s.contains('*'))
+        .count()
+}
 
-    Some(MessageResult::None)
+fn segment_count(address: &str) -> usize {
+    address.split('/').filter(|s| !s.is_empty()).count()
 }
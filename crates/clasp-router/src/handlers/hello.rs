@@ -2,14 +2,43 @@
 //!
 //! In `Authenticated` mode, the client must present a valid token (CPSK, capability,
 //! or entity). On success the handler creates a `Session`, sends WELCOME + snapshot.
+//!
+//! Tokens bound to a key (an [`AUDIENCE_METADATA`] entry, e.g. `cap_` tokens
+//! with an audience) also need proof of possession: WELCOME carries a random
+//! challenge, and the session only starts once the client answers with a
+//! PROOF signed by that key. Until then nothing is registered, so a replayed
+//! token can't take over another device's session.
+//!
+//! A HELLO carrying `session_resume` starts or resumes a durable session
+//! (see [`crate::durable_session`]).
 
-use clasp_core::{codec, ErrorMessage, Message, SecurityMode, ValidationResult};
+use clasp_core::error::ErrorCode;
+use clasp_core::frame::CORRELATION_FEATURE;
+use clasp_core::security::{possession_payload, AUDIENCE_METADATA};
+use clasp_core::{
+    alias, codec, ErrorMessage, Message, ProofMessage, SecurityMode, ValidationResult,
+};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rand::RngCore;
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
 use super::{send_chunked_snapshot, HandlerContext, MessageResult};
+use crate::alias::SessionAliases;
+use crate::delivery_stats::DeliveryStats;
+use crate::durable_session::SuspendedSession;
 use crate::session::Session;
 
+/// A session waiting for the client to prove it holds its token's key
+pub(crate) struct PendingProof {
+    session: Session,
+    challenge: String,
+    /// Hex-encoded Ed25519 public key the token is bound to
+    audience: String,
+    /// Suspended session to restore, with the token it was taken under
+    resume: Option<(String, SuspendedSession)>,
+}
+
 pub(crate) async fn handle(
     hello: &clasp_core::HelloMessage,
     ctx: &HandlerContext<'_>,
@@ -18,8 +47,8 @@ pub(crate) async fn handle(
     // require a token, run it through the validator chain (CPSK -> caps -> entity),
     // and reject on any failure before creating a session.
     // See pentest CAP-01: Token Forgery, ENT-01: Signature Bypass, ENT-04: Non-Existent Entity
-    let (authenticated, subject, scopes) = match ctx.security_mode {
-        SecurityMode::Open => (false, None, Vec::new()),
+    let (authenticated, subject, scopes, audience, entity) = match ctx.security_mode {
+        SecurityMode::Open => (false, None, Vec::new(), None, None),
         SecurityMode::Authenticated => {
             let token = match &hello.token {
                 Some(t) => t,
@@ -29,7 +58,7 @@ pub(crate) async fn handle(
                     #[cfg(feature = "metrics")]
                     metrics::counter!("clasp_errors_total", "code" => "300").increment(1);
                     let error = Message::Error(ErrorMessage {
-                        code: 300,
+                        code: ErrorCode::Unauthorized as u16,
                         message: "Authentication required".to_string(),
                         address: None,
                         correlation_id: None,
@@ -47,7 +76,7 @@ pub(crate) async fn handle(
                     #[cfg(feature = "metrics")]
                     metrics::counter!("clasp_errors_total", "code" => "500").increment(1);
                     let error = Message::Error(ErrorMessage {
-                        code: 500,
+                        code: ErrorCode::InternalError as u16,
                         message: "Server misconfiguration".to_string(),
                         address: None,
                         correlation_id: None,
@@ -58,21 +87,29 @@ pub(crate) async fn handle(
                 }
             };
 
-            match validator.validate(token) {
+            match crate::auth::validate(
+                validator.as_ref(),
+                ctx.validation_cache,
+                &ctx.config.validation,
+                token,
+            )
+            .await
+            {
                 ValidationResult::Valid(info) => {
                     info!(
                         "Token validated for subject: {:?}, scopes: {}",
                         info.subject,
                         info.scopes.len()
                     );
-                    (true, info.subject, info.scopes)
+                    let audience = info.metadata.get(AUDIENCE_METADATA).cloned();
+                    (true, info.subject, info.scopes, audience, info.entity)
                 }
                 ValidationResult::Expired => {
                     warn!("Connection rejected: token expired");
                     #[cfg(feature = "metrics")]
                     metrics::counter!("clasp_errors_total", "code" => "302").increment(1);
                     let error = Message::Error(ErrorMessage {
-                        code: 302,
+                        code: ErrorCode::TokenExpired as u16,
                         message: "Token has expired".to_string(),
                         address: None,
                         correlation_id: None,
@@ -86,7 +123,7 @@ pub(crate) async fn handle(
                     #[cfg(feature = "metrics")]
                     metrics::counter!("clasp_errors_total", "code" => "300").increment(1);
                     let error = Message::Error(ErrorMessage {
-                        code: 300,
+                        code: ErrorCode::Unauthorized as u16,
                         message: format!("Invalid token: {}", reason),
                         address: None,
                         correlation_id: None,
@@ -100,7 +137,7 @@ pub(crate) async fn handle(
                     #[cfg(feature = "metrics")]
                     metrics::counter!("clasp_errors_total", "code" => "300").increment(1);
                     let error = Message::Error(ErrorMessage {
-                        code: 300,
+                        code: ErrorCode::Unauthorized as u16,
                         message: "Unrecognized token format".to_string(),
                         address: None,
                         correlation_id: None,
@@ -113,6 +150,16 @@ pub(crate) async fn handle(
         }
     };
 
+    if let Some(error) = crate::session_limit::check_login(
+        &ctx.config.session_limit,
+        subject.as_deref(),
+        ctx.sessions,
+    ) {
+        let bytes = codec::encode(&error).ok()?;
+        let _ = ctx.sender.send(bytes).await;
+        return Some(MessageResult::Disconnect);
+    }
+
     let mut new_session = Session::new(
         ctx.sender.clone(),
         hello.name.clone(),
@@ -121,26 +168,152 @@ pub(crate) async fn handle(
 
     if authenticated {
         new_session.set_authenticated(hello.token.clone().unwrap_or_default(), subject, scopes);
+        new_session.entity = entity;
+    }
+
+    let mut resume = None;
+    if ctx.config.durable_session_ttl > 0 {
+        if let Some(ref token) = hello.session_resume {
+            if !token.is_empty() {
+                resume = ctx
+                    .state
+                    .durable_sessions()
+                    .take(token, new_session.subject.as_deref())
+                    .map(|suspended| (token.clone(), suspended));
+                if resume.is_none() {
+                    info!("Resume token unknown or expired, starting a new durable session");
+                }
+            }
+            new_session.set_resume_token(random_token());
+        }
     }
 
+    new_session.set_transport(ctx.transport);
+    new_session.set_connection(Arc::clone(ctx.connection));
+    new_session.set_aliases(SessionAliases::new(
+        &ctx.config.topic_aliases,
+        &hello.features,
+    ));
+    new_session.set_bandwidth_meter(Arc::clone(ctx.bandwidth));
+    if ctx.config.delivery_stats {
+        new_session.set_delivery_stats(DeliveryStats::enabled());
+    }
+    if let Some(ref subject) = new_session.subject {
+        ctx.bandwidth.set_subject(subject);
+    }
+    ctx.bandwidth.set_quota(
+        ctx.config
+            .quota
+            .resolve(new_session.subject.as_deref(), new_session.scopes()),
+        ctx.config.quota.action,
+    );
+
+    let mut features = ctx.config.features.clone();
+    if ctx.config.topic_aliases.is_enabled() && !features.iter().any(|f| f == alias::FEATURE) {
+        features.push(alias::FEATURE.to_string());
+    }
+    if !features.iter().any(|f| f == CORRELATION_FEATURE) {
+        features.push(CORRELATION_FEATURE.to_string());
+    }
+    let mut welcome = new_session.welcome_message(&ctx.config.name, &features);
+    if let Message::Welcome(ref mut welcome) = welcome {
+        welcome.resume_token = new_session.resume_token().map(str::to_string);
+        welcome.resumed = resume.is_some();
+    }
+
+    if let Some(audience) = audience {
+        let challenge = random_token();
+        if let Message::Welcome(ref mut welcome) = welcome {
+            welcome.challenge = Some(challenge.clone());
+        }
+        let response = codec::encode(&welcome).ok()?;
+        let _ = ctx.sender.send(response).await;
+        return Some(MessageResult::Challenge(Box::new(PendingProof {
+            session: new_session,
+            challenge,
+            audience,
+            resume,
+        })));
+    }
+
+    let response = codec::encode(&welcome).ok()?;
+    let _ = ctx.sender.send(response).await;
+    Some(establish(new_session, resume.map(|(_, suspended)| suspended), ctx).await)
+}
+
+/// Check the client's answer to a WELCOME challenge and start the session
+pub(crate) async fn handle_proof(
+    pending: PendingProof,
+    proof: &ProofMessage,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let payload = possession_payload(&pending.session.id, &pending.challenge);
+    if !verify_possession(&pending.audience, &payload, &proof.signature) {
+        warn!(
+            "Connection rejected: invalid proof of possession for {}",
+            pending.session.id
+        );
+        // A failed proof must not cost the real owner their durable session
+        if let Some((token, suspended)) = pending.resume {
+            ctx.state.durable_sessions().suspend(token, suspended);
+        }
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clasp_errors_total", "code" => "300").increment(1);
+        let error = Message::Error(ErrorMessage {
+            code: ErrorCode::Unauthorized as u16,
+            message: "Invalid proof of possession".to_string(),
+            address: None,
+            correlation_id: None,
+        });
+        let bytes = codec::encode(&error).ok()?;
+        let _ = ctx.sender.send(bytes).await;
+        return Some(MessageResult::Disconnect);
+    }
+    Some(
+        establish(
+            pending.session,
+            pending.resume.map(|(_, suspended)| suspended),
+            ctx,
+        )
+        .await,
+    )
+}
+
+/// Register a session whose WELCOME has been sent, restore any resumed
+/// subscriptions, then send the snapshot and the entity's configuration and
+/// settle handoff and login limits
+async fn establish(
+    new_session: Session,
+    resume: Option<SuspendedSession>,
+    ctx: &HandlerContext<'_>,
+) -> MessageResult {
     let new_session = Arc::new(new_session);
     let session_id = new_session.id.clone();
     ctx.sessions.insert(session_id.clone(), new_session.clone());
 
     info!(
         "Session created: {} ({}) authenticated={}",
-        hello.name, session_id, new_session.authenticated
+        new_session.name, session_id, new_session.authenticated
     );
 
     #[cfg(feature = "federation")]
     if new_session.is_federation_peer() {
-        info!("Federation peer detected: {} ({})", hello.name, session_id);
+        info!(
+            "Federation peer detected: {} ({})",
+            new_session.name, session_id
+        );
     }
 
-    let welcome = new_session.welcome_message(&ctx.config.name, &ctx.config.features);
-    let response = codec::encode(&welcome).ok()?;
-    let _ = ctx.sender.send(response).await;
+    if ctx.config.presence {
+        crate::presence::join(&new_session, ctx.state, ctx.sessions, ctx.subscriptions);
+    }
+
+    if let Some(suspended) = resume {
+        crate::durable_session::resume(&new_session, suspended, ctx).await;
+    }
 
+    // Under load, the snapshot waits (and is taken once load eases)
+    ctx.overload.defer_snapshot().await;
     let mut full_snapshot = ctx.state.full_snapshot();
     if let Some(ref filter) = ctx.snapshot_filter {
         full_snapshot.params =
@@ -148,5 +321,70 @@ pub(crate) async fn handle(
     }
     send_chunked_snapshot(ctx.sender, full_snapshot).await;
 
-    Some(MessageResult::NewSession(new_session))
+    if ctx.state.maintenance().is_active() {
+        if let Ok(bytes) = codec::encode(&ctx.state.maintenance().announcement()) {
+            let _ = ctx.sender.send(bytes).await;
+        }
+    }
+
+    if let Some(ref source) = ctx.config_source {
+        crate::entity_config::deliver(
+            &new_session,
+            source.as_ref(),
+            ctx.state,
+            ctx.sessions,
+            ctx.subscriptions,
+        )
+        .await;
+    }
+
+    crate::handoff::hand_off(
+        ctx.config.session_handoff,
+        &new_session,
+        ctx.sessions,
+        ctx.subscriptions,
+        ctx.state,
+    )
+    .await;
+    crate::session_limit::kick_excess(
+        &ctx.config.session_limit,
+        &new_session,
+        ctx.sessions,
+        ctx.subscriptions,
+    )
+    .await;
+
+    MessageResult::NewSession(new_session)
+}
+
+/// 32 random bytes, hex-encoded
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether `signature` is a valid Ed25519 signature of `payload` by the
+/// hex-encoded public key `audience`
+fn verify_possession(audience: &str, payload: &[u8], signature: &[u8]) -> bool {
+    let Some(key) = decode_hex(audience)
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+    else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return false;
+    };
+    key.verify(payload, &signature).is_ok()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
 }
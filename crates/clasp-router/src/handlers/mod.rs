@@ -11,11 +11,14 @@ pub mod get;
 pub mod hello;
 pub mod publish;
 pub mod set;
+pub mod set_many;
 pub mod subscribe;
 
 use bytes::Bytes;
+use clasp_core::error::ErrorCode;
 use clasp_core::{
-    codec, ErrorMessage, Frame, Message, SecurityMode, SnapshotMessage, TokenValidator,
+    codec, AckMessage, AsyncTokenValidator, ErrorMessage, Frame, Message, SecurityMode,
+    SnapshotMessage,
 };
 #[cfg(feature = "rules")]
 use clasp_rules::RulesEngine;
@@ -35,6 +38,9 @@ use crate::{
 /// Result of handling a message
 pub(crate) enum MessageResult {
     NewSession(Arc<Session>),
+    /// WELCOME carried a challenge; the session starts once the client's
+    /// PROOF checks out (see [`hello::handle_proof`])
+    Challenge(Box<hello::PendingProof>),
     Send(Bytes),
     #[allow(dead_code)]
     Broadcast(Bytes, SessionId),
@@ -54,26 +60,106 @@ pub(crate) struct HandlerContext<'a> {
     pub state: &'a Arc<RouterState>,
     pub config: &'a RouterConfig,
     pub security_mode: SecurityMode,
-    pub token_validator: &'a Option<Arc<dyn TokenValidator>>,
+    pub token_validator: &'a Option<Arc<dyn AsyncTokenValidator>>,
+    pub validation_cache: &'a Arc<crate::auth::ValidationCache>,
     pub p2p_capabilities: &'a Arc<P2PCapabilities>,
     pub gesture_registry: &'a Option<Arc<GestureRegistry>>,
+    pub aggregator: &'a Option<Arc<crate::aggregate::Aggregator>>,
     pub write_validator: &'a Option<Arc<dyn WriteValidator>>,
     pub snapshot_filter: &'a Option<Arc<dyn SnapshotFilter>>,
+    pub config_source: &'a Option<Arc<dyn crate::entity_config::ConfigSource>>,
+    pub fleet_source: &'a Option<Arc<dyn crate::fleet::FleetSource>>,
+    #[cfg(feature = "federation")]
+    pub registry_replica: &'a Option<Arc<dyn crate::registry_sync::RegistryReplica>>,
     pub transforms: &'a Option<Arc<dyn SignalTransform>>,
     #[cfg(feature = "rules")]
     pub rules_engine: &'a Option<Arc<parking_lot::Mutex<RulesEngine>>>,
+    pub tap: &'a Arc<crate::tap::WireTap>,
+    pub overload: &'a Arc<crate::overload::OverloadGuard>,
+    pub bandwidth: &'a Arc<crate::quota::BandwidthMeter>,
+    /// Transport the connection came in over (e.g. `websocket`)
+    pub transport: &'static str,
+    /// Addresses and TLS details of the connection
+    pub connection: &'a Arc<clasp_transport::ConnectionInfo>,
+    /// Correlation ID of the request frame, echoed in its ACK or ERROR
+    pub correlation_id: Option<u32>,
+}
+
+impl HandlerContext<'_> {
+    /// Tag an ACK or ERROR built elsewhere with the request's correlation ID
+    pub(crate) fn correlate(&self, mut msg: Message) -> Message {
+        match &mut msg {
+            Message::Ack(ack) => ack.correlation_id = self.correlation_id,
+            Message::Error(error) => error.correlation_id = self.correlation_id,
+            _ => {}
+        }
+        msg
+    }
+}
+
+/// Why a client may not write `address`, if the router maintains it
+pub(crate) fn router_owned(ctx: &HandlerContext<'_>, address: &str) -> Option<&'static str> {
+    owned_by_router(ctx.config, ctx.config_source.is_some(), address)
+}
+
+/// [`router_owned`] for callers without a [`HandlerContext`]
+pub(crate) fn owned_by_router(
+    config: &RouterConfig,
+    config_source: bool,
+    address: &str,
+) -> Option<&'static str> {
+    if config.shadow.is_computed(address) {
+        Some("Shadow delta is computed by the router")
+    } else if config.presence && crate::presence::is_presence(address) {
+        Some("Presence is maintained by the router")
+    } else if config_source && crate::entity_config::is_config(address) {
+        Some("Configuration is distributed by the router")
+    } else if config.schema != crate::schema::SchemaMode::Off && crate::schema::is_schema(address) {
+        Some("Schemas are published by the router")
+    } else {
+        None
+    }
+}
+
+/// The ERROR for a write to the reserved `/clasp/` namespace that `session`
+/// lacks admin scope for, after auditing it
+pub(crate) fn reserved_denied(
+    ctx: &HandlerContext<'_>,
+    session: &Session,
+    address: &str,
+    operation: &str,
+) -> Option<Message> {
+    if ctx
+        .config
+        .reserved
+        .permits(session, address, ctx.security_mode)
+    {
+        return None;
+    }
+    crate::reserved::audit(session, address, operation, ctx.sessions, ctx.subscriptions);
+    Some(Message::Error(ErrorMessage {
+        code: ErrorCode::Forbidden as u16,
+        message: "Admin scope required for reserved address".to_string(),
+        address: Some(address.to_string()),
+        correlation_id: ctx.correlation_id,
+    }))
 }
 
 /// Return a short uppercase label for a [`Message`] variant.
-fn message_type_str(msg: &Message) -> &'static str {
+pub(crate) fn message_type_str(msg: &Message) -> &'static str {
     match msg {
         Message::Hello(_) => "HELLO",
         Message::Welcome(_) => "WELCOME",
+        Message::Proof(_) => "PROOF",
         Message::Announce(_) => "ANNOUNCE",
         Message::Subscribe(_) => "SUBSCRIBE",
         Message::Unsubscribe(_) => "UNSUBSCRIBE",
+        Message::Alias(_) => "ALIAS",
+        Message::SubscribeAck(_) => "SUBSCRIBE_ACK",
+        Message::UnsubscribeAck(_) => "UNSUBSCRIBE_ACK",
         Message::Publish(_) => "PUBLISH",
         Message::Set(_) => "SET",
+        Message::SetMany(_) => "SET_MANY",
         Message::Get(_) => "GET",
         Message::Snapshot(_) => "SNAPSHOT",
         Message::Replay(_) => "REPLAY",
@@ -89,17 +175,35 @@ fn message_type_str(msg: &Message) -> &'static str {
     }
 }
 
+/// Whether a message is a request the router acknowledges when it carries
+/// a correlation ID (SUBSCRIBE and UNSUBSCRIBE have acks of their own)
+fn is_request(msg: &Message) -> bool {
+    matches!(
+        msg,
+        Message::Set(_)
+            | Message::SetMany(_)
+            | Message::Publish(_)
+            | Message::Announce(_)
+            | Message::Bundle(_)
+    )
+}
+
 /// Return a short lowercase label for metrics recording.
 #[cfg(feature = "metrics")]
 fn metrics_type_str(msg: &Message) -> &'static str {
     match msg {
         Message::Hello(_) => "hello",
         Message::Welcome(_) => "welcome",
+        Message::Proof(_) => "proof",
         Message::Announce(_) => "announce",
         Message::Subscribe(_) => "subscribe",
         Message::Unsubscribe(_) => "unsubscribe",
+        Message::Alias(_) => "alias",
+        Message::SubscribeAck(_) => "subscribe_ack",
+        Message::UnsubscribeAck(_) => "unsubscribe_ack",
         Message::Publish(_) => "publish",
         Message::Set(_) => "set",
+        Message::SetMany(_) => "set_many",
         Message::Get(_) => "get",
         Message::Snapshot(_) => "snapshot",
         Message::Replay(_) => "replay",
@@ -137,9 +241,11 @@ pub(crate) async fn handle_message(
             Message::Subscribe(sub) => subscribe::handle_subscribe(sub, ctx).await,
             Message::Unsubscribe(unsub) => subscribe::handle_unsubscribe(unsub, ctx).await,
             Message::Set(set) => set::handle(set, ctx).await,
+            Message::SetMany(batch) => set_many::handle(batch, ctx).await,
             Message::Get(get) => get::handle(get, ctx).await,
             Message::Publish(pub_msg) => publish::handle(pub_msg, msg, ctx).await,
             Message::Bundle(bundle) => bundle::handle(bundle, ctx).await,
+            Message::Alias(alias) => control::handle_alias(alias, ctx).await,
             Message::Ping => control::handle_ping(ctx).await,
             Message::Query(query) => control::handle_query(query, ctx).await,
             #[cfg(feature = "journal")]
@@ -154,6 +260,24 @@ pub(crate) async fn handle_message(
     .instrument(span)
     .await;
 
+    // A correlated request is always answered, so the client's future resolves
+    // even when the handler had nothing to say
+    let result = match (result, ctx.correlation_id) {
+        (Some(MessageResult::None), Some(correlation_id)) if is_request(msg) => {
+            let ack = Message::Ack(AckMessage {
+                address: crate::rate_limit::message_address(msg).map(str::to_string),
+                revision: None,
+                locked: None,
+                holder: None,
+                correlation_id: Some(correlation_id),
+                results: Vec::new(),
+                cursor: None,
+            });
+            codec::encode(&ack).ok().map(MessageResult::Send)
+        }
+        (result, _) => result,
+    };
+
     #[cfg(feature = "metrics")]
     {
         let elapsed = start.elapsed().as_secs_f64();
@@ -165,6 +289,9 @@ pub(crate) async fn handle_message(
 }
 
 /// Send a snapshot, chunking if too large for a single frame.
+///
+/// Every chunk of a correlated snapshot carries the correlation ID, and all
+/// but the last have `more` set.
 pub(crate) async fn send_chunked_snapshot(
     sender: &Arc<dyn clasp_transport::TransportSender>,
     snapshot: SnapshotMessage,
@@ -192,6 +319,8 @@ pub(crate) async fn send_chunked_snapshot(
     for (i, chunk) in chunks.enumerate() {
         let chunk_snapshot = SnapshotMessage {
             params: chunk.to_vec(),
+            correlation_id: snapshot.correlation_id,
+            more: snapshot.correlation_id.is_some() && i + 1 < chunk_count,
         };
         let msg = Message::Snapshot(chunk_snapshot);
         match codec::encode(&msg) {
@@ -222,12 +351,13 @@ pub(crate) async fn send_chunked_snapshot(
 /// via `tokio::spawn` to reduce DashMap lock contention on the sending path.
 const CONCURRENT_BROADCAST_THRESHOLD: usize = 10;
 
-/// Try to send a message to a session with drop tracking.
+/// Try to send a message to a session with drop tracking. Returns false if
+/// the message was dropped.
 pub(crate) fn try_send_with_drop_tracking_sync(
     session: &Arc<Session>,
     data: Bytes,
     session_id: &SessionId,
-) {
+) -> bool {
     if let Err(e) = session.try_send(data) {
         warn!(
             "Failed to send to {}: {} (buffer full, dropping)",
@@ -240,7 +370,7 @@ pub(crate) fn try_send_with_drop_tracking_sync(
             let drops = session.drops_in_window();
             tokio::spawn(async move {
                 let error = Message::Error(ErrorMessage {
-                    code: 503,
+                    code: ErrorCode::ServiceUnavailable as u16,
                     message: format!(
                         "Buffer overflow: messages being dropped ({} drops in last 10 seconds)",
                         drops
@@ -260,7 +390,9 @@ pub(crate) fn try_send_with_drop_tracking_sync(
                 }
             });
         }
+        return false;
     }
+    true
 }
 
 /// Broadcast to all sessions except one (non-blocking).
@@ -340,3 +472,45 @@ pub(crate) fn broadcast_to_subscriber_list(
         }
     }
 }
+
+/// Delete a departing session's session-scoped params and presence entry,
+/// and tell subscribers.
+///
+/// Each removed address is broadcast as a SET of `Null` at the next revision,
+/// the same shape subscribers already see when a value is cleared. Call after
+/// the session has been removed from `sessions`.
+pub(crate) fn release_session_state(
+    session_id: &SessionId,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    state.branches().remove_session(session_id);
+    let mut removed = state.remove_session_scoped(session_id);
+    removed.extend(state.remove_session_scoped(&crate::presence::writer(session_id)));
+    if removed.is_empty() {
+        return;
+    }
+    debug!(
+        "Session {} ended, removing {} session-scoped param(s)",
+        session_id,
+        removed.len()
+    );
+    for (address, revision) in removed {
+        let subscribers =
+            subscriptions.find_subscribers(&address, Some(clasp_core::SignalType::Param));
+        let msg = Message::Set(clasp_core::SetMessage {
+            address,
+            value: clasp_core::Value::Null,
+            revision: Some(revision),
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        if let Ok(bytes) = codec::encode(&msg) {
+            broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+        }
+    }
+}
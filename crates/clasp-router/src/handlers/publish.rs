@@ -1,5 +1,6 @@
 //! PUBLISH message handler -- broadcasts events to subscribers.
 
+use clasp_core::error::ErrorCode;
 use clasp_core::{codec, Action, ErrorMessage, Message, SecurityMode, SignalType};
 use tracing::{debug, warn};
 
@@ -23,15 +24,26 @@ pub(crate) async fn handle(
             session.id, pub_msg.address
         );
         let error = Message::Error(ErrorMessage {
-            code: 301,
+            code: ErrorCode::Forbidden as u16,
             message: "Insufficient scope for publish operation".to_string(),
             address: Some(pub_msg.address.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
     }
 
+    if let Some(error) = super::reserved_denied(ctx, session, &pub_msg.address, "PUBLISH") {
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+
+    if ctx.state.maintenance().blocks(&pub_msg.address) {
+        let error = ctx.correlate(ctx.state.maintenance().error(&pub_msg.address));
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+
     // SECURITY: Federation namespace enforcement -- prevents a compromised or
     // misconfigured peer from publishing events outside its declared namespaces.
     // Without this check, a peer could inject arbitrary events on the hub router.
@@ -49,10 +61,10 @@ pub(crate) async fn handle(
                     session.id, pub_msg.address
                 );
                 let error = Message::Error(ErrorMessage {
-                    code: 403,
+                    code: ErrorCode::WriteRejected as u16,
                     message: "PUBLISH outside declared federation namespace".to_string(),
                     address: Some(pub_msg.address.clone()),
-                    correlation_id: None,
+                    correlation_id: ctx.correlation_id,
                 });
                 let bytes = codec::encode(&error).ok()?;
                 return Some(MessageResult::Send(bytes));
@@ -74,10 +86,10 @@ pub(crate) async fn handle(
                 session.id, pub_msg.address, reason
             );
             let error = Message::Error(ErrorMessage {
-                code: 403,
+                code: ErrorCode::WriteRejected as u16,
                 message: reason,
                 address: Some(pub_msg.address.clone()),
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
             });
             let bytes = codec::encode(&error).ok()?;
             return Some(MessageResult::Send(bytes));
@@ -94,10 +106,10 @@ pub(crate) async fn handle(
                 } else {
                     warn!("P2P signal target session not found: {}", target_session);
                     let error = Message::Error(ErrorMessage {
-                        code: 404,
+                        code: ErrorCode::TargetNotFound as u16,
                         message: format!("Target session not found: {}", target_session),
                         address: Some(pub_msg.address.clone()),
-                        correlation_id: None,
+                        correlation_id: ctx.correlation_id,
                     });
                     let bytes = codec::encode(&error).ok()?;
                     return Some(MessageResult::Send(bytes));
@@ -118,8 +130,35 @@ pub(crate) async fn handle(
         P2PAddressType::NotP2P => {}
     }
 
+    // Aggregated addresses are written by the router at a fixed rate
+    if let Some(ref aggregator) = ctx.aggregator {
+        let value = pub_msg.value.as_ref().unwrap_or(&clasp_core::Value::Null);
+        if aggregator.absorb(&pub_msg.address, value) {
+            return Some(MessageResult::None);
+        }
+    }
+
     let signal_type = pub_msg.signal;
 
+    // Under load, gestures and streams are the first to go
+    if matches!(signal_type, Some(SignalType::Gesture | SignalType::Stream))
+        && ctx.overload.is_shedding()
+    {
+        ctx.overload.record(crate::overload::ShedKind::Publish);
+        return Some(MessageResult::None);
+    }
+
+    // Record every gesture frame, not just what coalescing forwards
+    #[cfg(feature = "journal")]
+    if signal_type == Some(SignalType::Gesture) {
+        ctx.state.journal_publish(
+            &pub_msg.address,
+            SignalType::Gesture,
+            Some(&crate::gesture::journal_value(pub_msg)),
+            &session.id,
+        );
+    }
+
     // Check for gesture coalescing
     if let Some(registry) = ctx.gesture_registry {
         if signal_type == Some(SignalType::Gesture) {
@@ -138,6 +177,7 @@ pub(crate) async fn handle(
                                 Some(&session.id),
                             );
                         }
+                        ctx.state.cluster_forward_publish(&forward_msg, &session.id);
                     }
                     return Some(MessageResult::None);
                 }
@@ -149,9 +189,18 @@ pub(crate) async fn handle(
         }
     }
 
-    let subscribers = ctx
+    let mut subscribers = ctx
         .subscriptions
         .find_subscribers(&pub_msg.address, signal_type);
+    if let Some(ref source) = ctx.fleet_source {
+        crate::fleet::add_members(
+            &pub_msg.address,
+            source.as_ref(),
+            ctx.sessions,
+            &mut subscribers,
+        )
+        .await;
+    }
 
     #[cfg(feature = "metrics")]
     metrics::histogram!("clasp_broadcast_fanout").record(subscribers.len() as f64);
@@ -159,14 +208,17 @@ pub(crate) async fn handle(
     if let Ok(bytes) = codec::encode(original_msg) {
         broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, Some(&session.id));
     }
+    ctx.state.cluster_forward_publish(pub_msg, &session.id);
 
     #[cfg(feature = "journal")]
-    ctx.state.journal_publish(
-        &pub_msg.address,
-        signal_type.unwrap_or(SignalType::Event),
-        pub_msg.value.as_ref(),
-        &session.id,
-    );
+    if signal_type != Some(SignalType::Gesture) {
+        ctx.state.journal_publish(
+            &pub_msg.address,
+            signal_type.unwrap_or(SignalType::Event),
+            pub_msg.value.as_ref(),
+            &session.id,
+        );
+    }
 
     #[cfg(feature = "rules")]
     if let Some(ref engine) = ctx.rules_engine {
@@ -175,11 +227,12 @@ pub(crate) async fn handle(
             .as_ref()
             .cloned()
             .unwrap_or(clasp_core::Value::Null);
-        let actions = engine.lock().evaluate(
+        let actions = engine.lock().evaluate_from(
             &pub_msg.address,
             &pub_value,
             signal_type.unwrap_or(SignalType::Event),
             Some(session.id.as_str()),
+            session.entity.as_deref(),
             |addr| ctx.state.get(addr),
         );
         if !actions.is_empty() {
@@ -1,15 +1,53 @@
 //! SET message handler -- applies state changes and broadcasts to subscribers.
 
-use clasp_core::{codec, AckMessage, Action, ErrorMessage, Message, SecurityMode, SignalType};
+use clasp_core::error::ErrorCode;
+use clasp_core::{
+    codec, AckMessage, Action, BundleResult, ErrorMessage, Message, SecurityMode, SetMessage,
+    SignalType,
+};
 use tracing::warn;
 
 use super::{broadcast_to_subscriber_list, HandlerContext, MessageResult};
+use crate::trace::Trace;
 
 pub(crate) async fn handle(
     set: &clasp_core::SetMessage,
     ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let trace = Trace::requested(set, ctx);
+    let result = apply(set, ctx, trace.as_ref()).await;
+    if let Some(trace) = trace {
+        trace.finish(&result, ctx);
+    }
+    result
+}
+
+async fn apply(
+    set: &clasp_core::SetMessage,
+    ctx: &HandlerContext<'_>,
+    trace: Option<&Trace>,
 ) -> Option<MessageResult> {
     let session = ctx.session.as_ref()?;
+    let note = |step: &str, result: &str| {
+        if let Some(trace) = trace {
+            trace.note(step, result);
+        }
+    };
+
+    if set.address.starts_with(crate::tap::TAP_ADMIN_PREFIX) {
+        return handle_tap_admin(set, session, ctx);
+    }
+    if set.address == crate::maintenance::MAINTENANCE_ADMIN_ADDRESS {
+        return handle_maintenance_admin(set, session, ctx);
+    }
+    if let Some(branch) = crate::branch::admin_branch(&set.address) {
+        return handle_branch_admin(branch, set, session, ctx);
+    }
+    if let Some(ref source) = ctx.config_source {
+        if crate::entity_config::is_config(&set.address) {
+            return handle_config(set, session, source.as_ref(), ctx).await;
+        }
+    }
 
     // See pentest PAT-05: Subscription Scope Escape
     if ctx.security_mode == SecurityMode::Authenticated
@@ -20,10 +58,39 @@ pub(crate) async fn handle(
             session.id, set.address
         );
         let error = Message::Error(ErrorMessage {
-            code: 301,
+            code: ErrorCode::Forbidden as u16,
             message: "Insufficient scope for write operation".to_string(),
             address: Some(set.address.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
+        });
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+    if let Some(error) = super::reserved_denied(ctx, session, &set.address, "SET") {
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+    note(
+        "scope",
+        if ctx.security_mode == SecurityMode::Authenticated {
+            "allowed"
+        } else {
+            "open"
+        },
+    );
+
+    if ctx.state.maintenance().blocks(&set.address) {
+        let error = ctx.correlate(ctx.state.maintenance().error(&set.address));
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+
+    if let Some(reason) = super::router_owned(ctx, &set.address) {
+        let error = Message::Error(ErrorMessage {
+            code: ErrorCode::WriteRejected as u16,
+            message: reason.to_string(),
+            address: Some(set.address.clone()),
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
@@ -46,10 +113,10 @@ pub(crate) async fn handle(
                     session.id, set.address
                 );
                 let error = Message::Error(ErrorMessage {
-                    code: 403,
+                    code: ErrorCode::WriteRejected as u16,
                     message: "SET outside declared federation namespace".to_string(),
                     address: Some(set.address.clone()),
-                    correlation_id: None,
+                    correlation_id: ctx.correlation_id,
                 });
                 let bytes = codec::encode(&error).ok()?;
                 return Some(MessageResult::Send(bytes));
@@ -65,20 +132,27 @@ pub(crate) async fn handle(
                 session.id, set.address, reason
             );
             let error = Message::Error(ErrorMessage {
-                code: 403,
+                code: ErrorCode::WriteRejected as u16,
                 message: reason,
                 address: Some(set.address.clone()),
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
             });
             let bytes = codec::encode(&error).ok()?;
             return Some(MessageResult::Send(bytes));
         }
+        note("validator", "allowed");
     }
 
-    // Apply signal transforms if configured (LensVM WASM transforms)
+    // Apply signal transforms if configured (LensVM WASM transforms).
+    // E2E envelopes are opaque to the router and stored as sent.
     let transformed;
-    let set = if let Some(ref transforms) = ctx.transforms {
+    let set = if let Some(transforms) = ctx
+        .transforms
+        .as_ref()
+        .filter(|_| !set.value.is_e2e_envelope())
+    {
         if let Some(new_value) = transforms.transform(&set.address, &set.value) {
+            note("transform", "value rewritten");
             transformed = clasp_core::SetMessage {
                 value: new_value,
                 ..set.clone()
@@ -91,6 +165,57 @@ pub(crate) async fn handle(
         set
     };
 
+    // Check the value against the param's announced schema
+    let checked;
+    let set = match crate::schema::enforce(ctx.state, &set.address, &set.value, ctx.config.schema) {
+        Ok(None) => set,
+        Ok(Some(value)) => {
+            note("schema", "clamped into range");
+            checked = clasp_core::SetMessage {
+                value,
+                ..set.clone()
+            };
+            &checked
+        }
+        Err(violation) => {
+            warn!(
+                "Session {} SET to {} violates its schema: {}",
+                session.id, set.address, violation
+            );
+            let error = Message::Error(ErrorMessage {
+                code: ErrorCode::InvalidValue as u16,
+                message: violation.to_string(),
+                address: Some(set.address.clone()),
+                correlation_id: ctx.correlation_id,
+            });
+            let bytes = codec::encode(&error).ok()?;
+            return Some(MessageResult::Send(bytes));
+        }
+    };
+
+    if let Some(ref branch) = set.branch {
+        note("branch", branch);
+        return apply_to_branch(branch, set, session, ctx);
+    }
+
+    // Aggregated addresses are written by the router at a fixed rate
+    if let Some(ref aggregator) = ctx.aggregator {
+        if aggregator.absorb(&set.address, &set.value) {
+            note("aggregate", "absorbed");
+            let ack = Message::Ack(AckMessage {
+                address: Some(set.address.clone()),
+                revision: None,
+                locked: None,
+                holder: None,
+                correlation_id: ctx.correlation_id,
+                results: Vec::new(),
+                cursor: None,
+            });
+            let ack_bytes = codec::encode(&ack).ok()?;
+            return Some(MessageResult::Send(ack_bytes));
+        }
+    }
+
     match ctx.state.apply_set(set, &session.id) {
         Ok(revision) => {
             let subscribers = ctx
@@ -102,19 +227,36 @@ pub(crate) async fn handle(
 
             let mut updated_set = set.clone();
             updated_set.revision = Some(revision);
+            updated_set.trace = false;
             let broadcast_msg = Message::Set(updated_set);
 
             if let Ok(bytes) = codec::encode(&broadcast_msg) {
-                broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None);
+                match trace {
+                    Some(trace) => trace.deliver(&bytes, &subscribers, ctx.sessions),
+                    None => broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None),
+                }
+                // Branches that haven't changed the address show it live
+                let watchers = ctx.state.branches().live_subscribers(&set.address);
+                broadcast_to_subscriber_list(&bytes, &watchers, ctx.sessions, None);
             }
+            ctx.state.cluster_forward_set(set, revision, &session.id);
+
+            ctx.state.shadows().after_set(
+                &set.address,
+                &ctx.config.shadow,
+                ctx.state,
+                ctx.sessions,
+                ctx.subscriptions,
+            );
 
             #[cfg(feature = "rules")]
             if let Some(ref engine) = ctx.rules_engine {
-                let actions = engine.lock().evaluate(
+                let actions = engine.lock().evaluate_from(
                     &set.address,
                     &set.value,
                     SignalType::Param,
                     Some(session.id.as_str()),
+                    session.entity.as_deref(),
                     |addr| ctx.state.get(addr),
                 );
                 if !actions.is_empty() {
@@ -132,22 +274,388 @@ pub(crate) async fn handle(
                 revision: Some(revision),
                 locked: None,
                 holder: None,
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
+                results: Vec::new(),
+                cursor: None,
             });
             let ack_bytes = codec::encode(&ack).ok()?;
             Some(MessageResult::Send(ack_bytes))
         }
         Err(e) => {
+            let code = e.error_code();
             #[cfg(feature = "metrics")]
-            metrics::counter!("clasp_errors_total", "code" => "400").increment(1);
+            metrics::counter!("clasp_errors_total", "code" => code.as_u16().to_string())
+                .increment(1);
             let error = Message::Error(ErrorMessage {
-                code: 400,
+                code: code.as_u16(),
                 message: format!("{:?}", e),
                 address: Some(set.address.clone()),
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
             });
             let bytes = codec::encode(&error).ok()?;
             Some(MessageResult::Send(bytes))
         }
     }
 }
+
+/// SET on `/clasp/admin/tap/{id}` toggles a wire tap instead of writing state
+fn handle_tap_admin(
+    set: &clasp_core::SetMessage,
+    session: &crate::session::Session,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let result = if ctx.security_mode == SecurityMode::Authenticated
+        && !session.has_scope(Action::Admin, &set.address)
+    {
+        warn!(
+            "Session {} denied wire tap change at {} - admin scope required",
+            session.id, set.address
+        );
+        Err((
+            ErrorCode::Forbidden,
+            "Admin scope required for wire tap".to_string(),
+        ))
+    } else {
+        ctx.tap
+            .apply_admin_set(&set.address, &set.value)
+            .map_err(|reason| (ErrorCode::InvalidRequest, reason))
+    };
+
+    let msg = match result {
+        Ok(()) => Message::Ack(AckMessage {
+            address: Some(set.address.clone()),
+            revision: None,
+            locked: None,
+            holder: None,
+            correlation_id: ctx.correlation_id,
+            results: Vec::new(),
+            cursor: None,
+        }),
+        Err((code, message)) => ctx.correlate(Message::Error(
+            ErrorMessage::new(code, message).with_address(set.address.clone()),
+        )),
+    };
+    let bytes = codec::encode(&msg).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// SET on `/clasp/admin/maintenance` enters or leaves maintenance mode and
+/// announces the change to every session
+fn handle_maintenance_admin(
+    set: &clasp_core::SetMessage,
+    session: &crate::session::Session,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let maintenance = ctx.state.maintenance();
+    let result = if ctx.security_mode == SecurityMode::Authenticated
+        && !session.has_scope(Action::Admin, &set.address)
+    {
+        warn!(
+            "Session {} denied maintenance change - admin scope required",
+            session.id
+        );
+        Err((
+            ErrorCode::Forbidden,
+            "Admin scope required for maintenance mode".to_string(),
+        ))
+    } else {
+        maintenance
+            .apply_admin_set(&set.value)
+            .map_err(|reason| (ErrorCode::InvalidRequest, reason))
+    };
+
+    let msg = match result {
+        Ok(()) => {
+            maintenance.announce(ctx.sessions);
+            Message::Ack(AckMessage {
+                address: Some(set.address.clone()),
+                revision: None,
+                locked: None,
+                holder: None,
+                correlation_id: ctx.correlation_id,
+                results: Vec::new(),
+                cursor: None,
+            })
+        }
+        Err((code, message)) => ctx.correlate(Message::Error(
+            ErrorMessage::new(code, message).with_address(set.address.clone()),
+        )),
+    };
+    let bytes = codec::encode(&msg).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// SET with `branch` writes the branch instead of live state, and only the
+/// branch's subscribers see it
+fn apply_to_branch(
+    branch: &str,
+    set: &SetMessage,
+    session: &crate::session::Session,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let result = crate::branch::validate_name(branch)
+        .and_then(|()| {
+            if set.address.starts_with("/clasp/") {
+                Err("Router addresses can't be written on a branch".to_string())
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|reason| (ErrorCode::InvalidRequest.as_u16(), reason))
+        .and_then(|()| {
+            ctx.state
+                .apply_branch_set(branch, set, &session.id)
+                .map_err(|e| (e.error_code().as_u16(), format!("{:?}", e)))
+        });
+
+    let msg = match result {
+        Ok(revision) => {
+            let subscribers = ctx.state.branches().subscribers(branch, &set.address);
+            let update = Message::Set(SetMessage {
+                revision: Some(revision),
+                trace: false,
+                ..set.clone()
+            });
+            if let Ok(bytes) = codec::encode(&update) {
+                broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None);
+            }
+            Message::Ack(AckMessage {
+                address: Some(set.address.clone()),
+                revision: Some(revision),
+                locked: None,
+                holder: None,
+                correlation_id: ctx.correlation_id,
+                results: Vec::new(),
+                cursor: None,
+            })
+        }
+        Err((code, message)) => Message::Error(ErrorMessage {
+            code,
+            message,
+            address: Some(set.address.clone()),
+            correlation_id: ctx.correlation_id,
+        }),
+    };
+    let bytes = codec::encode(&msg).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// SET on `/clasp/admin/branch/{name}` merges the branch into live state or
+/// discards it
+fn handle_branch_admin(
+    branch: &str,
+    set: &SetMessage,
+    session: &crate::session::Session,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let result = if ctx.security_mode == SecurityMode::Authenticated
+        && !session.has_scope(Action::Admin, &set.address)
+    {
+        warn!(
+            "Session {} denied branch operation on {} - admin scope required",
+            session.id, branch
+        );
+        Err((
+            ErrorCode::Forbidden,
+            "Admin scope required for branch operations".to_string(),
+        ))
+    } else {
+        crate::branch::validate_name(branch)
+            .and_then(|()| crate::branch::BranchOp::parse(&set.value))
+            .map_err(|reason| (ErrorCode::InvalidRequest, reason))
+            .and_then(|op| match op {
+                crate::branch::BranchOp::Merge => merge_branch(branch, session, ctx),
+                crate::branch::BranchOp::Discard => discard_branch(branch, ctx),
+            })
+    };
+
+    let msg = match result {
+        Ok(results) => Message::Ack(AckMessage {
+            address: Some(set.address.clone()),
+            revision: results.last().and_then(|r| r.revision),
+            locked: None,
+            holder: None,
+            correlation_id: ctx.correlation_id,
+            results,
+            cursor: None,
+        }),
+        Err((code, message)) => ctx.correlate(Message::Error(
+            ErrorMessage::new(code, message).with_address(set.address.clone()),
+        )),
+    };
+    let bytes = codec::encode(&msg).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// Promote a branch and send the merged values to live subscribers
+fn merge_branch(
+    branch: &str,
+    session: &crate::session::Session,
+    ctx: &HandlerContext<'_>,
+) -> Result<Vec<BundleResult>, (ErrorCode, String)> {
+    let applied = match ctx.state.merge_branch(branch, &session.id) {
+        None => {
+            return Err((
+                ErrorCode::TargetNotFound,
+                format!("Branch {} has no changes", branch),
+            ))
+        }
+        Some(Err((address, e))) => {
+            warn!(
+                "Session {} merge of branch {} rolled back - SET to {} failed: {:?}",
+                session.id, branch, address, e
+            );
+            return Err((
+                e.error_code(),
+                format!("Merge rolled back: SET to {} failed: {:?}", address, e),
+            ));
+        }
+        Some(Ok(applied)) => applied,
+    };
+    tracing::info!(
+        "Session {} merged branch {} ({} params)",
+        session.id,
+        branch,
+        applied.len()
+    );
+
+    let mut results = Vec::with_capacity(applied.len());
+    for (address, value, revision) in applied {
+        let mut subscribers = ctx
+            .subscriptions
+            .find_subscribers(&address, Some(SignalType::Param));
+        subscribers.extend(ctx.state.branches().live_subscribers(&address));
+        let update = Message::Set(SetMessage {
+            address: address.clone(),
+            value,
+            revision: Some(revision),
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        if let Ok(bytes) = codec::encode(&update) {
+            broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None);
+        }
+        if let Message::Set(ref set) = update {
+            ctx.state.cluster_forward_set(set, revision, &session.id);
+        }
+        ctx.state.shadows().after_set(
+            &address,
+            &ctx.config.shadow,
+            ctx.state,
+            ctx.sessions,
+            ctx.subscriptions,
+        );
+        results.push(BundleResult {
+            address,
+            revision: Some(revision),
+        });
+    }
+    Ok(results)
+}
+
+/// Drop a branch's changes and send its subscribers the live values back
+fn discard_branch(
+    branch: &str,
+    ctx: &HandlerContext<'_>,
+) -> Result<Vec<BundleResult>, (ErrorCode, String)> {
+    let addresses = ctx.state.discard_branch(branch).ok_or_else(|| {
+        (
+            ErrorCode::TargetNotFound,
+            format!("Branch {} has no changes", branch),
+        )
+    })?;
+    for address in addresses {
+        let subscribers = ctx.state.branches().subscribers(branch, &address);
+        if subscribers.is_empty() {
+            continue;
+        }
+        let live = ctx.state.get_state(&address);
+        let update = Message::Set(SetMessage {
+            address,
+            revision: live.as_ref().map(|param| param.revision),
+            value: live
+                .map(|param| param.value)
+                .unwrap_or(clasp_core::Value::Null),
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: Some(branch.to_string()),
+        });
+        if let Ok(bytes) = codec::encode(&update) {
+            broadcast_to_subscriber_list(&bytes, &subscribers, ctx.sessions, None);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// SET under `/clasp/config/` is only accepted as an entity acknowledging
+/// its own configuration version
+async fn handle_config(
+    set: &clasp_core::SetMessage,
+    session: &crate::session::Session,
+    source: &dyn crate::entity_config::ConfigSource,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let own = session.entity.as_ref().map(|entity| entity.id.as_str());
+    let result = match crate::entity_config::acked_entity(&set.address) {
+        None => Err((
+            ErrorCode::WriteRejected,
+            "Configuration is distributed by the router".to_string(),
+        )),
+        Some(entity_id) if own != Some(entity_id) => {
+            warn!(
+                "Session {} denied config acknowledgement for {}",
+                session.id, entity_id
+            );
+            Err((
+                ErrorCode::Forbidden,
+                "Only the entity can acknowledge its configuration".to_string(),
+            ))
+        }
+        Some(entity_id) => match set.value.as_i64().filter(|v| *v > 0) {
+            None => Err((
+                ErrorCode::InvalidValue,
+                "Acknowledgement must be a configuration version".to_string(),
+            )),
+            Some(version) if !source.acknowledge(entity_id, version as u64).await => Err((
+                ErrorCode::RevisionConflict,
+                format!("Version {} is not the current configuration", version),
+            )),
+            Some(version) => crate::entity_config::record_ack(
+                entity_id,
+                version as u64,
+                ctx.state,
+                ctx.sessions,
+                ctx.subscriptions,
+            )
+            .ok_or((
+                ErrorCode::InternalError,
+                "Failed to record acknowledgement".to_string(),
+            )),
+        },
+    };
+
+    let msg = match result {
+        Ok(revision) => Message::Ack(AckMessage {
+            address: Some(set.address.clone()),
+            revision: Some(revision),
+            locked: None,
+            holder: None,
+            correlation_id: ctx.correlation_id,
+            results: Vec::new(),
+            cursor: None,
+        }),
+        Err((code, message)) => Message::Error(ErrorMessage {
+            code: code as u16,
+            message,
+            address: Some(set.address.clone()),
+            correlation_id: ctx.correlation_id,
+        }),
+    };
+    let bytes = codec::encode(&msg).ok()?;
+    Some(MessageResult::Send(bytes))
+}
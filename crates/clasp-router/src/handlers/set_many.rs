@@ -0,0 +1,202 @@
+//! SET_MANY message handler -- batched writes under one base address.
+//!
+//! Every entry is checked the way a bundled SET is (maintenance, router-owned
+//! and reserved addresses, write scope, write validator, schema); if any
+//! fails, the whole batch is refused. The entries are then applied as one
+//! state transaction. Each subscriber receives a single SET_MANY holding the
+//! entries it subscribes to, except sessions that need per-address SETs
+//! (see [`Session::expands_batches`]), which get a SET per entry with its
+//! revision. The ACK carries the base address and the last revision.
+//!
+//! [`Session::expands_batches`]: crate::session::Session::expands_batches
+
+use clasp_core::error::ErrorCode;
+use clasp_core::{
+    codec, AckMessage, Action, ErrorMessage, Message, SecurityMode, SetManyMessage, SetMessage,
+    SignalType,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use super::{try_send_with_drop_tracking_sync, HandlerContext, MessageResult};
+use crate::session::SessionId;
+
+pub(crate) async fn handle(
+    batch: &SetManyMessage,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let session = ctx.session.as_ref()?;
+
+    let reject = |code: u16, message: String, address: &str| {
+        let err = Message::Error(ErrorMessage {
+            code,
+            message,
+            address: Some(address.to_string()),
+            correlation_id: ctx.correlation_id,
+        });
+        codec::encode(&err).ok().map(MessageResult::Send)
+    };
+
+    if !batch.in_range() {
+        return reject(
+            ErrorCode::InvalidRequest as u16,
+            "SET_MANY run extends past the largest index".to_string(),
+            &batch.base,
+        );
+    }
+
+    // PHASE 1: Validate every entry before applying any
+    let mut sets = batch.to_sets();
+    for set in &mut sets {
+        if ctx.state.maintenance().blocks(&set.address) {
+            let err = ctx.correlate(ctx.state.maintenance().error(&set.address));
+            return codec::encode(&err).ok().map(MessageResult::Send);
+        }
+
+        if let Some(reason) = super::router_owned(ctx, &set.address) {
+            return reject(
+                ErrorCode::WriteRejected as u16,
+                format!("SET_MANY rejected: {}", reason),
+                &set.address,
+            );
+        }
+
+        if let Some(error) = super::reserved_denied(ctx, session, &set.address, "SET_MANY") {
+            return codec::encode(&error).ok().map(MessageResult::Send);
+        }
+
+        if ctx.security_mode == SecurityMode::Authenticated
+            && !session.has_scope(Action::Write, &set.address)
+        {
+            warn!(
+                "Session {} denied SET_MANY entry {} - rejecting entire batch",
+                session.id, set.address
+            );
+            return reject(
+                ErrorCode::WriteRejected as u16,
+                format!("SET_MANY rejected: insufficient scope for {}", set.address),
+                &set.address,
+            );
+        }
+
+        if let Some(ref validator) = ctx.write_validator {
+            if let Err(reason) =
+                validator.validate_write(&set.address, &set.value, session, ctx.state)
+            {
+                warn!(
+                    "Session {} denied SET_MANY entry {} by write validator - rejecting entire batch: {}",
+                    session.id, set.address, reason
+                );
+                return reject(
+                    ErrorCode::WriteRejected as u16,
+                    format!("SET_MANY rejected: {}", reason),
+                    &set.address,
+                );
+            }
+        }
+
+        match crate::schema::enforce(ctx.state, &set.address, &set.value, ctx.config.schema) {
+            Ok(Some(clamped)) => set.value = clamped,
+            Ok(None) => {}
+            Err(violation) => {
+                warn!(
+                    "Session {} SET_MANY entry {} violates its schema - rejecting entire batch: {}",
+                    session.id, set.address, violation
+                );
+                return reject(
+                    ErrorCode::InvalidValue as u16,
+                    format!("SET_MANY rejected: {}", violation),
+                    &set.address,
+                );
+            }
+        }
+    }
+
+    // PHASE 2: Apply as one transaction; any rejection rolls back all
+    let revisions = match ctx.state.apply_all(&sets, &session.id) {
+        Ok(revisions) => revisions,
+        Err((index, e)) => {
+            let address = &sets[index].address;
+            warn!(
+                "Session {} SET_MANY rolled back - entry {} failed: {}",
+                session.id, address, e
+            );
+            let code = e.error_code();
+            #[cfg(feature = "metrics")]
+            metrics::counter!("clasp_errors_total", "code" => code.as_u16().to_string())
+                .increment(1);
+            return reject(
+                code.as_u16(),
+                format!("SET_MANY rolled back: SET to {} failed: {}", address, e),
+                address,
+            );
+        }
+    };
+
+    // Group entries by subscriber so each gets one frame
+    let mut matched: HashMap<SessionId, Vec<usize>> = HashMap::new();
+    for (i, set) in sets.iter().enumerate() {
+        for id in ctx
+            .subscriptions
+            .find_subscribers(&set.address, Some(SignalType::Param))
+        {
+            matched.entry(id).or_default().push(i);
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("clasp_broadcast_fanout").record(matched.len() as f64);
+
+    let indices: Vec<u32> = batch.entries().map(|(index, _)| index).collect();
+    for (id, entries) in matched {
+        let Some(subscriber) = ctx.sessions.get(&id).map(|s| Arc::clone(s.value())) else {
+            continue;
+        };
+        if subscriber.expands_batches() {
+            for i in entries {
+                let set = Message::Set(SetMessage {
+                    revision: Some(revisions[i]),
+                    ..sets[i].clone()
+                });
+                if let Ok(bytes) = codec::encode(&set) {
+                    try_send_with_drop_tracking_sync(&subscriber, bytes, &id);
+                }
+            }
+        } else {
+            let subset = SetManyMessage::from_entries(
+                batch.base.clone(),
+                entries
+                    .into_iter()
+                    .map(|i| (indices[i], sets[i].value.clone()))
+                    .collect(),
+            );
+            if let Ok(bytes) = codec::encode(&Message::SetMany(subset)) {
+                try_send_with_drop_tracking_sync(&subscriber, bytes, &id);
+            }
+        }
+    }
+
+    for (set, revision) in sets.iter().zip(&revisions) {
+        ctx.state.cluster_forward_set(set, *revision, &session.id);
+        ctx.state.shadows().after_set(
+            &set.address,
+            &ctx.config.shadow,
+            ctx.state,
+            ctx.sessions,
+            ctx.subscriptions,
+        );
+    }
+
+    let ack = Message::Ack(AckMessage {
+        address: Some(batch.base.clone()),
+        revision: revisions.last().copied(),
+        locked: None,
+        holder: None,
+        correlation_id: ctx.correlation_id,
+        results: Vec::new(),
+        cursor: None,
+    });
+    let ack_bytes = codec::encode(&ack).ok()?;
+    Some(MessageResult::Send(ack_bytes))
+}
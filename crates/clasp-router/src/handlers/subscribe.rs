@@ -2,12 +2,23 @@
 //!
 //! Manages per-session subscriptions with glob-pattern matching, enforces
 //! per-session subscription limits, and sends filtered snapshots on subscribe.
+//!
+//! A SUBSCRIBE or UNSUBSCRIBE frame that carries a correlation ID is answered
+//! with SUBSCRIBE_ACK (sent before the snapshot, with the options after
+//! clamping) or UNSUBSCRIBE_ACK. Uncorrelated ones succeed silently, as
+//! older clients expect.
 
-use clasp_core::{codec, ErrorMessage, Message, SecurityMode};
+use clasp_core::error::ErrorCode;
+use clasp_core::{
+    codec, ErrorMessage, Message, SecurityMode, SubscribeAckMessage, SubscribeOptions,
+    UnsubscribeAckMessage,
+};
 use tracing::{debug, warn};
 
 use super::{send_chunked_snapshot, HandlerContext, MessageResult};
+use crate::session::Session;
 use crate::subscription::Subscription;
+use std::sync::Arc;
 
 pub(crate) async fn handle_subscribe(
     sub: &clasp_core::SubscribeMessage,
@@ -25,10 +36,10 @@ pub(crate) async fn handle_subscribe(
         #[cfg(feature = "metrics")]
         metrics::counter!("clasp_errors_total", "code" => "429").increment(1);
         let error = Message::Error(ErrorMessage {
-            code: 429,
+            code: ErrorCode::RateLimited as u16,
             message: format!("Subscription limit reached (max {})", max_subs),
             address: Some(sub.pattern.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
@@ -42,23 +53,80 @@ pub(crate) async fn handle_subscribe(
             session.id, sub.pattern
         );
         let error = Message::Error(ErrorMessage {
-            code: 301,
+            code: ErrorCode::Forbidden as u16,
             message: "Insufficient scope for subscription".to_string(),
             address: Some(sub.pattern.clone()),
-            correlation_id: None,
+            correlation_id: ctx.correlation_id,
         });
         let bytes = codec::encode(&error).ok()?;
         return Some(MessageResult::Send(bytes));
     }
 
+    if let Some(branch) = sub.options.as_ref().and_then(|o| o.branch.as_deref()) {
+        return subscribe_to_branch(sub, branch, session, ctx).await;
+    }
+
+    let conversion = sub
+        .options
+        .as_ref()
+        .filter(|options| crate::conversion::converts(options));
+    let converts = conversion.is_some();
+    if let Some(options) = conversion {
+        if let Err(e) = crate::conversion::validate(ctx.state, &sub.pattern, options) {
+            warn!(
+                "Session {} SUBSCRIBE to {} rejected: {}",
+                session.id, sub.pattern, e
+            );
+            let error = Message::Error(ErrorMessage {
+                code: ErrorCode::InvalidRequest as u16,
+                message: e.to_string(),
+                address: Some(sub.pattern.clone()),
+                correlation_id: ctx.correlation_id,
+            });
+            let bytes = codec::encode(&error).ok()?;
+            return Some(MessageResult::Send(bytes));
+        }
+    }
+
     match Subscription::new(
         sub.id,
         session.id.clone(),
         &sub.pattern,
         sub.types.clone(),
-        sub.options.clone().unwrap_or_default(),
+        applied_options(sub.options.clone().unwrap_or_default()),
     ) {
         Ok(subscription) => {
+            // Under load, hold the subscription back until its snapshot can go
+            ctx.overload.defer_snapshot().await;
+            if converts {
+                session
+                    .unit_conversions()
+                    .add(subscription.clone(), ctx.state);
+            } else {
+                session.unit_conversions().remove(sub.id);
+            }
+            match subscription.options.tick_ms {
+                Some(tick_ms) => {
+                    crate::tick::start(session, subscription.clone(), tick_ms);
+                }
+                // Resubscribing an ID without tick_ms ends any previous aggregation
+                _ => {
+                    session.tick_subscriptions().remove(sub.id);
+                }
+            }
+            match subscription.options.stream {
+                Some(policy) => {
+                    crate::stream_policy::start(session, subscription.clone(), policy);
+                }
+                // Resubscribing an ID without a stream policy ends its queue
+                None => {
+                    session.stream_queues().remove(sub.id);
+                }
+            }
+            let options = subscription.options.clone();
+            // Resubscribing a branch subscription's ID moves it to live state
+            ctx.state.branches().unsubscribe(&session.id, sub.id);
+            session.delivery_stats().add(subscription.clone());
             ctx.subscriptions.add(subscription);
             session.add_subscription(sub.id);
             #[cfg(feature = "metrics")]
@@ -69,6 +137,27 @@ pub(crate) async fn handle_subscribe(
             if let Some(ref filter) = ctx.snapshot_filter {
                 snapshot.params = filter.filter_snapshot(snapshot.params, session, ctx.state);
             }
+            if converts {
+                for param in &mut snapshot.params {
+                    crate::conversion::convert_for(
+                        &mut param.value,
+                        &param.address,
+                        &options,
+                        ctx.state,
+                    );
+                }
+            }
+            if ctx.correlation_id.is_some() {
+                let ack = Message::SubscribeAck(SubscribeAckMessage {
+                    id: sub.id,
+                    matched: snapshot.params.len() as u32,
+                    options,
+                    correlation_id: ctx.correlation_id,
+                });
+                if let Ok(bytes) = codec::encode(&ack) {
+                    let _ = ctx.sender.send(bytes).await;
+                }
+            }
             if !snapshot.params.is_empty() {
                 send_chunked_snapshot(ctx.sender, snapshot).await;
             }
@@ -76,10 +165,10 @@ pub(crate) async fn handle_subscribe(
         Err(e) => {
             warn!("Invalid subscription pattern: {}", e);
             let error = Message::Error(ErrorMessage {
-                code: 202,
+                code: ErrorCode::PatternError as u16,
                 message: e.to_string(),
                 address: Some(sub.pattern.clone()),
-                correlation_id: None,
+                correlation_id: ctx.correlation_id,
             });
             let bytes = codec::encode(&error).ok()?;
             return Some(MessageResult::Send(bytes));
@@ -95,8 +184,130 @@ pub(crate) async fn handle_unsubscribe(
 ) -> Option<MessageResult> {
     let session = ctx.session.as_ref()?;
     ctx.subscriptions.remove(&session.id, unsub.id);
-    session.remove_subscription(unsub.id);
+    ctx.state.branches().unsubscribe(&session.id, unsub.id);
+    let removed = session.remove_subscription(unsub.id);
     #[cfg(feature = "metrics")]
     metrics::gauge!("clasp_subscriptions_active").decrement(1.0);
+
+    if ctx.correlation_id.is_none() {
+        return Some(MessageResult::None);
+    }
+    let ack = Message::UnsubscribeAck(UnsubscribeAckMessage {
+        id: unsub.id,
+        removed,
+        correlation_id: ctx.correlation_id,
+    });
+    let bytes = codec::encode(&ack).ok()?;
+    Some(MessageResult::Send(bytes))
+}
+
+/// SUBSCRIBE with the `branch` option watches a branch (see
+/// [`crate::branch`]): the snapshot is the branch over live state
+async fn subscribe_to_branch(
+    sub: &clasp_core::SubscribeMessage,
+    branch: &str,
+    session: &Arc<Session>,
+    ctx: &HandlerContext<'_>,
+) -> Option<MessageResult> {
+    let options = sub.options.clone().unwrap_or_default();
+    let checked = crate::branch::validate_name(branch)
+        .and_then(|()| {
+            if options.tick_ms.is_some()
+                || options.stream.is_some()
+                || crate::conversion::converts(&options)
+            {
+                Err(
+                    "branch can't be combined with tick_ms, stream, convert_to or transform"
+                        .to_string(),
+                )
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|reason| (ErrorCode::InvalidRequest.as_u16(), reason))
+        .and_then(|()| {
+            Subscription::new(
+                sub.id,
+                session.id.clone(),
+                &sub.pattern,
+                sub.types.clone(),
+                options.clone(),
+            )
+            .map_err(|e| (ErrorCode::PatternError.as_u16(), e.to_string()))
+        });
+    let subscription = match checked {
+        Ok(subscription) => subscription,
+        Err((code, message)) => {
+            warn!(
+                "Session {} SUBSCRIBE to {} on branch {} rejected: {}",
+                session.id, sub.pattern, branch, message
+            );
+            let error = Message::Error(ErrorMessage {
+                code,
+                message,
+                address: Some(sub.pattern.clone()),
+                correlation_id: ctx.correlation_id,
+            });
+            let bytes = codec::encode(&error).ok()?;
+            return Some(MessageResult::Send(bytes));
+        }
+    };
+
+    ctx.overload.defer_snapshot().await;
+    // The ID may have been a live or other-branch subscription before
+    ctx.subscriptions.remove(&session.id, sub.id);
+    session.unit_conversions().remove(sub.id);
+    session.tick_subscriptions().remove(sub.id);
+    session.stream_queues().remove(sub.id);
+    ctx.state.branches().unsubscribe(&session.id, sub.id);
+    if let Err(e) = ctx.state.branches().subscribe(branch, subscription.clone()) {
+        let error = Message::Error(ErrorMessage {
+            code: e.error_code().as_u16(),
+            message: format!("Too many branches (max {})", crate::branch::MAX_BRANCHES),
+            address: Some(sub.pattern.clone()),
+            correlation_id: ctx.correlation_id,
+        });
+        let bytes = codec::encode(&error).ok()?;
+        return Some(MessageResult::Send(bytes));
+    }
+    session.delivery_stats().add(subscription);
+    session.add_subscription(sub.id);
+    #[cfg(feature = "metrics")]
+    metrics::gauge!("clasp_subscriptions_active").increment(1.0);
+    debug!(
+        "Session {} subscribed to {} on branch {}",
+        session.id, sub.pattern, branch
+    );
+
+    let mut snapshot = ctx.state.branch_snapshot(branch, &sub.pattern);
+    if let Some(ref filter) = ctx.snapshot_filter {
+        snapshot.params = filter.filter_snapshot(snapshot.params, session, ctx.state);
+    }
+    if ctx.correlation_id.is_some() {
+        let ack = Message::SubscribeAck(SubscribeAckMessage {
+            id: sub.id,
+            matched: snapshot.params.len() as u32,
+            options,
+            correlation_id: ctx.correlation_id,
+        });
+        if let Ok(bytes) = codec::encode(&ack) {
+            let _ = ctx.sender.send(bytes).await;
+        }
+    }
+    if !snapshot.params.is_empty() {
+        send_chunked_snapshot(ctx.sender, snapshot).await;
+    }
     Some(MessageResult::None)
 }
+
+/// The options a subscription runs with: `tick_ms` is clamped to
+/// [`MIN_TICK_MS`](crate::tick::MIN_TICK_MS)..=[`MAX_TICK_MS`](crate::tick::MAX_TICK_MS),
+/// and 0 turns aggregation off
+fn applied_options(mut options: SubscribeOptions) -> SubscribeOptions {
+    options.tick_ms = options
+        .tick_ms
+        .filter(|&tick_ms| tick_ms > 0)
+        .map(|tick_ms| tick_ms.clamp(crate::tick::MIN_TICK_MS, crate::tick::MAX_TICK_MS));
+    options.stream = options.stream.map(crate::stream_policy::applied);
+    options
+}
@@ -0,0 +1,150 @@
+//! Session handoff between devices for the same entity
+//!
+//! When a user moves from a phone to a tablet, the tablet's session
+//! authenticates with the same token subject (entity ID). With a
+//! [`HandoffPolicy`] other than `Off`, the router moves the most recently
+//! active session's subscriptions and session-scoped params to the new
+//! session and sends the old session an ERROR 303 naming its successor,
+//! instead of leaving both sessions subscribed or letting the old session's
+//! scratch state vanish when it finally disconnects.
+//!
+//! Tick aggregation and unit conversion carry over with their subscriptions.
+//! Subscription IDs are kept, so the new client can UNSUBSCRIBE or replace
+//! them by ID.
+
+use clasp_core::error::ErrorCode;
+use clasp_core::{codec, ErrorMessage, Message};
+use dashmap::DashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::{Subscription, SubscriptionManager};
+
+/// What happens when a second session authenticates with the same subject
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HandoffPolicy {
+    /// Sessions coexist independently
+    #[default]
+    Off,
+    /// Move subscriptions and session-scoped state; the old session stays
+    /// connected
+    Transfer,
+    /// Move subscriptions and session-scoped state, then disconnect the old
+    /// session
+    Replace,
+}
+
+impl std::str::FromStr for HandoffPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "transfer" => Ok(Self::Transfer),
+            "replace" => Ok(Self::Replace),
+            other => Err(format!(
+                "unknown handoff policy '{}' (expected off, transfer or replace)",
+                other
+            )),
+        }
+    }
+}
+
+/// What a handoff moved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handoff {
+    /// Session the subscriptions and state came from
+    pub from: SessionId,
+    /// Number of subscriptions moved
+    pub subscriptions: usize,
+    /// Number of session-scoped params moved
+    pub params: usize,
+}
+
+/// Hand off from the subject's most recently active other session to
+/// `new_session`. Returns `None` if the policy is `Off`, the new session has
+/// no subject, or no other session shares it.
+pub(crate) async fn hand_off(
+    policy: HandoffPolicy,
+    new_session: &Arc<Session>,
+    sessions: &DashMap<SessionId, Arc<Session>>,
+    subscriptions: &SubscriptionManager,
+    state: &Arc<RouterState>,
+) -> Option<Handoff> {
+    if policy == HandoffPolicy::Off {
+        return None;
+    }
+    let subject = new_session.subject.as_deref()?;
+    let previous = sessions
+        .iter()
+        .filter(|entry| {
+            entry.key() != &new_session.id && entry.value().subject.as_deref() == Some(subject)
+        })
+        .max_by_key(|entry| *entry.value().last_activity.read())
+        .map(|entry| Arc::clone(entry.value()))?;
+
+    let mut moved = 0;
+    for id in previous.subscriptions() {
+        previous.remove_subscription(id);
+        let Some(subscription) = subscriptions.remove(&previous.id, id) else {
+            continue;
+        };
+        adopt(new_session, subscription, subscriptions, state);
+        moved += 1;
+    }
+    let params = state.transfer_session_scoped(&previous.id, &new_session.id);
+
+    info!(
+        "Session {} handed off to {} (subject {}): {} subscription(s), {} param(s)",
+        previous.id, new_session.id, subject, moved, params
+    );
+
+    let notice = Message::Error(ErrorMessage {
+        code: ErrorCode::SessionHandedOff as u16,
+        message: format!("Session handed off to {}", new_session.id),
+        address: None,
+        correlation_id: None,
+    });
+    if let Ok(bytes) = codec::encode(&notice) {
+        if let Err(e) = previous.send(bytes).await {
+            warn!("Failed to notify {} of handoff: {}", previous.id, e);
+        }
+    }
+    if policy == HandoffPolicy::Replace {
+        if let Err(e) = previous.close().await {
+            warn!("Failed to close handed-off session {}: {}", previous.id, e);
+        }
+    }
+
+    Some(Handoff {
+        from: previous.id.clone(),
+        subscriptions: moved,
+        params,
+    })
+}
+
+/// Give `subscription` to `session` under its existing ID, restarting its
+/// unit conversion and tick aggregation there
+pub(crate) fn adopt(
+    session: &Arc<Session>,
+    mut subscription: Subscription,
+    subscriptions: &SubscriptionManager,
+    state: &Arc<RouterState>,
+) {
+    let id = subscription.id;
+    subscription.session_id = session.id.clone();
+    if crate::conversion::converts(&subscription.options) {
+        session.unit_conversions().add(subscription.clone(), state);
+    }
+    if let Some(tick_ms) = subscription.options.tick_ms.filter(|ms| *ms > 0) {
+        crate::tick::start(session, subscription.clone(), tick_ms);
+    }
+    if let Some(policy) = subscription.options.stream {
+        crate::stream_policy::start(session, subscription.clone(), policy);
+    }
+    session.delivery_stats().add(subscription.clone());
+    subscriptions.add(subscription);
+    session.add_subscription(id);
+}
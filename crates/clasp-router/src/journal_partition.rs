@@ -0,0 +1,179 @@
+//! Namespace-partitioned journaling.
+//!
+//! By default every SET and PUBLISH goes to the router's single journal. A
+//! [`JournalPartition`] binds an address pattern to a journal of its own,
+//! with its own backend and [`Retention`], so high-volume namespaces (chat,
+//! telemetry) can be kept briefly while automation history is kept for good.
+//!
+//! [`RouterState`](crate::RouterState) sends each entry to the first
+//! partition whose pattern matches its address, falling back to the default
+//! journal. REPLAY queries and crash recovery read every partition.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clasp_journal::Journal;
+
+/// How much history a journal partition keeps.
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    /// Drop entries older than this
+    pub max_age: Option<Duration>,
+    /// Keep at most this many of the newest entries
+    pub max_entries: Option<u64>,
+}
+
+impl Retention {
+    /// Keep everything (the default)
+    pub fn is_unbounded(&self) -> bool {
+        self.max_age.is_none() && self.max_entries.is_none()
+    }
+
+    /// Compact `journal` down to this retention. Returns the number of
+    /// entries removed.
+    pub async fn apply(&self, journal: &dyn Journal) -> clasp_journal::Result<u64> {
+        let latest = journal.latest_seq().await?;
+        let mut keep_from = 0;
+
+        if let Some(max_entries) = self.max_entries {
+            keep_from = latest.saturating_sub(max_entries) + 1;
+        }
+        if let Some(max_age) = self.max_age {
+            let cutoff = clasp_core::time::now().saturating_sub(max_age.as_micros() as u64);
+            let oldest_kept = journal
+                .query("**", Some(cutoff), None, Some(1), &[])
+                .await?
+                .first()
+                .map(|entry| entry.seq)
+                .unwrap_or(latest + 1);
+            keep_from = keep_from.max(oldest_kept);
+        }
+
+        if keep_from <= 1 {
+            return Ok(0);
+        }
+        journal.compact(keep_from).await
+    }
+}
+
+/// A journal bound to the addresses matching `pattern`.
+#[derive(Clone)]
+pub struct JournalPartition {
+    /// Name used in logs
+    pub name: String,
+    /// Address pattern routed to this partition
+    pub pattern: String,
+    pub journal: Arc<dyn Journal>,
+    pub retention: Retention,
+}
+
+impl JournalPartition {
+    pub fn new(
+        name: impl Into<String>,
+        pattern: impl Into<String>,
+        journal: Arc<dyn Journal>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            pattern: pattern.into(),
+            journal,
+            retention: Retention::default(),
+        }
+    }
+
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Whether entries for `address` belong to this partition
+    pub fn matches(&self, address: &str) -> bool {
+        clasp_core::address::glob_match(&self.pattern, address)
+    }
+}
+
+impl std::fmt::Debug for JournalPartition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JournalPartition")
+            .field("name", &self.name)
+            .field("pattern", &self.pattern)
+            .field("retention", &self.retention)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::Value;
+    use clasp_journal::{JournalEntry, MemoryJournal};
+
+    async fn fill(journal: &MemoryJournal, timestamps: &[u64]) {
+        for (i, ts) in timestamps.iter().enumerate() {
+            journal
+                .append(JournalEntry::from_set(
+                    "/chat/room".to_string(),
+                    Value::Int(i as i64),
+                    i as u64 + 1,
+                    "s1".to_string(),
+                    *ts,
+                ))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retention_max_entries() {
+        let journal = MemoryJournal::new(100);
+        fill(&journal, &[1, 2, 3, 4, 5]).await;
+
+        let retention = Retention {
+            max_entries: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(retention.apply(&journal).await.unwrap(), 3);
+        let seqs: Vec<u64> = journal
+            .since(0, None)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.seq)
+            .collect();
+        assert_eq!(seqs, vec![4, 5]);
+        // Already within bounds
+        assert_eq!(retention.apply(&journal).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_retention_max_age() {
+        let now = clasp_core::time::now();
+        let hour = 3_600_000_000;
+        let journal = MemoryJournal::new(100);
+        fill(&journal, &[now - 3 * hour, now - 2 * hour, now]).await;
+
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(90 * 60)),
+            ..Default::default()
+        };
+        assert_eq!(retention.apply(&journal).await.unwrap(), 2);
+        assert_eq!(journal.len().await.unwrap(), 1);
+
+        // Everything expired
+        let retention = Retention {
+            max_age: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(retention.apply(&journal).await.unwrap(), 1);
+        assert_eq!(journal.len().await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_partition_matches() {
+        let partition = JournalPartition::new("chat", "/chat/**", Arc::new(MemoryJournal::new(10)));
+        assert!(partition.matches("/chat/room/1"));
+        assert!(!partition.matches("/automation/scene"));
+        assert!(partition.retention.is_unbounded());
+    }
+}
@@ -39,6 +39,7 @@
 //!
 //! - **WebSocket** (default): Universal, works in browsers and all platforms
 //! - **QUIC**: High-performance for native apps (requires UDP)
+//! - **WebRTC**: Browser data channels with HTTP signaling (`webrtc` feature)
 //!
 //! ## Quick Start
 //!
@@ -84,36 +85,136 @@
 //! - [`router`] - Main Router struct and message handling
 //! - [`session`] - Client session management
 //! - [`state`] - Parameter state storage
+//! - [`backend`] - Durable storage backends that router state writes through to
+//! - [`branch`] - Named copy-on-write state branches, merged into live state at showtime
+//! - [`cluster`] - Param state and fan-out shared by routers over a pub/sub backplane (Redis streams with `cluster-redis`)
+//! - [`journal_partition`] - Per-namespace journals with independent retention (requires `journal` feature)
+//! - [`replay`] - Paged REPLAY answers with continuation cursors, paced per session
 //! - [`subscription`] - Pattern-based subscription matching
+//! - [`tick`] - Fixed-tick bundle aggregation for `tick_ms` subscriptions
+//! - [`stream_policy`] - Backpressure policies for stream subscriptions (latest-only, ring, reliable)
+//! - [`conversion`] - Unit conversion on delivery for `convert_to` subscriptions
+//! - [`delivery_stats`] - Per-subscription delivered/dropped counts, read by clients at `/clasp/stats`
+//! - [`handoff`] - Session handoff between devices for the same entity
+//! - [`session_limit`] - Concurrent login limits per token subject
+//! - [`durable_session`] - Subscriptions kept across reconnects for clients that resume
+//! - [`maintenance`] - Router-wide maintenance mode that rejects client writes
+//! - [`shadow`] - Device shadows with router-computed desired/reported deltas
+//! - [`presence`] - Router-maintained `/clasp/presence/` entries for connected sessions
+//! - [`entity_config`] - Per-entity configuration delivered at `/clasp/config/` with acknowledgements
+//! - [`fleet`] - PUBLISH fan-out to every session of a fleet's member entities
+//! - [`registry_sync`] - Entity registry events carried over federation links (requires `federation` feature)
+//! - [`rate_limit`] - Rate limits by message type and address prefix
+//! - [`canonical`] - Canonical inbound addresses (separators, NFC, escapes, length and depth limits)
+//! - [`address_map`] - Template and regex address translation for the MQTT and OSC adapters
+//! - [`reserved`] - Admin-only writes to the reserved `/clasp/` namespace, with audit events
+//! - [`overload`] - Reconnect storm protection and priority-aware load shedding
+//! - [`tap`] - Sampled wire capture of protocol frames for debugging
+//! - [`trace`] - Per-SET trace reports on `/clasp/trace/` for admin-flagged frames
+//! - [`prometheus`] - Prometheus `/metrics` endpoint (requires `metrics` feature)
 //! - [`p2p`] - Peer-to-peer mesh networking support
 //! - [`gesture`] - Gesture move coalescing for bandwidth optimization
+//! - [`schema`] - Param schemas published at `/clasp/schema/` and enforced on SET
+//! - [`aggregate`] - Audience-scale write aggregation (count, sum, histogram, last-N) at a fixed rate
 //! - [`error`] - Error types
 
+pub mod address_map;
+pub mod aggregate;
+pub mod alias;
+pub mod auth;
+pub mod backend;
+pub mod branch;
+pub mod canonical;
+pub mod cluster;
+pub mod conversion;
+pub mod delivery_stats;
+pub mod durable_session;
+pub mod entity_config;
 pub mod error;
+pub mod fleet;
 pub mod gesture;
 pub mod handlers;
+pub mod handoff;
+#[cfg(feature = "journal")]
+pub mod journal_partition;
+pub mod maintenance;
+pub mod overload;
 pub mod p2p;
+pub mod presence;
+#[cfg(feature = "metrics")]
+pub mod prometheus;
+pub mod quota;
+pub mod rate_limit;
+#[cfg(feature = "federation")]
+pub mod registry_sync;
+pub mod replay;
+pub mod reserved;
 pub mod router;
+pub mod schema;
 pub mod session;
+pub mod session_limit;
+pub mod shadow;
 pub mod state;
+pub mod stream_policy;
 pub mod subscription;
+pub mod tap;
+pub mod tick;
+pub mod trace;
+pub mod transport_policy;
 
 // Protocol adapters (feature-gated)
-#[cfg(any(feature = "mqtt-server", feature = "osc-server"))]
+#[cfg(any(
+    feature = "mqtt-server",
+    feature = "osc-server",
+    feature = "resp-server"
+))]
 pub mod adapters;
 
+pub use address_map::{AddressMap, AddressMapper, AddressRule, Rewrite};
+pub use aggregate::{AggregateKind, AggregateRule, Aggregator};
+pub use alias::{SessionAliases, TopicAliasConfig};
+pub use auth::ValidationConfig;
+#[cfg(feature = "state-sled")]
+pub use backend::SledStateBackend;
+#[cfg(feature = "state-sqlite")]
+pub use backend::SqliteStateBackend;
+pub use backend::{MemoryStateBackend, StateBackend, StoredParam};
+pub use branch::{BranchOp, Branches};
+pub use canonical::{AddressPolicy, CanonicalizeReport, PercentPolicy};
+#[cfg(feature = "cluster-redis")]
+pub use cluster::RedisBackplane;
+pub use cluster::{Backplane, Cluster, MemoryBackplane};
+pub use durable_session::SessionStore;
+pub use entity_config::{ConfigSource, EntityConfig};
 pub use error::{Result, RouterError};
+pub use fleet::FleetSource;
 pub use gesture::{GestureRegistry, GestureResult};
+pub use handoff::HandoffPolicy;
+#[cfg(feature = "journal")]
+pub use journal_partition::{JournalPartition, Retention};
+pub use maintenance::Maintenance;
+pub use overload::{LoadStatus, OverloadConfig, OverloadGuard, ShedKind};
 pub use p2p::{analyze_address, P2PAddressType, P2PCapabilities};
-#[cfg(feature = "quic")]
-pub use router::QuicServerConfig;
+pub use quota::{BandwidthQuota, BandwidthUsage, QuotaAction, QuotaConfig, SessionBandwidth};
+pub use rate_limit::{RateLimitPolicy, RateLimitRule};
+#[cfg(feature = "federation")]
+pub use registry_sync::RegistryReplica;
+pub use replay::ReplayConfig;
+pub use reserved::ReservedNamespace;
 pub use router::{
-    MultiProtocolConfig, Router, RouterConfig, RouterConfigBuilder, SignalTransform, SnapshotFilter,
-    TransportConfig, WriteValidator,
+    LocalHandler, MultiProtocolConfig, Router, RouterConfig, RouterConfigBuilder, SignalTransform,
+    SnapshotFilter, TransportConfig, WriteValidator,
 };
+#[cfg(feature = "quic")]
+pub use router::{QuicCertificates, QuicServerConfig};
+pub use schema::{SchemaMode, Violation};
 pub use session::{Session, SessionId};
-pub use state::{RouterState, RouterStateConfig};
+pub use session_limit::{LimitPolicy, SessionLimit};
+pub use shadow::{ShadowConfig, Shadows};
+pub use state::{RouterState, RouterStateConfig, SignalPage};
 pub use subscription::SubscriptionManager;
+pub use tap::{TapDirection, TapRule, WireTap};
+pub use transport_policy::TransportPolicy;
 
 #[cfg(feature = "rules")]
 pub use router::execute_rule_actions;
@@ -123,3 +224,5 @@ pub use router::execute_rule_actions;
 pub use adapters::{MqttServerAdapter, MqttServerConfig};
 #[cfg(feature = "osc-server")]
 pub use adapters::{OscServerAdapter, OscServerConfig};
+#[cfg(feature = "resp-server")]
+pub use adapters::{RespServerAdapter, RespServerConfig};
@@ -0,0 +1,172 @@
+//! Router-wide maintenance mode
+//!
+//! During database migrations or a show load-in, operators can freeze state
+//! without disconnecting anyone. While maintenance is active, SET, PUBLISH
+//! and BUNDLE from clients are rejected with ERROR 503; GET, SUBSCRIBE and
+//! QUERY keep working, and existing subscribers still receive updates from
+//! the router itself (rules, TTL expiry, session cleanup). Addresses under
+//! `/clasp/` are exempt so admins can still reach the admin namespace and
+//! P2P signaling keeps flowing.
+//!
+//! Maintenance is toggled by SETting [`MAINTENANCE_ADMIN_ADDRESS`] (admin
+//! scope required in authenticated mode) or with
+//! [`Router::set_maintenance`](crate::Router::set_maintenance). Accepted
+//! values: `true`/`false`, a string (on, with that banner message), `null`
+//! (off), or a map `{ "active": bool, "message": string }`.
+//!
+//! Every change is announced to all sessions as an EVENT on
+//! [`MAINTENANCE_EVENT_ADDRESS`] carrying `active` and `message`, so clients
+//! can show a banner without subscribing first. Sessions that connect during
+//! maintenance get the same event after their snapshot.
+
+use clasp_core::error::ErrorCode;
+use clasp_core::{codec, ErrorMessage, Message, PublishMessage, SignalType, Value};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::session::{Session, SessionId};
+
+/// SET this address to enter or leave maintenance mode
+pub const MAINTENANCE_ADMIN_ADDRESS: &str = "/clasp/admin/maintenance";
+
+/// Maintenance changes are announced on this address
+pub const MAINTENANCE_EVENT_ADDRESS: &str = "/clasp/maintenance";
+
+/// Maintenance switch shared by every handler and adapter
+#[derive(Debug, Default)]
+pub struct Maintenance {
+    active: AtomicBool,
+    message: RwLock<Option<String>>,
+}
+
+impl Maintenance {
+    /// Whether client writes are currently rejected
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Banner message for the current maintenance window
+    pub fn message(&self) -> Option<String> {
+        self.message.read().clone()
+    }
+
+    /// Enter (`active = true`) or leave maintenance mode
+    pub fn set(&self, active: bool, message: Option<String>) {
+        *self.message.write() = if active { message } else { None };
+        self.active.store(active, Ordering::Release);
+        info!(
+            "Maintenance mode {}{}",
+            if active { "on" } else { "off" },
+            self.message()
+                .map(|m| format!(": {}", m))
+                .unwrap_or_default()
+        );
+    }
+
+    /// Whether a client write to `address` should be rejected
+    pub fn blocks(&self, address: &str) -> bool {
+        self.is_active() && !address.starts_with("/clasp/")
+    }
+
+    /// Apply a SET on [`MAINTENANCE_ADMIN_ADDRESS`]
+    pub(crate) fn apply_admin_set(&self, value: &Value) -> Result<(), String> {
+        let (active, message) = match value {
+            Value::Null => (false, None),
+            Value::Bool(active) => (*active, None),
+            Value::String(message) => (true, Some(message.clone())),
+            Value::Map(fields) => {
+                let active = match fields.get("active") {
+                    Some(Value::Bool(active)) => *active,
+                    None => true,
+                    Some(_) => return Err("'active' must be a bool".into()),
+                };
+                let message = match fields.get("message") {
+                    Some(Value::String(message)) => Some(message.clone()),
+                    None | Some(Value::Null) => None,
+                    Some(_) => return Err("'message' must be a string".into()),
+                };
+                (active, message)
+            }
+            _ => return Err("expected bool, string, null or map".into()),
+        };
+        self.set(active, message);
+        Ok(())
+    }
+
+    /// The ERROR sent in reply to a rejected write
+    pub fn error(&self, address: &str) -> Message {
+        Message::Error(ErrorMessage {
+            code: ErrorCode::Maintenance as u16,
+            message: self
+                .message()
+                .unwrap_or_else(|| "Router is in maintenance mode".to_string()),
+            address: Some(address.to_string()),
+            correlation_id: None,
+        })
+    }
+
+    /// The EVENT announcing the current maintenance status
+    pub fn announcement(&self) -> Message {
+        let mut fields = HashMap::new();
+        fields.insert("active".to_string(), Value::Bool(self.is_active()));
+        fields.insert(
+            "message".to_string(),
+            self.message().map(Value::String).unwrap_or(Value::Null),
+        );
+        Message::Publish(PublishMessage {
+            address: MAINTENANCE_EVENT_ADDRESS.to_string(),
+            signal: Some(SignalType::Event),
+            value: Some(Value::Map(fields)),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: Some(clasp_core::time::now()),
+            timeline: None,
+        })
+    }
+
+    /// Send the current status to every connected session
+    pub fn announce(&self, sessions: &DashMap<SessionId, Arc<Session>>) {
+        let Ok(bytes) = codec::encode(&self.announcement()) else {
+            return;
+        };
+        for entry in sessions.iter() {
+            let _ = entry.value().try_send(bytes.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_set_values() {
+        let maintenance = Maintenance::default();
+        maintenance
+            .apply_admin_set(&Value::String("Migrating".into()))
+            .unwrap();
+        assert!(maintenance.is_active());
+        assert_eq!(maintenance.message().as_deref(), Some("Migrating"));
+        assert!(maintenance.blocks("/lights/1"));
+        assert!(!maintenance.blocks(MAINTENANCE_ADMIN_ADDRESS));
+
+        maintenance.apply_admin_set(&Value::Null).unwrap();
+        assert!(!maintenance.is_active());
+        assert_eq!(maintenance.message(), None);
+
+        let mut fields = HashMap::new();
+        fields.insert("active".to_string(), Value::Bool(true));
+        maintenance.apply_admin_set(&Value::Map(fields)).unwrap();
+        assert!(maintenance.is_active());
+
+        assert!(maintenance.apply_admin_set(&Value::Int(1)).is_err());
+        assert!(maintenance.is_active());
+    }
+}
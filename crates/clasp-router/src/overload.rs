@@ -0,0 +1,393 @@
+//! Reconnect storm protection
+//!
+//! After a relay restart every client reconnects at once. Past a threshold,
+//! the router answers HELLO with ERROR 504 carrying a backoff hint instead of
+//! running authentication, sending snapshots and creating sessions for all of
+//! them together. The hint is [`OverloadConfig::retry_after`] plus a random
+//! share of [`OverloadConfig::retry_jitter`], so deferred clients spread out
+//! rather than returning as a second herd. `clasp_client` waits for the hint
+//! before its next reconnect attempt.
+//!
+//! Two signals mark the router as overloaded:
+//! - more than [`OverloadConfig::max_accept_rate`] connections accepted in
+//!   the current second
+//! - more than [`OverloadConfig::max_pending_handshakes`] connections
+//!   accepted but not yet through HELLO
+//!
+//! Both default to 0 (off).
+//!
+//! # Load shedding
+//!
+//! Past a reconnect storm, a router can also be overloaded by its traffic.
+//! With [`OverloadConfig::shed_queue_depth`] or
+//! [`OverloadConfig::shed_loop_lag`] set, the router samples every
+//! [`SAMPLE_INTERVAL`] the messages queued in session send buffers and how
+//! late the runtime wakes its sampling task (a sign the CPU is saturated).
+//! While either is over its threshold, and for [`SHED_HOLD`] after, the
+//! router sheds the least important work first:
+//!
+//! - PUBLISH with the Gesture or Stream signal type is dropped; params,
+//!   events and everything else still go through
+//! - snapshots for HELLO and SUBSCRIBE wait until shedding ends, for at most
+//!   [`MAX_SNAPSHOT_DEFER`]
+//! - new HELLOs get ERROR 504 with a backoff hint, as in a reconnect storm
+//!
+//! [`OverloadGuard::status`] reports what is being shed, for health checks;
+//! with the `metrics` feature the same figures are recorded as
+//! `clasp_load_shedding`, `clasp_send_queue_depth`,
+//! `clasp_loop_lag_seconds` and `clasp_shed_total{kind}`.
+
+use parking_lot::Mutex;
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How often load is sampled while shedding is enabled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long shedding continues after load drops below the thresholds
+pub const SHED_HOLD: Duration = Duration::from_secs(1);
+
+/// Longest a snapshot waits for shedding to end
+pub const MAX_SNAPSHOT_DEFER: Duration = Duration::from_secs(5);
+
+/// Thresholds and backoff hints for HELLO deferral
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverloadConfig {
+    /// Connections accepted per second before HELLO is deferred (0 = no limit)
+    pub max_accept_rate: u32,
+    /// Handshakes in flight before HELLO is deferred (0 = no limit)
+    pub max_pending_handshakes: usize,
+    /// Minimum backoff hint
+    pub retry_after: Duration,
+    /// Upper bound of the random delay added to each hint
+    pub retry_jitter: Duration,
+    /// Messages queued across session send buffers before load is shed
+    /// (0 = no limit)
+    pub shed_queue_depth: usize,
+    /// Runtime scheduling delay before load is shed (zero = no limit)
+    pub shed_loop_lag: Duration,
+}
+
+impl Default for OverloadConfig {
+    fn default() -> Self {
+        Self {
+            max_accept_rate: 0,
+            max_pending_handshakes: 0,
+            retry_after: Duration::from_secs(1),
+            retry_jitter: Duration::from_secs(5),
+            shed_queue_depth: 0,
+            shed_loop_lag: Duration::ZERO,
+        }
+    }
+}
+
+impl OverloadConfig {
+    fn is_enabled(&self) -> bool {
+        self.max_accept_rate > 0 || self.max_pending_handshakes > 0 || self.sheds_load()
+    }
+
+    /// Whether load is sampled and shed
+    pub fn sheds_load(&self) -> bool {
+        self.shed_queue_depth > 0 || !self.shed_loop_lag.is_zero()
+    }
+
+    /// A jittered backoff hint
+    pub fn backoff_hint(&self) -> Duration {
+        let jitter_ms = self.retry_jitter.as_millis() as u64;
+        let jitter = if jitter_ms == 0 {
+            0
+        } else {
+            rand::thread_rng().gen_range(0..=jitter_ms)
+        };
+        self.retry_after + Duration::from_millis(jitter)
+    }
+}
+
+/// What is shed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedKind {
+    /// Gesture and Stream PUBLISH messages
+    Publish,
+    /// Snapshots sent late
+    Snapshot,
+    /// HELLOs deferred with a backoff hint
+    Hello,
+}
+
+impl ShedKind {
+    /// Label used in metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShedKind::Publish => "publish",
+            ShedKind::Snapshot => "snapshot",
+            ShedKind::Hello => "hello",
+        }
+    }
+}
+
+/// Load as of the last sample, and what has been shed so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadStatus {
+    /// Whether load is being shed now
+    pub shedding: bool,
+    /// Messages queued across session send buffers
+    pub queued: usize,
+    /// How late the runtime woke the sampling task
+    pub loop_lag: Duration,
+    /// Gesture and Stream PUBLISH messages dropped
+    pub shed_publishes: u64,
+    /// Snapshots sent late
+    pub deferred_snapshots: u64,
+    /// HELLOs deferred with a backoff hint, by storm or shedding
+    pub deferred_hellos: u64,
+}
+
+/// Tracks accepts, in-flight handshakes and load
+pub struct OverloadGuard {
+    config: OverloadConfig,
+    pending: Arc<AtomicUsize>,
+    /// Start of the current one-second window and accepts counted in it
+    window: Mutex<(Instant, u32)>,
+    shedding: AtomicBool,
+    /// Last sample over a threshold
+    last_over: Mutex<Option<Instant>>,
+    queued: AtomicUsize,
+    loop_lag_us: AtomicU64,
+    shed_publishes: AtomicU64,
+    deferred_snapshots: AtomicU64,
+    deferred_hellos: AtomicU64,
+    /// Whether the sampling task is running (one per router)
+    sampling: AtomicBool,
+}
+
+/// An accepted connection that has not finished its handshake
+pub(crate) struct PendingHandshake {
+    pending: Arc<AtomicUsize>,
+}
+
+impl Drop for PendingHandshake {
+    fn drop(&mut self) {
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl OverloadGuard {
+    pub(crate) fn new(config: OverloadConfig) -> Self {
+        Self {
+            config,
+            pending: Arc::new(AtomicUsize::new(0)),
+            window: Mutex::new((Instant::now(), 0)),
+            shedding: AtomicBool::new(false),
+            last_over: Mutex::new(None),
+            queued: AtomicUsize::new(0),
+            loop_lag_us: AtomicU64::new(0),
+            shed_publishes: AtomicU64::new(0),
+            deferred_snapshots: AtomicU64::new(0),
+            deferred_hellos: AtomicU64::new(0),
+            sampling: AtomicBool::new(false),
+        }
+    }
+
+    /// Count an accepted connection; drop the result once HELLO is handled
+    pub(crate) fn accept(&self) -> PendingHandshake {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        if self.config.max_accept_rate > 0 {
+            let mut window = self.window.lock();
+            if window.0.elapsed() >= Duration::from_secs(1) {
+                *window = (Instant::now(), 0);
+            }
+            window.1 = window.1.saturating_add(1);
+        }
+        PendingHandshake {
+            pending: Arc::clone(&self.pending),
+        }
+    }
+
+    /// Handshakes in flight
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// A backoff hint if a HELLO arriving now should be deferred
+    pub(crate) fn check(&self) -> Option<Duration> {
+        if !self.config.is_enabled() {
+            return None;
+        }
+        let max_pending = self.config.max_pending_handshakes;
+        let too_many_pending = max_pending > 0 && self.pending() > max_pending;
+        let too_fast = self.config.max_accept_rate > 0 && {
+            let window = self.window.lock();
+            window.0.elapsed() < Duration::from_secs(1) && window.1 > self.config.max_accept_rate
+        };
+        if !(too_many_pending || too_fast || self.is_shedding()) {
+            return None;
+        }
+        self.record(ShedKind::Hello);
+        Some(self.config.backoff_hint())
+    }
+
+    /// Whether load is being shed
+    pub fn is_shedding(&self) -> bool {
+        self.shedding.load(Ordering::Relaxed)
+    }
+
+    /// Load as of the last sample, and what has been shed so far
+    pub fn status(&self) -> LoadStatus {
+        LoadStatus {
+            shedding: self.is_shedding(),
+            queued: self.queued.load(Ordering::Relaxed),
+            loop_lag: Duration::from_micros(self.loop_lag_us.load(Ordering::Relaxed)),
+            shed_publishes: self.shed_publishes.load(Ordering::Relaxed),
+            deferred_snapshots: self.deferred_snapshots.load(Ordering::Relaxed),
+            deferred_hellos: self.deferred_hellos.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record a load sample taken at `now`; returns whether load is shed
+    pub(crate) fn sample(&self, queued: usize, loop_lag: Duration, now: Instant) -> bool {
+        self.queued.store(queued, Ordering::Relaxed);
+        self.loop_lag_us
+            .store(loop_lag.as_micros() as u64, Ordering::Relaxed);
+
+        let depth = self.config.shed_queue_depth;
+        let lag = self.config.shed_loop_lag;
+        let over = (depth > 0 && queued > depth) || (!lag.is_zero() && loop_lag > lag);
+        let mut last_over = self.last_over.lock();
+        if over {
+            *last_over = Some(now);
+        }
+        let shedding = last_over.is_some_and(|at| now.duration_since(at) < SHED_HOLD);
+        if shedding != self.shedding.swap(shedding, Ordering::Relaxed) {
+            if shedding {
+                warn!(
+                    "Shedding load: {} messages queued, runtime {:?} behind",
+                    queued, loop_lag
+                );
+            } else {
+                info!("Load back under thresholds, no longer shedding");
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::gauge!("clasp_load_shedding").set(if shedding { 1.0 } else { 0.0 });
+            metrics::gauge!("clasp_send_queue_depth").set(queued as f64);
+            metrics::gauge!("clasp_loop_lag_seconds").set(loop_lag.as_secs_f64());
+        }
+        shedding
+    }
+
+    /// Count something shed
+    pub(crate) fn record(&self, kind: ShedKind) {
+        let counter = match kind {
+            ShedKind::Publish => &self.shed_publishes,
+            ShedKind::Snapshot => &self.deferred_snapshots,
+            ShedKind::Hello => &self.deferred_hellos,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clasp_shed_total", "kind" => kind.as_str()).increment(1);
+    }
+
+    /// Wait while load is shed, for at most [`MAX_SNAPSHOT_DEFER`], before
+    /// sending a snapshot
+    pub(crate) async fn defer_snapshot(&self) {
+        if !self.is_shedding() {
+            return;
+        }
+        self.record(ShedKind::Snapshot);
+        let deadline = tokio::time::Instant::now() + MAX_SNAPSHOT_DEFER;
+        while self.is_shedding() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+        }
+    }
+
+    /// Claim the sampling task; false if one is already running
+    pub(crate) fn start_sampling(&self) -> bool {
+        !self.sampling.swap(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn sampling_stopped(&self) {
+        self.sampling.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_never_defers() {
+        let guard = OverloadGuard::new(OverloadConfig::default());
+        let _slots: Vec<_> = (0..1000).map(|_| guard.accept()).collect();
+        assert!(guard.check().is_none());
+    }
+
+    #[test]
+    fn test_pending_handshakes_threshold() {
+        let guard = OverloadGuard::new(OverloadConfig {
+            max_pending_handshakes: 2,
+            retry_after: Duration::from_millis(500),
+            retry_jitter: Duration::from_millis(100),
+            ..Default::default()
+        });
+        let first = guard.accept();
+        let _second = guard.accept();
+        assert!(guard.check().is_none());
+
+        let third = guard.accept();
+        let hint = guard.check().unwrap();
+        assert!(hint >= Duration::from_millis(500) && hint <= Duration::from_millis(600));
+
+        drop(third);
+        drop(first);
+        assert_eq!(guard.pending(), 1);
+        assert!(guard.check().is_none());
+    }
+
+    #[test]
+    fn test_accept_rate_threshold() {
+        let guard = OverloadGuard::new(OverloadConfig {
+            max_accept_rate: 3,
+            retry_jitter: Duration::ZERO,
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            drop(guard.accept());
+        }
+        assert!(guard.check().is_none());
+        drop(guard.accept());
+        assert_eq!(guard.check(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_load_shedding_thresholds_and_hold() {
+        let guard = OverloadGuard::new(OverloadConfig {
+            retry_jitter: Duration::ZERO,
+            shed_queue_depth: 1000,
+            shed_loop_lag: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert!(!guard.sample(1000, Duration::from_millis(10), start));
+        assert!(guard.check().is_none());
+
+        // Either signal starts shedding, which defers HELLO
+        assert!(guard.sample(1001, Duration::ZERO, start));
+        assert_eq!(guard.check(), Some(Duration::from_secs(1)));
+        assert!(guard.sample(0, Duration::from_millis(60), start + SAMPLE_INTERVAL));
+
+        // Shedding holds for a while after load drops
+        let after = start + SAMPLE_INTERVAL;
+        assert!(guard.sample(0, Duration::ZERO, after + SHED_HOLD / 2));
+        assert!(!guard.sample(0, Duration::ZERO, after + SHED_HOLD));
+        assert!(guard.check().is_none());
+
+        let status = guard.status();
+        assert!(!status.shedding);
+        assert_eq!(status.deferred_hellos, 1);
+    }
+}
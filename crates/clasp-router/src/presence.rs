@@ -0,0 +1,179 @@
+//! Router-maintained presence
+//!
+//! With [`RouterConfig::presence`](crate::RouterConfig::presence), every
+//! session that completes the handshake gets a param at
+//! `/clasp/presence/{session_id}` describing it:
+//!
+//! - `name`: client name from HELLO
+//! - `features`: features from HELLO
+//! - `connected_at`: Unix time in microseconds
+//! - `transport`: `websocket`, `quic`, `tcp`, ...
+//! - `entity`: for sessions authenticated with an entity (`ent_`) token, a
+//!   map with the entity's `id`, `type`, `name`, `tags` and registry
+//!   `metadata`
+//!
+//! The entry is set to `null` when the session disconnects or times out, so
+//! subscribing to `/clasp/presence/*` is enough to track who's online. New
+//! sessions see everyone already connected in their snapshot.
+//!
+//! Entries are session-scoped state owned by [`writer`], not by the session
+//! itself: they are never journaled or persisted, and a session handoff
+//! doesn't move them. Clients can't SET under [`PRESENCE_PREFIX`] while
+//! presence is enabled.
+
+use clasp_core::{codec, EntityInfo, Message, SetMessage, SignalType, Ttl, Value};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::handlers::broadcast_to_subscriber_list;
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Presence entries live under this prefix, one per session
+pub const PRESENCE_PREFIX: &str = "/clasp/presence/";
+
+/// Presence address of a session
+pub fn address(session_id: &str) -> String {
+    format!("{}{}", PRESENCE_PREFIX, session_id)
+}
+
+/// Whether `address` is a presence entry
+pub fn is_presence(address: &str) -> bool {
+    address.starts_with(PRESENCE_PREFIX)
+}
+
+/// Writer that owns a session's presence entry
+pub fn writer(session_id: &str) -> SessionId {
+    format!("presence:{}", session_id)
+}
+
+/// Presence entry describing `session`
+pub fn entry(session: &Session) -> Value {
+    let connected_at =
+        clasp_core::time::now().saturating_sub(session.created_at.elapsed().as_micros() as u64);
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), Value::String(session.name.clone()));
+    map.insert(
+        "features".to_string(),
+        Value::Array(
+            session
+                .features
+                .iter()
+                .map(|f| Value::String(f.clone()))
+                .collect(),
+        ),
+    );
+    map.insert("connected_at".to_string(), Value::Int(connected_at as i64));
+    map.insert(
+        "transport".to_string(),
+        Value::String(session.transport().to_string()),
+    );
+    if let Some(ref entity) = session.entity {
+        map.insert("entity".to_string(), entity_entry(entity));
+    }
+    Value::Map(map)
+}
+
+fn entity_entry(entity: &EntityInfo) -> Value {
+    let mut map = HashMap::new();
+    map.insert("id".to_string(), Value::String(entity.id.clone()));
+    map.insert(
+        "type".to_string(),
+        Value::String(entity.entity_type.clone()),
+    );
+    map.insert("name".to_string(), Value::String(entity.name.clone()));
+    map.insert(
+        "tags".to_string(),
+        Value::Array(entity.tags.iter().cloned().map(Value::String).collect()),
+    );
+    map.insert(
+        "metadata".to_string(),
+        Value::Map(
+            entity
+                .metadata
+                .iter()
+                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .collect(),
+        ),
+    );
+    Value::Map(map)
+}
+
+/// Publish the presence entry of a session that just joined
+pub(crate) fn join(
+    session: &Session,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let address = address(&session.id);
+    let value = entry(session);
+    let revision = match state.set(
+        &address,
+        value.clone(),
+        &writer(&session.id),
+        None,
+        false,
+        false,
+        Some(Ttl::Session),
+    ) {
+        Ok(revision) => revision,
+        Err(e) => {
+            warn!("Presence SET to {} failed: {:?}", address, e);
+            return;
+        }
+    };
+
+    let subscribers = subscriptions.find_subscribers(&address, Some(SignalType::Param));
+    let msg = Message::Set(SetMessage {
+        address,
+        value,
+        revision: Some(revision),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    if let Ok(bytes) = codec::encode(&msg) {
+        broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_is_released_with_its_session() {
+        let state = RouterState::new();
+        let sessions = Arc::new(DashMap::new());
+        let subscriptions = SubscriptionManager::new();
+        let session = Session::stub(None);
+
+        join(&session, &state, &sessions, &subscriptions);
+        let Some(Value::Map(entry)) = state.get(&address(&session.id)) else {
+            panic!("presence entry missing");
+        };
+        assert_eq!(
+            entry.get("name"),
+            Some(&Value::String("test-stub".to_string()))
+        );
+        assert_eq!(
+            entry.get("transport"),
+            Some(&Value::String("unknown".to_string()))
+        );
+
+        // Handoff moves the session's own scoped state, not its presence
+        assert_eq!(
+            state.transfer_session_scoped(&session.id, &"next".to_string()),
+            0
+        );
+
+        crate::handlers::release_session_state(&session.id, &state, &sessions, &subscriptions);
+        assert_eq!(state.get(&address(&session.id)), None);
+    }
+}
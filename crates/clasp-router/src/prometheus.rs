@@ -0,0 +1,139 @@
+//! Prometheus scrape endpoint
+//!
+//! With the `metrics` feature the router records its metrics through the
+//! `metrics` facade, which does nothing until a recorder is installed.
+//! [`Router::serve_metrics`] (or `metrics_addr` in
+//! [`MultiProtocolConfig`]) installs a Prometheus recorder and answers
+//! `GET /metrics` in the text exposition format.
+//!
+//! Main series:
+//! - `clasp_sessions_active`, `clasp_subscriptions_active`,
+//!   `clasp_state_params_active`: gauges, refreshed on every scrape
+//! - `clasp_signals_registered`: announced signals in the registry
+//!   QUERY pages through (gauge, refreshed on every scrape)
+//! - `clasp_messages_total{type}`: messages handled per type; use `rate()`
+//!   for messages per second
+//! - `clasp_message_latency_seconds{type}`: handler latency
+//! - `clasp_messages_dropped_total`: broadcasts dropped on full send buffers
+//! - `clasp_accepts_total{transport,acceptor}`,
+//!   `clasp_accept_errors_total{transport,acceptor}`: connections accepted
+//!   and failed accepts per accept loop
+//! - `clasp_journal_lag`: journal appends issued but not yet written
+//!   (`journal` feature)
+//! - `clasp_load_shedding`, `clasp_send_queue_depth`,
+//!   `clasp_loop_lag_seconds`: load as sampled for shedding, and
+//!   `clasp_shed_total{kind}`: gesture and stream publishes dropped,
+//!   snapshots deferred and HELLOs deferred (see [`crate::overload`])
+//!
+//! Only one recorder can be installed per process. Routers in the same
+//! process share it; if the embedding application installed its own, this
+//! fails and the application should expose that recorder instead.
+//!
+//! [`Router::serve_metrics`]: crate::Router::serve_metrics
+//! [`MultiProtocolConfig`]: crate::MultiProtocolConfig
+
+use dashmap::DashMap;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::sync::{Arc, OnceLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+use crate::error::{Result, RouterError};
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Largest request head read before answering
+const MAX_REQUEST: usize = 8 * 1024;
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Install the process-wide Prometheus recorder, or reuse it
+fn handle() -> Result<PrometheusHandle> {
+    if let Some(handle) = HANDLE.get() {
+        return Ok(handle.clone());
+    }
+    let handle = PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(|e| RouterError::Config(format!("cannot install metrics recorder: {}", e)))?;
+    Ok(HANDLE.get_or_init(|| handle).clone())
+}
+
+/// What a scrape reads besides the recorded metrics
+pub(crate) struct Scrape {
+    pub sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    pub subscriptions: Arc<SubscriptionManager>,
+    pub state: Arc<RouterState>,
+}
+
+impl Scrape {
+    fn render(&self, handle: &PrometheusHandle) -> String {
+        metrics::gauge!("clasp_sessions_active").set(self.sessions.len() as f64);
+        metrics::gauge!("clasp_subscriptions_active").set(self.subscriptions.len() as f64);
+        metrics::gauge!("clasp_state_params_active").set(self.state.len() as f64);
+        metrics::gauge!("clasp_signals_registered").set(self.state.signal_count() as f64);
+        #[cfg(feature = "journal")]
+        metrics::gauge!("clasp_journal_lag").set(self.state.journal_lag() as f64);
+        handle.run_upkeep();
+        handle.render()
+    }
+}
+
+/// Serve `/metrics` on `addr` until the listener fails
+pub(crate) async fn serve(addr: &str, scrape: Scrape) -> Result<()> {
+    let handle = handle()?;
+    let listener = TcpListener::bind(addr).await?;
+    info!("Metrics endpoint listening on http://{}/metrics", addr);
+    let scrape = Arc::new(scrape);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let handle = handle.clone();
+        let scrape = Arc::clone(&scrape);
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &handle, &scrape).await {
+                debug!("Metrics request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    handle: &PrometheusHandle,
+    scrape: &Scrape,
+) -> std::io::Result<()> {
+    let mut request = Vec::with_capacity(1024);
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = line.split(|&b| b == b' ');
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let (status, content_type, body) = match (method, path) {
+        (b"GET", b"/metrics") => ("200 OK", "text/plain; version=0.0.4", scrape.render(handle)),
+        (b"GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,449 @@
+//! Per-session bandwidth accounting and quotas
+//!
+//! Every session counts the bytes it sends and receives. Hosted deployments
+//! can bill on those counters (see [`Router::bandwidth_usage`] and the
+//! `clasp_bandwidth_bytes_total` metric) and cap them with a
+//! [`BandwidthQuota`]: bytes per hour and/or per day, in and out combined.
+//!
+//! Quotas are resolved at HELLO, first by token subject (the tenant), then by
+//! the session's scopes, then [`QuotaConfig::default`]. A session that goes
+//! over its quota gets ERROR 306 once per window, then:
+//! - [`QuotaAction::Throttle`] drops its traffic in both directions until the
+//!   window rolls over
+//! - [`QuotaAction::Disconnect`] closes the connection
+//!
+//! Windows are fixed: the hourly window restarts an hour after it opened,
+//! the daily one after 24 hours.
+//!
+//! [`Router::bandwidth_usage`]: crate::Router::bandwidth_usage
+
+use bytes::Bytes;
+use clasp_core::error::ErrorCode;
+use clasp_core::{codec, ErrorMessage, Message, Scope};
+use clasp_transport::TransportSender;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+const HOUR: Duration = Duration::from_secs(3600);
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+/// Byte budget for one session (0 = unlimited)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthQuota {
+    pub bytes_per_hour: u64,
+    pub bytes_per_day: u64,
+}
+
+impl BandwidthQuota {
+    pub fn new(bytes_per_hour: u64, bytes_per_day: u64) -> Self {
+        Self {
+            bytes_per_hour,
+            bytes_per_day,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.bytes_per_hour == 0 && self.bytes_per_day == 0
+    }
+}
+
+/// What happens to a session over quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaAction {
+    /// Drop the session's traffic until the window rolls over
+    #[default]
+    Throttle,
+    /// Close the connection
+    Disconnect,
+}
+
+impl std::str::FromStr for QuotaAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "throttle" => Ok(Self::Throttle),
+            "disconnect" => Ok(Self::Disconnect),
+            other => Err(format!(
+                "unknown quota action '{}' (expected throttle or disconnect)",
+                other
+            )),
+        }
+    }
+}
+
+/// Quotas by tenant and scope
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Quota for sessions with no subject or scope override
+    pub default: BandwidthQuota,
+    /// Quotas by token subject
+    pub subjects: HashMap<String, BandwidthQuota>,
+    /// Quotas by scope string (e.g. `write:/tenant-a/**`)
+    pub scopes: HashMap<String, BandwidthQuota>,
+    /// What happens to a session over quota
+    pub action: QuotaAction,
+}
+
+impl QuotaConfig {
+    /// The quota for a session; the first of its scopes with an entry wins
+    pub fn resolve(&self, subject: Option<&str>, scopes: &[Scope]) -> BandwidthQuota {
+        if let Some(quota) = subject.and_then(|s| self.subjects.get(s)) {
+            return *quota;
+        }
+        if !self.scopes.is_empty() {
+            for scope in scopes {
+                if let Some(quota) = self.scopes.get(&scope.to_string()) {
+                    return *quota;
+                }
+            }
+        }
+        self.default
+    }
+}
+
+/// Result of counting bytes against a quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaStatus {
+    Within,
+    /// This write pushed the session over quota
+    JustExceeded,
+    /// The session was already over quota
+    Exceeded,
+}
+
+impl QuotaStatus {
+    pub fn is_exceeded(&self) -> bool {
+        !matches!(self, QuotaStatus::Within)
+    }
+}
+
+/// A session's byte counters, for diagnostics and billing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BandwidthUsage {
+    /// Bytes received since the session started
+    pub bytes_in: u64,
+    /// Bytes sent since the session started
+    pub bytes_out: u64,
+    /// Bytes in and out in the current hourly window
+    pub hour_bytes: u64,
+    /// Bytes in and out in the current daily window
+    pub day_bytes: u64,
+    pub quota: BandwidthQuota,
+    pub exceeded: bool,
+}
+
+/// Per-session usage for [`Router::bandwidth_usage`](crate::Router::bandwidth_usage)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionBandwidth {
+    pub session_id: String,
+    pub subject: Option<String>,
+    pub usage: BandwidthUsage,
+}
+
+#[derive(Debug)]
+struct Windows {
+    hour: (Instant, u64),
+    day: (Instant, u64),
+    exceeded: bool,
+}
+
+/// Byte counters and quota state for one connection
+#[derive(Debug)]
+pub struct BandwidthMeter {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    quota: RwLock<(BandwidthQuota, QuotaAction)>,
+    windows: Mutex<Windows>,
+    /// Token subject, for metric labels and logs
+    subject: OnceLock<String>,
+}
+
+impl Default for BandwidthMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BandwidthMeter {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            quota: RwLock::new((BandwidthQuota::default(), QuotaAction::default())),
+            windows: Mutex::new(Windows {
+                hour: (now, 0),
+                day: (now, 0),
+                exceeded: false,
+            }),
+            subject: OnceLock::new(),
+        }
+    }
+
+    /// Apply a quota (called at HELLO)
+    pub fn set_quota(&self, quota: BandwidthQuota, action: QuotaAction) {
+        *self.quota.write() = (quota, action);
+    }
+
+    /// Label counters with the session's token subject
+    pub fn set_subject(&self, subject: &str) {
+        let _ = self.subject.set(subject.to_string());
+    }
+
+    pub fn action(&self) -> QuotaAction {
+        self.quota.read().1
+    }
+
+    /// Count received bytes
+    pub fn record_in(&self, bytes: usize) -> QuotaStatus {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.count_metric("in", bytes);
+        self.record_at(bytes as u64, Instant::now())
+    }
+
+    /// Count sent bytes
+    pub fn record_out(&self, bytes: usize) -> QuotaStatus {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.count_metric("out", bytes);
+        self.record_at(bytes as u64, Instant::now())
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_metric(&self, direction: &'static str, bytes: usize) {
+        let subject = self
+            .subject
+            .get()
+            .cloned()
+            .unwrap_or_else(|| "anonymous".to_string());
+        metrics::counter!("clasp_bandwidth_bytes_total", "direction" => direction, "subject" => subject)
+            .increment(bytes as u64);
+    }
+
+    /// Whether the session is over quota in the current windows
+    pub fn is_exceeded(&self) -> bool {
+        let quota = self.quota.read().0;
+        if quota.is_unlimited() {
+            return false;
+        }
+        let mut windows = self.windows.lock();
+        windows.roll(&quota, Instant::now());
+        windows.exceeded
+    }
+
+    pub fn usage(&self) -> BandwidthUsage {
+        let quota = self.quota.read().0;
+        let mut windows = self.windows.lock();
+        windows.roll(&quota, Instant::now());
+        BandwidthUsage {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            hour_bytes: windows.hour.1,
+            day_bytes: windows.day.1,
+            quota,
+            exceeded: windows.exceeded,
+        }
+    }
+
+    fn record_at(&self, bytes: u64, now: Instant) -> QuotaStatus {
+        let quota = self.quota.read().0;
+        let mut windows = self.windows.lock();
+        windows.roll(&quota, now);
+        windows.hour.1 = windows.hour.1.saturating_add(bytes);
+        windows.day.1 = windows.day.1.saturating_add(bytes);
+        if windows.exceeded {
+            return QuotaStatus::Exceeded;
+        }
+        if windows.over(&quota) {
+            windows.exceeded = true;
+            QuotaStatus::JustExceeded
+        } else {
+            QuotaStatus::Within
+        }
+    }
+}
+
+impl Windows {
+    fn over(&self, quota: &BandwidthQuota) -> bool {
+        (quota.bytes_per_hour > 0 && self.hour.1 > quota.bytes_per_hour)
+            || (quota.bytes_per_day > 0 && self.day.1 > quota.bytes_per_day)
+    }
+
+    /// Start new windows whose period has elapsed. A session stays over
+    /// quota until every window it exceeded has rolled over.
+    fn roll(&mut self, quota: &BandwidthQuota, now: Instant) {
+        let mut rolled = false;
+        if now.duration_since(self.hour.0) >= HOUR {
+            self.hour = (now, 0);
+            rolled = true;
+        }
+        if now.duration_since(self.day.0) >= DAY {
+            self.day = (now, 0);
+            rolled = true;
+        }
+        if rolled {
+            self.exceeded = self.over(quota);
+        }
+    }
+}
+
+fn quota_notice(quota: &BandwidthQuota) -> Message {
+    Message::Error(ErrorMessage {
+        code: ErrorCode::QuotaExceeded as u16,
+        message: format!(
+            "Bandwidth quota exceeded ({} bytes/hour, {} bytes/day)",
+            quota.bytes_per_hour, quota.bytes_per_day
+        ),
+        address: None,
+        correlation_id: None,
+    })
+}
+
+/// Tell a session it went over quota and apply the quota action. `inner`
+/// must bypass the meter, or the notice itself would be dropped.
+pub(crate) async fn notify_exceeded(inner: &Arc<dyn TransportSender>, meter: &BandwidthMeter) {
+    let quota = meter.quota.read().0;
+    let action = meter.action();
+    warn!(
+        "Bandwidth quota exceeded by {} ({:?}): {:?}",
+        meter
+            .subject
+            .get()
+            .map(String::as_str)
+            .unwrap_or("anonymous"),
+        quota,
+        action
+    );
+    #[cfg(feature = "metrics")]
+    metrics::counter!("clasp_errors_total", "code" => "306").increment(1);
+    if let Ok(bytes) = codec::encode(&quota_notice(&quota)) {
+        let _ = inner.send(bytes).await;
+    }
+    if action == QuotaAction::Disconnect {
+        let _ = inner.close().await;
+    }
+}
+
+/// Sender that counts outbound bytes and drops them while over quota
+pub(crate) struct MeteredSender {
+    inner: Arc<dyn TransportSender>,
+    meter: Arc<BandwidthMeter>,
+}
+
+impl MeteredSender {
+    pub(crate) fn new(inner: Arc<dyn TransportSender>, meter: Arc<BandwidthMeter>) -> Self {
+        Self { inner, meter }
+    }
+}
+
+#[async_trait::async_trait]
+impl TransportSender for MeteredSender {
+    async fn send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        if self.meter.is_exceeded() {
+            return Ok(());
+        }
+        let status = self.meter.record_out(data.len());
+        self.inner.send(data).await?;
+        if status == QuotaStatus::JustExceeded {
+            notify_exceeded(&self.inner, &self.meter).await;
+        }
+        Ok(())
+    }
+
+    fn try_send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        if self.meter.is_exceeded() {
+            return Ok(());
+        }
+        let status = self.meter.record_out(data.len());
+        self.inner.try_send(data)?;
+        if status == QuotaStatus::JustExceeded {
+            let inner = Arc::clone(&self.inner);
+            let meter = Arc::clone(&self.meter);
+            tokio::spawn(async move { notify_exceeded(&inner, &meter).await });
+        }
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn queued(&self) -> Option<usize> {
+        self.inner.queued()
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::Action;
+
+    #[test]
+    fn test_resolve_prefers_subject_then_scope() {
+        let mut config = QuotaConfig {
+            default: BandwidthQuota::new(100, 0),
+            ..Default::default()
+        };
+        config
+            .subjects
+            .insert("tenant-a".to_string(), BandwidthQuota::new(1, 2));
+        config
+            .scopes
+            .insert("write:/tenant-b/**".to_string(), BandwidthQuota::new(3, 4));
+        let scopes = vec![Scope::new(Action::Write, "/tenant-b/**").unwrap()];
+
+        assert_eq!(
+            config.resolve(Some("tenant-a"), &scopes),
+            BandwidthQuota::new(1, 2)
+        );
+        assert_eq!(
+            config.resolve(Some("x"), &scopes),
+            BandwidthQuota::new(3, 4)
+        );
+        assert_eq!(config.resolve(None, &[]), BandwidthQuota::new(100, 0));
+    }
+
+    #[test]
+    fn test_meter_windows() {
+        let meter = BandwidthMeter::new();
+        meter.set_quota(BandwidthQuota::new(100, 150), QuotaAction::Throttle);
+        let start = Instant::now();
+
+        assert_eq!(meter.record_at(60, start), QuotaStatus::Within);
+        assert_eq!(meter.record_at(60, start), QuotaStatus::JustExceeded);
+        assert_eq!(meter.record_at(1, start), QuotaStatus::Exceeded);
+
+        // Next hour: the hourly window resets, the daily one is still at 121
+        let next_hour = start + HOUR;
+        assert_eq!(meter.record_at(20, next_hour), QuotaStatus::Within);
+        assert_eq!(meter.record_at(20, next_hour), QuotaStatus::JustExceeded);
+
+        let next_day = start + DAY;
+        assert_eq!(meter.record_at(50, next_day), QuotaStatus::Within);
+    }
+
+    #[test]
+    fn test_usage_counts_both_directions() {
+        let meter = BandwidthMeter::new();
+        meter.record_in(10);
+        meter.record_out(25);
+        let usage = meter.usage();
+        assert_eq!((usage.bytes_in, usage.bytes_out), (10, 25));
+        assert_eq!(usage.hour_bytes, 35);
+        assert!(!usage.exceeded);
+    }
+}
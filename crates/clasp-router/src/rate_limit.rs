@@ -0,0 +1,208 @@
+//! Per-type and per-prefix rate limits
+//!
+//! [`RouterConfig::max_messages_per_second`] caps everything a session sends.
+//! A [`RateLimitPolicy`] adds rules for particular traffic, so that e.g. a
+//! burst of gesture publishes can't use up the budget for param writes:
+//!
+//! ```
+//! use clasp_router::{RateLimitPolicy, RateLimitRule};
+//!
+//! let policy = RateLimitPolicy::new(vec![
+//!     RateLimitRule::new(120).types(&["PUBLISH"]).prefix("/gesture/"),
+//!     RateLimitRule::new(200).types(&["SET"]),
+//! ]);
+//! ```
+//!
+//! Rules are checked in order and the first one matching the message type and
+//! address applies; messages no rule matches fall under the global limit.
+//! Each rule counts per session in one-second windows, and a message over its
+//! rule's limit is answered with ERROR 429 and dropped. Messages limited by
+//! a rule don't count towards the global limit.
+//!
+//! Types are the uppercase message names (`SET`, `PUBLISH`, `SUBSCRIBE`,
+//! `GET`, `BUNDLE`, ...). The address is the SET/PUBLISH/GET address or the
+//! SUBSCRIBE pattern; messages without one (e.g. BUNDLE) only match rules
+//! without a prefix.
+//!
+//! [`RouterConfig::max_messages_per_second`]: crate::RouterConfig::max_messages_per_second
+
+use clasp_core::Message;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// A limit for messages of some types and/or under an address prefix
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitRule {
+    /// Message types the rule applies to (empty = all)
+    pub types: Vec<String>,
+    /// Address prefix the rule applies to (none = all addresses)
+    pub prefix: Option<String>,
+    /// Messages per second per session (0 = unlimited)
+    pub max_per_second: u32,
+}
+
+impl RateLimitRule {
+    pub fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            ..Default::default()
+        }
+    }
+
+    /// Restrict the rule to these message types
+    pub fn types(mut self, types: &[&str]) -> Self {
+        self.types = types.iter().map(|t| t.to_ascii_uppercase()).collect();
+        self
+    }
+
+    /// Restrict the rule to addresses starting with `prefix`
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    fn matches(&self, msg_type: &str, address: Option<&str>) -> bool {
+        if !self.types.is_empty() && !self.types.iter().any(|t| t == msg_type) {
+            return false;
+        }
+        match (&self.prefix, address) {
+            (None, _) => true,
+            (Some(prefix), Some(address)) => address.starts_with(prefix.as_str()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Ordered rate limit rules (see the [module docs](self))
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitPolicy {
+    pub rules: Vec<RateLimitRule>,
+}
+
+impl RateLimitPolicy {
+    pub fn new(rules: Vec<RateLimitRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Index and rule of the first rule that applies to `msg`
+    pub fn rule_for(&self, msg: &Message) -> Option<(usize, &RateLimitRule)> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let msg_type = crate::handlers::message_type_str(msg);
+        let address = message_address(msg);
+        self.rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(msg_type, address))
+    }
+}
+
+/// The SET/PUBLISH/GET address or SUBSCRIBE pattern of a message
+pub(crate) fn message_address(msg: &Message) -> Option<&str> {
+    match msg {
+        Message::Set(set) => Some(&set.address),
+        Message::SetMany(batch) => Some(&batch.base),
+        Message::Publish(publish) => Some(&publish.address),
+        Message::Get(get) => Some(&get.address),
+        Message::Subscribe(sub) => Some(&sub.pattern),
+        _ => None,
+    }
+}
+
+/// A session's message counts per rule, in one-second windows
+#[derive(Debug, Default)]
+pub(crate) struct RuleWindows {
+    /// (second, count) by rule index
+    windows: Mutex<Vec<(u64, u32)>>,
+}
+
+impl RuleWindows {
+    /// Count a message against rule `index`; false if it is over `max_per_second`
+    pub(crate) fn check(&self, index: usize, max_per_second: u32, now_secs: u64) -> bool {
+        if max_per_second == 0 {
+            return true;
+        }
+        let mut windows = self.windows.lock();
+        if windows.len() <= index {
+            windows.resize(index + 1, (0, 0));
+        }
+        let (second, count) = &mut windows[index];
+        if *second != now_secs {
+            *second = now_secs;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= max_per_second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{PublishMessage, SetMessage, SignalType, Value};
+
+    fn set(address: &str) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(1.0),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    fn publish(address: &str) -> Message {
+        Message::Publish(PublishMessage {
+            address: address.to_string(),
+            signal: Some(SignalType::Gesture),
+            value: None,
+            payload: None,
+            samples: None,
+            rate: None,
+            id: Some(1),
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        })
+    }
+
+    #[test]
+    fn test_first_matching_rule_applies() {
+        let policy = RateLimitPolicy::new(vec![
+            RateLimitRule::new(10)
+                .types(&["publish"])
+                .prefix("/gesture/"),
+            RateLimitRule::new(20).types(&["SET", "PUBLISH"]),
+        ]);
+
+        let (index, rule) = policy.rule_for(&publish("/gesture/pad/1")).unwrap();
+        assert_eq!((index, rule.max_per_second), (0, 10));
+        assert_eq!(policy.rule_for(&publish("/audio/level")).unwrap().0, 1);
+        assert_eq!(policy.rule_for(&set("/gesture/pad/1")).unwrap().0, 1);
+        assert!(policy.rule_for(&Message::Ping).is_none());
+        assert!(RateLimitPolicy::default().rule_for(&set("/a")).is_none());
+    }
+
+    #[test]
+    fn test_windows_count_per_rule_and_second() {
+        let windows = RuleWindows::default();
+        assert!(windows.check(1, 2, 100));
+        assert!(windows.check(1, 2, 100));
+        assert!(!windows.check(1, 2, 100));
+        // Other rules have their own counts
+        assert!(windows.check(0, 2, 100));
+        // A new second starts over
+        assert!(windows.check(1, 2, 101));
+        assert!(windows.check(1, 0, 101));
+    }
+}
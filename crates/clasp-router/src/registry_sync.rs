@@ -0,0 +1,88 @@
+//! Entity registry replication over federation links
+//!
+//! The registry lives outside the router (the relay keeps it, see
+//! `clasp_registry::replication`); the router only carries its events. With
+//! a [`RegistryReplica`] installed
+//! ([`Router::set_registry_replica`](crate::Router::set_registry_replica)), a
+//! federation peer may send:
+//!
+//! - `RegistryEvent`: one signed registry event in the payload, which the
+//!   replica verifies and applies
+//! - `RequestRegistrySync`: the router answers with a `RegistryEvent` for
+//!   every event the replica holds, then a `RequestRegistrySync` of its own
+//!   so the peer sends its events back
+//!
+//! In authenticated mode the peer needs admin scope for
+//! [`REGISTRY_SCOPE_ADDRESS`]. Passing applied events on to other peers is
+//! the replica's job; [`broadcast_registry_event`] sends one to the router's
+//! federation peers.
+
+use clasp_core::{codec, FederationOp, FederationSyncMessage, Message};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::session::{Session, SessionId};
+
+/// Federation peers need admin scope for this address to replicate the
+/// registry in authenticated mode
+pub const REGISTRY_SCOPE_ADDRESS: &str = "/clasp/registry";
+
+/// Where the router hands registry events from federation peers, e.g. the
+/// relay's entity registry
+#[async_trait::async_trait]
+pub trait RegistryReplica: Send + Sync {
+    /// Verify and apply an encoded event sent by the peer on session `from`.
+    /// An event that is already known or superseded is not an error.
+    async fn apply(&self, from: &SessionId, payload: &[u8]) -> Result<(), String>;
+
+    /// Every event a peer needs to catch up, encoded
+    async fn snapshot(&self) -> Vec<Vec<u8>>;
+}
+
+/// A `RegistryEvent` message carrying an encoded event
+pub fn registry_event(payload: Vec<u8>) -> Message {
+    Message::FederationSync(FederationSyncMessage {
+        op: FederationOp::RegistryEvent,
+        patterns: vec![],
+        revisions: HashMap::new(),
+        since_revision: None,
+        origin: None,
+        payload: Some(payload),
+    })
+}
+
+/// A `RequestRegistrySync` message from router `origin`
+pub fn request_registry_sync(origin: &str) -> Message {
+    Message::FederationSync(FederationSyncMessage {
+        op: FederationOp::RequestRegistrySync,
+        patterns: vec![],
+        revisions: HashMap::new(),
+        since_revision: None,
+        origin: Some(origin.to_string()),
+        payload: None,
+    })
+}
+
+/// Send an encoded event to every federation peer except `except`. Returns
+/// how many peers it was queued for.
+pub fn broadcast_registry_event(
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    payload: &[u8],
+    except: Option<&SessionId>,
+) -> usize {
+    let Ok(bytes) = codec::encode(&registry_event(payload.to_vec())) else {
+        return 0;
+    };
+    let mut sent = 0;
+    for entry in sessions.iter() {
+        let peer = entry.value();
+        if Some(&peer.id) == except || !peer.is_federation_peer() {
+            continue;
+        }
+        if peer.try_send(bytes.clone()).is_ok() {
+            sent += 1;
+        }
+    }
+    sent
+}
@@ -0,0 +1,223 @@
+//! Paged, rate-limited journal replay
+//!
+//! A REPLAY returns at most [`ReplayConfig::page_size`] journal entries (or
+//! the request's `limit`, if smaller), oldest first, then an ACK for the
+//! pattern. When more entries match, the ACK carries a `cursor`; sending
+//! the same REPLAY again with that cursor returns the next page. Cursors
+//! are opaque to clients.
+//!
+//! With [`ReplayConfig::max_rate`] set, entries are sent to each session at
+//! no more than that many per second, shared across the session's
+//! concurrent replays, so a slow client isn't flooded.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How REPLAY answers are paged and paced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+    /// Most entries per REPLAY page (0 = unlimited)
+    pub page_size: u32,
+    /// Most replayed entries per second to one session (0 = unlimited)
+    pub max_rate: u32,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            page_size: 1000,
+            max_rate: 0,
+        }
+    }
+}
+
+impl ReplayConfig {
+    /// Entries to return for a request asking for `limit`
+    pub fn page_limit(&self, limit: Option<u32>) -> Option<u32> {
+        match (limit, self.page_size) {
+            (Some(limit), 0) => Some(limit),
+            (Some(limit), page_size) => Some(limit.min(page_size)),
+            (None, 0) => None,
+            (None, page_size) => Some(page_size),
+        }
+    }
+}
+
+/// Spaces a session's replayed entries to its replay rate
+#[derive(Debug, Default)]
+pub struct ReplayPacer {
+    next: parking_lot::Mutex<Option<Instant>>,
+}
+
+impl ReplayPacer {
+    /// Wait for the next free slot at `rate` entries per second
+    pub async fn pace(&self, rate: u32) {
+        if rate == 0 {
+            return;
+        }
+        let slot = {
+            let mut next = self.next.lock();
+            let now = Instant::now();
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + Duration::from_secs(1) / rate);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[cfg(feature = "journal")]
+/// Where a replay page ended: the last entry's timestamp, and how many
+/// entries at that timestamp were already sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ReplayCursor {
+    pub timestamp: u64,
+    pub skip: u32,
+}
+
+#[cfg(feature = "journal")]
+impl ReplayCursor {
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let (timestamp, skip) = cursor.split_once('.')?;
+        Some(Self {
+            timestamp: timestamp.parse().ok()?,
+            skip: skip.parse().ok()?,
+        })
+    }
+
+    /// The `from` to query the journal with to continue after this cursor
+    pub fn from(&self, from: Option<u64>) -> u64 {
+        self.timestamp.max(from.unwrap_or(0))
+    }
+}
+
+#[cfg(feature = "journal")]
+impl std::fmt::Display for ReplayCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.timestamp, self.skip)
+    }
+}
+
+/// Cut one page out of entries queried from `after`'s timestamp (or the
+/// request's `from`), returning it and the cursor for the next page
+///
+/// `entries` should have been queried with a limit of `after.skip +
+/// page_size + 1`, so an entry past the page shows whether more follow.
+#[cfg(feature = "journal")]
+pub(crate) fn paginate(
+    mut entries: Vec<clasp_journal::JournalEntry>,
+    after: Option<ReplayCursor>,
+    page_size: Option<u32>,
+) -> (Vec<clasp_journal::JournalEntry>, Option<ReplayCursor>) {
+    let skipped = after.map_or(0, |after| {
+        entries
+            .iter()
+            .take(after.skip as usize)
+            .take_while(|e| e.timestamp == after.timestamp)
+            .count()
+    });
+    entries.drain(..skipped);
+
+    let Some(page_size) = page_size.map(|size| size as usize) else {
+        return (entries, None);
+    };
+    if entries.len() <= page_size {
+        return (entries, None);
+    }
+    entries.truncate(page_size);
+
+    let Some(last) = entries.last().map(|e| e.timestamp) else {
+        return (entries, None);
+    };
+    let mut skip = entries
+        .iter()
+        .rev()
+        .take_while(|e| e.timestamp == last)
+        .count();
+    if after.is_some_and(|after| after.timestamp == last) {
+        skip += skipped;
+    }
+    let next = ReplayCursor {
+        timestamp: last,
+        skip: skip as u32,
+    };
+    (entries, Some(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_limit() {
+        let config = ReplayConfig::default();
+        assert_eq!(config.page_limit(None), Some(1000));
+        assert_eq!(config.page_limit(Some(10)), Some(10));
+        assert_eq!(config.page_limit(Some(5000)), Some(1000));
+
+        let unlimited = ReplayConfig {
+            page_size: 0,
+            ..Default::default()
+        };
+        assert_eq!(unlimited.page_limit(None), None);
+        assert_eq!(unlimited.page_limit(Some(5000)), Some(5000));
+    }
+
+    #[cfg(feature = "journal")]
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = ReplayCursor {
+            timestamp: 1_700_000_000_000_000,
+            skip: 2,
+        };
+        assert_eq!(ReplayCursor::parse(&cursor.to_string()), Some(cursor));
+        assert_eq!(ReplayCursor::parse("garbage"), None);
+        assert_eq!(ReplayCursor::parse("12.x"), None);
+        assert_eq!(cursor.from(Some(5)), cursor.timestamp);
+    }
+
+    #[cfg(feature = "journal")]
+    #[test]
+    fn test_paginate_across_equal_timestamps() {
+        use clasp_core::Value;
+        use clasp_journal::JournalEntry;
+
+        let journal: Vec<JournalEntry> = [10, 20, 20, 20, 30]
+            .iter()
+            .enumerate()
+            .map(|(i, &timestamp)| {
+                let mut entry = JournalEntry::from_set(
+                    format!("/a/{}", i),
+                    Value::Int(i as i64),
+                    1,
+                    "test".to_string(),
+                    timestamp,
+                );
+                entry.seq = i as u64;
+                entry
+            })
+            .collect();
+        let query = |after: Option<ReplayCursor>| {
+            let from = after.map_or(0, |after| after.from(None));
+            journal
+                .iter()
+                .filter(|e| e.timestamp >= from)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let mut seen = Vec::new();
+        let mut after = None;
+        loop {
+            let (page, next) = paginate(query(after), after, Some(2));
+            seen.extend(page.iter().map(|e| e.seq));
+            match next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec![0, 1, 2, 3, 4]);
+    }
+}
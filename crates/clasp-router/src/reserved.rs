@@ -0,0 +1,195 @@
+//! The reserved `/clasp/` namespace
+//!
+//! Addresses under `/clasp/` carry the router's own state: rules, sessions,
+//! presence, configuration. In authenticated mode a session needs admin
+//! scope for the address to SET or PUBLISH there, on top of its write
+//! scope. The check runs before any [`WriteValidator`], so an application
+//! validator can't open the namespace up by accident.
+//!
+//! [`ReservedNamespace::exceptions`] lists patterns ordinary clients may
+//! still write; by default P2P signaling (`/clasp/p2p/**`) and fleet
+//! commands (`/clasp/fleet/**`). Admin addresses the router handles itself
+//! (maintenance, tap, branches, config acknowledgements) check their own
+//! scopes first and aren't affected.
+//!
+//! Every refused write is logged on the `clasp::audit` target and published
+//! as an EVENT on [`AUDIT_ADDRESS`] carrying `session`, `subject`,
+//! `address` and `operation`.
+//!
+//! [`WriteValidator`]: crate::router::WriteValidator
+
+use clasp_core::{codec, Action, Message, PublishMessage, SecurityMode, SignalType, Value};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::session::{Session, SessionId};
+use crate::subscription::SubscriptionManager;
+
+/// Addresses under this prefix are reserved for the router
+pub const RESERVED_PREFIX: &str = "/clasp/";
+
+/// Refused writes to the reserved namespace are published on this address
+pub const AUDIT_ADDRESS: &str = "/clasp/audit/reserved";
+
+/// Who may write under [`RESERVED_PREFIX`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReservedNamespace {
+    /// Require admin scope for reserved addresses in authenticated mode
+    pub enabled: bool,
+    /// Reserved patterns any session with write scope may write
+    pub exceptions: Vec<String>,
+}
+
+impl Default for ReservedNamespace {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exceptions: vec!["/clasp/p2p/**".to_string(), "/clasp/fleet/**".to_string()],
+        }
+    }
+}
+
+impl ReservedNamespace {
+    /// Whether writing `address` requires admin scope
+    pub fn is_reserved(&self, address: &str) -> bool {
+        self.enabled
+            && address.starts_with(RESERVED_PREFIX)
+            && !self
+                .exceptions
+                .iter()
+                .any(|pattern| clasp_core::address::glob_match(pattern, address))
+    }
+
+    /// Whether `session` may write `address`
+    ///
+    /// Sessions that never authenticated (protocol adapter clients without a
+    /// token) are refused even though they carry no scope restrictions.
+    pub fn permits(&self, session: &Session, address: &str, mode: SecurityMode) -> bool {
+        mode != SecurityMode::Authenticated
+            || !self.is_reserved(address)
+            || (session.authenticated && session.has_scope(Action::Admin, address))
+    }
+}
+
+/// Whether a protocol adapter may write `address` for `session`, auditing
+/// refusals. Adapters without a namespace (`None`) permit everything.
+#[cfg(any(
+    feature = "mqtt-server",
+    feature = "osc-server",
+    feature = "resp-server"
+))]
+pub(crate) fn adapter_permits(
+    reserved: Option<&ReservedNamespace>,
+    session: &Session,
+    address: &str,
+    operation: &str,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) -> bool {
+    let Some(reserved) = reserved else {
+        return true;
+    };
+    if reserved.permits(session, address, SecurityMode::Authenticated) {
+        return true;
+    }
+    audit(session, address, operation, sessions, subscriptions);
+    false
+}
+
+/// Log a refused write and publish it on [`AUDIT_ADDRESS`]
+pub(crate) fn audit(
+    session: &Session,
+    address: &str,
+    operation: &str,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    warn!(
+        target: "clasp::audit",
+        session = %session.id,
+        subject = session.subject.as_deref().unwrap_or("-"),
+        "{} to reserved address {} denied - admin scope required",
+        operation,
+        address
+    );
+
+    let subscribers = subscriptions.find_subscribers(AUDIT_ADDRESS, Some(SignalType::Event));
+    if subscribers.is_empty() {
+        return;
+    }
+    let mut fields = HashMap::new();
+    fields.insert("session".to_string(), Value::String(session.id.clone()));
+    fields.insert(
+        "subject".to_string(),
+        session
+            .subject
+            .clone()
+            .map(Value::String)
+            .unwrap_or(Value::Null),
+    );
+    fields.insert("address".to_string(), Value::String(address.to_string()));
+    fields.insert(
+        "operation".to_string(),
+        Value::String(operation.to_string()),
+    );
+    let msg = Message::Publish(PublishMessage {
+        address: AUDIT_ADDRESS.to_string(),
+        signal: Some(SignalType::Event),
+        value: Some(Value::Map(fields)),
+        payload: None,
+        samples: None,
+        rate: None,
+        id: None,
+        phase: None,
+        timestamp: Some(clasp_core::time::now()),
+        timeline: None,
+    });
+    if let Ok(bytes) = codec::encode(&msg) {
+        crate::handlers::broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_addresses() {
+        let reserved = ReservedNamespace::default();
+        assert!(reserved.is_reserved("/clasp/rules/mute"));
+        assert!(reserved.is_reserved("/clasp/sessions/abc"));
+        assert!(!reserved.is_reserved("/clasp/p2p/signal/abc"));
+        assert!(!reserved.is_reserved("/clasp/fleet/stage/cmd"));
+        assert!(!reserved.is_reserved("/clasprules"));
+        assert!(!reserved.is_reserved("/mixer/1/level"));
+
+        let disabled = ReservedNamespace {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(!disabled.is_reserved("/clasp/rules/mute"));
+    }
+
+    #[test]
+    fn test_open_mode_not_enforced() {
+        let reserved = ReservedNamespace::default();
+        let session = Session::stub(None);
+        assert!(reserved.permits(&session, "/clasp/rules/mute", SecurityMode::Open));
+    }
+
+    #[test]
+    fn test_unauthenticated_session_refused() {
+        let reserved = ReservedNamespace::default();
+        let session = Session::stub(None);
+        assert!(!reserved.permits(&session, "/clasp/rules/mute", SecurityMode::Authenticated));
+        assert!(reserved.permits(
+            &session,
+            "/clasp/p2p/signal/abc",
+            SecurityMode::Authenticated
+        ));
+    }
+}
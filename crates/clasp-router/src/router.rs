@@ -26,20 +26,30 @@
 //! }
 //! ```
 
+use bytes::Bytes;
+use clasp_core::error::ErrorCode;
 use clasp_core::{
-    codec, CpskValidator, ErrorMessage, Message, SecurityMode, SignalType, TokenValidator,
+    codec, AsyncTokenValidator, CpskValidator, ErrorMessage, Message, SecurityMode, SignalType,
+    SyncAdapter, TokenValidator,
 };
 #[cfg(feature = "rules")]
 use clasp_core::{PublishMessage, SetMessage};
 
+#[cfg(feature = "journal")]
+use crate::journal_partition::JournalPartition;
 #[cfg(feature = "journal")]
 use clasp_journal::Journal;
 #[cfg(feature = "rules")]
 use clasp_rules::RulesEngine;
-use clasp_transport::{TransportEvent, TransportReceiver, TransportSender, TransportServer};
+use clasp_transport::{
+    ConnectionInfo, TransportEvent, TransportReceiver, TransportSender, TransportServer,
+};
 use dashmap::DashMap;
 use parking_lot::RwLock;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+#[cfg(feature = "rules")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tracing::{debug, error, info, warn, Instrument};
 
@@ -49,14 +59,40 @@ use clasp_transport::WebSocketServer;
 #[cfg(feature = "quic")]
 use clasp_transport::{QuicConfig, QuicTransport};
 
+#[cfg(feature = "webrtc")]
+use clasp_transport::{WebRtcConfig, WebRtcServer};
+
 use crate::{
+    address_map::AddressMapper,
+    aggregate::{AggregateRule, Aggregator},
+    alias::TopicAliasConfig,
+    auth::{ValidationCache, ValidationConfig},
+    backend::StateBackend,
+    canonical::AddressPolicy,
+    cluster::{Backplane, Cluster},
+    entity_config::ConfigSource,
     error::{Result, RouterError},
+    fleet::FleetSource,
     gesture::GestureRegistry,
     handlers,
+    handoff::HandoffPolicy,
+    overload::{OverloadConfig, OverloadGuard},
     p2p::P2PCapabilities,
+    quota::{
+        self, BandwidthMeter, MeteredSender, QuotaAction, QuotaConfig, QuotaStatus,
+        SessionBandwidth,
+    },
+    rate_limit::RateLimitPolicy,
+    replay::ReplayConfig,
+    reserved::ReservedNamespace,
+    schema::SchemaMode,
     session::{Session, SessionId},
+    session_limit::SessionLimit,
+    shadow::ShadowConfig,
     state::{RouterState, RouterStateConfig},
     subscription::SubscriptionManager,
+    tap::{TapDirection, TapSender, WireTap},
+    transport_policy::TransportPolicy,
 };
 use std::time::Duration;
 
@@ -88,6 +124,10 @@ pub trait WriteValidator: Send + Sync {
 /// Called before sending the initial SNAPSHOT after WELCOME, and before sending
 /// subscription snapshots. Allows the application to strip sensitive fields
 /// or restrict visibility of certain paths.
+///
+/// Filters that edit values should leave E2E envelopes
+/// ([`Value::is_e2e_envelope`](clasp_core::Value::is_e2e_envelope)) whole:
+/// removing a field from one makes it undecryptable.
 pub trait SnapshotFilter: Send + Sync {
     /// Filter a snapshot before delivery to a session.
     ///
@@ -108,6 +148,7 @@ pub trait SnapshotFilter: Send + Sync {
 ///
 /// Transforms are matched by address pattern and applied in order.
 /// Used by LensVM to run WASM transforms on the router's hot path.
+/// E2E encrypted values skip transforms.
 pub trait SignalTransform: Send + Sync {
     /// Transform a value for the given address. Return None to pass through unchanged.
     fn transform(&self, address: &str, value: &clasp_core::Value) -> Option<clasp_core::Value>;
@@ -116,6 +157,16 @@ pub trait SignalTransform: Send + Sync {
 /// Timeout for clients to complete the handshake (send Hello message)
 const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Correlation id for messages handled with `Router::handle_local`, so every
+/// request gets an explicit reply
+const LOCAL_CORRELATION_ID: u32 = 1;
+
+/// Remote address reported for in-process sessions without a connection
+const LOCAL_ADDR: SocketAddr = SocketAddr::V4(std::net::SocketAddrV4::new(
+    std::net::Ipv4Addr::LOCALHOST,
+    0,
+));
+
 /// Transport configuration for multi-transport serving.
 ///
 /// Use with `Router::serve_multi()` to run multiple transports simultaneously.
@@ -141,6 +192,13 @@ pub enum TransportConfig {
         /// TLS private key (DER format)
         key: Vec<u8>,
     },
+
+    /// WebRTC data channels, signaled over HTTP (see [`Router::serve_webrtc`])
+    #[cfg(feature = "webrtc")]
+    WebRtc {
+        /// Signaling listen address, e.g., "0.0.0.0:7332"
+        addr: String,
+    },
 }
 
 /// Multi-protocol server configuration.
@@ -173,10 +231,19 @@ pub struct MultiProtocolConfig {
     #[cfg(feature = "websocket")]
     pub websocket_addr: Option<String>,
 
+    /// `SO_REUSEPORT` acceptors for the WebSocket listener (0 or 1 = a single
+    /// listener, see [`Router::serve_websocket_acceptors`])
+    #[cfg(feature = "websocket")]
+    pub websocket_acceptors: usize,
+
     /// QUIC configuration
     #[cfg(feature = "quic")]
     pub quic: Option<QuicServerConfig>,
 
+    /// WebRTC signaling listen address (e.g., "0.0.0.0:7332")
+    #[cfg(feature = "webrtc")]
+    pub webrtc_addr: Option<String>,
+
     /// MQTT server configuration
     #[cfg(feature = "mqtt-server")]
     pub mqtt: Option<crate::adapters::MqttServerConfig>,
@@ -184,6 +251,14 @@ pub struct MultiProtocolConfig {
     /// OSC server configuration
     #[cfg(feature = "osc-server")]
     pub osc: Option<crate::adapters::OscServerConfig>,
+
+    /// Redis protocol (RESP) server configuration
+    #[cfg(feature = "resp-server")]
+    pub resp: Option<crate::adapters::RespServerConfig>,
+
+    /// Prometheus `/metrics` listen address (see [`crate::prometheus`])
+    #[cfg(feature = "metrics")]
+    pub metrics_addr: Option<String>,
 }
 
 /// QUIC server configuration
@@ -198,6 +273,39 @@ pub struct QuicServerConfig {
     pub key: Vec<u8>,
 }
 
+/// Handle for swapping the QUIC listener's certificate at runtime
+///
+/// Get one with [`Router::quic_certificates`]. A reload applies to
+/// handshakes that start afterwards; connections already open keep going,
+/// so a renewed certificate can be installed without a restart.
+#[cfg(feature = "quic")]
+#[derive(Clone, Default)]
+pub struct QuicCertificates {
+    server: Arc<RwLock<Option<Arc<QuicTransport>>>>,
+}
+
+#[cfg(feature = "quic")]
+impl QuicCertificates {
+    /// Whether a QUIC listener is running
+    pub fn is_serving(&self) -> bool {
+        self.server.read().is_some()
+    }
+
+    /// Install a new certificate and key (DER format) on the listener
+    ///
+    /// On error the listener keeps its current certificate.
+    pub fn reload(&self, cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<()> {
+        let server = self
+            .server
+            .read()
+            .clone()
+            .ok_or_else(|| RouterError::Config("QUIC server is not running".to_string()))?;
+        server
+            .reload_certificate(cert_der, key_der)
+            .map_err(RouterError::Transport)
+    }
+}
+
 /// Router configuration
 #[derive(Debug, Clone)]
 pub struct RouterConfig {
@@ -221,8 +329,45 @@ pub struct RouterConfig {
     pub max_messages_per_second: u32,
     /// Enable rate limiting
     pub rate_limiting_enabled: bool,
+    /// Limits by message type and address prefix (see [`crate::rate_limit`])
+    pub rate_limits: RateLimitPolicy,
     /// State store configuration (TTL, limits)
     pub state_config: RouterStateConfig,
+    /// What happens when a session authenticates with a subject that
+    /// already has a session (see [`crate::handoff`])
+    pub session_handoff: HandoffPolicy,
+    /// Concurrent sessions allowed per subject (see [`crate::session_limit`])
+    pub session_limit: SessionLimit,
+    /// Seconds a durable session's subscriptions are kept after it
+    /// disconnects (0 = durable sessions off, see [`crate::durable_session`])
+    pub durable_session_ttl: u64,
+    /// HELLO token validation timeout and cache (see [`crate::auth`])
+    pub validation: ValidationConfig,
+    /// When to defer HELLO during reconnect storms (see [`crate::overload`])
+    pub overload: OverloadConfig,
+    /// Per-session bandwidth quotas (see [`crate::quota`])
+    pub quota: QuotaConfig,
+    /// Topic aliases for hot addresses (see [`crate::alias`])
+    pub topic_aliases: TopicAliasConfig,
+    /// Desired vs reported device state (see [`crate::shadow`])
+    pub shadow: ShadowConfig,
+    /// Publish who's online under `/clasp/presence/` (see [`crate::presence`])
+    pub presence: bool,
+    /// What clients may do, by transport name (see [`crate::transport_policy`])
+    pub transport_policies: HashMap<String, TransportPolicy>,
+    /// Addresses whose writes are aggregated (see [`crate::aggregate`])
+    pub aggregates: Vec<AggregateRule>,
+    /// Check SETs against announced param schemas (see [`crate::schema`])
+    pub schema: SchemaMode,
+    /// How inbound addresses are canonicalized (see [`crate::canonical`])
+    pub address_policy: AddressPolicy,
+    /// Page size and per-session rate of REPLAY answers (see [`crate::replay`])
+    pub replay: ReplayConfig,
+    /// Who may write under `/clasp/` (see [`crate::reserved`])
+    pub reserved: ReservedNamespace,
+    /// Count deliveries per subscription for `/clasp/stats` (see
+    /// [`crate::delivery_stats`])
+    pub delivery_stats: bool,
 }
 
 impl Default for RouterConfig {
@@ -235,6 +380,7 @@ impl Default for RouterConfig {
                 "stream".to_string(),
                 "timeline".to_string(),
                 "gesture".to_string(),
+                clasp_core::E2E_FEATURE.to_string(),
             ],
             max_sessions: 100,
             session_timeout: 300,
@@ -244,7 +390,24 @@ impl Default for RouterConfig {
             gesture_coalesce_interval_ms: 16,
             max_messages_per_second: 1000, // 1000 msgs/sec default
             rate_limiting_enabled: true,
+            rate_limits: RateLimitPolicy::default(),
             state_config: RouterStateConfig::default(), // 1 hour TTL by default
+            session_handoff: HandoffPolicy::Off,
+            session_limit: SessionLimit::default(), // unlimited
+            durable_session_ttl: 0,
+            validation: ValidationConfig::default(),
+            overload: OverloadConfig::default(), // off
+            quota: QuotaConfig::default(),       // unlimited
+            topic_aliases: TopicAliasConfig::default(),
+            shadow: ShadowConfig::default(), // off
+            presence: false,
+            transport_policies: HashMap::new(), // unrestricted
+            aggregates: Vec::new(),
+            schema: SchemaMode::Off,
+            address_policy: AddressPolicy::default(),
+            replay: ReplayConfig::default(),
+            reserved: ReservedNamespace::default(), // admin only
+            delivery_stats: false,
         }
     }
 }
@@ -290,6 +453,93 @@ impl RouterConfigBuilder {
         self
     }
 
+    pub fn session_handoff(mut self, policy: HandoffPolicy) -> Self {
+        self.config.session_handoff = policy;
+        self
+    }
+
+    pub fn session_limit(mut self, limit: SessionLimit) -> Self {
+        self.config.session_limit = limit;
+        self
+    }
+
+    pub fn durable_session_ttl(mut self, secs: u64) -> Self {
+        self.config.durable_session_ttl = secs;
+        self
+    }
+
+    pub fn validation(mut self, validation: ValidationConfig) -> Self {
+        self.config.validation = validation;
+        self
+    }
+
+    pub fn overload(mut self, overload: OverloadConfig) -> Self {
+        self.config.overload = overload;
+        self
+    }
+
+    pub fn quota(mut self, quota: QuotaConfig) -> Self {
+        self.config.quota = quota;
+        self
+    }
+
+    pub fn topic_aliases(mut self, topic_aliases: TopicAliasConfig) -> Self {
+        self.config.topic_aliases = topic_aliases;
+        self
+    }
+
+    pub fn rate_limits(mut self, rate_limits: RateLimitPolicy) -> Self {
+        self.config.rate_limits = rate_limits;
+        self
+    }
+
+    pub fn shadow(mut self, shadow: ShadowConfig) -> Self {
+        self.config.shadow = shadow;
+        self
+    }
+
+    pub fn presence(mut self, enabled: bool) -> Self {
+        self.config.presence = enabled;
+        self
+    }
+
+    pub fn transport_policy(mut self, transport: &str, policy: TransportPolicy) -> Self {
+        self.config
+            .transport_policies
+            .insert(transport.to_string(), policy);
+        self
+    }
+
+    pub fn address_policy(mut self, policy: AddressPolicy) -> Self {
+        self.config.address_policy = policy;
+        self
+    }
+
+    pub fn replay(mut self, replay: ReplayConfig) -> Self {
+        self.config.replay = replay;
+        self
+    }
+
+    pub fn reserved(mut self, reserved: ReservedNamespace) -> Self {
+        self.config.reserved = reserved;
+        self
+    }
+
+    pub fn delivery_stats(mut self, enabled: bool) -> Self {
+        self.config.delivery_stats = enabled;
+        self
+    }
+
+    pub fn aggregate(mut self, rule: AggregateRule) -> Self {
+        self.config.aggregates.push(rule);
+        self
+    }
+
+    pub fn schema(mut self, mode: SchemaMode) -> Self {
+        self.config.schema = mode;
+        self
+    }
+
     pub fn build(self) -> RouterConfig {
         self.config
     }
@@ -308,19 +558,48 @@ pub struct Router {
     running: Arc<RwLock<bool>>,
     /// Token validator (None = always reject in authenticated mode)
     token_validator: Option<Arc<dyn TokenValidator>>,
+    /// Token validator awaited by the HELLO handler
+    async_validator: Option<Arc<dyn AsyncTokenValidator>>,
+    /// Accepted tokens (see [`crate::auth`])
+    validation_cache: Arc<ValidationCache>,
     /// P2P capabilities tracker
     p2p_capabilities: Arc<P2PCapabilities>,
     /// Gesture registry for move coalescing
     gesture_registry: Option<Arc<GestureRegistry>>,
+    /// Aggregated writes (see [`crate::aggregate`])
+    aggregator: Option<Arc<Aggregator>>,
     /// Application-specific write validator
     write_validator: Option<Arc<dyn WriteValidator>>,
     /// Application-specific snapshot filter
     snapshot_filter: Option<Arc<dyn SnapshotFilter>>,
+    /// Address translation per protocol adapter (see [`crate::address_map`])
+    address_mappers: HashMap<String, Arc<dyn AddressMapper>>,
+    /// Per-entity configuration documents (see [`crate::entity_config`])
+    config_source: Option<Arc<dyn ConfigSource>>,
+    /// Fleet membership for `/clasp/fleet/` fan-out (see [`crate::fleet`])
+    fleet_source: Option<Arc<dyn FleetSource>>,
+    /// Entity registry replicated over federation links (see [`crate::registry_sync`])
+    #[cfg(feature = "federation")]
+    registry_replica: Option<Arc<dyn crate::registry_sync::RegistryReplica>>,
     /// Signal transform pipeline for SET values
     transforms: Option<Arc<dyn SignalTransform>>,
     /// Rules engine for server-side automation
     #[cfg(feature = "rules")]
     rules_engine: Option<Arc<parking_lot::Mutex<RulesEngine>>>,
+    /// Whether the OnSchedule rule task is running (one per router, even
+    /// when serving several transports)
+    #[cfg(feature = "rules")]
+    rule_scheduler_started: Arc<AtomicBool>,
+    /// Whether the OnInterval rule task is running
+    #[cfg(feature = "rules")]
+    interval_rules_started: Arc<AtomicBool>,
+    /// Runtime-controlled frame capture
+    tap: Arc<WireTap>,
+    /// Accept rate and in-flight handshakes (see [`crate::overload`])
+    overload: Arc<OverloadGuard>,
+    /// Certificate of the running QUIC listener
+    #[cfg(feature = "quic")]
+    quic_certificates: QuicCertificates,
 }
 
 impl Router {
@@ -334,34 +613,79 @@ impl Router {
             None
         };
 
+        let aggregator = (!config.aggregates.is_empty())
+            .then(|| Arc::new(Aggregator::new(config.aggregates.clone())));
+
         let state = Arc::new(RouterState::with_config(config.state_config.clone()));
+        let sessions = Arc::new(DashMap::new());
+        let subscriptions = Arc::new(SubscriptionManager::new());
+        let tap = Arc::new(WireTap::new(
+            Arc::clone(&sessions),
+            Arc::clone(&subscriptions),
+        ));
+
+        let overload = Arc::new(OverloadGuard::new(config.overload));
 
         Self {
             config,
-            sessions: Arc::new(DashMap::new()),
-            subscriptions: Arc::new(SubscriptionManager::new()),
+            sessions,
+            subscriptions,
             state,
             running: Arc::new(RwLock::new(false)),
             token_validator: None,
+            async_validator: None,
+            validation_cache: Arc::new(ValidationCache::default()),
             p2p_capabilities: Arc::new(P2PCapabilities::new()),
             gesture_registry,
+            aggregator,
             write_validator: None,
             snapshot_filter: None,
+            address_mappers: HashMap::new(),
+            config_source: None,
+            fleet_source: None,
+            #[cfg(feature = "federation")]
+            registry_replica: None,
             transforms: None,
             #[cfg(feature = "rules")]
             rules_engine: None,
+            #[cfg(feature = "rules")]
+            rule_scheduler_started: Arc::new(AtomicBool::new(false)),
+            #[cfg(feature = "rules")]
+            interval_rules_started: Arc::new(AtomicBool::new(false)),
+            tap,
+            overload,
+            #[cfg(feature = "quic")]
+            quic_certificates: QuicCertificates::default(),
         }
     }
 
     /// Create a router with a token validator for authenticated mode
     pub fn with_validator<V: TokenValidator + 'static>(mut self, validator: V) -> Self {
-        self.token_validator = Some(Arc::new(validator));
+        self.set_validator(validator);
         self
     }
 
     /// Set the token validator
+    ///
+    /// Also used for HELLO (through [`SyncAdapter`]) unless an async
+    /// validator is set afterwards with [`Router::set_async_validator`].
     pub fn set_validator<V: TokenValidator + 'static>(&mut self, validator: V) {
-        self.token_validator = Some(Arc::new(validator));
+        let validator: Arc<dyn TokenValidator> = Arc::new(validator);
+        self.async_validator = Some(Arc::new(SyncAdapter(Arc::clone(&validator))));
+        self.token_validator = Some(validator);
+    }
+
+    /// Set the validator awaited by the HELLO handler
+    ///
+    /// Protocol adapters (MQTT, OSC, RESP) keep using the sync validator from
+    /// [`Router::set_validator`], so set that too in authenticated mode.
+    pub fn set_async_validator<V: AsyncTokenValidator + 'static>(&mut self, validator: V) {
+        self.async_validator = Some(Arc::new(validator));
+    }
+
+    /// Forget all cached token validations (e.g. after revoking tokens)
+    pub fn clear_validation_cache(&self) {
+        self.validation_cache.clear();
     }
 
     /// Set the write validator for application-specific authorization
@@ -384,6 +708,44 @@ impl Router {
         self.snapshot_filter = Some(filter);
     }
 
+    /// Translate addresses of one protocol adapter ("mqtt" or "osc") to and
+    /// from the router's naming scheme (see [`crate::address_map`])
+    pub fn set_address_mapper(&mut self, transport: &str, mapper: Arc<dyn AddressMapper>) {
+        self.address_mappers.insert(transport.to_string(), mapper);
+    }
+
+    /// Set the source of per-entity configuration documents delivered at
+    /// `/clasp/config/{entity_id}` (see [`crate::entity_config`])
+    pub fn set_config_source<S: ConfigSource + 'static>(&mut self, source: S) {
+        self.config_source = Some(Arc::new(source));
+    }
+
+    /// Set the config source from a pre-wrapped `Arc` (for library embedding).
+    pub fn set_config_source_arc(&mut self, source: Arc<dyn ConfigSource>) {
+        self.config_source = Some(source);
+    }
+
+    /// Set the fleet membership source used to fan out PUBLISH messages
+    /// under `/clasp/fleet/{name}/` (see [`crate::fleet`])
+    pub fn set_fleet_source<S: FleetSource + 'static>(&mut self, source: S) {
+        self.fleet_source = Some(Arc::new(source));
+    }
+
+    /// Set the fleet source from a pre-wrapped `Arc` (for library embedding).
+    pub fn set_fleet_source_arc(&mut self, source: Arc<dyn FleetSource>) {
+        self.fleet_source = Some(source);
+    }
+
+    /// Set the entity registry that federation peers replicate (see
+    /// [`crate::registry_sync`])
+    #[cfg(feature = "federation")]
+    pub fn set_registry_replica(
+        &mut self,
+        replica: Arc<dyn crate::registry_sync::RegistryReplica>,
+    ) {
+        self.registry_replica = Some(replica);
+    }
+
     /// Add a signal transform pipeline for processing SET values.
     ///
     /// Transforms run after write validation and before state storage.
@@ -402,6 +764,83 @@ impl Router {
         // We need to recreate the state with journal support
         let mut state = RouterState::with_config(self.config.state_config.clone());
         state.set_journal(journal);
+        for partition in self.state.journal_partitions() {
+            state.add_journal_partition(partition.clone());
+        }
+        if let Some(backend) = self.state.backend() {
+            state.set_backend(Arc::clone(backend));
+        }
+        if let Some(cluster) = self.state.cluster() {
+            state.set_cluster(Arc::clone(cluster));
+        }
+        self.state = Arc::new(state);
+        self
+    }
+
+    /// Create a router that journals addresses matching the partition's
+    /// pattern to its own journal (see [`crate::journal_partition`]).
+    ///
+    /// Partitions are matched in the order they are added; other addresses
+    /// go to the journal from [`Router::with_journal`], if any.
+    #[cfg(feature = "journal")]
+    pub fn with_journal_partition(mut self, partition: JournalPartition) -> Self {
+        let mut state = RouterState::with_config(self.config.state_config.clone());
+        if let Some(journal) = self.state.journal() {
+            state.set_journal(Arc::clone(journal));
+        }
+        for existing in self.state.journal_partitions() {
+            state.add_journal_partition(existing.clone());
+        }
+        state.add_journal_partition(partition);
+        if let Some(backend) = self.state.backend() {
+            state.set_backend(Arc::clone(backend));
+        }
+        if let Some(cluster) = self.state.cluster() {
+            state.set_cluster(Arc::clone(cluster));
+        }
+        self.state = Arc::new(state);
+        self
+    }
+
+    /// Create a router whose param state and signal registry write through
+    /// to a durable backend.
+    ///
+    /// Call [`RouterState::restore_from_backend`] on [`Router::state`] before
+    /// serving to load what was stored on a previous run.
+    pub fn with_state_backend(mut self, backend: Arc<dyn StateBackend>) -> Self {
+        let mut state = RouterState::with_config(self.config.state_config.clone());
+        #[cfg(feature = "journal")]
+        if let Some(journal) = self.state.journal() {
+            state.set_journal(Arc::clone(journal));
+        }
+        #[cfg(feature = "journal")]
+        for partition in self.state.journal_partitions() {
+            state.add_journal_partition(partition.clone());
+        }
+        state.set_backend(backend);
+        if let Some(cluster) = self.state.cluster() {
+            state.set_cluster(Arc::clone(cluster));
+        }
+        self.state = Arc::new(state);
+        self
+    }
+
+    /// Create a router that shares param state and fan-out with the other
+    /// routers on `backplane` (see [`crate::cluster`]).
+    pub fn with_cluster(mut self, backplane: Arc<dyn Backplane>) -> Self {
+        let mut state = RouterState::with_config(self.config.state_config.clone());
+        #[cfg(feature = "journal")]
+        if let Some(journal) = self.state.journal() {
+            state.set_journal(Arc::clone(journal));
+        }
+        #[cfg(feature = "journal")]
+        for partition in self.state.journal_partitions() {
+            state.add_journal_partition(partition.clone());
+        }
+        if let Some(backend) = self.state.backend() {
+            state.set_backend(Arc::clone(backend));
+        }
+        state.set_cluster(Arc::new(Cluster::new(backplane)));
         self.state = Arc::new(state);
         self
     }
@@ -416,7 +855,8 @@ impl Router {
         self
     }
 
-    /// Get the rules engine interval rules for spawning timer tasks.
+    /// Get the rules engine, e.g. to enable or disable rules at runtime.
+    /// `serve_*` fires OnInterval and OnSchedule rules itself.
     #[cfg(feature = "rules")]
     pub fn rules_engine(&self) -> Option<&Arc<parking_lot::Mutex<RulesEngine>>> {
         self.rules_engine.as_ref()
@@ -457,13 +897,58 @@ impl Router {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn serve_on<S>(&self, mut server: S) -> Result<()>
+    pub async fn serve_on<S>(&self, server: S) -> Result<()>
     where
         S: TransportServer + 'static,
         S::Sender: 'static,
         S::Receiver: 'static,
     {
         info!("Router accepting connections");
+        self.start_background_tasks();
+        self.accept_connections(server, 0).await
+    }
+
+    /// Serve on several listeners of the same transport at once, e.g. the
+    /// `SO_REUSEPORT` listeners from [`WebSocketServer::bind_reuseport`].
+    ///
+    /// Each listener gets its own accept loop; background tasks are started
+    /// once. Accepts are counted per listener in
+    /// `clasp_accepts_total{transport, acceptor}` (`metrics` feature).
+    /// Returns when the first loop ends.
+    pub async fn serve_on_all<S>(&self, servers: Vec<S>) -> Result<()>
+    where
+        S: TransportServer + 'static,
+        S::Sender: 'static,
+        S::Receiver: 'static,
+    {
+        if servers.is_empty() {
+            return Err(RouterError::Config("No listeners to serve on".into()));
+        }
+
+        info!(
+            "Router accepting connections on {} acceptors",
+            servers.len()
+        );
+        self.start_background_tasks();
+
+        let handles: Vec<_> = servers
+            .into_iter()
+            .enumerate()
+            .map(|(acceptor, server)| {
+                let router = self.clone_internal();
+                tokio::spawn(async move { router.accept_connections(server, acceptor).await })
+            })
+            .collect();
+
+        let (result, _, remaining) = futures::future::select_all(handles).await;
+        for handle in remaining {
+            handle.abort();
+        }
+        result.map_err(|e| RouterError::Config(format!("acceptor task failed: {}", e)))?
+    }
+
+    /// Mark the router running and start its background tasks
+    fn start_background_tasks(&self) {
         *self.running.write() = true;
 
         // Start session cleanup task if timeout is configured
@@ -479,9 +964,56 @@ impl Router {
         // Start state cleanup task (removes stale params and signals)
         self.start_state_cleanup_task();
 
+        // Start writing aggregates if any addresses are aggregated
+        if let Some(ref aggregator) = self.aggregator {
+            self.start_aggregate_task(Arc::clone(aggregator));
+        }
+
+        // Start sampling load if it may be shed
+        self.start_load_sampling_task();
+
+        // Start the scheduler for OnSchedule rules
+        #[cfg(feature = "rules")]
+        self.start_rule_scheduler_task();
+
+        // Start the timer for OnInterval rules
+        #[cfg(feature = "rules")]
+        self.start_interval_rule_task();
+
+        // Start journal partition retention
+        #[cfg(feature = "journal")]
+        self.start_journal_retention_task();
+
+        // Start withdrawing unfulfilled shadow desires
+        self.start_shadow_expiry_task();
+
+        // Start exchanging state with the rest of the cluster
+        self.start_cluster_task();
+    }
+
+    /// Accept connections from `server` until the router stops
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    async fn accept_connections<S>(&self, mut server: S, acceptor: usize) -> Result<()>
+    where
+        S: TransportServer + 'static,
+        S::Sender: 'static,
+        S::Receiver: 'static,
+    {
+        #[cfg(feature = "metrics")]
+        let acceptor = acceptor.to_string();
+
         while *self.running.read() {
-            match server.accept().await {
-                Ok((sender, receiver, addr)) => {
+            match server.accept_with_info().await {
+                Ok((sender, receiver, connection)) => {
+                    let addr = connection.remote_addr;
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!(
+                        "clasp_accepts_total",
+                        "transport" => server.protocol(),
+                        "acceptor" => acceptor.clone()
+                    )
+                    .increment(1);
+
                     // Enforce max_sessions limit
                     let current_sessions = self.sessions.len();
                     if current_sessions >= self.config.max_sessions {
@@ -496,9 +1028,16 @@ impl Router {
                     info!("New connection from {}", addr);
                     #[cfg(feature = "metrics")]
                     metrics::gauge!("clasp_sessions_active").increment(1.0);
-                    self.handle_connection(Arc::new(sender), receiver, addr);
+                    self.handle_connection(Arc::new(sender), receiver, connection);
                 }
                 Err(e) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!(
+                        "clasp_accept_errors_total",
+                        "transport" => server.protocol(),
+                        "acceptor" => acceptor.clone()
+                    )
+                    .increment(1);
                     warn!("Accept error: {}", e);
                 }
             }
@@ -557,12 +1096,80 @@ impl Router {
         });
     }
 
+    /// Start background task that samples load for shedding
+    fn start_load_sampling_task(&self) {
+        if !self.config.overload.sheds_load() || !self.overload.start_sampling() {
+            return;
+        }
+
+        let overload = Arc::clone(&self.overload);
+        let sessions = Arc::clone(&self.sessions);
+        let running = Arc::clone(&self.running);
+
+        tokio::spawn(async move {
+            loop {
+                let asleep = tokio::time::Instant::now();
+                tokio::time::sleep(crate::overload::SAMPLE_INTERVAL).await;
+                let loop_lag = asleep
+                    .elapsed()
+                    .saturating_sub(crate::overload::SAMPLE_INTERVAL);
+
+                if !*running.read() {
+                    break;
+                }
+
+                let queued = sessions
+                    .iter()
+                    .filter_map(|entry| entry.value().queued())
+                    .sum();
+                overload.sample(queued, loop_lag, std::time::Instant::now());
+            }
+
+            overload.sampling_stopped();
+            debug!("Load sampling task stopped");
+        });
+    }
+
+    /// Start background task that writes due aggregates
+    fn start_aggregate_task(&self, aggregator: Arc<Aggregator>) {
+        if !aggregator.start() {
+            return;
+        }
+
+        let state = Arc::clone(&self.state);
+        let sessions = Arc::clone(&self.sessions);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let running = Arc::clone(&self.running);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(aggregator.tick());
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+            loop {
+                ticker.tick().await;
+
+                if !*running.read() {
+                    break;
+                }
+
+                for (address, value) in aggregator.due(std::time::Instant::now()) {
+                    crate::aggregate::write(&address, value, &state, &sessions, &subscriptions);
+                }
+            }
+
+            aggregator.stopped();
+            debug!("Aggregate task stopped");
+        });
+    }
+
     /// Start background task to clean up timed-out sessions
     fn start_session_cleanup_task(&self) {
         let sessions = Arc::clone(&self.sessions);
         let subscriptions = Arc::clone(&self.subscriptions);
+        let state = Arc::clone(&self.state);
         let running = Arc::clone(&self.running);
         let timeout_secs = self.config.session_timeout;
+        let durable_session_ttl = self.config.durable_session_ttl;
 
         tokio::spawn(async move {
             let check_interval = std::time::Duration::from_secs(timeout_secs / 4)
@@ -570,78 +1177,341 @@ impl Router {
             let timeout = std::time::Duration::from_secs(timeout_secs);
 
             loop {
-                tokio::time::sleep(check_interval).await;
+                tokio::time::sleep(check_interval).await;
+
+                if !*running.read() {
+                    break;
+                }
+
+                // Find and remove timed-out sessions
+                let timed_out: Vec<SessionId> = sessions
+                    .iter()
+                    .filter(|entry| entry.value().idle_duration() > timeout)
+                    .map(|entry| entry.key().clone())
+                    .collect();
+
+                for session_id in timed_out {
+                    if let Some((id, session)) = sessions.remove(&session_id) {
+                        info!(
+                            "Session {} timed out after {:?} idle",
+                            id,
+                            session.idle_duration()
+                        );
+                        crate::durable_session::suspend(
+                            &session,
+                            &state,
+                            &subscriptions,
+                            durable_session_ttl,
+                        )
+                        .await;
+                        subscriptions.remove_session(&id);
+                        handlers::release_session_state(&id, &state, &sessions, &subscriptions);
+                        #[cfg(feature = "federation")]
+                        handlers::federation::release_federation_routes(
+                            &session,
+                            &sessions,
+                            &subscriptions,
+                        );
+                    }
+                }
+            }
+
+            debug!("Session cleanup task stopped");
+        });
+    }
+
+    /// Start background task to clean up stale state entries
+    fn start_state_cleanup_task(&self) {
+        let state = Arc::clone(&self.state);
+        let running = Arc::clone(&self.running);
+        #[cfg(feature = "metrics")]
+        let sessions = Arc::clone(&self.sessions);
+        #[cfg(feature = "metrics")]
+        let subscriptions = Arc::clone(&self.subscriptions);
+
+        tokio::spawn(async move {
+            // Clean up every 60 seconds
+            let cleanup_interval = std::time::Duration::from_secs(60);
+
+            loop {
+                tokio::time::sleep(cleanup_interval).await;
+
+                if !*running.read() {
+                    break;
+                }
+
+                // Run cleanup on state store
+                let (params_removed, signals_removed) = state.cleanup_stale();
+
+                if params_removed > 0 || signals_removed > 0 {
+                    debug!(
+                        "State cleanup: removed {} stale params, {} stale signals",
+                        params_removed, signals_removed
+                    );
+                }
+
+                // Update absolute gauge values periodically
+                #[cfg(feature = "metrics")]
+                {
+                    metrics::gauge!("clasp_state_params_active").set(state.len() as f64);
+                    metrics::gauge!("clasp_signals_registered").set(state.signal_count() as f64);
+                    metrics::gauge!("clasp_sessions_active").set(sessions.len() as f64);
+                    metrics::gauge!("clasp_subscriptions_active").set(subscriptions.len() as f64);
+                }
+            }
+
+            debug!("State cleanup task stopped");
+        });
+    }
+
+    /// Start background task that withdraws shadow desires past their TTL
+    fn start_shadow_expiry_task(&self) {
+        if !self.config.shadow.enabled || self.config.shadow.desired_ttl.is_none() {
+            return;
+        }
+
+        let state = Arc::clone(&self.state);
+        let sessions = Arc::clone(&self.sessions);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let running = Arc::clone(&self.running);
+
+        tokio::spawn(async move {
+            let expiry_interval = Duration::from_secs(1);
+
+            loop {
+                tokio::time::sleep(expiry_interval).await;
+
+                if !*running.read() {
+                    break;
+                }
+
+                state.shadows().expire(
+                    std::time::Instant::now(),
+                    &state,
+                    &sessions,
+                    &subscriptions,
+                );
+            }
+
+            debug!("Shadow expiry task stopped");
+        });
+    }
+
+    /// Start relaying writes to and from the other routers of the cluster
+    fn start_cluster_task(&self) {
+        if let Some(cluster) = self.state.cluster() {
+            cluster.start(
+                Arc::clone(&self.state),
+                Arc::clone(&self.sessions),
+                Arc::clone(&self.subscriptions),
+                self.config.shadow,
+                Arc::clone(&self.running),
+            );
+        }
+    }
+
+    /// Start background task that trims journal partitions to their retention
+    #[cfg(feature = "journal")]
+    fn start_journal_retention_task(&self) {
+        if self
+            .state
+            .journal_partitions()
+            .iter()
+            .all(|p| p.retention.is_unbounded())
+        {
+            return;
+        }
+
+        let state = Arc::clone(&self.state);
+        let running = Arc::clone(&self.running);
+
+        tokio::spawn(async move {
+            let retention_interval = Duration::from_secs(60);
+
+            loop {
+                tokio::time::sleep(retention_interval).await;
+
+                if !*running.read() {
+                    break;
+                }
+
+                let removed = state.enforce_journal_retention().await;
+                if removed > 0 {
+                    debug!("Journal retention: removed {} entries", removed);
+                }
+            }
+
+            debug!("Journal retention task stopped");
+        });
+    }
+
+    /// Start background task that fires OnSchedule rules at their cron times
+    /// (host local time)
+    #[cfg(feature = "rules")]
+    fn start_rule_scheduler_task(&self) {
+        let Some(engine) = self.rules_engine.clone() else {
+            return;
+        };
+        if self.rule_scheduler_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let state = Arc::clone(&self.state);
+        let sessions = Arc::clone(&self.sessions);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let running = Arc::clone(&self.running);
+        let started = Arc::clone(&self.rule_scheduler_started);
+
+        tokio::spawn(async move {
+            // Wake at least once a minute so rules added or re-enabled at
+            // runtime are picked up
+            let max_wait = Duration::from_secs(60);
+            let mut last = chrono::Local::now().naive_local();
+
+            loop {
+                let next = engine.lock().next_scheduled(last);
+                let wait = match next {
+                    Some(next) => (next - chrono::Local::now().naive_local())
+                        .to_std()
+                        .unwrap_or(Duration::ZERO)
+                        .min(max_wait),
+                    None => max_wait,
+                };
+                tokio::time::sleep(wait).await;
 
                 if !*running.read() {
                     break;
                 }
 
-                // Find and remove timed-out sessions
-                let timed_out: Vec<SessionId> = sessions
-                    .iter()
-                    .filter(|entry| entry.value().idle_duration() > timeout)
-                    .map(|entry| entry.key().clone())
-                    .collect();
-
-                for session_id in timed_out {
-                    if let Some((id, session)) = sessions.remove(&session_id) {
-                        info!(
-                            "Session {} timed out after {:?} idle",
-                            id,
-                            session.idle_duration()
-                        );
-                        subscriptions.remove_session(&id);
+                let now = chrono::Local::now().naive_local();
+                if now < last {
+                    // Clock moved backwards: don't replay the repeated span
+                    last = now;
+                    continue;
+                }
+                let due = engine.lock().due_scheduled(last, now);
+                last = now;
+
+                for rule_id in due {
+                    let actions = engine
+                        .lock()
+                        .evaluate_scheduled(&rule_id, |addr| state.get(addr));
+                    if !actions.is_empty() {
+                        debug!("Schedule rule {} fired at {}", rule_id, now);
+                        execute_rule_actions(actions, &state, &sessions, &subscriptions);
                     }
                 }
             }
 
-            debug!("Session cleanup task stopped");
+            started.store(false, Ordering::SeqCst);
+            debug!("Rule scheduler task stopped");
         });
     }
 
-    /// Start background task to clean up stale state entries
-    fn start_state_cleanup_task(&self) {
+    /// Start background task that fires OnInterval rules. Each rule first
+    /// fires one period after it is seen enabled, then every period after
+    /// that, plus a random delay of up to its jitter. Disabling a rule stops
+    /// its timer; re-enabling starts a fresh period.
+    #[cfg(feature = "rules")]
+    fn start_interval_rule_task(&self) {
+        let Some(engine) = self.rules_engine.clone() else {
+            return;
+        };
+        if self.interval_rules_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
         let state = Arc::clone(&self.state);
-        let running = Arc::clone(&self.running);
-        #[cfg(feature = "metrics")]
         let sessions = Arc::clone(&self.sessions);
-        #[cfg(feature = "metrics")]
         let subscriptions = Arc::clone(&self.subscriptions);
+        let running = Arc::clone(&self.running);
+        let started = Arc::clone(&self.interval_rules_started);
 
         tokio::spawn(async move {
-            // Clean up every 60 seconds
-            let cleanup_interval = std::time::Duration::from_secs(60);
+            use rand::Rng;
+            use tokio::time::Instant;
+
+            // Rechecked this often for rules added, removed, enabled or disabled
+            let max_wait = Duration::from_secs(1);
+            let mut next_due: HashMap<String, Instant> = HashMap::new();
+            let next_after = |from: Instant, period: Duration, jitter: Duration| {
+                let jitter_ms = jitter.as_millis() as u64;
+                let delay = if jitter_ms > 0 {
+                    rand::thread_rng().gen_range(0..=jitter_ms)
+                } else {
+                    0
+                };
+                from + period + Duration::from_millis(delay)
+            };
 
             loop {
-                tokio::time::sleep(cleanup_interval).await;
+                let now = Instant::now();
+                let rules = engine.lock().interval_rules();
+                next_due.retain(|id, _| rules.iter().any(|(rule_id, _, _)| rule_id == id));
 
-                if !*running.read() {
-                    break;
+                let mut due = Vec::new();
+                for (rule_id, period, jitter) in &rules {
+                    if period.is_zero() {
+                        continue;
+                    }
+                    let next = next_due
+                        .entry(rule_id.clone())
+                        .or_insert_with(|| next_after(now, *period, *jitter));
+                    if *next <= now {
+                        due.push(rule_id.clone());
+                        *next = next_after(now, *period, *jitter);
+                    }
                 }
 
-                // Run cleanup on state store
-                let (params_removed, signals_removed) = state.cleanup_stale();
-
-                if params_removed > 0 || signals_removed > 0 {
-                    debug!(
-                        "State cleanup: removed {} stale params, {} stale signals",
-                        params_removed, signals_removed
-                    );
+                for rule_id in due {
+                    let actions = engine
+                        .lock()
+                        .evaluate_interval(&rule_id, |addr| state.get(addr));
+                    if !actions.is_empty() {
+                        debug!("Interval rule {} fired", rule_id);
+                        execute_rule_actions(actions, &state, &sessions, &subscriptions);
+                    }
                 }
 
-                // Update absolute gauge values periodically
-                #[cfg(feature = "metrics")]
-                {
-                    metrics::gauge!("clasp_state_params_active").set(state.len() as f64);
-                    metrics::gauge!("clasp_sessions_active").set(sessions.len() as f64);
-                    metrics::gauge!("clasp_subscriptions_active").set(subscriptions.len() as f64);
+                let wait = next_due
+                    .values()
+                    .min()
+                    .map(|next| next.saturating_duration_since(Instant::now()))
+                    .unwrap_or(max_wait)
+                    .min(max_wait);
+                tokio::time::sleep(wait).await;
+
+                if !*running.read() {
+                    break;
                 }
             }
 
-            debug!("State cleanup task stopped");
+            started.store(false, Ordering::SeqCst);
+            debug!("Interval rule task stopped");
         });
     }
 
+    // =========================================================================
+    // Metrics
+    // =========================================================================
+
+    /// Serve Prometheus metrics at `http://{addr}/metrics`.
+    ///
+    /// Installs the process-wide Prometheus recorder on first use; fails
+    /// if another recorder is already installed (see [`crate::prometheus`]).
+    #[cfg(feature = "metrics")]
+    pub async fn serve_metrics(&self, addr: &str) -> Result<()> {
+        crate::prometheus::serve(
+            addr,
+            crate::prometheus::Scrape {
+                sessions: Arc::clone(&self.sessions),
+                subscriptions: Arc::clone(&self.subscriptions),
+                state: Arc::clone(&self.state),
+            },
+        )
+        .await
+    }
+
     // =========================================================================
     // WebSocket Transport
     // =========================================================================
@@ -667,6 +1537,23 @@ impl Router {
         self.serve_websocket(addr).await
     }
 
+    /// Start the router on WebSocket with `acceptors` `SO_REUSEPORT`
+    /// listeners, each with its own accept loop.
+    ///
+    /// A single accept loop also runs every WebSocket handshake, which limits
+    /// connection setup under heavy churn. Several acceptors spread that work
+    /// across the runtime's threads. Only Linux balances `SO_REUSEPORT`
+    /// listeners; elsewhere, or with `acceptors` of 0 or 1, this is
+    /// [`serve_websocket`](Self::serve_websocket).
+    #[cfg(feature = "websocket")]
+    pub async fn serve_websocket_acceptors(&self, addr: &str, acceptors: usize) -> Result<()> {
+        if acceptors <= 1 {
+            return self.serve_websocket(addr).await;
+        }
+        let servers = WebSocketServer::bind_reuseport(addr, acceptors).await?;
+        self.serve_on_all(servers).await
+    }
+
     // =========================================================================
     // QUIC Transport (feature-gated)
     // =========================================================================
@@ -694,8 +1581,25 @@ impl Router {
         cert_der: Vec<u8>,
         key_der: Vec<u8>,
     ) -> Result<()> {
-        let server = QuicTransport::new_server(addr, cert_der, key_der)
-            .map_err(|e| RouterError::Transport(e))?;
+        self.serve_quic_with_config(addr, cert_der, key_der, QuicConfig::default())
+            .await
+    }
+
+    /// Start the router on QUIC with custom transport settings.
+    ///
+    /// Use this to require client certificates (`QuicConfig::client_auth`);
+    /// the verified chain is then available to hooks through
+    /// [`Session::connection`](crate::Session::connection).
+    #[cfg(feature = "quic")]
+    pub async fn serve_quic_with_config(
+        &self,
+        addr: SocketAddr,
+        cert_der: Vec<u8>,
+        key_der: Vec<u8>,
+        config: QuicConfig,
+    ) -> Result<()> {
+        let server = QuicTransport::new_server_with_config(addr, cert_der, key_der, config)
+            .map_err(RouterError::Transport)?;
         info!("QUIC server listening on {}", addr);
         self.serve_quic_transport(server).await
     }
@@ -707,6 +1611,8 @@ impl Router {
     #[cfg(feature = "quic")]
     async fn serve_quic_transport(&self, server: QuicTransport) -> Result<()> {
         *self.running.write() = true;
+        let server = Arc::new(server);
+        *self.quic_certificates.server.write() = Some(Arc::clone(&server));
 
         while *self.running.read() {
             match server.accept().await {
@@ -717,7 +1623,8 @@ impl Router {
                     // Accept bidirectional stream for CLASP protocol
                     match connection.accept_bi().await {
                         Ok((sender, receiver)) => {
-                            self.handle_connection(Arc::new(sender), receiver, addr);
+                            let info = connection.connection_info();
+                            self.handle_connection(Arc::new(sender), receiver, info);
                         }
                         Err(e) => {
                             error!("QUIC stream accept error: {}", e);
@@ -733,6 +1640,33 @@ impl Router {
         Ok(())
     }
 
+    // =========================================================================
+    // WebRTC Transport (feature-gated)
+    // =========================================================================
+
+    /// Start the router on WebRTC data channels.
+    ///
+    /// Browsers POST an SDP offer to `http://{addr}/offer` and connect
+    /// directly to the router over the returned answer, skipping the
+    /// TCP head-of-line blocking of WebSocket. See
+    /// [`WebRtcServer`](clasp_transport::WebRtcServer) for the signaling
+    /// exchange. ICE servers are the transport's defaults (public STUN).
+    ///
+    /// Default port: 7332
+    #[cfg(feature = "webrtc")]
+    pub async fn serve_webrtc(&self, addr: &str) -> Result<()> {
+        self.serve_webrtc_with_config(addr, WebRtcConfig::default())
+            .await
+    }
+
+    /// Start the router on WebRTC with custom ICE servers
+    #[cfg(feature = "webrtc")]
+    pub async fn serve_webrtc_with_config(&self, addr: &str, config: WebRtcConfig) -> Result<()> {
+        let server = WebRtcServer::bind_with_config(addr, config).await?;
+        info!("WebRTC signaling listening on {}", addr);
+        self.serve_on(server).await
+    }
+
     // =========================================================================
     // Multi-Transport Support
     // =========================================================================
@@ -776,6 +1710,8 @@ impl Router {
                     TransportConfig::Quic { addr, cert, key } => {
                         router.serve_quic(addr, cert, key).await
                     }
+                    #[cfg(feature = "webrtc")]
+                    TransportConfig::WebRtc { addr } => router.serve_webrtc(&addr).await,
                     #[allow(unreachable_patterns)]
                     _ => Err(RouterError::Config(
                         "Transport not enabled at compile time".into(),
@@ -832,9 +1768,10 @@ impl Router {
             protocol_names.push("WebSocket");
             let router = self.clone_internal();
             let addr = addr.clone();
-            handles.push(tokio::spawn(
-                async move { router.serve_websocket(&addr).await },
-            ));
+            let acceptors = config.websocket_acceptors;
+            handles.push(tokio::spawn(async move {
+                router.serve_websocket_acceptors(&addr, acceptors).await
+            }));
         }
 
         // QUIC server
@@ -851,17 +1788,38 @@ impl Router {
             }));
         }
 
+        // WebRTC server
+        #[cfg(feature = "webrtc")]
+        if let Some(ref addr) = config.webrtc_addr {
+            info!("Starting WebRTC server on {}", addr);
+            protocol_names.push("WebRTC");
+            let router = self.clone_internal();
+            let addr = addr.clone();
+            handles.push(tokio::spawn(
+                async move { router.serve_webrtc(&addr).await },
+            ));
+        }
+
         // MQTT server adapter
         #[cfg(feature = "mqtt-server")]
         if let Some(mqtt_config) = config.mqtt {
             info!("Starting MQTT server on {}", mqtt_config.bind_addr);
             protocol_names.push("MQTT");
-            let adapter = crate::adapters::MqttServerAdapter::new(
+            let mut adapter = crate::adapters::MqttServerAdapter::new(
                 mqtt_config,
                 Arc::clone(&self.sessions),
                 Arc::clone(&self.subscriptions),
                 Arc::clone(&self.state),
-            );
+            )
+            .with_policy(self.transport_policy("mqtt"))
+            .with_address_policy(self.config.address_policy.clone())
+            .with_write_checks(self.write_checks());
+            if let Some(mapper) = self.address_mappers.get("mqtt") {
+                adapter = adapter.with_address_mapper(Arc::clone(mapper));
+            }
+            if self.config.security_mode == SecurityMode::Authenticated {
+                adapter = adapter.with_reserved(self.config.reserved.clone());
+            }
             handles.push(tokio::spawn(async move { adapter.serve().await }));
         }
 
@@ -870,12 +1828,44 @@ impl Router {
         if let Some(osc_config) = config.osc {
             info!("Starting OSC server on {}", osc_config.bind_addr);
             protocol_names.push("OSC");
-            let adapter = crate::adapters::OscServerAdapter::new(
+            let mut adapter = crate::adapters::OscServerAdapter::new(
                 osc_config,
                 Arc::clone(&self.sessions),
                 Arc::clone(&self.subscriptions),
                 Arc::clone(&self.state),
-            );
+            )
+            .with_policy(self.transport_policy("osc"))
+            .with_address_policy(self.config.address_policy.clone())
+            .with_write_checks(self.write_checks());
+            if let Some(mapper) = self.address_mappers.get("osc") {
+                adapter = adapter.with_address_mapper(Arc::clone(mapper));
+            }
+            if self.config.security_mode == SecurityMode::Authenticated {
+                adapter = adapter.with_reserved(self.config.reserved.clone());
+            }
+            handles.push(tokio::spawn(async move { adapter.serve().await }));
+        }
+
+        // RESP server adapter
+        #[cfg(feature = "resp-server")]
+        if let Some(resp_config) = config.resp {
+            info!("Starting RESP server on {}", resp_config.bind_addr);
+            protocol_names.push("RESP");
+            let mut adapter = crate::adapters::RespServerAdapter::new(
+                resp_config,
+                Arc::clone(&self.sessions),
+                Arc::clone(&self.subscriptions),
+                Arc::clone(&self.state),
+            )
+            .with_policy(self.transport_policy("resp"))
+            .with_address_policy(self.config.address_policy.clone())
+            .with_write_checks(self.write_checks());
+            if let Some(ref validator) = self.token_validator {
+                adapter = adapter.with_validator(Arc::clone(validator));
+            }
+            if self.config.security_mode == SecurityMode::Authenticated {
+                adapter = adapter.with_reserved(self.config.reserved.clone());
+            }
             handles.push(tokio::spawn(async move { adapter.serve().await }));
         }
 
@@ -883,6 +1873,18 @@ impl Router {
             return Err(RouterError::Config("No protocols configured".into()));
         }
 
+        // Metrics endpoint; not a protocol, so it doesn't count towards the
+        // handles that keep the server running
+        #[cfg(feature = "metrics")]
+        if let Some(addr) = config.metrics_addr {
+            let router = self.clone_internal();
+            tokio::spawn(async move {
+                if let Err(e) = router.serve_metrics(&addr).await {
+                    error!("Metrics endpoint on {} failed: {}", addr, e);
+                }
+            });
+        }
+
         info!(
             "Multi-protocol server running with {} protocols: {}",
             handles.len(),
@@ -904,6 +1906,32 @@ impl Router {
         // Start state cleanup task (removes stale params and signals)
         self.start_state_cleanup_task();
 
+        // Start writing aggregates if any addresses are aggregated
+        if let Some(ref aggregator) = self.aggregator {
+            self.start_aggregate_task(Arc::clone(aggregator));
+        }
+
+        // Start sampling load if it may be shed
+        self.start_load_sampling_task();
+
+        // Start the scheduler for OnSchedule rules
+        #[cfg(feature = "rules")]
+        self.start_rule_scheduler_task();
+
+        // Start the timer for OnInterval rules
+        #[cfg(feature = "rules")]
+        self.start_interval_rule_task();
+
+        // Start journal partition retention
+        #[cfg(feature = "journal")]
+        self.start_journal_retention_task();
+
+        // Start withdrawing unfulfilled shadow desires
+        self.start_shadow_expiry_task();
+
+        // Start exchanging state with the rest of the cluster
+        self.start_cluster_task();
+
         // Wait for any server to complete (usually due to error or shutdown)
         loop {
             if handles.is_empty() {
@@ -948,6 +1976,115 @@ impl Router {
         )
     }
 
+    /// Handle one message on behalf of an in-process session, such as an
+    /// HTTP API request made with a client's token.
+    ///
+    /// The message takes the same path as one read off a connection (address
+    /// policy, rate and transport limits, then the handlers), so write
+    /// validators, reserved namespaces, maintenance mode, schemas and
+    /// snapshot filters all apply. The session is not registered, so it
+    /// receives no broadcasts; HELLO is not supported. Returns the replies
+    /// the router sent back: an ACK or ERROR for writes, SNAPSHOTs for GET.
+    pub async fn handle_local(&self, session: &Arc<Session>, msg: &Message) -> Vec<Message> {
+        let replies = Arc::new(ReplySink::default());
+        let sender: Arc<dyn TransportSender> = replies.clone();
+        let (mut msg, frame) = match codec::encode_with_correlation(msg, LOCAL_CORRELATION_ID)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| codec::decode(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                warn!(
+                    "Local message for session {} not encodable: {}",
+                    session.id, e
+                );
+                return Vec::new();
+            }
+        };
+        if matches!(msg, Message::Hello(_)) {
+            return Vec::new();
+        }
+
+        let error = match self.config.address_policy.apply(&mut msg) {
+            Err(reason) => Some(Message::Error(ErrorMessage {
+                code: ErrorCode::InvalidAddress as u16,
+                message: reason,
+                address: None,
+                correlation_id: frame.correlation_id,
+            })),
+            Ok(()) if self.config.rate_limiting_enabled => {
+                check_rate_limits(&self.config, session, &msg, frame.correlation_id)
+            }
+            Ok(()) => None,
+        }
+        .or_else(|| {
+            check_transport_policy(
+                &self.config,
+                session.transport(),
+                session,
+                &msg,
+                frame.correlation_id,
+            )
+        });
+        if let Some(error) = error {
+            return vec![error];
+        }
+
+        let connection = Arc::new(
+            session
+                .connection()
+                .cloned()
+                .unwrap_or_else(|| ConnectionInfo::new(session.transport(), LOCAL_ADDR)),
+        );
+        let bandwidth = Arc::new(BandwidthMeter::new());
+        let ctx = handlers::HandlerContext {
+            session: &Some(Arc::clone(session)),
+            sender: &sender,
+            sessions: &self.sessions,
+            subscriptions: &self.subscriptions,
+            state: &self.state,
+            config: &self.config,
+            security_mode: self.config.security_mode,
+            token_validator: &self.async_validator,
+            validation_cache: &self.validation_cache,
+            p2p_capabilities: &self.p2p_capabilities,
+            gesture_registry: &self.gesture_registry,
+            aggregator: &self.aggregator,
+            write_validator: &self.write_validator,
+            snapshot_filter: &self.snapshot_filter,
+            config_source: &self.config_source,
+            fleet_source: &self.fleet_source,
+            #[cfg(feature = "federation")]
+            registry_replica: &self.registry_replica,
+            transforms: &self.transforms,
+            #[cfg(feature = "rules")]
+            rules_engine: &self.rules_engine,
+            tap: &self.tap,
+            overload: &self.overload,
+            bandwidth: &bandwidth,
+            transport: session.transport(),
+            connection: &connection,
+            correlation_id: frame.correlation_id,
+        };
+        match handlers::handle_message(&msg, &frame, &ctx).await {
+            Some(handlers::MessageResult::Send(bytes)) => {
+                let _ = sender.send(bytes).await;
+            }
+            Some(handlers::MessageResult::Broadcast(bytes, exclude)) => {
+                handlers::broadcast_to_subscribers(&bytes, &self.sessions, &exclude);
+            }
+            _ => {}
+        }
+        replies.take()
+    }
+
+    /// A [`LocalHandler`] that can be handed to HTTP APIs set up before the
+    /// router is shared. It captures the router's hooks (validators, filters,
+    /// transforms) as they are now, so install those first.
+    pub fn local_handler(&self) -> LocalHandler {
+        LocalHandler(self.clone_internal())
+    }
+
     /// Internal clone for spawning transport tasks.
     /// Shares all Arc state with the original.
     fn clone_internal(&self) -> Self {
@@ -958,13 +2095,29 @@ impl Router {
             state: Arc::clone(&self.state),
             running: Arc::clone(&self.running),
             token_validator: self.token_validator.clone(),
+            async_validator: self.async_validator.clone(),
+            validation_cache: Arc::clone(&self.validation_cache),
             p2p_capabilities: Arc::clone(&self.p2p_capabilities),
             gesture_registry: self.gesture_registry.clone(),
+            aggregator: self.aggregator.clone(),
             write_validator: self.write_validator.clone(),
             snapshot_filter: self.snapshot_filter.clone(),
+            address_mappers: self.address_mappers.clone(),
+            config_source: self.config_source.clone(),
+            fleet_source: self.fleet_source.clone(),
+            #[cfg(feature = "federation")]
+            registry_replica: self.registry_replica.clone(),
             transforms: self.transforms.clone(),
             #[cfg(feature = "rules")]
             rules_engine: self.rules_engine.clone(),
+            #[cfg(feature = "rules")]
+            rule_scheduler_started: Arc::clone(&self.rule_scheduler_started),
+            #[cfg(feature = "rules")]
+            interval_rules_started: Arc::clone(&self.interval_rules_started),
+            tap: Arc::clone(&self.tap),
+            overload: Arc::clone(&self.overload),
+            #[cfg(feature = "quic")]
+            quic_certificates: self.quic_certificates.clone(),
         }
     }
 
@@ -981,22 +2134,41 @@ impl Router {
         &self,
         sender: Arc<dyn TransportSender>,
         mut receiver: impl TransportReceiver + 'static,
-        addr: SocketAddr,
+        connection: ConnectionInfo,
     ) {
+        let addr = connection.remote_addr;
+        let transport = connection.protocol;
+        let connection = Arc::new(connection);
+        let tap = Arc::clone(&self.tap);
+        let tap_sender = Arc::new(TapSender::new(sender, Arc::clone(&tap)));
+        let unmetered: Arc<dyn TransportSender> = tap_sender.clone();
+        let bandwidth = Arc::new(BandwidthMeter::new());
+        let sender: Arc<dyn TransportSender> = Arc::new(MeteredSender::new(
+            Arc::clone(&unmetered),
+            Arc::clone(&bandwidth),
+        ));
         let sessions = Arc::clone(&self.sessions);
         let subscriptions = Arc::clone(&self.subscriptions);
         let state = Arc::clone(&self.state);
         let config = self.config.clone();
         let running = Arc::clone(&self.running);
-        let token_validator = self.token_validator.clone();
+        let token_validator = self.async_validator.clone();
+        let validation_cache = Arc::clone(&self.validation_cache);
         let security_mode = self.config.security_mode;
         let p2p_capabilities = Arc::clone(&self.p2p_capabilities);
         let gesture_registry = self.gesture_registry.clone();
+        let aggregator = self.aggregator.clone();
         let write_validator = self.write_validator.clone();
         let snapshot_filter = self.snapshot_filter.clone();
+        let config_source = self.config_source.clone();
+        let fleet_source = self.fleet_source.clone();
+        #[cfg(feature = "federation")]
+        let registry_replica = self.registry_replica.clone();
         let transforms = self.transforms.clone();
         #[cfg(feature = "rules")]
         let rules_engine = self.rules_engine.clone();
+        let overload = Arc::clone(&self.overload);
+        let pending_handshake = overload.accept();
 
         let conn_span =
             tracing::info_span!("connection", session_id = tracing::field::Empty, remote = %addr);
@@ -1060,7 +2232,26 @@ impl Router {
                     }
                 };
 
+                // Defer the client if the router is overloaded
+                if let Some(delay) = overload.check() {
+                    info!(
+                        "Deferring {} for {:?} ({} handshakes pending, shedding: {})",
+                        addr,
+                        delay,
+                        overload.pending(),
+                        overload.is_shedding()
+                    );
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("clasp_errors_total", "code" => "504").increment(1);
+                    let error = Message::Error(ErrorMessage::retry_after(delay));
+                    if let Ok(bytes) = codec::encode(&error) {
+                        let _ = sender.send(bytes).await;
+                    }
+                    return;
+                }
+
                 // Process the Hello message
+                bandwidth.record_in(hello_data.len());
                 if let Ok((msg, frame)) = codec::decode(&hello_data) {
                     let ctx = handlers::HandlerContext {
                         session: &session,
@@ -1071,19 +2262,50 @@ impl Router {
                         config: &config,
                         security_mode,
                         token_validator: &token_validator,
+                        validation_cache: &validation_cache,
                         p2p_capabilities: &p2p_capabilities,
                         gesture_registry: &gesture_registry,
+                        aggregator: &aggregator,
                         write_validator: &write_validator,
                         snapshot_filter: &snapshot_filter,
+                        config_source: &config_source,
+                        fleet_source: &fleet_source,
+                        #[cfg(feature = "federation")]
+                        registry_replica: &registry_replica,
                         transforms: &transforms,
                         #[cfg(feature = "rules")]
                         rules_engine: &rules_engine,
+                        tap: &tap,
+                        overload: &overload,
+                        bandwidth: &bandwidth,
+                        transport,
+                        connection: &connection,
+                        correlation_id: frame.correlation_id,
                     };
-                    if let Some(response) = handlers::handle_message(&msg, &frame, &ctx).await {
+                    let mut response = handlers::handle_message(&msg, &frame, &ctx).await;
+                    if let Some(handlers::MessageResult::Challenge(pending)) = response {
+                        let proof = tokio::time::timeout(
+                            HANDSHAKE_TIMEOUT,
+                            next_proof(&mut receiver, &bandwidth),
+                        )
+                        .await;
+                        response = match proof {
+                            Ok(Some(proof)) => {
+                                handlers::hello::handle_proof(*pending, &proof, &ctx).await
+                            }
+                            _ => {
+                                info!("No proof of possession from {}", addr);
+                                return;
+                            }
+                        };
+                    }
+                    if let Some(response) = response {
                         match response {
                             handlers::MessageResult::NewSession(s) => {
                                 tracing::Span::current()
                                     .record("session_id", tracing::field::display(&s.id));
+                                tap_sender.set_session(&s.id);
+                                tap.record(TapDirection::In, &s.id, &hello_data);
                                 session = Some(s);
                                 handshake_complete = true;
                             }
@@ -1102,6 +2324,7 @@ impl Router {
                     }
                 }
 
+                drop(pending_handshake);
                 if !handshake_complete {
                     debug!("Handshake incomplete for {}", addr);
                     return;
@@ -1111,37 +2334,77 @@ impl Router {
                 while *running.read() {
                     match receiver.recv().await {
                         Some(TransportEvent::Data(data)) => {
-                            // Check rate limit before processing
-                            if config.rate_limiting_enabled {
-                                if let Some(ref s) = session {
-                                    if !s.check_rate_limit(config.max_messages_per_second) {
-                                        warn!(
-                                            "Rate limit exceeded for session {} ({} msgs/sec > {})",
-                                            s.id,
-                                            s.messages_per_second(),
-                                            config.max_messages_per_second
-                                        );
-                                        // Send error and continue (don't disconnect for rate limiting)
-                                        let error = Message::Error(ErrorMessage {
-                                            code: 429, // Too Many Requests
-                                            message: format!(
-                                                "Rate limit exceeded: {} messages/second",
-                                                config.max_messages_per_second
-                                            ),
-                                            address: None,
-                                            correlation_id: None,
-                                        });
-                                        if let Ok(bytes) = codec::encode(&error) {
-                                            let _ = sender.send(bytes).await;
-                                        }
-                                        continue;
-                                    }
+                            // Drop traffic from sessions over their bandwidth quota
+                            let status = bandwidth.record_in(data.len());
+                            if status.is_exceeded() {
+                                if status == QuotaStatus::JustExceeded {
+                                    quota::notify_exceeded(&unmetered, &bandwidth).await;
+                                }
+                                if bandwidth.action() == QuotaAction::Disconnect {
+                                    break;
                                 }
+                                continue;
                             }
 
                             // Decode message
                             match codec::decode(&data) {
-                                Ok((msg, frame)) => {
+                                Ok((mut msg, frame)) => {
+                                    if let Some(ref s) = session {
+                                        tap.record(TapDirection::In, &s.id, &data);
+                                        if let Err(e) = s.aliases().resolve(&mut msg) {
+                                            let error = Message::Error(ErrorMessage {
+                                                code: ErrorCode::InvalidAddress as u16,
+                                                message: e.to_string(),
+                                                address: None,
+                                                correlation_id: frame.correlation_id,
+                                            });
+                                            if let Ok(bytes) = codec::encode(&error) {
+                                                let _ = sender.send(bytes).await;
+                                            }
+                                            continue;
+                                        }
+                                        if let Err(reason) = config.address_policy.apply(&mut msg) {
+                                            debug!("Session {}: {}", s.id, reason);
+                                            let error = Message::Error(ErrorMessage {
+                                                code: ErrorCode::InvalidAddress as u16,
+                                                message: reason,
+                                                address: None,
+                                                correlation_id: frame.correlation_id,
+                                            });
+                                            if let Ok(bytes) = codec::encode(&error) {
+                                                let _ = sender.send(bytes).await;
+                                            }
+                                            continue;
+                                        }
+
+                                        // Check rate limits before processing
+                                        if config.rate_limiting_enabled {
+                                            if let Some(error) = check_rate_limits(
+                                                &config,
+                                                s,
+                                                &msg,
+                                                frame.correlation_id,
+                                            ) {
+                                                if let Ok(bytes) = codec::encode(&error) {
+                                                    let _ = sender.send(bytes).await;
+                                                }
+                                                continue;
+                                            }
+                                        }
+
+                                        if let Some(error) = check_transport_policy(
+                                            &config,
+                                            transport,
+                                            s,
+                                            &msg,
+                                            frame.correlation_id,
+                                        ) {
+                                            if let Ok(bytes) = codec::encode(&error) {
+                                                let _ = sender.send(bytes).await;
+                                            }
+                                            continue;
+                                        }
+                                    }
                                     let ctx = handlers::HandlerContext {
                                         session: &session,
                                         sender: &sender,
@@ -1151,13 +2414,25 @@ impl Router {
                                         config: &config,
                                         security_mode,
                                         token_validator: &token_validator,
+                                        validation_cache: &validation_cache,
                                         p2p_capabilities: &p2p_capabilities,
                                         gesture_registry: &gesture_registry,
+                                        aggregator: &aggregator,
                                         write_validator: &write_validator,
                                         snapshot_filter: &snapshot_filter,
+                                        config_source: &config_source,
+                                        fleet_source: &fleet_source,
+                                        #[cfg(feature = "federation")]
+                                        registry_replica: &registry_replica,
                                         transforms: &transforms,
                                         #[cfg(feature = "rules")]
                                         rules_engine: &rules_engine,
+                                        tap: &tap,
+                                        overload: &overload,
+                                        bandwidth: &bandwidth,
+                                        transport,
+                                        connection: &connection,
+                                        correlation_id: frame.correlation_id,
                                     };
                                     if let Some(response) =
                                         handlers::handle_message(&msg, &frame, &ctx).await
@@ -1184,7 +2459,10 @@ impl Router {
                                                 );
                                                 break;
                                             }
-                                            handlers::MessageResult::None => {}
+                                            // A repeated HELLO can't answer a challenge
+                                            // here; it doesn't replace the session
+                                            handlers::MessageResult::Challenge(_)
+                                            | handlers::MessageResult::None => {}
                                         }
                                     }
                                 }
@@ -1197,6 +2475,9 @@ impl Router {
                             info!("Client {} disconnected: {:?}", addr, reason);
                             break;
                         }
+                        Some(TransportEvent::Migrated { from, to }) => {
+                            info!("Client {} moved from {} to {}", addr, from, to);
+                        }
                         Some(TransportEvent::Error(e)) => {
                             error!("Transport error from {}: {}", addr, e);
                             break;
@@ -1212,7 +2493,17 @@ impl Router {
                 if let Some(s) = session {
                     info!("Removing session {}", s.id);
                     sessions.remove(&s.id);
+                    crate::durable_session::suspend(
+                        &s,
+                        &state,
+                        &subscriptions,
+                        config.durable_session_ttl,
+                    )
+                    .await;
                     subscriptions.remove_session(&s.id);
+                    handlers::release_session_state(&s.id, &state, &sessions, &subscriptions);
+                    #[cfg(feature = "federation")]
+                    handlers::federation::release_federation_routes(&s, &sessions, &subscriptions);
                     p2p_capabilities.unregister(&s.id);
                     #[cfg(feature = "metrics")]
                     metrics::gauge!("clasp_sessions_active").decrement(1.0);
@@ -1227,16 +2518,116 @@ impl Router {
         *self.running.write() = false;
     }
 
+    /// Stop the router and close every session
+    ///
+    /// Each session is sent an ERROR 503 carrying `message` so clients know
+    /// to reconnect elsewhere, then gets up to `drain` to disconnect on its
+    /// own. Sessions still open after that are closed. Returns how many had
+    /// to be closed.
+    pub async fn shutdown(&self, message: &str, drain: Duration) -> usize {
+        self.stop();
+
+        let notice = Message::Error(ErrorMessage {
+            code: ErrorCode::Maintenance as u16,
+            message: message.to_string(),
+            address: None,
+            correlation_id: None,
+        });
+        if let Ok(bytes) = codec::encode(&notice) {
+            for entry in self.sessions.iter() {
+                let _ = entry.value().try_send(bytes.clone());
+            }
+        }
+
+        let deadline = tokio::time::Instant::now() + drain;
+        while !self.sessions.is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let remaining: Vec<Arc<Session>> = self
+            .sessions
+            .iter()
+            .map(|entry| Arc::clone(entry.value()))
+            .collect();
+        for session in &remaining {
+            let _ = session.close().await;
+        }
+        remaining.len()
+    }
+
     /// Get session count
     pub fn session_count(&self) -> usize {
         self.sessions.len()
     }
 
+    /// Byte counters and quota state of every session (see [`crate::quota`])
+    pub fn bandwidth_usage(&self) -> Vec<SessionBandwidth> {
+        self.sessions
+            .iter()
+            .map(|entry| {
+                let session = entry.value();
+                SessionBandwidth {
+                    session_id: session.id.clone(),
+                    subject: session.subject.clone(),
+                    usage: session.bandwidth().usage(),
+                }
+            })
+            .collect()
+    }
+
     /// Get state
     pub fn state(&self) -> &RouterState {
         &self.state
     }
 
+    /// Enter or leave maintenance mode and announce it to every session
+    /// (see [`crate::maintenance`])
+    pub fn set_maintenance(&self, active: bool, message: Option<String>) {
+        let maintenance = self.state.maintenance();
+        maintenance.set(active, message);
+        maintenance.announce(&self.sessions);
+    }
+
+    /// SET checks for protocol adapters (see [`crate::adapters::WriteChecks`])
+    #[cfg(any(
+        feature = "mqtt-server",
+        feature = "osc-server",
+        feature = "resp-server"
+    ))]
+    pub fn write_checks(&self) -> crate::adapters::WriteChecks {
+        crate::adapters::WriteChecks::new(
+            self.config.clone(),
+            self.config_source.is_some(),
+            self.write_validator.clone(),
+        )
+    }
+
+    /// Policy configured for a transport, unrestricted if there is none
+    /// (see [`crate::transport_policy`])
+    pub fn transport_policy(&self, transport: &str) -> TransportPolicy {
+        self.config
+            .transport_policies
+            .get(transport)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Load shedding state and HELLO deferral (see [`crate::overload`])
+    pub fn overload(&self) -> Arc<OverloadGuard> {
+        Arc::clone(&self.overload)
+    }
+
+    /// Get the wire tap, for enabling frame capture programmatically
+    pub fn wire_tap(&self) -> Arc<WireTap> {
+        Arc::clone(&self.tap)
+    }
+
+    /// Handle for reloading the QUIC listener's certificate
+    #[cfg(feature = "quic")]
+    pub fn quic_certificates(&self) -> QuicCertificates {
+        self.quic_certificates.clone()
+    }
+
     /// Get subscription count
     pub fn subscription_count(&self) -> usize {
         self.subscriptions.len()
@@ -1249,6 +2640,155 @@ impl Default for Router {
     }
 }
 
+/// Handles messages for in-process sessions; see [`Router::local_handler`]
+pub struct LocalHandler(Router);
+
+impl LocalHandler {
+    /// Same as [`Router::handle_local`]
+    pub async fn handle(&self, session: &Arc<Session>, msg: &Message) -> Vec<Message> {
+        self.0.handle_local(session, msg).await
+    }
+
+    /// Run params read outside a GET through the router's snapshot filter,
+    /// as they would be filtered for `session`
+    pub fn filter_snapshot(
+        &self,
+        params: Vec<clasp_core::ParamValue>,
+        session: &Session,
+    ) -> Vec<clasp_core::ParamValue> {
+        match self.0.snapshot_filter {
+            Some(ref filter) => filter.filter_snapshot(params, session, &self.0.state),
+            None => params,
+        }
+    }
+}
+
+/// Collects the replies to a message handled with `Router::handle_local`
+#[derive(Default)]
+struct ReplySink {
+    replies: parking_lot::Mutex<Vec<Bytes>>,
+}
+
+impl ReplySink {
+    /// Decode and drain the replies collected so far
+    fn take(&self) -> Vec<Message> {
+        std::mem::take(&mut *self.replies.lock())
+            .iter()
+            .filter_map(|data| codec::decode(data).ok().map(|(msg, _)| msg))
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl TransportSender for ReplySink {
+    async fn send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.try_send(data)
+    }
+
+    fn try_send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.replies.lock().push(data);
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+}
+
+/// Wait for the PROOF answering a WELCOME challenge; anything else ends the
+/// handshake
+async fn next_proof(
+    receiver: &mut impl TransportReceiver,
+    bandwidth: &crate::quota::BandwidthMeter,
+) -> Option<clasp_core::ProofMessage> {
+    loop {
+        match receiver.recv().await? {
+            TransportEvent::Data(data) => {
+                bandwidth.record_in(data.len());
+                return match codec::decode(&data) {
+                    Ok((Message::Proof(proof), _)) => Some(proof),
+                    _ => None,
+                };
+            }
+            TransportEvent::Disconnected { .. } | TransportEvent::Error(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Count `msg` against the first matching rate limit rule, or the global
+/// limit if none matches; returns the ERROR 429 to send when it is over
+fn check_rate_limits(
+    config: &RouterConfig,
+    session: &Session,
+    msg: &Message,
+    correlation_id: Option<u32>,
+) -> Option<Message> {
+    let (within, limit, scope) = match config.rate_limits.rule_for(msg) {
+        Some((index, rule)) => (
+            session.check_rule_rate_limit(index, rule.max_per_second),
+            rule.max_per_second,
+            Some(rule),
+        ),
+        None => (
+            session.check_rate_limit(config.max_messages_per_second),
+            config.max_messages_per_second,
+            None,
+        ),
+    };
+    if within {
+        return None;
+    }
+
+    let message = match scope {
+        Some(rule) => {
+            let types = if rule.types.is_empty() {
+                "messages".to_string()
+            } else {
+                rule.types.join("/")
+            };
+            match rule.prefix {
+                Some(ref prefix) => format!(
+                    "Rate limit exceeded: {} {} under {} per second",
+                    limit, types, prefix
+                ),
+                None => format!("Rate limit exceeded: {} {} per second", limit, types),
+            }
+        }
+        None => format!("Rate limit exceeded: {} messages/second", limit),
+    };
+    warn!("Session {}: {}", session.id, message);
+    // Don't disconnect for rate limiting
+    Some(Message::Error(ErrorMessage {
+        code: ErrorCode::RateLimited as u16,
+        message,
+        address: None,
+        correlation_id,
+    }))
+}
+
+/// ERROR answering a message the session's transport policy refuses
+fn check_transport_policy(
+    config: &RouterConfig,
+    transport: &str,
+    session: &Session,
+    msg: &Message,
+    correlation_id: Option<u32>,
+) -> Option<Message> {
+    let reason = config.transport_policies.get(transport)?.check(msg)?;
+    warn!("Session {} ({}): {}", session.id, transport, reason);
+    Some(Message::Error(ErrorMessage {
+        code: ErrorCode::Forbidden as u16,
+        message: reason,
+        address: crate::rate_limit::message_address(msg).map(str::to_string),
+        correlation_id,
+    }))
+}
+
 /// Execute pending actions produced by the rules engine.
 ///
 /// Applies SET actions to state and broadcasts to subscribers.
@@ -1283,6 +2823,8 @@ pub fn execute_rule_actions(
                             lock: false,
                             unlock: false,
                             ttl: None,
+                            trace: false,
+                            branch: None,
                         });
                         if let Ok(bytes) = codec::encode(&set_msg) {
                             for sub_session_id in subscribers {
@@ -1295,6 +2837,9 @@ pub fn execute_rule_actions(
                                 }
                             }
                         }
+                        if let Message::Set(ref set) = set_msg {
+                            state.cluster_forward_set(set, revision, &action.origin);
+                        }
                         debug!("Rule {} applied SET to {}", action.rule_id, address);
                     }
                     Err(e) => {
@@ -1331,6 +2876,9 @@ pub fn execute_rule_actions(
                         }
                     }
                 }
+                if let Message::Publish(ref publish) = pub_msg {
+                    state.cluster_forward_publish(publish, &action.origin);
+                }
                 debug!("Rule {} applied PUBLISH to {}", action.rule_id, address);
             }
             clasp_rules::RuleAction::SetFromTrigger { address, transform } => {
@@ -1355,6 +2903,8 @@ pub fn execute_rule_actions(
                                 lock: false,
                                 unlock: false,
                                 ttl: None,
+                                trace: false,
+                                branch: None,
                             });
                             if let Ok(bytes) = codec::encode(&set_msg) {
                                 for sub_session_id in subscribers {
@@ -1367,6 +2917,9 @@ pub fn execute_rule_actions(
                                     }
                                 }
                             }
+                            if let Message::Set(ref set) = set_msg {
+                                state.cluster_forward_set(set, revision, &action.origin);
+                            }
                             debug!(
                                 "Rule {} applied SetFromTrigger to {}",
                                 action.rule_id, address
@@ -1384,6 +2937,13 @@ pub fn execute_rule_actions(
             clasp_rules::RuleAction::Delay { .. } => {
                 // Delay actions are handled at a higher level (relay timer tasks)
             }
+            clasp_rules::RuleAction::Notify { .. } => {
+                // Delivered by the engine's notifier (RulesEngine::with_notifier)
+            }
+            #[cfg(feature = "scripting")]
+            clasp_rules::RuleAction::Script { .. } => {
+                // Expanded into the actions the script emitted by the engine
+            }
         }
     }
 }
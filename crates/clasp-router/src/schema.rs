@@ -0,0 +1,343 @@
+//! Param schemas
+//!
+//! Signals announced with ANNOUNCE can carry constraints: a `datatype`, and
+//! in their meta a `range`, an `enum` of allowed values and a `unit`. With
+//! [`RouterConfig::schema`] on, the router publishes each announced signal's
+//! constraints as a param at `/clasp/schema{address}` (so the schema of
+//! `/lights/1/level` is at `/clasp/schema/lights/1/level`) and checks every
+//! SET, including SETs in a BUNDLE, against the signal it belongs to. A
+//! signal whose address is a pattern covers every address it matches.
+//!
+//! - [`SchemaMode::Reject`] answers a violating SET with ERROR 402
+//!   (`InvalidValue`)
+//! - [`SchemaMode::Clamp`] stores numbers outside the range as the nearest
+//!   bound, and rejects other violations
+//!
+//! The ERROR message starts with the violated constraint (`type`, `min`,
+//! `max` or `enum`), a colon and a description, e.g. `max: 1.4 is above 1`,
+//! so clients can tell violations apart without parsing prose.
+//!
+//! Datatypes checked are the usual names: `bool`, `int` (`integer`, `i32`,
+//! `u8`, ...), `float` (`f32`, `f64`, `double`, `number`; integers are
+//! accepted) and `string` (`str`, `text`), plus the single-letter OSC type
+//! tags `b`, `i`, `f` and `s`. Other datatypes aren't checked. Values that
+//! aren't numbers are never checked against a range.
+//!
+//! [`RouterConfig::schema`]: crate::RouterConfig::schema
+
+use clasp_core::{codec, Message, SetMessage, SignalDefinition, SignalType, Value};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::handlers::broadcast_to_subscriber_list;
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Schemas live under this prefix, at the signal's address
+pub const SCHEMA_PREFIX: &str = "/clasp/schema";
+
+/// Writer recorded on schema params
+pub const SCHEMA_WRITER: &str = "schema";
+
+/// Whether and how SET values are checked against announced schemas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaMode {
+    /// Schemas are neither published nor enforced
+    #[default]
+    Off,
+    /// Violating SETs are rejected
+    Reject,
+    /// Numbers are clamped into their range; other violations are rejected
+    Clamp,
+}
+
+impl std::str::FromStr for SchemaMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "reject" => Ok(Self::Reject),
+            "clamp" => Ok(Self::Clamp),
+            other => Err(format!(
+                "unknown schema mode '{}' (expected off, reject or clamp)",
+                other
+            )),
+        }
+    }
+}
+
+/// Schema address of a signal
+pub fn address(signal: &str) -> String {
+    format!("{}{}", SCHEMA_PREFIX, signal)
+}
+
+/// Whether `address` is a schema entry
+pub fn is_schema(address: &str) -> bool {
+    address
+        .strip_prefix(SCHEMA_PREFIX)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// A constraint a value broke
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// Not of the announced datatype
+    Type { expected: String },
+    /// Below the range
+    Min { value: f64, min: f64 },
+    /// Above the range
+    Max { value: f64, max: f64 },
+    /// Not one of the allowed values
+    Enum,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Type { expected } => write!(f, "type: expected {}", expected),
+            Self::Min { value, min } => write!(f, "min: {} is below {}", value, min),
+            Self::Max { value, max } => write!(f, "max: {} is above {}", value, max),
+            Self::Enum => write!(f, "enum: not one of the allowed values"),
+        }
+    }
+}
+
+/// Broad kind of a datatype name, if it is one that is checked
+fn kind(datatype: &str) -> Option<&'static str> {
+    match datatype.to_ascii_lowercase().as_str() {
+        "bool" | "boolean" | "b" => Some("bool"),
+        "int" | "integer" | "i" | "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => {
+            Some("int")
+        }
+        "float" | "double" | "number" | "f" | "f32" | "f64" => Some("float"),
+        "string" | "str" | "text" | "s" => Some("string"),
+        _ => None,
+    }
+}
+
+fn has_kind(value: &Value, kind: &str) -> bool {
+    match kind {
+        "bool" => matches!(value, Value::Bool(_)),
+        "int" => matches!(value, Value::Int(_)),
+        "float" => matches!(value, Value::Float(_) | Value::Int(_)),
+        "string" => matches!(value, Value::String(_)),
+        _ => true,
+    }
+}
+
+/// Check `value` against a signal's constraints. Returns the value to
+/// store: the same value, or in [`SchemaMode::Clamp`] a clamped number.
+pub fn check(
+    signal: &SignalDefinition,
+    value: &Value,
+    mode: SchemaMode,
+) -> Result<Option<Value>, Violation> {
+    if mode == SchemaMode::Off || value.is_e2e_envelope() {
+        return Ok(None);
+    }
+    if let Some(kind) = signal.datatype.as_deref().and_then(kind) {
+        if !has_kind(value, kind) {
+            return Err(Violation::Type {
+                expected: kind.to_string(),
+            });
+        }
+    }
+    let Some(ref meta) = signal.meta else {
+        return Ok(None);
+    };
+    if let Some(ref allowed) = meta.enum_values {
+        if !allowed.contains(value) {
+            return Err(Violation::Enum);
+        }
+    }
+    let (Some((min, max)), Some(n)) = (meta.range, value.as_f64()) else {
+        return Ok(None);
+    };
+    if n >= min && n <= max {
+        return Ok(None);
+    }
+    if mode == SchemaMode::Clamp {
+        let clamped = n.clamp(min, max);
+        return Ok(Some(match value {
+            Value::Int(_) if clamped.fract() == 0.0 => Value::Int(clamped as i64),
+            _ => Value::Float(clamped),
+        }));
+    }
+    Err(if n < min {
+        Violation::Min { value: n, min }
+    } else {
+        Violation::Max { value: n, max }
+    })
+}
+
+/// Check a SET to `address` against the signal announced for it, if any
+pub(crate) fn enforce(
+    state: &RouterState,
+    address: &str,
+    value: &Value,
+    mode: SchemaMode,
+) -> Result<Option<Value>, Violation> {
+    if mode == SchemaMode::Off {
+        return Ok(None);
+    }
+    match state.signal_definition(address) {
+        Some(signal) => check(&signal, value, mode),
+        None => Ok(None),
+    }
+}
+
+/// Schema param of a signal, or `None` if it has no constraints
+pub fn entry(signal: &SignalDefinition) -> Option<Value> {
+    let mut map = HashMap::new();
+    if let Some(ref datatype) = signal.datatype {
+        map.insert("type".to_string(), Value::String(datatype.clone()));
+    }
+    if let Some(ref meta) = signal.meta {
+        if let Some((min, max)) = meta.range {
+            map.insert("min".to_string(), Value::Float(min));
+            map.insert("max".to_string(), Value::Float(max));
+        }
+        if let Some(ref values) = meta.enum_values {
+            map.insert("enum".to_string(), Value::Array(values.clone()));
+        }
+        if let Some(ref unit) = meta.unit {
+            map.insert("unit".to_string(), Value::String(unit.clone()));
+        }
+    }
+    (!map.is_empty()).then_some(Value::Map(map))
+}
+
+/// Publish the schemas of newly announced signals
+pub(crate) fn publish(
+    signals: &[SignalDefinition],
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    for signal in signals {
+        let Some(value) = entry(signal) else {
+            continue;
+        };
+        let address = address(&signal.address);
+        let revision = match state.set(
+            &address,
+            value.clone(),
+            &SCHEMA_WRITER.to_string(),
+            None,
+            false,
+            false,
+            None,
+        ) {
+            Ok(revision) => revision,
+            Err(e) => {
+                warn!("Schema SET to {} failed: {:?}", address, e);
+                continue;
+            }
+        };
+
+        let subscribers = subscriptions.find_subscribers(&address, Some(SignalType::Param));
+        let msg = Message::Set(SetMessage {
+            address,
+            value,
+            revision: Some(revision),
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        if let Ok(bytes) = codec::encode(&msg) {
+            broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::SignalMeta;
+
+    fn signal(
+        datatype: &str,
+        range: Option<(f64, f64)>,
+        values: Option<Vec<Value>>,
+    ) -> SignalDefinition {
+        SignalDefinition {
+            address: "/lights/*/level".to_string(),
+            signal_type: SignalType::Param,
+            datatype: Some(datatype.to_string()),
+            access: None,
+            meta: Some(SignalMeta {
+                unit: None,
+                range,
+                default: None,
+                description: None,
+                enum_values: values,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_range_reject_and_clamp() {
+        let level = signal("f32", Some((0.0, 1.0)), None);
+        assert_eq!(
+            check(&level, &Value::Float(0.5), SchemaMode::Reject),
+            Ok(None)
+        );
+        assert_eq!(check(&level, &Value::Int(1), SchemaMode::Reject), Ok(None));
+        assert_eq!(
+            check(&level, &Value::Float(1.4), SchemaMode::Reject),
+            Err(Violation::Max {
+                value: 1.4,
+                max: 1.0
+            })
+        );
+        assert_eq!(
+            check(&level, &Value::Float(1.4), SchemaMode::Clamp),
+            Ok(Some(Value::Float(1.0)))
+        );
+        assert_eq!(
+            check(&level, &Value::Int(-3), SchemaMode::Clamp),
+            Ok(Some(Value::Int(0)))
+        );
+        assert_eq!(check(&level, &Value::Float(9.0), SchemaMode::Off), Ok(None));
+
+        // Type violations aren't clamped
+        let err = check(&level, &Value::String("full".into()), SchemaMode::Clamp).unwrap_err();
+        assert_eq!(err.to_string(), "type: expected float");
+    }
+
+    #[test]
+    fn test_enum_and_entry() {
+        let mode = signal(
+            "string",
+            None,
+            Some(vec![
+                Value::String("off".into()),
+                Value::String("strobe".into()),
+            ]),
+        );
+        assert_eq!(
+            check(&mode, &Value::String("strobe".into()), SchemaMode::Reject),
+            Ok(None)
+        );
+        assert_eq!(
+            check(&mode, &Value::String("disco".into()), SchemaMode::Clamp),
+            Err(Violation::Enum)
+        );
+
+        let Some(Value::Map(entry)) = entry(&mode) else {
+            panic!("expected a schema entry");
+        };
+        assert_eq!(entry["type"], Value::String("string".into()));
+        assert!(!entry.contains_key("min"));
+        assert_eq!(address("/lights/*/level"), "/clasp/schema/lights/*/level");
+        assert!(is_schema("/clasp/schema/lights/1") && !is_schema("/clasp/schemas"));
+    }
+}
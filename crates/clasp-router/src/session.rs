@@ -1,8 +1,8 @@
 //! Session management
 
 use bytes::Bytes;
-use clasp_core::{Action, Message, Scope, WelcomeMessage, PROTOCOL_VERSION};
-use clasp_transport::TransportSender;
+use clasp_core::{Action, EntityInfo, Message, Scope, WelcomeMessage, PROTOCOL_VERSION};
+use clasp_transport::{ConnectionInfo, TransportSender};
 use parking_lot::RwLock;
 use std::collections::HashSet;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
@@ -10,6 +10,15 @@ use std::sync::Arc;
 use std::time::Instant;
 use uuid::Uuid;
 
+use crate::alias::SessionAliases;
+use crate::conversion::UnitConversions;
+use crate::delivery_stats::DeliveryStats;
+use crate::quota::BandwidthMeter;
+use crate::rate_limit::RuleWindows;
+use crate::replay::ReplayPacer;
+use crate::stream_policy::StreamQueues;
+use crate::tick::TickSubscriptions;
+
 /// Session identifier
 pub type SessionId = String;
 
@@ -30,6 +39,22 @@ pub struct Session {
     sender: Arc<dyn TransportSender>,
     /// Active subscriptions (subscription IDs)
     subscriptions: RwLock<HashSet<u32>>,
+    /// Subscriptions delivering aggregated bundles on a fixed tick
+    tick_subscriptions: TickSubscriptions,
+    /// Stream subscriptions queued under a backpressure policy
+    stream_queues: StreamQueues,
+    /// Subscriptions receiving values converted to another unit
+    unit_conversions: UnitConversions,
+    /// Delivered and dropped counts per subscription
+    delivery_stats: DeliveryStats,
+    /// Topic aliases bound in each direction
+    aliases: SessionAliases,
+    /// Paces journal replays to the router's replay rate
+    replay_pacer: ReplayPacer,
+    /// Transport the session connected over (e.g. `websocket`)
+    transport: &'static str,
+    /// Addresses and TLS details of the connection, when the transport reports them
+    connection: Option<Arc<ConnectionInfo>>,
     /// Session creation time
     pub created_at: Instant,
     /// Last activity time
@@ -40,12 +65,19 @@ pub struct Session {
     pub token: Option<String>,
     /// Subject identifier from token (user, device, or service ID)
     pub subject: Option<String>,
+    /// Registry entity behind the token, for entity (`ent_`) tokens
+    pub entity: Option<Arc<EntityInfo>>,
+    /// Token that resumes this session after a disconnect (see
+    /// [`crate::durable_session`])
+    resume_token: Option<String>,
     /// Scopes granted to this session
     scopes: Vec<Scope>,
     /// Messages received in the current second (for rate limiting)
     messages_this_second: AtomicU32,
     /// The second when the message count was last reset (Unix timestamp)
     last_rate_limit_second: AtomicU64,
+    /// Messages counted against each [`RateLimitPolicy`](crate::RateLimitPolicy) rule
+    rule_rates: RuleWindows,
     /// Dropped messages in the current window
     drops_in_window: AtomicU32,
     /// Start of the current drop counting window (Unix timestamp)
@@ -54,6 +86,8 @@ pub struct Session {
     last_drop_notification: AtomicU64,
     /// Total drops since session started
     total_drops: AtomicU64,
+    /// Bytes in and out, and the session's bandwidth quota
+    bandwidth: Arc<BandwidthMeter>,
     /// Whether this session is a federation peer (advertised "federation" feature in HELLO)
     #[cfg(feature = "federation")]
     federation_peer: bool,
@@ -63,6 +97,11 @@ pub struct Session {
     /// Namespace patterns declared by this federation peer
     #[cfg(feature = "federation")]
     federation_namespaces: parking_lot::RwLock<Vec<String>>,
+    /// Namespaces gossiped through this federation peer (owner -> origin, patterns)
+    #[cfg(feature = "federation")]
+    federation_routes: parking_lot::RwLock<
+        std::collections::HashMap<String, (clasp_core::GossipOrigin, Vec<String>)>,
+    >,
 }
 
 /// No-op transport sender for test sessions.
@@ -116,24 +155,38 @@ impl Session {
             features,
             sender,
             subscriptions: RwLock::new(HashSet::new()),
+            tick_subscriptions: TickSubscriptions::default(),
+            stream_queues: StreamQueues::default(),
+            unit_conversions: UnitConversions::default(),
+            delivery_stats: DeliveryStats::default(),
+            aliases: SessionAliases::default(),
+            replay_pacer: ReplayPacer::default(),
+            transport: "unknown",
+            connection: None,
             created_at: now,
             last_activity: RwLock::new(now),
             authenticated: false,
             token: None,
             subject: None,
+            entity: None,
+            resume_token: None,
             scopes: Vec::new(),
             messages_this_second: AtomicU32::new(0),
             last_rate_limit_second: AtomicU64::new(0),
+            rule_rates: RuleWindows::default(),
             drops_in_window: AtomicU32::new(0),
             drop_window_start: AtomicU64::new(0),
             last_drop_notification: AtomicU64::new(0),
             total_drops: AtomicU64::new(0),
+            bandwidth: Arc::new(BandwidthMeter::new()),
             #[cfg(feature = "federation")]
             federation_peer: is_federation_peer,
             #[cfg(feature = "federation")]
             federation_router_id: parking_lot::RwLock::new(None),
             #[cfg(feature = "federation")]
             federation_namespaces: parking_lot::RwLock::new(Vec::new()),
+            #[cfg(feature = "federation")]
+            federation_routes: parking_lot::RwLock::new(std::collections::HashMap::new()),
         }
     }
 
@@ -150,6 +203,78 @@ impl Session {
         self.scopes = scopes;
     }
 
+    /// Make the session durable: on disconnect its subscriptions are kept
+    /// under `token` (see [`crate::durable_session`])
+    pub fn set_resume_token(&mut self, token: String) {
+        self.resume_token = Some(token);
+    }
+
+    /// Token that resumes this session, if it is durable
+    pub fn resume_token(&self) -> Option<&str> {
+        self.resume_token.as_deref()
+    }
+
+    /// Share the connection's bandwidth meter, which also counts traffic
+    /// sent before the session existed
+    pub fn set_bandwidth_meter(&mut self, meter: Arc<BandwidthMeter>) {
+        self.bandwidth = meter;
+    }
+
+    /// Count deliveries per subscription (see [`crate::delivery_stats`])
+    pub fn set_delivery_stats(&mut self, stats: DeliveryStats) {
+        self.delivery_stats = stats;
+    }
+
+    /// Set up topic alias tables (see [`crate::alias`])
+    pub fn set_aliases(&mut self, aliases: SessionAliases) {
+        self.aliases = aliases;
+    }
+
+    /// Topic alias tables for this session
+    pub fn aliases(&self) -> &SessionAliases {
+        &self.aliases
+    }
+
+    /// Record the transport the session connected over
+    pub fn set_transport(&mut self, transport: &'static str) {
+        self.transport = transport;
+    }
+
+    /// Transport the session connected over (e.g. `websocket`, `quic`, `mqtt`)
+    pub fn transport(&self) -> &'static str {
+        self.transport
+    }
+
+    /// Record the connection the session arrived on
+    pub fn set_connection(&mut self, connection: Arc<ConnectionInfo>) {
+        self.connection = Some(connection);
+    }
+
+    /// Connection the session arrived on: local address, WebSocket
+    /// subprotocol, and for QUIC the ALPN, SNI and client certificate chain.
+    /// `None` for sessions created by protocol adapters.
+    pub fn connection(&self) -> Option<&ConnectionInfo> {
+        self.connection.as_deref()
+    }
+
+    /// Whether SET_MANY writes must reach this session as one SET per
+    /// address: protocol adapters and federation peers only forward SETs,
+    /// and tick subscriptions and unit conversions work per address
+    pub fn expands_batches(&self) -> bool {
+        #[cfg(feature = "federation")]
+        if self.federation_peer {
+            return true;
+        }
+        matches!(self.transport, "mqtt" | "osc" | "resp")
+            || self.tick_subscriptions.is_active()
+            || self.unit_conversions.is_active()
+    }
+
+    /// Byte counters and quota state (see [`crate::quota`])
+    pub fn bandwidth(&self) -> &BandwidthMeter {
+        &self.bandwidth
+    }
+
     /// Check if this session has permission for the given action on the given address
     pub fn has_scope(&self, action: Action, address: &str) -> bool {
         // Unauthenticated sessions in open mode have no scope restrictions
@@ -191,8 +316,22 @@ impl Session {
 
     /// Try to send a message without blocking (for broadcasts)
     /// Returns Ok if sent or queued, Err if buffer is full
+    ///
+    /// Values for a `convert_to` or `transform` subscription are converted
+    /// first. Updates matching a tick subscription are buffered and
+    /// delivered in that subscription's next bundle instead, and stream
+    /// frames matching a subscription with a stream policy are queued for
+    /// its drain task. Hot addresses are sent as topic aliases if the client
+    /// supports them. With delivery stats on, what is sent or dropped is
+    /// counted against the matching subscriptions.
     pub fn try_send(&self, data: Bytes) -> Result<(), clasp_transport::TransportError> {
-        self.sender.try_send(data)?;
+        let data = self.unit_conversions.apply(data);
+        if self.tick_subscriptions.intercept(&data) || self.stream_queues.intercept(&data) {
+            return Ok(());
+        }
+        let result = self.aliases.try_send(data.clone(), self.sender.as_ref());
+        self.delivery_stats.record(&data, result.is_ok());
+        result?;
         *self.last_activity.write() = Instant::now();
         Ok(())
     }
@@ -206,6 +345,11 @@ impl Session {
         Ok(())
     }
 
+    /// Close the underlying transport, ending the connection
+    pub async fn close(&self) -> Result<(), clasp_transport::TransportError> {
+        self.sender.close().await
+    }
+
     /// Create welcome message for this session
     pub fn welcome_message(&self, server_name: &str, server_features: &[String]) -> Message {
         Message::Welcome(WelcomeMessage {
@@ -215,6 +359,9 @@ impl Session {
             features: server_features.to_vec(),
             time: clasp_core::time::now(),
             token: None,
+            challenge: None,
+            resume_token: None,
+            resumed: false,
         })
     }
 
@@ -225,9 +372,38 @@ impl Session {
 
     /// Remove a subscription
     pub fn remove_subscription(&self, id: u32) -> bool {
+        self.tick_subscriptions.remove(id);
+        self.stream_queues.remove(id);
+        self.unit_conversions.remove(id);
+        self.delivery_stats.remove(id);
         self.subscriptions.write().remove(&id)
     }
 
+    /// Tick-aggregated subscriptions for this session
+    pub fn tick_subscriptions(&self) -> &TickSubscriptions {
+        &self.tick_subscriptions
+    }
+
+    /// Stream subscriptions with a backpressure policy
+    pub fn stream_queues(&self) -> &StreamQueues {
+        &self.stream_queues
+    }
+
+    /// Unit-converting subscriptions for this session
+    pub fn unit_conversions(&self) -> &UnitConversions {
+        &self.unit_conversions
+    }
+
+    /// Delivered and dropped counts per subscription
+    pub fn delivery_stats(&self) -> &DeliveryStats {
+        &self.delivery_stats
+    }
+
+    /// Pacer shared by this session's journal replays
+    pub fn replay_pacer(&self) -> &ReplayPacer {
+        &self.replay_pacer
+    }
+
     /// Get all subscription IDs
     pub fn subscriptions(&self) -> Vec<u32> {
         self.subscriptions.read().iter().cloned().collect()
@@ -274,6 +450,16 @@ impl Session {
         }
     }
 
+    /// Check and increment the counter of a rate limit rule (see
+    /// [`crate::rate_limit`]). Returns true if within the rule's limit
+    pub fn check_rule_rate_limit(&self, rule: usize, max_per_second: u32) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.rule_rates.check(rule, max_per_second, now)
+    }
+
     /// Get current message count for this second
     pub fn messages_per_second(&self) -> u32 {
         self.messages_this_second.load(Ordering::Relaxed)
@@ -289,6 +475,8 @@ impl Session {
 
         // Increment total drops
         self.total_drops.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clasp_messages_dropped_total").increment(1);
 
         // Check if we're in a new window
         let window_start = self.drop_window_start.load(Ordering::Relaxed);
@@ -324,6 +512,11 @@ impl Session {
         self.drops_in_window.load(Ordering::Relaxed)
     }
 
+    /// Messages waiting in the transport's send buffer, if it has one
+    pub fn queued(&self) -> Option<usize> {
+        self.sender.queued()
+    }
+
     /// Check if this session is a federation peer
     #[cfg(feature = "federation")]
     pub fn is_federation_peer(&self) -> bool {
@@ -353,6 +546,32 @@ impl Session {
     pub fn set_federation_namespaces(&self, patterns: Vec<String>) {
         *self.federation_namespaces.write() = patterns;
     }
+
+    /// Get the namespaces gossiped through this federation peer
+    #[cfg(feature = "federation")]
+    pub fn federation_routes(&self) -> Vec<(clasp_core::GossipOrigin, Vec<String>)> {
+        self.federation_routes.read().values().cloned().collect()
+    }
+
+    /// Record (or, with no patterns, forget) namespaces gossiped through
+    /// this peer. Returns true if anything changed.
+    #[cfg(feature = "federation")]
+    pub fn set_federation_route(
+        &self,
+        origin: clasp_core::GossipOrigin,
+        patterns: Vec<String>,
+    ) -> bool {
+        let mut routes = self.federation_routes.write();
+        if patterns.is_empty() {
+            return routes.remove(&origin.router_id).is_some();
+        }
+        let route = (origin, patterns);
+        if routes.get(&route.0.router_id) == Some(&route) {
+            return false;
+        }
+        routes.insert(route.0.router_id.clone(), route);
+        true
+    }
 }
 
 impl std::fmt::Debug for Session {
@@ -363,6 +582,7 @@ impl std::fmt::Debug for Session {
             .field("features", &self.features)
             .field("authenticated", &self.authenticated)
             .field("subject", &self.subject)
+            .field("entity", &self.entity)
             .field("scopes", &self.scopes.len())
             .finish()
     }
@@ -0,0 +1,205 @@
+//! Concurrent login limits per token subject
+//!
+//! Caps how many sessions a single subject (user, device or entity ID) may
+//! hold at once, for licensing and to stop credential sharing. The limit is
+//! enforced at HELLO:
+//! - [`LimitPolicy::RejectNew`] refuses the new login with ERROR 304
+//! - [`LimitPolicy::KickOldest`] admits it and disconnects the subject's
+//!   oldest sessions with ERROR 305, publishing an event on
+//!   [`KICK_EVENT_ADDRESS`] for each one
+//!
+//! Kicks happen after any [session handoff](crate::handoff), so with a limit
+//! of one and `HandoffPolicy::Transfer` the new device takes over the old
+//! device's subscriptions before the old device is disconnected.
+
+use clasp_core::error::ErrorCode;
+use clasp_core::{codec, ErrorMessage, Message, PublishMessage, SignalType, Value};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::handlers::broadcast_to_subscriber_list;
+use crate::session::{Session, SessionId};
+use crate::subscription::SubscriptionManager;
+
+/// Address of the event published when a session is kicked
+pub const KICK_EVENT_ADDRESS: &str = "/clasp/session/kicked";
+
+/// What to do when a subject is already at its session limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Refuse the new login
+    #[default]
+    RejectNew,
+    /// Admit the new login and disconnect the oldest session
+    KickOldest,
+}
+
+impl std::str::FromStr for LimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject-new" | "reject" => Ok(Self::RejectNew),
+            "kick-oldest" | "kick" => Ok(Self::KickOldest),
+            other => Err(format!(
+                "unknown session limit policy '{}' (expected reject-new or kick-oldest)",
+                other
+            )),
+        }
+    }
+}
+
+/// Per-subject session limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SessionLimit {
+    /// Maximum simultaneous sessions per subject (0 = unlimited)
+    pub max_per_subject: usize,
+    /// What happens to a login over the limit
+    pub policy: LimitPolicy,
+}
+
+impl SessionLimit {
+    /// Limit every subject to `max` sessions
+    pub fn new(max_per_subject: usize, policy: LimitPolicy) -> Self {
+        Self {
+            max_per_subject,
+            policy,
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_per_subject == 0
+    }
+}
+
+/// Live sessions held by `subject`, oldest first, excluding `exclude`
+fn sessions_for(
+    sessions: &DashMap<SessionId, Arc<Session>>,
+    subject: &str,
+    exclude: Option<&SessionId>,
+) -> Vec<Arc<Session>> {
+    let mut held: Vec<Arc<Session>> = sessions
+        .iter()
+        .filter(|entry| Some(entry.key()) != exclude)
+        .filter(|entry| entry.value().subject.as_deref() == Some(subject))
+        .filter(|entry| entry.value().is_connected())
+        .map(|entry| Arc::clone(entry.value()))
+        .collect();
+    #[cfg(feature = "federation")]
+    held.retain(|s| !s.is_federation_peer());
+    held.sort_by_key(|s| s.created_at);
+    held
+}
+
+/// Check a login before its session is created. Returns the ERROR to send if
+/// the subject is at its limit and the policy rejects new logins.
+pub(crate) fn check_login(
+    limit: &SessionLimit,
+    subject: Option<&str>,
+    sessions: &DashMap<SessionId, Arc<Session>>,
+) -> Option<Message> {
+    if limit.is_unlimited() || limit.policy != LimitPolicy::RejectNew {
+        return None;
+    }
+    let subject = subject?;
+    let held = sessions_for(sessions, subject, None).len();
+    if held < limit.max_per_subject {
+        return None;
+    }
+    warn!(
+        "Login rejected: subject {} already has {} session(s) (limit {})",
+        subject, held, limit.max_per_subject
+    );
+    #[cfg(feature = "metrics")]
+    metrics::counter!("clasp_errors_total", "code" => "304").increment(1);
+    Some(Message::Error(ErrorMessage {
+        code: ErrorCode::SessionLimit as u16,
+        message: format!(
+            "Session limit reached ({} per subject)",
+            limit.max_per_subject
+        ),
+        address: None,
+        correlation_id: None,
+    }))
+}
+
+/// After `new_session` is admitted, disconnect the subject's oldest sessions
+/// until it is back within the limit. Returns the kicked session IDs.
+pub(crate) async fn kick_excess(
+    limit: &SessionLimit,
+    new_session: &Arc<Session>,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) -> Vec<SessionId> {
+    if limit.is_unlimited() || limit.policy != LimitPolicy::KickOldest {
+        return Vec::new();
+    }
+    let Some(subject) = new_session.subject.as_deref() else {
+        return Vec::new();
+    };
+    let held = sessions_for(sessions, subject, Some(&new_session.id));
+    // The new session counts towards the limit
+    let excess = (held.len() + 1).saturating_sub(limit.max_per_subject);
+
+    let mut kicked = Vec::with_capacity(excess);
+    for old in held.into_iter().take(excess) {
+        info!(
+            "Kicking session {} of subject {} for new session {}",
+            old.id, subject, new_session.id
+        );
+        let notice = Message::Error(ErrorMessage {
+            code: ErrorCode::SessionKicked as u16,
+            message: format!(
+                "Disconnected: session limit reached ({} per subject)",
+                limit.max_per_subject
+            ),
+            address: None,
+            correlation_id: None,
+        });
+        if let Ok(bytes) = codec::encode(&notice) {
+            let _ = old.send(bytes).await;
+        }
+        if let Err(e) = old.close().await {
+            warn!("Failed to close kicked session {}: {}", old.id, e);
+        }
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clasp_sessions_kicked_total").increment(1);
+
+        publish_kick(subject, &old.id, &new_session.id, sessions, subscriptions);
+        kicked.push(old.id.clone());
+    }
+    kicked
+}
+
+/// Publish a kick event to subscribers of [`KICK_EVENT_ADDRESS`]
+fn publish_kick(
+    subject: &str,
+    kicked: &SessionId,
+    by: &SessionId,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let mut event = HashMap::new();
+    event.insert("subject".to_string(), Value::String(subject.to_string()));
+    event.insert("session".to_string(), Value::String(kicked.clone()));
+    event.insert("by".to_string(), Value::String(by.clone()));
+
+    let msg = Message::Publish(PublishMessage {
+        address: KICK_EVENT_ADDRESS.to_string(),
+        signal: Some(SignalType::Event),
+        value: Some(Value::Map(event)),
+        payload: None,
+        samples: None,
+        rate: None,
+        id: None,
+        phase: None,
+        timestamp: Some(clasp_core::time::now()),
+        timeline: None,
+    });
+    let subscribers = subscriptions.find_subscribers(KICK_EVENT_ADDRESS, Some(SignalType::Event));
+    if let Ok(bytes) = codec::encode(&msg) {
+        broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+    }
+}
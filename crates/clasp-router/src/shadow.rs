@@ -0,0 +1,235 @@
+//! Device shadows: desired vs reported state
+//!
+//! With [`ShadowConfig::enabled`], every param whose address ends in
+//! `/desired` or `/reported` is one side of a device shadow (see
+//! [`clasp_core::shadow`]). After each write to either side the router
+//! recomputes what the device still has to apply and SETs it on the
+//! shadow's `/delta` address, or `null` once both sides agree. Devices
+//! subscribe to `<base>/delta`, apply it and write `<base>/reported`; apps
+//! write `<base>/desired` and watch the delta drain.
+//!
+//! Deltas are written by the router (writer [`SHADOW_WRITER`]); clients
+//! can't SET a `/delta` address while shadows are enabled.
+//!
+//! With [`ShadowConfig::desired_ttl`], a desire the device hasn't fulfilled
+//! within the TTL is withdrawn: `desired` and `delta` are both set to
+//! `null`, so a device that comes back much later doesn't act on stale
+//! intent. Each new write to `desired` restarts the clock.
+
+use clasp_core::shadow::{self, ShadowPart};
+use clasp_core::{codec, Message, SetMessage, SignalType, Value};
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::handlers::broadcast_to_subscriber_list;
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Writer recorded on router-computed deltas
+pub const SHADOW_WRITER: &str = "shadow";
+
+/// Device shadow settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowConfig {
+    /// Compute deltas for `/desired` and `/reported` params
+    pub enabled: bool,
+    /// Withdraw desires the device hasn't met within this time (None = keep)
+    pub desired_ttl: Option<Duration>,
+}
+
+impl ShadowConfig {
+    /// Whether `address` is a delta the router computes (and clients can't SET)
+    pub fn is_computed(&self, address: &str) -> bool {
+        self.enabled && matches!(shadow::split(address), Some((_, ShadowPart::Delta)))
+    }
+}
+
+/// Deadlines of unfulfilled desires, by shadow base address
+#[derive(Debug, Default)]
+pub struct Shadows {
+    deadlines: DashMap<String, Instant>,
+}
+
+impl Shadows {
+    /// Number of shadows whose desired state is waiting on the device
+    pub fn unfulfilled(&self) -> usize {
+        self.deadlines.len()
+    }
+
+    /// Recompute the delta of the shadow `address` belongs to, after a write
+    pub(crate) fn after_set(
+        &self,
+        address: &str,
+        config: &ShadowConfig,
+        state: &RouterState,
+        sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+        subscriptions: &SubscriptionManager,
+    ) {
+        if !config.enabled {
+            return;
+        }
+        let Some((base, part)) = shadow::split(address) else {
+            return;
+        };
+        if part == ShadowPart::Delta {
+            return;
+        }
+
+        let desired = state.get(&shadow::address(base, ShadowPart::Desired));
+        let reported = state.get(&shadow::address(base, ShadowPart::Reported));
+        let delta = desired
+            .as_ref()
+            .and_then(|desired| shadow::delta(desired, reported.as_ref()));
+
+        match delta {
+            Some(delta) => {
+                if let (ShadowPart::Desired, Some(ttl)) = (part, config.desired_ttl) {
+                    self.deadlines
+                        .insert(base.to_string(), Instant::now() + ttl);
+                }
+                write_delta(base, delta, state, sessions, subscriptions);
+            }
+            None => {
+                self.deadlines.remove(base);
+                write_delta(base, Value::Null, state, sessions, subscriptions);
+            }
+        }
+    }
+
+    /// Withdraw desires whose TTL ran out. Returns how many were withdrawn.
+    pub(crate) fn expire(
+        &self,
+        now: Instant,
+        state: &RouterState,
+        sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+        subscriptions: &SubscriptionManager,
+    ) -> usize {
+        let mut expired = Vec::new();
+        self.deadlines.retain(|base, deadline| {
+            let keep = *deadline > now;
+            if !keep {
+                expired.push(base.clone());
+            }
+            keep
+        });
+
+        for base in &expired {
+            info!("Desired state of {} expired unfulfilled", base);
+            let desired = shadow::address(base, ShadowPart::Desired);
+            write(&desired, Value::Null, state, sessions, subscriptions);
+            write_delta(base, Value::Null, state, sessions, subscriptions);
+        }
+        expired.len()
+    }
+}
+
+/// SET `<base>/delta` unless it already holds `delta`
+fn write_delta(
+    base: &str,
+    delta: Value,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let address = shadow::address(base, ShadowPart::Delta);
+    let current = state.get(&address);
+    // Don't create a delta param for shadows that never diverged
+    if current.as_ref() == Some(&delta) || (current.is_none() && delta == Value::Null) {
+        return;
+    }
+    write(&address, delta, state, sessions, subscriptions);
+}
+
+/// SET a param as the router and send it to its subscribers
+fn write(
+    address: &str,
+    value: Value,
+    state: &RouterState,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let writer = SHADOW_WRITER.to_string();
+    match state.set(address, value.clone(), &writer, None, false, false, None) {
+        Ok(revision) => {
+            let subscribers = subscriptions.find_subscribers(address, Some(SignalType::Param));
+            let msg = Message::Set(SetMessage {
+                address: address.to_string(),
+                value,
+                revision: Some(revision),
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            });
+            if let Ok(bytes) = codec::encode(&msg) {
+                broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+            }
+        }
+        Err(e) => warn!("Shadow SET to {} failed: {:?}", address, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(state: &RouterState, address: &str, value: Value) {
+        state
+            .set(address, value, &"s1".to_string(), None, false, false, None)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delta_follows_both_sides() {
+        let state = RouterState::new();
+        let sessions = Arc::new(DashMap::new());
+        let subscriptions = SubscriptionManager::new();
+        let config = ShadowConfig {
+            enabled: true,
+            desired_ttl: Some(Duration::from_secs(60)),
+        };
+        let shadows = Shadows::default();
+
+        // A report alone doesn't create a delta
+        set(&state, "/lamp/reported", Value::Int(1));
+        shadows.after_set("/lamp/reported", &config, &state, &sessions, &subscriptions);
+        assert_eq!(state.get("/lamp/delta"), None);
+
+        set(&state, "/lamp/desired", Value::Int(2));
+        shadows.after_set("/lamp/desired", &config, &state, &sessions, &subscriptions);
+        assert_eq!(state.get("/lamp/delta"), Some(Value::Int(2)));
+        assert_eq!(shadows.unfulfilled(), 1);
+
+        set(&state, "/lamp/reported", Value::Int(2));
+        shadows.after_set("/lamp/reported", &config, &state, &sessions, &subscriptions);
+        assert_eq!(state.get("/lamp/delta"), Some(Value::Null));
+        assert_eq!(shadows.unfulfilled(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unfulfilled_desire_expires() {
+        let state = RouterState::new();
+        let sessions = Arc::new(DashMap::new());
+        let subscriptions = SubscriptionManager::new();
+        let config = ShadowConfig {
+            enabled: true,
+            desired_ttl: Some(Duration::from_secs(5)),
+        };
+        let shadows = Shadows::default();
+
+        set(&state, "/lamp/desired", Value::Int(2));
+        shadows.after_set("/lamp/desired", &config, &state, &sessions, &subscriptions);
+        let now = Instant::now();
+        assert_eq!(shadows.expire(now, &state, &sessions, &subscriptions), 0);
+
+        let later = now + Duration::from_secs(6);
+        assert_eq!(shadows.expire(later, &state, &sessions, &subscriptions), 1);
+        assert_eq!(state.get("/lamp/desired"), Some(Value::Null));
+        assert_eq!(state.get("/lamp/delta"), Some(Value::Null));
+        assert_eq!(shadows.unfulfilled(), 0);
+    }
+}
@@ -1,18 +1,35 @@
 //! Router state management
 
 use clasp_core::state::{ParamState, StateStore, StateStoreConfig, UpdateError};
-use clasp_core::{ParamValue, SetMessage, SignalDefinition, SnapshotMessage, Value};
+use clasp_core::{
+    ParamValue, PublishMessage, SetMessage, SignalDefinition, SnapshotMessage, Ttl, Value,
+};
 use dashmap::DashMap;
 use parking_lot::RwLock;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+#[cfg(feature = "journal")]
+use crate::journal_partition::JournalPartition;
 #[cfg(feature = "journal")]
 use clasp_core::SignalType;
 #[cfg(feature = "journal")]
 use clasp_journal::{Journal, JournalEntry};
 #[cfg(feature = "journal")]
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use crate::backend::{StateBackend, StoredParam};
+use crate::branch::Branches;
+use crate::canonical::{AddressPolicy, CanonicalizeReport};
+use crate::cluster::Cluster;
+use crate::durable_session::SessionStore;
+use crate::error::RouterError;
+use crate::maintenance::Maintenance;
+use crate::shadow::Shadows;
 use crate::SessionId;
 
 /// Signal entry with registration time for cleanup
@@ -35,6 +52,8 @@ pub struct RouterStateConfig {
     pub signal_ttl: Option<Duration>,
     /// Maximum number of signals (None = unlimited)
     pub max_signals: Option<usize>,
+    /// Address patterns whose SETs are deleted when the writing session ends
+    pub session_scoped: Vec<String>,
 }
 
 impl Default for RouterStateConfig {
@@ -43,6 +62,7 @@ impl Default for RouterStateConfig {
             param_config: StateStoreConfig::default(),
             signal_ttl: Some(Duration::from_secs(3600)), // 1 hour
             max_signals: Some(10_000),
+            session_scoped: Vec::new(),
         }
     }
 }
@@ -54,13 +74,52 @@ impl RouterStateConfig {
             param_config: StateStoreConfig::unlimited(),
             signal_ttl: None,
             max_signals: None,
+            session_scoped: Vec::new(),
         }
     }
 }
 
+/// Writer recorded for params applied by [`RouterState::seed_from_file`]
+pub const SEED_WRITER: &str = "seed";
+
+/// One entry in a seed file: a bare value, or a value with options
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SeedEntry {
+    Full(SeedParam),
+    Bare(Value),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedParam {
+    value: Value,
+    /// Seconds until expiry; 0 never expires
+    #[serde(default)]
+    ttl: Option<u32>,
+    /// Lock the param to the seed writer so clients cannot change it
+    #[serde(default)]
+    lock: bool,
+}
+
 /// Listener callback type
 type ListenerFn = Box<dyn Fn(&str, &Value) + Send + Sync>;
 
+/// Each merged address with its value and new revision, or the first
+/// refused address with its error
+pub type MergeResult = Result<Vec<(String, Value, u64)>, (String, UpdateError)>;
+
+/// A page of [`RouterState::query_signals_page`]
+#[derive(Debug, Clone, Default)]
+pub struct SignalPage {
+    /// Signals on this page, in address order
+    pub signals: Vec<SignalDefinition>,
+    /// Whether more matching signals follow this page
+    pub more: bool,
+    /// Signals matching the pattern across all pages
+    pub total: usize,
+}
+
 /// Global router state
 pub struct RouterState {
     /// Parameter state store
@@ -74,6 +133,24 @@ pub struct RouterState {
     /// Optional journal for state persistence and replay
     #[cfg(feature = "journal")]
     journal: Option<Arc<dyn Journal>>,
+    /// Journals bound to address patterns (checked before `journal`)
+    #[cfg(feature = "journal")]
+    journal_partitions: Vec<JournalPartition>,
+    /// Journal appends spawned but not yet written
+    #[cfg(feature = "journal")]
+    journal_pending: Arc<AtomicU64>,
+    /// Router-wide maintenance switch
+    maintenance: Maintenance,
+    /// Unfulfilled device shadow desires
+    shadows: Shadows,
+    /// Disconnected durable sessions waiting to resume
+    durable_sessions: SessionStore,
+    /// Copy-on-write overlays being programmed alongside live state
+    branches: Branches,
+    /// Optional durable store that param writes go through to
+    backend: Option<Arc<dyn StateBackend>>,
+    /// Link to the other routers of a cluster
+    cluster: Option<Arc<Cluster>>,
 }
 
 impl RouterState {
@@ -90,9 +167,39 @@ impl RouterState {
             config,
             #[cfg(feature = "journal")]
             journal: None,
+            #[cfg(feature = "journal")]
+            journal_partitions: Vec::new(),
+            #[cfg(feature = "journal")]
+            journal_pending: Arc::new(AtomicU64::new(0)),
+            maintenance: Maintenance::default(),
+            shadows: Shadows::default(),
+            durable_sessions: SessionStore::default(),
+            branches: Branches::default(),
+            backend: None,
+            cluster: None,
         }
     }
 
+    /// Maintenance mode switch (see [`crate::maintenance`])
+    pub fn maintenance(&self) -> &Maintenance {
+        &self.maintenance
+    }
+
+    /// Device shadow tracking (see [`crate::shadow`])
+    pub fn shadows(&self) -> &Shadows {
+        &self.shadows
+    }
+
+    /// Suspended durable sessions (see [`crate::durable_session`])
+    pub fn durable_sessions(&self) -> &SessionStore {
+        &self.durable_sessions
+    }
+
+    /// State branches (see [`crate::branch`])
+    pub fn branches(&self) -> &Branches {
+        &self.branches
+    }
+
     /// Set the journal for state persistence and replay
     #[cfg(feature = "journal")]
     pub fn set_journal(&mut self, journal: Arc<dyn Journal>) {
@@ -105,8 +212,271 @@ impl RouterState {
         self.journal.as_ref()
     }
 
+    /// Route addresses matching a pattern to their own journal (see
+    /// [`crate::journal_partition`]). Partitions are checked in the order
+    /// they were added.
+    #[cfg(feature = "journal")]
+    pub fn add_journal_partition(&mut self, partition: JournalPartition) {
+        self.journal_partitions.push(partition);
+    }
+
+    /// Journal partitions, in routing order
+    #[cfg(feature = "journal")]
+    pub fn journal_partitions(&self) -> &[JournalPartition] {
+        &self.journal_partitions
+    }
+
+    /// The journal that records entries for `address`
+    #[cfg(feature = "journal")]
+    pub fn journal_for(&self, address: &str) -> Option<&Arc<dyn Journal>> {
+        self.journal_partitions
+            .iter()
+            .find(|p| p.matches(address))
+            .map(|p| &p.journal)
+            .or(self.journal.as_ref())
+    }
+
+    /// Journal appends issued but not yet written, i.e. how far the journal
+    /// lags behind the state
+    #[cfg(feature = "journal")]
+    pub fn journal_lag(&self) -> u64 {
+        self.journal_pending.load(Ordering::Relaxed)
+    }
+
+    /// Fire-and-forget journal append, counted in [`Self::journal_lag`]
+    #[cfg(feature = "journal")]
+    fn spawn_journal_append(&self, journal: Arc<dyn Journal>, entry: JournalEntry) {
+        let pending = Arc::clone(&self.journal_pending);
+        pending.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            let _ = journal.append(entry).await;
+            pending.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Journal a batch of applied SETs with one atomic append per journal
+    /// (see [`Journal::append_batch`]), counted in [`Self::journal_lag`]
+    #[cfg(feature = "journal")]
+    fn journal_batch(
+        &self,
+        updates: &[(String, Value, Option<Ttl>)],
+        revisions: &[u64],
+        writer: &SessionId,
+    ) {
+        let timestamp = clasp_core::time::now();
+        let mut batches: Vec<(Arc<dyn Journal>, Vec<JournalEntry>)> = Vec::new();
+        for ((address, value, ttl), revision) in updates.iter().zip(revisions) {
+            if *ttl == Some(Ttl::Session) {
+                continue;
+            }
+            let Some(journal) = self.journal_for(address) else {
+                continue;
+            };
+            let entry = JournalEntry::from_set(
+                address.clone(),
+                value.clone(),
+                *revision,
+                writer.clone(),
+                timestamp,
+            );
+            match batches.iter_mut().find(|(j, _)| Arc::ptr_eq(j, journal)) {
+                Some((_, entries)) => entries.push(entry),
+                None => batches.push((Arc::clone(journal), vec![entry])),
+            }
+        }
+
+        for (journal, entries) in batches {
+            let pending = Arc::clone(&self.journal_pending);
+            pending.fetch_add(1, Ordering::Relaxed);
+            tokio::spawn(async move {
+                if let Err(e) = journal.append_batch(entries).await {
+                    tracing::warn!("Journal batch append failed: {}", e);
+                }
+                pending.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+    }
+
+    /// The journal federation peers stream from in a journal sync, and
+    /// resumed durable sessions replay from
+    ///
+    /// Only a lone default journal qualifies: partitions number their entries
+    /// separately, so no single sequence number marks a cut across them.
+    #[cfg(feature = "journal")]
+    pub fn federation_journal(&self) -> Option<&Arc<dyn Journal>> {
+        if self.journal_partitions.is_empty() {
+            self.journal.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Whether any journal (default or partition) is configured
+    #[cfg(feature = "journal")]
+    pub fn has_journal(&self) -> bool {
+        self.journal.is_some() || !self.journal_partitions.is_empty()
+    }
+
+    /// Every configured journal: the default first, then the partitions
+    #[cfg(feature = "journal")]
+    fn journals(&self) -> impl Iterator<Item = &Arc<dyn Journal>> {
+        self.journal
+            .iter()
+            .chain(self.journal_partitions.iter().map(|p| &p.journal))
+    }
+
+    /// Write params through to a durable backend (see [`crate::backend`])
+    pub fn set_backend(&mut self, backend: Arc<dyn StateBackend>) {
+        self.backend = Some(backend);
+    }
+
+    /// Get a reference to the state backend (if configured)
+    pub fn backend(&self) -> Option<&Arc<dyn StateBackend>> {
+        self.backend.as_ref()
+    }
+
+    /// Share param state with other routers (see [`crate::cluster`])
+    pub fn set_cluster(&mut self, cluster: Arc<Cluster>) {
+        self.cluster = Some(cluster);
+    }
+
+    /// Get a reference to the cluster link (if configured)
+    pub fn cluster(&self) -> Option<&Arc<Cluster>> {
+        self.cluster.as_ref()
+    }
+
+    /// Pass a SET applied to live state at `revision` to the other nodes
+    /// of the cluster, unless the param stays on this node
+    pub fn cluster_forward_set(&self, set: &SetMessage, revision: u64, writer: &str) {
+        let Some(ref cluster) = self.cluster else {
+            return;
+        };
+        if set.ttl.is_some_and(|ttl| ttl != Ttl::Never)
+            || set.branch.is_some()
+            || !Cluster::shares(&set.address)
+            || self.is_session_scoped(&set.address)
+        {
+            return;
+        }
+        cluster.forward_set(set, revision, writer);
+    }
+
+    /// Pass a routed PUBLISH to the other nodes of the cluster
+    pub fn cluster_forward_publish(&self, publish: &PublishMessage, writer: &str) {
+        if let Some(ref cluster) = self.cluster {
+            cluster.forward_publish(publish, writer);
+        }
+    }
+
+    /// Load params and signal definitions from the backend, keeping param
+    /// revisions and writers. Call once at startup, before journal recovery
+    /// or seeding. Returns the number of params restored.
+    pub fn restore_from_backend(&self) -> crate::Result<usize> {
+        let Some(ref backend) = self.backend else {
+            return Err(RouterError::Config("no state backend configured".into()));
+        };
+        let stored = backend.load()?;
+
+        let mut params = self.params.write();
+        let mut restored = 0;
+        for (address, param) in stored {
+            if param.ttl == Some(Ttl::Session) {
+                continue;
+            }
+            let locked = param.lock_holder.is_some();
+            if let Err(e) = params.set(
+                &address,
+                param.value,
+                &param.writer,
+                None,
+                locked,
+                false,
+                param.ttl,
+            ) {
+                tracing::warn!("Not restoring {}: {}", address, e);
+                continue;
+            }
+            if let Some(state) = params.get_mut(&address) {
+                state.revision = param.revision;
+                state.timestamp = param.timestamp;
+                state.lock_holder = param.lock_holder;
+                state.origin = param.origin;
+            }
+            restored += 1;
+        }
+        drop(params);
+
+        let signals = backend.load_signals()?;
+        let signal_count = signals.len();
+        self.insert_signals(signals);
+
+        tracing::info!(
+            "Restored {} params and {} signals from {} backend",
+            restored,
+            signal_count,
+            backend.name()
+        );
+        Ok(restored)
+    }
+
+    /// Write the current state of `address` through to the backend
+    fn persist(&self, address: &str) {
+        let Some(ref backend) = self.backend else {
+            return;
+        };
+        let param = self.params.read().get(address).map(StoredParam::from);
+        let result = match param {
+            // Session-scoped state dies with its writer, so never store it
+            Some(param) if param.ttl == Some(Ttl::Session) => {
+                backend.remove(&[address.to_string()])
+            }
+            Some(param) => backend.put(address, &param),
+            None => backend.remove(&[address.to_string()]),
+        };
+        if let Err(e) = result {
+            tracing::warn!(
+                "State backend {} failed to store {}: {}",
+                backend.name(),
+                address,
+                e
+            );
+        }
+    }
+
+    /// Delete removed params from the backend
+    fn persist_removed(&self, addresses: &[String]) {
+        let Some(ref backend) = self.backend else {
+            return;
+        };
+        if addresses.is_empty() {
+            return;
+        }
+        if let Err(e) = backend.remove(addresses) {
+            tracing::warn!(
+                "State backend {} failed to remove {} params: {}",
+                backend.name(),
+                addresses.len(),
+                e
+            );
+        }
+    }
+
     /// Register signals from an ANNOUNCE message
     pub fn register_signals(&self, signals: Vec<SignalDefinition>) {
+        if let Some(ref backend) = self.backend {
+            if let Err(e) = backend.put_signals(&signals) {
+                tracing::warn!(
+                    "State backend {} failed to store {} signals: {}",
+                    backend.name(),
+                    signals.len(),
+                    e
+                );
+            }
+        }
+        self.insert_signals(signals);
+    }
+
+    fn insert_signals(&self, signals: Vec<SignalDefinition>) {
         let now = Instant::now();
         for signal in signals {
             let address = signal.address.clone();
@@ -130,6 +500,68 @@ impl RouterState {
             .collect()
     }
 
+    /// One page of the signals matching a pattern, in address order: at
+    /// most `limit` signals with addresses after `after`
+    pub fn query_signals_page(
+        &self,
+        pattern: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> SignalPage {
+        let mut total = 0;
+        let mut addresses: Vec<String> = self
+            .signals
+            .iter()
+            .filter(|entry| clasp_core::address::glob_match(pattern, entry.key()))
+            .inspect(|_| total += 1)
+            .filter(|entry| after.is_none_or(|after| entry.key().as_str() > after))
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        let more = addresses.len() > limit;
+        if more {
+            addresses.select_nth_unstable(limit);
+            addresses.truncate(limit);
+        }
+        addresses.sort_unstable();
+
+        let signals = addresses
+            .iter()
+            .filter_map(|address| self.signals.get(address))
+            .map(|entry| entry.definition.clone())
+            .collect();
+        SignalPage {
+            signals,
+            more,
+            total,
+        }
+    }
+
+    /// Unit announced for an address, from an exact signal definition or
+    /// one whose address is a pattern matching it
+    pub fn signal_unit(&self, address: &str) -> Option<String> {
+        let unit_of = |def: &SignalDefinition| def.meta.as_ref().and_then(|m| m.unit.clone());
+        if let Some(entry) = self.signals.get(address) {
+            return unit_of(&entry.definition);
+        }
+        self.signals
+            .iter()
+            .filter(|entry| clasp_core::address::glob_match(entry.key(), address))
+            .find_map(|entry| unit_of(&entry.definition))
+    }
+
+    /// Signal definition covering an address: the exact one, else one
+    /// whose address is a pattern matching it
+    pub fn signal_definition(&self, address: &str) -> Option<SignalDefinition> {
+        if let Some(entry) = self.signals.get(address) {
+            return Some(entry.definition.clone());
+        }
+        self.signals
+            .iter()
+            .find(|entry| clasp_core::address::glob_match(entry.key(), address))
+            .map(|entry| entry.definition.clone())
+    }
+
     /// Get all registered signals
     pub fn all_signals(&self) -> Vec<SignalDefinition> {
         self.signals
@@ -147,23 +579,42 @@ impl RouterState {
     /// Returns the number of signals removed
     pub fn cleanup_stale_signals(&self, ttl: Duration) -> usize {
         let now = Instant::now();
-        let before = self.signals.len();
-        self.signals
-            .retain(|_, entry| now.duration_since(entry.last_accessed) < ttl);
-        before - self.signals.len()
+        let mut removed = Vec::new();
+        self.signals.retain(|address, entry| {
+            let keep = now.duration_since(entry.last_accessed) < ttl;
+            if !keep {
+                removed.push(address.clone());
+            }
+            keep
+        });
+        if let Some(ref backend) = self.backend {
+            if !removed.is_empty() {
+                if let Err(e) = backend.remove_signals(&removed) {
+                    tracing::warn!(
+                        "State backend {} failed to remove {} signals: {}",
+                        backend.name(),
+                        removed.len(),
+                        e
+                    );
+                }
+            }
+        }
+        removed.len()
     }
 
     /// Remove stale params using the configured TTL
     /// Returns the number of params removed
     pub fn cleanup_stale_params(&self, ttl: Duration) -> usize {
-        self.params.write().cleanup_stale(ttl)
+        let removed = self.params.write().remove_stale(ttl);
+        self.persist_removed(&removed);
+        removed.len()
     }
 
     /// Run all cleanup operations using configured TTLs
     /// Returns (params_removed, signals_removed)
     pub fn cleanup_stale(&self) -> (usize, usize) {
         let params_removed = if let Some(ttl) = self.config.param_config.param_ttl {
-            self.params.write().cleanup_stale(ttl)
+            self.cleanup_stale_params(ttl)
         } else {
             0
         };
@@ -202,6 +653,7 @@ impl RouterState {
             self.params
                 .write()
                 .set(address, value.clone(), writer, revision, lock, unlock, ttl)?;
+        self.persist(address);
 
         // Notify listeners
         if let Some(listeners) = self.listeners.get(address) {
@@ -215,6 +667,11 @@ impl RouterState {
 
     /// Apply a SET message
     pub fn apply_set(&self, msg: &SetMessage, writer: &SessionId) -> Result<u64, UpdateError> {
+        let ttl = if self.is_session_scoped(&msg.address) {
+            Some(Ttl::Session)
+        } else {
+            msg.ttl
+        };
         let result = self.set(
             &msg.address,
             msg.value.clone(),
@@ -222,12 +679,16 @@ impl RouterState {
             msg.revision,
             msg.lock,
             msg.unlock,
-            msg.ttl,
+            ttl,
         )?;
 
-        // Fire-and-forget journal append
+        // Fire-and-forget journal append. Session-scoped values are skipped,
+        // since replaying them would resurrect state whose owner is gone.
         #[cfg(feature = "journal")]
-        if let Some(ref journal) = self.journal {
+        if let Some(journal) = self
+            .journal_for(&msg.address)
+            .filter(|_| ttl != Some(Ttl::Session))
+        {
             let entry = JournalEntry::from_set(
                 msg.address.clone(),
                 msg.value.clone(),
@@ -235,15 +696,362 @@ impl RouterState {
                 writer.clone(),
                 clasp_core::time::now(),
             );
-            let journal = Arc::clone(journal);
-            tokio::spawn(async move {
-                let _ = journal.append(entry).await;
-            });
+            self.spawn_journal_append(Arc::clone(journal), entry);
         }
 
         Ok(result)
     }
 
+    /// Apply a param replicated from another router, keeping its revision,
+    /// writer and timestamp.
+    ///
+    /// Conflict strategies and locks are bypassed: the source router already
+    /// resolved the write. Revisions below the current one are ignored, as is
+    /// the current revision unless `writer` sorts after the current writer,
+    /// so replaying entries twice is harmless and routers that wrote the same
+    /// revision concurrently settle on one value. Returns whether the param
+    /// changed.
+    pub fn apply_replicated(
+        &self,
+        address: &str,
+        value: Value,
+        writer: &str,
+        revision: u64,
+        timestamp: u64,
+    ) -> bool {
+        {
+            let mut params = self.params.write();
+            match params.get_mut(address) {
+                Some(param)
+                    if param.revision > revision
+                        || (param.revision == revision && param.writer.as_str() >= writer) =>
+                {
+                    return false
+                }
+                Some(param) => {
+                    param.value = value.clone();
+                    param.writer = writer.to_string();
+                }
+                None => {
+                    if let Err(e) =
+                        params.set(address, value.clone(), writer, None, false, false, None)
+                    {
+                        tracing::warn!("Not replicating {}: {}", address, e);
+                        return false;
+                    }
+                }
+            }
+            if let Some(param) = params.get_mut(address) {
+                param.revision = revision;
+                param.timestamp = timestamp;
+                param.touch();
+            }
+        }
+        self.persist(address);
+
+        if let Some(listeners) = self.listeners.get(address) {
+            for listener in listeners.iter() {
+                listener(address, &value);
+            }
+        }
+        true
+    }
+
+    /// Set several params atomically (see `StateStore::set_all`)
+    ///
+    /// Listeners and the journal only see the batch once it has been
+    /// applied. Returns the new revisions in order, or the index of the
+    /// first rejected update with its error.
+    pub fn set_all(
+        &self,
+        updates: &[(String, Value)],
+        writer: &SessionId,
+        dry_run: bool,
+    ) -> Result<Vec<u64>, (usize, UpdateError)> {
+        let updates: Vec<(String, Value, Option<Ttl>)> = updates
+            .iter()
+            .map(|(address, value)| {
+                let ttl = self.is_session_scoped(address).then_some(Ttl::Session);
+                (address.clone(), value.clone(), ttl)
+            })
+            .collect();
+        let revisions = self.params.write().set_all(&updates, writer, dry_run)?;
+        if !dry_run {
+            self.committed(&updates, &revisions, writer);
+        }
+        Ok(revisions)
+    }
+
+    /// Apply SET messages atomically, each with its own revision check,
+    /// lock and TTL (see `StateStore::apply_all`)
+    ///
+    /// If any SET is rejected, none are applied. Listeners and the journal
+    /// only see the batch once it has been applied. Returns the new
+    /// revisions in order, or the index of the first rejected SET with its
+    /// error.
+    pub fn apply_all(
+        &self,
+        sets: &[SetMessage],
+        writer: &SessionId,
+    ) -> Result<Vec<u64>, (usize, UpdateError)> {
+        let sets: Vec<SetMessage> = sets
+            .iter()
+            .map(|set| SetMessage {
+                ttl: if self.is_session_scoped(&set.address) {
+                    Some(Ttl::Session)
+                } else {
+                    set.ttl
+                },
+                ..set.clone()
+            })
+            .collect();
+        let revisions = self.params.write().apply_all(&sets, writer)?;
+        let updates: Vec<(String, Value, Option<Ttl>)> = sets
+            .into_iter()
+            .map(|set| (set.address, set.value, set.ttl))
+            .collect();
+        self.committed(&updates, &revisions, writer);
+        Ok(revisions)
+    }
+
+    /// Persist, notify and journal a batch the store has just applied
+    fn committed(
+        &self,
+        updates: &[(String, Value, Option<Ttl>)],
+        revisions: &[u64],
+        writer: &SessionId,
+    ) {
+        self.stored(updates);
+
+        #[cfg(feature = "journal")]
+        for ((address, value, ttl), revision) in updates.iter().zip(revisions) {
+            if let Some(journal) = self
+                .journal_for(address)
+                .filter(|_| *ttl != Some(Ttl::Session))
+            {
+                let entry = JournalEntry::from_set(
+                    address.clone(),
+                    value.clone(),
+                    *revision,
+                    writer.clone(),
+                    clasp_core::time::now(),
+                );
+                self.spawn_journal_append(Arc::clone(journal), entry);
+            }
+        }
+        #[cfg(not(feature = "journal"))]
+        let _ = (revisions, writer);
+    }
+
+    /// Persist and notify a batch the store has just applied
+    fn stored(&self, updates: &[(String, Value, Option<Ttl>)]) {
+        if let Some(ref backend) = self.backend {
+            let (session, durable): (Vec<_>, Vec<_>) = {
+                let params = self.params.read();
+                updates
+                    .iter()
+                    .filter_map(|(address, _, _)| {
+                        params
+                            .get(address)
+                            .map(|param| (address.clone(), StoredParam::from(param)))
+                    })
+                    .partition(|(_, param)| param.ttl == Some(Ttl::Session))
+            };
+            let session: Vec<String> = session.into_iter().map(|(address, _)| address).collect();
+            self.persist_removed(&session);
+            if let Err(e) = backend.put_all(&durable) {
+                tracing::warn!(
+                    "State backend {} failed to store batch: {}",
+                    backend.name(),
+                    e
+                );
+            }
+        }
+
+        for (address, value, _) in updates {
+            if let Some(listeners) = self.listeners.get(address) {
+                for listener in listeners.iter() {
+                    listener(address, value);
+                }
+            }
+        }
+    }
+
+    /// Apply a SET to a branch instead of live state (see [`crate::branch`])
+    pub fn apply_branch_set(
+        &self,
+        branch: &str,
+        msg: &SetMessage,
+        writer: &SessionId,
+    ) -> Result<u64, UpdateError> {
+        let live = self.get_state(&msg.address);
+        self.branches.set(branch, msg, writer, live)
+    }
+
+    /// Snapshot of the params matching a pattern as seen on a branch: the
+    /// branch's params over live state
+    pub fn branch_snapshot(&self, branch: &str, pattern: &str) -> SnapshotMessage {
+        let mut params: BTreeMap<String, ParamState> =
+            self.get_matching(pattern).into_iter().collect();
+        let pattern = clasp_core::address::Pattern::compile(pattern).ok();
+        for (address, state) in self.branches.changes(branch) {
+            if pattern.as_ref().is_some_and(|p| p.matches(&address)) {
+                params.insert(address, state);
+            }
+        }
+
+        SnapshotMessage {
+            params: params
+                .into_iter()
+                .map(|(address, state)| ParamValue {
+                    address,
+                    value: state.value,
+                    revision: state.revision,
+                    writer: Some(state.writer),
+                    timestamp: Some(state.timestamp),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply every param on a branch to live state as one transaction and
+    /// empty the branch
+    ///
+    /// If any param is refused, none are applied and the branch is kept.
+    /// The merged SETs are journaled as one transaction per journal.
+    /// Returns `None` if the branch has no changes.
+    pub fn merge_branch(&self, branch: &str, writer: &SessionId) -> Option<MergeResult> {
+        self.branches.take_with(branch, |changes| {
+            let updates: Vec<(String, Value, Option<Ttl>)> = changes
+                .into_iter()
+                .map(|(address, state)| {
+                    let ttl = self.is_session_scoped(&address).then_some(Ttl::Session);
+                    (address, state.value, ttl)
+                })
+                .collect();
+            let revisions = self
+                .params
+                .write()
+                .set_all(&updates, writer, false)
+                .map_err(|(index, e)| (updates[index].0.clone(), e))?;
+            self.stored(&updates);
+
+            #[cfg(feature = "journal")]
+            self.journal_batch(&updates, &revisions, writer);
+
+            Ok(updates
+                .into_iter()
+                .zip(revisions)
+                .map(|((address, value, _), revision)| (address, value, revision))
+                .collect())
+        })
+    }
+
+    /// Drop the changes on a branch, returning their addresses (`None` if
+    /// the branch has none)
+    pub fn discard_branch(&self, branch: &str) -> Option<Vec<String>> {
+        self.branches
+            .take_with(branch, |changes| {
+                Ok::<_, ()>(changes.into_iter().map(|(address, _)| address).collect())
+            })
+            .and_then(Result::ok)
+    }
+
+    /// Whether SETs to this address are forced to session scope by config
+    pub fn is_session_scoped(&self, address: &str) -> bool {
+        self.config
+            .session_scoped
+            .iter()
+            .any(|pattern| clasp_core::address::glob_match(pattern, address))
+    }
+
+    /// Delete the session-scoped params last written by a session
+    /// Returns each removed address with the revision its deletion is broadcast at
+    pub fn remove_session_scoped(&self, writer: &SessionId) -> Vec<(String, u64)> {
+        let removed = self.params.write().remove_session_scoped(writer);
+        removed
+            .into_iter()
+            .map(|(address, param)| {
+                if let Some(listeners) = self.listeners.get(&address) {
+                    for listener in listeners.iter() {
+                        listener(&address, &Value::Null);
+                    }
+                }
+                (address, param.revision + 1)
+            })
+            .collect()
+    }
+
+    /// Hand a session's session-scoped params to another session
+    /// Returns the number of params moved
+    pub fn transfer_session_scoped(&self, from: &SessionId, to: &SessionId) -> usize {
+        self.params.write().transfer_session_scoped(from, to)
+    }
+
+    /// Apply default params from a JSON or TOML file (TOML if the extension
+    /// is `.toml`). The file maps addresses to values, or to
+    /// `{ value, ttl, lock }` entries:
+    ///
+    /// ```json
+    /// { "/scene/default": 1, "/config/rate": { "value": 30, "ttl": 0, "lock": true } }
+    /// ```
+    ///
+    /// `ttl` is in seconds (0 never expires); `lock` locks the param to
+    /// [`SEED_WRITER`]. A map value whose keys are a subset of `value`, `ttl`
+    /// and `lock` is read as an entry, so wrap such maps in `{ "value": ... }`.
+    /// Addresses that already have a value (restored from a snapshot or the
+    /// journal) are left alone. Params are applied in address order, without
+    /// journaling. Returns the number of params seeded.
+    pub fn seed_from_file(&self, path: impl AsRef<Path>) -> crate::Result<usize> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let is_toml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let entries: BTreeMap<String, SeedEntry> = if is_toml {
+            toml::from_str(&text).map_err(|e| RouterError::Config(e.to_string()))?
+        } else {
+            serde_json::from_str(&text).map_err(|e| RouterError::Config(e.to_string()))?
+        };
+
+        // Validate everything before applying anything
+        for address in entries.keys() {
+            let parsed = clasp_core::Address::parse(address)
+                .map_err(|e| RouterError::Config(format!("{}: {}", address, e)))?;
+            if parsed.is_pattern() {
+                return Err(RouterError::Config(format!(
+                    "{}: wildcards are not allowed in seed addresses",
+                    address
+                )));
+            }
+        }
+
+        let writer = SEED_WRITER.to_string();
+        let mut seeded = 0;
+        for (address, entry) in entries {
+            if self.get(&address).is_some() {
+                continue;
+            }
+            let param = match entry {
+                SeedEntry::Full(param) => param,
+                SeedEntry::Bare(value) => SeedParam {
+                    value,
+                    ttl: None,
+                    lock: false,
+                },
+            };
+            let ttl = param.ttl.map(|secs| match secs {
+                0 => Ttl::Never,
+                secs => Ttl::Absolute(secs),
+            });
+            self.set(&address, param.value, &writer, None, param.lock, false, ttl)
+                .map_err(|e| RouterError::State(format!("{}: {}", address, e)))?;
+            seeded += 1;
+        }
+        Ok(seeded)
+    }
+
     /// Record a PUBLISH event in the journal (fire-and-forget)
     #[cfg(feature = "journal")]
     pub fn journal_publish(
@@ -253,7 +1061,7 @@ impl RouterState {
         value: Option<&Value>,
         author: &str,
     ) {
-        if let Some(ref journal) = self.journal {
+        if let Some(journal) = self.journal_for(address) {
             let entry = JournalEntry::from_publish(
                 address.to_string(),
                 signal_type,
@@ -261,10 +1069,7 @@ impl RouterState {
                 author.to_string(),
                 clasp_core::time::now(),
             );
-            let journal = Arc::clone(journal);
-            tokio::spawn(async move {
-                let _ = journal.append(entry).await;
-            });
+            self.spawn_journal_append(Arc::clone(journal), entry);
         }
     }
 
@@ -292,7 +1097,10 @@ impl RouterState {
             })
             .collect();
 
-        SnapshotMessage { params }
+        SnapshotMessage {
+            params,
+            ..Default::default()
+        }
     }
 
     /// Create a full snapshot
@@ -303,14 +1111,23 @@ impl RouterState {
     /// Recover state from journal snapshot and replay entries.
     ///
     /// Loads the most recent snapshot, then replays any entries appended after
-    /// the snapshot was taken. This enables crash recovery.
+    /// the snapshot was taken. This enables crash recovery. With journal
+    /// partitions, every partition is recovered after the default journal.
     #[cfg(feature = "journal")]
     pub async fn recover_from_journal(&self) -> std::result::Result<usize, String> {
-        let journal = self
-            .journal
-            .as_ref()
-            .ok_or_else(|| "No journal configured".to_string())?;
+        if !self.has_journal() {
+            return Err("No journal configured".to_string());
+        }
 
+        let mut recovered = 0;
+        for journal in self.journals() {
+            recovered += self.recover_from(journal.as_ref()).await;
+        }
+        Ok(recovered)
+    }
+
+    #[cfg(feature = "journal")]
+    async fn recover_from(&self, journal: &dyn Journal) -> usize {
         let mut recovered = 0;
 
         // Load snapshot if available
@@ -357,33 +1174,146 @@ impl RouterState {
             );
         }
 
-        Ok(recovered)
+        recovered
     }
 
     /// Save current state as a journal snapshot.
+    ///
+    /// Each journal partition gets a snapshot of the params routed to it.
+    /// Returns the sequence number of the default journal's snapshot (or of
+    /// the last partition's when there is no default journal).
     #[cfg(feature = "journal")]
     pub async fn save_snapshot(&self) -> std::result::Result<u64, String> {
-        let journal = self
-            .journal
-            .as_ref()
-            .ok_or_else(|| "No journal configured".to_string())?;
+        if !self.has_journal() {
+            return Err("No journal configured".to_string());
+        }
 
-        let all_params = self.get_matching("**");
-        let snapshots: Vec<clasp_journal::ParamSnapshot> = all_params
-            .into_iter()
-            .map(|(address, state)| clasp_journal::ParamSnapshot {
-                address,
-                value: state.value,
-                revision: state.revision,
-                writer: state.writer,
-                timestamp: state.timestamp,
-            })
-            .collect();
+        // Bucket params by partition index; None is the default journal
+        let mut buckets: BTreeMap<Option<usize>, Vec<clasp_journal::ParamSnapshot>> =
+            BTreeMap::new();
+        for (address, state) in self.get_matching("**") {
+            let partition = self
+                .journal_partitions
+                .iter()
+                .position(|p| p.matches(&address));
+            buckets
+                .entry(partition)
+                .or_default()
+                .push(clasp_journal::ParamSnapshot {
+                    address,
+                    value: state.value,
+                    revision: state.revision,
+                    writer: state.writer,
+                    timestamp: state.timestamp,
+                });
+        }
 
-        journal
-            .snapshot(&snapshots)
-            .await
-            .map_err(|e| e.to_string())
+        let mut seq = 0;
+        for (index, partition) in self.journal_partitions.iter().enumerate() {
+            let params = buckets.remove(&Some(index)).unwrap_or_default();
+            seq = partition
+                .journal
+                .snapshot(&params)
+                .await
+                .map_err(|e| format!("{}: {}", partition.name, e))?;
+        }
+        if let Some(ref journal) = self.journal {
+            let params = buckets.remove(&None).unwrap_or_default();
+            seq = journal.snapshot(&params).await.map_err(|e| e.to_string())?;
+        }
+        Ok(seq)
+    }
+
+    /// Query every journal for REPLAY.
+    ///
+    /// Results from partitions are merged by timestamp and cut to `limit`.
+    /// Returns `None` when no journal is configured.
+    #[cfg(feature = "journal")]
+    pub async fn query_journal(
+        &self,
+        pattern: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        limit: Option<u32>,
+        types: &[SignalType],
+    ) -> Option<clasp_journal::Result<Vec<JournalEntry>>> {
+        if self.journal_partitions.is_empty() {
+            let journal = self.journal.as_ref()?;
+            return Some(journal.query(pattern, from, to, limit, types).await);
+        }
+        if !self.has_journal() {
+            return None;
+        }
+
+        let mut entries = Vec::new();
+        for journal in self.journals() {
+            match journal.query(pattern, from, to, limit, types).await {
+                Ok(found) => entries.extend(found),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        entries.sort_by_key(|e| e.timestamp);
+        if let Some(limit) = limit {
+            entries.truncate(limit as usize);
+        }
+        Some(Ok(entries))
+    }
+
+    /// Apply each journal partition's retention.
+    ///
+    /// The partition's current params are snapshotted first, so dropping
+    /// history never loses state on recovery. Returns the number of entries
+    /// removed.
+    #[cfg(feature = "journal")]
+    pub async fn enforce_journal_retention(&self) -> u64 {
+        let mut removed = 0;
+        for partition in &self.journal_partitions {
+            if partition.retention.is_unbounded() {
+                continue;
+            }
+
+            let params: Vec<clasp_journal::ParamSnapshot> = self
+                .get_matching(&partition.pattern)
+                .into_iter()
+                .filter(|(address, _)| {
+                    self.journal_for(address)
+                        .is_some_and(|j| Arc::ptr_eq(j, &partition.journal))
+                })
+                .map(|(address, state)| clasp_journal::ParamSnapshot {
+                    address,
+                    value: state.value,
+                    revision: state.revision,
+                    writer: state.writer,
+                    timestamp: state.timestamp,
+                })
+                .collect();
+            if let Err(e) = partition.journal.snapshot(&params).await {
+                tracing::warn!(
+                    "Journal partition {} snapshot failed, skipping retention: {}",
+                    partition.name,
+                    e
+                );
+                continue;
+            }
+
+            match partition.retention.apply(partition.journal.as_ref()).await {
+                Ok(0) => {}
+                Ok(n) => {
+                    tracing::debug!(
+                        "Journal partition {}: dropped {} entries past retention",
+                        partition.name,
+                        n
+                    );
+                    removed += n;
+                }
+                Err(e) => tracing::warn!(
+                    "Journal partition {} retention failed: {}",
+                    partition.name,
+                    e
+                ),
+            }
+        }
+        removed
     }
 
     /// Number of parameters
@@ -396,9 +1326,79 @@ impl RouterState {
         self.params.read().is_empty()
     }
 
+    /// Move params to their canonical address under `policy`, keeping the
+    /// latest write where several collapse onto one address, and drop
+    /// params whose address `policy` refuses (see [`crate::canonical`]).
+    ///
+    /// Meant for migrating state at startup: subscribers aren't notified
+    /// and nothing is journaled, but the backend (if any) is updated.
+    pub fn canonicalize(&self, policy: &AddressPolicy) -> CanonicalizeReport {
+        let mut report = CanonicalizeReport::default();
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut params = self.params.write();
+        for (address, _) in params.snapshot() {
+            match policy.canonicalize(address) {
+                Ok(Cow::Borrowed(_)) => {}
+                Ok(Cow::Owned(canonical)) => groups
+                    .entry(canonical)
+                    .or_default()
+                    .push(address.to_string()),
+                Err(_) => report.invalid.push(address.to_string()),
+            }
+        }
+        report.invalid.sort();
+        for address in &report.invalid {
+            params.remove(address);
+        }
+
+        let mut written = Vec::new();
+        for (canonical, addresses) in groups {
+            let mut candidates: Vec<(String, ParamState)> = addresses
+                .into_iter()
+                .filter_map(|address| params.remove(&address).map(|param| (address, param)))
+                .collect();
+            if let Some(existing) = params.remove(&canonical) {
+                candidates.push((canonical.clone(), existing));
+            }
+            candidates.sort_by(|a, b| (a.1.timestamp, &a.0).cmp(&(b.1.timestamp, &b.0)));
+            let Some((winner, param)) = candidates.pop() else {
+                continue;
+            };
+            report
+                .merged
+                .extend(candidates.into_iter().map(|(address, _)| address));
+            if winner != canonical {
+                report.renamed.push((winner, canonical.clone()));
+            }
+            params.insert(canonical.clone(), param);
+            written.push(canonical);
+        }
+        drop(params);
+
+        let mut removed: Vec<String> = report.invalid.clone();
+        removed.extend(report.renamed.iter().map(|(old, _)| old.clone()));
+        removed.extend(
+            report
+                .merged
+                .iter()
+                .filter(|a| !written.contains(a))
+                .cloned(),
+        );
+        self.persist_removed(&removed);
+        for address in &written {
+            self.persist(address);
+        }
+        report
+    }
+
     /// Clear all state
     pub fn clear(&self) {
         self.params.write().clear();
+        if let Some(ref backend) = self.backend {
+            if let Err(e) = backend.clear() {
+                tracing::warn!("State backend {} failed to clear: {}", backend.name(), e);
+            }
+        }
     }
 }
 
@@ -504,6 +1504,38 @@ mod tests {
         assert_eq!(queried.len(), 2);
     }
 
+    #[test]
+    fn test_query_signals_page() {
+        use clasp_core::SignalType;
+
+        let state = RouterState::new();
+        state.register_signals(
+            (0..25)
+                .map(|i| SignalDefinition {
+                    address: format!("/page/{:02}", i),
+                    signal_type: SignalType::Param,
+                    datatype: None,
+                    access: None,
+                    meta: None,
+                })
+                .collect(),
+        );
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = state.query_signals_page("/page/**", cursor.as_deref(), 10);
+            assert_eq!(page.total, 25);
+            seen.extend(page.signals.iter().map(|s| s.address.clone()));
+            if !page.more {
+                break;
+            }
+            cursor = page.signals.last().map(|s| s.address.clone());
+        }
+        let expected: Vec<String> = (0..25).map(|i| format!("/page/{:02}", i)).collect();
+        assert_eq!(seen, expected);
+    }
+
     #[test]
     fn test_cleanup_stale_signals() {
         use clasp_core::SignalType;
@@ -512,6 +1544,7 @@ mod tests {
             param_config: StateStoreConfig::unlimited(),
             signal_ttl: Some(Duration::from_millis(10)),
             max_signals: None,
+            session_scoped: Vec::new(),
         };
         let state = RouterState::with_config(config);
 
@@ -545,6 +1578,7 @@ mod tests {
             param_config: StateStoreConfig::with_limits(1000, 1), // 1 second TTL
             signal_ttl: Some(Duration::from_millis(10)),
             max_signals: None,
+            session_scoped: Vec::new(),
         };
         let state = RouterState::with_config(config);
 
@@ -583,4 +1617,300 @@ mod tests {
         assert_eq!(state.signal_count(), 0);
         assert_eq!(state.len(), 1);
     }
+
+    fn write_seed(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("clasp-seed-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_seed_from_json() {
+        let path = write_seed(
+            "seed.json",
+            r#"{
+                "/scene/default": 1,
+                "/config/rate": { "value": 30, "ttl": 0, "lock": true },
+                "/config/labels": { "value": { "value": "a", "lock": "b" } }
+            }"#,
+        );
+        let state = RouterState::new();
+        state
+            .set(
+                "/scene/default",
+                Value::Int(7),
+                &"restore".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(state.seed_from_file(&path).unwrap(), 2);
+        // Existing values are kept
+        assert_eq!(state.get("/scene/default"), Some(Value::Int(7)));
+        let rate = state.get_state("/config/rate").unwrap();
+        assert_eq!(rate.value, Value::Int(30));
+        assert_eq!(rate.lock_holder.as_deref(), Some(SEED_WRITER));
+        assert!(matches!(
+            state.get("/config/labels"),
+            Some(Value::Map(ref m)) if m.len() == 2
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_seed_from_toml() {
+        let path = write_seed(
+            "seed.toml",
+            r#"
+            "/scene/name" = "house"
+
+            ["/scene/level"]
+            value = 0.5
+            ttl = 60
+            "#,
+        );
+        let state = RouterState::new();
+        assert_eq!(state.seed_from_file(&path).unwrap(), 2);
+        assert_eq!(
+            state.get("/scene/name"),
+            Some(Value::String("house".into()))
+        );
+        assert_eq!(state.get("/scene/level"), Some(Value::Float(0.5)));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_seed_rejects_patterns() {
+        let path = write_seed("bad.json", r#"{ "/ok": 1, "/scene/*": 2 }"#);
+        let state = RouterState::new();
+        assert!(state.seed_from_file(&path).is_err());
+        assert!(state.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_replicated_keeps_revisions() {
+        let state = RouterState::new();
+        assert!(state.apply_replicated("/a", Value::Int(1), "primary", 7, 100));
+        let param = state.get_state("/a").unwrap();
+        assert_eq!(param.revision, 7);
+        assert_eq!(param.writer, "primary");
+        assert_eq!(param.timestamp, 100);
+
+        // Replays and stale revisions are ignored
+        assert!(!state.apply_replicated("/a", Value::Int(0), "primary", 7, 100));
+        assert!(!state.apply_replicated("/a", Value::Int(0), "primary", 3, 50));
+        assert_eq!(state.get("/a"), Some(Value::Int(1)));
+
+        assert!(state.apply_replicated("/a", Value::Int(2), "other", 9, 200));
+        assert_eq!(state.get_state("/a").unwrap().revision, 9);
+
+        // A concurrent write at the same revision wins only with a greater writer
+        assert!(!state.apply_replicated("/a", Value::Int(0), "alpha", 9, 200));
+        assert!(state.apply_replicated("/a", Value::Int(2), "zulu", 9, 200));
+        assert_eq!(state.get_state("/a").unwrap().writer, "zulu");
+
+        // Local writes continue from the replicated revision
+        let revision = state
+            .set(
+                "/a",
+                Value::Int(3),
+                &"s1".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+        assert_eq!(revision, 10);
+    }
+
+    #[test]
+    fn test_backend_write_through_and_restore() {
+        let backend = Arc::new(crate::MemoryStateBackend::new());
+        let mut state = RouterState::new();
+        state.set_backend(backend.clone());
+
+        let writer = "s1".to_string();
+        state
+            .set("/a", Value::Int(1), &writer, None, false, false, None)
+            .unwrap();
+        state
+            .set("/a", Value::Int(2), &writer, None, true, false, None)
+            .unwrap();
+        state
+            .set(
+                "/tmp",
+                Value::Int(3),
+                &writer,
+                None,
+                false,
+                false,
+                Some(Ttl::Session),
+            )
+            .unwrap();
+        state
+            .set_all(&[("/b".to_string(), Value::Bool(true))], &writer, false)
+            .unwrap();
+
+        assert_eq!(backend.len(), 2);
+        assert!(backend.get("/tmp").is_none());
+        let stored = backend.get("/a").unwrap();
+        assert_eq!(stored.value, Value::Int(2));
+        assert_eq!(stored.revision, 2);
+        assert_eq!(stored.lock_holder.as_deref(), Some("s1"));
+
+        // A fresh state restores revisions and locks
+        let mut restored = RouterState::new();
+        restored.set_backend(backend.clone());
+        assert_eq!(restored.restore_from_backend().unwrap(), 2);
+        let param = restored.get_state("/a").unwrap();
+        assert_eq!(param.value, Value::Int(2));
+        assert_eq!(param.revision, 2);
+        assert_eq!(param.lock_holder.as_deref(), Some("s1"));
+        assert_eq!(restored.get("/b"), Some(Value::Bool(true)));
+        assert!(restored
+            .set(
+                "/a",
+                Value::Int(9),
+                &"s2".to_string(),
+                None,
+                false,
+                false,
+                None
+            )
+            .is_err());
+
+        restored.clear();
+        assert!(backend.is_empty());
+    }
+
+    #[test]
+    fn test_backend_drops_stale_params() {
+        let backend = Arc::new(crate::MemoryStateBackend::new());
+        let mut state = RouterState::new();
+        state.set_backend(backend.clone());
+
+        state
+            .set(
+                "/old",
+                Value::Int(1),
+                &"s1".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(state.cleanup_stale_params(Duration::from_millis(10)), 1);
+        assert!(backend.is_empty());
+        assert!(RouterState::new().restore_from_backend().is_err());
+    }
+
+    #[test]
+    fn test_backend_stores_signal_registry() {
+        use clasp_core::{SignalMeta, SignalType};
+
+        let backend = Arc::new(crate::MemoryStateBackend::new());
+        let mut state = RouterState::new();
+        state.set_backend(backend.clone());
+
+        let signal = |address: &str| SignalDefinition {
+            address: address.to_string(),
+            signal_type: SignalType::Param,
+            datatype: Some("f".to_string()),
+            access: Some("rw".to_string()),
+            meta: Some(SignalMeta {
+                unit: Some("dB".to_string()),
+                range: Some((-60.0, 12.0)),
+                default: None,
+                description: None,
+                enum_values: None,
+            }),
+        };
+        state.register_signals(vec![signal("/mixer/gain"), signal("/mixer/pan")]);
+        assert_eq!(backend.signal_count(), 2);
+
+        // A fresh state gets the registry back, units and ranges included
+        let mut restored = RouterState::new();
+        restored.set_backend(backend.clone());
+        restored.restore_from_backend().unwrap();
+        assert_eq!(restored.signal_count(), 2);
+        assert_eq!(restored.signal_unit("/mixer/gain").as_deref(), Some("dB"));
+        let gain = restored.query_signals("/mixer/gain").remove(0);
+        assert_eq!(gain.meta.and_then(|m| m.range), Some((-60.0, 12.0)));
+
+        // Expired signals are deleted from the backend
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(restored.cleanup_stale_signals(Duration::from_millis(10)), 2);
+        assert_eq!(backend.signal_count(), 0);
+    }
+
+    #[cfg(feature = "journal")]
+    #[tokio::test]
+    async fn test_journal_partitions_route_query_and_recover() {
+        use crate::journal_partition::{JournalPartition, Retention};
+        use clasp_journal::MemoryJournal;
+
+        let default: Arc<dyn Journal> = Arc::new(MemoryJournal::new(100));
+        let chat: Arc<dyn Journal> = Arc::new(MemoryJournal::new(100));
+        let mut state = RouterState::new();
+        state.set_journal(Arc::clone(&default));
+        state.add_journal_partition(
+            JournalPartition::new("chat", "/chat/**", Arc::clone(&chat)).with_retention(
+                Retention {
+                    max_entries: Some(1),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        let writer = "s1".to_string();
+        for (address, value) in [("/chat/room", 1), ("/scene/a", 2), ("/chat/room", 3)] {
+            let msg = SetMessage {
+                address: address.to_string(),
+                value: Value::Int(value),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            };
+            state.apply_set(&msg, &writer).unwrap();
+            // Appends are fire-and-forget
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(chat.len().await.unwrap(), 2);
+        assert_eq!(default.len().await.unwrap(), 1);
+        let replayed = state
+            .query_journal("/**", None, None, None, &[])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replayed.len(), 3);
+        let limited = state
+            .query_journal("/**", None, None, Some(2), &[])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(limited.len(), 2);
+
+        // Retention trims chat history but its state survives in the snapshot
+        assert_eq!(state.enforce_journal_retention().await, 1);
+        assert_eq!(chat.len().await.unwrap(), 1);
+        assert_eq!(default.len().await.unwrap(), 1);
+
+        let mut restored = RouterState::new();
+        restored.set_journal(default);
+        restored.add_journal_partition(JournalPartition::new("chat", "/chat/**", chat));
+        restored.recover_from_journal().await.unwrap();
+        assert_eq!(restored.get("/chat/room"), Some(Value::Int(3)));
+        assert_eq!(restored.get("/scene/a"), Some(Value::Int(2)));
+    }
 }
@@ -0,0 +1,354 @@
+//! Backpressure policies for stream subscriptions
+//!
+//! Stream signals (sensor data, motion capture, audio levels) can arrive
+//! faster than a client reads them. Without a policy, stream frames take the
+//! same `try_send` path as every other broadcast and are dropped, with drop
+//! tracking, once the session's send buffer is full. A subscription with a
+//! `stream` option gets its own queue instead, drained by a task that waits
+//! for the transport, and the policy decides what stays queued while the
+//! client is behind:
+//!
+//! - `latest_only` keeps the newest pending frame per address, so a slow
+//!   client skips straight to the current value
+//! - `ring(N)` keeps the newest N pending frames, dropping the oldest
+//! - `reliable` queues every frame and delivers them in order. Past
+//!   [`RELIABLE_LIMIT`] pending frames, new frames fall back to the normal
+//!   path and its drop tracking.
+//!
+//! Like tick aggregation, queuing happens in
+//! [`Session::try_send`](crate::Session::try_send) and only applies to
+//! PUBLISH frames with the Stream signal type. A frame matching a tick
+//! subscription is bundled rather than queued.
+
+use bytes::Bytes;
+use clasp_core::{codec, Message, SignalType, StreamPolicy};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::debug;
+
+use crate::session::Session;
+use crate::subscription::Subscription;
+
+/// Largest accepted ring size; larger values are clamped
+pub const MAX_RING: u32 = 4096;
+
+/// Frames a `reliable` subscription may have pending before new frames
+/// are dropped like any other broadcast
+pub const RELIABLE_LIMIT: usize = 65_536;
+
+/// How often an idle drain task checks that its session still exists
+const IDLE_CHECK: Duration = Duration::from_secs(1);
+
+/// Clamp a requested policy to what the router accepts
+pub fn applied(policy: StreamPolicy) -> StreamPolicy {
+    match policy {
+        StreamPolicy::Ring(capacity) => StreamPolicy::Ring(capacity.clamp(1, MAX_RING)),
+        other => other,
+    }
+}
+
+struct StreamQueue {
+    subscription: Subscription,
+    policy: StreamPolicy,
+    generation: u64,
+    /// Encoded frames waiting to be sent, with their address
+    pending: VecDeque<(String, Bytes)>,
+    notify: Arc<Notify>,
+}
+
+/// Per-session set of stream subscriptions with a backpressure policy
+#[derive(Default)]
+pub struct StreamQueues {
+    /// Fast-path check so sessions without stream policies skip decoding
+    active: AtomicBool,
+    next_generation: AtomicU64,
+    /// Frames discarded by `latest_only` and `ring` policies
+    discarded: AtomicU64,
+    queues: Mutex<Vec<StreamQueue>>,
+}
+
+impl StreamQueues {
+    /// Register (or replace) a stream subscription, returning its
+    /// generation and the handle that wakes its drain task. A drain task
+    /// only serves the generation it was started for.
+    pub fn add(&self, subscription: Subscription, policy: StreamPolicy) -> (u64, Arc<Notify>) {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let notify = Arc::new(Notify::new());
+        let mut queues = self.queues.lock();
+        for queue in queues
+            .iter()
+            .filter(|q| q.subscription.id == subscription.id)
+        {
+            queue.notify.notify_one();
+        }
+        queues.retain(|q| q.subscription.id != subscription.id);
+        queues.push(StreamQueue {
+            subscription,
+            policy: applied(policy),
+            generation,
+            pending: VecDeque::new(),
+            notify: Arc::clone(&notify),
+        });
+        self.active.store(true, Ordering::Release);
+        (generation, notify)
+    }
+
+    /// Remove a stream subscription and its pending frames. Returns `true`
+    /// if it existed.
+    pub fn remove(&self, id: u32) -> bool {
+        let mut queues = self.queues.lock();
+        let before = queues.len();
+        queues.retain(|q| {
+            if q.subscription.id == id {
+                q.notify.notify_one();
+                false
+            } else {
+                true
+            }
+        });
+        self.active.store(!queues.is_empty(), Ordering::Release);
+        queues.len() != before
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Frames waiting to be sent across all stream subscriptions
+    pub fn pending(&self) -> usize {
+        self.queues.lock().iter().map(|q| q.pending.len()).sum()
+    }
+
+    /// Frames discarded by `latest_only` and `ring` policies so far
+    pub fn discarded(&self) -> u64 {
+        self.discarded.load(Ordering::Relaxed)
+    }
+
+    /// Queue a broadcast if it is a stream frame matching a subscription
+    /// with a policy.
+    ///
+    /// Returns `true` when the data was queued and must not be sent now.
+    pub fn intercept(&self, data: &Bytes) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let Ok((Message::Publish(publish), _)) = codec::decode(data) else {
+            return false;
+        };
+        if publish.signal != Some(SignalType::Stream) {
+            return false;
+        }
+
+        let mut queues = self.queues.lock();
+        let Some(queue) = queues.iter_mut().find(|q| {
+            q.subscription
+                .matches(&publish.address, Some(SignalType::Stream))
+        }) else {
+            return false;
+        };
+        match queue.policy {
+            StreamPolicy::LatestOnly => {
+                match queue
+                    .pending
+                    .iter_mut()
+                    .find(|(address, _)| *address == publish.address)
+                {
+                    Some(slot) => {
+                        slot.1 = data.clone();
+                        self.discarded.fetch_add(1, Ordering::Relaxed);
+                    }
+                    None => queue.pending.push_back((publish.address, data.clone())),
+                }
+            }
+            StreamPolicy::Ring(capacity) => {
+                if queue.pending.len() >= capacity as usize {
+                    queue.pending.pop_front();
+                    self.discarded.fetch_add(1, Ordering::Relaxed);
+                }
+                queue.pending.push_back((publish.address, data.clone()));
+            }
+            StreamPolicy::Reliable => {
+                if queue.pending.len() >= RELIABLE_LIMIT {
+                    return false;
+                }
+                queue.pending.push_back((publish.address, data.clone()));
+            }
+        }
+        queue.notify.notify_one();
+        true
+    }
+
+    /// Take the next pending frame of one subscription.
+    ///
+    /// Returns `Err(())` once the subscription is gone or replaced, which
+    /// tells the drain task to stop.
+    fn next(&self, id: u32, generation: u64) -> Result<Option<Bytes>, ()> {
+        let mut queues = self.queues.lock();
+        let queue = queues
+            .iter_mut()
+            .find(|q| q.subscription.id == id && q.generation == generation)
+            .ok_or(())?;
+        Ok(queue.pending.pop_front().map(|(_, data)| data))
+    }
+}
+
+/// Spawn the task that delivers a stream subscription's queued frames.
+///
+/// Each send waits for the transport, so frames pile up in the queue (where
+/// the policy applies) rather than in the send buffer. The task holds only
+/// a weak reference and ends when the session is dropped or the
+/// subscription is removed or replaced.
+pub(crate) fn spawn_drainer(session: Weak<Session>, id: u32, generation: u64, notify: Arc<Notify>) {
+    tokio::spawn(async move {
+        loop {
+            let Some(session) = session.upgrade() else {
+                break;
+            };
+            match session.stream_queues().next(id, generation) {
+                Ok(Some(data)) => {
+                    if session.send(data.clone()).await.is_err() {
+                        break;
+                    }
+                    session.delivery_stats().record(&data, true);
+                    continue;
+                }
+                Ok(None) => {}
+                Err(()) => break,
+            }
+            drop(session);
+            let _ = tokio::time::timeout(IDLE_CHECK, notify.notified()).await;
+        }
+        debug!("Stream drainer for subscription {} stopped", id);
+    });
+}
+
+/// Convenience wrapper for the subscribe handler
+pub(crate) fn start(session: &Arc<Session>, subscription: Subscription, policy: StreamPolicy) {
+    let id = subscription.id;
+    let (generation, notify) = session.stream_queues().add(subscription, policy);
+    spawn_drainer(Arc::downgrade(session), id, generation, notify);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{PublishMessage, SubscribeOptions, Value};
+
+    fn frame(address: &str, value: i64) -> Bytes {
+        codec::encode(&Message::Publish(PublishMessage {
+            address: address.to_string(),
+            signal: Some(SignalType::Stream),
+            value: Some(Value::Int(value)),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        }))
+        .unwrap()
+    }
+
+    fn queues(policy: StreamPolicy) -> (StreamQueues, u64) {
+        let queues = StreamQueues::default();
+        let sub = Subscription::new(
+            1,
+            "s".to_string(),
+            "/mocap/**",
+            vec![],
+            SubscribeOptions::default(),
+        )
+        .unwrap();
+        let (generation, _) = queues.add(sub, policy);
+        (queues, generation)
+    }
+
+    fn drain(queues: &StreamQueues, generation: u64) -> Vec<(String, i64)> {
+        let mut frames = Vec::new();
+        while let Some(data) = queues.next(1, generation).unwrap() {
+            match codec::decode(&data).unwrap().0 {
+                Message::Publish(p) => frames.push((p.address, p.value.unwrap().as_i64().unwrap())),
+                other => panic!("expected publish, got {:?}", other),
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn test_latest_only_keeps_newest_frame_per_address() {
+        let (queues, generation) = queues(StreamPolicy::LatestOnly);
+        for (address, value) in [("/mocap/a", 1), ("/mocap/b", 2), ("/mocap/a", 3)] {
+            assert!(queues.intercept(&frame(address, value)));
+        }
+        assert_eq!(queues.discarded(), 1);
+        assert_eq!(
+            drain(&queues, generation),
+            vec![("/mocap/a".to_string(), 3), ("/mocap/b".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_ring_drops_oldest() {
+        let (queues, generation) = queues(StreamPolicy::Ring(2));
+        for value in 1..=4 {
+            assert!(queues.intercept(&frame("/mocap/a", value)));
+        }
+        assert_eq!(queues.discarded(), 2);
+        assert_eq!(
+            drain(&queues, generation),
+            vec![("/mocap/a".to_string(), 3), ("/mocap/a".to_string(), 4)]
+        );
+    }
+
+    #[test]
+    fn test_reliable_keeps_every_frame_in_order() {
+        let (queues, generation) = queues(StreamPolicy::Reliable);
+        for value in 1..=3 {
+            assert!(queues.intercept(&frame("/mocap/a", value)));
+        }
+        assert_eq!(queues.pending(), 3);
+        assert_eq!(queues.discarded(), 0);
+        let values: Vec<i64> = drain(&queues, generation)
+            .into_iter()
+            .map(|f| f.1)
+            .collect();
+        assert_eq!(values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_intercept_only_matching_stream_frames() {
+        let (queues, generation) = queues(StreamPolicy::Reliable);
+        assert!(!queues.intercept(&frame("/ui/x", 1)));
+        let set = codec::encode(&Message::Set(clasp_core::SetMessage {
+            address: "/mocap/a".to_string(),
+            value: Value::Int(1),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap();
+        assert!(!queues.intercept(&set));
+
+        assert!(queues.remove(1));
+        assert!(!queues.is_active());
+        assert!(!queues.intercept(&frame("/mocap/a", 1)));
+        assert!(queues.next(1, generation).is_err());
+    }
+
+    #[test]
+    fn test_ring_size_is_clamped() {
+        assert_eq!(applied(StreamPolicy::Ring(0)), StreamPolicy::Ring(1));
+        assert_eq!(
+            applied(StreamPolicy::Ring(u32::MAX)),
+            StreamPolicy::Ring(MAX_RING)
+        );
+    }
+}
@@ -0,0 +1,624 @@
+//! Wire tap: sampled capture of protocol frames for debugging
+//!
+//! A tap selects frames by session, address pattern and direction, keeps a
+//! fraction of them (`sample_rate`), and delivers each captured frame
+//! decoded to JSON:
+//!
+//! - as an EVENT on `/clasp/tap/{id}` for any subscriber (e.g. an admin UI)
+//! - optionally appended to a file: `.pcap` files get raw frames, anything
+//!   else gets one JSON record per line
+//!
+//! Taps are toggled at runtime by SETting `/clasp/admin/tap/{id}` (admin
+//! scope required in authenticated mode) to a rule map, or to `null` to
+//! remove it:
+//!
+//! ```json
+//! { "pattern": "/lights/**", "session": "<id>", "sample_rate": 0.1,
+//!   "direction": "in", "file": "/tmp/lights.pcap" }
+//! ```
+//!
+//! Over the admin namespace `file` must be a bare file name and is only
+//! accepted once a capture directory has been set with
+//! [`WireTap::set_capture_dir`], so clients cannot write arbitrary paths.
+//!
+//! pcap files use link type `USER0` (147). Each packet is a one byte
+//! direction (0 = in, 1 = out), a one byte session id length, the session
+//! id, then the CLASP frame exactly as it crossed the wire.
+
+use bytes::Bytes;
+use clasp_core::{codec, Message, PublishMessage, SignalType, Value};
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use tracing::{info, warn};
+
+use crate::session::{Session, SessionId};
+use crate::subscription::SubscriptionManager;
+
+/// SET `/clasp/admin/tap/{id}` to add, replace or remove a tap
+pub const TAP_ADMIN_PREFIX: &str = "/clasp/admin/tap/";
+
+/// Captured frames are published on `/clasp/tap/{id}`
+pub const TAP_OUTPUT_PREFIX: &str = "/clasp/tap/";
+
+/// Upper bound on concurrently active taps
+pub const MAX_TAPS: usize = 32;
+
+/// pcap link type for private use, carrying tap records
+pub const PCAP_LINKTYPE_USER0: u32 = 147;
+
+/// Which frames a tap sees
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TapDirection {
+    /// Frames received from clients
+    In,
+    /// Frames sent to clients
+    Out,
+    #[default]
+    Both,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+/// Selection and output settings for one tap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TapRule {
+    /// Only frames of this session id
+    #[serde(default)]
+    pub session: Option<String>,
+    /// Only frames touching an address matching this pattern
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Fraction of matching frames to capture (0.0 - 1.0)
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    #[serde(default)]
+    pub direction: TapDirection,
+    /// Append captured frames to this file
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+impl Default for TapRule {
+    fn default() -> Self {
+        Self {
+            session: None,
+            pattern: None,
+            sample_rate: 1.0,
+            direction: TapDirection::Both,
+            file: None,
+        }
+    }
+}
+
+/// Counters for an active tap
+#[derive(Debug, Clone, Serialize)]
+pub struct TapStats {
+    pub id: String,
+    pub rule: TapRule,
+    /// Frames that matched the rule
+    pub matched: u64,
+    /// Frames kept after sampling
+    pub captured: u64,
+}
+
+enum TapFile {
+    Pcap(BufWriter<File>),
+    JsonLines(BufWriter<File>),
+}
+
+impl TapFile {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let is_pcap = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pcap"));
+        let existing = path.metadata().map(|m| m.len() > 0).unwrap_or(false);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        if !is_pcap {
+            return Ok(TapFile::JsonLines(writer));
+        }
+        if !existing {
+            writer.write_all(&pcap_header())?;
+            writer.flush()?;
+        }
+        Ok(TapFile::Pcap(writer))
+    }
+
+    fn write(&mut self, record: &TapRecord<'_>) -> std::io::Result<()> {
+        match self {
+            TapFile::Pcap(w) => w.write_all(&pcap_packet(record)),
+            TapFile::JsonLines(w) => {
+                serde_json::to_writer(&mut *w, &record.to_json())?;
+                w.write_all(b"\n")
+            }
+        }?;
+        match self {
+            TapFile::Pcap(w) | TapFile::JsonLines(w) => w.flush(),
+        }
+    }
+}
+
+/// pcap global header (microsecond timestamps, little endian)
+pub fn pcap_header() -> [u8; 24] {
+    let mut header = [0u8; 24];
+    header[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    header[4..6].copy_from_slice(&2u16.to_le_bytes());
+    header[6..8].copy_from_slice(&4u16.to_le_bytes());
+    header[16..20].copy_from_slice(&65_535u32.to_le_bytes());
+    header[20..24].copy_from_slice(&PCAP_LINKTYPE_USER0.to_le_bytes());
+    header
+}
+
+fn pcap_packet(record: &TapRecord<'_>) -> Vec<u8> {
+    let session = &record.session.as_bytes()[..record.session.len().min(255)];
+    let len = 2 + session.len() + record.frame.len();
+    let mut packet = Vec::with_capacity(16 + len);
+    packet.extend_from_slice(&((record.timestamp / 1_000_000) as u32).to_le_bytes());
+    packet.extend_from_slice(&((record.timestamp % 1_000_000) as u32).to_le_bytes());
+    packet.extend_from_slice(&(len as u32).to_le_bytes());
+    packet.extend_from_slice(&(len as u32).to_le_bytes());
+    packet.push(match record.direction {
+        TapDirection::Out => 1,
+        _ => 0,
+    });
+    packet.push(session.len() as u8);
+    packet.extend_from_slice(session);
+    packet.extend_from_slice(record.frame);
+    packet
+}
+
+/// One captured frame
+struct TapRecord<'a> {
+    timestamp: u64,
+    session: &'a str,
+    direction: TapDirection,
+    frame: &'a [u8],
+    message: &'a Message,
+}
+
+impl TapRecord<'_> {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "timestamp": self.timestamp,
+            "session": self.session,
+            "direction": self.direction,
+            "bytes": self.frame.len(),
+            "message": self.message,
+        })
+    }
+}
+
+struct ActiveTap {
+    id: String,
+    rule: TapRule,
+    matched: AtomicU64,
+    captured: AtomicU64,
+    file: Option<Mutex<TapFile>>,
+}
+
+impl ActiveTap {
+    fn selects(&self, direction: TapDirection, session: &str, addresses: &[&str]) -> bool {
+        if self.rule.direction != TapDirection::Both && self.rule.direction != direction {
+            return false;
+        }
+        if let Some(ref wanted) = self.rule.session {
+            if wanted != session {
+                return false;
+            }
+        }
+        if let Some(ref pattern) = self.rule.pattern {
+            return addresses
+                .iter()
+                .any(|a| clasp_core::address::glob_match(pattern, a));
+        }
+        true
+    }
+
+    /// Deterministic sampling: keep frame n when floor(n * rate) advances
+    fn sample(&self) -> bool {
+        let n = self.matched.fetch_add(1, Ordering::Relaxed) + 1;
+        let rate = self.rule.sample_rate.clamp(0.0, 1.0);
+        (n as f64 * rate).floor() > ((n - 1) as f64 * rate).floor()
+    }
+}
+
+/// Runtime-controlled frame capture shared by all connections
+pub struct WireTap {
+    /// Fast-path check so frames are only decoded while a tap exists
+    active: AtomicBool,
+    taps: RwLock<HashMap<String, Arc<ActiveTap>>>,
+    /// Directory for files requested over the admin namespace
+    capture_dir: RwLock<Option<PathBuf>>,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+}
+
+impl WireTap {
+    pub fn new(
+        sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+        subscriptions: Arc<SubscriptionManager>,
+    ) -> Self {
+        Self {
+            active: AtomicBool::new(false),
+            taps: RwLock::new(HashMap::new()),
+            capture_dir: RwLock::new(None),
+            sessions,
+            subscriptions,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Allow admin-namespace taps to write files inside `dir`
+    pub fn set_capture_dir(&self, dir: Option<PathBuf>) {
+        *self.capture_dir.write() = dir;
+    }
+
+    /// Add or replace a tap
+    pub fn enable(&self, id: &str, rule: TapRule) -> Result<(), String> {
+        if id.is_empty() || id.contains('/') {
+            return Err("tap id must be a single address segment".into());
+        }
+        if !(0.0..=1.0).contains(&rule.sample_rate) {
+            return Err("sample_rate must be between 0 and 1".into());
+        }
+        if let Some(ref pattern) = rule.pattern {
+            clasp_core::address::Pattern::compile(pattern)
+                .map_err(|e| format!("invalid pattern: {}", e))?;
+        }
+        let file = match rule.file {
+            Some(ref path) => Some(Mutex::new(
+                TapFile::open(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+            )),
+            None => None,
+        };
+
+        let mut taps = self.taps.write();
+        if !taps.contains_key(id) && taps.len() >= MAX_TAPS {
+            return Err(format!("at most {} taps may be active", MAX_TAPS));
+        }
+        info!("Wire tap {} enabled: {:?}", id, rule);
+        taps.insert(
+            id.to_string(),
+            Arc::new(ActiveTap {
+                id: id.to_string(),
+                rule,
+                matched: AtomicU64::new(0),
+                captured: AtomicU64::new(0),
+                file,
+            }),
+        );
+        self.active.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Remove a tap. Returns `true` if it existed.
+    pub fn disable(&self, id: &str) -> bool {
+        let mut taps = self.taps.write();
+        let removed = taps.remove(id).is_some();
+        self.active.store(!taps.is_empty(), Ordering::Release);
+        if removed {
+            info!("Wire tap {} disabled", id);
+        }
+        removed
+    }
+
+    /// Active taps and their counters
+    pub fn stats(&self) -> Vec<TapStats> {
+        let mut stats: Vec<TapStats> = self
+            .taps
+            .read()
+            .values()
+            .map(|tap| TapStats {
+                id: tap.id.clone(),
+                rule: tap.rule.clone(),
+                matched: tap.matched.load(Ordering::Relaxed),
+                captured: tap.captured.load(Ordering::Relaxed),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.id.cmp(&b.id));
+        stats
+    }
+
+    /// Offer a frame to the active taps
+    pub fn record(&self, direction: TapDirection, session: &str, frame: &[u8]) {
+        if !self.is_active() {
+            return;
+        }
+        // Clone the tap list so delivery never runs under the lock: publishing
+        // a capture sends a frame, which is itself offered to the taps.
+        let taps: Vec<Arc<ActiveTap>> = self.taps.read().values().cloned().collect();
+        let Ok((message, _)) = codec::decode(frame) else {
+            return;
+        };
+        let mut addresses = Vec::new();
+        collect_addresses(&message, &mut addresses);
+        // Never capture the tap's own output
+        if addresses.iter().any(|a| a.starts_with(TAP_OUTPUT_PREFIX)) {
+            return;
+        }
+
+        let mut json: Option<serde_json::Value> = None;
+        for tap in taps {
+            if !tap.selects(direction, session, &addresses) || !tap.sample() {
+                continue;
+            }
+            tap.captured.fetch_add(1, Ordering::Relaxed);
+            let record = TapRecord {
+                timestamp: clasp_core::time::now(),
+                session,
+                direction,
+                frame,
+                message: &message,
+            };
+            if let Some(ref file) = tap.file {
+                if let Err(e) = file.lock().write(&record) {
+                    warn!("Wire tap {} write failed: {}", tap.id, e);
+                }
+            }
+            let json = json.get_or_insert_with(|| record.to_json());
+            self.publish(&tap.id, json);
+        }
+    }
+
+    fn publish(&self, id: &str, json: &serde_json::Value) {
+        let address = format!("{}{}", TAP_OUTPUT_PREFIX, id);
+        let subscribers = self
+            .subscriptions
+            .find_subscribers(&address, Some(SignalType::Event));
+        if subscribers.is_empty() {
+            return;
+        }
+        let value: Value = serde_json::from_value(json.clone()).unwrap_or(Value::Null);
+        let msg = Message::Publish(PublishMessage {
+            address,
+            signal: Some(SignalType::Event),
+            value: Some(value),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: Some(clasp_core::time::now()),
+            timeline: None,
+        });
+        if let Ok(bytes) = codec::encode(&msg) {
+            for session_id in subscribers {
+                if let Some(session) = self.sessions.get(&session_id) {
+                    let _ = session.try_send(bytes.clone());
+                }
+            }
+        }
+    }
+
+    /// Apply a SET on `/clasp/admin/tap/{id}`
+    pub(crate) fn apply_admin_set(&self, address: &str, value: &Value) -> Result<(), String> {
+        let id = address
+            .strip_prefix(TAP_ADMIN_PREFIX)
+            .ok_or_else(|| "not a tap admin address".to_string())?;
+        if matches!(value, Value::Null) {
+            self.disable(id);
+            return Ok(());
+        }
+        let json = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        let mut rule: TapRule =
+            serde_json::from_value(json).map_err(|e| format!("invalid tap rule: {}", e))?;
+        if let Some(name) = rule.file.take() {
+            let dir = self
+                .capture_dir
+                .read()
+                .clone()
+                .ok_or_else(|| "file capture is not enabled on this router".to_string())?;
+            let is_bare_name = name.file_name().is_some_and(|f| f == name.as_os_str());
+            if !is_bare_name {
+                return Err("file must be a bare file name".into());
+            }
+            rule.file = Some(dir.join(name));
+        }
+        self.enable(id, rule)
+    }
+}
+
+fn collect_addresses<'a>(msg: &'a Message, out: &mut Vec<&'a str>) {
+    match msg {
+        Message::Set(m) => out.push(&m.address),
+        Message::SetMany(m) => out.push(&m.base),
+        Message::Publish(m) => out.push(&m.address),
+        Message::Get(m) => out.push(&m.address),
+        Message::Subscribe(m) => out.push(&m.pattern),
+        Message::Replay(m) => out.push(&m.pattern),
+        Message::Query(m) => out.push(&m.pattern),
+        Message::Ack(m) => out.extend(m.address.as_deref()),
+        Message::Error(m) => out.extend(m.address.as_deref()),
+        Message::Snapshot(m) => out.extend(m.params.iter().map(|p| p.address.as_str())),
+        Message::Announce(m) => out.extend(m.signals.iter().map(|s| s.address.as_str())),
+        Message::Bundle(m) => {
+            for inner in &m.messages {
+                collect_addresses(inner, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Transport sender wrapper that offers outgoing frames to the wire tap
+pub(crate) struct TapSender {
+    inner: Arc<dyn clasp_transport::TransportSender>,
+    tap: Arc<WireTap>,
+    session_id: OnceLock<SessionId>,
+}
+
+impl TapSender {
+    pub(crate) fn new(inner: Arc<dyn clasp_transport::TransportSender>, tap: Arc<WireTap>) -> Self {
+        Self {
+            inner,
+            tap,
+            session_id: OnceLock::new(),
+        }
+    }
+
+    /// Attribute frames to the session once the handshake assigns its id
+    pub(crate) fn set_session(&self, id: &SessionId) {
+        let _ = self.session_id.set(id.clone());
+    }
+
+    fn offer(&self, data: &Bytes) {
+        if self.tap.is_active() {
+            let session = self.session_id.get().map(String::as_str).unwrap_or("");
+            self.tap.record(TapDirection::Out, session, data);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl clasp_transport::TransportSender for TapSender {
+    async fn send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.offer(&data);
+        self.inner.send(data).await
+    }
+
+    fn try_send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.offer(&data);
+        self.inner.try_send(data)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn queued(&self) -> Option<usize> {
+        self.inner.queued()
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::SetMessage;
+
+    fn set_frame(address: &str) -> Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(1.0),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    fn tap() -> WireTap {
+        WireTap::new(
+            Arc::new(DashMap::new()),
+            Arc::new(SubscriptionManager::new()),
+        )
+    }
+
+    #[test]
+    fn test_sampling_and_selection() {
+        let tap = tap();
+        tap.enable(
+            "t",
+            TapRule {
+                pattern: Some("/lights/**".into()),
+                sample_rate: 0.25,
+                direction: TapDirection::In,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for _ in 0..8 {
+            tap.record(TapDirection::In, "s1", &set_frame("/lights/1"));
+        }
+        tap.record(TapDirection::Out, "s1", &set_frame("/lights/1"));
+        tap.record(TapDirection::In, "s1", &set_frame("/audio/1"));
+
+        let stats = tap.stats();
+        assert_eq!(stats[0].matched, 8);
+        assert_eq!(stats[0].captured, 2);
+
+        assert!(tap.disable("t"));
+        assert!(!tap.is_active());
+    }
+
+    #[test]
+    fn test_admin_set_parses_rule() {
+        let tap = tap();
+        let mut rule = HashMap::new();
+        rule.insert("sample_rate".to_string(), Value::Float(0.5));
+        rule.insert("session".to_string(), Value::String("abc".into()));
+        tap.apply_admin_set("/clasp/admin/tap/debug", &Value::Map(rule))
+            .unwrap();
+        assert_eq!(tap.stats()[0].rule.session.as_deref(), Some("abc"));
+
+        let mut bad = HashMap::new();
+        bad.insert("sample_rate".to_string(), Value::Float(2.0));
+        assert!(tap
+            .apply_admin_set("/clasp/admin/tap/debug", &Value::Map(bad))
+            .is_err());
+
+        tap.apply_admin_set("/clasp/admin/tap/debug", &Value::Null)
+            .unwrap();
+        assert!(tap.stats().is_empty());
+
+        let mut with_file = HashMap::new();
+        with_file.insert("file".to_string(), Value::String("/etc/passwd".into()));
+        assert!(tap
+            .apply_admin_set("/clasp/admin/tap/debug", &Value::Map(with_file.clone()))
+            .is_err());
+        tap.set_capture_dir(Some(std::env::temp_dir()));
+        assert!(tap
+            .apply_admin_set("/clasp/admin/tap/debug", &Value::Map(with_file))
+            .is_err());
+        assert!(tap.stats().is_empty());
+    }
+
+    #[test]
+    fn test_pcap_file_output() {
+        let dir = std::env::temp_dir().join(format!("clasp-tap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("capture.pcap");
+        let _ = std::fs::remove_file(&path);
+
+        let tap = tap();
+        tap.enable(
+            "file",
+            TapRule {
+                file: Some(path.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let frame = set_frame("/a");
+        tap.record(TapDirection::Out, "sid", &frame);
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[..24], &pcap_header());
+        let packet = &data[24 + 16..];
+        assert_eq!(packet[0], 1);
+        assert_eq!(packet[1], 3);
+        assert_eq!(&packet[2..5], b"sid");
+        assert_eq!(&packet[5..], &frame[..]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,263 @@
+//! Fixed-tick aggregation for subscriptions with `tick_ms`
+//!
+//! Game engine clients render at a fixed rate and want one consolidated
+//! state diff per frame rather than every intermediate update. A
+//! subscription with `tick_ms` set collects matching SET and PUBLISH
+//! broadcasts for its session, keeps only the last message per address, and
+//! delivers them as a single BUNDLE on each tick boundary.
+//!
+//! Buffering happens in [`Session::try_send`](crate::Session::try_send), the
+//! path every broadcast goes through, so aggregation applies no matter which
+//! handler produced the update. Direct replies (ACK, ERROR, SNAPSHOT) are
+//! never delayed.
+
+use bytes::Bytes;
+use clasp_core::{codec, BundleMessage, Message, SignalType};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::session::Session;
+use crate::subscription::Subscription;
+
+/// Shortest accepted tick; smaller values are clamped
+pub const MIN_TICK_MS: u32 = 1;
+
+/// Longest accepted tick; larger values are clamped
+pub const MAX_TICK_MS: u32 = 60_000;
+
+/// Latest message per address, in first-write order
+#[derive(Debug, Default)]
+pub struct TickBuffer {
+    order: Vec<String>,
+    latest: HashMap<String, Message>,
+}
+
+impl TickBuffer {
+    /// Record a message, replacing any earlier one for the same address
+    pub fn push(&mut self, address: &str, message: Message) {
+        if self.latest.insert(address.to_string(), message).is_none() {
+            self.order.push(address.to_string());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Take all buffered messages, leaving the buffer empty
+    pub fn drain(&mut self) -> Vec<Message> {
+        let mut latest = std::mem::take(&mut self.latest);
+        std::mem::take(&mut self.order)
+            .into_iter()
+            .filter_map(|address| latest.remove(&address))
+            .collect()
+    }
+}
+
+struct TickSubscription {
+    subscription: Subscription,
+    generation: u64,
+    buffer: TickBuffer,
+}
+
+/// Per-session set of tick subscriptions
+#[derive(Default)]
+pub struct TickSubscriptions {
+    /// Fast-path check so sessions without tick subscriptions skip decoding
+    active: AtomicBool,
+    next_generation: AtomicU64,
+    subs: Mutex<Vec<TickSubscription>>,
+}
+
+impl TickSubscriptions {
+    /// Register (or replace) a tick subscription, returning its generation.
+    /// A flush task only serves the generation it was started for.
+    pub fn add(&self, subscription: Subscription) -> u64 {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let mut subs = self.subs.lock();
+        subs.retain(|s| s.subscription.id != subscription.id);
+        subs.push(TickSubscription {
+            subscription,
+            generation,
+            buffer: TickBuffer::default(),
+        });
+        self.active.store(true, Ordering::Release);
+        generation
+    }
+
+    /// Remove a tick subscription. Returns `true` if it existed.
+    pub fn remove(&self, id: u32) -> bool {
+        let mut subs = self.subs.lock();
+        let before = subs.len();
+        subs.retain(|s| s.subscription.id != id);
+        self.active.store(!subs.is_empty(), Ordering::Release);
+        subs.len() != before
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Buffer a broadcast if it matches a tick subscription.
+    ///
+    /// Returns `true` when the data was buffered and must not be sent now.
+    pub fn intercept(&self, data: &Bytes) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let Ok((message, _)) = codec::decode(data) else {
+            return false;
+        };
+        let (address, signal_type) = match &message {
+            Message::Set(set) => (set.address.clone(), Some(SignalType::Param)),
+            Message::Publish(publish) => (publish.address.clone(), publish.signal),
+            _ => return false,
+        };
+
+        let mut subs = self.subs.lock();
+        match subs
+            .iter_mut()
+            .find(|s| s.subscription.matches(&address, signal_type))
+        {
+            Some(sub) => {
+                sub.buffer.push(&address, message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Encode the pending messages of one subscription as a BUNDLE.
+    ///
+    /// Returns `Err(())` once the subscription is gone or replaced, which
+    /// tells the flush task to stop.
+    fn flush(&self, id: u32, generation: u64) -> Result<Option<Bytes>, ()> {
+        let messages = {
+            let mut subs = self.subs.lock();
+            let sub = subs
+                .iter_mut()
+                .find(|s| s.subscription.id == id && s.generation == generation)
+                .ok_or(())?;
+            if sub.buffer.is_empty() {
+                return Ok(None);
+            }
+            sub.buffer.drain()
+        };
+
+        let bundle = Message::Bundle(BundleMessage {
+            timestamp: Some(clasp_core::time::now()),
+            messages,
+        });
+        Ok(codec::encode(&bundle).ok())
+    }
+}
+
+/// Spawn the task that delivers a tick subscription's bundles.
+///
+/// The task holds only a weak reference and ends when the session is
+/// dropped or the subscription is removed or replaced.
+pub(crate) fn spawn_flusher(session: Weak<Session>, id: u32, generation: u64, tick_ms: u32) {
+    let period = Duration::from_millis(tick_ms.clamp(MIN_TICK_MS, MAX_TICK_MS) as u64);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let Some(session) = session.upgrade() else {
+                break;
+            };
+            match session.tick_subscriptions().flush(id, generation) {
+                Ok(Some(bytes)) => {
+                    crate::handlers::try_send_with_drop_tracking_sync(&session, bytes, &session.id);
+                }
+                Ok(None) => {}
+                Err(()) => break,
+            }
+        }
+        debug!("Tick flusher for subscription {} stopped", id);
+    });
+}
+
+/// Convenience wrapper for the subscribe handler
+pub(crate) fn start(session: &Arc<Session>, subscription: Subscription, tick_ms: u32) {
+    let id = subscription.id;
+    let generation = session.tick_subscriptions().add(subscription);
+    spawn_flusher(Arc::downgrade(session), id, generation, tick_ms);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{SetMessage, SubscribeOptions, Value};
+
+    fn set(address: &str, value: f64) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(value),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    #[test]
+    fn test_buffer_keeps_last_write_in_first_write_order() {
+        let mut buffer = TickBuffer::default();
+        buffer.push("/a", set("/a", 1.0));
+        buffer.push("/b", set("/b", 2.0));
+        buffer.push("/a", set("/a", 3.0));
+        assert_eq!(buffer.len(), 2);
+
+        let drained = buffer.drain();
+        assert!(buffer.is_empty());
+        let values: Vec<_> = drained
+            .iter()
+            .map(|m| match m {
+                Message::Set(s) => (s.address.as_str(), s.value.as_f64().unwrap()),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(values, vec![("/a", 3.0), ("/b", 2.0)]);
+    }
+
+    #[test]
+    fn test_intercept_only_matching_broadcasts() {
+        let ticks = TickSubscriptions::default();
+        let sub = Subscription::new(
+            1,
+            "s".to_string(),
+            "/game/**",
+            vec![],
+            SubscribeOptions::default(),
+        )
+        .unwrap();
+        let generation = ticks.add(sub);
+
+        assert!(ticks.intercept(&codec::encode(&set("/game/x", 1.0)).unwrap()));
+        assert!(ticks.intercept(&codec::encode(&set("/game/x", 2.0)).unwrap()));
+        assert!(!ticks.intercept(&codec::encode(&set("/ui/x", 1.0)).unwrap()));
+        assert!(!ticks.intercept(&codec::encode(&Message::Ping).unwrap()));
+
+        let bytes = ticks.flush(1, generation).unwrap().unwrap();
+        match codec::decode(&bytes).unwrap().0 {
+            Message::Bundle(bundle) => assert_eq!(bundle.messages.len(), 1),
+            other => panic!("expected bundle, got {:?}", other),
+        }
+        assert_eq!(ticks.flush(1, generation), Ok(None));
+
+        assert!(ticks.remove(1));
+        assert!(!ticks.is_active());
+        assert!(ticks.flush(1, generation).is_err());
+    }
+}
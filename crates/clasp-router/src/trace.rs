@@ -0,0 +1,252 @@
+//! Message tracing
+//!
+//! A SET sent with `trace: true` by a session holding admin scope for its
+//! address is handled as usual, and the router then publishes a report of
+//! what happened to it as an event on `/clasp/trace/{session_id}` (see
+//! [`address`]). Subscribe there before sending the SET. The report is a map:
+//!
+//! - `address`, and `correlation_id` when the frame carried one
+//! - `outcome`: `applied`, `absorbed` (by an aggregate), `rejected`, or
+//!   `handled` when the router sent no answer
+//! - `revision` when applied, `error` (`"<code> <message>"`) when rejected
+//! - `decisions`: the checks the SET passed, in order, as `"step: result"`
+//! - `subscribers`: how many sessions the update matched
+//! - `deliveries`: session ID to `queued`, `dropped` (send buffer full) or
+//!   `gone` (disconnected before the send)
+//! - `forwards`: router IDs of the federation peers the update went to
+//! - `journal_seq`: the journal sequence of the write, when journaled
+//!
+//! Without admin scope the flag is ignored and the SET is handled untraced.
+//! The flag is never forwarded to subscribers.
+
+use bytes::Bytes;
+use clasp_core::{codec, Action, Message, PublishMessage, SecurityMode, SignalType, Value};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::handlers::{try_send_with_drop_tracking_sync, HandlerContext, MessageResult};
+use crate::session::{Session, SessionId};
+use crate::state::RouterState;
+use crate::subscription::SubscriptionManager;
+
+/// Trace reports are published under this prefix, one address per session
+pub const TRACE_PREFIX: &str = "/clasp/trace/";
+
+/// How long to wait for a traced SET's journal append
+#[cfg(feature = "journal")]
+const JOURNAL_WAIT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Address a session's trace reports are published on
+pub fn address(session_id: &str) -> String {
+    format!("{}{}", TRACE_PREFIX, session_id)
+}
+
+/// What has been recorded for one traced SET
+pub(crate) struct Trace {
+    session_id: SessionId,
+    address: String,
+    correlation_id: Option<u32>,
+    /// When the SET arrived, in Unix microseconds
+    started_at: u64,
+    report: Mutex<Report>,
+}
+
+#[derive(Default)]
+struct Report {
+    decisions: Vec<String>,
+    subscribers: usize,
+    deliveries: HashMap<String, Value>,
+    forwards: Vec<Value>,
+}
+
+impl Trace {
+    /// Start a trace if the SET asks for one and the session may trace it
+    pub(crate) fn requested(
+        set: &clasp_core::SetMessage,
+        ctx: &HandlerContext<'_>,
+    ) -> Option<Self> {
+        if !set.trace {
+            return None;
+        }
+        let session = ctx.session.as_ref()?;
+        if ctx.security_mode == SecurityMode::Authenticated
+            && !session.has_scope(Action::Admin, &set.address)
+        {
+            debug!(
+                "Session {} asked to trace SET to {} without admin scope",
+                session.id, set.address
+            );
+            return None;
+        }
+        Some(Self {
+            session_id: session.id.clone(),
+            address: set.address.clone(),
+            correlation_id: ctx.correlation_id,
+            started_at: clasp_core::time::now(),
+            report: Mutex::new(Report::default()),
+        })
+    }
+
+    /// Record a check the SET passed
+    pub(crate) fn note(&self, step: &str, result: &str) {
+        self.report
+            .lock()
+            .decisions
+            .push(format!("{}: {}", step, result));
+    }
+
+    /// Send an update to its subscribers, recording each delivery. Used in
+    /// place of [`broadcast_to_subscriber_list`] for traced SETs.
+    ///
+    /// [`broadcast_to_subscriber_list`]: crate::handlers::broadcast_to_subscriber_list
+    pub(crate) fn deliver(
+        &self,
+        data: &Bytes,
+        subscriber_ids: &[SessionId],
+        sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    ) {
+        let mut report = self.report.lock();
+        report.subscribers = subscriber_ids.len();
+        for id in subscriber_ids {
+            let Some(session) = sessions.get(id).map(|entry| Arc::clone(entry.value())) else {
+                report
+                    .deliveries
+                    .insert(id.clone(), Value::String("gone".to_string()));
+                continue;
+            };
+            let queued = try_send_with_drop_tracking_sync(&session, data.clone(), id);
+            let outcome = if queued { "queued" } else { "dropped" };
+            report
+                .deliveries
+                .insert(id.clone(), Value::String(outcome.to_string()));
+
+            #[cfg(feature = "federation")]
+            if queued && session.is_federation_peer() {
+                let peer = session.federation_router_id().unwrap_or_else(|| id.clone());
+                report.forwards.push(Value::String(peer));
+            }
+        }
+    }
+
+    /// Publish the report once the SET has been answered with `result`
+    pub(crate) fn finish(self, result: &Option<MessageResult>, ctx: &HandlerContext<'_>) {
+        let answer = match result {
+            Some(MessageResult::Send(bytes)) => codec::decode(bytes).ok().map(|(msg, _)| msg),
+            _ => None,
+        };
+        let mut fields = HashMap::new();
+        let mut revision = None;
+        match answer {
+            Some(Message::Ack(ack)) => match ack.revision {
+                Some(rev) => {
+                    revision = Some(rev);
+                    fields.insert("outcome".to_string(), Value::String("applied".to_string()));
+                    fields.insert("revision".to_string(), Value::Int(rev as i64));
+                }
+                None => {
+                    fields.insert("outcome".to_string(), Value::String("absorbed".to_string()));
+                }
+            },
+            Some(Message::Error(error)) => {
+                fields.insert("outcome".to_string(), Value::String("rejected".to_string()));
+                fields.insert(
+                    "error".to_string(),
+                    Value::String(format!("{} {}", error.code, error.message)),
+                );
+            }
+            _ => {
+                fields.insert("outcome".to_string(), Value::String("handled".to_string()));
+            }
+        }
+
+        let report = self.report.into_inner();
+        fields.insert("address".to_string(), Value::String(self.address.clone()));
+        if let Some(id) = self.correlation_id {
+            fields.insert("correlation_id".to_string(), Value::Int(id as i64));
+        }
+        fields.insert(
+            "decisions".to_string(),
+            Value::Array(report.decisions.into_iter().map(Value::String).collect()),
+        );
+        fields.insert(
+            "subscribers".to_string(),
+            Value::Int(report.subscribers as i64),
+        );
+        fields.insert("deliveries".to_string(), Value::Map(report.deliveries));
+        fields.insert("forwards".to_string(), Value::Array(report.forwards));
+
+        let state = Arc::clone(ctx.state);
+        let sessions = Arc::clone(ctx.sessions);
+        let subscriptions = Arc::clone(ctx.subscriptions);
+        let session_id = self.session_id;
+        let address = self.address;
+        let started_at = self.started_at;
+        tokio::spawn(async move {
+            if let Some(seq) = journal_seq(&state, &address, revision, started_at).await {
+                fields.insert("journal_seq".to_string(), Value::Int(seq as i64));
+            }
+            publish(&session_id, Value::Map(fields), &sessions, &subscriptions);
+        });
+    }
+}
+
+/// Journal sequence of the SET that wrote `revision` to `address`, waiting
+/// briefly for the append to land
+#[cfg(feature = "journal")]
+async fn journal_seq(
+    state: &RouterState,
+    address: &str,
+    revision: Option<u64>,
+    since: u64,
+) -> Option<u64> {
+    let revision = revision?;
+    let journal = state.journal_for(address)?;
+    let deadline = tokio::time::Instant::now() + JOURNAL_WAIT;
+    loop {
+        if let Ok(entries) = journal
+            .query(address, Some(since), None, None, &[SignalType::Param])
+            .await
+        {
+            if let Some(entry) = entries.iter().find(|e| e.revision == Some(revision)) {
+                return Some(entry.seq);
+            }
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+}
+
+#[cfg(not(feature = "journal"))]
+async fn journal_seq(_: &RouterState, _: &str, _: Option<u64>, _: u64) -> Option<u64> {
+    None
+}
+
+fn publish(
+    session_id: &str,
+    report: Value,
+    sessions: &Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: &SubscriptionManager,
+) {
+    let address = address(session_id);
+    let subscribers = subscriptions.find_subscribers(&address, Some(SignalType::Event));
+    let msg = Message::Publish(PublishMessage {
+        address,
+        signal: Some(SignalType::Event),
+        value: Some(report),
+        payload: None,
+        samples: None,
+        rate: None,
+        id: None,
+        phase: None,
+        timestamp: Some(clasp_core::time::now()),
+        timeline: None,
+    });
+    if let Ok(bytes) = codec::encode(&msg) {
+        crate::handlers::broadcast_to_subscriber_list(&bytes, &subscribers, sessions, None);
+    }
+}
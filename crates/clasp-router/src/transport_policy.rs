@@ -0,0 +1,210 @@
+//! Per-transport feature policy
+//!
+//! [`RouterConfig::transport_policies`] restricts what clients on a given
+//! protocol may do, keyed by transport name (`websocket`, `tcp`, `quic`,
+//! `mqtt`, `osc`, `resp`). Transports without an entry are unrestricted.
+//! So the public OSC port can publish events but never mutate state:
+//!
+//! ```
+//! use clasp_router::TransportPolicy;
+//!
+//! let osc = TransportPolicy::new()
+//!     .types(&["PUBLISH"])
+//!     .namespace("/osc/**");
+//! assert!(osc.permits("PUBLISH", "/osc/cue/go"));
+//! assert!(!osc.permits("SET", "/osc/cue/go"));
+//! assert!(!osc.permits("PUBLISH", "/config/rate"));
+//! ```
+//!
+//! A policy limits:
+//!
+//! - `types`: the uppercase message names clients may send (`SET`,
+//!   `PUBLISH`, `SUBSCRIBE`, `GET`, `BUNDLE`, `QUERY`, `ANNOUNCE`, ...).
+//!   Empty allows all. HELLO, PING, SYNC, UNSUBSCRIBE and ALIAS are always
+//!   allowed, since a session can't work without them.
+//! - `namespaces`: address patterns clients may read or write. A SUBSCRIBE
+//!   pattern has to fall inside one of them too. Empty allows all.
+//! - `read_only`: refuses SET, SET_MANY, PUBLISH, BUNDLE and ANNOUNCE
+//!   whatever `types` says.
+//!
+//! Native transports are checked before each message is dispatched, and a
+//! refused message is answered with ERROR 301. Every message in a BUNDLE
+//! and every address a SET_MANY writes has to pass on its own. The MQTT and OSC adapters check before turning
+//! a packet into a CLASP operation: where SET is refused but PUBLISH is
+//! allowed, incoming values are forwarded as events instead of being
+//! stored, and anything else refused is dropped (refused MQTT SUBSCRIBEs
+//! get a failure return code). The RESP adapter answers refused commands
+//! with an error reply.
+//!
+//! [`RouterConfig::transport_policies`]: crate::RouterConfig::transport_policies
+
+use clasp_core::address::glob_match;
+use clasp_core::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::handlers::message_type_str;
+use crate::rate_limit::message_address;
+
+/// What clients on one transport may do (see the [module docs](self))
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransportPolicy {
+    /// Message types clients may send (empty = all)
+    pub types: Vec<String>,
+    /// Address patterns clients may read or write (empty = all)
+    pub namespaces: Vec<String>,
+    /// Refuse SET, PUBLISH, BUNDLE and ANNOUNCE
+    pub read_only: bool,
+}
+
+impl TransportPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow only these message types
+    pub fn types(mut self, types: &[&str]) -> Self {
+        self.types = types.iter().map(|t| t.to_ascii_uppercase()).collect();
+        self
+    }
+
+    /// Allow addresses matching `pattern` (can be called several times)
+    pub fn namespace(mut self, pattern: &str) -> Self {
+        self.namespaces.push(pattern.to_string());
+        self
+    }
+
+    /// Refuse every write
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Whether the policy allows a message of `msg_type` at all
+    pub fn allows_type(&self, msg_type: &str) -> bool {
+        if is_session_control(msg_type) {
+            return true;
+        }
+        if self.read_only && is_write(msg_type) {
+            return false;
+        }
+        self.types.is_empty() || self.types.iter().any(|t| t == msg_type)
+    }
+
+    /// Whether `address` (or SUBSCRIBE pattern) is inside an allowed namespace
+    pub fn allows_address(&self, address: &str) -> bool {
+        self.namespaces.is_empty()
+            || self
+                .namespaces
+                .iter()
+                .any(|pattern| glob_match(pattern, address))
+    }
+
+    /// Whether a `msg_type` message to `address` is allowed
+    pub fn permits(&self, msg_type: &str, address: &str) -> bool {
+        self.allows_type(msg_type) && self.allows_address(address)
+    }
+
+    /// Why `msg` is refused, or None if the policy allows it
+    pub fn check(&self, msg: &Message) -> Option<String> {
+        let msg_type = message_type_str(msg);
+        if !self.allows_type(msg_type) {
+            return Some(format!("{} is not allowed on this transport", msg_type));
+        }
+        if let Message::SetMany(batch) = msg {
+            return batch
+                .entries()
+                .map(|(index, _)| batch.address(index))
+                .find(|address| !self.allows_address(address))
+                .map(|address| {
+                    format!(
+                        "SET_MANY to {} is outside the namespaces allowed on this transport",
+                        address
+                    )
+                });
+        }
+        if let Some(address) = message_address(msg) {
+            if !self.allows_address(address) {
+                return Some(format!(
+                    "{} to {} is outside the namespaces allowed on this transport",
+                    msg_type, address
+                ));
+            }
+        }
+        if let Message::Bundle(bundle) = msg {
+            return bundle.messages.iter().find_map(|inner| self.check(inner));
+        }
+        None
+    }
+}
+
+/// Messages every session needs, whatever its policy
+fn is_session_control(msg_type: &str) -> bool {
+    matches!(
+        msg_type,
+        "HELLO" | "PROOF" | "PING" | "PONG" | "SYNC" | "UNSUBSCRIBE" | "ALIAS"
+    )
+}
+
+/// Messages that change state or reach other clients
+fn is_write(msg_type: &str) -> bool {
+    matches!(
+        msg_type,
+        "SET" | "SET_MANY" | "PUBLISH" | "BUNDLE" | "ANNOUNCE"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_core::{BundleMessage, SetManyMessage, SetMessage, SubscribeMessage, Value};
+
+    fn set(address: &str) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(1),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    #[test]
+    fn test_read_only_overrides_types() {
+        let policy = TransportPolicy::new().types(&["set", "get"]).read_only();
+        assert!(policy.check(&set("/a")).is_some());
+        assert!(policy.permits("GET", "/a"));
+        assert!(policy.permits("PING", "/a"));
+    }
+
+    #[test]
+    fn test_namespaces_cover_bundles_and_patterns() {
+        let policy = TransportPolicy::new().namespace("/osc/**");
+        assert!(policy.check(&set("/osc/x")).is_none());
+
+        let bundle = Message::Bundle(BundleMessage {
+            timestamp: None,
+            messages: vec![set("/osc/x"), set("/config/rate")],
+        });
+        assert!(policy.check(&bundle).unwrap().contains("/config/rate"));
+
+        let batch =
+            |base: &str| Message::SetMany(SetManyMessage::run(base, 1, vec![Value::Int(0); 2]));
+        assert!(policy.check(&batch("/osc/dmx")).is_none());
+        assert!(policy.check(&batch("/dmx")).unwrap().contains("/dmx/1"));
+
+        let subscribe = |pattern: &str| {
+            Message::Subscribe(SubscribeMessage {
+                id: 1,
+                pattern: pattern.to_string(),
+                types: vec![],
+                options: None,
+            })
+        };
+        assert!(policy.check(&subscribe("/osc/*/level")).is_none());
+        assert!(policy.check(&subscribe("/**")).is_some());
+    }
+}
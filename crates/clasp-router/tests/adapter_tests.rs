@@ -244,6 +244,322 @@ mod osc_adapter_tests {
     }
 }
 
+// =============================================================================
+// RESP Server Adapter Tests
+// =============================================================================
+
+#[cfg(feature = "resp-server")]
+mod resp_adapter_tests {
+    use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+    use clasp_core::SecurityMode;
+    use clasp_router::adapters::{RespServerAdapter, RespServerConfig};
+    use clasp_router::{
+        ReservedNamespace, Router, RouterConfig, RouterState, SchemaMode, Session, TransportPolicy,
+        WriteValidator,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn start(router: &Router, config: RespServerConfig) -> String {
+        let (sessions, subscriptions, state) = router.shared_state();
+        let mut adapter = RespServerAdapter::new(config, sessions, subscriptions, state)
+            .with_policy(router.transport_policy("resp"))
+            .with_write_checks(router.write_checks());
+        if router.security_mode() == SecurityMode::Authenticated {
+            adapter = adapter.with_reserved(ReservedNamespace::default());
+        }
+        let validator = Arc::new(CpskValidator::new());
+        for (token, scopes) in [
+            ("cpsk_reader", vec!["read:/**"]),
+            ("cpsk_writer", vec!["read:/**", "write:/**"]),
+            ("cpsk_admin", vec!["read:/**", "admin:/**"]),
+        ] {
+            validator.register(
+                token.to_string(),
+                TokenInfo::new(
+                    token.to_string(),
+                    scopes
+                        .into_iter()
+                        .map(|s| Scope::parse(s).unwrap())
+                        .collect(),
+                ),
+            );
+        }
+        adapter = adapter.with_validator(validator);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { adapter.serve_on(listener).await });
+        addr
+    }
+
+    /// Send a command and read until the reply ends with `expected`
+    async fn command(stream: &mut TcpStream, cmd: &[u8], expected: &str) {
+        stream.write_all(cmd).await.unwrap();
+        let mut buf = Vec::new();
+        let read = async {
+            while !String::from_utf8_lossy(&buf).ends_with(expected) {
+                let mut chunk = [0u8; 1024];
+                let n = stream.read(&mut chunk).await.unwrap();
+                assert!(n > 0, "connection closed");
+                buf.extend_from_slice(&chunk[..n]);
+            }
+        };
+        if tokio::time::timeout(Duration::from_secs(2), read)
+            .await
+            .is_err()
+        {
+            panic!(
+                "expected {:?}, got {:?}",
+                expected,
+                String::from_utf8_lossy(&buf)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resp_set_get_keys() {
+        let router = Router::default();
+        let addr = start(&router, RespServerConfig::default()).await;
+        let mut client = TcpStream::connect(&addr).await.unwrap();
+
+        command(&mut client, b"PING\r\n", "+PONG\r\n").await;
+        command(
+            &mut client,
+            b"*3\r\n$3\r\nSET\r\n$9\r\n/lights/1\r\n$3\r\n0.5\r\n",
+            "+OK\r\n",
+        )
+        .await;
+        command(&mut client, b"SET lights/2 on\r\n", "+OK\r\n").await;
+        command(&mut client, b"GET /lights/1\r\n", "$3\r\n0.5\r\n").await;
+        command(&mut client, b"GET /missing\r\n", "$-1\r\n").await;
+        command(
+            &mut client,
+            b"KEYS /lights/*\r\n",
+            "*2\r\n$9\r\n/lights/1\r\n$9\r\n/lights/2\r\n",
+        )
+        .await;
+
+        let state = router.state();
+        assert_eq!(state.get("/lights/1"), Some(clasp_core::Value::Float(0.5)));
+        assert_eq!(
+            state.get("/lights/2"),
+            Some(clasp_core::Value::String("on".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resp_psubscribe_receives_sets() {
+        let router = Router::default();
+        let addr = start(&router, RespServerConfig::default()).await;
+        let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+        let mut writer = TcpStream::connect(&addr).await.unwrap();
+
+        command(
+            &mut subscriber,
+            b"PSUBSCRIBE /lights/*\r\n",
+            "$10\r\npsubscribe\r\n$9\r\n/lights/*\r\n:1\r\n",
+        )
+        .await;
+        command(&mut writer, b"SET /lights/3/dimmer 1\r\n", "+OK\r\n").await;
+        command(&mut writer, b"SET /other/x 1\r\n", "+OK\r\n").await;
+        command(&mut writer, b"PUBLISH /lights/go now\r\n", ":1\r\n").await;
+
+        command(
+            &mut subscriber,
+            b"",
+            "*4\r\n$8\r\npmessage\r\n$9\r\n/lights/*\r\n$16\r\n/lights/3/dimmer\r\n$1\r\n1\r\n\
+             *4\r\n$8\r\npmessage\r\n$9\r\n/lights/*\r\n$10\r\n/lights/go\r\n$3\r\nnow\r\n",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_resp_auth_and_scopes() {
+        let router = Router::default();
+        let addr = start(
+            &router,
+            RespServerConfig {
+                require_auth: true,
+                ..Default::default()
+            },
+        )
+        .await;
+        let mut client = TcpStream::connect(&addr).await.unwrap();
+
+        command(
+            &mut client,
+            b"GET /a\r\n",
+            "-NOAUTH Authentication required.\r\n",
+        )
+        .await;
+        command(
+            &mut client,
+            b"AUTH wrong\r\n",
+            "-WRONGPASS invalid token\r\n",
+        )
+        .await;
+        command(&mut client, b"AUTH default cpsk_reader\r\n", "+OK\r\n").await;
+        command(&mut client, b"GET /a\r\n", "$-1\r\n").await;
+        command(
+            &mut client,
+            b"SET /a 1\r\n",
+            "-NOPERM no write access to '/a'\r\n",
+        )
+        .await;
+        assert!(router.state().get("/a").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resp_transport_policy() {
+        let policy = TransportPolicy::new()
+            .types(&["GET", "PUBLISH", "SUBSCRIBE"])
+            .namespace("/public/**");
+        let router = Router::new(RouterConfig {
+            transport_policies: [("resp".to_string(), policy)].into_iter().collect(),
+            ..Default::default()
+        });
+        router
+            .state()
+            .set(
+                "/config/rate",
+                clasp_core::Value::Int(30),
+                &"admin".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+        let addr = start(&router, RespServerConfig::default()).await;
+        let mut client = TcpStream::connect(&addr).await.unwrap();
+
+        command(
+            &mut client,
+            b"SET /public/x 1\r\n",
+            "-NOPERM SET to '/public/x' is not allowed\r\n",
+        )
+        .await;
+        command(&mut client, b"GET /config/rate\r\n", "$-1\r\n").await;
+        command(&mut client, b"PUBLISH /public/cue go\r\n", ":0\r\n").await;
+        command(
+            &mut client,
+            b"PUBLISH /config/rate 60\r\n",
+            "-NOPERM PUBLISH to '/config/rate' is not allowed\r\n",
+        )
+        .await;
+        command(
+            &mut client,
+            b"SUBSCRIBE /config/rate\r\n",
+            "-NOPERM no read access to '/config/rate'\r\n",
+        )
+        .await;
+        assert!(router.state().get("/public/x").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resp_reserved_namespace() {
+        let router = Router::new(RouterConfig {
+            security_mode: SecurityMode::Authenticated,
+            ..Default::default()
+        });
+        let addr = start(&router, RespServerConfig::default()).await;
+
+        let mut anonymous = TcpStream::connect(&addr).await.unwrap();
+        command(
+            &mut anonymous,
+            b"SET /clasp/rules/mute 1\r\n",
+            "-NOPERM '/clasp/rules/mute' is reserved\r\n",
+        )
+        .await;
+        command(&mut anonymous, b"SET /clasp/p2p/offer 1\r\n", "+OK\r\n").await;
+
+        let mut writer = TcpStream::connect(&addr).await.unwrap();
+        command(&mut writer, b"AUTH cpsk_writer\r\n", "+OK\r\n").await;
+        command(
+            &mut writer,
+            b"PUBLISH /clasp/rules/mute 1\r\n",
+            "-NOPERM '/clasp/rules/mute' is reserved\r\n",
+        )
+        .await;
+
+        let mut admin = TcpStream::connect(&addr).await.unwrap();
+        command(&mut admin, b"AUTH cpsk_admin\r\n", "+OK\r\n").await;
+        command(&mut admin, b"SET /clasp/rules/mute 1\r\n", "+OK\r\n").await;
+        assert_eq!(
+            router.state().get("/clasp/rules/mute"),
+            Some(clasp_core::Value::Int(1))
+        );
+    }
+
+    struct NoLocked;
+
+    impl WriteValidator for NoLocked {
+        fn validate_write(
+            &self,
+            address: &str,
+            _value: &clasp_core::Value,
+            _session: &Session,
+            _state: &RouterState,
+        ) -> Result<(), String> {
+            if address.starts_with("/locked/") {
+                Err("locked".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resp_write_checks() {
+        let mut router = Router::new(RouterConfig {
+            schema: SchemaMode::Reject,
+            ..Default::default()
+        });
+        router.set_write_validator(NoLocked);
+        router
+            .state()
+            .register_signals(vec![clasp_core::SignalDefinition {
+                address: "/lights/*/level".to_string(),
+                signal_type: clasp_core::SignalType::Param,
+                datatype: Some("f32".to_string()),
+                access: Some("rw".to_string()),
+                meta: Some(clasp_core::SignalMeta {
+                    unit: None,
+                    range: Some((0.0, 1.0)),
+                    default: None,
+                    description: None,
+                    enum_values: None,
+                }),
+            }]);
+        let addr = start(&router, RespServerConfig::default()).await;
+        let mut stream = TcpStream::connect(&addr).await.unwrap();
+
+        command(&mut stream, b"SET /locked/door 1\r\n", "-ERR locked\r\n").await;
+        command(
+            &mut stream,
+            b"SET /lights/1/level 1.5\r\n",
+            "-ERR max: 1.5 is above 1\r\n",
+        )
+        .await;
+        command(
+            &mut stream,
+            b"SET /clasp/schema/lights/1 1\r\n",
+            "-ERR Schemas are published by the router\r\n",
+        )
+        .await;
+        command(&mut stream, b"SET /lights/1/level 0.5\r\n", "+OK\r\n").await;
+
+        assert!(router.state().get("/locked/door").is_none());
+        assert_eq!(
+            router.state().get("/lights/1/level"),
+            Some(clasp_core::Value::Float(0.5))
+        );
+    }
+}
+
 // =============================================================================
 // Multi-Protocol Integration Tests
 // =============================================================================
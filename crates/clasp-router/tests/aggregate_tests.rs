@@ -0,0 +1,104 @@
+//! Aggregated Write Tests
+//!
+//! Tests for:
+//! - Many SETs to an aggregated address arriving as one count
+//! - Cumulative vote tallies readable with GET
+//! - Events to an aggregated address being absorbed too
+
+use clasp_core::Value;
+use clasp_router::{AggregateKind, AggregateRule, RouterConfig};
+use clasp_test_utils::{TestRouter, ValueCollector};
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn aggregate_router_config() -> RouterConfig {
+    RouterConfig {
+        aggregates: vec![
+            AggregateRule::new("/claps", AggregateKind::Count).interval_ms(200),
+            AggregateRule::new("/votes/*", AggregateKind::Histogram)
+                .interval_ms(50)
+                .cumulative(true),
+        ],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_sets_arrive_as_one_count() {
+    let router = TestRouter::start_with_config(aggregate_router_config()).await;
+
+    let screen = router
+        .connect_client_named("Screen")
+        .await
+        .expect("Screen should connect");
+    let claps = ValueCollector::new();
+    screen
+        .subscribe("/claps", claps.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(50)).await;
+
+    let phone = router
+        .connect_client_named("Phone")
+        .await
+        .expect("Phone should connect");
+    for _ in 0..50 {
+        phone
+            .set("/claps", Value::Bool(true))
+            .await
+            .expect("set should be sent");
+    }
+
+    assert!(
+        claps.wait_for_count(1, Duration::from_secs(2)).await,
+        "Screen should receive an aggregate"
+    );
+    sleep(Duration::from_millis(300)).await;
+
+    // Every clap is counted once, in however many intervals it took
+    let total: i64 = claps
+        .values()
+        .iter()
+        .map(|(_, value)| value.as_i64().unwrap_or(0))
+        .sum();
+    assert_eq!(total, 50);
+    assert!(
+        claps.count() < 50,
+        "Claps should not be forwarded one by one"
+    );
+}
+
+#[tokio::test]
+async fn test_cumulative_votes_readable_with_get() {
+    let router = TestRouter::start_with_config(aggregate_router_config()).await;
+
+    let voter = router
+        .connect_client_named("Voter")
+        .await
+        .expect("Voter should connect");
+    for vote in ["red", "blue", "red"] {
+        voter
+            .set("/votes/color", Value::String(vote.to_string()))
+            .await
+            .expect("set should be sent");
+    }
+    voter
+        .emit("/votes/color", Value::String("red".to_string()))
+        .await
+        .expect("emit should be sent");
+    sleep(Duration::from_millis(300)).await;
+
+    let reader = router
+        .connect_client_named("Reader")
+        .await
+        .expect("Reader should connect");
+    let Value::Map(tally) = reader
+        .get("/votes/color")
+        .await
+        .expect("Tally should be stored")
+    else {
+        panic!("Tally should be a map");
+    };
+    assert_eq!(tally["red"], Value::Int(3));
+    assert_eq!(tally["blue"], Value::Int(1));
+}
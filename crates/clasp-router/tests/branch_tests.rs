@@ -0,0 +1,313 @@
+//! State Branch Tests
+//!
+//! Tests for:
+//! - Branch writes staying off live state and live subscribers
+//! - Branch subscriptions seeing the branch over live state
+//! - Atomic merge into live state, and rollback when a param is locked
+//! - Discard handing branch watchers the live values back
+//! - Merged SETs journaled with consecutive sequence numbers
+
+use clasp_core::{ErrorCode, Value};
+use clasp_test_utils::{TestRouter, ValueCollector};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::test]
+async fn test_branch_writes_stay_off_live_state() {
+    let router = TestRouter::start().await;
+
+    let operator = router
+        .connect_client_named("Operator")
+        .await
+        .expect("Operator should connect");
+    let programmer = router
+        .connect_client_named("Programmer")
+        .await
+        .expect("Programmer should connect");
+    let preview = router
+        .connect_client_named("Preview")
+        .await
+        .expect("Preview should connect");
+
+    operator
+        .set_confirmed("/show/level", 0.5)
+        .await
+        .expect("live set should succeed");
+    operator
+        .set_confirmed("/show/color", "red")
+        .await
+        .expect("live set should succeed");
+
+    let live = ValueCollector::new();
+    operator
+        .subscribe("/show/**", live.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    assert!(live.wait_for_count(2, Duration::from_secs(2)).await);
+    live.clear();
+
+    programmer
+        .branch("next")
+        .set_confirmed("/show/level", 0.9)
+        .await
+        .expect("branch set should succeed");
+
+    let watched = ValueCollector::new();
+    preview
+        .branch("next")
+        .subscribe("/show/**", watched.callback_ref())
+        .await
+        .expect("Branch subscribe should succeed");
+    assert!(
+        watched.wait_for_count(2, Duration::from_secs(2)).await,
+        "Branch snapshot should cover both params"
+    );
+    assert_eq!(watched.values_for("/show/level"), vec![Value::Float(0.9)]);
+    assert_eq!(
+        watched.values_for("/show/color"),
+        vec![Value::String("red".into())]
+    );
+
+    assert_eq!(
+        operator
+            .get("/show/level")
+            .await
+            .expect("get should succeed"),
+        Value::Float(0.5),
+        "GET reads live state"
+    );
+
+    // A live write the branch hasn't overridden shows through it; one it has
+    // doesn't
+    operator
+        .set_confirmed("/show/color", "blue")
+        .await
+        .expect("live set should succeed");
+    operator
+        .set_confirmed("/show/level", 0.1)
+        .await
+        .expect("live set should succeed");
+    assert!(watched.wait_for_count(3, Duration::from_secs(2)).await);
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        watched.values_for("/show/color"),
+        vec![Value::String("red".into()), Value::String("blue".into())]
+    );
+    assert_eq!(watched.values_for("/show/level"), vec![Value::Float(0.9)]);
+
+    assert!(
+        !live.values_for("/show/level").contains(&Value::Float(0.9)),
+        "Live subscribers must not see branch writes"
+    );
+}
+
+#[tokio::test]
+async fn test_merge_applies_branch_to_live_state() {
+    let router = TestRouter::start().await;
+
+    let programmer = router
+        .connect_client_named("Programmer")
+        .await
+        .expect("Programmer should connect");
+    let audience = router
+        .connect_client_named("Audience")
+        .await
+        .expect("Audience should connect");
+
+    let live = ValueCollector::new();
+    audience
+        .subscribe("/show/**", live.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    let next = programmer.branch("next");
+    next.set_confirmed("/show/a", 1)
+        .await
+        .expect("branch set should succeed");
+    next.set_confirmed("/show/b", 2)
+        .await
+        .expect("branch set should succeed");
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(live.count(), 0, "Nothing is live before the merge");
+
+    let ack = next.merge().await.expect("merge should succeed");
+    let merged: Vec<&str> = ack.results.iter().map(|r| r.address.as_str()).collect();
+    assert_eq!(merged, vec!["/show/a", "/show/b"]);
+    assert!(ack.results.iter().all(|r| r.revision.is_some()));
+
+    assert!(
+        live.wait_for_count(2, Duration::from_secs(2)).await,
+        "Live subscribers should receive the merged values"
+    );
+    assert_eq!(live.values_for("/show/a"), vec![Value::Int(1)]);
+    assert_eq!(live.values_for("/show/b"), vec![Value::Int(2)]);
+
+    let err = next.merge().await.expect_err("merged branch is empty");
+    assert_eq!(err.error_code(), Some(ErrorCode::TargetNotFound));
+}
+
+#[tokio::test]
+async fn test_merge_rolls_back_when_a_param_is_locked() {
+    let router = TestRouter::start().await;
+
+    let holder = router
+        .connect_client_named("Holder")
+        .await
+        .expect("Holder should connect");
+    let programmer = router
+        .connect_client_named("Programmer")
+        .await
+        .expect("Programmer should connect");
+
+    holder
+        .set_locked("/show/b", 0)
+        .await
+        .expect("lock should be sent");
+    sleep(Duration::from_millis(100)).await;
+
+    let next = programmer.branch("next");
+    next.set_confirmed("/show/a", 1)
+        .await
+        .expect("branch set should succeed");
+    next.set_confirmed("/show/b", 2)
+        .await
+        .expect("branch set should succeed");
+
+    next.merge()
+        .await
+        .expect_err("merge over a lock should fail");
+    assert_eq!(
+        programmer.get("/show/a").await.unwrap_or(Value::Null),
+        Value::Null,
+        "No param of a failed merge is applied"
+    );
+
+    // The branch keeps its changes for another try
+    next.discard().await.expect("discard should succeed");
+}
+
+#[tokio::test]
+async fn test_discard_restores_live_values_to_watchers() {
+    let router = TestRouter::start().await;
+
+    let programmer = router
+        .connect_client_named("Programmer")
+        .await
+        .expect("Programmer should connect");
+    let preview = router
+        .connect_client_named("Preview")
+        .await
+        .expect("Preview should connect");
+
+    programmer
+        .set_confirmed("/show/level", 0.5)
+        .await
+        .expect("live set should succeed");
+
+    let next = programmer.branch("next");
+    next.set_confirmed("/show/level", 0.9)
+        .await
+        .expect("branch set should succeed");
+
+    let watched = ValueCollector::new();
+    preview
+        .branch("next")
+        .subscribe("/show/**", watched.callback_ref())
+        .await
+        .expect("Branch subscribe should succeed");
+    assert!(watched.wait_for_count(1, Duration::from_secs(2)).await);
+
+    next.discard().await.expect("discard should succeed");
+    assert!(
+        watched.wait_for_count(2, Duration::from_secs(2)).await,
+        "Watchers should get the live value back"
+    );
+    assert_eq!(
+        watched.values_for("/show/level"),
+        vec![Value::Float(0.9), Value::Float(0.5)]
+    );
+    assert_eq!(
+        programmer
+            .get("/show/level")
+            .await
+            .expect("get should succeed"),
+        Value::Float(0.5)
+    );
+}
+
+#[tokio::test]
+async fn test_branch_sets_cannot_be_bundled() {
+    let router = TestRouter::start().await;
+
+    let client = router
+        .connect_client_named("Programmer")
+        .await
+        .expect("Client should connect");
+
+    let bundle = vec![clasp_core::Message::Set(clasp_core::SetMessage {
+        address: "/show/a".to_string(),
+        value: Value::Int(1),
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: Some("next".to_string()),
+    })];
+    let err = client
+        .bundle_confirmed(bundle)
+        .await
+        .expect_err("bundled branch SET should be rejected");
+    assert_eq!(err.error_code(), Some(ErrorCode::InvalidRequest));
+}
+
+/// Test: A merge lands in the journal as ordinary SETs with consecutive
+/// sequence numbers
+#[cfg(feature = "journal")]
+#[tokio::test]
+async fn test_merge_journaled_as_consecutive_sets() {
+    use clasp_client::Clasp;
+    use clasp_journal::{Journal, MemoryJournal};
+    use clasp_router::{Router, RouterConfig};
+    use std::sync::Arc;
+
+    let journal = Arc::new(MemoryJournal::new(1000));
+    let router = Router::new(RouterConfig::default()).with_journal(journal.clone());
+    let addr = format!(
+        "127.0.0.1:{}",
+        clasp_test_utils::find_available_port().await
+    );
+    let serve_addr = addr.clone();
+    tokio::spawn(async move {
+        let _ = router.serve_websocket(&serve_addr).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    let client = Clasp::connect_to(&format!("ws://{}", addr))
+        .await
+        .expect("Connect failed");
+    let next = client.branch("next");
+    for (i, address) in ["/show/a", "/show/b", "/show/c"].iter().enumerate() {
+        next.set_confirmed(address, i as i64)
+            .await
+            .expect("branch set should succeed");
+    }
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        journal.len().await.unwrap(),
+        0,
+        "Branch writes aren't journaled"
+    );
+
+    next.merge().await.expect("merge should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    let entries = journal
+        .query("/show/**", None, None, None, &[])
+        .await
+        .expect("query should succeed");
+    let addresses: Vec<&str> = entries.iter().map(|e| e.address.as_str()).collect();
+    assert_eq!(addresses, vec!["/show/a", "/show/b", "/show/c"]);
+    assert!(entries.windows(2).all(|w| w[1].seq == w[0].seq + 1));
+}
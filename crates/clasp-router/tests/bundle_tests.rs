@@ -6,9 +6,10 @@
 //! - Mixed message types in bundle
 //! - Large bundles (many messages)
 //! - Timestamp precision
+//! - Rollback when a SET is refused, and per-message ACK results
 
 use clasp_client::ClaspBuilder;
-use clasp_core::{Message, PublishMessage, SetMessage, SignalType, Value};
+use clasp_core::{ErrorCode, Message, PublishMessage, SetMessage, SignalType, Value};
 use clasp_test_utils::{TestRouter, ValueCollector};
 use std::time::Duration;
 use tokio::time::sleep;
@@ -47,6 +48,8 @@ async fn test_bundle_atomic_execution() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             })
         })
         .collect();
@@ -110,6 +113,8 @@ async fn test_bundle_scheduled_execution() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     })];
 
     // Send scheduled bundle
@@ -174,6 +179,8 @@ async fn test_bundle_mixed_message_types() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }),
         Message::Publish(PublishMessage {
             address: "/mixed/event".to_string(),
@@ -267,6 +274,8 @@ async fn test_bundle_large_bundle() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             })
         })
         .collect();
@@ -339,6 +348,8 @@ async fn test_bundle_timestamp_precision() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     })];
 
     // Send scheduled bundle
@@ -358,3 +369,81 @@ async fn test_bundle_timestamp_precision() {
     let values = collector.values();
     assert_eq!(values.len(), 1, "Should receive 1 value");
 }
+
+#[tokio::test]
+async fn test_bundle_rolls_back_on_refused_set() {
+    let router = TestRouter::start().await;
+
+    let sender = ClaspBuilder::new(&router.url())
+        .name("Sender")
+        .connect()
+        .await
+        .expect("Sender should connect");
+
+    sender
+        .set_confirmed("/tx/a", Value::Int(1))
+        .await
+        .expect("Initial SET should apply");
+
+    let set = |address: &str, value: i64, revision: Option<u64>| {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(value),
+            revision,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    };
+
+    // The stale revision on /tx/a must undo the SET to /tx/b before it
+    let error = sender
+        .bundle_confirmed(vec![set("/tx/b", 2, None), set("/tx/a", 2, Some(99))])
+        .await
+        .expect_err("Bundle with a stale revision should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::RevisionConflict));
+
+    let reader = ClaspBuilder::new(&router.url())
+        .name("Reader")
+        .connect()
+        .await
+        .expect("Reader should connect");
+    assert!(
+        reader
+            .get("/tx/b")
+            .await
+            .map(|v| v == Value::Null)
+            .unwrap_or(true),
+        "Rolled back SET must not be stored"
+    );
+    assert_eq!(reader.get("/tx/a").await.unwrap(), Value::Int(1));
+
+    let event = Message::Publish(PublishMessage {
+        address: "/tx/event".to_string(),
+        signal: Some(SignalType::Event),
+        value: Some(Value::Bool(true)),
+        payload: None,
+        samples: None,
+        rate: None,
+        id: None,
+        phase: None,
+        timestamp: None,
+        timeline: None,
+    });
+    let ack = sender
+        .bundle_confirmed(vec![set("/tx/a", 3, Some(1)), event, set("/tx/b", 3, None)])
+        .await
+        .expect("Bundle should apply");
+    let results: Vec<_> = ack
+        .results
+        .iter()
+        .map(|r| (r.address.as_str(), r.revision))
+        .collect();
+    assert_eq!(
+        results,
+        vec![("/tx/a", Some(2)), ("/tx/event", None), ("/tx/b", Some(1))]
+    );
+    assert_eq!(ack.revision, Some(1));
+}
@@ -0,0 +1,105 @@
+//! Address Canonicalization Tests
+//!
+//! Tests for:
+//! - SETs to non-canonical spellings landing on one param
+//! - Invalid addresses refused with InvalidAddress
+//! - Migrating state written before canonicalization
+
+use clasp_core::{ErrorCode, Value};
+use clasp_router::{AddressPolicy, RouterConfig, RouterState};
+use clasp_test_utils::TestRouter;
+
+#[tokio::test]
+async fn test_spellings_share_one_param() {
+    let router = TestRouter::start().await;
+    let client = router
+        .connect_client_named("Console")
+        .await
+        .expect("Console should connect");
+
+    client
+        .set_confirmed("/lights//1/", Value::Int(1))
+        .await
+        .expect("SET should apply");
+    client
+        .set_confirmed("/cafe\u{301}/menu", Value::Int(2))
+        .await
+        .expect("SET should apply");
+
+    assert_eq!(client.get("/lights/1").await.ok(), Some(Value::Int(1)));
+    assert_eq!(
+        client.get("/caf\u{e9}/menu").await.ok(),
+        Some(Value::Int(2))
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_addresses_refused() {
+    let router = TestRouter::start_with_config(RouterConfig {
+        address_policy: AddressPolicy {
+            max_depth: 4,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let client = router
+        .connect_client_named("Console")
+        .await
+        .expect("Console should connect");
+
+    for address in ["/lights/../admin", "/a/b/c/d/e"] {
+        let error = client
+            .set_confirmed(address, Value::Int(1))
+            .await
+            .expect_err("SET to an invalid address should be refused");
+        assert_eq!(
+            error.error_code(),
+            Some(ErrorCode::InvalidAddress),
+            "{}",
+            address
+        );
+    }
+    client
+        .set_confirmed("/a/b/c/d", Value::Int(1))
+        .await
+        .expect("SET within the depth limit should apply");
+}
+
+#[test]
+fn test_migrate_existing_state() {
+    let state = RouterState::new();
+    let writer = "test".to_string();
+    for (address, value) in [
+        ("/lights/1/", 1),
+        ("/lights//1", 2),
+        ("/lights/2/", 3),
+        ("/ok", 4),
+        ("/x/../y", 5),
+    ] {
+        state
+            .set(
+                address,
+                Value::Int(value),
+                &writer,
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+        // Distinct timestamps, so the latest write wins deterministically
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    let report = state.canonicalize(&AddressPolicy::default());
+    assert_eq!(state.get("/lights/1"), Some(Value::Int(2)));
+    assert_eq!(state.get("/lights/2"), Some(Value::Int(3)));
+    assert_eq!(state.get("/ok"), Some(Value::Int(4)));
+    assert_eq!(state.len(), 3);
+    assert_eq!(report.merged, vec!["/lights/1/".to_string()]);
+    assert_eq!(report.invalid, vec!["/x/../y".to_string()]);
+    assert_eq!(report.renamed.len(), 2);
+
+    assert!(state.canonicalize(&AddressPolicy::default()).is_empty());
+}
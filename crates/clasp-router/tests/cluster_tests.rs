@@ -0,0 +1,150 @@
+//! Cluster Tests
+//!
+//! Tests for:
+//! - SETs and PUBLISHes reaching subscribers on other nodes, once each
+//! - Params written on one node readable from another
+//! - Params with a TTL staying on their node
+//! - A node that starts late syncing the params written before it joined
+
+use clasp_client::Clasp;
+use clasp_core::{Ttl, Value};
+use clasp_router::cluster::MemoryBackplane;
+use clasp_router::{Backplane, Router, RouterConfig};
+use clasp_test_utils::{find_available_port, ValueCollector};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Serve a clustered router and return its URL
+async fn start_node(backplane: Arc<dyn Backplane>) -> String {
+    let router = Router::new(RouterConfig::default()).with_cluster(backplane);
+    let addr = format!("127.0.0.1:{}", find_available_port().await);
+    let serve_addr = addr.clone();
+    tokio::spawn(async move {
+        let _ = router.serve_websocket(&serve_addr).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("ws://{}", addr)
+}
+
+async fn connect(url: &str) -> Clasp {
+    Clasp::connect_to(url).await.expect("Client should connect")
+}
+
+#[tokio::test]
+async fn test_cluster_shares_sets_and_publishes() {
+    let backplane = Arc::new(MemoryBackplane::new());
+    let node_a = start_node(backplane.clone()).await;
+    let node_b = start_node(backplane).await;
+
+    let listener = connect(&node_a).await;
+    let params = ValueCollector::new();
+    listener
+        .subscribe("/mixer/**", params.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    let events = ValueCollector::new();
+    listener
+        .subscribe("/cue/**", events.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    let writer = connect(&node_b).await;
+    writer
+        .set_confirmed("/mixer/1/level", 0.75)
+        .await
+        .expect("SET should succeed");
+    writer
+        .emit("/cue/go", Value::Int(3))
+        .await
+        .expect("PUBLISH should succeed");
+
+    assert!(params.wait_for_count(1, Duration::from_secs(2)).await);
+    assert!(events.wait_for_count(1, Duration::from_secs(2)).await);
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(params.count(), 1, "Delivered once, not echoed back");
+    assert_eq!(events.values_for("/cue/go"), vec![Value::Int(3)]);
+
+    // A client that never subscribed reads it from the other node
+    let reader = connect(&node_a).await;
+    assert_eq!(
+        reader
+            .get("/mixer/1/level")
+            .await
+            .expect("GET should succeed"),
+        Value::Float(0.75)
+    );
+
+    // Writes from the listener's node flow back the other way
+    let watcher = ValueCollector::new();
+    writer
+        .subscribe("/mixer/2/level", watcher.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+    reader
+        .set_confirmed("/mixer/2/level", 0.25)
+        .await
+        .expect("SET should succeed");
+    assert!(watcher.wait_for_count(1, Duration::from_secs(2)).await);
+}
+
+#[tokio::test]
+async fn test_cluster_keeps_ttl_params_local() {
+    let backplane = Arc::new(MemoryBackplane::new());
+    let node_a = start_node(backplane.clone()).await;
+    let node_b = start_node(backplane).await;
+
+    let listener = connect(&node_a).await;
+    let collector = ValueCollector::new();
+    listener
+        .subscribe("/room/**", collector.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    let writer = connect(&node_b).await;
+    writer
+        .set_with_ttl("/room/occupied", true, Ttl::Absolute(60))
+        .await
+        .expect("SET should succeed");
+    writer
+        .set_confirmed("/room/lights", 1.0)
+        .await
+        .expect("SET should succeed");
+
+    assert!(collector.wait_for_count(1, Duration::from_secs(2)).await);
+    sleep(Duration::from_millis(200)).await;
+    assert!(!collector.has_address("/room/occupied"));
+    assert!(collector.has_address("/room/lights"));
+}
+
+#[tokio::test]
+async fn test_cluster_late_node_syncs_state() {
+    let backplane = Arc::new(MemoryBackplane::new());
+    let node_a = start_node(backplane.clone()).await;
+
+    let writer = connect(&node_a).await;
+    for i in 0..3 {
+        writer
+            .set_confirmed(&format!("/scene/{}", i), Value::Int(i))
+            .await
+            .expect("SET should succeed");
+    }
+
+    let node_b = start_node(backplane).await;
+    sleep(Duration::from_millis(200)).await;
+
+    let reader = connect(&node_b).await;
+    for i in 0..3 {
+        assert_eq!(
+            reader
+                .get(&format!("/scene/{}", i))
+                .await
+                .expect("GET should succeed"),
+            Value::Int(i),
+            "Params written before the node joined"
+        );
+    }
+}
@@ -48,6 +48,7 @@ async fn federation_handshake<S: TransportSender, R: TransportReceiver>(
         features: vec!["param".to_string(), "federation".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -83,6 +84,7 @@ async fn normal_handshake<S: TransportSender, R: TransportReceiver>(
         features: vec!["param".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -136,6 +138,7 @@ async fn declare_and_ack<S: TransportSender, R: TransportReceiver>(
         revisions: HashMap::new(),
         since_revision: None,
         origin: Some("peer".to_string()),
+        payload: None,
     });
     sender.send(codec::encode(&declare).unwrap()).await.unwrap();
 
@@ -168,6 +171,7 @@ async fn test_non_federation_session_rejected() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: Some("rogue-peer".to_string()),
+        payload: None,
     });
     sender.send(codec::encode(&fed_msg).unwrap()).await.unwrap();
 
@@ -203,6 +207,7 @@ async fn test_declare_namespaces_too_many_patterns() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: Some("peer".to_string()),
+        payload: None,
     });
     sender.send(codec::encode(&fed_msg).unwrap()).await.unwrap();
 
@@ -210,10 +215,10 @@ async fn test_declare_namespaces_too_many_patterns() {
     assert!(response.is_some(), "should receive error response");
     match response.unwrap() {
         Message::Error(err) => {
-            assert_eq!(err.code, 400);
+            assert_eq!(err.code, clasp_core::ErrorCode::InvalidRequest as u16);
             assert!(err.message.contains("too many"));
         }
-        other => panic!("expected Error(400), got: {:?}", other),
+        other => panic!("expected Error(InvalidRequest), got: {:?}", other),
     }
 
     handle.abort();
@@ -254,6 +259,7 @@ async fn test_request_sync_outside_declared_namespaces() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: None,
+        payload: None,
     });
     sender.send(codec::encode(&sync).unwrap()).await.unwrap();
 
@@ -289,6 +295,7 @@ async fn test_request_sync_within_declared_namespaces() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: None,
+        payload: None,
     });
     sender.send(codec::encode(&sync).unwrap()).await.unwrap();
 
@@ -336,6 +343,8 @@ async fn test_revision_vector_filters_out_of_scope_addresses() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set).unwrap()).await.unwrap();
     // Wait for ACK to ensure state is written
@@ -357,6 +366,7 @@ async fn test_revision_vector_filters_out_of_scope_addresses() {
         revisions,
         since_revision: None,
         origin: None,
+        payload: None,
     });
     fed.send(codec::encode(&rev_vec).unwrap()).await.unwrap();
 
@@ -411,6 +421,8 @@ async fn test_redeclare_namespaces_cleanup() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set).unwrap()).await.unwrap();
     let ack = recv_msg(&mut setter_rx).await;
@@ -466,6 +478,8 @@ async fn test_federation_peer_receives_declared_namespace_data() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -492,3 +506,353 @@ async fn test_federation_peer_receives_declared_namespace_data() {
 
     handle.abort();
 }
+
+// ==========================================================================
+// Test: Mesh gossip is relayed to other peers and routes traffic to the
+// peer that announced it
+// ==========================================================================
+
+/// Declare namespaces with an explicit origin and wait for the ACK
+async fn declare_with_origin<S: TransportSender, R: TransportReceiver>(
+    sender: &S,
+    receiver: &mut R,
+    origin: &str,
+    patterns: Vec<String>,
+) {
+    let declare = Message::FederationSync(FederationSyncMessage {
+        op: FederationOp::DeclareNamespaces,
+        patterns,
+        revisions: HashMap::new(),
+        since_revision: None,
+        origin: Some(origin.to_string()),
+        payload: None,
+    });
+    sender.send(codec::encode(&declare).unwrap()).await.unwrap();
+    let ack = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(Message::Ack(_)) = recv_msg(receiver).await {
+                return;
+            }
+        }
+    })
+    .await;
+    assert!(ack.is_ok(), "expected ACK for declaration from {}", origin);
+}
+
+/// Wait for a relayed declaration and return (origin, patterns)
+async fn recv_gossip<R: TransportReceiver>(receiver: &mut R) -> Option<(String, Vec<String>)> {
+    timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(Message::FederationSync(msg)) = recv_msg(receiver).await {
+                if msg.op == FederationOp::DeclareNamespaces {
+                    return (msg.origin.unwrap_or_default(), msg.patterns);
+                }
+            }
+        }
+    })
+    .await
+    .ok()
+}
+
+#[tokio::test]
+async fn test_mesh_gossip_relayed_and_routed() {
+    let (url, handle) = setup_router().await;
+
+    let (peer_a, mut peer_a_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    federation_handshake(&peer_a, &mut peer_a_rx, "Peer A").await;
+    declare_with_origin(&peer_a, &mut peer_a_rx, "site-a", vec!["/site-a/**".into()]).await;
+
+    let (peer_b, mut peer_b_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    federation_handshake(&peer_b, &mut peer_b_rx, "Peer B").await;
+    declare_with_origin(&peer_b, &mut peer_b_rx, "site-b", vec!["/site-b/**".into()]).await;
+
+    // A can reach site-c, one hop further
+    declare_with_origin(
+        &peer_a,
+        &mut peer_a_rx,
+        "site-c;hops=0;ttl=2",
+        vec!["/site-c/**".into()],
+    )
+    .await;
+
+    // B hears about it with one more hop and one less TTL
+    let (origin, patterns) = recv_gossip(&mut peer_b_rx)
+        .await
+        .expect("gossip relayed to B");
+    assert_eq!(origin, "site-c;hops=1;ttl=1");
+    assert_eq!(patterns, vec!["/site-c/**".to_string()]);
+
+    // Traffic for site-c is forwarded to A
+    let (setter, mut setter_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    normal_handshake(&setter, &mut setter_rx, "Setter").await;
+    let set = Message::Set(clasp_core::SetMessage {
+        address: "/site-c/level".to_string(),
+        value: clasp_core::Value::Float(0.5),
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    setter.send(codec::encode(&set).unwrap()).await.unwrap();
+    let received = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(Message::Set(s)) = recv_msg(&mut peer_a_rx).await {
+                if s.address == "/site-c/level" {
+                    return;
+                }
+            }
+        }
+    })
+    .await;
+    assert!(
+        received.is_ok(),
+        "route owner's next hop should receive data"
+    );
+
+    // When A goes away, B is told the route is gone
+    peer_a.close().await.unwrap();
+    let (origin, patterns) = recv_gossip(&mut peer_b_rx)
+        .await
+        .expect("withdrawal relayed to B");
+    assert_eq!(origin, "site-c;hops=1;ttl=1");
+    assert!(patterns.is_empty());
+
+    handle.abort();
+}
+
+// ==========================================================================
+// Test: RequestJournalSync streams journal entries from the peer's checkpoint
+// ==========================================================================
+
+/// Params streamed and the journal sequence number reached, up to SyncComplete
+#[cfg(feature = "journal")]
+async fn collect_journal_sync<R: TransportReceiver>(
+    receiver: &mut R,
+) -> (Vec<clasp_core::ParamValue>, Vec<u64>, Option<u64>) {
+    let mut params = Vec::new();
+    let mut checkpoints = Vec::new();
+    let complete = timeout(Duration::from_secs(3), async {
+        loop {
+            match recv_msg(receiver).await {
+                Some(Message::Snapshot(snapshot)) => params.extend(snapshot.params),
+                Some(Message::FederationSync(fed)) if fed.op == FederationOp::JournalCheckpoint => {
+                    checkpoints.extend(fed.since_revision)
+                }
+                Some(Message::FederationSync(fed)) if fed.op == FederationOp::SyncComplete => {
+                    return fed.since_revision;
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("should receive SyncComplete");
+    (params, checkpoints, complete)
+}
+
+#[cfg(feature = "journal")]
+#[tokio::test]
+async fn test_journal_sync_streams_from_checkpoint() {
+    let port = find_available_port().await;
+    let addr = format!("127.0.0.1:{}", port);
+    let router = Router::new(RouterConfig {
+        features: vec!["param".to_string(), "federation".to_string()],
+        ..Default::default()
+    })
+    .with_journal(std::sync::Arc::new(clasp_journal::MemoryJournal::new(1000)));
+    let handle = {
+        let addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&addr).await;
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let url = format!("ws://{}", addr);
+
+    let (setter, mut setter_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    normal_handshake(&setter, &mut setter_rx, "Setter").await;
+    let set = |address: &str, value: i64| {
+        codec::encode(&Message::Set(clasp_core::SetMessage {
+            address: address.to_string(),
+            value: clasp_core::Value::Int(value),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    };
+    for (address, value) in [("/sensors/a", 1), ("/sensors/b", 2), ("/audio/x", 3)] {
+        setter.send(set(address, value)).await.unwrap();
+        assert!(matches!(
+            recv_msg(&mut setter_rx).await,
+            Some(Message::Ack(_))
+        ));
+    }
+
+    let (fed, mut fed_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    federation_handshake(&fed, &mut fed_rx, "Fed Peer").await;
+    declare_and_ack(&fed, &mut fed_rx, vec!["/sensors/**".to_string()]).await;
+
+    let request = |since: Option<u64>| {
+        codec::encode(&Message::FederationSync(FederationSyncMessage {
+            op: FederationOp::RequestJournalSync,
+            patterns: vec!["/sensors/**".to_string()],
+            revisions: HashMap::new(),
+            since_revision: since,
+            origin: None,
+            payload: None,
+        }))
+        .unwrap()
+    };
+
+    // No checkpoint yet: the current state, then the cut
+    fed.send(request(None)).await.unwrap();
+    let (params, _, cut) = collect_journal_sync(&mut fed_rx).await;
+    let cut = cut.expect("journal sync should report its cut");
+    let mut addresses: Vec<&str> = params.iter().map(|p| p.address.as_str()).collect();
+    addresses.sort();
+    assert_eq!(addresses, vec!["/sensors/a", "/sensors/b"]);
+    assert_eq!(cut, 3);
+
+    // Writes after the checkpoint are all that is streamed on resync
+    for (address, value) in [("/sensors/a", 10), ("/audio/x", 30), ("/sensors/c", 5)] {
+        setter.send(set(address, value)).await.unwrap();
+        assert!(matches!(
+            recv_msg(&mut setter_rx).await,
+            Some(Message::Ack(_))
+        ));
+    }
+    fed.send(request(Some(cut))).await.unwrap();
+    let (params, checkpoints, reached) = collect_journal_sync(&mut fed_rx).await;
+    let streamed: Vec<(&str, &clasp_core::Value)> = params
+        .iter()
+        .map(|p| (p.address.as_str(), &p.value))
+        .collect();
+    assert_eq!(
+        streamed,
+        vec![
+            ("/sensors/a", &clasp_core::Value::Int(10)),
+            ("/sensors/c", &clasp_core::Value::Int(5)),
+        ]
+    );
+    assert_eq!(checkpoints, vec![6]);
+    assert_eq!(reached, Some(6));
+
+    handle.abort();
+}
+
+// ==========================================================================
+// Test: Registry events are exchanged with a federation peer
+// ==========================================================================
+
+/// Records applied events and serves a fixed snapshot
+struct RecordingReplica {
+    applied: std::sync::Mutex<Vec<Vec<u8>>>,
+}
+
+#[async_trait::async_trait]
+impl clasp_router::RegistryReplica for RecordingReplica {
+    async fn apply(&self, _from: &clasp_router::SessionId, payload: &[u8]) -> Result<(), String> {
+        if payload.is_empty() {
+            return Err("empty event".to_string());
+        }
+        self.applied.lock().unwrap().push(payload.to_vec());
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Vec<Vec<u8>> {
+        vec![vec![1], vec![2]]
+    }
+}
+
+fn registry_msg(op: FederationOp, payload: Option<Vec<u8>>) -> bytes::Bytes {
+    codec::encode(&Message::FederationSync(FederationSyncMessage {
+        op,
+        patterns: vec![],
+        revisions: HashMap::new(),
+        since_revision: None,
+        origin: Some("peer".to_string()),
+        payload,
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_registry_events_exchanged_with_peer() {
+    let port = find_available_port().await;
+    let addr = format!("127.0.0.1:{}", port);
+    let mut router = Router::new(RouterConfig {
+        features: vec!["param".to_string(), "federation".to_string()],
+        ..Default::default()
+    });
+    let replica = std::sync::Arc::new(RecordingReplica {
+        applied: std::sync::Mutex::new(Vec::new()),
+    });
+    router.set_registry_replica(replica.clone());
+    let handle = {
+        let addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&addr).await;
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let url = format!("ws://{}", addr);
+
+    let (fed, mut fed_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    federation_handshake(&fed, &mut fed_rx, "Fed Peer").await;
+
+    // The router sends its events, then asks for ours
+    fed.send(registry_msg(FederationOp::RequestRegistrySync, None))
+        .await
+        .unwrap();
+    let mut payloads = Vec::new();
+    loop {
+        match recv_msg(&mut fed_rx).await {
+            Some(Message::FederationSync(msg)) if msg.op == FederationOp::RegistryEvent => {
+                payloads.push(msg.payload.unwrap())
+            }
+            Some(Message::FederationSync(msg)) if msg.op == FederationOp::RequestRegistrySync => {
+                break
+            }
+            other => panic!("expected registry sync, got: {:?}", other),
+        }
+    }
+    assert_eq!(payloads, vec![vec![1], vec![2]]);
+
+    fed.send(registry_msg(FederationOp::RegistryEvent, Some(vec![7])))
+        .await
+        .unwrap();
+    // A refused event is reported to the peer
+    fed.send(registry_msg(FederationOp::RegistryEvent, Some(vec![])))
+        .await
+        .unwrap();
+    match recv_msg(&mut fed_rx).await {
+        Some(Message::Error(err)) => assert!(err.message.contains("empty event")),
+        other => panic!("expected Error, got: {:?}", other),
+    }
+    assert_eq!(*replica.applied.lock().unwrap(), vec![vec![7]]);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_registry_event_without_replica_rejected() {
+    let (url, handle) = setup_router().await;
+    let (fed, mut fed_rx) = WebSocketTransport::connect(&url).await.unwrap();
+    federation_handshake(&fed, &mut fed_rx, "Fed Peer").await;
+
+    fed.send(registry_msg(FederationOp::RegistryEvent, Some(vec![7])))
+        .await
+        .unwrap();
+    match recv_msg(&mut fed_rx).await {
+        Some(Message::Error(err)) => assert!(err.message.contains("registry")),
+        other => panic!("expected Error, got: {:?}", other),
+    }
+
+    handle.abort();
+}
@@ -6,6 +6,7 @@
 //! - Gesture cancellation
 //! - High-frequency move updates
 //! - Cross-client gesture routing
+//! - Recording to the journal and timed replay
 
 use clasp_client::Clasp;
 use clasp_core::{GesturePhase, Value};
@@ -291,3 +292,90 @@ async fn test_gesture_high_frequency() {
         count
     );
 }
+
+/// Test: Every move of a gesture is journaled and a timed REPLAY plays it
+/// back at the recorded pace
+#[cfg(feature = "journal")]
+#[tokio::test]
+async fn test_gesture_recorded_and_replayed_with_timing() {
+    use clasp_core::{ReplayMessage, SignalType};
+    use clasp_router::{Router, RouterConfig};
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    let router = Router::new(RouterConfig::default())
+        .with_journal(Arc::new(clasp_journal::MemoryJournal::new(1000)));
+    let addr = format!(
+        "127.0.0.1:{}",
+        clasp_test_utils::find_available_port().await
+    );
+    let serve_addr = addr.clone();
+    tokio::spawn(async move {
+        let _ = router.serve_websocket(&serve_addr).await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let url = format!("ws://{}", addr);
+
+    // Moves 30ms apart, faster than coalescing forwards them live
+    let performer = Clasp::connect_to(&url).await.expect("Connect failed");
+    let _ = performer
+        .gesture("/stage/touch", 1, GesturePhase::Start, Value::Float(0.0))
+        .await;
+    for i in 1..=5 {
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let _ = performer
+            .gesture(
+                "/stage/touch",
+                1,
+                GesturePhase::Move,
+                Value::Float(i as f64 / 10.0),
+            )
+            .await;
+    }
+    let _ = performer
+        .gesture("/stage/touch", 1, GesturePhase::End, Value::Float(1.0))
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let rehearsal = Clasp::connect_to(&url).await.expect("Connect failed");
+    let received: Arc<Mutex<Vec<(Instant, Value)>>> = Arc::default();
+    let sink = received.clone();
+    rehearsal
+        .subscribe("/stage/**", move |value, _address| {
+            sink.lock().unwrap().push((Instant::now(), value));
+        })
+        .await
+        .expect("Subscribe failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    rehearsal
+        .replay(ReplayMessage {
+            pattern: "/stage/**".to_string(),
+            from: None,
+            to: None,
+            limit: None,
+            types: vec![SignalType::Gesture],
+            timed: true,
+            cursor: None,
+        })
+        .await
+        .expect("Replay failed");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let received = received.lock().unwrap();
+    let values: Vec<Value> = received.iter().map(|(_, v)| v.clone()).collect();
+    let mut expected = vec![Value::Float(0.0)];
+    expected.extend((1..=5).map(|i| Value::Float(i as f64 / 10.0)));
+    expected.push(Value::Float(1.0));
+    assert_eq!(
+        values, expected,
+        "Every recorded frame should replay in order"
+    );
+
+    let span = received.last().unwrap().0 - received[0].0;
+    assert!(
+        span >= Duration::from_millis(120),
+        "Replay took {:?}, recorded gesture took ~150ms",
+        span
+    );
+}
@@ -0,0 +1,82 @@
+//! GET Tests
+//!
+//! Tests for:
+//! - Wildcard GET returning every matching param
+//! - Depth limits on wildcard GET
+//! - Correlated answers split over several SNAPSHOT chunks
+
+use clasp_core::Value;
+use clasp_router::RouterConfig;
+use clasp_test_utils::TestRouter;
+
+fn addresses(params: &[clasp_core::ParamValue]) -> Vec<&str> {
+    let mut addresses: Vec<_> = params.iter().map(|p| p.address.as_str()).collect();
+    addresses.sort();
+    addresses
+}
+
+#[tokio::test]
+async fn test_wildcard_get_returns_subtree() {
+    let router = TestRouter::start().await;
+    let client = router
+        .connect_client_named("Reader")
+        .await
+        .expect("Client should connect");
+
+    for (address, value) in [
+        ("/lights/a", 1.0),
+        ("/lights/a/level", 0.5),
+        ("/lights/b", 2.0),
+        ("/audio/gain", 3.0),
+    ] {
+        client
+            .set_confirmed(address, value)
+            .await
+            .expect("set should be applied");
+    }
+
+    let all = client.get_matching("/lights/**", None).await.unwrap();
+    assert_eq!(
+        addresses(&all),
+        vec!["/lights/a", "/lights/a/level", "/lights/b"]
+    );
+    let level = all.iter().find(|p| p.address == "/lights/a/level").unwrap();
+    assert_eq!(level.value, Value::Float(0.5));
+
+    let shallow = client.get_matching("/lights/**", Some(1)).await.unwrap();
+    assert_eq!(addresses(&shallow), vec!["/lights/a", "/lights/b"]);
+
+    let none = client.get_matching("/video/**", None).await.unwrap();
+    assert!(none.is_empty());
+
+    // A correlated GET of a single address is answered even when unknown
+    assert!(client
+        .get_matching("/lights/c", None)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_wildcard_get_joins_chunks() {
+    let router = TestRouter::start_with_config(RouterConfig {
+        rate_limiting_enabled: false,
+        ..Default::default()
+    })
+    .await;
+    let client = router
+        .connect_client_named("Bulk")
+        .await
+        .expect("Client should connect");
+
+    for i in 0..1000 {
+        client
+            .set(&format!("/bulk/{}", i), Value::Int(i))
+            .await
+            .unwrap();
+    }
+    client.set_confirmed("/bulk/last", 0i64).await.unwrap();
+
+    let params = client.get_matching("/bulk/*", None).await.unwrap();
+    assert_eq!(params.len(), 1001);
+}
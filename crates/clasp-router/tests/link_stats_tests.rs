@@ -0,0 +1,153 @@
+//! Link Stats Tests
+//!
+//! Tests for:
+//! - Per-subscription delivered counts reported at /clasp/stats
+//! - No counts unless the router enables delivery stats
+//! - Counts leaving with the subscription on UNSUBSCRIBE
+//! - Stats readable without read scope in authenticated mode
+
+use clasp_client::Clasp;
+use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+use clasp_core::{SecurityMode, SetManyMessage, Value};
+use clasp_router::{Router, RouterConfig};
+use clasp_test_utils::{TestRouter, ValueCollector};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::test]
+async fn test_link_stats_count_deliveries_per_subscription() {
+    let router = TestRouter::start_with_config(RouterConfig {
+        delivery_stats: true,
+        ..Default::default()
+    })
+    .await;
+    let sender = router
+        .connect_client()
+        .await
+        .expect("Sender should connect");
+    let receiver = router
+        .connect_client()
+        .await
+        .expect("Receiver should connect");
+
+    let collector = ValueCollector::new();
+    let mixer = receiver
+        .subscribe("/mixer/**", collector.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    let lights = receiver
+        .subscribe("/lights/**", |_, _| {})
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    for i in 0..3 {
+        sender
+            .set(&format!("/mixer/{}/level", i), Value::Float(0.5))
+            .await
+            .expect("SET should succeed");
+    }
+    sender
+        .set_many_confirmed(SetManyMessage::run(
+            "/mixer/bus",
+            1,
+            vec![Value::Int(1), Value::Int(2)],
+        ))
+        .await
+        .expect("SET_MANY should be applied");
+    assert!(collector.wait_for_count(5, Duration::from_secs(2)).await);
+
+    let stats = receiver
+        .link_stats()
+        .await
+        .expect("Stats should be answered");
+    let counts = stats.subscription(mixer).expect("mixer subscription");
+    assert_eq!(counts.pattern, "/mixer/**");
+    assert_eq!(counts.delivered, 5, "Each SET_MANY entry counts");
+    assert_eq!(counts.dropped, 0);
+    assert_eq!(stats.subscription(lights).map(|s| s.delivered), Some(0));
+    assert_eq!(stats.dropped, 0);
+    assert!(stats.queued.is_some(), "WebSocket reports its queue depth");
+
+    receiver
+        .unsubscribe_confirmed(lights)
+        .await
+        .expect("Unsubscribe should succeed");
+    let stats = receiver
+        .link_stats()
+        .await
+        .expect("Stats should be answered");
+    assert!(stats.subscription(lights).is_none());
+    assert_eq!(stats.subscriptions.len(), 1);
+}
+
+#[tokio::test]
+async fn test_link_stats_off_by_default() {
+    let router = TestRouter::start().await;
+    let sender = router
+        .connect_client()
+        .await
+        .expect("Sender should connect");
+    let receiver = router
+        .connect_client()
+        .await
+        .expect("Receiver should connect");
+
+    let collector = ValueCollector::new();
+    receiver
+        .subscribe("/mixer/**", collector.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+    sender
+        .set("/mixer/1/level", Value::Float(0.5))
+        .await
+        .expect("SET should succeed");
+    assert!(collector.wait_for_count(1, Duration::from_secs(2)).await);
+
+    let stats = receiver
+        .link_stats()
+        .await
+        .expect("Stats should be answered");
+    assert!(stats.subscriptions.is_empty());
+    assert_eq!(stats.dropped, 0);
+    assert!(stats.queued.is_some());
+}
+
+#[tokio::test]
+async fn test_link_stats_need_no_read_scope() {
+    const TOKEN: &str = "cpsk_stats_writer";
+    let validator = CpskValidator::new();
+    validator.register(
+        TOKEN.to_string(),
+        TokenInfo::new(
+            TOKEN.to_string(),
+            vec![Scope::parse("write:/sensors/**").unwrap()],
+        ),
+    );
+    let router = Router::new(RouterConfig {
+        security_mode: SecurityMode::Authenticated,
+        ..Default::default()
+    })
+    .with_validator(validator);
+    let addr = format!(
+        "127.0.0.1:{}",
+        clasp_test_utils::find_available_port().await
+    );
+    let serve_addr = addr.clone();
+    tokio::spawn(async move {
+        let _ = router.serve_websocket(&serve_addr).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    let client = Clasp::builder(&format!("ws://{}", addr))
+        .token(TOKEN)
+        .connect()
+        .await
+        .expect("Connect failed");
+    let stats = client
+        .link_stats()
+        .await
+        .expect("Stats should be answered without read scope");
+    assert!(stats.subscriptions.is_empty());
+}
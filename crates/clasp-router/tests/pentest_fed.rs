@@ -48,6 +48,7 @@ async fn federation_handshake<S: TransportSender, R: TransportReceiver>(
         features: vec!["param".to_string(), "federation".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -81,6 +82,7 @@ async fn normal_handshake<S: TransportSender, R: TransportReceiver>(
         features: vec!["param".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -133,6 +135,7 @@ async fn declare_and_ack<S: TransportSender, R: TransportReceiver>(
         revisions: HashMap::new(),
         since_revision: None,
         origin: Some("peer".to_string()),
+        payload: None,
     });
     sender.send(codec::encode(&declare).unwrap()).await.unwrap();
 
@@ -178,6 +181,8 @@ async fn test_fed_01_namespace_hijacking() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set).unwrap()).await.unwrap();
     let _ = recv_msg(&mut setter_rx).await; // ACK
@@ -234,7 +239,7 @@ async fn test_fed_01_namespace_hijacking() {
 }
 
 // ── FED-02: Pattern count exhaustion ───────────────────────────────────
-/// 1001 patterns -> Error(400, "too many")
+/// 1001 patterns -> Error(InvalidRequest, "too many")
 #[tokio::test]
 async fn test_fed_02_pattern_count_exhaustion() {
     let (url, handle) = setup_router().await;
@@ -249,6 +254,7 @@ async fn test_fed_02_pattern_count_exhaustion() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: Some("peer".to_string()),
+        payload: None,
     });
     fed.send(codec::encode(&msg).unwrap()).await.unwrap();
 
@@ -256,17 +262,17 @@ async fn test_fed_02_pattern_count_exhaustion() {
     assert!(response.is_some());
     match response.unwrap() {
         Message::Error(err) => {
-            assert_eq!(err.code, 400);
+            assert_eq!(err.code, clasp_core::ErrorCode::InvalidRequest as u16);
             assert!(err.message.contains("too many"));
         }
-        other => panic!("expected Error(400), got: {:?}", other),
+        other => panic!("expected Error(InvalidRequest), got: {:?}", other),
     }
 
     handle.abort();
 }
 
 // ── FED-03: Revision vector exhaustion ─────────────────────────────────
-/// RevisionVector with 10,001 entries -> Error(400)
+/// RevisionVector with 10,001 entries -> Error(InvalidRequest)
 #[tokio::test]
 async fn test_fed_03_revision_vector_exhaustion() {
     let (url, handle) = setup_router().await;
@@ -287,10 +293,11 @@ async fn test_fed_03_revision_vector_exhaustion() {
         revisions,
         since_revision: None,
         origin: None,
+        payload: None,
     });
 
     // The codec may reject the oversized payload at encode time (PayloadTooLarge),
-    // which is itself a valid defense. Otherwise, the router should return Error(400).
+    // which is itself a valid defense. Otherwise, the router should return Error(InvalidRequest).
     match codec::encode(&msg) {
         Err(_) => {
             // PayloadTooLarge at codec level — oversized revision vector is
@@ -302,9 +309,9 @@ async fn test_fed_03_revision_vector_exhaustion() {
             assert!(response.is_some());
             match response.unwrap() {
                 Message::Error(err) => {
-                    assert_eq!(err.code, 400);
+                    assert_eq!(err.code, clasp_core::ErrorCode::InvalidRequest as u16);
                 }
-                other => panic!("expected Error(400), got: {:?}", other),
+                other => panic!("expected Error(InvalidRequest), got: {:?}", other),
             }
         }
     }
@@ -333,6 +340,8 @@ async fn test_fed_04_loop_injection() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     peer_a.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -390,6 +399,7 @@ async fn test_fed_05_unauthorized_sync_request() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: None,
+        payload: None,
     });
     fed.send(codec::encode(&sync).unwrap()).await.unwrap();
 
@@ -420,6 +430,7 @@ async fn test_fed_06_non_federation_sync_attempt() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: Some("rogue".to_string()),
+        payload: None,
     });
     client.send(codec::encode(&fed_msg).unwrap()).await.unwrap();
 
@@ -454,6 +465,7 @@ async fn test_fed_07_pattern_coverage_bypass() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: None,
+        payload: None,
     });
     fed.send(codec::encode(&sync).unwrap()).await.unwrap();
 
@@ -476,6 +488,7 @@ async fn test_fed_07_pattern_coverage_bypass() {
         revisions: HashMap::new(),
         since_revision: None,
         origin: None,
+        payload: None,
     });
     fed.send(codec::encode(&sync).unwrap()).await.unwrap();
 
@@ -515,6 +528,8 @@ async fn test_fed_08_subscription_accumulation() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set1).unwrap()).await.unwrap();
     let _ = recv_msg(&mut setter_rx).await; // ACK
@@ -548,6 +563,8 @@ async fn test_fed_08_subscription_accumulation() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set2).unwrap()).await.unwrap();
     let _ = recv_msg(&mut setter_rx).await; // ACK
@@ -579,6 +596,8 @@ async fn test_fed_08_subscription_accumulation() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     setter.send(codec::encode(&set3).unwrap()).await.unwrap();
     let _ = recv_msg(&mut setter_rx).await; // ACK
@@ -645,6 +664,7 @@ async fn test_fed_09_unauthenticated_federation() {
         features: vec!["param".to_string(), "federation".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     fed.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -669,6 +689,7 @@ async fn test_fed_09_unauthenticated_federation() {
                                     revisions: HashMap::new(),
                                     since_revision: None,
                                     origin: Some("unauth-peer".to_string()),
+                                    payload: None,
                                 });
                                 fed.send(codec::encode(&declare).unwrap()).await.unwrap();
                             }
@@ -770,6 +791,8 @@ async fn test_fed_10_state_poisoning() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     fed.send(codec::encode(&poison_set).unwrap()).await.unwrap();
 
@@ -802,6 +825,8 @@ async fn test_fed_10_state_poisoning() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     legit
         .send(codec::encode(&legit_set).unwrap())
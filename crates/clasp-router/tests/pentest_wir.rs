@@ -46,6 +46,7 @@ async fn normal_handshake<S: TransportSender, R: TransportReceiver>(
         features: vec!["param".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -144,6 +145,8 @@ async fn test_wir_01_malformed_messages() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         hs_atk.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -162,6 +165,8 @@ async fn test_wir_01_malformed_messages() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     good.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -191,6 +196,7 @@ async fn test_wir_02_type_confusion() {
         features: vec!["param".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     client.send(codec::encode(&hello2).unwrap()).await.unwrap();
 
@@ -236,6 +242,9 @@ async fn test_wir_02_type_confusion() {
         features: vec![],
         time: 0,
         token: None,
+        challenge: None,
+        resume_token: None,
+        resumed: false,
     });
     client.send(codec::encode(&welcome).unwrap()).await.unwrap();
 
@@ -265,6 +274,8 @@ async fn test_wir_02_type_confusion() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     client.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -300,6 +311,8 @@ async fn test_wir_03_oversized_payloads() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let result = codec::encode(&set);
@@ -326,6 +339,8 @@ async fn test_wir_03_oversized_payloads() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let result = codec::encode(&set);
@@ -346,6 +361,7 @@ async fn test_wir_03_oversized_payloads() {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
 
         match codec::encode(&hello) {
@@ -379,6 +395,8 @@ async fn test_wir_03_oversized_payloads() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         client.send(codec::encode(&set).unwrap()).await.unwrap();
         let response = recv_msg(&mut rx).await;
@@ -409,6 +427,7 @@ async fn test_wir_04_protocol_version_mismatch() {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         client.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -470,6 +489,8 @@ async fn test_wir_04_protocol_version_mismatch() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         client.send(codec::encode(&set).unwrap()).await.unwrap();
         let response = recv_msg(&mut rx).await;
@@ -511,6 +532,8 @@ async fn test_wir_05_replay_flooding() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         client.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -544,6 +567,8 @@ async fn test_wir_05_replay_flooding() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     client.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -0,0 +1,105 @@
+//! Presence Tests
+//!
+//! Tests for:
+//! - Presence entries published when sessions join
+//! - Entries cleared when sessions leave
+//! - Rejection of client writes under the presence prefix
+
+use clasp_core::{codec, HelloMessage, Message, Value};
+use clasp_router::RouterConfig;
+use clasp_test_utils::{wait_for, TestRouter, ValueCollector};
+use clasp_transport::{Transport, TransportSender, WebSocketTransport};
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn presence_router_config() -> RouterConfig {
+    RouterConfig {
+        presence: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_presence_follows_sessions() {
+    let router = TestRouter::start_with_config(presence_router_config()).await;
+
+    let watcher = router
+        .connect_client_named("Watcher")
+        .await
+        .expect("Watcher should connect");
+    let collector = ValueCollector::new();
+    watcher
+        .subscribe("/clasp/presence/*", collector.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+
+    // Raw connection, so closing it drops the session right away
+    let (guest, _guest_rx) = WebSocketTransport::connect(&router.url()).await.unwrap();
+    let hello = Message::Hello(HelloMessage {
+        version: 2,
+        name: "Guest".to_string(),
+        features: vec!["param".to_string()],
+        capabilities: None,
+        token: None,
+        session_resume: None,
+    });
+    guest.send(codec::encode(&hello).unwrap()).await.unwrap();
+
+    let guest_entry = || {
+        collector.values().into_iter().find(|(_, value)| {
+            matches!(value, Value::Map(entry)
+                if entry.get("name") == Some(&Value::String("Guest".to_string())))
+        })
+    };
+    assert!(
+        wait_for(
+            || async { guest_entry().is_some() },
+            Duration::from_millis(20),
+            Duration::from_secs(2),
+        )
+        .await,
+        "Watcher should see the guest join"
+    );
+    let (guest_address, _) = guest_entry().unwrap();
+    let Some(Value::Map(entry)) = collector.values_for(&guest_address).pop() else {
+        panic!("guest presence entry missing");
+    };
+    assert_eq!(entry.get("name"), Some(&Value::String("Guest".to_string())));
+    assert_eq!(
+        entry.get("transport"),
+        Some(&Value::String("websocket".to_string()))
+    );
+    assert!(matches!(entry.get("connected_at"), Some(Value::Int(t)) if *t > 0));
+
+    guest.close().await.unwrap();
+    assert!(
+        wait_for(
+            || async { collector.values_for(&guest_address).pop() == Some(Value::Null) },
+            Duration::from_millis(20),
+            Duration::from_secs(2),
+        )
+        .await,
+        "Watcher should see the guest leave"
+    );
+}
+
+#[tokio::test]
+async fn test_client_cannot_write_presence() {
+    let router = TestRouter::start_with_config(presence_router_config()).await;
+
+    let client = router
+        .connect_client_named("Spoofer")
+        .await
+        .expect("Client should connect");
+
+    client
+        .set("/clasp/presence/someone-else", Value::Bool(true))
+        .await
+        .expect("set should be sent");
+    sleep(Duration::from_millis(200)).await;
+
+    let error = client
+        .last_error()
+        .expect("SET under the presence prefix should be rejected");
+    assert_eq!(error.code, 403);
+}
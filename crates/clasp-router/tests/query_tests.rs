@@ -0,0 +1,222 @@
+//! Query Tests
+//!
+//! Tests for signal registry introspection with QUERY / RESULT:
+//! - Unpaged queries
+//! - Paging through a registry with limit and cursor
+//! - Field selection
+//! - Client queries resolving with their own RESULT
+
+use clasp_core::{
+    codec, AnnounceMessage, HelloMessage, Message, QueryMessage, ResultMessage, SignalDefinition,
+    SignalField, SignalMeta, SignalType,
+};
+use clasp_test_utils::TestRouter;
+use clasp_transport::{
+    websocket::{WebSocketReceiver, WebSocketSender},
+    Transport, TransportEvent, TransportReceiver, TransportSender, WebSocketTransport,
+};
+use std::time::Duration;
+use tokio::time::timeout;
+
+// ============================================================================
+// Utilities
+// ============================================================================
+
+async fn connect_and_handshake(url: &str) -> (WebSocketSender, WebSocketReceiver) {
+    let (sender, mut receiver) = WebSocketTransport::connect(url).await.unwrap();
+
+    let hello = Message::Hello(HelloMessage {
+        version: 2,
+        name: "Browser".to_string(),
+        features: vec!["param".to_string()],
+        capabilities: None,
+        token: None,
+        session_resume: None,
+    });
+    sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+
+    let mut got_welcome = false;
+    let mut got_snapshot = false;
+    while !got_welcome || !got_snapshot {
+        match timeout(Duration::from_secs(2), receiver.recv()).await {
+            Ok(Some(TransportEvent::Data(data))) => match codec::decode(&data).unwrap().0 {
+                Message::Welcome(_) => got_welcome = true,
+                Message::Snapshot(_) => got_snapshot = true,
+                _ => {}
+            },
+            Ok(Some(TransportEvent::Connected)) => continue,
+            _ => panic!("Handshake failed"),
+        }
+    }
+
+    (sender, receiver)
+}
+
+async fn announce(sender: &WebSocketSender, count: usize) {
+    let signals = (0..count)
+        .map(|i| SignalDefinition {
+            address: format!("/mixer/ch/{:03}", i),
+            signal_type: SignalType::Param,
+            datatype: Some("f".to_string()),
+            access: Some("rw".to_string()),
+            meta: Some(SignalMeta {
+                unit: Some("dB".to_string()),
+                range: None,
+                default: None,
+                description: None,
+                enum_values: None,
+            }),
+        })
+        .collect();
+    let announce = Message::Announce(AnnounceMessage {
+        namespace: "/mixer".to_string(),
+        signals,
+        meta: None,
+    });
+    sender
+        .send(codec::encode(&announce).unwrap())
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+async fn query(
+    sender: &WebSocketSender,
+    receiver: &mut WebSocketReceiver,
+    query: QueryMessage,
+) -> ResultMessage {
+    sender
+        .send(codec::encode(&Message::Query(query)).unwrap())
+        .await
+        .unwrap();
+    loop {
+        match timeout(Duration::from_secs(2), receiver.recv()).await {
+            Ok(Some(TransportEvent::Data(data))) => {
+                if let Message::Result(result) = codec::decode(&data).unwrap().0 {
+                    return result;
+                }
+            }
+            other => panic!("No RESULT: {:?}", other),
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_unpaged_query_returns_everything() {
+    let router = TestRouter::start().await;
+    let (sender, mut receiver) = connect_and_handshake(&router.url()).await;
+    announce(&sender, 30).await;
+
+    let result = query(
+        &sender,
+        &mut receiver,
+        QueryMessage {
+            pattern: "/mixer/**".to_string(),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    assert_eq!(result.signals.len(), 30);
+    assert!(result.next_cursor.is_none());
+    assert!(result.total.is_none());
+    // Meta is only sent when asked for
+    assert!(result.signals.iter().all(|s| s.meta.is_none()));
+    assert!(result.signals.iter().all(|s| s.access.is_some()));
+}
+
+#[tokio::test]
+async fn test_query_pages_through_registry() {
+    let router = TestRouter::start().await;
+    let (sender, mut receiver) = connect_and_handshake(&router.url()).await;
+    announce(&sender, 30).await;
+
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let result = query(
+            &sender,
+            &mut receiver,
+            QueryMessage {
+                pattern: "/mixer/**".to_string(),
+                limit: Some(12),
+                cursor: cursor.take(),
+                fields: None,
+            },
+        )
+        .await;
+        pages += 1;
+        assert_eq!(result.total, Some(30));
+        assert!(result.signals.len() <= 12);
+        seen.extend(result.signals.into_iter().map(|s| s.address));
+        match result.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 3);
+    let expected: Vec<String> = (0..30).map(|i| format!("/mixer/ch/{:03}", i)).collect();
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+async fn test_query_selects_fields() {
+    let router = TestRouter::start().await;
+    let (sender, mut receiver) = connect_and_handshake(&router.url()).await;
+    announce(&sender, 3).await;
+
+    let result = query(
+        &sender,
+        &mut receiver,
+        QueryMessage {
+            pattern: "/mixer/**".to_string(),
+            limit: Some(10),
+            cursor: None,
+            fields: Some(vec![SignalField::Meta]),
+        },
+    )
+    .await;
+
+    assert_eq!(result.signals.len(), 3);
+    for signal in &result.signals {
+        assert!(signal.datatype.is_none() && signal.access.is_none());
+        assert_eq!(
+            signal.meta.as_ref().and_then(|m| m.unit.as_deref()),
+            Some("dB")
+        );
+    }
+    assert!(result.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_client_queries_resolve_in_order() {
+    let router = TestRouter::start().await;
+    let (sender, _receiver) = connect_and_handshake(&router.url()).await;
+    announce(&sender, 5).await;
+
+    let client = router
+        .connect_client()
+        .await
+        .expect("Client should connect");
+    let (all, one) = tokio::join!(
+        client.query(QueryMessage {
+            pattern: "/mixer/**".to_string(),
+            ..Default::default()
+        }),
+        client.query(QueryMessage {
+            pattern: "/mixer/ch/002".to_string(),
+            ..Default::default()
+        }),
+    );
+
+    assert_eq!(all.expect("QUERY should be answered").signals.len(), 5);
+    let one = one.expect("QUERY should be answered");
+    assert_eq!(one.signals.len(), 1);
+    assert_eq!(one.signals[0].address, "/mixer/ch/002");
+}
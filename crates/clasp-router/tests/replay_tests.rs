@@ -0,0 +1,143 @@
+//! Replay Paging Tests
+//!
+//! Tests for:
+//! - REPLAY answers split into pages joined by cursors
+//! - Invalid cursors refused
+//! - Per-session replay rate limiting
+
+#![cfg(feature = "journal")]
+
+use clasp_client::Clasp;
+use clasp_core::{ErrorCode, ReplayMessage, Value};
+use clasp_router::{ReplayConfig, Router, RouterConfig};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Start a router with an in-memory journal, returning its URL
+async fn start_router(replay: ReplayConfig) -> String {
+    let router = Router::new(RouterConfig {
+        replay,
+        ..Default::default()
+    })
+    .with_journal(Arc::new(clasp_journal::MemoryJournal::new(1000)));
+    let addr = format!(
+        "127.0.0.1:{}",
+        clasp_test_utils::find_available_port().await
+    );
+    let serve_addr = addr.clone();
+    tokio::spawn(async move {
+        let _ = router.serve_websocket(&serve_addr).await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    format!("ws://{}", addr)
+}
+
+/// Connect a client that records every value it sees under /data/**
+async fn recording_client(url: &str) -> (Clasp, Arc<Mutex<Vec<Value>>>) {
+    let client = Clasp::connect_to(url).await.expect("Connect failed");
+    let received: Arc<Mutex<Vec<Value>>> = Arc::default();
+    let sink = received.clone();
+    client
+        .subscribe("/data/**", move |value, _address| {
+            sink.lock().unwrap().push(value);
+        })
+        .await
+        .expect("Subscribe failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (client, received)
+}
+
+fn replay(cursor: Option<String>) -> ReplayMessage {
+    ReplayMessage {
+        pattern: "/data/**".to_string(),
+        from: None,
+        to: None,
+        limit: None,
+        types: vec![],
+        timed: false,
+        cursor,
+    }
+}
+
+async fn write_entries(url: &str, count: i64) {
+    let writer = Clasp::connect_to(url).await.expect("Connect failed");
+    for i in 0..count {
+        writer
+            .set_confirmed(&format!("/data/{}", i), Value::Int(i))
+            .await
+            .expect("SET failed");
+    }
+}
+
+#[tokio::test]
+async fn test_replay_pages_follow_cursors() {
+    let url = start_router(ReplayConfig {
+        page_size: 2,
+        ..Default::default()
+    })
+    .await;
+    write_entries(&url, 5).await;
+    let (reader, received) = recording_client(&url).await;
+    received.lock().unwrap().clear();
+
+    let mut pages = 0;
+    let mut cursor = None;
+    loop {
+        pages += 1;
+        cursor = reader
+            .replay_page(replay(cursor))
+            .await
+            .expect("Replay failed");
+        if cursor.is_none() {
+            break;
+        }
+        assert!(pages < 10, "Replay never finished");
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(pages, 3, "5 entries in pages of 2");
+    assert_eq!(
+        *received.lock().unwrap(),
+        (0..5).map(Value::Int).collect::<Vec<_>>(),
+        "Every entry should replay once, in order"
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_cursor_refused() {
+    let url = start_router(ReplayConfig::default()).await;
+    let reader = Clasp::connect_to(&url).await.expect("Connect failed");
+
+    let error = reader
+        .replay_page(replay(Some("not-a-cursor".to_string())))
+        .await
+        .expect_err("Replay with a bogus cursor should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::InvalidRequest));
+}
+
+#[tokio::test]
+async fn test_replay_rate_limited() {
+    let url = start_router(ReplayConfig {
+        max_rate: 20,
+        ..Default::default()
+    })
+    .await;
+    write_entries(&url, 6).await;
+    let (reader, received) = recording_client(&url).await;
+    received.lock().unwrap().clear();
+
+    let started = Instant::now();
+    let cursor = reader
+        .replay_page(replay(None))
+        .await
+        .expect("Replay failed");
+    let elapsed = started.elapsed();
+
+    assert_eq!(cursor, None);
+    assert_eq!(received.lock().unwrap().len(), 6);
+    assert!(
+        elapsed >= Duration::from_millis(200),
+        "6 entries at 20/s took {:?}",
+        elapsed
+    );
+}
@@ -0,0 +1,161 @@
+//! Reserved Namespace Tests
+//!
+//! Tests for:
+//! - Writes under /clasp/ refused without admin scope in authenticated mode
+//! - Default exceptions (P2P signaling) and configured exceptions
+//! - Refused writes published as audit events
+
+use clasp_client::Clasp;
+use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+use clasp_core::{ErrorCode, Message, PublishMessage, SecurityMode, SignalType, Value};
+use clasp_router::reserved::AUDIT_ADDRESS;
+use clasp_router::{ReservedNamespace, Router, RouterConfig};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const USER_TOKEN: &str = "cpsk_reserved_user";
+const ADMIN_TOKEN: &str = "cpsk_reserved_admin";
+
+/// Start an authenticated router where USER_TOKEN may read and write
+/// everything and ADMIN_TOKEN also has admin scope, returning its URL
+async fn start_router(reserved: ReservedNamespace) -> String {
+    let validator = CpskValidator::new();
+    for (token, subject, scopes) in [
+        (USER_TOKEN, "user", vec!["read:/**", "write:/**"]),
+        (ADMIN_TOKEN, "admin", vec!["read:/**", "admin:/**"]),
+    ] {
+        validator.register(
+            token.to_string(),
+            TokenInfo::new(
+                token.to_string(),
+                scopes
+                    .into_iter()
+                    .map(|s| Scope::parse(s).unwrap())
+                    .collect(),
+            )
+            .with_subject(subject),
+        );
+    }
+    let router = Router::new(RouterConfig {
+        security_mode: SecurityMode::Authenticated,
+        rate_limiting_enabled: false,
+        reserved,
+        ..Default::default()
+    })
+    .with_validator(validator);
+
+    let addr = format!(
+        "127.0.0.1:{}",
+        clasp_test_utils::find_available_port().await
+    );
+    let serve_addr = addr.clone();
+    tokio::spawn(async move {
+        let _ = router.serve_websocket(&serve_addr).await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    format!("ws://{}", addr)
+}
+
+async fn connect(url: &str, token: &str) -> Clasp {
+    Clasp::builder(url)
+        .token(token)
+        .connect()
+        .await
+        .expect("Connect failed")
+}
+
+fn publish(address: &str) -> Message {
+    Message::Publish(PublishMessage {
+        address: address.to_string(),
+        signal: Some(SignalType::Event),
+        value: Some(Value::Bool(true)),
+        payload: None,
+        samples: None,
+        rate: None,
+        id: None,
+        phase: None,
+        timestamp: None,
+        timeline: None,
+    })
+}
+
+#[tokio::test]
+async fn test_reserved_writes_need_admin_scope() {
+    let url = start_router(ReservedNamespace::default()).await;
+    let user = connect(&url, USER_TOKEN).await;
+    let admin = connect(&url, ADMIN_TOKEN).await;
+
+    let error = user
+        .set_confirmed("/clasp/rules/mute", Value::Bool(true))
+        .await
+        .expect_err("SET to /clasp/rules should need admin scope");
+    assert_eq!(error.error_code(), Some(ErrorCode::Forbidden));
+    let error = user
+        .request(publish("/clasp/sessions/kick"))
+        .await
+        .expect_err("PUBLISH to /clasp/sessions should need admin scope");
+    assert_eq!(error.error_code(), Some(ErrorCode::Forbidden));
+
+    admin
+        .set_confirmed("/clasp/rules/mute", Value::Bool(true))
+        .await
+        .expect("Admin SET should be accepted");
+    user.set_confirmed("/mixer/1/level", Value::Float(0.5))
+        .await
+        .expect("Ordinary SET should be accepted");
+    user.request(publish("/clasp/p2p/announce"))
+        .await
+        .expect("P2P signaling is exempt by default");
+}
+
+#[tokio::test]
+async fn test_configured_exceptions() {
+    let url = start_router(ReservedNamespace {
+        exceptions: vec!["/clasp/ui/**".to_string()],
+        ..Default::default()
+    })
+    .await;
+    let user = connect(&url, USER_TOKEN).await;
+
+    user.set_confirmed("/clasp/ui/theme", Value::String("dark".into()))
+        .await
+        .expect("Configured exception should be writable");
+    let error = user
+        .set_confirmed("/clasp/rules/mute", Value::Bool(true))
+        .await
+        .expect_err("Other reserved addresses stay admin-only");
+    assert_eq!(error.error_code(), Some(ErrorCode::Forbidden));
+}
+
+#[tokio::test]
+async fn test_violations_audited() {
+    let url = start_router(ReservedNamespace::default()).await;
+    let admin = connect(&url, ADMIN_TOKEN).await;
+    let events: Arc<Mutex<Vec<Value>>> = Arc::default();
+    let sink = events.clone();
+    admin
+        .subscribe(AUDIT_ADDRESS, move |value, _address| {
+            sink.lock().unwrap().push(value);
+        })
+        .await
+        .expect("Subscribe failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let user = connect(&url, USER_TOKEN).await;
+    let _ = user
+        .set_confirmed("/clasp/rules/mute", Value::Bool(true))
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1, "One audit event per refused write");
+    let Value::Map(fields) = &events[0] else {
+        panic!("Audit event should be a map: {:?}", events[0]);
+    };
+    assert_eq!(
+        fields.get("address"),
+        Some(&Value::String("/clasp/rules/mute".into()))
+    );
+    assert_eq!(fields.get("subject"), Some(&Value::String("user".into())));
+    assert_eq!(fields.get("operation"), Some(&Value::String("SET".into())));
+}
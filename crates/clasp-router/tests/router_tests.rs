@@ -130,6 +130,41 @@ mod websocket_tests {
         router_handle.abort();
     }
 
+    /// Test clients connecting through several SO_REUSEPORT acceptors
+    #[tokio::test]
+    async fn test_websocket_acceptors() {
+        let port = find_available_port().await;
+        let addr = format!("127.0.0.1:{}", port);
+
+        let router = Router::default();
+
+        let router_handle = {
+            let addr = addr.clone();
+            tokio::spawn(async move {
+                let _ = router.serve_websocket_acceptors(&addr, 4).await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("ws://{}", addr);
+        let connects = (0..16).map(|_| {
+            let url = url.clone();
+            tokio::spawn(async move {
+                timeout(Duration::from_secs(2), WebSocketTransport::connect(&url)).await
+            })
+        });
+        let mut connections = vec![];
+        for connect in connects.collect::<Vec<_>>() {
+            let result = connect.await.unwrap();
+            assert!(result.is_ok(), "Should connect through an acceptor");
+            connections.push(result.unwrap().unwrap());
+        }
+        assert_eq!(connections.len(), 16);
+
+        router_handle.abort();
+    }
+
     /// Test HELLO/WELCOME handshake
     #[tokio::test]
     async fn test_hello_welcome_handshake() {
@@ -157,6 +192,7 @@ mod websocket_tests {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         let hello_bytes = codec::encode(&hello).unwrap();
         sender.send(hello_bytes).await.unwrap();
@@ -217,6 +253,7 @@ mod websocket_tests {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -249,6 +286,8 @@ mod websocket_tests {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         sender.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -312,6 +351,7 @@ mod websocket_tests {
                 features: vec!["param".to_string()],
                 capabilities: None,
                 token: None,
+                session_resume: None,
             });
             sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -356,6 +396,8 @@ mod websocket_tests {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         sender2.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -380,6 +422,424 @@ mod websocket_tests {
 
         router_handle.abort();
     }
+
+    /// Test enabling a wire tap through the admin namespace
+    #[tokio::test]
+    async fn test_wire_tap_captures_frames() {
+        let port = find_available_port().await;
+        let addr = format!("127.0.0.1:{}", port);
+
+        let router = Router::default();
+        let tap = router.wire_tap();
+
+        let router_handle = {
+            let addr = addr.clone();
+            tokio::spawn(async move {
+                let _ = router.serve_websocket(&addr).await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("ws://{}", addr);
+        let (sender, mut receiver) = WebSocketTransport::connect(&url).await.unwrap();
+
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: "Tap Client".to_string(),
+            features: vec!["param".to_string()],
+            capabilities: None,
+            token: None,
+            session_resume: None,
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+
+        use clasp_transport::TransportEvent;
+        loop {
+            if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                let (msg, _) = codec::decode(&data).unwrap();
+                if matches!(msg, Message::Snapshot(_)) {
+                    break;
+                }
+            }
+        }
+
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 1,
+            pattern: "/clasp/tap/**".to_string(),
+            types: vec![],
+            options: None,
+        });
+        sender
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+
+        let mut rule = std::collections::HashMap::new();
+        rule.insert("pattern".to_string(), Value::String("/lights/**".into()));
+        rule.insert("direction".to_string(), Value::String("in".into()));
+        let enable = Message::Set(SetMessage {
+            address: "/clasp/admin/tap/debug".to_string(),
+            value: Value::Map(rule),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        sender.send(codec::encode(&enable).unwrap()).await.unwrap();
+
+        let ack = timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                    if let (Message::Ack(ack), _) = codec::decode(&data).unwrap() {
+                        return ack;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("Should receive ACK for tap rule");
+        assert_eq!(ack.address.as_deref(), Some("/clasp/admin/tap/debug"));
+        assert!(tap.is_active());
+        assert_eq!(tap.stats().len(), 1);
+
+        for address in ["/audio/gain", "/lights/1"] {
+            let set = Message::Set(SetMessage {
+                address: address.to_string(),
+                value: Value::Float(0.5),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            });
+            sender.send(codec::encode(&set).unwrap()).await.unwrap();
+        }
+
+        let captured = timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                    if let (Message::Publish(p), _) = codec::decode(&data).unwrap() {
+                        return p;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("Should receive captured frame");
+        assert_eq!(captured.address, "/clasp/tap/debug");
+        let Some(Value::Map(record)) = captured.value else {
+            panic!("Expected a map record");
+        };
+        assert_eq!(record["direction"].as_str(), Some("in"));
+        let Value::Map(ref message) = record["message"] else {
+            panic!("Expected a decoded message");
+        };
+        assert_eq!(message["address"].as_str(), Some("/lights/1"));
+        assert_eq!(tap.stats()[0].captured, 1);
+
+        router_handle.abort();
+    }
+
+    /// Test that maintenance mode rejects writes and announces itself
+    #[tokio::test]
+    async fn test_maintenance_mode_rejects_writes() {
+        use clasp_transport::TransportEvent;
+
+        let port = find_available_port().await;
+        let addr = format!("127.0.0.1:{}", port);
+
+        let router = std::sync::Arc::new(Router::default());
+        let router_handle = {
+            let addr = addr.clone();
+            let router = std::sync::Arc::clone(&router);
+            tokio::spawn(async move {
+                let _ = router.serve_websocket(&addr).await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("ws://{}", addr);
+        let (sender, mut receiver) = WebSocketTransport::connect(&url).await.unwrap();
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: "Maintenance Client".to_string(),
+            features: vec!["param".to_string()],
+            capabilities: None,
+            token: None,
+            session_resume: None,
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+
+        async fn next(
+            receiver: &mut clasp_transport::websocket::WebSocketReceiver,
+            pred: fn(&Message) -> bool,
+        ) -> Message {
+            timeout(Duration::from_secs(2), async {
+                loop {
+                    if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                        let (msg, _) = codec::decode(&data).unwrap();
+                        if pred(&msg) {
+                            return msg;
+                        }
+                    }
+                }
+            })
+            .await
+            .expect("expected message not received")
+        }
+        next(&mut receiver, |m| matches!(m, Message::Snapshot(_))).await;
+
+        let set = |address: &str, value: Value| {
+            codec::encode(&Message::Set(SetMessage {
+                address: address.to_string(),
+                value,
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            }))
+            .unwrap()
+        };
+
+        sender
+            .send(set(
+                "/clasp/admin/maintenance",
+                Value::String("Migrating".into()),
+            ))
+            .await
+            .unwrap();
+        let Message::Publish(banner) =
+            next(&mut receiver, |m| matches!(m, Message::Publish(_))).await
+        else {
+            unreachable!();
+        };
+        assert_eq!(banner.address, "/clasp/maintenance");
+        let Some(Value::Map(fields)) = banner.value else {
+            panic!("Expected a map announcement");
+        };
+        assert_eq!(fields["active"], Value::Bool(true));
+        assert_eq!(fields["message"].as_str(), Some("Migrating"));
+
+        sender
+            .send(set("/lights/1", Value::Float(0.5)))
+            .await
+            .unwrap();
+        let Message::Error(error) = next(&mut receiver, |m| matches!(m, Message::Error(_))).await
+        else {
+            unreachable!();
+        };
+        assert_eq!(error.code, 503);
+        assert_eq!(error.address.as_deref(), Some("/lights/1"));
+        assert_eq!(router.state().get("/lights/1"), None);
+
+        router.set_maintenance(false, None);
+        sender
+            .send(set("/lights/1", Value::Float(0.5)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(router.state().get("/lights/1"), Some(Value::Float(0.5)));
+
+        router_handle.abort();
+    }
+
+    /// Test that shutdown notifies sessions and closes those that stay
+    #[tokio::test]
+    async fn test_shutdown_closes_sessions() {
+        use clasp_transport::TransportEvent;
+
+        let port = find_available_port().await;
+        let addr = format!("127.0.0.1:{}", port);
+
+        let router = std::sync::Arc::new(Router::default());
+        let router_handle = {
+            let addr = addr.clone();
+            let router = std::sync::Arc::clone(&router);
+            tokio::spawn(async move {
+                let _ = router.serve_websocket(&addr).await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("ws://{}", addr);
+        let (sender, mut receiver) = WebSocketTransport::connect(&url).await.unwrap();
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: "Shutdown Client".to_string(),
+            features: vec!["param".to_string()],
+            capabilities: None,
+            token: None,
+            session_resume: None,
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+        timeout(Duration::from_secs(2), async {
+            while router.session_count() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Session should be registered");
+
+        let closed = router
+            .shutdown("Relay restarting", Duration::from_millis(200))
+            .await;
+        assert_eq!(closed, 1);
+
+        let notice = timeout(Duration::from_secs(2), async {
+            loop {
+                match receiver.recv().await {
+                    Some(TransportEvent::Data(data)) => {
+                        if let (Message::Error(error), _) = codec::decode(&data).unwrap() {
+                            return error;
+                        }
+                    }
+                    Some(_) => {}
+                    None => panic!("Connection closed before the notice"),
+                }
+            }
+        })
+        .await
+        .expect("Should receive the shutdown notice");
+        assert_eq!(notice.code, 503);
+        assert_eq!(notice.message, "Relay restarting");
+
+        let disconnected = timeout(Duration::from_secs(2), async {
+            loop {
+                match receiver.recv().await {
+                    Some(TransportEvent::Disconnected { .. }) | None => return,
+                    Some(_) => {}
+                }
+            }
+        })
+        .await;
+        assert!(disconnected.is_ok(), "Router should close the session");
+
+        router_handle.abort();
+    }
+
+    /// Test that session-scoped params are deleted when their writer disconnects
+    #[tokio::test]
+    async fn test_session_scoped_params_cleared_on_disconnect() {
+        use clasp_core::Ttl;
+        use clasp_transport::{TransportEvent, TransportReceiver};
+
+        let port = find_available_port().await;
+        let addr = format!("127.0.0.1:{}", port);
+
+        let mut config = RouterConfig::default();
+        config.state_config.session_scoped = vec!["/tmp/**".to_string()];
+        let router = std::sync::Arc::new(Router::new(config));
+
+        let router_handle = {
+            let addr = addr.clone();
+            let router = std::sync::Arc::clone(&router);
+            tokio::spawn(async move {
+                let _ = router.serve_websocket(&addr).await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("ws://{}", addr);
+        let connect = || async {
+            let (sender, mut receiver) = WebSocketTransport::connect(&url).await.unwrap();
+            let hello = Message::Hello(HelloMessage {
+                version: 2,
+                name: "Scratch Client".to_string(),
+                features: vec!["param".to_string()],
+                capabilities: None,
+                token: None,
+                session_resume: None,
+            });
+            sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+            loop {
+                if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                    if let (Message::Snapshot(_), _) = codec::decode(&data).unwrap() {
+                        break;
+                    }
+                }
+            }
+            (sender, receiver)
+        };
+
+        let (watcher, mut watcher_rx) = connect().await;
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 1,
+            pattern: "/**".to_string(),
+            types: vec![],
+            options: None,
+        });
+        watcher
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+
+        let (writer, _writer_rx) = connect().await;
+        for (address, ttl) in [
+            ("/tmp/cursor", None),
+            ("/scratch/draft", Some(Ttl::Session)),
+            ("/kept", None),
+        ] {
+            let set = Message::Set(SetMessage {
+                address: address.to_string(),
+                value: Value::Float(0.5),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl,
+                trace: false,
+                branch: None,
+            });
+            writer.send(codec::encode(&set).unwrap()).await.unwrap();
+        }
+
+        // Wait until the watcher has seen all three writes
+        let mut seen = 0;
+        timeout(Duration::from_secs(2), async {
+            while seen < 3 {
+                if let Some(TransportEvent::Data(data)) = watcher_rx.recv().await {
+                    if let (Message::Set(_), _) = codec::decode(&data).unwrap() {
+                        seen += 1;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("Should receive the writer's SETs");
+
+        writer.close().await.unwrap();
+
+        let mut cleared = timeout(Duration::from_secs(2), async {
+            let mut cleared = Vec::new();
+            while cleared.len() < 2 {
+                if let Some(TransportEvent::Data(data)) = watcher_rx.recv().await {
+                    if let (Message::Set(set), _) = codec::decode(&data).unwrap() {
+                        assert_eq!(set.value, Value::Null);
+                        cleared.push(set.address);
+                    }
+                }
+            }
+            cleared
+        })
+        .await
+        .expect("Should be notified of session-scoped deletions");
+        cleared.sort();
+        assert_eq!(cleared, vec!["/scratch/draft", "/tmp/cursor"]);
+
+        assert!(router.state().get("/tmp/cursor").is_none());
+        assert!(router.state().get("/scratch/draft").is_none());
+        assert!(router.state().get("/kept").is_some());
+
+        router_handle.abort();
+    }
 }
 
 /// Tests for generic serve_on method
@@ -408,6 +868,84 @@ mod state_tests {
     }
 }
 
+/// Tests for messages handled on behalf of in-process sessions
+mod local_tests {
+    use super::*;
+    use clasp_core::GetMessage;
+    use clasp_router::{RouterState, Session, WriteValidator};
+    use std::sync::Arc;
+
+    struct NoDoors;
+
+    impl WriteValidator for NoDoors {
+        fn validate_write(
+            &self,
+            address: &str,
+            _value: &Value,
+            _session: &Session,
+            _state: &RouterState,
+        ) -> Result<(), String> {
+            if address.starts_with("/doors/") {
+                Err("doors are read-only".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn set(address: &str) -> Message {
+        Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(0.5),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })
+    }
+
+    /// Test that local messages get replies and pass the write validator
+    #[tokio::test]
+    async fn test_handle_local_runs_handlers() {
+        let mut router = Router::default();
+        router.set_write_validator(NoDoors);
+        let local = router.local_handler();
+        let session = Arc::new(Session::stub(Some("alice".to_string())));
+
+        let replies = local.handle(&session, &set("/lights/1")).await;
+        assert!(
+            matches!(replies.as_slice(), [Message::Ack(ack)] if ack.revision == Some(1)),
+            "{:?}",
+            replies
+        );
+        assert_eq!(
+            router.state().get_state("/lights/1").map(|p| p.writer),
+            Some(session.id.clone())
+        );
+
+        let replies = local.handle(&session, &set("/doors/front")).await;
+        assert!(
+            matches!(replies.as_slice(), [Message::Error(e)] if e.message.contains("read-only")),
+            "{:?}",
+            replies
+        );
+        assert!(router.state().get_state("/doors/front").is_none());
+
+        let get = Message::Get(GetMessage {
+            address: "/lights/1".to_string(),
+            depth: None,
+        });
+        let replies = local.handle(&session, &get).await;
+        assert!(
+            matches!(replies.as_slice(), [Message::Snapshot(s)] if s.params.len() == 1),
+            "{:?}",
+            replies
+        );
+    }
+}
+
 /// Tests for P2P signaling
 #[cfg(feature = "websocket")]
 mod p2p_tests {
@@ -434,6 +972,7 @@ mod p2p_tests {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -642,3 +1181,75 @@ mod p2p_tests {
         }
     }
 }
+
+#[cfg(all(feature = "metrics", feature = "websocket"))]
+mod metrics_tests {
+    use super::*;
+    use clasp_client::Clasp;
+    use clasp_router::MultiProtocolConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn find_available_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    async fn get(addr: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        timeout(Duration::from_secs(2), stream.read_to_string(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        response
+    }
+
+    /// Test the /metrics endpoint started by serve_all
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let ws_addr = format!("127.0.0.1:{}", find_available_port().await);
+        let metrics_addr = format!("127.0.0.1:{}", find_available_port().await);
+
+        let router = Router::default();
+        // Other protocol fields exist depending on features
+        #[allow(clippy::needless_update)]
+        let config = MultiProtocolConfig {
+            websocket_addr: Some(ws_addr.clone()),
+            metrics_addr: Some(metrics_addr.clone()),
+            ..Default::default()
+        };
+        let router_handle = tokio::spawn(async move {
+            let _ = router.serve_all(config).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let client = Clasp::builder(&format!("ws://{}", ws_addr))
+            .name("metrics-test")
+            .connect()
+            .await
+            .unwrap();
+        client.set("/metrics/test", 1.0).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let response = get(&metrics_addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("text/plain; version=0.0.4"));
+        assert!(response.contains("clasp_sessions_active 1"), "{}", response);
+        assert!(response.contains("clasp_messages_total{type=\"set\"}"));
+        assert!(response.contains("clasp_state_params_active 1"));
+        assert!(
+            response.contains("clasp_accepts_total{transport=\"websocket\",acceptor=\"0\"}"),
+            "{}",
+            response
+        );
+
+        let response = get(&metrics_addr, "/other").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+
+        client.close().await;
+        router_handle.abort();
+    }
+}
@@ -0,0 +1,177 @@
+//! Param Schema Tests
+//!
+//! Tests for:
+//! - Announced schemas published under /clasp/schema/
+//! - Out-of-range and off-enum SETs refused with InvalidValue
+//! - Clamping SETs into range
+//! - Bundles with a violating SET being refused whole
+
+use clasp_client::Clasp;
+use clasp_core::{
+    AnnounceMessage, ErrorCode, Message, SetMessage, SignalDefinition, SignalMeta, SignalType,
+    Value,
+};
+use clasp_router::{RouterConfig, SchemaMode};
+use clasp_test_utils::TestRouter;
+
+fn schema_router_config(mode: SchemaMode) -> RouterConfig {
+    RouterConfig {
+        schema: mode,
+        ..Default::default()
+    }
+}
+
+async fn announce_lights(client: &Clasp) {
+    let signal = |address: &str, datatype: &str, meta: SignalMeta| SignalDefinition {
+        address: address.to_string(),
+        signal_type: SignalType::Param,
+        datatype: Some(datatype.to_string()),
+        access: Some("rw".to_string()),
+        meta: Some(meta),
+    };
+    let announce = Message::Announce(AnnounceMessage {
+        namespace: "/lights".to_string(),
+        signals: vec![
+            signal(
+                "/lights/*/level",
+                "f32",
+                SignalMeta {
+                    unit: None,
+                    range: Some((0.0, 1.0)),
+                    default: None,
+                    description: None,
+                    enum_values: None,
+                },
+            ),
+            signal(
+                "/lights/*/mode",
+                "string",
+                SignalMeta {
+                    unit: None,
+                    range: None,
+                    default: None,
+                    description: None,
+                    enum_values: Some(vec![
+                        Value::String("steady".to_string()),
+                        Value::String("strobe".to_string()),
+                    ]),
+                },
+            ),
+        ],
+        meta: None,
+    });
+    client
+        .request(announce)
+        .await
+        .expect("ANNOUNCE should be acknowledged");
+}
+
+fn set(address: &str, value: Value) -> Message {
+    Message::Set(SetMessage {
+        address: address.to_string(),
+        value,
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    })
+}
+
+#[tokio::test]
+async fn test_violating_sets_rejected() {
+    let router = TestRouter::start_with_config(schema_router_config(SchemaMode::Reject)).await;
+    let client = router
+        .connect_client_named("Console")
+        .await
+        .expect("Console should connect");
+    announce_lights(&client).await;
+
+    let Value::Map(schema) = client
+        .get("/clasp/schema/lights/*/level")
+        .await
+        .expect("Schema should be published")
+    else {
+        panic!("Schema should be a map");
+    };
+    assert_eq!(schema["type"], Value::String("f32".to_string()));
+    assert_eq!(schema["max"], Value::Float(1.0));
+
+    client
+        .set_confirmed("/lights/1/level", Value::Float(0.5))
+        .await
+        .expect("SET in range should apply");
+
+    let error = client
+        .set_confirmed("/lights/1/level", Value::Float(1.4))
+        .await
+        .expect_err("SET above the range should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::InvalidValue));
+    assert!(error.to_string().contains("max: 1.4 is above 1"));
+
+    let error = client
+        .set_confirmed("/lights/1/mode", Value::String("disco".to_string()))
+        .await
+        .expect_err("SET outside the enum should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::InvalidValue));
+
+    let error = client
+        .set_confirmed("/clasp/schema/lights/*/level", Value::Null)
+        .await
+        .expect_err("Schemas should not be writable");
+    assert_eq!(error.error_code(), Some(ErrorCode::WriteRejected));
+
+    // A bundle with one bad SET applies nothing
+    client
+        .set_confirmed("/lights/2/level", Value::Float(0.1))
+        .await
+        .expect("SET in range should apply");
+    let error = client
+        .bundle_confirmed(vec![
+            set("/lights/2/level", Value::Float(0.2)),
+            set("/lights/2/mode", Value::String("disco".to_string())),
+        ])
+        .await
+        .expect_err("Bundle with a violating SET should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::InvalidValue));
+    assert_eq!(
+        client.get("/lights/2/level").await.ok(),
+        Some(Value::Float(0.1))
+    );
+}
+
+#[tokio::test]
+async fn test_clamp_mode_stores_nearest_bound() {
+    let router = TestRouter::start_with_config(schema_router_config(SchemaMode::Clamp)).await;
+    let client = router
+        .connect_client_named("Console")
+        .await
+        .expect("Console should connect");
+    announce_lights(&client).await;
+
+    client
+        .set_confirmed("/lights/1/level", Value::Float(1.4))
+        .await
+        .expect("SET above the range should be clamped");
+    client
+        .bundle_confirmed(vec![set("/lights/2/level", Value::Int(-2))])
+        .await
+        .expect("Bundled SET below the range should be clamped");
+
+    assert_eq!(
+        client.get("/lights/1/level").await.ok(),
+        Some(Value::Float(1.0))
+    );
+    assert_eq!(
+        client.get("/lights/2/level").await.ok(),
+        Some(Value::Int(0))
+    );
+
+    // Clamping doesn't make an off-enum value acceptable
+    let error = client
+        .set_confirmed("/lights/1/mode", Value::String("disco".to_string()))
+        .await
+        .expect_err("SET outside the enum should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::InvalidValue));
+}
@@ -6,6 +6,11 @@
 //! - Session timeout handling
 //! - Multiple concurrent sessions
 //! - Session state isolation
+//! - Session handoff between devices with the same token subject
+//! - Concurrent login limits per token subject
+//! - Topic aliases for hot addresses
+//! - Rate limits by message type and address prefix
+//! - Entity-aware sessions from registry tokens
 //! - Negative tests and edge cases
 
 use clasp_client::Clasp;
@@ -722,3 +727,1583 @@ async fn test_session_after_server_restart() {
     client2.close().await;
     handle2.abort();
 }
+
+// ============================================================================
+// Session Handoff Tests
+// ============================================================================
+
+mod handoff {
+    use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+    use clasp_core::{
+        codec, HelloMessage, Message, SecurityMode, SetMessage, SubscribeMessage, Ttl, Value,
+    };
+    use clasp_router::{HandoffPolicy, Router, RouterConfig, RouterState};
+    use clasp_test_utils::find_available_port;
+    use clasp_transport::{
+        websocket::{WebSocketReceiver, WebSocketSender},
+        Transport, TransportEvent, TransportReceiver, TransportSender, WebSocketTransport,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    pub(super) const TOKEN: &str = "cpsk_handoff_user";
+    pub(super) const MONITOR_TOKEN: &str = "cpsk_handoff_monitor";
+
+    async fn start(policy: HandoffPolicy) -> (String, Arc<RouterState>) {
+        start_with(RouterConfig {
+            session_handoff: policy,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Start an authenticated router where TOKEN has subject "user-1" and
+    /// MONITOR_TOKEN has subject "monitor"
+    pub(super) async fn start_with(config: RouterConfig) -> (String, Arc<RouterState>) {
+        serve(authenticated(config)).await
+    }
+
+    /// An authenticated router accepting TOKEN and MONITOR_TOKEN
+    pub(super) fn authenticated(config: RouterConfig) -> Router {
+        let validator = CpskValidator::new();
+        for (token, subject) in [(TOKEN, "user-1"), (MONITOR_TOKEN, "monitor")] {
+            validator.register(
+                token.to_string(),
+                TokenInfo::new(
+                    token.to_string(),
+                    vec![
+                        Scope::parse("read:/**").unwrap(),
+                        Scope::parse("write:/**").unwrap(),
+                    ],
+                )
+                .with_subject(subject),
+            );
+        }
+        Router::new(RouterConfig {
+            security_mode: SecurityMode::Authenticated,
+            rate_limiting_enabled: false,
+            ..config
+        })
+        .with_validator(validator)
+    }
+
+    /// Serve `router` over WebSocket on a free port
+    pub(super) async fn serve(router: Router) -> (String, Arc<RouterState>) {
+        let (_, _, state) = router.shared_state();
+
+        let addr = format!("127.0.0.1:{}", find_available_port().await);
+        let serve_addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&serve_addr).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (format!("ws://{}", addr), state)
+    }
+
+    pub(super) async fn connect(url: &str, name: &str) -> (WebSocketSender, WebSocketReceiver) {
+        connect_as(url, name, TOKEN).await
+    }
+
+    pub(super) async fn connect_as(
+        url: &str,
+        name: &str,
+        token: &str,
+    ) -> (WebSocketSender, WebSocketReceiver) {
+        let (sender, mut receiver) = send_hello(url, name, token).await;
+        next_matching(&mut receiver, |msg| matches!(msg, Message::Snapshot(_)))
+            .await
+            .expect("handshake failed");
+        (sender, receiver)
+    }
+
+    pub(super) async fn send_hello(
+        url: &str,
+        name: &str,
+        token: &str,
+    ) -> (WebSocketSender, WebSocketReceiver) {
+        let (sender, receiver) = WebSocketTransport::connect(url).await.unwrap();
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: name.to_string(),
+            features: vec!["param".to_string()],
+            capabilities: None,
+            token: Some(token.to_string()),
+            session_resume: None,
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+        (sender, receiver)
+    }
+
+    /// Wait for the first message matching `pred`; `None` on disconnect or timeout
+    pub(super) async fn next_matching(
+        receiver: &mut WebSocketReceiver,
+        pred: impl Fn(&Message) -> bool,
+    ) -> Option<Message> {
+        timeout(Duration::from_secs(2), async {
+            loop {
+                match receiver.recv().await {
+                    Some(TransportEvent::Data(data)) => {
+                        let (msg, _) = codec::decode(&data).unwrap();
+                        if pred(&msg) {
+                            return Some(msg);
+                        }
+                    }
+                    Some(TransportEvent::Disconnected { .. }) | None => return None,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    fn set(address: &str, value: Value, ttl: Option<Ttl>) -> bytes::Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value,
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transfer_moves_subscriptions_and_session_state() {
+        let (url, state) = start(HandoffPolicy::Transfer).await;
+
+        let (phone, mut phone_rx) = connect(&url, "phone").await;
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 7,
+            pattern: "/doc/**".to_string(),
+            types: vec![],
+            options: None,
+        });
+        phone
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+        phone
+            .send(set("/doc/cursor", Value::Int(3), Some(Ttl::Session)))
+            .await
+            .unwrap();
+        next_matching(&mut phone_rx, |msg| matches!(msg, Message::Set(_)))
+            .await
+            .expect("phone should see its own SET");
+
+        let (tablet, mut tablet_rx) = connect(&url, "tablet").await;
+        let Some(Message::Error(notice)) =
+            next_matching(&mut phone_rx, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("phone was not told about the handoff");
+        };
+        assert_eq!(notice.code, 303);
+
+        // The tablet receives updates through the phone's subscription
+        tablet
+            .send(set("/doc/title", Value::String("draft".into()), None))
+            .await
+            .unwrap();
+        let Some(Message::Set(update)) =
+            next_matching(&mut tablet_rx, |msg| matches!(msg, Message::Set(_))).await
+        else {
+            panic!("tablet did not inherit the subscription");
+        };
+        assert_eq!(update.address, "/doc/title");
+
+        // The cursor now belongs to the tablet and outlives the phone
+        phone.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(state.get("/doc/cursor"), Some(Value::Int(3)));
+
+        tablet.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(state.get("/doc/cursor"), None);
+    }
+
+    #[tokio::test]
+    async fn test_replace_disconnects_old_session() {
+        let (url, _state) = start(HandoffPolicy::Replace).await;
+        let (_phone, mut phone_rx) = connect(&url, "phone").await;
+        let (_tablet, _tablet_rx) = connect(&url, "tablet").await;
+
+        assert!(matches!(
+            next_matching(&mut phone_rx, |msg| matches!(msg, Message::Error(_))).await,
+            Some(Message::Error(ref e)) if e.code == 303
+        ));
+        assert!(next_matching(&mut phone_rx, |_| true).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_off_keeps_sessions_independent() {
+        let (url, _state) = start(HandoffPolicy::Off).await;
+        let (_phone, mut phone_rx) = connect(&url, "phone").await;
+        let (_tablet, _tablet_rx) = connect(&url, "tablet").await;
+
+        assert!(timeout(Duration::from_millis(300), phone_rx.recv())
+            .await
+            .is_err());
+    }
+}
+
+// ============================================================================
+// Concurrent Login Limit Tests
+// ============================================================================
+
+mod session_limit {
+    use super::handoff::{
+        connect, connect_as, next_matching, send_hello, start_with, MONITOR_TOKEN, TOKEN,
+    };
+    use clasp_core::{codec, Message, SubscribeMessage, Value};
+    use clasp_router::{HandoffPolicy, LimitPolicy, RouterConfig, SessionLimit};
+    use clasp_transport::{TransportReceiver, TransportSender};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_reject_new_refuses_login_over_limit() {
+        let (url, _state) = start_with(RouterConfig {
+            session_limit: SessionLimit::new(1, LimitPolicy::RejectNew),
+            ..Default::default()
+        })
+        .await;
+        let (_phone, mut phone_rx) = connect(&url, "phone").await;
+
+        let (_tablet, mut tablet_rx) = send_hello(&url, "tablet", TOKEN).await;
+        assert!(matches!(
+            next_matching(&mut tablet_rx, |msg| !matches!(msg, Message::Ping)).await,
+            Some(Message::Error(ref e)) if e.code == 304
+        ));
+        assert!(next_matching(&mut tablet_rx, |_| true).await.is_none());
+
+        // Other subjects and the existing session are unaffected
+        let _monitor = connect_as(&url, "monitor", MONITOR_TOKEN).await;
+        assert!(timeout(Duration::from_millis(300), phone_rx.recv())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_kick_oldest_disconnects_and_publishes_event() {
+        let (url, _state) = start_with(RouterConfig {
+            session_limit: SessionLimit::new(1, LimitPolicy::KickOldest),
+            ..Default::default()
+        })
+        .await;
+        let (monitor, mut monitor_rx) = connect_as(&url, "monitor", MONITOR_TOKEN).await;
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 1,
+            pattern: clasp_router::session_limit::KICK_EVENT_ADDRESS.to_string(),
+            types: vec![],
+            options: None,
+        });
+        monitor
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (_phone, mut phone_rx) = connect(&url, "phone").await;
+        let (_tablet, _tablet_rx) = connect(&url, "tablet").await;
+
+        assert!(matches!(
+            next_matching(&mut phone_rx, |msg| matches!(msg, Message::Error(_))).await,
+            Some(Message::Error(ref e)) if e.code == 305
+        ));
+        assert!(next_matching(&mut phone_rx, |_| true).await.is_none());
+
+        let Some(Message::Publish(event)) =
+            next_matching(&mut monitor_rx, |msg| matches!(msg, Message::Publish(_))).await
+        else {
+            panic!("no kick event published");
+        };
+        let Some(Value::Map(fields)) = event.value else {
+            panic!("kick event should carry a map");
+        };
+        assert_eq!(fields.get("subject"), Some(&Value::String("user-1".into())));
+    }
+
+    #[tokio::test]
+    async fn test_kick_oldest_runs_after_handoff() {
+        let (url, state) = start_with(RouterConfig {
+            session_handoff: HandoffPolicy::Transfer,
+            session_limit: SessionLimit::new(1, LimitPolicy::KickOldest),
+            ..Default::default()
+        })
+        .await;
+        let (phone, mut phone_rx) = connect(&url, "phone").await;
+        phone
+            .send(
+                codec::encode(&Message::Set(clasp_core::SetMessage {
+                    address: "/doc/cursor".to_string(),
+                    value: Value::Int(3),
+                    revision: None,
+                    lock: false,
+                    unlock: false,
+                    ttl: Some(clasp_core::Ttl::Session),
+                    trace: false,
+                    branch: None,
+                }))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (_tablet, _tablet_rx) = connect(&url, "tablet").await;
+        assert!(next_matching(
+            &mut phone_rx,
+            |msg| matches!(msg, Message::Error(e) if e.code == 305)
+        )
+        .await
+        .is_some());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // The kicked phone's session-scoped state moved to the tablet first
+        assert_eq!(state.get("/doc/cursor"), Some(Value::Int(3)));
+    }
+}
+
+mod async_validation {
+    use super::handoff::{next_matching, send_hello};
+    use clasp_core::security::{AsyncTokenValidator, Scope, TokenInfo, ValidationResult};
+    use clasp_core::{Message, SecurityMode};
+    use clasp_router::{Router, RouterConfig, ValidationConfig};
+    use clasp_test_utils::find_available_port;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Accepts "ent_slow" after `delay`, like a registry-backed validator
+    struct SlowValidator {
+        delay: Duration,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl AsyncTokenValidator for SlowValidator {
+        async fn validate(&self, token: &str) -> ValidationResult {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            if token == "ent_slow" {
+                ValidationResult::Valid(
+                    TokenInfo::new(token.to_string(), vec![Scope::parse("read:/**").unwrap()])
+                        .with_subject("device-1"),
+                )
+            } else {
+                ValidationResult::NotMyToken
+            }
+        }
+
+        fn name(&self) -> &str {
+            "Slow"
+        }
+    }
+
+    async fn start(delay: Duration, validation: ValidationConfig) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut router = Router::new(RouterConfig {
+            security_mode: SecurityMode::Authenticated,
+            rate_limiting_enabled: false,
+            validation,
+            ..Default::default()
+        });
+        router.set_async_validator(SlowValidator {
+            delay,
+            calls: Arc::clone(&calls),
+        });
+
+        let addr = format!("127.0.0.1:{}", find_available_port().await);
+        let serve_addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&serve_addr).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (format!("ws://{}", addr), calls)
+    }
+
+    #[tokio::test]
+    async fn test_hello_validation_timeout() {
+        let (url, _calls) = start(
+            Duration::from_secs(5),
+            ValidationConfig {
+                timeout: Duration::from_millis(100),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let (_sender, mut receiver) = send_hello(&url, "device", "ent_slow").await;
+        match next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await {
+            Some(Message::Error(e)) => {
+                assert_eq!(e.code, 300);
+                assert!(e.message.contains("timed out"), "{}", e.message);
+            }
+            other => panic!("expected ERROR, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hello_validation_cache() {
+        let (url, calls) = start(
+            Duration::from_millis(50),
+            ValidationConfig {
+                cache_ttl: Duration::from_secs(60),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        for name in ["first", "second", "third"] {
+            let (_sender, mut receiver) = send_hello(&url, name, "ent_slow").await;
+            assert!(
+                next_matching(&mut receiver, |msg| matches!(msg, Message::Snapshot(_)))
+                    .await
+                    .is_some(),
+                "{} was not admitted",
+                name
+            );
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
+
+mod overload {
+    use super::handoff::{connect, next_matching, send_hello, start_with, TOKEN};
+    use clasp_core::Message;
+    use clasp_router::{OverloadConfig, RouterConfig};
+    use clasp_transport::{Transport, TransportSender, WebSocketTransport};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_hello_deferred_with_jittered_hint() {
+        let (url, _state) = start_with(RouterConfig {
+            overload: OverloadConfig {
+                max_pending_handshakes: 1,
+                retry_after: Duration::from_millis(500),
+                retry_jitter: Duration::from_millis(250),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await;
+
+        // A connection that never sends HELLO holds the only handshake slot
+        let (idle, _idle_rx) = WebSocketTransport::connect(&url).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (_sender, mut receiver) = send_hello(&url, "late", TOKEN).await;
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR 504");
+        };
+        assert_eq!(error.code, 504);
+        let hint = error.retry_after_hint().unwrap();
+        assert!(hint >= Duration::from_millis(500) && hint <= Duration::from_millis(750));
+        assert!(next_matching(&mut receiver, |_| true).await.is_none());
+
+        // Once the idle handshake goes away, HELLO is admitted again
+        idle.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let _admitted = connect(&url, "retry").await;
+    }
+
+    #[tokio::test]
+    async fn test_hello_deferred_while_shedding_load() {
+        // Any scheduling delay at all counts as overload
+        let (url, _state) = start_with(RouterConfig {
+            overload: OverloadConfig {
+                retry_after: Duration::from_millis(300),
+                retry_jitter: Duration::ZERO,
+                shed_loop_lag: Duration::from_nanos(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let (_sender, mut receiver) = send_hello(&url, "late", TOKEN).await;
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR 504");
+        };
+        assert_eq!(error.code, 504);
+        assert_eq!(error.retry_after_hint(), Some(Duration::from_millis(300)));
+    }
+}
+
+mod quota {
+    use super::handoff::{connect, connect_as, next_matching, start_with, MONITOR_TOKEN};
+    use clasp_core::{codec, Message, SetMessage, Value};
+    use clasp_router::{BandwidthQuota, QuotaAction, QuotaConfig, RouterConfig};
+    use clasp_transport::TransportSender;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn set(address: &str, value: i64) -> bytes::Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(value),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    fn user_quota(action: QuotaAction) -> RouterConfig {
+        RouterConfig {
+            quota: QuotaConfig {
+                subjects: HashMap::from([("user-1".to_string(), BandwidthQuota::new(2_000, 0))]),
+                action,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttle_drops_traffic_over_quota() {
+        let (url, state) = start_with(user_quota(QuotaAction::Throttle)).await;
+        let (sender, mut receiver) = connect(&url, "tenant").await;
+        let (monitor, _monitor_rx) = connect_as(&url, "monitor", MONITOR_TOKEN).await;
+
+        for i in 0..100 {
+            sender.send(set("/quota/value", i)).await.unwrap();
+        }
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR 306");
+        };
+        assert_eq!(error.code, 306);
+
+        // Still connected, but writes are dropped
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let before = state.get("/quota/value");
+        assert_ne!(before, Some(Value::Int(99)));
+        sender.send(set("/quota/after", 1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(state.get("/quota/after"), None);
+        assert!(sender.is_connected());
+
+        // Other tenants are unaffected
+        monitor.send(set("/quota/monitor", 1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(state.get("/quota/monitor"), Some(Value::Int(1)));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_over_quota() {
+        let (url, _state) = start_with(user_quota(QuotaAction::Disconnect)).await;
+        let (sender, mut receiver) = connect(&url, "tenant").await;
+
+        for i in 0..100 {
+            if sender.send(set("/quota/value", i)).await.is_err() {
+                break;
+            }
+        }
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR 306");
+        };
+        assert_eq!(error.code, 306);
+        assert!(next_matching(&mut receiver, |_| true).await.is_none());
+    }
+}
+
+// ============================================================================
+// Topic Alias Tests
+// ============================================================================
+
+mod topic_alias {
+    use super::handoff::{connect, next_matching, start_with, TOKEN};
+    use clasp_client::Clasp;
+    use clasp_core::{
+        codec, AliasMessage, HelloMessage, Message, SetMessage, SubscribeMessage, Value,
+    };
+    use clasp_router::{RouterConfig, TopicAliasConfig};
+    use clasp_test_utils::TestRouter;
+    use clasp_transport::{
+        websocket::{WebSocketReceiver, WebSocketSender},
+        Transport, TransportEvent, TransportReceiver, TransportSender, WebSocketTransport,
+    };
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn set(address: &str, value: i64) -> bytes::Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(value),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    fn alias(alias: u16, address: &str) -> bytes::Bytes {
+        codec::encode(&Message::Alias(AliasMessage {
+            alias,
+            address: address.to_string(),
+        }))
+        .unwrap()
+    }
+
+    /// Connect advertising the alias feature and subscribe to `pattern`
+    async fn subscriber(url: &str, pattern: &str) -> (WebSocketSender, WebSocketReceiver) {
+        let (sender, mut receiver) = WebSocketTransport::connect(url).await.unwrap();
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: "alias-subscriber".to_string(),
+            features: vec!["param".to_string(), "alias".to_string()],
+            capabilities: None,
+            token: Some(TOKEN.to_string()),
+            session_resume: None,
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+        let Some(Message::Welcome(welcome)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Welcome(_))).await
+        else {
+            panic!("handshake failed");
+        };
+        assert!(welcome.features.contains(&"alias".to_string()));
+        next_matching(&mut receiver, |msg| matches!(msg, Message::Snapshot(_))).await;
+
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 1,
+            pattern: pattern.to_string(),
+            types: vec![],
+            options: None,
+        });
+        sender
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (sender, receiver)
+    }
+
+    /// Collect SET addresses and ALIAS bindings until `count` SETs arrive
+    async fn collect(receiver: &mut WebSocketReceiver, count: usize) -> Vec<Message> {
+        let mut seen = Vec::new();
+        let mut sets = 0;
+        timeout(Duration::from_secs(2), async {
+            while sets < count {
+                let Some(TransportEvent::Data(data)) = receiver.recv().await else {
+                    break;
+                };
+                let (msg, _) = codec::decode(&data).unwrap();
+                match msg {
+                    Message::Set(_) => sets += 1,
+                    Message::Alias(_) => {}
+                    _ => continue,
+                }
+                seen.push(msg);
+            }
+        })
+        .await
+        .expect("timed out waiting for updates");
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_client_aliases_resolve() {
+        let (url, state) = start_with(RouterConfig::default()).await;
+        let (sender, mut receiver) = connect(&url, "writer").await;
+
+        sender.send(alias(1, "/alias/gain")).await.unwrap();
+        sender.send(set("#1", 7)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(state.get("/alias/gain"), Some(Value::Int(7)));
+        assert_eq!(state.get("#1"), None);
+
+        // Unknown aliases and wildcard bindings are rejected with ERROR 200
+        sender.send(set("#2", 1)).await.unwrap();
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR for unknown alias");
+        };
+        assert_eq!(error.code, 200);
+
+        sender.send(alias(2, "/alias/*")).await.unwrap();
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR for wildcard binding");
+        };
+        assert_eq!(error.code, 200);
+    }
+
+    #[tokio::test]
+    async fn test_router_aliases_hot_addresses() {
+        let (url, _state) = start_with(RouterConfig {
+            topic_aliases: TopicAliasConfig {
+                max_aliases: 16,
+                hot_after: 3,
+            },
+            ..Default::default()
+        })
+        .await;
+        let (_sub, mut sub_rx) = subscriber(&url, "/hot/**").await;
+        let (plain, mut plain_rx) = connect(&url, "plain").await;
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 1,
+            pattern: "/hot/**".to_string(),
+            types: vec![],
+            options: None,
+        });
+        plain
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (writer, _writer_rx) = connect(&url, "writer").await;
+        for i in 0..5 {
+            writer.send(set("/hot/fader", i)).await.unwrap();
+        }
+
+        let seen = collect(&mut sub_rx, 5).await;
+        let addresses: Vec<String> = seen
+            .iter()
+            .map(|msg| match msg {
+                Message::Set(set) => set.address.clone(),
+                Message::Alias(bind) => format!("ALIAS {} {}", bind.alias, bind.address),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            addresses,
+            [
+                "/hot/fader",
+                "/hot/fader",
+                "ALIAS 1 /hot/fader",
+                "#1",
+                "#1",
+                "#1"
+            ]
+        );
+
+        // Sessions without the feature keep full addresses
+        let seen = collect(&mut plain_rx, 5).await;
+        assert!(seen
+            .iter()
+            .all(|msg| matches!(msg, Message::Set(set) if set.address == "/hot/fader")));
+    }
+
+    #[tokio::test]
+    async fn test_clients_alias_end_to_end() {
+        let router = TestRouter::start().await;
+        let reader = Clasp::connect_to(&router.url()).await.unwrap();
+        let writer = Clasp::connect_to(&router.url()).await.unwrap();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        reader
+            .subscribe("/e2e/**", move |value, address| {
+                sink.lock().push((address.to_string(), value));
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for i in 0..20 {
+            writer.set("/e2e/position", i).await.unwrap();
+        }
+        let done = received.clone();
+        assert!(
+            clasp_test_utils::wait_for(
+                || {
+                    let done = done.clone();
+                    async move { done.lock().len() >= 20 }
+                },
+                Duration::from_millis(10),
+                Duration::from_secs(2),
+            )
+            .await
+        );
+        let received = received.lock();
+        assert!(received
+            .iter()
+            .all(|(address, _)| address == "/e2e/position"));
+        assert_eq!(received.last().unwrap().1, Value::Int(19));
+    }
+}
+
+// ============================================================================
+// Rate Limit Policy Tests
+// ============================================================================
+
+mod rate_limits {
+    use super::handoff::next_matching;
+    use clasp_core::{codec, HelloMessage, Message, PublishMessage, SetMessage, SignalType, Value};
+    use clasp_router::{RateLimitPolicy, RateLimitRule, Router, RouterConfig};
+    use clasp_test_utils::find_available_port;
+    use clasp_transport::{Transport, TransportSender, WebSocketTransport};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_gesture_burst_does_not_starve_sets() {
+        let router = Router::new(RouterConfig {
+            max_messages_per_second: 20,
+            rate_limiting_enabled: true,
+            rate_limits: RateLimitPolicy::new(vec![RateLimitRule::new(5)
+                .types(&["PUBLISH"])
+                .prefix("/gesture/")]),
+            ..Default::default()
+        });
+        let (_, _, state) = router.shared_state();
+        let addr = format!("127.0.0.1:{}", find_available_port().await);
+        let serve_addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&serve_addr).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (sender, mut receiver) = WebSocketTransport::connect(&format!("ws://{}", addr))
+            .await
+            .unwrap();
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: "burst".to_string(),
+            features: vec!["param".to_string(), "gesture".to_string()],
+            capabilities: None,
+            token: None,
+            session_resume: None,
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+        next_matching(&mut receiver, |msg| matches!(msg, Message::Snapshot(_)))
+            .await
+            .expect("handshake failed");
+
+        // Far more gesture moves than the global limit allows
+        for i in 0..50 {
+            let publish = Message::Publish(PublishMessage {
+                address: "/gesture/pad".to_string(),
+                signal: Some(SignalType::Event),
+                value: Some(Value::Int(i)),
+                payload: None,
+                samples: None,
+                rate: None,
+                id: None,
+                phase: None,
+                timestamp: None,
+                timeline: None,
+            });
+            sender.send(codec::encode(&publish).unwrap()).await.unwrap();
+        }
+        for i in 0..10 {
+            let set = Message::Set(SetMessage {
+                address: "/mixer/gain".to_string(),
+                value: Value::Int(i),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            });
+            sender.send(codec::encode(&set).unwrap()).await.unwrap();
+        }
+
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR 429 for the gesture burst");
+        };
+        assert_eq!(error.code, 429);
+        assert!(error.message.contains("/gesture/"), "{}", error.message);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(state.get("/mixer/gain"), Some(Value::Int(9)));
+    }
+}
+
+mod possession {
+    use super::handoff::{next_matching, send_hello};
+    use clasp_client::ClaspBuilder;
+    use clasp_core::security::{
+        possession_payload, CpskValidator, Scope, TokenInfo, AUDIENCE_METADATA,
+    };
+    use clasp_core::{codec, Message, ProofMessage, SecurityMode};
+    use clasp_router::{Router, RouterConfig, Session, SessionId};
+    use clasp_test_utils::find_available_port;
+    use clasp_transport::TransportSender;
+    use dashmap::DashMap;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const TOKEN: &str = "cpsk_bound_device";
+
+    fn device_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    /// Start a router where TOKEN is bound to `device_key()`
+    async fn start() -> (String, Arc<DashMap<SessionId, Arc<Session>>>) {
+        let audience: String = device_key()
+            .verifying_key()
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let validator = CpskValidator::new();
+        validator.register(
+            TOKEN.to_string(),
+            TokenInfo::new(TOKEN.to_string(), vec![Scope::parse("write:/**").unwrap()])
+                .with_metadata(AUDIENCE_METADATA, audience),
+        );
+        let router = Router::new(RouterConfig {
+            security_mode: SecurityMode::Authenticated,
+            ..Default::default()
+        })
+        .with_validator(validator);
+        let (sessions, _, _) = router.shared_state();
+
+        let addr = format!("127.0.0.1:{}", find_available_port().await);
+        let serve_addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&serve_addr).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (format!("ws://{}", addr), sessions)
+    }
+
+    #[tokio::test]
+    async fn test_session_starts_after_valid_proof() {
+        let (url, sessions) = start().await;
+        let (sender, mut receiver) = send_hello(&url, "device", TOKEN).await;
+
+        let Some(Message::Welcome(welcome)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Welcome(_))).await
+        else {
+            panic!("expected WELCOME");
+        };
+        let challenge = welcome.challenge.expect("WELCOME without challenge");
+        assert!(sessions.is_empty(), "session registered before proof");
+
+        let payload = possession_payload(&welcome.session, &challenge);
+        let proof = Message::Proof(ProofMessage {
+            signature: device_key().sign(&payload).to_bytes().to_vec(),
+        });
+        sender.send(codec::encode(&proof).unwrap()).await.unwrap();
+        next_matching(&mut receiver, |msg| matches!(msg, Message::Snapshot(_)))
+            .await
+            .expect("no snapshot after proof");
+        assert!(sessions.contains_key(&welcome.session));
+    }
+
+    #[tokio::test]
+    async fn test_replayed_token_is_rejected() {
+        let (url, sessions) = start().await;
+        let (sender, mut receiver) = send_hello(&url, "intruder", TOKEN).await;
+
+        let Some(Message::Welcome(welcome)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Welcome(_))).await
+        else {
+            panic!("expected WELCOME");
+        };
+        let payload = possession_payload(&welcome.session, &welcome.challenge.unwrap());
+        let proof = Message::Proof(ProofMessage {
+            signature: SigningKey::from_bytes(&[8u8; 32])
+                .sign(&payload)
+                .to_bytes()
+                .to_vec(),
+        });
+        sender.send(codec::encode(&proof).unwrap()).await.unwrap();
+
+        let Some(Message::Error(error)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Error(_))).await
+        else {
+            panic!("expected ERROR 300");
+        };
+        assert_eq!(error.code, 300);
+        assert!(sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_client_answers_challenge() {
+        let (url, sessions) = start().await;
+        let key = device_key();
+        let client = ClaspBuilder::new(&url)
+            .token(TOKEN)
+            .possession_signer(move |payload| key.sign(payload).to_bytes().to_vec())
+            .reconnect(false)
+            .connect()
+            .await
+            .expect("connect with signer failed");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(sessions.contains_key(&client.session_id().unwrap()));
+
+        // Without the key the client can't get past the challenge
+        let unsigned = ClaspBuilder::new(&url)
+            .token(TOKEN)
+            .reconnect(false)
+            .connect()
+            .await;
+        assert!(unsigned.is_err());
+    }
+}
+
+// ============================================================================
+// Durable Session Tests
+// ============================================================================
+
+mod durable_session {
+    use super::handoff::{authenticated, connect_as, next_matching, serve, MONITOR_TOKEN, TOKEN};
+    use clasp_core::{
+        codec, HelloMessage, Message, SetMessage, SubscribeMessage, Value, WelcomeMessage,
+    };
+    use clasp_router::RouterConfig;
+    use clasp_transport::{
+        websocket::{WebSocketReceiver, WebSocketSender},
+        Transport, TransportSender, WebSocketTransport,
+    };
+    use std::time::Duration;
+
+    fn config() -> RouterConfig {
+        RouterConfig {
+            durable_session_ttl: 60,
+            ..Default::default()
+        }
+    }
+
+    /// Connect with `session_resume` and return the WELCOME once the
+    /// snapshot that follows it has arrived
+    async fn connect_durable(
+        url: &str,
+        token: &str,
+        resume: &str,
+    ) -> (WebSocketSender, WebSocketReceiver, WelcomeMessage) {
+        let (sender, mut receiver) = WebSocketTransport::connect(url).await.unwrap();
+        let hello = Message::Hello(HelloMessage {
+            version: 2,
+            name: "phone".to_string(),
+            features: vec!["param".to_string()],
+            capabilities: None,
+            token: Some(token.to_string()),
+            session_resume: Some(resume.to_string()),
+        });
+        sender.send(codec::encode(&hello).unwrap()).await.unwrap();
+        let Some(Message::Welcome(welcome)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Welcome(_))).await
+        else {
+            panic!("no WELCOME");
+        };
+        (sender, receiver, welcome)
+    }
+
+    async fn subscribe(sender: &WebSocketSender, receiver: &mut WebSocketReceiver) {
+        next_matching(receiver, |msg| matches!(msg, Message::Snapshot(_))).await;
+        let subscribe = Message::Subscribe(SubscribeMessage {
+            id: 7,
+            pattern: "/doc/**".to_string(),
+            types: vec![],
+            options: None,
+        });
+        sender
+            .send(codec::encode(&subscribe).unwrap())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    fn set(address: &str, value: i64) -> bytes::Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(value),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resume_restores_subscriptions() {
+        let (url, state) = serve(authenticated(config())).await;
+        let (monitor, _monitor_rx) = connect_as(&url, "monitor", MONITOR_TOKEN).await;
+
+        let (phone, mut phone_rx, welcome) = connect_durable(&url, TOKEN, "").await;
+        assert!(!welcome.resumed);
+        let token = welcome.resume_token.expect("no resume token issued");
+        subscribe(&phone, &mut phone_rx).await;
+        phone.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(state.durable_sessions().len(), 1);
+
+        let (_phone, mut phone_rx, welcome) = connect_durable(&url, TOKEN, &token).await;
+        assert!(welcome.resumed);
+        assert_ne!(welcome.resume_token.as_deref(), Some(token.as_str()));
+        next_matching(&mut phone_rx, |msg| matches!(msg, Message::Snapshot(_))).await;
+
+        // Updates arrive without subscribing again
+        monitor.send(set("/doc/title", 1)).await.unwrap();
+        let Some(Message::Set(update)) =
+            next_matching(&mut phone_rx, |msg| matches!(msg, Message::Set(_))).await
+        else {
+            panic!("subscription was not restored");
+        };
+        assert_eq!(update.address, "/doc/title");
+
+        // Tokens are single use
+        let (_again, _again_rx, welcome) = connect_durable(&url, TOKEN, &token).await;
+        assert!(!welcome.resumed);
+    }
+
+    #[tokio::test]
+    async fn test_resume_requires_same_subject() {
+        let (url, _state) = serve(authenticated(config())).await;
+        let (phone, mut phone_rx, welcome) = connect_durable(&url, TOKEN, "").await;
+        let token = welcome.resume_token.unwrap();
+        subscribe(&phone, &mut phone_rx).await;
+        phone.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let (_other, _other_rx, welcome) = connect_durable(&url, MONITOR_TOKEN, &token).await;
+        assert!(!welcome.resumed);
+        let (_phone, _phone_rx, welcome) = connect_durable(&url, TOKEN, &token).await;
+        assert!(welcome.resumed);
+    }
+
+    #[cfg(feature = "journal")]
+    #[tokio::test]
+    async fn test_resume_replays_missed_sets() {
+        let router = authenticated(config())
+            .with_journal(std::sync::Arc::new(clasp_journal::MemoryJournal::new(1000)));
+        let (url, _state) = serve(router).await;
+        let (monitor, _monitor_rx) = connect_as(&url, "monitor", MONITOR_TOKEN).await;
+
+        let (phone, mut phone_rx, welcome) = connect_durable(&url, TOKEN, "").await;
+        let token = welcome.resume_token.unwrap();
+        subscribe(&phone, &mut phone_rx).await;
+        phone.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        for value in 1..=3 {
+            monitor.send(set("/doc/count", value)).await.unwrap();
+        }
+        monitor.send(set("/other/x", 9)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let (_phone, mut phone_rx, welcome) = connect_durable(&url, TOKEN, &token).await;
+        assert!(welcome.resumed);
+        let mut missed = Vec::new();
+        while let Some(Message::Set(update)) = next_matching(&mut phone_rx, |msg| {
+            matches!(msg, Message::Set(_) | Message::Snapshot(_))
+        })
+        .await
+        {
+            missed.push((update.address, update.value));
+        }
+        assert_eq!(
+            missed,
+            vec![
+                ("/doc/count".to_string(), Value::Int(1)),
+                ("/doc/count".to_string(), Value::Int(2)),
+                ("/doc/count".to_string(), Value::Int(3)),
+            ]
+        );
+    }
+}
+
+// ============================================================================
+// Entity-Aware Session Tests
+// ============================================================================
+
+mod entity_session {
+    use super::handoff::{next_matching, send_hello, serve};
+    use clasp_core::security::{CpskValidator, EntityInfo, Scope, TokenInfo};
+    use clasp_core::{codec, Message, SecurityMode, SetMessage, Value};
+    use clasp_router::{
+        ConfigSource, EntityConfig, FleetSource, Router, RouterConfig, RouterState, Session,
+        WriteValidator,
+    };
+    use clasp_transport::{
+        websocket::{WebSocketReceiver, WebSocketSender},
+        TransportSender,
+    };
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const DEVICE_TOKEN: &str = "cpsk_entity_device";
+    const USER_TOKEN: &str = "cpsk_entity_user";
+
+    /// Only device entities may write sensor readings
+    struct DevicesOnly;
+
+    impl WriteValidator for DevicesOnly {
+        fn validate_write(
+            &self,
+            address: &str,
+            _value: &Value,
+            session: &Session,
+            _state: &RouterState,
+        ) -> Result<(), String> {
+            let is_device = session
+                .entity
+                .as_ref()
+                .is_some_and(|e| e.entity_type == "device");
+            if address.starts_with("/sensors/") && !is_device {
+                return Err("only devices write sensor readings".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    fn router() -> Router {
+        let scopes = || vec![Scope::parse("admin:/**").unwrap()];
+        let validator = CpskValidator::new();
+        validator.register(
+            DEVICE_TOKEN.to_string(),
+            TokenInfo::new(DEVICE_TOKEN.to_string(), scopes())
+                .with_subject("clasp:thermo")
+                .with_entity(EntityInfo {
+                    id: "clasp:thermo".to_string(),
+                    entity_type: "device".to_string(),
+                    name: "Thermostat".to_string(),
+                    tags: vec!["hvac".to_string()],
+                    metadata: [("site".to_string(), "north".to_string())].into(),
+                }),
+        );
+        validator.register(
+            USER_TOKEN.to_string(),
+            TokenInfo::new(USER_TOKEN.to_string(), scopes()).with_subject("user-1"),
+        );
+        let mut router = Router::new(RouterConfig {
+            security_mode: SecurityMode::Authenticated,
+            rate_limiting_enabled: false,
+            presence: true,
+            ..Default::default()
+        })
+        .with_validator(validator);
+        router.set_write_validator(DevicesOnly);
+        router
+    }
+
+    /// Configuration documents by entity ID, with the versions acknowledged
+    #[derive(Default)]
+    struct Configs {
+        docs: Mutex<HashMap<String, EntityConfig>>,
+        acked: Mutex<Vec<(String, u64)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ConfigSource for Configs {
+        async fn config(&self, entity_id: &str) -> Option<EntityConfig> {
+            self.docs.lock().get(entity_id).cloned()
+        }
+
+        async fn acknowledge(&self, entity_id: &str, version: u64) -> bool {
+            let current = self.docs.lock().get(entity_id).map(|d| d.version);
+            if current != Some(version) {
+                return false;
+            }
+            self.acked.lock().push((entity_id.to_string(), version));
+            true
+        }
+    }
+
+    /// The thermostat is the only member of the `hvac` fleet
+    struct Fleets;
+
+    #[async_trait::async_trait]
+    impl FleetSource for Fleets {
+        async fn members(&self, fleet: &str) -> Vec<String> {
+            match fleet {
+                "hvac" => vec!["clasp:thermo".to_string()],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    /// Connect and return the session ID from WELCOME
+    async fn connect(url: &str, token: &str) -> (WebSocketSender, WebSocketReceiver, String) {
+        let (sender, mut receiver) = send_hello(url, "client", token).await;
+        let Some(Message::Welcome(welcome)) =
+            next_matching(&mut receiver, |msg| matches!(msg, Message::Welcome(_))).await
+        else {
+            panic!("no WELCOME");
+        };
+        next_matching(&mut receiver, |msg| matches!(msg, Message::Snapshot(_))).await;
+        (sender, receiver, welcome.session)
+    }
+
+    fn publish(address: &str) -> bytes::Bytes {
+        codec::encode(&Message::Publish(clasp_core::PublishMessage {
+            address: address.to_string(),
+            signal: Some(clasp_core::SignalType::Event),
+            value: Some(Value::Bool(true)),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        }))
+        .unwrap()
+    }
+
+    fn set(address: &str, value: f64) -> bytes::Bytes {
+        set_msg(address, Value::Float(value))
+    }
+
+    fn set_msg(address: &str, value: Value) -> bytes::Bytes {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value,
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_presence_carries_entity() {
+        let (url, state) = serve(router()).await;
+        let (_device, _device_rx, device_id) = connect(&url, DEVICE_TOKEN).await;
+        let (_user, _user_rx, user_id) = connect(&url, USER_TOKEN).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let Some(Value::Map(entry)) = state.get(&format!("/clasp/presence/{}", device_id)) else {
+            panic!("device presence entry missing");
+        };
+        let Some(Value::Map(entity)) = entry.get("entity") else {
+            panic!("device presence entry has no entity");
+        };
+        assert_eq!(
+            entity.get("id"),
+            Some(&Value::String("clasp:thermo".to_string()))
+        );
+        assert_eq!(
+            entity.get("type"),
+            Some(&Value::String("device".to_string()))
+        );
+        let Some(Value::Map(metadata)) = entity.get("metadata") else {
+            panic!("entity metadata missing");
+        };
+        assert_eq!(
+            metadata.get("site"),
+            Some(&Value::String("north".to_string()))
+        );
+
+        let Some(Value::Map(entry)) = state.get(&format!("/clasp/presence/{}", user_id)) else {
+            panic!("user presence entry missing");
+        };
+        assert!(!entry.contains_key("entity"));
+    }
+
+    #[tokio::test]
+    async fn test_write_validator_sees_entity_type() {
+        let (url, state) = serve(router()).await;
+        let (device, _device_rx, _) = connect(&url, DEVICE_TOKEN).await;
+        let (user, mut user_rx, _) = connect(&url, USER_TOKEN).await;
+
+        device.send(set("/sensors/temp", 21.5)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(state.get("/sensors/temp"), Some(Value::Float(21.5)));
+
+        user.send(set("/sensors/temp", 99.0)).await.unwrap();
+        assert!(
+            next_matching(&mut user_rx, |msg| matches!(msg, Message::Error(_)))
+                .await
+                .is_some(),
+            "user write should be rejected"
+        );
+        assert_eq!(state.get("/sensors/temp"), Some(Value::Float(21.5)));
+    }
+
+    #[tokio::test]
+    async fn test_config_delivered_on_connect_and_acknowledged() {
+        let configs = Arc::new(Configs::default());
+        configs.docs.lock().insert(
+            "clasp:thermo".to_string(),
+            EntityConfig {
+                version: 3,
+                config: Value::Float(19.5),
+            },
+        );
+        let mut router = router();
+        router.set_config_source_arc(configs.clone());
+        let (url, state) = serve(router).await;
+
+        let (device, mut device_rx) = send_hello(&url, "thermostat", DEVICE_TOKEN).await;
+        next_matching(&mut device_rx, |msg| matches!(msg, Message::Snapshot(_))).await;
+        let Some(Message::Set(set)) = next_matching(
+            &mut device_rx,
+            |msg| matches!(msg, Message::Set(s) if s.address == "/clasp/config/clasp:thermo"),
+        )
+        .await
+        else {
+            panic!("device should receive its configuration");
+        };
+        let Value::Map(entry) = set.value else {
+            panic!("config entry should be a map");
+        };
+        assert_eq!(entry.get("version"), Some(&Value::Int(3)));
+        assert_eq!(entry.get("config"), Some(&Value::Float(19.5)));
+
+        // Another session can't acknowledge, or overwrite, the device's config
+        let (user, mut user_rx, _) = connect(&url, USER_TOKEN).await;
+        for (address, value) in [
+            ("/clasp/config/clasp:thermo/ack", 3.0),
+            ("/clasp/config/clasp:thermo", 1.0),
+        ] {
+            user.send(set_msg(address, Value::Float(value)))
+                .await
+                .unwrap();
+            assert!(
+                next_matching(&mut user_rx, |msg| matches!(msg, Message::Error(_)))
+                    .await
+                    .is_some(),
+                "user SET to {} should be rejected",
+                address
+            );
+        }
+
+        // A stale version is refused, the current one recorded
+        device
+            .send(set_msg("/clasp/config/clasp:thermo/ack", Value::Int(2)))
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_matching(&mut device_rx, |msg| matches!(
+                msg,
+                Message::Error(_) | Message::Ack(_)
+            ))
+            .await,
+            Some(Message::Error(_))
+        ));
+        device
+            .send(set_msg("/clasp/config/clasp:thermo/ack", Value::Int(3)))
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_matching(&mut device_rx, |msg| matches!(
+                msg,
+                Message::Error(_) | Message::Ack(_)
+            ))
+            .await,
+            Some(Message::Ack(_))
+        ));
+        assert_eq!(*configs.acked.lock(), vec![("clasp:thermo".to_string(), 3)]);
+        assert_eq!(
+            state.get("/clasp/config/clasp:thermo/ack"),
+            Some(Value::Int(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fleet_publish_reaches_member_sessions() {
+        let mut router = router();
+        router.set_fleet_source(Fleets);
+        let (url, _state) = serve(router).await;
+        let (_device, mut device_rx, _) = connect(&url, DEVICE_TOKEN).await;
+        let (user, _user_rx, _) = connect(&url, USER_TOKEN).await;
+
+        // The device never subscribed; fleet membership alone delivers it
+        user.send(publish("/clasp/fleet/lights/off")).await.unwrap();
+        user.send(publish("/clasp/fleet/hvac/reboot"))
+            .await
+            .unwrap();
+        let Some(Message::Publish(received)) =
+            next_matching(&mut device_rx, |msg| matches!(msg, Message::Publish(_))).await
+        else {
+            panic!("fleet member should receive the fleet command");
+        };
+        assert_eq!(received.address, "/clasp/fleet/hvac/reboot");
+    }
+}
+
+// ============================================================================
+// Connection Info Tests
+// ============================================================================
+
+mod connection_info {
+    use super::*;
+    use clasp_core::Value;
+    use clasp_router::{RouterState, Session, WriteValidator};
+    use parking_lot::Mutex;
+    use std::net::SocketAddr;
+
+    /// Records the connection each write arrived on
+    #[derive(Default)]
+    struct Connections {
+        seen: Mutex<Vec<(&'static str, SocketAddr, Option<SocketAddr>)>>,
+    }
+
+    impl WriteValidator for Connections {
+        fn validate_write(
+            &self,
+            _address: &str,
+            _value: &Value,
+            session: &Session,
+            _state: &RouterState,
+        ) -> Result<(), String> {
+            let info = session.connection().ok_or("no connection info")?;
+            self.seen
+                .lock()
+                .push((info.protocol, info.remote_addr, info.local_addr));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_reports_connection_info() {
+        let connections = Arc::new(Connections::default());
+        let mut router = Router::new(RouterConfig::default());
+        router.set_write_validator_arc(connections.clone());
+        let port = find_available_port().await;
+        let addr = format!("127.0.0.1:{}", port);
+        let serve_addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_websocket(&serve_addr).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let client = Clasp::connect_to(&format!("ws://{}", addr))
+            .await
+            .expect("Client should connect");
+        client
+            .set_confirmed("/studio/fader", 0.5)
+            .await
+            .expect("SET should succeed");
+
+        let seen = connections.seen.lock().clone();
+        assert_eq!(seen.len(), 1);
+        let (protocol, remote, local) = seen[0];
+        assert_eq!(protocol, "websocket");
+        assert!(remote.ip().is_loopback());
+        assert_eq!(local.map(|a| a.port()), Some(port));
+    }
+}
@@ -0,0 +1,161 @@
+//! SET_MANY Tests
+//!
+//! Tests for:
+//! - Batched writes applied to `{base}/{index}` and acknowledged
+//! - One frame per subscriber, holding only the entries it subscribes to
+//! - All-or-nothing refusal when one entry fails a check
+
+use clasp_client::{ClaspBuilder, Flow, Interceptor};
+use clasp_core::{ErrorCode, Message, SetManyMessage, SetManyValues, Value};
+use clasp_test_utils::{TestRouter, ValueCollector};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Records every SET and SET_MANY the router sends
+#[derive(Clone, Default)]
+struct Frames(Arc<Mutex<Vec<Message>>>);
+
+impl Interceptor for Frames {
+    fn incoming(&self, msg: &mut Message) -> Flow {
+        if matches!(msg, Message::Set(_) | Message::SetMany(_)) {
+            self.0.lock().unwrap().push(msg.clone());
+        }
+        Flow::Continue
+    }
+}
+
+#[tokio::test]
+async fn test_set_many_applies_and_broadcasts_one_frame() {
+    let router = TestRouter::start().await;
+    let sender = router
+        .connect_client()
+        .await
+        .expect("Sender should connect");
+
+    let frames = Frames::default();
+    let receiver = ClaspBuilder::new(&router.url())
+        .interceptor(frames.clone())
+        .connect()
+        .await
+        .expect("Receiver should connect");
+    let collector = ValueCollector::new();
+    receiver
+        .subscribe("/dmx/1/**", collector.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    let levels: Vec<Value> = (0..512).map(|i| Value::Int(i % 256)).collect();
+    let ack = sender
+        .set_many_confirmed(SetManyMessage::run("/dmx/1", 1, levels))
+        .await
+        .expect("SET_MANY should be applied");
+    assert_eq!(ack.address.as_deref(), Some("/dmx/1"));
+    assert!(ack.revision.is_some());
+
+    assert!(
+        collector.wait_for_count(512, Duration::from_secs(2)).await,
+        "Receiver should see every channel, got {}",
+        collector.count()
+    );
+    assert_eq!(collector.values_for("/dmx/1/1"), vec![Value::Int(0)]);
+    assert_eq!(collector.values_for("/dmx/1/512"), vec![Value::Int(255)]);
+    assert_eq!(
+        sender.get("/dmx/1/300").await.expect("GET should succeed"),
+        Value::Int(299 % 256)
+    );
+
+    let frames = frames.0.lock().unwrap();
+    assert_eq!(frames.len(), 1, "One frame for the whole batch");
+    assert!(matches!(&frames[0], Message::SetMany(batch) if batch.len() == 512));
+}
+
+#[tokio::test]
+async fn test_set_many_subscribers_get_their_entries() {
+    let router = TestRouter::start().await;
+    let sender = router
+        .connect_client()
+        .await
+        .expect("Sender should connect");
+
+    let frames = Frames::default();
+    let receiver = ClaspBuilder::new(&router.url())
+        .interceptor(frames.clone())
+        .connect()
+        .await
+        .expect("Receiver should connect");
+    for channel in [2, 4] {
+        receiver
+            .subscribe(&format!("/strip/{}", channel), |_, _| {})
+            .await
+            .expect("Subscribe should succeed");
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    sender
+        .set_many_confirmed(SetManyMessage::run(
+            "/strip",
+            1,
+            vec![
+                Value::Float(0.1),
+                Value::Float(0.2),
+                Value::Float(0.3),
+                Value::Float(0.4),
+            ],
+        ))
+        .await
+        .expect("SET_MANY should be applied");
+    sleep(Duration::from_millis(100)).await;
+
+    let frames = frames.0.lock().unwrap();
+    assert_eq!(frames.len(), 1);
+    let Message::SetMany(batch) = &frames[0] else {
+        panic!("Expected SET_MANY, got {:?}", frames[0]);
+    };
+    assert_eq!(
+        batch.values,
+        SetManyValues::Indexed(vec![(2, Value::Float(0.2)), (4, Value::Float(0.4))])
+    );
+}
+
+#[tokio::test]
+async fn test_set_many_all_or_nothing() {
+    let router = TestRouter::start().await;
+    let client = router
+        .connect_client()
+        .await
+        .expect("Client should connect");
+
+    client
+        .set_locked("/fixture/7", Value::Int(10))
+        .await
+        .expect("set_locked should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    let other = router
+        .connect_client()
+        .await
+        .expect("Client should connect");
+    let error = other
+        .set_many_confirmed(SetManyMessage::indexed(
+            "/fixture",
+            vec![(6, Value::Int(1)), (7, Value::Int(2))],
+        ))
+        .await
+        .expect_err("A locked entry should refuse the batch");
+    assert_eq!(error.error_code(), Some(ErrorCode::LockHeld));
+
+    assert_eq!(
+        other.get("/fixture/7").await.expect("GET should succeed"),
+        Value::Int(10)
+    );
+    assert!(
+        other
+            .get("/fixture/6")
+            .await
+            .map(|v| v == Value::Null)
+            .unwrap_or(true),
+        "Entries before the refused one are rolled back"
+    );
+}
@@ -0,0 +1,118 @@
+//! Device Shadow Tests
+//!
+//! Tests for:
+//! - Router-computed deltas between desired and reported state
+//! - Delta draining to null once the device reports the desired state
+//! - Rejection of client writes to the computed delta address
+
+use clasp_core::Value;
+use clasp_router::{RouterConfig, ShadowConfig};
+use clasp_test_utils::{TestRouter, ValueCollector};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn shadow_router_config() -> RouterConfig {
+    RouterConfig {
+        shadow: ShadowConfig {
+            enabled: true,
+            desired_ttl: None,
+        },
+        ..Default::default()
+    }
+}
+
+fn map(pairs: &[(&str, Value)]) -> Value {
+    Value::Map(
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect::<HashMap<_, _>>(),
+    )
+}
+
+#[tokio::test]
+async fn test_delta_converges_when_device_reports() {
+    let router = TestRouter::start_with_config(shadow_router_config()).await;
+
+    let device = router
+        .connect_client_named("Lamp")
+        .await
+        .expect("Device should connect");
+    let app = router
+        .connect_client_named("App")
+        .await
+        .expect("App should connect");
+
+    let lamp = device.shadow("/devices/lamp");
+    let mut reported = map(&[("power", Value::Bool(false)), ("level", Value::Float(0.2))]);
+    lamp.report(reported.clone())
+        .await
+        .expect("report should succeed");
+
+    let deltas = ValueCollector::new();
+    device
+        .subscribe("/devices/lamp/delta", deltas.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(100)).await;
+
+    app.shadow("/devices/lamp")
+        .desire(map(&[
+            ("power", Value::Bool(true)),
+            ("level", Value::Float(0.2)),
+        ]))
+        .await
+        .expect("desire should succeed");
+
+    assert!(
+        deltas.wait_for_count(1, Duration::from_secs(2)).await,
+        "Device should receive a delta"
+    );
+    let delta = deltas.last_value().unwrap().1;
+    assert_eq!(delta, map(&[("power", Value::Bool(true))]));
+
+    lamp.report_delta(&mut reported, &delta)
+        .await
+        .expect("report should succeed");
+
+    assert!(
+        deltas.wait_for_count(2, Duration::from_secs(2)).await,
+        "Delta should be cleared"
+    );
+    assert_eq!(deltas.last_value().unwrap().1, Value::Null);
+}
+
+#[tokio::test]
+async fn test_client_cannot_write_delta() {
+    let router = TestRouter::start_with_config(shadow_router_config()).await;
+
+    let client = router
+        .connect_client_named("Writer")
+        .await
+        .expect("Client should connect");
+
+    client
+        .set("/devices/lamp/delta", Value::Int(1))
+        .await
+        .expect("set should be sent");
+    sleep(Duration::from_millis(200)).await;
+
+    let error = client
+        .last_error()
+        .expect("SET to delta should be rejected");
+    assert_eq!(error.code, 403);
+
+    let reader = router
+        .connect_client_named("Reader")
+        .await
+        .expect("Reader should connect");
+    assert!(
+        reader
+            .get("/devices/lamp/delta")
+            .await
+            .map(|v| v == Value::Null)
+            .unwrap_or(true),
+        "Rejected delta must not be stored"
+    );
+}
@@ -7,9 +7,16 @@
 //! - Subscription lifecycle (add/remove)
 //! - Multiple subscriptions per client
 //! - Subscription filtering by signal type
+//! - Fixed-tick bundle aggregation (tick_ms)
+//! - Stream backpressure policies (stream)
+//! - Unit conversion on delivery (convert_to)
+//! - Range mapping, clamping and rounding on delivery (transform)
+//! - SUBSCRIBE_ACK / UNSUBSCRIBE_ACK for correlated requests
 
 use clasp_core::{
-    codec, HelloMessage, Message, SetMessage, SubscribeMessage, UnsubscribeMessage, Value,
+    codec, AnnounceMessage, HelloMessage, Message, PublishMessage, RangeMap, SetMessage,
+    SignalDefinition, SignalMeta, SignalType, StreamPolicy, SubscribeMessage, SubscribeOptions,
+    UnsubscribeMessage, Value, ValueTransform,
 };
 use clasp_test_utils::TestRouter;
 use clasp_transport::{
@@ -32,6 +39,7 @@ async fn connect_and_handshake(url: &str, name: &str) -> (WebSocketSender, WebSo
         features: vec!["param".to_string(), "event".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -89,6 +97,8 @@ async fn test_exact_match_subscription() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     pub_sender
         .send(codec::encode(&set1).unwrap())
@@ -103,6 +113,8 @@ async fn test_exact_match_subscription() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     pub_sender
         .send(codec::encode(&set2).unwrap())
@@ -180,6 +192,8 @@ async fn test_single_wildcard_subscription() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
             .unwrap(),
         )
@@ -240,6 +254,8 @@ async fn test_multi_wildcard_subscription() {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .unwrap(),
             )
@@ -308,6 +324,8 @@ async fn test_unsubscribe() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
             .unwrap(),
         )
@@ -346,6 +364,8 @@ async fn test_unsubscribe() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
             .unwrap(),
         )
@@ -408,6 +428,8 @@ async fn test_multiple_subscriptions() {
                     lock: false,
                     unlock: false,
                     ttl: None,
+                    trace: false,
+                    branch: None,
                 }))
                 .unwrap(),
             )
@@ -453,6 +475,8 @@ async fn test_subscription_initial_snapshot() {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             }))
             .unwrap(),
         )
@@ -544,3 +568,408 @@ async fn test_invalid_subscription_pattern() {
     // This test passes regardless - it's documenting the behavior
     let _ = error;
 }
+
+#[tokio::test]
+async fn test_tick_subscription_aggregates_into_bundle() {
+    let router = TestRouter::start().await;
+
+    let (sub_sender, mut sub_receiver) = connect_and_handshake(&router.url(), "Engine").await;
+    let (pub_sender, _pub_receiver) = connect_and_handshake(&router.url(), "Publisher").await;
+
+    let subscribe = Message::Subscribe(SubscribeMessage {
+        id: 1,
+        pattern: "/tick/**".to_string(),
+        types: vec![],
+        options: Some(SubscribeOptions {
+            tick_ms: Some(100),
+            ..Default::default()
+        }),
+    });
+    sub_sender
+        .send(codec::encode(&subscribe).unwrap())
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for (address, value) in [
+        ("/tick/a", 1),
+        ("/tick/b", 10),
+        ("/tick/a", 2),
+        ("/tick/a", 3),
+    ] {
+        let set = Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Int(value),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        pub_sender.send(codec::encode(&set).unwrap()).await.unwrap();
+    }
+
+    // All changes arrive together in one bundle, last write per address
+    let bundle = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                let (msg, _) = codec::decode(&data).unwrap();
+                match msg {
+                    Message::Bundle(bundle) => return bundle,
+                    Message::Set(set) => panic!("Received unbundled SET to {}", set.address),
+                    _ => {}
+                }
+            }
+        }
+    })
+    .await
+    .expect("Did not receive tick bundle");
+
+    let values: Vec<(String, Value)> = bundle
+        .messages
+        .into_iter()
+        .map(|msg| match msg {
+            Message::Set(set) => (set.address, set.value),
+            other => panic!("Unexpected bundle entry: {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            ("/tick/a".to_string(), Value::Int(3)),
+            ("/tick/b".to_string(), Value::Int(10)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_reliable_stream_subscription_delivers_every_frame_in_order() {
+    let router = TestRouter::start().await;
+
+    let (sub_sender, mut sub_receiver) = connect_and_handshake(&router.url(), "Recorder").await;
+    let (pub_sender, _pub_receiver) = connect_and_handshake(&router.url(), "Mocap").await;
+
+    let subscribe = Message::Subscribe(SubscribeMessage {
+        id: 1,
+        pattern: "/mocap/**".to_string(),
+        types: vec![],
+        options: Some(SubscribeOptions {
+            stream: Some(StreamPolicy::Reliable),
+            ..Default::default()
+        }),
+    });
+    sub_sender
+        .send(codec::encode(&subscribe).unwrap())
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for value in 0..20 {
+        let frame = Message::Publish(PublishMessage {
+            address: "/mocap/hand".to_string(),
+            signal: Some(SignalType::Stream),
+            value: Some(Value::Int(value)),
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: None,
+            timeline: None,
+        });
+        pub_sender
+            .send(codec::encode(&frame).unwrap())
+            .await
+            .unwrap();
+    }
+
+    let received = timeout(Duration::from_secs(2), async {
+        let mut values = Vec::new();
+        while values.len() < 20 {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                if let (Message::Publish(publish), _) = codec::decode(&data).unwrap() {
+                    values.push(publish.value.unwrap().as_i64().unwrap());
+                }
+            }
+        }
+        values
+    })
+    .await
+    .expect("Did not receive every stream frame");
+    assert_eq!(received, (0..20).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_convert_to_subscription_converts_units() {
+    let router = TestRouter::start().await;
+
+    let (sub_sender, mut sub_receiver) = connect_and_handshake(&router.url(), "Dashboard").await;
+    let (pub_sender, mut pub_receiver) = connect_and_handshake(&router.url(), "Sensor").await;
+
+    let announce = Message::Announce(AnnounceMessage {
+        namespace: "/sensors".to_string(),
+        signals: vec![SignalDefinition {
+            address: "/sensors/*/temp".to_string(),
+            signal_type: SignalType::Param,
+            datatype: Some("float".to_string()),
+            access: None,
+            meta: Some(SignalMeta {
+                unit: Some("C".to_string()),
+                range: None,
+                default: None,
+                description: None,
+                enum_values: None,
+            }),
+        }],
+        meta: None,
+    });
+    pub_sender
+        .send(codec::encode(&announce).unwrap())
+        .await
+        .unwrap();
+    timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = pub_receiver.recv().await {
+                if let (Message::Ack(_), _) = codec::decode(&data).unwrap() {
+                    break;
+                }
+            }
+        }
+    })
+    .await
+    .expect("ANNOUNCE not acknowledged");
+
+    let subscribe = |id: u32, unit: &str| {
+        codec::encode(&Message::Subscribe(SubscribeMessage {
+            id,
+            pattern: "/sensors/**".to_string(),
+            types: vec![],
+            options: Some(SubscribeOptions {
+                convert_to: Some(unit.to_string()),
+                ..Default::default()
+            }),
+        }))
+        .unwrap()
+    };
+
+    // Metres cannot be converted from the announced Celsius
+    sub_sender.send(subscribe(1, "m")).await.unwrap();
+    let error = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                if let (Message::Error(error), _) = codec::decode(&data).unwrap() {
+                    return error;
+                }
+            }
+        }
+    })
+    .await
+    .expect("Incompatible conversion not rejected");
+    assert_eq!(error.code, 103);
+
+    sub_sender.send(subscribe(2, "F")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let set = Message::Set(SetMessage {
+        address: "/sensors/lobby/temp".to_string(),
+        value: Value::Float(20.0),
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    pub_sender.send(codec::encode(&set).unwrap()).await.unwrap();
+
+    let received = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                if let (Message::Set(set), _) = codec::decode(&data).unwrap() {
+                    return set;
+                }
+            }
+        }
+    })
+    .await
+    .expect("Did not receive converted SET");
+    assert_eq!(received.address, "/sensors/lobby/temp");
+    assert_eq!(received.value, Value::Float(68.0));
+}
+
+#[tokio::test]
+async fn test_transform_subscription_remaps_values() {
+    let router = TestRouter::start().await;
+
+    let (sub_sender, mut sub_receiver) = connect_and_handshake(&router.url(), "DMX Node").await;
+    let (pub_sender, _pub_receiver) = connect_and_handshake(&router.url(), "Console").await;
+
+    let set = |address: &str, level: f64| {
+        codec::encode(&Message::Set(SetMessage {
+            address: address.to_string(),
+            value: Value::Float(level),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        }))
+        .unwrap()
+    };
+    pub_sender.send(set("/lights/1/level", 1.0)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let subscribe = |id: u32, from: (f64, f64)| {
+        codec::encode(&Message::Subscribe(SubscribeMessage {
+            id,
+            pattern: "/lights/**".to_string(),
+            types: vec![],
+            options: Some(SubscribeOptions {
+                transform: Some(ValueTransform {
+                    map: Some(RangeMap {
+                        from,
+                        to: (0.0, 255.0),
+                    }),
+                    clamp: Some((0.0, 255.0)),
+                    round: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        }))
+        .unwrap()
+    };
+
+    // An empty range can't be mapped from
+    sub_sender.send(subscribe(1, (1.0, 1.0))).await.unwrap();
+    let error = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                if let (Message::Error(error), _) = codec::decode(&data).unwrap() {
+                    return error;
+                }
+            }
+        }
+    })
+    .await
+    .expect("Invalid transform not rejected");
+    assert_eq!(error.code, 103);
+
+    // The snapshot is transformed like later updates
+    sub_sender.send(subscribe(2, (0.0, 1.0))).await.unwrap();
+    let snapshot = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                if let (Message::Snapshot(snapshot), _) = codec::decode(&data).unwrap() {
+                    return snapshot;
+                }
+            }
+        }
+    })
+    .await
+    .expect("Did not receive snapshot");
+    assert_eq!(snapshot.params[0].value, Value::Int(255));
+
+    pub_sender.send(set("/lights/2/level", 0.5)).await.unwrap();
+    pub_sender.send(set("/lights/3/level", 1.5)).await.unwrap();
+    let mut received = Vec::new();
+    timeout(Duration::from_secs(2), async {
+        while received.len() < 2 {
+            if let Some(TransportEvent::Data(data)) = sub_receiver.recv().await {
+                if let (Message::Set(set), _) = codec::decode(&data).unwrap() {
+                    received.push((set.address, set.value));
+                }
+            }
+        }
+    })
+    .await
+    .expect("Did not receive transformed SETs");
+    assert_eq!(
+        received,
+        vec![
+            ("/lights/2/level".to_string(), Value::Int(128)),
+            ("/lights/3/level".to_string(), Value::Int(255)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_correlated_subscribe_is_acknowledged_before_snapshot() {
+    let router = TestRouter::start().await;
+
+    let (sender, mut receiver) = connect_and_handshake(&router.url(), "Engine").await;
+    let set = Message::Set(SetMessage {
+        address: "/acked/a".to_string(),
+        value: Value::Int(1),
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    sender.send(codec::encode(&set).unwrap()).await.unwrap();
+
+    let subscribe = Message::Subscribe(SubscribeMessage {
+        id: 7,
+        pattern: "/acked/**".to_string(),
+        types: vec![],
+        options: Some(SubscribeOptions {
+            tick_ms: Some(600_000),
+            ..Default::default()
+        }),
+    });
+    sender
+        .send(codec::encode_with_correlation(&subscribe, 41).unwrap())
+        .await
+        .unwrap();
+
+    // The ACK comes first, with the tick clamped to the maximum
+    let replies = timeout(Duration::from_secs(2), async {
+        let mut replies = Vec::new();
+        while replies.len() < 2 {
+            if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                let msg = codec::decode(&data).unwrap().0;
+                if matches!(msg, Message::SubscribeAck(_) | Message::Snapshot(_)) {
+                    replies.push(msg);
+                }
+            }
+        }
+        replies
+    })
+    .await
+    .expect("Did not receive SUBSCRIBE_ACK and snapshot");
+    match &replies[..] {
+        [Message::SubscribeAck(ack), Message::Snapshot(snapshot)] => {
+            assert_eq!((ack.id, ack.matched, ack.correlation_id), (7, 1, Some(41)));
+            assert_eq!(ack.options.tick_ms, Some(clasp_router::tick::MAX_TICK_MS));
+            assert_eq!(snapshot.params.len(), 1);
+        }
+        other => panic!("Unexpected replies: {:?}", other),
+    }
+
+    let unsubscribe = Message::Unsubscribe(UnsubscribeMessage { id: 7 });
+    sender
+        .send(codec::encode_with_correlation(&unsubscribe, 42).unwrap())
+        .await
+        .unwrap();
+    let ack = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                if let Message::UnsubscribeAck(ack) = codec::decode(&data).unwrap().0 {
+                    return ack;
+                }
+            }
+        }
+    })
+    .await
+    .expect("Did not receive UNSUBSCRIBE_ACK");
+    assert_eq!(
+        (ack.id, ack.removed, ack.correlation_id),
+        (7, true, Some(42))
+    );
+}
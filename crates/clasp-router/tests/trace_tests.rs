@@ -0,0 +1,124 @@
+//! Message Trace Tests
+//!
+//! Tests for:
+//! - A traced SET reporting its revision, decisions and deliveries
+//! - A rejected traced SET reporting the error
+
+use clasp_client::Clasp;
+use clasp_core::{Message, SetMessage, Value};
+use clasp_router::RouterConfig;
+use clasp_test_utils::{TestRouter, ValueCollector};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn traced_set(address: &str, value: Value) -> Message {
+    Message::Set(SetMessage {
+        address: address.to_string(),
+        value,
+        revision: None,
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: true,
+        branch: None,
+    })
+}
+
+async fn trace_collector(client: &Clasp) -> ValueCollector {
+    let session_id = client.session_id().expect("Client should have a session");
+    let reports = ValueCollector::new();
+    client
+        .subscribe(
+            &clasp_router::trace::address(&session_id),
+            reports.callback_ref(),
+        )
+        .await
+        .expect("Subscribe should succeed");
+    sleep(Duration::from_millis(50)).await;
+    reports
+}
+
+async fn next_report(reports: &ValueCollector) -> HashMap<String, Value> {
+    assert!(
+        reports.wait_for_count(1, Duration::from_secs(2)).await,
+        "A trace report should be published"
+    );
+    match reports.values().pop() {
+        Some((_, Value::Map(report))) => report,
+        other => panic!("Trace report should be a map, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_traced_set_reports_deliveries() {
+    let router = TestRouter::start().await;
+
+    let display = router
+        .connect_client_named("Display")
+        .await
+        .expect("Display should connect");
+    let levels = ValueCollector::new();
+    display
+        .subscribe("/lights/1/level", levels.callback_ref())
+        .await
+        .expect("Subscribe should succeed");
+
+    let console = router
+        .connect_client_named("Console")
+        .await
+        .expect("Console should connect");
+    let reports = trace_collector(&console).await;
+
+    console
+        .request(traced_set("/lights/1/level", Value::Float(0.8)))
+        .await
+        .expect("Traced SET should be acknowledged");
+
+    let report = next_report(&reports).await;
+    assert_eq!(report["outcome"], Value::String("applied".to_string()));
+    assert_eq!(report["revision"], Value::Int(1));
+    assert!(report.contains_key("correlation_id"));
+    assert_eq!(report["subscribers"], Value::Int(1));
+
+    let display_id = display.session_id().expect("Display should have a session");
+    let Value::Map(ref deliveries) = report["deliveries"] else {
+        panic!("Deliveries should be a map");
+    };
+    assert_eq!(
+        deliveries.get(&display_id),
+        Some(&Value::String("queued".to_string()))
+    );
+    let Value::Array(ref decisions) = report["decisions"] else {
+        panic!("Decisions should be a list");
+    };
+    assert!(decisions.contains(&Value::String("scope: open".to_string())));
+
+    assert!(levels.wait_for_count(1, Duration::from_secs(2)).await);
+}
+
+#[tokio::test]
+async fn test_rejected_set_reports_error() {
+    let router = TestRouter::start_with_config(RouterConfig {
+        presence: true,
+        ..Default::default()
+    })
+    .await;
+
+    let console = router
+        .connect_client_named("Console")
+        .await
+        .expect("Console should connect");
+    let reports = trace_collector(&console).await;
+
+    let _ = console
+        .request(traced_set("/clasp/presence/someone", Value::Null))
+        .await;
+
+    let report = next_report(&reports).await;
+    assert_eq!(report["outcome"], Value::String("rejected".to_string()));
+    let Value::String(ref error) = report["error"] else {
+        panic!("Error should be a string");
+    };
+    assert!(error.contains("Presence is maintained by the router"));
+}
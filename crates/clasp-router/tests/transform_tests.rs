@@ -37,6 +37,7 @@ mod websocket_transform_tests {
             features: vec!["param".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         });
         sender.send(codec::encode(&hello).unwrap()).await.unwrap();
 
@@ -119,6 +120,15 @@ mod websocket_transform_tests {
         }
     }
 
+    /// Replaces every value with Null.
+    struct NullEverything;
+
+    impl SignalTransform for NullEverything {
+        fn transform(&self, _address: &str, _value: &Value) -> Option<Value> {
+            Some(Value::Null)
+        }
+    }
+
     /// Helper: build a router with a transform and start it on a random port.
     /// Returns (url, router_handle).
     async fn start_router_with_transform(
@@ -157,6 +167,8 @@ mod websocket_transform_tests {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         sender.send(codec::encode(&set).unwrap()).await.unwrap();
 
@@ -181,6 +193,7 @@ mod websocket_transform_tests {
     ) -> Option<Value> {
         let get = Message::Get(GetMessage {
             address: address.to_string(),
+            depth: None,
         });
         sender.send(codec::encode(&get).unwrap()).await.unwrap();
 
@@ -294,6 +307,35 @@ mod websocket_transform_tests {
         handle.abort();
     }
 
+    #[tokio::test]
+    async fn transform_skips_e2e_envelopes() {
+        let (url, handle) = start_router_with_transform(Arc::new(NullEverything)).await;
+
+        let (sender, mut receiver) = WebSocketTransport::connect(&url).await.unwrap();
+        complete_handshake(&sender, &mut receiver, "test-client").await;
+
+        let envelope = Value::Map(
+            [
+                ("_e2e", Value::Int(1)),
+                ("ct", Value::String("Y2lwaGVydGV4dA==".to_string())),
+                ("iv", Value::String("aXZpdml2aXZpdml2".to_string())),
+                ("v", Value::Int(1)),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        );
+        send_set_and_ack(&sender, &mut receiver, "/chat/msg", envelope.clone()).await;
+        let stored = get_value(&sender, &mut receiver, "/chat/msg").await;
+        assert_eq!(stored, Some(envelope));
+
+        send_set_and_ack(&sender, &mut receiver, "/chat/plain", Value::Int(3)).await;
+        let stored = get_value(&sender, &mut receiver, "/chat/plain").await;
+        assert_eq!(stored, Some(Value::Null));
+
+        handle.abort();
+    }
+
     #[tokio::test]
     async fn no_transform_passes_value_unchanged() {
         // Baseline: router without any transform
@@ -0,0 +1,98 @@
+//! Transport Policy Tests
+//!
+//! Tests for:
+//! - Message types refused on a restricted transport
+//! - Namespaces limiting reads, writes and subscription patterns
+//! - Transports without a policy staying unrestricted
+
+use clasp_core::{ErrorCode, Message, SetMessage, Value};
+use clasp_router::{RouterConfig, TransportPolicy};
+use clasp_test_utils::TestRouter;
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn policy_router_config(transport: &str, policy: TransportPolicy) -> RouterConfig {
+    RouterConfig {
+        transport_policies: [(transport.to_string(), policy)].into_iter().collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_policy_refuses_types_and_namespaces() {
+    let policy = TransportPolicy::new()
+        .types(&["SET", "GET", "SUBSCRIBE"])
+        .namespace("/public/**");
+    let router = TestRouter::start_with_config(policy_router_config("websocket", policy)).await;
+
+    let client = router
+        .connect_client_named("Public")
+        .await
+        .expect("Client should connect");
+
+    let ack = client
+        .set_confirmed("/public/level", Value::Int(1))
+        .await
+        .expect("SET inside the namespace should apply");
+    assert!(ack.revision.is_some());
+
+    let error = client
+        .set_confirmed("/config/rate", Value::Int(60))
+        .await
+        .expect_err("SET outside the namespace should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::Forbidden));
+
+    let bundle = Message::Bundle(clasp_core::BundleMessage {
+        timestamp: None,
+        messages: vec![Message::Set(SetMessage {
+            address: "/public/level".to_string(),
+            value: Value::Int(2),
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        })],
+    });
+    let error = client
+        .request(bundle)
+        .await
+        .expect_err("BUNDLE is not an allowed type");
+    assert_eq!(error.error_code(), Some(ErrorCode::Forbidden));
+
+    let error = client
+        .subscribe_confirmed("/**", |_, _| {})
+        .await
+        .expect_err("Subscribing beyond the namespace should be refused");
+    assert_eq!(error.error_code(), Some(ErrorCode::Forbidden));
+
+    assert_eq!(
+        client.get("/public/level").await.unwrap(),
+        Value::Int(1),
+        "Refused bundle must not be applied"
+    );
+}
+
+#[tokio::test]
+async fn test_policy_only_applies_to_its_transport() {
+    let router = TestRouter::start_with_config(policy_router_config(
+        "mqtt",
+        TransportPolicy::new().read_only(),
+    ))
+    .await;
+
+    let client = router
+        .connect_client_named("Native")
+        .await
+        .expect("Client should connect");
+
+    client
+        .set("/config/rate", Value::Int(60))
+        .await
+        .expect("set should be sent");
+    sleep(Duration::from_millis(200)).await;
+
+    assert!(client.last_error().is_none());
+    assert_eq!(client.get("/config/rate").await.unwrap(), Value::Int(60));
+}
@@ -0,0 +1,108 @@
+//! WebRTC Tests
+//!
+//! Tests for:
+//! - A browser-style peer joining the router over WebRTC data channels
+
+#![cfg(feature = "webrtc")]
+
+use bytes::Bytes;
+use clasp_core::{codec, HelloMessage, Message};
+use clasp_router::{Router, RouterConfig};
+use clasp_transport::{WebRtcConfig, WebRtcTransport};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+/// Host candidates only, so tests don't reach out to STUN servers
+fn local_config() -> WebRtcConfig {
+    WebRtcConfig {
+        ice_servers: vec![],
+        ..Default::default()
+    }
+}
+
+/// POST an SDP offer to the signaling endpoint, returning the answer
+async fn post_offer(addr: &str, offer: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "POST /offer HTTP/1.1\r\nHost: {}\r\nContent-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{}",
+        addr,
+        offer.len(),
+        offer
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(
+        head.starts_with("HTTP/1.1 201"),
+        "unexpected response: {}",
+        head
+    );
+    body.to_string()
+}
+
+#[tokio::test]
+async fn test_webrtc_client_joins_router() {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let router = Router::new(RouterConfig::default());
+    let handle = {
+        let addr = addr.clone();
+        tokio::spawn(async move {
+            let _ = router.serve_webrtc_with_config(&addr, local_config()).await;
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (client, _) = WebRtcTransport::new_offerer_with_config(local_config())
+        .await
+        .unwrap();
+    let (ready_tx, mut ready_rx) = mpsc::unbounded_channel();
+    client.on_connection_ready(move || {
+        let _ = ready_tx.send(());
+    });
+    let (data_tx, mut data_rx) = mpsc::unbounded_channel::<Bytes>();
+    client.on_data(move |data, _| {
+        let _ = data_tx.send(data);
+    });
+
+    let offer = client.gathered_local_description().await.unwrap();
+    let answer = post_offer(&addr, &offer).await;
+    client.set_remote_answer(&answer).await.unwrap();
+    timeout(Duration::from_secs(10), ready_rx.recv())
+        .await
+        .expect("Channel should open");
+
+    let hello = Message::Hello(HelloMessage {
+        version: 2,
+        name: "Browser".to_string(),
+        features: vec!["param".to_string()],
+        capabilities: None,
+        token: None,
+        session_resume: None,
+    });
+    client
+        .send_reliable(codec::encode(&hello).unwrap())
+        .await
+        .unwrap();
+
+    let welcome = timeout(Duration::from_secs(5), async {
+        while let Some(data) = data_rx.recv().await {
+            if let Ok((Message::Welcome(welcome), _)) = codec::decode(&data) {
+                return Some(welcome);
+            }
+        }
+        None
+    })
+    .await
+    .expect("Router should answer HELLO")
+    .expect("Router should send WELCOME");
+    assert!(!welcome.session.is_empty());
+
+    handle.abort();
+}
@@ -3,12 +3,18 @@
 //! The engine evaluates rules against incoming state changes and events,
 //! producing actions that the router should execute.
 
-use clasp_core::{SignalType, Value};
+use chrono::NaiveDateTime;
+use clasp_core::{EntityInfo, SignalType, Value};
 use std::collections::HashMap;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::error::{Result, RulesError};
-use crate::rule::{Rule, RuleAction, Trigger};
+use crate::notify::Notifier;
+use crate::rule::{NotifyChannel, Rule, RuleAction, Trigger};
+use crate::schedule::CronSchedule;
+#[cfg(feature = "scripting")]
+use crate::script::ScriptHost;
 
 /// Output from rule evaluation -- an action the router should execute
 #[derive(Debug, Clone)]
@@ -29,6 +35,13 @@ pub struct RulesEngine {
     last_fired: HashMap<String, Instant>,
     /// Currently evaluating rules (for loop detection)
     evaluating: Vec<String>,
+    /// Delivers Notify actions (None = Notify actions are only returned)
+    notifier: Option<Arc<Notifier>>,
+    /// Parsed cron expressions of OnSchedule rules
+    schedules: HashMap<String, CronSchedule>,
+    /// Compiled Script actions
+    #[cfg(feature = "scripting")]
+    scripts: ScriptHost,
 }
 
 impl RulesEngine {
@@ -38,6 +51,35 @@ impl RulesEngine {
             rules: HashMap::new(),
             last_fired: HashMap::new(),
             evaluating: Vec::new(),
+            notifier: None,
+            schedules: HashMap::new(),
+            #[cfg(feature = "scripting")]
+            scripts: ScriptHost::new(),
+        }
+    }
+
+    /// Attach a notifier that delivers `Notify` actions as they are produced.
+    ///
+    /// `Notify` actions are still returned from `evaluate()` so callers can
+    /// observe them; the router treats them as already handled.
+    pub fn with_notifier(mut self, notifier: Arc<Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Get the attached notifier, if any
+    pub fn notifier(&self) -> Option<&Arc<Notifier>> {
+        self.notifier.as_ref()
+    }
+
+    /// Hand any `Notify` actions to the notifier
+    fn dispatch_notifications(&self, actions: &[PendingAction]) {
+        if let Some(ref notifier) = self.notifier {
+            for action in actions {
+                if matches!(action.action, RuleAction::Notify { .. }) {
+                    notifier.dispatch(action);
+                }
+            }
         }
     }
 
@@ -51,12 +93,23 @@ impl RulesEngine {
                 "rule must have at least one action".into(),
             ));
         }
+        #[cfg(feature = "scripting")]
+        self.scripts.add_rule(&rule)?;
+        if let Trigger::OnSchedule { cron } = &rule.trigger {
+            self.schedules
+                .insert(rule.id.clone(), CronSchedule::parse(cron)?);
+        } else {
+            self.schedules.remove(&rule.id);
+        }
         self.rules.insert(rule.id.clone(), rule);
         Ok(())
     }
 
     /// Remove a rule by ID
     pub fn remove_rule(&mut self, id: &str) -> Result<()> {
+        self.schedules.remove(id);
+        #[cfg(feature = "scripting")]
+        self.scripts.remove_rule(id);
         self.rules
             .remove(id)
             .map(|_| ())
@@ -108,6 +161,26 @@ impl RulesEngine {
         origin: Option<&str>,
         state_lookup: F,
     ) -> Vec<PendingAction>
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        self.evaluate_from(address, value, signal_type, origin, None, state_lookup)
+    }
+
+    /// Evaluate rules triggered by a state change made by `entity`.
+    ///
+    /// Same as [`evaluate()`](Self::evaluate), but rules with an
+    /// [`EntityMatch`](crate::EntityMatch) only fire when the author's
+    /// registry entity matches it.
+    pub fn evaluate_from<F>(
+        &mut self,
+        address: &str,
+        value: &Value,
+        signal_type: SignalType,
+        origin: Option<&str>,
+        entity: Option<&EntityInfo>,
+        state_lookup: F,
+    ) -> Vec<PendingAction>
     where
         F: Fn(&str) -> Option<Value>,
     {
@@ -125,7 +198,11 @@ impl RulesEngine {
         let matching_ids: Vec<String> = self
             .rules
             .values()
-            .filter(|rule| rule.enabled && rule.trigger.matches(address, signal_type))
+            .filter(|rule| {
+                rule.enabled
+                    && rule.trigger.matches(address, signal_type)
+                    && rule.entity.as_ref().is_none_or(|m| m.matches(entity))
+            })
             .map(|rule| rule.id.clone())
             .collect();
 
@@ -187,24 +264,7 @@ impl RulesEngine {
 
             // Collect actions
             let rule_origin = format!("rule:{}", rule_id);
-            for action in &rule.actions {
-                let resolved_action = match action {
-                    RuleAction::SetFromTrigger {
-                        address: target,
-                        transform,
-                    } => RuleAction::Set {
-                        address: target.clone(),
-                        value: transform.apply(value),
-                    },
-                    other => other.clone(),
-                };
-
-                actions.push(PendingAction {
-                    rule_id: rule_id.clone(),
-                    action: resolved_action,
-                    origin: rule_origin.clone(),
-                });
-            }
+            actions.extend(self.expand_actions(rule, &rule_origin, address, value, &state_lookup));
 
             // Update last fired time
             self.last_fired.insert(rule_id.clone(), now);
@@ -213,6 +273,7 @@ impl RulesEngine {
             self.evaluating.retain(|id| id != &rule_id);
         }
 
+        self.dispatch_notifications(&actions);
         actions
     }
 
@@ -222,6 +283,24 @@ impl RulesEngine {
     /// the rule directly (checking enabled, cooldown, and conditions).
     /// Returns actions that should be executed by the router.
     pub fn evaluate_interval<F>(&mut self, rule_id: &str, state_lookup: F) -> Vec<PendingAction>
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        self.fire(rule_id, "interval", state_lookup)
+    }
+
+    /// Evaluate a schedule rule by ID, once [`due_scheduled`](Self::due_scheduled)
+    /// reports it due. Checks enabled, cooldown and conditions like
+    /// [`evaluate_interval`](Self::evaluate_interval).
+    pub fn evaluate_scheduled<F>(&mut self, rule_id: &str, state_lookup: F) -> Vec<PendingAction>
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        self.fire(rule_id, "schedule", state_lookup)
+    }
+
+    /// Fire a rule that has no trigger address or value
+    fn fire<F>(&mut self, rule_id: &str, origin: &str, state_lookup: F) -> Vec<PendingAction>
     where
         F: Fn(&str) -> Option<Value>,
     {
@@ -254,50 +333,152 @@ impl RulesEngine {
             return vec![];
         }
 
-        let rule_origin = format!("interval:{}", rule_id);
-        let actions: Vec<PendingAction> = rule
-            .actions
-            .iter()
-            .map(|action| {
-                let resolved_action = match action {
-                    RuleAction::SetFromTrigger {
-                        address: target,
-                        transform,
-                    } => {
-                        // For interval triggers there's no trigger value, use Null
-                        RuleAction::Set {
-                            address: target.clone(),
-                            value: transform.apply(&Value::Null),
-                        }
-                    }
-                    other => other.clone(),
-                };
-                PendingAction {
-                    rule_id: rule_id.to_string(),
-                    action: resolved_action,
-                    origin: rule_origin.clone(),
-                }
-            })
-            .collect();
+        let rule_origin = format!("{}:{}", origin, rule_id);
+        // For timed triggers there's no trigger address or value
+        let actions = self.expand_actions(rule, &rule_origin, "", &Value::Null, &state_lookup);
 
         self.last_fired.insert(rule_id.to_string(), now);
+        self.dispatch_notifications(&actions);
+        actions
+    }
+
+    /// Resolve a fired rule's actions, running any Script actions in place
+    #[cfg_attr(not(feature = "scripting"), allow(unused_variables))]
+    fn expand_actions<F>(
+        &self,
+        rule: &Rule,
+        origin: &str,
+        address: &str,
+        value: &Value,
+        state_lookup: &F,
+    ) -> Vec<PendingAction>
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        let mut actions = Vec::new();
+        for (index, action) in rule.actions.iter().enumerate() {
+            #[cfg(feature = "scripting")]
+            if let RuleAction::Script { .. } = action {
+                for emitted in self.scripts.run(rule, index, address, value, state_lookup) {
+                    actions.push(PendingAction {
+                        rule_id: rule.id.clone(),
+                        action: emitted,
+                        origin: origin.to_string(),
+                    });
+                }
+                continue;
+            }
+            actions.push(PendingAction {
+                rule_id: rule.id.clone(),
+                action: resolve_action(action, rule, address, value),
+                origin: origin.to_string(),
+            });
+        }
         actions
     }
 
-    /// Get rule IDs that have interval triggers (for the router to schedule)
-    pub fn interval_rules(&self) -> Vec<(String, u64)> {
+    /// Get enabled interval rules as (rule ID, period, max jitter), for the
+    /// router to schedule
+    pub fn interval_rules(&self) -> Vec<(String, Duration, Duration)> {
         self.rules
             .values()
             .filter(|r| r.enabled)
             .filter_map(|r| {
-                if let Trigger::OnInterval { seconds } = &r.trigger {
-                    Some((r.id.clone(), *seconds))
+                if let Trigger::OnInterval { seconds, jitter } = &r.trigger {
+                    Some((
+                        r.id.clone(),
+                        Duration::from_secs(*seconds),
+                        Duration::from_secs(jitter.unwrap_or(0)),
+                    ))
                 } else {
                     None
                 }
             })
             .collect()
     }
+
+    /// Get rule IDs that have schedule triggers, with their cron expressions
+    pub fn schedule_rules(&self) -> Vec<(String, String)> {
+        self.enabled_schedules()
+            .map(|(id, cron)| (id.to_string(), cron.to_string()))
+            .collect()
+    }
+
+    /// The next wall-clock time after `after` at which any enabled schedule
+    /// rule fires (for the router to sleep until)
+    pub fn next_scheduled(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.enabled_schedules()
+            .filter_map(|(_, cron)| cron.next_after(after))
+            .min()
+    }
+
+    /// IDs of enabled schedule rules that fire in `(after, until]`
+    pub fn due_scheduled(&self, after: NaiveDateTime, until: NaiveDateTime) -> Vec<String> {
+        self.enabled_schedules()
+            .filter(|(_, cron)| cron.next_after(after).is_some_and(|next| next <= until))
+            .map(|(id, _)| id.to_string())
+            .collect()
+    }
+
+    fn enabled_schedules(&self) -> impl Iterator<Item = (&str, &CronSchedule)> {
+        self.schedules
+            .iter()
+            .filter(|(id, _)| self.rules.get(*id).is_some_and(|r| r.enabled))
+            .map(|(id, cron)| (id.as_str(), cron))
+    }
+}
+
+/// Resolve trigger-dependent actions: `SetFromTrigger` becomes a `Set` with the
+/// transformed value, and `Notify` templates are rendered.
+fn resolve_action(action: &RuleAction, rule: &Rule, address: &str, value: &Value) -> RuleAction {
+    match action {
+        RuleAction::SetFromTrigger {
+            address: target,
+            transform,
+        } => RuleAction::Set {
+            address: target.clone(),
+            value: transform.apply(value),
+        },
+        RuleAction::Notify { channel, template } => {
+            let channel = match channel {
+                NotifyChannel::Email { to, subject } => NotifyChannel::Email {
+                    to: to.clone(),
+                    subject: Some(render_template(
+                        subject.as_deref().unwrap_or(&rule.name),
+                        rule,
+                        address,
+                        value,
+                    )),
+                },
+                other => other.clone(),
+            };
+            RuleAction::Notify {
+                channel,
+                template: render_template(template, rule, address, value),
+            }
+        }
+        other => other.clone(),
+    }
+}
+
+/// Replace `{address}`, `{value}`, `{rule}` and `{name}` in a notify template.
+fn render_template(template: &str, rule: &Rule, address: &str, value: &Value) -> String {
+    template
+        .replace("{address}", address)
+        .replace("{value}", &value_to_string(value))
+        .replace("{rule}", &rule.id)
+        .replace("{name}", &rule.name)
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
 }
 
 impl Default for RulesEngine {
@@ -325,6 +506,7 @@ mod tests {
                 value,
             }],
             cooldown: None,
+            entity: None,
         }
     }
 
@@ -465,6 +647,7 @@ mod tests {
                     value: Some(Value::String("high temperature".to_string())),
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
 
@@ -509,6 +692,7 @@ mod tests {
                     },
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
 
@@ -606,6 +790,7 @@ mod tests {
                     value: Value::Bool(true),
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
 
@@ -630,6 +815,51 @@ mod tests {
         assert!(actions.is_empty());
     }
 
+    #[test]
+    fn test_entity_match() {
+        use crate::rule::EntityMatch;
+
+        let mut engine = RulesEngine::new();
+        let mut rule = make_rule("r1", "/sensor/**", "/alarm", Value::Bool(true));
+        rule.entity = Some(EntityMatch {
+            types: vec!["device".to_string()],
+            metadata: [("site".to_string(), "north".to_string())].into(),
+        });
+        engine.add_rule(rule).unwrap();
+
+        let mut device = EntityInfo {
+            id: "clasp:device1".to_string(),
+            entity_type: "device".to_string(),
+            name: "Door sensor".to_string(),
+            tags: vec![],
+            metadata: [("site".to_string(), "north".to_string())].into(),
+        };
+        let mut fire = |entity: Option<&EntityInfo>| {
+            engine
+                .evaluate_from(
+                    "/sensor/door",
+                    &Value::Bool(true),
+                    SignalType::Param,
+                    None,
+                    entity,
+                    |_| None,
+                )
+                .len()
+        };
+
+        assert_eq!(fire(Some(&device)), 1);
+        assert_eq!(fire(None), 0);
+        device
+            .metadata
+            .insert("site".to_string(), "south".to_string());
+        assert_eq!(fire(Some(&device)), 0);
+        device
+            .metadata
+            .insert("site".to_string(), "north".to_string());
+        device.entity_type = "user".to_string();
+        assert_eq!(fire(Some(&device)), 0);
+    }
+
     #[test]
     fn test_interval_rules() {
         let mut engine = RulesEngine::new();
@@ -638,7 +868,10 @@ mod tests {
                 id: "heartbeat".to_string(),
                 name: "Heartbeat".to_string(),
                 enabled: true,
-                trigger: Trigger::OnInterval { seconds: 30 },
+                trigger: Trigger::OnInterval {
+                    seconds: 30,
+                    jitter: Some(5),
+                },
                 conditions: vec![],
                 actions: vec![RuleAction::Publish {
                     address: "/system/heartbeat".to_string(),
@@ -646,12 +879,20 @@ mod tests {
                     value: None,
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
 
         let intervals = engine.interval_rules();
         assert_eq!(intervals.len(), 1);
-        assert_eq!(intervals[0], ("heartbeat".to_string(), 30));
+        assert_eq!(
+            intervals[0],
+            (
+                "heartbeat".to_string(),
+                Duration::from_secs(30),
+                Duration::from_secs(5)
+            )
+        );
     }
 
     #[test]
@@ -662,7 +903,10 @@ mod tests {
                 id: "heartbeat".to_string(),
                 name: "Heartbeat".to_string(),
                 enabled: true,
-                trigger: Trigger::OnInterval { seconds: 30 },
+                trigger: Trigger::OnInterval {
+                    seconds: 30,
+                    jitter: None,
+                },
                 conditions: vec![],
                 actions: vec![RuleAction::Publish {
                     address: "/system/heartbeat".to_string(),
@@ -670,6 +914,7 @@ mod tests {
                     value: None,
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
 
@@ -687,7 +932,10 @@ mod tests {
                 id: "conditional_interval".to_string(),
                 name: "Conditional interval".to_string(),
                 enabled: true,
-                trigger: Trigger::OnInterval { seconds: 10 },
+                trigger: Trigger::OnInterval {
+                    seconds: 10,
+                    jitter: None,
+                },
                 conditions: vec![Condition {
                     address: "/mode".to_string(),
                     op: CompareOp::Eq,
@@ -698,6 +946,7 @@ mod tests {
                     value: Value::Bool(true),
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
 
@@ -723,13 +972,17 @@ mod tests {
             id: "disabled_interval".to_string(),
             name: "Disabled".to_string(),
             enabled: false,
-            trigger: Trigger::OnInterval { seconds: 5 },
+            trigger: Trigger::OnInterval {
+                seconds: 5,
+                jitter: None,
+            },
             conditions: vec![],
             actions: vec![RuleAction::Set {
                 address: "/x".to_string(),
                 value: Value::Null,
             }],
             cooldown: None,
+            entity: None,
         };
         engine.add_rule(rule).unwrap();
 
@@ -743,4 +996,154 @@ mod tests {
         let actions = engine.evaluate_interval("nonexistent", |_| None);
         assert!(actions.is_empty());
     }
+
+    fn schedule_rule(id: &str, cron: &str) -> Rule {
+        Rule {
+            id: id.to_string(),
+            name: format!("Scheduled {}", id),
+            enabled: true,
+            trigger: Trigger::OnSchedule {
+                cron: cron.to_string(),
+            },
+            conditions: vec![],
+            actions: vec![RuleAction::Set {
+                address: "/lights/house/dimmer".to_string(),
+                value: Value::Float(0.2),
+            }],
+            cooldown: None,
+            entity: None,
+        }
+    }
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 3, 2)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schedule_rules_due() {
+        let mut engine = RulesEngine::new();
+        engine.add_rule(schedule_rule("dim", "0 18 * * *")).unwrap();
+        engine
+            .add_rule(schedule_rule("quarter", "*/15 * * * *"))
+            .unwrap();
+
+        assert_eq!(engine.next_scheduled(at(17, 50)), Some(at(18, 0)));
+        let mut due = engine.due_scheduled(at(17, 59), at(18, 0));
+        due.sort();
+        assert_eq!(due, vec!["dim".to_string(), "quarter".to_string()]);
+        assert!(engine.due_scheduled(at(18, 0), at(18, 14)).is_empty());
+
+        engine.set_enabled("quarter", false).unwrap();
+        assert_eq!(
+            engine.next_scheduled(at(18, 0)),
+            Some(at(18, 0) + chrono::Duration::days(1))
+        );
+
+        let actions = engine.evaluate_scheduled("dim", |_| None);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].origin, "schedule:dim");
+    }
+
+    #[test]
+    fn test_schedule_rule_invalid_cron() {
+        let mut engine = RulesEngine::new();
+        let result = engine.add_rule(schedule_rule("bad", "0 25 * * *"));
+        assert!(matches!(result, Err(RulesError::InvalidRule(_))));
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn test_schedule_rule_replaced_and_removed() {
+        let mut engine = RulesEngine::new();
+        engine.add_rule(schedule_rule("dim", "0 18 * * *")).unwrap();
+        engine
+            .add_rule(make_rule("dim", "/x", "/y", Value::Null))
+            .unwrap();
+        assert!(engine.schedule_rules().is_empty());
+
+        engine.add_rule(schedule_rule("dim", "0 18 * * *")).unwrap();
+        engine.remove_rule("dim").unwrap();
+        assert_eq!(engine.next_scheduled(at(0, 0)), None);
+    }
+
+    #[test]
+    fn test_notify_template_rendered_from_trigger() {
+        let mut engine = RulesEngine::new();
+        engine
+            .add_rule(Rule {
+                id: "hot".to_string(),
+                name: "Too hot".to_string(),
+                enabled: true,
+                trigger: Trigger::OnThreshold {
+                    address: "/sensor/temp".to_string(),
+                    above: Some(30.0),
+                    below: None,
+                },
+                conditions: vec![],
+                actions: vec![RuleAction::Notify {
+                    channel: NotifyChannel::Email {
+                        to: vec!["ops@example.com".to_string()],
+                        subject: None,
+                    },
+                    template: "{address} is {value} ({rule})".to_string(),
+                }],
+                cooldown: None,
+                entity: None,
+            })
+            .unwrap();
+
+        let actions = engine.evaluate(
+            "/sensor/temp",
+            &Value::Float(31.5),
+            SignalType::Param,
+            None,
+            |_| None,
+        );
+        assert_eq!(actions.len(), 1);
+        match &actions[0].action {
+            RuleAction::Notify { channel, template } => {
+                assert_eq!(template, "/sensor/temp is 31.5 (hot)");
+                assert_eq!(
+                    channel,
+                    &NotifyChannel::Email {
+                        to: vec!["ops@example.com".to_string()],
+                        subject: Some("Too hot".to_string()),
+                    }
+                );
+            }
+            other => panic!("expected Notify, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_notify_deserializes_from_json() {
+        let json = r#"{
+            "id": "door",
+            "name": "Door opened",
+            "enabled": true,
+            "trigger": { "OnEvent": { "pattern": "/door/*" } },
+            "actions": [
+                { "Notify": { "channel": { "Sms": { "to": ["+15550100"] } }, "template": "{address}" } },
+                { "Notify": { "channel": { "Webhook": { "url": "http://hooks.local/door" } }, "template": "{value}" } }
+            ]
+        }"#;
+        let rule: Rule = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            rule.actions[0],
+            RuleAction::Notify {
+                channel: NotifyChannel::Sms { .. },
+                ..
+            }
+        ));
+        assert!(matches!(
+            rule.actions[1],
+            RuleAction::Notify {
+                channel: NotifyChannel::Webhook { .. },
+                ..
+            }
+        ));
+    }
 }
@@ -24,6 +24,10 @@ pub enum RulesError {
     /// Cooldown active
     #[error("rule {0} is in cooldown")]
     Cooldown(String),
+
+    /// Notification delivery failed
+    #[error("notify error: {0}")]
+    Notify(String),
 }
 
 /// Result type for rules operations
@@ -6,9 +6,14 @@
 //!
 //! # Features
 //!
-//! - **Trigger types**: OnChange (pattern), OnThreshold, OnEvent, OnInterval
+//! - **Trigger types**: OnChange (pattern), OnThreshold, OnEvent, OnInterval,
+//!   OnSchedule (cron)
 //! - **Conditions**: Compare current state values before firing
-//! - **Actions**: Set params, publish events, transform trigger values
+//! - **Entity matching**: Only fire for changes made by registry entities of
+//!   a given type or with given metadata
+//! - **Actions**: Set params, publish events, transform trigger values,
+//!   send notifications (email, SMS, webhook), run rhai scripts (`scripting`
+//!   feature)
 //! - **Loop prevention**: Actions from rules are marked with `origin: "rule:{id}"`
 //!   and skip rule evaluation
 //! - **Cooldown**: Minimum time between rule firings
@@ -34,6 +39,7 @@
 //!         value: Value::Float(1.0),
 //!     }],
 //!     cooldown: None,
+//!     entity: None,
 //! }).unwrap();
 //!
 //! let actions = engine.evaluate(
@@ -49,8 +55,18 @@
 
 pub mod engine;
 pub mod error;
+pub mod notify;
 pub mod rule;
+pub mod schedule;
+#[cfg(feature = "scripting")]
+pub mod script;
 
 pub use engine::{PendingAction, RulesEngine};
 pub use error::{Result, RulesError};
-pub use rule::{CompareOp, Condition, Rule, RuleAction, Transform, Trigger};
+pub use notify::{Notification, Notifier, NotifierConfig, NotifyBackend};
+pub use rule::{
+    CompareOp, Condition, EntityMatch, NotifyChannel, Rule, RuleAction, Transform, Trigger,
+};
+pub use schedule::CronSchedule;
+#[cfg(feature = "scripting")]
+pub use script::ScriptLanguage;
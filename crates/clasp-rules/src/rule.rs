@@ -1,7 +1,8 @@
 //! Rule definitions and types
 
-use clasp_core::{SignalType, Value};
+use clasp_core::{EntityInfo, SignalType, Value};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
 /// A rule definition that triggers actions based on signal changes.
@@ -23,6 +24,37 @@ pub struct Rule {
     /// Minimum time between firings (None = no cooldown)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cooldown: Option<Duration>,
+    /// Only fire for changes made by a matching registry entity
+    /// (None = any author). Ignored for interval and schedule triggers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<EntityMatch>,
+}
+
+/// Restricts a rule to changes made by sessions authenticated as a
+/// registry entity
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityMatch {
+    /// Entity types allowed to trigger the rule (empty = any type)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    /// Registry metadata the entity must carry, by exact value
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl EntityMatch {
+    /// Check whether the author's entity matches. Changes by sessions
+    /// without an entity never match.
+    pub fn matches(&self, entity: Option<&EntityInfo>) -> bool {
+        let Some(entity) = entity else {
+            return false;
+        };
+        (self.types.is_empty() || self.types.contains(&entity.entity_type))
+            && self
+                .metadata
+                .iter()
+                .all(|(key, value)| entity.metadata.get(key) == Some(value))
+    }
 }
 
 /// What causes a rule to evaluate
@@ -41,7 +73,16 @@ pub enum Trigger {
     /// Fires when an event matching the pattern is published
     OnEvent { pattern: String },
     /// Fires periodically
-    OnInterval { seconds: u64 },
+    OnInterval {
+        seconds: u64,
+        /// Random delay of up to this many seconds added to each firing, so
+        /// relays running the same rules don't fire in lockstep
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        jitter: Option<u64>,
+    },
+    /// Fires at wall-clock times given by a cron expression
+    /// (see [`crate::schedule`]), e.g. `"0 18 * * *"` for every day at 18:00
+    OnSchedule { cron: String },
 }
 
 /// A condition that must be true for a rule to fire
@@ -127,6 +168,40 @@ pub enum RuleAction {
     },
     /// Delay before the next action in the sequence
     Delay { milliseconds: u64 },
+    /// Send a notification through an external channel.
+    ///
+    /// `template` may reference `{address}`, `{value}`, `{rule}` and `{name}`;
+    /// the engine renders it from the trigger before returning the action.
+    Notify {
+        channel: NotifyChannel,
+        template: String,
+    },
+    /// Run a script that reads state and emits `Set` and `Publish` actions.
+    ///
+    /// The engine runs the script when the rule fires and returns the actions
+    /// it emitted in its place (see [`crate::script`]).
+    #[cfg(feature = "scripting")]
+    Script {
+        #[serde(default)]
+        language: crate::script::ScriptLanguage,
+        source: String,
+    },
+}
+
+/// Delivery channel for [`RuleAction::Notify`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NotifyChannel {
+    /// Email via the configured SMTP backend
+    Email {
+        to: Vec<String>,
+        /// Subject line, templated like the body (defaults to the rule name)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subject: Option<String>,
+    },
+    /// SMS via the configured Twilio-compatible backend
+    Sms { to: Vec<String> },
+    /// JSON POST to an HTTP endpoint
+    Webhook { url: String },
 }
 
 /// Value transformations for SetFromTrigger
@@ -181,7 +256,7 @@ impl Trigger {
             Trigger::OnChange { pattern } => Some(pattern),
             Trigger::OnThreshold { address, .. } => Some(address),
             Trigger::OnEvent { pattern } => Some(pattern),
-            Trigger::OnInterval { .. } => None,
+            Trigger::OnInterval { .. } | Trigger::OnSchedule { .. } => None,
         }
     }
 
@@ -199,7 +274,7 @@ impl Trigger {
                 signal_type == SignalType::Event
                     && clasp_core::address::glob_match(pattern, address)
             }
-            Trigger::OnInterval { .. } => false,
+            Trigger::OnInterval { .. } | Trigger::OnSchedule { .. } => false,
         }
     }
 }
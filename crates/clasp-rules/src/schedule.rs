@@ -0,0 +1,334 @@
+//! Cron expressions for `OnSchedule` triggers
+//!
+//! Standard five-field cron: `minute hour day-of-month month day-of-week`.
+//! Each field accepts `*`, a number, a range (`1-5`), a list (`0,30`) and a
+//! step (`*/15`, `10-50/10`). Months and weekdays also accept three-letter
+//! names (`JAN`, `MON`), and Sunday is both `0` and `7`. The macros
+//! `@hourly`, `@daily` (`@midnight`), `@weekly`, `@monthly` and `@yearly`
+//! (`@annually`) are shorthands for the usual expressions.
+//!
+//! As in Vixie cron, when both day-of-month and day-of-week are restricted
+//! a day matches if either does.
+//!
+//! Times are wall-clock times without a zone; the router evaluates them in
+//! the host's local time.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Result, RulesError};
+
+/// How far ahead to look for the next match. Covers leap days (Feb 29 can be
+/// four years away, or eight across a skipped century leap year).
+const SEARCH_DAYS: i64 = 366 * 8 + 2;
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    days_of_week: u8,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parse a cron expression
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expanded = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid(
+                expr,
+                format!("expected 5 fields, found {}", fields.len()),
+            ));
+        }
+
+        let minutes = parse_field(fields[0], 0, 59, &[]).map_err(|e| invalid(expr, e))?;
+        let hours = parse_field(fields[1], 0, 23, &[]).map_err(|e| invalid(expr, e))?;
+        let days_of_month = parse_field(fields[2], 1, 31, &[]).map_err(|e| invalid(expr, e))?;
+        let months = parse_field(fields[3], 1, 12, &MONTHS).map_err(|e| invalid(expr, e))?;
+        let mut days_of_week =
+            parse_field(fields[4], 0, 7, &WEEKDAYS).map_err(|e| invalid(expr, e))?;
+        // 7 is Sunday too
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            source: expr.trim().to_string(),
+            minutes,
+            hours: hours as u32,
+            days_of_month: days_of_month as u32,
+            months: months as u16,
+            days_of_week: days_of_week as u8,
+            dom_restricted: !fields[2].starts_with('*'),
+            dow_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// The expression as written
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the schedule fires at `time` (seconds are ignored)
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        self.matches_date(time.date())
+            && self.hours & (1 << time.hour()) != 0
+            && self.minutes & (1 << time.minute()) != 0
+    }
+
+    /// The first time strictly after `after` at which the schedule fires
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        // Start at the next whole minute
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut date = start.date();
+        let mut from = start.time();
+
+        for _ in 0..SEARCH_DAYS {
+            if self.matches_date(date) {
+                if let Some(time) = self.first_time_from(from) {
+                    return Some(date.and_time(time));
+                }
+            }
+            date = date.succ_opt()?;
+            from = NaiveTime::MIN;
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let dom = self.days_of_month & (1 << date.day()) != 0;
+        let dow = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.dom_restricted && self.dow_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+
+    fn first_time_from(&self, from: NaiveTime) -> Option<NaiveTime> {
+        for hour in from.hour()..24 {
+            if self.hours & (1 << hour) == 0 {
+                continue;
+            }
+            let first_minute = if hour == from.hour() {
+                from.minute()
+            } else {
+                0
+            };
+            for minute in first_minute..60 {
+                if self.minutes & (1 << minute) != 0 {
+                    return NaiveTime::from_hms_opt(hour, minute, 0);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = RulesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn invalid(expr: &str, reason: String) -> RulesError {
+    RulesError::InvalidRule(format!("invalid cron expression '{}': {}", expr, reason))
+}
+
+/// Parse one field into a bitmask of allowed values
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+) -> std::result::Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step '{}'", step))?;
+                if step == 0 {
+                    return Err("step must be at least 1".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (
+                parse_value(a, min, max, names)?,
+                parse_value(b, min, max, names)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, names)?;
+            // `5/15` means from 5 to the end in steps of 15
+            if step > 1 {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(format!("range '{}' is backwards", range));
+        }
+
+        let mut value = start;
+        while value <= end {
+            mask |= 1 << value;
+            value += step;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(s: &str, min: u32, max: u32, names: &[&str]) -> std::result::Result<u32, String> {
+    let upper = s.to_ascii_uppercase();
+    if let Some(index) = names.iter().position(|name| *name == upper) {
+        // Month names start at 1, weekday names at 0
+        return Ok(index as u32 + min);
+    }
+    let value: u32 = s.parse().map_err(|_| format!("invalid value '{}'", s))?;
+    if value < min || value > max {
+        return Err(format!("{} is outside {}-{}", value, min, max));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_daily_at_six_pm() {
+        let cron = CronSchedule::parse("0 18 * * *").unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 3, 1, 9, 30)),
+            Some(at(2026, 3, 1, 18, 0))
+        );
+        // Exactly at the fire time: the next one is tomorrow
+        assert_eq!(
+            cron.next_after(at(2026, 3, 1, 18, 0)),
+            Some(at(2026, 3, 2, 18, 0))
+        );
+        assert!(cron.matches(at(2026, 3, 1, 18, 0)));
+        assert!(!cron.matches(at(2026, 3, 1, 18, 1)));
+    }
+
+    #[test]
+    fn test_steps_ranges_and_names() {
+        let cron = CronSchedule::parse("*/15 9-17 * * MON-FRI").unwrap();
+        // Saturday 2026-03-07 -> Monday 09:00
+        assert_eq!(
+            cron.next_after(at(2026, 3, 7, 12, 0)),
+            Some(at(2026, 3, 9, 9, 0))
+        );
+        assert_eq!(
+            cron.next_after(at(2026, 3, 9, 9, 1)),
+            Some(at(2026, 3, 9, 9, 15))
+        );
+        assert_eq!(
+            cron.next_after(at(2026, 3, 9, 17, 45)),
+            Some(at(2026, 3, 10, 9, 0))
+        );
+    }
+
+    #[test]
+    fn test_day_of_month_or_day_of_week() {
+        // The 1st of the month or any Sunday
+        let cron = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert!(cron.matches(at(2026, 3, 1, 0, 0))); // Sunday the 1st
+        assert!(cron.matches(at(2026, 3, 8, 0, 0))); // Sunday
+        assert!(cron.matches(at(2026, 4, 1, 0, 0))); // Wednesday the 1st
+        assert!(!cron.matches(at(2026, 4, 2, 0, 0)));
+    }
+
+    #[test]
+    fn test_leap_day() {
+        let cron = CronSchedule::parse("0 12 29 FEB *").unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 1, 1, 0, 0)),
+            Some(at(2028, 2, 29, 12, 0))
+        );
+        // Never happens
+        let cron = CronSchedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(cron.next_after(at(2026, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_macros() {
+        assert_eq!(
+            CronSchedule::parse("@daily")
+                .unwrap()
+                .next_after(at(2026, 3, 1, 9, 0)),
+            Some(at(2026, 3, 2, 0, 0))
+        );
+        assert_eq!(
+            CronSchedule::parse("@hourly")
+                .unwrap()
+                .next_after(at(2026, 3, 1, 9, 0)),
+            Some(at(2026, 3, 1, 10, 0))
+        );
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expr in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 * FOO *",
+        ] {
+            assert!(
+                matches!(CronSchedule::parse(expr), Err(RulesError::InvalidRule(_))),
+                "{:?} should not parse",
+                expr
+            );
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Script actions evaluated with [rhai](https://rhai.rs)
+//!
+//! A [`RuleAction::Script`] runs when its rule fires and expands into the
+//! `Set` and `Publish` actions it emits. Scripts see these constants:
+//!
+//! - `address`: the trigger address (`""` for interval and schedule rules)
+//! - `value`: the trigger value (`()` for interval and schedule rules)
+//! - `rule`: the rule ID
+//!
+//! and these functions:
+//!
+//! - `get(address)`: current value of a param, or `()` if unset
+//! - `set(address, value)`: emit a `Set` action
+//! - `publish(address)` / `publish(address, value)`: emit an event
+//!
+//! ```rhai
+//! let total = get("/power/a") + get("/power/b");
+//! set("/power/total", total);
+//! if total > 3000.0 {
+//!     publish("/alerts/overload", total);
+//! }
+//! ```
+//!
+//! `get()` reads are resolved before the script runs, so its argument must
+//! be a string literal; any other address reads as `()`. Scripts are compiled
+//! when the rule is added and stopped after a fixed number of operations, so
+//! a runaway loop can't stall the router.
+
+use clasp_core::{SignalType, Value};
+use parking_lot::{Mutex, RwLock};
+use rhai::{Array, Blob, Dynamic, Engine, ImmutableString, Map, Scope, AST};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{Result, RulesError};
+use crate::rule::{Rule, RuleAction};
+
+/// Operation budget for a single script run
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Language of a [`RuleAction::Script`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScriptLanguage {
+    /// [rhai](https://rhai.rs)
+    #[default]
+    Rhai,
+}
+
+struct Compiled {
+    ast: AST,
+    /// Literal `get()` addresses, looked up before each run
+    reads: Vec<String>,
+}
+
+/// Compiles and runs the script actions of every rule
+pub(crate) struct ScriptHost {
+    engine: Engine,
+    /// Keyed by rule ID and action index
+    compiled: HashMap<(String, usize), Compiled>,
+    reads: Arc<RwLock<HashMap<String, Dynamic>>>,
+    emitted: Arc<Mutex<Vec<RuleAction>>>,
+}
+
+impl ScriptHost {
+    pub(crate) fn new() -> Self {
+        let reads: Arc<RwLock<HashMap<String, Dynamic>>> = Arc::default();
+        let emitted: Arc<Mutex<Vec<RuleAction>>> = Arc::default();
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let lookup = reads.clone();
+        engine.register_fn("get", move |address: ImmutableString| -> Dynamic {
+            lookup
+                .read()
+                .get(address.as_str())
+                .cloned()
+                .unwrap_or(Dynamic::UNIT)
+        });
+        let sink = emitted.clone();
+        engine.register_fn("set", move |address: ImmutableString, value: Dynamic| {
+            sink.lock().push(RuleAction::Set {
+                address: address.to_string(),
+                value: from_dynamic(value),
+            });
+        });
+        let sink = emitted.clone();
+        engine.register_fn("publish", move |address: ImmutableString| {
+            sink.lock().push(RuleAction::Publish {
+                address: address.to_string(),
+                signal: SignalType::Event,
+                value: None,
+            });
+        });
+        let sink = emitted.clone();
+        engine.register_fn(
+            "publish",
+            move |address: ImmutableString, value: Dynamic| {
+                sink.lock().push(RuleAction::Publish {
+                    address: address.to_string(),
+                    signal: SignalType::Event,
+                    value: Some(from_dynamic(value)),
+                });
+            },
+        );
+
+        Self {
+            engine,
+            compiled: HashMap::new(),
+            reads,
+            emitted,
+        }
+    }
+
+    /// Compile the script actions of a rule, replacing any previous version
+    pub(crate) fn add_rule(&mut self, rule: &Rule) -> Result<()> {
+        let mut compiled = Vec::new();
+        for (index, action) in rule.actions.iter().enumerate() {
+            if let RuleAction::Script { language, source } = action {
+                match language {
+                    ScriptLanguage::Rhai => {
+                        let ast = self.engine.compile(source).map_err(|e| {
+                            RulesError::InvalidRule(format!("script action {}: {}", index, e))
+                        })?;
+                        compiled.push((
+                            index,
+                            Compiled {
+                                ast,
+                                reads: literal_gets(source),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        self.remove_rule(&rule.id);
+        for (index, script) in compiled {
+            self.compiled.insert((rule.id.clone(), index), script);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove_rule(&mut self, id: &str) {
+        self.compiled.retain(|(rule_id, _), _| rule_id != id);
+    }
+
+    /// Run the script at `index` of `rule` and return the actions it emitted
+    ///
+    /// A script that fails at runtime emits nothing.
+    pub(crate) fn run<F>(
+        &self,
+        rule: &Rule,
+        index: usize,
+        address: &str,
+        value: &Value,
+        state_lookup: &F,
+    ) -> Vec<RuleAction>
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        let Some(script) = self.compiled.get(&(rule.id.clone(), index)) else {
+            return vec![];
+        };
+
+        {
+            let mut reads = self.reads.write();
+            reads.clear();
+            for read in &script.reads {
+                if let Some(current) = state_lookup(read) {
+                    reads.insert(read.clone(), to_dynamic(&current));
+                }
+            }
+        }
+        self.emitted.lock().clear();
+
+        let mut scope = Scope::new();
+        scope.push_constant("address", address.to_string());
+        scope.push_constant("value", to_dynamic(value));
+        scope.push_constant("rule", rule.id.clone());
+
+        let result = self.engine.run_ast_with_scope(&mut scope, &script.ast);
+        let emitted = std::mem::take(&mut *self.emitted.lock());
+        match result {
+            Ok(()) => emitted,
+            Err(e) => {
+                tracing::warn!("Rule {} script action {} failed: {}", rule.id, index, e);
+                vec![]
+            }
+        }
+    }
+}
+
+/// Addresses passed as string literals to `get()`
+fn literal_gets(source: &str) -> Vec<String> {
+    let mut reads = Vec::new();
+    let mut rest = source;
+    while let Some(pos) = rest.find("get(") {
+        // Skip method calls and longer names ending in `get`
+        let preceded = rest[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.');
+        rest = &rest[pos + 4..];
+        if preceded {
+            continue;
+        }
+        if let Some(arg) = rest.trim_start().strip_prefix('"') {
+            if let Some(end) = arg.find('"') {
+                let read = arg[..end].to_string();
+                if !reads.contains(&read) {
+                    reads.push(read);
+                }
+            }
+        }
+    }
+    reads
+}
+
+fn to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Bool(b) => Dynamic::from_bool(*b),
+        Value::Int(i) => Dynamic::from_int(*i),
+        Value::Float(f) => Dynamic::from_float(*f),
+        Value::String(s) => s.clone().into(),
+        Value::Array(items) => Dynamic::from_array(items.iter().map(to_dynamic).collect()),
+        Value::Map(map) => Dynamic::from_map(
+            map.iter()
+                .map(|(k, v)| (k.as_str().into(), to_dynamic(v)))
+                .collect(),
+        ),
+        Value::Bytes(bytes) => Dynamic::from_blob(bytes.clone()),
+    }
+}
+
+fn from_dynamic(value: Dynamic) -> Value {
+    if value.is_unit() {
+        Value::Null
+    } else if let Ok(b) = value.as_bool() {
+        Value::Bool(b)
+    } else if let Ok(i) = value.as_int() {
+        Value::Int(i)
+    } else if let Ok(f) = value.as_float() {
+        Value::Float(f)
+    } else if value.is_string() {
+        Value::String(value.into_string().unwrap_or_default())
+    } else if value.is_array() {
+        Value::Array(
+            value
+                .cast::<Array>()
+                .into_iter()
+                .map(from_dynamic)
+                .collect(),
+        )
+    } else if value.is_map() {
+        Value::Map(
+            value
+                .cast::<Map>()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), from_dynamic(v)))
+                .collect(),
+        )
+    } else if value.is_blob() {
+        Value::Bytes(value.cast::<Blob>())
+    } else {
+        Value::String(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Trigger;
+
+    fn script_rule(source: &str) -> Rule {
+        Rule {
+            id: "script".to_string(),
+            name: "Script".to_string(),
+            enabled: true,
+            trigger: Trigger::OnChange {
+                pattern: "/power/**".to_string(),
+            },
+            conditions: vec![],
+            actions: vec![RuleAction::Script {
+                language: ScriptLanguage::Rhai,
+                source: source.to_string(),
+            }],
+            cooldown: None,
+            entity: None,
+        }
+    }
+
+    #[test]
+    fn test_literal_gets() {
+        assert_eq!(
+            literal_gets(r#"get("/a") + get( "/b" ) + m.get("x") + target("/c") + get("/a")"#),
+            vec!["/a".to_string(), "/b".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_script_reads_state_and_emits_actions() {
+        let rule = script_rule(
+            r#"
+            let total = value + get("/power/b");
+            set("/power/total", total);
+            if total > 10.0 { publish("/alerts/overload", total); }
+            publish(rule);
+            "#,
+        );
+        let mut host = ScriptHost::new();
+        host.add_rule(&rule).unwrap();
+
+        let lookup = |addr: &str| (addr == "/power/b").then_some(Value::Float(8.0));
+        let actions = host.run(&rule, 0, "/power/a", &Value::Float(4.0), &lookup);
+
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(
+            &actions[0],
+            RuleAction::Set { address, value } if address == "/power/total" && *value == Value::Float(12.0)
+        ));
+        assert!(matches!(
+            &actions[1],
+            RuleAction::Publish { address, value: Some(Value::Float(v)), .. }
+                if address == "/alerts/overload" && *v == 12.0
+        ));
+        assert!(matches!(
+            &actions[2],
+            RuleAction::Publish { address, value: None, .. } if address == "script"
+        ));
+    }
+
+    #[test]
+    fn test_script_compile_error_rejects_rule() {
+        let mut host = ScriptHost::new();
+        assert!(matches!(
+            host.add_rule(&script_rule("set(\"/x\", ")),
+            Err(RulesError::InvalidRule(_))
+        ));
+    }
+
+    #[test]
+    fn test_runaway_script_emits_nothing() {
+        let rule = script_rule(r#"set("/x", 1); loop {}"#);
+        let mut host = ScriptHost::new();
+        host.add_rule(&rule).unwrap();
+        assert!(host
+            .run(&rule, 0, "/power/a", &Value::Null, &|_: &str| None)
+            .is_empty());
+    }
+}
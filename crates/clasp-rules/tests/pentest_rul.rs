@@ -21,6 +21,7 @@ fn make_rule(id: &str, pattern: &str, target: &str, value: Value) -> Rule {
             value,
         }],
         cooldown: None,
+        entity: None,
     }
 }
 
@@ -86,6 +87,7 @@ fn test_rul_02_rule_count_exhaustion() {
                     value: Value::Float(1.0),
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
     }
@@ -173,13 +175,17 @@ fn test_rul_04_cooldown_bypass() {
             id: "interval_cd".to_string(),
             name: "Interval with cooldown".to_string(),
             enabled: true,
-            trigger: Trigger::OnInterval { seconds: 5 },
+            trigger: Trigger::OnInterval {
+                seconds: 5,
+                jitter: None,
+            },
             conditions: vec![],
             actions: vec![RuleAction::Set {
                 address: "/x".to_string(),
                 value: Value::Null,
             }],
             cooldown: Some(Duration::from_secs(60)),
+            entity: None,
         })
         .unwrap();
 
@@ -211,6 +217,7 @@ fn test_rul_05_set_from_trigger_scope_escape() {
                 transform: Transform::Identity,
             }],
             cooldown: None,
+            entity: None,
         })
         .unwrap();
 
@@ -297,6 +304,7 @@ fn test_rul_06_malformed_rules_json() {
             value: Value::Null,
         }],
         cooldown: None,
+        entity: None,
     });
     assert!(result.is_err(), "empty rule ID must be rejected");
 
@@ -311,6 +319,7 @@ fn test_rul_06_malformed_rules_json() {
         conditions: vec![],
         actions: vec![],
         cooldown: None,
+        entity: None,
     });
     assert!(result.is_err(), "rule with no actions must be rejected");
 }
@@ -329,13 +338,17 @@ fn test_rul_07_interval_flood() {
                 id: format!("int_{}", i),
                 name: format!("Interval {}", i),
                 enabled: true,
-                trigger: Trigger::OnInterval { seconds: 10 },
+                trigger: Trigger::OnInterval {
+                    seconds: 10,
+                    jitter: None,
+                },
                 conditions: vec![],
                 actions: vec![RuleAction::Set {
                     address: format!("/output/{}", i),
                     value: Value::Int(i),
                 }],
                 cooldown: None,
+                entity: None,
             })
             .unwrap();
     }
@@ -354,7 +367,7 @@ fn test_rul_07_interval_flood() {
     assert_eq!(intervals.len(), 10);
 
     // Evaluate each interval rule and verify origin tagging
-    for (id, _seconds) in &intervals {
+    for (id, _period, _jitter) in &intervals {
         let actions = engine.evaluate_interval(id, |_| None);
         assert_eq!(
             actions.len(),
@@ -101,6 +101,7 @@ fn ttl_mode_str(ttl: Option<&Ttl>) -> &'static str {
         Some(Ttl::Sliding(_)) => "sliding",
         Some(Ttl::Absolute(_)) => "absolute",
         Some(Ttl::Never) => "never",
+        Some(Ttl::Session) => "session",
         None => "none",
     }
 }
@@ -118,6 +119,7 @@ pub fn str_to_ttl(mode: &str, secs: u32) -> Option<Ttl> {
         "sliding" => Some(Ttl::Sliding(secs)),
         "absolute" => Some(Ttl::Absolute(secs)),
         "never" => Some(Ttl::Never),
+        "session" => Some(Ttl::Session),
         _ => None,
     }
 }
@@ -501,7 +501,7 @@ impl DefraStateStore {
             .filter(|entry| {
                 let v = entry.value();
                 match v.ttl {
-                    Some(Ttl::Never) => false,
+                    Some(Ttl::Never) | Some(Ttl::Session) => false,
                     Some(Ttl::Sliding(secs)) => {
                         let cutoff = now.saturating_sub(secs as u64 * 1_000_000);
                         v.last_accessed < cutoff
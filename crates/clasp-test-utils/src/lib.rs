@@ -112,7 +112,24 @@ impl TestRouter {
             gesture_coalesce_interval_ms: 16,
             max_messages_per_second: 0, // Disable rate limiting for tests
             rate_limiting_enabled: false,
+            rate_limits: clasp_router::RateLimitPolicy::default(),
             state_config: clasp_router::RouterStateConfig::unlimited(), // No TTL in tests
+            session_handoff: clasp_router::HandoffPolicy::Off,
+            session_limit: clasp_router::SessionLimit::default(),
+            durable_session_ttl: 0,
+            validation: clasp_router::ValidationConfig::default(),
+            overload: clasp_router::OverloadConfig::default(),
+            quota: clasp_router::QuotaConfig::default(),
+            topic_aliases: clasp_router::TopicAliasConfig::default(),
+            shadow: clasp_router::ShadowConfig::default(),
+            presence: false,
+            transport_policies: Default::default(),
+            aggregates: Vec::new(),
+            schema: Default::default(),
+            address_policy: Default::default(),
+            replay: Default::default(),
+            reserved: Default::default(),
+            delivery_stats: false,
         })
         .await
     }
@@ -0,0 +1,786 @@
+//! permessage-deflate (RFC 7692) for the WebSocket transport.
+//!
+//! tungstenite has no extension support and rejects frames with RSV1 set, so
+//! [`DeflateStream`] sits between the socket and tungstenite and rewrites
+//! frames on the way through:
+//!
+//! - Inbound messages with RSV1 set are reassembled, inflated and handed to
+//!   tungstenite as a single uncompressed frame.
+//! - Outbound data frames are deflated and marked with RSV1.
+//!
+//! The stream passes the HTTP upgrade through untouched and switches on when
+//! it sees the handshake response (written by the server, read by the
+//! client) accept the extension. Both directions keep their LZ77 window
+//! across messages unless `*_no_context_takeover` was negotiated.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Extension token in `Sec-WebSocket-Extensions`
+pub(crate) const EXTENSION: &str = "permessage-deflate";
+
+/// Appended by a sync flush; stripped on send and restored on receive
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Messages smaller than this are sent uncompressed
+const MIN_COMPRESS_SIZE: usize = 64;
+
+/// Largest frame we'll parse (tungstenite's default message limit)
+const MAX_FRAME_SIZE: usize = 64 << 20;
+
+/// Outbound bytes buffered before writes apply backpressure
+const WRITE_HIGH_WATER: usize = 64 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+
+/// Which end of the connection this stream is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    Server,
+    Client,
+}
+
+/// Negotiated permessage-deflate parameters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DeflateParams {
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+}
+
+impl DeflateParams {
+    /// Pick the first offer in a client's `Sec-WebSocket-Extensions` that we
+    /// can honor. Offers that limit our window below 15 bits are declined,
+    /// since the deflate backend always uses a 32 KiB window.
+    pub(crate) fn accept_offer(header: &str) -> Option<Self> {
+        header.split(',').find_map(|offer| {
+            let mut params = Self::default();
+            for (name, value) in parse_extension(offer, EXTENSION)? {
+                match (name.as_str(), value) {
+                    ("server_no_context_takeover", None) => {
+                        params.server_no_context_takeover = true
+                    }
+                    ("client_no_context_takeover", None) => {}
+                    ("server_max_window_bits", Some(bits)) if bits == "15" => {}
+                    ("client_max_window_bits", None) => {}
+                    ("client_max_window_bits", Some(bits))
+                        if matches!(bits.parse::<u8>(), Ok(8..=15)) => {}
+                    _ => return None,
+                }
+            }
+            Some(params)
+        })
+    }
+
+    /// Parse the server's response to our (parameterless) offer. Returns
+    /// `Ok(None)` when the extension wasn't accepted.
+    pub(crate) fn from_response(header: &str) -> io::Result<Option<Self>> {
+        let mut accepted = None;
+        for extension in header.split(',') {
+            let Some(list) = parse_extension(extension, EXTENSION) else {
+                return Err(invalid(format!(
+                    "unexpected extension: {}",
+                    extension.trim()
+                )));
+            };
+            if accepted.is_some() {
+                return Err(invalid("permessage-deflate accepted twice"));
+            }
+            let mut params = Self::default();
+            for (name, value) in list {
+                match (name.as_str(), value) {
+                    ("server_no_context_takeover", None) => {
+                        params.server_no_context_takeover = true
+                    }
+                    ("client_no_context_takeover", None) => {
+                        params.client_no_context_takeover = true
+                    }
+                    ("server_max_window_bits", Some(bits))
+                        if matches!(bits.parse::<u8>(), Ok(8..=15)) => {}
+                    _ => return Err(invalid(format!("unsupported parameter: {}", name))),
+                }
+            }
+            accepted = Some(params);
+        }
+        Ok(accepted)
+    }
+
+    /// Value for the server's `Sec-WebSocket-Extensions` response header
+    pub(crate) fn to_header(self) -> String {
+        let mut header = EXTENSION.to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        header
+    }
+}
+
+/// Split `name; a; b=1` into its parameters if `name` matches.
+fn parse_extension(extension: &str, expected: &str) -> Option<Vec<(String, Option<String>)>> {
+    let mut parts = extension.split(';').map(str::trim);
+    if !parts.next()?.eq_ignore_ascii_case(expected) {
+        return None;
+    }
+    Some(
+        parts
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.split_once('=') {
+                Some((name, value)) => (
+                    name.trim().to_ascii_lowercase(),
+                    Some(value.trim().trim_matches('"').to_string()),
+                ),
+                None => (p.to_ascii_lowercase(), None),
+            })
+            .collect(),
+    )
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Parsed frame header.
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    header_len: usize,
+    payload_len: usize,
+}
+
+impl FrameHeader {
+    /// Parse a header from the front of `buf` (`None` if incomplete).
+    fn parse(buf: &[u8]) -> io::Result<Option<Self>> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+        let masked = buf[1] & 0x80 != 0;
+        let (payload_len, mut header_len) = match buf[1] & 0x7f {
+            126 => {
+                if buf.len() < 4 {
+                    return Ok(None);
+                }
+                (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4)
+            }
+            127 => {
+                if buf.len() < 10 {
+                    return Ok(None);
+                }
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&buf[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            len => (len as u64, 2),
+        };
+        if payload_len > MAX_FRAME_SIZE as u64 {
+            return Err(invalid("WebSocket frame too large"));
+        }
+        let mask = if masked {
+            if buf.len() < header_len + 4 {
+                return Ok(None);
+            }
+            let mut key = [0u8; 4];
+            key.copy_from_slice(&buf[header_len..header_len + 4]);
+            header_len += 4;
+            Some(key)
+        } else {
+            None
+        };
+        Ok(Some(Self {
+            fin: buf[0] & 0x80 != 0,
+            rsv1: buf[0] & 0x40 != 0,
+            opcode: buf[0] & 0x0f,
+            mask,
+            header_len,
+            payload_len: payload_len as usize,
+        }))
+    }
+
+    fn frame_len(&self) -> usize {
+        self.header_len + self.payload_len
+    }
+}
+
+fn apply_mask(data: &mut [u8], key: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= key[i % 4];
+    }
+}
+
+/// Append a complete, unfragmented frame to `out`.
+fn encode_frame(out: &mut Vec<u8>, rsv1: bool, opcode: u8, mask: Option<[u8; 4]>, payload: &[u8]) {
+    out.push(0x80 | if rsv1 { 0x40 } else { 0 } | opcode);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => out.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    let start = out.len();
+    if let Some(key) = mask {
+        out.extend_from_slice(&key);
+    }
+    let payload_start = out.len();
+    out.extend_from_slice(payload);
+    if let Some(key) = mask {
+        apply_mask(&mut out[payload_start..], key);
+    }
+    debug_assert!(out.len() - start >= payload.len());
+}
+
+/// A compressed message being reassembled from fragments.
+struct Inbound {
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    payload: Vec<u8>,
+}
+
+/// Per-connection compression state.
+struct Codec {
+    compress: Compress,
+    decompress: Decompress,
+    /// Reset our compressor after every message
+    reset_compress: bool,
+    /// Largest inbound message, compressed or inflated
+    max_message_size: usize,
+    inbound: Option<Inbound>,
+}
+
+impl Codec {
+    fn new(params: DeflateParams, role: Role, max_message_size: usize) -> Self {
+        Self {
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+            reset_compress: match role {
+                Role::Server => params.server_no_context_takeover,
+                Role::Client => params.client_no_context_takeover,
+            },
+            max_message_size,
+            inbound: None,
+        }
+    }
+
+    /// Move complete frames from `raw` to `out`, inflating compressed messages.
+    fn process_inbound(&mut self, raw: &mut Vec<u8>, out: &mut Vec<u8>) -> io::Result<()> {
+        let mut pos = 0;
+        while let Some(header) = FrameHeader::parse(&raw[pos..])? {
+            if raw.len() - pos < header.frame_len() {
+                break;
+            }
+            let frame = &raw[pos..pos + header.frame_len()];
+            pos += header.frame_len();
+
+            let is_data = matches!(header.opcode, OP_TEXT | OP_BINARY);
+            let continues_compressed = header.opcode == OP_CONTINUATION && self.inbound.is_some();
+            if !(is_data && header.rsv1) && !continues_compressed {
+                // Control frames and uncompressed messages pass through
+                out.extend_from_slice(frame);
+                continue;
+            }
+            if continues_compressed && header.rsv1 {
+                return Err(invalid("RSV1 set on a continuation frame"));
+            }
+
+            let mut payload = frame[header.header_len..].to_vec();
+            if let Some(key) = header.mask {
+                apply_mask(&mut payload, key);
+            }
+            let inbound = self.inbound.get_or_insert_with(|| Inbound {
+                opcode: header.opcode,
+                mask: header.mask,
+                payload: Vec::new(),
+            });
+            if inbound.payload.len() + payload.len() > self.max_message_size {
+                return Err(invalid("compressed WebSocket message too large"));
+            }
+            inbound.payload.extend_from_slice(&payload);
+
+            if header.fin {
+                let inbound = self.inbound.take().unwrap_or_else(|| unreachable!());
+                let inflated = self.inflate(&inbound.payload)?;
+                encode_frame(out, false, inbound.opcode, inbound.mask, &inflated);
+            }
+        }
+        raw.drain(..pos);
+        Ok(())
+    }
+
+    /// Move complete frames from `raw` to `out`, deflating data messages.
+    fn process_outbound(&mut self, raw: &mut Vec<u8>, out: &mut Vec<u8>) -> io::Result<()> {
+        let mut pos = 0;
+        while let Some(header) = FrameHeader::parse(&raw[pos..])? {
+            if raw.len() - pos < header.frame_len() {
+                break;
+            }
+            let frame = &raw[pos..pos + header.frame_len()];
+            pos += header.frame_len();
+
+            // tungstenite never fragments outgoing messages; anything else
+            // (control frames, small messages) goes out as-is
+            if !matches!(header.opcode, OP_TEXT | OP_BINARY)
+                || !header.fin
+                || header.rsv1
+                || header.payload_len < MIN_COMPRESS_SIZE
+            {
+                out.extend_from_slice(frame);
+                continue;
+            }
+
+            let mut payload = frame[header.header_len..].to_vec();
+            if let Some(key) = header.mask {
+                apply_mask(&mut payload, key);
+            }
+            let deflated = self.deflate(&payload)?;
+            encode_frame(out, true, header.opcode, header.mask, &deflated);
+        }
+        raw.drain(..pos);
+        Ok(())
+    }
+
+    fn deflate(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            if out.capacity() - out.len() < 64 {
+                out.reserve(out.capacity().max(1024));
+            }
+            self.compress
+                .compress_vec(&data[consumed..], &mut out, FlushCompress::Sync)
+                .map_err(|e| invalid(e.to_string()))?;
+            // Done once all input is in and the flush fit in the buffer
+            if self.compress.total_in() - start == data.len() as u64 && out.len() < out.capacity() {
+                break;
+            }
+        }
+        if out.ends_with(&TRAILER) {
+            out.truncate(out.len() - TRAILER.len());
+        }
+        if self.reset_compress {
+            self.compress.reset();
+        }
+        Ok(out)
+    }
+
+    fn inflate(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut input = Vec::with_capacity(data.len() + TRAILER.len());
+        input.extend_from_slice(data);
+        input.extend_from_slice(&TRAILER);
+
+        let mut out = Vec::with_capacity(data.len() * 4 + 64);
+        let start = self.decompress.total_in();
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if out.capacity() - out.len() < 1024 {
+                out.reserve(out.capacity().max(4096));
+            }
+            let before = (self.decompress.total_in(), self.decompress.total_out());
+            self.decompress
+                .decompress_vec(&input[consumed..], &mut out, FlushDecompress::Sync)
+                .map_err(|e| invalid(format!("invalid compressed message: {}", e)))?;
+            if out.len() > self.max_message_size {
+                return Err(invalid("compressed WebSocket message too large"));
+            }
+            let done = self.decompress.total_in() - start == input.len() as u64;
+            if done && out.len() < out.capacity() {
+                break;
+            }
+            if (self.decompress.total_in(), self.decompress.total_out()) == before {
+                return Err(invalid("invalid compressed message: no progress"));
+            }
+        }
+        Ok(out)
+    }
+}
+
+enum Phase {
+    /// Upgrade request/response still in flight
+    Handshake,
+    /// Extension not negotiated: bytes pass through
+    Plain,
+    Deflate(Box<Codec>),
+}
+
+/// Byte stream that applies permessage-deflate to the WebSocket frames
+/// passing through it (see the module docs).
+pub(crate) struct DeflateStream<S> {
+    inner: S,
+    role: Role,
+    max_message_size: usize,
+    phase: Phase,
+    read_raw: Vec<u8>,
+    read_ready: Vec<u8>,
+    read_pos: usize,
+    read_eof: bool,
+    write_raw: Vec<u8>,
+    write_ready: Vec<u8>,
+    write_pos: usize,
+}
+
+impl<S> DeflateStream<S> {
+    /// Wrap `inner`; inbound compressed messages over `max_message_size`
+    /// (before or after inflating) fail the stream
+    pub(crate) fn new(inner: S, role: Role, max_message_size: usize) -> Self {
+        Self {
+            inner,
+            role,
+            max_message_size,
+            phase: Phase::Handshake,
+            read_raw: Vec::new(),
+            read_ready: Vec::new(),
+            read_pos: 0,
+            read_eof: false,
+            write_raw: Vec::new(),
+            write_ready: Vec::new(),
+            write_pos: 0,
+        }
+    }
+
+    /// Leave the handshake phase based on the response header block.
+    fn finish_handshake(&mut self, response: &[u8]) -> io::Result<()> {
+        let text = String::from_utf8_lossy(response);
+        let switching = text
+            .lines()
+            .next()
+            .is_some_and(|status| status.split_whitespace().nth(1) == Some("101"));
+        let extensions: Vec<&str> = text
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-extensions"))
+            .map(|(_, value)| value.trim())
+            .collect();
+
+        let params = if switching && !extensions.is_empty() {
+            DeflateParams::from_response(&extensions.join(", "))?
+        } else {
+            None
+        };
+        self.phase = match params {
+            Some(params) => Phase::Deflate(Box::new(Codec::new(
+                params,
+                self.role,
+                self.max_message_size,
+            ))),
+            None => Phase::Plain,
+        };
+        Ok(())
+    }
+
+    fn process_read(&mut self) -> io::Result<()> {
+        if matches!(self.phase, Phase::Handshake) {
+            if self.role == Role::Server {
+                // The server's handshake ends on the write side
+                self.read_ready.append(&mut self.read_raw);
+                return Ok(());
+            }
+            let Some(end) = find_header_end(&self.read_raw) else {
+                return Ok(());
+            };
+            let response: Vec<u8> = self.read_raw.drain(..end).collect();
+            self.finish_handshake(&response)?;
+            self.read_ready.extend_from_slice(&response);
+        }
+        match self.phase {
+            Phase::Deflate(ref mut codec) => {
+                codec.process_inbound(&mut self.read_raw, &mut self.read_ready)
+            }
+            _ => {
+                self.read_ready.append(&mut self.read_raw);
+                Ok(())
+            }
+        }
+    }
+
+    fn process_write(&mut self) -> io::Result<()> {
+        if matches!(self.phase, Phase::Handshake) {
+            if self.role == Role::Client {
+                // The client's handshake ends on the read side
+                self.write_ready.append(&mut self.write_raw);
+                return Ok(());
+            }
+            let Some(end) = find_header_end(&self.write_raw) else {
+                return Ok(());
+            };
+            let response: Vec<u8> = self.write_raw.drain(..end).collect();
+            self.finish_handshake(&response)?;
+            self.write_ready.extend_from_slice(&response);
+        }
+        match self.phase {
+            Phase::Deflate(ref mut codec) => {
+                codec.process_outbound(&mut self.write_raw, &mut self.write_ready)
+            }
+            _ => {
+                self.write_ready.append(&mut self.write_raw);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> DeflateStream<S> {
+    /// Write buffered output to the inner stream.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.write_pos < self.write_ready.len() {
+            let n = ready!(
+                Pin::new(&mut self.inner).poll_write(cx, &self.write_ready[self.write_pos..])
+            )?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_pos += n;
+        }
+        self.write_ready.clear();
+        self.write_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.read_pos < this.read_ready.len() {
+                let n = buf.remaining().min(this.read_ready.len() - this.read_pos);
+                buf.put_slice(&this.read_ready[this.read_pos..this.read_pos + n]);
+                this.read_pos += n;
+                if this.read_pos == this.read_ready.len() {
+                    this.read_ready.clear();
+                    this.read_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.read_eof {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = [0u8; 8192];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf))?;
+            if chunk_buf.filled().is_empty() {
+                // Hand over any partial frame so tungstenite reports it
+                this.read_eof = true;
+                this.read_ready.append(&mut this.read_raw);
+                continue;
+            }
+            this.read_raw.extend_from_slice(chunk_buf.filled());
+            this.process_read()?;
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.write_ready.len() - this.write_pos >= WRITE_HIGH_WATER {
+            ready!(this.poll_drain(cx))?;
+        }
+        this.write_raw.extend_from_slice(buf);
+        this.process_write()?;
+        // Start sending; a pending inner write just leaves bytes buffered
+        if let Poll::Ready(Err(e)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if matches!(this.phase, Phase::Handshake) && !this.write_raw.is_empty() {
+            // Flushing a partial header block: nothing left to detect
+            this.write_ready.append(&mut this.write_raw);
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_offer() {
+        assert_eq!(
+            DeflateParams::accept_offer("permessage-deflate; client_max_window_bits"),
+            Some(DeflateParams::default())
+        );
+        // First acceptable offer wins
+        assert_eq!(
+            DeflateParams::accept_offer(
+                "permessage-deflate; server_max_window_bits=10, \
+                 permessage-deflate; server_no_context_takeover"
+            ),
+            Some(DeflateParams {
+                server_no_context_takeover: true,
+                client_no_context_takeover: false,
+            })
+        );
+        assert_eq!(DeflateParams::accept_offer("x-webkit-deflate-frame"), None);
+        assert_eq!(
+            DeflateParams::accept_offer("permessage-deflate; unknown_param"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_from_response() {
+        assert_eq!(
+            DeflateParams::from_response("permessage-deflate; client_no_context_takeover").unwrap(),
+            Some(DeflateParams {
+                server_no_context_takeover: false,
+                client_no_context_takeover: true,
+            })
+        );
+        // We never offer client_max_window_bits, so the server can't set it
+        assert!(
+            DeflateParams::from_response("permessage-deflate; client_max_window_bits=9").is_err()
+        );
+        assert!(DeflateParams::from_response("other-extension").is_err());
+    }
+
+    fn frame(opcode: u8, fin: bool, mask: Option<[u8; 4]>, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_frame(&mut out, false, opcode, mask, payload);
+        if !fin {
+            out[0] &= 0x7f;
+        }
+        out
+    }
+
+    /// Server writes, client reads: the client sees the original frames.
+    fn roundtrip(params: DeflateParams, frames: &[Vec<u8>]) -> (Vec<u8>, usize) {
+        let mut server = Codec::new(params, Role::Server, MAX_FRAME_SIZE);
+        let mut client = Codec::new(params, Role::Client, MAX_FRAME_SIZE);
+        let mut wire = Vec::new();
+        for f in frames {
+            server.process_outbound(&mut f.clone(), &mut wire).unwrap();
+        }
+        let wire_len = wire.len();
+        let mut received = Vec::new();
+        client.process_inbound(&mut wire, &mut received).unwrap();
+        (received, wire_len)
+    }
+
+    #[test]
+    fn test_codec_roundtrip_compresses() {
+        let snapshot = "/lights/stage/1/brightness=0.5;".repeat(1000);
+        let frames = vec![
+            frame(OP_BINARY, true, None, snapshot.as_bytes()),
+            frame(OP_BINARY, true, None, b"tiny"),
+            frame(0x9, true, None, b"ping"),
+            frame(OP_BINARY, true, None, snapshot.as_bytes()),
+        ];
+        let expected: Vec<u8> = frames.concat();
+        for params in [
+            DeflateParams::default(),
+            DeflateParams {
+                server_no_context_takeover: true,
+                client_no_context_takeover: true,
+            },
+        ] {
+            let (received, wire_len) = roundtrip(params, &frames);
+            assert_eq!(received, expected);
+            assert!(
+                wire_len * 10 < expected.len(),
+                "{} bytes on the wire",
+                wire_len
+            );
+        }
+    }
+
+    #[test]
+    fn test_inbound_masked_fragments_reassembled() {
+        let payload = "fragmented ".repeat(100);
+        let mut sender = Codec::new(DeflateParams::default(), Role::Client, MAX_FRAME_SIZE);
+        let deflated = sender.deflate(payload.as_bytes()).unwrap();
+        let (first, rest) = deflated.split_at(deflated.len() / 2);
+        let key = [1, 2, 3, 4];
+
+        let mut wire = Vec::new();
+        encode_frame(&mut wire, true, OP_BINARY, Some(key), first);
+        wire[0] &= 0x7f; // not FIN
+        wire.extend(frame(0xA, true, Some(key), b"pong"));
+        wire.extend(frame(OP_CONTINUATION, true, Some(key), rest));
+
+        let mut server = Codec::new(DeflateParams::default(), Role::Server, MAX_FRAME_SIZE);
+        let mut out = Vec::new();
+        // Feed the bytes in two pieces to exercise partial frames
+        let mut raw = wire[..5].to_vec();
+        server.process_inbound(&mut raw, &mut out).unwrap();
+        assert!(out.is_empty());
+        raw.extend_from_slice(&wire[5..]);
+        server.process_inbound(&mut raw, &mut out).unwrap();
+        assert!(raw.is_empty());
+
+        let mut expected = frame(0xA, true, Some(key), b"pong");
+        expected.extend(frame(OP_BINARY, true, Some(key), payload.as_bytes()));
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_oversized_message_refused() {
+        let mut sender = Codec::new(DeflateParams::default(), Role::Client, MAX_FRAME_SIZE);
+        // 8 KiB that deflates to a few dozen bytes
+        let deflated = sender.deflate(&[b'a'; 8192]).unwrap();
+        assert!(deflated.len() < 1024);
+
+        let mut server = Codec::new(DeflateParams::default(), Role::Server, 1024);
+        let mut wire = Vec::new();
+        encode_frame(&mut wire, true, OP_BINARY, None, &deflated);
+        let err = server
+            .process_inbound(&mut wire, &mut Vec::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}", err);
+
+        // Compressed payloads over the limit are refused before inflating
+        let mut server = Codec::new(DeflateParams::default(), Role::Server, 1024);
+        let mut wire = Vec::new();
+        encode_frame(&mut wire, true, OP_BINARY, None, &[0xff; 2048]);
+        let err = server
+            .process_inbound(&mut wire, &mut Vec::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}", err);
+    }
+
+    #[test]
+    fn test_garbage_rejected() {
+        let mut codec = Codec::new(DeflateParams::default(), Role::Server, MAX_FRAME_SIZE);
+        let mut wire = Vec::new();
+        encode_frame(&mut wire, true, OP_BINARY, None, &[0xff; 16]);
+        assert!(codec.process_inbound(&mut wire, &mut Vec::new()).is_err());
+    }
+}
@@ -12,6 +12,7 @@
 //! - Serial (direct hardware, lowest latency) - native only
 //! - BLE (Bluetooth Low Energy, wireless controllers) - native only
 //! - WebRTC (P2P, NAT traversal, low-latency)
+//!   - Native: client and server (HTTP signaling) via webrtc-rs
 
 pub mod error;
 pub mod traits;
@@ -20,6 +21,9 @@ pub mod traits;
 #[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
 pub mod websocket;
 
+#[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+mod deflate;
+
 // WASM WebSocket (uses web-sys)
 #[cfg(all(feature = "wasm-websocket", target_arch = "wasm32"))]
 pub mod wasm_websocket;
@@ -44,7 +48,9 @@ pub mod ble;
 pub mod webrtc;
 
 pub use error::{Result, TransportError};
-pub use traits::{Transport, TransportEvent, TransportReceiver, TransportSender, TransportServer};
+pub use traits::{
+    ConnectionInfo, Transport, TransportEvent, TransportReceiver, TransportSender, TransportServer,
+};
 
 // Native WebSocket exports
 #[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
@@ -64,10 +70,10 @@ pub use udp::{UdpConfig, UdpTransport};
 pub use ble::{BleConfig, BleTransport};
 
 #[cfg(feature = "webrtc")]
-pub use webrtc::{WebRtcConfig, WebRtcTransport};
+pub use webrtc::{WebRtcConfig, WebRtcServer, WebRtcTransport};
 
 #[cfg(all(feature = "quic", not(target_arch = "wasm32")))]
-pub use quic::{QuicConfig, QuicConnection, QuicTransport};
+pub use quic::{ClientAuth, QuicConfig, QuicConnection, QuicTransport, StreamPriority};
 
 #[cfg(all(feature = "serial", not(target_arch = "wasm32")))]
 pub use serial::{SerialConfig, SerialTransport};
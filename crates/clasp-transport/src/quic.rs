@@ -20,10 +20,16 @@ use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
 use crate::error::{Result, TransportError};
-use crate::traits::{TransportEvent, TransportReceiver, TransportSender};
+use crate::traits::{ConnectionInfo, TransportEvent, TransportReceiver, TransportSender};
 
 #[cfg(feature = "quic")]
-use quinn::{ClientConfig, Connection, Endpoint, SendStream, ServerConfig, TransportConfig};
+use bytes::BytesMut;
+#[cfg(feature = "quic")]
+use clasp_core::{Frame, MAGIC_BYTE};
+#[cfg(feature = "quic")]
+use quinn::{
+    ClientConfig, Connection, Endpoint, RecvStream, SendStream, ServerConfig, TransportConfig,
+};
 #[cfg(feature = "quic")]
 use std::net::SocketAddr;
 
@@ -33,6 +39,10 @@ pub const CLASP_ALPN: &[u8] = b"clasp/2";
 /// Default channel buffer size for QUIC connections
 const DEFAULT_CHANNEL_BUFFER_SIZE: usize = 1000;
 
+/// How often stream readers check whether the peer's address has changed
+#[cfg(feature = "quic")]
+const MIGRATION_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
 /// Certificate verification mode
 #[derive(Debug, Clone, Default)]
 pub enum CertVerification {
@@ -46,6 +56,19 @@ pub enum CertVerification {
     CustomRoots(Vec<Vec<u8>>),
 }
 
+/// Whether a QUIC server asks clients for a certificate (mutual TLS)
+#[derive(Debug, Clone, Default)]
+pub enum ClientAuth {
+    /// Don't ask clients for a certificate
+    #[default]
+    None,
+    /// Ask for a certificate signed by one of these roots (DER), but also
+    /// accept clients without one
+    Optional(Vec<Vec<u8>>),
+    /// Refuse clients without a certificate signed by one of these roots (DER)
+    Required(Vec<Vec<u8>>),
+}
+
 /// QUIC transport configuration
 #[derive(Debug, Clone)]
 pub struct QuicConfig {
@@ -59,6 +82,10 @@ pub struct QuicConfig {
     pub initial_window: u32,
     /// Certificate verification mode
     pub cert_verification: CertVerification,
+    /// Client certificates a server asks for
+    pub client_auth: ClientAuth,
+    /// Certificate and private key (DER) a client presents when asked
+    pub client_certificate: Option<(Vec<u8>, Vec<u8>)>,
 }
 
 impl Default for QuicConfig {
@@ -69,6 +96,8 @@ impl Default for QuicConfig {
             idle_timeout_ms: 30000,
             initial_window: 10,
             cert_verification: CertVerification::default(),
+            client_auth: ClientAuth::default(),
+            client_certificate: None,
         }
     }
 }
@@ -102,7 +131,6 @@ impl QuicConfig {
 /// QUIC transport for CLASP
 #[cfg(feature = "quic")]
 pub struct QuicTransport {
-    #[allow(dead_code)]
     config: QuicConfig,
     endpoint: Endpoint,
 }
@@ -161,7 +189,10 @@ impl QuicTransport {
             .map_err(|e| TransportError::ConnectionFailed(format!("Connection failed: {}", e)))?;
 
         info!("QUIC connected to {} ({})", server_name, addr);
-        Ok(QuicConnection::new(connection))
+        Ok(QuicConnection::new(
+            connection,
+            self.endpoint.local_addr().ok(),
+        ))
     }
 
     /// Accept incoming connections (server mode)
@@ -178,7 +209,36 @@ impl QuicTransport {
 
         let remote = connection.remote_address();
         info!("QUIC accepted connection from {}", remote);
-        Ok(QuicConnection::new(connection))
+        Ok(QuicConnection::new(
+            connection,
+            self.endpoint.local_addr().ok(),
+        ))
+    }
+
+    /// Move the endpoint to a new local UDP socket.
+    ///
+    /// Clients call this when the device changes networks. Open connections
+    /// migrate to the new address without a new handshake, and the server's
+    /// stream receivers report [`TransportEvent::Migrated`].
+    pub fn rebind(&self, addr: SocketAddr) -> Result<()> {
+        let socket = std::net::UdpSocket::bind(addr)
+            .map_err(|e| TransportError::ConnectionFailed(format!("Bind failed: {}", e)))?;
+        self.endpoint
+            .rebind(socket)
+            .map_err(|e| TransportError::ConnectionFailed(format!("Rebind failed: {}", e)))?;
+        info!("QUIC endpoint rebound to {}", addr);
+        Ok(())
+    }
+
+    /// Replace the server certificate and key.
+    ///
+    /// Handshakes that start afterwards use the new certificate; connections
+    /// already open are not affected. On error the old certificate stays.
+    pub fn reload_certificate(&self, cert_der: Vec<u8>, key_der: Vec<u8>) -> Result<()> {
+        let server_config = Self::build_server_config(&self.config, cert_der, key_der)?;
+        self.endpoint.set_server_config(Some(server_config));
+        info!("QUIC server certificate reloaded");
+        Ok(())
     }
 
     /// Get the local address
@@ -193,10 +253,10 @@ impl QuicTransport {
             CertVerification::SkipVerification => {
                 // WARNING: Do not use in production - vulnerable to MITM attacks
                 warn!("QUIC using insecure certificate verification - DO NOT USE IN PRODUCTION");
-                let mut cfg = rustls::ClientConfig::builder()
+                let builder = rustls::ClientConfig::builder()
                     .dangerous()
-                    .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
-                    .with_no_client_auth();
+                    .with_custom_certificate_verifier(Arc::new(SkipServerVerification));
+                let mut cfg = Self::with_client_certificate(builder, config)?;
                 cfg.alpn_protocols = vec![CLASP_ALPN.to_vec()];
                 cfg
             }
@@ -227,9 +287,8 @@ impl QuicTransport {
                     ));
                 }
 
-                let mut cfg = rustls::ClientConfig::builder()
-                    .with_root_certificates(root_store)
-                    .with_no_client_auth();
+                let builder = rustls::ClientConfig::builder().with_root_certificates(root_store);
+                let mut cfg = Self::with_client_certificate(builder, config)?;
                 cfg.alpn_protocols = vec![CLASP_ALPN.to_vec()];
                 cfg
             }
@@ -251,9 +310,8 @@ impl QuicTransport {
                 }
 
                 info!("Using {} custom root certificates", root_store.len());
-                let mut cfg = rustls::ClientConfig::builder()
-                    .with_root_certificates(root_store)
-                    .with_no_client_auth();
+                let builder = rustls::ClientConfig::builder().with_root_certificates(root_store);
+                let mut cfg = Self::with_client_certificate(builder, config)?;
                 cfg.alpn_protocols = vec![CLASP_ALPN.to_vec()];
                 cfg
             }
@@ -289,8 +347,17 @@ impl QuicTransport {
         let key = rustls::pki_types::PrivateKeyDer::try_from(key_der)
             .map_err(|e| TransportError::ConnectionFailed(format!("Invalid private key: {}", e)))?;
 
-        let mut server_crypto = rustls::ServerConfig::builder()
-            .with_no_client_auth()
+        let builder = rustls::ServerConfig::builder();
+        let builder = match &config.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(roots) => {
+                builder.with_client_cert_verifier(Self::client_verifier(roots, true)?)
+            }
+            ClientAuth::Required(roots) => {
+                builder.with_client_cert_verifier(Self::client_verifier(roots, false)?)
+            }
+        };
+        let mut server_crypto = builder
             .with_single_cert(vec![cert], key)
             .map_err(|e| TransportError::ConnectionFailed(format!("TLS config failed: {}", e)))?;
 
@@ -316,6 +383,45 @@ impl QuicTransport {
 
         Ok(server_config)
     }
+
+    /// Finish a client TLS config, presenting the configured certificate
+    fn with_client_certificate(
+        builder: rustls::ConfigBuilder<rustls::ClientConfig, rustls::client::WantsClientCert>,
+        config: &QuicConfig,
+    ) -> Result<rustls::ClientConfig> {
+        let Some((cert_der, key_der)) = &config.client_certificate else {
+            return Ok(builder.with_no_client_auth());
+        };
+        let cert = rustls::pki_types::CertificateDer::from(cert_der.clone());
+        let key = rustls::pki_types::PrivateKeyDer::try_from(key_der.clone()).map_err(|e| {
+            TransportError::ConnectionFailed(format!("Invalid client private key: {}", e))
+        })?;
+        builder.with_client_auth_cert(vec![cert], key).map_err(|e| {
+            TransportError::ConnectionFailed(format!("Client certificate rejected: {}", e))
+        })
+    }
+
+    /// Verifier for client certificates signed by `roots`
+    fn client_verifier(
+        roots: &[Vec<u8>],
+        optional: bool,
+    ) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+        let mut root_store = rustls::RootCertStore::empty();
+        for cert_der in roots {
+            root_store
+                .add(rustls::pki_types::CertificateDer::from(cert_der.clone()))
+                .map_err(|e| {
+                    TransportError::ConnectionFailed(format!("Invalid client CA: {}", e))
+                })?;
+        }
+        let mut builder = rustls::server::WebPkiClientVerifier::builder(Arc::new(root_store));
+        if optional {
+            builder = builder.allow_unauthenticated();
+        }
+        builder
+            .build()
+            .map_err(|e| TransportError::ConnectionFailed(format!("Client verifier failed: {}", e)))
+    }
 }
 
 /// Skip server certificate verification (for development only)
@@ -370,60 +476,103 @@ impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
     }
 }
 
+/// Send priority of a QUIC stream
+///
+/// A connection can carry one stream per class, so that e.g. a burst of
+/// stream samples doesn't hold up param writes queued behind it. When
+/// several streams have data ready, QUIC sends the higher priority first.
+/// Priority only affects sending; the peer reads all streams alike.
+#[cfg(feature = "quic")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StreamPriority {
+    /// Handshake, acks, errors and locks
+    Control,
+    /// Params and events
+    #[default]
+    Normal,
+    /// High-rate streams and gestures, delayed first under congestion
+    Bulk,
+}
+
+#[cfg(feature = "quic")]
+impl StreamPriority {
+    fn as_quinn(self) -> i32 {
+        match self {
+            StreamPriority::Control => 1,
+            StreamPriority::Normal => 0,
+            StreamPriority::Bulk => -1,
+        }
+    }
+}
+
 /// QUIC connection wrapper
+///
+/// Cloning gives another handle to the same connection, e.g. to open
+/// streams from several tasks.
 #[cfg(feature = "quic")]
+#[derive(Clone)]
 pub struct QuicConnection {
     connection: Connection,
+    local_addr: Option<SocketAddr>,
 }
 
 #[cfg(feature = "quic")]
 impl QuicConnection {
-    fn new(connection: Connection) -> Self {
-        Self { connection }
+    fn new(connection: Connection, local_addr: Option<SocketAddr>) -> Self {
+        Self {
+            connection,
+            local_addr,
+        }
+    }
+
+    /// What the handshake revealed: ALPN, SNI and the client's certificate
+    /// chain, if the server asked for one (see [`ClientAuth`])
+    pub fn connection_info(&self) -> ConnectionInfo {
+        let mut info = ConnectionInfo {
+            local_addr: self.local_addr,
+            ..ConnectionInfo::new("quic", self.connection.remote_address())
+        };
+        if let Some(handshake) = self
+            .connection
+            .handshake_data()
+            .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+        {
+            info.alpn = handshake.protocol;
+            info.server_name = handshake.server_name;
+        }
+        if let Some(chain) = self.connection.peer_identity().and_then(|identity| {
+            identity
+                .downcast::<Vec<rustls::pki_types::CertificateDer<'static>>>()
+                .ok()
+        }) {
+            info.peer_certificates = chain
+                .iter()
+                .map(|cert| Bytes::copy_from_slice(cert))
+                .collect();
+        }
+        info
     }
 
     /// Open a bidirectional stream (reliable, ordered)
     pub async fn open_bi(&self) -> Result<(QuicSender, QuicReceiver)> {
+        self.open_bi_with_priority(StreamPriority::Normal).await
+    }
+
+    /// Open a bidirectional stream whose sends have the given priority.
+    ///
+    /// Call once per class to get a stream for each.
+    pub async fn open_bi_with_priority(
+        &self,
+        priority: StreamPriority,
+    ) -> Result<(QuicSender, QuicReceiver)> {
         let (send, recv) =
             self.connection.open_bi().await.map_err(|e| {
                 TransportError::ConnectionFailed(format!("Open stream failed: {}", e))
             })?;
+        set_priority(&send, priority)?;
 
-        let (tx, rx) = mpsc::channel(DEFAULT_CHANNEL_BUFFER_SIZE);
         let connected = Arc::new(Mutex::new(true));
-        let connected_clone = connected.clone();
-
-        // Spawn receiver task
-        tokio::spawn(async move {
-            let mut recv = recv;
-            let mut buf = vec![0u8; 65536];
-
-            loop {
-                match recv.read(&mut buf).await {
-                    Ok(Some(n)) => {
-                        let data = Bytes::copy_from_slice(&buf[..n]);
-                        if tx.send(TransportEvent::Data(data)).await.is_err() {
-                            break;
-                        }
-                    }
-                    Ok(None) => {
-                        *connected_clone.lock() = false;
-                        let _ = tx.send(TransportEvent::Disconnected { reason: None }).await;
-                        break;
-                    }
-                    Err(e) => {
-                        error!("QUIC read error: {}", e);
-                        *connected_clone.lock() = false;
-                        let _ = tx
-                            .send(TransportEvent::Disconnected {
-                                reason: Some(e.to_string()),
-                            })
-                            .await;
-                        break;
-                    }
-                }
-            }
-        });
+        let rx = spawn_reader(recv, self.connection.clone(), connected.clone());
 
         Ok((
             QuicSender {
@@ -440,41 +589,8 @@ impl QuicConnection {
             TransportError::ConnectionFailed(format!("Accept stream failed: {}", e))
         })?;
 
-        let (tx, rx) = mpsc::channel(DEFAULT_CHANNEL_BUFFER_SIZE);
         let connected = Arc::new(Mutex::new(true));
-        let connected_clone = connected.clone();
-
-        // Spawn receiver task
-        tokio::spawn(async move {
-            let mut recv = recv;
-            let mut buf = vec![0u8; 65536];
-
-            loop {
-                match recv.read(&mut buf).await {
-                    Ok(Some(n)) => {
-                        let data = Bytes::copy_from_slice(&buf[..n]);
-                        if tx.send(TransportEvent::Data(data)).await.is_err() {
-                            break;
-                        }
-                    }
-                    Ok(None) => {
-                        *connected_clone.lock() = false;
-                        let _ = tx.send(TransportEvent::Disconnected { reason: None }).await;
-                        break;
-                    }
-                    Err(e) => {
-                        error!("QUIC read error: {}", e);
-                        *connected_clone.lock() = false;
-                        let _ = tx
-                            .send(TransportEvent::Disconnected {
-                                reason: Some(e.to_string()),
-                            })
-                            .await;
-                        break;
-                    }
-                }
-            }
-        });
+        let rx = spawn_reader(recv, self.connection.clone(), connected.clone());
 
         Ok((
             QuicSender {
@@ -487,11 +603,17 @@ impl QuicConnection {
 
     /// Open a unidirectional send stream
     pub async fn open_uni(&self) -> Result<QuicSender> {
+        self.open_uni_with_priority(StreamPriority::Normal).await
+    }
+
+    /// Open a unidirectional send stream with the given priority
+    pub async fn open_uni_with_priority(&self, priority: StreamPriority) -> Result<QuicSender> {
         let send = self
             .connection
             .open_uni()
             .await
             .map_err(|e| TransportError::ConnectionFailed(format!("Open uni failed: {}", e)))?;
+        set_priority(&send, priority)?;
 
         Ok(QuicSender {
             send: Arc::new(tokio::sync::Mutex::new(send)),
@@ -506,40 +628,7 @@ impl QuicConnection {
                 TransportError::ConnectionFailed(format!("Accept uni failed: {}", e))
             })?;
 
-        let (tx, rx) = mpsc::channel(DEFAULT_CHANNEL_BUFFER_SIZE);
-        let connected = Arc::new(Mutex::new(true));
-        let connected_clone = connected.clone();
-
-        tokio::spawn(async move {
-            let mut recv = recv;
-            let mut buf = vec![0u8; 65536];
-
-            loop {
-                match recv.read(&mut buf).await {
-                    Ok(Some(n)) => {
-                        let data = Bytes::copy_from_slice(&buf[..n]);
-                        if tx.send(TransportEvent::Data(data)).await.is_err() {
-                            break;
-                        }
-                    }
-                    Ok(None) => {
-                        *connected_clone.lock() = false;
-                        let _ = tx.send(TransportEvent::Disconnected { reason: None }).await;
-                        break;
-                    }
-                    Err(e) => {
-                        error!("QUIC read error: {}", e);
-                        *connected_clone.lock() = false;
-                        let _ = tx
-                            .send(TransportEvent::Disconnected {
-                                reason: Some(e.to_string()),
-                            })
-                            .await;
-                        break;
-                    }
-                }
-            }
-        });
+        let rx = spawn_reader(recv, self.connection.clone(), Arc::new(Mutex::new(true)));
 
         Ok(QuicReceiver { rx })
     }
@@ -571,6 +660,102 @@ impl QuicConnection {
     }
 }
 
+#[cfg(feature = "quic")]
+fn set_priority(send: &SendStream, priority: StreamPriority) -> Result<()> {
+    send.set_priority(priority.as_quinn())
+        .map_err(|e| TransportError::ConnectionFailed(format!("Set priority failed: {}", e)))
+}
+
+/// Read frames from a stream into an event channel.
+///
+/// Besides data and disconnects, the task reports when the peer's address
+/// changes (connection migration, e.g. a phone moving from Wi-Fi to
+/// cellular) as [`TransportEvent::Migrated`]. The connection and its
+/// streams carry on; every stream of the connection reports the move.
+#[cfg(feature = "quic")]
+fn spawn_reader(
+    mut recv: RecvStream,
+    connection: Connection,
+    connected: Arc<Mutex<bool>>,
+) -> mpsc::Receiver<TransportEvent> {
+    let (tx, rx) = mpsc::channel(DEFAULT_CHANNEL_BUFFER_SIZE);
+
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 65536];
+        let mut pending = BytesMut::new();
+        let mut remote = connection.remote_address();
+        let mut migration_check = tokio::time::interval(MIGRATION_CHECK_INTERVAL);
+        migration_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        'read: loop {
+            tokio::select! {
+                read = recv.read(&mut buf) => match read {
+                    Ok(Some(n)) => {
+                        pending.extend_from_slice(&buf[..n]);
+                        for data in split_frames(&mut pending) {
+                            if tx.send(TransportEvent::Data(data)).await.is_err() {
+                                break 'read;
+                            }
+                        }
+                    }
+                    Ok(None) => {
+                        *connected.lock() = false;
+                        let _ = tx.send(TransportEvent::Disconnected { reason: None }).await;
+                        break;
+                    }
+                    Err(e) => {
+                        error!("QUIC read error: {}", e);
+                        *connected.lock() = false;
+                        let _ = tx
+                            .send(TransportEvent::Disconnected {
+                                reason: Some(e.to_string()),
+                            })
+                            .await;
+                        break;
+                    }
+                },
+                _ = migration_check.tick() => {
+                    let current = connection.remote_address();
+                    if current != remote {
+                        info!("QUIC peer migrated from {} to {}", remote, current);
+                        let event = TransportEvent::Migrated {
+                            from: remote,
+                            to: current,
+                        };
+                        remote = current;
+                        if tx.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+/// Split buffered stream bytes into CLASP frames.
+///
+/// A QUIC stream doesn't keep write boundaries, so one read can hold several
+/// frames or part of one. Bytes that don't start a CLASP frame are passed
+/// through as they arrived.
+#[cfg(feature = "quic")]
+fn split_frames(pending: &mut BytesMut) -> Vec<Bytes> {
+    let mut frames = Vec::new();
+    while !pending.is_empty() {
+        if pending[0] != MAGIC_BYTE {
+            frames.push(pending.split().freeze());
+            break;
+        }
+        match Frame::check_complete(pending) {
+            Some(len) => frames.push(pending.split_to(len).freeze()),
+            None => break,
+        }
+    }
+    frames
+}
+
 /// QUIC stream sender
 #[cfg(feature = "quic")]
 pub struct QuicSender {
@@ -678,6 +863,13 @@ impl QuicTransport {
 #[cfg(not(feature = "quic"))]
 pub struct QuicConnection;
 
+#[cfg(not(feature = "quic"))]
+pub enum StreamPriority {
+    Control,
+    Normal,
+    Bulk,
+}
+
 #[cfg(not(feature = "quic"))]
 pub struct QuicSender;
 
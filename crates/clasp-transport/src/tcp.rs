@@ -14,7 +14,9 @@ use tokio::sync::mpsc;
 use tracing::{debug, error, info};
 
 use crate::error::{Result, TransportError};
-use crate::traits::{TransportEvent, TransportReceiver, TransportSender, TransportServer};
+use crate::traits::{
+    ConnectionInfo, TransportEvent, TransportReceiver, TransportSender, TransportServer,
+};
 
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 
@@ -219,6 +221,10 @@ impl TransportSender for TcpSender {
         *self.connected.lock()
     }
 
+    fn queued(&self) -> Option<usize> {
+        Some(self.tx.max_capacity() - self.tx.capacity())
+    }
+
     async fn close(&self) -> Result<()> {
         *self.connected.lock() = false;
         Ok(())
@@ -275,7 +281,16 @@ impl TransportServer for TcpServer {
     type Sender = TcpSender;
     type Receiver = TcpReceiver;
 
+    fn protocol(&self) -> &'static str {
+        "tcp"
+    }
+
     async fn accept(&mut self) -> Result<(Self::Sender, Self::Receiver, SocketAddr)> {
+        let (sender, receiver, info) = self.accept_with_info().await?;
+        Ok((sender, receiver, info.remote_addr))
+    }
+
+    async fn accept_with_info(&mut self) -> Result<(Self::Sender, Self::Receiver, ConnectionInfo)> {
         let (stream, peer_addr) = self
             .listener
             .accept()
@@ -299,6 +314,8 @@ impl TransportServer for TcpServer {
         let max_size = self.config.max_message_size;
         let connected_clone = connected.clone();
 
+        let stream_local_addr = stream.local_addr().ok();
+
         // Spawn reader/writer task
         let stream: TcpStream = stream; // Ensure type is known
         tokio::spawn(async move {
@@ -314,7 +331,11 @@ impl TransportServer for TcpServer {
             .await;
         });
 
-        Ok((sender, receiver, peer_addr))
+        let info = ConnectionInfo {
+            local_addr: stream_local_addr,
+            ..ConnectionInfo::new(self.protocol(), peer_addr)
+        };
+        Ok((sender, receiver, info))
     }
 
     fn local_addr(&self) -> Result<SocketAddr> {
@@ -17,6 +17,54 @@ pub enum TransportEvent {
     Data(Bytes),
     /// Error occurred
     Error(String),
+    /// The peer's address changed but the connection survived
+    /// (QUIC connection migration)
+    Migrated { from: SocketAddr, to: SocketAddr },
+}
+
+/// What the transport learned about an accepted connection during its
+/// handshake
+///
+/// Routers keep it with the session, so authentication can use the TLS
+/// peer identity and tenants can be told apart by SNI.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// Transport name (see [`TransportServer::protocol`])
+    pub protocol: &'static str,
+    /// Peer address
+    pub remote_addr: SocketAddr,
+    /// Address the connection was accepted on
+    pub local_addr: Option<SocketAddr>,
+    /// WebSocket subprotocol agreed with the client
+    pub subprotocol: Option<String>,
+    /// ALPN protocol agreed in the TLS handshake
+    pub alpn: Option<Vec<u8>>,
+    /// Server name the client asked for in the TLS handshake (SNI)
+    pub server_name: Option<String>,
+    /// Certificate chain the client presented (DER, leaf first); empty
+    /// without client authentication
+    pub peer_certificates: Vec<Bytes>,
+}
+
+impl ConnectionInfo {
+    /// Info for a connection whose handshake revealed nothing beyond the
+    /// peer address
+    pub fn new(protocol: &'static str, remote_addr: SocketAddr) -> Self {
+        Self {
+            protocol,
+            remote_addr,
+            local_addr: None,
+            subprotocol: None,
+            alpn: None,
+            server_name: None,
+            peer_certificates: Vec::new(),
+        }
+    }
+
+    /// The client's own certificate (DER), if it presented one
+    pub fn peer_certificate(&self) -> Option<&Bytes> {
+        self.peer_certificates.first()
+    }
 }
 
 /// Trait for sending data
@@ -32,6 +80,11 @@ pub trait TransportSender: Send + Sync {
     /// Check if connected
     fn is_connected(&self) -> bool;
 
+    /// Messages waiting in the send buffer, if the transport buffers sends
+    fn queued(&self) -> Option<usize> {
+        None
+    }
+
     /// Close the sender
     async fn close(&self) -> Result<()>;
 }
@@ -74,9 +127,24 @@ pub trait TransportServer: Send + Sync {
     /// Accept a new connection
     async fn accept(&mut self) -> Result<(Self::Sender, Self::Receiver, SocketAddr)>;
 
+    /// Accept a new connection, with what its handshake revealed
+    ///
+    /// The default knows only the peer address; servers that negotiate
+    /// TLS, ALPN or a subprotocol override it.
+    async fn accept_with_info(&mut self) -> Result<(Self::Sender, Self::Receiver, ConnectionInfo)> {
+        let (sender, receiver, addr) = self.accept().await?;
+        Ok((sender, receiver, ConnectionInfo::new(self.protocol(), addr)))
+    }
+
     /// Get the local address
     fn local_addr(&self) -> Result<SocketAddr>;
 
+    /// Short name of the transport (e.g. `websocket`), reported to clients
+    /// in router presence
+    fn protocol(&self) -> &'static str {
+        "unknown"
+    }
+
     /// Close the server
     async fn close(&self) -> Result<()>;
 }
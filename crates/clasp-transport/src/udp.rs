@@ -1,13 +1,52 @@
 //! UDP transport implementation
+//!
+//! Plain UDP carries one CLASP frame per datagram with no protection against
+//! an on-path attacker replaying captured packets. [`FrameAuth`] adds an
+//! optional authenticated framing: each datagram carries a per-session
+//! sequence number and a truncated HMAC-SHA256 tag, and the receiver keeps a
+//! 64-frame sliding window per peer, dropping forged, duplicate and stale
+//! frames before they reach the application.
+//!
+//! ```text
+//! | 0xA5 | seq (u64 BE) | CLASP frame | HMAC-SHA256(marker | seq | frame)[..16] |
+//! ```
+//!
+//! The key is derived from a shared secret, normally the authenticated token
+//! or the session secret handed out in WELCOME. Both ends must enable it.
+//!
+//! Plain UDP also drops frames without telling anyone. Setting
+//! [`UdpConfig::reliability`] numbers every frame per peer and has the
+//! receiver acknowledge it; unacknowledged frames are retransmitted with
+//! exponential backoff, and the receiver drops duplicates with the same
+//! 64-frame window. Acks are selective: each one carries the highest
+//! sequence number seen and a bitmap of the 63 before it. Reliability sits
+//! inside [`FrameAuth`] when both are enabled, and again both ends must
+//! enable it.
+//!
+//! ```text
+//! data: | 0xB1 | epoch (u32 BE) | seq (u64 BE) | CLASP frame |
+//! ack:  | 0xB2 | epoch (u32 BE) | highest seq (u64 BE) | seen bitmap (u64 BE) |
+//! ```
+//!
+//! The epoch is picked at bind time, so a peer that restarts and numbers
+//! from 1 again gets a fresh window instead of having its frames dropped as
+//! duplicates.
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use hmac::{Hmac, Mac};
 use parking_lot::Mutex;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::UdpSocket;
 use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, warn};
 
 use crate::error::{Result, TransportError};
 use crate::traits::{TransportEvent, TransportReceiver, TransportSender};
@@ -19,6 +58,8 @@ pub struct UdpConfig {
     pub recv_buffer_size: usize,
     /// Maximum packet size
     pub max_packet_size: usize,
+    /// Acknowledge and retransmit frames (None = fire and forget)
+    pub reliability: Option<ReliabilityConfig>,
 }
 
 impl Default for UdpConfig {
@@ -26,14 +67,495 @@ impl Default for UdpConfig {
         Self {
             recv_buffer_size: 65536,
             max_packet_size: 65507, // Max UDP payload
+            reliability: None,
         }
     }
 }
 
+/// Retransmission settings for reliable UDP
+#[derive(Debug, Clone)]
+pub struct ReliabilityConfig {
+    /// Wait for an ack before the first retransmit
+    pub initial_rto: Duration,
+    /// Longest wait between retransmits; the wait doubles up to this
+    pub max_rto: Duration,
+    /// Retransmits before a frame is given up
+    pub max_retransmits: u32,
+    /// Unacknowledged frames kept per peer; beyond this the oldest is given up
+    pub max_in_flight: usize,
+}
+
+impl Default for ReliabilityConfig {
+    fn default() -> Self {
+        Self {
+            initial_rto: Duration::from_millis(200),
+            max_rto: Duration::from_secs(2),
+            max_retransmits: 8,
+            max_in_flight: 256,
+        }
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// First byte of an authenticated datagram
+const FRAME_MARKER: u8 = 0xA5;
+/// Bytes of the HMAC-SHA256 tag kept on the wire
+const TAG_LEN: usize = 16;
+/// Marker + sequence number
+const HEADER_LEN: usize = 1 + 8;
+/// Frames behind the highest sequence seen that are still accepted once
+const REPLAY_WINDOW: u64 = 64;
+
+/// Why an authenticated datagram was dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRejected {
+    /// Too short, or missing the marker byte
+    Malformed,
+    /// Tag does not match (wrong key or tampered frame)
+    BadMac,
+    /// Sequence number already seen, or older than the replay window
+    Replayed,
+}
+
+impl fmt::Display for FrameRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameRejected::Malformed => write!(f, "malformed frame"),
+            FrameRejected::BadMac => write!(f, "bad frame MAC"),
+            FrameRejected::Replayed => write!(f, "replayed or stale frame"),
+        }
+    }
+}
+
+/// Sliding replay window over sequence numbers (RFC 4303 style)
+#[derive(Debug, Default, Clone, Copy)]
+struct ReplayWindow {
+    /// Highest sequence number accepted (0 = none yet)
+    highest: u64,
+    /// Bit `n` set = `highest - n` has been accepted
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// Accept `seq` if it is new and inside the window
+    fn accept(&mut self, seq: u64) -> bool {
+        if seq == 0 {
+            return false;
+        }
+        if seq > self.highest {
+            let shift = seq - self.highest;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.highest = seq;
+            return true;
+        }
+        let offset = self.highest - seq;
+        if offset >= REPLAY_WINDOW {
+            return false;
+        }
+        let bit = 1u64 << offset;
+        if self.seen & bit != 0 {
+            return false;
+        }
+        self.seen |= bit;
+        true
+    }
+}
+
+/// Sequence numbers, MAC keys and replay windows for authenticated UDP framing
+///
+/// One instance is shared by a transport's senders and its receiver. The
+/// default key applies to every peer; [`set_peer_secret`](Self::set_peer_secret)
+/// overrides it for one address, e.g. with a per-session secret.
+pub struct FrameAuth {
+    key: [u8; 32],
+    peer_keys: Mutex<HashMap<SocketAddr, [u8; 32]>>,
+    next_seq: AtomicU64,
+    windows: Mutex<HashMap<SocketAddr, ReplayWindow>>,
+    rejected: AtomicU64,
+}
+
+impl fmt::Debug for FrameAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameAuth")
+            .field("next_seq", &self.next_seq.load(Ordering::Relaxed))
+            .field("rejected", &self.rejected.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl FrameAuth {
+    /// Derive the frame key from a shared secret
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            key: derive_key(secret),
+            peer_keys: Mutex::new(HashMap::new()),
+            next_seq: AtomicU64::new(1),
+            windows: Mutex::new(HashMap::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Derive the frame key from the token the peers authenticated with
+    pub fn from_token(token: &str) -> Self {
+        Self::from_secret(token.as_bytes())
+    }
+
+    /// Use a different secret for one peer and reset its replay window
+    pub fn set_peer_secret(&self, peer: SocketAddr, secret: &[u8]) {
+        self.peer_keys.lock().insert(peer, derive_key(secret));
+        self.windows.lock().remove(&peer);
+    }
+
+    /// Forget a peer's key override and replay window
+    pub fn remove_peer(&self, peer: &SocketAddr) {
+        self.peer_keys.lock().remove(peer);
+        self.windows.lock().remove(peer);
+    }
+
+    /// Datagrams dropped so far
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    fn key_for(&self, peer: &SocketAddr) -> [u8; 32] {
+        self.peer_keys.lock().get(peer).copied().unwrap_or(self.key)
+    }
+
+    fn mac(key: &[u8; 32], header: &[u8], payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(header);
+        mac.update(payload);
+        mac
+    }
+
+    /// Wrap a frame for `peer` with the next sequence number and a tag
+    pub fn seal(&self, peer: &SocketAddr, payload: &[u8]) -> Bytes {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = FRAME_MARKER;
+        header[1..].copy_from_slice(&seq.to_be_bytes());
+        let tag = Self::mac(&self.key_for(peer), &header, payload)
+            .finalize()
+            .into_bytes();
+
+        let mut packet = Vec::with_capacity(HEADER_LEN + payload.len() + TAG_LEN);
+        packet.extend_from_slice(&header);
+        packet.extend_from_slice(payload);
+        packet.extend_from_slice(&tag[..TAG_LEN]);
+        Bytes::from(packet)
+    }
+
+    /// Verify a datagram from `peer` and return its frame
+    ///
+    /// The replay window only advances for frames with a valid tag, so
+    /// forged packets cannot push genuine ones out of the window.
+    pub fn open(
+        &self,
+        peer: &SocketAddr,
+        packet: &[u8],
+    ) -> std::result::Result<Bytes, FrameRejected> {
+        let result = self.verify(peer, packet);
+        if result.is_err() {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn verify(
+        &self,
+        peer: &SocketAddr,
+        packet: &[u8],
+    ) -> std::result::Result<Bytes, FrameRejected> {
+        if packet.len() < HEADER_LEN + TAG_LEN || packet[0] != FRAME_MARKER {
+            return Err(FrameRejected::Malformed);
+        }
+        let (header, rest) = packet.split_at(HEADER_LEN);
+        let (payload, tag) = rest.split_at(rest.len() - TAG_LEN);
+        Self::mac(&self.key_for(peer), header, payload)
+            .verify_truncated_left(tag)
+            .map_err(|_| FrameRejected::BadMac)?;
+
+        let seq = u64::from_be_bytes(header[1..].try_into().expect("8-byte sequence"));
+        if !self.windows.lock().entry(*peer).or_default().accept(seq) {
+            return Err(FrameRejected::Replayed);
+        }
+        Ok(Bytes::copy_from_slice(payload))
+    }
+}
+
+fn derive_key(secret: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(b"clasp-udp-frame-v1");
+    mac.finalize().into_bytes().into()
+}
+
+/// First byte of a reliable data packet
+const DATA_MARKER: u8 = 0xB1;
+/// First byte of an acknowledgement
+const ACK_MARKER: u8 = 0xB2;
+/// Marker + epoch + sequence number
+const DATA_HEADER_LEN: usize = 1 + 4 + 8;
+/// Marker + epoch + highest sequence + bitmap
+const ACK_LEN: usize = 1 + 4 + 8 + 8;
+
+/// A frame waiting for its ack
+#[derive(Debug)]
+struct Pending {
+    packet: Bytes,
+    due: Instant,
+    rto: Duration,
+    retransmits: u32,
+}
+
+/// Sending side of one peer
+#[derive(Debug, Default)]
+struct Outgoing {
+    next_seq: u64,
+    pending: BTreeMap<u64, Pending>,
+}
+
+/// Receiving side of one peer
+#[derive(Debug, Default)]
+struct Incoming {
+    epoch: u32,
+    window: ReplayWindow,
+}
+
+/// What the receiver does with an inbound packet
+#[derive(Debug, Default)]
+struct Inbound {
+    /// Frame to hand to the application
+    frame: Option<Bytes>,
+    /// Ack to send back to the peer
+    ack: Option<Bytes>,
+}
+
+/// Sequence numbers, retransmit queues and dedup windows for reliable UDP
+///
+/// One instance is shared by a transport's senders and its receiver. The
+/// receiver task sends acks and retransmits, so
+/// [`UdpTransport::start_receiver`] must be running for frames to be
+/// acknowledged in either direction.
+pub struct Reliability {
+    config: ReliabilityConfig,
+    epoch: u32,
+    outgoing: Mutex<HashMap<SocketAddr, Outgoing>>,
+    incoming: Mutex<HashMap<SocketAddr, Incoming>>,
+    retransmits: AtomicU64,
+    duplicates: AtomicU64,
+    given_up: AtomicU64,
+}
+
+impl fmt::Debug for Reliability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reliability")
+            .field("config", &self.config)
+            .field("epoch", &self.epoch)
+            .field("in_flight", &self.in_flight())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Reliability {
+    /// Create the reliability state with a fresh epoch
+    pub fn new(config: ReliabilityConfig) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let epoch = (nanos ^ (nanos >> 32)) as u32 ^ std::process::id().rotate_left(16);
+        Self::with_epoch(config, epoch)
+    }
+
+    fn with_epoch(config: ReliabilityConfig, epoch: u32) -> Self {
+        Self {
+            config,
+            epoch,
+            outgoing: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+            retransmits: AtomicU64::new(0),
+            duplicates: AtomicU64::new(0),
+            given_up: AtomicU64::new(0),
+        }
+    }
+
+    /// The settings in use
+    pub fn config(&self) -> &ReliabilityConfig {
+        &self.config
+    }
+
+    /// Frames retransmitted so far
+    pub fn retransmits(&self) -> u64 {
+        self.retransmits.load(Ordering::Relaxed)
+    }
+
+    /// Duplicate frames dropped by the receiver so far
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
+    /// Frames given up without an ack so far
+    pub fn given_up(&self) -> u64 {
+        self.given_up.load(Ordering::Relaxed)
+    }
+
+    /// Frames sent and not yet acknowledged, across all peers
+    pub fn in_flight(&self) -> usize {
+        self.outgoing
+            .lock()
+            .values()
+            .map(|out| out.pending.len())
+            .sum()
+    }
+
+    /// Forget a peer's queues and dedup window
+    pub fn remove_peer(&self, peer: &SocketAddr) {
+        self.outgoing.lock().remove(peer);
+        self.incoming.lock().remove(peer);
+    }
+
+    /// Number a frame for `peer` and queue it until it is acknowledged
+    fn wrap(&self, peer: &SocketAddr, frame: &[u8]) -> Bytes {
+        let mut outgoing = self.outgoing.lock();
+        let out = outgoing.entry(*peer).or_default();
+        out.next_seq += 1;
+        let seq = out.next_seq;
+
+        let mut packet = Vec::with_capacity(DATA_HEADER_LEN + frame.len());
+        packet.push(DATA_MARKER);
+        packet.extend_from_slice(&self.epoch.to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(frame);
+        let packet = Bytes::from(packet);
+
+        out.pending.insert(
+            seq,
+            Pending {
+                packet: packet.clone(),
+                due: Instant::now() + self.config.initial_rto,
+                rto: self.config.initial_rto,
+                retransmits: 0,
+            },
+        );
+        while out.pending.len() > self.config.max_in_flight.max(1) {
+            out.pending.pop_first();
+            self.given_up.fetch_add(1, Ordering::Relaxed);
+        }
+        packet
+    }
+
+    /// Handle a packet from `peer`. Packets without a reliability marker are
+    /// passed through unchanged.
+    fn receive(&self, peer: &SocketAddr, packet: Bytes) -> Inbound {
+        match packet.first() {
+            Some(&DATA_MARKER) if packet.len() >= DATA_HEADER_LEN => {
+                let epoch = u32::from_be_bytes(packet[1..5].try_into().expect("4-byte epoch"));
+                let seq = u64::from_be_bytes(packet[5..13].try_into().expect("8-byte sequence"));
+
+                let mut incoming = self.incoming.lock();
+                let state = incoming.entry(*peer).or_default();
+                if state.epoch != epoch {
+                    // Peer restarted and numbers from 1 again
+                    *state = Incoming {
+                        epoch,
+                        window: ReplayWindow::default(),
+                    };
+                }
+                let fresh = state.window.accept(seq);
+                if !fresh {
+                    self.duplicates.fetch_add(1, Ordering::Relaxed);
+                }
+
+                let mut ack = Vec::with_capacity(ACK_LEN);
+                ack.push(ACK_MARKER);
+                ack.extend_from_slice(&epoch.to_be_bytes());
+                ack.extend_from_slice(&state.window.highest.to_be_bytes());
+                ack.extend_from_slice(&state.window.seen.to_be_bytes());
+                Inbound {
+                    frame: fresh.then(|| packet.slice(DATA_HEADER_LEN..)),
+                    ack: Some(Bytes::from(ack)),
+                }
+            }
+            Some(&ACK_MARKER) if packet.len() >= ACK_LEN => {
+                let epoch = u32::from_be_bytes(packet[1..5].try_into().expect("4-byte epoch"));
+                let highest =
+                    u64::from_be_bytes(packet[5..13].try_into().expect("8-byte sequence"));
+                let seen = u64::from_be_bytes(packet[13..21].try_into().expect("8-byte bitmap"));
+                if epoch == self.epoch {
+                    if let Some(out) = self.outgoing.lock().get_mut(peer) {
+                        for offset in 0..REPLAY_WINDOW.min(highest) {
+                            if seen & (1 << offset) != 0 {
+                                out.pending.remove(&(highest - offset));
+                            }
+                        }
+                    }
+                }
+                Inbound::default()
+            }
+            _ => Inbound {
+                frame: Some(packet),
+                ack: None,
+            },
+        }
+    }
+
+    /// Packets whose ack is overdue at `now`, backing off each one's timer
+    fn due(&self, now: Instant) -> Vec<(SocketAddr, Bytes)> {
+        let mut resend = Vec::new();
+        let mut outgoing = self.outgoing.lock();
+        for (peer, out) in outgoing.iter_mut() {
+            out.pending.retain(|seq, pending| {
+                if pending.due > now {
+                    return true;
+                }
+                if pending.retransmits >= self.config.max_retransmits {
+                    warn!("UDP gave up on frame {} to {}", seq, peer);
+                    self.given_up.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                pending.retransmits += 1;
+                pending.rto = (pending.rto * 2).min(self.config.max_rto);
+                pending.due = now + pending.rto;
+                resend.push((*peer, pending.packet.clone()));
+                true
+            });
+        }
+        self.retransmits
+            .fetch_add(resend.len() as u64, Ordering::Relaxed);
+        resend
+    }
+
+    /// How often the receiver task checks for overdue acks
+    fn tick(&self) -> Duration {
+        (self.config.initial_rto / 4).max(Duration::from_millis(5))
+    }
+}
+
+/// Send one datagram, sealing it first when frame authentication is on
+async fn send_datagram(
+    socket: &UdpSocket,
+    auth: Option<&FrameAuth>,
+    target: SocketAddr,
+    packet: &[u8],
+) -> std::io::Result<usize> {
+    match auth {
+        Some(auth) => socket.send_to(&auth.seal(&target, packet), target).await,
+        None => socket.send_to(packet, target).await,
+    }
+}
+
 /// UDP transport (connectionless)
 pub struct UdpTransport {
     socket: Arc<UdpSocket>,
     config: UdpConfig,
+    auth: Option<Arc<FrameAuth>>,
+    reliability: Option<Arc<Reliability>>,
 }
 
 impl UdpTransport {
@@ -48,6 +570,8 @@ impl UdpTransport {
         Ok(Self {
             socket: Arc::new(socket),
             config: UdpConfig::default(),
+            auth: None,
+            reliability: None,
         })
     }
 
@@ -57,12 +581,37 @@ impl UdpTransport {
             .await
             .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
 
+        let reliability = config
+            .reliability
+            .clone()
+            .map(|config| Arc::new(Reliability::new(config)));
         Ok(Self {
             socket: Arc::new(socket),
             config,
+            auth: None,
+            reliability,
         })
     }
 
+    /// Authenticate every datagram sent and received with `auth`
+    ///
+    /// Unauthenticated, forged and replayed datagrams are dropped by the
+    /// receiver and counted in [`FrameAuth::rejected`].
+    pub fn with_frame_auth(mut self, auth: FrameAuth) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// The frame authentication in use, if any
+    pub fn frame_auth(&self) -> Option<&Arc<FrameAuth>> {
+        self.auth.as_ref()
+    }
+
+    /// The reliability state in use, if [`UdpConfig::reliability`] is set
+    pub fn reliability(&self) -> Option<&Arc<Reliability>> {
+        self.reliability.as_ref()
+    }
+
     /// Get local address
     pub fn local_addr(&self) -> Result<SocketAddr> {
         self.socket.local_addr().map_err(TransportError::Io)
@@ -74,38 +623,90 @@ impl UdpTransport {
             socket: self.socket.clone(),
             remote,
             connected: Arc::new(Mutex::new(true)),
+            auth: self.auth.clone(),
+            reliability: self.reliability.clone(),
         }
     }
 
     /// Start receiving packets
+    ///
+    /// With reliability enabled, this task also acknowledges inbound frames
+    /// and retransmits outbound ones.
     pub fn start_receiver(&self) -> UdpReceiver {
         let (tx, rx) = mpsc::channel(100);
         let socket = self.socket.clone();
         let max_size = self.config.max_packet_size;
+        let auth = self.auth.clone();
+        let reliability = self.reliability.clone();
 
         tokio::spawn(async move {
             let mut buf = vec![0u8; max_size];
+            let mut retransmit = tokio::time::interval(
+                reliability
+                    .as_ref()
+                    .map_or(Duration::from_secs(1), |r| r.tick()),
+            );
+            retransmit.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
             loop {
-                match socket.recv_from(&mut buf).await {
-                    Ok((len, from)) => {
-                        debug!("UDP received {} bytes from {}", len, from);
-                        let data = Bytes::copy_from_slice(&buf[..len]);
-                        if tx.send((TransportEvent::Data(data), from)).await.is_err() {
-                            break;
+                tokio::select! {
+                    received = socket.recv_from(&mut buf) => match received {
+                        Ok((len, from)) => {
+                            debug!("UDP received {} bytes from {}", len, from);
+                            let data = match &auth {
+                                Some(auth) => match auth.open(&from, &buf[..len]) {
+                                    Ok(data) => data,
+                                    Err(reason) => {
+                                        debug!("UDP dropped datagram from {}: {}", from, reason);
+                                        continue;
+                                    }
+                                },
+                                None => Bytes::copy_from_slice(&buf[..len]),
+                            };
+                            let data = match &reliability {
+                                Some(reliability) => {
+                                    let inbound = reliability.receive(&from, data);
+                                    if let Some(ack) = inbound.ack {
+                                        if let Err(e) =
+                                            send_datagram(&socket, auth.as_deref(), from, &ack).await
+                                        {
+                                            debug!("UDP ack to {} failed: {}", from, e);
+                                        }
+                                    }
+                                    match inbound.frame {
+                                        Some(frame) => frame,
+                                        None => continue,
+                                    }
+                                }
+                                None => data,
+                            };
+                            if tx.send((TransportEvent::Data(data), from)).await.is_err() {
+                                break;
+                            }
                         }
-                    }
-                    Err(e) => {
-                        error!("UDP receive error: {}", e);
-                        if tx
-                            .send((
-                                TransportEvent::Error(e.to_string()),
-                                SocketAddr::from(([0, 0, 0, 0], 0)),
-                            ))
-                            .await
-                            .is_err()
-                        {
-                            break;
+                        Err(e) => {
+                            error!("UDP receive error: {}", e);
+                            if tx
+                                .send((
+                                    TransportEvent::Error(e.to_string()),
+                                    SocketAddr::from(([0, 0, 0, 0], 0)),
+                                ))
+                                .await
+                                .is_err()
+                            {
+                                break;
+                            }
+                        }
+                    },
+                    _ = retransmit.tick(), if reliability.is_some() => {
+                        let due = reliability.as_ref().map(|r| r.due(Instant::now()));
+                        for (peer, packet) in due.unwrap_or_default() {
+                            debug!("UDP retransmitting {} bytes to {}", packet.len(), peer);
+                            if let Err(e) =
+                                send_datagram(&socket, auth.as_deref(), peer, &packet).await
+                            {
+                                debug!("UDP retransmit to {} failed: {}", peer, e);
+                            }
                         }
                     }
                 }
@@ -117,8 +718,15 @@ impl UdpTransport {
 
     /// Send to a specific address
     pub async fn send_to(&self, data: &[u8], target: SocketAddr) -> Result<()> {
-        self.socket
-            .send_to(data, target)
+        let wrapped;
+        let data = match &self.reliability {
+            Some(reliability) => {
+                wrapped = reliability.wrap(&target, data);
+                &wrapped[..]
+            }
+            None => data,
+        };
+        send_datagram(&self.socket, self.auth.as_deref(), target, data)
             .await
             .map_err(|e| TransportError::SendFailed(e.to_string()))?;
         Ok(())
@@ -137,11 +745,27 @@ pub struct UdpSender {
     socket: Arc<UdpSocket>,
     remote: SocketAddr,
     connected: Arc<Mutex<bool>>,
+    auth: Option<Arc<FrameAuth>>,
+    reliability: Option<Arc<Reliability>>,
+}
+
+impl UdpSender {
+    fn frame(&self, data: Bytes) -> Bytes {
+        let data = match &self.reliability {
+            Some(reliability) => reliability.wrap(&self.remote, &data),
+            None => data,
+        };
+        match &self.auth {
+            Some(auth) => auth.seal(&self.remote, &data),
+            None => data,
+        }
+    }
 }
 
 #[async_trait]
 impl TransportSender for UdpSender {
     async fn send(&self, data: Bytes) -> Result<()> {
+        let data = self.frame(data);
         self.socket
             .send_to(&data, self.remote)
             .await
@@ -151,6 +775,7 @@ impl TransportSender for UdpSender {
 
     fn try_send(&self, data: Bytes) -> Result<()> {
         // UDP is inherently non-blocking - spawn a task for the async send
+        let data = self.frame(data);
         let socket = Arc::clone(&self.socket);
         let remote = self.remote;
         tokio::spawn(async move {
@@ -257,4 +882,145 @@ mod tests {
 
         assert_eq!(from.port(), client.local_addr().unwrap().port());
     }
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+        assert!(!window.accept(0));
+        assert!(window.accept(1));
+        assert!(!window.accept(1));
+        assert!(window.accept(5));
+        assert!(window.accept(3), "out of order inside the window");
+        assert!(!window.accept(3));
+
+        assert!(window.accept(100));
+        assert!(window.accept(37), "oldest slot still accepted");
+        assert!(!window.accept(36), "older than the window");
+        assert!(!window.accept(5));
+    }
+
+    #[test]
+    fn test_frame_auth_rejects_tamper_replay_and_wrong_key() {
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let sender = FrameAuth::from_token("token");
+        let receiver = FrameAuth::from_token("token");
+
+        let packet = sender.seal(&peer, b"frame");
+        assert_eq!(receiver.open(&peer, &packet).unwrap().as_ref(), b"frame");
+        assert_eq!(receiver.open(&peer, &packet), Err(FrameRejected::Replayed));
+
+        let mut tampered = sender.seal(&peer, b"frame").to_vec();
+        tampered[HEADER_LEN] ^= 1;
+        assert_eq!(receiver.open(&peer, &tampered), Err(FrameRejected::BadMac));
+        assert_eq!(
+            receiver.open(&peer, b"frame"),
+            Err(FrameRejected::Malformed)
+        );
+
+        let other = FrameAuth::from_token("other");
+        let forged = other.seal(&peer, b"frame");
+        assert_eq!(receiver.open(&peer, &forged), Err(FrameRejected::BadMac));
+        assert_eq!(receiver.rejected(), 4);
+
+        receiver.set_peer_secret(peer, b"other");
+        assert!(receiver.open(&peer, &forged).is_ok());
+    }
+
+    fn reliability(epoch: u32) -> Reliability {
+        Reliability::with_epoch(
+            ReliabilityConfig {
+                initial_rto: Duration::from_millis(100),
+                max_rto: Duration::from_millis(250),
+                max_retransmits: 2,
+                max_in_flight: 4,
+            },
+            epoch,
+        )
+    }
+
+    #[test]
+    fn test_reliability_acks_and_dedup() {
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let sender = reliability(7);
+        let receiver = reliability(9);
+
+        let first = sender.wrap(&peer, b"one");
+        let second = sender.wrap(&peer, b"two");
+        let third = sender.wrap(&peer, b"three");
+        assert_eq!(sender.in_flight(), 3);
+
+        // The first packet is lost; the others arrive out of order
+        let inbound = receiver.receive(&peer, third.clone());
+        assert_eq!(inbound.frame.unwrap().as_ref(), b"three");
+        sender.receive(&peer, inbound.ack.unwrap());
+        let inbound = receiver.receive(&peer, second);
+        assert_eq!(inbound.frame.unwrap().as_ref(), b"two");
+        assert!(sender.receive(&peer, inbound.ack.unwrap()).frame.is_none());
+        assert_eq!(
+            sender.in_flight(),
+            1,
+            "selective ack leaves only the lost frame"
+        );
+
+        let duplicate = receiver.receive(&peer, third);
+        assert!(duplicate.frame.is_none());
+        assert!(duplicate.ack.is_some(), "duplicates are acked again");
+        assert_eq!(receiver.duplicates(), 1);
+
+        let inbound = receiver.receive(&peer, first);
+        assert_eq!(inbound.frame.unwrap().as_ref(), b"one");
+        sender.receive(&peer, inbound.ack.unwrap());
+        assert_eq!(sender.in_flight(), 0);
+
+        // Unmarked packets pass through; acks for another epoch are ignored
+        let plain = receiver.receive(&peer, Bytes::from_static(b"plain"));
+        assert_eq!(plain.frame.unwrap().as_ref(), b"plain");
+        assert!(plain.ack.is_none());
+        sender.wrap(&peer, b"four");
+        let inbound = receiver.receive(&peer, reliability(8).wrap(&peer, b"other epoch"));
+        sender.receive(&peer, inbound.ack.unwrap());
+        assert_eq!(sender.in_flight(), 1);
+    }
+
+    #[test]
+    fn test_reliability_restarted_peer_gets_fresh_window() {
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let receiver = reliability(1);
+        let before = reliability(10);
+        assert!(receiver
+            .receive(&peer, before.wrap(&peer, b"a"))
+            .frame
+            .is_some());
+
+        let restarted = reliability(11);
+        let inbound = receiver.receive(&peer, restarted.wrap(&peer, b"b"));
+        assert_eq!(inbound.frame.unwrap().as_ref(), b"b");
+    }
+
+    #[test]
+    fn test_reliability_backoff_and_give_up() {
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let sender = reliability(1);
+        let packet = sender.wrap(&peer, b"set");
+        let start = Instant::now();
+
+        assert!(sender.due(start).is_empty());
+        let due = sender.due(start + Duration::from_millis(100));
+        assert_eq!(due, vec![(peer, packet.clone())]);
+        // Backed off to 200ms
+        assert!(sender.due(start + Duration::from_millis(250)).is_empty());
+        assert_eq!(sender.due(start + Duration::from_millis(300)).len(), 1);
+        // Capped at max_rto (250ms), then given up after two retransmits
+        assert!(sender.due(start + Duration::from_millis(500)).is_empty());
+        assert!(sender.due(start + Duration::from_millis(550)).is_empty());
+        assert_eq!(sender.retransmits(), 2);
+        assert_eq!(sender.given_up(), 1);
+        assert_eq!(sender.in_flight(), 0);
+
+        for _ in 0..6 {
+            sender.wrap(&peer, b"burst");
+        }
+        assert_eq!(sender.in_flight(), 4);
+        assert_eq!(sender.given_up(), 3);
+    }
 }
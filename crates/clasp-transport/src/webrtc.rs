@@ -8,6 +8,11 @@
 //! CLASP uses two DataChannels:
 //! - "clasp" - Unreliable, unordered (for streams, QoS Fire)
 //! - "clasp-reliable" - Reliable, ordered (for params/events, QoS Confirm/Commit)
+//!
+//! [`WebRtcServer`] accepts connections on a native router. A browser POSTs
+//! its SDP offer (with its ICE candidates gathered) to [`SIGNALING_PATH`]
+//! on the server's HTTP address and gets the SDP answer back; the
+//! connection is accepted once the "clasp-reliable" channel opens.
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -17,7 +22,19 @@ use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
 use crate::error::{Result, TransportError};
+#[cfg(feature = "webrtc")]
+use crate::traits::TransportServer;
 use crate::traits::{TransportEvent, TransportReceiver, TransportSender};
+#[cfg(feature = "webrtc")]
+use std::net::SocketAddr;
+#[cfg(feature = "webrtc")]
+use std::sync::Weak;
+#[cfg(feature = "webrtc")]
+use std::time::Duration;
+#[cfg(feature = "webrtc")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "webrtc")]
+use tokio::net::{TcpListener, TcpStream};
 
 #[cfg(feature = "webrtc")]
 use webrtc_rs::{
@@ -66,6 +83,25 @@ impl Default for WebRtcConfig {
 /// Data received callback type - (data, reliable)
 pub type DataCallback = Box<dyn Fn(Bytes, bool) + Send + Sync>;
 
+/// HTTP path a [`WebRtcServer`] takes SDP offers on
+pub const SIGNALING_PATH: &str = "/offer";
+
+/// Longest wait for ICE gathering before an SDP is sent without the rest
+#[cfg(feature = "webrtc")]
+const GATHERING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time an answered peer has to open its reliable channel
+#[cfg(feature = "webrtc")]
+const OPEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest signaling request accepted (an SDP offer is a few KB)
+#[cfg(feature = "webrtc")]
+const MAX_SIGNALING_REQUEST: usize = 64 * 1024;
+
+/// Connections opened but not yet accepted by the server's owner
+#[cfg(feature = "webrtc")]
+const ACCEPT_BACKLOG: usize = 64;
+
 /// WebRTC transport for CLASP
 #[cfg(feature = "webrtc")]
 pub struct WebRtcTransport {
@@ -357,6 +393,13 @@ impl WebRtcTransport {
         Ok(())
     }
 
+    /// The local SDP once ICE gathering has finished, with every candidate in
+    /// it, for signaling that doesn't trickle candidates (such as
+    /// [`WebRtcServer`])
+    pub async fn gathered_local_description(&self) -> Result<String> {
+        gathered_sdp(&self.peer_connection).await
+    }
+
     /// Set callback to be called when connection is ready (reliable channel opens)
     pub fn on_connection_ready<F>(&self, callback: F)
     where
@@ -402,6 +445,7 @@ impl WebRtcTransport {
                 WebRtcSender {
                     channel: dc.clone(),
                     connected: Arc::new(Mutex::new(true)),
+                    peer_connection: None,
                 },
                 WebRtcReceiver { rx },
             )
@@ -416,6 +460,7 @@ impl WebRtcTransport {
                 WebRtcSender {
                     channel: dc.clone(),
                     connected: Arc::new(Mutex::new(true)),
+                    peer_connection: None,
                 },
                 WebRtcReceiver { rx },
             )
@@ -595,6 +640,9 @@ impl WebRtcTransport {
 pub struct WebRtcSender {
     channel: Arc<RTCDataChannel>,
     connected: Arc<Mutex<bool>>,
+    /// Peer connection owned by this sender (connections accepted by a
+    /// [`WebRtcServer`]), closed with it
+    peer_connection: Option<Arc<RTCPeerConnection>>,
 }
 
 #[cfg(feature = "webrtc")]
@@ -641,6 +689,11 @@ impl TransportSender for WebRtcSender {
             .close()
             .await
             .map_err(|e| TransportError::SendFailed(format!("DataChannel close failed: {}", e)))?;
+        if let Some(ref pc) = self.peer_connection {
+            pc.close().await.map_err(|e| {
+                TransportError::SendFailed(format!("PeerConnection close failed: {}", e))
+            })?;
+        }
         Ok(())
     }
 }
@@ -659,6 +712,392 @@ impl TransportReceiver for WebRtcReceiver {
     }
 }
 
+/// An accepted connection, waiting for [`WebRtcServer::accept`]
+#[cfg(feature = "webrtc")]
+type Accepted = (WebRtcSender, WebRtcReceiver, SocketAddr);
+
+/// Events of an answered peer, taken when its reliable channel opens
+#[cfg(feature = "webrtc")]
+type PendingEvents = Arc<Mutex<Option<mpsc::Receiver<TransportEvent>>>>;
+
+/// WebRTC server: answers SDP offers POSTed to [`SIGNALING_PATH`] over HTTP
+/// and accepts each peer once its reliable channel opens.
+///
+/// Signaling doesn't trickle ICE candidates: the offer must carry the
+/// browser's candidates (send `localDescription` after ICE gathering
+/// completes) and the answer carries the server's. Responses allow any
+/// origin, so a page served from elsewhere can connect. The peer address
+/// reported for a connection is that of its signaling request.
+///
+/// ```no_run
+/// use clasp_transport::{TransportServer, WebRtcConfig, WebRtcServer};
+///
+/// # async fn example() -> clasp_transport::Result<()> {
+/// let mut server = WebRtcServer::bind_with_config(
+///     "0.0.0.0:7332",
+///     WebRtcConfig {
+///         ice_servers: vec![],
+///         ..Default::default()
+///     },
+/// )
+/// .await?;
+/// let (sender, receiver, addr) = server.accept().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "webrtc")]
+pub struct WebRtcServer {
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<Accepted>,
+    signaling: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "webrtc")]
+impl WebRtcServer {
+    /// Bind the signaling endpoint, using the default ICE servers
+    pub async fn bind(addr: &str) -> Result<Self> {
+        Self::bind_with_config(addr, WebRtcConfig::default()).await
+    }
+
+    /// Bind with custom configuration (ICE servers for the server's side)
+    pub async fn bind_with_config(addr: &str, config: WebRtcConfig) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| TransportError::BindFailed(e.to_string()))?;
+        let local_addr = listener.local_addr()?;
+        let (accepted_tx, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+        let signaling = tokio::spawn(run_signaling(listener, config, accepted_tx));
+
+        info!(
+            "WebRTC signaling listening on http://{}{}",
+            local_addr, SIGNALING_PATH
+        );
+        Ok(Self {
+            local_addr,
+            accepted,
+            signaling,
+        })
+    }
+}
+
+#[cfg(feature = "webrtc")]
+impl Drop for WebRtcServer {
+    fn drop(&mut self) {
+        self.signaling.abort();
+    }
+}
+
+#[cfg(feature = "webrtc")]
+#[async_trait]
+impl TransportServer for WebRtcServer {
+    type Sender = WebRtcSender;
+    type Receiver = WebRtcReceiver;
+
+    fn protocol(&self) -> &'static str {
+        "webrtc"
+    }
+
+    async fn accept(&mut self) -> Result<(Self::Sender, Self::Receiver, SocketAddr)> {
+        self.accepted
+            .recv()
+            .await
+            .ok_or_else(|| TransportError::AcceptFailed("signaling stopped".into()))
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.signaling.abort();
+        Ok(())
+    }
+}
+
+/// Serve signaling requests until the server is dropped
+#[cfg(feature = "webrtc")]
+async fn run_signaling(
+    listener: TcpListener,
+    config: WebRtcConfig,
+    accepted: mpsc::Sender<Accepted>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let config = config.clone();
+                let accepted = accepted.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = signal(stream, peer, &config, accepted).await {
+                        debug!("WebRTC signaling with {} failed: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) => warn!("WebRTC signaling accept error: {}", e),
+        }
+    }
+}
+
+/// Answer one signaling request
+#[cfg(feature = "webrtc")]
+async fn signal(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    config: &WebRtcConfig,
+    accepted: mpsc::Sender<Accepted>,
+) -> std::io::Result<()> {
+    let (status, content_type, body) = match read_request(&mut stream).await? {
+        Some((method, path, _)) if method == "OPTIONS" => {
+            debug!("WebRTC signaling preflight for {}", path);
+            ("204 No Content", "text/plain", String::new())
+        }
+        Some((method, path, offer)) if method == "POST" && path == SIGNALING_PATH => {
+            match answer(&offer, peer, config, accepted).await {
+                Ok(sdp) => ("201 Created", "application/sdp", sdp),
+                Err(e) => {
+                    warn!("WebRTC offer from {} refused: {}", peer, e);
+                    ("400 Bad Request", "text/plain", format!("{}\n", e))
+                }
+            }
+        }
+        Some((method, _, _)) if method == "POST" => {
+            ("404 Not Found", "text/plain", "not found\n".to_string())
+        }
+        Some(_) => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+        None => ("400 Bad Request", "text/plain", "bad request\n".to_string()),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read an HTTP request: (method, path, body), or `None` if it is malformed
+/// or too large
+#[cfg(feature = "webrtc")]
+async fn read_request(
+    stream: &mut TcpStream,
+) -> std::io::Result<Option<(String, String, Vec<u8>)>> {
+    let mut request = Vec::with_capacity(4096);
+    let mut buf = [0u8; 4096];
+    let head_len = loop {
+        if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if request.len() >= MAX_SIGNALING_REQUEST {
+            return Ok(None);
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buf[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&request[..head_len]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut parts = lines.next().unwrap_or_default().split(' ');
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if head_len + content_length > MAX_SIGNALING_REQUEST {
+        return Ok(None);
+    }
+
+    let mut body = request.split_off(head_len);
+    while body.len() < content_length {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+    body.truncate(content_length);
+    Ok(Some((method, path, body)))
+}
+
+/// Answer an SDP offer with a new peer connection, which is passed to
+/// `accepted` once its reliable channel opens
+#[cfg(feature = "webrtc")]
+async fn answer(
+    offer: &[u8],
+    peer: SocketAddr,
+    config: &WebRtcConfig,
+    accepted: mpsc::Sender<Accepted>,
+) -> Result<String> {
+    let offer = std::str::from_utf8(offer)
+        .map_err(|_| TransportError::Protocol("SDP offer is not UTF-8".into()))?;
+    let offer = RTCSessionDescription::offer(offer.to_string())
+        .map_err(|e| TransportError::Protocol(format!("Invalid offer: {}", e)))?;
+
+    let pc = WebRtcTransport::create_peer_connection(config).await?;
+    let (events_tx, events_rx) = mpsc::channel(100);
+    let connected = Arc::new(Mutex::new(true));
+    // Still here if the reliable channel never opens
+    let pending: PendingEvents = Arc::new(Mutex::new(Some(events_rx)));
+
+    let state_events = events_tx.clone();
+    let state_connected = Arc::clone(&connected);
+    pc.on_peer_connection_state_change(Box::new(move |state| {
+        debug!("WebRTC peer {} connection state: {:?}", peer, state);
+        let events = state_events.clone();
+        let connected = Arc::clone(&state_connected);
+        Box::pin(async move {
+            if matches!(
+                state,
+                RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed
+            ) && std::mem::replace(&mut *connected.lock(), false)
+            {
+                let _ = events
+                    .send(TransportEvent::Disconnected {
+                        reason: Some(format!("peer connection {}", state)),
+                    })
+                    .await;
+            }
+        })
+    }));
+
+    // The handlers hold the peer connection weakly; the sender owns it
+    let weak_pc = Arc::downgrade(&pc);
+    let channel_pending = Arc::clone(&pending);
+    pc.on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
+        let data_events = events_tx.clone();
+        channel.on_message(Box::new(move |msg: DataChannelMessage| {
+            let events = data_events.clone();
+            let data = Bytes::copy_from_slice(&msg.data);
+            Box::pin(async move {
+                let _ = events.send(TransportEvent::Data(data)).await;
+            })
+        }));
+
+        if channel.label() == "clasp-reliable" {
+            open_reliable_channel(
+                channel,
+                peer,
+                Weak::clone(&weak_pc),
+                Arc::clone(&channel_pending),
+                Arc::clone(&connected),
+                events_tx.clone(),
+                accepted.clone(),
+            );
+        }
+        Box::pin(async {})
+    }));
+
+    let sdp = async {
+        pc.set_remote_description(offer).await.map_err(|e| {
+            TransportError::ConnectionFailed(format!("Set remote description failed: {}", e))
+        })?;
+        let answer = pc.create_answer(None).await.map_err(|e| {
+            TransportError::ConnectionFailed(format!("Create answer failed: {}", e))
+        })?;
+        pc.set_local_description(answer).await.map_err(|e| {
+            TransportError::ConnectionFailed(format!("Set local description failed: {}", e))
+        })?;
+        gathered_sdp(&pc).await
+    }
+    .await;
+    let sdp = match sdp {
+        Ok(sdp) => sdp,
+        Err(e) => {
+            let _ = pc.close().await;
+            return Err(e);
+        }
+    };
+
+    // Give up on peers that never open the channel
+    tokio::spawn(async move {
+        tokio::time::sleep(OPEN_TIMEOUT).await;
+        if pending.lock().is_some() {
+            debug!("WebRTC peer {} never opened its channel", peer);
+            let _ = pc.close().await;
+        }
+    });
+
+    Ok(sdp)
+}
+
+/// Accept the peer when its reliable channel opens
+#[cfg(feature = "webrtc")]
+fn open_reliable_channel(
+    channel: Arc<RTCDataChannel>,
+    peer: SocketAddr,
+    pc: Weak<RTCPeerConnection>,
+    pending: PendingEvents,
+    connected: Arc<Mutex<bool>>,
+    events: mpsc::Sender<TransportEvent>,
+    accepted: mpsc::Sender<Accepted>,
+) {
+    let close_events = events;
+    let close_connected = Arc::clone(&connected);
+    channel.on_close(Box::new(move || {
+        let events = close_events.clone();
+        let connected = Arc::clone(&close_connected);
+        Box::pin(async move {
+            if std::mem::replace(&mut *connected.lock(), false) {
+                let _ = events
+                    .send(TransportEvent::Disconnected { reason: None })
+                    .await;
+            }
+        })
+    }));
+
+    let open_channel = Arc::clone(&channel);
+    channel.on_open(Box::new(move || {
+        let rx = pending.lock().take();
+        let (Some(rx), Some(pc)) = (rx, pc.upgrade()) else {
+            return Box::pin(async {});
+        };
+        let sender = WebRtcSender {
+            channel: Arc::clone(&open_channel),
+            connected: Arc::clone(&connected),
+            peer_connection: Some(pc),
+        };
+        let accepted = accepted.clone();
+        Box::pin(async move {
+            info!("WebRTC connection accepted from {}", peer);
+            if let Err(mpsc::error::SendError((sender, _, _))) =
+                accepted.send((sender, WebRtcReceiver { rx }, peer)).await
+            {
+                let _ = sender.close().await;
+            }
+        })
+    }));
+}
+
+/// The local SDP of `pc` once ICE gathering finishes (or times out)
+#[cfg(feature = "webrtc")]
+async fn gathered_sdp(pc: &RTCPeerConnection) -> Result<String> {
+    let mut gathered = pc.gathering_complete_promise().await;
+    if tokio::time::timeout(GATHERING_TIMEOUT, gathered.recv())
+        .await
+        .is_err()
+    {
+        warn!("ICE gathering timed out, sending the candidates found so far");
+    }
+    pc.local_description()
+        .await
+        .map(|description| description.sdp)
+        .ok_or_else(|| TransportError::ConnectionFailed("No local description".into()))
+}
+
 // Stub implementations when WebRTC feature is disabled
 #[cfg(not(feature = "webrtc"))]
 pub struct WebRtcTransport;
@@ -6,10 +6,10 @@ use futures_util::{SinkExt, StreamExt};
 use parking_lot::Mutex;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc;
 use tokio_tungstenite::{
-    connect_async,
+    client_async, connect_async,
     tungstenite::{
         handshake::{
             client::generate_key,
@@ -18,12 +18,14 @@ use tokio_tungstenite::{
         http::Request,
         protocol::Message as WsMessage,
     },
+    WebSocketStream,
 };
 use tracing::{debug, error, info, warn};
 
+use crate::deflate::{DeflateParams, DeflateStream, Role, EXTENSION};
 use crate::error::{Result, TransportError};
 use crate::traits::{
-    Transport, TransportEvent, TransportReceiver, TransportSender, TransportServer,
+    ConnectionInfo, Transport, TransportEvent, TransportReceiver, TransportSender, TransportServer,
 };
 
 use clasp_core::WS_SUBPROTOCOL;
@@ -37,12 +39,16 @@ pub const DEFAULT_CHANNEL_BUFFER_SIZE: usize = 1000;
 pub struct WebSocketConfig {
     /// Subprotocol to use
     pub subprotocol: String,
-    /// Maximum message size
+    /// Maximum message size. With compression on, this bounds compressed
+    /// messages both as received and once inflated.
     pub max_message_size: usize,
     /// Ping interval in seconds
     pub ping_interval: u64,
     /// Channel buffer size for send/receive queues
     pub channel_buffer_size: usize,
+    /// Negotiate permessage-deflate compression (RFC 7692). Only takes
+    /// effect when the peer supports it too.
+    pub compression: bool,
 }
 
 impl Default for WebSocketConfig {
@@ -52,6 +58,7 @@ impl Default for WebSocketConfig {
             max_message_size: 64 * 1024, // 64KB
             ping_interval: 30,
             channel_buffer_size: DEFAULT_CHANNEL_BUFFER_SIZE,
+            compression: false,
         }
     }
 }
@@ -72,6 +79,213 @@ impl WebSocketTransport {
     pub fn with_config(config: WebSocketConfig) -> Self {
         Self { config }
     }
+
+    /// Connect using `config` instead of the defaults
+    pub async fn connect_with_config(
+        url: &str,
+        config: WebSocketConfig,
+    ) -> Result<(WebSocketSender, WebSocketReceiver)> {
+        info!("Connecting to WebSocket: {}", url);
+
+        // Parse the URL to extract host for the Host header
+        let parsed_url =
+            url::Url::parse(url).map_err(|e| TransportError::InvalidUrl(e.to_string()))?;
+
+        let host = parsed_url
+            .host_str()
+            .ok_or_else(|| TransportError::InvalidUrl("Missing host in URL".to_string()))?;
+
+        let host_header = if let Some(port) = parsed_url.port() {
+            format!("{}:{}", host, port)
+        } else {
+            host.to_string()
+        };
+
+        // Build a complete WebSocket upgrade request with all required headers
+        let ws_key = generate_key();
+        let mut request = Request::builder()
+            .method("GET")
+            .uri(url)
+            .header("Host", &host_header)
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Sec-WebSocket-Key", &ws_key)
+            .header("Sec-WebSocket-Version", "13")
+            .header("Sec-WebSocket-Protocol", &config.subprotocol);
+        if config.compression {
+            request = request.header("Sec-WebSocket-Extensions", EXTENSION);
+        }
+        let request = request
+            .body(())
+            .map_err(|e| TransportError::InvalidUrl(e.to_string()))?;
+
+        if !config.compression {
+            let (ws_stream, response) = connect_async(request)
+                .await
+                .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+            debug!("WebSocket connected, response: {:?}", response.status());
+
+            // Check subprotocol
+            if let Some(protocol) = response.headers().get("Sec-WebSocket-Protocol") {
+                debug!("Server subprotocol: {:?}", protocol);
+            }
+            return Ok(spawn_client_tasks(ws_stream, config.channel_buffer_size));
+        }
+
+        // Compression sits between the socket (or TLS) and tungstenite, so
+        // open the connection here instead of letting connect_async do it
+        let port = parsed_url
+            .port_or_known_default()
+            .ok_or_else(|| TransportError::InvalidUrl("Missing port in URL".to_string()))?;
+        let tcp = tokio::net::TcpStream::connect((host, port))
+            .await
+            .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+        match parsed_url.scheme() {
+            "ws" => {
+                let stream = DeflateStream::new(tcp, Role::Client, config.max_message_size);
+                handshake_client(request, stream, config.channel_buffer_size).await
+            }
+            "wss" => {
+                let connector = native_tls::TlsConnector::new()
+                    .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+                let tls = tokio_native_tls::TlsConnector::from(connector)
+                    .connect(host, tcp)
+                    .await
+                    .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+                let stream = DeflateStream::new(tls, Role::Client, config.max_message_size);
+                handshake_client(request, stream, config.channel_buffer_size).await
+            }
+            scheme => Err(TransportError::InvalidUrl(format!(
+                "Unsupported URL scheme: {}",
+                scheme
+            ))),
+        }
+    }
+}
+
+/// Run the client handshake over an already-connected stream.
+async fn handshake_client<S>(
+    request: Request<()>,
+    stream: S,
+    buffer_size: usize,
+) -> Result<(WebSocketSender, WebSocketReceiver)>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (ws_stream, response) = client_async(request, stream)
+        .await
+        .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+
+    debug!("WebSocket connected, response: {:?}", response.status());
+    if let Some(extensions) = response.headers().get("Sec-WebSocket-Extensions") {
+        debug!("Server extensions: {:?}", extensions);
+    }
+
+    Ok(spawn_client_tasks(ws_stream, buffer_size))
+}
+
+/// Spawn the writer and reader tasks for a client connection.
+fn spawn_client_tasks<S>(
+    ws_stream: WebSocketStream<S>,
+    buffer_size: usize,
+) -> (WebSocketSender, WebSocketReceiver)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // Split the WebSocket stream
+    let (write, read) = ws_stream.split();
+
+    // Create channels with larger buffers for better load handling
+    let (send_tx, mut send_rx) = mpsc::channel::<WsMessage>(buffer_size);
+    let (event_tx, event_rx) = mpsc::channel::<TransportEvent>(buffer_size);
+
+    let connected = Arc::new(Mutex::new(true));
+    let connected_write = connected.clone();
+    let connected_read = connected.clone();
+
+    // Spawn writer task
+    tokio::spawn(async move {
+        let mut write = write;
+        while let Some(msg) = send_rx.recv().await {
+            if let Err(e) = write.send(msg).await {
+                error!("WebSocket write error: {}", e);
+                break;
+            }
+        }
+        *connected_write.lock() = false;
+    });
+
+    // Spawn reader task
+    let event_tx_clone = event_tx.clone();
+    tokio::spawn(async move {
+        let mut read = read;
+
+        // Send connected event
+        let _ = event_tx_clone.send(TransportEvent::Connected).await;
+
+        while let Some(result) = read.next().await {
+            match result {
+                Ok(msg) => {
+                    match msg {
+                        WsMessage::Binary(data) => {
+                            let _ = event_tx_clone
+                                .send(TransportEvent::Data(Bytes::from(data)))
+                                .await;
+                        }
+                        WsMessage::Text(text) => {
+                            // Convert text to bytes (shouldn't happen in Clasp)
+                            warn!("Received text message, converting to bytes");
+                            let _ = event_tx_clone
+                                .send(TransportEvent::Data(Bytes::from(text)))
+                                .await;
+                        }
+                        WsMessage::Ping(data) => {
+                            debug!("Received ping");
+                            // Pong is handled automatically by tungstenite
+                            let _ = data;
+                        }
+                        WsMessage::Pong(_) => {
+                            debug!("Received pong");
+                        }
+                        WsMessage::Close(frame) => {
+                            let reason = frame.map(|f| f.reason.to_string());
+                            info!("WebSocket closed: {:?}", reason);
+                            let _ = event_tx_clone
+                                .send(TransportEvent::Disconnected { reason })
+                                .await;
+                            break;
+                        }
+                        WsMessage::Frame(_) => {
+                            // Raw frame, ignore
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("WebSocket read error: {}", e);
+                    let _ = event_tx_clone
+                        .send(TransportEvent::Error(e.to_string()))
+                        .await;
+                    let _ = event_tx_clone
+                        .send(TransportEvent::Disconnected {
+                            reason: Some(e.to_string()),
+                        })
+                        .await;
+                    break;
+                }
+            }
+        }
+
+        *connected_read.lock() = false;
+    });
+
+    let sender = WebSocketSender {
+        tx: send_tx,
+        connected,
+    };
+
+    let receiver = WebSocketReceiver { rx: event_rx };
+
+    (sender, receiver)
 }
 
 impl Default for WebSocketTransport {
@@ -116,6 +330,10 @@ impl TransportSender for WebSocketSender {
         *self.connected.lock()
     }
 
+    fn queued(&self) -> Option<usize> {
+        Some(self.tx.max_capacity() - self.tx.capacity())
+    }
+
     async fn close(&self) -> Result<()> {
         let _ = self.tx.send(WsMessage::Close(None)).await;
         *self.connected.lock() = false;
@@ -141,142 +359,7 @@ impl Transport for WebSocketTransport {
     type Receiver = WebSocketReceiver;
 
     async fn connect(url: &str) -> Result<(Self::Sender, Self::Receiver)> {
-        info!("Connecting to WebSocket: {}", url);
-
-        // Parse the URL to extract host for the Host header
-        let parsed_url =
-            url::Url::parse(url).map_err(|e| TransportError::InvalidUrl(e.to_string()))?;
-
-        let host = parsed_url
-            .host_str()
-            .ok_or_else(|| TransportError::InvalidUrl("Missing host in URL".to_string()))?;
-
-        let host_header = if let Some(port) = parsed_url.port() {
-            format!("{}:{}", host, port)
-        } else {
-            host.to_string()
-        };
-
-        // Build a complete WebSocket upgrade request with all required headers
-        let ws_key = generate_key();
-        let request = Request::builder()
-            .method("GET")
-            .uri(url)
-            .header("Host", &host_header)
-            .header("Upgrade", "websocket")
-            .header("Connection", "Upgrade")
-            .header("Sec-WebSocket-Key", &ws_key)
-            .header("Sec-WebSocket-Version", "13")
-            .header("Sec-WebSocket-Protocol", WS_SUBPROTOCOL)
-            .body(())
-            .map_err(|e| TransportError::InvalidUrl(e.to_string()))?;
-
-        // Connect
-        let (ws_stream, response) = connect_async(request)
-            .await
-            .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
-
-        debug!("WebSocket connected, response: {:?}", response.status());
-
-        // Check subprotocol
-        if let Some(protocol) = response.headers().get("Sec-WebSocket-Protocol") {
-            debug!("Server subprotocol: {:?}", protocol);
-        }
-
-        // Split the WebSocket stream
-        let (write, read) = ws_stream.split();
-
-        // Create channels with larger buffers for better load handling
-        let (send_tx, mut send_rx) = mpsc::channel::<WsMessage>(DEFAULT_CHANNEL_BUFFER_SIZE);
-        let (event_tx, event_rx) = mpsc::channel::<TransportEvent>(DEFAULT_CHANNEL_BUFFER_SIZE);
-
-        let connected = Arc::new(Mutex::new(true));
-        let connected_write = connected.clone();
-        let connected_read = connected.clone();
-
-        // Spawn writer task
-        tokio::spawn(async move {
-            let mut write = write;
-            while let Some(msg) = send_rx.recv().await {
-                if let Err(e) = write.send(msg).await {
-                    error!("WebSocket write error: {}", e);
-                    break;
-                }
-            }
-            *connected_write.lock() = false;
-        });
-
-        // Spawn reader task
-        let event_tx_clone = event_tx.clone();
-        tokio::spawn(async move {
-            let mut read = read;
-
-            // Send connected event
-            let _ = event_tx_clone.send(TransportEvent::Connected).await;
-
-            while let Some(result) = read.next().await {
-                match result {
-                    Ok(msg) => {
-                        match msg {
-                            WsMessage::Binary(data) => {
-                                let _ = event_tx_clone
-                                    .send(TransportEvent::Data(Bytes::from(data)))
-                                    .await;
-                            }
-                            WsMessage::Text(text) => {
-                                // Convert text to bytes (shouldn't happen in Clasp)
-                                warn!("Received text message, converting to bytes");
-                                let _ = event_tx_clone
-                                    .send(TransportEvent::Data(Bytes::from(text)))
-                                    .await;
-                            }
-                            WsMessage::Ping(data) => {
-                                debug!("Received ping");
-                                // Pong is handled automatically by tungstenite
-                                let _ = data;
-                            }
-                            WsMessage::Pong(_) => {
-                                debug!("Received pong");
-                            }
-                            WsMessage::Close(frame) => {
-                                let reason = frame.map(|f| f.reason.to_string());
-                                info!("WebSocket closed: {:?}", reason);
-                                let _ = event_tx_clone
-                                    .send(TransportEvent::Disconnected { reason })
-                                    .await;
-                                break;
-                            }
-                            WsMessage::Frame(_) => {
-                                // Raw frame, ignore
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("WebSocket read error: {}", e);
-                        let _ = event_tx_clone
-                            .send(TransportEvent::Error(e.to_string()))
-                            .await;
-                        let _ = event_tx_clone
-                            .send(TransportEvent::Disconnected {
-                                reason: Some(e.to_string()),
-                            })
-                            .await;
-                        break;
-                    }
-                }
-            }
-
-            *connected_read.lock() = false;
-        });
-
-        let sender = WebSocketSender {
-            tx: send_tx,
-            connected,
-        };
-
-        let receiver = WebSocketReceiver { rx: event_rx };
-
-        Ok((sender, receiver))
+        Self::connect_with_config(url, WebSocketConfig::default()).await
     }
 
     fn local_addr(&self) -> Option<SocketAddr> {
@@ -308,18 +391,88 @@ impl WebSocketServer {
         })
     }
 
+    /// Bind `count` listeners to the same address with `SO_REUSEPORT`.
+    ///
+    /// The kernel spreads incoming connections across the listeners, so each
+    /// can run its own accept loop (and handshakes) in parallel. Port 0 picks
+    /// one free port shared by all of them. Only Linux balances
+    /// `SO_REUSEPORT` listeners; elsewhere a single listener is returned.
+    pub async fn bind_reuseport(addr: &str, count: usize) -> Result<Vec<Self>> {
+        let count = count.max(1);
+
+        #[cfg(target_os = "linux")]
+        {
+            let mut bind_addr = tokio::net::lookup_host(addr)
+                .await
+                .map_err(|e| TransportError::BindFailed(format!("{}: {}", addr, e)))?
+                .next()
+                .ok_or_else(|| TransportError::BindFailed(format!("{}: no address", addr)))?;
+
+            let mut servers = Vec::with_capacity(count);
+            for _ in 0..count {
+                let listener = reuseport_listener(bind_addr)
+                    .map_err(|e| TransportError::BindFailed(format!("{}: {}", bind_addr, e)))?;
+                bind_addr = listener.local_addr().map_err(TransportError::Io)?;
+                servers.push(Self {
+                    listener,
+                    config: WebSocketConfig::default(),
+                });
+            }
+
+            info!(
+                "WebSocket server listening on {} ({} acceptors)",
+                bind_addr, count
+            );
+            Ok(servers)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            if count > 1 {
+                warn!(
+                    "SO_REUSEPORT acceptors are only supported on Linux, using one listener for {}",
+                    addr
+                );
+            }
+            Ok(vec![Self::bind(addr).await?])
+        }
+    }
+
     pub fn with_config(mut self, config: WebSocketConfig) -> Self {
         self.config = config;
         self
     }
 }
 
+/// A non-blocking TCP listener with `SO_REUSEADDR` and `SO_REUSEPORT` set
+#[cfg(target_os = "linux")]
+fn reuseport_listener(addr: SocketAddr) -> std::io::Result<tokio::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
 #[async_trait]
 impl TransportServer for WebSocketServer {
     type Sender = WebSocketSender;
     type Receiver = WebSocketReceiver;
 
+    fn protocol(&self) -> &'static str {
+        "websocket"
+    }
+
     async fn accept(&mut self) -> Result<(Self::Sender, Self::Receiver, SocketAddr)> {
+        let (sender, receiver, info) = self.accept_with_info().await?;
+        Ok((sender, receiver, info.remote_addr))
+    }
+
+    async fn accept_with_info(&mut self) -> Result<(Self::Sender, Self::Receiver, ConnectionInfo)> {
         let (stream, addr) = loop {
             let (mut stream, addr) = self
                 .listener
@@ -388,120 +541,176 @@ impl TransportServer for WebSocketServer {
         };
 
         debug!("Accepted TCP connection from {}", addr);
-
-        // Upgrade to WebSocket with subprotocol negotiation
-        let subprotocol = self.config.subprotocol.clone();
-        let ws_stream = tokio_tungstenite::accept_hdr_async(
-            stream,
-            |req: &HsRequest, mut response: HsResponse| {
-                // Check if client requested our subprotocol
-                if let Some(protocols) = req.headers().get("Sec-WebSocket-Protocol") {
-                    if let Ok(protocols_str) = protocols.to_str() {
-                        // Client may request multiple protocols, comma-separated
-                        let requested: Vec<&str> =
-                            protocols_str.split(',').map(|s| s.trim()).collect();
-                        if requested.contains(&subprotocol.as_str()) {
-                            // Add our subprotocol to the response
-                            response
-                                .headers_mut()
-                                .insert("Sec-WebSocket-Protocol", subprotocol.parse().unwrap());
-                        }
-                    }
-                }
-                Ok(response)
-            },
-        )
-        .await
-        .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+        let local_addr = stream.local_addr().ok();
+
+        // Upgrade to WebSocket with subprotocol and compression negotiation
+        let subprotocol = self.config.subprotocol.as_str();
+        let compression = self.config.compression;
+        let mut agreed = None;
+        let callback = |req: &HsRequest, response: HsResponse| {
+            let response = negotiate(req, response, subprotocol, compression);
+            agreed = response
+                .headers()
+                .get("Sec-WebSocket-Protocol")
+                .and_then(|protocol| protocol.to_str().ok())
+                .map(str::to_string);
+            Ok(response)
+        };
+        let buffer_size = self.config.channel_buffer_size;
+        let (sender, receiver) = if compression {
+            let stream = DeflateStream::new(stream, Role::Server, self.config.max_message_size);
+            let ws_stream = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+            spawn_server_tasks(ws_stream, buffer_size)
+        } else {
+            let ws_stream = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .map_err(|e| TransportError::ConnectionFailed(e.to_string()))?;
+            spawn_server_tasks(ws_stream, buffer_size)
+        };
 
         info!("WebSocket client connected from {}", addr);
 
-        // Split the stream
-        let (write, read) = ws_stream.split();
+        let info = ConnectionInfo {
+            local_addr,
+            subprotocol: agreed,
+            ..ConnectionInfo::new(self.protocol(), addr)
+        };
+        Ok((sender, receiver, info))
+    }
 
-        // Create channels with configurable buffer size for better load handling
-        let buffer_size = self.config.channel_buffer_size;
-        let (send_tx, mut send_rx) = mpsc::channel::<WsMessage>(buffer_size);
-        let (event_tx, event_rx) = mpsc::channel::<TransportEvent>(buffer_size);
-
-        let connected = Arc::new(Mutex::new(true));
-        let connected_write = connected.clone();
-        let connected_read = connected.clone();
-
-        // Spawn writer task
-        tokio::spawn(async move {
-            let mut write = write;
-            while let Some(msg) = send_rx.recv().await {
-                if let Err(e) = write.send(msg).await {
-                    error!("WebSocket write error: {}", e);
-                    break;
-                }
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr().map_err(TransportError::Io)
+    }
+
+    async fn close(&self) -> Result<()> {
+        // TCP listener doesn't need explicit close
+        Ok(())
+    }
+}
+
+/// Fill in the handshake response: echo our subprotocol if the client asked
+/// for it, and accept permessage-deflate if enabled and offered.
+fn negotiate(
+    req: &HsRequest,
+    mut response: HsResponse,
+    subprotocol: &str,
+    compression: bool,
+) -> HsResponse {
+    // Check if client requested our subprotocol
+    if let Some(protocols) = req.headers().get("Sec-WebSocket-Protocol") {
+        if let Ok(protocols_str) = protocols.to_str() {
+            // Client may request multiple protocols, comma-separated
+            let requested: Vec<&str> = protocols_str.split(',').map(|s| s.trim()).collect();
+            if requested.contains(&subprotocol) {
+                // Add our subprotocol to the response
+                response
+                    .headers_mut()
+                    .insert("Sec-WebSocket-Protocol", subprotocol.parse().unwrap());
             }
-            *connected_write.lock() = false;
-        });
+        }
+    }
+    if compression {
+        let params = req
+            .headers()
+            .get_all("Sec-WebSocket-Extensions")
+            .iter()
+            .filter_map(|offers| offers.to_str().ok())
+            .find_map(DeflateParams::accept_offer);
+        if let Some(params) = params {
+            response.headers_mut().insert(
+                "Sec-WebSocket-Extensions",
+                params.to_header().parse().unwrap(),
+            );
+        }
+    }
+    response
+}
 
-        // Spawn reader task
-        let event_tx_clone = event_tx.clone();
-        tokio::spawn(async move {
-            let mut read = read;
+/// Spawn the writer and reader tasks for an accepted connection.
+fn spawn_server_tasks<S>(
+    ws_stream: WebSocketStream<S>,
+    buffer_size: usize,
+) -> (WebSocketSender, WebSocketReceiver)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // Split the stream
+    let (write, read) = ws_stream.split();
+
+    // Create channels with configurable buffer size for better load handling
+    let (send_tx, mut send_rx) = mpsc::channel::<WsMessage>(buffer_size);
+    let (event_tx, event_rx) = mpsc::channel::<TransportEvent>(buffer_size);
+
+    let connected = Arc::new(Mutex::new(true));
+    let connected_write = connected.clone();
+    let connected_read = connected.clone();
+
+    // Spawn writer task
+    tokio::spawn(async move {
+        let mut write = write;
+        while let Some(msg) = send_rx.recv().await {
+            if let Err(e) = write.send(msg).await {
+                error!("WebSocket write error: {}", e);
+                break;
+            }
+        }
+        *connected_write.lock() = false;
+    });
 
-            let _ = event_tx_clone.send(TransportEvent::Connected).await;
+    // Spawn reader task
+    let event_tx_clone = event_tx.clone();
+    tokio::spawn(async move {
+        let mut read = read;
 
-            while let Some(result) = read.next().await {
-                match result {
-                    Ok(msg) => match msg {
-                        WsMessage::Binary(data) => {
-                            let _ = event_tx_clone
-                                .send(TransportEvent::Data(Bytes::from(data)))
-                                .await;
-                        }
-                        WsMessage::Close(frame) => {
-                            let reason = frame.map(|f| f.reason.to_string());
-                            let _ = event_tx_clone
-                                .send(TransportEvent::Disconnected { reason })
-                                .await;
-                            break;
-                        }
-                        WsMessage::Ping(_) | WsMessage::Pong(_) => {
-                            // tungstenite auto-responds to Ping with Pong
-                        }
-                        WsMessage::Text(_) => {
-                            debug!("Ignoring unexpected text WebSocket frame");
-                        }
-                        _ => {}
-                    },
-                    Err(e) => {
+        let _ = event_tx_clone.send(TransportEvent::Connected).await;
+
+        while let Some(result) = read.next().await {
+            match result {
+                Ok(msg) => match msg {
+                    WsMessage::Binary(data) => {
                         let _ = event_tx_clone
-                            .send(TransportEvent::Disconnected {
-                                reason: Some(e.to_string()),
-                            })
+                            .send(TransportEvent::Data(Bytes::from(data)))
+                            .await;
+                    }
+                    WsMessage::Close(frame) => {
+                        let reason = frame.map(|f| f.reason.to_string());
+                        let _ = event_tx_clone
+                            .send(TransportEvent::Disconnected { reason })
                             .await;
                         break;
                     }
+                    WsMessage::Ping(_) | WsMessage::Pong(_) => {
+                        // tungstenite auto-responds to Ping with Pong
+                    }
+                    WsMessage::Text(_) => {
+                        debug!("Ignoring unexpected text WebSocket frame");
+                    }
+                    _ => {}
+                },
+                Err(e) => {
+                    let _ = event_tx_clone
+                        .send(TransportEvent::Disconnected {
+                            reason: Some(e.to_string()),
+                        })
+                        .await;
+                    break;
                 }
             }
+        }
 
-            *connected_read.lock() = false;
-        });
-
-        let sender = WebSocketSender {
-            tx: send_tx,
-            connected,
-        };
+        *connected_read.lock() = false;
+    });
 
-        let receiver = WebSocketReceiver { rx: event_rx };
+    let sender = WebSocketSender {
+        tx: send_tx,
+        connected,
+    };
 
-        Ok((sender, receiver, addr))
-    }
+    let receiver = WebSocketReceiver { rx: event_rx };
 
-    fn local_addr(&self) -> Result<SocketAddr> {
-        self.listener.local_addr().map_err(TransportError::Io)
-    }
-
-    async fn close(&self) -> Result<()> {
-        // TCP listener doesn't need explicit close
-        Ok(())
-    }
+    (sender, receiver)
 }
 
 #[cfg(test)]
@@ -513,4 +722,55 @@ mod tests {
         let config = WebSocketConfig::default();
         assert_eq!(config.subprotocol, "clasp");
     }
+
+    async fn roundtrip(server_compression: bool, client_compression: bool) {
+        let mut server = WebSocketServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_config(WebSocketConfig {
+                compression: server_compression,
+                ..Default::default()
+            });
+        let url = format!("ws://{}", server.local_addr().unwrap());
+        let accept = tokio::spawn(async move { server.accept().await.unwrap() });
+
+        let config = WebSocketConfig {
+            compression: client_compression,
+            ..Default::default()
+        };
+        let (client_tx, mut client_rx) = WebSocketTransport::connect_with_config(&url, config)
+            .await
+            .unwrap();
+        let (server_tx, mut server_rx, _) = accept.await.unwrap();
+
+        let snapshot = Bytes::from("/mixer/ch/1/fader=0.75;".repeat(5000));
+        for _ in 0..3 {
+            client_tx.send(snapshot.clone()).await.unwrap();
+            server_tx.send(snapshot.clone()).await.unwrap();
+            client_tx.send(Bytes::from_static(b"small")).await.unwrap();
+        }
+        for rx in [&mut server_rx, &mut client_rx] {
+            let mut received = Vec::new();
+            while received.len() < 3 {
+                match rx.recv().await.unwrap() {
+                    TransportEvent::Data(data) => received.push(data),
+                    TransportEvent::Connected => {}
+                    other => panic!("unexpected event: {:?}", other),
+                }
+            }
+            assert!(received.iter().all(|d| d == &snapshot || d == "small"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_websocket_compression_roundtrip() {
+        roundtrip(true, true).await;
+    }
+
+    #[tokio::test]
+    async fn test_websocket_compression_one_sided() {
+        // Either side falls back to uncompressed frames
+        roundtrip(true, false).await;
+        roundtrip(false, true).await;
+    }
 }
@@ -6,6 +6,9 @@
 //! - Connection establishment
 //! - Stream operations
 //! - Datagram support
+//! - Stream priorities and connection migration
+//! - Certificate reload
+//! - Client certificates and handshake info
 //!
 //! Note: These tests require the 'quic' feature to be enabled
 
@@ -15,9 +18,14 @@ use std::net::SocketAddr;
 use std::time::Duration;
 
 use bytes::Bytes;
-use clasp_transport::quic::{CertVerification, QuicConfig, QuicTransport, CLASP_ALPN};
-use clasp_transport::{TransportReceiver, TransportSender};
-use rcgen::{generate_simple_self_signed, CertifiedKey};
+use clasp_transport::quic::{
+    CertVerification, ClientAuth, QuicConfig, QuicTransport, StreamPriority, CLASP_ALPN,
+};
+use clasp_transport::{TransportEvent, TransportReceiver, TransportSender};
+use rcgen::{
+    generate_simple_self_signed, BasicConstraints, CertificateParams, CertifiedKey,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
 
 // ============================================================================
 // Helper Functions
@@ -61,6 +69,7 @@ async fn test_quic_config_custom() {
         idle_timeout_ms: 60000,
         initial_window: 20,
         cert_verification: CertVerification::SkipVerification,
+        ..Default::default()
     };
 
     assert!(!config.enable_0rtt, "enable_0rtt should be false");
@@ -99,6 +108,7 @@ async fn test_quic_client_with_config() {
         idle_timeout_ms: 15000,
         initial_window: 5,
         cert_verification: CertVerification::SkipVerification,
+        ..Default::default()
     };
 
     let result = QuicTransport::new_client_with_config(config);
@@ -266,3 +276,319 @@ async fn test_quic_bidirectional_stream() {
     // Clean up server
     let _ = server_handle.await;
 }
+
+#[tokio::test]
+async fn test_quic_stream_preserves_frame_boundaries() {
+    let port = find_available_port().await;
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+    let (cert, key) = generate_self_signed_cert();
+    let server =
+        QuicTransport::new_server(addr, cert, key).expect("Server creation should succeed");
+    let client = QuicTransport::new_client_with_config(QuicConfig::insecure())
+        .expect("Client creation should succeed");
+
+    let frames: Vec<Bytes> = (0..20)
+        .map(|i| {
+            clasp_core::Frame::new(vec![i as u8; 100 + i * 500])
+                .encode()
+                .unwrap()
+        })
+        .collect();
+
+    let expected = frames.clone();
+    let server_handle = tokio::spawn(async move {
+        let conn = server.accept().await.expect("Accept should succeed");
+        let (_sender, mut receiver) = conn.accept_bi().await.expect("Accept bi should succeed");
+        for frame in expected {
+            match receiver.recv().await {
+                Some(clasp_transport::TransportEvent::Data(data)) => assert_eq!(data, frame),
+                other => panic!("Unexpected event: {:?}", other),
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let conn = client
+        .connect(addr, "localhost")
+        .await
+        .expect("Client connect should succeed");
+    let (sender, _receiver) = conn.open_bi().await.expect("Open bi should succeed");
+
+    // Back-to-back writes get coalesced (and split) by the stream; the
+    // receiver must still hand over one event per frame
+    for frame in frames {
+        sender.send(frame).await.expect("Send should succeed");
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), server_handle)
+        .await
+        .expect("Should not timeout")
+        .expect("Server task should succeed");
+}
+
+#[tokio::test]
+async fn test_quic_stream_per_priority_class() {
+    let port = find_available_port().await;
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+    let (cert, key) = generate_self_signed_cert();
+    let server =
+        QuicTransport::new_server(addr, cert, key).expect("Server creation should succeed");
+    let client = QuicTransport::new_client_with_config(QuicConfig::insecure())
+        .expect("Client creation should succeed");
+
+    let classes = [
+        StreamPriority::Control,
+        StreamPriority::Normal,
+        StreamPriority::Bulk,
+    ];
+
+    let server_handle = tokio::spawn(async move {
+        let conn = server.accept().await.expect("Accept should succeed");
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let (_sender, mut receiver) = conn.accept_bi().await.expect("Accept bi should succeed");
+            match receiver.recv().await {
+                Some(TransportEvent::Data(data)) => received.push(data[0]),
+                other => panic!("Unexpected event: {:?}", other),
+            }
+        }
+        received.sort();
+        received
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let conn = client
+        .connect(addr, "localhost")
+        .await
+        .expect("Client connect should succeed");
+
+    // One stream per class on the same connection; each is opened on the
+    // wire by its first write
+    let mut senders = Vec::new();
+    for (i, priority) in classes.into_iter().enumerate() {
+        let (sender, _receiver) = conn
+            .open_bi_with_priority(priority)
+            .await
+            .expect("Open bi should succeed");
+        sender
+            .send(Bytes::from(vec![i as u8]))
+            .await
+            .expect("Send should succeed");
+        senders.push(sender);
+    }
+
+    let received = tokio::time::timeout(Duration::from_secs(5), server_handle)
+        .await
+        .expect("Should not timeout")
+        .expect("Server task should succeed");
+    assert_eq!(received, vec![0, 1, 2]);
+}
+
+#[tokio::test]
+async fn test_quic_migration_is_reported() {
+    let port = find_available_port().await;
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+    let (cert, key) = generate_self_signed_cert();
+    let server =
+        QuicTransport::new_server(addr, cert, key).expect("Server creation should succeed");
+    let client = QuicTransport::new_client_with_config(QuicConfig::insecure())
+        .expect("Client creation should succeed");
+
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+    let server_handle = tokio::spawn(async move {
+        let conn = server.accept().await.expect("Accept should succeed");
+        let (_sender, mut receiver) = conn.accept_bi().await.expect("Accept bi should succeed");
+        let before = conn.remote_address();
+        match receiver.recv().await {
+            Some(TransportEvent::Data(data)) => assert_eq!(&data[..], b"before"),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+        let _ = ready_tx.send(());
+
+        // The move is reported, then data keeps flowing on the same stream
+        let mut moved_to = None;
+        loop {
+            match receiver.recv().await {
+                Some(TransportEvent::Migrated { from, to }) => {
+                    assert_eq!(from, before);
+                    moved_to = Some(to);
+                }
+                Some(TransportEvent::Data(data)) if &data[..] == b"after" => {
+                    return moved_to;
+                }
+                Some(TransportEvent::Data(_)) => {}
+                other => panic!("Unexpected event: {:?}", other),
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let conn = client
+        .connect(addr, "localhost")
+        .await
+        .expect("Client connect should succeed");
+    let (sender, _receiver) = conn.open_bi().await.expect("Open bi should succeed");
+    sender
+        .send(Bytes::from_static(b"before"))
+        .await
+        .expect("Send should succeed");
+    tokio::time::timeout(Duration::from_secs(5), ready_rx)
+        .await
+        .expect("Should not timeout")
+        .expect("Server should receive the first frame");
+
+    // Switch networks: same connection, new local socket
+    client
+        .rebind("127.0.0.1:0".parse().unwrap())
+        .expect("Rebind should succeed");
+    sender
+        .send(Bytes::from_static(b"moving"))
+        .await
+        .expect("Send should succeed");
+    // Leave time for the server's reader to notice the new address
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    sender
+        .send(Bytes::from_static(b"after"))
+        .await
+        .expect("Send should succeed");
+
+    let moved_to = tokio::time::timeout(Duration::from_secs(5), server_handle)
+        .await
+        .expect("Should not timeout")
+        .expect("Server task should succeed");
+    assert_eq!(moved_to, Some(client.local_addr().unwrap()));
+}
+
+#[tokio::test]
+async fn test_quic_certificate_reload() {
+    let port = find_available_port().await;
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+    let (old_cert, old_key) = generate_self_signed_cert();
+    let (new_cert, new_key) = generate_self_signed_cert();
+    let server = std::sync::Arc::new(
+        QuicTransport::new_server(addr, old_cert, old_key).expect("Server creation should succeed"),
+    );
+    let acceptor = std::sync::Arc::clone(&server);
+    tokio::spawn(async move {
+        loop {
+            // Failed handshakes surface as accept errors; keep listening
+            let Ok(conn) = acceptor.accept().await else {
+                continue;
+            };
+            tokio::spawn(async move {
+                if let Ok((sender, mut receiver)) = conn.accept_bi().await {
+                    // Echo until the stream closes
+                    while let Some(TransportEvent::Data(data)) = receiver.recv().await {
+                        let _ = sender.send(data).await;
+                    }
+                }
+            });
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Connected before the reload
+    let insecure = QuicTransport::new_client_with_config(QuicConfig::insecure()).unwrap();
+    let old_conn = insecure.connect(addr, "localhost").await.unwrap();
+    let (old_sender, mut old_receiver) = old_conn.open_bi().await.unwrap();
+
+    // Clients trusting only the new certificate can't connect yet
+    let pinned =
+        QuicTransport::new_client_with_config(QuicConfig::with_custom_roots(
+            vec![new_cert.clone()],
+        ))
+        .unwrap();
+    assert!(pinned.connect(addr, "localhost").await.is_err());
+
+    server
+        .reload_certificate(new_cert, new_key)
+        .expect("Reload should succeed");
+    assert!(server
+        .reload_certificate(vec![1, 2, 3], vec![4, 5, 6])
+        .is_err());
+
+    pinned
+        .connect(addr, "localhost")
+        .await
+        .expect("New handshakes should use the new certificate");
+
+    // The old connection is still up
+    old_sender
+        .send(Bytes::from_static(b"still here"))
+        .await
+        .unwrap();
+    match tokio::time::timeout(Duration::from_secs(5), old_receiver.recv()).await {
+        Ok(Some(TransportEvent::Data(data))) => assert_eq!(&data[..], b"still here"),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_quic_client_certificate_identity() {
+    let port = find_available_port().await;
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+    // A CA and a device certificate it signed
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    let device_key = KeyPair::generate().unwrap();
+    let mut device_params = CertificateParams::new(vec!["device-1".to_string()]).unwrap();
+    device_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let device_cert = device_params
+        .signed_by(&device_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let (cert, key) = generate_self_signed_cert();
+    let server = QuicTransport::new_server_with_config(
+        addr,
+        cert,
+        key,
+        QuicConfig {
+            client_auth: ClientAuth::Required(vec![ca_cert.der().to_vec()]),
+            ..Default::default()
+        },
+    )
+    .expect("Server creation should succeed");
+    let server_handle = tokio::spawn(async move {
+        loop {
+            // Clients without a certificate fail the handshake
+            if let Ok(conn) = server.accept().await {
+                return conn.connection_info();
+            }
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Accepted first, this would be the connection the server reports
+    let anonymous = QuicTransport::new_client_with_config(QuicConfig::insecure()).unwrap();
+    let _ = anonymous.connect(addr, "localhost").await;
+
+    let device = QuicTransport::new_client_with_config(QuicConfig {
+        client_certificate: Some((device_cert.der().to_vec(), device_key.serialize_der())),
+        ..QuicConfig::insecure()
+    })
+    .unwrap();
+    let _conn = device
+        .connect(addr, "localhost")
+        .await
+        .expect("Device should connect");
+
+    let info = tokio::time::timeout(Duration::from_secs(5), server_handle)
+        .await
+        .expect("Accept should not timeout")
+        .expect("Server task should not panic");
+    assert_eq!(info.protocol, "quic");
+    assert_eq!(info.local_addr, Some(addr));
+    assert_eq!(info.alpn.as_deref(), Some(CLASP_ALPN));
+    assert_eq!(info.server_name.as_deref(), Some("localhost"));
+    assert_eq!(
+        info.peer_certificate().map(|cert| cert.to_vec()),
+        Some(device_cert.der().to_vec())
+    );
+}
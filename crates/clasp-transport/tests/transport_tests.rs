@@ -6,7 +6,7 @@
 //! - Round-trip message verification
 //! - Reconnection handling
 //! - Error handling
-//! - Subprotocol negotiation
+//! - Subprotocol negotiation and accepted connection info
 //! - Large message handling
 //! - Concurrent connections
 
@@ -41,6 +41,7 @@ async fn connect_and_handshake(
         features: vec!["param".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     sender.send(codec::encode(&hello)?).await?;
 
@@ -54,7 +55,7 @@ async fn connect_and_handshake(
                     return Ok((sender, receiver));
                 }
             }
-            Ok(Some(TransportEvent::Connected)) => continue,
+            Ok(Some(TransportEvent::Connected | TransportEvent::Migrated { .. })) => continue,
             Ok(Some(TransportEvent::Disconnected { .. })) => {
                 return Err("Disconnected during handshake".into());
             }
@@ -96,6 +97,49 @@ async fn test_websocket_subprotocol() {
     assert!(connect_result.is_ok(), "Connect with subprotocol failed");
 }
 
+#[tokio::test]
+async fn test_websocket_accept_reports_connection_info() {
+    use clasp_transport::{TransportServer, WebSocketServer};
+
+    let mut server = WebSocketServer::bind("127.0.0.1:0")
+        .await
+        .expect("bind failed");
+    let addr = server.local_addr().unwrap();
+    let accept =
+        tokio::spawn(async move { server.accept_with_info().await.map(|(_, _, info)| info) });
+
+    let _client = WebSocketTransport::connect(&format!("ws://{}", addr))
+        .await
+        .expect("connect failed");
+    let info = timeout(Duration::from_secs(5), accept)
+        .await
+        .expect("accept timed out")
+        .unwrap()
+        .expect("accept failed");
+    assert_eq!(info.protocol, "websocket");
+    assert_eq!(info.local_addr, Some(addr));
+    assert_eq!(info.subprotocol.as_deref(), Some(WS_SUBPROTOCOL));
+    assert!(info.peer_certificate().is_none());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_websocket_reuseport_listeners_share_port() {
+    use clasp_transport::{TransportServer, WebSocketServer};
+
+    let servers = WebSocketServer::bind_reuseport("127.0.0.1:0", 4)
+        .await
+        .expect("bind failed");
+    assert_eq!(servers.len(), 4);
+
+    let ports: HashSet<u16> = servers
+        .iter()
+        .map(|s| s.local_addr().unwrap().port())
+        .collect();
+    assert_eq!(ports.len(), 1, "Acceptors should share one port");
+    assert_ne!(ports.into_iter().next(), Some(0));
+}
+
 #[tokio::test]
 async fn test_protocol_version() {
     // Verify protocol version (currently v1 in the codebase)
@@ -117,6 +161,7 @@ async fn test_websocket_binary_frames() {
         features: vec!["param".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     let bytes = codec::encode(&hello).expect("Encode failed");
     sender.send(bytes).await.expect("Send failed");
@@ -157,6 +202,7 @@ async fn test_roundtrip_encode_decode() {
             features: vec!["param".to_string(), "event".to_string()],
             capabilities: None,
             token: None,
+            session_resume: None,
         }),
         Message::Set(SetMessage {
             address: "/test/value".to_string(),
@@ -165,6 +211,8 @@ async fn test_roundtrip_encode_decode() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }),
         Message::Set(SetMessage {
             address: "/test/string".to_string(),
@@ -173,6 +221,8 @@ async fn test_roundtrip_encode_decode() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }),
     ];
 
@@ -229,6 +279,8 @@ async fn test_roundtrip_via_server() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     sender
         .send(codec::encode(&set_msg).unwrap())
@@ -348,6 +400,7 @@ async fn test_send_after_close() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
     let send_result = sender.send(codec::encode(&hello).unwrap()).await;
 
@@ -381,6 +434,8 @@ async fn test_large_message() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
     sender
         .send(codec::encode(&set).unwrap())
@@ -429,6 +484,8 @@ async fn test_message_size_boundaries() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         let encoded = codec::encode(&set).unwrap_or_else(|_| panic!("Encode size {} failed", size));
         sender
@@ -546,6 +603,7 @@ async fn test_magic_byte_verification() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
 
     let encoded = codec::encode(&msg).expect("Encode failed");
@@ -562,6 +620,7 @@ async fn test_frame_header_format() {
         features: vec![],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
 
     let encoded = codec::encode(&msg).expect("Encode failed");
@@ -606,6 +665,8 @@ async fn test_value_roundtrip_all_types() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&msg).unwrap_or_else(|_| panic!("Encode {} failed", name));
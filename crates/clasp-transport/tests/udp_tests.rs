@@ -5,9 +5,11 @@
 //! - Send/receive operations
 //! - Broadcast functionality
 //! - Multiple concurrent sockets
+//! - Authenticated framing and replay rejection
+//! - Reliable delivery with retransmits
 
 use bytes::Bytes;
-use clasp_transport::udp::{UdpBroadcast, UdpConfig, UdpTransport};
+use clasp_transport::udp::{FrameAuth, ReliabilityConfig, UdpBroadcast, UdpConfig, UdpTransport};
 use clasp_transport::{TransportEvent, TransportSender};
 use std::collections::HashSet;
 use std::time::Duration;
@@ -32,6 +34,7 @@ async fn test_udp_bind_with_config() {
     let config = UdpConfig {
         recv_buffer_size: 32768,
         max_packet_size: 1500,
+        reliability: None,
     };
 
     let transport = UdpTransport::bind_with_config("127.0.0.1:0", config)
@@ -284,3 +287,123 @@ async fn test_udp_bidirectional() {
         }
     }
 }
+
+// ============================================================================
+// Authenticated Framing Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_udp_frame_auth_drops_replayed_and_unauthenticated() {
+    let server = UdpTransport::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .with_frame_auth(FrameAuth::from_token("shared-token"));
+    let server_addr = server.local_addr().unwrap();
+    let mut receiver = server.start_receiver();
+
+    // An on-path attacker holding a captured datagram, sending from a plain socket
+    let client = UdpTransport::bind("127.0.0.1:0").await.unwrap();
+    let auth = FrameAuth::from_token("shared-token");
+    let captured = auth.seal(&server_addr, b"first");
+
+    client.send_to(&captured, server_addr).await.unwrap();
+    client.send_to(&captured, server_addr).await.unwrap();
+    client
+        .send_to(b"unauthenticated", server_addr)
+        .await
+        .unwrap();
+    client
+        .send_to(&auth.seal(&server_addr, b"second"), server_addr)
+        .await
+        .unwrap();
+
+    for expected in [&b"first"[..], &b"second"[..]] {
+        match tokio::time::timeout(Duration::from_secs(2), receiver.recv_from()).await {
+            Ok(Some((TransportEvent::Data(data), _))) => assert_eq!(data.as_ref(), expected),
+            other => panic!("Expected {:?}, got {:?}", expected, other),
+        }
+    }
+    assert_eq!(server.frame_auth().unwrap().rejected(), 2);
+}
+
+#[tokio::test]
+async fn test_udp_frame_auth_sender_round_trip() {
+    let server = UdpTransport::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .with_frame_auth(FrameAuth::from_secret(b"session-secret"));
+    let client = UdpTransport::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .with_frame_auth(FrameAuth::from_secret(b"session-secret"));
+    let mut receiver = server.start_receiver();
+
+    let sender = client.sender_to(server.local_addr().unwrap());
+    sender.send(Bytes::from_static(b"ping")).await.unwrap();
+
+    match tokio::time::timeout(Duration::from_secs(2), receiver.recv_from()).await {
+        Ok(Some((TransportEvent::Data(data), _))) => assert_eq!(data.as_ref(), b"ping"),
+        other => panic!("Expected ping, got {:?}", other),
+    }
+}
+
+// ============================================================================
+// Reliable Delivery Tests
+// ============================================================================
+
+fn reliable_config() -> UdpConfig {
+    UdpConfig {
+        reliability: Some(ReliabilityConfig {
+            initial_rto: Duration::from_millis(50),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_udp_reliable_retransmits_lost_frame() {
+    // Reserve a port, then free it so the first transmission is lost
+    let placeholder = UdpTransport::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = placeholder.local_addr().unwrap();
+    drop(placeholder);
+
+    let client = UdpTransport::bind_with_config("127.0.0.1:0", reliable_config())
+        .await
+        .unwrap()
+        .with_frame_auth(FrameAuth::from_token("shared-token"));
+    let _client_receiver = client.start_receiver();
+    let sender = client.sender_to(server_addr);
+    sender.send(Bytes::from_static(b"set")).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    let server = UdpTransport::bind_with_config(&server_addr.to_string(), reliable_config())
+        .await
+        .unwrap()
+        .with_frame_auth(FrameAuth::from_token("shared-token"));
+    let mut receiver = server.start_receiver();
+
+    match tokio::time::timeout(Duration::from_secs(2), receiver.recv_from()).await {
+        Ok(Some((TransportEvent::Data(data), _))) => assert_eq!(data.as_ref(), b"set"),
+        other => panic!("Expected retransmitted frame, got {:?}", other),
+    }
+
+    let reliability = client.reliability().unwrap();
+    for _ in 0..40 {
+        if reliability.in_flight() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    assert_eq!(reliability.in_flight(), 0, "ack should clear the frame");
+    assert!(reliability.retransmits() >= 1);
+    assert_eq!(reliability.given_up(), 0);
+
+    // Retransmits that crossed the ack are dropped, not delivered twice
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), receiver.recv_from())
+            .await
+            .is_err(),
+        "frame should be delivered once"
+    );
+}
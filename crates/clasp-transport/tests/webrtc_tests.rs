@@ -0,0 +1,129 @@
+//! WebRTC Server Tests (clasp-transport)
+//!
+//! Tests for accepting WebRTC connections with HTTP signaling:
+//! - Offer/answer over the signaling endpoint
+//! - Data in both directions on the accepted connection
+//! - Malformed and misrouted signaling requests
+
+#![cfg(feature = "webrtc")]
+
+use bytes::Bytes;
+use clasp_transport::webrtc::SIGNALING_PATH;
+use clasp_transport::{
+    TransportEvent, TransportReceiver, TransportSender, TransportServer, WebRtcConfig,
+    WebRtcServer, WebRtcTransport,
+};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+/// Host candidates only, so tests don't reach out to STUN servers
+fn local_config() -> WebRtcConfig {
+    WebRtcConfig {
+        ice_servers: vec![],
+        ..Default::default()
+    }
+}
+
+/// POST `body` to the signaling server, returning (status line, body)
+async fn post(addr: SocketAddr, method: &str, path: &str, body: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap().to_string();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn test_webrtc_server_accepts_offer() {
+    let mut server = WebRtcServer::bind_with_config("127.0.0.1:0", local_config())
+        .await
+        .expect("Bind should succeed");
+    let addr = server.local_addr().unwrap();
+    assert_eq!(server.protocol(), "webrtc");
+
+    let (client, _) = WebRtcTransport::new_offerer_with_config(local_config())
+        .await
+        .unwrap();
+    let (ready_tx, mut ready_rx) = mpsc::unbounded_channel();
+    client.on_connection_ready(move || {
+        let _ = ready_tx.send(());
+    });
+    let (data_tx, mut data_rx) = mpsc::unbounded_channel();
+    client.on_data(move |data, reliable| {
+        let _ = data_tx.send((data, reliable));
+    });
+
+    let offer = client.gathered_local_description().await.unwrap();
+    let (status, answer) = post(addr, "POST", SIGNALING_PATH, &offer).await;
+    assert!(status.contains("201"), "unexpected status: {}", status);
+    client.set_remote_answer(&answer).await.unwrap();
+
+    let (sender, mut receiver, _) = timeout(Duration::from_secs(10), server.accept())
+        .await
+        .expect("Accept should not time out")
+        .expect("Accept should succeed");
+    timeout(Duration::from_secs(10), ready_rx.recv())
+        .await
+        .expect("Client channel should open");
+
+    client
+        .send_reliable(Bytes::from_static(b"hello router"))
+        .await
+        .unwrap();
+    let event = timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("Server should receive data");
+    match event {
+        Some(TransportEvent::Data(data)) => assert_eq!(&data[..], b"hello router"),
+        other => panic!("expected data, got {:?}", other),
+    }
+
+    sender
+        .send(Bytes::from_static(b"hello browser"))
+        .await
+        .unwrap();
+    let (data, reliable) = timeout(Duration::from_secs(5), data_rx.recv())
+        .await
+        .expect("Client should receive data")
+        .unwrap();
+    assert_eq!(&data[..], b"hello browser");
+    assert!(reliable);
+
+    sender.close().await.unwrap();
+    assert!(!sender.is_connected());
+}
+
+#[tokio::test]
+async fn test_webrtc_signaling_rejects_bad_requests() {
+    let server = WebRtcServer::bind_with_config("127.0.0.1:0", local_config())
+        .await
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let (status, _) = post(addr, "POST", SIGNALING_PATH, "not an sdp").await;
+    assert!(status.contains("400"), "unexpected status: {}", status);
+
+    let (status, _) = post(addr, "POST", "/elsewhere", "").await;
+    assert!(status.contains("404"), "unexpected status: {}", status);
+
+    let (status, _) = post(addr, "GET", SIGNALING_PATH, "").await;
+    assert!(status.contains("405"), "unexpected status: {}", status);
+
+    // CORS preflight for browsers on another origin
+    let (status, _) = post(addr, "OPTIONS", SIGNALING_PATH, "").await;
+    assert!(status.contains("204"), "unexpected status: {}", status);
+}
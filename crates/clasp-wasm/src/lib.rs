@@ -13,7 +13,7 @@ use wasm_bindgen::prelude::*;
 use web_sys::{CloseEvent, ErrorEvent, MessageEvent, WebSocket};
 
 use clasp_core::{
-    codec, HelloMessage, Message, SetMessage, SubscribeMessage, SubscribeOptions, Value,
+    codec, ErrorCode, HelloMessage, Message, SetMessage, SubscribeMessage, SubscribeOptions, Value,
     PROTOCOL_VERSION, WS_SUBPROTOCOL,
 };
 
@@ -112,6 +112,7 @@ impl ClaspWasm {
                 ],
                 capabilities: None,
                 token: token_value,
+                session_resume: None,
             });
 
             if let Ok(bytes) = codec::encode(&hello) {
@@ -148,9 +149,8 @@ impl ClaspWasm {
                         }
                         Message::Error(error) => {
                             // Handle authentication errors
-                            match error.code {
-                                300 | 302 => {
-                                    // 300 = Unauthorized, 302 = TokenExpired
+                            match error.error_code() {
+                                Some(ErrorCode::Unauthorized | ErrorCode::TokenExpired) => {
                                     // Close the connection and notify
                                     let _ = ws_msg.close();
                                     *connected_msg.borrow_mut() = false;
@@ -332,6 +332,8 @@ impl ClaspWasm {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
         self.send_message(&msg);
     }
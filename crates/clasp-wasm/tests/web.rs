@@ -33,6 +33,8 @@ fn test_value_conversion_null() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     }))
     .unwrap();
 
@@ -56,6 +58,8 @@ fn test_value_conversion_bool() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
         .unwrap();
 
@@ -82,6 +86,8 @@ fn test_value_conversion_int() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
         .unwrap();
 
@@ -108,6 +114,8 @@ fn test_value_conversion_float() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
         .unwrap();
 
@@ -145,6 +153,8 @@ fn test_value_conversion_string() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
         .unwrap();
 
@@ -177,6 +187,8 @@ fn test_value_conversion_bytes() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
         .unwrap();
 
@@ -233,6 +245,8 @@ fn test_value_conversion_array() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         }))
         .unwrap();
 
@@ -261,6 +275,8 @@ fn test_value_conversion_map() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     }))
     .unwrap();
 
@@ -303,6 +319,8 @@ fn test_value_conversion_nested() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     }))
     .unwrap();
 
@@ -327,6 +345,7 @@ fn test_hello_message() {
         features: vec!["param".to_string(), "event".to_string()],
         capabilities: None,
         token: None,
+        session_resume: None,
     });
 
     let encoded = codec::encode(&hello).unwrap();
@@ -351,6 +370,7 @@ fn test_hello_with_token() {
         features: vec!["param".to_string()],
         capabilities: None,
         token: Some(token.clone()),
+        session_resume: None,
     });
 
     let encoded = codec::encode(&hello).unwrap();
@@ -373,6 +393,9 @@ fn test_welcome_message() {
         features: vec!["param".to_string(), "stream".to_string()],
         time: 1234567890,
         token: None,
+        challenge: None,
+        resume_token: None,
+        resumed: false,
     });
 
     let encoded = codec::encode(&welcome).unwrap();
@@ -397,6 +420,8 @@ fn test_set_message() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&set).unwrap();
@@ -489,6 +514,8 @@ fn test_binary_data_integrity() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).unwrap();
@@ -531,6 +558,8 @@ fn test_large_binary_payload() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).unwrap();
@@ -593,6 +622,8 @@ fn test_empty_containers() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&msg).unwrap();
@@ -620,6 +651,8 @@ fn test_special_floats() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&msg).unwrap();
@@ -665,6 +698,8 @@ fn test_unicode_strings() {
             lock: false,
             unlock: false,
             ttl: None,
+            trace: false,
+            branch: None,
         });
 
         let encoded = codec::encode(&msg).unwrap();
@@ -692,6 +727,8 @@ fn benchmark_encoding() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let start = js_sys::Date::now();
@@ -719,6 +756,8 @@ fn benchmark_decoding() {
         lock: false,
         unlock: false,
         ttl: None,
+        trace: false,
+        branch: None,
     });
 
     let encoded = codec::encode(&msg).unwrap();
@@ -9,8 +9,11 @@
 
 use clasp_core::Value;
 use clasp_router::{RouterState, Session, SnapshotFilter, WriteValidator};
+use anyhow::Context;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 // ---------------------------------------------------------------------------
 // Config types
@@ -40,6 +43,16 @@ pub struct AppConfig {
     pub rate_limits: Option<RateLimitConfig>,
 }
 
+impl AppConfig {
+    /// Read and parse an app config file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read app config {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse app config {}", path.display()))
+    }
+}
+
 /// A write validation rule.
 #[derive(Debug, Clone, Deserialize)]
 pub struct WriteRule {
@@ -269,13 +282,23 @@ fn substitute(template: &str, captures: &HashMap<&str, &str>, session_id: &str)
 // ---------------------------------------------------------------------------
 
 /// Generic rule-based write validator. Implements `WriteValidator`.
+///
+/// The rules can be swapped with [`replace`](Self::replace) while the
+/// validator is installed on a running router.
 pub struct RuleWriteValidator {
-    rules: Vec<WriteRule>,
+    rules: RwLock<Arc<Vec<WriteRule>>>,
 }
 
 impl RuleWriteValidator {
     pub fn new(rules: Vec<WriteRule>) -> Self {
-        Self { rules }
+        Self {
+            rules: RwLock::new(Arc::new(rules)),
+        }
+    }
+
+    /// Replace the rules; writes already being validated finish with the old ones
+    pub fn replace(&self, rules: Vec<WriteRule>) {
+        *self.rules.write().unwrap() = Arc::new(rules);
     }
 }
 
@@ -292,7 +315,8 @@ impl WriteValidator for RuleWriteValidator {
             None => return Ok(()), // unauthenticated: let scope check handle it
         };
 
-        for rule in &self.rules {
+        let rules = Arc::clone(&self.rules.read().unwrap());
+        for rule in rules.iter() {
             if let Some(captures) = match_address(&rule.path, address) {
                 // Pre-checks always run (even for null writes)
                 if !rule.pre_checks.is_empty() {
@@ -486,7 +510,14 @@ fn evaluate_single_check(
 // ---------------------------------------------------------------------------
 
 /// Generic rule-based snapshot filter. Implements `SnapshotFilter`.
+///
+/// Like [`RuleWriteValidator`], the rules can be swapped with
+/// [`replace`](Self::replace) while the filter is installed.
 pub struct RuleSnapshotFilter {
+    rules: RwLock<Arc<SnapshotRules>>,
+}
+
+struct SnapshotRules {
     transforms: Vec<SnapshotTransform>,
     visibility: Vec<VisibilityRule>,
 }
@@ -494,13 +525,30 @@ pub struct RuleSnapshotFilter {
 impl RuleSnapshotFilter {
     pub fn new(transforms: Vec<SnapshotTransform>, visibility: Vec<VisibilityRule>) -> Self {
         Self {
+            rules: RwLock::new(Arc::new(SnapshotRules {
+                transforms,
+                visibility,
+            })),
+        }
+    }
+
+    /// Replace the transforms and visibility rules
+    pub fn replace(&self, transforms: Vec<SnapshotTransform>, visibility: Vec<VisibilityRule>) {
+        *self.rules.write().unwrap() = Arc::new(SnapshotRules {
             transforms,
             visibility,
-        }
+        });
     }
+}
+
+impl SnapshotRules {
 
     /// Apply matching transforms (redact fields) to a ParamValue.
     fn apply_transforms(&self, mut pv: clasp_core::ParamValue) -> clasp_core::ParamValue {
+        // Redacting a field of an E2E envelope would only make it undecryptable
+        if pv.value.is_e2e_envelope() {
+            return pv;
+        }
         for transform in &self.transforms {
             if match_address(&transform.path, &pv.address).is_some() {
                 if let Value::Map(ref mut map) = pv.value {
@@ -614,21 +662,56 @@ impl SnapshotFilter for RuleSnapshotFilter {
         state: &RouterState,
     ) -> Vec<clasp_core::ParamValue> {
         let session_id = session.subject.as_deref().unwrap_or("");
+        let rules = Arc::clone(&self.rules.read().unwrap());
 
         params
             .into_iter()
             .filter_map(|pv| {
                 // Check visibility first (first-match)
-                if !self.is_visible(&pv.address, session_id, state) {
+                if !rules.is_visible(&pv.address, session_id, state) {
                     return None;
                 }
                 // Apply transforms (all matching)
-                Some(self.apply_transforms(pv))
+                Some(rules.apply_transforms(pv))
             })
             .collect()
     }
 }
 
+// ---------------------------------------------------------------------------
+// Hot reload
+// ---------------------------------------------------------------------------
+
+/// The parts of a running relay built from the app config.
+///
+/// [`apply`](Self::apply) installs a reloaded config on all of them in place,
+/// so sessions stay connected. Parts that are `None` (e.g. a library override
+/// of the write validator, or no `--auth-port`) are left alone.
+#[derive(Clone, Default)]
+pub struct LiveAppConfig {
+    pub write_validator: Option<Arc<RuleWriteValidator>>,
+    pub snapshot_filter: Option<Arc<RuleSnapshotFilter>>,
+    pub auth: Option<Arc<crate::auth::AuthState>>,
+}
+
+impl LiveAppConfig {
+    /// Install write rules, snapshot rules, scopes and rate limits from `config`.
+    pub fn apply(&self, config: &AppConfig) {
+        if let Some(ref validator) = self.write_validator {
+            validator.replace(config.write_rules.clone());
+        }
+        if let Some(ref filter) = self.snapshot_filter {
+            filter.replace(
+                config.snapshot_transforms.clone(),
+                config.snapshot_visibility.clone(),
+            );
+        }
+        if let Some(ref auth) = self.auth {
+            auth.update_app_config(config);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,6 +2,11 @@
 //!
 //! Provides user registration and login with argon2 password hashing,
 //! SQLite user storage, and CPSK token generation with scoped permissions.
+//!
+//! With the `caps` feature and an exchange key, `/auth/exchange` trades a
+//! `cpsk_` token for a short-lived `cap_` token limited to a subset of its
+//! scopes, so access can be handed to a third party without sharing the
+//! pre-shared key.
 
 use anyhow::Result;
 use argon2::{
@@ -10,17 +15,18 @@ use argon2::{
 };
 use axum::{
     extract::{ConnectInfo, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     routing::post,
     Json, Router,
 };
 use axum::http::{HeaderValue, Method};
 use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+use clasp_registry::migrate::Migration;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tower_http::cors::CorsLayer;
 
@@ -75,6 +81,19 @@ impl RateLimiter {
     }
 }
 
+/// Schema history of the auth database
+pub const AUTH_MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "users",
+    up: "CREATE TABLE IF NOT EXISTS users (
+            id TEXT PRIMARY KEY,
+            username TEXT UNIQUE NOT NULL,
+            password_hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );",
+    down: "DROP TABLE users;",
+}];
+
 /// Shared auth state
 pub struct AuthState {
     db: Mutex<Connection>,
@@ -83,9 +102,13 @@ pub struct AuthState {
     register_limiter: Mutex<RateLimiter>,
     /// Scope templates from app config. `{userId}` is replaced at token issue time.
     /// If None, issues full read/write tokens.
-    scope_templates: Option<Vec<String>>,
+    scope_templates: RwLock<Option<Vec<String>>>,
     /// Rate limit configuration (from app config or defaults).
-    rate_config: crate::app_config::RateLimitConfig,
+    rate_config: RwLock<crate::app_config::RateLimitConfig>,
+    /// Key that signs `cap_` tokens issued by `/auth/exchange` (a trust anchor
+    /// of the capability validator). If None, token exchange is disabled.
+    #[cfg(feature = "caps")]
+    exchange_key: Option<ed25519_dalek::SigningKey>,
 }
 
 impl AuthState {
@@ -95,31 +118,42 @@ impl AuthState {
         scope_templates: Option<Vec<String>>,
         rate_config: crate::app_config::RateLimitConfig,
     ) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-
-        // Create users table
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                username TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                created_at INTEGER NOT NULL
-            );"
-        )?;
+        let mut conn = Connection::open(db_path)?;
+        clasp_registry::migrate::migrate(&mut conn, AUTH_MIGRATIONS)?;
 
         Ok(Self {
             db: Mutex::new(conn),
             validator,
             login_limiter: Mutex::new(RateLimiter::new()),
             register_limiter: Mutex::new(RateLimiter::new()),
-            scope_templates,
-            rate_config,
+            scope_templates: RwLock::new(scope_templates),
+            rate_config: RwLock::new(rate_config),
+            #[cfg(feature = "caps")]
+            exchange_key: None,
         })
     }
 
+    /// Enable `/auth/exchange`, signing issued capability tokens with `key`.
+    #[cfg(feature = "caps")]
+    pub fn with_exchange_key(mut self, key: ed25519_dalek::SigningKey) -> Self {
+        self.exchange_key = Some(key);
+        self
+    }
+
+    /// Take scopes and rate limits from a reloaded app config.
+    /// Tokens already issued keep their scopes.
+    pub fn update_app_config(&self, app_config: &crate::app_config::AppConfig) {
+        *self.scope_templates.write().unwrap() = Some(app_config.scopes.clone());
+        *self.rate_config.write().unwrap() = app_config.rate_limits.clone().unwrap_or_default();
+    }
+
+    fn rate_config(&self) -> crate::app_config::RateLimitConfig {
+        self.rate_config.read().unwrap().clone()
+    }
+
     /// Build scopes for a user by substituting `{userId}` in scope templates.
     fn build_scopes(&self, user_id: &str) -> Vec<String> {
-        match &self.scope_templates {
+        match &*self.scope_templates.read().unwrap() {
             Some(templates) => templates
                 .iter()
                 .map(|s| s.replace("{userId}", user_id))
@@ -179,8 +213,9 @@ async fn register(
     let ip = extract_ip(request.extensions());
 
     // Rate limit registration per IP
-    let register_window = Duration::from_secs(state.rate_config.register_window_secs);
-    let register_max = state.rate_config.register_max_attempts;
+    let rate_config = state.rate_config();
+    let register_window = Duration::from_secs(rate_config.register_window_secs);
+    let register_max = rate_config.register_max_attempts;
     {
         let mut limiter = state.register_limiter.lock().unwrap();
         limiter.prune(register_window);
@@ -322,8 +357,9 @@ async fn login(
     let password = req.password;
     let ip_key = format!("ip:{}", ip);
     let user_key = format!("user:{}", username.to_lowercase());
-    let login_window = Duration::from_secs(state.rate_config.login_window_secs);
-    let login_max = state.rate_config.login_max_attempts;
+    let rate_config = state.rate_config();
+    let login_window = Duration::from_secs(rate_config.login_window_secs);
+    let login_max = rate_config.login_max_attempts;
 
     // Check rate limits before doing any work
     {
@@ -425,8 +461,9 @@ async fn guest(
     let ip = extract_ip(request.extensions());
 
     // Rate limit guest creation per IP
-    let register_window = Duration::from_secs(state.rate_config.register_window_secs);
-    let register_max = state.rate_config.register_max_attempts;
+    let rate_config = state.rate_config();
+    let register_window = Duration::from_secs(rate_config.register_window_secs);
+    let register_max = rate_config.register_max_attempts;
     {
         let mut limiter = state.register_limiter.lock().unwrap();
         limiter.prune(register_window);
@@ -524,6 +561,149 @@ async fn guest(
     }))
 }
 
+/// Lifetime of an exchanged token when the request doesn't give one (seconds)
+#[cfg(feature = "caps")]
+pub const EXCHANGE_DEFAULT_EXPIRES_IN: u64 = 300;
+/// Longest lifetime an exchanged token may have (seconds)
+#[cfg(feature = "caps")]
+pub const EXCHANGE_MAX_EXPIRES_IN: u64 = 3600;
+
+/// Request body for `POST /auth/exchange`.
+#[cfg(feature = "caps")]
+#[derive(Deserialize)]
+pub struct ExchangeRequest {
+    /// Scopes to grant, each within the presented token's scopes
+    scopes: Vec<String>,
+    /// Lifetime in seconds (default 300, at most 3600). Never outlives the
+    /// presented token.
+    expires_in: Option<u64>,
+    /// Hex-encoded Ed25519 public key to bind the token to (default: bearer)
+    audience: Option<String>,
+}
+
+#[cfg(feature = "caps")]
+#[derive(Serialize)]
+pub struct ExchangeResponse {
+    token: String,
+    scopes: Vec<String>,
+    expires_at: u64,
+}
+
+/// Whether `requested` grants nothing beyond one of the `granted` scopes.
+#[cfg(feature = "caps")]
+fn scope_within(requested: &Scope, granted: &[Scope]) -> bool {
+    granted.iter().any(|scope| {
+        scope.action().allows(requested.action())
+            && clasp_caps::token::pattern_is_subset(
+                requested.pattern().address().as_str(),
+                scope.pattern().address().as_str(),
+            )
+    })
+}
+
+/// Decode a hex-encoded Ed25519 public key.
+#[cfg(feature = "caps")]
+fn parse_audience(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let bytes: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<_>>()?;
+    let key: [u8; 32] = bytes.as_slice().try_into().ok()?;
+    ed25519_dalek::VerifyingKey::from_bytes(&key).ok()?;
+    Some(bytes)
+}
+
+/// Exchange the Bearer `cpsk_` token for an attenuated capability token.
+#[cfg(feature = "caps")]
+async fn exchange(
+    State(state): State<Arc<AuthState>>,
+    headers: HeaderMap,
+    Json(req): Json<ExchangeRequest>,
+) -> Result<Json<ExchangeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    use clasp_core::security::{TokenValidator, ValidationResult};
+
+    let unauthorized = |error: &str| (StatusCode::UNAUTHORIZED, Json(ErrorResponse { error: error.into() }));
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+
+    let Some(ref key) = state.exchange_key else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse {
+            error: "Token exchange is not enabled".into(),
+        })));
+    };
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| unauthorized("Expected a Bearer cpsk_ token"))?;
+    let info = match state.validator.validate(token) {
+        ValidationResult::Valid(info) => info,
+        ValidationResult::Expired => return Err(unauthorized("Token expired")),
+        _ => return Err(unauthorized("Invalid token")),
+    };
+
+    if req.scopes.is_empty() {
+        return Err(bad_request("At least one scope is required".into()));
+    }
+    for scope in &req.scopes {
+        let parsed = Scope::parse(scope)
+            .map_err(|e| bad_request(format!("Invalid scope {}: {}", scope, e)))?;
+        if !scope_within(&parsed, &info.scopes) {
+            return Err((StatusCode::FORBIDDEN, Json(ErrorResponse {
+                error: format!("Scope {} exceeds the presented token", scope),
+            })));
+        }
+    }
+    let expires_in = req.expires_in.unwrap_or(EXCHANGE_DEFAULT_EXPIRES_IN);
+    if expires_in == 0 || expires_in > EXCHANGE_MAX_EXPIRES_IN {
+        return Err(bad_request(format!(
+            "expires_in must be between 1 and {} seconds",
+            EXCHANGE_MAX_EXPIRES_IN
+        )));
+    }
+    let audience = match req.audience {
+        Some(ref hex) => Some(parse_audience(hex).ok_or_else(|| {
+            bad_request("audience must be a hex-encoded Ed25519 public key".into())
+        })?),
+        None => None,
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut expires_at = now + expires_in;
+    if let Some(token_expires) = info.expires_at {
+        let token_expires = token_expires
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        expires_at = expires_at.min(token_expires);
+    }
+
+    let internal = |_| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+        error: "Failed to issue token".into(),
+    }));
+    let cap = clasp_caps::CapabilityToken::create_root(key, req.scopes.clone(), expires_at, audience)
+        .map_err(internal)?;
+    let encoded = cap.encode().map_err(internal)?;
+
+    tracing::info!(
+        "Token exchange: {} -> {} until {}",
+        info.subject.as_deref().unwrap_or("unknown subject"),
+        req.scopes.join(","),
+        expires_at
+    );
+
+    Ok(Json(ExchangeResponse {
+        token: encoded,
+        scopes: req.scopes,
+        expires_at,
+    }))
+}
+
 /// Build the auth HTTP router.
 /// `cors_origins`: comma-separated allowed origins, or empty/None for permissive (dev only).
 pub fn auth_router(state: Arc<AuthState>, cors_origins: Option<&str>) -> Router {
@@ -544,10 +724,17 @@ pub fn auth_router(state: Arc<AuthState>, cors_origins: Option<&str>) -> Router
         }
     };
 
-    Router::new()
+    #[allow(unused_mut)]
+    let mut router = Router::new()
         .route("/auth/register", post(register))
         .route("/auth/login", post(login))
-        .route("/auth/guest", post(guest))
+        .route("/auth/guest", post(guest));
+    #[cfg(feature = "caps")]
+    if state.exchange_key.is_some() {
+        router = router.route("/auth/exchange", post(exchange));
+    }
+
+    router
         .layer(cors)
         .with_state(state)
 }
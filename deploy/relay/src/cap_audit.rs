@@ -0,0 +1,276 @@
+//! Capability audit log REST API for the relay server.
+//!
+//! Exposes the records written by `--cap-audit-db` for compliance reviews,
+//! protected by admin CPSK scope. Follows the same Axum + shared state
+//! pattern as `journal_api.rs`.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use clasp_caps::{AuditLog, AuditOutcome, AuditQuery, AuditRecord};
+use clasp_core::security::{Action, CpskValidator, TokenValidator, ValidationResult};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Most records returned by one query
+pub const MAX_LIMIT: usize = 10_000;
+
+pub struct CapAuditApiState {
+    pub log: Arc<dyn AuditLog>,
+    pub validator: Arc<CpskValidator>,
+}
+
+/// Query parameters for `GET /api/caps/audit`.
+#[derive(Deserialize)]
+pub struct AuditQueryParams {
+    /// Issuer or chain root fingerprint (hex public key)
+    pub issuer: Option<String>,
+    pub nonce: Option<String>,
+    /// valid, expired or invalid
+    pub outcome: Option<String>,
+    /// Start timestamp (seconds since epoch, inclusive)
+    pub since: Option<u64>,
+    /// End timestamp (seconds since epoch, exclusive)
+    pub until: Option<u64>,
+    /// Maximum number of records to return (default 100)
+    pub limit: Option<usize>,
+}
+
+/// Query parameters for `DELETE /api/caps/audit`.
+#[derive(Deserialize)]
+pub struct PruneParams {
+    /// Delete records older than this timestamp (seconds since epoch)
+    pub before: u64,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct AuditRecordResponse {
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    root_issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audience: Option<String>,
+    scopes: Vec<String>,
+    chain_depth: usize,
+    outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl From<AuditRecord> for AuditRecordResponse {
+    fn from(r: AuditRecord) -> Self {
+        Self {
+            timestamp: r.timestamp,
+            nonce: r.nonce,
+            issuer: r.issuer,
+            root_issuer: r.root_issuer,
+            audience: r.audience,
+            scopes: r.scopes,
+            chain_depth: r.chain_depth,
+            outcome: r.outcome.as_str(),
+            reason: r.reason,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PruneResponse {
+    deleted: usize,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn err(status: StatusCode, msg: impl Into<String>) -> ApiError {
+    (status, Json(ErrorResponse { error: msg.into() }))
+}
+
+/// Validate admin Bearer token from request headers.
+fn validate_admin(headers: &HeaderMap, validator: &CpskValidator) -> Result<(), ApiError> {
+    let auth_header = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "expected Bearer token"))?;
+
+    match validator.validate(token) {
+        ValidationResult::Valid(info) => {
+            if info.has_scope(Action::Admin, "/**") {
+                Ok(())
+            } else {
+                Err(err(StatusCode::FORBIDDEN, "admin scope required"))
+            }
+        }
+        ValidationResult::Expired => Err(err(StatusCode::UNAUTHORIZED, "token expired")),
+        _ => Err(err(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+async fn query_audit(
+    State(state): State<Arc<CapAuditApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<AuditQueryParams>,
+) -> Result<Json<Vec<AuditRecordResponse>>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+
+    let outcome = match params.outcome.as_deref() {
+        Some(outcome) => Some(AuditOutcome::parse(outcome).ok_or_else(|| {
+            err(
+                StatusCode::BAD_REQUEST,
+                "outcome must be valid, expired or invalid",
+            )
+        })?),
+        None => None,
+    };
+    let query = AuditQuery {
+        issuer: params.issuer.map(|i| i.to_lowercase()),
+        nonce: params.nonce,
+        outcome,
+        since: params.since,
+        until: params.until,
+        limit: params.limit.map(|l| l.min(MAX_LIMIT)),
+    };
+
+    let records = state.log.query(&query).map_err(|e| {
+        err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("audit query failed: {}", e),
+        )
+    })?;
+    Ok(Json(records.into_iter().map(Into::into).collect()))
+}
+
+async fn prune_audit(
+    State(state): State<Arc<CapAuditApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<PruneParams>,
+) -> Result<Json<PruneResponse>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+
+    let deleted = state.log.prune(params.before).map_err(|e| {
+        err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("audit prune failed: {}", e),
+        )
+    })?;
+    tracing::info!("Pruned {} capability audit record(s)", deleted);
+    Ok(Json(PruneResponse { deleted }))
+}
+
+/// Build the capability audit REST router.
+pub fn cap_audit_router(state: Arc<CapAuditApiState>) -> Router {
+    Router::new()
+        .route("/api/caps/audit", get(query_audit).delete(prune_audit))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use clasp_caps::MemoryAuditLog;
+    use clasp_core::security::{Scope, TokenInfo};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn record(nonce: &str, issuer: &str, outcome: AuditOutcome) -> AuditRecord {
+        AuditRecord {
+            timestamp: 1_700_000_000,
+            nonce: Some(nonce.to_string()),
+            issuer: Some(issuer.to_string()),
+            root_issuer: Some("00ff".to_string()),
+            audience: None,
+            scopes: vec!["write:/lights/**".to_string()],
+            chain_depth: 1,
+            outcome,
+            reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_and_prune_audit() {
+        let log = Arc::new(MemoryAuditLog::new());
+        log.record(record("n1", "abcd", AuditOutcome::Valid))
+            .unwrap();
+        log.record(record("n2", "beef", AuditOutcome::Invalid))
+            .unwrap();
+        let validator = Arc::new(CpskValidator::new());
+        let admin_token = CpskValidator::generate_token();
+        validator.register(
+            admin_token.clone(),
+            TokenInfo::new(
+                admin_token.clone(),
+                vec![Scope::new(Action::Admin, "/**").unwrap()],
+            ),
+        );
+        let app = cap_audit_router(Arc::new(CapAuditApiState { log, validator }));
+        let request = |method: &str, uri: &str, token: Option<&str>| {
+            let mut builder = axum::http::Request::builder().method(method).uri(uri);
+            if let Some(token) = token {
+                builder = builder.header("authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let resp = app
+            .clone()
+            .oneshot(request("GET", "/api/caps/audit", None))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = app
+            .clone()
+            .oneshot(request(
+                "GET",
+                "/api/caps/audit?issuer=ABCD",
+                Some(&admin_token),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["nonce"], "n1");
+        assert_eq!(json[0]["outcome"], "valid");
+        assert_eq!(json[0]["scopes"][0], "write:/lights/**");
+
+        let resp = app
+            .clone()
+            .oneshot(request(
+                "GET",
+                "/api/caps/audit?outcome=denied",
+                Some(&admin_token),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = app
+            .oneshot(request(
+                "DELETE",
+                "/api/caps/audit?before=1800000000",
+                Some(&admin_token),
+            ))
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["deleted"], 2);
+    }
+}
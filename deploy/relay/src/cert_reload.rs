@@ -0,0 +1,104 @@
+//! Hot reload of the QUIC certificate.
+//!
+//! Certificates from ACME and similar issuers renew every few months. The
+//! relay watches the `--cert` and `--key` files and installs them on the
+//! running QUIC listener when either changes, checked every
+//! `--cert-reload-interval` seconds, or right away on SIGHUP. Open
+//! connections are not dropped; new handshakes get the new certificate.
+//! If the new files don't parse, or don't match each other, the listener
+//! keeps the old certificate and the error is logged.
+
+use anyhow::Context;
+use clasp_router::QuicCertificates;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Read a PEM certificate and key as DER (first certificate in the file)
+pub fn load_pem(cert_path: &Path, key_path: &Path) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    let cert_pem = std::fs::read(cert_path)
+        .with_context(|| format!("Failed to read certificate {}", cert_path.display()))?;
+    let key_pem = std::fs::read(key_path)
+        .with_context(|| format!("Failed to read private key {}", key_path.display()))?;
+
+    let cert_der = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("No certificate found in PEM file"))?
+        .to_vec();
+
+    let key_der = rustls_pemfile::private_key(&mut key_pem.as_slice())?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in PEM file"))?
+        .secret_der()
+        .to_vec();
+
+    Ok((cert_der, key_der))
+}
+
+/// Modification times of the certificate and key files
+pub fn modified(cert_path: &Path, key_path: &Path) -> (Option<SystemTime>, Option<SystemTime>) {
+    let mtime = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    (mtime(cert_path), mtime(key_path))
+}
+
+/// Load the files and install them on the listener
+pub fn reload(certs: &QuicCertificates, cert_path: &Path, key_path: &Path) -> anyhow::Result<()> {
+    let (cert, key) = load_pem(cert_path, key_path)?;
+    certs.reload(cert, key)?;
+    Ok(())
+}
+
+/// Reload the certificate whenever its files change or SIGHUP arrives
+///
+/// `interval` of zero disables polling, leaving only SIGHUP.
+pub async fn watch(certs: QuicCertificates, cert_path: PathBuf, key_path: PathBuf, interval: Duration) {
+    let mut last = modified(&cert_path, &key_path);
+
+    #[cfg(unix)]
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => Some(signal),
+        Err(e) => {
+            tracing::warn!("Failed to register SIGHUP handler: {}", e);
+            None
+        }
+    };
+
+    loop {
+        let poll = async {
+            if interval.is_zero() {
+                std::future::pending::<()>().await
+            } else {
+                tokio::time::sleep(interval).await
+            }
+        };
+        #[cfg(unix)]
+        let forced = tokio::select! {
+            _ = poll => false,
+            Some(_) = async {
+                match hangup.as_mut() {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => true,
+        };
+        #[cfg(not(unix))]
+        let forced = {
+            poll.await;
+            false
+        };
+
+        let current = modified(&cert_path, &key_path);
+        if !forced && current == last {
+            continue;
+        }
+        // The listener starts after the watcher; try again next time
+        if !certs.is_serving() {
+            continue;
+        }
+        last = current;
+        match reload(&certs, &cert_path, &key_path) {
+            Ok(()) => tracing::info!("QUIC certificate reloaded from {}", cert_path.display()),
+            Err(e) => tracing::error!("QUIC certificate reload failed, keeping the old one: {:#}", e),
+        }
+    }
+}
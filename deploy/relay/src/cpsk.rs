@@ -4,7 +4,9 @@
 //! used by both the router (token validation) and the auth HTTP API (token
 //! registration). Also includes [`write_secret_file`] for safe credential I/O.
 
-use clasp_core::security::{CpskValidator, TokenValidator, ValidationResult};
+use clasp_core::security::{
+    AsyncTokenValidator, CpskValidator, TokenValidator, ValidationResult, ValidatorChain,
+};
 use std::sync::Arc;
 
 /// Write a file containing sensitive data with restrictive permissions.
@@ -52,3 +54,29 @@ impl TokenValidator for SharedValidator {
         self
     }
 }
+
+/// Wrapper to share the full validator chain between the router and HTTP
+/// APIs that accept any token the router accepts (e.g. GraphQL).
+pub struct SharedChain(pub Arc<ValidatorChain>);
+
+impl TokenValidator for SharedChain {
+    fn validate(&self, token: &str) -> ValidationResult {
+        self.0.validate(token)
+    }
+    fn name(&self) -> &str {
+        TokenValidator::name(self.0.as_ref())
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncTokenValidator for SharedChain {
+    async fn validate(&self, token: &str) -> ValidationResult {
+        AsyncTokenValidator::validate(self.0.as_ref(), token).await
+    }
+    fn name(&self) -> &str {
+        TokenValidator::name(self.0.as_ref())
+    }
+}
@@ -0,0 +1,619 @@
+//! `clasp-relay doctor`: environment checks before serving.
+//!
+//! Runs the same configuration the relay would start with through a series
+//! of checks and prints a pass/fail report, without starting anything:
+//!
+//! - every enabled listen port can be bound
+//! - the QUIC certificate and key parse, and the certificate hasn't expired
+//! - the auth database opens and its `users` table has the expected columns
+//! - the journal, snapshot and state database locations are writable
+//! - federation hub and replication primary accept TCP connections
+//! - the system clock is plausible
+//! - the token validator has what it needs (admin token, trust anchors)
+//!
+//! ```bash
+//! clasp-relay --auth-port 7350 --journal ./data/journal.db doctor
+//! clasp-relay --quic-port 7331 --cert cert.pem --key key.pem doctor --json
+//! ```
+//!
+//! Relay flags go before `doctor`. The process exits non-zero if any check
+//! fails; warnings don't affect the exit code.
+
+use serde::Serialize;
+use std::fmt;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::RelayConfig;
+
+/// Timeout for federation and replication reachability probes
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A system clock before this (2025-01-01 UTC) is certainly wrong
+const CLOCK_FLOOR_SECS: u64 = 1_735_689_600;
+
+/// Certificates expiring within this many days get a warning
+#[cfg(feature = "quic")]
+const CERT_WARN_DAYS: i64 = 14;
+
+/// Columns `AuthState::new` creates in the `users` table
+const USERS_COLUMNS: &[&str] = &["id", "username", "password_hash", "created_at"];
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+    /// Not applicable to this configuration or build
+    Skip,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+            Status::Skip => "SKIP",
+        })
+    }
+}
+
+/// One line of the report
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+/// Results of every check, in the order they ran
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn push(&mut self, name: impl Into<String>, status: Status, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    /// Whether no check failed
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|c| c.status != Status::Fail)
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+
+    /// Report as a JSON document
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&serde_json::json!({
+            "ok": self.ok(),
+            "checks": self.checks,
+        }))
+        .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{}  {:width$}  {}",
+                check.status,
+                check.name,
+                check.detail,
+                width = width
+            )?;
+        }
+        write!(
+            f,
+            "\n{} passed, {} warnings, {} failed, {} skipped",
+            self.count(Status::Pass),
+            self.count(Status::Warn),
+            self.count(Status::Fail),
+            self.count(Status::Skip)
+        )
+    }
+}
+
+/// Run every check against `config`
+pub fn run(config: &RelayConfig) -> Report {
+    let mut report = Report::default();
+    check_ports(config, &mut report);
+    check_tls(config, &mut report);
+    check_auth_db(config, &mut report);
+    check_storage(config, &mut report);
+    check_peers(config, &mut report);
+    check_clock(config, &mut report);
+    check_validator(config, &mut report);
+    report
+}
+
+// ---------------------------------------------------------------------------
+// Ports
+// ---------------------------------------------------------------------------
+
+fn check_ports(config: &RelayConfig, report: &mut Report) {
+    let mut tcp = Vec::new();
+    let mut udp = Vec::new();
+    if !config.no_websocket {
+        tcp.push(("websocket", config.ws_port));
+    }
+    tcp.extend(config.auth_port.map(|p| ("auth", p)));
+    tcp.extend(config.health_port.map(|p| ("health", p)));
+    tcp.extend(config.metrics_port.map(|p| ("metrics", p)));
+    tcp.extend(config.webrtc_port.map(|p| ("webrtc", p)));
+    tcp.extend(config.mqtt_port.map(|p| ("mqtt", p)));
+    tcp.extend(config.resp_port.map(|p| ("resp", p)));
+    if config.rendezvous_port > 0 {
+        tcp.push(("rendezvous", config.rendezvous_port));
+    }
+    udp.extend(config.quic_port.map(|p| ("quic", p)));
+    udp.extend(config.osc_port.map(|p| ("osc", p)));
+
+    for (name, port) in tcp {
+        let addr = format!("{}:{}", config.host, port);
+        let (status, detail) = match TcpListener::bind(&addr) {
+            Ok(_) => (Status::Pass, format!("tcp {} is free", addr)),
+            Err(e) => (Status::Fail, format!("tcp {}: {}", addr, e)),
+        };
+        report.push(format!("port.{}", name), status, detail);
+    }
+    for (name, port) in udp {
+        let addr = format!("{}:{}", config.host, port);
+        let (status, detail) = match UdpSocket::bind(&addr) {
+            Ok(_) => (Status::Pass, format!("udp {} is free", addr)),
+            Err(e) => (Status::Fail, format!("udp {}: {}", addr, e)),
+        };
+        report.push(format!("port.{}", name), status, detail);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TLS
+// ---------------------------------------------------------------------------
+
+fn check_tls(config: &RelayConfig, report: &mut Report) {
+    if config.quic_port.is_none() && config.cert.is_none() && config.key.is_none() {
+        report.push("tls", Status::Skip, "QUIC is not enabled");
+        return;
+    }
+    let (Some(cert), Some(key)) = (&config.cert, &config.key) else {
+        report.push("tls", Status::Fail, "QUIC needs both --cert and --key");
+        return;
+    };
+    tls_files(cert, key, report);
+}
+
+#[cfg(feature = "quic")]
+fn tls_files(cert: &Path, key: &Path, report: &mut Report) {
+    let cert_der = std::fs::read(cert)
+        .map_err(|e| format!("{}: {}", cert.display(), e))
+        .and_then(|pem| {
+            rustls_pemfile::certs(&mut pem.as_slice())
+                .next()
+                .ok_or_else(|| format!("{}: no certificate in PEM file", cert.display()))?
+                .map_err(|e| format!("{}: {}", cert.display(), e))
+        });
+    match cert_der {
+        Ok(der) => {
+            let (status, detail) = match not_after(&der) {
+                Some(expires) => expiry_status(expires, unix_now()),
+                None => (Status::Warn, "could not read the validity period".to_string()),
+            };
+            report.push("tls.cert", status, format!("{}: {}", cert.display(), detail));
+        }
+        Err(e) => report.push("tls.cert", Status::Fail, e),
+    }
+
+    let key_der = std::fs::read(key)
+        .map_err(|e| e.to_string())
+        .and_then(|pem| match rustls_pemfile::private_key(&mut pem.as_slice()) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err("no private key in PEM file".to_string()),
+            Err(e) => Err(e.to_string()),
+        });
+    match key_der {
+        Ok(()) => report.push("tls.key", Status::Pass, format!("{} parses", key.display())),
+        Err(e) => report.push("tls.key", Status::Fail, format!("{}: {}", key.display(), e)),
+    }
+}
+
+#[cfg(not(feature = "quic"))]
+fn tls_files(_cert: &Path, _key: &Path, report: &mut Report) {
+    report.push("tls", Status::Skip, "built without the quic feature");
+}
+
+/// Status for a certificate that expires at `expires` (Unix seconds)
+#[cfg(feature = "quic")]
+fn expiry_status(expires: i64, now: i64) -> (Status, String) {
+    let days = (expires - now).div_euclid(86_400);
+    if expires <= now {
+        (Status::Fail, format!("expired {} days ago", -days))
+    } else if days < CERT_WARN_DAYS {
+        (Status::Warn, format!("expires in {} days", days))
+    } else {
+        (Status::Pass, format!("valid for {} more days", days))
+    }
+}
+
+/// notAfter of a DER-encoded X.509 certificate, in Unix seconds
+///
+/// Walks just enough of the structure to reach `tbsCertificate.validity`.
+#[cfg(feature = "quic")]
+pub fn not_after(der: &[u8]) -> Option<i64> {
+    let (_, cert, _) = der_read(der)?;
+    let (_, tbs, _) = der_read(cert)?;
+    let mut rest = tbs;
+    // Optional [0] version
+    if rest.first() == Some(&0xa0) {
+        rest = der_read(rest)?.2;
+    }
+    // serialNumber, signature, issuer
+    for _ in 0..3 {
+        rest = der_read(rest)?.2;
+    }
+    let (_, validity, _) = der_read(rest)?;
+    let (_, _not_before, rest) = der_read(validity)?;
+    let (tag, not_after, _) = der_read(rest)?;
+    let text = std::str::from_utf8(not_after).ok()?;
+    match tag {
+        // UTCTime: YYMMDDHHMMSSZ
+        0x17 => {
+            let yy: i64 = text.get(0..2)?.parse().ok()?;
+            let year = if yy >= 50 { 1900 + yy } else { 2000 + yy };
+            parse_time(year, text.get(2..)?)
+        }
+        // GeneralizedTime: YYYYMMDDHHMMSSZ
+        0x18 => parse_time(text.get(0..4)?.parse().ok()?, text.get(4..)?),
+        _ => None,
+    }
+}
+
+/// Split one TLV off the front of `input`: (tag, contents, rest)
+#[cfg(feature = "quic")]
+fn der_read(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *input.first()?;
+    let first = *input.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let len = input
+            .get(2..2 + n)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + n)
+    };
+    let contents = input.get(header..header + len)?;
+    Some((tag, contents, &input[header + len..]))
+}
+
+/// Unix seconds for `year` followed by MMDDHHMMSS
+#[cfg(feature = "quic")]
+fn parse_time(year: i64, rest: &str) -> Option<i64> {
+    let field = |i: usize| -> Option<i64> { rest.get(i..i + 2)?.parse().ok() };
+    let (month, day) = (field(0)?, field(2)?);
+    let (hour, minute, second) = (field(4)?, field(6)?, field(8)?);
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+#[cfg(feature = "quic")]
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// ---------------------------------------------------------------------------
+// Auth database
+// ---------------------------------------------------------------------------
+
+fn check_auth_db(config: &RelayConfig, report: &mut Report) {
+    if config.auth_port.is_none() {
+        report.push("auth.db", Status::Skip, "auth is not enabled");
+        return;
+    }
+    let path = Path::new(&config.auth_db);
+    if !path.exists() {
+        let (status, detail) = writable_dir(path);
+        report.push("auth.db", status, format!("{} will be created: {}", path.display(), detail));
+        return;
+    }
+    let (status, detail) = match users_schema(path) {
+        Ok(columns) if columns.is_empty() => (Status::Pass, "users table will be created".to_string()),
+        Ok(columns) => {
+            let missing: Vec<_> = USERS_COLUMNS
+                .iter()
+                .filter(|c| !columns.iter().any(|have| have == *c))
+                .collect();
+            if missing.is_empty() {
+                (Status::Pass, "users table is up to date".to_string())
+            } else {
+                (Status::Fail, format!("users table is missing columns {:?}", missing))
+            }
+        }
+        Err(e) => (Status::Fail, e.to_string()),
+    };
+    report.push("auth.db", status, format!("{}: {}", path.display(), detail));
+}
+
+/// Column names of the `users` table (empty if it doesn't exist yet)
+fn users_schema(path: &Path) -> rusqlite::Result<Vec<String>> {
+    let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare("PRAGMA table_info(users)")?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+// ---------------------------------------------------------------------------
+// Storage
+// ---------------------------------------------------------------------------
+
+fn check_storage(config: &RelayConfig, report: &mut Report) {
+    let paths = [
+        ("storage.journal", config.journal.as_deref()),
+        ("storage.persist", config.persist.as_deref()),
+        ("storage.state_db", config.state_db.as_deref()),
+    ];
+    let mut any = false;
+    for (name, path) in paths {
+        let Some(path) = path else { continue };
+        any = true;
+        let (status, detail) = writable(path);
+        report.push(name, status, format!("{}: {}", path.display(), detail));
+    }
+    if !any {
+        report.push("storage", Status::Skip, "no journal, snapshot or state database configured");
+    }
+}
+
+/// Whether the relay can write `path`, creating it if needed
+fn writable(path: &Path) -> (Status, String) {
+    if path.is_dir() {
+        return (Status::Fail, "is a directory".to_string());
+    }
+    if path.exists() {
+        return match std::fs::OpenOptions::new().append(true).open(path) {
+            Ok(_) => (Status::Pass, "writable".to_string()),
+            Err(e) => (Status::Fail, e.to_string()),
+        };
+    }
+    writable_dir(path)
+}
+
+/// Whether a file can be created next to `path`
+fn writable_dir(path: &Path) -> (Status, String) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.exists() {
+        // The relay creates missing parents for some paths but not all
+        return (Status::Warn, format!("directory {} does not exist", dir.display()));
+    }
+    let probe = dir.join(format!(".clasp-doctor-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            (Status::Pass, "directory is writable".to_string())
+        }
+        Err(e) => (Status::Fail, format!("{}: {}", dir.display(), e)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Peers
+// ---------------------------------------------------------------------------
+
+fn check_peers(config: &RelayConfig, report: &mut Report) {
+    let peers = [
+        ("peer.federation_hub", config.federation_hub.as_deref()),
+        ("peer.replicate_from", config.replicate_from.as_deref()),
+    ];
+    let mut any = false;
+    for (name, url) in peers {
+        let Some(url) = url else { continue };
+        any = true;
+        let (status, detail) = match host_port(url) {
+            Some(authority) => reachable(&authority),
+            None => (Status::Fail, "not a ws://, wss://, http:// or https:// URL".to_string()),
+        };
+        report.push(name, status, format!("{}: {}", url, detail));
+    }
+    if !any {
+        report.push("peers", Status::Skip, "no federation hub or replication primary configured");
+    }
+}
+
+/// `host:port` of a ws/wss/http/https URL, with the scheme's default port
+pub fn host_port(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let default_port = match scheme {
+        "ws" | "http" => 80,
+        "wss" | "https" => 443,
+        _ => return None,
+    };
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit('@').next()?;
+    if authority.is_empty() {
+        return None;
+    }
+    let has_port = match authority.rfind(']') {
+        Some(end) => authority[end..].contains(':'),
+        None => authority.contains(':'),
+    };
+    Some(if has_port {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, default_port)
+    })
+}
+
+fn reachable(authority: &str) -> (Status, String) {
+    let addrs = match authority.to_socket_addrs() {
+        Ok(addrs) => addrs.collect::<Vec<_>>(),
+        Err(e) => return (Status::Fail, format!("cannot resolve {}: {}", authority, e)),
+    };
+    let mut last_err = None;
+    for addr in &addrs {
+        match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+            Ok(_) => return (Status::Pass, format!("reachable at {}", addr)),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    match last_err {
+        Some(e) => (Status::Fail, format!("cannot connect to {}: {}", authority, e)),
+        None => (Status::Fail, format!("{} resolved to no addresses", authority)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Clock
+// ---------------------------------------------------------------------------
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn check_clock(config: &RelayConfig, report: &mut Report) {
+    let now = SystemTime::now();
+    if now < UNIX_EPOCH + Duration::from_secs(CLOCK_FLOOR_SECS) {
+        report.push("clock", Status::Fail, "system clock is set before 2025");
+        return;
+    }
+    // Files written by the last run shouldn't be from the future
+    let ahead = [&config.journal, &config.persist, &config.state_db]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| {
+            let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+            let ahead = modified.duration_since(now).ok()?;
+            (ahead > Duration::from_secs(60)).then(|| (path, ahead))
+        })
+        .max_by_key(|(_, ahead)| *ahead);
+    match ahead {
+        Some((path, ahead)) => report.push(
+            "clock",
+            Status::Warn,
+            format!(
+                "{} was modified {}s in the future; the clock may have gone backwards",
+                path.display(),
+                ahead.as_secs()
+            ),
+        ),
+        None => report.push("clock", Status::Pass, format!("system clock is plausible (Unix {})", unix_now())),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Token validation
+// ---------------------------------------------------------------------------
+
+fn check_validator(config: &RelayConfig, report: &mut Report) {
+    if config.auth_port.is_none() {
+        report.push(
+            "auth.validator",
+            Status::Warn,
+            "open mode: no --auth-port, so tokens are not validated",
+        );
+        return;
+    }
+    let mut validators = vec!["cpsk"];
+    if !config.trust_anchor.is_empty() {
+        validators.push("capability");
+    }
+    if config.registry_db.is_some() {
+        validators.push("registry");
+    }
+    report.push("auth.validator", Status::Pass, validators.join(", "));
+
+    if let Some(ref path) = config.admin_token {
+        let (status, detail) = if path.exists() {
+            match std::fs::read_to_string(path) {
+                Ok(token) if token.trim().is_empty() => (Status::Fail, "file is empty".to_string()),
+                Ok(_) => (Status::Pass, "token present".to_string()),
+                Err(e) => (Status::Fail, e.to_string()),
+            }
+        } else {
+            let (status, detail) = writable_dir(path);
+            (status, format!("will be generated: {}", detail))
+        };
+        report.push("auth.admin_token", status, format!("{}: {}", path.display(), detail));
+    }
+
+    for path in &config.trust_anchor {
+        let (status, detail) = match std::fs::read(path) {
+            Ok(bytes) => trust_anchor_status(&bytes),
+            Err(e) => (Status::Fail, e.to_string()),
+        };
+        report.push("auth.trust_anchor", status, format!("{}: {}", path.display(), detail));
+    }
+}
+
+/// Whether `bytes` hold a trust anchor in a format the relay accepts
+fn trust_anchor_status(bytes: &[u8]) -> (Status, String) {
+    let text = String::from_utf8_lossy(bytes);
+    let trimmed = text.trim();
+    if trimmed.len() == 64 && trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
+        (Status::Pass, "hex signing key".to_string())
+    } else if bytes.len() == 32 {
+        (Status::Pass, "raw public key".to_string())
+    } else {
+        (
+            Status::Fail,
+            format!(
+                "expected 64 hex chars (signing key) or 32 raw bytes (public key), got {} bytes",
+                bytes.len()
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_port_defaults() {
+        assert_eq!(host_port("ws://hub:7330").as_deref(), Some("hub:7330"));
+        assert_eq!(host_port("wss://hub.example.com/clasp").as_deref(), Some("hub.example.com:443"));
+        assert_eq!(host_port("http://[::1]/api").as_deref(), Some("[::1]:80"));
+        assert_eq!(host_port("tcp://hub:1"), None);
+    }
+
+    #[cfg(feature = "quic")]
+    #[test]
+    fn test_expiry_status() {
+        let day = 86_400;
+        assert_eq!(expiry_status(100 * day, 0).0, Status::Pass);
+        assert_eq!(expiry_status(3 * day, 0).0, Status::Warn);
+        assert_eq!(expiry_status(0, 2 * day).0, Status::Fail);
+        assert_eq!(days_from_civil(2025, 1, 1) * day, CLOCK_FLOOR_SECS as i64);
+    }
+}
@@ -1,24 +1,40 @@
 //! Federation leaf mode for CLASP relay.
 //!
 //! Connects to a hub router via WebSocket and bridges state between local
-//! and remote routers using namespace-scoped forwarding.
+//! and remote routers using namespace-scoped forwarding. With registry sync
+//! the link also carries entity registry events to and from the hub (see
+//! [`crate::registry_sync`]).
 
 use clasp_core::{codec, Message, SetMessage, SignalType};
 use clasp_federation::{FederationConfig, FederationLink, FederationManager, LinkEvent};
-use clasp_router::{session::{Session, SessionId}, RouterState, SubscriptionManager};
-use clasp_transport::{Transport, WebSocketTransport};
+use clasp_router::{
+    session::{Session, SessionId},
+    RouterState, SubscriptionManager,
+};
+use clasp_transport::{Transport, TransportSender, WebSocketTransport};
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+use crate::registry_sync::RegistrySync;
 
 /// Run the federation leaf, connecting to a hub and bridging state.
 ///
 /// This function connects to the hub, runs the federation protocol, and
 /// processes events in a loop. On disconnect, it reconnects if configured.
+/// Once `shutdown` turns true the link to the hub is closed, so the hub sees
+/// the leaf leave, and the function returns without reconnecting.
+///
+/// With `registry`, `config.registry_sync` should be on: the leaf applies
+/// the hub's registry events and sends its own.
 pub async fn run_federation_leaf(
     config: FederationConfig,
     state: Arc<RouterState>,
     sessions: Arc<DashMap<SessionId, Arc<Session>>>,
     subscriptions: Arc<SubscriptionManager>,
+    registry: Option<Arc<RegistrySync>>,
+    mut shutdown: watch::Receiver<bool>,
 ) {
     let mut manager = FederationManager::new(config.clone());
     let event_tx = manager.event_sender();
@@ -34,23 +50,43 @@ pub async fn run_federation_leaf(
         }
     };
 
+    // Journal checkpoints outlive each link so resync picks up where it left off
+    let checkpoints: Arc<Mutex<HashMap<String, u64>>> = Arc::default();
+
     // Spawn connection task
     let conn_config = config.clone();
+    let conn_checkpoints = Arc::clone(&checkpoints);
     let conn_tx = event_tx.clone();
-    tokio::spawn(async move {
+    let conn_registry = registry.clone();
+    let mut conn_shutdown = shutdown.clone();
+    let connection = tokio::spawn(async move {
         let mut attempt = 0u32;
         loop {
+            if *conn_shutdown.borrow() {
+                break;
+            }
             tracing::info!("Federation: connecting to hub {}", hub_endpoint);
             match WebSocketTransport::connect(&hub_endpoint).await {
                 Ok((sender, receiver)) => {
                     attempt = 0;
-                    let link = FederationLink::new(
-                        conn_config.clone(),
-                        Arc::new(sender),
-                        conn_tx.clone(),
-                    );
-                    if let Err(e) = link.run(Box::new(receiver)).await {
-                        tracing::warn!("Federation link ended: {}", e);
+                    let sender = Arc::new(sender);
+                    let mut link =
+                        FederationLink::new(conn_config.clone(), sender.clone(), conn_tx.clone())
+                            .with_journal_checkpoints(conn_checkpoints.lock().unwrap().clone());
+                    if let Some(ref registry) = conn_registry {
+                        link = link.with_registry_events(registry.upstream());
+                    }
+                    tokio::select! {
+                        result = link.run(Box::new(receiver)) => {
+                            if let Err(e) = result {
+                                tracing::warn!("Federation link ended: {}", e);
+                            }
+                        }
+                        _ = stopped(&mut conn_shutdown) => {
+                            tracing::info!("Federation: closing link to hub for shutdown");
+                            let _ = sender.close().await;
+                            break;
+                        }
                     }
                 }
                 Err(e) => {
@@ -79,12 +115,26 @@ pub async fn run_federation_leaf(
                 conn_config.reconnect_delay,
                 attempt
             );
-            tokio::time::sleep(conn_config.reconnect_delay).await;
+            tokio::select! {
+                _ = tokio::time::sleep(conn_config.reconnect_delay) => {}
+                _ = stopped(&mut conn_shutdown) => break,
+            }
         }
     });
 
-    // Process events from the federation link
-    while let Some(event) = event_rx.recv().await {
+    // Process events from the federation link until shutdown
+    loop {
+        let event = tokio::select! {
+            event = event_rx.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            _ = stopped(&mut shutdown) => {
+                let _ = connection.await;
+                tracing::info!("Federation: leaf stopped");
+                break;
+            }
+        };
         manager.process_event(&event).await;
 
         match event {
@@ -94,7 +144,15 @@ pub async fn run_federation_leaf(
                 revision,
                 origin,
             } => {
-                match state.set(&address, value.clone(), &origin, revision, false, false, None) {
+                match state.set(
+                    &address,
+                    value.clone(),
+                    &origin,
+                    revision,
+                    false,
+                    false,
+                    None,
+                ) {
                     Ok(rev) => {
                         let subscribers =
                             subscriptions.find_subscribers(&address, Some(SignalType::Param));
@@ -103,7 +161,10 @@ pub async fn run_federation_leaf(
                             value,
                             revision: Some(rev),
                             lock: false,
-                            unlock: false, ttl: None,
+                            unlock: false,
+                            ttl: None,
+                            trace: false,
+                            branch: None,
                         });
                         if let Ok(bytes) = codec::encode(&set_msg) {
                             for sub_session_id in &subscribers {
@@ -147,18 +208,17 @@ pub async fn run_federation_leaf(
                         subscribers.len()
                     );
                 } else {
-                    tracing::debug!("Federation: received non-publish remote message from {}", origin);
+                    tracing::debug!(
+                        "Federation: received non-publish remote message from {}",
+                        origin
+                    );
                 }
             }
             LinkEvent::Connected { router_id } => {
                 tracing::info!("Federation: connected to hub {}", router_id);
             }
             LinkEvent::Disconnected { router_id, reason } => {
-                tracing::info!(
-                    "Federation: disconnected from {} ({:?})",
-                    router_id,
-                    reason
-                );
+                tracing::info!("Federation: disconnected from {} ({:?})", router_id, reason);
             }
             LinkEvent::PeerNamespaces {
                 router_id,
@@ -170,6 +230,44 @@ pub async fn run_federation_leaf(
                     patterns
                 );
             }
+            LinkEvent::RouteAnnounced {
+                via,
+                origin,
+                patterns,
+            } => {
+                tracing::debug!(
+                    "Federation: {} relayed namespaces of {}: {:?}",
+                    via,
+                    origin.router_id,
+                    patterns
+                );
+            }
+            LinkEvent::JournalCheckpoint {
+                router_id,
+                pattern,
+                seq,
+            } => {
+                tracing::debug!(
+                    "Federation: applied journal of {} for {} up to seq {}",
+                    router_id,
+                    pattern,
+                    seq
+                );
+                checkpoints.lock().unwrap().insert(pattern, seq);
+            }
+            LinkEvent::RegistryEvent { router_id, payload } => match registry {
+                Some(ref registry) => registry.receive_from_hub(&payload).await,
+                None => tracing::debug!(
+                    "Federation: ignoring registry event from {} (registry sync off)",
+                    router_id
+                ),
+            },
+            LinkEvent::RegistrySyncRequested { router_id } => {
+                if let Some(ref registry) = registry {
+                    tracing::debug!("Federation: {} asked for the registry", router_id);
+                    registry.send_snapshot_to_hub().await;
+                }
+            }
             LinkEvent::SyncComplete {
                 router_id,
                 pattern,
@@ -185,3 +283,8 @@ pub async fn run_federation_leaf(
         }
     }
 }
+
+/// Resolves once `shutdown` turns true
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stop| *stop).await;
+}
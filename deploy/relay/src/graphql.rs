@@ -0,0 +1,626 @@
+//! GraphQL facade over router state.
+//!
+//! Exposes the relay's state tree and signal registry to clients that speak
+//! GraphQL rather than the CLASP wire protocol:
+//! - queries `param`, `params` and `signals` read current state
+//! - mutations `set` and `publish` write through to CLASP subscribers
+//! - subscription `changes` streams SET/PUBLISH traffic for a pattern
+//!
+//! Every request carries a Bearer token that is checked by the same validator
+//! chain the router uses, so CPSK, capability and entity tokens all work and
+//! their scopes apply per address. Reads and writes then go through the
+//! router's handlers for an in-process session holding the token, so write
+//! validators, reserved namespaces, maintenance mode and snapshot filters
+//! apply as they would to a CLASP client.
+//!
+//! Routes:
+//! - `POST /graphql` executes a query or mutation and returns JSON
+//! - `POST /graphql/stream` executes any operation and streams responses as
+//!   server-sent events (use this for subscriptions)
+//! - `GET /graphql/schema` returns the schema in SDL
+
+use async_graphql::futures_util::{stream, Stream, StreamExt};
+use async_graphql::{
+    Context, Enum, Error, Json as GqlJson, Object, Result, Schema, SimpleObject, Subscription,
+};
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use bytes::Bytes;
+use clasp_core::security::{Action, TokenInfo, TokenValidator, ValidationResult};
+use clasp_core::{
+    codec, ErrorMessage, GetMessage, Message, ParamValue, PublishMessage, SetMessage, SignalType,
+    Value,
+};
+use clasp_router::session::{Session, SessionId};
+use clasp_router::subscription::Subscription as ClaspSubscription;
+use clasp_router::{LocalHandler, RouterState, SubscriptionManager};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Maximum number of params returned by a single `params` query.
+const MAX_PARAMS: usize = 10_000;
+
+/// Buffer for each GraphQL subscription's internal session.
+const STREAM_BUFFER: usize = 1024;
+
+/// How often a subscription's session is touched so idle cleanup keeps it.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Transport name for GraphQL sessions, as matched by transport policies.
+const TRANSPORT: &str = "graphql";
+
+/// Writer id recorded for GraphQL SETs from tokens without a subject.
+const DEFAULT_WRITER: &str = "graphql";
+
+/// Router handles the resolvers operate on.
+#[derive(Clone)]
+pub struct RouterHandles {
+    pub sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    pub subscriptions: Arc<SubscriptionManager>,
+    pub state: Arc<RouterState>,
+    /// Runs reads and writes through the router's handlers
+    pub local: Arc<LocalHandler>,
+}
+
+impl RouterHandles {
+    /// Handles onto `router`. Install its write validator and snapshot
+    /// filter first; they are captured here.
+    pub fn new(router: &clasp_router::Router) -> Self {
+        let (sessions, subscriptions, state) = router.shared_state();
+        Self {
+            sessions,
+            subscriptions,
+            state,
+            local: Arc::new(router.local_handler()),
+        }
+    }
+}
+
+/// The relay's GraphQL schema.
+pub type ClaspSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+/// Build the schema around the router's shared state.
+pub fn build_schema(handles: RouterHandles) -> ClaspSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(handles)
+        .finish()
+}
+
+/// Signal type as exposed in the schema.
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Signal {
+    Param,
+    Event,
+    Stream,
+    Gesture,
+    Timeline,
+}
+
+impl From<SignalType> for Signal {
+    fn from(s: SignalType) -> Self {
+        match s {
+            SignalType::Param => Signal::Param,
+            SignalType::Event => Signal::Event,
+            SignalType::Stream => Signal::Stream,
+            SignalType::Gesture => Signal::Gesture,
+            SignalType::Timeline => Signal::Timeline,
+        }
+    }
+}
+
+impl From<Signal> for SignalType {
+    fn from(s: Signal) -> Self {
+        match s {
+            Signal::Param => SignalType::Param,
+            Signal::Event => SignalType::Event,
+            Signal::Stream => SignalType::Stream,
+            Signal::Gesture => SignalType::Gesture,
+            Signal::Timeline => SignalType::Timeline,
+        }
+    }
+}
+
+/// Current value of a param.
+#[derive(SimpleObject)]
+pub struct Param {
+    pub address: String,
+    pub value: GqlJson<serde_json::Value>,
+    pub revision: u64,
+    pub writer: String,
+    /// Server time of the last write (microseconds since epoch)
+    pub timestamp: u64,
+}
+
+/// A registered signal definition.
+#[derive(SimpleObject)]
+pub struct SignalInfo {
+    pub address: String,
+    pub signal: Signal,
+    pub datatype: Option<String>,
+    pub access: Option<String>,
+}
+
+/// Result of a `set` mutation.
+#[derive(SimpleObject)]
+pub struct SetResult {
+    pub address: String,
+    pub revision: u64,
+}
+
+/// A SET or PUBLISH delivered to a `changes` subscription.
+#[derive(SimpleObject)]
+pub struct Change {
+    pub address: String,
+    pub signal: Signal,
+    pub value: Option<GqlJson<serde_json::Value>>,
+    /// Present for params
+    pub revision: Option<u64>,
+}
+
+fn caller<'a>(ctx: &Context<'a>) -> Result<&'a TokenInfo> {
+    ctx.data::<TokenInfo>()
+}
+
+fn require(info: &TokenInfo, action: Action, address: &str) -> Result<()> {
+    if info.has_scope(action, address) {
+        Ok(())
+    } else {
+        Err(Error::new(format!(
+            "insufficient scope: {:?} {}",
+            action, address
+        )))
+    }
+}
+
+fn to_json(value: &Value) -> GqlJson<serde_json::Value> {
+    GqlJson(serde_json::to_value(value).unwrap_or(serde_json::Value::Null))
+}
+
+fn from_json(value: serde_json::Value) -> Result<Value> {
+    serde_json::from_value(value).map_err(|e| Error::new(format!("invalid value: {}", e)))
+}
+
+fn validate_address(address: &str) -> Result<()> {
+    clasp_core::Address::parse(address)
+        .map(|_| ())
+        .map_err(|e| Error::new(format!("invalid address: {}", e)))
+}
+
+fn to_param(p: ParamValue) -> Param {
+    Param {
+        address: p.address,
+        value: to_json(&p.value),
+        revision: p.revision,
+        writer: p.writer.unwrap_or_default(),
+        timestamp: p.timestamp.unwrap_or_default(),
+    }
+}
+
+fn rejected(error: ErrorMessage) -> Error {
+    Error::new(format!("rejected ({}): {}", error.code, error.message))
+}
+
+/// Unregistered session acting for the caller's token; it only exists for
+/// the length of one request. Its id is the token's subject, so SETs record
+/// that as the writer.
+fn local_session(info: &TokenInfo) -> Arc<Session> {
+    let mut session = Session::new(Arc::new(NullSender), TRANSPORT.to_string(), vec![]);
+    session.id = info
+        .subject
+        .clone()
+        .unwrap_or_else(|| DEFAULT_WRITER.to_string());
+    session.set_authenticated(
+        info.token_id.clone(),
+        info.subject.clone(),
+        info.scopes.clone(),
+    );
+    session.entity = info.entity.clone();
+    session.set_transport(TRANSPORT);
+    Arc::new(session)
+}
+
+/// Run `message` through the router's handlers as the caller.
+async fn handle(ctx: &Context<'_>, message: Message) -> Result<Vec<Message>> {
+    let handles = ctx.data::<RouterHandles>()?;
+    let session = local_session(caller(ctx)?);
+    Ok(handles.local.handle(&session, &message).await)
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Current value of a single param.
+    async fn param(&self, ctx: &Context<'_>, address: String) -> Result<Option<Param>> {
+        require(caller(ctx)?, Action::Read, &address)?;
+        validate_address(&address)?;
+        let get = Message::Get(GetMessage {
+            address,
+            depth: None,
+        });
+        for reply in handle(ctx, get).await? {
+            match reply {
+                Message::Snapshot(snapshot) => {
+                    return Ok(snapshot.params.into_iter().next().map(to_param))
+                }
+                Message::Error(error) => return Err(rejected(error)),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Params matching a glob pattern, sorted by address. Params the caller
+    /// cannot read, or that the router's snapshot filter hides, are omitted.
+    async fn params(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = "/**")] pattern: String,
+        limit: Option<usize>,
+    ) -> Result<Vec<Param>> {
+        let info = caller(ctx)?;
+        let handles = ctx.data::<RouterHandles>()?;
+        let matching: Vec<_> = handles
+            .state
+            .get_matching(&pattern)
+            .into_iter()
+            .filter(|(address, _)| info.has_scope(Action::Read, address))
+            .map(|(address, p)| ParamValue {
+                address,
+                value: p.value,
+                revision: p.revision,
+                writer: Some(p.writer),
+                timestamp: Some(p.timestamp),
+            })
+            .collect();
+        let mut visible = handles
+            .local
+            .filter_snapshot(matching, &local_session(info));
+        visible.sort_by(|a, b| a.address.cmp(&b.address));
+        visible.truncate(limit.unwrap_or(MAX_PARAMS).min(MAX_PARAMS));
+        Ok(visible.into_iter().map(to_param).collect())
+    }
+
+    /// Signals announced by connected clients, filtered the same way.
+    async fn signals(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = "/**")] pattern: String,
+    ) -> Result<Vec<SignalInfo>> {
+        let info = caller(ctx)?;
+        let handles = ctx.data::<RouterHandles>()?;
+        let mut signals: Vec<SignalInfo> = handles
+            .state
+            .query_signals(&pattern)
+            .into_iter()
+            .filter(|def| info.has_scope(Action::Read, &def.address))
+            .map(|def| SignalInfo {
+                address: def.address,
+                signal: def.signal_type.into(),
+                datatype: def.datatype,
+                access: def.access,
+            })
+            .collect();
+        signals.sort_by(|a, b| a.address.cmp(&b.address));
+        Ok(signals)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Set a param and notify its subscribers.
+    async fn set(
+        &self,
+        ctx: &Context<'_>,
+        address: String,
+        value: GqlJson<serde_json::Value>,
+    ) -> Result<SetResult> {
+        require(caller(ctx)?, Action::Write, &address)?;
+        validate_address(&address)?;
+        let value = from_json(value.0)?;
+
+        let message = Message::Set(SetMessage {
+            address: address.clone(),
+            value,
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        for reply in handle(ctx, message).await? {
+            match reply {
+                Message::Ack(ack) => {
+                    return Ok(SetResult {
+                        address: ack.address.unwrap_or(address),
+                        revision: ack.revision.unwrap_or_default(),
+                    })
+                }
+                Message::Error(error) => return Err(rejected(error)),
+                _ => {}
+            }
+        }
+        Err(Error::new("set was not acknowledged"))
+    }
+
+    /// Publish an event, stream sample, gesture or timeline message.
+    async fn publish(
+        &self,
+        ctx: &Context<'_>,
+        address: String,
+        value: Option<GqlJson<serde_json::Value>>,
+        #[graphql(default_with = "Signal::Event")] signal: Signal,
+    ) -> Result<bool> {
+        require(caller(ctx)?, Action::Write, &address)?;
+        validate_address(&address)?;
+        if signal == Signal::Param {
+            return Err(Error::new("use the set mutation for params"));
+        }
+        let value = value.map(|v| from_json(v.0)).transpose()?;
+
+        let signal_type: SignalType = signal.into();
+        let message = Message::Publish(PublishMessage {
+            address: address.clone(),
+            signal: Some(signal_type),
+            value,
+            payload: None,
+            samples: None,
+            rate: None,
+            id: None,
+            phase: None,
+            timestamp: Some(clasp_core::time::now()),
+            timeline: None,
+        });
+        for reply in handle(ctx, message).await? {
+            match reply {
+                Message::Ack(_) => return Ok(true),
+                Message::Error(error) => return Err(rejected(error)),
+                _ => {}
+            }
+        }
+        Err(Error::new("publish was not acknowledged"))
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Stream SETs and PUBLISHes on addresses matching `pattern`.
+    async fn changes(
+        &self,
+        ctx: &Context<'_>,
+        pattern: String,
+    ) -> Result<impl Stream<Item = Change>> {
+        let info = caller(ctx)?.clone();
+        require(&info, Action::Read, &pattern)?;
+        let handles = ctx.data::<RouterHandles>()?.clone();
+        let (rx, guard) = register_stream(&handles, &pattern)?;
+
+        Ok(stream::unfold(
+            (rx, guard, info),
+            |(mut rx, guard, info)| async move {
+                loop {
+                    let data = rx.recv().await?;
+                    if let Some(change) = decode_change(&data) {
+                        if info.has_scope(Action::Read, &change.address) {
+                            return Some((change, (rx, guard, info)));
+                        }
+                    }
+                }
+            },
+        ))
+    }
+}
+
+fn decode_change(data: &Bytes) -> Option<Change> {
+    match codec::decode(data).ok()?.0 {
+        Message::Set(msg) => Some(Change {
+            value: Some(to_json(&msg.value)),
+            address: msg.address,
+            signal: Signal::Param,
+            revision: msg.revision,
+        }),
+        Message::Publish(msg) => Some(Change {
+            value: msg.value.or(msg.payload).as_ref().map(to_json),
+            address: msg.address,
+            signal: msg.signal.unwrap_or(SignalType::Event).into(),
+            revision: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Channel-backed sender for a subscription's internal session.
+struct StreamSender {
+    tx: mpsc::Sender<Bytes>,
+}
+
+#[async_trait]
+impl clasp_transport::TransportSender for StreamSender {
+    async fn send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.try_send(data)
+    }
+
+    fn try_send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.tx
+            .try_send(data)
+            .map_err(|_| clasp_transport::TransportError::BufferFull)
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sender for per-request sessions; the router's replies reach the resolver
+/// directly, so nothing is ever sent to the session itself.
+struct NullSender;
+
+#[async_trait]
+impl clasp_transport::TransportSender for NullSender {
+    async fn send(&self, _data: Bytes) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+
+    fn try_send(&self, _data: Bytes) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+}
+
+/// Removes the internal session and its subscription when the GraphQL
+/// subscription ends.
+struct StreamGuard {
+    session_id: SessionId,
+    handles: RouterHandles,
+    keepalive: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.keepalive.abort();
+        self.handles.subscriptions.remove(&self.session_id, 1);
+        self.handles.sessions.remove(&self.session_id);
+    }
+}
+
+fn register_stream(
+    handles: &RouterHandles,
+    pattern: &str,
+) -> Result<(mpsc::Receiver<Bytes>, StreamGuard)> {
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    let session = Arc::new(Session::new(
+        Arc::new(StreamSender { tx }),
+        "graphql-subscription".to_string(),
+        vec![],
+    ));
+    let session_id = session.id.clone();
+
+    let subscription =
+        ClaspSubscription::new(1, session_id.clone(), pattern, vec![], Default::default())
+            .map_err(|e| Error::new(format!("invalid pattern: {}", e)))?;
+    handles
+        .sessions
+        .insert(session_id.clone(), Arc::clone(&session));
+    handles.subscriptions.add(subscription);
+    session.add_subscription(1);
+
+    let weak = Arc::downgrade(&session);
+    let keepalive = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(KEEPALIVE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match weak.upgrade() {
+                Some(session) => session.touch(),
+                None => break,
+            }
+        }
+    });
+
+    Ok((
+        rx,
+        StreamGuard {
+            session_id,
+            handles: handles.clone(),
+            keepalive,
+        },
+    ))
+}
+
+// -- HTTP layer --
+
+pub struct GraphqlApiState {
+    pub schema: ClaspSchema,
+    pub validator: Arc<dyn TokenValidator>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn err(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(ErrorResponse { error: msg.into() }))
+}
+
+/// Validate the Bearer token with the relay's validator chain.
+fn authenticate(
+    headers: &HeaderMap,
+    validator: &dyn TokenValidator,
+) -> Result<TokenInfo, ApiError> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "expected Bearer token"))?;
+    match validator.validate(token) {
+        ValidationResult::Valid(info) => Ok(info),
+        ValidationResult::Expired => Err(err(StatusCode::UNAUTHORIZED, "token expired")),
+        _ => Err(err(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+async fn execute(
+    State(state): State<Arc<GraphqlApiState>>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<Json<async_graphql::Response>, ApiError> {
+    let info = authenticate(&headers, state.validator.as_ref())?;
+    Ok(Json(state.schema.execute(request.data(info)).await))
+}
+
+async fn execute_stream(
+    State(state): State<Arc<GraphqlApiState>>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>, ApiError> {
+    let info = authenticate(&headers, state.validator.as_ref())?;
+    let responses = state
+        .schema
+        .execute_stream(request.data(info))
+        .map(|response| {
+            Ok(Event::default()
+                .json_data(&response)
+                .unwrap_or_else(|_| Event::default().data("{}")))
+        });
+    Ok(Sse::new(responses).keep_alive(KeepAlive::default()))
+}
+
+async fn sdl(State(state): State<Arc<GraphqlApiState>>) -> String {
+    state.schema.sdl()
+}
+
+pub fn graphql_router(state: Arc<GraphqlApiState>) -> Router {
+    Router::new()
+        .route("/graphql", post(execute))
+        .route("/graphql/stream", post(execute_stream))
+        .route("/graphql/schema", get(sdl))
+        .with_state(state)
+}
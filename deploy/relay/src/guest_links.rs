@@ -0,0 +1,482 @@
+//! Guest link REST API for the relay server.
+//!
+//! Mints narrow, short-lived capability tokens signed with `--guest-link-key`
+//! and hands them out behind short codes, so an event producer can put one
+//! URL on a screen and let the audience vote for two hours. Opening `/g/{code}`
+//! redirects to `--guest-link-url` with the token in the fragment, or returns
+//! the token as JSON when no page is configured. An optional `max_uses` is
+//! signed into the token and counted server-side by the capability validator.
+//! Minting and listing require admin CPSK scope; opening a link does not.
+//!
+//! Links live in memory: after a restart the short codes stop resolving, but
+//! tokens already handed out stay valid until they expire.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use clasp_caps::{CapabilityToken, UseCounter};
+use clasp_core::security::{Action, CpskValidator, Scope, TokenValidator, ValidationResult};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lifetime of a link when the request doesn't give one (seconds)
+pub const DEFAULT_EXPIRES_IN: u64 = 3600;
+/// Longest lifetime a link may be minted with (seconds)
+pub const MAX_EXPIRES_IN: u64 = 7 * 24 * 3600;
+/// Characters in a short code
+const CODE_LEN: usize = 16;
+
+struct GuestLink {
+    token: String,
+    nonce: String,
+    scopes: Vec<String>,
+    expires_at: u64,
+    max_uses: Option<u64>,
+    created_at: u64,
+}
+
+pub struct GuestLinkState {
+    /// Key guest tokens are signed with (a trust anchor of the validator)
+    pub key: SigningKey,
+    /// Use counts kept by the capability validator
+    pub uses: Arc<dyn UseCounter>,
+    /// Admin token validator
+    pub validator: Arc<CpskValidator>,
+    /// Page guests are sent to, with `#token=...` appended
+    pub redirect_url: Option<String>,
+    links: Mutex<HashMap<String, GuestLink>>,
+}
+
+impl GuestLinkState {
+    pub fn new(
+        key: SigningKey,
+        uses: Arc<dyn UseCounter>,
+        validator: Arc<CpskValidator>,
+        redirect_url: Option<String>,
+    ) -> Self {
+        Self {
+            key,
+            uses,
+            validator,
+            redirect_url,
+            links: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn uses(&self, nonce: &str) -> u64 {
+        self.uses.uses(nonce).unwrap_or(0)
+    }
+}
+
+/// Read a hex-encoded Ed25519 signing key (same format as `clasp key generate --out`).
+pub fn load_signing_key(path: &std::path::Path) -> anyhow::Result<SigningKey> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read key file {}: {}", path.display(), e))?;
+    let hex_str = contents.trim();
+    anyhow::ensure!(
+        hex_str.len() == 64,
+        "Key file {} must contain 64 hex characters (32-byte Ed25519 signing key)",
+        path.display()
+    );
+    let bytes: Vec<u8> = (0..hex_str.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex_str[i..i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow::anyhow!("Invalid hex in key file {}", path.display()))?;
+    let key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid key length in {}", path.display()))?;
+    Ok(SigningKey::from_bytes(&key))
+}
+
+/// Request body for `POST /api/guest-links`.
+#[derive(Deserialize)]
+pub struct CreateGuestLinkRequest {
+    /// Scopes to grant, e.g. `write:/votes/**` (no admin scopes)
+    pub scopes: Vec<String>,
+    /// Lifetime in seconds (default 3600, at most 7 days)
+    pub expires_in: Option<u64>,
+    /// Times the token may be used to connect (default unlimited)
+    pub max_uses: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct GuestLinkResponse {
+    code: String,
+    /// Path of the short link on this server
+    path: String,
+    token: String,
+    nonce: String,
+    scopes: Vec<String>,
+    expires_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_uses: Option<u64>,
+    uses: u64,
+}
+
+impl GuestLinkResponse {
+    fn new(code: &str, link: &GuestLink, uses: u64) -> Self {
+        Self {
+            code: code.to_string(),
+            path: format!("/g/{}", code),
+            token: link.token.clone(),
+            nonce: link.nonce.clone(),
+            scopes: link.scopes.clone(),
+            expires_at: link.expires_at,
+            max_uses: link.max_uses,
+            uses,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OpenLinkResponse {
+    token: String,
+    expires_at: u64,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn err(status: StatusCode, msg: impl Into<String>) -> ApiError {
+    (status, Json(ErrorResponse { error: msg.into() }))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Validate admin Bearer token from request headers.
+fn validate_admin(headers: &HeaderMap, validator: &CpskValidator) -> Result<(), ApiError> {
+    let auth_header = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "expected Bearer token"))?;
+
+    match validator.validate(token) {
+        ValidationResult::Valid(info) => {
+            if info.has_scope(Action::Admin, "/**") {
+                Ok(())
+            } else {
+                Err(err(StatusCode::FORBIDDEN, "admin scope required"))
+            }
+        }
+        ValidationResult::Expired => Err(err(StatusCode::UNAUTHORIZED, "token expired")),
+        _ => Err(err(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+/// Drop expired links and their use counts.
+fn prune(state: &GuestLinkState, now: u64) {
+    state
+        .links
+        .lock()
+        .unwrap()
+        .retain(|_, link| link.expires_at >= now);
+    let _ = state.uses.prune(now);
+}
+
+async fn create_link(
+    State(state): State<Arc<GuestLinkState>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateGuestLinkRequest>,
+) -> Result<(StatusCode, Json<GuestLinkResponse>), ApiError> {
+    validate_admin(&headers, &state.validator)?;
+
+    if req.scopes.is_empty() {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "at least one scope is required",
+        ));
+    }
+    for scope in &req.scopes {
+        let parsed = Scope::parse(scope).map_err(|e| {
+            err(
+                StatusCode::BAD_REQUEST,
+                format!("invalid scope {}: {}", scope, e),
+            )
+        })?;
+        if parsed.action() == Action::Admin {
+            return Err(err(
+                StatusCode::BAD_REQUEST,
+                "guest links cannot grant admin scope",
+            ));
+        }
+    }
+    let expires_in = req.expires_in.unwrap_or(DEFAULT_EXPIRES_IN);
+    if expires_in == 0 || expires_in > MAX_EXPIRES_IN {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "expires_in must be between 1 and {} seconds",
+                MAX_EXPIRES_IN
+            ),
+        ));
+    }
+    if req.max_uses == Some(0) {
+        return Err(err(StatusCode::BAD_REQUEST, "max_uses must be at least 1"));
+    }
+
+    let now = now_secs();
+    let expires_at = now + expires_in;
+    let token = match req.max_uses {
+        Some(max_uses) => CapabilityToken::create_root_with_uses(
+            &state.key,
+            req.scopes.clone(),
+            expires_at,
+            None,
+            max_uses,
+        ),
+        None => CapabilityToken::create_root(&state.key, req.scopes.clone(), expires_at, None),
+    }
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let encoded = token
+        .encode()
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    prune(&state, now);
+    let code =
+        CpskValidator::generate_token()[CpskValidator::PREFIX.len()..][..CODE_LEN].to_string();
+    let link = GuestLink {
+        token: encoded,
+        nonce: token.nonce,
+        scopes: req.scopes,
+        expires_at,
+        max_uses: req.max_uses,
+        created_at: now,
+    };
+    let response = GuestLinkResponse::new(&code, &link, 0);
+    tracing::info!(
+        "Guest link /g/{} minted: {} until {} (max uses: {:?})",
+        code,
+        link.scopes.join(","),
+        link.expires_at,
+        link.max_uses
+    );
+    state.links.lock().unwrap().insert(code, link);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+async fn list_links(
+    State(state): State<Arc<GuestLinkState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<GuestLinkResponse>>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+
+    prune(&state, now_secs());
+    let links = state.links.lock().unwrap();
+    let mut list: Vec<(u64, GuestLinkResponse)> = links
+        .iter()
+        .map(|(code, link)| {
+            (
+                link.created_at,
+                GuestLinkResponse::new(code, link, state.uses(&link.nonce)),
+            )
+        })
+        .collect();
+    list.sort_by_key(|(created_at, _)| *created_at);
+    Ok(Json(list.into_iter().map(|(_, link)| link).collect()))
+}
+
+async fn open_link(
+    State(state): State<Arc<GuestLinkState>>,
+    Path(code): Path<String>,
+) -> Result<Response, ApiError> {
+    let (token, expires_at, max_uses, nonce) = {
+        let links = state.links.lock().unwrap();
+        let link = links
+            .get(&code)
+            .filter(|link| link.expires_at >= now_secs())
+            .ok_or_else(|| err(StatusCode::NOT_FOUND, "guest link not found or expired"))?;
+        (
+            link.token.clone(),
+            link.expires_at,
+            link.max_uses,
+            link.nonce.clone(),
+        )
+    };
+    if max_uses.is_some_and(|max| state.uses(&nonce) >= max) {
+        return Err(err(StatusCode::GONE, "guest link used up"));
+    }
+
+    Ok(match state.redirect_url {
+        Some(ref url) => (
+            StatusCode::SEE_OTHER,
+            [(header::LOCATION, format!("{}#token={}", url, token))],
+        )
+            .into_response(),
+        None => Json(OpenLinkResponse { token, expires_at }).into_response(),
+    })
+}
+
+/// Build the guest link REST router.
+pub fn guest_link_router(state: Arc<GuestLinkState>) -> Router {
+    Router::new()
+        .route("/api/guest-links", post(create_link).get(list_links))
+        .route("/g/{code}", get(open_link))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use clasp_caps::{CapabilityValidator, MemoryUseCounter};
+    use clasp_core::security::TokenInfo;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn setup(redirect_url: Option<&str>) -> (Router, String, CapabilityValidator) {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let uses: Arc<dyn UseCounter> = Arc::new(MemoryUseCounter::new());
+        let cap_validator =
+            CapabilityValidator::new(vec![key.verifying_key().to_bytes().to_vec()], 5)
+                .with_use_counter(Arc::clone(&uses));
+
+        let validator = Arc::new(CpskValidator::new());
+        let admin_token = CpskValidator::generate_token();
+        validator.register(
+            admin_token.clone(),
+            TokenInfo::new(
+                admin_token.clone(),
+                vec![Scope::new(Action::Admin, "/**").unwrap()],
+            ),
+        );
+        let state = GuestLinkState::new(key, uses, validator, redirect_url.map(String::from));
+        (
+            guest_link_router(Arc::new(state)),
+            admin_token,
+            cap_validator,
+        )
+    }
+
+    fn create(token: Option<&str>, body: serde_json::Value) -> axum::http::Request<Body> {
+        let mut builder = axum::http::Request::builder()
+            .method("POST")
+            .uri("/api/guest-links")
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {}", token));
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    fn open(path: &str) -> axum::http::Request<Body> {
+        axum::http::Request::builder()
+            .uri(path)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn json(resp: Response) -> serde_json::Value {
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_guest_link_mint_open_and_use_limit() {
+        let (app, admin_token, cap_validator) = setup(None);
+        let votes = serde_json::json!({
+            "scopes": ["write:/votes/**"],
+            "expires_in": 7200,
+            "max_uses": 2
+        });
+
+        let resp = app
+            .clone()
+            .oneshot(create(None, votes.clone()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = app
+            .clone()
+            .oneshot(create(
+                Some(&admin_token),
+                serde_json::json!({"scopes": ["admin:/**"]}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = app
+            .clone()
+            .oneshot(create(Some(&admin_token), votes))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let link = json(resp).await;
+        let path = link["path"].as_str().unwrap().to_string();
+        assert_eq!(path.len(), "/g/".len() + CODE_LEN);
+        assert_eq!(link["max_uses"], 2);
+
+        let resp = app.clone().oneshot(open(&path)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let token = json(resp).await["token"].as_str().unwrap().to_string();
+        assert_eq!(token, link["token"]);
+
+        for _ in 0..2 {
+            match cap_validator.validate(&token) {
+                ValidationResult::Valid(info) => {
+                    assert!(info.has_scope(Action::Write, "/votes/a"));
+                    assert!(!info.has_scope(Action::Write, "/lights/a"));
+                }
+                other => panic!("expected Valid, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            cap_validator.validate(&token),
+            ValidationResult::Invalid(_)
+        ));
+
+        // Used up: the link stops handing out the token
+        let resp = app.clone().oneshot(open(&path)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::GONE);
+        let resp = app.oneshot(open("/g/unknown")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_guest_link_redirect() {
+        let (app, admin_token, _) = setup(Some("https://vote.example.com/"));
+        let resp = app
+            .clone()
+            .oneshot(create(
+                Some(&admin_token),
+                serde_json::json!({"scopes": ["write:/votes/**"]}),
+            ))
+            .await
+            .unwrap();
+        let link = json(resp).await;
+
+        let resp = app
+            .oneshot(open(link["path"].as_str().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            resp.headers()[header::LOCATION],
+            format!(
+                "https://vote.example.com/#token={}",
+                link["token"].as_str().unwrap()
+            )
+        );
+    }
+}
@@ -2,12 +2,15 @@
 //!
 //! Exposes:
 //! - `GET /healthz` — Liveness: returns 200 if the process is running
-//! - `GET /readyz`  — Readiness: returns 200 if the router is accepting connections
+//! - `GET /readyz`  — Readiness: returns 200 if the router is accepting connections,
+//!   503 while starting, shutting down or shedding load (`--shed-queue-depth`,
+//!   `--shed-loop-lag-ms`)
 
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::routing::get;
 use axum::Router;
+use clasp_router::OverloadGuard;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -16,12 +19,15 @@ pub struct HealthState {
     /// Set to `true` once the router is ready to accept connections.
     /// Set back to `false` during graceful shutdown.
     pub ready: AtomicBool,
+    /// Router load, for reporting shedding
+    pub overload: Arc<OverloadGuard>,
 }
 
 impl HealthState {
-    pub fn new() -> Self {
+    pub fn new(overload: Arc<OverloadGuard>) -> Self {
         Self {
             ready: AtomicBool::new(false),
+            overload,
         }
     }
 }
@@ -50,10 +56,21 @@ async fn healthz() -> (StatusCode, &'static str) {
     (StatusCode::OK, "ok\n")
 }
 
-async fn readyz(State(state): State<Arc<HealthState>>) -> (StatusCode, &'static str) {
-    if state.ready.load(Ordering::Relaxed) {
-        (StatusCode::OK, "ready\n")
-    } else {
-        (StatusCode::SERVICE_UNAVAILABLE, "not ready\n")
+async fn readyz(State(state): State<Arc<HealthState>>) -> (StatusCode, String) {
+    if !state.ready.load(Ordering::Relaxed) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "not ready\n".to_string());
     }
+    let load = state.overload.status();
+    if load.shedding {
+        // Load balancers stop sending new connections until load eases
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "shedding load: {} messages queued, runtime {}ms behind\n",
+                load.queued,
+                load.loop_lag.as_millis()
+            ),
+        );
+    }
+    (StatusCode::OK, "ready\n".to_string())
 }
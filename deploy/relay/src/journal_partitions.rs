@@ -0,0 +1,101 @@
+//! Namespace-partitioned journals for the relay.
+//!
+//! `--journal-partitions` points at a JSON file binding address patterns to
+//! journals of their own, each with its own backend and retention:
+//!
+//! ```json
+//! {
+//!   "partitions": [
+//!     { "name": "chat", "pattern": "/chat/**", "memory": true, "max_entries": 10000 },
+//!     { "name": "automation", "pattern": "/automation/**",
+//!       "sqlite": "/var/lib/clasp/automation.db" }
+//!   ]
+//! }
+//! ```
+//!
+//! Partitions are matched in file order; other addresses go to `--journal`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use clasp_router::{JournalPartition, Retention};
+use serde::Deserialize;
+
+/// Contents of the `--journal-partitions` file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartitionsConfig {
+    pub partitions: Vec<PartitionSpec>,
+}
+
+/// One partition: a pattern, a backend and a retention.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartitionSpec {
+    pub name: String,
+    /// Address pattern routed to this partition
+    pub pattern: String,
+    /// SQLite journal path
+    #[serde(default)]
+    pub sqlite: Option<PathBuf>,
+    /// Use an in-memory journal instead of SQLite
+    #[serde(default)]
+    pub memory: bool,
+    /// Drop entries older than this many seconds
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Keep at most this many of the newest entries
+    #[serde(default)]
+    pub max_entries: Option<u64>,
+}
+
+impl PartitionSpec {
+    pub fn retention(&self) -> Retention {
+        Retention {
+            max_age: self.max_age_secs.map(Duration::from_secs),
+            max_entries: self.max_entries,
+        }
+    }
+
+    /// Open the partition's journal.
+    pub fn build(&self) -> anyhow::Result<JournalPartition> {
+        if !self.pattern.starts_with('/') {
+            bail!(
+                "journal partition {}: pattern must start with '/'",
+                self.name
+            );
+        }
+        let journal: Arc<dyn clasp_journal::Journal> = match (&self.sqlite, self.memory) {
+            (Some(path), false) => Arc::new(
+                clasp_journal::SqliteJournal::new(
+                    path.to_str().context("journal path must be valid UTF-8")?,
+                )
+                .with_context(|| {
+                    format!(
+                        "journal partition {}: failed to open {}",
+                        self.name,
+                        path.display()
+                    )
+                })?,
+            ),
+            (None, true) => {
+                Arc::new(clasp_journal::SqliteJournal::in_memory().with_context(|| {
+                    format!("journal partition {}: failed to create journal", self.name)
+                })?)
+            }
+            _ => bail!(
+                "journal partition {}: set exactly one of \"sqlite\" or \"memory\"",
+                self.name
+            ),
+        };
+        Ok(JournalPartition::new(&self.name, &self.pattern, journal)
+            .with_retention(self.retention()))
+    }
+}
+
+pub fn load_config(path: &Path) -> anyhow::Result<PartitionsConfig> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read journal partitions {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse journal partitions {}", path.display()))
+}
@@ -37,15 +37,42 @@
 
 pub mod app_config;
 pub mod auth;
+#[cfg(feature = "caps")]
+pub mod cap_audit;
+#[cfg(feature = "quic")]
+pub mod cert_reload;
 pub mod config;
 pub mod cpsk;
+pub mod doctor;
 #[cfg(feature = "federation")]
 pub mod federation;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "caps")]
+pub mod guest_links;
 pub mod health;
 #[cfg(feature = "lens")]
 pub mod lens;
 #[cfg(feature = "journal")]
 pub mod journal_api;
+#[cfg(feature = "journal")]
+pub mod journal_partitions;
+pub mod migrate;
+#[cfg(feature = "metrics")]
+pub mod param_metrics;
+#[cfg(feature = "projector")]
+pub mod projector;
+#[cfg(feature = "push")]
+pub mod push;
 #[cfg(feature = "registry")]
 pub mod registry;
+#[cfg(feature = "federation")]
+pub mod registry_sync;
+#[cfg(feature = "replication")]
+pub mod replication;
 pub mod server;
+pub mod shutdown;
+#[cfg(feature = "state-api")]
+pub mod state_api;
+#[cfg(feature = "timeseries")]
+pub mod timeseries;
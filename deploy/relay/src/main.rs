@@ -25,22 +25,55 @@
 //!
 //! # All protocols
 //! clasp-relay --mqtt-port 1883 --osc-port 8000 --quic-port 7331 --cert cert.pem --key key.pem
+//!
+//! # Check the environment for a configuration without serving
+//! clasp-relay --auth-port 7350 --journal ./data/journal.db doctor
+//!
+//! # Report pending schema migrations of the auth and registry databases
+//! clasp-relay --auth-db ./data/auth.db migrate --dry-run
 //! ```
 
 mod app_config;
 mod auth;
+#[cfg(feature = "caps")]
+mod cap_audit;
+#[cfg(feature = "quic")]
+mod cert_reload;
 mod config;
 mod cpsk;
+mod doctor;
 #[cfg(feature = "federation")]
 mod federation;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "caps")]
+mod guest_links;
 mod health;
 #[cfg(feature = "lens")]
 mod lens;
 #[cfg(feature = "journal")]
 mod journal_api;
+#[cfg(feature = "journal")]
+mod journal_partitions;
+mod migrate;
+#[cfg(feature = "metrics")]
+mod param_metrics;
+#[cfg(feature = "projector")]
+mod projector;
+#[cfg(feature = "push")]
+mod push;
 #[cfg(feature = "registry")]
 mod registry;
+#[cfg(feature = "federation")]
+mod registry_sync;
+#[cfg(feature = "replication")]
+mod replication;
 mod server;
+mod shutdown;
+#[cfg(feature = "state-api")]
+mod state_api;
+#[cfg(feature = "timeseries")]
+mod timeseries;
 
 use anyhow::Result;
 use clap::Parser;
@@ -49,7 +82,23 @@ use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = config::Cli::parse();
+    let mut cli = config::Cli::parse();
+
+    if let Some(config::Command::Doctor { json }) = cli.command.take() {
+        let report = doctor::run(&RelayConfig::from(cli));
+        if json {
+            println!("{}", report.to_json());
+        } else {
+            println!("{}", report);
+        }
+        std::process::exit(if report.ok() { 0 } else { 1 });
+    }
+
+    if let Some(config::Command::Migrate { dry_run }) = cli.command.take() {
+        let report = migrate::run(&RelayConfig::from(cli), dry_run)?;
+        println!("{}", report);
+        return Ok(());
+    }
 
     // Setup logging (uses cli.verbose before conversion)
     let filter = if cli.verbose {
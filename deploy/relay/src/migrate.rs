@@ -0,0 +1,130 @@
+//! `clasp-relay migrate`: schema upgrades for the relay's SQLite databases.
+//!
+//! The auth database (`--auth-db`) and the entity registry (`--registry-db`)
+//! are migrated automatically when the relay opens them, each backed up to
+//! `{path}.v{version}.bak` first. `migrate` does the same without serving;
+//! with `--dry-run` it only reports what is pending:
+//!
+//! ```bash
+//! clasp-relay --auth-db ./data/auth.db --registry-db ./data/registry.db migrate --dry-run
+//! ```
+//!
+//! Databases that don't exist yet are skipped; they are created at the
+//! latest version on first open. The process exits non-zero if a database
+//! can't be read or migrated, including one at a newer version than this
+//! build supports.
+
+use anyhow::{Context, Result};
+use clasp_registry::migrate::{self, Migration};
+use rusqlite::{Connection, OpenFlags};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::RelayConfig;
+
+/// Migration state of one database
+#[derive(Debug, Clone)]
+pub struct DatabaseStatus {
+    pub name: &'static str,
+    pub path: PathBuf,
+    /// Schema version before this run (`None` if the file doesn't exist)
+    pub version: Option<u32>,
+    /// Pending migrations as `(version, name)`
+    pub pending: Vec<(u32, &'static str)>,
+    /// Whether the pending migrations were applied
+    pub applied: bool,
+}
+
+/// Every database the configuration uses
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub databases: Vec<DatabaseStatus>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, db) in self.databases.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}  {}  ", db.name, db.path.display())?;
+            let Some(version) = db.version else {
+                write!(f, "not created yet")?;
+                continue;
+            };
+            if db.pending.is_empty() {
+                write!(f, "up to date at version {}", version)?;
+                continue;
+            }
+            let names: Vec<String> = db
+                .pending
+                .iter()
+                .map(|(version, name)| format!("{} ({})", version, name))
+                .collect();
+            write!(
+                f,
+                "version {}, {} {}",
+                version,
+                if db.applied { "applied" } else { "pending" },
+                names.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Report the pending migrations of every configured database, and apply
+/// them unless `dry_run`
+pub fn run(config: &RelayConfig, dry_run: bool) -> Result<Report> {
+    let mut databases = vec![(
+        "auth",
+        PathBuf::from(&config.auth_db),
+        crate::auth::AUTH_MIGRATIONS,
+    )];
+    if let Some(path) = &config.registry_db {
+        databases.push(("registry", path.clone(), clasp_registry::sqlite::MIGRATIONS));
+    }
+
+    let mut report = Report::default();
+    for (name, path, migrations) in databases {
+        let status = database(name, &path, migrations, dry_run)
+            .with_context(|| format!("{} database {}", name, path.display()))?;
+        report.databases.push(status);
+    }
+    Ok(report)
+}
+
+fn database(
+    name: &'static str,
+    path: &Path,
+    migrations: &'static [Migration],
+    dry_run: bool,
+) -> Result<DatabaseStatus> {
+    let mut status = DatabaseStatus {
+        name,
+        path: path.to_path_buf(),
+        version: None,
+        pending: Vec::new(),
+        applied: false,
+    };
+    if !path.exists() {
+        return Ok(status);
+    }
+
+    let flags = if dry_run {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    };
+    let mut conn = Connection::open_with_flags(path, flags)?;
+    status.version = Some(migrate::current_version(&conn)?);
+    status.pending = migrate::pending(&conn, migrations)?
+        .into_iter()
+        .map(|m| (m.version, m.name))
+        .collect();
+    if !dry_run && !status.pending.is_empty() {
+        migrate::migrate(&mut conn, migrations)?;
+        status.applied = true;
+    }
+    Ok(status)
+}
@@ -0,0 +1,102 @@
+//! OpenMetrics export of router state for long-term trending.
+//!
+//! Numeric params matching the configured patterns (`--metrics-param`) are
+//! rendered as a `clasp_param` gauge family with an `address` label, so an
+//! existing Prometheus/Grafana stack can trend sensor values by scraping
+//! `GET /metrics/params` on the metrics port.
+//!
+//! Values are read from the state store on every scrape, so removed or
+//! expired params disappear instead of going stale. Ints and floats map
+//! directly, bools map to 0/1, and all other value types are skipped.
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use clasp_core::Value;
+use clasp_router::RouterState;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Upper bound on exported series per scrape
+pub const MAX_SERIES: usize = 10_000;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Shared state for the param exporter.
+pub struct ParamMetricsState {
+    pub state: Arc<RouterState>,
+    pub patterns: Vec<String>,
+}
+
+/// Build the `/metrics/params` router.
+pub fn param_metrics_router(state: Arc<ParamMetricsState>) -> Router {
+    Router::new()
+        .route("/metrics/params", get(scrape))
+        .with_state(state)
+}
+
+async fn scrape(State(state): State<Arc<ParamMetricsState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        render(&state.state, &state.patterns),
+    )
+}
+
+/// Render matching numeric params in the OpenMetrics text format.
+pub fn render(state: &RouterState, patterns: &[String]) -> String {
+    // Sorted and de-duplicated across overlapping patterns
+    let mut samples = BTreeMap::new();
+    for pattern in patterns {
+        for (address, param) in state.get_matching(pattern) {
+            if let Some(value) = gauge_value(&param.value) {
+                samples.insert(address, value);
+            }
+        }
+    }
+    if samples.len() > MAX_SERIES {
+        tracing::warn!(
+            "Param metrics: {} series match, exporting the first {}",
+            samples.len(),
+            MAX_SERIES
+        );
+    }
+
+    let mut out = String::new();
+    out.push_str("# TYPE clasp_param gauge\n");
+    out.push_str("# HELP clasp_param Current value of a CLASP param.\n");
+    for (address, value) in samples.into_iter().take(MAX_SERIES) {
+        let _ = writeln!(
+            out,
+            "clasp_param{{address=\"{}\"}} {}",
+            escape_label(&address),
+            value
+        );
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn gauge_value(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(i) => Some(*i as f64),
+        Value::Float(f) if f.is_finite() => Some(*f),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
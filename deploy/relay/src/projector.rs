@@ -0,0 +1,775 @@
+//! SQL read model projected from the journal.
+//!
+//! The projector tails the journal in a background task and maintains
+//! ordinary SQL tables described in the `--projector-config` JSON file, so BI
+//! tools can query current and historical CLASP state without connecting to
+//! the router. It reads the journal after the fact and never touches the
+//! router's hot path.
+//!
+//! # Config format
+//!
+//! ```json
+//! {
+//!   "sqlite": "/var/lib/clasp/read-model.db",
+//!   "tables": [
+//!     {
+//!       "path": "/lights/{zone}/{fixture}/brightness",
+//!       "table": "light_levels",
+//!       "mode": "current",
+//!       "columns": { "zone": "zone", "fixture": "fixture", "level": "number" }
+//!     },
+//!     { "path": "/sensors/**", "table": "sensor_history", "mode": "history" }
+//!   ],
+//!   "batch_size": 500,
+//!   "poll_interval_ms": 1000
+//! }
+//! ```
+//!
+//! Exactly one of `sqlite` or `postgres` (`{ "url": "postgres://..." }`,
+//! requires the `projector-postgres` feature) must be set.
+//!
+//! `columns` maps column names to a source: `address`, `value` (text; JSON
+//! for non-strings), `number` (numeric values, otherwise NULL), `author`,
+//! `signal`, `timestamp` (microseconds since the epoch), `seq`, `revision`,
+//! or the name of a `{capture}` in `path`. Without `columns`, each capture
+//! gets a column of the same name plus `address`, `value` and `timestamp`.
+//!
+//! A `current` table keeps one row per address (keyed on its `address`
+//! column); a `history` table gets a row per journal entry (keyed on its
+//! `seq` column). Key columns are added if the mapping leaves them out.
+//! Tables are created if missing. An entry is projected into every table
+//! whose `path` matches.
+//!
+//! The last projected sequence number is stored in `clasp_projector_offset`
+//! in the same transaction as the rows, so a restarted relay resumes where
+//! it stopped without duplicating history.
+
+use crate::app_config::match_address;
+use async_trait::async_trait;
+use clasp_core::{SignalType, Value};
+use clasp_journal::{Journal, JournalEntry};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// First retry delay after a failed batch.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest delay between retries.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// ---------------------------------------------------------------------------
+// Config types
+// ---------------------------------------------------------------------------
+
+/// Top-level projector config loaded from `--projector-config`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectorConfig {
+    /// Address pattern to table mappings.
+    pub tables: Vec<TableMapping>,
+
+    /// Journal entries read per batch.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
+
+    /// How often to check the journal once caught up.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+
+    /// Key in `clasp_projector_offset`, to let several relays share a database.
+    #[serde(default = "default_name")]
+    pub name: String,
+
+    /// SQLite database file.
+    #[serde(default)]
+    pub sqlite: Option<PathBuf>,
+
+    /// PostgreSQL destination (requires the `projector-postgres` feature).
+    #[serde(default)]
+    pub postgres: Option<PostgresConfig>,
+}
+
+fn default_batch_size() -> u32 {
+    500
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_name() -> String {
+    "default".to_string()
+}
+
+/// Maps addresses matching `path` to rows of `table`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TableMapping {
+    /// Path pattern; `{named}` segments can be used as column sources.
+    pub path: String,
+
+    /// Target table (`[A-Za-z0-9_.]`).
+    pub table: String,
+
+    #[serde(default)]
+    pub mode: ProjectionMode,
+
+    /// Column name to source (see the module docs).
+    #[serde(default)]
+    pub columns: BTreeMap<String, String>,
+}
+
+/// Whether a table holds the latest value or every change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionMode {
+    /// One row per address, updated in place
+    #[default]
+    Current,
+    /// One row per journal entry
+    History,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostgresConfig {
+    /// libpq-style connection string, e.g. `postgres://user@host/db`.
+    pub url: String,
+}
+
+/// Load a projector config from a JSON file.
+pub fn load_config(path: &Path) -> anyhow::Result<ProjectorConfig> {
+    use anyhow::Context;
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read projector config {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse projector config {}", path.display()))
+}
+
+// ---------------------------------------------------------------------------
+// Tables
+// ---------------------------------------------------------------------------
+
+/// Where a column's value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnSource {
+    Address,
+    Value,
+    Number,
+    Author,
+    Signal,
+    Timestamp,
+    Seq,
+    Revision,
+    Capture(String),
+}
+
+/// SQL type of a column, mapped to each dialect's name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Real,
+}
+
+impl ColumnSource {
+    fn parse(source: &str, captures: &[&str]) -> anyhow::Result<Self> {
+        Ok(match source {
+            "address" => ColumnSource::Address,
+            "value" => ColumnSource::Value,
+            "number" => ColumnSource::Number,
+            "author" => ColumnSource::Author,
+            "signal" => ColumnSource::Signal,
+            "timestamp" => ColumnSource::Timestamp,
+            "seq" => ColumnSource::Seq,
+            "revision" => ColumnSource::Revision,
+            name if captures.contains(&name) => ColumnSource::Capture(name.to_string()),
+            other => anyhow::bail!("unknown column source '{}'", other),
+        })
+    }
+
+    pub fn column_type(&self) -> ColumnType {
+        match self {
+            ColumnSource::Number => ColumnType::Real,
+            ColumnSource::Timestamp | ColumnSource::Seq | ColumnSource::Revision => {
+                ColumnType::Integer
+            }
+            _ => ColumnType::Text,
+        }
+    }
+}
+
+/// A validated [`TableMapping`] with its columns in a fixed order.
+#[derive(Debug, Clone)]
+pub struct TableSpec {
+    pub path: String,
+    pub table: String,
+    pub mode: ProjectionMode,
+    pub columns: Vec<(String, ColumnSource)>,
+    /// Index into `columns` of the primary key
+    pub key: usize,
+}
+
+impl TableSpec {
+    pub fn new(mapping: &TableMapping) -> anyhow::Result<Self> {
+        if !valid_identifier(&mapping.table, true) {
+            anyhow::bail!(
+                "projector table name '{}' must be [A-Za-z0-9_.]",
+                mapping.table
+            );
+        }
+        let captures: Vec<&str> = mapping
+            .path
+            .split('/')
+            .filter_map(|seg| seg.strip_prefix('{')?.strip_suffix('}'))
+            .collect();
+
+        let mut columns = Vec::new();
+        if mapping.columns.is_empty() {
+            for name in &captures {
+                columns.push((name.to_string(), ColumnSource::Capture(name.to_string())));
+            }
+            columns.push(("address".to_string(), ColumnSource::Address));
+            columns.push(("value".to_string(), ColumnSource::Value));
+            columns.push(("timestamp".to_string(), ColumnSource::Timestamp));
+        } else {
+            for (name, source) in &mapping.columns {
+                let source = ColumnSource::parse(source, &captures).map_err(|e| {
+                    anyhow::anyhow!("table {} column {}: {}", mapping.table, name, e)
+                })?;
+                columns.push((name.clone(), source));
+            }
+        }
+        for (name, _) in &columns {
+            if !valid_identifier(name, false) {
+                anyhow::bail!(
+                    "projector column name '{}' in {} must be [A-Za-z0-9_]",
+                    name,
+                    mapping.table
+                );
+            }
+        }
+
+        let (key_source, key_name) = match mapping.mode {
+            ProjectionMode::Current => (ColumnSource::Address, "address"),
+            ProjectionMode::History => (ColumnSource::Seq, "seq"),
+        };
+        let key = match columns.iter().position(|(_, s)| *s == key_source) {
+            Some(key) => key,
+            None => {
+                if columns.iter().any(|(name, _)| name == key_name) {
+                    anyhow::bail!(
+                        "table {} needs a `{}` key column but `{}` is mapped to something else",
+                        mapping.table,
+                        key_name,
+                        key_name
+                    );
+                }
+                columns.push((key_name.to_string(), key_source));
+                columns.len() - 1
+            }
+        };
+
+        Ok(Self {
+            path: mapping.path.clone(),
+            table: mapping.table.clone(),
+            mode: mapping.mode,
+            columns,
+            key,
+        })
+    }
+
+    /// The row for `entry`, if its address matches this table.
+    pub fn row(&self, entry: &JournalEntry) -> Option<Vec<Cell>> {
+        let captures = match_address(&self.path, &entry.address)?;
+        Some(
+            self.columns
+                .iter()
+                .map(|(_, source)| match source {
+                    ColumnSource::Address => Cell::Text(Some(entry.address.clone())),
+                    ColumnSource::Value => Cell::Text(value_text(&entry.value)),
+                    ColumnSource::Number => Cell::Real(value_number(&entry.value)),
+                    ColumnSource::Author => Cell::Text(Some(entry.author.clone())),
+                    ColumnSource::Signal => {
+                        Cell::Text(Some(signal_name(entry.signal_type).to_string()))
+                    }
+                    ColumnSource::Timestamp => Cell::Integer(Some(entry.timestamp as i64)),
+                    ColumnSource::Seq => Cell::Integer(Some(entry.seq as i64)),
+                    ColumnSource::Revision => Cell::Integer(entry.revision.map(|r| r as i64)),
+                    ColumnSource::Capture(name) => {
+                        Cell::Text(captures.get(name.as_str()).map(|s| s.to_string()))
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// `CREATE TABLE IF NOT EXISTS` for this table.
+    pub fn create_sql(&self, dialect: Dialect) -> String {
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(name, source)| format!("{} {}", name, dialect.type_name(source.column_type())))
+            .collect();
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}, PRIMARY KEY ({}))",
+            self.table,
+            columns.join(", "),
+            self.columns[self.key].0
+        )
+    }
+
+    /// Upsert (`current`) or insert-once (`history`) statement for one row.
+    pub fn write_sql(&self, dialect: Dialect) -> String {
+        let names: Vec<&str> = self.columns.iter().map(|(n, _)| n.as_str()).collect();
+        let placeholders: Vec<String> = (1..=names.len()).map(|i| dialect.placeholder(i)).collect();
+        let key = &self.columns[self.key].0;
+        let updates: Vec<String> = names
+            .iter()
+            .filter(|name| **name != key)
+            .map(|name| format!("{} = excluded.{}", name, name))
+            .collect();
+        let conflict = if self.mode == ProjectionMode::Current && !updates.is_empty() {
+            format!("DO UPDATE SET {}", updates.join(", "))
+        } else {
+            "DO NOTHING".to_string()
+        };
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) {}",
+            self.table,
+            names.join(", "),
+            placeholders.join(", "),
+            key,
+            conflict
+        )
+    }
+}
+
+fn valid_identifier(name: &str, allow_schema: bool) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || (allow_schema && c == '.'))
+}
+
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => serde_json::to_string(other).ok(),
+    }
+}
+
+fn value_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(f) if f.is_finite() => Some(*f),
+        Value::Int(i) => Some(*i as f64),
+        Value::Bool(b) => Some(f64::from(u8::from(*b))),
+        _ => None,
+    }
+}
+
+fn signal_name(signal: SignalType) -> &'static str {
+    match signal {
+        SignalType::Param => "param",
+        SignalType::Event => "event",
+        SignalType::Stream => "stream",
+        SignalType::Gesture => "gesture",
+        SignalType::Timeline => "timeline",
+    }
+}
+
+/// One column value bound to a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(Option<String>),
+    Integer(Option<i64>),
+    Real(Option<f64>),
+}
+
+/// A row for the table at `table` in the store's spec list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectedRow {
+    pub table: usize,
+    pub cells: Vec<Cell>,
+}
+
+/// SQL flavour of a [`ProjectionStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+impl Dialect {
+    fn type_name(&self, column_type: ColumnType) -> &'static str {
+        match (self, column_type) {
+            (_, ColumnType::Text) => "TEXT",
+            (Dialect::Sqlite, ColumnType::Integer) => "INTEGER",
+            (Dialect::Sqlite, ColumnType::Real) => "REAL",
+            (Dialect::Postgres, ColumnType::Integer) => "BIGINT",
+            (Dialect::Postgres, ColumnType::Real) => "DOUBLE PRECISION",
+        }
+    }
+
+    fn placeholder(&self, index: usize) -> String {
+        match self {
+            Dialect::Sqlite => format!("?{}", index),
+            Dialect::Postgres => format!("${}", index),
+        }
+    }
+}
+
+const OFFSET_TABLE: &str = "clasp_projector_offset";
+
+// ---------------------------------------------------------------------------
+// Stores
+// ---------------------------------------------------------------------------
+
+/// Applies projected rows to a database.
+///
+/// [`SqliteStore`] and `PostgresStore` talk to real databases; tests and
+/// embedders can supply their own implementation.
+#[async_trait]
+pub trait ProjectionStore: Send + Sync {
+    /// The last journal sequence number applied under `name` (0 if none).
+    async fn offset(&self, name: &str) -> anyhow::Result<u64>;
+
+    /// Write `rows` and move `name`'s offset to `seq`, atomically.
+    async fn apply(&self, name: &str, rows: &[ProjectedRow], seq: u64) -> anyhow::Result<()>;
+}
+
+/// SQLite read model.
+pub struct SqliteStore {
+    conn: std::sync::Mutex<rusqlite::Connection>,
+    statements: Vec<String>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path, tables: &[TableSpec]) -> anyhow::Result<Self> {
+        Self::with_connection(rusqlite::Connection::open(path)?, tables)
+    }
+
+    pub fn in_memory(tables: &[TableSpec]) -> anyhow::Result<Self> {
+        Self::with_connection(rusqlite::Connection::open_in_memory()?, tables)
+    }
+
+    fn with_connection(conn: rusqlite::Connection, tables: &[TableSpec]) -> anyhow::Result<Self> {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {OFFSET_TABLE} (name TEXT PRIMARY KEY, seq INTEGER NOT NULL)"
+        ))?;
+        for table in tables {
+            conn.execute_batch(&table.create_sql(Dialect::Sqlite))?;
+        }
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+            statements: tables
+                .iter()
+                .map(|t| t.write_sql(Dialect::Sqlite))
+                .collect(),
+        })
+    }
+
+    /// Run a read-only query against the read model (for tests and tooling).
+    pub fn with_conn<T>(&self, f: impl FnOnce(&rusqlite::Connection) -> T) -> T {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        f(&conn)
+    }
+}
+
+impl rusqlite::ToSql for Cell {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        match self {
+            Cell::Text(v) => v.to_sql(),
+            Cell::Integer(v) => v.to_sql(),
+            Cell::Real(v) => v.to_sql(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProjectionStore for SqliteStore {
+    async fn offset(&self, name: &str) -> anyhow::Result<u64> {
+        use rusqlite::OptionalExtension;
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let seq: Option<i64> = conn
+            .query_row(
+                &format!("SELECT seq FROM {OFFSET_TABLE} WHERE name = ?1"),
+                [name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(seq.unwrap_or(0) as u64)
+    }
+
+    async fn apply(&self, name: &str, rows: &[ProjectedRow], seq: u64) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let tx = conn.transaction()?;
+        for row in rows {
+            let mut stmt = tx.prepare_cached(&self.statements[row.table])?;
+            stmt.execute(rusqlite::params_from_iter(row.cells.iter()))?;
+        }
+        tx.execute(
+            &format!(
+                "INSERT INTO {OFFSET_TABLE} (name, seq) VALUES (?1, ?2) \
+                 ON CONFLICT (name) DO UPDATE SET seq = excluded.seq"
+            ),
+            rusqlite::params![name, seq as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// PostgreSQL read model.
+#[cfg(feature = "projector-postgres")]
+pub struct PostgresStore {
+    url: String,
+    creates: Vec<String>,
+    statements: Vec<String>,
+    client: tokio::sync::Mutex<Option<tokio_postgres::Client>>,
+}
+
+#[cfg(feature = "projector-postgres")]
+impl PostgresStore {
+    pub fn new(config: &PostgresConfig, tables: &[TableSpec]) -> Self {
+        Self {
+            url: config.url.clone(),
+            creates: tables
+                .iter()
+                .map(|t| t.create_sql(Dialect::Postgres))
+                .collect(),
+            statements: tables
+                .iter()
+                .map(|t| t.write_sql(Dialect::Postgres))
+                .collect(),
+            client: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> anyhow::Result<tokio_postgres::Client> {
+        let (client, connection) =
+            tokio_postgres::connect(&self.url, tokio_postgres::NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!("Projector: PostgreSQL connection closed: {}", e);
+            }
+        });
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {OFFSET_TABLE} (name TEXT PRIMARY KEY, seq BIGINT NOT NULL)"
+            ))
+            .await?;
+        for create in &self.creates {
+            client.batch_execute(create).await?;
+        }
+        Ok(client)
+    }
+
+    async fn client(
+        &self,
+    ) -> anyhow::Result<tokio::sync::MutexGuard<'_, Option<tokio_postgres::Client>>> {
+        let mut guard = self.client.lock().await;
+        if guard.as_ref().is_none_or(|c| c.is_closed()) {
+            *guard = Some(self.connect().await?);
+        }
+        Ok(guard)
+    }
+}
+
+#[cfg(feature = "projector-postgres")]
+#[async_trait]
+impl ProjectionStore for PostgresStore {
+    async fn offset(&self, name: &str) -> anyhow::Result<u64> {
+        let guard = self.client().await?;
+        let client = guard.as_ref().expect("connected");
+        let row = client
+            .query_opt(
+                &format!("SELECT seq FROM {OFFSET_TABLE} WHERE name = $1"),
+                &[&name],
+            )
+            .await?;
+        Ok(row.map(|r| r.get::<_, i64>(0)).unwrap_or(0) as u64)
+    }
+
+    async fn apply(&self, name: &str, rows: &[ProjectedRow], seq: u64) -> anyhow::Result<()> {
+        use tokio_postgres::types::ToSql;
+
+        let mut guard = self.client().await?;
+        let client = guard.as_mut().expect("connected");
+        let result = async {
+            let tx = client.transaction().await?;
+            for row in rows {
+                let params: Vec<Box<dyn ToSql + Sync + Send>> = row
+                    .cells
+                    .iter()
+                    .map(|cell| -> Box<dyn ToSql + Sync + Send> {
+                        match cell {
+                            Cell::Text(v) => Box::new(v.clone()),
+                            Cell::Integer(v) => Box::new(*v),
+                            Cell::Real(v) => Box::new(*v),
+                        }
+                    })
+                    .collect();
+                let refs: Vec<&(dyn ToSql + Sync)> = params
+                    .iter()
+                    .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+                    .collect();
+                tx.execute(self.statements[row.table].as_str(), &refs)
+                    .await?;
+            }
+            tx.execute(
+                &format!(
+                    "INSERT INTO {OFFSET_TABLE} (name, seq) VALUES ($1, $2) \
+                     ON CONFLICT (name) DO UPDATE SET seq = excluded.seq"
+                ),
+                &[&name, &(seq as i64)],
+            )
+            .await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(ref e) = result {
+            if e.is_closed() {
+                *guard = None;
+            }
+        }
+        Ok(result?)
+    }
+}
+
+/// Build the store selected by the config.
+pub fn build_store(
+    config: &ProjectorConfig,
+    tables: &[TableSpec],
+) -> anyhow::Result<Arc<dyn ProjectionStore>> {
+    match (&config.sqlite, &config.postgres) {
+        (Some(path), None) => Ok(Arc::new(SqliteStore::open(path, tables)?)),
+        #[cfg(feature = "projector-postgres")]
+        (None, Some(postgres)) => Ok(Arc::new(PostgresStore::new(postgres, tables))),
+        #[cfg(not(feature = "projector-postgres"))]
+        (None, Some(_)) => anyhow::bail!(
+            "PostgreSQL projector requires the 'projector-postgres' feature. Rebuild with --features projector-postgres"
+        ),
+        (Some(_), Some(_)) => anyhow::bail!("projector config sets both sqlite and postgres"),
+        (None, None) => anyhow::bail!("projector config needs a sqlite or postgres destination"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Projector
+// ---------------------------------------------------------------------------
+
+/// Counters for the projector, also exported as metrics with the `metrics` feature.
+#[derive(Debug, Default)]
+pub struct ProjectorStats {
+    /// Journal sequence number applied so far
+    pub seq: AtomicU64,
+    pub rows: AtomicU64,
+    pub errors: AtomicU64,
+}
+
+/// Reads the journal in batches and applies matching entries to the store.
+pub struct Projector {
+    config: ProjectorConfig,
+    tables: Vec<TableSpec>,
+    journal: Arc<dyn Journal>,
+    store: Arc<dyn ProjectionStore>,
+    seq: Option<u64>,
+    pub stats: Arc<ProjectorStats>,
+}
+
+impl Projector {
+    /// `tables` must be the specs the store was built with, in the same order.
+    pub fn new(
+        config: ProjectorConfig,
+        tables: Vec<TableSpec>,
+        journal: Arc<dyn Journal>,
+        store: Arc<dyn ProjectionStore>,
+    ) -> Self {
+        Self {
+            config,
+            tables,
+            journal,
+            store,
+            seq: None,
+            stats: Arc::new(ProjectorStats::default()),
+        }
+    }
+
+    /// Validate the config's table mappings.
+    pub fn table_specs(config: &ProjectorConfig) -> anyhow::Result<Vec<TableSpec>> {
+        config.tables.iter().map(TableSpec::new).collect()
+    }
+
+    /// Rows for one journal entry, one per matching table.
+    pub fn project(&self, entry: &JournalEntry) -> Vec<ProjectedRow> {
+        self.tables
+            .iter()
+            .enumerate()
+            .filter_map(|(table, spec)| spec.row(entry).map(|cells| ProjectedRow { table, cells }))
+            .collect()
+    }
+
+    /// Apply the next batch of journal entries. Returns how many were read.
+    pub async fn step(&mut self) -> anyhow::Result<usize> {
+        let seq = match self.seq {
+            Some(seq) => seq,
+            None => {
+                let seq = self.store.offset(&self.config.name).await?;
+                self.seq = Some(seq);
+                seq
+            }
+        };
+
+        let entries = self
+            .journal
+            .since(seq, Some(self.config.batch_size.max(1)))
+            .await?;
+        let Some(last) = entries.last().map(|e| e.seq) else {
+            return Ok(0);
+        };
+        let rows: Vec<ProjectedRow> = entries.iter().flat_map(|e| self.project(e)).collect();
+        self.store.apply(&self.config.name, &rows, last).await?;
+
+        self.seq = Some(last);
+        self.stats.seq.store(last, Ordering::Relaxed);
+        self.stats
+            .rows
+            .fetch_add(rows.len() as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("clasp_projector_rows_total").increment(rows.len() as u64);
+            metrics::gauge!("clasp_projector_seq").set(last as f64);
+        }
+        Ok(entries.len())
+    }
+}
+
+/// Tail the journal forever: drain full batches back to back, then poll.
+/// Failed batches are retried with exponential backoff.
+pub async fn run_projector(mut projector: Projector) {
+    let poll = Duration::from_millis(projector.config.poll_interval_ms.max(10));
+    let batch_size = projector.config.batch_size.max(1) as usize;
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        match projector.step().await {
+            Ok(n) => {
+                backoff = INITIAL_BACKOFF;
+                if n < batch_size {
+                    tokio::time::sleep(poll).await;
+                }
+            }
+            Err(e) => {
+                projector.stats.errors.fetch_add(1, Ordering::Relaxed);
+                #[cfg(feature = "metrics")]
+                metrics::counter!("clasp_projector_errors_total").increment(1);
+                tracing::warn!("Projector: batch failed ({}), retrying in {:?}", e, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
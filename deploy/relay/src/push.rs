@@ -0,0 +1,1073 @@
+//! Push notification gateway for offline users.
+//!
+//! Rules in the `--push-config` JSON file map address patterns to notification
+//! payloads. When a SET or PUBLISH lands on a matching address and the target
+//! subject has no active session, the relay sends a notification to every
+//! device token registered for that entity via FCM, APNs, or Web Push.
+//!
+//! Device tokens are registered over HTTP with the caller's CPSK token, and
+//! stored per entity (the token subject) in SQLite:
+//! - `POST /api/push/devices` registers `{"platform": "fcm", "token": "..."}`
+//! - `GET /api/push/devices` lists the caller's devices
+//! - `DELETE /api/push/devices` removes `{"token": "..."}`
+//!
+//! # Config format
+//!
+//! ```json
+//! {
+//!   "rules": [
+//!     {
+//!       "path": "/chat/user/{targetId}/dms/{roomId}",
+//!       "target": "targetId",
+//!       "title": "New message from {fromName}",
+//!       "body": "{text}",
+//!       "data": { "room": "{roomId}" }
+//!     }
+//!   ],
+//!   "fcm": { "service_account": "/etc/clasp/fcm-service-account.json" },
+//!   "apns": { "team_id": "TEAMID", "key_id": "KEYID", "key_file": "/etc/clasp/apns.p8", "topic": "com.example.app" },
+//!   "webpush": { "vapid_private_key_file": "/etc/clasp/vapid.pem", "vapid_public_key": "B...", "subject": "mailto:ops@example.com" },
+//!   "send_timeout_ms": 10000
+//! }
+//! ```
+//!
+//! Web Push notifications are sent without a payload (no RFC 8291 encryption);
+//! the service worker is expected to fetch the message content on wake.
+
+use crate::app_config::match_address;
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use bytes::Bytes;
+use clasp_core::security::{CpskValidator, TokenValidator, ValidationResult};
+use clasp_core::{codec, Message, Value};
+use clasp_router::session::{Session, SessionId};
+use clasp_router::subscription::Subscription;
+use clasp_router::SubscriptionManager;
+use dashmap::DashMap;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, Semaphore};
+
+/// How often the gateway session is touched so the idle cleanup task keeps it.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Buffered writes waiting for dispatch before new ones are dropped.
+const OBSERVER_BUFFER: usize = 1024;
+
+/// Writes being delivered at once; further writes are dropped until one finishes.
+const MAX_PENDING_WRITES: usize = 256;
+
+/// Provider requests in flight at once, across all writes.
+const MAX_CONCURRENT_SENDS: usize = 32;
+
+/// Default `send_timeout_ms`.
+const SEND_TIMEOUT_MS: u64 = 10_000;
+
+// ---------------------------------------------------------------------------
+// Config types
+// ---------------------------------------------------------------------------
+
+/// Top-level push config loaded from `--push-config`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PushConfig {
+    /// Address rules that trigger notifications (first-match).
+    #[serde(default)]
+    pub rules: Vec<PushRule>,
+
+    /// Firebase Cloud Messaging (HTTP v1 API).
+    #[serde(default)]
+    pub fcm: Option<FcmConfig>,
+
+    /// Apple Push Notification service (token-based auth).
+    #[serde(default)]
+    pub apns: Option<ApnsConfig>,
+
+    /// Web Push with VAPID.
+    #[serde(default)]
+    pub webpush: Option<WebPushConfig>,
+
+    /// Milliseconds a single provider request may take before it is
+    /// abandoned (0 = the 10 second default).
+    #[serde(default)]
+    pub send_timeout_ms: u64,
+}
+
+/// Maps an address pattern to a notification for the subject in `target`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PushRule {
+    /// Path pattern with `{named}` captures, e.g. `/chat/user/{targetId}/dms/{roomId}`.
+    pub path: String,
+
+    /// Name of the capture holding the recipient's subject (entity ID).
+    pub target: String,
+
+    /// Notification title. `{name}` is replaced by a path capture or a
+    /// top-level field of the written value.
+    pub title: String,
+
+    /// Notification body, templated like `title`.
+    #[serde(default)]
+    pub body: String,
+
+    /// Extra key/value data delivered with the notification, templated like `title`.
+    #[serde(default)]
+    pub data: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FcmConfig {
+    /// Path to a Google service account JSON key with FCM send permission.
+    pub service_account: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApnsConfig {
+    pub team_id: String,
+    pub key_id: String,
+    /// Path to the `.p8` signing key downloaded from the Apple developer portal.
+    pub key_file: String,
+    /// App bundle ID, sent as `apns-topic`.
+    pub topic: String,
+    /// Use the sandbox gateway (development builds).
+    #[serde(default)]
+    pub sandbox: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebPushConfig {
+    /// Path to the VAPID P-256 private key (PEM, PKCS#8).
+    pub vapid_private_key_file: String,
+    /// VAPID public key as given to `pushManager.subscribe()` (base64url).
+    pub vapid_public_key: String,
+    /// Contact URI for the push service, e.g. `mailto:ops@example.com`.
+    pub subject: String,
+}
+
+/// Device platform for a registered push token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Fcm,
+    Apns,
+    Webpush,
+}
+
+impl Platform {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Fcm => "fcm",
+            Platform::Apns => "apns",
+            Platform::Webpush => "webpush",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "fcm" => Some(Platform::Fcm),
+            "apns" => Some(Platform::Apns),
+            "webpush" => Some(Platform::Webpush),
+            _ => None,
+        }
+    }
+}
+
+/// A device token registered for an entity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Device {
+    pub platform: Platform,
+    /// FCM registration token, APNs device token (hex), or the Web Push
+    /// subscription JSON (`{"endpoint": ..., "keys": {...}}`).
+    pub token: String,
+}
+
+/// A rendered notification ready for delivery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub data: HashMap<String, String>,
+}
+
+// ---------------------------------------------------------------------------
+// Rule evaluation
+// ---------------------------------------------------------------------------
+
+impl PushConfig {
+    /// Find the first rule matching `address` and render its notification.
+    ///
+    /// Returns the recipient subject and the notification, or `None` if no rule
+    /// matches. Null writes (deletions) never notify.
+    pub fn evaluate(&self, address: &str, value: &Value) -> Option<(String, Notification)> {
+        if matches!(value, Value::Null) {
+            return None;
+        }
+        for rule in &self.rules {
+            if let Some(captures) = match_address(&rule.path, address) {
+                let Some(target) = captures.get(rule.target.as_str()) else {
+                    continue;
+                };
+                let target = target.to_string();
+                let notification = Notification {
+                    title: render(&rule.title, &captures, value),
+                    body: render(&rule.body, &captures, value),
+                    data: rule
+                        .data
+                        .iter()
+                        .map(|(k, v)| (k.clone(), render(v, &captures, value)))
+                        .collect(),
+                };
+                return Some((target, notification));
+            }
+        }
+        None
+    }
+
+    /// Check that every rule's `target` names a `{capture}` in its path.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for rule in &self.rules {
+            let capture = format!("{{{}}}", rule.target);
+            if !rule.path.split('/').any(|seg| seg == capture) {
+                anyhow::bail!(
+                    "push rule {}: target \"{}\" is not a capture in the path",
+                    rule.path,
+                    rule.target
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Subscription patterns covering every rule path (`{name}` becomes `*`).
+    pub fn subscription_patterns(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                rule.path
+                    .split('/')
+                    .map(|seg| {
+                        if seg.starts_with('{') && seg.ends_with('}') {
+                            "*"
+                        } else {
+                            seg
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect()
+    }
+}
+
+/// Replace `{name}` with a path capture, or else a top-level field of the value.
+/// Unknown placeholders are left as-is.
+fn render(template: &str, captures: &HashMap<&str, &str>, value: &Value) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+        result.push_str(&rest[..start]);
+        if let Some(capture) = captures.get(name) {
+            result.push_str(capture);
+        } else if let Some(field) = match value {
+            Value::Map(map) => map.get(name),
+            _ => None,
+        } {
+            match field {
+                Value::String(s) => result.push_str(s),
+                Value::Int(i) => result.push_str(&i.to_string()),
+                Value::Float(f) => result.push_str(&f.to_string()),
+                Value::Bool(b) => result.push_str(&b.to_string()),
+                _ => {}
+            }
+        } else {
+            result.push_str(&rest[start..start + len + 1]);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+// ---------------------------------------------------------------------------
+// Device store
+// ---------------------------------------------------------------------------
+
+/// SQLite-backed device token store, keyed by entity ID.
+pub struct DeviceStore {
+    db: Mutex<Connection>,
+}
+
+impl DeviceStore {
+    pub fn open(db_path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(db_path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS push_devices (
+                entity_id TEXT NOT NULL,
+                platform TEXT NOT NULL,
+                token TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (entity_id, token)
+            );",
+        )?;
+        Ok(Self {
+            db: Mutex::new(conn),
+        })
+    }
+
+    /// Register a device for an entity. Re-registering the same token is a no-op.
+    pub fn register(&self, entity_id: &str, device: &Device) -> rusqlite::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        self.db.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO push_devices (entity_id, platform, token, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            (entity_id, device.platform.as_str(), &device.token, now),
+        )?;
+        Ok(())
+    }
+
+    /// Remove a device token. Returns true if it was registered.
+    pub fn unregister(&self, entity_id: &str, token: &str) -> rusqlite::Result<bool> {
+        let n = self.db.lock().unwrap().execute(
+            "DELETE FROM push_devices WHERE entity_id = ?1 AND token = ?2",
+            (entity_id, token),
+        )?;
+        Ok(n > 0)
+    }
+
+    /// All devices registered for an entity.
+    pub fn devices(&self, entity_id: &str) -> rusqlite::Result<Vec<Device>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT platform, token FROM push_devices WHERE entity_id = ?1 ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map([entity_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut devices = Vec::new();
+        for row in rows {
+            let (platform, token) = row?;
+            if let Some(platform) = Platform::parse(&platform) {
+                devices.push(Device { platform, token });
+            }
+        }
+        Ok(devices)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Delivery
+// ---------------------------------------------------------------------------
+
+/// Error returned by a [`PushSender`].
+#[derive(Debug)]
+pub enum PushError {
+    /// The provider reported the token as invalid or unregistered. The
+    /// gateway removes the device from the store.
+    InvalidToken,
+    /// No provider is configured for the device's platform.
+    NotConfigured(Platform),
+    /// Any other delivery failure.
+    Failed(String),
+}
+
+impl std::fmt::Display for PushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushError::InvalidToken => write!(f, "invalid device token"),
+            PushError::NotConfigured(p) => write!(f, "no {} provider configured", p.as_str()),
+            PushError::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PushError {}
+
+/// Delivers a notification to a single device.
+///
+/// [`ProviderSender`] talks to the real push services; tests and embedders
+/// can supply their own implementation.
+#[async_trait]
+pub trait PushSender: Send + Sync {
+    async fn send(&self, device: &Device, notification: &Notification) -> Result<(), PushError>;
+}
+
+/// Cached OAuth access token for FCM.
+struct AccessToken {
+    token: String,
+    expires_at: SystemTime,
+}
+
+#[derive(Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    client_email: String,
+    private_key: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".into()
+}
+
+#[derive(Serialize)]
+struct JwtClaims<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aud: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+struct FcmProvider {
+    account: ServiceAccount,
+    key: jsonwebtoken::EncodingKey,
+    access: tokio::sync::Mutex<Option<AccessToken>>,
+}
+
+struct ApnsProvider {
+    config: ApnsConfig,
+    key: jsonwebtoken::EncodingKey,
+    /// Provider JWTs are valid for an hour; Apple asks that they are reused.
+    jwt: Mutex<Option<(String, u64)>>,
+    client: reqwest::Client,
+}
+
+struct WebPushProvider {
+    config: WebPushConfig,
+    key: jsonwebtoken::EncodingKey,
+}
+
+/// [`PushSender`] backed by FCM, APNs, and Web Push, per [`PushConfig`].
+pub struct ProviderSender {
+    client: reqwest::Client,
+    fcm: Option<FcmProvider>,
+    apns: Option<ApnsProvider>,
+    webpush: Option<WebPushProvider>,
+}
+
+impl ProviderSender {
+    /// Load provider credentials from the files referenced in `config`.
+    pub fn from_config(config: &PushConfig) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        use jsonwebtoken::EncodingKey;
+
+        let fcm = match &config.fcm {
+            Some(fcm) => {
+                let json = std::fs::read_to_string(&fcm.service_account).with_context(|| {
+                    format!("Failed to read FCM service account {}", fcm.service_account)
+                })?;
+                let account: ServiceAccount = serde_json::from_str(&json)
+                    .context("Failed to parse FCM service account JSON")?;
+                let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+                    .context("Invalid FCM service account private key")?;
+                Some(FcmProvider {
+                    account,
+                    key,
+                    access: tokio::sync::Mutex::new(None),
+                })
+            }
+            None => None,
+        };
+
+        let apns = match &config.apns {
+            Some(apns) => {
+                let pem = std::fs::read(&apns.key_file)
+                    .with_context(|| format!("Failed to read APNs key {}", apns.key_file))?;
+                let key = EncodingKey::from_ec_pem(&pem).context("Invalid APNs signing key")?;
+                // APNs only speaks HTTP/2
+                let client = reqwest::Client::builder()
+                    .http2_prior_knowledge()
+                    .build()
+                    .context("Failed to build APNs HTTP client")?;
+                Some(ApnsProvider {
+                    config: apns.clone(),
+                    key,
+                    jwt: Mutex::new(None),
+                    client,
+                })
+            }
+            None => None,
+        };
+
+        let webpush = match &config.webpush {
+            Some(wp) => {
+                let pem = std::fs::read(&wp.vapid_private_key_file).with_context(|| {
+                    format!("Failed to read VAPID key {}", wp.vapid_private_key_file)
+                })?;
+                let key = EncodingKey::from_ec_pem(&pem).context("Invalid VAPID private key")?;
+                Some(WebPushProvider {
+                    config: wp.clone(),
+                    key,
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            fcm,
+            apns,
+            webpush,
+        })
+    }
+
+    async fn fcm_access_token(&self, fcm: &FcmProvider) -> Result<String, PushError> {
+        let mut cached = fcm.access.lock().await;
+        if let Some(ref t) = *cached {
+            if t.expires_at > SystemTime::now() + Duration::from_secs(60) {
+                return Ok(t.token.clone());
+            }
+        }
+
+        let now = unix_now();
+        let claims = JwtClaims {
+            iss: Some(&fcm.account.client_email),
+            sub: None,
+            aud: Some(&fcm.account.token_uri),
+            scope: Some("https://www.googleapis.com/auth/firebase.messaging"),
+            iat: Some(now),
+            exp: Some(now + 3600),
+        };
+        let assertion = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+            &claims,
+            &fcm.key,
+        )
+        .map_err(|e| PushError::Failed(format!("FCM JWT signing failed: {}", e)))?;
+
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+            expires_in: u64,
+        }
+
+        let resp = self
+            .client
+            .post(&fcm.account.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(format!("FCM token exchange failed: {}", e)))?;
+        if !resp.status().is_success() {
+            return Err(PushError::Failed(format!(
+                "FCM token exchange returned {}",
+                resp.status()
+            )));
+        }
+        let token: TokenResponse = resp
+            .json()
+            .await
+            .map_err(|e| PushError::Failed(format!("FCM token response: {}", e)))?;
+
+        *cached = Some(AccessToken {
+            token: token.access_token.clone(),
+            expires_at: SystemTime::now() + Duration::from_secs(token.expires_in),
+        });
+        Ok(token.access_token)
+    }
+
+    async fn send_fcm(&self, token: &str, n: &Notification) -> Result<(), PushError> {
+        let fcm = self
+            .fcm
+            .as_ref()
+            .ok_or(PushError::NotConfigured(Platform::Fcm))?;
+        let access_token = self.fcm_access_token(fcm).await?;
+        let url = format!(
+            "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+            fcm.account.project_id
+        );
+        let body = serde_json::json!({
+            "message": {
+                "token": token,
+                "notification": { "title": n.title, "body": n.body },
+                "data": n.data,
+            }
+        });
+        let resp = self
+            .client
+            .post(url)
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(format!("FCM request failed: {}", e)))?;
+        match resp.status().as_u16() {
+            200..=299 => Ok(()),
+            // UNREGISTERED / INVALID_ARGUMENT for a stale or malformed token
+            400 | 404 => Err(PushError::InvalidToken),
+            status => Err(PushError::Failed(format!("FCM returned {}", status))),
+        }
+    }
+
+    fn apns_jwt(&self, apns: &ApnsProvider) -> Result<String, PushError> {
+        let now = unix_now();
+        let mut cached = apns.jwt.lock().unwrap();
+        if let Some((ref jwt, issued)) = *cached {
+            // Refresh well before the one hour limit
+            if now < issued + 50 * 60 {
+                return Ok(jwt.clone());
+            }
+        }
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::ES256);
+        header.kid = Some(apns.config.key_id.clone());
+        let claims = JwtClaims {
+            iss: Some(&apns.config.team_id),
+            sub: None,
+            aud: None,
+            scope: None,
+            iat: Some(now),
+            exp: None,
+        };
+        let jwt = jsonwebtoken::encode(&header, &claims, &apns.key)
+            .map_err(|e| PushError::Failed(format!("APNs JWT signing failed: {}", e)))?;
+        *cached = Some((jwt.clone(), now));
+        Ok(jwt)
+    }
+
+    async fn send_apns(&self, token: &str, n: &Notification) -> Result<(), PushError> {
+        let apns = self
+            .apns
+            .as_ref()
+            .ok_or(PushError::NotConfigured(Platform::Apns))?;
+        let jwt = self.apns_jwt(apns)?;
+        let host = if apns.config.sandbox {
+            "api.sandbox.push.apple.com"
+        } else {
+            "api.push.apple.com"
+        };
+        let mut body = serde_json::json!({
+            "aps": { "alert": { "title": n.title, "body": n.body } }
+        });
+        for (k, v) in &n.data {
+            if k != "aps" {
+                body[k] = serde_json::Value::String(v.clone());
+            }
+        }
+        let resp = apns
+            .client
+            .post(format!("https://{}/3/device/{}", host, token))
+            .header("authorization", format!("bearer {}", jwt))
+            .header("apns-topic", &apns.config.topic)
+            .header("apns-push-type", "alert")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(format!("APNs request failed: {}", e)))?;
+        match resp.status().as_u16() {
+            200..=299 => Ok(()),
+            // 410 Unregistered; 400 BadDeviceToken
+            400 | 410 => Err(PushError::InvalidToken),
+            status => Err(PushError::Failed(format!("APNs returned {}", status))),
+        }
+    }
+
+    async fn send_webpush(&self, subscription: &str) -> Result<(), PushError> {
+        let wp = self
+            .webpush
+            .as_ref()
+            .ok_or(PushError::NotConfigured(Platform::Webpush))?;
+
+        #[derive(Deserialize)]
+        struct PushSubscription {
+            endpoint: String,
+        }
+        let sub: PushSubscription =
+            serde_json::from_str(subscription).map_err(|_| PushError::InvalidToken)?;
+        let url = reqwest::Url::parse(&sub.endpoint).map_err(|_| PushError::InvalidToken)?;
+        let audience = url.origin().ascii_serialization();
+
+        let claims = JwtClaims {
+            iss: None,
+            sub: Some(&wp.config.subject),
+            aud: Some(&audience),
+            scope: None,
+            iat: None,
+            exp: Some(unix_now() + 12 * 3600),
+        };
+        let jwt = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::ES256),
+            &claims,
+            &wp.key,
+        )
+        .map_err(|e| PushError::Failed(format!("VAPID JWT signing failed: {}", e)))?;
+
+        let resp = self
+            .client
+            .post(url)
+            .header(
+                "authorization",
+                format!("vapid t={}, k={}", jwt, wp.config.vapid_public_key),
+            )
+            .header("ttl", "86400")
+            .header("urgency", "high")
+            .header("content-length", "0")
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(format!("Web Push request failed: {}", e)))?;
+        match resp.status().as_u16() {
+            200..=299 => Ok(()),
+            404 | 410 => Err(PushError::InvalidToken),
+            status => Err(PushError::Failed(format!("Web Push returned {}", status))),
+        }
+    }
+}
+
+#[async_trait]
+impl PushSender for ProviderSender {
+    async fn send(&self, device: &Device, notification: &Notification) -> Result<(), PushError> {
+        match device.platform {
+            Platform::Fcm => self.send_fcm(&device.token, notification).await,
+            Platform::Apns => self.send_apns(&device.token, notification).await,
+            Platform::Webpush => self.send_webpush(&device.token).await,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Gateway
+// ---------------------------------------------------------------------------
+
+/// Evaluates push rules against routed writes and delivers notifications to
+/// subjects without an active session.
+pub struct PushGateway {
+    config: PushConfig,
+    store: Arc<DeviceStore>,
+    sender: Arc<dyn PushSender>,
+    send_limit: Arc<Semaphore>,
+}
+
+impl PushGateway {
+    pub fn new(config: PushConfig, store: Arc<DeviceStore>, sender: Arc<dyn PushSender>) -> Self {
+        Self {
+            config,
+            store,
+            sender,
+            send_limit: Arc::new(Semaphore::new(MAX_CONCURRENT_SENDS)),
+        }
+    }
+
+    /// Handle a routed write. Returns the number of notifications delivered.
+    ///
+    /// Devices are sent to concurrently, each request bounded by
+    /// `send_timeout_ms`, so one slow provider doesn't hold up the others.
+    pub async fn handle_write(
+        &self,
+        address: &str,
+        value: &Value,
+        sessions: &DashMap<SessionId, Arc<Session>>,
+    ) -> usize {
+        let Some((target, notification)) = self.config.evaluate(address, value) else {
+            return 0;
+        };
+
+        let online = sessions
+            .iter()
+            .any(|s| s.value().subject.as_deref() == Some(target.as_str()));
+        if online {
+            return 0;
+        }
+
+        let devices = match self.store.devices(&target) {
+            Ok(d) => d,
+            Err(e) => {
+                tracing::warn!("Push: device lookup for {} failed: {}", target, e);
+                return 0;
+            }
+        };
+
+        let timeout = match self.config.send_timeout_ms {
+            0 => Duration::from_millis(SEND_TIMEOUT_MS),
+            ms => Duration::from_millis(ms),
+        };
+        let notification = Arc::new(notification);
+        let mut sends = tokio::task::JoinSet::new();
+        for device in devices {
+            let sender = Arc::clone(&self.sender);
+            let limit = Arc::clone(&self.send_limit);
+            let notification = Arc::clone(&notification);
+            sends.spawn(async move {
+                let _permit = limit.acquire_owned().await;
+                let send = sender.send(&device, &notification);
+                let result = match tokio::time::timeout(timeout, send).await {
+                    Ok(result) => result,
+                    Err(_) => Err(PushError::Failed(format!("timed out after {:?}", timeout))),
+                };
+                (device, result)
+            });
+        }
+
+        let mut delivered = 0;
+        while let Some(joined) = sends.join_next().await {
+            let Ok((device, result)) = joined else {
+                continue;
+            };
+            match result {
+                Ok(()) => delivered += 1,
+                Err(PushError::InvalidToken) => {
+                    tracing::info!(
+                        "Push: removing invalid {} token for {}",
+                        device.platform.as_str(),
+                        target
+                    );
+                    let _ = self.store.unregister(&target, &device.token);
+                }
+                Err(e) => {
+                    tracing::warn!("Push: delivery to {} failed: {}", target, e);
+                }
+            }
+        }
+        if delivered > 0 {
+            tracing::debug!("Push: {} -> {} device(s) for {}", address, delivered, target);
+        }
+        delivered
+    }
+}
+
+/// Forwards encoded messages from the router to the gateway task.
+struct ObserverSender {
+    tx: mpsc::Sender<Bytes>,
+}
+
+#[async_trait]
+impl clasp_transport::TransportSender for ObserverSender {
+    async fn send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.try_send(data)
+    }
+
+    fn try_send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.tx
+            .try_send(data)
+            .map_err(|_| clasp_transport::TransportError::BufferFull)
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+}
+
+/// Register an internal session subscribed to every rule pattern, then feed
+/// the writes it receives into the gateway. Runs until the router drops the
+/// session's sender.
+pub async fn run_push_gateway(
+    gateway: Arc<PushGateway>,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+) {
+    let (tx, mut rx) = mpsc::channel(OBSERVER_BUFFER);
+    let session = Arc::new(Session::new(
+        Arc::new(ObserverSender { tx }),
+        "push-gateway".to_string(),
+        vec![],
+    ));
+    let session_id = session.id.clone();
+    sessions.insert(session_id.clone(), Arc::clone(&session));
+
+    for (i, pattern) in gateway.config.subscription_patterns().iter().enumerate() {
+        match Subscription::new(
+            i as u32 + 1,
+            session_id.clone(),
+            pattern,
+            vec![],
+            Default::default(),
+        ) {
+            Ok(sub) => {
+                subscriptions.add(sub);
+                session.add_subscription(i as u32 + 1);
+            }
+            Err(e) => tracing::warn!("Push: invalid rule pattern {}: {}", pattern, e),
+        }
+    }
+
+    let pending = Arc::new(Semaphore::new(MAX_PENDING_WRITES));
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    loop {
+        tokio::select! {
+            data = rx.recv() => {
+                let Some(data) = data else { break };
+                let (address, value) = match codec::decode(&data) {
+                    Ok((Message::Set(msg), _)) => (msg.address, msg.value),
+                    Ok((Message::Publish(msg), _)) => {
+                        let value = msg.value.or(msg.payload).unwrap_or(Value::Null);
+                        (msg.address, value)
+                    }
+                    _ => continue,
+                };
+                let Ok(permit) = Arc::clone(&pending).try_acquire_owned() else {
+                    tracing::warn!("Push: too many pending deliveries, dropped {}", address);
+                    continue;
+                };
+                let gateway = Arc::clone(&gateway);
+                let sessions = Arc::clone(&sessions);
+                tokio::spawn(async move {
+                    gateway.handle_write(&address, &value, &sessions).await;
+                    drop(permit);
+                });
+            }
+            _ = keepalive.tick() => {
+                // The router evicts idle sessions; this one only ever receives.
+                session.touch();
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Device registration API
+// ---------------------------------------------------------------------------
+
+pub struct PushApiState {
+    pub store: Arc<DeviceStore>,
+    pub validator: Arc<CpskValidator>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn api_error(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(ErrorResponse { error: msg.into() }))
+}
+
+/// Resolve the caller's subject from a Bearer CPSK token.
+fn caller_subject(state: &PushApiState, headers: &axum::http::HeaderMap) -> Result<String, ApiError> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "expected Bearer token"))?;
+    match state.validator.validate(token) {
+        ValidationResult::Valid(info) => info
+            .subject
+            .ok_or_else(|| api_error(StatusCode::FORBIDDEN, "token has no subject")),
+        ValidationResult::Expired => Err(api_error(StatusCode::UNAUTHORIZED, "token expired")),
+        _ => Err(api_error(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+#[derive(Deserialize)]
+struct RegisterDeviceRequest {
+    platform: Platform,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct UnregisterDeviceRequest {
+    token: String,
+}
+
+#[derive(Serialize)]
+struct DevicesResponse {
+    devices: Vec<Device>,
+}
+
+/// Upper bound on a stored token; Web Push subscriptions are the longest.
+const MAX_TOKEN_LEN: usize = 4096;
+
+async fn register_device(
+    State(state): State<Arc<PushApiState>>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<RegisterDeviceRequest>,
+) -> Result<StatusCode, ApiError> {
+    let subject = caller_subject(&state, &headers)?;
+    if req.token.trim().is_empty() || req.token.len() > MAX_TOKEN_LEN {
+        return Err(api_error(StatusCode::BAD_REQUEST, "invalid device token"));
+    }
+    let device = Device {
+        platform: req.platform,
+        token: req.token,
+    };
+    state
+        .store
+        .register(&subject, &device)
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "database error"))?;
+    tracing::info!("Push: registered {} device for {}", device.platform.as_str(), subject);
+    Ok(StatusCode::CREATED)
+}
+
+async fn list_devices(
+    State(state): State<Arc<PushApiState>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<DevicesResponse>, ApiError> {
+    let subject = caller_subject(&state, &headers)?;
+    let devices = state
+        .store
+        .devices(&subject)
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "database error"))?;
+    Ok(Json(DevicesResponse { devices }))
+}
+
+async fn unregister_device(
+    State(state): State<Arc<PushApiState>>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<UnregisterDeviceRequest>,
+) -> Result<StatusCode, ApiError> {
+    let subject = caller_subject(&state, &headers)?;
+    match state.store.unregister(&subject, &req.token) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(api_error(StatusCode::NOT_FOUND, "device not registered")),
+        Err(_) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "database error")),
+    }
+}
+
+/// Build the device registration router.
+pub fn push_router(state: Arc<PushApiState>) -> Router {
+    Router::new()
+        .route(
+            "/api/push/devices",
+            get(list_devices)
+                .post(register_device)
+                .delete(unregister_device),
+        )
+        .with_state(state)
+}
+
+/// Load a push config from a JSON file.
+pub fn load_config(path: &Path) -> anyhow::Result<PushConfig> {
+    use anyhow::Context;
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read push config {}", path.display()))?;
+    let config: PushConfig = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse push config {}", path.display()))?;
+    config.validate()?;
+    Ok(config)
+}
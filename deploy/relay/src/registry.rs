@@ -2,6 +2,20 @@
 //!
 //! Provides CRUD endpoints for entity management, protected by admin CPSK scope.
 //! Follows the same Axum + shared state pattern as `auth.rs`.
+//!
+//! Fleet lifecycle: `suspend` and `activate` toggle whether an entity's tokens
+//! are accepted; `revoke` is permanent, and a revoked entity can only be
+//! deleted and registered again.
+//!
+//! Configuration distribution: `PUT /api/entities/{id}/config` stores a new
+//! version of the entity's configuration document and pushes it to the
+//! entity's connected sessions at `/clasp/config/{id}`; the router delivers
+//! the current version whenever the entity connects. `GET` shows the version
+//! the entity last acknowledged.
+//!
+//! Fleets: `PUT /api/fleets/{name}/members/{id}` adds an entity to a named
+//! fleet. The router then delivers every PUBLISH to `/clasp/fleet/{name}/...`
+//! to all of the fleet's connected members.
 
 use axum::{
     extract::{Path, State},
@@ -9,10 +23,21 @@ use axum::{
     routing::{get, post, put},
     Json, Router,
 };
-use clasp_core::security::{Action, CpskValidator, TokenValidator, ValidationResult};
-use clasp_registry::{Entity, EntityId, EntityStatus, EntityStore};
+use clasp_core::security::{
+    Action, AsyncTokenValidator, CachingValidator, CpskValidator, TokenValidator, ValidationResult,
+};
+use clasp_registry::replication::RegistryEventKind;
+use clasp_registry::{
+    ConfigDocument, ConfigStore, Entity, EntityId, EntityStatus, EntityStore, EntityValidator,
+    FleetStore, RegistryError,
+};
+use clasp_router::{
+    ConfigSource, EntityConfig, FleetSource, RouterState, Session, SessionId, SubscriptionManager,
+};
+use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
 pub struct RegistryState {
     store: Arc<dyn EntityStore>,
@@ -21,6 +46,27 @@ pub struct RegistryState {
     trust_anchors: Vec<String>,
     /// Max delegation chain depth for capability tokens
     cap_max_depth: usize,
+    /// Entity token results cached by the router's validator chain
+    validation_cache: Option<Arc<CachingValidator<EntityValidator>>>,
+    /// Configuration documents and the router handles used to push them
+    configs: Option<ConfigApi>,
+    /// Fleet membership
+    fleets: Option<Arc<dyn FleetStore>>,
+    /// Replication of entity changes to federation peers
+    #[cfg(feature = "federation")]
+    sync: Option<Arc<crate::registry_sync::RegistrySync>>,
+}
+
+struct ConfigApi {
+    store: Arc<dyn ConfigStore>,
+    router: RouterHandles,
+}
+
+/// Router state needed to push configuration to connected entities
+pub struct RouterHandles {
+    pub sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    pub subscriptions: Arc<SubscriptionManager>,
+    pub state: Arc<RouterState>,
 }
 
 impl RegistryState {
@@ -30,6 +76,11 @@ impl RegistryState {
             validator,
             trust_anchors: Vec::new(),
             cap_max_depth: 5,
+            validation_cache: None,
+            configs: None,
+            fleets: None,
+            #[cfg(feature = "federation")]
+            sync: None,
         }
     }
 
@@ -39,6 +90,151 @@ impl RegistryState {
         self.cap_max_depth = max_depth;
         self
     }
+
+    /// Invalidate cached entity token results when entities change
+    pub fn with_validation_cache(mut self, cache: Arc<CachingValidator<EntityValidator>>) -> Self {
+        self.validation_cache = Some(cache);
+        self
+    }
+
+    /// Serve and distribute per-entity configuration documents
+    pub fn with_configs(mut self, configs: Arc<dyn ConfigStore>, router: RouterHandles) -> Self {
+        self.configs = Some(ConfigApi {
+            store: configs,
+            router,
+        });
+        self
+    }
+
+    /// Serve fleet membership
+    pub fn with_fleets(mut self, fleets: Arc<dyn FleetStore>) -> Self {
+        self.fleets = Some(fleets);
+        self
+    }
+
+    /// Replicate entity changes to federation peers
+    #[cfg(feature = "federation")]
+    pub fn with_sync(mut self, sync: Arc<crate::registry_sync::RegistrySync>) -> Self {
+        self.sync = Some(sync);
+        self
+    }
+
+    /// Refuse entity changes on a read-only mirror of the hub's registry
+    fn check_writable(&self) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+        #[cfg(feature = "federation")]
+        if let Some(ref sync) = self.sync {
+            sync.check_writable().map_err(|e| {
+                (
+                    StatusCode::FORBIDDEN,
+                    Json(ErrorResponse {
+                        error: e.to_string(),
+                    }),
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Send an entity change to federation peers
+    #[allow(unused_variables)]
+    async fn replicate(&self, kind: RegistryEventKind, entity: &Entity) {
+        #[cfg(feature = "federation")]
+        if let Some(ref sync) = self.sync {
+            sync.publish(kind, entity).await;
+        }
+    }
+
+    fn invalidate(&self, entity_id: &EntityId) {
+        if let Some(ref cache) = self.validation_cache {
+            let dropped = cache.invalidate_subject(entity_id.as_str());
+            tracing::debug!("Dropped {} cached validations for {}", dropped, entity_id);
+        }
+    }
+}
+
+/// Wrapper to share a cached [`EntityValidator`] between the router's
+/// validator chain and the registry API, which invalidates it.
+pub struct SharedEntityValidator(pub Arc<CachingValidator<EntityValidator>>);
+
+impl TokenValidator for SharedEntityValidator {
+    fn validate(&self, token: &str) -> ValidationResult {
+        TokenValidator::validate(self.0.as_ref(), token)
+    }
+    fn name(&self) -> &str {
+        TokenValidator::name(self.0.as_ref())
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncTokenValidator for SharedEntityValidator {
+    async fn validate(&self, token: &str) -> ValidationResult {
+        AsyncTokenValidator::validate(self.0.as_ref(), token).await
+    }
+    fn name(&self) -> &str {
+        TokenValidator::name(self.0.as_ref())
+    }
+}
+
+/// Serves configuration documents from the registry to the router
+pub struct RegistryConfigSource(pub Arc<dyn ConfigStore>);
+
+#[async_trait::async_trait]
+impl ConfigSource for RegistryConfigSource {
+    async fn config(&self, entity_id: &str) -> Option<EntityConfig> {
+        let id = EntityId::parse(entity_id).ok()?;
+        match self.0.get_config(&id).await {
+            Ok(doc) => doc.and_then(|doc| entity_config(&doc)),
+            Err(e) => {
+                tracing::warn!("Failed to load config for {}: {}", entity_id, e);
+                None
+            }
+        }
+    }
+
+    async fn acknowledge(&self, entity_id: &str, version: u64) -> bool {
+        let Ok(id) = EntityId::parse(entity_id) else {
+            return false;
+        };
+        match self.0.ack_config(&id, version).await {
+            Ok(acked) => acked,
+            Err(e) => {
+                tracing::warn!("Failed to record config ack for {}: {}", entity_id, e);
+                false
+            }
+        }
+    }
+}
+
+/// Resolves fleet members from the registry for the router's fan-out
+pub struct RegistryFleetSource(pub Arc<dyn FleetStore>);
+
+#[async_trait::async_trait]
+impl FleetSource for RegistryFleetSource {
+    async fn members(&self, fleet: &str) -> Vec<String> {
+        match self.0.fleet_members(fleet).await {
+            Ok(members) => members.iter().map(|id| id.as_str().to_string()).collect(),
+            Err(e) => {
+                tracing::warn!("Failed to load members of fleet {}: {}", fleet, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn entity_config(doc: &ConfigDocument) -> Option<EntityConfig> {
+    match serde_json::from_value(doc.config.clone()) {
+        Ok(config) => Some(EntityConfig {
+            version: doc.version,
+            config,
+        }),
+        Err(e) => {
+            tracing::warn!("Config for {} is not a CLASP value: {}", doc.entity_id, e);
+            None
+        }
+    }
 }
 
 /// Extractor that validates a Bearer token with admin scope.
@@ -138,6 +334,48 @@ impl From<Entity> for EntityResponse {
     }
 }
 
+#[derive(Serialize)]
+struct ConfigResponse {
+    entity_id: String,
+    version: u64,
+    config: serde_json::Value,
+    /// Unix seconds
+    updated_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acked_version: Option<u64>,
+    /// Whether the entity acknowledged the current version
+    acked: bool,
+}
+
+impl From<ConfigDocument> for ConfigResponse {
+    fn from(doc: ConfigDocument) -> Self {
+        Self {
+            acked: doc.is_acked(),
+            entity_id: doc.entity_id.as_str().to_string(),
+            version: doc.version,
+            config: doc.config,
+            updated_at: doc
+                .updated_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            acked_version: doc.acked_version,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FleetSummary {
+    name: String,
+    members: usize,
+}
+
+#[derive(Serialize)]
+struct FleetResponse {
+    name: String,
+    members: Vec<String>,
+}
+
 #[derive(Deserialize)]
 struct CreateEntityRequest {
     entity_type: clasp_registry::EntityType,
@@ -164,6 +402,18 @@ struct ListQuery {
     offset: Option<usize>,
     #[serde(default = "default_limit")]
     limit: Option<usize>,
+    /// Only entities with this tag
+    #[serde(default)]
+    tag: Option<String>,
+    /// Only entities with this status
+    #[serde(default)]
+    status: Option<EntityStatus>,
+    /// Only entities of this type
+    #[serde(default)]
+    entity_type: Option<clasp_registry::EntityType>,
+    /// Only entities with this metadata entry, as `key=value`
+    #[serde(default)]
+    attr: Option<String>,
 }
 
 fn default_limit() -> Option<usize> {
@@ -175,6 +425,7 @@ async fn create_entity(
     _admin: AdminToken,
     Json(req): Json<CreateEntityRequest>,
 ) -> Result<(StatusCode, Json<EntityResponse>), (StatusCode, Json<ErrorResponse>)> {
+    state.check_writable()?;
     let public_key = hex::decode(&req.public_key).map_err(|_| {
         (
             StatusCode::BAD_REQUEST,
@@ -215,6 +466,9 @@ async fn create_entity(
         )
     })?;
 
+    // A token presented before registration may be cached as "not found"
+    state.invalidate(&entity.id);
+    state.replicate(RegistryEventKind::Created, &entity).await;
     tracing::info!("Entity created: {} ({})", entity.name, entity.id);
     Ok((StatusCode::CREATED, Json(EntityResponse::from(entity))))
 }
@@ -226,17 +480,54 @@ async fn list_entities(
 ) -> Result<Json<Vec<EntityResponse>>, (StatusCode, Json<ErrorResponse>)> {
     let offset = query.offset.unwrap_or(0);
     let limit = query.limit.unwrap_or(100);
-
-    let entities = state.store.list(offset, limit).await.map_err(|e| {
+    let list_error = |e: clasp_registry::RegistryError| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
                 error: format!("failed to list entities: {}", e),
             }),
         )
-    })?;
+    };
+
+    if query.tag.is_none()
+        && query.status.is_none()
+        && query.entity_type.is_none()
+        && query.attr.is_none()
+    {
+        let entities = state.store.list(offset, limit).await.map_err(list_error)?;
+        return Ok(Json(
+            entities.into_iter().map(EntityResponse::from).collect(),
+        ));
+    }
+
+    let mut filter = clasp_registry::EntityFilter {
+        entity_type: query.entity_type,
+        status: query.status,
+        tag: query.tag,
+        ..Default::default()
+    };
+    if let Some(ref attr) = query.attr {
+        let (key, value) = attr.split_once('=').ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "attr must be key=value".into(),
+                }),
+            )
+        })?;
+        filter = filter.attribute(key, value);
+    }
 
-    Ok(Json(entities.into_iter().map(EntityResponse::from).collect()))
+    // Filtered listings paginate over the matches, not the whole store
+    let matches = state.store.query(&filter).await.map_err(list_error)?;
+    Ok(Json(
+        matches
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(EntityResponse::from)
+            .collect(),
+    ))
 }
 
 async fn get_entity(
@@ -291,6 +582,15 @@ async fn delete_entity(
         )
     })?;
 
+    state.check_writable()?;
+    let entity = state.store.get(&entity_id).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("failed to delete entity: {}", e),
+            }),
+        )
+    })?;
     let deleted = state.store.delete(&entity_id).await.map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -301,6 +601,10 @@ async fn delete_entity(
     })?;
 
     if deleted {
+        state.invalidate(&entity_id);
+        if let Some(entity) = entity {
+            state.replicate(RegistryEventKind::Deleted, &entity).await;
+        }
         tracing::info!("Entity deleted: {}", id);
         Ok(StatusCode::NO_CONTENT)
     } else {
@@ -319,7 +623,48 @@ async fn update_entity_status(
     Path(id): Path<String>,
     Json(req): Json<UpdateStatusRequest>,
 ) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
-    let entity_id = EntityId::parse(&id).map_err(|e| {
+    set_status(&state, &id, req.status).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn suspend_entity(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(id): Path<String>,
+) -> Result<Json<EntityResponse>, (StatusCode, Json<ErrorResponse>)> {
+    set_status(&state, &id, EntityStatus::Suspended)
+        .await
+        .map(Json)
+}
+
+async fn activate_entity(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(id): Path<String>,
+) -> Result<Json<EntityResponse>, (StatusCode, Json<ErrorResponse>)> {
+    set_status(&state, &id, EntityStatus::Active)
+        .await
+        .map(Json)
+}
+
+async fn revoke_entity(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(id): Path<String>,
+) -> Result<Json<EntityResponse>, (StatusCode, Json<ErrorResponse>)> {
+    set_status(&state, &id, EntityStatus::Revoked)
+        .await
+        .map(Json)
+}
+
+/// Move an entity to `status`, dropping its cached token validations.
+/// Revocation is final: a revoked entity cannot change status again.
+async fn set_status(
+    state: &RegistryState,
+    id: &str,
+    status: EntityStatus,
+) -> Result<EntityResponse, (StatusCode, Json<ErrorResponse>)> {
+    let entity_id = EntityId::parse(id).map_err(|e| {
         (
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
@@ -328,21 +673,54 @@ async fn update_entity_status(
         )
     })?;
 
-    state
+    state.check_writable()?;
+    let store_error = |e: clasp_registry::RegistryError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("failed to update status: {}", e),
+            }),
+        )
+    };
+    let mut entity = state
         .store
-        .update_status(&entity_id, req.status)
+        .get(&entity_id)
         .await
-        .map_err(|e| {
+        .map_err(store_error)?
+        .ok_or_else(|| {
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
-                    error: format!("failed to update status: {}", e),
+                    error: "entity not found".into(),
                 }),
             )
         })?;
 
-    tracing::info!("Entity {} status -> {}", id, req.status);
-    Ok(StatusCode::OK)
+    if entity.status == EntityStatus::Revoked && status != EntityStatus::Revoked {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "entity is revoked; delete and re-register it instead".into(),
+            }),
+        ));
+    }
+
+    state
+        .store
+        .update_status(&entity_id, status)
+        .await
+        .map_err(store_error)?;
+
+    state.invalidate(&entity_id);
+    tracing::info!("Entity {} status -> {}", id, status);
+    entity.status = status;
+    let kind = if status == EntityStatus::Revoked {
+        RegistryEventKind::Revoked
+    } else {
+        RegistryEventKind::Updated
+    };
+    state.replicate(kind, &entity).await;
+    Ok(EntityResponse::from(entity))
 }
 
 // =========================================================================
@@ -511,11 +889,266 @@ pub fn registry_router(state: Arc<RegistryState>) -> Router {
             get(get_entity).delete(delete_entity),
         )
         .route("/api/entities/{id}/status", put(update_entity_status))
+        .route("/api/entities/{id}/suspend", post(suspend_entity))
+        .route("/api/entities/{id}/activate", post(activate_entity))
+        .route("/api/entities/{id}/revoke", post(revoke_entity))
         .route("/api/entities/{id}/token", post(mint_entity_token))
+        .route(
+            "/api/entities/{id}/config",
+            get(get_entity_config)
+                .put(put_entity_config)
+                .delete(delete_entity_config),
+        )
+        .route("/api/fleets", get(list_fleets))
+        .route("/api/fleets/{name}", get(get_fleet))
+        .route(
+            "/api/fleets/{name}/members/{id}",
+            put(add_fleet_member).delete(remove_fleet_member),
+        )
         .route("/api/trust-anchors", get(get_trust_anchors))
         .with_state(state)
 }
 
+fn config_api(state: &RegistryState) -> Result<&ConfigApi, (StatusCode, Json<ErrorResponse>)> {
+    state.configs.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "configuration distribution not enabled".into(),
+            }),
+        )
+    })
+}
+
+fn parse_entity_id(id: &str) -> Result<EntityId, (StatusCode, Json<ErrorResponse>)> {
+    EntityId::parse(id).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("invalid entity ID: {}", e),
+            }),
+        )
+    })
+}
+
+fn store_error(e: clasp_registry::RegistryError) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: format!("store error: {}", e),
+        }),
+    )
+}
+
+async fn get_entity_config(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(id): Path<String>,
+) -> Result<Json<ConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let configs = config_api(&state)?;
+    let entity_id = parse_entity_id(&id)?;
+    let doc = configs
+        .store
+        .get_config(&entity_id)
+        .await
+        .map_err(store_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "entity has no configuration".into(),
+                }),
+            )
+        })?;
+    Ok(Json(ConfigResponse::from(doc)))
+}
+
+/// Store a new configuration version and push it to the entity's sessions.
+/// The body is the configuration document itself.
+async fn put_entity_config(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(id): Path<String>,
+    Json(config): Json<serde_json::Value>,
+) -> Result<Json<ConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let configs = config_api(&state)?;
+    let entity_id = parse_entity_id(&id)?;
+    if state
+        .store
+        .get(&entity_id)
+        .await
+        .map_err(store_error)?
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "entity not found".into(),
+            }),
+        ));
+    }
+    if serde_json::from_value::<clasp_core::Value>(config.clone()).is_err() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "configuration is not a valid CLASP value".into(),
+            }),
+        ));
+    }
+
+    let doc = configs
+        .store
+        .put_config(&entity_id, config)
+        .await
+        .map_err(store_error)?;
+    if let Some(entity_config) = entity_config(&doc) {
+        clasp_router::entity_config::publish(
+            entity_id.as_str(),
+            &entity_config,
+            &configs.router.state,
+            &configs.router.sessions,
+            &configs.router.subscriptions,
+        );
+    }
+    tracing::info!("Config v{} stored for {}", doc.version, id);
+    Ok(Json(ConfigResponse::from(doc)))
+}
+
+async fn delete_entity_config(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let configs = config_api(&state)?;
+    let entity_id = parse_entity_id(&id)?;
+    if configs
+        .store
+        .delete_config(&entity_id)
+        .await
+        .map_err(store_error)?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "entity has no configuration".into(),
+            }),
+        ))
+    }
+}
+
+fn fleet_store(
+    state: &RegistryState,
+) -> Result<&Arc<dyn FleetStore>, (StatusCode, Json<ErrorResponse>)> {
+    state.fleets.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "fleets not enabled".into(),
+            }),
+        )
+    })
+}
+
+fn fleet_error(e: RegistryError) -> (StatusCode, Json<ErrorResponse>) {
+    match e {
+        RegistryError::InvalidFleet(_) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        ),
+        e => store_error(e),
+    }
+}
+
+async fn list_fleets(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+) -> Result<Json<Vec<FleetSummary>>, (StatusCode, Json<ErrorResponse>)> {
+    let fleets = fleet_store(&state)?
+        .list_fleets()
+        .await
+        .map_err(store_error)?;
+    Ok(Json(
+        fleets
+            .into_iter()
+            .map(|(name, members)| FleetSummary { name, members })
+            .collect(),
+    ))
+}
+
+async fn get_fleet(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path(name): Path<String>,
+) -> Result<Json<FleetResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let members = fleet_store(&state)?
+        .fleet_members(&name)
+        .await
+        .map_err(store_error)?;
+    Ok(Json(FleetResponse {
+        name,
+        members: members.iter().map(|id| id.as_str().to_string()).collect(),
+    }))
+}
+
+async fn add_fleet_member(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let fleets = fleet_store(&state)?;
+    let entity_id = parse_entity_id(&id)?;
+    if state
+        .store
+        .get(&entity_id)
+        .await
+        .map_err(store_error)?
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "entity not found".into(),
+            }),
+        ));
+    }
+    if fleets
+        .add_to_fleet(&name, &entity_id)
+        .await
+        .map_err(fleet_error)?
+    {
+        tracing::info!("Entity {} added to fleet {}", id, name);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_fleet_member(
+    State(state): State<Arc<RegistryState>>,
+    _admin: AdminToken,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let fleets = fleet_store(&state)?;
+    let entity_id = parse_entity_id(&id)?;
+    if fleets
+        .remove_from_fleet(&name, &entity_id)
+        .await
+        .map_err(store_error)?
+    {
+        tracing::info!("Entity {} removed from fleet {}", id, name);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "entity is not in the fleet".into(),
+            }),
+        ))
+    }
+}
+
 /// Hex decode/encode helper for public keys in JSON
 mod hex {
     pub fn decode(s: &str) -> Result<Vec<u8>, String> {
@@ -729,6 +1362,56 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::FORBIDDEN);
     }
 
+    #[tokio::test]
+    async fn test_status_change_invalidates_validation_cache() {
+        let store: Arc<dyn EntityStore> = Arc::new(MemoryEntityStore::new());
+        let admin = Arc::new(CpskValidator::new());
+        let admin_token = CpskValidator::generate_token();
+        admin.register(
+            admin_token.clone(),
+            TokenInfo::new(
+                admin_token.clone(),
+                vec![Scope::new(Action::Admin, "/**").unwrap()],
+            ),
+        );
+        let cache = Arc::new(CachingValidator::new(
+            EntityValidator::new(Arc::clone(&store)),
+            16,
+            std::time::Duration::from_secs(60),
+        ));
+        let validator = SharedEntityValidator(Arc::clone(&cache));
+
+        let keypair = clasp_registry::EntityKeypair::generate().unwrap();
+        let entity = keypair.to_entity(clasp_registry::EntityType::Device, "cached".to_string());
+        store.create(&entity).await.unwrap();
+        let token = clasp_registry::generate_token(&keypair).unwrap();
+        assert!(matches!(
+            AsyncTokenValidator::validate(&validator, &token).await,
+            ValidationResult::Valid(_)
+        ));
+        assert_eq!(cache.len(), 1);
+
+        let app = make_app(Arc::new(
+            RegistryState::new(store, admin).with_validation_cache(Arc::clone(&cache)),
+        ));
+        let req = axum::http::Request::builder()
+            .method("PUT")
+            .uri(format!("/api/entities/{}/status", entity.id))
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", admin_token))
+            .body(Body::from(r#"{"status":"suspended"}"#))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // The suspension takes effect immediately, not after the cache TTL
+        assert!(cache.is_empty());
+        assert!(matches!(
+            AsyncTokenValidator::validate(&validator, &token).await,
+            ValidationResult::Invalid(_)
+        ));
+    }
+
     #[tokio::test]
     async fn test_create_and_get_entity() {
         let (state, admin_token) = make_test_state();
@@ -756,4 +1439,264 @@ mod tests {
         let resp = app.oneshot(req).await.unwrap();
         assert_eq!(resp.status(), StatusCode::CREATED);
     }
+
+    #[tokio::test]
+    async fn test_lifecycle_actions_and_filtered_list() {
+        let (state, admin_token) = make_test_state();
+        let mut ids = Vec::new();
+        for (name, tag) in [("cam-1", "camera"), ("cam-2", "camera"), ("light", "dmx")] {
+            let keypair = clasp_registry::EntityKeypair::generate().unwrap();
+            let mut entity =
+                keypair.to_entity(clasp_registry::EntityType::Device, name.to_string());
+            entity.tags = vec![tag.to_string()];
+            entity
+                .metadata
+                .insert("location".to_string(), format!("stage-{}", name));
+            state.store.create(&entity).await.unwrap();
+            ids.push(entity.id.to_string());
+        }
+        let app = make_app(state);
+        let request = |method: &str, uri: String| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", admin_token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let resp = app
+            .clone()
+            .oneshot(request("POST", format!("/api/entities/{}/suspend", ids[0])))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "suspended");
+
+        let resp = app
+            .clone()
+            .oneshot(request(
+                "GET",
+                "/api/entities?tag=camera&status=active".to_string(),
+            ))
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let names: Vec<_> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["name"].clone())
+            .collect();
+        assert_eq!(names, vec![serde_json::json!("cam-2")]);
+
+        let resp = app
+            .clone()
+            .oneshot(request(
+                "GET",
+                "/api/entities?attr=location=stage-light".to_string(),
+            ))
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["metadata"]["location"], "stage-light");
+
+        // Revocation is final
+        for (action, expected) in [
+            ("revoke", StatusCode::OK),
+            ("activate", StatusCode::CONFLICT),
+            ("suspend", StatusCode::CONFLICT),
+        ] {
+            let resp = app
+                .clone()
+                .oneshot(request(
+                    "POST",
+                    format!("/api/entities/{}/{}", ids[2], action),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), expected, "{}", action);
+        }
+
+        let unknown = clasp_registry::EntityKeypair::generate().unwrap();
+        let unknown = unknown.to_entity(clasp_registry::EntityType::Device, "x".to_string());
+        let resp = app
+            .oneshot(request(
+                "POST",
+                format!("/api/entities/{}/suspend", unknown.id),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_entity_config_put_get_and_ack() {
+        let store: Arc<dyn EntityStore> = Arc::new(MemoryEntityStore::new());
+        let admin = Arc::new(CpskValidator::new());
+        let admin_token = CpskValidator::generate_token();
+        admin.register(
+            admin_token.clone(),
+            TokenInfo::new(
+                admin_token.clone(),
+                vec![Scope::new(Action::Admin, "/**").unwrap()],
+            ),
+        );
+        let keypair = clasp_registry::EntityKeypair::generate().unwrap();
+        let entity = keypair.to_entity(clasp_registry::EntityType::Device, "cam".to_string());
+        store.create(&entity).await.unwrap();
+
+        let configs: Arc<dyn ConfigStore> = Arc::new(clasp_registry::MemoryConfigStore::new());
+        let router = clasp_router::Router::new(clasp_router::RouterConfig::default());
+        let (sessions, subscriptions, router_state) = router.shared_state();
+        let app = make_app(Arc::new(RegistryState::new(store, admin).with_configs(
+            Arc::clone(&configs),
+            RouterHandles {
+                sessions,
+                subscriptions,
+                state: Arc::clone(&router_state),
+            },
+        )));
+        let request = |method: &str, body: Body| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri(format!("/api/entities/{}/config", entity.id))
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", admin_token))
+                .body(body)
+                .unwrap()
+        };
+
+        let resp = app
+            .clone()
+            .oneshot(request("GET", Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = app
+            .clone()
+            .oneshot(request("PUT", Body::from(r#"{"fps":30}"#)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["acked"], false);
+
+        // Pushed into router state for the entity and config subscribers
+        let entry = router_state
+            .get(&clasp_router::entity_config::address(entity.id.as_str()))
+            .unwrap();
+        match entry {
+            clasp_core::Value::Map(map) => assert_eq!(map["version"], clasp_core::Value::Int(1)),
+            other => panic!("expected map, got {:?}", other),
+        }
+
+        let source = RegistryConfigSource(configs);
+        assert!(!source.acknowledge(entity.id.as_str(), 2).await);
+        assert!(source.acknowledge(entity.id.as_str(), 1).await);
+        let resp = app
+            .clone()
+            .oneshot(request("GET", Body::empty()))
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["acked_version"], 1);
+        assert_eq!(json["acked"], true);
+        assert_eq!(json["config"]["fps"], 30);
+
+        let resp = app
+            .oneshot(request("DELETE", Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_fleet_membership_api() {
+        let store: Arc<dyn EntityStore> = Arc::new(MemoryEntityStore::new());
+        let admin = Arc::new(CpskValidator::new());
+        let admin_token = CpskValidator::generate_token();
+        admin.register(
+            admin_token.clone(),
+            TokenInfo::new(
+                admin_token.clone(),
+                vec![Scope::new(Action::Admin, "/**").unwrap()],
+            ),
+        );
+        let keypair = clasp_registry::EntityKeypair::generate().unwrap();
+        let entity = keypair.to_entity(clasp_registry::EntityType::Device, "kiosk".to_string());
+        store.create(&entity).await.unwrap();
+        let unknown = clasp_registry::EntityKeypair::generate().unwrap().entity_id;
+
+        let fleets: Arc<dyn FleetStore> = Arc::new(clasp_registry::MemoryFleetStore::new());
+        let app = make_app(Arc::new(
+            RegistryState::new(store, admin).with_fleets(Arc::clone(&fleets)),
+        ));
+        let request = |method: &str, uri: String| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", admin_token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for (uri, expected) in [
+            (
+                format!("/api/fleets/kiosks-b/members/{}", entity.id),
+                StatusCode::NO_CONTENT,
+            ),
+            (
+                format!("/api/fleets/kiosks-b/members/{}", unknown),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                format!("/api/fleets/bad*name/members/{}", entity.id),
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let resp = app
+                .clone()
+                .oneshot(request("PUT", uri.clone()))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), expected, "{}", uri);
+        }
+
+        let resp = app
+            .clone()
+            .oneshot(request("GET", "/api/fleets/kiosks-b".to_string()))
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["members"], serde_json::json!([entity.id.as_str()]));
+
+        // The router's fan-out sees the same membership
+        let source = RegistryFleetSource(fleets);
+        assert_eq!(
+            source.members("kiosks-b").await,
+            vec![entity.id.to_string()]
+        );
+
+        let uri = format!("/api/fleets/kiosks-b/members/{}", entity.id);
+        let resp = app
+            .clone()
+            .oneshot(request("DELETE", uri.clone()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = app.oneshot(request("DELETE", uri)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(source.members("kiosks-b").await.is_empty());
+    }
 }
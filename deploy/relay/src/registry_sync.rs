@@ -0,0 +1,313 @@
+//! Entity registry replication for federated relays.
+//!
+//! With `--registry-sync`, entity changes made through the registry API are
+//! signed and sent to federation peers, and peers' changes are applied to
+//! the local registry (see `clasp_registry::replication` for signing and
+//! conflict resolution):
+//!
+//! - A hub accepts events from its leaves and passes each one it applies on
+//!   to its other peers.
+//! - A leaf (`--federation-hub`) sends its changes to the hub and applies
+//!   the hub's. When its link comes up the two exchange their registries.
+//! - A leaf with `--registry-mirror` keeps a read-only copy of the hub's
+//!   registry: the entity API refuses changes and nothing is sent upstream.
+//!
+//! Events are only accepted from routers whose public key was given with
+//! `--registry-peer`. Configuration documents and fleets stay local to each
+//! relay.
+
+use clasp_core::security::CachingValidator;
+use clasp_registry::replication::{RegistryEventKind, ReplicationMode, Replicator};
+use clasp_registry::{Entity, EntityValidator, RegistryError};
+use clasp_router::registry_sync::broadcast_registry_event;
+use clasp_router::{RegistryReplica, Session, SessionId};
+use dashmap::DashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Buffered events for the hub while the link is down or slow
+const UPSTREAM_CAPACITY: usize = 1024;
+
+/// Shared between the registry API, the router (events from peers) and the
+/// federation leaf (events from and to the hub)
+pub struct RegistrySync {
+    replicator: Replicator,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    upstream: broadcast::Sender<Vec<u8>>,
+    validation_cache: Option<Arc<CachingValidator<EntityValidator>>>,
+}
+
+impl RegistrySync {
+    pub fn new(replicator: Replicator, sessions: Arc<DashMap<SessionId, Arc<Session>>>) -> Self {
+        let (upstream, _) = broadcast::channel(UPSTREAM_CAPACITY);
+        Self {
+            replicator,
+            sessions,
+            upstream,
+            validation_cache: None,
+        }
+    }
+
+    /// Drop cached token validations of entities changed by peers
+    pub fn with_validation_cache(mut self, cache: Arc<CachingValidator<EntityValidator>>) -> Self {
+        self.validation_cache = Some(cache);
+        self
+    }
+
+    /// Refuse local changes on a mirror
+    pub fn check_writable(&self) -> Result<(), RegistryError> {
+        self.replicator.check_writable()
+    }
+
+    /// Events for the hub, for the federation leaf's link
+    pub fn upstream(&self) -> broadcast::Receiver<Vec<u8>> {
+        self.upstream.subscribe()
+    }
+
+    /// Sign a change made through the registry API and send it to every peer
+    pub async fn publish(&self, kind: RegistryEventKind, entity: &Entity) {
+        match self.replicator.record(kind, entity).await {
+            Ok(event) => {
+                let peers = broadcast_registry_event(&self.sessions, &event, None);
+                let _ = self.upstream.send(event);
+                tracing::debug!(
+                    "Registry sync: {} {} sent to {} peer(s)",
+                    kind,
+                    entity.id,
+                    peers
+                );
+            }
+            Err(e) => tracing::warn!(
+                "Registry sync: failed to sign {} {}: {}",
+                kind,
+                entity.id,
+                e
+            ),
+        }
+    }
+
+    /// Apply an event from the hub
+    pub async fn receive_from_hub(&self, payload: &[u8]) {
+        if let Err(e) = self.receive(payload, None).await {
+            tracing::warn!("Registry sync: refused event from hub: {}", e);
+        }
+    }
+
+    /// Send the local registry to the hub, which asked for it. A mirror has
+    /// nothing of its own to send.
+    pub async fn send_snapshot_to_hub(&self) {
+        if self.replicator.mode() == ReplicationMode::Mirror {
+            return;
+        }
+        match self.replicator.snapshot().await {
+            Ok(events) => {
+                tracing::info!("Registry sync: sending {} event(s) to hub", events.len());
+                for event in events {
+                    let _ = self.upstream.send(event);
+                }
+            }
+            Err(e) => tracing::warn!("Registry sync: failed to read registry: {}", e),
+        }
+    }
+
+    /// Apply an event from the hub (`from` is `None`) or a peer session and
+    /// pass it on to everyone else if it changed the registry
+    async fn receive(&self, payload: &[u8], from: Option<&SessionId>) -> Result<(), RegistryError> {
+        let Some(event) = self.replicator.apply(payload).await? else {
+            return Ok(());
+        };
+        if let Some(ref cache) = self.validation_cache {
+            cache.invalidate_subject(event.entity.id.as_str());
+        }
+        broadcast_registry_event(&self.sessions, payload, from);
+        if from.is_some() {
+            let _ = self.upstream.send(payload.to_vec());
+        }
+        tracing::info!(
+            "Registry sync: applied {} {} from {}",
+            event.kind,
+            event.entity.id,
+            event.origin
+        );
+        Ok(())
+    }
+}
+
+/// Build the replica from `--registry-key`, `--registry-peer` and
+/// `--registry-mirror`, generating the signing key file if it is missing
+#[cfg(feature = "registry")]
+pub fn from_config(
+    config: &crate::config::RelayConfig,
+    store: Arc<dyn clasp_registry::EntityStore>,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+) -> anyhow::Result<RegistrySync> {
+    let mut replicator = if config.registry_mirror {
+        anyhow::ensure!(
+            config.federation_hub.is_some(),
+            "--registry-mirror needs --federation-hub"
+        );
+        Replicator::mirror(store)
+    } else {
+        let path = config
+            .registry_key
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("--registry-sync needs --registry-key"))?;
+        let keypair = load_or_create_key(path)?;
+        tracing::info!(
+            "Registry sync: signing as {} (public key {})",
+            keypair.entity_id,
+            hex_encode(keypair.public_key_bytes())
+        );
+        Replicator::new(store, keypair)
+    };
+    for peer in &config.registry_peer {
+        let key = hex_decode(peer.trim())
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| anyhow::anyhow!("--registry-peer {} is not 64 hex chars", peer))?;
+        replicator = replicator
+            .trust(&key)
+            .map_err(|e| anyhow::anyhow!("--registry-peer {}: {}", peer, e))?;
+    }
+    Ok(RegistrySync::new(replicator, sessions))
+}
+
+#[cfg(feature = "registry")]
+fn load_or_create_key(path: &std::path::Path) -> anyhow::Result<clasp_registry::EntityKeypair> {
+    use anyhow::Context;
+
+    if !path.exists() {
+        let keypair = clasp_registry::EntityKeypair::generate()?;
+        crate::cpsk::write_secret_file(
+            path,
+            hex_encode(&keypair.signing_key.to_bytes()).as_bytes(),
+        )
+        .with_context(|| format!("failed to write registry key {}", path.display()))?;
+        tracing::info!("Registry sync: generated signing key {}", path.display());
+        return Ok(keypair);
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read registry key {}", path.display()))?;
+    let bytes: [u8; 32] = hex_decode(contents.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "registry key {} must contain 64 hex characters (32-byte Ed25519 signing key)",
+                path.display()
+            )
+        })?;
+    Ok(clasp_registry::EntityKeypair::from_signing_key(
+        ed25519_dalek::SigningKey::from_bytes(&bytes),
+    )?)
+}
+
+#[cfg(feature = "registry")]
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(feature = "registry")]
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl RegistryReplica for RegistrySync {
+    async fn apply(&self, from: &SessionId, payload: &[u8]) -> Result<(), String> {
+        if self.replicator.mode() == ReplicationMode::Mirror {
+            return Err("this registry is a read-only mirror of its hub".to_string());
+        }
+        self.receive(payload, Some(from))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn snapshot(&self) -> Vec<Vec<u8>> {
+        self.replicator.snapshot().await.unwrap_or_else(|e| {
+            tracing::warn!("Registry sync: failed to read registry: {}", e);
+            Vec::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clasp_registry::{EntityKeypair, EntityStore, EntityType, MemoryEntityStore};
+
+    fn device() -> Entity {
+        EntityKeypair::generate()
+            .unwrap()
+            .to_entity(EntityType::Device, "kiosk-1".to_string())
+    }
+
+    #[tokio::test]
+    async fn mirror_refuses_changes_and_applies_hub_events() {
+        let hub_key = EntityKeypair::generate().unwrap();
+        let hub_public = hub_key.public_key_bytes().to_vec();
+        let hub_store = Arc::new(MemoryEntityStore::new());
+        let hub = RegistrySync::new(
+            Replicator::new(hub_store.clone(), hub_key),
+            Arc::new(DashMap::new()),
+        );
+
+        let leaf_store = Arc::new(MemoryEntityStore::new());
+        let leaf = RegistrySync::new(
+            Replicator::mirror(leaf_store.clone())
+                .trust(&hub_public)
+                .unwrap(),
+            Arc::new(DashMap::new()),
+        );
+        assert!(matches!(
+            leaf.check_writable(),
+            Err(RegistryError::ReadOnly(_))
+        ));
+
+        let entity = device();
+        hub_store.create(&entity).await.unwrap();
+        let mut sent = hub.upstream();
+        hub.publish(RegistryEventKind::Created, &entity).await;
+        leaf.receive_from_hub(&sent.recv().await.unwrap()).await;
+        assert!(leaf_store.get(&entity.id).await.unwrap().is_some());
+
+        // A mirror takes no events from peers of its own
+        let from: SessionId = "peer-1".to_string();
+        assert!(RegistryReplica::apply(&leaf, &from, &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn peer_events_go_upstream() {
+        let leaf_key = EntityKeypair::generate().unwrap();
+        let leaf_public = leaf_key.public_key_bytes().to_vec();
+        let leaf_store = Arc::new(MemoryEntityStore::new());
+        let leaf = Replicator::new(leaf_store.clone(), leaf_key);
+
+        let hub_key = EntityKeypair::generate().unwrap();
+        let hub = RegistrySync::new(
+            Replicator::new(Arc::new(MemoryEntityStore::new()), hub_key)
+                .trust(&leaf_public)
+                .unwrap(),
+            Arc::new(DashMap::new()),
+        );
+        let mut upstream = hub.upstream();
+
+        let entity = device();
+        leaf_store.create(&entity).await.unwrap();
+        let event = leaf
+            .record(RegistryEventKind::Created, &entity)
+            .await
+            .unwrap();
+        let from: SessionId = "peer-1".to_string();
+        RegistryReplica::apply(&hub, &from, &event).await.unwrap();
+        assert_eq!(upstream.recv().await.unwrap(), event);
+
+        // Applying it again changes nothing, so it isn't passed on
+        RegistryReplica::apply(&hub, &from, &event).await.unwrap();
+        assert!(upstream.try_recv().is_err());
+    }
+}
@@ -0,0 +1,870 @@
+//! Warm standby replication of the journal.
+//!
+//! A primary relay with a journal streams its entries to followers over the
+//! auth port. A standby started with `--replicate-from` follows one primary:
+//!
+//! ```text
+//! clasp-relay --journal /var/lib/clasp/journal.db --auth-port 7350
+//! clasp-relay --journal /var/lib/clasp/journal.db --auth-port 7350 \
+//!     --replicate-from http://primary:7350 --replication-token $ADMIN
+//! ```
+//!
+//! The standby opens `GET /api/replication/stream`, a server-sent event
+//! stream. A follower with nothing applied yet (or one that fell behind
+//! compaction) first receives a `snapshot` event with every param and the
+//! journal sequence it was taken at, then one `entry` event per journal
+//! entry, live as they are appended. SET entries are applied with the
+//! primary's revision, writer and timestamp, and every entry is appended to
+//! the standby's own journal. The standby acknowledges the last applied
+//! sequence with `POST /api/replication/ack` and resumes from it after a
+//! reconnect. `GET /api/replication/status` reports the role and, on the
+//! primary, each follower's acknowledged sequence and lag.
+//!
+//! While in standby, client SET and PUBLISH messages are rejected.
+//! `POST /api/replication/promote` stops following and accepts writes, which
+//! continue from the replicated revisions.
+//!
+//! Only the default journal is streamed: addresses routed to a
+//! `--journal-partitions` partition arrive with the snapshot but not live.
+//! All endpoints require a token with admin scope, like the journal API.
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use clasp_core::security::{Action, CpskValidator, TokenValidator, ValidationResult};
+use clasp_core::{codec, Message, SetMessage, SignalType, Ttl, Value};
+use clasp_journal::{entry::ParamSnapshot, Journal, JournalEntry};
+use clasp_router::session::{Session, SessionId};
+use clasp_router::{RouterState, SubscriptionManager, WriteValidator};
+use dashmap::DashMap;
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+
+/// Journal entries read per query while a follower catches up.
+const STREAM_BATCH: u32 = 500;
+
+/// Events buffered per follower before the stream task waits.
+const STREAM_BUFFER: usize = 256;
+
+/// How often a standby acknowledges the sequence it has applied.
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// First reconnect delay after the stream drops.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest delay between reconnects.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// SET message type code in journal entries.
+const MSG_SET: u8 = 0x21;
+
+// ---------------------------------------------------------------------------
+// Role
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Primary,
+    Standby,
+}
+
+#[derive(Debug, Default)]
+struct FollowerStatus {
+    acked_seq: u64,
+    connected: bool,
+    last_ack: Option<Instant>,
+}
+
+/// Replication role of this relay and what it knows about its peers.
+pub struct Replication {
+    role: watch::Sender<Role>,
+    /// Auth URL of the primary (standby only)
+    primary: Option<String>,
+    /// Last primary sequence applied (standby only)
+    applied_seq: AtomicU64,
+    /// Followers by id (primary only)
+    followers: DashMap<String, FollowerStatus>,
+}
+
+impl Replication {
+    pub fn primary() -> Self {
+        Self::new(Role::Primary, None)
+    }
+
+    /// A standby following the primary whose auth port is at `primary`.
+    pub fn standby(primary: impl Into<String>) -> Self {
+        Self::new(Role::Standby, Some(primary.into()))
+    }
+
+    fn new(role: Role, primary: Option<String>) -> Self {
+        Self {
+            role: watch::Sender::new(role),
+            primary,
+            applied_seq: AtomicU64::new(0),
+            followers: DashMap::new(),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        *self.role.borrow()
+    }
+
+    pub fn is_standby(&self) -> bool {
+        self.role() == Role::Standby
+    }
+
+    /// Switch to primary. Returns false if this relay already was one.
+    pub fn promote(&self) -> bool {
+        self.role.send_if_modified(|role| {
+            let was_standby = *role == Role::Standby;
+            *role = Role::Primary;
+            was_standby
+        })
+    }
+
+    /// Last primary sequence applied by this standby.
+    pub fn applied_seq(&self) -> u64 {
+        self.applied_seq.load(Ordering::Relaxed)
+    }
+
+    fn set_applied(&self, seq: u64) {
+        self.applied_seq.store(seq, Ordering::Relaxed);
+    }
+
+    fn set_connected(&self, follower: &str, connected: bool) {
+        self.followers
+            .entry(follower.to_string())
+            .or_default()
+            .connected = connected;
+    }
+
+    fn record_ack(&self, follower: &str, seq: u64) {
+        let mut status = self.followers.entry(follower.to_string()).or_default();
+        status.acked_seq = status.acked_seq.max(seq);
+        status.last_ack = Some(Instant::now());
+    }
+}
+
+/// Rejects client writes while the relay is a standby, then defers to the
+/// configured validator (if any).
+pub struct StandbyGuard {
+    replication: Arc<Replication>,
+    inner: Option<Arc<dyn WriteValidator>>,
+}
+
+impl StandbyGuard {
+    pub fn new(replication: Arc<Replication>, inner: Option<Arc<dyn WriteValidator>>) -> Self {
+        Self { replication, inner }
+    }
+}
+
+impl WriteValidator for StandbyGuard {
+    fn validate_write(
+        &self,
+        address: &str,
+        value: &Value,
+        session: &Session,
+        state: &RouterState,
+    ) -> Result<(), String> {
+        if self.replication.is_standby() {
+            return Err("relay is a read-only standby; write to the primary".to_string());
+        }
+        match self.inner {
+            Some(ref inner) => inner.validate_write(address, value, session, state),
+            None => Ok(()),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Journal wrapper
+// ---------------------------------------------------------------------------
+
+/// Journal that announces each appended sequence, so replication streams
+/// wake as soon as an entry lands instead of polling.
+pub struct ReplicatedJournal {
+    inner: Arc<dyn Journal>,
+    head: watch::Sender<u64>,
+}
+
+impl ReplicatedJournal {
+    pub fn new(inner: Arc<dyn Journal>) -> Self {
+        Self {
+            inner,
+            head: watch::Sender::new(0),
+        }
+    }
+
+    /// Receiver that changes whenever an entry is appended.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.head.subscribe()
+    }
+}
+
+#[async_trait]
+impl Journal for ReplicatedJournal {
+    async fn append(&self, entry: JournalEntry) -> clasp_journal::Result<u64> {
+        let seq = self.inner.append(entry).await?;
+        self.head.send_modify(|head| *head = (*head).max(seq));
+        Ok(seq)
+    }
+
+    async fn query(
+        &self,
+        pattern: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        limit: Option<u32>,
+        types: &[SignalType],
+    ) -> clasp_journal::Result<Vec<JournalEntry>> {
+        self.inner.query(pattern, from, to, limit, types).await
+    }
+
+    async fn since(
+        &self,
+        seq: u64,
+        limit: Option<u32>,
+    ) -> clasp_journal::Result<Vec<JournalEntry>> {
+        self.inner.since(seq, limit).await
+    }
+
+    async fn latest_seq(&self) -> clasp_journal::Result<u64> {
+        self.inner.latest_seq().await
+    }
+
+    async fn snapshot(&self, state: &[ParamSnapshot]) -> clasp_journal::Result<u64> {
+        self.inner.snapshot(state).await
+    }
+
+    async fn load_snapshot(&self) -> clasp_journal::Result<Option<Vec<ParamSnapshot>>> {
+        self.inner.load_snapshot().await
+    }
+
+    async fn compact(&self, before_seq: u64) -> clasp_journal::Result<u64> {
+        self.inner.compact(before_seq).await
+    }
+
+    async fn len(&self) -> clasp_journal::Result<usize> {
+        self.inner.len().await
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Primary: REST + SSE API
+// ---------------------------------------------------------------------------
+
+pub struct ReplicationApiState {
+    pub replication: Arc<Replication>,
+    /// Journal streamed to followers (`None` without `--journal`)
+    pub journal: Option<Arc<ReplicatedJournal>>,
+    pub state: Arc<RouterState>,
+    pub validator: Arc<CpskValidator>,
+}
+
+/// Query parameters for the `/api/replication/stream` endpoint.
+#[derive(Deserialize)]
+pub struct StreamParams {
+    /// Last sequence the follower applied (0 = start with a snapshot)
+    #[serde(default)]
+    pub from: u64,
+    /// Follower id reported in `/api/replication/status`
+    pub follower: Option<String>,
+}
+
+/// Payload of a `snapshot` event.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotEvent {
+    /// Journal sequence the snapshot covers; entries follow from here
+    pub seq: u64,
+    pub params: Vec<ParamSnapshot>,
+}
+
+/// Body of `POST /api/replication/ack`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AckRequest {
+    pub follower: String,
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FollowerInfo {
+    pub id: String,
+    pub acked_seq: u64,
+    /// Entries appended on the primary that the follower has not acknowledged
+    pub lag: u64,
+    pub connected: bool,
+    /// Seconds since the last acknowledgement
+    pub last_ack_secs: Option<u64>,
+}
+
+/// Response of `/api/replication/status` and `/api/replication/promote`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StatusResponse {
+    pub role: Role,
+    /// Latest local journal sequence
+    pub seq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<String>,
+    /// Last primary sequence applied (standby)
+    pub applied_seq: u64,
+    pub followers: Vec<FollowerInfo>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn err(status: StatusCode, msg: impl Into<String>) -> ApiError {
+    (status, Json(ErrorResponse { error: msg.into() }))
+}
+
+/// Validate admin Bearer token from request headers.
+fn validate_admin(headers: &HeaderMap, validator: &CpskValidator) -> Result<(), ApiError> {
+    let auth_header = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "expected Bearer token"))?;
+
+    match validator.validate(token) {
+        ValidationResult::Valid(info) => {
+            if info.has_scope(Action::Admin, "/**") {
+                Ok(())
+            } else {
+                Err(err(StatusCode::FORBIDDEN, "admin scope required"))
+            }
+        }
+        ValidationResult::Expired => Err(err(StatusCode::UNAUTHORIZED, "token expired")),
+        _ => Err(err(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+async fn stream_journal(
+    State(state): State<Arc<ReplicationApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<StreamParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+    let Some(ref journal) = state.journal else {
+        return Err(err(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "replication requires a journal (--journal or --journal-memory)",
+        ));
+    };
+
+    let follower = params.follower.unwrap_or_else(|| "standby".to_string());
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(feed_follower(
+        Arc::clone(&state),
+        Arc::clone(journal),
+        follower,
+        params.from,
+        tx,
+    ));
+
+    let events = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok(event), rx))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Send a follower what it is missing, then every new entry, until it
+/// disconnects.
+async fn feed_follower(
+    state: Arc<ReplicationApiState>,
+    journal: Arc<ReplicatedJournal>,
+    follower: String,
+    from: u64,
+    tx: mpsc::Sender<Event>,
+) {
+    // Subscribe before reading so no append between the two is missed
+    let mut head = journal.subscribe();
+    state.replication.set_connected(&follower, true);
+    tracing::info!(
+        "Replication: follower {} connected at seq {}",
+        follower,
+        from
+    );
+
+    if let Err(e) = feed(&state, &journal, from, &tx, &mut head).await {
+        tracing::warn!("Replication: stream to {} failed: {}", follower, e);
+    }
+
+    state.replication.set_connected(&follower, false);
+    tracing::info!("Replication: follower {} disconnected", follower);
+}
+
+async fn feed(
+    state: &ReplicationApiState,
+    journal: &ReplicatedJournal,
+    mut cursor: u64,
+    tx: &mpsc::Sender<Event>,
+    head: &mut watch::Receiver<u64>,
+) -> Result<(), String> {
+    if needs_snapshot(journal, cursor).await? {
+        // Read the sequence first: entries appended while the params are
+        // collected are replayed afterwards, and replays are ignored by
+        // revision on the standby.
+        let seq = journal.latest_seq().await.map_err(|e| e.to_string())?;
+        let params = state
+            .state
+            .get_matching("**")
+            .into_iter()
+            .filter(|(_, param)| param.ttl != Some(Ttl::Session))
+            .map(|(address, param)| ParamSnapshot {
+                address,
+                value: param.value,
+                revision: param.revision,
+                writer: param.writer,
+                timestamp: param.timestamp,
+            })
+            .collect();
+        let event = Event::default()
+            .event("snapshot")
+            .id(seq.to_string())
+            .json_data(SnapshotEvent { seq, params })
+            .map_err(|e| e.to_string())?;
+        if tx.send(event).await.is_err() {
+            return Ok(());
+        }
+        cursor = seq;
+    }
+
+    loop {
+        let entries = journal
+            .since(cursor, Some(STREAM_BATCH))
+            .await
+            .map_err(|e| e.to_string())?;
+        if entries.is_empty() {
+            tokio::select! {
+                changed = head.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                }
+                _ = tx.closed() => return Ok(()),
+            }
+            continue;
+        }
+        for entry in entries {
+            cursor = entry.seq;
+            let event = Event::default()
+                .event("entry")
+                .id(entry.seq.to_string())
+                .json_data(&entry)
+                .map_err(|e| e.to_string())?;
+            if tx.send(event).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Whether a follower at `from` needs a snapshot: it has applied nothing,
+/// is ahead of this journal, or the entries after `from` were compacted.
+async fn needs_snapshot(journal: &ReplicatedJournal, from: u64) -> Result<bool, String> {
+    if from == 0 {
+        return Ok(true);
+    }
+    let latest = journal.latest_seq().await.map_err(|e| e.to_string())?;
+    if from > latest {
+        return Ok(true);
+    }
+    let next = journal
+        .since(from, Some(1))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(next.first().is_some_and(|entry| entry.seq != from + 1))
+}
+
+async fn ack(
+    State(state): State<Arc<ReplicationApiState>>,
+    headers: HeaderMap,
+    Json(request): Json<AckRequest>,
+) -> Result<StatusCode, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+    state.replication.record_ack(&request.follower, request.seq);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn status_response(state: &ReplicationApiState) -> StatusResponse {
+    let seq = match state.journal {
+        Some(ref journal) => journal.latest_seq().await.ok(),
+        None => None,
+    };
+    let mut followers: Vec<FollowerInfo> = state
+        .replication
+        .followers
+        .iter()
+        .map(|entry| FollowerInfo {
+            id: entry.key().clone(),
+            acked_seq: entry.acked_seq,
+            lag: seq.unwrap_or(0).saturating_sub(entry.acked_seq),
+            connected: entry.connected,
+            last_ack_secs: entry.last_ack.map(|at| at.elapsed().as_secs()),
+        })
+        .collect();
+    followers.sort_by(|a, b| a.id.cmp(&b.id));
+
+    StatusResponse {
+        role: state.replication.role(),
+        seq,
+        primary: state.replication.primary.clone(),
+        applied_seq: state.replication.applied_seq(),
+        followers,
+    }
+}
+
+async fn status(
+    State(state): State<Arc<ReplicationApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<StatusResponse>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+    Ok(Json(status_response(&state).await))
+}
+
+async fn promote(
+    State(state): State<Arc<ReplicationApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<StatusResponse>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+    if !state.replication.promote() {
+        return Err(err(StatusCode::CONFLICT, "relay is already primary"));
+    }
+    tracing::warn!(
+        "Replication: promoted to primary at seq {}",
+        state.replication.applied_seq()
+    );
+    Ok(Json(status_response(&state).await))
+}
+
+/// Build the replication REST router.
+pub fn replication_router(state: Arc<ReplicationApiState>) -> Router {
+    Router::new()
+        .route("/api/replication/stream", get(stream_journal))
+        .route("/api/replication/ack", post(ack))
+        .route("/api/replication/status", get(status))
+        .route("/api/replication/promote", post(promote))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Standby: follower
+// ---------------------------------------------------------------------------
+
+/// Where a standby follows its primary from.
+#[derive(Debug, Clone)]
+pub struct FollowerConfig {
+    /// Primary auth port URL, e.g. `http://primary:7350`
+    pub primary: String,
+    /// Admin token for the primary
+    pub token: String,
+    /// Id reported to the primary
+    pub id: String,
+}
+
+/// Follow the primary until this relay is promoted, reconnecting with
+/// backoff whenever the stream drops.
+pub async fn run_follower(
+    config: FollowerConfig,
+    replication: Arc<Replication>,
+    state: Arc<RouterState>,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+) {
+    let follower = Follower {
+        client: reqwest::Client::new(),
+        config,
+        replication: Arc::clone(&replication),
+        state,
+        sessions,
+        subscriptions,
+    };
+    let mut role = replication.role.subscribe();
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        tokio::select! {
+            result = follower.follow(&mut backoff) => match result {
+                Ok(()) => tracing::warn!("Replication: stream from {} ended", follower.config.primary),
+                Err(e) => tracing::warn!("Replication: stream from {} failed: {}", follower.config.primary, e),
+            },
+            _ = role.wait_for(|role| *role == Role::Primary) => break,
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = role.wait_for(|role| *role == Role::Primary) => break,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    tracing::info!(
+        "Replication: stopped following {} at seq {}",
+        follower.config.primary,
+        replication.applied_seq()
+    );
+}
+
+struct Follower {
+    client: reqwest::Client,
+    config: FollowerConfig,
+    replication: Arc<Replication>,
+    state: Arc<RouterState>,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+}
+
+impl Follower {
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.primary.trim_end_matches('/'), path)
+    }
+
+    async fn follow(&self, backoff: &mut Duration) -> Result<(), String> {
+        let from = self.replication.applied_seq();
+        let mut response = self
+            .client
+            .get(self.url("/api/replication/stream"))
+            .bearer_auth(&self.config.token)
+            .query(&[
+                ("from", from.to_string()),
+                ("follower", self.config.id.clone()),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("{} {}", status, body.trim()));
+        }
+        tracing::info!(
+            "Replication: following {} from seq {}",
+            self.config.primary,
+            from
+        );
+        *backoff = INITIAL_BACKOFF;
+
+        let mut parser = SseParser::default();
+        let mut acked = from;
+        let mut ack_timer = tokio::time::interval(ACK_INTERVAL);
+        loop {
+            tokio::select! {
+                chunk = response.chunk() => {
+                    let Some(chunk) = chunk.map_err(|e| e.to_string())? else {
+                        return Ok(());
+                    };
+                    for event in parser.push(&chunk) {
+                        self.apply(event).await?;
+                    }
+                }
+                _ = ack_timer.tick() => {
+                    let seq = self.replication.applied_seq();
+                    if seq != acked {
+                        match self.ack(seq).await {
+                            Ok(()) => acked = seq,
+                            Err(e) => tracing::debug!("Replication: ack failed: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn ack(&self, seq: u64) -> Result<(), String> {
+        let response = self
+            .client
+            .post(self.url("/api/replication/ack"))
+            .bearer_auth(&self.config.token)
+            .json(&AckRequest {
+                follower: self.config.id.clone(),
+                seq,
+            })
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(response.status().to_string());
+        }
+        Ok(())
+    }
+
+    async fn apply(&self, event: SseEvent) -> Result<(), String> {
+        match event.event.as_str() {
+            "snapshot" => {
+                let snapshot: SnapshotEvent = serde_json::from_str(&event.data)
+                    .map_err(|e| format!("invalid snapshot: {}", e))?;
+                for param in &snapshot.params {
+                    self.apply_set(
+                        &param.address,
+                        &param.value,
+                        &param.writer,
+                        param.revision,
+                        param.timestamp,
+                    );
+                }
+                if let Some(journal) = self.state.journal() {
+                    if let Err(e) = journal.snapshot(&snapshot.params).await {
+                        tracing::warn!("Replication: failed to store snapshot: {}", e);
+                    }
+                }
+                tracing::info!(
+                    "Replication: applied snapshot of {} params at seq {}",
+                    snapshot.params.len(),
+                    snapshot.seq
+                );
+                self.replication.set_applied(snapshot.seq);
+            }
+            "entry" => {
+                let entry: JournalEntry = serde_json::from_str(&event.data)
+                    .map_err(|e| format!("invalid entry: {}", e))?;
+                if entry.msg_type == MSG_SET {
+                    if let Some(revision) = entry.revision {
+                        self.apply_set(
+                            &entry.address,
+                            &entry.value,
+                            &entry.author,
+                            revision,
+                            entry.timestamp,
+                        );
+                    }
+                }
+                let seq = entry.seq;
+                if let Some(journal) = self.state.journal_for(&entry.address) {
+                    if let Err(e) = journal.append(entry).await {
+                        tracing::warn!("Replication: failed to journal entry {}: {}", seq, e);
+                    }
+                }
+                self.replication.set_applied(seq);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Apply a replicated SET and pass it on to local subscribers.
+    fn apply_set(&self, address: &str, value: &Value, writer: &str, revision: u64, timestamp: u64) {
+        if !self
+            .state
+            .apply_replicated(address, value.clone(), writer, revision, timestamp)
+        {
+            return;
+        }
+        let message = Message::Set(SetMessage {
+            address: address.to_string(),
+            value: value.clone(),
+            revision: Some(revision),
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        });
+        let Ok(bytes) = codec::encode(&message) else {
+            return;
+        };
+        for session_id in self
+            .subscriptions
+            .find_subscribers(address, Some(SignalType::Param))
+        {
+            if let Some(session) = self.sessions.get(&session_id) {
+                let _ = session.value().try_send(bytes.clone());
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Server-sent event parsing
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default, PartialEq)]
+struct SseEvent {
+    event: String,
+    data: String,
+}
+
+/// Splits a server-sent event body into events as chunks arrive.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let block: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let block = String::from_utf8_lossy(&block[..end]);
+            let mut event = SseEvent::default();
+            let mut data: Vec<&str> = Vec::new();
+            for line in block.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    event.event = value.trim_start().to_string();
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data.push(value.strip_prefix(' ').unwrap_or(value));
+                }
+            }
+            // Comment-only blocks are keep-alives
+            if data.is_empty() {
+                continue;
+            }
+            event.data = data.join("\n");
+            events.push(event);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_parser_splits_events_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: entry\nid: 1\nda").is_empty());
+        let events = parser.push(b"ta: {\"a\":1}\n\n:\n\nevent: snapshot\ndata: x\n");
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: "entry".into(),
+                data: "{\"a\":1}".into()
+            }]
+        );
+        let events = parser.push(b"data: y\n\n");
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: "snapshot".into(),
+                data: "x\ny".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn promote_only_once() {
+        let replication = Replication::standby("http://primary:7350");
+        assert!(replication.is_standby());
+        assert!(replication.promote());
+        assert_eq!(replication.role(), Role::Primary);
+        assert!(!replication.promote());
+    }
+}
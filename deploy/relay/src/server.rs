@@ -1,13 +1,18 @@
 //! Server orchestration: router setup, transport configuration, and main run loop.
 
 use crate::config::RelayConfig;
-use crate::cpsk::{write_secret_file, SharedValidator};
+use crate::cpsk::{write_secret_file, SharedChain, SharedValidator};
 
 use anyhow::{Context, Result};
 use clasp_core::security::{CpskValidator, ValidatorChain};
 use clasp_core::types::SnapshotMessage;
 use clasp_core::SecurityMode;
-use clasp_router::{MultiProtocolConfig, Router, RouterConfig, RouterState, RouterStateConfig};
+use clasp_router::{
+    AddressMap, AddressPolicy, AddressRule, AggregateRule, BandwidthQuota, MultiProtocolConfig, OverloadConfig, QuotaConfig, ReplayConfig, ReservedNamespace, Router, RouterConfig,
+    RouterState, RouterStateConfig, SessionLimit, ShadowConfig, TopicAliasConfig,
+    TransportPolicy, ValidationConfig,
+};
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -22,22 +27,41 @@ pub async fn run(config: RelayConfig) -> Result<()> {
     tracing::info!("║           CLASP Multi-Protocol Relay Server                  ║");
     tracing::info!("╚══════════════════════════════════════════════════════════════╝");
 
-    // Start Prometheus metrics exporter if configured
+    // Start Prometheus metrics exporter if configured. With --metrics-param the
+    // relay serves /metrics itself, next to the /metrics/params state export,
+    // once the router state exists.
+    #[cfg(feature = "metrics")]
+    let mut metrics_server = None;
     #[cfg(feature = "metrics")]
     if let Some(metrics_port) = config.metrics_port {
         let metrics_addr: SocketAddr = format!("{}:{}", config.host, metrics_port)
             .parse()
             .context("Invalid metrics address")?;
         let builder = metrics_exporter_prometheus::PrometheusBuilder::new();
-        builder
-            .with_http_listener(metrics_addr)
-            .install()
-            .context("Failed to install Prometheus metrics exporter")?;
+        if config.metrics_param.is_empty() {
+            builder
+                .with_http_listener(metrics_addr)
+                .install()
+                .context("Failed to install Prometheus metrics exporter")?;
+        } else {
+            let handle = builder
+                .install_recorder()
+                .context("Failed to install Prometheus metrics exporter")?;
+            metrics_server = Some((metrics_addr, handle));
+        }
         tracing::info!("Metrics: http://{}/metrics", metrics_addr);
     }
+    #[cfg(feature = "metrics")]
+    if config.metrics_port.is_none() && !config.metrics_param.is_empty() {
+        tracing::warn!("--metrics-param requires --metrics-port; param export disabled");
+    }
+    #[cfg(not(feature = "metrics"))]
+    if !config.metrics_param.is_empty() {
+        tracing::warn!("--metrics-param requires the 'metrics' feature; param export disabled");
+    }
 
     // Create state store configuration based on config flags
-    let state_config = if config.no_ttl {
+    let mut state_config = if config.no_ttl {
         tracing::info!("TTL disabled: parameters and signals persist indefinitely");
         RouterStateConfig::unlimited()
     } else {
@@ -64,8 +88,16 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             },
             signal_ttl,
             max_signals: Some(100_000),
+            session_scoped: Vec::new(),
         }
     };
+    if !config.session_scoped.is_empty() {
+        tracing::info!(
+            "Session-scoped addresses: {}",
+            config.session_scoped.join(", ")
+        );
+        state_config.session_scoped = config.session_scoped.clone();
+    }
 
     // Determine security mode based on auth
     let auth_enabled = config.auth_port.is_some();
@@ -75,6 +107,88 @@ pub async fn run(config: RelayConfig) -> Result<()> {
         SecurityMode::Open
     };
 
+    // Bandwidth quotas: defaults and action from flags, overrides from --quota-file
+    let mut quota = match config.quota_file {
+        Some(ref path) => {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read quota file {}", path.display()))?;
+            let file: QuotaConfig = serde_json::from_str(&json)
+                .with_context(|| format!("Failed to parse quota JSON from {}", path.display()))?;
+            tracing::info!(
+                "Bandwidth quotas: {} subject(s), {} scope(s) from {}",
+                file.subjects.len(),
+                file.scopes.len(),
+                path.display()
+            );
+            QuotaConfig {
+                subjects: file.subjects,
+                scopes: file.scopes,
+                ..Default::default()
+            }
+        }
+        None => QuotaConfig::default(),
+    };
+    quota.default = BandwidthQuota::new(config.quota_bytes_per_hour, config.quota_bytes_per_day);
+    quota.action = config.quota_action;
+
+    // What each protocol may do, from --transport-policies
+    let transport_policies: HashMap<String, TransportPolicy> = match config.transport_policies {
+        Some(ref path) => {
+            let json = std::fs::read_to_string(path).with_context(|| {
+                format!("Failed to read transport policies {}", path.display())
+            })?;
+            let policies: HashMap<String, TransportPolicy> = serde_json::from_str(&json)
+                .with_context(|| {
+                    format!("Failed to parse transport policies from {}", path.display())
+                })?;
+            for (transport, policy) in &policies {
+                tracing::info!("Transport policy for {}: {:?}", transport, policy);
+            }
+            policies
+        }
+        None => HashMap::new(),
+    };
+
+    // Audience-scale write aggregation, from --aggregates
+    let aggregates: Vec<AggregateRule> = match config.aggregates {
+        Some(ref path) => {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read aggregates {}", path.display()))?;
+            let rules: Vec<AggregateRule> = serde_json::from_str(&json)
+                .with_context(|| format!("Failed to parse aggregates from {}", path.display()))?;
+            for rule in &rules {
+                tracing::info!(
+                    "Aggregating {} as {:?} every {}ms{}",
+                    rule.pattern,
+                    rule.kind,
+                    rule.interval_ms,
+                    if rule.cumulative { " (cumulative)" } else { "" }
+                );
+            }
+            rules
+        }
+        None => Vec::new(),
+    };
+
+    let address_policy = if config.no_address_canonicalization {
+        AddressPolicy::off()
+    } else {
+        AddressPolicy {
+            enabled: true,
+            max_length: config.address_max_length,
+            max_depth: config.address_max_depth,
+            percent: config.address_percent,
+        }
+    };
+
+    let mut reserved = ReservedNamespace {
+        enabled: !config.no_reserved_namespace,
+        ..Default::default()
+    };
+    reserved
+        .exceptions
+        .extend(config.reserved_exception.iter().cloned());
+
     // Create router configuration
     let router_config = RouterConfig {
         name: config.name.clone(),
@@ -98,11 +212,89 @@ pub async fn run(config: RelayConfig) -> Result<()> {
         gesture_coalesce_interval_ms: 16,
         max_messages_per_second: if auth_enabled { 30 } else { 0 },
         rate_limiting_enabled: auth_enabled,
+        rate_limits: Default::default(),
         state_config,
+        session_handoff: config.session_handoff,
+        session_limit: SessionLimit::new(
+            config.max_sessions_per_subject,
+            config.session_limit_policy,
+        ),
+        durable_session_ttl: config.durable_session_ttl,
+        validation: ValidationConfig {
+            cache_ttl: Duration::from_secs(config.auth_cache_ttl),
+            ..Default::default()
+        },
+        overload: OverloadConfig {
+            max_accept_rate: config.max_accept_rate,
+            max_pending_handshakes: config.max_pending_handshakes,
+            retry_after: Duration::from_millis(config.retry_after_ms),
+            retry_jitter: Duration::from_millis(config.retry_jitter_ms),
+            shed_queue_depth: config.shed_queue_depth,
+            shed_loop_lag: Duration::from_millis(config.shed_loop_lag_ms),
+        },
+        quota,
+        topic_aliases: TopicAliasConfig {
+            max_aliases: config.max_topic_aliases,
+            ..Default::default()
+        },
+        shadow: ShadowConfig {
+            enabled: config.shadow,
+            desired_ttl: (config.shadow_desired_ttl > 0)
+                .then(|| Duration::from_secs(config.shadow_desired_ttl)),
+        },
+        presence: config.presence,
+        transport_policies,
+        aggregates,
+        schema: config.schema,
+        address_policy: address_policy.clone(),
+        replay: ReplayConfig {
+            page_size: config.replay_page_size,
+            max_rate: config.replay_max_rate,
+        },
+        reserved,
+        delivery_stats: config.delivery_stats,
     };
 
     let mut router = Router::new(router_config);
 
+    // Adapter address translation, from --address-maps
+    if let Some(ref path) = config.address_maps {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read address maps {}", path.display()))?;
+        let maps: HashMap<String, Vec<AddressRule>> = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse address maps from {}", path.display()))?;
+        for (transport, rules) in maps {
+            tracing::info!("Address map for {}: {} rules", transport, rules.len());
+            let map = AddressMap::new(rules).map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid address map for {} in {}: {}",
+                    transport,
+                    path.display(),
+                    e
+                )
+            })?;
+            router.set_address_mapper(&transport, Arc::new(map));
+        }
+    }
+
+    // Replication role: a standby follows --replicate-from until promoted
+    #[cfg(feature = "replication")]
+    let replication = Arc::new(match config.replicate_from {
+        Some(ref primary) => {
+            if config.replication_token.is_none() {
+                anyhow::bail!("--replicate-from requires --replication-token");
+            }
+            crate::replication::Replication::standby(primary.clone())
+        }
+        None => crate::replication::Replication::primary(),
+    });
+    #[cfg(not(feature = "replication"))]
+    if config.replicate_from.is_some() {
+        anyhow::bail!("--replicate-from requires the 'replication' feature. Rebuild with --features replication");
+    }
+    #[cfg(feature = "replication")]
+    let mut replicated_journal: Option<Arc<crate::replication::ReplicatedJournal>> = None;
+
     // Wire journal if configured
     #[cfg(feature = "journal")]
     let journal_for_api: Option<Arc<dyn clasp_journal::Journal>>;
@@ -147,16 +339,129 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             None
         };
 
+        // Archive sealed segments to object storage; queries read through to them
+        #[cfg(feature = "s3")]
+        let journal_arc = match (journal_arc, config.archive_s3_bucket.as_ref()) {
+            (Some(local), Some(bucket)) => {
+                let store = clasp_journal::S3SegmentStore::new(clasp_journal::S3Config {
+                    endpoint: config.archive_s3_endpoint.clone(),
+                    bucket: bucket.clone(),
+                    region: config.archive_s3_region.clone(),
+                    access_key: std::env::var("AWS_ACCESS_KEY_ID")
+                        .context("--archive-s3-bucket requires AWS_ACCESS_KEY_ID")?,
+                    secret_key: std::env::var("AWS_SECRET_ACCESS_KEY")
+                        .context("--archive-s3-bucket requires AWS_SECRET_ACCESS_KEY")?,
+                    session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+                    storage_class: config.archive_storage_class.clone(),
+                    virtual_host: false,
+                })?;
+                let archive = Arc::new(clasp_journal::JournalArchive::new(
+                    Arc::new(store),
+                    clasp_journal::ArchiveConfig {
+                        prefix: config.archive_prefix.clone(),
+                        segment_entries: config.archive_segment_entries.max(1),
+                        interval: config.archive_interval,
+                        lifecycle: clasp_journal::LifecycleConfig {
+                            expire_after: config
+                                .archive_expire_days
+                                .map(|days| Duration::from_secs(days * 86_400)),
+                        },
+                        ..Default::default()
+                    },
+                ));
+                tracing::info!(
+                    "Journal archive: s3://{}/{} ({} entries per segment)",
+                    bucket,
+                    config.archive_prefix,
+                    config.archive_segment_entries
+                );
+                tokio::spawn(Arc::clone(&archive).run(Arc::clone(&local)));
+                Some(Arc::new(clasp_journal::ArchivedJournal::new(local, archive))
+                    as Arc<dyn clasp_journal::Journal>)
+            }
+            (None, Some(_)) => {
+                anyhow::bail!("--archive-s3-bucket requires --journal or --journal-memory");
+            }
+            (journal, None) => journal,
+        };
+        #[cfg(not(feature = "s3"))]
+        if config.archive_s3_bucket.is_some() {
+            tracing::warn!("--archive-s3-bucket requires the 's3' feature. Rebuild with --features s3");
+        }
+
+        // Announce appends so replication streams wake immediately
+        #[cfg(feature = "replication")]
+        let journal_arc = journal_arc.map(|journal| {
+            let replicated = Arc::new(crate::replication::ReplicatedJournal::new(journal));
+            replicated_journal = Some(Arc::clone(&replicated));
+            replicated as Arc<dyn clasp_journal::Journal>
+        });
+
         journal_for_api = journal_arc.as_ref().map(Arc::clone);
 
         if let Some(journal) = journal_arc {
             router = router.with_journal(journal);
         }
+
+        // Route namespaces with their own retention to separate journals
+        if let Some(ref path) = config.journal_partitions {
+            let partitions = crate::journal_partitions::load_config(path)?;
+            for spec in &partitions.partitions {
+                router = router.with_journal_partition(spec.build()?);
+                tracing::info!("Journal partition {}: {}", spec.name, spec.pattern);
+            }
+        }
+    }
+    #[cfg(not(feature = "journal"))]
+    if config.journal_partitions.is_some() {
+        tracing::warn!("--journal-partitions requires the 'journal' feature. Rebuild with --features journal");
+    }
+
+    // Join the cluster before loading state: with_cluster rebuilds it
+    if let Some(ref url) = config.cluster_redis {
+        #[cfg(feature = "cluster")]
+        {
+            let backplane = clasp_router::RedisBackplane::new(url, config.cluster_stream.clone())
+                .context("failed to configure cluster backplane")?;
+            router = router.with_cluster(Arc::new(backplane));
+            tracing::info!("Cluster: Redis stream {} at {}", config.cluster_stream, url);
+        }
+        #[cfg(not(feature = "cluster"))]
+        {
+            let _ = url;
+            anyhow::bail!("--cluster-redis requires the 'cluster' feature. Rebuild with --features cluster");
+        }
+    }
+
+    // Wire the durable state backend and load what it holds (before journal
+    // recovery, so journal entries newer than the stored state win)
+    if let Some(ref path) = config.state_db {
+        #[cfg(feature = "state-db")]
+        {
+            let backend = clasp_router::SqliteStateBackend::open(path)
+                .with_context(|| format!("failed to open state database {}", path.display()))?;
+            router = router.with_state_backend(Arc::new(backend));
+            let count = router
+                .state()
+                .restore_from_backend()
+                .with_context(|| format!("failed to load state database {}", path.display()))?;
+            tracing::info!("State: SQLite at {} ({} params restored)", path.display(), count);
+        }
+        #[cfg(not(feature = "state-db"))]
+        {
+            let _ = path;
+            anyhow::bail!("--state-db requires the 'state-db' feature. Rebuild with --features state-db");
+        }
+    }
+
+    // Maintenance lives in router state, so set it once the state is final
+    if config.maintenance {
+        router.set_maintenance(true, config.maintenance_message.clone());
     }
 
     // Recover state from journal if available (after journal is wired, before serving)
     #[cfg(feature = "journal")]
-    if config.journal.is_some() || config.journal_memory {
+    if router.state().has_journal() {
         match router.state().recover_from_journal().await {
             Ok(count) => {
                 if count > 0 {
@@ -169,8 +474,6 @@ pub async fn run(config: RelayConfig) -> Result<()> {
 
     // Wire rules engine if configured
     #[cfg(feature = "rules")]
-    let mut interval_rules: Vec<(String, u64)> = Vec::new();
-    #[cfg(feature = "rules")]
     if let Some(ref rules_path) = config.rules {
         let json = std::fs::read_to_string(rules_path)
             .with_context(|| format!("Failed to read rules file {}", rules_path.display()))?;
@@ -178,21 +481,58 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             .with_context(|| format!("Failed to parse rules JSON from {}", rules_path.display()))?;
         let mut engine = clasp_rules::RulesEngine::new();
         for rule in &rules {
-            if let clasp_rules::Trigger::OnInterval { seconds } = &rule.trigger {
-                interval_rules.push((rule.id.clone(), *seconds));
-            }
             engine
                 .add_rule(rule.clone())
                 .with_context(|| format!("Failed to add rule '{}'", rule.id))?;
         }
         tracing::info!("Rules engine: {} rule(s) from {}", rules.len(), rules_path.display());
+
+        #[cfg(feature = "notify")]
+        {
+            use clasp_rules::notify::{SmtpBackend, TwilioBackend, WebhookBackend};
+
+            let mut notifier =
+                clasp_rules::Notifier::default().with_webhook(Arc::new(WebhookBackend::new()));
+            if let Some(ref url) = config.smtp_url {
+                let from = config
+                    .smtp_from
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("--smtp-from required with --smtp-url"))?;
+                notifier = notifier.with_email(Arc::new(
+                    SmtpBackend::new(url, from).context("Failed to configure SMTP backend")?,
+                ));
+                tracing::info!("Rules notify: email via SMTP as {}", from);
+            }
+            if let (Some(sid), Some(token), Some(from)) = (
+                config.twilio_account_sid.as_deref(),
+                config.twilio_auth_token.as_deref(),
+                config.twilio_from.as_deref(),
+            ) {
+                let mut backend = TwilioBackend::new(sid, token, from);
+                if let Some(ref base_url) = config.sms_api_url {
+                    backend = backend.with_base_url(base_url);
+                }
+                notifier = notifier.with_sms(Arc::new(backend));
+                tracing::info!("Rules notify: SMS from {}", from);
+            }
+            engine = engine.with_notifier(Arc::new(notifier));
+        }
+
         router = router.with_rules(engine);
 
-        if !interval_rules.is_empty() {
-            tracing::info!(
-                "Rules: {} interval trigger(s) registered",
-                interval_rules.len()
-            );
+        let intervals = rules
+            .iter()
+            .filter(|r| matches!(r.trigger, clasp_rules::Trigger::OnInterval { .. }))
+            .count();
+        if intervals > 0 {
+            tracing::info!("Rules: {} interval trigger(s) registered", intervals);
+        }
+        let schedules = rules
+            .iter()
+            .filter(|r| matches!(r.trigger, clasp_rules::Trigger::OnSchedule { .. }))
+            .count();
+        if schedules > 0 {
+            tracing::info!("Rules: {} schedule trigger(s) registered", schedules);
         }
     }
 
@@ -212,26 +552,44 @@ pub async fn run(config: RelayConfig) -> Result<()> {
 
     // Set up write validation and snapshot filtering.
     // Explicit config.write_validator / .snapshot_filter (library API) takes precedence.
-    // Otherwise, if app_config has rules, create rule-based validators.
-    if let Some(validator) = config.write_validator {
-        router.set_write_validator_arc(validator);
+    // Otherwise, if app_config has rules, create rule-based validators. A watched
+    // app config file gets them even without rules, so rules added later apply.
+    let app_config_watched = config.app_config_path.is_some();
+    let mut live_app_config = crate::app_config::LiveAppConfig::default();
+    let mut write_validator: Option<Arc<dyn clasp_router::WriteValidator>> = None;
+    if let Some(ref validator) = config.write_validator {
+        write_validator = Some(Arc::clone(validator));
         tracing::info!("Custom write validator enabled (library override)");
     } else if let Some(ref ac) = config.app_config {
-        if !ac.write_rules.is_empty() {
-            let v = Arc::new(crate::app_config::RuleWriteValidator::new(ac.write_rules.clone()));
-            router.set_write_validator_arc(v);
+        if !ac.write_rules.is_empty() || app_config_watched {
+            let validator = Arc::new(crate::app_config::RuleWriteValidator::new(ac.write_rules.clone()));
+            live_app_config.write_validator = Some(Arc::clone(&validator));
+            write_validator = Some(validator);
             tracing::info!("Rule-based write validator: {} rule(s) from app config", ac.write_rules.len());
         }
     }
-    if let Some(filter) = config.snapshot_filter {
-        router.set_snapshot_filter_arc(filter);
+    // A standby rejects client writes until it is promoted
+    #[cfg(feature = "replication")]
+    if replication.is_standby() {
+        write_validator = Some(Arc::new(crate::replication::StandbyGuard::new(
+            Arc::clone(&replication),
+            write_validator,
+        )));
+        tracing::info!("Replication: standby of {}", config.replicate_from.as_deref().unwrap_or_default());
+    }
+    if let Some(validator) = write_validator {
+        router.set_write_validator_arc(validator);
+    }
+    if let Some(ref filter) = config.snapshot_filter {
+        router.set_snapshot_filter_arc(Arc::clone(filter));
         tracing::info!("Custom snapshot filter enabled (library override)");
     } else if let Some(ref ac) = config.app_config {
-        if !ac.snapshot_transforms.is_empty() || !ac.snapshot_visibility.is_empty() {
+        if !ac.snapshot_transforms.is_empty() || !ac.snapshot_visibility.is_empty() || app_config_watched {
             let f = Arc::new(crate::app_config::RuleSnapshotFilter::new(
                 ac.snapshot_transforms.clone(),
                 ac.snapshot_visibility.clone(),
             ));
+            live_app_config.snapshot_filter = Some(Arc::clone(&f));
             router.set_snapshot_filter_arc(f);
             tracing::info!(
                 "Rule-based snapshot filter: {} transform(s), {} visibility rule(s) from app config",
@@ -275,9 +633,54 @@ pub async fn run(config: RelayConfig) -> Result<()> {
         }
     }
 
+    // Seed default params after any restore, so restored values win
+    if let Some(ref seed_path) = config.seed {
+        let count = router
+            .state()
+            .seed_from_file(seed_path)
+            .with_context(|| format!("failed to apply seed file {}", seed_path.display()))?;
+        tracing::info!("Seeded {} params from {}", count, seed_path.display());
+    }
+
+    // Bring state written before canonicalization (or under looser limits)
+    // into canonical form, now that every source has been loaded
+    let report = router.state().canonicalize(&address_policy);
+    if !report.is_empty() {
+        tracing::info!(
+            "Canonicalized state: {} params renamed, {} merged, {} dropped as invalid",
+            report.renamed.len(),
+            report.merged.len(),
+            report.invalid.len()
+        );
+        for address in &report.invalid {
+            tracing::warn!("Dropped param with invalid address {}", address);
+        }
+    }
+
     // Create shared validator and start auth HTTP server if enabled
     #[cfg(feature = "registry")]
     let mut entity_store: Option<Arc<dyn clasp_registry::EntityStore>> = None;
+    #[cfg(feature = "registry")]
+    let mut entity_cache = None;
+    #[cfg(feature = "caps")]
+    let mut cap_audit: Option<Arc<dyn clasp_caps::AuditLog>> = None;
+    #[cfg(feature = "caps")]
+    let mut guest_links: Option<(ed25519_dalek::SigningKey, Arc<dyn clasp_caps::UseCounter>)> = None;
+    #[cfg(feature = "caps")]
+    let mut exchange_key: Option<ed25519_dalek::SigningKey> = None;
+    #[cfg(feature = "registry")]
+    let mut entity_configs: Option<Arc<dyn clasp_registry::ConfigStore>> = None;
+    #[cfg(feature = "registry")]
+    let mut entity_fleets: Option<Arc<dyn clasp_registry::FleetStore>> = None;
+    #[cfg(not(all(feature = "registry", feature = "federation")))]
+    if config.registry_sync {
+        anyhow::bail!("--registry-sync requires the 'registry' and 'federation' features. Rebuild with --features registry,federation");
+    }
+    #[cfg(feature = "federation")]
+    #[allow(unused_mut)]
+    let mut registry_sync: Option<Arc<crate::registry_sync::RegistrySync>> = None;
+    #[cfg(feature = "push")]
+    let mut push_gateway: Option<Arc<crate::push::PushGateway>> = None;
 
     if let Some(auth_port) = config.auth_port {
         let cpsk_validator = Arc::new(if config.token_ttl > 0 {
@@ -321,11 +724,24 @@ pub async fn run(config: RelayConfig) -> Result<()> {
         }
 
         let mut chain = ValidatorChain::new();
+        if config.validator_timeout_ms > 0 {
+            chain.set_timeout(Some(Duration::from_millis(config.validator_timeout_ms)));
+        }
         chain.add(SharedValidator(Arc::clone(&cpsk_validator)));
 
         // Add capability token validator if trust anchors provided
         #[cfg(feature = "caps")]
-        if !config.trust_anchor.is_empty() {
+        if !config.trust_anchor.is_empty()
+            || config.guest_link_key.is_some()
+            || config.token_exchange_key.is_some()
+        {
+            let guest_key = match config.guest_link_key {
+                Some(ref path) => Some(crate::guest_links::load_signing_key(path)?),
+                None => None,
+            };
+            if let Some(ref path) = config.token_exchange_key {
+                exchange_key = Some(crate::guest_links::load_signing_key(path)?);
+            }
             let anchors: Vec<Vec<u8>> = {
                 let mut result = Vec::new();
                 for p in &config.trust_anchor {
@@ -357,20 +773,79 @@ pub async fn run(config: RelayConfig) -> Result<()> {
                         );
                     }
                 }
+                // Guest link tokens are signed with their own key
+                if let Some(ref key) = guest_key {
+                    result.push(key.verifying_key().to_bytes().to_vec());
+                }
+                // So are tokens issued by /auth/exchange
+                if let Some(ref key) = exchange_key {
+                    result.push(key.verifying_key().to_bytes().to_vec());
+                }
                 result
             };
-            chain.add(clasp_caps::CapabilityValidator::new(anchors, config.cap_max_depth));
+            let mut validator =
+                clasp_caps::CapabilityValidator::new(anchors, config.cap_max_depth);
+            if let Some(ref path) = config.cap_revocation_db {
+                let store = clasp_caps::SqliteRevocationStore::open(&path.to_string_lossy())
+                    .with_context(|| {
+                        format!("Failed to open revocation list {}", path.display())
+                    })?;
+                tracing::info!(
+                    "Capability revocation list: {} ({} entries)",
+                    path.display(),
+                    clasp_caps::RevocationStore::list(&store).map_or(0, |l| l.len())
+                );
+                validator = validator.with_revocations(Arc::new(store));
+            }
+            if let Some(ref path) = config.cap_audit_db {
+                let log: Arc<dyn clasp_caps::AuditLog> = Arc::new(
+                    clasp_caps::SqliteAuditLog::open(&path.to_string_lossy()).with_context(
+                        || format!("Failed to open capability audit log {}", path.display()),
+                    )?,
+                );
+                tracing::info!("Capability audit log: {}", path.display());
+                validator = validator.with_audit(Arc::clone(&log));
+                cap_audit = Some(log);
+            }
+            let mut use_counter: Option<Arc<dyn clasp_caps::UseCounter>> = None;
+            if let Some(ref path) = config.cap_uses_db {
+                use_counter = Some(Arc::new(
+                    clasp_caps::SqliteUseCounter::open(&path.to_string_lossy()).with_context(
+                        || format!("Failed to open capability use counter {}", path.display()),
+                    )?,
+                ));
+                tracing::info!("Capability use counter: {}", path.display());
+            } else if guest_key.is_some() {
+                use_counter = Some(Arc::new(clasp_caps::MemoryUseCounter::new()));
+            }
+            if let Some(ref counter) = use_counter {
+                validator = validator.with_use_counter(Arc::clone(counter));
+            }
+            // Cached results would skip the audit log and the use counter, so
+            // those tokens are validated on every presentation
+            if config.validation_cache_ttl > 0 && cap_audit.is_none() && use_counter.is_none() {
+                chain.add(clasp_core::CachingValidator::new(
+                    validator,
+                    config.validation_cache_size,
+                    Duration::from_secs(config.validation_cache_ttl),
+                ));
+            } else {
+                chain.add(validator);
+            }
             tracing::info!(
                 "Capability tokens: {} trust anchor(s), max depth {}",
                 config.trust_anchor.len(),
                 config.cap_max_depth
             );
+            if let (Some(key), Some(counter)) = (guest_key, use_counter) {
+                guest_links = Some((key, counter));
+            }
         }
 
         // Add entity registry validator if configured
         #[cfg(feature = "registry")]
         if let Some(ref db_path) = config.registry_db {
-            let store: Arc<dyn clasp_registry::EntityStore> = Arc::new(
+            let sqlite = Arc::new(
                 clasp_registry::SqliteEntityStore::open(
                     db_path
                         .to_str()
@@ -378,12 +853,44 @@ pub async fn run(config: RelayConfig) -> Result<()> {
                 )
                 .expect("Failed to open entity registry database"),
             );
-            chain.add(clasp_registry::EntityValidator::new(Arc::clone(&store)));
+            let store: Arc<dyn clasp_registry::EntityStore> = sqlite.clone();
+            let configs: Arc<dyn clasp_registry::ConfigStore> = sqlite.clone();
+            router.set_config_source(crate::registry::RegistryConfigSource(Arc::clone(&configs)));
+            entity_configs = Some(configs);
+            let fleets: Arc<dyn clasp_registry::FleetStore> = sqlite;
+            router.set_fleet_source(crate::registry::RegistryFleetSource(Arc::clone(&fleets)));
+            entity_fleets = Some(fleets);
+            let validator = clasp_registry::EntityValidator::new(Arc::clone(&store));
+            if config.validation_cache_ttl > 0 {
+                let cache = Arc::new(clasp_core::CachingValidator::new(
+                    validator,
+                    config.validation_cache_size,
+                    Duration::from_secs(config.validation_cache_ttl),
+                ));
+                chain.add_async(crate::registry::SharedEntityValidator(Arc::clone(&cache)));
+                entity_cache = Some(cache);
+            } else {
+                chain.add_async(validator);
+            }
+            #[cfg(feature = "federation")]
+            if config.registry_sync {
+                let (sessions, _, _) = router.shared_state();
+                let mut sync = crate::registry_sync::from_config(&config, Arc::clone(&store), sessions)?;
+                if let Some(ref cache) = entity_cache {
+                    sync = sync.with_validation_cache(Arc::clone(cache));
+                }
+                let sync = Arc::new(sync);
+                router.set_registry_replica(sync.clone());
+                registry_sync = Some(sync);
+            }
             entity_store = Some(store);
             tracing::info!("Entity registry: {}", db_path.display());
         }
 
-        router.set_validator(chain);
+        // The chain is shared so HTTP APIs accept the same tokens as the router
+        let chain = Arc::new(chain);
+        router.set_validator(SharedChain(Arc::clone(&chain)));
+        router.set_async_validator(SharedChain(Arc::clone(&chain)));
 
         // Extract scope templates and rate limits from app config
         let scope_templates = config.app_config.as_ref().map(|ac| ac.scopes.clone());
@@ -402,15 +909,23 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             tracing::warn!("No app config scopes -- tokens will have full read/write access");
         }
 
-        let auth_state = Arc::new(
-            crate::auth::AuthState::new(
-                &config.auth_db,
-                Arc::clone(&cpsk_validator),
-                scope_templates,
-                rate_config,
-            )
-            .expect("Failed to initialize auth database"),
-        );
+        #[allow(unused_mut)]
+        let mut auth_state = crate::auth::AuthState::new(
+            &config.auth_db,
+            Arc::clone(&cpsk_validator),
+            scope_templates,
+            rate_config,
+        )
+        .expect("Failed to initialize auth database");
+        #[cfg(feature = "caps")]
+        if let Some(key) = exchange_key.take() {
+            auth_state = auth_state.with_exchange_key(key);
+            tracing::info!("Token exchange mounted at /auth/exchange");
+        }
+        let auth_state = Arc::new(auth_state);
+        if config.app_config.is_some() {
+            live_app_config.auth = Some(Arc::clone(&auth_state));
+        }
         #[allow(unused_mut)]
         let mut auth_app = crate::auth::auth_router(auth_state, config.cors_origin.as_deref());
 
@@ -445,15 +960,37 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             #[cfg(not(feature = "caps"))]
             let trust_anchor_hexes: Vec<String> = Vec::new();
 
-            let reg_state = Arc::new(
-                crate::registry::RegistryState::new(
-                    Arc::clone(store),
-                    Arc::clone(&cpsk_validator),
-                )
-                .with_trust_anchors(trust_anchor_hexes, config.cap_max_depth)
-            );
+            let mut reg_state = crate::registry::RegistryState::new(
+                Arc::clone(store),
+                Arc::clone(&cpsk_validator),
+            )
+            .with_trust_anchors(trust_anchor_hexes, config.cap_max_depth);
+            if let Some(ref cache) = entity_cache {
+                reg_state = reg_state.with_validation_cache(Arc::clone(cache));
+            }
+            if let Some(ref configs) = entity_configs {
+                let (sessions, subscriptions, state) = router.shared_state();
+                reg_state = reg_state.with_configs(
+                    Arc::clone(configs),
+                    crate::registry::RouterHandles {
+                        sessions,
+                        subscriptions,
+                        state,
+                    },
+                );
+            }
+            if let Some(ref fleets) = entity_fleets {
+                reg_state = reg_state.with_fleets(Arc::clone(fleets));
+            }
+            #[cfg(feature = "federation")]
+            if let Some(ref sync) = registry_sync {
+                reg_state = reg_state.with_sync(Arc::clone(sync));
+            }
+            let reg_state = Arc::new(reg_state);
             auth_app = auth_app.merge(crate::registry::registry_router(reg_state));
             tracing::info!("Entity REST API mounted at /api/entities (admin auth required)");
+            tracing::info!("Entity config API mounted at /api/entities/{{id}}/config");
+            tracing::info!("Fleet API mounted at /api/fleets (admin auth required)");
             tracing::info!("Trust anchors API mounted at /api/trust-anchors (public)");
         }
 
@@ -468,6 +1005,97 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             tracing::info!("Journal REST API mounted at /api/journal/* (admin auth required)");
         }
 
+        // Mount capability audit log routes if configured
+        #[cfg(feature = "caps")]
+        if let Some(ref log) = cap_audit {
+            let audit_state = Arc::new(crate::cap_audit::CapAuditApiState {
+                log: Arc::clone(log),
+                validator: Arc::clone(&cpsk_validator),
+            });
+            auth_app = auth_app.merge(crate::cap_audit::cap_audit_router(audit_state));
+            tracing::info!("Capability audit API mounted at /api/caps/audit (admin auth required)");
+        }
+
+        // Mount guest link routes if a guest link key is configured
+        #[cfg(feature = "caps")]
+        if let Some((key, uses)) = guest_links.take() {
+            let guest_state = Arc::new(crate::guest_links::GuestLinkState::new(
+                key,
+                uses,
+                Arc::clone(&cpsk_validator),
+                config.guest_link_url.clone(),
+            ));
+            auth_app = auth_app.merge(crate::guest_links::guest_link_router(guest_state));
+            tracing::info!("Guest link API mounted at /api/guest-links and /g/{{code}}");
+        }
+
+        // Mount replication stream, status and promotion routes
+        #[cfg(feature = "replication")]
+        {
+            let replication_state = Arc::new(crate::replication::ReplicationApiState {
+                replication: Arc::clone(&replication),
+                journal: replicated_journal.as_ref().map(Arc::clone),
+                state: router.shared_state().2,
+                validator: Arc::clone(&cpsk_validator),
+            });
+            auth_app = auth_app.merge(crate::replication::replication_router(replication_state));
+            tracing::info!("Replication API mounted at /api/replication/* (admin auth required)");
+        }
+
+        // Mount bulk state import/export routes
+        #[cfg(feature = "state-api")]
+        {
+            let (sessions, subscriptions, state) = router.shared_state();
+            let state_api = Arc::new(crate::state_api::StateApiState {
+                sessions,
+                subscriptions,
+                state,
+                validator: Arc::clone(&cpsk_validator),
+            });
+            auth_app = auth_app.merge(crate::state_api::state_router(state_api));
+            tracing::info!("State import/export API mounted at /api/state/* (admin auth required)");
+        }
+
+        // Mount push device registration and build the gateway if configured
+        #[cfg(feature = "push")]
+        if let Some(ref push_path) = config.push_config {
+            let push_config = crate::push::load_config(push_path)?;
+            let store = Arc::new(
+                crate::push::DeviceStore::open(&config.auth_db)
+                    .context("Failed to open push device store")?,
+            );
+            let sender = Arc::new(crate::push::ProviderSender::from_config(&push_config)?);
+            tracing::info!(
+                "Push: {} rule(s) from {}",
+                push_config.rules.len(),
+                push_path.display()
+            );
+            push_gateway = Some(Arc::new(crate::push::PushGateway::new(
+                push_config,
+                Arc::clone(&store),
+                sender,
+            )));
+            let push_state = Arc::new(crate::push::PushApiState {
+                store,
+                validator: Arc::clone(&cpsk_validator),
+            });
+            auth_app = auth_app.merge(crate::push::push_router(push_state));
+            tracing::info!("Push device API mounted at /api/push/devices (bearer auth required)");
+        }
+
+        // Mount the GraphQL facade if enabled
+        #[cfg(feature = "graphql")]
+        if config.graphql {
+            let schema =
+                crate::graphql::build_schema(crate::graphql::RouterHandles::new(&router));
+            let graphql_state = Arc::new(crate::graphql::GraphqlApiState {
+                schema,
+                validator: Arc::new(SharedChain(Arc::clone(&chain))),
+            });
+            auth_app = auth_app.merge(crate::graphql::graphql_router(graphql_state));
+            tracing::info!("GraphQL API mounted at /graphql (bearer auth required)");
+        }
+
         let auth_addr: SocketAddr = format!("{}:{}", config.host, auth_port).parse()?;
         tracing::info!("Auth HTTP: http://{}", auth_addr);
 
@@ -513,22 +1141,8 @@ pub async fn run(config: RelayConfig) -> Result<()> {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("--key required for QUIC"))?;
 
-        // Load certificate and key
-        let cert_pem = std::fs::read(cert_path)?;
-        let key_pem = std::fs::read(key_path)?;
-
-        // Parse PEM to DER
-        let cert_der = rustls_pemfile::certs(&mut cert_pem.as_slice())
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("No certificate found in PEM file"))?
-            .to_vec();
-
-        let key_der = rustls_pemfile::private_key(&mut key_pem.as_slice())?
-            .ok_or_else(|| anyhow::anyhow!("No private key found in PEM file"))?
-            .secret_der()
-            .to_vec();
+        // Load certificate and key (PEM to DER)
+        let (cert_der, key_der) = crate::cert_reload::load_pem(cert_path, key_path)?;
 
         let addr: SocketAddr = format!("{}:{}", config.host, quic_port).parse()?;
         tracing::info!("QUIC: {}", addr);
@@ -546,6 +1160,20 @@ pub async fn run(config: RelayConfig) -> Result<()> {
     #[cfg(not(feature = "quic"))]
     let _quic_config: Option<()> = None;
 
+    // WebRTC
+    #[cfg(feature = "webrtc")]
+    let webrtc_addr = config.webrtc_port.map(|webrtc_port| {
+        let addr = format!("{}:{}", config.host, webrtc_port);
+        tracing::info!("WebRTC: http://{}/offer", addr);
+        protocols.push("WebRTC");
+        addr
+    });
+
+    #[cfg(not(feature = "webrtc"))]
+    if config.webrtc_port.is_some() {
+        anyhow::bail!("--webrtc-port requires the 'webrtc' feature. Rebuild with --features webrtc");
+    }
+
     // MQTT
     #[cfg(feature = "mqtt-server")]
     let mqtt_config = if let Some(mqtt_port) = config.mqtt_port {
@@ -588,8 +1216,28 @@ pub async fn run(config: RelayConfig) -> Result<()> {
     #[cfg(not(feature = "osc-server"))]
     let _osc_config: Option<()> = None;
 
+    // RESP (Redis protocol)
+    #[cfg(feature = "resp-server")]
+    let resp_config = if let Some(resp_port) = config.resp_port {
+        let addr = format!("{}:{}", config.host, resp_port);
+        tracing::info!("RESP: redis://{}", addr);
+        protocols.push("RESP");
+
+        Some(clasp_router::RespServerConfig {
+            bind_addr: addr,
+            namespace: config.resp_namespace.clone(),
+            require_auth: auth_enabled,
+            max_clients: config.max_sessions,
+        })
+    } else {
+        None
+    };
+
+    #[cfg(not(feature = "resp-server"))]
+    let _resp_config: Option<()> = None;
+
     if protocols.is_empty() {
-        anyhow::bail!("No protocols enabled. Enable at least one of: WebSocket, QUIC, MQTT, OSC");
+        anyhow::bail!("No protocols enabled. Enable at least one of: WebSocket, QUIC, MQTT, OSC, RESP");
     }
 
     tracing::info!("Server name: {}", config.name);
@@ -611,12 +1259,21 @@ pub async fn run(config: RelayConfig) -> Result<()> {
     let multi_config = MultiProtocolConfig {
         #[cfg(feature = "websocket")]
         websocket_addr,
+        #[cfg(feature = "websocket")]
+        websocket_acceptors: config.ws_acceptors,
         #[cfg(feature = "quic")]
         quic: quic_config,
+        #[cfg(feature = "webrtc")]
+        webrtc_addr,
         #[cfg(feature = "mqtt-server")]
         mqtt: mqtt_config,
         #[cfg(feature = "osc-server")]
         osc: osc_config,
+        #[cfg(feature = "resp-server")]
+        resp: resp_config,
+        // The relay serves its own exporter on --metrics-port
+        #[cfg(feature = "metrics")]
+        metrics_addr: None,
     };
 
     // Log persistence config
@@ -662,30 +1319,65 @@ pub async fn run(config: RelayConfig) -> Result<()> {
     // Get shared state refs for persistence, rules, and federation tasks
     let (sessions_arc, subscriptions_arc, state_arc) = router.shared_state();
 
-    // Spawn interval rule timer tasks
-    #[cfg(feature = "rules")]
-    if !interval_rules.is_empty() {
-        if let Some(rules_engine) = router.rules_engine().cloned() {
-            for (rule_id, seconds) in interval_rules {
-                let engine = Arc::clone(&rules_engine);
-                let state = Arc::clone(&state_arc);
-                let sessions = Arc::clone(&sessions_arc);
-                let subs = Arc::clone(&subscriptions_arc);
-                tokio::spawn(async move {
-                    let mut interval = tokio::time::interval(Duration::from_secs(seconds));
-                    loop {
-                        interval.tick().await;
-                        let actions = engine.lock().evaluate_interval(
-                            &rule_id,
-                            |addr| state.get(addr),
-                        );
-                        if !actions.is_empty() {
-                            clasp_router::execute_rule_actions(actions, &state, &sessions, &subs);
-                        }
-                    }
-                });
-            }
-        }
+    // Start push notification gateway if configured
+    #[cfg(feature = "push")]
+    if let Some(gateway) = push_gateway {
+        let push_sessions = Arc::clone(&sessions_arc);
+        let push_subs = Arc::clone(&subscriptions_arc);
+        tokio::spawn(async move {
+            crate::push::run_push_gateway(gateway, push_sessions, push_subs).await;
+        });
+    } else if config.push_config.is_some() {
+        tracing::warn!("--push-config requires --auth-port (devices are registered per subject); push disabled");
+    }
+
+    // Start the time-series sink if configured
+    #[cfg(feature = "timeseries")]
+    if let Some(ref ts_path) = config.timeseries_config {
+        let ts_config = crate::timeseries::load_config(ts_path)?;
+        let writer = crate::timeseries::build_writer(&ts_config)?;
+        tracing::info!(
+            "Timeseries: {} rule(s) from {}",
+            ts_config.rules.len(),
+            ts_path.display()
+        );
+        let sink = crate::timeseries::TimeseriesSink::new(ts_config, writer);
+        let ts_sessions = Arc::clone(&sessions_arc);
+        let ts_subs = Arc::clone(&subscriptions_arc);
+        tokio::spawn(async move {
+            crate::timeseries::run_timeseries_sink(sink, ts_sessions, ts_subs).await;
+        });
+    }
+    #[cfg(not(feature = "timeseries"))]
+    if config.timeseries_config.is_some() {
+        tracing::warn!("--timeseries-config requires the 'timeseries' feature. Rebuild with --features timeseries");
+    }
+
+    // Start the read model projector if configured
+    #[cfg(feature = "projector")]
+    if let Some(ref projector_path) = config.projector_config {
+        let Some(ref journal) = journal_for_api else {
+            anyhow::bail!("--projector-config requires --journal or --journal-memory");
+        };
+        let projector_config = crate::projector::load_config(projector_path)?;
+        let tables = crate::projector::Projector::table_specs(&projector_config)?;
+        let store = crate::projector::build_store(&projector_config, &tables)?;
+        tracing::info!(
+            "Projector: {} table(s) from {}",
+            tables.len(),
+            projector_path.display()
+        );
+        let projector = crate::projector::Projector::new(
+            projector_config,
+            tables,
+            Arc::clone(journal),
+            store,
+        );
+        tokio::spawn(crate::projector::run_projector(projector));
+    }
+    #[cfg(not(feature = "projector"))]
+    if config.projector_config.is_some() {
+        tracing::warn!("--projector-config requires the 'projector' feature. Rebuild with --features projector");
     }
 
     // Spawn background persistence task if --persist is set
@@ -703,7 +1395,14 @@ pub async fn run(config: RelayConfig) -> Result<()> {
         });
     }
 
-    // Start federation leaf if configured
+    #[cfg(feature = "federation")]
+    if config.registry_sync && registry_sync.is_none() {
+        anyhow::bail!("--registry-sync needs an entity registry (--registry-db and --auth-port)");
+    }
+
+    // Start federation leaf if configured, keeping a handle to stop it on shutdown
+    #[cfg(feature = "federation")]
+    let mut federation_leaf = None;
     #[cfg(feature = "federation")]
     if let Some(ref hub_url) = config.federation_hub {
         let fed_config = clasp_federation::FederationConfig {
@@ -724,24 +1423,83 @@ pub async fn run(config: RelayConfig) -> Result<()> {
                 config.federation_namespace.clone()
             },
             auth_token: config.federation_token.clone(),
+            journal_sync: config.federation_journal_sync,
+            registry_sync: registry_sync.is_some(),
             ..Default::default()
         };
         let fed_state = Arc::clone(&state_arc);
         let fed_sessions = Arc::clone(&sessions_arc);
         let fed_subs = Arc::clone(&subscriptions_arc);
+        let fed_registry = registry_sync.clone();
         tracing::info!(
             "Federation: leaf mode, hub={}, id={}, namespaces={:?}",
             hub_url,
             fed_config.router_id,
             fed_config.owned_namespaces
         );
+        let (fed_stop, fed_stop_rx) = tokio::sync::watch::channel(false);
+        let leaf = tokio::spawn(async move {
+            crate::federation::run_federation_leaf(fed_config, fed_state, fed_sessions, fed_subs, fed_registry, fed_stop_rx).await;
+        });
+        federation_leaf = Some((fed_stop, leaf));
+    }
+
+    // Follow the primary if this relay is a standby
+    #[cfg(feature = "replication")]
+    if let (Some(primary), Some(token)) = (config.replicate_from.clone(), config.replication_token.clone()) {
+        if config.auth_port.is_none() {
+            tracing::warn!("Replication: no --auth-port, so this standby can only be promoted by restarting without --replicate-from");
+        }
+        let follower_config = crate::replication::FollowerConfig {
+            primary,
+            token,
+            id: config.replication_id.clone(),
+        };
+        tokio::spawn(crate::replication::run_follower(
+            follower_config,
+            Arc::clone(&replication),
+            Arc::clone(&state_arc),
+            Arc::clone(&sessions_arc),
+            Arc::clone(&subscriptions_arc),
+        ));
+    }
+
+    // Serve /metrics and the OpenMetrics param export
+    #[cfg(feature = "metrics")]
+    if let Some((metrics_addr, handle)) = metrics_server {
+        let upkeep = handle.clone();
         tokio::spawn(async move {
-            crate::federation::run_federation_leaf(fed_config, fed_state, fed_sessions, fed_subs).await;
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                upkeep.run_upkeep();
+            }
+        });
+        let param_state = Arc::new(crate::param_metrics::ParamMetricsState {
+            state: Arc::clone(&state_arc),
+            patterns: config.metrics_param.clone(),
+        });
+        let app = crate::param_metrics::param_metrics_router(param_state).route(
+            "/metrics",
+            axum::routing::get(move || std::future::ready(handle.render())),
+        );
+        let listener = tokio::net::TcpListener::bind(metrics_addr)
+            .await
+            .context("Failed to bind metrics listener")?;
+        tracing::info!(
+            "Param metrics: http://{}/metrics/params ({})",
+            metrics_addr,
+            config.metrics_param.join(", ")
+        );
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("Metrics server error: {}", e);
+            }
         });
     }
 
     // Start health check server if configured
-    let health_state = Arc::new(crate::health::HealthState::new());
+    let health_state = Arc::new(crate::health::HealthState::new(router.overload()));
     if let Some(health_port) = config.health_port {
         let health_addr: SocketAddr = format!("{}:{}", config.host, health_port)
             .parse()
@@ -755,38 +1513,167 @@ pub async fn run(config: RelayConfig) -> Result<()> {
     // Mark as ready
     health_state.ready.store(true, std::sync::atomic::Ordering::Relaxed);
 
-    // Run serve_all alongside a shutdown signal listener
-    let shutdown_state = Arc::clone(&state_arc);
-    let persist_path_shutdown = config.persist.clone();
-    let drain_timeout = config.drain_timeout;
+    // Pick up renewed QUIC certificates without a restart
+    #[cfg(feature = "quic")]
+    if let (Some(_), Some(cert), Some(key)) = (config.quic_port, config.cert.clone(), config.key.clone()) {
+        let certs = router.quic_certificates();
+        let interval = config.cert_reload_interval;
+        if interval.is_zero() {
+            tracing::info!("Certificate reload: on SIGHUP");
+        } else {
+            tracing::info!("Certificate reload: every {:?} or on SIGHUP", interval);
+        }
+        tokio::spawn(crate::cert_reload::watch(certs, cert, key, interval));
+    }
 
+    // Apply edits to the app config file without dropping sessions
+    if let Some(path) = config.app_config_path.clone() {
+        let interval = config.app_config_reload_interval;
+        if interval.is_zero() {
+            tracing::info!("App config reload: on SIGHUP");
+        } else {
+            tracing::info!("App config reload: every {:?} or on SIGHUP", interval);
+        }
+        tokio::spawn(watch_app_config(live_app_config, path, interval));
+    }
+
+    // Shutdown steps, run in order within --shutdown-grace
+    let router = Arc::new(router);
+    let mut hooks = crate::shutdown::ShutdownHooks::new(config.shutdown_grace);
+
+    // Draining gets at most half the grace so the flush and checkpoint fit
+    let drain_timeout = config.drain_timeout.min(config.shutdown_grace / 2);
+    {
+        let router = Arc::clone(&router);
+        hooks.add("drain sessions", move || async move {
+            let closed = router.shutdown("Relay shutting down", drain_timeout).await;
+            if closed > 0 {
+                tracing::info!("Closed {} session(s) still open after {:?}", closed, drain_timeout);
+            }
+        });
+    }
+
+    #[cfg(feature = "federation")]
+    if let Some((stop, leaf)) = federation_leaf {
+        hooks.add("notify federation peers", move || async move {
+            let _ = stop.send(true);
+            let _ = leaf.await;
+        });
+    }
+
+    #[cfg(feature = "journal")]
+    if state_arc.has_journal() {
+        let state = Arc::clone(&state_arc);
+        hooks.add("flush journal", move || async move {
+            while state.journal_lag() > 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        let state = Arc::clone(&state_arc);
+        hooks.add("checkpoint journal", move || async move {
+            match state.save_snapshot().await {
+                Ok(seq) => tracing::info!("Journal snapshot at seq {}", seq),
+                Err(e) => tracing::error!("Journal snapshot failed: {}", e),
+            }
+        });
+    }
+
+    if let Some(path) = config.persist.clone() {
+        let state = Arc::clone(&state_arc);
+        hooks.add("write snapshot", move || async move {
+            let _ = tokio::task::spawn_blocking(move || write_snapshot(&state, &path)).await;
+        });
+    }
+
+    // Run serve_all alongside a shutdown signal listener
     tokio::select! {
         result = router.serve_all(multi_config) => {
             result?;
         }
         _ = shutdown_signal() => {
-            tracing::info!("Shutdown signal received, starting graceful drain...");
+            tracing::info!("Shutdown signal received (grace: {:?})", hooks.grace());
 
             // Mark as not ready so load balancers stop sending traffic
             health_state.ready.store(false, std::sync::atomic::Ordering::Relaxed);
 
-            // Wait for in-flight messages to drain
-            tracing::info!("Draining connections (timeout: {:?})...", drain_timeout);
-            tokio::time::sleep(drain_timeout).await;
-
-            // Write final snapshot
-            if let Some(ref path) = persist_path_shutdown {
-                tracing::info!("Writing final snapshot before exit...");
-                write_snapshot(&shutdown_state, path);
+            let report = hooks.run().await;
+            if report.timed_out.is_none() {
+                tracing::info!("Shutdown complete");
+            } else {
+                tracing::warn!("Shutdown cut short by the grace period, exiting");
             }
-
-            tracing::info!("Shutdown complete");
         }
     }
 
     Ok(())
 }
 
+/// Reload the app config whenever its file changes or SIGHUP arrives
+///
+/// Write rules, snapshot rules, auth scopes and auth rate limits are swapped
+/// in place. A file that fails to parse is logged and the old config kept.
+/// `interval` of zero disables polling, leaving only SIGHUP.
+pub async fn watch_app_config(
+    live: crate::app_config::LiveAppConfig,
+    path: std::path::PathBuf,
+    interval: Duration,
+) {
+    let modified = |p: &std::path::Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    let mut last = modified(&path);
+
+    #[cfg(unix)]
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => Some(signal),
+        Err(e) => {
+            tracing::warn!("Failed to register SIGHUP handler: {}", e);
+            None
+        }
+    };
+
+    loop {
+        let poll = async {
+            if interval.is_zero() {
+                std::future::pending::<()>().await
+            } else {
+                tokio::time::sleep(interval).await
+            }
+        };
+        #[cfg(unix)]
+        let forced = tokio::select! {
+            _ = poll => false,
+            Some(_) = async {
+                match hangup.as_mut() {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => true,
+        };
+        #[cfg(not(unix))]
+        let forced = {
+            poll.await;
+            false
+        };
+
+        let current = modified(&path);
+        if !forced && current == last {
+            continue;
+        }
+        last = current;
+        match crate::app_config::AppConfig::load(&path) {
+            Ok(app_config) => {
+                live.apply(&app_config);
+                tracing::info!(
+                    "App config reloaded from {}: {} write rule(s), {} scope(s)",
+                    path.display(),
+                    app_config.write_rules.len(),
+                    app_config.scopes.len()
+                );
+            }
+            Err(e) => tracing::error!("App config reload failed, keeping the old one: {:#}", e),
+        }
+    }
+}
+
 /// Write a state snapshot atomically (write to .tmp then rename).
 fn write_snapshot(state: &RouterState, path: &std::path::Path) {
     let snapshot = state.full_snapshot();
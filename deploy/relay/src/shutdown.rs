@@ -0,0 +1,84 @@
+//! Ordered shutdown steps bounded by a grace period
+//!
+//! On SIGTERM or Ctrl-C the relay runs its shutdown steps in the order they
+//! were added: drain sessions, notify federation peers, flush the journal,
+//! checkpoint state. The whole sequence must finish within `--shutdown-grace`,
+//! which should be a little under the orchestrator's termination grace period
+//! (30s by default in Kubernetes). A step still running at the deadline is
+//! abandoned and the steps after it are skipped, so the process always exits
+//! before it is killed.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::time::Instant;
+
+type Step = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Shutdown steps run in order within a grace period
+pub struct ShutdownHooks {
+    grace: Duration,
+    steps: Vec<(&'static str, Step)>,
+}
+
+/// What happened when the hooks ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Steps that finished, in order
+    pub completed: Vec<&'static str>,
+    /// The step that was running when the grace period ran out
+    pub timed_out: Option<&'static str>,
+    /// Steps never started because the grace period ran out
+    pub skipped: Vec<&'static str>,
+}
+
+impl ShutdownHooks {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            steps: Vec::new(),
+        }
+    }
+
+    /// The grace period every step shares
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Add a step, run after the ones already added
+    pub fn add<F, Fut>(&mut self, name: &'static str, step: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.steps.push((name, Box::new(move || Box::pin(step()))));
+    }
+
+    /// Run every step in order, stopping at the end of the grace period
+    pub async fn run(self) -> ShutdownReport {
+        let deadline = Instant::now() + self.grace;
+        let mut report = ShutdownReport {
+            completed: Vec::new(),
+            timed_out: None,
+            skipped: Vec::new(),
+        };
+
+        let mut steps = self.steps.into_iter();
+        for (name, step) in steps.by_ref() {
+            let started = Instant::now();
+            tracing::info!("Shutdown: {}...", name);
+            if tokio::time::timeout_at(deadline, step()).await.is_err() {
+                tracing::warn!("Shutdown: grace period ran out during {}", name);
+                report.timed_out = Some(name);
+                break;
+            }
+            tracing::info!("Shutdown: {} done in {:?}", name, started.elapsed());
+            report.completed.push(name);
+        }
+        report.skipped = steps.map(|(name, _)| name).collect();
+        if !report.skipped.is_empty() {
+            tracing::warn!("Shutdown: skipped {}", report.skipped.join(", "));
+        }
+        report
+    }
+}
@@ -0,0 +1,286 @@
+//! Bulk state import/export REST API for the relay server.
+//!
+//! Lets deployment scripts back up and restore a namespace subtree (a venue's
+//! configuration, say) with curl:
+//!
+//! ```text
+//! curl -H "Authorization: Bearer $ADMIN" \
+//!     "http://relay:7350/api/state/export?pattern=/venue/**" > venue.json
+//! curl -H "Authorization: Bearer $ADMIN" -H "Content-Type: application/json" \
+//!     -d @venue.json "http://relay:7350/api/state/import?dry_run=true"
+//! ```
+//!
+//! Imports are atomic: every param is validated and applied under one state
+//! lock, and if any is rejected (locked by another session, relay at
+//! capacity) nothing changes and the response names the offending address.
+//! A dry run performs the same checks and reports the revisions the import
+//! would produce without keeping them. Exported revisions are informational;
+//! imported values are written as new revisions and broadcast to subscribers.
+//!
+//! Both endpoints require a token with admin scope, like the journal API.
+
+use axum::{
+    extract::{DefaultBodyLimit, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use clasp_core::security::{Action, CpskValidator, TokenInfo, TokenValidator, ValidationResult};
+use clasp_core::{codec, Message, SetMessage, SignalType, Value};
+use clasp_router::session::{Session, SessionId};
+use clasp_router::{RouterState, SubscriptionManager};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Largest accepted import body.
+const MAX_IMPORT_BYTES: usize = 16 * 1024 * 1024;
+
+/// Writer id recorded for imports from tokens without a subject.
+const DEFAULT_WRITER: &str = "state-import";
+
+pub struct StateApiState {
+    pub sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    pub subscriptions: Arc<SubscriptionManager>,
+    pub state: Arc<RouterState>,
+    pub validator: Arc<CpskValidator>,
+}
+
+/// Query parameters for the `/api/state/export` endpoint.
+#[derive(Deserialize)]
+pub struct ExportParams {
+    /// Address glob pattern (e.g. "/venue/**")
+    pub pattern: Option<String>,
+}
+
+/// Query parameters for the `/api/state/import` endpoint.
+#[derive(Deserialize)]
+pub struct ImportParams {
+    /// Validate without applying
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A param as exported. Only `address` and `value` are read on import.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportedParam {
+    pub address: String,
+    pub value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub writer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+/// Export document, also accepted as the import body.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StateDocument {
+    /// Pattern the params were exported from. When present on import, every
+    /// address must match it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Export time (microseconds since epoch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exported_at: Option<u64>,
+    pub params: Vec<ExportedParam>,
+}
+
+#[derive(Serialize)]
+struct ImportedParam {
+    address: String,
+    revision: u64,
+}
+
+#[derive(Serialize)]
+struct ImportResponse {
+    dry_run: bool,
+    count: usize,
+    params: Vec<ImportedParam>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn err(status: StatusCode, msg: impl Into<String>) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: msg.into(),
+            address: None,
+        }),
+    )
+}
+
+fn err_at(status: StatusCode, address: &str, msg: impl Into<String>) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: msg.into(),
+            address: Some(address.to_string()),
+        }),
+    )
+}
+
+/// Validate admin Bearer token from request headers.
+fn validate_admin(headers: &HeaderMap, validator: &CpskValidator) -> Result<TokenInfo, ApiError> {
+    let auth_header = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "expected Bearer token"))?;
+
+    match validator.validate(token) {
+        ValidationResult::Valid(info) => {
+            if info.has_scope(Action::Admin, "/**") {
+                Ok(info)
+            } else {
+                Err(err(StatusCode::FORBIDDEN, "admin scope required"))
+            }
+        }
+        ValidationResult::Expired => Err(err(StatusCode::UNAUTHORIZED, "token expired")),
+        _ => Err(err(StatusCode::UNAUTHORIZED, "invalid token")),
+    }
+}
+
+async fn export_state(
+    State(state): State<Arc<StateApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<ExportParams>,
+) -> Result<Json<StateDocument>, ApiError> {
+    validate_admin(&headers, &state.validator)?;
+
+    let pattern = params.pattern.unwrap_or_else(|| "/**".to_string());
+    let mut exported: Vec<ExportedParam> = state
+        .state
+        .get_matching(&pattern)
+        .into_iter()
+        .map(|(address, param)| ExportedParam {
+            address,
+            value: param.value,
+            revision: Some(param.revision),
+            writer: Some(param.writer),
+            timestamp: Some(param.timestamp),
+        })
+        .collect();
+    exported.sort_by(|a, b| a.address.cmp(&b.address));
+
+    Ok(Json(StateDocument {
+        pattern: Some(pattern),
+        exported_at: Some(clasp_core::time::now()),
+        params: exported,
+    }))
+}
+
+async fn import_state(
+    State(state): State<Arc<StateApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<ImportParams>,
+    Json(document): Json<StateDocument>,
+) -> Result<Json<ImportResponse>, ApiError> {
+    let info = validate_admin(&headers, &state.validator)?;
+
+    for param in &document.params {
+        let address = clasp_core::Address::parse(&param.address).map_err(|e| {
+            err_at(
+                StatusCode::BAD_REQUEST,
+                &param.address,
+                format!("invalid address: {}", e),
+            )
+        })?;
+        if address.is_pattern() {
+            return Err(err_at(
+                StatusCode::BAD_REQUEST,
+                &param.address,
+                "wildcards are not allowed in imported addresses",
+            ));
+        }
+        if let Some(ref pattern) = document.pattern {
+            if !clasp_core::address::glob_match(pattern, &param.address) {
+                return Err(err_at(
+                    StatusCode::BAD_REQUEST,
+                    &param.address,
+                    format!("address is outside the document pattern {}", pattern),
+                ));
+            }
+        }
+    }
+
+    let updates: Vec<(String, Value)> = document
+        .params
+        .into_iter()
+        .map(|p| (p.address, p.value))
+        .collect();
+    let writer = info
+        .subject
+        .clone()
+        .unwrap_or_else(|| DEFAULT_WRITER.to_string());
+
+    let revisions = state
+        .state
+        .set_all(&updates, &writer, params.dry_run)
+        .map_err(|(index, e)| err_at(StatusCode::CONFLICT, &updates[index].0, e.to_string()))?;
+
+    if !params.dry_run {
+        for ((address, value), revision) in updates.iter().zip(&revisions) {
+            broadcast_set(&state, address, value, *revision);
+        }
+        tracing::info!("State import: {} param(s) by {}", updates.len(), writer);
+    }
+
+    Ok(Json(ImportResponse {
+        dry_run: params.dry_run,
+        count: updates.len(),
+        params: updates
+            .into_iter()
+            .zip(revisions)
+            .map(|((address, _), revision)| ImportedParam { address, revision })
+            .collect(),
+    }))
+}
+
+/// Send an imported SET to every session subscribed to its address.
+fn broadcast_set(state: &StateApiState, address: &str, value: &Value, revision: u64) {
+    let message = Message::Set(SetMessage {
+        address: address.to_string(),
+        value: value.clone(),
+        revision: Some(revision),
+        lock: false,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
+    });
+    let Ok(bytes) = codec::encode(&message) else {
+        return;
+    };
+    for session_id in state
+        .subscriptions
+        .find_subscribers(address, Some(SignalType::Param))
+    {
+        if let Some(session) = state.sessions.get(&session_id) {
+            let _ = session.value().try_send(bytes.clone());
+        }
+    }
+}
+
+/// Build the state import/export REST router.
+pub fn state_router(state: Arc<StateApiState>) -> Router {
+    Router::new()
+        .route("/api/state/export", get(export_state))
+        .route(
+            "/api/state/import",
+            post(import_state).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+        )
+        .with_state(state)
+}
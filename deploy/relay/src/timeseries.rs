@@ -0,0 +1,754 @@
+//! Time-series sink for numeric signal history.
+//!
+//! Rules in the `--timeseries-config` JSON file select addresses whose SET
+//! and PUBLISH values are recorded. Matching numeric values become points,
+//! tagged with the address and any `{named}` path captures, and are written
+//! in batches to InfluxDB (line protocol over HTTP) or TimescaleDB.
+//!
+//! This gives sensor deployments history without routing every reading
+//! through the journal, which is built for replay rather than trending.
+//!
+//! # Config format
+//!
+//! ```json
+//! {
+//!   "rules": [
+//!     { "path": "/sensors/{site}/{room}/{metric}", "measurement": "environment" },
+//!     { "path": "/lights/**" }
+//!   ],
+//!   "batch_size": 500,
+//!   "flush_interval_ms": 1000,
+//!   "max_buffer": 50000,
+//!   "influx": {
+//!     "url": "http://localhost:8086",
+//!     "org": "venue",
+//!     "bucket": "clasp",
+//!     "token_env": "INFLUX_TOKEN"
+//!   }
+//! }
+//! ```
+//!
+//! Exactly one of `influx` or `timescale` must be set. For TimescaleDB:
+//!
+//! ```json
+//! { "timescale": { "url": "postgres://clasp@localhost/metrics", "table": "clasp_signals" } }
+//! ```
+//!
+//! Failed writes are retried with exponential backoff while new points keep
+//! buffering. When the buffer is full the oldest points are dropped and
+//! counted (`clasp_timeseries_points_dropped_total` with `--metrics-port`).
+
+use crate::app_config::match_address;
+use async_trait::async_trait;
+use bytes::Bytes;
+use clasp_core::{codec, Message, Value};
+use clasp_router::session::{Session, SessionId};
+use clasp_router::subscription::Subscription;
+use clasp_router::SubscriptionManager;
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How often the sink session is touched so the idle cleanup task keeps it.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Routed writes waiting to be converted before new ones are dropped.
+const OBSERVER_BUFFER: usize = 4096;
+
+/// First retry delay after a failed write.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest delay between retries.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// ---------------------------------------------------------------------------
+// Config types
+// ---------------------------------------------------------------------------
+
+/// Top-level sink config loaded from `--timeseries-config`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeseriesConfig {
+    /// Address rules selecting what is recorded (first match wins).
+    pub rules: Vec<SeriesRule>,
+
+    /// Points per write request.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+
+    /// Longest time a point waits before its batch is written.
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+
+    /// Points held while the database is unreachable. Oldest are dropped first.
+    #[serde(default = "default_max_buffer")]
+    pub max_buffer: usize,
+
+    /// InfluxDB destination.
+    #[serde(default)]
+    pub influx: Option<InfluxConfig>,
+
+    /// TimescaleDB destination (requires the `timescale` feature).
+    #[serde(default)]
+    pub timescale: Option<TimescaleConfig>,
+}
+
+fn default_batch_size() -> usize {
+    500
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_max_buffer() -> usize {
+    50_000
+}
+
+/// Selects addresses and names the series they are written to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeriesRule {
+    /// Path pattern; `{named}` captures become tags, e.g. `/sensors/{site}/{metric}`.
+    pub path: String,
+
+    /// Measurement (InfluxDB) or `measurement` column (TimescaleDB).
+    #[serde(default = "default_measurement")]
+    pub measurement: String,
+
+    /// Static tags added to every point from this rule.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+fn default_measurement() -> String {
+    "clasp".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InfluxConfig {
+    /// Base URL, e.g. `http://localhost:8086`.
+    pub url: String,
+
+    /// InfluxDB 2.x organization and bucket.
+    #[serde(default)]
+    pub org: Option<String>,
+    #[serde(default)]
+    pub bucket: Option<String>,
+
+    /// InfluxDB 1.x database (uses the `/write` endpoint instead).
+    #[serde(default)]
+    pub database: Option<String>,
+
+    /// API token, or the name of an environment variable holding it.
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimescaleConfig {
+    /// libpq-style connection string, e.g. `postgres://user@host/db`.
+    pub url: String,
+
+    /// Target table, created as a hypertable if missing.
+    #[serde(default = "default_table")]
+    pub table: String,
+}
+
+fn default_table() -> String {
+    "clasp_signals".to_string()
+}
+
+impl TimeseriesConfig {
+    /// Glob patterns to subscribe to, one per rule.
+    pub fn subscription_patterns(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                rule.path
+                    .split('/')
+                    .map(|seg| {
+                        if seg.starts_with('{') && seg.ends_with('}') {
+                            "*"
+                        } else {
+                            seg
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect()
+    }
+
+    /// Turn a routed write into a point, if a rule matches and the value is numeric.
+    pub fn point(&self, address: &str, value: &Value, timestamp: u64) -> Option<Point> {
+        let value = PointValue::from_value(value)?;
+        self.rules.iter().find_map(|rule| {
+            let captures = match_address(&rule.path, address)?;
+            let mut tags = rule.tags.clone();
+            for (name, segment) in captures {
+                tags.insert(name.to_string(), segment.to_string());
+            }
+            tags.insert("address".to_string(), address.to_string());
+            Some(Point {
+                measurement: rule.measurement.clone(),
+                tags,
+                value,
+                timestamp,
+            })
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Points
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointValue {
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+}
+
+impl PointValue {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(f) if f.is_finite() => Some(PointValue::Float(*f)),
+            Value::Int(i) => Some(PointValue::Int(*i)),
+            Value::Bool(b) => Some(PointValue::Bool(*b)),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            PointValue::Float(f) => *f,
+            PointValue::Int(i) => *i as f64,
+            PointValue::Bool(b) => f64::from(u8::from(*b)),
+        }
+    }
+}
+
+/// One recorded sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    /// Always contains `address`, plus path captures and static tags.
+    pub tags: BTreeMap<String, String>,
+    pub value: PointValue,
+    /// Microseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl Point {
+    /// Render as one InfluxDB line protocol line (microsecond precision).
+    pub fn to_line_protocol(&self) -> String {
+        let mut line = escape_line(&self.measurement, &[',', ' ']);
+        for (key, value) in &self.tags {
+            if value.is_empty() {
+                continue;
+            }
+            line.push(',');
+            line.push_str(&escape_line(key, &[',', '=', ' ']));
+            line.push('=');
+            line.push_str(&escape_line(value, &[',', '=', ' ']));
+        }
+        let field = match self.value {
+            PointValue::Float(f) => format!("{:?}", f),
+            PointValue::Int(i) => format!("{}i", i),
+            PointValue::Bool(b) => b.to_string(),
+        };
+        line.push_str(&format!(" value={} {}", field, self.timestamp));
+        line
+    }
+}
+
+fn escape_line(s: &str, special: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Writers
+// ---------------------------------------------------------------------------
+
+/// Error returned by a [`SeriesWriter`].
+#[derive(Debug)]
+pub enum SinkError {
+    /// The database rejected the batch; retrying will not help.
+    Rejected(String),
+    /// Connection or server failure; the batch is retried.
+    Unavailable(String),
+}
+
+impl std::fmt::Display for SinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkError::Rejected(msg) => write!(f, "rejected: {}", msg),
+            SinkError::Unavailable(msg) => write!(f, "unavailable: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+/// Writes a batch of points to a time-series database.
+///
+/// [`InfluxWriter`] and `TimescaleWriter` talk to real databases; tests and
+/// embedders can supply their own implementation.
+#[async_trait]
+pub trait SeriesWriter: Send + Sync {
+    async fn write(&self, points: &[Point]) -> Result<(), SinkError>;
+}
+
+/// InfluxDB writer using the line protocol HTTP API (1.x or 2.x).
+pub struct InfluxWriter {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl InfluxWriter {
+    pub fn new(config: &InfluxConfig) -> anyhow::Result<Self> {
+        let base = config.url.trim_end_matches('/');
+        let url = match (&config.database, &config.org, &config.bucket) {
+            (Some(db), _, _) => format!("{}/write?db={}&precision=u", base, urlencode(db)),
+            (None, Some(org), Some(bucket)) => format!(
+                "{}/api/v2/write?org={}&bucket={}&precision=us",
+                base,
+                urlencode(org),
+                urlencode(bucket)
+            ),
+            _ => anyhow::bail!("influx config needs `database` (1.x) or `org` and `bucket` (2.x)"),
+        };
+        let token = match (&config.token, &config.token_env) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(var)) => Some(
+                std::env::var(var)
+                    .map_err(|_| anyhow::anyhow!("influx token_env {} is not set", var))?,
+            ),
+            (None, None) => None,
+        };
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            url,
+            token,
+        })
+    }
+}
+
+fn urlencode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl SeriesWriter for InfluxWriter {
+    async fn write(&self, points: &[Point]) -> Result<(), SinkError> {
+        let body: String = points.iter().map(|p| p.to_line_protocol() + "\n").collect();
+        let mut request = self.client.post(&self.url).body(body);
+        if let Some(ref token) = self.token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SinkError::Unavailable(e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        if status.is_server_error() || status.as_u16() == 429 {
+            Err(SinkError::Unavailable(format!("{}: {}", status, text)))
+        } else {
+            Err(SinkError::Rejected(format!("{}: {}", status, text)))
+        }
+    }
+}
+
+/// TimescaleDB writer. One row per point:
+/// `(time timestamptz, measurement text, address text, tags jsonb, value float8)`.
+#[cfg(feature = "timescale")]
+pub struct TimescaleWriter {
+    config: TimescaleConfig,
+    client: tokio::sync::Mutex<Option<tokio_postgres::Client>>,
+}
+
+#[cfg(feature = "timescale")]
+impl TimescaleWriter {
+    pub fn new(config: &TimescaleConfig) -> anyhow::Result<Self> {
+        let valid = !config.table.is_empty()
+            && config
+                .table
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if !valid {
+            anyhow::bail!("timescale table name must be [A-Za-z0-9_.]");
+        }
+        Ok(Self {
+            config: config.clone(),
+            client: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    async fn connect(&self) -> Result<tokio_postgres::Client, SinkError> {
+        let (client, connection) = tokio_postgres::connect(&self.config.url, tokio_postgres::NoTls)
+            .await
+            .map_err(|e| SinkError::Unavailable(e.to_string()))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!("Timeseries: TimescaleDB connection closed: {}", e);
+            }
+        });
+        let table = &self.config.table;
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    time TIMESTAMPTZ NOT NULL,
+                    measurement TEXT NOT NULL,
+                    address TEXT NOT NULL,
+                    tags JSONB NOT NULL,
+                    value DOUBLE PRECISION NOT NULL
+                )"
+            ))
+            .await
+            .map_err(|e| SinkError::Unavailable(e.to_string()))?;
+        // Plain PostgreSQL works too; the hypertable is an optimisation
+        if let Err(e) = client
+            .execute(
+                "SELECT create_hypertable($1, 'time', if_not_exists => TRUE)",
+                &[&table.as_str()],
+            )
+            .await
+        {
+            tracing::info!("Timeseries: {} is not a hypertable ({})", table, e);
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(feature = "timescale")]
+#[async_trait]
+impl SeriesWriter for TimescaleWriter {
+    async fn write(&self, points: &[Point]) -> Result<(), SinkError> {
+        let mut guard = self.client.lock().await;
+        if guard.as_ref().is_none_or(|c| c.is_closed()) {
+            *guard = Some(self.connect().await?);
+        }
+        let Some(client) = guard.as_ref() else {
+            return Err(SinkError::Unavailable("not connected".into()));
+        };
+
+        let mut sql = format!(
+            "INSERT INTO {} (time, measurement, address, tags, value) VALUES ",
+            self.config.table
+        );
+        let mut rows: Vec<(f64, &str, &str, String, f64)> = Vec::with_capacity(points.len());
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                sql.push(',');
+            }
+            let n = i * 5;
+            sql.push_str(&format!(
+                "(to_timestamp(${}), ${}, ${}, ${}::text::jsonb, ${})",
+                n + 1,
+                n + 2,
+                n + 3,
+                n + 4,
+                n + 5
+            ));
+            rows.push((
+                point.timestamp as f64 / 1_000_000.0,
+                point.measurement.as_str(),
+                point.tags.get("address").map(String::as_str).unwrap_or(""),
+                serde_json::to_string(&point.tags).unwrap_or_else(|_| "{}".into()),
+                point.value.as_f64(),
+            ));
+        }
+        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
+            Vec::with_capacity(rows.len() * 5);
+        for row in &rows {
+            params.extend_from_slice(&[&row.0, &row.1, &row.2, &row.3, &row.4]);
+        }
+
+        match client.execute(sql.as_str(), &params).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.is_closed() || e.as_db_error().is_none() => {
+                *guard = None;
+                Err(SinkError::Unavailable(e.to_string()))
+            }
+            Err(e) => Err(SinkError::Rejected(e.to_string())),
+        }
+    }
+}
+
+/// Build the writer selected by the config.
+pub fn build_writer(config: &TimeseriesConfig) -> anyhow::Result<Arc<dyn SeriesWriter>> {
+    match (&config.influx, &config.timescale) {
+        (Some(influx), None) => Ok(Arc::new(InfluxWriter::new(influx)?)),
+        #[cfg(feature = "timescale")]
+        (None, Some(timescale)) => Ok(Arc::new(TimescaleWriter::new(timescale)?)),
+        #[cfg(not(feature = "timescale"))]
+        (None, Some(_)) => {
+            anyhow::bail!("TimescaleDB sink requires the 'timescale' feature. Rebuild with --features timescale")
+        }
+        (Some(_), Some(_)) => anyhow::bail!("timeseries config sets both influx and timescale"),
+        (None, None) => anyhow::bail!("timeseries config needs an influx or timescale destination"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sink
+// ---------------------------------------------------------------------------
+
+/// Counters for the sink, also exported as metrics with the `metrics` feature.
+#[derive(Debug, Default)]
+pub struct SinkStats {
+    pub written: AtomicU64,
+    pub dropped: AtomicU64,
+    pub write_errors: AtomicU64,
+}
+
+/// Buffers points and writes them in batches with retry/backoff.
+pub struct TimeseriesSink {
+    config: TimeseriesConfig,
+    writer: Arc<dyn SeriesWriter>,
+    buffer: VecDeque<Point>,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    pub stats: Arc<SinkStats>,
+}
+
+impl TimeseriesSink {
+    pub fn new(config: TimeseriesConfig, writer: Arc<dyn SeriesWriter>) -> Self {
+        Self {
+            config,
+            writer,
+            buffer: VecDeque::new(),
+            backoff: INITIAL_BACKOFF,
+            retry_at: None,
+            stats: Arc::new(SinkStats::default()),
+        }
+    }
+
+    pub fn config(&self) -> &TimeseriesConfig {
+        &self.config
+    }
+
+    /// Points waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Buffer a routed write if it matches a rule.
+    pub fn record(&mut self, address: &str, value: &Value, timestamp: u64) {
+        let Some(point) = self.config.point(address, value, timestamp) else {
+            return;
+        };
+        if self.buffer.len() >= self.config.max_buffer.max(1) {
+            self.buffer.pop_front();
+            self.drop_points(1, "buffer_full");
+        }
+        self.buffer.push_back(point);
+    }
+
+    /// A full batch is waiting and no retry delay is pending.
+    pub fn batch_ready(&self) -> bool {
+        self.buffer.len() >= self.config.batch_size.max(1) && self.retry_at.is_none()
+    }
+
+    /// Write buffered points in batches. Stops at the first unavailable error
+    /// and schedules a retry with exponential backoff.
+    pub async fn flush(&mut self) {
+        if let Some(at) = self.retry_at {
+            if Instant::now() < at {
+                return;
+            }
+            self.retry_at = None;
+        }
+        while !self.buffer.is_empty() {
+            let n = self.buffer.len().min(self.config.batch_size.max(1));
+            let batch: Vec<Point> = self.buffer.iter().take(n).cloned().collect();
+            match self.writer.write(&batch).await {
+                Ok(()) => {
+                    self.buffer.drain(..n);
+                    self.backoff = INITIAL_BACKOFF;
+                    self.stats.written.fetch_add(n as u64, Ordering::Relaxed);
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("clasp_timeseries_points_written_total").increment(n as u64);
+                }
+                Err(SinkError::Rejected(e)) => {
+                    tracing::warn!("Timeseries: dropping batch of {} points, {}", n, e);
+                    self.buffer.drain(..n);
+                    self.count_error();
+                    self.drop_points(n as u64, "rejected");
+                }
+                Err(SinkError::Unavailable(e)) => {
+                    tracing::warn!(
+                        "Timeseries: write failed ({}), retrying in {:?} with {} points buffered",
+                        e,
+                        self.backoff,
+                        self.buffer.len()
+                    );
+                    self.count_error();
+                    self.retry_at = Some(Instant::now() + self.backoff);
+                    self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn count_error(&self) {
+        self.stats.write_errors.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clasp_timeseries_write_errors_total").increment(1);
+    }
+
+    fn drop_points(&self, n: u64, reason: &'static str) {
+        self.stats.dropped.fetch_add(n, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("clasp_timeseries_points_dropped_total", "reason" => reason).increment(n);
+        #[cfg(not(feature = "metrics"))]
+        let _ = reason;
+    }
+}
+
+/// Forwards encoded messages from the router to the sink task.
+struct ObserverSender {
+    tx: mpsc::Sender<Bytes>,
+    stats: Arc<SinkStats>,
+}
+
+#[async_trait]
+impl clasp_transport::TransportSender for ObserverSender {
+    async fn send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.try_send(data)
+    }
+
+    fn try_send(&self, data: Bytes) -> clasp_transport::Result<()> {
+        self.tx.try_send(data).map_err(|_| {
+            self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("clasp_timeseries_points_dropped_total", "reason" => "backlog")
+                .increment(1);
+            clasp_transport::TransportError::BufferFull
+        })
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    async fn close(&self) -> clasp_transport::Result<()> {
+        Ok(())
+    }
+}
+
+/// Register an internal session subscribed to every rule pattern, then batch
+/// the writes it receives into the database. Runs until the router drops the
+/// session's sender.
+pub async fn run_timeseries_sink(
+    mut sink: TimeseriesSink,
+    sessions: Arc<DashMap<SessionId, Arc<Session>>>,
+    subscriptions: Arc<SubscriptionManager>,
+) {
+    let (tx, mut rx) = mpsc::channel(OBSERVER_BUFFER);
+    let session = Arc::new(Session::new(
+        Arc::new(ObserverSender {
+            tx,
+            stats: Arc::clone(&sink.stats),
+        }),
+        "timeseries-sink".to_string(),
+        vec![],
+    ));
+    let session_id = session.id.clone();
+    sessions.insert(session_id.clone(), Arc::clone(&session));
+
+    for (i, pattern) in sink.config().subscription_patterns().iter().enumerate() {
+        match Subscription::new(
+            i as u32 + 1,
+            session_id.clone(),
+            pattern,
+            vec![],
+            Default::default(),
+        ) {
+            Ok(sub) => {
+                subscriptions.add(sub);
+                session.add_subscription(i as u32 + 1);
+            }
+            Err(e) => tracing::warn!("Timeseries: invalid rule pattern {}: {}", pattern, e),
+        }
+    }
+
+    let mut flush = tokio::time::interval(Duration::from_millis(
+        sink.config().flush_interval_ms.max(10),
+    ));
+    flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    loop {
+        tokio::select! {
+            data = rx.recv() => {
+                let Some(data) = data else { break };
+                let (address, value, timestamp) = match codec::decode(&data) {
+                    Ok((Message::Set(msg), frame)) => (msg.address, msg.value, frame.timestamp),
+                    Ok((Message::Publish(msg), frame)) => {
+                        let Some(value) = msg.value.or(msg.payload) else { continue };
+                        (msg.address, value, msg.timestamp.or(frame.timestamp))
+                    }
+                    _ => continue,
+                };
+                let timestamp = timestamp.unwrap_or_else(clasp_core::time::now);
+                sink.record(&address, &value, timestamp);
+                if sink.batch_ready() {
+                    sink.flush().await;
+                }
+            }
+            _ = flush.tick() => {
+                sink.flush().await;
+            }
+            _ = keepalive.tick() => {
+                // The router evicts idle sessions; this one only ever receives.
+                session.touch();
+            }
+        }
+    }
+    sink.flush().await;
+    if sink.pending() > 0 {
+        tracing::warn!(
+            "Timeseries: {} points not written at shutdown",
+            sink.pending()
+        );
+    }
+}
+
+/// Load a sink config from a JSON file.
+pub fn load_config(path: &Path) -> anyhow::Result<TimeseriesConfig> {
+    use anyhow::Context;
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read timeseries config {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse timeseries config {}", path.display()))
+}
@@ -0,0 +1,109 @@
+//! Tests for hot reload of the app config.
+
+use clasp_core::Value;
+use clasp_relay::app_config::{AppConfig, LiveAppConfig, RuleSnapshotFilter, RuleWriteValidator};
+use clasp_relay::server::watch_app_config;
+use clasp_router::{RouterState, Session, SnapshotFilter, WriteValidator};
+use std::sync::Arc;
+use std::time::Duration;
+
+const OWNER_RULE: &str = r#"{
+    "write_rules": [
+        {
+            "path": "/notes/{userId}",
+            "checks": [{ "type": "segment_equals_session", "segment": "userId" }]
+        }
+    ]
+}"#;
+
+fn live() -> (LiveAppConfig, Arc<RuleWriteValidator>, Arc<RuleSnapshotFilter>) {
+    let validator = Arc::new(RuleWriteValidator::new(vec![]));
+    let filter = Arc::new(RuleSnapshotFilter::new(vec![], vec![]));
+    let live = LiveAppConfig {
+        write_validator: Some(Arc::clone(&validator)),
+        snapshot_filter: Some(Arc::clone(&filter)),
+        auth: None,
+    };
+    (live, validator, filter)
+}
+
+fn write_allowed(validator: &RuleWriteValidator, address: &str) -> bool {
+    let state = RouterState::new();
+    let session = Session::stub(Some("alice".to_string()));
+    validator
+        .validate_write(address, &Value::Int(1), &session, &state)
+        .is_ok()
+}
+
+#[test]
+fn apply_replaces_rules_in_place() {
+    let (live, validator, filter) = live();
+    assert!(write_allowed(&validator, "/notes/bob"));
+
+    let config: AppConfig = serde_json::from_str(
+        r#"{
+            "write_rules": [
+                {
+                    "path": "/notes/{userId}",
+                    "checks": [{ "type": "segment_equals_session", "segment": "userId" }]
+                }
+            ],
+            "snapshot_visibility": [{ "path": "/secret/**", "visible": false }]
+        }"#,
+    )
+    .unwrap();
+    live.apply(&config);
+
+    assert!(!write_allowed(&validator, "/notes/bob"));
+    assert!(write_allowed(&validator, "/notes/alice"));
+
+    let state = RouterState::new();
+    let session = Session::stub(Some("alice".to_string()));
+    let params = ["/secret/key", "/public/key"]
+        .iter()
+        .map(|address| clasp_core::ParamValue {
+            address: address.to_string(),
+            value: Value::Int(1),
+            revision: 1,
+            writer: None,
+            timestamp: None,
+        })
+        .collect();
+    let visible = filter.filter_snapshot(params, &session, &state);
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].address, "/public/key");
+}
+
+#[tokio::test]
+async fn watcher_reloads_changed_file_and_keeps_config_on_parse_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("app.json");
+    std::fs::write(&path, "{}").unwrap();
+
+    let (live, validator, _filter) = live();
+    let watcher = tokio::spawn(watch_app_config(
+        live,
+        path.clone(),
+        Duration::from_millis(50),
+    ));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(write_allowed(&validator, "/notes/bob"));
+
+    std::fs::write(&path, OWNER_RULE).unwrap();
+    let reloaded = tokio::time::timeout(Duration::from_secs(5), async {
+        while write_allowed(&validator, "/notes/bob") {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(reloaded.is_ok(), "Write rules should be reloaded");
+
+    // A broken file leaves the last good config in place
+    std::fs::write(&path, "{ not json").unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!write_allowed(&validator, "/notes/bob"));
+    assert!(write_allowed(&validator, "/notes/alice"));
+
+    watcher.abort();
+}
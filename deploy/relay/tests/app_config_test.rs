@@ -586,6 +586,38 @@ fn test_snapshot_redacts_room_meta_passwords() {
     }
 }
 
+#[test]
+fn test_snapshot_keeps_e2e_envelopes_whole() {
+    let state = RouterState::new();
+    let f = RuleSnapshotFilter::new(
+        vec![clasp_relay::app_config::SnapshotTransform {
+            path: "/secret/**".to_string(),
+            redact_fields: vec!["ct".to_string()],
+        }],
+        vec![],
+    );
+    let envelope = Value::Map(HashMap::from([
+        ("_e2e".to_string(), Value::Int(1)),
+        ("ct".to_string(), Value::String("Y2lwaGVy".into())),
+        ("iv".to_string(), Value::String("aXY=".into())),
+        ("v".to_string(), Value::Int(1)),
+    ]));
+    let plain = Value::Map(HashMap::from([(
+        "ct".to_string(),
+        Value::String("plain".into()),
+    )]));
+    let result = f.filter_snapshot(
+        vec![
+            make_pv("/secret/a", envelope.clone()),
+            make_pv("/secret/b", plain),
+        ],
+        &make_session("alice"),
+        &state,
+    );
+    assert_eq!(result[0].value, envelope);
+    assert_eq!(result[1].value, Value::Map(HashMap::new()));
+}
+
 // ===========================================================
 //  Snapshot filter -- __auth stripping
 // ===========================================================
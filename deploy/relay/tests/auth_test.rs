@@ -526,6 +526,148 @@ async fn cors_explicit_origin_is_set() {
     assert_eq!(acao.unwrap().to_str().unwrap(), "https://chat.example.com");
 }
 
+// === Token exchange tests ===
+
+#[cfg(feature = "caps")]
+mod exchange {
+    use super::*;
+    use clasp_caps::CapabilityValidator;
+    use clasp_core::security::{Action, TokenInfo, TokenValidator, ValidationResult};
+    use ed25519_dalek::SigningKey;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Auth router with token exchange enabled, a `cpsk_` token holding
+    /// `write:/lights/**` (expiring in `ttl`), and a validator trusting the
+    /// exchange key
+    fn setup(ttl: Duration) -> (axum::Router, String, CapabilityValidator) {
+        let key = SigningKey::from_bytes(&[5u8; 32]);
+        let caps = CapabilityValidator::new(vec![key.verifying_key().to_bytes().to_vec()], 5);
+
+        let validator = Arc::new(CpskValidator::new());
+        let token = CpskValidator::generate_token();
+        validator.register(
+            token.clone(),
+            TokenInfo::new(
+                "u-1".to_string(),
+                vec![Scope::parse("write:/lights/**").unwrap()],
+            )
+            .with_subject("u-1")
+            .with_expires_in(ttl),
+        );
+        let state = AuthState::new(":memory:", validator, None, Default::default())
+            .unwrap()
+            .with_exchange_key(key);
+        (auth_router(Arc::new(state), None), token, caps)
+    }
+
+    async fn exchange(app: axum::Router, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri("/auth/exchange")
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {}", token));
+        }
+        let req = builder.body(Body::from(body.to_string())).unwrap();
+        let response = app.oneshot(req).await.unwrap();
+        let status = response.status();
+        let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            serde_json::from_slice(&body_bytes).unwrap_or(json!({})),
+        )
+    }
+
+    #[tokio::test]
+    async fn exchange_issues_attenuated_cap_token() {
+        let (app, token, caps) = setup(Duration::from_secs(86400));
+
+        let (status, body) = exchange(
+            app.clone(),
+            Some(&token),
+            json!({ "scopes": ["read:/lights/room1/**"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let cap = body["token"].as_str().unwrap();
+        assert!(cap.starts_with("cap_"));
+        match caps.validate(cap) {
+            ValidationResult::Valid(info) => {
+                assert!(info.has_scope(Action::Read, "/lights/room1/level"));
+                assert!(!info.has_scope(Action::Write, "/lights/room1/level"));
+                assert!(!info.has_scope(Action::Read, "/lights/room2/level"));
+            }
+            other => panic!("expected Valid, got {:?}", other),
+        }
+
+        // Wider than the presented token
+        let (status, _) = exchange(
+            app.clone(),
+            Some(&token),
+            json!({ "scopes": ["admin:/lights/**"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = exchange(
+            app.clone(),
+            Some(&token),
+            json!({ "scopes": ["write:/audio/**"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = exchange(
+            app.clone(),
+            Some(&token),
+            json!({ "scopes": ["read:/lights/**"], "expires_in": 86400 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = exchange(
+            app.clone(),
+            Some(&token),
+            json!({ "scopes": ["read:/lights/**"], "audience": "abcd" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Only a cpsk_ token can be exchanged
+        let (status, _) =
+            exchange(app.clone(), None, json!({ "scopes": ["read:/lights/**"] })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = exchange(app, Some(cap), json!({ "scopes": ["read:/lights/**"] })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn exchange_never_outlives_presented_token() {
+        let (app, token, _) = setup(Duration::from_secs(60));
+        let (status, body) = exchange(
+            app,
+            Some(&token),
+            json!({ "scopes": ["write:/lights/**"], "expires_in": 3600 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(body["expires_at"].as_u64().unwrap() <= now + 60);
+    }
+
+    #[tokio::test]
+    async fn exchange_disabled_without_key() {
+        let (status, _) = exchange(
+            setup_app(),
+            Some("cpsk_anything"),
+            json!({ "scopes": ["read:/**"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
+
 // === has_strict_read_scope tests ===
 
 /// Create an authenticated Session with the scopes from build_scopes()
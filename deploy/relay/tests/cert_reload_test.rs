@@ -0,0 +1,64 @@
+//! Tests for hot reload of the QUIC certificate.
+
+#![cfg(feature = "quic")]
+
+use clasp_relay::cert_reload;
+use clasp_router::{Router, RouterConfig};
+use rcgen::{generate_simple_self_signed, CertifiedKey};
+use std::path::Path;
+use std::time::Duration;
+
+/// Write a fresh self-signed certificate and key as PEM files
+fn write_pem(dir: &Path, name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+    let CertifiedKey { cert, key_pair } =
+        generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_path = dir.join(format!("{}.crt", name));
+    let key_path = dir.join(format!("{}.key", name));
+    std::fs::write(&cert_path, cert.pem()).unwrap();
+    std::fs::write(&key_path, key_pair.serialize_pem()).unwrap();
+    (cert_path, key_path)
+}
+
+#[test]
+fn load_pem_reads_certificate_and_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert, key) = write_pem(dir.path(), "a");
+    let (cert_der, key_der) = cert_reload::load_pem(&cert, &key).unwrap();
+    assert!(!cert_der.is_empty());
+    assert!(!key_der.is_empty());
+
+    // A key where the certificate should be
+    assert!(cert_reload::load_pem(&key, &key).is_err());
+}
+
+#[tokio::test]
+async fn reload_swaps_certificate_on_running_listener() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old_cert, old_key) = write_pem(dir.path(), "old");
+    let (new_cert, new_key) = write_pem(dir.path(), "new");
+
+    let router = Router::new(RouterConfig::default());
+    let certs = router.quic_certificates();
+    assert!(cert_reload::reload(&certs, &new_cert, &new_key).is_err());
+
+    let (cert_der, key_der) = cert_reload::load_pem(&old_cert, &old_key).unwrap();
+    let addr = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    tokio::spawn(async move { router.serve_quic(addr, cert_der, key_der).await });
+    for _ in 0..50 {
+        if certs.is_serving() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(certs.is_serving());
+
+    cert_reload::reload(&certs, &new_cert, &new_key).expect("reload should succeed");
+
+    // Broken files leave the listener as it was
+    std::fs::write(&new_key, "not a key").unwrap();
+    assert!(cert_reload::reload(&certs, &new_cert, &new_key).is_err());
+    assert!(certs.is_serving());
+}
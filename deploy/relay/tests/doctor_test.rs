@@ -0,0 +1,116 @@
+//! Tests for the `clasp-relay doctor` environment checks.
+
+use clasp_relay::config::RelayConfig;
+use clasp_relay::doctor::{self, Status};
+use std::net::TcpListener;
+
+fn check<'a>(report: &'a doctor::Report, name: &str) -> &'a doctor::Check {
+    report
+        .checks
+        .iter()
+        .find(|c| c.name == name)
+        .unwrap_or_else(|| panic!("{} check missing:\n{}", name, report))
+}
+
+fn status(report: &doctor::Report, name: &str) -> Status {
+    check(report, name).status
+}
+
+#[test]
+fn doctor_reports_busy_port() {
+    let busy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = RelayConfig {
+        host: "127.0.0.1".to_string(),
+        ws_port: busy.local_addr().unwrap().port(),
+        ..Default::default()
+    };
+    let report = doctor::run(&config);
+    assert_eq!(status(&report, "port.websocket"), Status::Fail);
+    assert!(!report.ok());
+
+    drop(busy);
+    let report = doctor::run(&config);
+    assert_eq!(status(&report, "port.websocket"), Status::Pass);
+    assert_eq!(status(&report, "auth.validator"), Status::Warn);
+    assert!(report.ok(), "{}", report);
+}
+
+#[test]
+fn doctor_checks_storage_and_auth_db() {
+    let dir = tempfile::tempdir().unwrap();
+    let auth_db = dir.path().join("auth.db");
+    let conn = rusqlite::Connection::open(&auth_db).unwrap();
+    conn.execute_batch("CREATE TABLE users (id TEXT PRIMARY KEY, username TEXT);")
+        .unwrap();
+    drop(conn);
+
+    let config = RelayConfig {
+        no_websocket: true,
+        auth_port: None,
+        journal: Some(dir.path().join("journal.db")),
+        persist: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let report = doctor::run(&config);
+    assert_eq!(status(&report, "storage.journal"), Status::Pass);
+    assert_eq!(status(&report, "storage.persist"), Status::Fail);
+    assert_eq!(status(&report, "auth.db"), Status::Skip);
+
+    // Auth enabled on an outdated database
+    let busy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = busy.local_addr().unwrap().port();
+    drop(busy);
+    let config = RelayConfig {
+        host: "127.0.0.1".to_string(),
+        no_websocket: true,
+        auth_port: Some(port),
+        auth_db: auth_db.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+    let report = doctor::run(&config);
+    let auth_db = check(&report, "auth.db");
+    assert_eq!(auth_db.status, Status::Fail);
+    assert!(auth_db.detail.contains("password_hash"), "{}", auth_db.detail);
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["ok"], false);
+    assert!(json["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|c| c["name"] == "auth.db" && c["status"] == "fail"));
+}
+
+#[test]
+fn doctor_checks_trust_anchors_and_peers() {
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("good.key");
+    let bad = dir.path().join("bad.key");
+    std::fs::write(&good, "ab".repeat(32)).unwrap();
+    std::fs::write(&bad, "not a key").unwrap();
+
+    // Nothing listens on a port we just released
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed_port = closed.local_addr().unwrap().port();
+    drop(closed);
+    let primary = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let config = RelayConfig {
+        no_websocket: true,
+        auth_port: Some(0),
+        trust_anchor: vec![good, bad],
+        federation_hub: Some(format!("ws://127.0.0.1:{}", closed_port)),
+        replicate_from: Some(format!("http://{}", primary.local_addr().unwrap())),
+        ..Default::default()
+    };
+    let report = doctor::run(&config);
+    let anchors: Vec<_> = report
+        .checks
+        .iter()
+        .filter(|c| c.name == "auth.trust_anchor")
+        .map(|c| c.status)
+        .collect();
+    assert_eq!(anchors, vec![Status::Pass, Status::Fail]);
+    assert_eq!(status(&report, "peer.federation_hub"), Status::Fail);
+    assert_eq!(status(&report, "peer.replicate_from"), Status::Pass);
+}
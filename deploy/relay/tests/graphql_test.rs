@@ -0,0 +1,325 @@
+//! Tests for the GraphQL facade.
+//!
+//! Gated behind `#[cfg(feature = "graphql")]` since the graphql module is optional.
+//! Run with: cargo test --features graphql
+
+#[cfg(feature = "graphql")]
+mod graphql_tests {
+    use async_graphql::futures_util::StreamExt;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use clasp_core::security::{CpskValidator, Scope, TokenInfo, TokenValidator};
+    use clasp_core::{SecurityMode, Value};
+    use clasp_relay::app_config::{RuleSnapshotFilter, RuleWriteValidator};
+    use clasp_relay::graphql::{
+        build_schema, graphql_router, ClaspSchema, GraphqlApiState, RouterHandles,
+    };
+    use clasp_router::{Router, RouterConfig};
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value as JsonValue};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn handles() -> RouterHandles {
+        RouterHandles::new(&Router::default())
+    }
+
+    /// Token that can read everything but only write under /lights.
+    fn lights_token() -> TokenInfo {
+        TokenInfo::new(
+            "t".into(),
+            vec![
+                Scope::parse("read:/**").unwrap(),
+                Scope::parse("write:/lights/**").unwrap(),
+            ],
+        )
+        .with_subject("alice")
+    }
+
+    async fn run(schema: &ClaspSchema, query: &str, info: TokenInfo) -> JsonValue {
+        let response = schema
+            .execute(async_graphql::Request::new(query).data(info))
+            .await;
+        serde_json::to_value(&response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn set_then_query_param() {
+        let handles = handles();
+        let schema = build_schema(handles.clone());
+
+        let set = run(
+            &schema,
+            r#"mutation { set(address: "/lights/a", value: 0.5) { address revision } }"#,
+            lights_token(),
+        )
+        .await;
+        assert!(set.get("errors").is_none(), "{}", set);
+        assert_eq!(set["data"]["set"]["address"], "/lights/a");
+
+        let query = run(
+            &schema,
+            r#"{ param(address: "/lights/a") { value writer } params(pattern: "/lights/**") { address } }"#,
+            lights_token(),
+        )
+        .await;
+        assert_eq!(query["data"]["param"]["value"], json!(0.5));
+        assert_eq!(query["data"]["param"]["writer"], "alice");
+        assert_eq!(query["data"]["params"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            handles.state.get("/lights/a").and_then(|v| v.as_f64()),
+            Some(0.5)
+        );
+    }
+
+    #[tokio::test]
+    async fn set_outside_write_scope_is_rejected() {
+        let handles = handles();
+        let schema = build_schema(handles.clone());
+
+        let result = run(
+            &schema,
+            r#"mutation { set(address: "/doors/front", value: true) { revision } }"#,
+            lights_token(),
+        )
+        .await;
+        assert!(result["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("insufficient scope"));
+        assert!(handles.state.get("/doors/front").is_none());
+    }
+
+    #[tokio::test]
+    async fn params_omit_unreadable_addresses() {
+        let handles = handles();
+        handles
+            .state
+            .set(
+                "/public/a",
+                Value::Int(1),
+                &"s".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+        handles
+            .state
+            .set(
+                "/private/b",
+                Value::Int(2),
+                &"s".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+        let schema = build_schema(handles);
+        let info = TokenInfo::new("t".into(), vec![Scope::parse("read:/public/**").unwrap()]);
+
+        let result = run(&schema, "{ params { address } }", info).await;
+        assert_eq!(
+            result["data"]["params"],
+            json!([{ "address": "/public/a" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn set_is_checked_by_write_rules() {
+        let mut router = Router::default();
+        router.set_write_validator(RuleWriteValidator::new(
+            serde_json::from_value(json!([{
+                "path": "/lights/{owner}/level",
+                "checks": [{ "type": "segment_equals_session", "segment": "owner" }]
+            }]))
+            .unwrap(),
+        ));
+        let handles = RouterHandles::new(&router);
+        let schema = build_schema(handles.clone());
+
+        let own = run(
+            &schema,
+            r#"mutation { set(address: "/lights/alice/level", value: 1) { revision } }"#,
+            lights_token(),
+        )
+        .await;
+        assert!(own.get("errors").is_none(), "{}", own);
+
+        let other = run(
+            &schema,
+            r#"mutation { set(address: "/lights/bob/level", value: 1) { revision } }"#,
+            lights_token(),
+        )
+        .await;
+        assert!(other["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("rejected"));
+        assert!(handles.state.get("/lights/bob/level").is_none());
+    }
+
+    #[tokio::test]
+    async fn reserved_addresses_need_admin_scope() {
+        let router = Router::new(RouterConfig {
+            security_mode: SecurityMode::Authenticated,
+            ..Default::default()
+        });
+        let handles = RouterHandles::new(&router);
+        let schema = build_schema(handles.clone());
+        let writer = TokenInfo::new("t".into(), vec![Scope::parse("write:/**").unwrap()]);
+        let admin = TokenInfo::new("t".into(), vec![Scope::parse("admin:/**").unwrap()]);
+
+        for mutation in [
+            r#"mutation { set(address: "/clasp/rules/mute", value: 1) { revision } }"#,
+            r#"mutation { publish(address: "/clasp/rules/mute", value: 1) }"#,
+        ] {
+            let denied = run(&schema, mutation, writer.clone()).await;
+            assert!(denied["errors"][0]["message"]
+                .as_str()
+                .unwrap()
+                .contains("Admin scope required"));
+        }
+        assert!(handles.state.get("/clasp/rules/mute").is_none());
+
+        let allowed = run(
+            &schema,
+            r#"mutation { set(address: "/clasp/rules/mute", value: 1) { revision } }"#,
+            admin,
+        )
+        .await;
+        assert!(allowed.get("errors").is_none(), "{}", allowed);
+        assert!(handles.state.get("/clasp/rules/mute").is_some());
+    }
+
+    #[tokio::test]
+    async fn reads_are_checked_by_snapshot_filter() {
+        let mut router = Router::default();
+        router.set_snapshot_filter(RuleSnapshotFilter::new(
+            vec![],
+            serde_json::from_value(json!([{ "path_contains": "secret", "visible": false }]))
+                .unwrap(),
+        ));
+        let handles = RouterHandles::new(&router);
+        for address in ["/lights/a", "/lights/secret"] {
+            handles
+                .state
+                .set(
+                    address,
+                    Value::Int(1),
+                    &"s".to_string(),
+                    None,
+                    false,
+                    false,
+                    None,
+                )
+                .unwrap();
+        }
+        let schema = build_schema(handles);
+
+        let result = run(
+            &schema,
+            r#"{ param(address: "/lights/secret") { value } params(pattern: "/lights/**") { address } }"#,
+            lights_token(),
+        )
+        .await;
+        assert!(result.get("errors").is_none(), "{}", result);
+        assert_eq!(result["data"]["param"], JsonValue::Null);
+        assert_eq!(
+            result["data"]["params"],
+            json!([{ "address": "/lights/a" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn subscription_receives_sets() {
+        let handles = handles();
+        let schema = build_schema(handles.clone());
+
+        let mut stream = schema.execute_stream(
+            async_graphql::Request::new(
+                r#"subscription { changes(pattern: "/lights/**") { address signal value } }"#,
+            )
+            .data(lights_token()),
+        );
+        let next = tokio::spawn(async move { stream.next().await });
+
+        // Wait for the internal subscription to be registered
+        for _ in 0..50 {
+            if !handles.sessions.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(handles.sessions.len(), 1);
+
+        run(
+            &schema,
+            r#"mutation { set(address: "/lights/b", value: 1) { revision } }"#,
+            lights_token(),
+        )
+        .await;
+
+        let response = tokio::time::timeout(Duration::from_secs(2), next)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let data = serde_json::to_value(&response).unwrap();
+        assert_eq!(data["data"]["changes"]["address"], "/lights/b");
+        assert_eq!(data["data"]["changes"]["signal"], "PARAM");
+        assert_eq!(data["data"]["changes"]["value"], json!(1));
+
+        // Dropping the stream removes the internal session
+        assert!(handles.sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn http_requires_valid_bearer_token() {
+        let validator = Arc::new(CpskValidator::new());
+        let token = CpskValidator::generate_token();
+        validator.register(token.clone(), lights_token());
+        let state = Arc::new(GraphqlApiState {
+            schema: build_schema(handles()),
+            validator: validator as Arc<dyn TokenValidator>,
+        });
+        let app = graphql_router(state);
+
+        let request = |auth: Option<String>| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header("content-type", "application/json");
+            if let Some(auth) = auth {
+                builder = builder.header("authorization", auth);
+            }
+            builder
+                .body(Body::from(
+                    json!({ "query": "{ signals { address } }" }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .clone()
+            .oneshot(request(Some("Bearer cpsk_bogus".into())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(request(Some(format!("Bearer {}", token))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: JsonValue = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["data"]["signals"], json!([]));
+    }
+}
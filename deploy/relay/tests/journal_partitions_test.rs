@@ -0,0 +1,61 @@
+//! Tests for the `--journal-partitions` config.
+//!
+//! Gated behind `#[cfg(feature = "journal")]` since the module is optional.
+//! Run with: cargo test --features journal
+
+#[cfg(feature = "journal")]
+mod journal_partitions_tests {
+    use clasp_relay::journal_partitions::{PartitionSpec, PartitionsConfig};
+    use std::time::Duration;
+
+    fn spec(value: serde_json::Value) -> PartitionSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn parses_partitions_with_retention() {
+        let config: PartitionsConfig = serde_json::from_value(serde_json::json!({
+            "partitions": [
+                { "name": "chat", "pattern": "/chat/**", "memory": true,
+                  "max_age_secs": 3600, "max_entries": 500 },
+                { "name": "automation", "pattern": "/automation/**", "memory": true }
+            ]
+        }))
+        .unwrap();
+
+        let chat = config.partitions[0].build().unwrap();
+        assert!(chat.matches("/chat/room/1"));
+        assert_eq!(chat.retention.max_age, Some(Duration::from_secs(3600)));
+        assert_eq!(chat.retention.max_entries, Some(500));
+        assert!(config.partitions[1]
+            .build()
+            .unwrap()
+            .retention
+            .is_unbounded());
+    }
+
+    #[test]
+    fn opens_sqlite_partition() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.db");
+        let partition = spec(serde_json::json!({
+            "name": "chat", "pattern": "/chat/**", "sqlite": path
+        }))
+        .build()
+        .unwrap();
+        assert_eq!(partition.name, "chat");
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn rejects_bad_partitions() {
+        for value in [
+            serde_json::json!({ "name": "a", "pattern": "/a/**" }),
+            serde_json::json!({ "name": "a", "pattern": "/a/**", "memory": true,
+                                "sqlite": "a.db" }),
+            serde_json::json!({ "name": "a", "pattern": "a/**", "memory": true }),
+        ] {
+            assert!(spec(value.clone()).build().is_err(), "{:?}", value);
+        }
+    }
+}
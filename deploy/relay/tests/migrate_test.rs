@@ -0,0 +1,80 @@
+//! Tests for schema migrations of the relay's SQLite databases.
+
+use clasp_relay::auth::AUTH_MIGRATIONS;
+use clasp_relay::config::RelayConfig;
+use clasp_relay::migrate;
+
+#[test]
+fn migrate_dry_run_reports_and_run_applies() {
+    let dir = tempfile::tempdir().unwrap();
+    let auth_db = dir.path().join("auth.db");
+
+    // An auth database from before migrations
+    let conn = rusqlite::Connection::open(&auth_db).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE users (
+            id TEXT PRIMARY KEY,
+            username TEXT UNIQUE NOT NULL,
+            password_hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        INSERT INTO users VALUES ('u1', 'alice', 'hash', 0);",
+    )
+    .unwrap();
+    drop(conn);
+
+    let config = RelayConfig {
+        auth_db: auth_db.to_string_lossy().into_owned(),
+        registry_db: Some(dir.path().join("registry.db")),
+        ..Default::default()
+    };
+
+    let report = migrate::run(&config, true).unwrap();
+    let auth = &report.databases[0];
+    assert_eq!(auth.version, Some(0));
+    assert_eq!(auth.pending, vec![(1, "users")]);
+    assert!(!auth.applied);
+    assert_eq!(
+        report.databases[1].version, None,
+        "registry not created yet"
+    );
+    assert!(
+        report.to_string().contains("pending 1 (users)"),
+        "{}",
+        report
+    );
+    assert!(!dir.path().join("auth.db.v0.bak").exists());
+
+    let report = migrate::run(&config, false).unwrap();
+    assert!(report.databases[0].applied);
+    assert!(dir.path().join("auth.db.v0.bak").exists());
+
+    let report = migrate::run(&config, true).unwrap();
+    assert!(report.databases[0].pending.is_empty());
+    let conn = rusqlite::Connection::open(&auth_db).unwrap();
+    let users: u32 = conn
+        .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(users, 1, "migration keeps existing users");
+}
+
+#[test]
+fn migrate_refuses_newer_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let auth_db = dir.path().join("auth.db");
+    let mut conn = rusqlite::Connection::open(&auth_db).unwrap();
+    clasp_registry::migrate::migrate(&mut conn, AUTH_MIGRATIONS).unwrap();
+    conn.execute(
+        "INSERT INTO schema_migrations (version, name, applied_at) VALUES (99, 'future', 0)",
+        [],
+    )
+    .unwrap();
+    drop(conn);
+
+    let config = RelayConfig {
+        auth_db: auth_db.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+    let err = migrate::run(&config, true).unwrap_err();
+    assert!(format!("{:#}", err).contains("newer"), "{:#}", err);
+}
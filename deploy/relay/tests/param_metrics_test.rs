@@ -0,0 +1,91 @@
+//! Tests for the OpenMetrics param export.
+//!
+//! Gated behind `#[cfg(feature = "metrics")]` since the module is optional.
+//! Run with: cargo test --features metrics
+
+#[cfg(feature = "metrics")]
+mod param_metrics_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use clasp_core::Value;
+    use clasp_relay::param_metrics::{param_metrics_router, render, ParamMetricsState};
+    use clasp_router::RouterState;
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn set(state: &RouterState, address: &str, value: Value) {
+        state
+            .set(address, value, &"s".to_string(), None, false, false, None)
+            .unwrap();
+    }
+
+    fn sample_state() -> RouterState {
+        let state = RouterState::new();
+        set(&state, "/sensors/temp", Value::Float(21.5));
+        set(&state, "/sensors/count", Value::Int(3));
+        set(&state, "/sensors/door", Value::Bool(true));
+        set(&state, "/sensors/name", Value::String("lobby".into()));
+        set(&state, "/lights/1", Value::Float(0.5));
+        state
+    }
+
+    #[test]
+    fn renders_numeric_params_as_gauges() {
+        let output = render(&sample_state(), &["/sensors/**".to_string()]);
+        assert_eq!(
+            output,
+            "# TYPE clasp_param gauge\n\
+             # HELP clasp_param Current value of a CLASP param.\n\
+             clasp_param{address=\"/sensors/count\"} 3\n\
+             clasp_param{address=\"/sensors/door\"} 1\n\
+             clasp_param{address=\"/sensors/temp\"} 21.5\n\
+             # EOF\n"
+        );
+    }
+
+    #[test]
+    fn overlapping_patterns_do_not_duplicate_series() {
+        let output = render(
+            &sample_state(),
+            &["/sensors/temp".to_string(), "/sensors/*".to_string()],
+        );
+        assert_eq!(output.matches("/sensors/temp").count(), 1);
+        assert!(!output.contains("/lights/1"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let state = RouterState::new();
+        set(&state, "/odd/\"quoted\"", Value::Int(1));
+        let output = render(&state, &["/odd/*".to_string()]);
+        assert!(output.contains("clasp_param{address=\"/odd/\\\"quoted\\\"\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn http_endpoint_uses_openmetrics_content_type() {
+        let app = param_metrics_router(Arc::new(ParamMetricsState {
+            state: Arc::new(sample_state()),
+            patterns: vec!["/lights/**".to_string()],
+        }));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics/params")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("application/openmetrics-text"));
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(body.contains("clasp_param{address=\"/lights/1\"} 0.5\n"));
+        assert!(body.ends_with("# EOF\n"));
+    }
+}
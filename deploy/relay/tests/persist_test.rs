@@ -14,7 +14,24 @@ fn test_router() -> Router {
         gesture_coalesce_interval_ms: 16,
         max_messages_per_second: 0,
         rate_limiting_enabled: false,
+        rate_limits: Default::default(),
         state_config: RouterStateConfig::unlimited(),
+        session_handoff: Default::default(),
+        session_limit: Default::default(),
+        durable_session_ttl: 0,
+        validation: Default::default(),
+        overload: Default::default(),
+        quota: Default::default(),
+        topic_aliases: Default::default(),
+        shadow: Default::default(),
+        presence: false,
+        transport_policies: Default::default(),
+        aggregates: Vec::new(),
+        schema: Default::default(),
+        address_policy: Default::default(),
+        replay: Default::default(),
+        reserved: Default::default(),
+        delivery_stats: false,
     };
     Router::new(config)
 }
@@ -126,3 +143,51 @@ fn snapshot_restore_into_fresh_router() {
     let count = dest_snapshot.params.iter().find(|p| p.address == "/user/count").unwrap();
     assert_eq!(count.value, Value::Int(5));
 }
+
+#[cfg(feature = "state-db")]
+#[test]
+fn state_db_survives_router_restart() {
+    use std::sync::Arc;
+
+    let path = std::env::temp_dir().join(format!("clasp-relay-state-{}.db", std::process::id()));
+    let admin = "admin".to_string();
+
+    {
+        let backend = clasp_router::SqliteStateBackend::open(&path).unwrap();
+        let router = test_router().with_state_backend(Arc::new(backend));
+        router
+            .state()
+            .set("/room/1/name", Value::String("General".into()), &admin, None, false, false, None)
+            .unwrap();
+        router
+            .state()
+            .set("/room/1/name", Value::String("Lobby".into()), &admin, None, false, false, None)
+            .unwrap();
+        router.state().register_signals(vec![clasp_core::SignalDefinition {
+            address: "/room/1/volume".to_string(),
+            signal_type: clasp_core::SignalType::Param,
+            datatype: Some("f".to_string()),
+            access: Some("rw".to_string()),
+            meta: Some(clasp_core::SignalMeta {
+                unit: Some("dB".to_string()),
+                range: Some((-60.0, 0.0)),
+                default: None,
+                description: None,
+                enum_values: None,
+            }),
+        }]);
+    }
+
+    let backend = clasp_router::SqliteStateBackend::open(&path).unwrap();
+    let router = test_router().with_state_backend(Arc::new(backend));
+    assert_eq!(router.state().restore_from_backend().unwrap(), 1);
+    let name = router.state().get_state("/room/1/name").unwrap();
+    assert_eq!(name.value, Value::String("Lobby".into()));
+    assert_eq!(name.revision, 2);
+    assert_eq!(router.state().signal_count(), 1);
+    assert_eq!(router.state().signal_unit("/room/1/volume").as_deref(), Some("dB"));
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
@@ -0,0 +1,183 @@
+//! Tests for the journal read model projector.
+//!
+//! Gated behind `#[cfg(feature = "projector")]` since the module is optional.
+//! Run with: cargo test --features projector
+
+#[cfg(feature = "projector")]
+mod projector_tests {
+    use clasp_core::{SignalType, Value};
+    use clasp_journal::{Journal, JournalEntry, MemoryJournal};
+    use clasp_relay::projector::{
+        Dialect, ProjectionStore, Projector, ProjectorConfig, SqliteStore, TableSpec,
+    };
+    use std::sync::Arc;
+
+    fn config() -> ProjectorConfig {
+        serde_json::from_value(serde_json::json!({
+            "sqlite": ":memory:",
+            "tables": [
+                {
+                    "path": "/lights/{zone}/{fixture}/brightness",
+                    "table": "light_levels",
+                    "mode": "current",
+                    "columns": { "zone": "zone", "fixture": "fixture", "level": "number" }
+                },
+                { "path": "/lights/**", "table": "light_history", "mode": "history" }
+            ],
+            "batch_size": 2
+        }))
+        .unwrap()
+    }
+
+    async fn set(journal: &MemoryJournal, address: &str, value: f64, timestamp: u64) {
+        journal
+            .append(JournalEntry::from_set(
+                address.to_string(),
+                Value::Float(value),
+                1,
+                "session-1".to_string(),
+                timestamp,
+            ))
+            .await
+            .unwrap();
+    }
+
+    fn projector(
+        journal: &Arc<MemoryJournal>,
+        store: &Arc<SqliteStore>,
+    ) -> (Projector, Vec<TableSpec>) {
+        let config = config();
+        let tables = Projector::table_specs(&config).unwrap();
+        let projector = Projector::new(
+            config,
+            tables.clone(),
+            Arc::clone(journal) as Arc<dyn Journal>,
+            Arc::clone(store) as Arc<dyn ProjectionStore>,
+        );
+        (projector, tables)
+    }
+
+    #[test]
+    fn table_specs_add_key_columns() {
+        let tables = Projector::table_specs(&config()).unwrap();
+        let names: Vec<&str> = tables[0].columns.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["fixture", "level", "zone", "address"]);
+        assert_eq!(
+            tables[0].create_sql(Dialect::Postgres),
+            "CREATE TABLE IF NOT EXISTS light_levels (fixture TEXT, level DOUBLE PRECISION, \
+             zone TEXT, address TEXT, PRIMARY KEY (address))"
+        );
+
+        let names: Vec<&str> = tables[1].columns.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["address", "value", "timestamp", "seq"]);
+        assert!(tables[1]
+            .write_sql(Dialect::Sqlite)
+            .ends_with("ON CONFLICT (seq) DO NOTHING"));
+    }
+
+    #[test]
+    fn table_specs_reject_bad_mappings() {
+        for table in [
+            serde_json::json!({ "path": "/a/{x}", "table": "t; DROP", "mode": "current" }),
+            serde_json::json!({ "path": "/a/{x}", "table": "t", "columns": { "c": "missing" } }),
+            serde_json::json!({ "path": "/a", "table": "t", "columns": { "bad name": "value" } }),
+            serde_json::json!({ "path": "/a", "table": "t", "mode": "history",
+                                "columns": { "seq": "value" } }),
+        ] {
+            let config: ProjectorConfig =
+                serde_json::from_value(serde_json::json!({ "tables": [table] })).unwrap();
+            assert!(Projector::table_specs(&config).is_err(), "{:?}", table);
+        }
+    }
+
+    #[tokio::test]
+    async fn projects_current_and_history_tables() {
+        let journal = Arc::new(MemoryJournal::new(1000));
+        set(&journal, "/lights/stage/par1/brightness", 0.5, 100).await;
+        set(&journal, "/lights/stage/par1/brightness", 0.8, 200).await;
+        set(&journal, "/lights/house/wash/brightness", 0.1, 300).await;
+        journal
+            .append(JournalEntry::from_publish(
+                "/audio/beat".to_string(),
+                SignalType::Event,
+                Value::Null,
+                "session-1".to_string(),
+                400,
+            ))
+            .await
+            .unwrap();
+
+        let tables = Projector::table_specs(&config()).unwrap();
+        let store = Arc::new(SqliteStore::in_memory(&tables).unwrap());
+        let (mut projector, _) = projector(&journal, &store);
+        // batch_size 2: two full batches, then caught up
+        assert_eq!(projector.step().await.unwrap(), 2);
+        assert_eq!(projector.step().await.unwrap(), 2);
+        assert_eq!(projector.step().await.unwrap(), 0);
+
+        let current: Vec<(String, String, f64)> = store.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT zone, fixture, level FROM light_levels ORDER BY zone")
+                .unwrap();
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        });
+        assert_eq!(
+            current,
+            vec![
+                ("house".to_string(), "wash".to_string(), 0.1),
+                ("stage".to_string(), "par1".to_string(), 0.8),
+            ]
+        );
+
+        let history: Vec<(i64, String)> = store.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT timestamp, value FROM light_history ORDER BY seq")
+                .unwrap();
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        });
+        assert_eq!(
+            history,
+            vec![
+                (100, "0.5".to_string()),
+                (200, "0.8".to_string()),
+                (300, "0.1".to_string())
+            ]
+        );
+        assert_eq!(store.offset("default").await.unwrap(), 4);
+        assert_eq!(
+            projector
+                .stats
+                .rows
+                .load(std::sync::atomic::Ordering::Relaxed),
+            6
+        );
+    }
+
+    #[tokio::test]
+    async fn resumes_from_stored_offset() {
+        let journal = Arc::new(MemoryJournal::new(1000));
+        set(&journal, "/lights/a/b/brightness", 0.1, 100).await;
+
+        let tables = Projector::table_specs(&config()).unwrap();
+        let store = Arc::new(SqliteStore::in_memory(&tables).unwrap());
+        let (mut first, _) = projector(&journal, &store);
+        first.step().await.unwrap();
+
+        set(&journal, "/lights/a/b/brightness", 0.2, 200).await;
+        // A new projector (e.g. after a restart) starts after the stored offset
+        let (mut second, _) = projector(&journal, &store);
+        assert_eq!(second.step().await.unwrap(), 1);
+
+        let rows: i64 = store.with_conn(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM light_history", [], |r| r.get(0))
+                .unwrap()
+        });
+        assert_eq!(rows, 2);
+    }
+}
@@ -0,0 +1,340 @@
+//! Tests for the push notification gateway.
+//!
+//! Gated behind `#[cfg(feature = "push")]` since the push module is optional.
+//! Run with: cargo test --features push
+
+#[cfg(feature = "push")]
+mod push_tests {
+    use async_trait::async_trait;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+    use clasp_core::Value;
+    use clasp_relay::push::{
+        push_router, Device, DeviceStore, Notification, Platform, PushApiState, PushConfig,
+        PushError, PushGateway, PushSender,
+    };
+    use clasp_router::{Session, SessionId};
+    use dashmap::DashMap;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value as JsonValue};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tower::ServiceExt;
+
+    fn dm_config() -> PushConfig {
+        serde_json::from_value(json!({
+            "rules": [{
+                "path": "/chat/user/{targetId}/dms/{roomId}",
+                "target": "targetId",
+                "title": "New message from {fromName}",
+                "body": "{text}",
+                "data": { "room": "{roomId}" }
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn dm_value(text: &str) -> Value {
+        let mut map = HashMap::new();
+        map.insert("fromId".to_string(), Value::String("alice".into()));
+        map.insert("fromName".to_string(), Value::String("Alice".into()));
+        map.insert("text".to_string(), Value::String(text.into()));
+        Value::Map(map)
+    }
+
+    /// Records deliveries; tokens starting with "stale" are rejected as
+    /// invalid and tokens starting with "hang" never complete.
+    #[derive(Default)]
+    struct RecordingSender {
+        sent: Mutex<Vec<(Device, Notification)>>,
+    }
+
+    #[async_trait]
+    impl PushSender for RecordingSender {
+        async fn send(&self, device: &Device, n: &Notification) -> Result<(), PushError> {
+            if device.token.starts_with("stale") {
+                return Err(PushError::InvalidToken);
+            }
+            if device.token.starts_with("hang") {
+                std::future::pending::<()>().await;
+            }
+            self.sent.lock().unwrap().push((device.clone(), n.clone()));
+            Ok(())
+        }
+    }
+
+    fn device(platform: Platform, token: &str) -> Device {
+        Device {
+            platform,
+            token: token.to_string(),
+        }
+    }
+
+    // -- Rule evaluation --
+
+    #[test]
+    fn evaluate_renders_captures_and_value_fields() {
+        let config = dm_config();
+        let (target, n) = config
+            .evaluate("/chat/user/bob/dms/room1", &dm_value("hi bob"))
+            .unwrap();
+        assert_eq!(target, "bob");
+        assert_eq!(n.title, "New message from Alice");
+        assert_eq!(n.body, "hi bob");
+        assert_eq!(n.data.get("room").map(String::as_str), Some("room1"));
+    }
+
+    #[test]
+    fn evaluate_ignores_non_matching_address() {
+        let config = dm_config();
+        assert!(config
+            .evaluate("/chat/room/r1/messages", &dm_value("x"))
+            .is_none());
+    }
+
+    #[test]
+    fn evaluate_ignores_null_writes() {
+        let config = dm_config();
+        assert!(config
+            .evaluate("/chat/user/bob/dms/room1", &Value::Null)
+            .is_none());
+    }
+
+    #[test]
+    fn evaluate_leaves_unknown_placeholders() {
+        let config: PushConfig = serde_json::from_value(json!({
+            "rules": [{ "path": "/alerts/{who}", "target": "who", "title": "{missing} alert" }]
+        }))
+        .unwrap();
+        let (_, n) = config.evaluate("/alerts/bob", &Value::Int(1)).unwrap();
+        assert_eq!(n.title, "{missing} alert");
+        assert_eq!(n.body, "");
+    }
+
+    #[test]
+    fn evaluate_skips_rules_without_target_capture() {
+        let config: PushConfig = serde_json::from_value(json!({
+            "rules": [
+                { "path": "/alerts/{who}", "target": "owner", "title": "unreachable" },
+                { "path": "/alerts/{who}", "target": "who", "title": "alert" }
+            ]
+        }))
+        .unwrap();
+        let (target, n) = config.evaluate("/alerts/bob", &Value::Int(1)).unwrap();
+        assert_eq!(target, "bob");
+        assert_eq!(n.title, "alert");
+    }
+
+    #[test]
+    fn validate_rejects_target_outside_path() {
+        assert!(dm_config().validate().is_ok());
+        let config: PushConfig = serde_json::from_value(json!({
+            "rules": [{ "path": "/alerts/{who}", "target": "owner", "title": "x" }]
+        }))
+        .unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("owner"), "{}", err);
+    }
+
+    #[test]
+    fn subscription_patterns_replace_captures_with_wildcards() {
+        assert_eq!(
+            dm_config().subscription_patterns(),
+            vec!["/chat/user/*/dms/*".to_string()]
+        );
+    }
+
+    // -- Device store --
+
+    #[test]
+    fn device_store_register_list_unregister() {
+        let store = DeviceStore::open(":memory:").unwrap();
+        store.register("bob", &device(Platform::Fcm, "t1")).unwrap();
+        store.register("bob", &device(Platform::Apns, "t2")).unwrap();
+        store.register("bob", &device(Platform::Fcm, "t1")).unwrap();
+        store.register("carol", &device(Platform::Webpush, "t3")).unwrap();
+
+        let devices = store.devices("bob").unwrap();
+        assert_eq!(devices.len(), 2);
+        assert!(devices.contains(&device(Platform::Apns, "t2")));
+
+        assert!(store.unregister("bob", "t1").unwrap());
+        assert!(!store.unregister("bob", "t1").unwrap());
+        assert!(!store.unregister("bob", "t3").unwrap());
+        assert_eq!(store.devices("bob").unwrap().len(), 1);
+    }
+
+    // -- Gateway --
+
+    #[tokio::test]
+    async fn gateway_notifies_offline_subject() {
+        let store = Arc::new(DeviceStore::open(":memory:").unwrap());
+        store.register("bob", &device(Platform::Fcm, "t1")).unwrap();
+        let sender = Arc::new(RecordingSender::default());
+        let gateway = PushGateway::new(dm_config(), store, sender.clone());
+        let sessions: DashMap<SessionId, Arc<Session>> = DashMap::new();
+
+        let n = gateway
+            .handle_write("/chat/user/bob/dms/room1", &dm_value("hi"), &sessions)
+            .await;
+        assert_eq!(n, 1);
+        let sent = sender.sent.lock().unwrap();
+        assert_eq!(sent[0].0.token, "t1");
+        assert_eq!(sent[0].1.body, "hi");
+    }
+
+    #[tokio::test]
+    async fn gateway_skips_online_subject() {
+        let store = Arc::new(DeviceStore::open(":memory:").unwrap());
+        store.register("bob", &device(Platform::Fcm, "t1")).unwrap();
+        let sender = Arc::new(RecordingSender::default());
+        let gateway = PushGateway::new(dm_config(), store, sender.clone());
+
+        let sessions: DashMap<SessionId, Arc<Session>> = DashMap::new();
+        let bob = Arc::new(Session::stub(Some("bob".into())));
+        sessions.insert(bob.id.clone(), bob);
+
+        let n = gateway
+            .handle_write("/chat/user/bob/dms/room1", &dm_value("hi"), &sessions)
+            .await;
+        assert_eq!(n, 0);
+        assert!(sender.sent.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn gateway_removes_invalid_tokens() {
+        let store = Arc::new(DeviceStore::open(":memory:").unwrap());
+        store.register("bob", &device(Platform::Fcm, "stale-1")).unwrap();
+        store.register("bob", &device(Platform::Apns, "t2")).unwrap();
+        let sender = Arc::new(RecordingSender::default());
+        let gateway = PushGateway::new(dm_config(), Arc::clone(&store), sender);
+        let sessions: DashMap<SessionId, Arc<Session>> = DashMap::new();
+
+        let n = gateway
+            .handle_write("/chat/user/bob/dms/room1", &dm_value("hi"), &sessions)
+            .await;
+        assert_eq!(n, 1);
+        assert_eq!(store.devices("bob").unwrap(), vec![device(Platform::Apns, "t2")]);
+    }
+
+    #[tokio::test]
+    async fn gateway_times_out_slow_provider() {
+        let store = Arc::new(DeviceStore::open(":memory:").unwrap());
+        store.register("bob", &device(Platform::Fcm, "hang-1")).unwrap();
+        store.register("bob", &device(Platform::Apns, "t2")).unwrap();
+        let sender = Arc::new(RecordingSender::default());
+        let config = PushConfig {
+            send_timeout_ms: 50,
+            ..dm_config()
+        };
+        let gateway = PushGateway::new(config, Arc::clone(&store), sender.clone());
+        let sessions: DashMap<SessionId, Arc<Session>> = DashMap::new();
+
+        let started = Instant::now();
+        let n = gateway
+            .handle_write("/chat/user/bob/dms/room1", &dm_value("hi"), &sessions)
+            .await;
+        assert_eq!(n, 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(sender.sent.lock().unwrap()[0].0.token, "t2");
+        // A timeout isn't an invalid token; the device is kept
+        assert_eq!(store.devices("bob").unwrap().len(), 2);
+    }
+
+    // -- Registration API --
+
+    fn setup_api() -> (axum::Router, Arc<DeviceStore>, String) {
+        let store = Arc::new(DeviceStore::open(":memory:").unwrap());
+        let validator = Arc::new(CpskValidator::new());
+        let token = CpskValidator::generate_token();
+        validator.register(
+            token.clone(),
+            TokenInfo::new(token.clone(), vec![Scope::parse("read:/**").unwrap()])
+                .with_subject("bob"),
+        );
+        let state = Arc::new(PushApiState {
+            store: Arc::clone(&store),
+            validator,
+        });
+        (push_router(state), store, token)
+    }
+
+    async fn request(
+        app: axum::Router,
+        method: &str,
+        token: Option<&str>,
+        body: Option<JsonValue>,
+    ) -> (StatusCode, JsonValue) {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri("/api/push/devices")
+            .header("content-type", "application/json");
+        if let Some(t) = token {
+            builder = builder.header("authorization", format!("Bearer {}", t));
+        }
+        let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+        let response = app.oneshot(builder.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap_or(json!({})))
+    }
+
+    #[tokio::test]
+    async fn api_register_stores_device_for_token_subject() {
+        let (app, store, token) = setup_api();
+        let (status, _) = request(
+            app.clone(),
+            "POST",
+            Some(&token),
+            Some(json!({ "platform": "apns", "token": "abc123" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(store.devices("bob").unwrap(), vec![device(Platform::Apns, "abc123")]);
+
+        let (status, body) = request(app, "GET", Some(&token), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["devices"][0]["platform"], "apns");
+        assert_eq!(body["devices"][0]["token"], "abc123");
+    }
+
+    #[tokio::test]
+    async fn api_requires_valid_token() {
+        let (app, _, _) = setup_api();
+        let body = json!({ "platform": "fcm", "token": "abc" });
+        let (status, _) = request(app.clone(), "POST", None, Some(body.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = request(app, "POST", Some("cpsk_bogus"), Some(body)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn api_rejects_empty_token() {
+        let (app, _, token) = setup_api();
+        let (status, _) = request(
+            app,
+            "POST",
+            Some(&token),
+            Some(json!({ "platform": "fcm", "token": "  " })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn api_unregister_device() {
+        let (app, store, token) = setup_api();
+        store.register("bob", &device(Platform::Fcm, "t1")).unwrap();
+
+        let (status, _) =
+            request(app.clone(), "DELETE", Some(&token), Some(json!({ "token": "t1" }))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(store.devices("bob").unwrap().is_empty());
+
+        let (status, _) =
+            request(app, "DELETE", Some(&token), Some(json!({ "token": "t1" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
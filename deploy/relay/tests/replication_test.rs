@@ -0,0 +1,245 @@
+//! Tests for journal replication to a warm standby.
+//!
+//! Gated behind `#[cfg(feature = "replication")]` since the module is optional.
+//! Run with: cargo test --features replication
+
+#[cfg(feature = "replication")]
+mod replication_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+    use clasp_core::{SetMessage, Value};
+    use clasp_journal::{Journal, SqliteJournal};
+    use clasp_relay::replication::{
+        replication_router, run_follower, FollowerConfig, ReplicatedJournal, Replication,
+        ReplicationApiState, Role, StatusResponse,
+    };
+    use clasp_router::{RouterState, SubscriptionManager};
+    use dashmap::DashMap;
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    struct Node {
+        api: Arc<ReplicationApiState>,
+        state: Arc<RouterState>,
+        journal: Arc<ReplicatedJournal>,
+    }
+
+    fn admin_validator() -> (Arc<CpskValidator>, String) {
+        let validator = Arc::new(CpskValidator::new());
+        let token = CpskValidator::generate_token();
+        validator.register(
+            token.clone(),
+            TokenInfo::new(token.clone(), vec![Scope::parse("admin:/**").unwrap()]),
+        );
+        (validator, token)
+    }
+
+    fn node(replication: Replication, validator: &Arc<CpskValidator>) -> Node {
+        let journal = Arc::new(ReplicatedJournal::new(Arc::new(
+            SqliteJournal::in_memory().unwrap(),
+        )));
+        let mut state = RouterState::new();
+        state.set_journal(journal.clone());
+        let state = Arc::new(state);
+        Node {
+            api: Arc::new(ReplicationApiState {
+                replication: Arc::new(replication),
+                journal: Some(journal.clone()),
+                state: state.clone(),
+                validator: validator.clone(),
+            }),
+            state,
+            journal,
+        }
+    }
+
+    async fn serve(api: Arc<ReplicationApiState>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, replication_router(api))
+                .await
+                .unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn set(state: &RouterState, address: &str, value: Value) -> u64 {
+        let msg = SetMessage {
+            address: address.to_string(),
+            value,
+            revision: None,
+            lock: false,
+            unlock: false,
+            ttl: None,
+            trace: false,
+            branch: None,
+        };
+        state.apply_set(&msg, &"writer".to_string()).unwrap()
+    }
+
+    async fn wait_until(mut check: impl FnMut() -> bool) {
+        for _ in 0..100 {
+            if check() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    async fn status(api: &Arc<ReplicationApiState>, token: &str) -> StatusResponse {
+        let resp = replication_router(api.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/replication/status")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stream_requires_admin_token() {
+        let (validator, _) = admin_validator();
+        let primary = node(Replication::primary(), &validator);
+        let resp = replication_router(primary.api.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/replication/stream")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn stream_without_journal_is_unavailable() {
+        let (validator, token) = admin_validator();
+        let api = Arc::new(ReplicationApiState {
+            replication: Arc::new(Replication::primary()),
+            journal: None,
+            state: Arc::new(RouterState::new()),
+            validator,
+        });
+        let resp = replication_router(api)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/replication/stream")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn standby_follows_acks_and_promotes() {
+        let (validator, token) = admin_validator();
+        let primary = node(Replication::primary(), &validator);
+        let url = serve(primary.api.clone()).await;
+
+        // State from before the standby connects arrives as a snapshot
+        set(&primary.state, "/mixer/gain", Value::Float(0.5));
+        set(&primary.state, "/mixer/gain", Value::Float(0.7));
+        wait_until(|| primary.journal.subscribe().borrow().eq(&2)).await;
+
+        let standby = node(Replication::standby(url.clone()), &validator);
+        tokio::spawn(run_follower(
+            FollowerConfig {
+                primary: url,
+                token: token.clone(),
+                id: "standby-1".to_string(),
+            },
+            standby.api.replication.clone(),
+            standby.state.clone(),
+            Arc::new(DashMap::new()),
+            Arc::new(SubscriptionManager::new()),
+        ));
+
+        let state = standby.state.clone();
+        wait_until(|| state.get("/mixer/gain") == Some(Value::Float(0.7))).await;
+        assert_eq!(state.get_state("/mixer/gain").unwrap().revision, 2);
+
+        // New writes stream live with their revisions
+        set(&primary.state, "/mixer/mute", Value::Bool(true));
+        let revision = set(&primary.state, "/mixer/gain", Value::Float(0.9));
+        assert_eq!(revision, 3);
+        wait_until(|| state.get("/mixer/gain") == Some(Value::Float(0.9))).await;
+        assert_eq!(state.get_state("/mixer/gain").unwrap().revision, 3);
+        assert_eq!(state.get("/mixer/mute"), Some(Value::Bool(true)));
+        assert!(standby.journal.latest_seq().await.unwrap() >= 2);
+
+        // The primary sees the follower's acknowledgements
+        let replication = standby.api.replication.clone();
+        wait_until(|| replication.applied_seq() == 4).await;
+        let mut acked = false;
+        for _ in 0..100 {
+            let report = status(&primary.api, &token).await;
+            if report
+                .followers
+                .iter()
+                .any(|f| f.id == "standby-1" && f.acked_seq == 4 && f.lag == 0 && f.connected)
+            {
+                acked = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(acked, "primary never saw the ack");
+
+        // Promotion stops following and keeps the revisions
+        let resp = replication_router(standby.api.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/replication/promote")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(standby.api.replication.role(), Role::Primary);
+
+        set(&primary.state, "/mixer/gain", Value::Float(0.1));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(state.get("/mixer/gain"), Some(Value::Float(0.9)));
+        assert_eq!(set(&state, "/mixer/gain", Value::Float(0.2)), 4);
+
+        let report = status(&standby.api, &token).await;
+        assert_eq!(report.role, Role::Primary);
+        assert_eq!(report.applied_seq, 4);
+    }
+
+    #[tokio::test]
+    async fn promote_primary_conflicts() {
+        let (validator, token) = admin_validator();
+        let primary = node(Replication::primary(), &validator);
+        let resp = replication_router(primary.api.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/replication/promote")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+}
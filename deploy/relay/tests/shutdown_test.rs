@@ -0,0 +1,39 @@
+//! Tests for the ordered shutdown steps.
+
+use clasp_relay::shutdown::ShutdownHooks;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[tokio::test]
+async fn steps_run_in_order() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let mut hooks = ShutdownHooks::new(Duration::from_secs(5));
+    for name in ["drain", "flush", "checkpoint"] {
+        let order = Arc::clone(&order);
+        hooks.add(name, move || async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            order.lock().unwrap().push(name);
+        });
+    }
+
+    let report = hooks.run().await;
+    assert_eq!(report.completed, vec!["drain", "flush", "checkpoint"]);
+    assert_eq!(report.timed_out, None);
+    assert!(report.skipped.is_empty());
+    assert_eq!(*order.lock().unwrap(), vec!["drain", "flush", "checkpoint"]);
+}
+
+#[tokio::test]
+async fn grace_period_abandons_the_running_step_and_skips_the_rest() {
+    let mut hooks = ShutdownHooks::new(Duration::from_millis(200));
+    hooks.add("drain", || async {});
+    hooks.add("flush", || std::future::pending::<()>());
+    hooks.add("checkpoint", || async {});
+
+    let started = std::time::Instant::now();
+    let report = hooks.run().await;
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(report.completed, vec!["drain"]);
+    assert_eq!(report.timed_out, Some("flush"));
+    assert_eq!(report.skipped, vec!["checkpoint"]);
+}
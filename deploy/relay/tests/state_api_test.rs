@@ -0,0 +1,235 @@
+//! Tests for the bulk state import/export REST API.
+//!
+//! Gated behind `#[cfg(feature = "state-api")]` since the module is optional.
+//! Run with: cargo test --features state-api
+
+#[cfg(feature = "state-api")]
+mod state_api_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use clasp_core::security::{CpskValidator, Scope, TokenInfo};
+    use clasp_core::Value;
+    use clasp_relay::state_api::{state_router, StateApiState};
+    use clasp_router::{RouterState, SubscriptionManager};
+    use dashmap::DashMap;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value as JsonValue};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    struct TestHarness {
+        state: Arc<StateApiState>,
+        admin_token: String,
+        read_only_token: String,
+    }
+
+    impl TestHarness {
+        fn new() -> Self {
+            let validator = Arc::new(CpskValidator::new());
+            let admin_token = CpskValidator::generate_token();
+            let read_only_token = CpskValidator::generate_token();
+            validator.register(
+                admin_token.clone(),
+                TokenInfo::new(
+                    admin_token.clone(),
+                    vec![Scope::parse("admin:/**").unwrap()],
+                ),
+            );
+            validator.register(
+                read_only_token.clone(),
+                TokenInfo::new(
+                    read_only_token.clone(),
+                    vec![Scope::parse("read:/**").unwrap()],
+                ),
+            );
+
+            let router_state = Arc::new(RouterState::new());
+            for (address, value) in [
+                ("/venue/lights/1", Value::Float(0.5)),
+                ("/venue/lights/2", Value::Float(0.8)),
+                ("/other/x", Value::Int(1)),
+            ] {
+                router_state
+                    .set(
+                        address,
+                        value,
+                        &"seed".to_string(),
+                        None,
+                        false,
+                        false,
+                        None,
+                    )
+                    .unwrap();
+            }
+
+            Self {
+                state: Arc::new(StateApiState {
+                    sessions: Arc::new(DashMap::new()),
+                    subscriptions: Arc::new(SubscriptionManager::new()),
+                    state: router_state,
+                    validator,
+                }),
+                admin_token,
+                read_only_token,
+            }
+        }
+
+        async fn request(
+            &self,
+            method: &str,
+            uri: &str,
+            token: &str,
+            body: Option<JsonValue>,
+        ) -> (StatusCode, JsonValue) {
+            let builder = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json");
+            let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+            let resp = state_router(self.state.clone())
+                .oneshot(builder.body(body).unwrap())
+                .await
+                .unwrap();
+            let status = resp.status();
+            let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+            (status, serde_json::from_slice(&bytes).unwrap_or(json!({})))
+        }
+    }
+
+    #[tokio::test]
+    async fn export_requires_admin_scope() {
+        let h = TestHarness::new();
+        let (status, _) = h
+            .request("GET", "/api/state/export", &h.read_only_token, None)
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = h.request("GET", "/api/state/export", "bogus", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn export_subtree_with_revisions() {
+        let h = TestHarness::new();
+        let (status, body) = h
+            .request(
+                "GET",
+                "/api/state/export?pattern=/venue/**",
+                &h.admin_token,
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pattern"], "/venue/**");
+        let params = body["params"].as_array().unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0]["address"], "/venue/lights/1");
+        assert_eq!(params[0]["value"], 0.5);
+        assert_eq!(params[0]["revision"], 1);
+    }
+
+    #[tokio::test]
+    async fn export_then_import_round_trips() {
+        let h = TestHarness::new();
+        let (_, document) = h
+            .request(
+                "GET",
+                "/api/state/export?pattern=/venue/**",
+                &h.admin_token,
+                None,
+            )
+            .await;
+        h.state
+            .state
+            .set(
+                "/venue/lights/1",
+                Value::Float(0.0),
+                &"other".to_string(),
+                None,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+
+        let (status, body) = h
+            .request("POST", "/api/state/import", &h.admin_token, Some(document))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["params"][0]["revision"], 3);
+        assert_eq!(
+            h.state.state.get("/venue/lights/1"),
+            Some(Value::Float(0.5))
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_leaves_state_untouched() {
+        let h = TestHarness::new();
+        let document = json!({ "params": [
+            { "address": "/venue/lights/1", "value": 1.0 },
+            { "address": "/venue/new", "value": "x" }
+        ]});
+        let (status, body) = h
+            .request(
+                "POST",
+                "/api/state/import?dry_run=true",
+                &h.admin_token,
+                Some(document),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["dry_run"], true);
+        assert_eq!(body["params"][0]["revision"], 2);
+        assert_eq!(
+            h.state.state.get("/venue/lights/1"),
+            Some(Value::Float(0.5))
+        );
+        assert!(h.state.state.get("/venue/new").is_none());
+    }
+
+    #[tokio::test]
+    async fn rejected_param_aborts_whole_import() {
+        let h = TestHarness::new();
+        h.state
+            .state
+            .set(
+                "/venue/locked",
+                Value::Int(0),
+                &"holder".to_string(),
+                None,
+                true,
+                false,
+                None,
+            )
+            .unwrap();
+        let document = json!({ "params": [
+            { "address": "/venue/lights/1", "value": 1.0 },
+            { "address": "/venue/locked", "value": 1 }
+        ]});
+        let (status, body) = h
+            .request("POST", "/api/state/import", &h.admin_token, Some(document))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["address"], "/venue/locked");
+        assert_eq!(
+            h.state.state.get("/venue/lights/1"),
+            Some(Value::Float(0.5))
+        );
+    }
+
+    #[tokio::test]
+    async fn import_validates_addresses() {
+        let h = TestHarness::new();
+        for document in [
+            json!({ "params": [{ "address": "/venue/*", "value": 1 }] }),
+            json!({ "pattern": "/venue/**", "params": [{ "address": "/other/x", "value": 1 }] }),
+        ] {
+            let (status, _) = h
+                .request("POST", "/api/state/import", &h.admin_token, Some(document))
+                .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Tests for the time-series sink.
+//!
+//! Gated behind `#[cfg(feature = "timeseries")]` since the module is optional.
+//! Run with: cargo test --features timeseries
+
+#[cfg(feature = "timeseries")]
+mod timeseries_tests {
+    use async_trait::async_trait;
+    use clasp_core::Value;
+    use clasp_relay::timeseries::{
+        build_writer, Point, PointValue, SeriesWriter, SinkError, TimeseriesConfig, TimeseriesSink,
+    };
+    use std::collections::{BTreeMap, VecDeque};
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+
+    /// Records batches and fails according to a scripted queue of results.
+    #[derive(Default)]
+    struct MockWriter {
+        batches: Mutex<Vec<Vec<Point>>>,
+        script: Mutex<VecDeque<Result<(), SinkError>>>,
+    }
+
+    impl MockWriter {
+        fn failing(results: Vec<Result<(), SinkError>>) -> Arc<Self> {
+            Arc::new(Self {
+                batches: Mutex::new(Vec::new()),
+                script: Mutex::new(results.into()),
+            })
+        }
+
+        fn written(&self) -> usize {
+            self.batches.lock().unwrap().iter().map(Vec::len).sum()
+        }
+    }
+
+    #[async_trait]
+    impl SeriesWriter for MockWriter {
+        async fn write(&self, points: &[Point]) -> Result<(), SinkError> {
+            let result = self.script.lock().unwrap().pop_front().unwrap_or(Ok(()));
+            if result.is_ok() {
+                self.batches.lock().unwrap().push(points.to_vec());
+            }
+            result
+        }
+    }
+
+    fn config(extra: serde_json::Value) -> TimeseriesConfig {
+        let mut json = serde_json::json!({
+            "rules": [
+                { "path": "/sensors/{site}/{metric}", "measurement": "environment",
+                  "tags": { "source": "relay" } },
+                { "path": "/lights/**" }
+            ],
+            "influx": { "url": "http://localhost:8086", "database": "clasp" }
+        });
+        for (k, v) in extra.as_object().unwrap() {
+            json[k] = v.clone();
+        }
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn point_tags_from_path_captures() {
+        let point = config(serde_json::json!({}))
+            .point(
+                "/sensors/lobby/temp",
+                &Value::Float(21.5),
+                1_700_000_000_000_000,
+            )
+            .unwrap();
+        assert_eq!(point.measurement, "environment");
+        assert_eq!(point.tags["site"], "lobby");
+        assert_eq!(point.tags["metric"], "temp");
+        assert_eq!(point.tags["source"], "relay");
+        assert_eq!(point.tags["address"], "/sensors/lobby/temp");
+        assert_eq!(point.value, PointValue::Float(21.5));
+    }
+
+    #[test]
+    fn non_numeric_and_unmatched_values_are_skipped() {
+        let config = config(serde_json::json!({}));
+        assert!(config
+            .point("/sensors/lobby/name", &Value::String("x".into()), 1)
+            .is_none());
+        assert!(config
+            .point("/other/thing", &Value::Float(1.0), 1)
+            .is_none());
+        assert_eq!(
+            config
+                .point("/lights/1/on", &Value::Bool(true), 1)
+                .unwrap()
+                .measurement,
+            "clasp"
+        );
+    }
+
+    #[test]
+    fn subscription_patterns_replace_captures() {
+        assert_eq!(
+            config(serde_json::json!({})).subscription_patterns(),
+            vec!["/sensors/*/*".to_string(), "/lights/**".to_string()]
+        );
+    }
+
+    #[test]
+    fn line_protocol_escapes_and_types_fields() {
+        let mut tags = BTreeMap::new();
+        tags.insert("address".to_string(), "/a b/c,d".to_string());
+        tags.insert("room".to_string(), "x=y".to_string());
+        let mut point = Point {
+            measurement: "my measure".to_string(),
+            tags,
+            value: PointValue::Float(1.0),
+            timestamp: 42,
+        };
+        assert_eq!(
+            point.to_line_protocol(),
+            "my\\ measure,address=/a\\ b/c\\,d,room=x\\=y value=1.0 42"
+        );
+        point.value = PointValue::Int(-3);
+        assert!(point.to_line_protocol().ends_with(" value=-3i 42"));
+        point.value = PointValue::Bool(false);
+        assert!(point.to_line_protocol().ends_with(" value=false 42"));
+    }
+
+    #[tokio::test]
+    async fn flush_writes_in_batches() {
+        let writer = MockWriter::failing(vec![]);
+        let mut sink = TimeseriesSink::new(
+            config(serde_json::json!({ "batch_size": 2 })),
+            writer.clone(),
+        );
+        for i in 0..5 {
+            sink.record("/lights/1", &Value::Int(i), i as u64);
+        }
+        assert!(sink.batch_ready());
+        sink.flush().await;
+
+        let sizes: Vec<usize> = writer
+            .batches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(sink.pending(), 0);
+        assert_eq!(sink.stats.written.load(Ordering::Relaxed), 5);
+    }
+
+    #[tokio::test]
+    async fn unavailable_writes_are_retried_after_backoff() {
+        let writer = MockWriter::failing(vec![Err(SinkError::Unavailable(
+            "connection refused".into(),
+        ))]);
+        let mut sink = TimeseriesSink::new(config(serde_json::json!({})), writer.clone());
+        sink.record("/lights/1", &Value::Float(0.5), 1);
+
+        sink.flush().await;
+        assert_eq!(sink.pending(), 1);
+        assert_eq!(sink.stats.write_errors.load(Ordering::Relaxed), 1);
+
+        // Still backing off
+        sink.flush().await;
+        assert_eq!(writer.written(), 0);
+
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        sink.flush().await;
+        assert_eq!(writer.written(), 1);
+        assert_eq!(sink.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejected_batches_are_dropped() {
+        let writer = MockWriter::failing(vec![Err(SinkError::Rejected("400: bad".into()))]);
+        let mut sink = TimeseriesSink::new(config(serde_json::json!({})), writer.clone());
+        sink.record("/lights/1", &Value::Float(0.5), 1);
+        sink.flush().await;
+        assert_eq!(sink.pending(), 0);
+        assert_eq!(sink.stats.dropped.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn full_buffer_drops_oldest() {
+        let mut sink = TimeseriesSink::new(
+            config(serde_json::json!({ "max_buffer": 3 })),
+            MockWriter::failing(vec![]),
+        );
+        for i in 0..5 {
+            sink.record("/lights/1", &Value::Int(i), i as u64);
+        }
+        assert_eq!(sink.pending(), 3);
+        assert_eq!(sink.stats.dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn writer_requires_a_destination() {
+        let mut config = config(serde_json::json!({}));
+        assert!(build_writer(&config).is_ok());
+        config.influx = None;
+        assert!(build_writer(&config).is_err());
+    }
+}
@@ -16,12 +16,10 @@ use tokio::time::sleep;
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter("info")
-        .init();
+    tracing_subscriber::fmt().with_env_filter("info").init();
 
-    let server_url = std::env::var("CLASP_URL")
-        .unwrap_or_else(|_| "ws://localhost:7330".to_string());
+    let server_url =
+        std::env::var("CLASP_URL").unwrap_or_else(|_| "ws://localhost:7330".to_string());
 
     println!("CLASP Basic Client Example");
     println!("==========================\n");
@@ -49,17 +47,24 @@ async fn main() -> Result<()> {
     println!("Setting up subscriptions...");
 
     // Subscribe to all example parameters
-    let sub_id = client.on("/example/**", |value, address| {
-        println!("  [SUB] {} = {:?}", address, value);
-    }).await?;
+    let sub_id = client
+        .on("/example/**", |value, address| {
+            println!("  [SUB] {} = {:?}", address, value);
+        })
+        .await?;
     println!("  Subscribed to /example/** (id: {})", sub_id);
 
     // Example 3: Emit an event
     println!("\nEmitting events...");
-    client.emit("/example/rust/started", serde_json::json!({
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-        "pid": std::process::id()
-    })).await?;
+    client
+        .emit(
+            "/example/rust/started",
+            serde_json::json!({
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+                "pid": std::process::id()
+            }),
+        )
+        .await?;
     println!("  Emitted /example/rust/started");
 
     // Example 4: Stream high-rate data
@@ -88,36 +93,50 @@ async fn main() -> Result<()> {
     // Example 7: Atomic bundle
     println!("\nSending atomic bundle...");
     use clasp_core::{Message, SetMessage};
-    client.bundle(vec![
-        Message::Set(SetMessage {
-            address: "/example/rust/bundle/a".to_string(),
-            value: 1.0.into(),
-            revision: None,
-            lock: false,
-            unlock: false, ttl: None,
-        }),
-        Message::Set(SetMessage {
-            address: "/example/rust/bundle/b".to_string(),
-            value: 2.0.into(),
-            revision: None,
-            lock: false,
-            unlock: false, ttl: None,
-        }),
-    ]).await?;
+    client
+        .bundle(vec![
+            Message::Set(SetMessage {
+                address: "/example/rust/bundle/a".to_string(),
+                value: 1.0.into(),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            }),
+            Message::Set(SetMessage {
+                address: "/example/rust/bundle/b".to_string(),
+                value: 2.0.into(),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            }),
+        ])
+        .await?;
     println!("  Bundle sent");
 
     // Example 8: Scheduled bundle
     println!("\nSending scheduled bundle (executes in 1 second)...");
     let future_time = client.time() + 1_000_000; // 1 second from now (microseconds)
-    client.bundle_at(vec![
-        Message::Set(SetMessage {
-            address: "/example/rust/scheduled".to_string(),
-            value: "delayed!".into(),
-            revision: None,
-            lock: false,
-            unlock: false, ttl: None,
-        }),
-    ], future_time).await?;
+    client
+        .bundle_at(
+            vec![Message::Set(SetMessage {
+                address: "/example/rust/scheduled".to_string(),
+                value: "delayed!".into(),
+                revision: None,
+                lock: false,
+                unlock: false,
+                ttl: None,
+                trace: false,
+                branch: None,
+            })],
+            future_time,
+        )
+        .await?;
     println!("  Scheduled bundle queued");
 
     // Wait a bit to see scheduled bundle execute
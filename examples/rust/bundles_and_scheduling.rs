@@ -6,7 +6,7 @@
 //!   cargo run --example bundles_and_scheduling
 
 use clasp_client::Clasp;
-use clasp_core::{Message, BundleMessage, SetMessage, PublishMessage};
+use clasp_core::{BundleMessage, Message, PublishMessage, SetMessage};
 use std::env;
 use std::time::Duration;
 
@@ -20,10 +20,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Connected to CLASP server");
 
     // Subscribe to see all changes
-    client.subscribe("/**", |value, address| {
-        let time = chrono::Local::now().format("%H:%M:%S%.3f");
-        println!("[{}] {} = {:?}", time, address, value);
-    }).await?;
+    client
+        .subscribe("/**", |value, address| {
+            let time = chrono::Local::now().format("%H:%M:%S%.3f");
+            println!("[{}] {} = {:?}", time, address, value);
+        })
+        .await?;
 
     tokio::time::sleep(Duration::from_millis(100)).await;
 
@@ -33,23 +35,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n--- 1. Atomic Bundle ---");
     println!("Setting multiple values atomically...");
 
-    client.bundle(vec![
-        Message::Set(SetMessage {
-            address: "/scene/active".to_string(),
-            value: "sunset".into(),
-            revision: None,
-        }),
-        Message::Set(SetMessage {
-            address: "/lights/1/brightness".to_string(),
-            value: 0.8.into(),
-            revision: None,
-        }),
-        Message::Set(SetMessage {
-            address: "/lights/2/brightness".to_string(),
-            value: 0.6.into(),
-            revision: None,
-        }),
-    ], None).await?;
+    client
+        .bundle(
+            vec![
+                Message::Set(SetMessage {
+                    address: "/scene/active".to_string(),
+                    value: "sunset".into(),
+                    revision: None,
+                    trace: false,
+                    branch: None,
+                }),
+                Message::Set(SetMessage {
+                    address: "/lights/1/brightness".to_string(),
+                    value: 0.8.into(),
+                    revision: None,
+                    trace: false,
+                    branch: None,
+                }),
+                Message::Set(SetMessage {
+                    address: "/lights/2/brightness".to_string(),
+                    value: 0.6.into(),
+                    revision: None,
+                    trace: false,
+                    branch: None,
+                }),
+            ],
+            None,
+        )
+        .await?;
 
     println!("Atomic bundle sent!");
     tokio::time::sleep(Duration::from_millis(500)).await;
@@ -63,13 +76,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let execute_at = server_time + 2_000_000; // 2 seconds from now
     println!("Scheduling bundle for 2 seconds from now...");
 
-    client.bundle(vec![
-        Message::Set(SetMessage {
-            address: "/scheduled/counter".to_string(),
-            value: 1.into(),
-            revision: None,
-        }),
-    ], Some(execute_at)).await?;
+    client
+        .bundle(
+            vec![Message::Set(SetMessage {
+                address: "/scheduled/counter".to_string(),
+                value: 1.into(),
+                revision: None,
+                trace: false,
+                branch: None,
+            })],
+            Some(execute_at),
+        )
+        .await?;
 
     println!("Scheduled bundle sent! Waiting for execution...");
     tokio::time::sleep(Duration::from_millis(2500)).await;
@@ -87,18 +105,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let brightness = i as f64 / 5.0;
         let execute_time = animation_start + (i as i64 * step_duration);
 
-        client.bundle(vec![
-            Message::Set(SetMessage {
-                address: "/animation/brightness".to_string(),
-                value: brightness.into(),
-                revision: None,
-            }),
-            Message::Set(SetMessage {
-                address: "/animation/step".to_string(),
-                value: (i as i64).into(),
-                revision: None,
-            }),
-        ], Some(execute_time)).await?;
+        client
+            .bundle(
+                vec![
+                    Message::Set(SetMessage {
+                        address: "/animation/brightness".to_string(),
+                        value: brightness.into(),
+                        revision: None,
+                        trace: false,
+                        branch: None,
+                    }),
+                    Message::Set(SetMessage {
+                        address: "/animation/step".to_string(),
+                        value: (i as i64).into(),
+                        revision: None,
+                        trace: false,
+                        branch: None,
+                    }),
+                ],
+                Some(execute_time),
+            )
+            .await?;
     }
 
     println!("Animation scheduled! Watching...");
@@ -109,20 +136,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // =====================
     println!("\n--- 4. Mixed Bundle with Events and Params ---");
 
-    client.bundle(vec![
-        Message::Set(SetMessage {
-            address: "/cue/current".to_string(),
-            value: "intro".into(),
-            revision: None,
-        }),
-        Message::Publish(PublishMessage {
-            address: "/cue/started".to_string(),
-            signal: clasp_core::SignalType::Event,
-            payload: Some(serde_json::json!({"name": "intro"})),
-            value: None,
-            timestamp: None,
-        }),
-    ], None).await?;
+    client
+        .bundle(
+            vec![
+                Message::Set(SetMessage {
+                    address: "/cue/current".to_string(),
+                    value: "intro".into(),
+                    revision: None,
+                    trace: false,
+                    branch: None,
+                }),
+                Message::Publish(PublishMessage {
+                    address: "/cue/started".to_string(),
+                    signal: clasp_core::SignalType::Event,
+                    payload: Some(serde_json::json!({"name": "intro"})),
+                    value: None,
+                    timestamp: None,
+                }),
+            ],
+            None,
+        )
+        .await?;
 
     println!("Mixed bundle sent!");
     tokio::time::sleep(Duration::from_millis(500)).await;
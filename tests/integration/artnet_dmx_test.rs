@@ -2,7 +2,9 @@
 //!
 //! Tests Art-Net and DMX <-> CLASP conversion
 
-use clasp_bridge::{ArtNetBridge, ArtNetBridgeConfig, DmxBridge, DmxBridgeConfig, DmxInterfaceType};
+use clasp_bridge::{
+    ArtNetBridge, ArtNetBridgeConfig, DmxBridge, DmxBridgeConfig, DmxInterfaceType,
+};
 use clasp_bridge::{Bridge, BridgeEvent};
 use clasp_core::{Message, SetMessage, Value};
 use std::time::Duration;
@@ -15,12 +17,16 @@ async fn test_artnet_bridge_lifecycle() {
         remote_addr: Some("127.0.0.1:6456".to_string()),
         universes: vec![],
         namespace: "/artnet".to_string(),
+        ..Default::default()
     };
 
     let mut bridge = ArtNetBridge::new(config);
 
     // Start bridge
-    let mut rx = bridge.start().await.expect("Failed to start Art-Net bridge");
+    let mut rx = bridge
+        .start()
+        .await
+        .expect("Failed to start Art-Net bridge");
 
     // Check for connected event
     match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
@@ -72,7 +78,10 @@ async fn test_dmx_virtual_bridge() {
         value: Value::Int(255),
         revision: None,
         lock: false,
-        unlock: false, ttl: None,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
     });
 
     bridge.send(msg).await.expect("Failed to send DMX message");
@@ -128,6 +137,7 @@ async fn test_artnet_clasp_conversion() {
         remote_addr: Some("127.0.0.1:6458".to_string()),
         universes: vec![],
         namespace: "/artnet".to_string(),
+        ..Default::default()
     };
 
     let mut bridge = ArtNetBridge::new(config);
@@ -139,7 +149,10 @@ async fn test_artnet_clasp_conversion() {
         value: Value::Int(200),
         revision: None,
         lock: false,
-        unlock: false, ttl: None,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
     });
 
     // This will fail because remote isn't listening, but tests the conversion
@@ -165,7 +178,10 @@ fn test_artnet_address_parsing() {
     assert_eq!(universe, 1);
     assert_eq!(channel, 42);
 
-    println!("Parsed Art-Net address: Universe {}, Channel {}", universe, channel);
+    println!(
+        "Parsed Art-Net address: Universe {}, Channel {}",
+        universe, channel
+    );
 }
 
 #[test]
@@ -182,5 +198,8 @@ fn test_dmx_frame_operations() {
 
     // Note: In virtual mode without sender, get_channel reads from internal state
     // The actual verification would happen in a running bridge
-    println!("DMX frame set with {} channels", frame.iter().filter(|&&v| v > 0).count());
+    println!(
+        "DMX frame set with {} channels",
+        frame.iter().filter(|&&v| v > 0).count()
+    );
 }
@@ -83,7 +83,10 @@ async fn test_clasp_to_osc() {
         value: Value::Float(0.5),
         revision: None,
         lock: false,
-        unlock: false, ttl: None,
+        unlock: false,
+        ttl: None,
+        trace: false,
+        branch: None,
     });
 
     bridge.send(msg).await.expect("Failed to send message");
@@ -122,9 +125,7 @@ async fn test_osc_echo_roundtrip() {
 
     // Set up echo receiver
     let receiver = UdpSocket::bind("127.0.0.1:9022").expect("Failed to bind receiver");
-    receiver
-        .set_read_timeout(Some(Duration::from_secs(1)))
-        .ok();
+    receiver.set_read_timeout(Some(Duration::from_secs(1))).ok();
 
     // Send OSC
     let sender = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind sender");
@@ -220,6 +220,7 @@ impl BridgeService {
                     },
                     namespace: "/midi".to_string(),
                     device_name: "default".to_string(),
+                    ..Default::default()
                 };
                 Box::new(MidiBridge::new(config))
             }
@@ -242,6 +243,11 @@ impl BridgeService {
                     },
                     universes,
                     namespace: "/artnet".to_string(),
+                    refresh_rate: extra_config
+                        .as_ref()
+                        .and_then(|c| c.get("refresh_rate"))
+                        .and_then(|v| v.as_f64())
+                        .unwrap_or(44.0),
                 };
                 Box::new(ArtNetBridge::new(config))
             }
@@ -296,6 +302,7 @@ impl BridgeService {
                     qos: 0,
                     keep_alive_secs: 60,
                     namespace: "/mqtt".to_string(),
+                    homeassistant: None,
                 };
                 Box::new(MqttBridge::new(config))
             }
@@ -471,6 +478,11 @@ impl BridgeService {
                     namespace: "/sacn".to_string(),
                     preview: false,
                     sync_address: 0,
+                    refresh_rate: extra_config
+                        .as_ref()
+                        .and_then(|c| c.get("refresh_rate"))
+                        .and_then(|v| v.as_f64())
+                        .unwrap_or(44.0),
                 };
                 Box::new(SacnBridge::new(config))
             }
@@ -768,6 +780,8 @@ impl BridgeService {
                 lock: false,
                 unlock: false,
                 ttl: None,
+                trace: false,
+                branch: None,
             });
             bridge.bridge.send(msg).await?;
             Ok(())